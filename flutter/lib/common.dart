@@ -2238,7 +2238,8 @@ List<String>? urlLinkToCmdArgs(Uri uri) {
     }
     if (isMobile && id != null) {
       if (optionIndex == 0 || optionIndex == 1) {
-        connect(Get.context!, id);
+        connect(Get.context!, id,
+            switchUuid: uri.queryParameters["handoff"]);
       } else if (optionIndex == 2) {
         connect(Get.context!, id, isFileTransfer: true);
       }
@@ -2267,7 +2268,8 @@ List<String>? urlLinkToCmdArgs(Uri uri) {
   if (isMobile) {
     if (id != null) {
       final forceRelay = uri.queryParameters["relay"] != null;
-      connect(Get.context!, id, forceRelay: forceRelay);
+      connect(Get.context!, id,
+          forceRelay: forceRelay, switchUuid: uri.queryParameters["handoff"]);
       return null;
     }
   }
@@ -2279,7 +2281,8 @@ List<String>? urlLinkToCmdArgs(Uri uri) {
     var param = uri.queryParameters;
     String? password = param["password"];
     if (password != null) args.addAll(['--password', password]);
-    String? switch_uuid = param["switch_uuid"];
+    // the ticket of a session handed off logs in as the one of switching the sides
+    String? switch_uuid = param["switch_uuid"] ?? param["handoff"];
     if (switch_uuid != null) args.addAll(['--switch_uuid', switch_uuid]);
     if (param["relay"] != null) args.add("--relay");
     return args;
@@ -2295,13 +2298,15 @@ connectMainDesktop(String id,
     bool? forceRelay,
     String? password,
     String? connToken,
-    bool? isSharedPassword}) async {
+    bool? isSharedPassword,
+    List<String>? dropFiles}) async {
   if (isFileTransfer) {
     await rustDeskWinManager.newFileTransfer(id,
         password: password,
         isSharedPassword: isSharedPassword,
         connToken: connToken,
-        forceRelay: forceRelay);
+        forceRelay: forceRelay,
+        dropFiles: dropFiles);
   } else if (isTcpTunneling || isRDP) {
     await rustDeskWinManager.newPortForward(id, isRDP,
         password: password,
@@ -2320,6 +2325,7 @@ connectMainDesktop(String id,
 /// If [isFileTransfer], starts a session only for file transfer.
 /// If [isTcpTunneling], starts a session only for tcp tunneling.
 /// If [isRDP], starts a session only for rdp.
+/// [dropFiles] are the local files to upload to the peer once the file transfer is open.
 connect(BuildContext context, String id,
     {bool isFileTransfer = false,
     bool isTcpTunneling = false,
@@ -2327,7 +2333,9 @@ connect(BuildContext context, String id,
     bool forceRelay = false,
     String? password,
     String? connToken,
-    bool? isSharedPassword}) async {
+    bool? isSharedPassword,
+    String? switchUuid,
+    List<String>? dropFiles}) async {
 
   if (!gFFI.userModel.isLogin) {
     // Show login dialog and wait for result
@@ -2369,6 +2377,7 @@ connect(BuildContext context, String id,
         password: password,
         isSharedPassword: isSharedPassword,
         forceRelay: forceRelay2,
+        dropFiles: dropFiles,
       );
     } else {
       await rustDeskWinManager.call(WindowType.Main, kWindowConnect, {
@@ -2380,6 +2389,7 @@ connect(BuildContext context, String id,
         'isSharedPassword': isSharedPassword,
         'forceRelay': forceRelay,
         'connToken': connToken,
+        'dropFiles': dropFiles,
       });
     }
   } else {
@@ -2431,7 +2441,10 @@ connect(BuildContext context, String id,
           context,
           MaterialPageRoute(
             builder: (BuildContext context) => RemotePage(
-                id: id, password: password, isSharedPassword: isSharedPassword),
+                id: id,
+                password: password,
+                isSharedPassword: isSharedPassword,
+                switchUuid: switchUuid),
           ),
         );
       }
@@ -2782,7 +2795,9 @@ Widget futureBuilder(
 void onCopyFingerprint(String value) {
   if (value.isNotEmpty) {
     Clipboard.setData(ClipboardData(text: value));
-    showToast('$value\n${translate("Copied")}');
+    final sas = bind.mainFingerprintToSas(fingerprint: value);
+    showToast(
+        '$value\n${translate("Short authentication string")}: $sas\n${translate("Copied")}');
   } else {
     showToast(translate("no fingerprints"));
   }
@@ -3055,6 +3070,9 @@ openMonitorInTheSameTab(int i, FFI ffi, PeerInfo pi,
   );
   ffi.ffiModel.switchToNewDisplay(i, ffi.sessionId, ffi.id,
       updateCursorPos: updateCursorPos);
+  // shown first on the next connection
+  bind.sessionSetFlutterOption(
+      sessionId: ffi.sessionId, k: lastDisplayKey, v: i.toString());
 }
 
 // Open new tab or window to show this monitor.
@@ -77,9 +77,11 @@ class CurrentDisplayState {
 class ConnectionType {
   final Rx<String> _secure = kInvalidValueStr.obs;
   final Rx<String> _direct = kInvalidValueStr.obs;
+  final RxBool _degraded = false.obs;
 
   Rx<String> get secure => _secure;
   Rx<String> get direct => _direct;
+  RxBool get degraded => _degraded;
 
   static String get strSecure => 'secure';
   static String get strInsecure => 'insecure';
@@ -94,6 +96,10 @@ class ConnectionType {
     _direct.value = v ? strDirect : strIndirect;
   }
 
+  void setDegraded(bool v) {
+    _degraded.value = v;
+  }
+
   bool isValid() {
     return _secure.value != kInvalidValueStr &&
         _direct.value != kInvalidValueStr;
@@ -1,10 +1,15 @@
+import 'dart:io';
+
 import 'package:dash_chat_2/dash_chat_2.dart';
+import 'package:desktop_drop/desktop_drop.dart';
 import 'package:flutter/material.dart';
 import 'package:flutter_hbb/common.dart';
 import 'package:flutter_hbb/models/chat_model.dart';
 import 'package:get/get.dart';
 import 'package:provider/provider.dart';
+import 'package:url_launcher/url_launcher.dart';
 
+import '../../consts.dart';
 import '../../mobile/pages/home_page.dart';
 
 enum ChatPageType {
@@ -129,6 +134,17 @@ class ChatPage extends StatelessWidget implements PageShape {
                         color: MyTheme.accent,
                         icon: Icons.send_rounded,
                       ),
+                      leading: isWeb
+                          ? null
+                          : [
+                              IconButton(
+                                tooltip: translate('Send a file'),
+                                padding: EdgeInsets.zero,
+                                icon: Icon(Icons.attach_file_rounded,
+                                    color: MyTheme.accent),
+                                onPressed: chatModel.pickAttachment,
+                              )
+                            ],
                     ),
                     messageOptions: MessageOptions(
                       showOtherUsersAvatar: false,
@@ -142,14 +158,19 @@ class ChatPage extends StatelessWidget implements PageShape {
                               ? CrossAxisAlignment.end
                               : CrossAxisAlignment.start,
                           children: <Widget>[
-                            Text(message.text,
-                                style: TextStyle(color: Colors.white)),
-                            Text(
-                              "${message.createdAt.hour}:${message.createdAt.minute.toString().padLeft(2, '0')}",
-                              style: TextStyle(
-                                color: Colors.white,
-                                fontSize: 8,
-                              ),
+                            _buildContent(message),
+                            Row(
+                              mainAxisSize: MainAxisSize.min,
+                              children: [
+                                Text(
+                                  "${message.createdAt.hour}:${message.createdAt.minute.toString().padLeft(2, '0')}",
+                                  style: TextStyle(
+                                    color: Colors.white,
+                                    fontSize: 8,
+                                  ),
+                                ),
+                                if (isOwnMessage) _buildStatus(message),
+                              ],
                             ).marginOnly(top: 3),
                           ],
                         );
@@ -168,7 +189,17 @@ class ChatPage extends StatelessWidget implements PageShape {
                       },
                     ),
                   );
-                  return SelectionArea(child: chat);
+                  if (!isDesktop || readOnly) {
+                    return SelectionArea(child: chat);
+                  }
+                  // drop a file to send it
+                  return DropTarget(
+                      onDragDone: (detail) async {
+                        for (final file in detail.files) {
+                          await chatModel.sendAttachment(file.path);
+                        }
+                      },
+                      child: SelectionArea(child: chat));
                 }),
               ],
             ).paddingOnly(bottom: 8);
@@ -177,4 +208,68 @@ class ChatPage extends StatelessWidget implements PageShape {
       ),
     );
   }
+
+  Widget _buildContent(ChatMessage message) {
+    final path = message.customProperties?[kChatAttachmentPath];
+    if (path is! String) {
+      return Text(message.text, style: TextStyle(color: Colors.white));
+    }
+    final Widget content;
+    if (message.customProperties?[kChatAttachmentImage] == true && !isWeb) {
+      content = ConstrainedBox(
+        constraints: BoxConstraints(maxHeight: 160),
+        child: Image.file(File(path),
+            errorBuilder: (context, error, stackTrace) =>
+                Text(message.text, style: TextStyle(color: Colors.white))),
+      );
+    } else {
+      content = Row(
+        mainAxisSize: MainAxisSize.min,
+        children: [
+          Icon(Icons.insert_drive_file_outlined, color: Colors.white, size: 16)
+              .marginOnly(right: 4),
+          Flexible(
+            child: Text(message.text,
+                style: TextStyle(
+                    color: Colors.white, decoration: TextDecoration.underline)),
+          ),
+        ],
+      );
+    }
+    return InkWell(
+      onTap: isWeb ? null : () => launchUrl(Uri.file(path)),
+      child: Tooltip(message: path, child: content),
+    );
+  }
+
+  Widget _buildStatus(ChatMessage message) {
+    final status = message.customProperties?[kChatMessageStatus];
+    final IconData icon;
+    String tip;
+    switch (status) {
+      case kChatStatusSending:
+        icon = Icons.schedule;
+        tip = 'Sending';
+        break;
+      case kChatStatusDelivered:
+        icon = Icons.done;
+        tip = 'Delivered';
+        break;
+      case kChatStatusFailed:
+        icon = Icons.error_outline;
+        tip = 'Failed';
+        break;
+      default:
+        return Offstage();
+    }
+    tip = translate(tip);
+    final error = message.customProperties?[kChatMessageError];
+    if (error is String && error.isNotEmpty) {
+      tip = '$tip: ${translate(error)}';
+    }
+    return Tooltip(
+      message: tip,
+      child: Icon(icon, color: Colors.white, size: 10),
+    ).marginOnly(left: 3);
+  }
 }
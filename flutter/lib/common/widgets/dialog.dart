@@ -2,6 +2,7 @@ import 'dart:async';
 import 'dart:convert';
 
 import 'package:bot_toast/bot_toast.dart';
+import 'package:debounce_throttle/debounce_throttle.dart';
 import 'package:flutter/material.dart';
 import 'package:flutter/services.dart';
 import 'package:flutter/widgets.dart';
@@ -273,6 +274,136 @@ void changeWhiteList({Function()? callback}) async {
   });
 }
 
+void changeIpRules({Function()? callback}) async {
+  final controller =
+      TextEditingController(text: await bind.mainGetOption(key: kOptionIpRules));
+  var msg = "";
+  final isOptFixed = isOptionFixed(kOptionIpRules);
+  gFFI.dialogManager.show((setState, close, context) {
+    return CustomAlertDialog(
+      title: Text(translate("IP rules")),
+      content: Column(
+        crossAxisAlignment: CrossAxisAlignment.start,
+        children: [
+          Text(translate("ip_rules_tip")),
+          const SizedBox(
+            height: 8.0,
+          ),
+          Row(
+            children: [
+              Expanded(
+                child: TextField(
+                    maxLines: null,
+                    decoration: InputDecoration(
+                      hintText: "allow 192.168.1.0/24 mon-fri 09:00-18:00",
+                      errorText: msg.isEmpty ? null : msg,
+                      errorMaxLines: 3,
+                    ),
+                    controller: controller,
+                    enabled: !isOptFixed,
+                    autofocus: true),
+              ),
+            ],
+          ),
+        ],
+      ),
+      actions: [
+        dialogButton("Cancel", onPressed: close, isOutline: true),
+        if (!isOptFixed)
+          dialogButton("Clear", onPressed: () async {
+            await bind.mainSetOption(key: kOptionIpRules, value: '');
+            callback?.call();
+            close();
+          }, isOutline: true),
+        if (!isOptFixed)
+          dialogButton(
+            "OK",
+            onPressed: () async {
+              final rules = controller.text.trim();
+              final err = bind.mainCheckIpRules(rules: rules);
+              if (err.isNotEmpty) {
+                setState(() {
+                  msg = err;
+                });
+                return;
+              }
+              await bind.mainSetOption(key: kOptionIpRules, value: rules);
+              callback?.call();
+              close();
+            },
+          ),
+      ],
+      onCancel: close,
+    );
+  });
+}
+
+void changeUnattendedSchedule({Function()? callback}) async {
+  final controller = TextEditingController(
+      text: await bind.mainGetOption(key: kOptionUnattendedSchedule));
+  var msg = "";
+  final isOptFixed = isOptionFixed(kOptionUnattendedSchedule);
+  gFFI.dialogManager.show((setState, close, context) {
+    return CustomAlertDialog(
+      title: Text(translate("Schedule the unattended access")),
+      content: Column(
+        crossAxisAlignment: CrossAxisAlignment.start,
+        children: [
+          Text(translate("unattended_schedule_tip")),
+          const SizedBox(
+            height: 8.0,
+          ),
+          Row(
+            children: [
+              Expanded(
+                child: TextField(
+                    maxLines: null,
+                    decoration: InputDecoration(
+                      hintText: "mon-fri 08:00-18:00",
+                      errorText: msg.isEmpty ? null : msg,
+                      errorMaxLines: 3,
+                    ),
+                    controller: controller,
+                    enabled: !isOptFixed,
+                    autofocus: true),
+              ),
+            ],
+          ),
+        ],
+      ),
+      actions: [
+        dialogButton("Cancel", onPressed: close, isOutline: true),
+        if (!isOptFixed)
+          dialogButton("Clear", onPressed: () async {
+            await bind.mainSetOption(key: kOptionUnattendedSchedule, value: '');
+            callback?.call();
+            close();
+          }, isOutline: true),
+        if (!isOptFixed)
+          dialogButton(
+            "OK",
+            onPressed: () async {
+              final schedule = controller.text.trim();
+              final err =
+                  bind.mainCheckUnattendedSchedule(schedule: schedule);
+              if (err.isNotEmpty) {
+                setState(() {
+                  msg = err;
+                });
+                return;
+              }
+              await bind.mainSetOption(
+                  key: kOptionUnattendedSchedule, value: schedule);
+              callback?.call();
+              close();
+            },
+          ),
+      ],
+      onCancel: close,
+    );
+  });
+}
+
 Future<String> changeDirectAccessPort(
     String currentIP, String currentPort) async {
   final controller = TextEditingController(text: currentPort);
@@ -1257,6 +1388,8 @@ void showWaitAcceptDialog(SessionID sessionId, String type, String title,
 
 void showRestartRemoteDevice(PeerInfo pi, String id, SessionID sessionId,
     OverlayDialogManager dialogManager) async {
+  final reconnect = true.obs;
+  final safeMode = false.obs;
   final res = await dialogManager
       .show<bool>((setState, close, context) => CustomAlertDialog(
             title: Row(children: [
@@ -1265,8 +1398,30 @@ void showRestartRemoteDevice(PeerInfo pi, String id, SessionID sessionId,
                   child: Text(translate("Restart remote device"))
                       .paddingOnly(left: 10)),
             ]),
-            content: Text(
-                "${translate('Are you sure you want to restart')} \n${pi.username}@${pi.hostname}($id) ?"),
+            content: Column(
+              crossAxisAlignment: CrossAxisAlignment.start,
+              mainAxisSize: MainAxisSize.min,
+              children: [
+                Text(
+                    "${translate('Are you sure you want to restart')} \n${pi.username}@${pi.hostname}($id) ?"),
+                Obx(() => CheckboxListTile(
+                      contentPadding: EdgeInsets.zero,
+                      controlAffinity: ListTileControlAffinity.leading,
+                      value: reconnect.value,
+                      onChanged: (v) => reconnect.value = v == true,
+                      title: Text(translate('Reconnect when it is back online')),
+                    )),
+                if (pi.platform == kPeerPlatformWindows)
+                  Obx(() => CheckboxListTile(
+                        contentPadding: EdgeInsets.zero,
+                        controlAffinity: ListTileControlAffinity.leading,
+                        value: safeMode.value,
+                        onChanged: (v) => safeMode.value = v == true,
+                        title: Text(
+                            translate('Restart into safe mode with networking')),
+                      )),
+              ],
+            ),
             actions: [
               dialogButton(
                 "Cancel",
@@ -1283,7 +1438,12 @@ void showRestartRemoteDevice(PeerInfo pi, String id, SessionID sessionId,
             onCancel: close,
             onSubmit: () => close(true),
           ));
-  if (res == true) bind.sessionRestartRemoteDevice(sessionId: sessionId);
+  if (res == true) {
+    bind.sessionRestartRemoteDevice(
+        sessionId: sessionId,
+        safeMode: safeMode.value,
+        reconnect: reconnect.value);
+  }
 }
 
 showSetOSPassword(
@@ -1607,6 +1767,211 @@ customImageQualityDialog(SessionID sessionId, String id, FFI ffi) async {
   msgBoxCommon(ffi.dialogManager, 'Custom Image Quality', content, [btnClose]);
 }
 
+remoteVolumeDialog(SessionID sessionId, FFI ffi) {
+  final volume = ffi.ffiModel.remoteVolume;
+  final mute = ffi.ffiModel.remoteMute;
+  bind.sessionQueryRemoteVolume(sessionId: sessionId);
+  final debouncer = Debouncer<int>(
+    Duration(milliseconds: 300),
+    onChanged: (level) =>
+        bind.sessionSetRemoteVolume(sessionId: sessionId, level: level),
+    initialValue: volume.value,
+  );
+  final content = Obx(() => Column(
+        mainAxisSize: MainAxisSize.min,
+        children: [
+          Row(
+            children: [
+              Expanded(
+                child: Slider(
+                  value: volume.value.clamp(0, 100).toDouble(),
+                  min: 0,
+                  max: 100,
+                  divisions: 20,
+                  onChanged: volume.value < 0
+                      ? null
+                      : (double value) {
+                          volume.value = value.round();
+                          debouncer.value = value.round();
+                        },
+                ),
+              ),
+              SizedBox(
+                  width: 48,
+                  child: Text(volume.value < 0 ? '-' : '${volume.value}%')),
+            ],
+          ),
+          CheckboxListTile(
+            contentPadding: EdgeInsets.zero,
+            value: mute.value,
+            onChanged: volume.value < 0
+                ? null
+                : (bool? value) {
+                    if (value == null) return;
+                    bind.sessionSetRemoteMute(
+                        sessionId: sessionId, mute: value);
+                  },
+            title: Text(translate('Mute')),
+          ),
+        ],
+      ));
+  msgBoxCommon(ffi.dialogManager, 'Remote volume', content, [
+    dialogButton('Close', onPressed: () => ffi.dialogManager.dismissAll())
+  ]);
+}
+
+bandwidthLimitDialog(SessionID sessionId, FFI ffi) async {
+  final limit = await bind.sessionGetPeerOption(
+      sessionId: sessionId, name: kOptionBandwidthLimit);
+  final controller = TextEditingController(text: limit);
+  ffi.dialogManager.show((setState, close, context) {
+    submit() {
+      final kbps = int.tryParse(controller.text.trim()) ?? 0;
+      bind.sessionSetBandwidthLimit(sessionId: sessionId, kbps: kbps);
+      close();
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Bandwidth limit')),
+      content: TextField(
+        controller: controller,
+        autofocus: true,
+        keyboardType: TextInputType.number,
+        inputFormatters: [FilteringTextInputFormatter.digitsOnly],
+        decoration: InputDecoration(
+            labelText: 'kbps', hintText: translate('Unlimited')),
+      ),
+      actions: [
+        dialogButton('Cancel', onPressed: close, isOutline: true),
+        dialogButton('OK', onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: close,
+    );
+  });
+}
+
+// [kbps] is 0 for no limit
+fileTransferRateLimitDialog(
+    OverlayDialogManager dialogManager, int kbps, Function(int) onSubmit) {
+  final controller = TextEditingController(text: kbps > 0 ? '$kbps' : '');
+  dialogManager.show((setState, close, context) {
+    submit() {
+      onSubmit(int.tryParse(controller.text.trim()) ?? 0);
+      close();
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Speed limit')),
+      content: TextField(
+        controller: controller,
+        autofocus: true,
+        keyboardType: TextInputType.number,
+        inputFormatters: [FilteringTextInputFormatter.digitsOnly],
+        decoration: InputDecoration(
+            labelText: 'kbps', hintText: translate('Unlimited')),
+      ),
+      actions: [
+        dialogButton('Cancel', onPressed: close, isOutline: true),
+        dialogButton('OK', onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: close,
+    );
+  });
+}
+
+macrosDialog(SessionID sessionId, FFI ffi) {
+  final controller = TextEditingController();
+  List<String> getMacros() {
+    try {
+      return List<String>.from(
+          jsonDecode(bind.sessionGetMacros(sessionId: sessionId)));
+    } catch (e) {
+      return [];
+    }
+  }
+
+  var macros = getMacros();
+  ffi.dialogManager.show((setState, close, context) {
+    record() {
+      final err = bind.sessionStartMacroRecording(
+          sessionId: sessionId, name: controller.text.trim());
+      if (err.isNotEmpty) {
+        showToast(err);
+        return;
+      }
+      close();
+    }
+
+    play(String name) {
+      final err = bind.sessionPlayMacro(sessionId: sessionId, name: name);
+      if (err.isNotEmpty) {
+        showToast(err);
+        return;
+      }
+      close();
+    }
+
+    remove(String name) async {
+      await bind.sessionRemoveMacro(sessionId: sessionId, name: name);
+      setState(() => macros = getMacros());
+    }
+
+    final playing = bind.sessionIsPlayingMacro(sessionId: sessionId);
+    return CustomAlertDialog(
+      title: Text(translate('Macros')),
+      content: Column(
+        mainAxisSize: MainAxisSize.min,
+        crossAxisAlignment: CrossAxisAlignment.start,
+        children: [
+          Row(children: [
+            Expanded(
+              child: TextField(
+                controller: controller,
+                autofocus: true,
+                decoration:
+                    InputDecoration(labelText: translate('Macro name')),
+              ),
+            ),
+            IconButton(
+              tooltip: translate('Record macro'),
+              icon: Icon(Icons.fiber_manual_record, color: Colors.red),
+              onPressed: record,
+            ),
+          ]),
+          if (macros.isEmpty)
+            Text(translate('No macros')).paddingOnly(top: 12)
+          else
+            ...macros.map((name) => Row(children: [
+                  Expanded(child: Text(name, overflow: TextOverflow.ellipsis)),
+                  IconButton(
+                    tooltip: translate('Play'),
+                    icon: Icon(Icons.play_arrow),
+                    onPressed: playing ? null : () => play(name),
+                  ),
+                  IconButton(
+                    tooltip: translate('Delete'),
+                    icon: Icon(Icons.delete_outline),
+                    onPressed: () => remove(name),
+                  ),
+                ])),
+        ],
+      ),
+      actions: [
+        if (playing)
+          dialogButton('Stop macro', onPressed: () {
+            bind.sessionStopMacro(sessionId: sessionId);
+            close();
+          }, isOutline: true),
+        dialogButton('Close', onPressed: close),
+      ],
+      onSubmit: record,
+      onCancel: close,
+    );
+  });
+}
+
 void deleteConfirmDialog(Function onSubmit, String title) async {
   gFFI.dialogManager.show(
     (setState, close, context) {
@@ -1967,6 +2332,60 @@ void enter2FaDialog(
   });
 }
 
+void enterAccessReasonDialog(
+    SessionID sessionId, OverlayDialogManager dialogManager) async {
+  final controller = TextEditingController();
+  final RxBool submitReady = false.obs;
+
+  dialogManager.dismissAll();
+  dialogManager.show((setState, close, context) {
+    cancel() {
+      close();
+      closeConnection();
+    }
+
+    submit() {
+      final reason = controller.text.trim();
+      if (reason.isEmpty) return;
+      gFFI.sendAccessReason(sessionId, reason);
+      close();
+      dialogManager.showLoading(translate('Logging in...'),
+          onCancel: closeConnection);
+    }
+
+    return CustomAlertDialog(
+        title: Text(translate('Access reason')),
+        content: Column(
+          crossAxisAlignment: CrossAxisAlignment.start,
+          children: [
+            Text(translate('access_reason_tip')).marginOnly(bottom: 8),
+            TextField(
+              controller: controller,
+              autofocus: true,
+              maxLength: 256,
+              maxLines: 3,
+              minLines: 1,
+              decoration: InputDecoration(border: OutlineInputBorder()),
+              onChanged: (v) => submitReady.value = v.trim().isNotEmpty,
+            ),
+          ],
+        ),
+        actions: [
+          dialogButton('Cancel',
+              onPressed: cancel,
+              isOutline: true,
+              style: TextStyle(
+                  color: Theme.of(context).textTheme.bodyMedium?.color)),
+          Obx(() => dialogButton(
+                'OK',
+                onPressed: submitReady.isTrue ? submit : null,
+              )),
+        ],
+        onSubmit: submit,
+        onCancel: cancel);
+  });
+}
+
 // This dialog should not be dismissed, otherwise it will be black screen, have not reproduced this.
 void showWindowsSessionsDialog(
     String type,
@@ -2246,9 +2665,9 @@ void CommonConfirmDialog(OverlayDialogManager dialogManager, String content,
   });
 }
 
-void changeUnlockPinDialog(String oldPin, Function() callback) {
-  final pinController = TextEditingController(text: oldPin);
-  final confirmController = TextEditingController(text: oldPin);
+void changeUnlockPinDialog(Function() callback) {
+  final pinController = TextEditingController();
+  final confirmController = TextEditingController();
   String? pinErrorText;
   String? confirmationErrorText;
   final maxLength = bind.mainMaxEncryptLen();
@@ -2306,13 +2725,13 @@ void changeUnlockPinDialog(String oldPin, Function() callback) {
   });
 }
 
-void checkUnlockPinDialog(String correctPin, Function() passCallback) {
+void checkUnlockPinDialog(Function() passCallback) {
   final controller = TextEditingController();
   String? errorText;
   gFFI.dialogManager.show((setState, close, context) {
     submit() async {
       final pin = controller.text.trim();
-      if (correctPin != pin) {
+      if (!bind.mainVerifyUnlockPin(pin: pin)) {
         setState(() {
           errorText = translate('Wrong PIN');
         });
@@ -2497,3 +2916,712 @@ Widget trustedDevicesTable(
         )),
   );
 }
+
+void rotateKeyDialog({Function()? callback}) {
+  var compromised = false;
+  gFFI.dialogManager.show((setState, close, context) {
+    submit() async {
+      // the old key is kept signing along for a week, unless it is compromised
+      await bind.mainRotateKeyPair(graceDays: compromised ? 0 : 7);
+      close();
+      callback?.call();
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Rotate key')),
+      content: Column(
+        crossAxisAlignment: CrossAxisAlignment.start,
+        mainAxisSize: MainAxisSize.min,
+        children: [
+          Text(translate('rotate_key_tip')),
+          const SizedBox(
+            height: 8.0,
+          ),
+          CheckboxListTile(
+            contentPadding: const EdgeInsets.all(0),
+            dense: true,
+            controlAffinity: ListTileControlAffinity.leading,
+            title: Text(translate('The key is compromised')),
+            value: compromised,
+            onChanged: (v) {
+              if (v == null) return;
+              setState(() => compromised = v);
+            },
+          ),
+        ],
+      ),
+      actions: [
+        dialogButton("Cancel", onPressed: close, isOutline: true),
+        dialogButton("OK", onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: close,
+    );
+  });
+}
+
+void oneTimePasswordsDialog() async {
+  List<dynamic> load(String json) {
+    try {
+      return json.isEmpty ? [] : jsonDecode(json) as List<dynamic>;
+    } catch (e) {
+      return [];
+    }
+  }
+
+  var passwords = load(await bind.mainGetOneTimePasswords());
+  final countController = TextEditingController(text: '5');
+  final minutesController = TextEditingController(text: '60');
+  final labelController = TextEditingController();
+  const scopes = ['', 'view', 'file-transfer'];
+  const scopeNames = ['Any', 'View only', 'File transfer only'];
+  var scope = '';
+  // shown only once, they are not kept
+  List<String> codes = [];
+  gFFI.dialogManager.show((setState, close, context) {
+    generate() async {
+      final count = int.tryParse(countController.text.trim()) ?? 0;
+      final minutes = int.tryParse(minutesController.text.trim()) ?? 0;
+      if (count <= 0 || minutes <= 0) return;
+      final generated = load(await bind.mainGenerateOneTimePasswords(
+          count: count,
+          minutes: minutes,
+          scope: scope,
+          label: labelController.text.trim()));
+      passwords = load(await bind.mainGetOneTimePasswords());
+      setState(() => codes = generated.cast<String>());
+    }
+
+    remove(String id) async {
+      await bind.mainRemoveOneTimePasswords(json: jsonEncode([id]));
+      passwords = load(await bind.mainGetOneTimePasswords());
+      setState(() {});
+    }
+
+    String expiry(dynamic p) {
+      final int remaining =
+          (p['expires_at'] ?? 0) - DateTime.now().millisecondsSinceEpoch;
+      final minutes = remaining <= 0 ? 0 : (remaining / 60000).ceil();
+      return '${translate('Valid for minutes')}: $minutes';
+    }
+
+    numberField(TextEditingController controller, String label) => SizedBox(
+          width: 80,
+          child: TextField(
+            controller: controller,
+            keyboardType: TextInputType.number,
+            inputFormatters: [FilteringTextInputFormatter.digitsOnly],
+            decoration: InputDecoration(labelText: translate(label)),
+          ),
+        );
+
+    return CustomAlertDialog(
+      title: Text(translate('One-time passwords')),
+      content: Column(
+        crossAxisAlignment: CrossAxisAlignment.start,
+        mainAxisSize: MainAxisSize.min,
+        children: [
+          Text(translate('one_time_passwords_tip')),
+          Row(
+            children: [
+              numberField(countController, 'Count'),
+              const SizedBox(width: 8),
+              numberField(minutesController, 'Valid for minutes'),
+              const SizedBox(width: 8),
+              DropdownButton<String>(
+                value: scope,
+                items: List.generate(
+                    scopes.length,
+                    (i) => DropdownMenuItem(
+                        value: scopes[i],
+                        child: Text(translate(scopeNames[i])))),
+                onChanged: (v) => setState(() => scope = v ?? ''),
+              ),
+            ],
+          ),
+          TextField(
+            controller: labelController,
+            decoration: InputDecoration(labelText: translate('Note')),
+          ),
+          if (codes.isNotEmpty)
+            Row(
+              children: [
+                Expanded(child: SelectableText(codes.join('\n'))),
+                IconButton(
+                  icon: const Icon(Icons.copy),
+                  onPressed: () {
+                    Clipboard.setData(ClipboardData(text: codes.join('\n')));
+                    showToast(translate('Copied'));
+                  },
+                ),
+              ],
+            ).marginOnly(top: 8),
+          const Divider(),
+          ...passwords.map((p) => Row(
+                children: [
+                  Expanded(
+                      child: Text(
+                          '${p['label'] ?? ''} ${translate(scopeNames[scopes.indexOf(p['scope'] ?? '').clamp(0, scopes.length - 1)])}, ${expiry(p)}')),
+                  IconButton(
+                    icon: const Icon(Icons.delete_outline),
+                    onPressed: () => remove(p['id'] ?? ''),
+                  ),
+                ],
+              )),
+        ],
+      ),
+      actions: [
+        dialogButton('Close', onPressed: close, isOutline: true),
+        dialogButton('Generate', onPressed: generate),
+      ],
+      onCancel: close,
+    );
+  });
+}
+
+void trustedKeysDialog() async {
+  List<dynamic> load(String json) {
+    try {
+      return json.isEmpty ? [] : jsonDecode(json) as List<dynamic>;
+    } catch (e) {
+      return [];
+    }
+  }
+
+  var keys = load(await bind.mainGetTrustedKeys());
+  gFFI.dialogManager.show((setState, close, context) {
+    remove(String fingerprint) async {
+      await bind.mainRemoveTrustedKeys(json: jsonEncode([fingerprint]));
+      keys = load(await bind.mainGetTrustedKeys());
+      setState(() {});
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Trusted device keys')),
+      content: Column(
+        crossAxisAlignment: CrossAxisAlignment.start,
+        mainAxisSize: MainAxisSize.min,
+        children: [
+          Text(translate('trusted_device_keys_tip')),
+          const Divider(),
+          if (keys.isEmpty) Text(translate('Empty')),
+          ...keys.map((k) => Row(
+                children: [
+                  Expanded(
+                    child: Column(
+                      crossAxisAlignment: CrossAxisAlignment.start,
+                      children: [
+                        Text(
+                            '${k['name'] ?? ''} (${k['id'] ?? ''}) ${k['platform'] ?? ''}'),
+                        SelectableText(k['fingerprint'] ?? '',
+                            style: const TextStyle(fontSize: 12)),
+                      ],
+                    ),
+                  ),
+                  IconButton(
+                    icon: const Icon(Icons.delete_outline),
+                    tooltip: translate('Revoke'),
+                    onPressed: () => remove(k['fingerprint'] ?? ''),
+                  ),
+                ],
+              ).marginOnly(bottom: 4)),
+        ],
+      ),
+      actions: [
+        dialogButton('Close', onPressed: close, isOutline: true),
+      ],
+      onCancel: close,
+    );
+  });
+}
+
+void notificationsDialog() async {
+  const events = ['session_start', 'session_end', 'auth_failure'];
+  const eventLabels = [
+    'Session started',
+    'Session ended',
+    'Authentication failed'
+  ];
+  const textKeys = [
+    kOptionNotifyWebhook,
+    kOptionNotifySmtpServer,
+    kOptionNotifySmtpUsername,
+    kOptionNotifySmtpPassword,
+    kOptionNotifyEmailFrom,
+    kOptionNotifyEmailTo,
+  ];
+  const textLabels = [
+    'Webhook',
+    'SMTP server',
+    'Username',
+    'Password',
+    'From',
+    'To',
+  ];
+  const textHints = [
+    'https://example.com/hook',
+    'smtp.example.com:587',
+    '',
+    '',
+    'admin@example.com',
+    'admin@example.com, it@example.com',
+  ];
+  final controllers = <TextEditingController>[];
+  for (final key in textKeys) {
+    controllers
+        .add(TextEditingController(text: await bind.mainGetOption(key: key)));
+  }
+  // all of them if empty
+  final option = (await bind.mainGetOption(key: kOptionNotifyEvents)).trim();
+  final selected = events
+      .map((e) => option.isEmpty || option.split(',').any((s) => s.trim() == e))
+      .toList();
+  gFFI.dialogManager.show((setState, close, context) {
+    submit() async {
+      for (var i = 0; i < textKeys.length; i++) {
+        await bind.mainSetOption(
+            key: textKeys[i], value: controllers[i].text.trim());
+      }
+      final checked = [
+        for (var i = 0; i < events.length; i++)
+          if (selected[i]) events[i]
+      ];
+      await bind.mainSetOption(
+          key: kOptionNotifyEvents,
+          value: checked.length == events.length
+              ? ''
+              : checked.isEmpty
+                  ? 'none'
+                  : checked.join(','));
+      close();
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Notifications')),
+      content: Column(
+        crossAxisAlignment: CrossAxisAlignment.start,
+        mainAxisSize: MainAxisSize.min,
+        children: [
+          Text(translate('notifications_tip')),
+          const SizedBox(height: 8.0),
+          for (var i = 0; i < textKeys.length; i++)
+            TextField(
+              controller: controllers[i],
+              obscureText: textKeys[i] == kOptionNotifySmtpPassword,
+              decoration: InputDecoration(
+                labelText: translate(textLabels[i]),
+                hintText: textHints[i],
+              ),
+            ).marginOnly(bottom: 4),
+          const SizedBox(height: 8.0),
+          for (var i = 0; i < events.length; i++)
+            CheckboxListTile(
+              contentPadding: EdgeInsets.zero,
+              dense: true,
+              controlAffinity: ListTileControlAffinity.leading,
+              value: selected[i],
+              title: Text(translate(eventLabels[i])),
+              onChanged: (v) => setState(() => selected[i] = v ?? false),
+            ),
+        ],
+      ),
+      actions: [
+        dialogButton('Cancel', onPressed: close, isOutline: true),
+        dialogButton('OK', onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: close,
+    );
+  });
+}
+
+void showInactivityWarning(SessionID sessionId, int seconds, bool viewOnly,
+    OverlayDialogManager dialogManager) {
+  final tag = '$sessionId-inactivity-warning';
+  dialogManager.dismissByTag(tag);
+  // 0 once the input came in time
+  if (seconds <= 0) return;
+  final deadline = DateTime.now().add(Duration(seconds: seconds));
+  Timer? timer;
+  dialogManager.show(tag: tag, (setState, close, context) {
+    timer ??= Timer.periodic(const Duration(seconds: 1), (t) {
+      if (!dialogManager.existing(tag)) {
+        t.cancel();
+      } else if (DateTime.now().isAfter(deadline)) {
+        t.cancel();
+        close();
+      } else {
+        setState(() {});
+      }
+    });
+    submit() {
+      timer?.cancel();
+      close();
+    }
+
+    final left = deadline.difference(DateTime.now()).inSeconds.clamp(0, seconds);
+    return CustomAlertDialog(
+      title: Text(translate('Inactive session')),
+      content: Text(
+          '${translate(viewOnly ? 'inactivity_view_only_tip' : 'inactivity_disconnect_tip')}\n\n${left}s'),
+      actions: [
+        dialogButton('OK', onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: submit,
+    );
+  });
+}
+
+void showSessionHandoff(SessionID sessionId, String link, int timeout,
+    String error, OverlayDialogManager dialogManager) {
+  if (error.isNotEmpty || link.isEmpty) {
+    dialogManager.show((setState, close, context) => CustomAlertDialog(
+          title: Text(translate('Hand off the session')),
+          content: Text(translate(error.isNotEmpty ? error : 'Failed')),
+          actions: [dialogButton('OK', onPressed: close)],
+          onSubmit: close,
+          onCancel: close,
+        ));
+    return;
+  }
+  final tag = '$sessionId-session-handoff';
+  dialogManager.dismissByTag(tag);
+  dialogManager.show(tag: tag, (setState, close, context) {
+    return CustomAlertDialog(
+      title: Text(translate('Hand off the session')),
+      content: Column(
+        crossAxisAlignment: CrossAxisAlignment.start,
+        mainAxisSize: MainAxisSize.min,
+        children: [
+          Text(translate('session_handoff_tip')
+                  .replaceAll('{}', timeout.toString()))
+              .marginOnly(bottom: 12),
+          Center(
+            child: SizedBox(
+                width: 160,
+                height: 160,
+                child: QrImageView(
+                  backgroundColor: Colors.white,
+                  data: link,
+                  version: QrVersions.auto,
+                  size: 160,
+                  gapless: false,
+                )),
+          ).marginOnly(bottom: 6),
+          Row(
+            children: [
+              Expanded(
+                  child: SelectableText(link, style: TextStyle(fontSize: 12))),
+              IconButton(
+                icon: const Icon(Icons.copy),
+                onPressed: () {
+                  Clipboard.setData(ClipboardData(text: link));
+                  showToast(translate('Copied'));
+                },
+              ),
+            ],
+          ),
+        ],
+      ),
+      actions: [dialogButton('Close', onPressed: close)],
+      onSubmit: close,
+      onCancel: close,
+    );
+  });
+}
+
+void showKillElevationRequired(
+    SessionID sessionId, OverlayDialogManager dialogManager) {
+  dialogManager.show((setState, close, context) {
+    return CustomAlertDialog(
+      title: Text(translate('Task manager')),
+      content: Text(translate('kill_process_elevation_tip')),
+      actions: [
+        dialogButton('Cancel', onPressed: close, isOutline: true),
+        dialogButton('Request Elevation', onPressed: () {
+          close();
+          showRequestElevationDialog(sessionId, dialogManager);
+        }),
+      ],
+      onSubmit: close,
+      onCancel: close,
+    );
+  });
+}
+
+// A document of the virtual printer of the peer, printed here once the user agrees.
+void showPrintJob(Map<String, dynamic> evt, OverlayDialogManager dialogManager) {
+  final String title = evt['title'] ?? '';
+  final String path = evt['path'] ?? '';
+  final copies = int.tryParse(evt['copies'] ?? '') ?? 1;
+  dialogManager.show((setState, close, context) {
+    submit() {
+      close();
+      final error = bind.mainPrintPdf(path: path, copies: copies);
+      if (error.isNotEmpty) {
+        showToast('${translate('Failed')}: ${translate(error)}',
+            timeout: const Duration(seconds: 6));
+      }
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Print')),
+      content: Text(
+          '${translate('print_job_tip')}\n\n${title.isEmpty ? path : title}\n${translate('Copies')}: $copies'),
+      actions: [
+        dialogButton('Cancel', onPressed: close, isOutline: true),
+        dialogButton('Print', onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: close,
+    );
+  });
+}
+
+// The processes of the peer, sorted by the column clicked, the ones using the most first.
+void showTaskManager(FFI ffi) {
+  final model = ffi.taskManagerModel;
+  final tag = '${ffi.sessionId}-task-manager';
+  ffi.dialogManager.dismissByTag(tag);
+  model.open();
+  ffi.dialogManager.show(tag: tag, (setState, close, context) {
+    void closeTaskManager() {
+      model.close();
+      close();
+    }
+
+    Widget header(String title, int sort, int flex) => Expanded(
+          flex: flex,
+          child: InkWell(
+            onTap: () => model.sortBy(sort),
+            child: Obx(() => Row(children: [
+                  Flexible(
+                      child: Text(translate(title),
+                          overflow: TextOverflow.ellipsis,
+                          style: TextStyle(fontWeight: FontWeight.bold))),
+                  if (model.sort.value == sort)
+                    Icon(
+                        model.ascending.value
+                            ? Icons.arrow_drop_up
+                            : Icons.arrow_drop_down,
+                        size: 18),
+                ])),
+          ),
+        );
+
+    Widget cell(String text, int flex) => Expanded(
+        flex: flex,
+        child: Text(text, overflow: TextOverflow.ellipsis, maxLines: 1));
+
+    void confirmKill(ProcessInfo p) {
+      ffi.dialogManager.show((setState, close, context) {
+        submit() {
+          model.kill(p.pid);
+          close();
+        }
+
+        return CustomAlertDialog(
+          title: Text(translate('End process')),
+          content: Text('${p.name} (${p.pid})'),
+          actions: [
+            dialogButton('Cancel', onPressed: close, isOutline: true),
+            dialogButton('OK', onPressed: submit),
+          ],
+          onSubmit: submit,
+          onCancel: close,
+        );
+      });
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Task manager')),
+      contentBoxConstraints: BoxConstraints(minWidth: 600, maxWidth: 600),
+      content: SizedBox(
+        width: 600,
+        height: 400,
+        child: Column(
+          children: [
+            Row(children: [
+              header('PID', kProcessSortPid, 2),
+              header('Name', kProcessSortName, 5),
+              Expanded(
+                  flex: 3,
+                  child: Text(translate('User'),
+                      style: TextStyle(fontWeight: FontWeight.bold))),
+              header('CPU', kProcessSortCpu, 2),
+              header('Memory', kProcessSortMemory, 3),
+              const SizedBox(width: 40),
+            ]),
+            const Divider(),
+            Expanded(
+              child: Obx(() {
+                if (model.error.value.isNotEmpty) {
+                  return Center(child: Text(translate(model.error.value)));
+                }
+                final processes = model.processes;
+                return ListView.builder(
+                  itemCount: processes.length,
+                  itemBuilder: (context, i) {
+                    final p = processes[i];
+                    return Row(children: [
+                      cell('${p.pid}', 2),
+                      cell(p.name, 5),
+                      cell(p.user, 3),
+                      cell('${p.cpu.toStringAsFixed(1)}%', 2),
+                      cell(readableFileSize(p.memory.toDouble()), 3),
+                      SizedBox(
+                        width: 40,
+                        child: IconButton(
+                          icon: const Icon(Icons.close, size: 16),
+                          tooltip: translate('End process'),
+                          onPressed: () => confirmKill(p),
+                        ),
+                      ),
+                    ]);
+                  },
+                );
+              }),
+            ),
+            Obx(() => Align(
+                  alignment: Alignment.centerLeft,
+                  child: Text(
+                      '${model.processes.length} / ${model.total.value}',
+                      style: TextStyle(fontSize: 12)),
+                )),
+          ],
+        ),
+      ),
+      actions: [dialogButton('Close', onPressed: closeTaskManager)],
+      onSubmit: closeTaskManager,
+      onCancel: closeTaskManager,
+    );
+  });
+}
+
+// The services of the peer, found by the name or the description, to be started, stopped or
+// restarted.
+void showServiceManager(FFI ffi) {
+  final model = ffi.serviceManagerModel;
+  final tag = '${ffi.sessionId}-service-manager';
+  ffi.dialogManager.dismissByTag(tag);
+  model.clear();
+  model.refresh();
+  ffi.dialogManager.show(tag: tag, (setState, close, context) {
+    void confirm(ServiceInfo s, int action, String title) {
+      ffi.dialogManager.show((setState, close, context) {
+        submit() {
+          model.control(s.name, action);
+          close();
+        }
+
+        return CustomAlertDialog(
+          title: Text(translate(title)),
+          content: Text(s.description.isEmpty
+              ? s.name
+              : '${s.description} (${s.name})'),
+          actions: [
+            dialogButton('Cancel', onPressed: close, isOutline: true),
+            dialogButton('OK', onPressed: submit),
+          ],
+          onSubmit: submit,
+          onCancel: close,
+        );
+      });
+    }
+
+    Widget action(ServiceInfo s, IconData icon, int action, String title) =>
+        IconButton(
+          icon: Icon(icon, size: 16),
+          tooltip: translate(title),
+          onPressed: () => confirm(s, action, title),
+        );
+
+    return CustomAlertDialog(
+      title: Row(children: [
+        Expanded(child: Text(translate('Service manager'))),
+        IconButton(
+            icon: const Icon(Icons.refresh),
+            tooltip: translate('Refresh'),
+            onPressed: model.refresh),
+      ]),
+      contentBoxConstraints: BoxConstraints(minWidth: 600, maxWidth: 600),
+      content: SizedBox(
+        width: 600,
+        height: 400,
+        child: Column(
+          children: [
+            TextField(
+              decoration: InputDecoration(
+                  prefixIcon: const Icon(Icons.search),
+                  hintText: translate('Search')),
+              onChanged: (value) => model.filter.value = value,
+            ),
+            Expanded(
+              child: Obx(() {
+                if (model.error.value.isNotEmpty) {
+                  return Center(child: Text(translate(model.error.value)));
+                }
+                if (model.loading.value && model.services.isEmpty) {
+                  return const Center(child: CircularProgressIndicator());
+                }
+                final services = model.filtered;
+                return ListView.builder(
+                  itemCount: services.length,
+                  itemBuilder: (context, i) {
+                    final s = services[i];
+                    final pending = model.pending.contains(s.name);
+                    return Row(children: [
+                      Icon(Icons.circle,
+                          size: 10,
+                          color: s.running ? Colors.green : Colors.grey),
+                      const SizedBox(width: 8),
+                      Expanded(
+                          flex: 4,
+                          child: Text(s.name,
+                              overflow: TextOverflow.ellipsis, maxLines: 1)),
+                      Expanded(
+                          flex: 5,
+                          child: Text(s.description,
+                              overflow: TextOverflow.ellipsis, maxLines: 1)),
+                      Expanded(
+                          flex: 2,
+                          child: Text(s.state,
+                              overflow: TextOverflow.ellipsis, maxLines: 1)),
+                      if (pending)
+                        const SizedBox(
+                            width: 120,
+                            child: Center(
+                                child: SizedBox(
+                                    width: 16,
+                                    height: 16,
+                                    child: CircularProgressIndicator(
+                                        strokeWidth: 2))))
+                      else
+                        SizedBox(
+                          width: 120,
+                          child: Row(children: [
+                            if (!s.running)
+                              action(s, Icons.play_arrow, kServiceStart,
+                                  'Start the service'),
+                            if (s.running)
+                              action(s, Icons.stop, kServiceStop,
+                                  'Stop the service'),
+                            action(s, Icons.restart_alt, kServiceRestart,
+                                'Restart the service'),
+                          ]),
+                        ),
+                    ]);
+                  },
+                );
+              }),
+            ),
+          ],
+        ),
+      ),
+      actions: [dialogButton('Close', onPressed: close)],
+      onSubmit: close,
+      onCancel: close,
+    );
+  });
+}
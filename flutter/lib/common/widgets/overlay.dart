@@ -596,12 +596,219 @@ class QualityMonitor extends StatelessWidget {
                       _row(
                           "Codec", qualityMonitorModel.data.codecFormat ?? '-'),
                       _row("Chroma", qualityMonitorModel.data.chroma ?? '-'),
+                      if (qualityMonitorModel.data.bandwidth != null)
+                        _row("Bandwidth",
+                            "${qualityMonitorModel.data.bandwidth}kb"),
+                      if (qualityMonitorModel.data.avSync != null)
+                        _row("A/V sync",
+                            "${qualityMonitorModel.data.avSync}ms"),
+                      if (qualityMonitorModel.data.jitter != null)
+                        _row("Jitter", "${qualityMonitorModel.data.jitter}ms"),
+                      if (qualityMonitorModel.data.loss != null)
+                        _row("Loss", "${qualityMonitorModel.data.loss}%"),
+                      if (qualityMonitorModel.data.encodeFps != null)
+                        _row("Encode FPS",
+                            qualityMonitorModel.data.encodeFps ?? '-'),
+                      if (qualityMonitorModel.data.direct != null)
+                        _row(
+                            "Connection",
+                            qualityMonitorModel.data.direct == 'true'
+                                ? 'Direct'
+                                : 'Relay'),
                     ],
                   ),
                 )
               : const SizedBox.shrink()));
 }
 
+// The load of the controlled side, collapsed to the cpu and the memory.
+class SystemStatsPanel extends StatelessWidget {
+  final FFI ffi;
+  SystemStatsPanel(this.ffi);
+
+  Widget _row(String info, String value) {
+    return Row(
+      children: [
+        Expanded(
+            flex: 8,
+            child: AutoSizeText(info,
+                style: TextStyle(color: Color.fromARGB(255, 210, 210, 210)),
+                textAlign: TextAlign.right,
+                maxLines: 1)),
+        Spacer(flex: 1),
+        Expanded(
+            flex: 8,
+            child: AutoSizeText(value,
+                style: TextStyle(color: Colors.white), maxLines: 1)),
+      ],
+    );
+  }
+
+  Widget _title(String title) => Padding(
+      padding: const EdgeInsets.only(top: 4),
+      child: Text(translate(title),
+          style: TextStyle(color: Colors.white, fontWeight: FontWeight.bold)));
+
+  @override
+  Widget build(BuildContext context) => ChangeNotifierProvider.value(
+      value: ffi.systemStatsModel,
+      child: Consumer<SystemStatsModel>(builder: (context, model, child) {
+        if (!model.show) return const SizedBox.shrink();
+        final stats = model.stats;
+        return Container(
+          constraints: BoxConstraints(maxWidth: 220),
+          padding: const EdgeInsets.all(8),
+          color: MyTheme.canvasColor.withAlpha(150),
+          child: Column(
+            crossAxisAlignment: CrossAxisAlignment.start,
+            mainAxisSize: MainAxisSize.min,
+            children: [
+              InkWell(
+                onTap: model.toggleCollapsed,
+                child: Row(children: [
+                  Icon(
+                      model.collapsed
+                          ? Icons.keyboard_arrow_right
+                          : Icons.keyboard_arrow_down,
+                      color: Colors.white,
+                      size: 16),
+                  Text(translate('System stats'),
+                      style: TextStyle(color: Colors.white)),
+                ]),
+              ),
+              if (stats == null)
+                _row(translate('CPU'), '-')
+              else ...[
+                _row(translate('CPU'), '${stats.cpu.toStringAsFixed(1)}%'),
+                _row(translate('Memory'),
+                    '${readableFileSize(stats.memoryUsed.toDouble())} / ${readableFileSize(stats.memoryTotal.toDouble())}'),
+                if (!model.collapsed) ...[
+                  if (stats.cores.isNotEmpty) ...[
+                    _title('Cores'),
+                    for (var i = 0; i < stats.cores.length; i++)
+                      _row('$i', '${stats.cores[i].toStringAsFixed(1)}%'),
+                  ],
+                  if (stats.disks.isNotEmpty) ...[
+                    _title('Disks'),
+                    for (final (mountPoint, total, available) in stats.disks)
+                      _row(mountPoint,
+                          '${readableFileSize(available.toDouble())} / ${readableFileSize(total.toDouble())}'),
+                  ],
+                  if (stats.temperatures.isNotEmpty) ...[
+                    _title('Temperatures'),
+                    for (final (label, celsius) in stats.temperatures)
+                      _row(label, '${celsius.toStringAsFixed(0)}°C'),
+                  ],
+                ],
+              ],
+            ],
+          ),
+        );
+      }));
+}
+
+class CameraPanel extends StatelessWidget {
+  final FFI ffi;
+  CameraPanel(this.ffi);
+
+  Widget _dropdown<T>(T value, List<T> values, String Function(T) label,
+      void Function(T) onChanged) {
+    return DropdownButton<T>(
+      value: values.contains(value) ? value : null,
+      isDense: true,
+      underline: const SizedBox.shrink(),
+      dropdownColor: MyTheme.canvasColor,
+      style: TextStyle(color: Colors.white, fontSize: 12),
+      items: values
+          .map((v) => DropdownMenuItem<T>(value: v, child: Text(label(v))))
+          .toList(),
+      onChanged: (v) {
+        if (v != null) onChanged(v);
+      },
+    );
+  }
+
+  @override
+  Widget build(BuildContext context) => ChangeNotifierProvider.value(
+      value: ffi.cameraModel,
+      child: Consumer<CameraModel>(builder: (context, model, child) {
+        if (!model.show) return const SizedBox.shrink();
+        final sessionId = ffi.sessionId;
+        final image = model.image;
+        return Container(
+          width: 320,
+          padding: const EdgeInsets.all(8),
+          color: MyTheme.canvasColor.withAlpha(150),
+          child: Column(
+            crossAxisAlignment: CrossAxisAlignment.start,
+            mainAxisSize: MainAxisSize.min,
+            children: [
+              Row(children: [
+                Expanded(
+                    child: Text(translate('Camera'),
+                        style: TextStyle(color: Colors.white))),
+                InkWell(
+                  onTap: () => model.toggle(sessionId),
+                  child: Icon(Icons.close, color: Colors.white, size: 16),
+                ),
+              ]),
+              Wrap(spacing: 8, children: [
+                if (model.cameras.isNotEmpty)
+                  _dropdown<int>(
+                      model.index,
+                      model.cameras.map((c) => c.index).toList(),
+                      (i) => model.cameras.firstWhere((c) => c.index == i).name,
+                      (i) => model.set(sessionId, index: i)),
+                _dropdown<int>(model.fps, kCameraFpsOptions,
+                    (v) => '$v FPS', (v) => model.set(sessionId, fps: v)),
+                _dropdown<int>(model.quality, kCameraQualityOptions,
+                    (v) => '$v%', (v) => model.set(sessionId, quality: v)),
+                _dropdown<bool>(model.vp9, [true, false],
+                    (v) => v ? 'VP9' : 'VP8',
+                    (v) => model.set(sessionId, vp9: v)),
+              ]),
+              if (model.error.isNotEmpty)
+                Text(translate(model.error),
+                    style: TextStyle(color: Colors.red, fontSize: 12))
+              else if (image == null)
+                Text(translate('Waiting'),
+                    style: TextStyle(color: Colors.white, fontSize: 12))
+              else
+                AspectRatio(
+                  aspectRatio: image.width / image.height,
+                  child: RawImage(image: image, fit: BoxFit.contain),
+                ),
+            ],
+          ),
+        );
+      }));
+}
+
+/// The count of the viewers of the broadcast of the peer.
+class BroadcastViewersIndicator extends StatelessWidget {
+  final FFI ffi;
+  BroadcastViewersIndicator(this.ffi);
+
+  @override
+  Widget build(BuildContext context) => Obx(() {
+        final max = ffi.broadcastMaxViewers.value;
+        if (max == 0) return const SizedBox.shrink();
+        return Tooltip(
+          message: translate('Viewers of the broadcast'),
+          child: Container(
+            padding: const EdgeInsets.symmetric(horizontal: 8, vertical: 4),
+            color: MyTheme.canvasColor.withAlpha(150),
+            child: Row(mainAxisSize: MainAxisSize.min, children: [
+              Icon(Icons.visibility, color: Colors.white, size: 14),
+              const SizedBox(width: 4),
+              Text('${ffi.broadcastViewers.value}/$max',
+                  style: TextStyle(color: Colors.white, fontSize: 12)),
+            ]),
+          ),
+        );
+      });
+}
+
 class BlockableOverlayState extends OverlayKeyState {
   final _middleBlocked = false.obs;
 
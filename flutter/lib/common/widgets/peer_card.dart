@@ -15,6 +15,7 @@ import '../../models/platform_model.dart';
 import '../../desktop/widgets/material_mod_popup_menu.dart' as mod_menu;
 import '../../desktop/widgets/popup_menu.dart';
 import 'dart:math' as math;
+import 'dart:convert';
 
 typedef PopupMenuEntryBuilder = Future<List<mod_menu.PopupMenuEntry<String>>>
     Function(BuildContext);
@@ -592,6 +593,37 @@ abstract class BasePeerCard extends StatelessWidget {
     );
   }
 
+  @protected
+  MenuEntryBase<String> _wakeThroughPeerAction(String id) {
+    return MenuEntryButton<String>(
+      childBuilder: (TextStyle? style) => Text(
+        translate('Wake through a peer'),
+        style: style,
+      ),
+      proc: () {
+        _wakeThroughPeerDialog(id);
+      },
+      padding: menuPadding,
+      dismissOnClicked: true,
+    );
+  }
+
+  @protected
+  MenuEntryBase<String> _screenshotAction(String id) {
+    return MenuEntryButton<String>(
+      childBuilder: (TextStyle? style) => Text(
+        translate('Screenshot'),
+        style: style,
+      ),
+      proc: () async {
+        final err = await bind.mainTakeScreenshot(id: id, display: 0);
+        showToast(err.isEmpty ? translate('Successful') : translate(err));
+      },
+      padding: menuPadding,
+      dismissOnClicked: true,
+    );
+  }
+
   /// Only available on Windows.
   @protected
   MenuEntryBase<String> _createShortCutAction(String id) {
@@ -886,6 +918,10 @@ class RecentPeerCard extends BasePeerCard {
 
     if (isDesktop && peer.platform != kPeerPlatformAndroid) {
       menuItems.add(_tcpTunnelingAction(context));
+      menuItems.add(_screenshotAction(peer.id));
+      if (!peer.online) {
+        menuItems.add(_wakeThroughPeerAction(peer.id));
+      }
     }
     // menuItems.add(await _openNewConnInOptAction(peer.id));
     if (!isWeb) {
@@ -942,6 +978,10 @@ class FavoritePeerCard extends BasePeerCard {
     ];
     if (isDesktop && peer.platform != kPeerPlatformAndroid) {
       menuItems.add(_tcpTunnelingAction(context));
+      menuItems.add(_screenshotAction(peer.id));
+      if (!peer.online) {
+        menuItems.add(_wakeThroughPeerAction(peer.id));
+      }
     }
     // menuItems.add(await _openNewConnInOptAction(peer.id));
     if (!isWeb) {
@@ -998,6 +1038,7 @@ class DiscoveredPeerCard extends BasePeerCard {
 
     if (isDesktop && peer.platform != kPeerPlatformAndroid) {
       menuItems.add(_tcpTunnelingAction(context));
+      menuItems.add(_screenshotAction(peer.id));
     }
     // menuItems.add(await _openNewConnInOptAction(peer.id));
     if (!isWeb) {
@@ -1048,6 +1089,10 @@ class AddressBookPeerCard extends BasePeerCard {
     ];
     if (isDesktop && peer.platform != kPeerPlatformAndroid) {
       menuItems.add(_tcpTunnelingAction(context));
+      menuItems.add(_screenshotAction(peer.id));
+      if (!peer.online) {
+        menuItems.add(_wakeThroughPeerAction(peer.id));
+      }
     }
     // menuItems.add(await _openNewConnInOptAction(peer.id));
     // menuItems.add(await _forceAlwaysRelayAction(peer.id));
@@ -1180,6 +1225,10 @@ class MyGroupPeerCard extends BasePeerCard {
     ];
     if (isDesktop && peer.platform != kPeerPlatformAndroid) {
       menuItems.add(_tcpTunnelingAction(context));
+      menuItems.add(_screenshotAction(peer.id));
+      if (!peer.online) {
+        menuItems.add(_wakeThroughPeerAction(peer.id));
+      }
     }
     // menuItems.add(await _openNewConnInOptAction(peer.id));
     // menuItems.add(await _forceAlwaysRelayAction(peer.id));
@@ -1205,6 +1254,58 @@ class MyGroupPeerCard extends BasePeerCard {
   void _update() => gFFI.groupModel.pull();
 }
 
+void _wakeThroughPeerDialog(String id) async {
+  final List relays = jsonDecode(bind.mainGetWolRelays(id: id));
+  final controller =
+      TextEditingController(text: relays.isNotEmpty ? relays.first : '');
+  RxBool isInProgress = false.obs;
+
+  gFFI.dialogManager.show((setState, close, context) {
+    submit() async {
+      final relayId = controller.text.trim();
+      if (relayId.isEmpty || isInProgress.value) return;
+      isInProgress.value = true;
+      final err = await bind.mainWakeThroughPeer(id: id, relayId: relayId);
+      isInProgress.value = false;
+      showToast(err.isEmpty ? translate('Successful') : translate(err));
+      if (err.isEmpty) close();
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Wake through a peer')),
+      content: ConstrainedBox(
+        constraints: const BoxConstraints(minWidth: 400),
+        child: Column(
+          crossAxisAlignment: CrossAxisAlignment.start,
+          mainAxisSize: MainAxisSize.min,
+          children: [
+            Text(translate('wake-through-peer-tip')).marginOnly(bottom: 8),
+            TextField(
+              decoration: InputDecoration(labelText: translate('ID')),
+              controller: controller,
+              autofocus: true,
+            ),
+            ...relays.map((relayId) => ListTile(
+                  dense: true,
+                  title: Text(relayId),
+                  onTap: () => setState(() => controller.text = relayId),
+                )),
+            Obx(() => isInProgress.value
+                ? const LinearProgressIndicator().marginOnly(top: 8)
+                : Offstage()),
+          ],
+        ),
+      ),
+      actions: [
+        dialogButton("Cancel", onPressed: close, isOutline: true),
+        dialogButton("OK", onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: close,
+    );
+  });
+}
+
 void _rdpDialog(String id) async {
   final maxLength = bind.mainMaxEncryptLen();
   final port = await bind.mainGetPeerOption(id: id, key: 'rdp_port');
@@ -465,6 +465,28 @@ class _RawTouchGestureDetectorRegionState
   }
 }
 
+/// Sends the raw touch points instead of the gestures, see [FfiModel.nativeTouch].
+class RawTouchForwardRegion extends StatelessWidget {
+  final InputModel inputModel;
+  final Widget child;
+
+  RawTouchForwardRegion({
+    required this.inputModel,
+    required this.child,
+  });
+
+  @override
+  Widget build(BuildContext context) {
+    return Listener(
+      onPointerDown: inputModel.onNativeTouch,
+      onPointerMove: inputModel.onNativeTouch,
+      onPointerUp: inputModel.onNativeTouch,
+      onPointerCancel: inputModel.onNativeTouch,
+      child: child,
+    );
+  }
+}
+
 class RawPointerMouseRegion extends StatelessWidget {
   final InputModel inputModel;
   final Widget child;
@@ -497,6 +519,7 @@ class RawPointerMouseRegion extends StatelessWidget {
         inputModel.onPointUpImage(evt);
       },
       onPointerMove: inputModel.onPointMoveImage,
+      onPointerCancel: inputModel.onPointCancelImage,
       onPointerSignal: inputModel.onPointerSignalImage,
       onPointerPanZoomStart: inputModel.onPointerPanZoomStart,
       onPointerPanZoomUpdate: inputModel.onPointerPanZoomUpdate,
@@ -130,16 +130,26 @@ List<TTextMenu> toolbarControls(BuildContext context, String id, FFI ffi) {
     );
   }
   // paste
-  if (pi.platform != kPeerPlatformAndroid && perms['keyboard'] != false) {
-    v.add(TTextMenu(
-        child: Text(translate('Send clipboard keystrokes')),
-        onPressed: () async {
-          ClipboardData? data = await Clipboard.getData(Clipboard.kTextPlain);
-          if (data != null && data.text != null) {
-            bind.sessionInputString(
-                sessionId: sessionId, value: data.text ?? "");
-          }
-        }));
+  if (pi.platform != kPeerPlatformAndroid &&
+      perms['keyboard'] != false &&
+      perms['clipboard_typing'] != false) {
+    // typed key by key as a macro
+    if (bind.sessionIsPlayingMacro(sessionId: sessionId)) {
+      v.add(TTextMenu(
+          child: Text(translate('Stop typing')),
+          onPressed: () => bind.sessionStopMacro(sessionId: sessionId)));
+    } else {
+      v.add(TTextMenu(
+          child: Text(translate('Send clipboard keystrokes')),
+          onPressed: () async {
+            ClipboardData? data =
+                await Clipboard.getData(Clipboard.kTextPlain);
+            if (data != null && data.text != null) {
+              bind.sessionTypeClipboard(
+                  sessionId: sessionId, value: data.text ?? "");
+            }
+          }));
+    }
   }
   // reset canvas
   if (isMobile) {
@@ -185,6 +195,67 @@ List<TTextMenu> toolbarControls(BuildContext context, String id, FFI ffi) {
           onPressed: () => showAuditDialog(ffi)),
     );
   }
+  // the master volume of the peer
+  if (!isWeb &&
+      perms['audio'] != false &&
+      pi.platformAdditions[kPlatformAdditionsVolume] == true) {
+    v.add(
+      TTextMenu(
+          child: Text(translate('Remote volume')),
+          onPressed: () => remoteVolumeDialog(sessionId, ffi)),
+    );
+  }
+  // the control of the input, held by one of the peers connected at once
+  if (ffiModel.keyboard && ffiModel.inputControlEnabled.isTrue) {
+    final holding = ffiModel.inputControlHolding.isTrue;
+    final requested = ffiModel.inputControlRequested.isTrue;
+    v.add(
+      TTextMenu(
+          child: Text(translate(holding
+              ? 'Release control'
+              : requested
+                  ? 'Cancel control request'
+                  : 'Request control')),
+          onPressed: () => bind.sessionRequestInputControl(
+              sessionId: sessionId, request: !holding && !requested)),
+    );
+  }
+  // draw over the screen of the peer
+  if (isDesktop && ffiModel.keyboard) {
+    final drawing = ffi.annotationModel.drawing.isTrue;
+    v.add(
+      TTextMenu(
+          child: Text(translate(drawing ? 'Stop drawing' : 'Draw on screen')),
+          onPressed: () => ffi.annotationModel.toggle()),
+    );
+  }
+  // bandwidth limit, not supported by the web client
+  if (!isWeb) {
+    v.add(
+      TTextMenu(
+          child: Text(translate('Bandwidth limit')),
+          onPressed: () => bandwidthLimitDialog(sessionId, ffi)),
+    );
+  }
+  // macros, recorded on this computer
+  if (!isWeb && !ffiModel.viewOnly && ffiModel.keyboard) {
+    if (bind.sessionIsRecordingMacro(sessionId: sessionId)) {
+      v.add(
+        TTextMenu(
+            child: Text(translate('Stop recording macro')),
+            onPressed: () {
+              final err = bind.sessionStopMacroRecording(sessionId: sessionId);
+              showToast(err.isEmpty ? translate('Successful') : err);
+            }),
+      );
+    } else {
+      v.add(
+        TTextMenu(
+            child: Text(translate('Macros')),
+            onPressed: () => macrosDialog(sessionId, ffi)),
+      );
+    }
+  }
   // divider
   if (isDesktop || isWebDesktop) {
     v.add(TTextMenu(child: Offstage(), onPressed: () {}, divider: true));
@@ -247,6 +318,23 @@ List<TTextMenu> toolbarControls(BuildContext context, String id, FFI ffi) {
         onPressed: () =>
             showConfirmSwitchSidesDialog(sessionId, id, ffi.dialogManager)));
   }
+  // handoff
+  if (!isWeb) {
+    v.add(TTextMenu(
+        child: Text(translate('Hand off the session')),
+        onPressed: () => bind.sessionRequestHandoff(sessionId: sessionId)));
+  }
+  // task manager
+  if (!isWeb &&
+      perms['keyboard'] != false &&
+      pi.platform != kPeerPlatformAndroid) {
+    v.add(TTextMenu(
+        child: Text(translate('Task manager')),
+        onPressed: () => showTaskManager(ffi)));
+    v.add(TTextMenu(
+        child: Text(translate('Service manager')),
+        onPressed: () => showServiceManager(ffi)));
+  }
   // refresh
   if (pi.version.isNotEmpty) {
     v.add(TTextMenu(
@@ -534,6 +622,40 @@ Future<List<TToggleMenu>> toolbarDisplayToggle(
         ffi.qualityMonitorModel.checkShowQualityMonitor(sessionId);
       },
       child: Text(translate('Show quality monitor'))));
+  // show system stats
+  v.add(TToggleMenu(
+      value: ffi.systemStatsModel.show,
+      onChanged: (value) {
+        if (value == null) return;
+        ffi.systemStatsModel.toggle(sessionId);
+      },
+      child: Text(translate('Show system stats'))));
+  // show camera, captured on windows and linux only
+  if (!isWeb &&
+      (pi.platform == kPeerPlatformWindows ||
+          pi.platform == kPeerPlatformLinux)) {
+    v.add(TToggleMenu(
+        value: ffi.cameraModel.show,
+        onChanged: (value) {
+          if (value == null) return;
+          ffi.cameraModel.toggle(sessionId);
+        },
+        child: Text(translate('Show camera'))));
+  }
+  // print the documents of the virtual printer of the peer, leaving it as the files do
+  if (!isWeb &&
+      perms['file'] != false &&
+      (pi.platform == kPeerPlatformLinux ||
+          pi.platform == kPeerPlatformMacOS)) {
+    v.add(TToggleMenu(
+        value: ffi.remotePrinter.value,
+        onChanged: (value) {
+          if (value == null) return;
+          ffi.remotePrinter.value = value;
+          bind.sessionRemotePrinter(sessionId: sessionId, on: value);
+        },
+        child: Text(translate('Print the documents of the remote side'))));
+  }
   // mute
   if (perms['audio'] != false) {
     final option = 'disable-audio';
@@ -547,6 +669,33 @@ Future<List<TToggleMenu>> toolbarDisplayToggle(
         },
         child: Text(translate('Mute'))));
   }
+  // noise suppression, of the microphone in a voice call too
+  if (perms['audio'] != false &&
+      pi.platformAdditions[kPlatformAdditionsDenoise] == true) {
+    final option = kOptionDenoiseAudio;
+    final value =
+        bind.sessionGetToggleOptionSync(sessionId: sessionId, arg: option);
+    v.add(TToggleMenu(
+        value: value,
+        onChanged: (value) {
+          if (value == null) return;
+          bind.sessionToggleOption(sessionId: sessionId, value: option);
+        },
+        child: Text(translate('Noise suppression'))));
+  }
+  // presenter mode, the cursor and the clicks highlighted on the screen
+  if (ffiModel.keyboard) {
+    final option = kOptionPresenterMode;
+    final value =
+        bind.sessionGetToggleOptionSync(sessionId: sessionId, arg: option);
+    v.add(TToggleMenu(
+        value: value,
+        onChanged: (value) {
+          if (value == null) return;
+          bind.sessionToggleOption(sessionId: sessionId, value: option);
+        },
+        child: Text(translate('Presenter mode'))));
+  }
   // file copy and paste
   // If the version is less than 1.2.4, file copy and paste is supported on Windows only.
   final isSupportIfPeer_1_2_3 = versionCmp(pi.version, '1.2.4') < 0 &&
@@ -656,6 +805,34 @@ Future<List<TToggleMenu>> toolbarDisplayToggle(
         child: Text(translate('True color (4:4:4)'))));
   }
 
+  // post-processing filters of the decoded frames, web decodes in js
+  if (!isWeb) {
+    final filters = (await bind.sessionGetPeerOption(
+            sessionId: sessionId, name: kOptionVideoFilters))
+        .split(',')
+        .where((e) => e.isNotEmpty)
+        .toList();
+    for (final e in {
+      'sharpen': 'Sharpen',
+      'deband': 'Deband',
+      'grayscale': 'Grayscale',
+      'smooth': 'Smooth scaling',
+    }.entries) {
+      v.add(TToggleMenu(
+          value: filters.contains(e.key),
+          onChanged: (value) async {
+            if (value == null) return;
+            filters.remove(e.key);
+            if (value) filters.add(e.key);
+            await bind.sessionPeerOption(
+                sessionId: sessionId,
+                name: kOptionVideoFilters,
+                value: filters.join(','));
+          },
+          child: Text(translate(e.value))));
+    }
+  }
+
   if (isMobile) {
     v.addAll(toolbarKeyboardToggles(ffi));
   }
@@ -810,6 +987,41 @@ List<TToggleMenu> toolbarKeyboardToggles(FFI ffi) {
         onChanged: enabled ? onChanged : null,
         child: Text(translate('swap-left-right-mouse'))));
   }
+
+  // forward gamepad
+  if (ffiModel.keyboard && pi.features.gamepad && isDesktop) {
+    final option = kOptionForwardGamepad;
+    final value =
+        bind.sessionGetToggleOptionSync(sessionId: sessionId, arg: option);
+    onChanged(bool? value) {
+      if (value == null) return;
+      bind.sessionToggleOption(sessionId: sessionId, value: option);
+    }
+
+    final enabled = !ffi.ffiModel.viewOnly;
+    v.add(TToggleMenu(
+        value: value,
+        onChanged: enabled ? onChanged : null,
+        child: Text(translate('Forward gamepad'))));
+  }
+
+  // native touch, the pinch and the other gestures are handled by the remote apps
+  if (ffiModel.keyboard && pi.features.touch && isMobile) {
+    final option = kOptionNativeTouch;
+    final value =
+        bind.sessionGetToggleOptionSync(sessionId: sessionId, arg: option);
+    onChanged(bool? value) {
+      if (value == null) return;
+      bind.sessionToggleOption(sessionId: sessionId, value: option);
+      ffiModel.setNativeTouch(value);
+    }
+
+    final enabled = !ffi.ffiModel.viewOnly;
+    v.add(TToggleMenu(
+        value: value,
+        onChanged: enabled ? onChanged : null,
+        child: Text(translate('Native touch'))));
+  }
   return v;
 }
 
@@ -25,6 +25,10 @@ const String kPlatformAdditionsRustDeskVirtualDisplays =
 const String kPlatformAdditionsAmyuniVirtualDisplays =
     "amyuni_virtual_displays";
 const String kPlatformAdditionsHasFileClipboard = "has_file_clipboard";
+const String kPlatformAdditionsAudioInputs = "audio_inputs";
+const String kPlatformAdditionsDenoise = "denoise";
+const String kPlatformAdditionsVolume = "volume";
+const String kPlatformAdditionsSurround = "surround";
 const String kPlatformAdditionsSupportedPrivacyModeImpl =
     "supported_privacy_mode_impl";
 
@@ -81,12 +85,36 @@ const String kOptionForceAlwaysRelay = "force-always-relay";
 const String kOptionViewOnly = "view_only";
 const String kOptionEnableLanDiscovery = "enable-lan-discovery";
 const String kOptionWhitelist = "whitelist";
+const String kOptionIpRules = "ip-rules";
+const String kOptionUnattendedSchedule = "unattended-schedule";
+const String kOptionSyncViewSettings = "sync-view-settings";
+const String kOptionRefuseOutsideSchedule = "refuse-outside-schedule";
+const String kOptionTlsPins = "tls-pins";
 const String kOptionEnableAbr = "enable-abr";
 const String kOptionEnableRecordSession = "enable-record-session";
 const String kOptionDirectServer = "direct-server";
 const String kOptionDirectAccessPort = "direct-access-port";
+const String kOptionDirectAccessKnockKey = "direct-access-knock-key";
+const String kOptionAllowPortMapping = "allow-port-mapping";
+const String kOptionTurnServer = "turn-server";
+const String kOptionTurnUsername = "turn-username";
+const String kOptionTurnPassword = "turn-password";
+const String kOptionKeepAliveInterval = "keep-alive-interval";
+const String kOptionKeepAliveRetries = "keep-alive-retries";
+const String kOptionKeepAliveTimeout = "keep-alive-timeout";
+const String kOptionDscpInteractive = "dscp-interactive";
+const String kOptionDscpFileTransfer = "dscp-file-transfer";
+const String kOptionPreferredIpFamily = "preferred-ip-family";
+const String kOptionAllowQuic = "allow-quic";
 const String kOptionAllowAutoDisconnect = "allow-auto-disconnect";
 const String kOptionAutoDisconnectTimeout = "auto-disconnect-timeout";
+const String kOptionAutoDisconnectAction = "auto-disconnect-action";
+const String kOptionAllowAutoBlockInput = "allow-auto-block-input";
+const String kOptionAutoBlockInputTimeout = "auto-block-input-timeout";
+const String kOptionAllowVoiceCallVirtualMic = "allow-voice-call-virtual-mic";
+const String kOptionEnableVoiceCallEchoSuppression =
+    "enable-voice-call-echo-suppression";
+const String kOptionVoiceCallPushToTalk = "voice-call-push-to-talk";
 const String kOptionEnableHwcodec = "enable-hwcodec";
 const String kOptionAllowAutoRecordIncoming = "allow-auto-record-incoming";
 const String kOptionAllowAutoRecordOutgoing = "allow-auto-record-outgoing";
@@ -98,12 +126,43 @@ const String kOptionEnableClipboard = "enable-clipboard";
 const String kOptionEnableFileTransfer = "enable-file-transfer";
 const String kOptionEnableAudio = "enable-audio";
 const String kOptionEnableTunnel = "enable-tunnel";
+const String kOptionAllowTerminal = "allow-terminal";
+const String kOptionAllowWolRelay = "allow-wol-relay";
+const String kOptionAllowInputArbitration = "allow-input-arbitration";
+const String kOptionAllowAnnotation = "allow-annotation";
+const String kOptionAllowSystemStats = "allow-system-stats";
+const String kOptionAllowTaskManager = "allow-task-manager";
+const String kOptionAllowServiceManager = "allow-service-manager";
+const String kOptionAllowRemoteCamera = "allow-remote-camera";
+const String kOptionAllowBroadcast = "allow-broadcast";
 const String kOptionEnableRemoteRestart = "enable-remote-restart";
 const String kOptionEnableBlockInput = "enable-block-input";
+const String kOptionEnableKeyInput = "enable-key-input";
+const String kOptionEnableMouseInput = "enable-mouse-input";
+const String kOptionEnableClipboardTyping = "enable-clipboard-typing";
 const String kOptionAllowRemoteConfigModification =
     "allow-remote-config-modification";
 const String kOptionVerificationMethod = "verification-method";
 const String kOptionApproveMode = "approve-mode";
+const String kOptionRequireAccessReason = "require-access-reason";
+const String kOptionAllowApproveTimeout = "allow-approve-timeout";
+const String kOptionApproveTimeout = "approve-timeout";
+const String kOptionTrustKeysOnFirstUse = "trust-keys-on-first-use";
+const String kOptionRejectUntrustedKeys = "reject-untrusted-keys";
+const String kOptionNotifyWebhook = "notify-webhook";
+const String kOptionNotifySmtpServer = "notify-smtp-server";
+const String kOptionNotifySmtpUsername = "notify-smtp-username";
+const String kOptionNotifySmtpPassword = "notify-smtp-password";
+const String kOptionNotifyEmailFrom = "notify-email-from";
+const String kOptionNotifyEmailTo = "notify-email-to";
+const String kOptionNotifyEvents = "notify-events";
+const String kOptionAllowWatermark = "allow-watermark";
+const String kOptionOidcIssuer = "oidc-issuer";
+const String kOptionOidcClientId = "oidc-client-id";
+const String kOptionIdentityProvider = "identity-provider";
+const String kOptionIdentityGroup = "identity-group";
+const String kOptionEnableAuditLog = "enable-audit-log";
+const String kOptionAuditLogRetentionDays = "audit-log-retention-days";
 const String kOptionCollapseToolbar = "collapse_toolbar";
 const String kOptionShowRemoteCursor = "show_remote_cursor";
 const String kOptionFollowRemoteCursor = "follow_remote_cursor";
@@ -119,7 +178,41 @@ const String kOptionPrivacyMode = "privacy_mode";
 const String kOptionTouchMode = "touch-mode";
 const String kOptionI444 = "i444";
 const String kOptionSwapLeftRightMouse = "swap-left-right-mouse";
+const String kOptionForwardGamepad = "forward-gamepad";
+const String kOptionRemoteAudioInput = "remote-audio-input";
+const String kOptionAudioQuality = "audio-quality";
+const String kOptionDenoiseAudio = "denoise-audio";
+const String kOptionPresenterMode = "presenter-mode";
+const String kOptionNativeTouch = "native-touch";
+// comma separated, kept on this side instead of sent to the peer
+const String kOptionLocalShortcuts = "local-shortcuts";
+const String kOptionCaptureAllKeysInFullscreen =
+    "capture-all-keys-in-fullscreen";
+// in milliseconds
+const String kOptionClipboardTypingDelay = "clipboard-typing-delay";
+const String kLocalShortcutAltTab = "alt-tab";
+const String kLocalShortcutCmdTab = "cmd-tab";
+const String kLocalShortcutWin = "win";
+const String kLocalShortcutMedia = "media";
 const String kOptionCodecPreference = "codec-preference";
+const String kOptionVideoFilters = "video-filters";
+const String kOptionBandwidthLimit = "bandwidth-limit";
+const String kOptionFileTransferRateLimit = "file-transfer-rate-limit";
+const String kOptionEnableFileTransferCompression =
+    "enable-file-transfer-compression";
+const String kOptionEnableFileTransferAuditLog =
+    "enable-file-transfer-audit-log";
+const String kOptionEnablePreserveFilePermissions =
+    "enable-preserve-file-permissions";
+const String kOptionEnablePreserveFileTimestamps =
+    "enable-preserve-file-timestamps";
+const String kOptionEnablePreserveFileXattrs = "enable-preserve-file-xattrs";
+// skip, copy or follow
+const String kOptionFileTransferLinkPolicy = "file-transfer-link-policy";
+// ask, overwrite, newer, skip or rename
+const String kOptionFileTransferConflictPolicy =
+    "file-transfer-conflict-policy";
+const String kOptionEnableDeletePermanently = "enable-delete-permanently";
 const String kOptionRemoteMenubarDragLeft = "remote-menubar-drag-left";
 const String kOptionRemoteMenubarDragRight = "remote-menubar-drag-right";
 const String kOptionHideAbTagsPanel = "hideAbTagsPanel";
@@ -168,6 +261,7 @@ const int kWindowMainId = 0;
 
 const String kPointerEventKindTouch = "touch";
 const String kPointerEventKindMouse = "mouse";
+const String kPointerEventKindPen = "pen";
 
 const String kMouseEventTypeDefault = "";
 const String kMouseEventTypePanStart = "pan_start";
@@ -204,6 +298,8 @@ const int kDesktopMaxDisplaySize = 3840;
 
 const double kDesktopFileTransferRowHeight = 30.0;
 const double kDesktopFileTransferHeaderHeight = 25.0;
+// the most bytes of a file read for a preview at a time, as capped by the reader
+const int kFileRangeMaxSize = 1024 * 1024;
 
 const double kMinFps = 5;
 const double kDefaultFps = 30;
@@ -236,6 +332,24 @@ extension StringExtension on String {
 
 const Size kConnectionManagerWindowSizeClosedChat = Size(300, 490);
 const Size kConnectionManagerWindowSizeOpenChat = Size(700, 490);
+
+// the custom properties of a chat message
+const String kChatMessageId = "id";
+const String kChatMessageStatus = "status";
+const String kChatMessageError = "error";
+const String kChatAttachmentPath = "path";
+const String kChatAttachmentImage = "image";
+const String kChatStatusSending = "sending";
+const String kChatStatusDelivered = "delivered";
+const String kChatStatusFailed = "failed";
+const List<String> kChatImageExts = [
+  "png",
+  "jpg",
+  "jpeg",
+  "gif",
+  "bmp",
+  "webp"
+];
 // Tabbar transition duration, now we remove the duration
 const Duration kTabTransitionDuration = Duration.zero;
 const double kEmptyMarginTop = 50;
@@ -246,6 +360,30 @@ const int kMinCursorSize = 12;
 
 /// [kDefaultScrollAmountMultiplier] indicates how many rows can be scrolled after a minimum scroll action of mouse
 const kDefaultScrollAmountMultiplier = 5.0;
+
+// the shapes of the drawings over the screen of the peer, as of the protocol
+const kAnnotationFreehand = 0;
+const kAnnotationArrow = 1;
+const kAnnotationBox = 2;
+const kAnnotationColors = [0xF44336, 0xFFEB3B, 0x4CAF50, 0x2196F3];
+const kAnnotationStrokeWidth = 4;
+const kAnnotationMaxPoints = 4096;
+// the orders of the processes of the peer, as of the protocol
+const kProcessSortCpu = 0;
+const kProcessSortMemory = 1;
+const kProcessSortName = 2;
+const kProcessSortPid = 3;
+const kProcessListLimit = 200;
+const kProcessListInterval = Duration(seconds: 3);
+// the controls of the services of the peer, as of the protocol
+const kServiceStart = 0;
+const kServiceStop = 1;
+const kServiceRestart = 2;
+// the stream of the camera of the peer, the quality in percent of the base bitrate
+const kCameraDefaultFps = 10;
+const kCameraFpsOptions = [1, 5, 10, 15, 30];
+const kCameraDefaultQuality = 50;
+const kCameraQualityOptions = [25, 50, 100];
 const kDefaultScrollDuration = Duration(milliseconds: 50);
 const kDefaultMouseWheelThrottleDuration = Duration(milliseconds: 50);
 const kFullScreenEdgeSize = 0.0;
@@ -1,6 +1,7 @@
 import 'dart:async';
 import 'dart:io';
 import 'dart:convert';
+import 'dart:math';
 
 import 'package:auto_size_text/auto_size_text.dart';
 import 'package:flutter/material.dart';
@@ -786,6 +787,9 @@ class _DesktopHomePageState extends State<DesktopHomePage>
           password: call.arguments['password'],
           forceRelay: call.arguments['forceRelay'],
           connToken: call.arguments['connToken'],
+          dropFiles: (call.arguments['dropFiles'] as List?)
+              ?.map((e) => e.toString())
+              .toList(),
         );
       } else if (call.method == kWindowEventMoveTabToNewWindow) {
         final args = call.arguments.split(',');
@@ -879,7 +883,7 @@ void setPasswordDialog({VoidCallback? notEmptyCallback}) async {
     UppercaseValidationRule(),
     LowercaseValidationRule(),
     // SpecialCharacterValidationRule(),
-    MinCharactersValidationRule(8),
+    MinCharactersValidationRule(max(8, bind.mainPasswordMinLength())),
   ];
   final maxLength = bind.mainMaxEncryptLen();
 
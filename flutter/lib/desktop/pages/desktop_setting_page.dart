@@ -420,6 +420,10 @@ class _GeneralState extends State<_General> {
             kOptionEnableConfirmClosingTabs,
             isServer: false),
       _OptionCheckBox(context, 'Adaptive bitrate', kOptionEnableAbr),
+      _OptionCheckBox(context, 'Compress file transfer',
+          kOptionEnableFileTransferCompression),
+      _OptionCheckBox(context, 'Log file transfers',
+          kOptionEnableFileTransferAuditLog),
       if (!isWeb) wallpaper(),
       if (!isWeb && !bind.isIncomingOnly()) ...[
         _OptionCheckBox(
@@ -546,7 +550,13 @@ class _GeneralState extends State<_General> {
           setState(() {});
         },
       ).marginOnly(left: _kContentHMargin);
-      return _Card(title: 'Audio Input Device', children: [child]);
+      return _Card(title: 'Audio Input Device', children: [
+        child,
+        _OptionCheckBox(context, 'voice_call_virtual_mic_tip',
+            kOptionAllowVoiceCallVirtualMic),
+        _OptionCheckBox(context, 'Suppress the echo in a voice call',
+            kOptionEnableVoiceCallEchoSuppression),
+      ]);
     }
 
     return AudioInput(builder: builder, isCm: false, isVoiceCall: false);
@@ -911,6 +921,18 @@ class _SafetyState extends State<_Safety> with AutomaticKeepAliveClientMixin {
             _OptionCheckBox(
                 context, 'Enable keyboard/mouse', kOptionEnableKeyboard,
                 enabled: enabled, fakeValue: fakeValue),
+            // the parts of keyboard/mouse
+            _OptionCheckBox(context, 'Enable key input', kOptionEnableKeyInput,
+                    enabled: enabled, fakeValue: fakeValue)
+                .marginOnly(left: 30),
+            _OptionCheckBox(
+                    context, 'Enable mouse input', kOptionEnableMouseInput,
+                    enabled: enabled, fakeValue: fakeValue)
+                .marginOnly(left: 30),
+            _OptionCheckBox(context, 'Enable clipboard typing',
+                    kOptionEnableClipboardTyping,
+                    enabled: enabled, fakeValue: fakeValue)
+                .marginOnly(left: 30),
             _OptionCheckBox(context, 'Enable clipboard', kOptionEnableClipboard,
                 enabled: enabled, fakeValue: fakeValue),
             _OptionCheckBox(
@@ -921,6 +943,36 @@ class _SafetyState extends State<_Safety> with AutomaticKeepAliveClientMixin {
             _OptionCheckBox(
                 context, 'Enable TCP tunneling', kOptionEnableTunnel,
                 enabled: enabled, fakeValue: fakeValue),
+            _OptionCheckBox(context, 'Enable terminal', kOptionAllowTerminal,
+                enabled: enabled, fakeValue: fakeValue),
+            _OptionCheckBox(
+                context, 'Enable Wake-on-LAN relay', kOptionAllowWolRelay,
+                enabled: enabled, fakeValue: fakeValue),
+            _OptionCheckBox(context, 'Let one peer control at a time',
+                kOptionAllowInputArbitration,
+                enabled: enabled, fakeValue: fakeValue),
+            _OptionCheckBox(
+                context, 'Enable drawing on screen', kOptionAllowAnnotation,
+                enabled: enabled, fakeValue: fakeValue),
+            _OptionCheckBox(
+                context, 'Enable system stats', kOptionAllowSystemStats,
+                enabled: enabled, fakeValue: fakeValue),
+            _OptionCheckBox(
+                context, 'Enable task manager', kOptionAllowTaskManager,
+                enabled: enabled, fakeValue: fakeValue),
+            _OptionCheckBox(context, 'Allow the peers to control the services',
+                kOptionAllowServiceManager,
+                enabled: enabled, fakeValue: fakeValue),
+            if (isWindows || isLinux)
+              _OptionCheckBox(context, 'Allow the peers to view the camera',
+                  kOptionAllowRemoteCamera,
+                  enabled: enabled, fakeValue: fakeValue),
+            _OptionCheckBox(
+                context,
+                'Allow the view-only peers to join the broadcast',
+                kOptionAllowBroadcast,
+                enabled: enabled,
+                fakeValue: fakeValue),
             _OptionCheckBox(
                 context, 'Enable remote restart', kOptionEnableRemoteRestart,
                 enabled: enabled, fakeValue: fakeValue),
@@ -1054,6 +1106,9 @@ class _SafetyState extends State<_Safety> with AutomaticKeepAliveClientMixin {
                     ],
                   ),
                   enabled: tmpEnabled && !locked),
+            if (usePassword)
+              _SubButton('One-time passwords', oneTimePasswordsDialog,
+                  tmpEnabled && !locked),
             if (usePassword) radios[1],
             if (usePassword)
               _SubButton('Set permanent password', setPasswordDialog,
@@ -1073,12 +1128,33 @@ class _SafetyState extends State<_Safety> with AutomaticKeepAliveClientMixin {
           reverse: true, enabled: enabled),
       ...directIp(context),
       whitelist(),
+      ipRules(),
+      ...unattendedSchedule(context),
       ...autoDisconnect(context),
+      _OptionCheckBox(
+          context, 'Require access reason', kOptionRequireAccessReason,
+          enabled: enabled),
+      ...approveTimeout(context),
+      _OptionCheckBox(context, 'Trust device keys on first use',
+          kOptionTrustKeysOnFirstUse,
+          enabled: enabled),
+      _OptionCheckBox(
+          context, 'Reject untrusted device keys', kOptionRejectUntrustedKeys,
+          enabled: enabled),
+      _SubButton('Trusted device keys', trustedKeysDialog, enabled),
+      _SubButton('Notifications', notificationsDialog, enabled),
+      _OptionCheckBox(
+          context, 'Watermark the shared screen', kOptionAllowWatermark,
+          enabled: enabled),
+      if (isWindows) ...autoBlockInput(context),
+      ...auditLog(context),
       if (bind.mainIsInstalled())
         _OptionCheckBox(context, 'allow-only-conn-window-open-tip',
             'allow-only-conn-window-open',
             reverse: false, enabled: enabled),
-      if (bind.mainIsInstalled()) unlockPin()
+      if (bind.mainIsInstalled()) unlockPin(),
+      if (bind.mainIsInstalled())
+        _SubButton('Rotate key', rotateKeyDialog, enabled),
     ]);
   }
 
@@ -1172,6 +1248,57 @@ class _SafetyState extends State<_Safety> with AutomaticKeepAliveClientMixin {
 
         return tmpWrapper();
       }(),
+      () {
+        TextEditingController keyController = TextEditingController();
+        RxBool keyApplyEnabled = false.obs;
+        bool enabled = option2bool(kOptionDirectServer,
+            bind.mainGetOptionSync(key: kOptionDirectServer));
+        keyController.text =
+            bind.mainGetOptionSync(key: kOptionDirectAccessKnockKey);
+        final isOptFixed = isOptionFixed(kOptionDirectAccessKnockKey);
+        return Offstage(
+          offstage: !enabled,
+          child: Tooltip(
+            message: translate('direct_access_knock_tip'),
+            child: _SubLabeledWidget(
+              context,
+              'Knock key',
+              Row(children: [
+                SizedBox(
+                  width: 200,
+                  child: TextField(
+                    controller: keyController,
+                    obscureText: true,
+                    enabled: enabled && !locked && !isOptFixed,
+                    onChanged: (_) => keyApplyEnabled.value = true,
+                    decoration: const InputDecoration(
+                      contentPadding:
+                          EdgeInsets.symmetric(vertical: 12, horizontal: 12),
+                    ),
+                  ).marginOnly(right: 15),
+                ),
+                Obx(() => ElevatedButton(
+                      onPressed: keyApplyEnabled.value &&
+                              enabled &&
+                              !locked &&
+                              !isOptFixed
+                          ? () async {
+                              keyApplyEnabled.value = false;
+                              await bind.mainSetOption(
+                                  key: kOptionDirectAccessKnockKey,
+                                  value: keyController.text);
+                            }
+                          : null,
+                      child: Text(
+                        translate('Apply'),
+                      ),
+                    ))
+              ]),
+              enabled: enabled && !locked && !isOptFixed,
+            ),
+          ),
+        );
+      }(),
     ];
   }
 
@@ -1227,6 +1354,96 @@ class _SafetyState extends State<_Safety> with AutomaticKeepAliveClientMixin {
     return tmpWrapper();
   }
 
+  Widget ipRules() {
+    bool enabled = !locked;
+    RxBool hasRules =
+        bind.mainGetOptionSync(key: kOptionIpRules).trim().isNotEmpty.obs;
+    update() async {
+      hasRules.value =
+          bind.mainGetOptionSync(key: kOptionIpRules).trim().isNotEmpty;
+    }
+
+    onChanged(bool? checked) async {
+      changeIpRules(callback: update);
+    }
+
+    final isOptFixed = isOptionFixed(kOptionIpRules);
+    return GestureDetector(
+      child: Tooltip(
+        message: translate('ip_rules_tip'),
+        child: Obx(() => Row(
+              children: [
+                Checkbox(
+                        value: hasRules.value,
+                        onChanged: enabled && !isOptFixed ? onChanged : null)
+                    .marginOnly(right: 5),
+                Expanded(
+                    child: Text(
+                  translate('Use IP rules'),
+                  style: TextStyle(color: disabledTextColor(context, enabled)),
+                ))
+              ],
+            )),
+      ),
+      onTap: enabled && !isOptFixed
+          ? () {
+              onChanged(!hasRules.value);
+            }
+          : null,
+    ).marginOnly(left: _kCheckBoxLeftMargin);
+  }
+
+  List<Widget> unattendedSchedule(BuildContext context) {
+    bool enabled = !locked;
+    RxBool hasSchedule = bind
+        .mainGetOptionSync(key: kOptionUnattendedSchedule)
+        .trim()
+        .isNotEmpty
+        .obs;
+    update() async {
+      hasSchedule.value = bind
+          .mainGetOptionSync(key: kOptionUnattendedSchedule)
+          .trim()
+          .isNotEmpty;
+    }
+
+    onChanged(bool? checked) async {
+      changeUnattendedSchedule(callback: update);
+    }
+
+    final isOptFixed = isOptionFixed(kOptionUnattendedSchedule);
+    return [
+      GestureDetector(
+        child: Tooltip(
+          message: translate('unattended_schedule_tip'),
+          child: Obx(() => Row(
+                children: [
+                  Checkbox(
+                          value: hasSchedule.value,
+                          onChanged: enabled && !isOptFixed ? onChanged : null)
+                      .marginOnly(right: 5),
+                  Expanded(
+                      child: Text(
+                    translate('Schedule the unattended access'),
+                    style:
+                        TextStyle(color: disabledTextColor(context, enabled)),
+                  ))
+                ],
+              )),
+        ),
+        onTap: enabled && !isOptFixed
+            ? () {
+                onChanged(!hasSchedule.value);
+              }
+            : null,
+      ).marginOnly(left: _kCheckBoxLeftMargin),
+      Obx(() => _OptionCheckBox(context, 'Refuse outside the schedule',
+              kOptionRefuseOutsideSchedule,
+              enabled: enabled && hasSchedule.value)
+          .marginOnly(left: 30)),
+    ];
+  }
+
   Widget hide_cm(bool enabled) {
     return ChangeNotifierProvider.value(
         value: gFFI.serverModel,
@@ -1267,26 +1484,67 @@ class _SafetyState extends State<_Safety> with AutomaticKeepAliveClientMixin {
         }));
   }
 
-  List<Widget> autoDisconnect(BuildContext context) {
+  List<Widget> autoDisconnect(BuildContext context) => [
+        ..._timeoutOption(context, 'auto_disconnect_option_tip',
+            kOptionAllowAutoDisconnect, kOptionAutoDisconnectTimeout,
+            'Timeout in minutes', '10'),
+        if (option2bool(kOptionAllowAutoDisconnect,
+            bind.mainGetOptionSync(key: kOptionAllowAutoDisconnect)))
+          _OptionCheckBox(context,
+                  'Take the control away instead of disconnecting',
+                  kOptionAutoDisconnectAction,
+                  enabled: !locked,
+                  optGetter: () =>
+                      bind.mainGetOptionSync(
+                          key: kOptionAutoDisconnectAction) ==
+                      'view-only',
+                  optSetter: (key, v) => bind.mainSetOption(
+                      key: key, value: v ? 'view-only' : ''))
+              .marginOnly(left: _kContentHSubMargin - 6),
+      ];
+
+  List<Widget> approveTimeout(BuildContext context) => _timeoutOption(
+      context,
+      'approve_timeout_tip',
+      kOptionAllowApproveTimeout,
+      kOptionApproveTimeout,
+      'Timeout in seconds',
+      '60');
+
+  List<Widget> autoBlockInput(BuildContext context) => _timeoutOption(
+      context,
+      'auto_block_input_tip',
+      kOptionAllowAutoBlockInput,
+      kOptionAutoBlockInputTimeout,
+      'Timeout in seconds',
+      '3');
+
+  List<Widget> auditLog(BuildContext context) => _timeoutOption(
+      context,
+      'audit_log_tip',
+      kOptionEnableAuditLog,
+      kOptionAuditLogRetentionDays,
+      'Retention in days',
+      '90');
+
+  // A checkbox, and the timeout of it when it is checked.
+  List<Widget> _timeoutOption(BuildContext context, String label,
+      String option, String timeoutOption, String timeoutLabel, String hint) {
     TextEditingController controller = TextEditingController();
     update(bool v) => setState(() {});
     RxBool applyEnabled = false.obs;
     return [
-      _OptionCheckBox(
-          context, 'auto_disconnect_option_tip', kOptionAllowAutoDisconnect,
-          update: update, enabled: !locked),
+      _OptionCheckBox(context, label, option, update: update, enabled: !locked),
       () {
-        bool enabled = option2bool(kOptionAllowAutoDisconnect,
-            bind.mainGetOptionSync(key: kOptionAllowAutoDisconnect));
+        bool enabled = option2bool(option, bind.mainGetOptionSync(key: option));
         if (!enabled) applyEnabled.value = false;
-        controller.text =
-            bind.mainGetOptionSync(key: kOptionAutoDisconnectTimeout);
-        final isOptFixed = isOptionFixed(kOptionAutoDisconnectTimeout);
+        controller.text = bind.mainGetOptionSync(key: timeoutOption);
+        final isOptFixed = isOptionFixed(timeoutOption);
         return Offstage(
           offstage: !enabled,
           child: _SubLabeledWidget(
             context,
-            'Timeout in minutes',
+            timeoutLabel,
             Row(children: [
               SizedBox(
                 width: 95,
@@ -1298,10 +1556,10 @@ class _SafetyState extends State<_Safety> with AutomaticKeepAliveClientMixin {
                     FilteringTextInputFormatter.allow(RegExp(
                         r'^([0-9]|[1-9]\d|[1-9]\d{2}|[1-9]\d{3}|[1-5]\d{4}|6[0-4]\d{3}|65[0-4]\d{2}|655[0-2]\d|6553[0-5])$')),
                   ],
-                  decoration: const InputDecoration(
-                    hintText: '10',
-                    contentPadding:
-                        EdgeInsets.symmetric(vertical: 12, horizontal: 12),
+                  decoration: InputDecoration(
+                    hintText: hint,
+                    contentPadding: const EdgeInsets.symmetric(
+                        vertical: 12, horizontal: 12),
                   ),
                 ).marginOnly(right: 15),
               ),
@@ -1311,8 +1569,7 @@ class _SafetyState extends State<_Safety> with AutomaticKeepAliveClientMixin {
                             ? () async {
                                 applyEnabled.value = false;
                                 await bind.mainSetOption(
-                                    key: kOptionAutoDisconnectTimeout,
-                                    value: controller.text);
+                                    key: timeoutOption, value: controller.text);
                               }
                             : null,
                     child: Text(
@@ -1335,7 +1592,7 @@ class _SafetyState extends State<_Safety> with AutomaticKeepAliveClientMixin {
     }
 
     onChanged(bool? checked) async {
-      changeUnlockPinDialog(unlockPin.value, update);
+      changeUnlockPinDialog(update);
     }
 
     final isOptFixed = isOptionFixed(kOptionWhitelist);
@@ -1402,12 +1659,53 @@ class _NetworkState extends State<_Network> with AutomaticKeepAliveClientMixin {
                     _Card(title: 'Proxy', children: [
                       _Button('Socks5/Http(s) Proxy', changeSocks5Proxy,
                           enabled: enabled),
+                      _Button('Network profiles', changeNetworkProfiles,
+                          enabled: enabled),
+                      _Button('TURN server', changeTurnServer,
+                          enabled: enabled),
                     ]),
+                  ipFamily(enabled),
+                  _Card(title: 'QUIC', children: [
+                    _OptionCheckBox(
+                        context, 'Use QUIC for direct IP access', kOptionAllowQuic,
+                        enabled: enabled),
+                  ]),
+                  _Card(title: 'Connection', children: [
+                    _Button('Keep alive', changeKeepAlive, enabled: enabled),
+                    _Button('DSCP marking', changeDscp, enabled: enabled),
+                    _Button('TLS pins', changeTlsPins, enabled: enabled),
+                    Tooltip(
+                      message: translate('port_mapping_tip'),
+                      child: _OptionCheckBox(context, 'Map ports on the router',
+                          kOptionAllowPortMapping,
+                          enabled: enabled),
+                    ),
+                  ]),
                 ]),
               ),
             ]).marginOnly(bottom: _kListViewBottomMargin));
   }
 
+  Widget ipFamily(bool enabled) {
+    final current = bind.mainGetOptionSync(key: kOptionPreferredIpFamily);
+    final isOptFixed = isOptionFixed(kOptionPreferredIpFamily);
+    onChanged(String value) async {
+      await bind.mainSetOption(key: kOptionPreferredIpFamily, value: value);
+      setState(() {});
+    }
+
+    radio(String value, String label) => _Radio<String>(context,
+        value: value,
+        groupValue: current,
+        label: label,
+        onChanged: enabled && !isOptFixed ? onChanged : null);
+    return _Card(title: 'Preferred IP version', children: [
+      radio('', 'Follow System'),
+      radio('ipv4', 'IPv4'),
+      radio('ipv6', 'IPv6'),
+    ]);
+  }
+
   server(bool enabled) {
     // Simple temp wrapper for PR check
     tmpWrapper() {
@@ -1726,6 +2024,8 @@ class _Account extends StatefulWidget {
 }
 
 class _AccountState extends State<_Account> {
+  final RxString idpSession = bind.mainIdpGetSession().obs;
+
   @override
   Widget build(BuildContext context) {
     final scrollController = ScrollController();
@@ -1735,7 +2035,14 @@ class _AccountState extends State<_Account> {
           physics: DraggableNeverScrollableScrollPhysics(),
           controller: scrollController,
           children: [
-            _Card(title: 'Account', children: [accountAction(), useInfo()]),
+            _Card(title: 'Account', children: [
+              accountAction(),
+              useInfo(),
+              _OptionCheckBox(context, 'Sync the view settings of the peers',
+                  kOptionSyncViewSettings,
+                  isServer: false),
+            ]),
+            _Card(title: 'Identity provider', children: [identityProvider()]),
           ],
         ).marginOnly(bottom: _kListViewBottomMargin));
   }
@@ -1750,6 +2057,41 @@ class _AccountState extends State<_Account> {
             }));
   }
 
+  Widget identityProvider() {
+    update() => idpSession.value = bind.mainIdpGetSession();
+    return Obx(() {
+      Map<String, dynamic> session = {};
+      try {
+        session = jsonDecode(idpSession.value);
+      } catch (_) {}
+      final signedIn = session['name'] != null;
+      return Column(
+        crossAxisAlignment: CrossAxisAlignment.start,
+        children: [
+          if (signedIn)
+            Align(
+              alignment: Alignment.centerLeft,
+              child: SelectionArea(
+                  child: Text(
+                      '${translate('Username')}: ${session['name']} (${session['provider'].toString().toUpperCase()})')),
+            ).marginOnly(left: 18, top: 8, bottom: 4),
+          if (signedIn)
+            _Button('Logout', () {
+              bind.mainIdpSignOut();
+              update();
+            }),
+          if (!signedIn)
+            _Button('Sign in with OIDC',
+                () => oidcSignInDialog(callback: update)),
+          if (!signedIn)
+            _Button('Sign in with LDAP',
+                () => ldapSignInDialog(callback: update)),
+          _Button('Settings', changeIdentityProvider),
+        ],
+      );
+    });
+  }
+
   Widget useInfo() {
     text(String key, String value) {
       return Align(
@@ -1884,17 +2226,21 @@ class _AboutState extends State<_About> {
       final version = await bind.mainGetVersion();
       final buildDate = await bind.mainGetBuildDate();
       final fingerprint = await bind.mainGetFingerprint();
+      final oldFingerprint = await bind.mainGetOldFingerprint();
       return {
         'license': license,
         'version': version,
         'buildDate': buildDate,
-        'fingerprint': fingerprint
+        'fingerprint': fingerprint,
+        'oldFingerprint': oldFingerprint
       };
     }(), hasData: (data) {
       final license = data['license'].toString();
       final version = data['version'].toString();
       final buildDate = data['buildDate'].toString();
       final fingerprint = data['fingerprint'].toString();
+      final sas = bind.mainFingerprintToSas(fingerprint: fingerprint);
+      final oldFingerprint = data['oldFingerprint'].toString();
       const linkStyle = TextStyle(decoration: TextDecoration.underline);
       final scrollController = ScrollController();
       return DesktopScrollWrapper(
@@ -1919,6 +2265,16 @@ class _AboutState extends State<_About> {
                     SelectionArea(
                         child: Text('${translate('Fingerprint')}: $fingerprint')
                             .marginSymmetric(vertical: 4.0)),
+                  if (!isWeb && sas.isNotEmpty)
+                    SelectionArea(
+                        child: Text(
+                                '${translate('Short authentication string')}: $sas')
+                            .marginSymmetric(vertical: 4.0)),
+                  if (!isWeb && oldFingerprint.isNotEmpty)
+                    SelectionArea(
+                        child: Text(
+                                '${translate('Old fingerprint')}: $oldFingerprint')
+                            .marginSymmetric(vertical: 4.0)),
                   // InkWell(
                   //     onTap: () {
                   //       launchUrlString('https://rustdesk.com/privacy.html');
@@ -2265,7 +2621,7 @@ Widget _lock(
                         onUnlock();
                       }
                     } else {
-                      checkUnlockPinDialog(unlockPin, onUnlock);
+                      checkUnlockPinDialog(onUnlock);
                     }
                   },
                 ).marginSymmetric(horizontal: 2, vertical: 4),
@@ -2546,4 +2902,511 @@ void changeSocks5Proxy() async {
   });
 }
 
+// The proxies used instead of the one above on the networks given, by CIDRs, e.g. the office.
+void changeNetworkProfiles() async {
+  final List<List<TextEditingController>> profiles = [];
+  add(Map<String, dynamic> profile) {
+    final socks = profile['socks'] ?? {};
+    profiles.add([
+      TextEditingController(text: profile['name'] ?? ''),
+      TextEditingController(text: profile['networks'] ?? ''),
+      TextEditingController(text: socks['proxy'] ?? ''),
+      TextEditingController(text: socks['username'] ?? ''),
+      TextEditingController(text: socks['password'] ?? ''),
+    ]);
+  }
+
+  try {
+    for (var profile in jsonDecode(await bind.mainGetNetworkProfiles())) {
+      add(profile);
+    }
+  } catch (e) {
+    debugPrint('Failed to load the network profiles: $e');
+  }
+  final isOptFixed = isOptionFixed("proxy-url");
+
+  gFFI.dialogManager.show((setState, close, context) {
+    submit() async {
+      final value = profiles.map((p) {
+        final proxy = p[2].text.trim();
+        return {
+          'name': p[0].text.trim(),
+          'networks': p[1].text.trim(),
+          'socks': proxy.isEmpty
+              ? null
+              : {
+                  'proxy': proxy,
+                  'username': p[3].text.trim(),
+                  'password': p[4].text.trim(),
+                },
+        };
+      }).toList();
+      await bind.mainSetNetworkProfiles(profiles: jsonEncode(value));
+      close();
+    }
+
+    field(TextEditingController controller, String label,
+            {String? hint, bool obscure = false}) =>
+        Expanded(
+          child: TextField(
+            controller: controller,
+            obscureText: obscure,
+            enabled: !isOptFixed,
+            decoration: InputDecoration(
+                labelText: translate(label),
+                hintText: hint == null ? null : translate(hint)),
+          ).marginOnly(right: 8),
+        );
+
+    return CustomAlertDialog(
+      title: Text(translate('Network profiles')),
+      content: ConstrainedBox(
+        constraints: const BoxConstraints(minWidth: 600),
+        child: Column(
+          crossAxisAlignment: CrossAxisAlignment.start,
+          children: [
+            Text(translate('network_profiles_tip')).marginOnly(bottom: 8),
+            ...profiles.map((p) => Column(children: [
+                  Row(children: [
+                    field(p[0], 'Name'),
+                    field(p[1], 'Networks', hint: 'network_profile_cidr_tip'),
+                    IconButton(
+                      icon: const Icon(Icons.delete_outline),
+                      onPressed: isOptFixed
+                          ? null
+                          : () => setState(() => profiles.remove(p)),
+                    ),
+                  ]),
+                  Row(children: [
+                    field(p[2], 'Server', hint: 'network_profile_direct_tip'),
+                    field(p[3], 'Username'),
+                    field(p[4], 'Password', obscure: true),
+                  ]).marginOnly(bottom: 12),
+                ])),
+            if (!isOptFixed)
+              TextButton.icon(
+                icon: const Icon(Icons.add),
+                label: Text(translate('Add')),
+                onPressed: () => setState(() => add({})),
+              ),
+          ],
+        ),
+      ),
+      actions: [
+        dialogButton('Cancel', onPressed: close, isOutline: true),
+        if (!isOptFixed) dialogButton('OK', onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: close,
+    );
+  });
+}
+
+// The TURN server the sessions are relayed through instead of the relay server.
+void changeTurnServer() async {
+  final serverController = TextEditingController(
+      text: bind.mainGetOptionSync(key: kOptionTurnServer));
+  final userController = TextEditingController(
+      text: bind.mainGetOptionSync(key: kOptionTurnUsername));
+  final pwdController = TextEditingController(
+      text: bind.mainGetOptionSync(key: kOptionTurnPassword));
+  RxBool obscure = true.obs;
+  final isOptFixed = isOptionFixed(kOptionTurnServer);
+
+  gFFI.dialogManager.show((setState, close, context) {
+    submit() async {
+      await bind.mainSetOption(
+          key: kOptionTurnServer, value: serverController.text.trim());
+      await bind.mainSetOption(
+          key: kOptionTurnUsername, value: userController.text.trim());
+      await bind.mainSetOption(
+          key: kOptionTurnPassword, value: pwdController.text.trim());
+      close();
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('TURN server')),
+      content: ConstrainedBox(
+        constraints: const BoxConstraints(minWidth: 500),
+        child: Column(
+          crossAxisAlignment: CrossAxisAlignment.start,
+          children: [
+            TextField(
+              controller: serverController,
+              autofocus: true,
+              enabled: !isOptFixed,
+              decoration: InputDecoration(
+                labelText: translate('Server'),
+                hintText: 'turn:turn.example.com:3478',
+                helperText: translate('turn_server_tip'),
+                helperMaxLines: 3,
+              ),
+            ).marginOnly(bottom: 8),
+            TextField(
+              controller: userController,
+              enabled: !isOptFixed,
+              decoration: InputDecoration(labelText: translate('Username')),
+            ).marginOnly(bottom: 8),
+            Obx(() => TextField(
+                  controller: pwdController,
+                  obscureText: obscure.value,
+                  enabled: !isOptFixed,
+                  decoration: InputDecoration(
+                      labelText: translate('Password'),
+                      suffixIcon: IconButton(
+                          onPressed: () => obscure.value = !obscure.value,
+                          icon: Icon(obscure.value
+                              ? Icons.visibility_off
+                              : Icons.visibility))),
+                )),
+          ],
+        ),
+      ),
+      actions: [
+        dialogButton('Cancel', onPressed: close, isOutline: true),
+        if (!isOptFixed) dialogButton('OK', onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: close,
+    );
+  });
+}
+
+void changeKeepAlive() async {
+  final options = [
+    (kOptionKeepAliveInterval, 'keep_alive_interval_tip', '1'),
+    (kOptionKeepAliveRetries, 'keep_alive_retries_tip', '3'),
+    (kOptionKeepAliveTimeout, 'keep_alive_timeout_tip', '30'),
+  ];
+  final controllers = options
+      .map((e) =>
+          TextEditingController(text: bind.mainGetOptionSync(key: e.$1)))
+      .toList();
+  final isOptFixed = options.any((e) => isOptionFixed(e.$1));
+
+  gFFI.dialogManager.show((setState, close, context) {
+    submit() async {
+      for (var i = 0; i < options.length; i++) {
+        final text = controllers[i].text.trim();
+        // empty for the default
+        final value = int.tryParse(text);
+        await bind.mainSetOption(
+            key: options[i].$1,
+            value: value != null && value > 0 ? value.toString() : '');
+      }
+      close();
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Keep alive')),
+      content: ConstrainedBox(
+        constraints: const BoxConstraints(minWidth: 500),
+        child: Column(
+          crossAxisAlignment: CrossAxisAlignment.start,
+          children: [
+            for (var i = 0; i < options.length; i++)
+              TextField(
+                controller: controllers[i],
+                autofocus: i == 0,
+                enabled: !isOptFixed,
+                keyboardType: TextInputType.number,
+                inputFormatters: [FilteringTextInputFormatter.digitsOnly],
+                decoration: InputDecoration(
+                  labelText: translate(options[i].$2),
+                  hintText: options[i].$3,
+                ),
+              ).marginOnly(bottom: 8),
+          ],
+        ),
+      ),
+      actions: [
+        dialogButton('Cancel', onPressed: close, isOutline: true),
+        if (!isOptFixed) dialogButton('OK', onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: close,
+    );
+  });
+}
+
+void changeDscp() async {
+  final options = [
+    (kOptionDscpInteractive, 'dscp_interactive_tip', 'EF'),
+    (kOptionDscpFileTransfer, 'dscp_file_transfer_tip', 'CS1'),
+  ];
+  final controllers = options
+      .map((e) =>
+          TextEditingController(text: bind.mainGetOptionSync(key: e.$1)))
+      .toList();
+  final isOptFixed = options.any((e) => isOptionFixed(e.$1));
+
+  gFFI.dialogManager.show((setState, close, context) {
+    submit() async {
+      for (var i = 0; i < options.length; i++) {
+        await bind.mainSetOption(
+            key: options[i].$1, value: controllers[i].text.trim());
+      }
+      close();
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('DSCP marking')),
+      content: ConstrainedBox(
+        constraints: const BoxConstraints(minWidth: 500),
+        child: Column(
+          crossAxisAlignment: CrossAxisAlignment.start,
+          children: [
+            for (var i = 0; i < options.length; i++)
+              TextField(
+                controller: controllers[i],
+                autofocus: i == 0,
+                enabled: !isOptFixed,
+                decoration: InputDecoration(
+                  labelText: translate(options[i].$2),
+                  hintText: options[i].$3,
+                ),
+              ).marginOnly(bottom: 8),
+          ],
+        ),
+      ),
+      actions: [
+        dialogButton('Cancel', onPressed: close, isOutline: true),
+        if (!isOptFixed) dialogButton('OK', onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: close,
+    );
+  });
+}
+
+void changeTlsPins() async {
+  final controller =
+      TextEditingController(text: bind.mainGetOptionSync(key: kOptionTlsPins));
+  final isOptFixed = isOptionFixed(kOptionTlsPins);
+  var msg = '';
+
+  gFFI.dialogManager.show((setState, close, context) {
+    submit() async {
+      final pins = controller.text.trim();
+      final err = bind.mainCheckTlsPins(pins: pins);
+      if (err.isNotEmpty) {
+        setState(() => msg = err);
+        return;
+      }
+      await bind.mainSetOption(key: kOptionTlsPins, value: pins);
+      close();
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('TLS pins')),
+      content: ConstrainedBox(
+        constraints: const BoxConstraints(minWidth: 500),
+        child: Column(
+          crossAxisAlignment: CrossAxisAlignment.start,
+          children: [
+            Text(translate('tls_pins_tip')),
+            TextField(
+              controller: controller,
+              autofocus: true,
+              enabled: !isOptFixed,
+              maxLines: null,
+              decoration: InputDecoration(
+                hintText: 'sha256/...',
+                errorText: msg.isEmpty ? null : msg,
+                errorMaxLines: 3,
+              ),
+            ).marginOnly(top: 8),
+          ],
+        ),
+      ),
+      actions: [
+        dialogButton('Cancel', onPressed: close, isOutline: true),
+        if (!isOptFixed) dialogButton('OK', onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: close,
+    );
+  });
+}
+
+void oidcSignInDialog({Function()? callback}) async {
+  bind.mainIdpOidcAuth();
+  var result = <String, dynamic>{};
+  Timer? timer;
+
+  gFFI.dialogManager.show((setState, close, context) {
+    cancel() {
+      timer?.cancel();
+      bind.mainIdpOidcAuthCancel();
+      close();
+    }
+
+    timer ??= Timer.periodic(const Duration(seconds: 1), (_) async {
+      try {
+        result = jsonDecode(await bind.mainIdpOidcAuthResult());
+      } catch (_) {}
+      if ((result['name'] ?? '').isNotEmpty) {
+        timer?.cancel();
+        close();
+        callback?.call();
+      } else {
+        setState(() {});
+      }
+    });
+
+    final String url = result['url'] ?? '';
+    final String userCode = result['user_code'] ?? '';
+    final String failed = result['failed_msg'] ?? '';
+    return CustomAlertDialog(
+      title: Text(translate('Sign in with OIDC')),
+      content: ConstrainedBox(
+        constraints: const BoxConstraints(minWidth: 400),
+        child: Column(
+          crossAxisAlignment: CrossAxisAlignment.start,
+          children: [
+            if (failed.isNotEmpty)
+              Text(translate(failed), style: TextStyle(color: Colors.red))
+            else if (url.isEmpty)
+              const LinearProgressIndicator()
+            else ...[
+              Text(translate('oidc_device_code_tip')),
+              SelectionArea(
+                  child: Text(userCode,
+                      style: const TextStyle(
+                          fontSize: 24, fontWeight: FontWeight.bold))),
+              InkWell(
+                onTap: () => launchUrlString(url),
+                child: Text(url,
+                    style: const TextStyle(
+                        decoration: TextDecoration.underline)),
+              ),
+            ],
+          ],
+        ),
+      ),
+      actions: [
+        dialogButton('Cancel', onPressed: cancel, isOutline: true),
+      ],
+      onCancel: cancel,
+    );
+  });
+}
+
+void ldapSignInDialog({Function()? callback}) async {
+  final userController = TextEditingController();
+  final passwordController = TextEditingController();
+  var msg = '';
+  var isInProgress = false;
+
+  gFFI.dialogManager.show((setState, close, context) {
+    submit() async {
+      setState(() {
+        msg = '';
+        isInProgress = true;
+      });
+      final err = await bind.mainIdpLdapSignIn(
+          username: userController.text.trim(),
+          password: passwordController.text);
+      if (err.isNotEmpty) {
+        setState(() {
+          msg = err;
+          isInProgress = false;
+        });
+        return;
+      }
+      close();
+      callback?.call();
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Sign in with LDAP')),
+      content: ConstrainedBox(
+        constraints: const BoxConstraints(minWidth: 400),
+        child: Column(
+          children: [
+            DialogTextField(
+              title: translate(DialogTextField.kUsernameTitle),
+              controller: userController,
+              prefixIcon: DialogTextField.kUsernameIcon,
+              errorText: msg.isEmpty ? null : msg,
+            ),
+            PasswordWidget(controller: passwordController, autoFocus: false),
+            if (isInProgress) const LinearProgressIndicator(),
+          ],
+        ),
+      ),
+      actions: [
+        dialogButton('Cancel', onPressed: close, isOutline: true),
+        dialogButton('OK', onPressed: isInProgress ? null : submit),
+      ],
+      onSubmit: submit,
+      onCancel: close,
+    );
+  });
+}
+
+void changeIdentityProvider() async {
+  final fields = [
+    ['OIDC issuer', kOptionOidcIssuer],
+    ['OIDC client ID', kOptionOidcClientId],
+    ['Required group', kOptionIdentityGroup],
+  ];
+  final controllers = fields
+      .map((f) =>
+          TextEditingController(text: bind.mainGetOptionSync(key: f[1])))
+      .toList();
+  var provider = bind.mainGetOptionSync(key: kOptionIdentityProvider);
+  if (!['', 'oidc', 'ldap'].contains(provider)) provider = '';
+
+  gFFI.dialogManager.show((setState, close, context) {
+    submit() async {
+      for (var i = 0; i < fields.length; i++) {
+        if (!isOptionFixed(fields[i][1])) {
+          await bind.mainSetOption(
+              key: fields[i][1], value: controllers[i].text.trim());
+        }
+      }
+      if (!isOptionFixed(kOptionIdentityProvider)) {
+        await bind.mainSetOption(
+            key: kOptionIdentityProvider, value: provider);
+      }
+      close();
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Identity provider')),
+      content: ConstrainedBox(
+        constraints: const BoxConstraints(minWidth: 500),
+        child: Column(
+          crossAxisAlignment: CrossAxisAlignment.start,
+          children: [
+            for (var i = 0; i < fields.length; i++)
+              TextField(
+                controller: controllers[i],
+                enabled: !isOptionFixed(fields[i][1]),
+                decoration: InputDecoration(
+                  labelText: translate(fields[i][0]),
+                ),
+              ),
+            Text(translate('identity_provider_tip')).marginOnly(top: 12),
+            ComboBox(
+              keys: const ['', 'oidc', 'ldap'],
+              values: [translate('None'), 'OIDC', 'LDAP'],
+              initialKey: provider,
+              enabled: !isOptionFixed(kOptionIdentityProvider),
+              onChanged: (key) => provider = key,
+            ),
+          ],
+        ),
+      ),
+      actions: [
+        dialogButton('Cancel', onPressed: close, isOutline: true),
+        dialogButton('OK', onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: close,
+    );
+  });
+}
+
 //#endregion
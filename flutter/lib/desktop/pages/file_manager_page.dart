@@ -1,4 +1,5 @@
 import 'dart:async';
+import 'dart:convert';
 import 'dart:io';
 import 'dart:math';
 
@@ -23,6 +24,7 @@ import 'package:flutter_hbb/web/dummy.dart'
 import '../../consts.dart';
 import '../../desktop/widgets/material_mod_popup_menu.dart' as mod_menu;
 import '../../common.dart';
+import '../../common/widgets/dialog.dart';
 import '../../models/model.dart';
 import '../../models/platform_model.dart';
 import '../widgets/popup_menu.dart';
@@ -59,7 +61,8 @@ class FileManagerPage extends StatefulWidget {
       required this.isSharedPassword,
       this.tabController,
       this.connToken,
-      this.forceRelay})
+      this.forceRelay,
+      this.dropFiles})
       : super(key: key);
   final String id;
   final String? password;
@@ -67,6 +70,8 @@ class FileManagerPage extends StatefulWidget {
   final bool? forceRelay;
   final String? connToken;
   final DesktopTabController? tabController;
+  // the local files dropped on the remote desktop window, uploaded to the remote desktop folder
+  final List<String>? dropFiles;
 
   @override
   State<StatefulWidget> createState() => _FileManagerPageState();
@@ -112,6 +117,10 @@ class _FileManagerPageState extends State<FileManagerPage>
       widget.tabController?.onSelected?.call(widget.id);
     });
     WidgetsBinding.instance.addObserver(this);
+    if (widget.dropFiles?.isNotEmpty == true) {
+      model.uploadDroppedFiles(localEntries(widget.dropFiles!),
+          toDesktop: true);
+    }
   }
 
   @override
@@ -248,7 +257,11 @@ class _FileManagerPageState extends State<FileManagerPage>
                               ),
                               Tooltip(
                                 waitDuration: Duration(milliseconds: 500),
-                                message: status,
+                                message: [
+                                  status,
+                                  ...item.digests.entries
+                                      .map((e) => 'SHA-256 ${e.key}: ${e.value}')
+                                ].join('\n'),
                                 child: Text(status,
                                     style: TextStyle(
                                       fontSize: 12,
@@ -276,6 +289,50 @@ class _FileManagerPageState extends State<FileManagerPage>
                         Row(
                           mainAxisAlignment: MainAxisAlignment.end,
                           children: [
+                            Offstage(
+                              offstage: item.type != JobType.transfer ||
+                                  (item.state != JobState.inProgress &&
+                                      item.state != JobState.paused),
+                              child: MenuButton(
+                                tooltip:
+                                    '${translate("Speed limit")}: ${item.rateLimit > 0 ? '${item.rateLimit} kbps' : translate("Unlimited")}',
+                                onPressed: () => fileTransferRateLimitDialog(
+                                    _ffi.dialogManager,
+                                    item.rateLimit,
+                                    (kbps) =>
+                                        jobController.setRateLimit(item.id, kbps)),
+                                child: Icon(Icons.speed, color: Colors.white),
+                                color: MyTheme.accent,
+                                hoverColor: MyTheme.accent80,
+                              ),
+                            ),
+                            Offstage(
+                              offstage: item.type != JobType.transfer ||
+                                  index == 0 ||
+                                  item.state == JobState.done ||
+                                  item.state == JobState.error,
+                              child: MenuButton(
+                                tooltip: translate("Move up"),
+                                onPressed: () =>
+                                    jobController.moveJobUp(item.id),
+                                child: Icon(Icons.arrow_upward,
+                                    color: Colors.white),
+                                color: MyTheme.accent,
+                                hoverColor: MyTheme.accent80,
+                              ),
+                            ),
+                            Offstage(
+                              offstage: item.type != JobType.transfer ||
+                                  item.state != JobState.inProgress,
+                              child: MenuButton(
+                                tooltip: translate("Pause"),
+                                onPressed: () =>
+                                    jobController.pauseJob(item.id),
+                                child: Icon(Icons.pause, color: Colors.white),
+                                color: MyTheme.accent,
+                                hoverColor: MyTheme.accent80,
+                              ),
+                            ),
                             Offstage(
                               offstage: item.state != JobState.paused,
                               child: MenuButton(
@@ -369,6 +426,23 @@ class _FileManagerPageState extends State<FileManagerPage>
   }
 }
 
+/// The entries of the local files of [paths] to send.
+List<Entry> localEntries(List<String> paths) {
+  final entries = <Entry>[];
+  for (final p in paths) {
+    final type = FileSystemEntity.typeSync(p);
+    if (type == FileSystemEntityType.notFound) continue;
+    final isDir = type == FileSystemEntityType.directory;
+    entries.add(Entry()
+      ..path = p
+      ..name = PathUtil.split(p, isWindows).last
+      // see `FileType` in message.proto
+      ..entryType = isDir ? 0 : 4
+      ..size = isDir ? 0 : File(p).lengthSync());
+  }
+  return entries;
+}
+
 class FileManagerView extends StatefulWidget {
   final FileController controller;
   final FFI _ffi;
@@ -393,6 +467,7 @@ class _FileManagerViewState extends State<FileManagerView> {
   final _sizeColWidth = 0.0.obs;
   final _fileListScrollController = ScrollController();
   final _globalHeaderKey = GlobalKey();
+  final _showPreview = false.obs;
 
   /// [_lastClickTime], [_lastClickEntry] help to handle double click
   var _lastClickTime =
@@ -450,7 +525,20 @@ class _FileManagerViewState extends State<FileManagerView> {
                   onExit: (evt) =>
                       widget._mouseFocusScope.value = MouseFocusScope.none,
                   child: _buildFileList(context, _fileListScrollController),
-                ))
+                )),
+                Obx(() {
+                  if (!_showPreview.value) return const SizedBox.shrink();
+                  final items = selectedItems.items;
+                  return SizedBox(
+                    width: 260,
+                    child: _FilePreview(
+                        ffi: _ffi,
+                        controller: controller,
+                        entry: items.length == 1 && items.first.isFile
+                            ? items.first
+                            : null),
+                  );
+                }),
               ],
             ),
           ),
@@ -972,6 +1060,96 @@ class _FileManagerViewState extends State<FileManagerView> {
         padding: kDesktopMenuPadding,
         dismissOnClicked: true,
       ),
+      if (!isWeb)
+        for (final option in {
+          kOptionEnablePreserveFilePermissions: "Preserve permissions",
+          kOptionEnablePreserveFileTimestamps: "Preserve timestamps",
+          kOptionEnablePreserveFileXattrs: "Preserve extended attributes",
+        }.entries)
+          MenuEntrySwitch<String>(
+            switchType: SwitchType.scheckbox,
+            text: translate(option.value),
+            getter: () async => mainGetLocalBoolOptionSync(option.key),
+            setter: (bool v) async =>
+                await mainSetLocalBoolOption(option.key, v),
+            padding: kDesktopMenuPadding,
+            dismissOnClicked: true,
+          ),
+      if (!isWeb) MenuEntryDivider<String>(),
+      if (!isWeb)
+        MenuEntryRadios<String>(
+          text: translate('Links'),
+          optionsGetter: () => [
+            for (final option in {
+              'skip': 'Skip links',
+              'copy': 'Copy links',
+              'follow': 'Follow links',
+            }.entries)
+              MenuEntryRadioOption(
+                text: translate(option.value),
+                value: option.key,
+                dismissOnClicked: true,
+              ),
+          ],
+          curOptionGetter: () async {
+            final policy =
+                bind.mainGetLocalOption(key: kOptionFileTransferLinkPolicy);
+            return policy.isEmpty ? 'skip' : policy;
+          },
+          optionSetter: (String oldValue, String newValue) async {
+            await bind.mainSetLocalOption(
+                key: kOptionFileTransferLinkPolicy,
+                value: newValue == 'skip' ? '' : newValue);
+          },
+          padding: kDesktopMenuPadding,
+        ),
+      if (!isWeb)
+        MenuEntryRadios<String>(
+          text: translate('On conflict'),
+          optionsGetter: () => [
+            for (final option in {
+              'ask': 'Ask every time',
+              ...fileConflictPolicies,
+            }.entries)
+              MenuEntryRadioOption(
+                text: translate(option.value),
+                value: option.key,
+                dismissOnClicked: true,
+              ),
+          ],
+          curOptionGetter: () async {
+            final policy = bind.mainGetLocalOption(
+                key: kOptionFileTransferConflictPolicy);
+            return policy.isEmpty ? 'ask' : policy;
+          },
+          optionSetter: (String oldValue, String newValue) async {
+            await bind.mainSetLocalOption(
+                key: kOptionFileTransferConflictPolicy,
+                value: newValue == 'ask' ? '' : newValue);
+          },
+          padding: kDesktopMenuPadding,
+        ),
+      if (!isWeb)
+        MenuEntrySwitch<String>(
+          switchType: SwitchType.scheckbox,
+          text: translate("Delete permanently"),
+          getter: () async =>
+              mainGetLocalBoolOptionSync(kOptionEnableDeletePermanently),
+          setter: (bool v) async => await mainSetLocalBoolOption(
+              kOptionEnableDeletePermanently, v),
+          padding: kDesktopMenuPadding,
+          dismissOnClicked: true,
+        ),
+      if (!isWeb) MenuEntryDivider<String>(),
+      if (!isWeb)
+        MenuEntrySwitch<String>(
+          switchType: SwitchType.scheckbox,
+          text: translate("Preview"),
+          getter: () async => _showPreview.value,
+          setter: (bool v) async => _showPreview.value = v,
+          padding: kDesktopMenuPadding,
+          dismissOnClicked: true,
+        ),
       MenuEntryButton(
           childBuilder: (style) => Text(translate("Select All"), style: style),
           proc: () => setState(() =>
@@ -983,7 +1161,29 @@ class _FileManagerViewState extends State<FileManagerView> {
               Text(translate("Unselect All"), style: style),
           proc: () => selectedItems.clear(),
           padding: kDesktopMenuPadding,
-          dismissOnClicked: true)
+          dismissOnClicked: true),
+      if (!isWeb)
+        MenuEntryButton(
+            childBuilder: (style) =>
+                Text(translate("Undo delete"), style: style),
+            proc: () => controller.undoRemove(),
+            padding: kDesktopMenuPadding,
+            enabled: controller.lastTrashedJobIds.isNotEmpty.obs,
+            dismissOnClicked: true),
+      if (!isLocal && !isWeb)
+        MenuEntryButton(
+            childBuilder: (style) =>
+                Text(translate("Search files"), style: style),
+            proc: () => showFileSearchDialog(_ffi),
+            padding: kDesktopMenuPadding,
+            dismissOnClicked: true),
+      if (!isWeb)
+        MenuEntryButton(
+            childBuilder: (style) =>
+                Text(translate("Sync jobs"), style: style),
+            proc: () => showSyncJobsDialog(_ffi),
+            padding: kDesktopMenuPadding,
+            dismissOnClicked: true)
     ];
 
     return Listener(
@@ -1676,3 +1876,483 @@ Widget buildWindowsThisPC(BuildContext context, [TextStyle? textStyle]) {
     Text(translate('This PC'), style: textStyle)
   ]);
 }
+
+/// Searches the folder open on the remote side by the names, and opens the folder of the entry
+/// chosen.
+const _kPreviewTextExts = {'txt', 'log', 'md', 'csv', 'json', 'xml', 'yaml',
+  'yml', 'toml', 'ini', 'conf', 'cfg', 'sh', 'bat', 'ps1', 'py', 'rs', 'dart',
+  'js', 'ts', 'c', 'h', 'cpp', 'java', 'go', 'html', 'css', 'sql'};
+const _kPreviewImageExts = {'png', 'jpg', 'jpeg', 'gif', 'bmp', 'webp'};
+// the beginning of a text file shown
+const _kPreviewTextSize = 64 * 1024;
+// the images larger are not read
+const _kPreviewImageMaxSize = 8 * 1024 * 1024;
+
+/// The beginning of the text file or the image selected, read without downloading the file.
+class _FilePreview extends StatefulWidget {
+  final FFI ffi;
+  final FileController controller;
+  final Entry? entry;
+
+  const _FilePreview(
+      {required this.ffi, required this.controller, required this.entry});
+
+  @override
+  State<_FilePreview> createState() => _FilePreviewState();
+}
+
+class _FilePreviewState extends State<_FilePreview> {
+  late Future<Widget> _content = _load();
+
+  @override
+  void didUpdateWidget(covariant _FilePreview oldWidget) {
+    super.didUpdateWidget(oldWidget);
+    if (oldWidget.entry != widget.entry) {
+      _content = _load();
+    }
+  }
+
+  Widget _message(String text) =>
+      Center(child: Text(text, textAlign: TextAlign.center));
+
+  Future<Widget> _load() async {
+    final entry = widget.entry;
+    if (entry == null) return _message(translate('Select a file to preview'));
+    final name = entry.name.toLowerCase();
+    final ext = name.contains('.') ? name.split('.').last : '';
+    final path = PathUtil.join(widget.controller.directory.value.path,
+        entry.name, widget.controller.options.value.isWindows);
+    final fileModel = widget.ffi.fileModel;
+    final isLocal = widget.controller.isLocal;
+    if (_kPreviewTextExts.contains(ext)) {
+      final range = await fileModel.readFileRange(path, 0, _kPreviewTextSize,
+          isLocal: isLocal);
+      var text = utf8.decode(range.data, allowMalformed: true);
+      if (range.data.length < range.size) text += '\n…';
+      return SingleChildScrollView(
+          child: SelectableText(text,
+              style: const TextStyle(fontFamily: 'monospace', fontSize: 12)));
+    }
+    if (_kPreviewImageExts.contains(ext)) {
+      if (entry.size > _kPreviewImageMaxSize) {
+        return _message(translate('File too large to preview'));
+      }
+      final data = BytesBuilder(copy: false);
+      while (data.length < entry.size) {
+        final range = await fileModel.readFileRange(
+            path, data.length, kFileRangeMaxSize,
+            isLocal: isLocal);
+        if (range.data.isEmpty) break;
+        data.add(range.data);
+      }
+      return Image.memory(data.takeBytes(),
+          fit: BoxFit.contain,
+          cacheWidth: 512,
+          errorBuilder: (context, error, stackTrace) =>
+              _message(translate('No preview available')));
+    }
+    return _message(translate('No preview available'));
+  }
+
+  @override
+  Widget build(BuildContext context) {
+    return Container(
+      margin: const EdgeInsets.only(left: 8),
+      padding: const EdgeInsets.all(8),
+      decoration: BoxDecoration(
+          border: Border.all(color: Theme.of(context).dividerColor),
+          borderRadius: BorderRadius.circular(4)),
+      child: FutureBuilder<Widget>(
+        future: _content,
+        builder: (context, snapshot) {
+          if (snapshot.hasError) {
+            return _message(
+                '${translate('Failed to preview')}: ${snapshot.error}');
+          }
+          if (snapshot.connectionState != ConnectionState.done) {
+            return const Center(child: CircularProgressIndicator());
+          }
+          return snapshot.data!;
+        },
+      ),
+    );
+  }
+}
+
+void showFileSearchDialog(FFI ffi) {
+  final controller = ffi.fileModel.remoteController;
+  final dir = controller.directory.value.path;
+  final isWindows = controller.options.value.isWindows;
+  final pattern = TextEditingController();
+  final results = <Entry>[];
+  int? searchId;
+  var searched = false;
+  var truncated = false;
+  ffi.dialogManager.show((setState, close, context) {
+    stop() {
+      if (searchId != null) {
+        ffi.fileModel.cancelSearch(searchId!);
+        setState(() => searchId = null);
+      }
+    }
+
+    search() {
+      stop();
+      if (pattern.text.trim().isEmpty) return;
+      setState(() {
+        results.clear();
+        searched = true;
+        truncated = false;
+      });
+      searchId = ffi.fileModel.searchRemoteFiles(dir, pattern.text,
+          (entries, done, isTruncated) {
+        setState(() {
+          results.addAll(entries);
+          truncated = isTruncated;
+          if (done) searchId = null;
+        });
+      });
+    }
+
+    cancel() {
+      stop();
+      close();
+    }
+
+    open(Entry entry) {
+      final path = PathUtil.join(dir, entry.name, isWindows);
+      cancel();
+      controller.openDirectory(
+          entry.isDirectory ? path : PathUtil.dirname(path, isWindows));
+    }
+
+    Widget resultRow(Entry entry) {
+      return ListTile(
+        dense: true,
+        leading: Icon(
+            entry.isDirectory
+                ? Icons.folder_outlined
+                : Icons.description_outlined,
+            size: 18),
+        title: Text(entry.name, overflow: TextOverflow.ellipsis),
+        subtitle: Text(
+            '${entry.isFile ? '${readableFileSize(entry.size.toDouble())}   ' : ''}'
+            '${entry.lastModified().toString().replaceAll(".000", "")}'),
+        onTap: () => open(entry),
+      );
+    }
+
+    final running = searchId != null;
+    return CustomAlertDialog(
+      title: Text('${translate('Search files')} - $dir'),
+      contentBoxConstraints: BoxConstraints(minWidth: 500, maxWidth: 600),
+      content: Column(
+        mainAxisSize: MainAxisSize.min,
+        crossAxisAlignment: CrossAxisAlignment.start,
+        children: [
+          TextField(
+            controller: pattern,
+            autofocus: true,
+            decoration: InputDecoration(
+                labelText: translate('search_files_tip'),
+                suffixIcon: running
+                    ? Padding(
+                        padding: EdgeInsets.all(12),
+                        child: SizedBox(
+                            width: 16,
+                            height: 16,
+                            child: CircularProgressIndicator(strokeWidth: 2)))
+                    : null),
+            onSubmitted: (_) => search(),
+          ),
+          const SizedBox(height: 8),
+          if (searched && !running && results.isEmpty)
+            Text(translate('No files found')),
+          if (truncated)
+            Text(translate('search_truncated_tip'),
+                style: Theme.of(context).textTheme.bodySmall),
+          if (results.isNotEmpty)
+            ConstrainedBox(
+              constraints: BoxConstraints(maxHeight: 360),
+              child: ListView.builder(
+                shrinkWrap: true,
+                itemCount: results.length,
+                itemBuilder: (context, i) => resultRow(results[i]),
+              ),
+            ),
+        ],
+      ),
+      actions: [
+        running
+            ? dialogButton('Stop', onPressed: stop, isOutline: true)
+            : dialogButton('Search', onPressed: search, isOutline: true),
+        dialogButton('Close', onPressed: cancel),
+      ],
+      onCancel: cancel,
+    );
+  });
+}
+
+void showSyncJobsDialog(FFI ffi) {
+  final sync = ffi.fileModel.syncJobController;
+  var jobs = sync.getJobs();
+  ffi.dialogManager.show((setState, close, context) {
+    edit(SyncJob? job) {
+      close();
+      final fileModel = ffi.fileModel;
+      _showSyncJobEditDialog(
+          ffi,
+          job ??
+              (SyncJob()
+                ..local = fileModel.localController.directory.value.path
+                ..remote = fileModel.remoteController.directory.value.path));
+    }
+
+    remove(String name) async {
+      await sync.removeJob(name);
+      setState(() => jobs = sync.getJobs());
+    }
+
+    Widget jobRow(SyncJob job) {
+      final arrow = job.direction == SyncDirection.upload
+          ? '→'
+          : (job.direction == SyncDirection.download ? '←' : '⇄');
+      return Row(children: [
+        Expanded(
+          child: Column(
+            crossAxisAlignment: CrossAxisAlignment.start,
+            children: [
+              Text(job.name, overflow: TextOverflow.ellipsis),
+              Text('${job.local} $arrow ${job.remote}',
+                  overflow: TextOverflow.ellipsis,
+                  style: Theme.of(context).textTheme.bodySmall),
+            ],
+          ),
+        ),
+        IconButton(
+          tooltip: translate('Preview'),
+          icon: Icon(Icons.visibility_outlined),
+          onPressed: () {
+            close();
+            _showSyncPlanDialog(ffi, job.name);
+          },
+        ),
+        IconButton(
+          tooltip: translate('Run'),
+          icon: Icon(Icons.play_arrow),
+          onPressed: sync.isRunning(job.name)
+              ? null
+              : () {
+                  sync.run(job.name);
+                  close();
+                },
+        ),
+        IconButton(
+          tooltip: translate('Sync log'),
+          icon: Icon(Icons.history),
+          onPressed: () {
+            close();
+            _showSyncLogDialog(ffi, job);
+          },
+        ),
+        IconButton(
+          tooltip: translate('Edit'),
+          icon: Icon(Icons.edit_outlined),
+          onPressed: () => edit(job),
+        ),
+        IconButton(
+          tooltip: translate('Delete'),
+          icon: Icon(Icons.delete_outline),
+          onPressed: () => remove(job.name),
+        ),
+      ]);
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Sync jobs')),
+      contentBoxConstraints: BoxConstraints(maxWidth: 600),
+      content: Column(
+        mainAxisSize: MainAxisSize.min,
+        crossAxisAlignment: CrossAxisAlignment.start,
+        children: jobs.isEmpty
+            ? [Text(translate('No sync jobs'))]
+            : jobs.map(jobRow).toList(),
+      ),
+      actions: [
+        dialogButton('Add', onPressed: () => edit(null), isOutline: true),
+        dialogButton('Close', onPressed: close),
+      ],
+      onCancel: close,
+    );
+  });
+}
+
+void _showSyncJobEditDialog(FFI ffi, SyncJob job) {
+  final name = TextEditingController(text: job.name);
+  final local = TextEditingController(text: job.local);
+  final remote = TextEditingController(text: job.remote);
+  final interval = TextEditingController(text: job.interval.toString());
+  final isNew = job.name.isEmpty;
+  String? errorText;
+  ffi.dialogManager.show((setState, close, context) {
+    submit() {
+      job
+        ..name = name.text.trim()
+        ..local = local.text.trim()
+        ..remote = remote.text.trim()
+        ..interval = int.tryParse(interval.text.trim()) ?? 0;
+      final err = ffi.fileModel.syncJobController.saveJob(job);
+      if (err.isNotEmpty) {
+        setState(() => errorText = err);
+        return;
+      }
+      close();
+      showSyncJobsDialog(ffi);
+    }
+
+    cancel() {
+      close();
+      showSyncJobsDialog(ffi);
+    }
+
+    return CustomAlertDialog(
+      title: Text(translate('Sync jobs')),
+      content: Column(
+        mainAxisSize: MainAxisSize.min,
+        crossAxisAlignment: CrossAxisAlignment.start,
+        children: [
+          TextField(
+            controller: name,
+            enabled: isNew,
+            autofocus: isNew,
+            decoration: InputDecoration(
+                labelText: translate('Name'), errorText: errorText),
+          ),
+          TextField(
+            controller: local,
+            decoration: InputDecoration(labelText: translate('Local folder')),
+          ),
+          TextField(
+            controller: remote,
+            decoration: InputDecoration(labelText: translate('Remote folder')),
+          ),
+          DropdownButton<SyncDirection>(
+            value: job.direction,
+            isExpanded: true,
+            items: SyncDirection.values
+                .map((d) =>
+                    DropdownMenuItem(value: d, child: Text(d.display())))
+                .toList(),
+            onChanged: (d) {
+              if (d != null) setState(() => job.direction = d);
+            },
+          ).marginOnly(top: 8),
+          TextField(
+            controller: interval,
+            keyboardType: TextInputType.number,
+            inputFormatters: [FilteringTextInputFormatter.digitsOnly],
+            decoration: InputDecoration(
+                labelText: translate('sync_interval_tip')),
+          ),
+          CheckboxListTile(
+            contentPadding: EdgeInsets.zero,
+            controlAffinity: ListTileControlAffinity.leading,
+            value: job.includeHidden,
+            title: Text(translate('Show Hidden Files')),
+            onChanged: (v) => setState(() => job.includeHidden = v == true),
+          ),
+        ],
+      ),
+      actions: [
+        dialogButton('Cancel', onPressed: cancel, isOutline: true),
+        dialogButton('OK', onPressed: submit),
+      ],
+      onSubmit: submit,
+      onCancel: cancel,
+    );
+  });
+}
+
+void _showSyncPlanDialog(FFI ffi, String name) async {
+  final sync = ffi.fileModel.syncJobController;
+  final loading = ffi.dialogManager.showLoading(translate('Waiting'));
+  final SyncPlan plan;
+  try {
+    plan = await sync.fetchPlan(name, dryRun: true);
+  } catch (e) {
+    ffi.dialogManager.dismissByTag(loading);
+    showToast(e.toString());
+    showSyncJobsDialog(ffi);
+    return;
+  }
+  ffi.dialogManager.dismissByTag(loading);
+  ffi.dialogManager.show((setState, close, context) {
+    back() {
+      close();
+      showSyncJobsDialog(ffi);
+    }
+
+    run() {
+      sync.run(name);
+      close();
+    }
+
+    final rows = [
+      ...plan.copies.map((c) => Row(children: [
+            Icon(c.upload ? Icons.upload : Icons.download, size: 16),
+            Expanded(
+                child: Text(c.name, overflow: TextOverflow.ellipsis)
+                    .marginOnly(left: 6)),
+            Text(readableFileSize(c.size.toDouble())),
+          ])),
+      ...plan.conflicts.map((c) => Row(children: [
+            Icon(Icons.warning_amber, size: 16, color: Colors.orange),
+            Expanded(
+                child: Text(c, overflow: TextOverflow.ellipsis)
+                    .marginOnly(left: 6)),
+            Text(translate('Conflicts')),
+          ])),
+    ];
+    return CustomAlertDialog(
+      title: Text(name),
+      content: rows.isEmpty
+          ? Text(translate('Up to date'))
+          : ConstrainedBox(
+              constraints: BoxConstraints(maxHeight: 400),
+              child: ListView(shrinkWrap: true, children: rows),
+            ),
+      actions: [
+        dialogButton('Close', onPressed: back, isOutline: true),
+        if (plan.copies.isNotEmpty) dialogButton('Run', onPressed: run),
+      ],
+      onCancel: back,
+    );
+  });
+}
+
+void _showSyncLogDialog(FFI ffi, SyncJob job) {
+  ffi.dialogManager.show((setState, close, context) {
+    back() {
+      close();
+      showSyncJobsDialog(ffi);
+    }
+
+    return CustomAlertDialog(
+      title: Text('${translate('Sync log')} - ${job.name}'),
+      content: job.logs.isEmpty
+          ? Text(translate('Empty'))
+          : ConstrainedBox(
+              constraints: BoxConstraints(maxHeight: 400),
+              child: ListView(
+                shrinkWrap: true,
+                children: job.logs.reversed
+                    .map((log) => Text(
+                        '${log.time.toString().split('.').first}  ${log.text}'))
+                    .toList(),
+              ),
+            ),
+      actions: [dialogButton('Close', onPressed: back)],
+      onCancel: back,
+    );
+  });
+}
@@ -4,6 +4,7 @@ import 'package:desktop_multi_window/desktop_multi_window.dart';
 import 'package:flutter/material.dart';
 import 'package:flutter_hbb/common.dart';
 import 'package:flutter_hbb/consts.dart';
+import 'package:flutter_hbb/models/model.dart';
 import 'package:flutter_hbb/models/state_model.dart';
 import 'package:flutter_hbb/desktop/pages/file_manager_page.dart';
 import 'package:flutter_hbb/desktop/widgets/tabbar_widget.dart';
@@ -49,6 +50,7 @@ class _FileManagerTabPageState extends State<FileManagerTabPage> {
           tabController: tabController,
           forceRelay: params['forceRelay'],
           connToken: params['connToken'],
+          dropFiles: dropFilesOf(params),
         )));
   }
 
@@ -64,6 +66,14 @@ class _FileManagerTabPageState extends State<FileManagerTabPage> {
         final args = jsonDecode(call.arguments);
         final id = args['id'];
         windowOnTop(windowId());
+        final dropFiles = dropFilesOf(args);
+        if (dropFiles != null &&
+            tabController.state.value.tabs.any((e) => e.key == id)) {
+          // dropped while the file transfer is open, to the folder open on the remote side
+          final ffi = Get.find<FFI>(tag: 'ft_$id');
+          ffi.fileModel
+              .uploadDroppedFiles(localEntries(dropFiles), toDesktop: false);
+        }
         tabController.add(TabInfo(
             key: id,
             label: id,
@@ -78,6 +88,7 @@ class _FileManagerTabPageState extends State<FileManagerTabPage> {
               tabController: tabController,
               forceRelay: args['forceRelay'],
               connToken: args['connToken'],
+              dropFiles: dropFiles,
             )));
       } else if (call.method == "onDestroy") {
         tabController.clear();
@@ -118,6 +129,11 @@ class _FileManagerTabPageState extends State<FileManagerTabPage> {
           );
   }
 
+  List<String>? dropFilesOf(Map<String, dynamic> args) {
+    final files = args['dropFiles'];
+    return files is List ? files.map((e) => e.toString()).toList() : null;
+  }
+
   void onRemoveId(String id) {
     if (tabController.state.value.tabs.isEmpty) {
       WindowController.fromWindowId(windowId()).close();
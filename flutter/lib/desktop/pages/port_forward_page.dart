@@ -196,7 +196,8 @@ class _PortForwardPageState extends State<PortForwardPage>
             controller: remoteHostController, hint: 'localhost'),
         buildTunnelInputCell(context,
             controller: remotePortController,
-            inputFormatters: portInputFormatter),
+            inputFormatters: portInputFormatter,
+            hint: '0: SOCKS5'),
         ElevatedButton(
           onPressed: () async {
             int? localPort = int.tryParse(localPortController.text);
@@ -258,8 +259,9 @@ class _PortForwardPageState extends State<PortForwardPage>
       child: Row(children: [
         text(pf.localPort.toString()),
         const SizedBox(width: _kColumn1Width),
-        text(pf.remoteHost),
-        text(pf.remotePort.toString()),
+        // a remote port of 0 tunnels through a local socks5 proxy
+        text(pf.remotePort == 0 ? 'SOCKS5' : pf.remoteHost),
+        text(pf.remotePort == 0 ? '*' : pf.remotePort.toString()),
         SizedBox(
           width: _kColumn4Width,
           child: IconButton(
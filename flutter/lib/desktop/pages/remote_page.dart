@@ -1,5 +1,7 @@
 import 'dart:async';
+import 'dart:math';
 
+import 'package:desktop_drop/desktop_drop.dart';
 import 'package:desktop_multi_window/desktop_multi_window.dart';
 import 'package:flutter/material.dart';
 import 'package:flutter/services.dart';
@@ -279,6 +281,17 @@ class _RemotePageState extends State<RemotePage>
         ),
       );
 
+  // The files dropped on the remote desktop are uploaded to the desktop folder of the peer, in the
+  // file transfer window.
+  void onDropFiles(DropDoneDetails details) {
+    final paths = details.files.map((e) => e.path).toList();
+    if (paths.isEmpty) return;
+    connect(context, widget.id,
+        isFileTransfer: true,
+        connToken: bind.sessionGetConnToken(sessionId: _ffi.sessionId),
+        dropFiles: paths);
+  }
+
   Widget buildBody(BuildContext context) {
     remoteToolbar(BuildContext context) => RemoteToolbar(
           id: widget.id,
@@ -325,7 +338,11 @@ class _RemotePageState extends State<RemotePage>
                     }
                   },
                   inputModel: _ffi.inputModel,
-                  child: getBodyForDesktop(context))),
+                  child: isWeb
+                      ? getBodyForDesktop(context)
+                      : DropTarget(
+                          onDragDone: onDropFiles,
+                          child: getBodyForDesktop(context)))),
           Stack(
             children: [
               _ffi.ffiModel.pi.isSet.isTrue &&
@@ -405,6 +422,7 @@ class _RemotePageState extends State<RemotePage>
           ChangeNotifierProvider.value(value: _ffi.cursorModel),
           ChangeNotifierProvider.value(value: _ffi.canvasModel),
           ChangeNotifierProvider.value(value: _ffi.recordingModel),
+          ChangeNotifierProvider.value(value: _ffi.annotationModel),
         ], child: buildBody(context)));
   }
 
@@ -429,7 +447,11 @@ class _RemotePageState extends State<RemotePage>
 
   void leaveView(PointerExitEvent evt) {
     if (_ffi.ffiModel.keyboard) {
-      _ffi.inputModel.tryMoveEdgeOnExit(evt.position);
+      if (_ffi.inputModel.isPenEvent(evt)) {
+        _ffi.inputModel.leavePen(evt);
+      } else {
+        _ffi.inputModel.tryMoveEdgeOnExit(evt.position);
+      }
     }
 
     _cursorOverImage.value = false;
@@ -526,6 +548,7 @@ class _RemotePageState extends State<RemotePage>
                   zoomCursor: _zoomCursor,
                 )));
     }
+    paints.add(Positioned.fill(child: AnnotationPaint(ffi: _ffi)));
     paints.add(
       Positioned(
         top: 10,
@@ -534,6 +557,29 @@ class _RemotePageState extends State<RemotePage>
             QualityMonitor(_ffi.qualityMonitorModel), null, null),
       ),
     );
+    paints.add(
+      Positioned(
+        bottom: 10,
+        right: 10,
+        child: _buildRawTouchAndPointerRegion(
+            SystemStatsPanel(_ffi), null, null),
+      ),
+    );
+    paints.add(
+      Positioned(
+        bottom: 10,
+        left: 10,
+        child: _buildRawTouchAndPointerRegion(CameraPanel(_ffi), null, null),
+      ),
+    );
+    paints.add(
+      Positioned(
+        top: 10,
+        left: 10,
+        child: _buildRawTouchAndPointerRegion(
+            BroadcastViewersIndicator(_ffi), null, null),
+      ),
+    );
     return Stack(
       children: paints,
     );
@@ -905,3 +951,143 @@ class CursorPaint extends StatelessWidget {
     );
   }
 }
+
+// The drawings over the screen of the peer, and the palette while drawing.
+class AnnotationPaint extends StatelessWidget {
+  final FFI ffi;
+
+  const AnnotationPaint({Key? key, required this.ffi}) : super(key: key);
+
+  @override
+  Widget build(BuildContext context) {
+    final m = Provider.of<AnnotationModel>(context);
+    final c = Provider.of<CanvasModel>(context);
+    final rect = ffi.ffiModel.rect;
+    if (rect == null || (m.strokes.isEmpty && m.drawing.isFalse)) {
+      return Offstage();
+    }
+    double cx = c.x;
+    double cy = c.y;
+    if (c.viewStyle.style == kRemoteViewStyleOriginal &&
+        c.scrollStyle == ScrollStyle.scrollbar) {
+      if (cx < 0) {
+        cx = -rect.width * c.scale * c.scrollX;
+      }
+      if (cy < 0) {
+        cy = -rect.height * c.scale * c.scrollY;
+      }
+    }
+    Offset toLocal(Offset p) => Offset(
+        (p.dx - rect.left) * c.scale + cx, (p.dy - rect.top) * c.scale + cy);
+    Offset toRemote(Offset p) => Offset(
+        (p.dx - cx) / c.scale + rect.left, (p.dy - cy) / c.scale + rect.top);
+    final current = m.current;
+    final paint = IgnorePointer(
+      child: CustomPaint(
+        size: Size.infinite,
+        painter: _AnnotationPainter(
+          strokes: [...m.strokes, if (current != null) current],
+          toLocal: toLocal,
+          scale: c.scale,
+        ),
+      ),
+    );
+    if (m.drawing.isFalse) {
+      return paint;
+    }
+    return Stack(
+      children: [
+        Listener(
+          behavior: HitTestBehavior.opaque,
+          onPointerDown: (e) => m.start(toRemote(e.localPosition)),
+          onPointerMove: (e) => m.move(toRemote(e.localPosition)),
+          onPointerUp: (_) => m.end(),
+          onPointerCancel: (_) => m.end(),
+          child: MouseRegion(cursor: SystemMouseCursors.precise, child: paint),
+        ),
+        Positioned(top: 10, left: 10, child: _buildPalette(m)),
+      ],
+    );
+  }
+
+  Widget _buildPalette(AnnotationModel m) {
+    Widget button(IconData icon, String tip, VoidCallback onPressed,
+            {bool selected = false, Color? color}) =>
+        IconButton(
+          icon: Icon(icon,
+              color: color ?? (selected ? MyTheme.accent : Colors.white)),
+          tooltip: translate(tip),
+          splashRadius: 18,
+          onPressed: onPressed,
+        );
+    return Container(
+      decoration: BoxDecoration(
+          color: Colors.black54, borderRadius: BorderRadius.circular(8)),
+      child: Obx(() => Row(
+            mainAxisSize: MainAxisSize.min,
+            children: [
+              button(Icons.gesture, 'Freehand',
+                  () => m.shape.value = kAnnotationFreehand,
+                  selected: m.shape.value == kAnnotationFreehand),
+              button(Icons.north_east, 'Arrow',
+                  () => m.shape.value = kAnnotationArrow,
+                  selected: m.shape.value == kAnnotationArrow),
+              button(Icons.crop_square, 'Box',
+                  () => m.shape.value = kAnnotationBox,
+                  selected: m.shape.value == kAnnotationBox),
+              button(Icons.circle, 'Color', () {
+                final i = kAnnotationColors.indexOf(m.color.value);
+                m.color.value =
+                    kAnnotationColors[(i + 1) % kAnnotationColors.length];
+              }, color: Color(0xFF000000 | m.color.value)),
+              button(Icons.undo, 'Undo', () => m.undo(false)),
+              button(Icons.delete_sweep, 'Clear', () => m.undo(true)),
+              button(Icons.close, 'Close', () => m.toggle()),
+            ],
+          )),
+    );
+  }
+}
+
+class _AnnotationPainter extends CustomPainter {
+  final List<AnnotationStroke> strokes;
+  final Offset Function(Offset) toLocal;
+  final double scale;
+
+  _AnnotationPainter(
+      {required this.strokes, required this.toLocal, required this.scale});
+
+  @override
+  void paint(Canvas canvas, Size size) {
+    for (final stroke in strokes) {
+      if (stroke.points.isEmpty) continue;
+      final paint = Paint()
+        ..color = Color(0xFF000000 | stroke.color)
+        ..strokeWidth = max(1.0, stroke.width * scale)
+        ..style = PaintingStyle.stroke
+        ..strokeCap = StrokeCap.round
+        ..strokeJoin = StrokeJoin.round;
+      final points = stroke.points.map(toLocal).toList();
+      if (stroke.shape == kAnnotationBox && points.length >= 2) {
+        canvas.drawRect(Rect.fromPoints(points[0], points[1]), paint);
+      } else if (stroke.shape == kAnnotationArrow && points.length >= 2) {
+        canvas.drawLine(points[0], points[1], paint);
+        // the two wings of the head
+        final angle = (points[1] - points[0]).direction;
+        final len = (16 + stroke.width * 2) * scale;
+        for (final a in [0.45, -0.45]) {
+          canvas.drawLine(points[1],
+              points[1] - Offset.fromDirection(angle + a, len), paint);
+        }
+      } else if (points.length == 1) {
+        canvas.drawCircle(points[0], paint.strokeWidth / 2,
+            paint..style = PaintingStyle.fill);
+      } else {
+        canvas.drawPath(Path()..addPolygon(points, false), paint);
+      }
+    }
+  }
+
+  @override
+  bool shouldRepaint(covariant CustomPainter oldDelegate) => true;
+}
@@ -168,6 +168,11 @@ class _ConnectionTabPageState extends State<ConnectionTabPage> {
             } else {
               msgFingerprint += fingerprint;
             }
+            final sas = bind.mainFingerprintToSas(fingerprint: fingerprint);
+            if (sas.isNotEmpty) {
+              msgFingerprint +=
+                  '\n${translate('Short authentication string')}: $sas';
+            }
 
             final tab = Row(
               mainAxisAlignment: MainAxisAlignment.center,
@@ -182,6 +187,13 @@ class _ConnectionTabPageState extends State<ConnectionTabPage> {
                   ).paddingOnly(right: 5),
                 ),
                 label,
+                if (connectionType.degraded.value)
+                  Tooltip(
+                    message: translate('connection_degraded_tip'),
+                    child: Icon(Icons.signal_wifi_bad,
+                            size: themeConf.iconSize, color: Colors.orange)
+                        .marginOnly(left: 4),
+                  ),
                 unreadMessageCountBuilder(UnreadChatCountState.find(key))
                     .marginOnly(left: 4),
               ],
@@ -18,6 +18,7 @@ import 'package:flutter_svg/flutter_svg.dart';
 
 import '../../common.dart';
 import '../../common/widgets/chat_page.dart';
+import '../../common/widgets/dialog.dart';
 import '../../models/file_model.dart';
 import '../../models/platform_model.dart';
 import '../../models/server_model.dart';
@@ -339,6 +340,24 @@ Widget buildConnectionCard(Client client) {
       key: ValueKey(client.id),
       children: [
         _CmHeader(client: client),
+        if (!client.authorized && client.reason.isNotEmpty)
+          Tooltip(
+            message: client.reason,
+            child: Text(
+              '${translate('Access reason')}: ${client.reason}',
+              maxLines: 3,
+              overflow: TextOverflow.ellipsis,
+            ),
+          ).marginSymmetric(horizontal: 5.0, vertical: 4.0),
+        if (!client.authorized && client.deviceKey.isNotEmpty)
+          Tooltip(
+            message: translate('new_device_key_tip'),
+            child: Text(
+              '${translate('New device')}: ${client.deviceKey}',
+              maxLines: 3,
+              overflow: TextOverflow.ellipsis,
+            ),
+          ).marginSymmetric(horizontal: 5.0, vertical: 4.0),
         client.type_() != ClientType.remote || client.disconnected
             ? Offstage()
             : _PrivilegeBoard(client: client),
@@ -578,6 +597,19 @@ class _PrivilegeBoardState extends State<_PrivilegeBoard> {
     );
   }
 
+  // the permissions locked by the policy can only be switched off
+  Function(bool)? lockedByPolicy(String name, Function(bool) onTap) {
+    if (!client.locked.contains(name)) return onTap;
+    return (enabled) {
+      if (!enabled) onTap(enabled);
+    };
+  }
+
+  String lockedTooltip(String name, String tooltip) =>
+      client.locked.contains(name)
+          ? '$tooltip (${translate('Locked by the policy')})'
+          : tooltip;
+
   @override
   Widget build(BuildContext context) {
     final crossAxisCount = 4;
@@ -602,10 +634,34 @@ class _PrivilegeBoardState extends State<_PrivilegeBoard> {
       child: Column(
         crossAxisAlignment: CrossAxisAlignment.center,
         children: [
-          Text(
-            translate("Permissions"),
-            style: TextStyle(fontSize: 16, fontWeight: FontWeight.bold),
-            textAlign: TextAlign.center,
+          Row(
+            mainAxisAlignment: MainAxisAlignment.center,
+            children: [
+              Text(
+                translate("Permissions"),
+                style: TextStyle(fontSize: 16, fontWeight: FontWeight.bold),
+                textAlign: TextAlign.center,
+              ),
+              SizedBox(
+                height: 22,
+                child: PopupMenuButton<bool>(
+                  tooltip: translate('permission_profile_tip'),
+                  padding: EdgeInsets.zero,
+                  iconSize: 18,
+                  icon: Icon(Icons.bookmark_border),
+                  itemBuilder: (context) => [
+                    PopupMenuItem(
+                        value: true,
+                        child: Text(translate('Remember for this peer'))),
+                    PopupMenuItem(
+                        value: false,
+                        child: Text(translate('Forget for this peer'))),
+                  ],
+                  onSelected: (save) => bind.cmSavePermissionProfile(
+                      connId: client.id, save: save),
+                ),
+              ),
+            ],
           ).marginOnly(left: 4.0, bottom: 8.0),
           Expanded(
             child: GridView.count(
@@ -626,17 +682,57 @@ class _PrivilegeBoardState extends State<_PrivilegeBoard> {
                   },
                   translate('Enable keyboard/mouse'),
                 ),
+                buildPermissionIcon(
+                  client.keyInput,
+                  Icons.keyboard_alt_outlined,
+                  (enabled) {
+                    bind.cmSwitchPermission(
+                        connId: client.id, name: "key_input", enabled: enabled);
+                    setState(() {
+                      client.keyInput = enabled;
+                    });
+                  },
+                  translate('Enable key input'),
+                ),
+                buildPermissionIcon(
+                  client.mouseInput,
+                  Icons.mouse,
+                  (enabled) {
+                    bind.cmSwitchPermission(
+                        connId: client.id,
+                        name: "mouse_input",
+                        enabled: enabled);
+                    setState(() {
+                      client.mouseInput = enabled;
+                    });
+                  },
+                  translate('Enable mouse input'),
+                ),
+                buildPermissionIcon(
+                  client.clipboardTyping,
+                  Icons.content_paste_go,
+                  (enabled) {
+                    bind.cmSwitchPermission(
+                        connId: client.id,
+                        name: "clipboard_typing",
+                        enabled: enabled);
+                    setState(() {
+                      client.clipboardTyping = enabled;
+                    });
+                  },
+                  translate('Enable clipboard typing'),
+                ),
                 buildPermissionIcon(
                   client.clipboard,
                   Icons.assignment_rounded,
-                  (enabled) {
+                  lockedByPolicy("clipboard", (enabled) {
                     bind.cmSwitchPermission(
                         connId: client.id, name: "clipboard", enabled: enabled);
                     setState(() {
                       client.clipboard = enabled;
                     });
-                  },
-                  translate('Enable clipboard'),
+                  }),
+                  lockedTooltip("clipboard", translate('Enable clipboard')),
                 ),
                 buildPermissionIcon(
                   client.audio,
@@ -653,14 +749,14 @@ class _PrivilegeBoardState extends State<_PrivilegeBoard> {
                 buildPermissionIcon(
                   client.file,
                   Icons.upload_file_rounded,
-                  (enabled) {
+                  lockedByPolicy("file", (enabled) {
                     bind.cmSwitchPermission(
                         connId: client.id, name: "file", enabled: enabled);
                     setState(() {
                       client.file = enabled;
                     });
-                  },
-                  translate('Enable file copy and paste'),
+                  }),
+                  lockedTooltip("file", translate('Enable file copy and paste')),
                 ),
                 buildPermissionIcon(
                   client.restart,
@@ -686,6 +782,18 @@ class _PrivilegeBoardState extends State<_PrivilegeBoard> {
                   },
                   translate('Enable recording session'),
                 ),
+                buildPermissionIcon(
+                  client.terminal,
+                  Icons.terminal,
+                  lockedByPolicy("terminal", (enabled) {
+                    bind.cmSwitchPermission(
+                        connId: client.id, name: "terminal", enabled: enabled);
+                    setState(() {
+                      client.terminal = enabled;
+                    });
+                  }),
+                  lockedTooltip("terminal", translate('Enable terminal')),
+                ),
                 // only windows support block input
                 if (isWindows)
                   buildPermissionIcon(
@@ -855,6 +963,48 @@ class _CmControlPanel extends StatelessWidget {
             ],
           ),
         ),
+        Offstage(
+          offstage: !client.inputControlRequested,
+          child: Row(
+            children: [
+              Expanded(
+                child: buildButton(context,
+                    color: MyTheme.accent,
+                    onClick: () => handleInputControl(true),
+                    icon: Icon(
+                      Icons.mouse_rounded,
+                      color: Colors.white,
+                      size: 14,
+                    ),
+                    text: "Give control",
+                    textColor: Colors.white),
+              ),
+              Expanded(
+                child: buildButton(
+                  context,
+                  color: Colors.red,
+                  onClick: () => handleInputControl(false),
+                  icon: Icon(
+                    Icons.block_rounded,
+                    color: Colors.white,
+                    size: 14,
+                  ),
+                  text: "Dismiss",
+                  textColor: Colors.white,
+                ),
+              )
+            ],
+          ),
+        ),
+        Offstage(
+          offstage: client.annotations == 0,
+          child: buildButton(context,
+              color: MyTheme.accent,
+              onClick: () => gFFI.serverModel.clearAnnotations(client),
+              icon: Icon(Icons.layers_clear_rounded, color: Colors.white),
+              text: "Clear drawings",
+              textColor: Colors.white),
+        ),
         Offstage(
           offstage: !client.fromSwitch,
           child: buildButton(context,
@@ -1072,6 +1222,10 @@ class _CmControlPanel extends StatelessWidget {
   void closeVoiceCall() {
     bind.cmCloseVoiceCall(id: client.id);
   }
+
+  void handleInputControl(bool grant) {
+    bind.cmHandleInputControlRequest(id: client.id, grant: grant);
+  }
 }
 
 void checkClickTime(int id, Function() callback) async {
@@ -1102,7 +1256,34 @@ class _FileTransferLogPage extends StatefulWidget {
 class __FileTransferLogPageState extends State<_FileTransferLogPage> {
   @override
   Widget build(BuildContext context) {
-    return statusList();
+    return Column(
+      children: [
+        rateLimitButton(),
+        Expanded(child: statusList()),
+      ],
+    );
+  }
+
+  // the limit of all the file transfers sent by this computer
+  Widget rateLimitButton() {
+    final kbps = int.tryParse(
+            bind.mainGetOptionSync(key: kOptionFileTransferRateLimit)) ??
+        0;
+    return Align(
+      alignment: Alignment.centerRight,
+      child: TextButton.icon(
+        icon: Icon(Icons.speed),
+        label: Text(
+            '${translate("Speed limit")}: ${kbps > 0 ? '$kbps kbps' : translate("Unlimited")}'),
+        onPressed: () => fileTransferRateLimitDialog(
+            gFFI.dialogManager, kbps, (kbps) async {
+          await bind.mainSetOption(
+              key: kOptionFileTransferRateLimit,
+              value: kbps > 0 ? '$kbps' : '');
+          if (mounted) setState(() {});
+        }),
+      ),
+    ).paddingOnly(top: 12, right: 12);
   }
 
   Widget generateCard(Widget child) {
@@ -484,6 +484,7 @@ class _RemoteToolbarState extends State<RemoteToolbar> {
       toolbarItems.add(_VoiceCallMenu(id: widget.id, ffi: widget.ffi));
     }
     if (!isWeb) toolbarItems.add(_RecordMenu());
+    toolbarItems.add(_ServerRecordIndicator());
     toolbarItems.add(_CloseMenu(id: widget.id, ffi: widget.ffi));
     final toolbarBorderRadius = BorderRadius.all(Radius.circular(4.0));
     return Column(
@@ -1043,6 +1044,8 @@ class _DisplayMenuState extends State<_DisplayMenu> {
         scrollStyle(),
         imageQuality(),
         codec(),
+        audioInput(),
+        audioQuality(),
         _ResolutionsMenu(
           id: widget.id,
           ffi: widget.ffi,
@@ -1199,6 +1202,67 @@ class _DisplayMenuState extends State<_DisplayMenu> {
         });
   }
 
+  // the audio input of the peer to capture, empty for the one of its settings
+  audioInput() {
+    final inputs = List<String>.from(
+        pi.platformAdditions[kPlatformAdditionsAudioInputs] ?? []);
+    if (isWeb || inputs.isEmpty || perms['audio'] == false) return Offstage();
+    return futureBuilder(
+        future: bind.sessionGetPeerOption(
+            sessionId: ffi.sessionId, name: kOptionRemoteAudioInput),
+        hasData: (data) {
+          final groupValue = data as String;
+          return _SubmenuButton(
+              ffi: widget.ffi,
+              child: Text(translate('Audio Input Device')),
+              menuChildren: ['', ...inputs]
+                  .map((e) => RdoMenuButton<String>(
+                      value: e,
+                      groupValue: groupValue,
+                      onChanged: (v) async {
+                        if (v == null) return;
+                        await bind.sessionSetRemoteAudioInput(
+                            sessionId: ffi.sessionId, device: v);
+                      },
+                      child: Text(e.isEmpty ? translate('Default') : e),
+                      ffi: ffi))
+                  .toList());
+        });
+  }
+
+  // the preset of the audio encoder of the peer, empty for the lowest delay
+  audioQuality() {
+    if (isWeb || perms['audio'] == false) return Offstage();
+    return futureBuilder(
+        future: bind.sessionGetPeerOption(
+            sessionId: ffi.sessionId, name: kOptionAudioQuality),
+        hasData: (data) {
+          final groupValue = data as String;
+          final presets = {
+            '': 'Low latency',
+            'voice': 'Voice',
+            'music': 'Music',
+            if (pi.platformAdditions[kPlatformAdditionsSurround] == true)
+              'surround': 'Surround',
+          };
+          return _SubmenuButton(
+              ffi: widget.ffi,
+              child: Text(translate('Audio quality')),
+              menuChildren: presets.entries
+                  .map((e) => RdoMenuButton<String>(
+                      value: e.key,
+                      groupValue: groupValue,
+                      onChanged: (v) async {
+                        if (v == null) return;
+                        await bind.sessionSetAudioQuality(
+                            sessionId: ffi.sessionId, value: v);
+                      },
+                      child: Text(translate(e.value)),
+                      ffi: ffi))
+                  .toList());
+        });
+  }
+
   cursorToggles() {
     return futureBuilder(
         future: toolbarCursor(context, id, ffi),
@@ -1582,6 +1646,8 @@ class _KeyboardMenu extends StatelessWidget {
               keyboardMode(),
               localKeyboardType(),
               inputSource(),
+              localShortcuts(),
+              clipboardTypingDelay(),
               Divider(),
               viewMode(),
               Divider(),
@@ -1709,6 +1775,85 @@ class _KeyboardMenu extends StatelessWidget {
     return Column(children: children);
   }
 
+  localShortcuts() {
+    if (isWeb) return Offstage();
+    final enabled = !ffi.ffiModel.viewOnly;
+    final shortcuts = bind
+        .mainGetLocalOption(key: kOptionLocalShortcuts)
+        .split(',')
+        .where((e) => e.isNotEmpty)
+        .toList();
+    final List<(String, String)> choices = isMacOS
+        ? [(kLocalShortcutCmdTab, 'Cmd+Tab')]
+        : [
+            (kLocalShortcutAltTab, 'Alt+Tab'),
+            (kLocalShortcutWin, translate('Win key')),
+            (kLocalShortcutMedia, translate('Media keys')),
+          ];
+    final captureAll = bind.mainGetLocalOption(
+            key: kOptionCaptureAllKeysInFullscreen) ==
+        'Y';
+    return _SubmenuButton(
+      ffi: ffi,
+      menuChildren: [
+        ...choices.map((e) => CkbMenuButton(
+              value: shortcuts.contains(e.$1),
+              onChanged: enabled
+                  ? (v) async {
+                      if (v == null) return;
+                      shortcuts.remove(e.$1);
+                      if (v) shortcuts.add(e.$1);
+                      await bind.mainSetLocalOption(
+                          key: kOptionLocalShortcuts,
+                          value: shortcuts.join(','));
+                    }
+                  : null,
+              ffi: ffi,
+              child: Text(e.$2),
+            )),
+        Divider(),
+        CkbMenuButton(
+          value: captureAll,
+          onChanged: enabled
+              ? (v) async {
+                  if (v == null) return;
+                  await bind.mainSetLocalOption(
+                      key: kOptionCaptureAllKeysInFullscreen,
+                      value: v ? 'Y' : '');
+                }
+              : null,
+          ffi: ffi,
+          child: Text(translate('Capture all keys in fullscreen')),
+        ),
+      ],
+      child: Text(translate('Keep shortcuts on this computer')),
+    );
+  }
+
+  // between the keys of "Send clipboard keystrokes"
+  clipboardTypingDelay() {
+    if (isWeb) return Offstage();
+    final value = bind.mainGetLocalOption(key: kOptionClipboardTypingDelay);
+    final groupValue = value.isEmpty ? '20' : value;
+    return _SubmenuButton(
+      ffi: ffi,
+      menuChildren: ['0', '20', '50', '100', '200']
+          .map((e) => RdoMenuButton<String>(
+                value: e,
+                groupValue: groupValue,
+                onChanged: (v) async {
+                  if (v == null) return;
+                  await bind.mainSetLocalOption(
+                      key: kOptionClipboardTypingDelay, value: v);
+                },
+                ffi: ffi,
+                child: Text('$e ms'),
+              ))
+          .toList(),
+      child: Text(translate('Clipboard typing delay')),
+    );
+  }
+
   viewMode() {
     final ffiModel = ffi.ffiModel;
     final enabled = versionCmp(pi.version, '1.2.0') >= 0 && ffiModel.keyboard;
@@ -1876,6 +2021,14 @@ class _VoiceCallMenu extends StatelessWidget {
       return [
         audioInput,
         Divider(),
+        Obx(() => CkbMenuButton(
+              value: ffi.chatModel.voiceCallPushToTalk.value,
+              onChanged: (v) {
+                if (v != null) ffi.chatModel.setVoiceCallPushToTalk(v);
+              },
+              child: Text(translate('Push to talk')),
+              ffi: ffi,
+            )),
         MenuButton(
           child: Text(translate('End call')),
           onPressed: () => bind.sessionCloseVoiceCall(sessionId: ffi.sessionId),
@@ -1890,7 +2043,7 @@ class _VoiceCallMenu extends StatelessWidget {
           case VoiceCallStatus.waitingForResponse:
             return buildCallWaiting(context);
           case VoiceCallStatus.connected:
-            return _IconSubmenuButton(
+            final menu = _IconSubmenuButton(
               tooltip: 'Voice call',
               svg: 'assets/voice_call.svg',
               color: _ToolbarTheme.blueColor,
@@ -1898,6 +2051,11 @@ class _VoiceCallMenu extends StatelessWidget {
               menuChildrenGetter: menuChildrenGetter,
               ffi: ffi,
             );
+            if (!ffi.chatModel.voiceCallPushToTalk.value) return menu;
+            return Row(
+              mainAxisSize: MainAxisSize.min,
+              children: [menu, _PushToTalkButton(ffi: ffi)],
+            );
           default:
             return Offstage();
         }
@@ -1916,6 +2074,50 @@ class _VoiceCallMenu extends StatelessWidget {
   }
 }
 
+// The microphone is sent while it is held.
+class _PushToTalkButton extends StatefulWidget {
+  final FFI ffi;
+  const _PushToTalkButton({Key? key, required this.ffi}) : super(key: key);
+
+  @override
+  State<_PushToTalkButton> createState() => _PushToTalkButtonState();
+}
+
+class _PushToTalkButtonState extends State<_PushToTalkButton> {
+  bool talking = false;
+
+  void setTalking(bool value) {
+    if (talking == value) return;
+    setState(() => talking = value);
+    widget.ffi.chatModel.setVoiceCallTalking(value);
+  }
+
+  @override
+  void dispose() {
+    if (talking) widget.ffi.chatModel.setVoiceCallTalking(false);
+    super.dispose();
+  }
+
+  @override
+  Widget build(BuildContext context) {
+    return Listener(
+      onPointerDown: (_) => setTalking(true),
+      onPointerUp: (_) => setTalking(false),
+      onPointerCancel: (_) => setTalking(false),
+      child: _IconMenuButton(
+        icon: Icon(talking ? Icons.mic : Icons.mic_none,
+            color: Colors.white, size: _ToolbarTheme.buttonSize * 0.75),
+        tooltip: 'Hold to talk',
+        onPressed: () {},
+        color: talking ? _ToolbarTheme.redColor : _ToolbarTheme.blueColor,
+        hoverColor: talking
+            ? _ToolbarTheme.hoverRedColor
+            : _ToolbarTheme.hoverBlueColor,
+      ),
+    );
+  }
+}
+
 class _RecordMenu extends StatelessWidget {
   const _RecordMenu({Key? key}) : super(key: key);
 
@@ -1942,6 +2144,23 @@ class _RecordMenu extends StatelessWidget {
   }
 }
 
+class _ServerRecordIndicator extends StatelessWidget {
+  const _ServerRecordIndicator({Key? key}) : super(key: key);
+
+  @override
+  Widget build(BuildContext context) {
+    var recordingModel = Provider.of<RecordingModel>(context);
+    if (!recordingModel.serverStart) return Offstage();
+    return _IconMenuButton(
+      assetName: 'assets/rec.svg',
+      tooltip: 'server_record_tip',
+      onPressed: null,
+      color: _ToolbarTheme.redColor,
+      hoverColor: _ToolbarTheme.hoverRedColor,
+    );
+  }
+}
+
 class _CloseMenu extends StatelessWidget {
   final String id;
   final FFI ffi;
@@ -191,6 +191,19 @@ class _FileManagerPageState extends State<FileManagerPage> {
                         ],
                       ),
                       value: "hidden",
+                    ),
+                    PopupMenuItem(
+                      enabled:
+                          currentFileController.lastTrashedJobIds.isNotEmpty,
+                      child: Row(
+                        children: [
+                          Icon(Icons.undo,
+                              color: Theme.of(context).iconTheme.color),
+                          SizedBox(width: 5),
+                          Text(translate("Undo delete"))
+                        ],
+                      ),
+                      value: "undo",
                     )
                   ];
                 },
@@ -254,6 +267,8 @@ class _FileManagerPageState extends State<FileManagerPage> {
                     });
                   } else if (v == "hidden") {
                     currentFileController.toggleShowHidden();
+                  } else if (v == "undo") {
+                    currentFileController.undoRemove();
                   }
                 }),
           ],
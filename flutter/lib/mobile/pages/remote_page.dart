@@ -27,12 +27,19 @@ import '../widgets/dialog.dart';
 final initText = '1' * 1024;
 
 class RemotePage extends StatefulWidget {
-  RemotePage({Key? key, required this.id, this.password, this.isSharedPassword})
+  RemotePage(
+      {Key? key,
+      required this.id,
+      this.password,
+      this.isSharedPassword,
+      this.switchUuid})
       : super(key: key);
 
   final String id;
   final String? password;
   final bool? isSharedPassword;
+  // the ticket of a session handed off by another device
+  final String? switchUuid;
 
   @override
   State<RemotePage> createState() => _RemotePageState(id);
@@ -76,6 +83,7 @@ class _RemotePageState extends State<RemotePage> with WidgetsBindingObserver {
       widget.id,
       password: widget.password,
       isSharedPassword: widget.isSharedPassword,
+      switchUuid: widget.switchUuid,
     );
     WidgetsBinding.instance.addPostFrameCallback((_) {
       SystemChrome.setEnabledSystemUIMode(SystemUiMode.manual, overlays: []);
@@ -400,10 +408,15 @@ class _RemotePageState extends State<RemotePage> with WidgetsBindingObserver {
                                 color: MyTheme.canvasColor,
                                 child: inputModel.isPhysicalMouse.value
                                     ? getBodyForMobile()
-                                    : RawTouchGestureDetectorRegion(
-                                        child: getBodyForMobile(),
-                                        ffi: gFFI,
-                                      ),
+                                    : gFFI.ffiModel.nativeTouch
+                                        ? RawTouchForwardRegion(
+                                            inputModel: inputModel,
+                                            child: getBodyForMobile(),
+                                          )
+                                        : RawTouchGestureDetectorRegion(
+                                            child: getBodyForMobile(),
+                                            ffi: gFFI,
+                                          ),
                               );
                             }),
                           ),
@@ -545,6 +558,21 @@ class _RemotePageState extends State<RemotePage> with WidgetsBindingObserver {
               right: 10,
               child: QualityMonitor(gFFI.qualityMonitorModel),
             ),
+            Positioned(
+              bottom: 10,
+              right: 10,
+              child: SystemStatsPanel(gFFI),
+            ),
+            Positioned(
+              bottom: 10,
+              left: 10,
+              child: CameraPanel(gFFI),
+            ),
+            Positioned(
+              top: 10,
+              left: 10,
+              child: BroadcastViewersIndicator(gFFI),
+            ),
             KeyHelpTools(
                 keyboardIsVisible: keyboardIsVisible,
                 showGestureHelp: _showGestureHelp),
@@ -666,6 +666,16 @@ class ConnectionManager extends StatelessWidget {
                           translate("android_new_connection_tip"),
                           style: Theme.of(context).textTheme.bodyMedium,
                         ).marginOnly(bottom: 5),
+                  if (!client.authorized && client.reason.isNotEmpty)
+                    Text(
+                      '${translate('Access reason')}: ${client.reason}',
+                      style: Theme.of(context).textTheme.bodyMedium,
+                    ).marginOnly(bottom: 5),
+                  if (!client.authorized && client.deviceKey.isNotEmpty)
+                    Text(
+                      '${translate('New device')}: ${client.deviceKey}',
+                      style: Theme.of(context).textTheme.bodyMedium,
+                    ).marginOnly(bottom: 5),
                   client.authorized
                       ? _buildDisconnectButton(client)
                       : _buildNewConnectionHint(serverModel, client),
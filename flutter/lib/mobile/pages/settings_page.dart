@@ -786,6 +786,15 @@ class _SettingsState extends State<SettingsPage> with WidgetsBindingObserver {
                     child: Text(_fingerprint),
                   ),
                   leading: Icon(Icons.fingerprint)),
+            if (isAndroid && _fingerprint.isNotEmpty)
+              SettingsTile(
+                  title: Text(translate("Short authentication string")),
+                  value: Padding(
+                    padding: EdgeInsets.symmetric(vertical: 8),
+                    child: Text(
+                        bind.mainFingerprintToSas(fingerprint: _fingerprint)),
+                  ),
+                  leading: Icon(Icons.pin_outlined)),
             SettingsTile(
               title: Text(translate("Privacy Statement")),
               onPressed: (context) =>
@@ -3,6 +3,7 @@ import 'dart:async';
 import 'package:dash_chat_2/dash_chat_2.dart';
 import 'package:desktop_multi_window/desktop_multi_window.dart';
 import 'package:draggable_float_widget/draggable_float_widget.dart';
+import 'package:file_picker/file_picker.dart';
 import 'package:flutter/material.dart';
 import 'package:flutter/services.dart';
 import 'package:flutter_hbb/common/shared_state.dart';
@@ -66,10 +67,14 @@ class ChatModel with ChangeNotifier {
   final Rx<VoiceCallStatus> _voiceCallStatus = Rx(VoiceCallStatus.notStarted);
 
   Rx<VoiceCallStatus> get voiceCallStatus => _voiceCallStatus;
+  // the microphone sent only while the button of push-to-talk is held
+  final RxBool voiceCallPushToTalk = false.obs;
 
   TextEditingController textController = TextEditingController();
   RxInt mobileUnreadSum = 0.obs;
   MessageKey? latestReceivedKey;
+  // of the messages sent, to match the receipts of the peers
+  int _nextMessageId = 1;
 
   Offset chatWindowPosition = Offset(20, 80);
 
@@ -344,7 +349,7 @@ class ChatModel with ChangeNotifier {
     mobileClearClientUnread(key.connId);
   }
 
-  receive(int id, String text) async {
+  receive(int id, String text, {String? path, bool image = false}) async {
     final session = parent.target;
     if (session == null) {
       debugPrint("Failed to receive msg, session state is null");
@@ -430,8 +435,15 @@ class ChatModel with ChangeNotifier {
       }
       chatUser = ChatUser(id: client.peerId, firstName: client.name);
     }
-    insertMessage(messagekey,
-        ChatMessage(text: text, user: chatUser, createdAt: DateTime.now()));
+    insertMessage(
+        messagekey,
+        ChatMessage(
+            text: text,
+            user: chatUser,
+            createdAt: DateTime.now(),
+            customProperties: path == null
+                ? null
+                : {kChatAttachmentPath: path, kChatAttachmentImage: image}));
     if (id == clientModeID || _currentKey.peerId.isEmpty) {
       // client or invalid
       _currentKey = messagekey;
@@ -447,17 +459,81 @@ class ChatModel with ChangeNotifier {
       return;
     }
     message.text = trimmedText;
+    // the web client gets no receipt
+    final id = isWeb ? 0 : _nextMessageId++;
+    if (id != 0) {
+      message.customProperties = {
+        kChatMessageId: id,
+        kChatMessageStatus: kChatStatusSending
+      };
+    }
     insertMessage(_currentKey, message);
     if (_currentKey.connId == clientModeID && parent.target != null) {
-      bind.sessionSendChat(sessionId: sessionId, text: message.text);
+      bind.sessionSendChat(sessionId: sessionId, id: id, text: message.text);
     } else {
-      bind.cmSendChat(connId: _currentKey.connId, msg: message.text);
+      bind.cmSendChat(connId: _currentKey.connId, id: id, msg: message.text);
     }
 
     notifyListeners();
     inputNode.requestFocus();
   }
 
+  pickAttachment() async {
+    final result = await FilePicker.platform.pickFiles();
+    final path = result?.files.single.path;
+    if (path != null) {
+      await sendAttachment(path);
+    }
+  }
+
+  sendAttachment(String path) async {
+    final key = _currentKey;
+    final id = _nextMessageId++;
+    final name = path.split(RegExp(r'[/\\]')).last;
+    final message = ChatMessage(
+        text: name,
+        user: me,
+        createdAt: DateTime.now(),
+        customProperties: {
+          kChatMessageId: id,
+          kChatMessageStatus: kChatStatusSending,
+          kChatAttachmentPath: path,
+          kChatAttachmentImage: kChatImageExts
+              .contains(name.split('.').last.toLowerCase()),
+        });
+    insertMessage(key, message);
+    notifyListeners();
+    final String error;
+    if (key.connId == clientModeID && parent.target != null) {
+      error = await bind.sessionSendChatAttachment(
+          sessionId: sessionId, id: id, path: path);
+    } else {
+      error = await bind.cmSendChatAttachment(
+          connId: key.connId, id: id, path: path);
+    }
+    if (error.isNotEmpty) {
+      onReceipt(key.connId, id, error);
+    }
+  }
+
+  // the peer of the connection got the message, or failed to
+  onReceipt(int connId, int id, String error) {
+    for (final entry in _messages.entries) {
+      if (entry.key.connId != connId) continue;
+      final message = entry.value.chatMessages.firstWhereOrNull(
+          (m) => m.customProperties?[kChatMessageId] == id);
+      if (message != null) {
+        message.customProperties![kChatMessageStatus] =
+            error.isEmpty ? kChatStatusDelivered : kChatStatusFailed;
+        if (error.isNotEmpty) {
+          message.customProperties![kChatMessageError] = error;
+        }
+        notifyListeners();
+        return;
+      }
+    }
+  }
+
   insertMessage(MessageKey key, ChatMessage message) {
     updateConnIdOfKey(key);
     if (!_messages.containsKey(key)) {
@@ -528,6 +604,8 @@ class ChatModel with ChangeNotifier {
 
   void onVoiceCallStarted() {
     _voiceCallStatus.value = VoiceCallStatus.connected;
+    voiceCallPushToTalk.value =
+        bind.mainGetLocalOption(key: kOptionVoiceCallPushToTalk) == 'Y';
     if (isAndroid) {
       parent.target?.invokeMethod("on_voice_call_started");
     }
@@ -551,6 +629,18 @@ class ChatModel with ChangeNotifier {
   void closeVoiceCall() {
     bind.sessionCloseVoiceCall(sessionId: sessionId);
   }
+
+  Future<void> setVoiceCallPushToTalk(bool on) async {
+    voiceCallPushToTalk.value = on;
+    await bind.mainSetLocalOption(
+        key: kOptionVoiceCallPushToTalk, value: on ? 'Y' : '');
+    await setVoiceCallTalking(!on);
+  }
+
+  Future<void> setVoiceCallTalking(bool talking) async {
+    await bind.sessionSetVoiceCallTalking(
+        sessionId: sessionId, talking: talking);
+  }
 }
 
 enum VoiceCallStatus {
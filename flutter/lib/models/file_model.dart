@@ -1,5 +1,6 @@
 import 'dart:async';
 import 'dart:convert';
+import 'dart:typed_data';
 
 import 'package:flutter/material.dart';
 import 'package:flutter_hbb/common.dart';
@@ -43,6 +44,7 @@ class FileModel {
   // late final String sessionId;
   late final FileFetcher fileFetcher;
   late final JobController jobController;
+  late final SyncJobController syncJobController;
 
   late final FileController localController;
   late final FileController remoteController;
@@ -57,6 +59,7 @@ class FileModel {
     getDialogManager = () => parent.target?.dialogManager;
     fileFetcher = FileFetcher(getSessionID);
     jobController = JobController(getSessionID, getDialogManager);
+    syncJobController = SyncJobController(getSessionID, jobController);
     localController = FileController(
         isLocal: true,
         getSessionID: getSessionID,
@@ -78,9 +81,13 @@ class FileModel {
     await evtLoop.onReady();
     if (!isWeb) await localController.onReady();
     await remoteController.onReady();
+    syncJobController.onReady();
   }
 
   Future<void> close() async {
+    syncJobController.close();
+    _searches.clear();
+    _ranges.clear();
     await evtLoop.close();
     parent.target?.dialogManager.dismissAll();
     await localController.close();
@@ -92,6 +99,111 @@ class FileModel {
     await remoteController.refresh();
   }
 
+  /// Uploads the local files dropped on the remote desktop window, to the desktop folder of the
+  /// peer if [toDesktop], otherwise to the folder open on the remote side.
+  Future<void> uploadDroppedFiles(List<Entry> entries,
+      {required bool toDesktop}) async {
+    // the home of the peer is known once its first directory is read
+    for (var i = 0; i < 300 && remoteController.homePath.isEmpty; i++) {
+      await Future.delayed(Duration(milliseconds: 200));
+    }
+    final options = remoteController.options.value;
+    final dir = FileDirectory();
+    if (toDesktop || remoteController.directory.value.path.isEmpty) {
+      if (options.home.isEmpty) {
+        debugPrint("Failed to upload the dropped files, no remote home");
+        return;
+      }
+      dir.path = PathUtil.join(options.home, 'Desktop', options.isWindows);
+    } else {
+      dir.path = remoteController.directory.value.path;
+    }
+    final items = SelectedItems(isLocal: true);
+    for (final entry in entries) {
+      items.add(entry);
+    }
+    localController.sendFiles(items, DirectoryData(dir, options));
+  }
+
+  // the listeners of the running searches of the peer, by the ids of the searches
+  final _searches = <int, void Function(List<Entry>, bool, bool)>{};
+
+  /// Searches the names under [path] of the peer, the entries found are named relative to [path]
+  /// and streamed to [onResult] with whether the search is done and stopped at a limit.
+  int searchRemoteFiles(String path, String pattern,
+      void Function(List<Entry> entries, bool done, bool truncated) onResult) {
+    final id = JobController.jobID.next();
+    _searches[id] = onResult;
+    bind.sessionSearchRemoteFiles(
+        sessionId: sessionId,
+        actId: id,
+        path: path,
+        pattern: pattern,
+        includeHidden: remoteController.options.value.showHidden,
+        maxDepth: 0,
+        timeoutSecs: 0);
+    return id;
+  }
+
+  Future<void> cancelSearch(int id) async {
+    if (_searches.remove(id) != null) {
+      await bind.sessionCancelJob(sessionId: sessionId, actId: id);
+    }
+  }
+
+  void receiveSearchResult(Map<String, dynamic> evt) {
+    try {
+      final fd = FileDirectory.fromJson(jsonDecode(evt['value']));
+      final done = evt['done'] == 'true';
+      final onResult = done ? _searches.remove(fd.id) : _searches[fd.id];
+      onResult?.call(fd.entries, done, evt['truncated'] == 'true');
+    } catch (e) {
+      debugPrint("Failed to decode search result: $e");
+    }
+  }
+
+  // the reads of the parts of the files for the previews, by their ids
+  final _ranges = <int, Completer<FileRange>>{};
+
+  /// Reads at most [length] bytes of the file at [path] from [offset], without a transfer job.
+  /// The reader reads [kFileRangeMaxSize] at most at a time.
+  Future<FileRange> readFileRange(String path, int offset, int length,
+      {required bool isLocal}) async {
+    final id = JobController.jobID.next();
+    final completer = Completer<FileRange>();
+    _ranges[id] = completer;
+    await bind.sessionReadFileRange(
+        sessionId: sessionId,
+        actId: id,
+        path: path,
+        offset: offset,
+        length: length,
+        isRemote: !isLocal);
+    try {
+      // the older peers do not answer
+      return await completer.future.timeout(Duration(seconds: 10));
+    } finally {
+      _ranges.remove(id);
+    }
+  }
+
+  void receiveFileRange(Map<String, dynamic> evt) {
+    final completer = _ranges.remove(int.tryParse(evt['id']) ?? 0);
+    try {
+      completer?.complete(FileRange(int.parse(evt['offset']),
+          base64Decode(evt['data']), int.parse(evt['size'])));
+    } catch (e) {
+      completer?.completeError(e);
+    }
+  }
+
+  /// Fails the read of the error, returns false if the error is of another job.
+  bool failFileRange(Map<String, dynamic> evt) {
+    final completer = _ranges.remove(int.tryParse(evt['id']) ?? 0);
+    completer?.completeError(evt['err'].toString());
+    return completer != null;
+  }
+
   void receiveFileDir(Map<String, dynamic> evt) {
     if (evt['is_local'] == "false") {
       // init remote home, the remote connection will send one dir event when established. TODO opt
@@ -101,22 +213,30 @@ class FileModel {
   }
 
   Future<void> postOverrideFileConfirm(Map<String, dynamic> evt) async {
+    final id = int.tryParse(evt['id']) ?? 0;
+    if (syncJobController.isSyncTransfer(id)) {
+      // the sync job has chosen the files to overwrite
+      await bind.sessionSetConfirmOverrideFile(
+          sessionId: sessionId,
+          actId: id,
+          fileNum: int.parse(evt['file_num']),
+          needOverride: true,
+          remember: true,
+          isUpload: evt['is_upload'] == "true");
+      return;
+    }
     evtLoop.pushEvent(
         _FileDialogEvent(WeakReference(this), FileDialogType.overwrite, evt));
   }
 
   Future<void> overrideFileConfirm(Map<String, dynamic> evt,
-      {bool? overrideConfirm, bool skip = false}) async {
-    // If `skip == true`, it means to skip this file without showing dialog.
-    // Because `resp` may be null after the user operation or the last remembered operation,
-    // and we should distinguish them.
-    final resp = overrideConfirm ??
-        (!skip
-            ? await showFileConfirmDialog(translate("Overwrite"),
-                "${evt['read_path']}", true, evt['is_identical'] == "true")
-            : null);
+      {String? conflictPolicy}) async {
+    // The policy remembered for all conflicts is answered without showing the dialog.
+    final policy = conflictPolicy ??
+        await showFileConfirmDialog(translate("Overwrite"),
+            "${evt['read_path']}", true, evt['is_identical'] == "true");
     final id = int.tryParse(evt['id']) ?? 0;
-    if (false == resp) {
+    if (policy == null) {
       final jobIndex = jobController.getJob(id);
       if (jobIndex != -1) {
         await jobController.cancelJob(id);
@@ -124,42 +244,33 @@ class FileModel {
         job.state = JobState.done;
         jobController.jobTable.refresh();
       }
-    } else {
-      var need_override = false;
-      if (resp == null) {
-        // skip
-        need_override = false;
-      } else {
-        // overwrite
-        need_override = true;
-      }
-      // Update the loop config.
-      if (fileConfirmCheckboxRemember) {
-        evtLoop.setSkip(!need_override);
-      }
-      await bind.sessionSetConfirmOverrideFile(
-          sessionId: sessionId,
-          actId: id,
-          fileNum: int.parse(evt['file_num']),
-          needOverride: need_override,
-          remember: fileConfirmCheckboxRemember,
-          isUpload: evt['is_upload'] == "true");
+      return;
     }
+    await bind.sessionResolveFileConflict(
+        sessionId: sessionId,
+        actId: id,
+        fileNum: int.parse(evt['file_num']),
+        policy: policy,
+        remember: fileConfirmCheckboxRemember,
+        isUpload: evt['is_upload'] == "true");
     // Update the loop config.
     if (fileConfirmCheckboxRemember) {
-      evtLoop.setOverrideConfirm(resp);
+      evtLoop.setConflictPolicy(policy);
     }
   }
 
   bool fileConfirmCheckboxRemember = false;
+  String fileConfirmPolicy = 'overwrite';
 
-  Future<bool?> showFileConfirmDialog(
+  /// Returns the conflict policy chosen, null if cancelled.
+  Future<String?> showFileConfirmDialog(
       String title, String content, bool showCheckbox, bool isIdentical) async {
     fileConfirmCheckboxRemember = false;
-    return await parent.target?.dialogManager.show<bool?>(
-        (setState, Function(bool? v) close, context) {
-      cancel() => close(false);
-      submit() => close(true);
+    fileConfirmPolicy = 'overwrite';
+    return await parent.target?.dialogManager.show<String?>(
+        (setState, Function(String? v) close, context) {
+      cancel() => close(null);
+      submit() => close(fileConfirmPolicy);
       return CustomAlertDialog(
         title: Row(
           children: [
@@ -190,6 +301,13 @@ class FileModel {
                   ],
                 ),
               ),
+              const SizedBox(height: 5),
+              for (final option in fileConflictPolicies.entries)
+                getRadio<String>(Text(translate(option.value)), option.key,
+                    fileConfirmPolicy, (v) {
+                  if (v == null) return;
+                  setState(() => fileConfirmPolicy = v);
+                }, dense: true),
               showCheckbox
                   ? CheckboxListTile(
                       contentPadding: const EdgeInsets.all(0),
@@ -213,12 +331,6 @@ class FileModel {
             onPressed: cancel,
             isOutline: true,
           ),
-          dialogButton(
-            "Skip",
-            icon: Icon(Icons.navigate_next_rounded),
-            onPressed: () => close(null),
-            isOutline: true,
-          ),
           dialogButton(
             "OK",
             icon: Icon(Icons.done_rounded),
@@ -492,13 +604,17 @@ class FileController {
           fileNum: 0,
           includeHidden: showHidden,
           isRemote: isRemoteToLocal,
-          isDir: from.isDirectory);
+          isDir: from.isDirectory,
+          conflictPolicy: '');
       debugPrint(
           "path: ${from.path}, toPath: $toPath, to: ${PathUtil.join(toPath, from.name, isWindows)}");
     }
   }
 
   bool _removeCheckboxRemember = false;
+  bool _removeToTrash = true;
+  // the delete jobs of the last removal moved to the trash
+  final lastTrashedJobIds = RxList<int>.empty(growable: true);
 
   Future<void> removeAction(SelectedItems items) async {
     _removeCheckboxRemember = false;
@@ -507,6 +623,9 @@ class FileController {
       return;
     }
     final isWindows = options.value.isWindows;
+    _removeToTrash =
+        !isWeb && !mainGetLocalBoolOptionSync(kOptionEnableDeletePermanently);
+    final jobIds = <int>[];
     await Future.forEach(items.items, (Entry item) async {
       final jobID = JobController.jobID.next();
       var title = "";
@@ -528,6 +647,7 @@ class FileController {
         dialogManager?.dismissAll();
         if (fd.entries.isEmpty) {
           var deleteJobId = jobController.addDeleteDirJob(item, !isLocal, 0);
+          jobIds.add(deleteJobId);
           final confirm = await showRemoveDialog(
               translate(
                   "Are you sure you want to delete this empty directory?"),
@@ -556,6 +676,7 @@ class FileController {
       } else {
         deleteJobId = jobController.addDeleteFileJob(item, !isLocal);
       }
+      jobIds.add(deleteJobId);
 
       for (var i = 0; i < entries.length; i++) {
         final dirShow = item.isDirectory
@@ -604,6 +725,30 @@ class FileController {
         }
       }
     });
+    if (_removeToTrash) {
+      lastTrashedJobIds.value = jobIds;
+    }
+    refresh();
+  }
+
+  /// Puts back the files the last removal moved to the trash.
+  Future<void> undoRemove() async {
+    if (lastTrashedJobIds.isEmpty) return;
+    final removeIds = Int32List.fromList(lastTrashedJobIds);
+    lastTrashedJobIds.clear();
+    bind.sessionUndoRemove(
+        sessionId: sessionId,
+        actId: JobController.jobID.next(),
+        removeIds: removeIds,
+        isRemote: !isLocal);
+    try {
+      final res = await jobController.jobResultListener.start();
+      if (res['err'] != null) {
+        showToast(translate(res['err'].toString()));
+      }
+    } catch (e) {
+      debugPrint("undo remove error: $e");
+    }
     refresh();
   }
 
@@ -631,13 +776,14 @@ class FileController {
           crossAxisAlignment: CrossAxisAlignment.start,
           children: [
             Text(content),
-            Text(
-              translate("This is irreversible!"),
-              style: const TextStyle(
-                fontWeight: FontWeight.bold,
-                color: Colors.red,
-              ),
-            ).paddingOnly(top: 20),
+            if (!_removeToTrash)
+              Text(
+                translate("This is irreversible!"),
+                style: const TextStyle(
+                  fontWeight: FontWeight.bold,
+                  color: Colors.red,
+                ),
+              ).paddingOnly(top: 20),
             showCheckbox
                 ? CheckboxListTile(
                     contentPadding: const EdgeInsets.all(0),
@@ -680,13 +826,18 @@ class FileController {
         actId: actId,
         path: path,
         isRemote: !isLocal,
-        fileNum: fileNum);
+        fileNum: fileNum,
+        trash: _removeToTrash);
   }
 
   void sendRemoveEmptyDir(String path, int fileNum, int actId) {
     history.removeWhere((element) => element.contains(path));
     bind.sessionRemoveAllEmptyDirs(
-        sessionId: sessionId, actId: actId, path: path, isRemote: !isLocal);
+        sessionId: sessionId,
+        actId: actId,
+        path: path,
+        isRemote: !isLocal,
+        trash: _removeToTrash);
   }
 
   Future<void> createDir(String path) async {
@@ -881,6 +1032,13 @@ class JobController {
       } catch (_) {}
       if (fileNum != null) job.fileNum = fileNum;
       if (speed != null) job.speed = speed;
+      try {
+        final digests = jsonDecode(evt['digests'] ?? '');
+        if (digests is Map) {
+          job.digests = digests
+              .map((k, v) => MapEntry(k.toString(), v.toString()));
+        }
+      } catch (_) {}
       job.state = JobState.done;
     }
     jobTable.refresh();
@@ -918,6 +1076,9 @@ class JobController {
         }
       }
       jobTable.refresh();
+    } else if (jobResultListener.isListening) {
+      // the undo of a removal has no job
+      jobResultListener.complete(evt);
     }
     if (err == _kOneWayFileTransferError) {
       if (DateTime.now().millisecondsSinceEpoch - _lastTimeShowMsgbox > 3000) {
@@ -957,6 +1118,36 @@ class JobController {
     await bind.sessionCancelJob(sessionId: sessionId, actId: id);
   }
 
+  void setRateLimit(int id, int kbps) {
+    final jobIndex = getJob(id);
+    if (jobIndex == -1) return;
+    bind.sessionSetJobRateLimit(sessionId: sessionId, actId: id, kbps: kbps);
+    jobTable[jobIndex].rateLimit = kbps;
+    jobTable.refresh();
+  }
+
+  void pauseJob(int jobId) {
+    final jobIndex = getJob(jobId);
+    if (jobIndex == -1) return;
+    final job = jobTable[jobIndex];
+    bind.sessionPauseJob(
+        sessionId: sessionId, actId: job.id, isRemote: job.isRemoteToLocal);
+    job.state = JobState.paused;
+    jobTable.refresh();
+  }
+
+  /// Moves the job up the queue, the jobs first in the queue are sent first under the speed
+  /// limit, and resumed first when the peer reconnects.
+  void moveJobUp(int jobId) {
+    final jobIndex = getJob(jobId);
+    if (jobIndex <= 0) return;
+    final job = jobTable.removeAt(jobIndex);
+    jobTable.insert(jobIndex - 1, job);
+    bind.sessionReorderJobs(
+        sessionId: sessionId,
+        ids: Int32List.fromList(jobTable.map((e) => e.id).toList()));
+  }
+
   void loadLastJob(Map<String, dynamic> evt) {
     debugPrint("load last job: $evt");
     Map<String, dynamic> jobDetail = json.decode(evt['value']);
@@ -966,6 +1157,14 @@ class JobController {
     bool showHidden = jobDetail['show_hidden'];
     int fileNum = jobDetail['file_num'];
     bool isRemote = jobDetail['is_remote'];
+    bool paused = jobDetail['paused'] ?? true;
+    // the job of the session dropped, which is loaded again
+    jobTable.removeWhere((e) =>
+        e.type == JobType.transfer &&
+        e.state != JobState.done &&
+        e.isRemoteToLocal == isRemote &&
+        e.remote == remote &&
+        e.to == to);
     final currJobId = JobController.jobID.next();
     String fileName = path.basename(isRemote ? remote : to);
     var jobProgress = JobProgress()
@@ -989,6 +1188,9 @@ class JobController {
       to: isRemote ? to : remote,
       fileNum: fileNum,
     );
+    if (!paused) {
+      resumeJob(currJobId);
+    }
   }
 
   void resumeJob(int jobId) {
@@ -997,6 +1199,11 @@ class JobController {
       final job = jobTable[jobIndex];
       bind.sessionResumeJob(
           sessionId: sessionId, actId: job.id, isRemote: job.isRemoteToLocal);
+      // the job is created again
+      if (job.rateLimit > 0) {
+        bind.sessionSetJobRateLimit(
+            sessionId: sessionId, actId: job.id, kbps: job.rateLimit);
+      }
       job.state = JobState.inProgress;
       jobTable.refresh();
     } else {
@@ -1162,6 +1369,240 @@ class FileFetcher {
   }
 }
 
+enum SyncDirection {
+  upload,
+  download,
+  twoWay;
+
+  String toJson() => this == twoWay ? 'two_way' : name;
+
+  static SyncDirection fromJson(String s) =>
+      s == 'two_way' ? twoWay : (s == 'download' ? download : upload);
+
+  String display() {
+    switch (this) {
+      case upload:
+        return translate('Upload only');
+      case download:
+        return translate('Download only');
+      case twoWay:
+        return translate('Two-way');
+    }
+  }
+}
+
+class SyncJobLog {
+  final DateTime time;
+  final String text;
+
+  SyncJobLog.fromJson(Map<String, dynamic> json)
+      : time = DateTime.fromMillisecondsSinceEpoch(json['time']),
+        text = json['text'];
+}
+
+class SyncJob {
+  String name = '';
+  String local = '';
+  String remote = '';
+  SyncDirection direction = SyncDirection.upload;
+  // minutes, 0 to run manually only
+  int interval = 0;
+  bool includeHidden = false;
+  List<SyncJobLog> logs = [];
+
+  SyncJob();
+
+  SyncJob.fromJson(Map<String, dynamic> json) {
+    name = json['name'];
+    local = json['local'];
+    remote = json['remote'];
+    direction = SyncDirection.fromJson(json['direction']);
+    interval = json['interval'] ?? 0;
+    includeHidden = json['include_hidden'] ?? false;
+    logs = (json['logs'] as List? ?? [])
+        .map((e) => SyncJobLog.fromJson(e))
+        .toList();
+  }
+
+  Map<String, dynamic> toJson() => {
+        'name': name,
+        'local': local,
+        'remote': remote,
+        'direction': direction.toJson(),
+        'interval': interval,
+        'include_hidden': includeHidden,
+      };
+}
+
+class SyncCopy {
+  final String name;
+  final bool upload;
+  final int size;
+  final String from;
+  final String to;
+
+  SyncCopy.fromJson(Map<String, dynamic> json)
+      : name = json['name'],
+        upload = json['upload'],
+        size = json['size'],
+        from = json['from'],
+        to = json['to'];
+}
+
+class SyncPlan {
+  List<SyncCopy> copies = [];
+  List<String> conflicts = [];
+
+  SyncPlan.fromJson(Map<String, dynamic> json) {
+    copies =
+        (json['copies'] as List).map((e) => SyncCopy.fromJson(e)).toList();
+    conflicts = List<String>.from(json['conflicts']);
+  }
+}
+
+class _SyncRun {
+  final String name;
+  int pending;
+  int failed = 0;
+
+  _SyncRun(this.name, this.pending);
+}
+
+/// Compares the folders of the sync jobs on the rust side, and copies the files of the plan with
+/// the transfers of the file manager.
+class SyncJobController {
+  final GetSessionID getSessionID;
+  final JobController jobController;
+  SessionID get sessionId => getSessionID();
+
+  final _plans = <int, Completer<SyncPlan>>{};
+  // the runs of the transfers, by the ids of the transfers
+  final _transfers = <int, _SyncRun>{};
+  Timer? _timer;
+
+  SyncJobController(this.getSessionID, this.jobController);
+
+  void onReady() {
+    _timer = Timer.periodic(Duration(minutes: 1), (_) => _runDueJobs());
+  }
+
+  void close() {
+    _timer?.cancel();
+    _timer = null;
+  }
+
+  List<SyncJob> getJobs() {
+    try {
+      return (jsonDecode(bind.sessionGetSyncJobs(sessionId: sessionId)) as List)
+          .map((e) => SyncJob.fromJson(e))
+          .toList();
+    } catch (e) {
+      debugPrint("Failed to get sync jobs: $e");
+      return [];
+    }
+  }
+
+  /// Returns the error, empty if ok.
+  String saveJob(SyncJob job) {
+    return bind.sessionSaveSyncJob(
+        sessionId: sessionId, job: jsonEncode(job.toJson()));
+  }
+
+  Future<void> removeJob(String name) async {
+    await bind.sessionRemoveSyncJob(sessionId: sessionId, name: name);
+  }
+
+  bool isRunning(String name) => _transfers.values.any((r) => r.name == name);
+
+  bool isSyncTransfer(int id) => _transfers.containsKey(id);
+
+  Future<SyncPlan> fetchPlan(String name, {required bool dryRun}) {
+    final id = JobController.jobID.next();
+    final c = Completer<SyncPlan>();
+    _plans[id] = c;
+    bind.sessionRunSyncJob(
+        sessionId: sessionId, actId: id, name: name, dryRun: dryRun);
+    // the folders of the peer may take a while to list
+    return c.future.timeout(Duration(minutes: 1), onTimeout: () {
+      _plans.remove(id);
+      throw translate('Timeout');
+    });
+  }
+
+  void onPlan(Map<String, dynamic> evt) {
+    final c = _plans.remove(int.tryParse(evt['id']) ?? 0);
+    if (c == null) return;
+    final String err = evt['error'] ?? '';
+    if (err.isNotEmpty) {
+      c.completeError(err);
+      return;
+    }
+    try {
+      c.complete(SyncPlan.fromJson(jsonDecode(evt['plan'])));
+    } catch (e) {
+      c.completeError(e);
+    }
+  }
+
+  /// Copies the files of the plan, the result is logged once all the transfers end.
+  Future<void> run(String name) async {
+    if (isRunning(name)) return;
+    final SyncPlan plan;
+    try {
+      plan = await fetchPlan(name, dryRun: false);
+    } catch (e) {
+      debugPrint("Failed to run sync job $name: $e");
+      return;
+    }
+    if (plan.copies.isEmpty) return;
+    final run = _SyncRun(name, plan.copies.length);
+    for (final copy in plan.copies) {
+      final entry = Entry()
+        ..name = copy.name
+        ..path = copy.from
+        ..size = copy.size;
+      final id = jobController.addTransferJob(entry, !copy.upload);
+      _transfers[id] = run;
+      bind.sessionSendFiles(
+          sessionId: sessionId,
+          actId: id,
+          path: copy.from,
+          to: copy.to,
+          fileNum: 0,
+          includeHidden: true,
+          isRemote: !copy.upload,
+          isDir: false,
+          // the plan has chosen the files to overwrite
+          conflictPolicy: 'overwrite');
+    }
+  }
+
+  void onTransferEnd(int id, {String? err}) {
+    final run = _transfers.remove(id);
+    if (run == null) return;
+    if (err != null && err != 'skipped') run.failed++;
+    run.pending--;
+    if (run.pending <= 0) {
+      bind.sessionLogSyncJob(
+          sessionId: sessionId,
+          name: run.name,
+          text: run.failed > 0 ? "done, ${run.failed} failed" : "done");
+    }
+  }
+
+  Future<void> _runDueJobs() async {
+    try {
+      final names = List<String>.from(
+          jsonDecode(bind.sessionGetDueSyncJobs(sessionId: sessionId)));
+      for (final name in names) {
+        await run(name);
+      }
+    } catch (e) {
+      debugPrint("Failed to run the due sync jobs: $e");
+    }
+  }
+}
+
 class FileDirectory {
   List<Entry> entries = [];
   int id = 0;
@@ -1198,6 +1639,15 @@ class FileDirectory {
   }
 }
 
+class FileRange {
+  final int offset;
+  final Uint8List data;
+  // of the whole file
+  final int size;
+
+  FileRange(this.offset, this.data, this.size);
+}
+
 class Entry {
   int entryType = 4;
   int modifiedTime = 0;
@@ -1267,6 +1717,10 @@ class JobProgress {
   var showHidden = false;
   var err = "";
   int lastTransferredSize = 0;
+  // kbps, 0 for no limit
+  var rateLimit = 0;
+  // the sha256 of the files checked by the peer, by their names
+  Map<String, String> digests = {};
 
   clear() {
     type = JobType.none;
@@ -1282,6 +1736,8 @@ class JobProgress {
     remote = "";
     to = "";
     err = "";
+    rateLimit = 0;
+    digests = {};
   }
 
   String display() {
@@ -1535,19 +1991,22 @@ List<Entry> _sortList(List<Entry> list, SortBy sortType, bool ascending) {
 /// The `_FileDialogType` and `_DialogEvent` are invisible for other models.
 enum FileDialogType { overwrite, unknown }
 
+// the conflict policies the user may answer, by their names in the option
+const fileConflictPolicies = {
+  'overwrite': 'Overwrite',
+  'newer': 'Overwrite if newer',
+  'skip': 'Skip',
+  'rename': 'Keep both',
+};
+
 class _FileDialogEvent extends BaseEvent<FileDialogType, Map<String, dynamic>> {
   WeakReference<FileModel> fileModel;
-  bool? _overrideConfirm;
-  bool _skip = false;
+  String? _conflictPolicy;
 
   _FileDialogEvent(this.fileModel, super.type, super.data);
 
-  void setOverrideConfirm(bool? confirm) {
-    _overrideConfirm = confirm;
-  }
-
-  void setSkip(bool skip) {
-    _skip = skip;
+  void setConflictPolicy(String? policy) {
+    _conflictPolicy = policy;
   }
 
   @override
@@ -1560,7 +2019,7 @@ class _FileDialogEvent extends BaseEvent<FileDialogType, Map<String, dynamic>> {
       case FileDialogType.overwrite:
         return (data) async {
           return await model.overrideFileConfirm(data,
-              overrideConfirm: _overrideConfirm, skip: _skip);
+              conflictPolicy: _conflictPolicy);
         };
       default:
         debugPrint("Unknown event type: $type with $data");
@@ -1571,31 +2030,24 @@ class _FileDialogEvent extends BaseEvent<FileDialogType, Map<String, dynamic>> {
 
 class FileDialogEventLoop
     extends BaseEventLoop<FileDialogType, Map<String, dynamic>> {
-  bool? _overrideConfirm;
-  bool _skip = false;
+  String? _conflictPolicy;
 
   @override
   Future<void> onPreConsume(
       BaseEvent<FileDialogType, Map<String, dynamic>> evt) async {
     var event = evt as _FileDialogEvent;
-    event.setOverrideConfirm(_overrideConfirm);
-    event.setSkip(_skip);
+    event.setConflictPolicy(_conflictPolicy);
     debugPrint(
-        "FileDialogEventLoop: consuming<jobId: ${evt.data['id']} overrideConfirm: $_overrideConfirm, skip: $_skip>");
+        "FileDialogEventLoop: consuming<jobId: ${evt.data['id']} conflictPolicy: $_conflictPolicy>");
   }
 
   @override
   Future<void> onEventsClear() {
-    _overrideConfirm = null;
-    _skip = false;
+    _conflictPolicy = null;
     return super.onEventsClear();
   }
 
-  void setOverrideConfirm(bool? confirm) {
-    _overrideConfirm = confirm;
-  }
-
-  void setSkip(bool skip) {
-    _skip = skip;
+  void setConflictPolicy(String? policy) {
+    _conflictPolicy = policy;
   }
 }
@@ -324,6 +324,92 @@ class ToReleaseKeys {
   }
 }
 
+/// The text input of the remote view in the legacy mode, for the input methods like CJK.
+/// The composition and the commit string are sent instead of the keys typed into it.
+class ImeInputClient extends TextInputClient {
+  final SessionID sessionId;
+  TextInputConnection? _connection;
+  TextEditingValue _value = TextEditingValue.empty;
+  String _composition = '';
+
+  ImeInputClient(this.sessionId);
+
+  bool get isAttached => _connection?.attached == true;
+
+  /// The key is handled by the input method, not sent to the peer.
+  bool isTypedInto(LogicalKeyboardKey key) =>
+      isAttached &&
+      (_composition.isNotEmpty || key == LogicalKeyboardKey.process);
+
+  void attach() {
+    if (isAttached) return;
+    _value = TextEditingValue.empty;
+    _connection = TextInput.attach(
+        this,
+        const TextInputConfiguration(
+            autocorrect: false,
+            enableSuggestions: false,
+            inputAction: TextInputAction.none));
+    _connection!.setEditingState(_value);
+    _connection!.show();
+  }
+
+  void detach() {
+    _connection?.close();
+    _connection = null;
+    _setComposition('');
+  }
+
+  void _setComposition(String text) {
+    if (text == _composition) return;
+    _composition = text;
+    bind.sessionInputIme(sessionId: sessionId, text: text, commit: false);
+  }
+
+  @override
+  TextEditingValue? get currentTextEditingValue => _value;
+
+  @override
+  AutofillScope? get currentAutofillScope => null;
+
+  @override
+  void updateEditingValue(TextEditingValue value) {
+    if (value.isComposingRangeValid) {
+      _value = value;
+      _setComposition(value.composing.textInside(value.text));
+      return;
+    }
+    if (value.text.isNotEmpty) {
+      // Ends the composition on the peer.
+      _composition = '';
+      bind.sessionInputIme(sessionId: sessionId, text: value.text, commit: true);
+    } else {
+      _setComposition('');
+    }
+    // The committed text is on the peer, start over.
+    _value = TextEditingValue.empty;
+    _connection?.setEditingState(_value);
+  }
+
+  @override
+  void performAction(TextInputAction action) {}
+
+  @override
+  void performPrivateCommand(String action, Map<String, dynamic> data) {}
+
+  @override
+  void updateFloatingCursor(RawFloatingCursorPoint point) {}
+
+  @override
+  void showAutocorrectionPromptRect(int start, int end) {}
+
+  @override
+  void connectionClosed() {
+    _connection = null;
+    _setComposition('');
+  }
+}
+
 class InputModel {
   final WeakReference<FFI> parent;
   String keyboardMode = '';
@@ -336,6 +422,7 @@ class InputModel {
 
   final ToReleaseRawKeys toReleaseRawKeys = ToReleaseRawKeys();
   final ToReleaseKeys toReleaseKeys = ToReleaseKeys();
+  late final ImeInputClient _imeInput = ImeInputClient(sessionId);
 
   // trackpad
   var _trackpadLastDelta = Offset.zero;
@@ -354,6 +441,9 @@ class InputModel {
   // mouse
   final isPhysicalMouse = false.obs;
   int _lastButtons = 0;
+  bool _penInRange = false;
+  // The fingers on the screen in native touch, by the pointer id.
+  final Map<int, Offset> _touchPoints = {};
   Offset lastMousePos = Offset.zero;
 
   bool _queryOtherWindowCoords = false;
@@ -478,6 +568,10 @@ class InputModel {
     }
 
     final key = e.logicalKey;
+    if (e is RawKeyDownEvent && _imeInput.isTypedInto(key)) {
+      // Sent as the text when it is committed.
+      return KeyEventResult.ignored;
+    }
     if (e is RawKeyDownEvent) {
       if (!e.repeat) {
         if (e.isAltPressed && !alt) {
@@ -538,6 +632,10 @@ class InputModel {
       }
     }
 
+    if (e is! KeyUpEvent && _imeInput.isTypedInto(e.logicalKey)) {
+      // Sent as the text when it is committed.
+      return KeyEventResult.ignored;
+    }
     if (e is KeyUpEvent) {
       handleKeyUpEventModifiers(e);
     } else if (e is KeyDownEvent) {
@@ -824,8 +922,20 @@ class InputModel {
     if (!isInputSourceFlutter) {
       bind.sessionEnterOrLeave(sessionId: sessionId, enter: enter);
     }
+    if (enter &&
+        isDesktop &&
+        isInputSourceFlutter &&
+        keyboardMode == kKeyLegacyMode &&
+        keyboardPerm &&
+        !isViewOnly) {
+      _imeInput.attach();
+    } else {
+      _imeInput.detach();
+    }
     if (!isWeb && enter) {
       bind.setCurSessionId(sessionId: sessionId);
+      // The windows share the keyboard hook.
+      bind.mainSetFullscreen(fullscreen: stateGlobal.fullscreen.isTrue);
     }
   }
 
@@ -842,6 +952,10 @@ class InputModel {
   void onPointHoverImage(PointerHoverEvent e) {
     _stopFling = true;
     if (isViewOnly) return;
+    if (isPenEvent(e)) {
+      handlePen(e);
+      return;
+    }
     if (e.kind != ui.PointerDeviceKind.mouse) return;
     if (!isPhysicalMouse.value) {
       isPhysicalMouse.value = true;
@@ -992,6 +1106,10 @@ class InputModel {
     _remoteWindowCoords = [];
     _windowRect = null;
     if (isViewOnly) return;
+    if (isPenEvent(e)) {
+      handlePen(e);
+      return;
+    }
     if (e.kind != ui.PointerDeviceKind.mouse) {
       if (isPhysicalMouse.value) {
         isPhysicalMouse.value = false;
@@ -1005,6 +1123,10 @@ class InputModel {
   void onPointUpImage(PointerUpEvent e) {
     if (isDesktop) _queryOtherWindowCoords = false;
     if (isViewOnly) return;
+    if (isPenEvent(e)) {
+      handlePen(e);
+      return;
+    }
     if (e.kind != ui.PointerDeviceKind.mouse) return;
     if (isPhysicalMouse.value) {
       handleMouse(_getMouseEvent(e, _kMouseEventUp), e.position);
@@ -1013,6 +1135,10 @@ class InputModel {
 
   void onPointMoveImage(PointerMoveEvent e) {
     if (isViewOnly) return;
+    if (isPenEvent(e)) {
+      handlePen(e);
+      return;
+    }
     if (e.kind != ui.PointerDeviceKind.mouse) return;
     if (_queryOtherWindowCoords) {
       Future.delayed(Duration.zero, () async {
@@ -1025,6 +1151,97 @@ class InputModel {
     }
   }
 
+  void onNativeTouch(PointerEvent e) {
+    if (isViewOnly) return;
+    if (e.kind != ui.PointerDeviceKind.touch) return;
+    if (e is PointerDownEvent || e is PointerMoveEvent) {
+      _touchPoints[e.pointer] = e.position;
+    } else if (e is PointerUpEvent || e is PointerCancelEvent) {
+      _touchPoints.remove(e.pointer);
+    } else {
+      return;
+    }
+    final points = [];
+    for (final p in _touchPoints.entries) {
+      final pos = handlePointerDevicePos(kPointerEventKindTouch, p.value.dx,
+          max(0.0, p.value.dy), false, kMouseEventTypeDefault);
+      if (pos == null) continue;
+      points.add({'id': p.key, 'x': pos.x, 'y': pos.y});
+    }
+    bind.sessionSendPointer(
+        sessionId: sessionId,
+        msg: json.encode(
+            PointerEventToRust(kPointerEventKindTouch, 'points', points)
+                .toJson()));
+  }
+
+  void onPointCancelImage(PointerCancelEvent e) {
+    if (isViewOnly) return;
+    if (isPenEvent(e)) {
+      leavePen(e);
+    }
+  }
+
+  /// Pen events are sent with pressure and tilt if the peer can inject them,
+  /// otherwise they are handled as touch events.
+  bool isPenEvent(PointerEvent e) =>
+      (e.kind == ui.PointerDeviceKind.stylus ||
+          e.kind == ui.PointerDeviceKind.invertedStylus) &&
+      parent.target?.ffiModel.pi.features.pen == true;
+
+  void leavePen(PointerEvent e) {
+    if (_penInRange) {
+      handlePen(e, inRange: false);
+    }
+  }
+
+  void handlePen(PointerEvent e, {bool inRange = true}) {
+    double x = e.position.dx;
+    double y = max(0.0, e.position.dy);
+    if (_checkPeerControlProtected(x, y)) {
+      return;
+    }
+    final isUp = e is PointerUpEvent || !inRange;
+    final pos = handlePointerDevicePos(
+      kPointerEventKindPen,
+      x,
+      y,
+      e is PointerHoverEvent || e is PointerMoveEvent,
+      isUp ? kMouseEventTypeUp : kMouseEventTypeDefault,
+    );
+    if (pos == null) {
+      return;
+    }
+    final contact = inRange && e.down;
+    var pressure = 0.0;
+    if (contact) {
+      pressure = e.pressureMax > e.pressureMin
+          ? (e.pressure - e.pressureMin) / (e.pressureMax - e.pressureMin)
+          : 1.0;
+    }
+    // [PointerEvent.orientation] is 0 when the pen points up, clockwise.
+    final tanTilt = tan(e.tilt);
+    final tiltX = atan(tanTilt * sin(e.orientation)) * 180 / pi;
+    final tiltY = -atan(tanTilt * cos(e.orientation)) * 180 / pi;
+    var buttons = 0;
+    if (e.buttons & kPrimaryStylusButton != 0) buttons |= 1;
+    if (e.buttons & kSecondaryStylusButton != 0) buttons |= 2;
+    if (e.kind == ui.PointerDeviceKind.invertedStylus) buttons |= 4;
+    _penInRange = inRange;
+    final evt = PointerEventToRust(kPointerEventKindPen, '', {
+      'x': pos.x,
+      'y': pos.y,
+      'pressure': (pressure.clamp(0.0, 1.0) * 1024).round(),
+      'tilt_x': tiltX.round(),
+      'tilt_y': tiltY.round(),
+      'buttons': buttons,
+      'contact': contact,
+      'in_range': inRange,
+    }).toJson();
+    bind.sessionSendPointer(
+        sessionId: sessionId, msg: json.encode(modify(evt)));
+  }
+
   static Future<Rect?> fillRemoteCoordsAndGetCurFrame(
       List<RemoteWindowCoords> remoteWindowCoords) async {
     final coords =
@@ -1391,7 +1608,7 @@ class InputModel {
     evtX = InputModel.tryGetNearestRange(evtX, minX, maxX, 5);
     evtY = InputModel.tryGetNearestRange(evtY, minY, maxY, 5);
     if (isLocalDesktop) {
-      if (kind == kPointerEventKindMouse) {
+      if (kind == kPointerEventKindMouse || kind == kPointerEventKindPen) {
         if (evtX < minX || evtY < minY || evtX > maxX || evtY > maxY) {
           // If left mouse up, no early return.
           if (!(buttons == kPrimaryMouseButton &&
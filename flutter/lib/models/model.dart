@@ -108,6 +108,7 @@ class FfiModel with ChangeNotifier {
   bool? _secure;
   bool? _direct;
   bool _touchMode = false;
+  bool _nativeTouch = false;
   Timer? _timer;
   var _reconnects = 1;
   bool _viewOnly = false;
@@ -118,6 +119,14 @@ class FfiModel with ChangeNotifier {
   Timer? waitForImageTimer;
   RxBool waitForFirstImage = true.obs;
   bool isRefreshing = false;
+  // the master volume of the peer from 0 to 100, negative until it replies
+  RxInt remoteVolume = (-1).obs;
+  RxBool remoteMute = false.obs;
+  // the control of the input when the peer lets one connection hold it
+  RxBool inputControlEnabled = false.obs;
+  RxBool inputControlHolding = false.obs;
+  RxString inputControlHolder = ''.obs;
+  RxBool inputControlRequested = false.obs;
 
   Rect? get rect => _rect;
   bool get isOriginalResolutionSet =>
@@ -143,6 +152,9 @@ class FfiModel with ChangeNotifier {
 
   bool get touchMode => _touchMode;
 
+  /// Send the raw touch points instead of the gestures, if the peer can inject them.
+  bool get nativeTouch => _nativeTouch && _pi.features.touch;
+
   bool get isPeerAndroid => _pi.platform == kPeerPlatformAndroid;
   bool get isPeerMobile => isPeerAndroid;
 
@@ -192,6 +204,11 @@ class FfiModel with ChangeNotifier {
     }
   }
 
+  setNativeTouch(bool v) {
+    _nativeTouch = v;
+    notifyListeners();
+  }
+
   updatePermission(Map<String, dynamic> evt, String id) {
     evt.forEach((k, v) {
       if (k == 'name' || k.isEmpty) return;
@@ -286,6 +303,13 @@ class FfiModel with ChangeNotifier {
       } else if (name == 'connection_ready') {
         setConnectionType(
             peerId, evt['secure'] == 'true', evt['direct'] == 'true');
+      } else if (name == 'connection_degraded') {
+        try {
+          ConnectionTypeState.find(peerId)
+              .setDegraded(evt['degraded'] == 'true');
+        } catch (e) {
+          //
+        }
       } else if (name == 'switch_display') {
         // switch display is kept for backward compatibility
         handleSwitchDisplay(evt, sessionId, peerId);
@@ -302,13 +326,26 @@ class FfiModel with ChangeNotifier {
       } else if (name == 'permission') {
         updatePermission(evt, peerId);
       } else if (name == 'chat_client_mode') {
-        parent.target?.chatModel
-            .receive(ChatModel.clientModeID, evt['text'] ?? '');
+        parent.target?.chatModel.receive(
+            ChatModel.clientModeID, evt['text'] ?? '',
+            path: evt['path'], image: evt['image'] == 'true');
       } else if (name == 'chat_server_mode') {
-        parent.target?.chatModel
-            .receive(int.parse(evt['id'] as String), evt['text'] ?? '');
+        parent.target?.chatModel.receive(
+            int.parse(evt['id'] as String), evt['text'] ?? '',
+            path: evt['path'], image: evt['image'] == 'true');
+      } else if (name == 'chat_receipt') {
+        parent.target?.chatModel.onReceipt(
+            evt['id'] != null
+                ? int.parse(evt['id'] as String)
+                : ChatModel.clientModeID,
+            int.parse(evt['msg_id'] as String),
+            evt['error'] ?? '');
       } else if (name == 'file_dir') {
         parent.target?.fileModel.receiveFileDir(evt);
+      } else if (name == 'file_search_result') {
+        parent.target?.fileModel.receiveSearchResult(evt);
+      } else if (name == 'file_range') {
+        parent.target?.fileModel.receiveFileRange(evt);
       } else if (name == 'job_progress') {
         parent.target?.fileModel.jobController.tryUpdateJobProgress(evt);
       } else if (name == 'job_done') {
@@ -319,8 +356,16 @@ class FfiModel with ChangeNotifier {
           // todo: refresh may not work when confirm delete local directory
           parent.target?.fileModel.refreshAll();
         }
+        parent.target?.fileModel.syncJobController
+            .onTransferEnd(int.tryParse(evt['id']) ?? 0);
       } else if (name == 'job_error') {
-        parent.target?.fileModel.jobController.jobError(evt);
+        if (parent.target?.fileModel.failFileRange(evt) != true) {
+          parent.target?.fileModel.jobController.jobError(evt);
+          parent.target?.fileModel.syncJobController
+              .onTransferEnd(int.tryParse(evt['id']) ?? 0, err: evt['err']);
+        }
+      } else if (name == 'sync_job_plan') {
+        parent.target?.fileModel.syncJobController.onPlan(evt);
       } else if (name == 'override_file_confirm') {
         parent.target?.fileModel.postOverrideFileConfirm(evt);
       } else if (name == 'load_last_job') {
@@ -366,6 +411,10 @@ class FfiModel with ChangeNotifier {
         parent.target?.chatModel.onVoiceCallIncoming();
       } else if (name == 'update_voice_call_state') {
         parent.target?.serverModel.updateVoiceCallState(evt);
+      } else if (name == 'update_input_control_state') {
+        parent.target?.serverModel.updateInputControlState(evt);
+      } else if (name == 'update_annotation_state') {
+        parent.target?.serverModel.updateAnnotationState(evt);
       } else if (name == 'fingerprint') {
         FingerprintState.find(peerId).value = evt['fingerprint'] ?? '';
       } else if (name == 'plugin_manager') {
@@ -404,6 +453,49 @@ class FfiModel with ChangeNotifier {
         if (desktopType == DesktopType.remote || isMobile) {
           parent.target?.recordingModel.updateStatus(evt['start'] == 'true');
         }
+      } else if (name == "server_record_status") {
+        parent.target?.recordingModel.updateServerStatus(evt['on'] == 'true');
+      } else if (name == "inactivity_warning") {
+        showInactivityWarning(
+            sessionId,
+            int.tryParse(evt['seconds'] ?? '') ?? 0,
+            evt['view_only'] == 'true',
+            parent.target!.dialogManager);
+      } else if (name == "remote_volume") {
+        remoteVolume.value = int.tryParse(evt['level'] ?? '') ?? -1;
+        remoteMute.value = evt['mute'] == 'true';
+      } else if (name == "input_control") {
+        updateInputControl(evt);
+      } else if (name == "session_handoff") {
+        showSessionHandoff(
+            sessionId,
+            evt['link'] ?? '',
+            int.tryParse(evt['timeout'] ?? '') ?? 0,
+            evt['error'] ?? '',
+            parent.target!.dialogManager);
+      } else if (name == "annotation") {
+        parent.target?.annotationModel.update(evt);
+      } else if (name == "system_stats") {
+        parent.target?.systemStatsModel.update(evt);
+      } else if (name == "process_list") {
+        parent.target?.taskManagerModel.update(evt);
+      } else if (name == "process_killed") {
+        parent.target?.taskManagerModel.onKilled(evt);
+      } else if (name == "service_list") {
+        parent.target?.serviceManagerModel.update(evt);
+      } else if (name == "service_control_result") {
+        parent.target?.serviceManagerModel.onControlled(evt);
+      } else if (name == "camera_list") {
+        parent.target?.cameraModel.updateList(evt);
+      } else if (name == "camera_frame") {
+        parent.target?.cameraModel.updateFrame(evt);
+      } else if (name == "print_job") {
+        showPrintJob(evt, parent.target!.dialogManager);
+      } else if (name == "broadcast_viewers") {
+        parent.target?.broadcastViewers.value =
+            int.tryParse(evt['count'] ?? '') ?? 0;
+        parent.target?.broadcastMaxViewers.value =
+            int.tryParse(evt['max'] ?? '') ?? 0;
       } else {
         debugPrint('Event is not handled in the fixed branch: $name');
       }
@@ -572,6 +664,8 @@ class FfiModel with ChangeNotifier {
       wrongPasswordDialog(sessionId, dialogManager, type, title, text);
     } else if (type == 'input-2fa') {
       enter2FaDialog(sessionId, dialogManager);
+    } else if (type == 'input-access-reason') {
+      enterAccessReasonDialog(sessionId, dialogManager);
     } else if (type == 'input-password') {
       enterPasswordDialog(sessionId, dialogManager);
     } else if (type == 'session-login' || type == 'session-re-login') {
@@ -781,6 +875,15 @@ class FfiModel with ChangeNotifier {
     // This call is to ensuer the keyboard mode is updated depending on the peer version.
     parent.target?.inputModel.updateKeyboardMode();
 
+    // The connection is new, it is asked for the stats again.
+    if (!isCache) {
+      parent.target?.systemStatsModel.rewatch(sessionId);
+      parent.target?.cameraModel.rerequest(sessionId);
+      if (parent.target?.remotePrinter.value == true) {
+        bind.sessionRemotePrinter(sessionId: sessionId, on: true);
+      }
+    }
+
     // Map clone is required here, otherwise "evt" may be changed by other threads through the reference.
     // Because this function is asynchronous, there's an "await" in this function.
     cachedPeerData.peerInfo = {...evt};
@@ -821,6 +924,8 @@ class FfiModel with ChangeNotifier {
               sessionId: sessionId, arg: kOptionTouchMode) !=
           '';
     }
+    _nativeTouch = bind.sessionGetToggleOptionSync(
+        sessionId: sessionId, arg: kOptionNativeTouch);
     if (connType == ConnType.fileTransfer) {
       parent.target?.fileModel.onReady();
     } else if (connType == ConnType.defaultConn) {
@@ -842,6 +947,9 @@ class FfiModel with ChangeNotifier {
       }
       Map<String, dynamic> features = json.decode(evt['features']);
       _pi.features.privacyMode = features['privacy_mode'] == true;
+      _pi.features.gamepad = features['gamepad'] == true;
+      _pi.features.pen = features['pen'] == true;
+      _pi.features.touch = features['touch'] == true;
       if (!isCache) {
         handleResolutions(peerId, evt["resolutions"]);
       }
@@ -875,9 +983,36 @@ class FfiModel with ChangeNotifier {
 
     if (!isCache) {
       tryUseAllMyDisplaysForTheRemoteSession(peerId);
+      tryRestoreLastDisplay(peerId);
     }
   }
 
+  // The display chosen in the last session, unless the displays are shown in their own windows.
+  tryRestoreLastDisplay(String peerId) async {
+    if (parent.target?.connType != ConnType.defaultConn ||
+        _pi.displays.length <= 1 ||
+        bind.peerGetDefaultSessionsCount(id: peerId) > 1 ||
+        bind.sessionGetUseAllMyDisplaysForTheRemoteSession(
+                sessionId: sessionId) ==
+            'Y' ||
+        bind.sessionGetDisplaysAsIndividualWindows(sessionId: sessionId) ==
+            'Y') {
+      return;
+    }
+    final v = await bind.sessionGetFlutterOption(
+        sessionId: sessionId, k: lastDisplayKey);
+    final display = int.tryParse(v ?? '');
+    if (display == null || display == _pi.currentDisplay) {
+      return;
+    }
+    if (display == kAllDisplayValue
+        ? !_pi.isSupportMultiDisplay
+        : display < 0 || display >= _pi.displays.length) {
+      return;
+    }
+    openMonitorInTheSameTab(display, parent.target!, _pi);
+  }
+
   checkDesktopKeyboardMode() async {
     if (isInputSourceFlutter) {
       // Local side, flutter keyboard input source
@@ -1161,6 +1296,22 @@ class FfiModel with ChangeNotifier {
     }
   }
 
+  updateInputControl(Map<String, dynamic> evt) {
+    final holding = evt['holding'] == 'true';
+    final holder = evt['holder'] ?? '';
+    if (inputControlHolding.value && !holding && holder.isNotEmpty) {
+      showToast('${translate('The control is taken by')} $holder');
+    } else if (!inputControlHolding.value &&
+        holding &&
+        inputControlRequested.value) {
+      showToast(translate('The control is given to you'));
+    }
+    inputControlEnabled.value = evt['enabled'] == 'true';
+    inputControlHolding.value = holding;
+    inputControlHolder.value = holder;
+    inputControlRequested.value = evt['requested'] == 'true';
+  }
+
   updatePrivacyMode(
       Map<String, dynamic> evt, SessionID sessionId, String peerId) async {
     notifyListeners();
@@ -1455,6 +1606,27 @@ class CanvasModel with ChangeNotifier {
     _scrollY = y;
   }
 
+  // Scrolls to the percents kept of the last session, once the scroll bars are laid out.
+  restoreScroll(double x, double y) {
+    if (_lastViewStyle.style != kRemoteViewStyleOriginal ||
+        _scrollStyle != ScrollStyle.scrollbar) {
+      return;
+    }
+    setScrollPercent(x, y);
+    jump(ScrollController c, double percent) {
+      if (!c.hasClients) return;
+      final p = c.position;
+      c.jumpTo((percent * (p.maxScrollExtent + p.viewportDimension))
+          .clamp(0.0, p.maxScrollExtent));
+    }
+
+    WidgetsBinding.instance.addPostFrameCallback((_) {
+      jump(_horizontal, x);
+      jump(_vertical, y);
+      notifyListeners();
+    });
+  }
+
   ScrollController get scrollHorizontal => _horizontal;
   ScrollController get scrollVertical => _vertical;
   double get scrollX => _scrollX;
@@ -1521,6 +1693,9 @@ class CanvasModel with ChangeNotifier {
     if (kIgnoreDpi && style == kRemoteViewStyleOriginal) {
       _scale = 1.0 / _devicePixelRatio;
     }
+    // in physical pixels, used by the smooth video filter
+    bind.sessionSetViewScale(
+        sessionId: sessionId, scale: _scale * _devicePixelRatio);
     _resetCanvasOffset(displayWidth, displayHeight);
     _imageOverflow.value = _x < 0 || y < 0;
     if (notify) {
@@ -2455,6 +2630,12 @@ class QualityMonitorData {
   String? targetBitrate;
   String? codecFormat;
   String? chroma;
+  String? bandwidth;
+  String? avSync;
+  String? jitter;
+  String? loss;
+  String? encodeFps;
+  String? direct;
 }
 
 class QualityMonitorModel with ChangeNotifier {
@@ -2517,6 +2698,26 @@ class QualityMonitorModel with ChangeNotifier {
       if (evt.containsKey('chroma') && (evt['chroma'] as String).isNotEmpty) {
         _data.chroma = evt['chroma'];
       }
+      if (evt.containsKey('bandwidth') &&
+          (evt['bandwidth'] as String).isNotEmpty) {
+        _data.bandwidth = evt['bandwidth'];
+      }
+      if (evt.containsKey('av_sync') && (evt['av_sync'] as String).isNotEmpty) {
+        _data.avSync = evt['av_sync'];
+      }
+      if (evt.containsKey('jitter') && (evt['jitter'] as String).isNotEmpty) {
+        _data.jitter = evt['jitter'];
+      }
+      if (evt.containsKey('loss') && (evt['loss'] as String).isNotEmpty) {
+        _data.loss = evt['loss'];
+      }
+      if (evt.containsKey('encode_fps') &&
+          (evt['encode_fps'] as String).isNotEmpty) {
+        _data.encodeFps = evt['encode_fps'];
+      }
+      if (evt.containsKey('direct') && (evt['direct'] as String).isNotEmpty) {
+        _data.direct = evt['direct'];
+      }
       notifyListeners();
     } catch (e) {
       //
@@ -2524,11 +2725,387 @@ class QualityMonitorModel with ChangeNotifier {
   }
 }
 
+class SystemStats {
+  final double cpu;
+  final List<double> cores;
+  final int memoryUsed;
+  final int memoryTotal;
+  // the mount points, the bytes in total and the ones available
+  final List<(String, int, int)> disks;
+  final List<(String, double)> temperatures;
+
+  SystemStats.fromJson(Map<String, dynamic> json)
+      : cpu = (json['cpu'] as num? ?? 0).toDouble(),
+        cores = ((json['cores'] ?? []) as List)
+            .map((e) => (e as num).toDouble())
+            .toList(),
+        memoryUsed = json['memory_used'] ?? 0,
+        memoryTotal = json['memory_total'] ?? 0,
+        disks = ((json['disks'] ?? []) as List)
+            .map((e) => (e[0] as String, e[1] as int, e[2] as int))
+            .toList(),
+        temperatures = ((json['temperatures'] ?? []) as List)
+            .map((e) => (e[0] as String, (e[1] as num).toDouble()))
+            .toList();
+}
+
+// The load of the controlled side, sent while the panel of it is shown.
+class SystemStatsModel with ChangeNotifier {
+  WeakReference<FFI> parent;
+  SystemStatsModel(this.parent);
+
+  var _show = false;
+  var collapsed = false;
+  SystemStats? stats;
+
+  bool get show => _show;
+
+  toggle(SessionID sessionId) {
+    _show = !_show;
+    if (!_show) {
+      stats = null;
+    }
+    bind.sessionWatchSystemStats(sessionId: sessionId, on: _show);
+    notifyListeners();
+  }
+
+  toggleCollapsed() {
+    collapsed = !collapsed;
+    notifyListeners();
+  }
+
+  rewatch(SessionID sessionId) {
+    if (_show) {
+      bind.sessionWatchSystemStats(sessionId: sessionId, on: true);
+    }
+  }
+
+  update(Map<String, dynamic> evt) {
+    try {
+      stats = SystemStats.fromJson(jsonDecode(evt['stats'] ?? '{}'));
+    } catch (e) {
+      debugPrint('Failed to parse the system stats: $e');
+      stats = null;
+    }
+    notifyListeners();
+  }
+
+  clear() {
+    _show = false;
+    collapsed = false;
+    stats = null;
+  }
+}
+
+class ProcessInfo {
+  final int pid;
+  final int parent;
+  final String name;
+  final String user;
+  final double cpu;
+  final int memory;
+
+  ProcessInfo.fromJson(Map<String, dynamic> json)
+      : pid = json['pid'] ?? 0,
+        parent = json['parent'] ?? 0,
+        name = json['name'] ?? '',
+        user = json['user'] ?? '',
+        cpu = (json['cpu'] as num? ?? 0).toDouble(),
+        memory = json['memory'] ?? 0;
+}
+
+// The processes of the peer, listed again every few seconds while its task manager is open.
+class TaskManagerModel {
+  WeakReference<FFI> parent;
+  TaskManagerModel(this.parent);
+
+  final RxList<ProcessInfo> processes = <ProcessInfo>[].obs;
+  final RxInt total = 0.obs;
+  final RxString error = ''.obs;
+  final RxInt sort = kProcessSortCpu.obs;
+  final RxBool ascending = false.obs;
+  Timer? _timer;
+
+  open() {
+    error.value = '';
+    _list();
+    _timer?.cancel();
+    _timer = Timer.periodic(kProcessListInterval, (_) => _list());
+  }
+
+  close() {
+    _timer?.cancel();
+    _timer = null;
+    processes.clear();
+    total.value = 0;
+  }
+
+  // the same column again turns the order
+  sortBy(int by) {
+    if (sort.value == by) {
+      ascending.value = !ascending.value;
+    } else {
+      sort.value = by;
+      ascending.value = by == kProcessSortName || by == kProcessSortPid;
+    }
+    _list();
+  }
+
+  kill(int pid) {
+    final sessionId = parent.target?.sessionId;
+    if (sessionId == null) return;
+    bind.sessionKillProcess(sessionId: sessionId, pid: pid);
+  }
+
+  _list() {
+    final sessionId = parent.target?.sessionId;
+    if (sessionId == null) return;
+    bind.sessionListProcesses(
+        sessionId: sessionId,
+        sort: sort.value,
+        ascending: ascending.value,
+        limit: kProcessListLimit);
+  }
+
+  update(Map<String, dynamic> evt) {
+    if (_timer == null) return;
+    error.value = evt['error'] ?? '';
+    total.value = int.tryParse(evt['total'] ?? '') ?? 0;
+    try {
+      final List<dynamic> list = jsonDecode(evt['processes'] ?? '[]');
+      processes.value = list.map((e) => ProcessInfo.fromJson(e)).toList();
+    } catch (e) {
+      debugPrint('Failed to parse the processes: $e');
+      processes.clear();
+    }
+  }
+
+  onKilled(Map<String, dynamic> evt) {
+    final ffi = parent.target;
+    if (ffi == null) return;
+    final error = evt['error'] ?? '';
+    if (error.isEmpty) {
+      showToast(translate('Successful'));
+      _list();
+    } else if (evt['elevation_required'] == 'true' &&
+        ffi.elevationModel.showRequestMenu) {
+      showKillElevationRequired(ffi.sessionId, ffi.dialogManager);
+    } else {
+      showToast('${translate('Failed')}: ${translate(error)}');
+    }
+  }
+}
+
+class ServiceInfo {
+  final String name;
+  final String description;
+  final bool running;
+  final String state;
+
+  ServiceInfo.fromJson(Map<String, dynamic> json)
+      : name = json['name'] ?? '',
+        description = json['description'] ?? '',
+        running = json['running'] ?? false,
+        state = json['state'] ?? '';
+}
+
+// The services of the peer, listed when its service manager is opened and once one is controlled.
+class ServiceManagerModel {
+  WeakReference<FFI> parent;
+  ServiceManagerModel(this.parent);
+
+  final RxList<ServiceInfo> services = <ServiceInfo>[].obs;
+  final RxString error = ''.obs;
+  final RxString filter = ''.obs;
+  final RxBool loading = false.obs;
+  // the ones being started, stopped or restarted
+  final RxSet<String> pending = <String>{}.obs;
+
+  List<ServiceInfo> get filtered {
+    final text = filter.value.toLowerCase();
+    if (text.isEmpty) return services;
+    return services
+        .where((s) =>
+            s.name.toLowerCase().contains(text) ||
+            s.description.toLowerCase().contains(text))
+        .toList();
+  }
+
+  refresh() {
+    final sessionId = parent.target?.sessionId;
+    if (sessionId == null) return;
+    loading.value = true;
+    bind.sessionListServices(sessionId: sessionId);
+  }
+
+  control(String name, int action) {
+    final sessionId = parent.target?.sessionId;
+    if (sessionId == null) return;
+    pending.add(name);
+    bind.sessionControlService(
+        sessionId: sessionId, name: name, action: action);
+  }
+
+  update(Map<String, dynamic> evt) {
+    loading.value = false;
+    error.value = evt['error'] ?? '';
+    try {
+      final List<dynamic> list = jsonDecode(evt['services'] ?? '[]');
+      services.value = list.map((e) => ServiceInfo.fromJson(e)).toList();
+    } catch (e) {
+      debugPrint('Failed to parse the services: $e');
+      services.clear();
+    }
+  }
+
+  onControlled(Map<String, dynamic> evt) {
+    final name = evt['name'] ?? '';
+    final error = evt['error'] ?? '';
+    pending.remove(name);
+    if (error.isEmpty) {
+      showToast(translate('Successful'));
+    } else {
+      showToast('${translate('Failed')}: ${translate(error)}',
+          timeout: const Duration(seconds: 6));
+    }
+    refresh();
+  }
+
+  clear() {
+    services.clear();
+    pending.clear();
+    error.value = '';
+    filter.value = '';
+    loading.value = false;
+  }
+}
+
+class CameraInfo {
+  final int index;
+  final String name;
+
+  CameraInfo.fromJson(Map<String, dynamic> json)
+      : index = json['index'] ?? 0,
+        name = json['name'] ?? '';
+}
+
+// The camera of the peer, viewed apart from its displays with settings of its own.
+class CameraModel with ChangeNotifier {
+  WeakReference<FFI> parent;
+  CameraModel(this.parent);
+
+  var _show = false;
+  List<CameraInfo> cameras = [];
+  int index = 0;
+  int fps = kCameraDefaultFps;
+  int quality = kCameraDefaultQuality;
+  bool vp9 = true;
+  ui.Image? image;
+  String error = '';
+  bool _decoding = false;
+
+  bool get show => _show;
+
+  toggle(SessionID sessionId) {
+    _show = !_show;
+    if (_show) {
+      bind.sessionListCameras(sessionId: sessionId);
+    } else {
+      _reset();
+    }
+    request(sessionId);
+    notifyListeners();
+  }
+
+  // Sent again on a new connection, or when a setting changed.
+  request(SessionID sessionId) {
+    bind.sessionRequestCamera(
+        sessionId: sessionId,
+        on: _show,
+        index: index,
+        fps: fps,
+        quality: quality,
+        vp9: vp9);
+  }
+
+  rerequest(SessionID sessionId) {
+    if (_show) {
+      request(sessionId);
+    }
+  }
+
+  set(SessionID sessionId, {int? index, int? fps, int? quality, bool? vp9}) {
+    this.index = index ?? this.index;
+    this.fps = fps ?? this.fps;
+    this.quality = quality ?? this.quality;
+    this.vp9 = vp9 ?? this.vp9;
+    error = '';
+    request(sessionId);
+    notifyListeners();
+  }
+
+  updateList(Map<String, dynamic> evt) {
+    try {
+      cameras = (jsonDecode(evt['cameras'] ?? '[]') as List<dynamic>)
+          .map((e) => CameraInfo.fromJson(e))
+          .toList();
+    } catch (e) {
+      debugPrint('Failed to parse the cameras: $e');
+      cameras = [];
+    }
+    error = evt['error'] ?? '';
+    notifyListeners();
+  }
+
+  updateFrame(Map<String, dynamic> evt) async {
+    if (!_show) return;
+    error = evt['error'] ?? '';
+    if (error.isNotEmpty) {
+      notifyListeners();
+      return;
+    }
+    // the frames coming while one is decoded are skipped, the last one is kept in rust
+    if (_decoding) return;
+    final sessionId = parent.target?.sessionId;
+    final width = int.tryParse(evt['width'] ?? '') ?? 0;
+    final height = int.tryParse(evt['height'] ?? '') ?? 0;
+    if (sessionId == null || width <= 0 || height <= 0) return;
+    final rgba = bind.sessionGetCameraFrame(sessionId: sessionId);
+    if (rgba.length < width * height * 4) return;
+    _decoding = true;
+    final decoded = await img.decodeImageFromPixels(
+        rgba, width, height, ui.PixelFormat.rgba8888);
+    _decoding = false;
+    if (!_show) {
+      decoded?.dispose();
+      return;
+    }
+    image?.dispose();
+    image = decoded;
+    notifyListeners();
+  }
+
+  _reset() {
+    image?.dispose();
+    image = null;
+    error = '';
+  }
+
+  clear() {
+    _show = false;
+    cameras = [];
+    _reset();
+  }
+}
+
 class RecordingModel with ChangeNotifier {
   WeakReference<FFI> parent;
   RecordingModel(this.parent);
   bool _start = false;
   bool get start => _start;
+  // Recorded by the peer.
+  bool _serverStart = false;
+  bool get serverStart => _serverStart;
 
   toggle() async {
     if (isIOS) return;
@@ -2547,6 +3124,123 @@ class RecordingModel with ChangeNotifier {
     _start = status;
     notifyListeners();
   }
+
+  updateServerStatus(bool status) {
+    _serverStart = status;
+    notifyListeners();
+  }
+}
+
+class AnnotationStroke {
+  final int shape;
+  // 0xRRGGBB
+  final int color;
+  final int width;
+  // in the coordinates of the screens of the peer
+  final List<Offset> points;
+
+  AnnotationStroke(this.shape, this.color, this.width, this.points);
+
+  AnnotationStroke.fromJson(Map<String, dynamic> json)
+      : shape = json['shape'] ?? kAnnotationFreehand,
+        color = json['color'] ?? 0,
+        width = json['width'] ?? 1,
+        points = _toPoints(List<int>.from(json['points'] ?? []));
+
+  static List<Offset> _toPoints(List<int> v) {
+    final points = <Offset>[];
+    for (var i = 0; i + 1 < v.length; i += 2) {
+      points.add(Offset(v[i].toDouble(), v[i + 1].toDouble()));
+    }
+    return points;
+  }
+}
+
+// The drawings over the screen of the peer, the ones of the other peers controlling it too.
+class AnnotationModel with ChangeNotifier {
+  WeakReference<FFI> parent;
+  AnnotationModel(this.parent);
+
+  List<AnnotationStroke> strokes = [];
+  // being drawn, not sent yet
+  AnnotationStroke? current;
+  final RxBool drawing = false.obs;
+  final RxInt shape = kAnnotationFreehand.obs;
+  final RxInt color = kAnnotationColors.first.obs;
+
+  update(Map<String, dynamic> evt) {
+    try {
+      final List<dynamic> list = jsonDecode(evt['strokes'] ?? '[]');
+      strokes = list.map((e) => AnnotationStroke.fromJson(e)).toList();
+    } catch (e) {
+      debugPrint('Failed to parse the annotations: $e');
+      strokes = [];
+    }
+    notifyListeners();
+  }
+
+  toggle() {
+    drawing.value = !drawing.value;
+    current = null;
+    notifyListeners();
+  }
+
+  start(Offset pos) {
+    current = AnnotationStroke(
+        shape.value, color.value, kAnnotationStrokeWidth, [pos]);
+    notifyListeners();
+  }
+
+  move(Offset pos) {
+    final stroke = current;
+    if (stroke == null) return;
+    if (stroke.shape == kAnnotationFreehand) {
+      if ((stroke.points.last - pos).distance < 2 ||
+          stroke.points.length >= kAnnotationMaxPoints) {
+        return;
+      }
+      stroke.points.add(pos);
+    } else {
+      stroke.points.length = 1;
+      stroke.points.add(pos);
+    }
+    notifyListeners();
+  }
+
+  end() async {
+    final stroke = current;
+    final sessionId = parent.target?.sessionId;
+    if (stroke == null || sessionId == null) return;
+    if (stroke.shape != kAnnotationFreehand && stroke.points.length < 2) {
+      current = null;
+      notifyListeners();
+      return;
+    }
+    // kept until the peer sends it back with the others
+    strokes.add(stroke);
+    current = null;
+    notifyListeners();
+    await bind.sessionAnnotate(
+        sessionId: sessionId,
+        shape: stroke.shape,
+        points: Int32List.fromList(stroke.points
+            .expand((p) => [p.dx.round(), p.dy.round()])
+            .toList()),
+        color: stroke.color,
+        width: stroke.width);
+  }
+
+  undo(bool all) {
+    final sessionId = parent.target?.sessionId;
+    if (sessionId == null) return;
+    bind.sessionUndoAnnotation(sessionId: sessionId, all: all);
+  }
+
+  clear() {
+    strokes = [];
+    current = null;
+    drawing.value = false;
+  }
 }
 
 class ElevationModel with ChangeNotifier {
@@ -2590,6 +3284,16 @@ class FFI {
   late final PeerTabModel peerTabModel; // global
   late final QualityMonitorModel qualityMonitorModel; // session
   late final RecordingModel recordingModel; // session
+  late final AnnotationModel annotationModel; // session
+  late final SystemStatsModel systemStatsModel; // session
+  late final TaskManagerModel taskManagerModel; // session
+  late final ServiceManagerModel serviceManagerModel; // session
+  late final CameraModel cameraModel; // session
+  // the documents of the virtual printer of the peer are printed here
+  final remotePrinter = false.obs; // session
+  // the viewers of the broadcast of the peer, none if it has no broadcast
+  final broadcastViewers = 0.obs; // session
+  final broadcastMaxViewers = 0.obs; // session
   late final InputModel inputModel; // session
   late final ElevationModel elevationModel; // session
   late final CmFileModel cmFileModel; // cm
@@ -2613,6 +3317,11 @@ class FFI {
     groupModel = GroupModel(WeakReference(this));
     qualityMonitorModel = QualityMonitorModel(WeakReference(this));
     recordingModel = RecordingModel(WeakReference(this));
+    annotationModel = AnnotationModel(WeakReference(this));
+    systemStatsModel = SystemStatsModel(WeakReference(this));
+    taskManagerModel = TaskManagerModel(WeakReference(this));
+    serviceManagerModel = ServiceManagerModel(WeakReference(this));
+    cameraModel = CameraModel(WeakReference(this));
     inputModel = InputModel(WeakReference(this));
     elevationModel = ElevationModel(WeakReference(this));
     cmFileModel = CmFileModel(WeakReference(this));
@@ -2843,6 +3552,10 @@ class FFI {
         sessionId: sessionId, code: code, trustThisDevice: trustThisDevice);
   }
 
+  void sendAccessReason(SessionID sessionId, String reason) {
+    bind.sessionSendAccessReason(sessionId: sessionId, reason: reason);
+  }
+
   /// Close the remote session.
   Future<void> close({bool closeSession = true}) async {
     closed = true;
@@ -2855,12 +3568,22 @@ class FFI {
           canvasModel.x,
           canvasModel.y,
           canvasModel.scale,
-          ffiModel.pi.currentDisplay);
+          ffiModel.pi.currentDisplay,
+          scrollX: canvasModel.scrollX,
+          scrollY: canvasModel.scrollY);
     }
     await imageModel.update(null);
     cursorModel.clear();
     ffiModel.clear();
     canvasModel.clear();
+    annotationModel.clear();
+    systemStatsModel.clear();
+    taskManagerModel.close();
+    serviceManagerModel.clear();
+    cameraModel.clear();
+    remotePrinter.value = false;
+    broadcastViewers.value = 0;
+    broadcastMaxViewers.value = 0;
     inputModel.resetModifiers();
     if (closeSession) {
       await bind.sessionClose(sessionId: sessionId);
@@ -2937,6 +3660,9 @@ class Resolution {
 
 class Features {
   bool privacyMode = false;
+  bool gamepad = false;
+  bool pen = false;
+  bool touch = false;
 }
 
 const kInvalidDisplayIndex = -1;
@@ -3037,6 +3763,7 @@ class PeerInfo with ChangeNotifier {
 }
 
 const canvasKey = 'canvas';
+const lastDisplayKey = 'last-display';
 
 Future<void> setCanvasConfig(
     SessionID sessionId,
@@ -3045,7 +3772,9 @@ Future<void> setCanvasConfig(
     double xCanvas,
     double yCanvas,
     double scale,
-    int currentDisplay) async {
+    int currentDisplay,
+    {double scrollX = 0.0,
+    double scrollY = 0.0}) async {
   final p = <String, dynamic>{};
   p['xCursor'] = xCursor;
   p['yCursor'] = yCursor;
@@ -3053,12 +3782,13 @@ Future<void> setCanvasConfig(
   p['yCanvas'] = yCanvas;
   p['scale'] = scale;
   p['currentDisplay'] = currentDisplay;
+  p['scrollX'] = scrollX;
+  p['scrollY'] = scrollY;
   await bind.sessionSetFlutterOption(
       sessionId: sessionId, k: canvasKey, v: jsonEncode(p));
 }
 
 Future<Map<String, dynamic>?> getCanvasConfig(SessionID sessionId) async {
-  if (!isWebDesktop) return null;
   var p =
       await bind.sessionGetFlutterOption(sessionId: sessionId, k: canvasKey);
   if (p == null || p.isEmpty) return null;
@@ -3081,6 +3811,14 @@ Future<void> initializeCursorAndCanvas(FFI ffi) async {
         ffi.ffiModel.rect?.left ?? 0, ffi.ffiModel.rect?.top ?? 0);
     return;
   }
+  if (isDesktop) {
+    // the zoom follows the view style, only the scroll is kept
+    ffi.cursorModel.updateDisplayOrigin(
+        ffi.ffiModel.rect?.left ?? 0, ffi.ffiModel.rect?.top ?? 0);
+    ffi.canvasModel.restoreScroll(
+        (p['scrollX'] ?? 0.0).toDouble(), (p['scrollY'] ?? 0.0).toDouble());
+    return;
+  }
   double xCursor = p['xCursor'];
   double yCursor = p['yCursor'];
   double xCanvas = p['xCanvas'];
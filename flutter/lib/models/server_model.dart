@@ -751,6 +751,43 @@ class ServerModel with ChangeNotifier {
     }
   }
 
+  void updateInputControlState(Map<String, dynamic> evt) {
+    try {
+      final client = Client.fromJson(jsonDecode(evt["client"]));
+      final index = _clients.indexWhere((element) => element.id == client.id);
+      if (index != -1) {
+        _clients[index].inputControlRequested = client.inputControlRequested;
+        if (client.inputControlRequested && !isAndroid) {
+          Future.delayed(Duration.zero, () {
+            windowOnTop(null);
+          });
+        }
+        notifyListeners();
+      }
+    } catch (e) {
+      debugPrint("updateInputControlState failed: $e");
+    }
+  }
+
+  void updateAnnotationState(Map<String, dynamic> evt) {
+    try {
+      final client = Client.fromJson(jsonDecode(evt["client"]));
+      final index = _clients.indexWhere((element) => element.id == client.id);
+      if (index != -1) {
+        _clients[index].annotations = client.annotations;
+        notifyListeners();
+      }
+    } catch (e) {
+      debugPrint("updateAnnotationState failed: $e");
+    }
+  }
+
+  void clearAnnotations(Client client) {
+    bind.cmClearAnnotations(id: client.id);
+    client.annotations = 0;
+    notifyListeners();
+  }
+
   void androidUpdatekeepScreenOn() async {
     if (!isAndroid) return;
     var floatingWindowDisabled =
@@ -777,12 +814,14 @@ enum ClientType {
   remote,
   file,
   portForward,
+  terminal,
 }
 
 class Client {
   int id = 0; // client connections inner count id
   bool authorized = false;
   bool isFileTransfer = false;
+  bool isTerminal = false;
   String portForward = "";
   String name = "";
   String peerId = ""; // peer user's id,show at app
@@ -793,10 +832,19 @@ class Client {
   bool restart = false;
   bool recording = false;
   bool blockInput = false;
+  bool keyInput = false;
+  bool mouseInput = false;
+  bool clipboardTyping = false;
+  bool terminal = false;
   bool disconnected = false;
   bool fromSwitch = false;
   bool inVoiceCall = false;
   bool incomingVoiceCall = false;
+  bool inputControlRequested = false;
+  int annotations = 0;
+  String reason = '';
+  List<String> locked = [];
+  String deviceKey = '';
 
   RxInt unreadChatMessageCount = 0.obs;
 
@@ -807,6 +855,7 @@ class Client {
     id = json['id'];
     authorized = json['authorized'];
     isFileTransfer = json['is_file_transfer'];
+    isTerminal = json['is_terminal'] ?? false;
     portForward = json['port_forward'];
     name = json['name'];
     peerId = json['peer_id'];
@@ -817,10 +866,19 @@ class Client {
     restart = json['restart'];
     recording = json['recording'];
     blockInput = json['block_input'];
+    keyInput = json['key_input'];
+    mouseInput = json['mouse_input'];
+    clipboardTyping = json['clipboard_typing'];
+    terminal = json['terminal'] ?? false;
     disconnected = json['disconnected'];
     fromSwitch = json['from_switch'];
     inVoiceCall = json['in_voice_call'];
     incomingVoiceCall = json['incoming_voice_call'];
+    inputControlRequested = json['input_control_requested'] ?? false;
+    annotations = json['annotations'] ?? 0;
+    reason = json['reason'] ?? '';
+    locked = List<String>.from(json['locked'] ?? []);
+    deviceKey = json['device_key'] ?? '';
   }
 
   Map<String, dynamic> toJson() {
@@ -828,6 +886,7 @@ class Client {
     data['id'] = id;
     data['authorized'] = authorized;
     data['is_file_transfer'] = isFileTransfer;
+    data['is_terminal'] = isTerminal;
     data['port_forward'] = portForward;
     data['name'] = name;
     data['peer_id'] = peerId;
@@ -838,10 +897,19 @@ class Client {
     data['restart'] = restart;
     data['recording'] = recording;
     data['block_input'] = blockInput;
+    data['key_input'] = keyInput;
+    data['mouse_input'] = mouseInput;
+    data['clipboard_typing'] = clipboardTyping;
+    data['terminal'] = terminal;
     data['disconnected'] = disconnected;
     data['from_switch'] = fromSwitch;
     data['in_voice_call'] = inVoiceCall;
     data['incoming_voice_call'] = incomingVoiceCall;
+    data['input_control_requested'] = inputControlRequested;
+    data['annotations'] = annotations;
+    data['reason'] = reason;
+    data['locked'] = locked;
+    data['device_key'] = deviceKey;
     return data;
   }
 
@@ -850,6 +918,8 @@ class Client {
       return ClientType.file;
     } else if (portForward.isNotEmpty) {
       return ClientType.portForward;
+    } else if (isTerminal) {
+      return ClientType.terminal;
     } else {
       return ClientType.remote;
     }
@@ -77,6 +77,7 @@ class StateGlobal {
         procFullscreenWeb();
       } else {
         procFullscreenNative(procWnd);
+        bind.mainSetFullscreen(fullscreen: v);
       }
     }
   }
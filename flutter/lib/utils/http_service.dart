@@ -1,6 +1,7 @@
 import 'dart:convert';
 import 'package:flutter/foundation.dart';
 import 'package:http/http.dart' as http;
+import '../consts.dart';
 import '../models/platform_model.dart';
 export 'package:http/http.dart' show Response;
 
@@ -17,8 +18,11 @@ class HttpService {
 
     // Determine if there is currently a proxy setting, and if so, use FFI to call the Rust HTTP method.
     final isProxy = await bind.mainGetProxyStatus();
+    // The Rust HTTP method checks the pins of the tls servers as well.
+    final isPinned =
+        bind.mainGetOptionSync(key: kOptionTlsPins).trim().isNotEmpty;
 
-    if (!isProxy) {
+    if (!isProxy && !isPinned) {
       return await _pollFultterHttp(url, method, headers: headers, body: body);
     }
 
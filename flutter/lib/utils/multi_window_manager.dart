@@ -202,6 +202,7 @@ class RustDeskMultiWindowManager {
     bool? isRDP,
     bool? isSharedPassword,
     String? connToken,
+    List<String>? dropFiles,
   }) async {
     var params = {
       "type": type.index,
@@ -209,6 +210,9 @@ class RustDeskMultiWindowManager {
       "password": password,
       "forceRelay": forceRelay
     };
+    if (dropFiles != null) {
+      params['dropFiles'] = dropFiles;
+    }
     if (switchUuid != null) {
       params['switch_uuid'] = switchUuid;
     }
@@ -264,6 +268,7 @@ class RustDeskMultiWindowManager {
     bool? isSharedPassword,
     bool? forceRelay,
     String? connToken,
+    List<String>? dropFiles,
   }) async {
     return await newSession(
       WindowType.FileTransfer,
@@ -274,6 +279,7 @@ class RustDeskMultiWindowManager {
       forceRelay: forceRelay,
       isSharedPassword: isSharedPassword,
       connToken: connToken,
+      dropFiles: dropFiles,
     );
   }
 
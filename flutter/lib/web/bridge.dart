@@ -182,6 +182,42 @@ class RustdeskImpl {
     return false;
   }
 
+  String sessionStartMacroRecording(
+      {required UuidValue sessionId, required String name, dynamic hint}) {
+    return '';
+  }
+
+  String sessionStopMacroRecording(
+      {required UuidValue sessionId, dynamic hint}) {
+    return '';
+  }
+
+  bool sessionIsRecordingMacro({required UuidValue sessionId, dynamic hint}) {
+    return false;
+  }
+
+  String sessionPlayMacro(
+      {required UuidValue sessionId, required String name, dynamic hint}) {
+    return '';
+  }
+
+  Future<void> sessionStopMacro({required UuidValue sessionId, dynamic hint}) {
+    return Future.value();
+  }
+
+  bool sessionIsPlayingMacro({required UuidValue sessionId, dynamic hint}) {
+    return false;
+  }
+
+  String sessionGetMacros({required UuidValue sessionId, dynamic hint}) {
+    return '[]';
+  }
+
+  Future<void> sessionRemoveMacro(
+      {required UuidValue sessionId, required String name, dynamic hint}) {
+    return Future.value();
+  }
+
   Future<void> sessionReconnect(
       {required UuidValue sessionId, required bool forceRelay, dynamic hint}) {
     return Future(() => js.context.callMethod('setByName', ['reconnect']));
@@ -435,6 +471,8 @@ class RustdeskImpl {
     js.context.callMethod('setByName', ['enter_or_leave', enter]);
   }
 
+  void mainSetFullscreen({required bool fullscreen, dynamic hint}) {}
+
   Future<void> sessionInputKey(
       {required UuidValue sessionId,
       required String name,
@@ -465,12 +503,48 @@ class RustdeskImpl {
         () => js.context.callMethod('setByName', ['input_string', value]));
   }
 
+  Future<void> sessionTypeClipboard(
+      {required UuidValue sessionId, required String value, dynamic hint}) {
+    // The web client sends the text as the other strings.
+    return Future(
+        () => js.context.callMethod('setByName', ['input_string', value]));
+  }
+
+  Future<void> sessionSetRemoteAudioInput(
+      {required UuidValue sessionId, required String device, dynamic hint}) {
+    throw UnimplementedError("sessionSetRemoteAudioInput");
+  }
+
+  Future<void> sessionInputIme(
+      {required UuidValue sessionId,
+      required String text,
+      required bool commit,
+      dynamic hint}) {
+    // Only the committed text is typed, the same as the other strings.
+    if (!commit || text.isEmpty) {
+      return Future.value();
+    }
+    return Future(
+        () => js.context.callMethod('setByName', ['input_string', text]));
+  }
+
   Future<void> sessionSendChat(
-      {required UuidValue sessionId, required String text, dynamic hint}) {
+      {required UuidValue sessionId,
+      required int id,
+      required String text,
+      dynamic hint}) {
     return Future(
         () => js.context.callMethod('setByName', ['send_chat', text]));
   }
 
+  Future<String> sessionSendChatAttachment(
+      {required UuidValue sessionId,
+      required int id,
+      required String path,
+      dynamic hint}) {
+    throw UnimplementedError("sessionSendChatAttachment");
+  }
+
   Future<void> sessionPeerOption(
       {required UuidValue sessionId,
       required String name,
@@ -505,6 +579,29 @@ class RustdeskImpl {
         ]));
   }
 
+  Future<void> sessionSearchRemoteFiles(
+      {required UuidValue sessionId,
+      required int actId,
+      required String path,
+      required String pattern,
+      required bool includeHidden,
+      required int maxDepth,
+      required int timeoutSecs,
+      dynamic hint}) {
+    throw UnimplementedError("sessionSearchRemoteFiles");
+  }
+
+  Future<void> sessionReadFileRange(
+      {required UuidValue sessionId,
+      required int actId,
+      required String path,
+      required int offset,
+      required int length,
+      required bool isRemote,
+      dynamic hint}) {
+    throw UnimplementedError("sessionReadFileRange");
+  }
+
   Future<void> sessionSendFiles(
       {required UuidValue sessionId,
       required int actId,
@@ -514,6 +611,7 @@ class RustdeskImpl {
       required bool includeHidden,
       required bool isRemote,
       required bool isDir,
+      required String conflictPolicy,
       dynamic hint}) {
     return Future(() => js.context.callMethod('setByName', [
           'send_files',
@@ -525,6 +623,7 @@ class RustdeskImpl {
             'include_hidden': includeHidden,
             'is_remote': isRemote,
             'is_dir': isDir,
+            'conflict_policy': conflictPolicy,
           })
         ]));
   }
@@ -549,12 +648,24 @@ class RustdeskImpl {
         ]));
   }
 
+  Future<void> sessionResolveFileConflict(
+      {required UuidValue sessionId,
+      required int actId,
+      required int fileNum,
+      required String policy,
+      required bool remember,
+      required bool isUpload,
+      dynamic hint}) {
+    throw UnimplementedError("sessionResolveFileConflict");
+  }
+
   Future<void> sessionRemoveFile(
       {required UuidValue sessionId,
       required int actId,
       required String path,
       required int fileNum,
       required bool isRemote,
+      required bool trash,
       dynamic hint}) {
     return Future(() => js.context.callMethod('setByName', [
           'remove_file',
@@ -562,7 +673,8 @@ class RustdeskImpl {
             'id': actId,
             'path': path,
             'file_num': fileNum,
-            'is_remote': isRemote
+            'is_remote': isRemote,
+            'trash': trash
           })
         ]));
   }
@@ -590,13 +702,28 @@ class RustdeskImpl {
       required int actId,
       required String path,
       required bool isRemote,
+      required bool trash,
       dynamic hint}) {
     return Future(() => js.context.callMethod('setByName', [
           'remove_all_empty_dirs',
-          jsonEncode({'id': actId, 'path': path, 'is_remote': isRemote})
+          jsonEncode({
+            'id': actId,
+            'path': path,
+            'is_remote': isRemote,
+            'trash': trash
+          })
         ]));
   }
 
+  Future<void> sessionUndoRemove(
+      {required UuidValue sessionId,
+      required int actId,
+      required Int32List removeIds,
+      required bool isRemote,
+      dynamic hint}) {
+    throw UnimplementedError("sessionUndoRemove");
+  }
+
   Future<void> sessionCancelJob(
       {required UuidValue sessionId, required int actId, dynamic hint}) {
     return Future(
@@ -657,6 +784,19 @@ class RustdeskImpl {
     throw UnimplementedError("sessionResumeJob");
   }
 
+  Future<void> sessionPauseJob(
+      {required UuidValue sessionId,
+      required int actId,
+      required bool isRemote,
+      dynamic hint}) {
+    throw UnimplementedError("sessionPauseJob");
+  }
+
+  Future<void> sessionReorderJobs(
+      {required UuidValue sessionId, required Int32List ids, dynamic hint}) {
+    throw UnimplementedError("sessionReorderJobs");
+  }
+
   Future<void> sessionElevateDirect(
       {required UuidValue sessionId, dynamic hint}) {
     return Future(() => js.context.callMethod('setByName', ['elevate_direct']));
@@ -700,6 +840,36 @@ class RustdeskImpl {
     return Future.value();
   }
 
+  Future<void> sessionSetBandwidthLimit(
+      {required UuidValue sessionId, required int kbps, dynamic hint}) {
+    return Future.value();
+  }
+
+  Future<void> sessionSetAudioQuality(
+      {required UuidValue sessionId, required String value, dynamic hint}) {
+    return Future.value();
+  }
+
+  Future<void> sessionQueryRemoteVolume(
+      {required UuidValue sessionId, dynamic hint}) {
+    return Future.value();
+  }
+
+  Future<void> sessionSetRemoteVolume(
+      {required UuidValue sessionId, required int level, dynamic hint}) {
+    return Future.value();
+  }
+
+  Future<void> sessionSetRemoteMute(
+      {required UuidValue sessionId, required bool mute, dynamic hint}) {
+    return Future.value();
+  }
+
+  Future<void> sessionSetViewScale(
+      {required UuidValue sessionId, required double scale, dynamic hint}) {
+    return Future.value();
+  }
+
   Future<void> sessionSendSelectedSessionId(
       {required UuidValue sessionId, required String sid, dynamic hint}) {
     return Future(
@@ -791,6 +961,15 @@ class RustdeskImpl {
     throw UnimplementedError("mainGetSocks");
   }
 
+  Future<String> mainGetNetworkProfiles({dynamic hint}) {
+    throw UnimplementedError("mainGetNetworkProfiles");
+  }
+
+  Future<void> mainSetNetworkProfiles(
+      {required String profiles, dynamic hint}) {
+    throw UnimplementedError("mainSetNetworkProfiles");
+  }
+
   Future<String> mainGetAppName({dynamic hint}) {
     return Future.value(mainGetAppNameSync(hint: hint));
   }
@@ -1104,6 +1283,11 @@ class RustdeskImpl {
     throw UnimplementedError("sessionCloseVoiceCall");
   }
 
+  Future<void> sessionSetVoiceCallTalking(
+      {required UuidValue sessionId, required bool talking, dynamic hint}) {
+    throw UnimplementedError("sessionSetVoiceCallTalking");
+  }
+
   Future<void> cmHandleIncomingVoiceCall(
       {required int id, required bool accept, dynamic hint}) {
     throw UnimplementedError("cmHandleIncomingVoiceCall");
@@ -1113,6 +1297,15 @@ class RustdeskImpl {
     throw UnimplementedError("cmCloseVoiceCall");
   }
 
+  Future<void> cmHandleInputControlRequest(
+      {required int id, required bool grant, dynamic hint}) {
+    throw UnimplementedError("cmHandleInputControlRequest");
+  }
+
+  Future<void> cmClearAnnotations({required int id, dynamic hint}) {
+    throw UnimplementedError("cmClearAnnotations");
+  }
+
   Future<String> mainGetLastRemoteId({dynamic hint}) {
     return Future(() => mainGetLocalOption(key: 'last_remote_id'));
   }
@@ -1257,10 +1450,99 @@ class RustdeskImpl {
   }
 
   Future<void> sessionRestartRemoteDevice(
-      {required UuidValue sessionId, dynamic hint}) {
+      {required UuidValue sessionId,
+      required bool safeMode,
+      required bool reconnect,
+      dynamic hint}) {
     return Future(() => js.context.callMethod('setByName', ['restart']));
   }
 
+  Future<void> sessionRequestInputControl(
+      {required UuidValue sessionId, required bool request, dynamic hint}) {
+    throw UnimplementedError("sessionRequestInputControl");
+  }
+
+  Future<void> sessionRequestHandoff(
+      {required UuidValue sessionId, dynamic hint}) {
+    throw UnimplementedError("sessionRequestHandoff");
+  }
+
+  Future<void> sessionWatchSystemStats(
+      {required UuidValue sessionId, required bool on, dynamic hint}) {
+    throw UnimplementedError("sessionWatchSystemStats");
+  }
+
+  Future<void> sessionListProcesses(
+      {required UuidValue sessionId,
+      required int sort,
+      required bool ascending,
+      required int limit,
+      dynamic hint}) {
+    throw UnimplementedError("sessionListProcesses");
+  }
+
+  Future<void> sessionKillProcess(
+      {required UuidValue sessionId, required int pid, dynamic hint}) {
+    throw UnimplementedError("sessionKillProcess");
+  }
+
+  Future<void> sessionListServices({required UuidValue sessionId, dynamic hint}) {
+    throw UnimplementedError("sessionListServices");
+  }
+
+  Future<void> sessionControlService(
+      {required UuidValue sessionId,
+      required String name,
+      required int action,
+      dynamic hint}) {
+    throw UnimplementedError("sessionControlService");
+  }
+
+  Future<void> sessionListCameras({required UuidValue sessionId, dynamic hint}) {
+    throw UnimplementedError("sessionListCameras");
+  }
+
+  Future<void> sessionRequestCamera(
+      {required UuidValue sessionId,
+      required bool on,
+      required int index,
+      required int fps,
+      required int quality,
+      required bool vp9,
+      dynamic hint}) {
+    throw UnimplementedError("sessionRequestCamera");
+  }
+
+  Uint8List sessionGetCameraFrame(
+      {required UuidValue sessionId, dynamic hint}) {
+    return Uint8List(0);
+  }
+
+  Future<void> sessionRemotePrinter(
+      {required UuidValue sessionId, required bool on, dynamic hint}) {
+    throw UnimplementedError("sessionRemotePrinter");
+  }
+
+  String mainPrintPdf(
+      {required String path, required int copies, dynamic hint}) {
+    return "Printing is not supported on this side";
+  }
+
+  Future<void> sessionAnnotate(
+      {required UuidValue sessionId,
+      required int shape,
+      required Int32List points,
+      required int color,
+      required int width,
+      dynamic hint}) {
+    throw UnimplementedError("sessionAnnotate");
+  }
+
+  Future<void> sessionUndoAnnotation(
+      {required UuidValue sessionId, required bool all, dynamic hint}) {
+    throw UnimplementedError("sessionUndoAnnotation");
+  }
+
   String sessionGetAuditServerSync(
       {required UuidValue sessionId, required String typ, dynamic hint}) {
     return js.context.callMethod('getByName', ['audit_server', typ]);
@@ -1341,15 +1623,40 @@ class RustdeskImpl {
     throw UnimplementedError("mainWol");
   }
 
+  String mainGetWolRelays({required String id, dynamic hint}) {
+    return '[]';
+  }
+
+  Future<String> mainWakeThroughPeer(
+      {required String id, required String relayId, dynamic hint}) {
+    throw UnimplementedError("mainWakeThroughPeer");
+  }
+
   Future<void> mainCreateShortcut({required String id, dynamic hint}) {
     throw UnimplementedError("mainCreateShortcut");
   }
 
+  Future<String> mainTakeScreenshot(
+      {required String id, required int display, dynamic hint}) {
+    throw UnimplementedError("mainTakeScreenshot");
+  }
+
   Future<void> cmSendChat(
-      {required int connId, required String msg, dynamic hint}) {
+      {required int connId,
+      required int id,
+      required String msg,
+      dynamic hint}) {
     throw UnimplementedError("cmSendChat");
   }
 
+  Future<String> cmSendChatAttachment(
+      {required int connId,
+      required int id,
+      required String path,
+      dynamic hint}) {
+    throw UnimplementedError("cmSendChatAttachment");
+  }
+
   Future<void> cmLoginRes(
       {required int connId, required bool res, dynamic hint}) {
     throw UnimplementedError("cmLoginRes");
@@ -1380,6 +1687,11 @@ class RustdeskImpl {
     throw UnimplementedError("cmSwitchPermission");
   }
 
+  Future<void> cmSavePermissionProfile(
+      {required int connId, required bool save, dynamic hint}) {
+    throw UnimplementedError("cmSavePermissionProfile");
+  }
+
   bool cmCanElevate({dynamic hint}) {
     throw UnimplementedError("cmCanElevate");
   }
@@ -1758,6 +2070,16 @@ class RustdeskImpl {
     throw UnimplementedError("mainSetUnlockPin");
   }
 
+  bool mainVerifyUnlockPin({required String pin, dynamic hint}) {
+    throw UnimplementedError("mainVerifyUnlockPin");
+  }
+
+  Future<void> sessionSendAccessReason(
+      {required UuidValue sessionId, required String reason, dynamic hint}) {
+    return Future(() =>
+        js.context.callMethod('setByName', ['send_access_reason', reason]));
+  }
+
   bool sessionGetEnableTrustedDevices(
       {required UuidValue sessionId, dynamic hint}) {
     return js.context.callMethod('getByName', ['enable_trusted_devices']) ==
@@ -1797,10 +2119,142 @@ class RustdeskImpl {
     throw UnimplementedError("installInstallOptions");
   }
 
+  Future<String> mainGetPermissionProfiles({dynamic hint}) {
+    throw UnimplementedError("mainGetPermissionProfiles");
+  }
+
+  Future<void> mainRemovePermissionProfiles(
+      {required String json, dynamic hint}) {
+    throw UnimplementedError("mainRemovePermissionProfiles");
+  }
+
+  Future<String> mainGetOneTimePasswords({dynamic hint}) {
+    throw UnimplementedError("mainGetOneTimePasswords");
+  }
+
+  Future<String> mainGenerateOneTimePasswords(
+      {required int count,
+      required int minutes,
+      required String scope,
+      required String label,
+      dynamic hint}) {
+    throw UnimplementedError("mainGenerateOneTimePasswords");
+  }
+
+  Future<void> mainRemoveOneTimePasswords(
+      {required String json, dynamic hint}) {
+    throw UnimplementedError("mainRemoveOneTimePasswords");
+  }
+
+  Future<String> mainGetTrustedKeys({dynamic hint}) {
+    throw UnimplementedError("mainGetTrustedKeys");
+  }
+
+  Future<void> mainRemoveTrustedKeys({required String json, dynamic hint}) {
+    throw UnimplementedError("mainRemoveTrustedKeys");
+  }
+
+  Future<void> mainIdpOidcAuth({dynamic hint}) {
+    throw UnimplementedError("mainIdpOidcAuth");
+  }
+
+  Future<void> mainIdpOidcAuthCancel({dynamic hint}) {
+    throw UnimplementedError("mainIdpOidcAuthCancel");
+  }
+
+  Future<String> mainIdpOidcAuthResult({dynamic hint}) {
+    throw UnimplementedError("mainIdpOidcAuthResult");
+  }
+
+  Future<String> mainIdpLdapSignIn(
+      {required String username, required String password, dynamic hint}) {
+    throw UnimplementedError("mainIdpLdapSignIn");
+  }
+
+  Future<void> mainIdpSignOut({dynamic hint}) {
+    throw UnimplementedError("mainIdpSignOut");
+  }
+
+  String mainIdpGetSession({dynamic hint}) {
+    return '';
+  }
+
+  String mainCheckTlsPins({required String pins, dynamic hint}) {
+    return '';
+  }
+
+  String mainCheckIpRules({required String rules, dynamic hint}) {
+    return '';
+  }
+
+  String mainCheckUnattendedSchedule(
+      {required String schedule, dynamic hint}) {
+    return '';
+  }
+
+  Future<String> mainGetOldFingerprint({dynamic hint}) {
+    return Future.value('');
+  }
+
+  String mainFingerprintToSas({required String fingerprint, dynamic hint}) {
+    return '';
+  }
+
+  Future<void> mainRotateKeyPair({required int graceDays, dynamic hint}) {
+    throw UnimplementedError("mainRotateKeyPair");
+  }
+
   int mainMaxEncryptLen({dynamic hint}) {
     throw UnimplementedError("mainMaxEncryptLen");
   }
 
+  int mainPasswordMinLength({dynamic hint}) {
+    return 0;
+  }
+
+  Future<void> sessionRunSyncJob(
+      {required UuidValue sessionId,
+      required int actId,
+      required String name,
+      required bool dryRun,
+      dynamic hint}) {
+    return Future.value();
+  }
+
+  Future<void> sessionSetJobRateLimit(
+      {required UuidValue sessionId,
+      required int actId,
+      required int kbps,
+      dynamic hint}) {
+    return Future.value();
+  }
+
+  String sessionGetSyncJobs({required UuidValue sessionId, dynamic hint}) {
+    return '[]';
+  }
+
+  String sessionSaveSyncJob(
+      {required UuidValue sessionId, required String job, dynamic hint}) {
+    return '';
+  }
+
+  Future<void> sessionRemoveSyncJob(
+      {required UuidValue sessionId, required String name, dynamic hint}) {
+    return Future.value();
+  }
+
+  Future<void> sessionLogSyncJob(
+      {required UuidValue sessionId,
+      required String name,
+      required String text,
+      dynamic hint}) {
+    return Future.value();
+  }
+
+  String sessionGetDueSyncJobs({required UuidValue sessionId, dynamic hint}) {
+    return '[]';
+  }
+
   Future<void> sessionRenameFile(
       {required UuidValue sessionId,
       required int actId,
@@ -0,0 +1,95 @@
+// The files on the clipboard checked against the rules of the data loss prevention.
+//
+// The file list of the clipboard is the format data response of the file descriptors, of which the
+// names and the sizes are checked, and which is replaced by a failure if they are blocked, so that
+// the paste fails before any content is requested.
+
+use hbb_common::dlp::{self, Direction, Verdict};
+
+use crate::ClipboardFile;
+
+const CB_RESPONSE_OK: i32 = 0x01;
+const CB_RESPONSE_FAIL: i32 = 0x02;
+// the size of a FILEDESCRIPTORW
+const FILE_DESCRIPTOR_SIZE: usize = 592;
+const FD_FILESIZE: u32 = 0x40;
+
+// the names and the sizes of a CLIPRDR_FILELIST, None if it is not one
+fn file_list(data: &[u8]) -> Option<Vec<(String, u64)>> {
+    let count = u32::from_le_bytes(data.get(..4)?.try_into().ok()?) as usize;
+    if Some(data.len() - 4) != count.checked_mul(FILE_DESCRIPTOR_SIZE) {
+        return None;
+    }
+    let u32_at = |fd: &[u8], i: usize| u32::from_le_bytes(fd[i..i + 4].try_into().unwrap());
+    let files = data[4..]
+        .chunks_exact(FILE_DESCRIPTOR_SIZE)
+        .map(|fd| {
+            let size = if u32_at(fd, 0) & FD_FILESIZE != 0 {
+                ((u32_at(fd, 64) as u64) << 32) + u32_at(fd, 68) as u64
+            } else {
+                0
+            };
+            let name: Vec<u16> = fd[72..]
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|c| *c != 0)
+                .collect();
+            (String::from_utf16_lossy(&name), size)
+        })
+        .collect();
+    Some(files)
+}
+
+/// The verdict of the rules on the files of `msg` if it is a file list, and the name of the file
+/// it is of.
+pub fn check(msg: &ClipboardFile, direction: Direction) -> Option<(Verdict, String)> {
+    let ClipboardFile::FormatDataResponse {
+        msg_flags,
+        format_data,
+    } = msg
+    else {
+        return None;
+    };
+    if *msg_flags != CB_RESPONSE_OK {
+        return None;
+    }
+    let files = file_list(format_data)?;
+    dlp::check_files(files.iter().map(|(n, s)| (n.as_str(), *s)), direction)
+}
+
+/// The response a file list blocked is replaced with.
+pub fn blocked_response() -> ClipboardFile {
+    ClipboardFile::FormatDataResponse {
+        msg_flags: CB_RESPONSE_FAIL,
+        format_data: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_list() {
+        let mut data = 2u32.to_le_bytes().to_vec();
+        for (name, size) in [("dir\\a.exe", 0x1_0000_0002u64), ("b.txt", 3)] {
+            let mut fd = vec![0u8; FILE_DESCRIPTOR_SIZE];
+            fd[..4].copy_from_slice(&FD_FILESIZE.to_le_bytes());
+            fd[64..68].copy_from_slice(&((size >> 32) as u32).to_le_bytes());
+            fd[68..72].copy_from_slice(&(size as u32).to_le_bytes());
+            for (i, c) in name.encode_utf16().enumerate() {
+                fd[72 + i * 2..74 + i * 2].copy_from_slice(&c.to_le_bytes());
+            }
+            data.extend(fd);
+        }
+        assert_eq!(
+            file_list(&data).unwrap(),
+            vec![
+                ("dir\\a.exe".to_owned(), 0x1_0000_0002),
+                ("b.txt".to_owned(), 3)
+            ]
+        );
+        assert!(file_list(&data[..100]).is_none());
+        assert!(file_list(&[]).is_none());
+    }
+}
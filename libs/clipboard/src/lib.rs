@@ -18,6 +18,7 @@ use serde_derive::{Deserialize, Serialize};
 use thiserror::Error;
 
 pub mod context_send;
+pub mod dlp;
 pub mod platform;
 pub use context_send::*;
 
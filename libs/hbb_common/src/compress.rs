@@ -32,3 +32,8 @@ pub fn compress(data: &[u8]) -> Vec<u8> {
 pub fn decompress(data: &[u8]) -> Vec<u8> {
     zstd::decode_all(data).unwrap_or_default()
 }
+
+/// Decompresses `data` of at most `max` bytes, an error if it is more.
+pub fn decompress_bounded(data: &[u8], max: usize) -> io::Result<Vec<u8>> {
+    zstd::bulk::decompress(data, max)
+}
@@ -21,10 +21,10 @@ use sodiumoxide::crypto::sign;
 
 use crate::{
     compress::{compress, decompress},
-    log,
+    keystore, log,
     password_security::{
         decrypt_str_or_original, decrypt_vec_or_original, encrypt_str_or_original,
-        encrypt_vec_or_original, symmetric_crypt,
+        encrypt_vec_or_original, hash_secret, is_secret_hash, symmetric_crypt, verify_secret,
     },
 };
 
@@ -60,6 +60,8 @@ lazy_static::lazy_static! {
         _ => "",
     }.to_owned());
     pub static ref EXE_RENDEZVOUS_SERVER: RwLock<String> = Default::default();
+    // the one of the failover servers in use, set by the health checks
+    static ref ACTIVE_RENDEZVOUS_SERVER: RwLock<String> = Default::default();
     pub static ref APP_NAME: RwLock<String> = RwLock::new("RustDesk".to_owned());
     static ref KEY_PAIR: Mutex<Option<KeyPair>> = Default::default();
     static ref USER_DEFAULT_CONFIG: RwLock<(UserDefaultConfig, Instant)> = RwLock::new((UserDefaultConfig::load(), Instant::now()));
@@ -72,6 +74,10 @@ lazy_static::lazy_static! {
     pub static ref OVERWRITE_LOCAL_SETTINGS: RwLock<HashMap<String, String>> = Default::default();
     pub static ref HARD_SETTINGS: RwLock<HashMap<String, String>> = Default::default();
     pub static ref BUILTIN_SETTINGS: RwLock<HashMap<String, String>> = Default::default();
+    static ref LOCKED_CONFIG: RwLock<Option<LockedConfig>> = Default::default();
+    // the permanent password is in the keystore, which can not be read now, so it is kept there
+    // and can not log in, rather than be taken as empty
+    static ref PASSWORD_UNAVAILABLE: Mutex<bool> = Default::default();
 }
 
 lazy_static::lazy_static! {
@@ -189,6 +195,14 @@ pub struct Config {
     key_confirmed: bool,
     #[serde(default, deserialize_with = "deserialize_hashmap_string_bool")]
     keys_confirmed: HashMap<String, bool>,
+    // the key pair before the last rotation, still signed with until old_key_expiry
+    #[serde(default, deserialize_with = "deserialize_keypair")]
+    old_key_pair: KeyPair,
+    #[serde(default, deserialize_with = "deserialize_i64")]
+    old_key_expiry: i64,
+    // the key the peers trust this device by, apart from the key pair not to be rotated with it
+    #[serde(default, deserialize_with = "deserialize_keypair")]
+    device_key_pair: KeyPair,
 }
 
 #[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
@@ -201,6 +215,19 @@ pub struct Socks5Server {
     pub password: String,
 }
 
+/// The proxy used on some networks, e.g. the one of the office, instead of `Config2::socks`.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct NetworkProfile {
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub name: String,
+    // CIDRs separated by commas, e.g. "10.1.0.0/16, 2001:db8::/32", one of the local addresses is in
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub networks: String,
+    // None to connect directly
+    #[serde(default)]
+    pub socks: Option<Socks5Server>,
+}
+
 // more variable configs
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Config2 {
@@ -210,6 +237,7 @@ pub struct Config2 {
     nat_type: i32,
     #[serde(default, deserialize_with = "deserialize_i32")]
     serial: i32,
+    // the argon2id hash
     #[serde(default, deserialize_with = "deserialize_string")]
     unlock_pin: String,
     #[serde(default, deserialize_with = "deserialize_string")]
@@ -217,6 +245,14 @@ pub struct Config2 {
 
     #[serde(default)]
     socks: Option<Socks5Server>,
+    #[serde(default)]
+    network_profiles: Vec<NetworkProfile>,
+    #[serde(default, deserialize_with = "deserialize_vec_permissionprofile")]
+    permission_profiles: Vec<PermissionProfile>,
+    #[serde(default, deserialize_with = "deserialize_vec_onetimepassword")]
+    one_time_passwords: Vec<OneTimePassword>,
+    #[serde(default, deserialize_with = "deserialize_vec_trustedkey")]
+    trusted_keys: Vec<TrustedKey>,
 
     // the other scalar value must before this
     #[serde(default, deserialize_with = "deserialize_hashmap_string_string")]
@@ -388,6 +424,11 @@ pub struct PeerInfoSerde {
     pub hostname: String,
     #[serde(default, deserialize_with = "deserialize_string")]
     pub platform: String,
+    // learned from the sessions, to wake the peer when it is offline
+    #[serde(default, deserialize_with = "deserialize_vec_string")]
+    pub macs: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_vec_string")]
+    pub lan_networks: Vec<String>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
@@ -444,10 +485,23 @@ impl Config2 {
             config.socks = Some(socks);
             store |= store2;
         }
-        let (unlock_pin, _, store2) =
-            decrypt_str_or_original(&config.unlock_pin, PASSWORD_ENC_VERSION);
-        config.unlock_pin = unlock_pin;
-        store |= store2;
+        for socks in config
+            .network_profiles
+            .iter_mut()
+            .filter_map(|p| p.socks.as_mut())
+        {
+            let (password, _, store2) =
+                decrypt_str_or_original(&socks.password, PASSWORD_ENC_VERSION);
+            socks.password = password;
+            store |= store2;
+        }
+        // the pins encrypted or in plain before are hashed
+        if !config.unlock_pin.is_empty() && !is_secret_hash(&config.unlock_pin) {
+            let (unlock_pin, _, _) =
+                decrypt_str_or_original(&config.unlock_pin, PASSWORD_ENC_VERSION);
+            config.unlock_pin = hash_secret(&unlock_pin);
+            store = true;
+        }
         if store {
             config.store();
         }
@@ -465,8 +519,14 @@ impl Config2 {
                 encrypt_str_or_original(&socks.password, PASSWORD_ENC_VERSION, ENCRYPT_MAX_LEN);
             config.socks = Some(socks);
         }
-        config.unlock_pin =
-            encrypt_str_or_original(&config.unlock_pin, PASSWORD_ENC_VERSION, ENCRYPT_MAX_LEN);
+        for socks in config
+            .network_profiles
+            .iter_mut()
+            .filter_map(|p| p.socks.as_mut())
+        {
+            socks.password =
+                encrypt_str_or_original(&socks.password, PASSWORD_ENC_VERSION, ENCRYPT_MAX_LEN);
+        }
         Config::store_(&config, "2");
     }
 
@@ -542,9 +602,21 @@ impl Config {
     fn load() -> Config {
         let mut config = Config::load_::<Config>("");
         let mut store = false;
-        let (password, _, store1) = decrypt_str_or_original(&config.password, PASSWORD_ENC_VERSION);
-        config.password = password;
-        store |= store1;
+        if config.password == keystore::MARKER {
+            config.password = keystore::get(keystore::PERMANENT_PASSWORD).unwrap_or_else(|| {
+                log::error!(
+                    "The permanent password is in the keystore, which can not be read, so it can not log in"
+                );
+                *PASSWORD_UNAVAILABLE.lock().unwrap() = true;
+                String::default()
+            });
+        } else {
+            let (password, _, store1) =
+                decrypt_str_or_original(&config.password, PASSWORD_ENC_VERSION);
+            config.password = password;
+            // moved into the keystore
+            store |= store1 || (!config.password.is_empty() && keystore::available());
+        }
         let mut id_valid = false;
         let (id, encrypted, store2) = decrypt_str_or_original(&config.enc_id, PASSWORD_ENC_VERSION);
         if encrypted {
@@ -584,8 +656,18 @@ impl Config {
 
     fn store(&self) {
         let mut config = self.clone();
-        config.password =
-            encrypt_str_or_original(&config.password, PASSWORD_ENC_VERSION, ENCRYPT_MAX_LEN);
+        // the permanent password is checked with the peers through its sha256, so it can only be
+        // kept reversible, in the keystore if any, or else encrypted
+        config.password = if config.password.is_empty() && Self::is_permanent_password_unavailable()
+        {
+            keystore::MARKER.to_owned()
+        } else if keystore::set(keystore::PERMANENT_PASSWORD, &config.password)
+            && !config.password.is_empty()
+        {
+            keystore::MARKER.to_owned()
+        } else {
+            encrypt_str_or_original(&config.password, PASSWORD_ENC_VERSION, ENCRYPT_MAX_LEN)
+        };
         config.enc_id = encrypt_str_or_original(&config.id, PASSWORD_ENC_VERSION, ENCRYPT_MAX_LEN);
         config.id = "".to_owned();
         Config::store_(&config, "");
@@ -720,7 +802,10 @@ impl Config {
     }
 
     pub fn get_rendezvous_server() -> String {
-        let mut rendezvous_server = EXE_RENDEZVOUS_SERVER.read().unwrap().clone();
+        let mut rendezvous_server = Self::get_active_rendezvous_server();
+        if rendezvous_server.is_empty() {
+            rendezvous_server = EXE_RENDEZVOUS_SERVER.read().unwrap().clone();
+        }
         if rendezvous_server.is_empty() {
             rendezvous_server = Self::get_option("custom-rendezvous-server");
         }
@@ -745,15 +830,15 @@ impl Config {
     pub fn get_rendezvous_servers() -> Vec<String> {
         let s = EXE_RENDEZVOUS_SERVER.read().unwrap().clone();
         if !s.is_empty() {
-            return vec![s];
+            return Self::with_backup_rendezvous_servers(s);
         }
         let s = Self::get_option("custom-rendezvous-server");
         if !s.is_empty() {
-            return vec![s];
+            return Self::with_backup_rendezvous_servers(s);
         }
         let s = PROD_RENDEZVOUS_SERVER.read().unwrap().clone();
         if !s.is_empty() {
-            return vec![s];
+            return Self::with_backup_rendezvous_servers(s);
         }
         let serial_obsolute = CONFIG2.read().unwrap().serial > SERIAL;
         if serial_obsolute {
@@ -769,6 +854,44 @@ impl Config {
         return RENDEZVOUS_SERVERS.iter().map(|x| x.to_string()).collect();
     }
 
+    // the server first, then the backup servers in order
+    fn with_backup_rendezvous_servers(server: String) -> Vec<String> {
+        let mut servers = vec![server];
+        for backup in Self::get_option(keys::OPTION_BACKUP_RENDEZVOUS_SERVERS).split(',') {
+            let backup = backup.trim();
+            if !backup.is_empty() && !servers.iter().any(|s| s == backup) {
+                servers.push(backup.to_owned());
+            }
+        }
+        servers
+    }
+
+    /// Whether backup servers are configured, the servers are then used one at a time in order
+    /// instead of all at once.
+    pub fn is_rendezvous_failover() -> bool {
+        !Self::get_option(keys::OPTION_BACKUP_RENDEZVOUS_SERVERS)
+            .trim()
+            .is_empty()
+            && Self::get_rendezvous_servers().len() > 1
+    }
+
+    /// The failover server in use, empty if it is not in the servers any more.
+    pub fn get_active_rendezvous_server() -> String {
+        let active = ACTIVE_RENDEZVOUS_SERVER.read().unwrap().clone();
+        if active.is_empty() || !Self::is_rendezvous_failover() {
+            return String::new();
+        }
+        if Self::get_rendezvous_servers().contains(&active) {
+            active
+        } else {
+            String::new()
+        }
+    }
+
+    pub fn set_active_rendezvous_server(host: &str) {
+        *ACTIVE_RENDEZVOUS_SERVER.write().unwrap() = host.to_owned();
+    }
+
     pub fn reset_online() {
         *ONLINE.lock().unwrap() = Default::default();
     }
@@ -913,6 +1036,49 @@ impl Config {
         config.key_pair
     }
 
+    /// Replaces the key pair with a new one, the old one is kept signing along for `grace_days`,
+    /// for the peers which are given it by a rendezvous server not updated yet. The new public key
+    /// is registered to the rendezvous servers again.
+    ///
+    /// Not where the public key is the uuid of the device, mobile or no machine uid, the uuid
+    /// the rendezvous servers know it by and the passwords are encrypted with would change.
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    pub fn rotate_key_pair(grace_days: u32) -> Result<()> {
+        if machine_uid::get().is_err() {
+            anyhow::bail!("The keypair is the uuid of this device, it can not be rotated");
+        }
+        let mut config = CONFIG.write().unwrap();
+        let (pk, sk) = sign::gen_keypair();
+        let key_pair = (sk.0.to_vec(), pk.0.into());
+        log::info!("Rotated the keypair for id: {}", config.id);
+        config.old_key_pair = std::mem::replace(&mut config.key_pair, key_pair.clone());
+        config.old_key_expiry = crate::get_time() + grace_days as i64 * 24 * 3600 * 1000;
+        config.key_confirmed = false;
+        config.keys_confirmed = Default::default();
+        *KEY_PAIR.lock().unwrap() = Some(key_pair);
+        config.store();
+        Ok(())
+    }
+
+    /// The key pair of this device the peers trust it by, generated on first use.
+    pub fn get_device_key_pair() -> KeyPair {
+        let mut config = CONFIG.write().unwrap();
+        if config.device_key_pair.0.is_empty() {
+            let (pk, sk) = sign::gen_keypair();
+            config.device_key_pair = (sk.0.to_vec(), pk.0.into());
+            config.store();
+        }
+        config.device_key_pair.clone()
+    }
+
+    pub fn get_old_key_pair() -> Option<KeyPair> {
+        let config = CONFIG.read().unwrap();
+        if config.old_key_pair.0.is_empty() || config.old_key_expiry <= crate::get_time() {
+            return None;
+        }
+        Some(config.old_key_pair.clone())
+    }
+
     pub fn get_id() -> String {
         let mut id = CONFIG.read().unwrap().id.clone();
         if id.is_empty() {
@@ -995,6 +1161,14 @@ impl Config {
     }
 
     pub fn set_permanent_password(password: &str) {
+        let min_len = LockedConfig::password_min_length();
+        if !password.is_empty() && password.chars().count() < min_len {
+            log::error!(
+                "The permanent password is shorter than {} of the locked config",
+                min_len
+            );
+            return;
+        }
         if HARD_SETTINGS
             .read()
             .unwrap()
@@ -1004,17 +1178,19 @@ impl Config {
             return;
         }
         let mut config = CONFIG.write().unwrap();
-        if password == config.password {
+        if password == config.password && !Self::is_permanent_password_unavailable() {
             return;
         }
         config.password = password.into();
+        // replaces the one in the keystore
+        *PASSWORD_UNAVAILABLE.lock().unwrap() = false;
         config.store();
         Self::clear_trusted_devices();
     }
 
     pub fn get_permanent_password() -> String {
         let mut password = CONFIG.read().unwrap().password.clone();
-        if password.is_empty() {
+        if password.is_empty() && !Self::is_permanent_password_unavailable() {
             if let Some(v) = HARD_SETTINGS.read().unwrap().get("password") {
                 password = v.to_owned();
             }
@@ -1022,6 +1198,12 @@ impl Config {
         password
     }
 
+    /// Whether the permanent password is in the keystore but can not be read, so that it can not
+    /// log in until the keystore is back or a new one is set.
+    pub fn is_permanent_password_unavailable() -> bool {
+        *PASSWORD_UNAVAILABLE.lock().unwrap()
+    }
+
     pub fn set_salt(salt: &str) {
         let mut config = CONFIG.write().unwrap();
         if salt == config.salt {
@@ -1067,7 +1249,8 @@ impl Config {
         })
     }
 
-    pub fn get_socks() -> Option<Socks5Server> {
+    /// The proxy of the networks without a profile.
+    pub fn get_default_socks() -> Option<Socks5Server> {
         Self::get_socks_from_custom_client_advanced_settings(&OVERWRITE_SETTINGS.read().unwrap())
             .or(CONFIG2.read().unwrap().socks.clone())
             .or(Self::get_socks_from_custom_client_advanced_settings(
@@ -1075,45 +1258,232 @@ impl Config {
             ))
     }
 
+    /// The proxy to connect with on the network this side is on, the one forced by the custom
+    /// client comes first.
+    pub fn get_socks() -> Option<Socks5Server> {
+        if OVERWRITE_SETTINGS
+            .read()
+            .unwrap()
+            .contains_key(keys::OPTION_PROXY_URL)
+        {
+            return Self::get_default_socks();
+        }
+        match Self::get_network_profile() {
+            Some(profile) => profile.socks,
+            None => Self::get_default_socks(),
+        }
+    }
+
+    pub fn get_network_profiles() -> Vec<NetworkProfile> {
+        CONFIG2.read().unwrap().network_profiles.clone()
+    }
+
+    pub fn set_network_profiles(profiles: Vec<NetworkProfile>) {
+        let mut config = CONFIG2.write().unwrap();
+        if config.network_profiles == profiles {
+            return;
+        }
+        config.network_profiles = profiles;
+        config.store();
+    }
+
+    /// The first profile with a network one of the local addresses is in.
+    pub fn get_network_profile() -> Option<NetworkProfile> {
+        let profiles = Self::get_network_profiles();
+        if profiles.is_empty() {
+            return None;
+        }
+        // the proxies given by address are routed on their networks even if nothing else is
+        let proxies: Vec<SocketAddr> = profiles
+            .iter()
+            .filter_map(|p| p.socks.as_ref())
+            .filter_map(|s| s.proxy.split("://").last()?.parse().ok())
+            .collect();
+        let local_addrs = crate::socket_client::local_addrs(&proxies);
+        profiles.into_iter().find(|p| {
+            p.networks.split(',').any(|network| {
+                local_addrs
+                    .iter()
+                    .any(|ip| crate::socket_client::in_network(ip, network.trim()))
+            })
+        })
+    }
+
     #[inline]
     pub fn is_proxy() -> bool {
         Self::get_network_type() != NetworkType::Direct
     }
 
     pub fn get_network_type() -> NetworkType {
-        if OVERWRITE_SETTINGS
+        if Self::get_socks().is_some() {
+            NetworkType::ProxySocks
+        } else {
+            NetworkType::Direct
+        }
+    }
+
+    /// The hash of the unlock pin, empty if none.
+    pub fn get_unlock_pin() -> String {
+        CONFIG2.read().unwrap().unlock_pin.clone()
+    }
+
+    /// Sets the unlock pin, or its hash from the server.
+    pub fn set_unlock_pin(pin: &str) {
+        let mut config = CONFIG2.write().unwrap();
+        if pin == config.unlock_pin || !pin.is_empty() && verify_secret(pin, &config.unlock_pin) {
+            return;
+        }
+        config.unlock_pin = if pin.is_empty() || is_secret_hash(pin) {
+            pin.to_owned()
+        } else {
+            hash_secret(pin)
+        };
+        config.store();
+    }
+
+    pub fn verify_unlock_pin(pin: &str) -> bool {
+        verify_secret(pin, &CONFIG2.read().unwrap().unlock_pin)
+    }
+
+    pub fn get_permission_profiles() -> Vec<PermissionProfile> {
+        CONFIG2.read().unwrap().permission_profiles.clone()
+    }
+
+    pub fn get_permission_profile(id: &str) -> Option<PermissionProfile> {
+        CONFIG2
             .read()
             .unwrap()
-            .get(keys::OPTION_PROXY_URL)
-            .is_some()
+            .permission_profiles
+            .iter()
+            .find(|p| p.id == id)
+            .cloned()
+    }
+
+    /// Replaces the profile of the same peer.
+    pub fn set_permission_profile(profile: PermissionProfile) {
+        let mut config = CONFIG2.write().unwrap();
+        if config.permission_profiles.contains(&profile) {
+            return;
+        }
+        config.permission_profiles.retain(|p| p.id != profile.id);
+        config.permission_profiles.push(profile);
+        config.store();
+    }
+
+    pub fn remove_permission_profiles(ids: &Vec<String>) {
+        let mut config = CONFIG2.write().unwrap();
+        let len = config.permission_profiles.len();
+        config.permission_profiles.retain(|p| !ids.contains(&p.id));
+        if config.permission_profiles.len() != len {
+            config.store();
+        }
+    }
+
+    /// The one-time passwords not used or expired yet.
+    pub fn get_one_time_passwords() -> Vec<OneTimePassword> {
+        let mut config = CONFIG2.write().unwrap();
+        let len = config.one_time_passwords.len();
+        config.one_time_passwords.retain(|p| !p.expired());
+        if config.one_time_passwords.len() != len {
+            config.store();
+        }
+        config.one_time_passwords.clone()
+    }
+
+    /// Generates `count` one-time passwords valid for `minutes`, and returns the codes of them,
+    /// which are not kept.
+    pub fn generate_one_time_passwords(
+        count: usize,
+        minutes: u32,
+        scope: &str,
+        label: &str,
+    ) -> Vec<String> {
+        if ![
+            "",
+            OneTimePassword::SCOPE_VIEW,
+            OneTimePassword::SCOPE_FILE_TRANSFER,
+        ]
+        .contains(&scope)
         {
-            return NetworkType::ProxySocks;
+            log::error!("Unknown scope of the one-time passwords: {}", scope);
+            return vec![];
         }
-        if CONFIG2.read().unwrap().socks.is_some() {
-            return NetworkType::ProxySocks;
+        let salt = Self::get_salt();
+        let now = crate::get_time();
+        let mut codes = Vec::new();
+        let mut config = CONFIG2.write().unwrap();
+        config.one_time_passwords.retain(|p| !p.expired());
+        for _ in 0..count {
+            let code = Self::get_auto_password(OneTimePassword::CODE_LEN);
+            config.one_time_passwords.push(OneTimePassword {
+                id: Self::get_auto_password(8),
+                label: label.to_owned(),
+                hash: OneTimePassword::hash(&code, &salt),
+                created_at: now,
+                expires_at: now + minutes as i64 * 60_000,
+                scope: scope.to_owned(),
+            });
+            codes.push(code);
         }
-        if DEFAULT_SETTINGS
+        config.store();
+        codes
+    }
+
+    pub fn remove_one_time_passwords(ids: &Vec<String>) {
+        let mut config = CONFIG2.write().unwrap();
+        let len = config.one_time_passwords.len();
+        config.one_time_passwords.retain(|p| !ids.contains(&p.id));
+        if config.one_time_passwords.len() != len {
+            config.store();
+        }
+    }
+
+    /// Removes and returns the first one-time password not expired `f` accepts, so that it is
+    /// used only once.
+    pub fn take_one_time_password(f: impl Fn(&OneTimePassword) -> bool) -> Option<OneTimePassword> {
+        let mut config = CONFIG2.write().unwrap();
+        let i = config
+            .one_time_passwords
+            .iter()
+            .position(|p| !p.expired() && f(p))?;
+        let password = config.one_time_passwords.remove(i);
+        config.store();
+        Some(password)
+    }
+
+    pub fn get_trusted_keys() -> Vec<TrustedKey> {
+        CONFIG2.read().unwrap().trusted_keys.clone()
+    }
+
+    #[inline]
+    pub fn is_key_trusted(fingerprint: &str) -> bool {
+        CONFIG2
             .read()
             .unwrap()
-            .get(keys::OPTION_PROXY_URL)
-            .is_some()
-        {
-            return NetworkType::ProxySocks;
-        }
-        NetworkType::Direct
+            .trusted_keys
+            .iter()
+            .any(|k| k.fingerprint == fingerprint)
     }
 
-    pub fn get_unlock_pin() -> String {
-        CONFIG2.read().unwrap().unlock_pin.clone()
+    /// Trusts the key of a device, the one of the same fingerprint replaced.
+    pub fn trust_key(key: TrustedKey) {
+        let mut config = CONFIG2.write().unwrap();
+        config
+            .trusted_keys
+            .retain(|k| k.fingerprint != key.fingerprint);
+        config.trusted_keys.push(key);
+        config.store();
     }
 
-    pub fn set_unlock_pin(pin: &str) {
+    pub fn remove_trusted_keys(fingerprints: &Vec<String>) {
         let mut config = CONFIG2.write().unwrap();
-        if pin == config.unlock_pin {
-            return;
+        let len = config.trusted_keys.len();
+        config
+            .trusted_keys
+            .retain(|k| !fingerprints.contains(&k.fingerprint));
+        if config.trusted_keys.len() != len {
+            config.store();
         }
-        config.unlock_pin = pin.to_string();
-        config.store();
     }
 
     pub fn get_trusted_devices_json() -> String {
@@ -1638,6 +2008,15 @@ pub struct DiscoveryPeer {
     pub online: bool,
     #[serde(default, deserialize_with = "deserialize_hashmap_string_string")]
     pub ip_mac: HashMap<String, String>,
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub os: String,
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub version: String,
+    #[serde(default, deserialize_with = "deserialize_bool")]
+    pub rendezvous_online: bool,
+    // the last answer, in seconds since the epoch
+    #[serde(default)]
+    pub last_seen: i64,
 }
 
 impl DiscoveryPeer {
@@ -1682,6 +2061,51 @@ impl LanPeers {
     }
 }
 
+/// The failed logins of a peer id from an ip, kept across the restarts of the service.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LoginLockout {
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub id: String,
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub ip: String,
+    // in a row
+    #[serde(default, deserialize_with = "deserialize_i32")]
+    pub failures: i32,
+    // in milliseconds since the epoch
+    #[serde(default, deserialize_with = "deserialize_i64")]
+    pub last_failure: i64,
+    #[serde(default, deserialize_with = "deserialize_i64")]
+    pub locked_until: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct LoginLockouts {
+    #[serde(default, deserialize_with = "deserialize_vec_loginlockout")]
+    pub lockouts: Vec<LoginLockout>,
+}
+
+impl LoginLockouts {
+    pub fn load() -> LoginLockouts {
+        let _lock = CONFIG.read().unwrap();
+        match confy::load_path(Config::file_("_lockouts")) {
+            Ok(lockouts) => lockouts,
+            Err(err) => {
+                log::error!("Failed to load login lockouts: {}", err);
+                Default::default()
+            }
+        }
+    }
+
+    pub fn store(lockouts: &[LoginLockout]) {
+        let f = LoginLockouts {
+            lockouts: lockouts.to_owned(),
+        };
+        if let Err(err) = store_path(Config::file_("_lockouts"), f) {
+            log::error!("Failed to store login lockouts: {}", err);
+        }
+    }
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct UserDefaultConfig {
     #[serde(default, deserialize_with = "deserialize_hashmap_string_string")]
@@ -2029,6 +2453,166 @@ impl Group {
     }
 }
 
+/// The permissions saved for the connections from a peer by the connection manager.
+///
+/// The id is the one the peer claims, so the permissions saved only take away the ones of the
+/// options, never add to them.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct PermissionProfile {
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub id: String,
+    // by the names of the connection manager, e.g. "keyboard", "clipboard", "audio" and "file"
+    #[serde(default)]
+    pub permissions: HashMap<String, bool>,
+}
+
+/// A password for a single login, e.g. of an external technician, removed once used or expired.
+///
+/// Only the sha256 of the code and the salt is kept, which is all a login is checked against, so
+/// that the code is shown once when generated and never again.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct OneTimePassword {
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub id: String,
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub label: String,
+    // base64
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub hash: String,
+    // in milliseconds
+    #[serde(default, deserialize_with = "deserialize_i64")]
+    pub created_at: i64,
+    #[serde(default, deserialize_with = "deserialize_i64")]
+    pub expires_at: i64,
+    // SCOPE_VIEW or SCOPE_FILE_TRANSFER, any connection if empty
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub scope: String,
+}
+
+impl OneTimePassword {
+    pub const SCOPE_VIEW: &'static str = "view";
+    pub const SCOPE_FILE_TRANSFER: &'static str = "file-transfer";
+    const CODE_LEN: usize = 10;
+
+    pub fn expired(&self) -> bool {
+        self.expires_at <= crate::get_time()
+    }
+
+    fn hash(code: &str, salt: &str) -> String {
+        use sha2::{Digest, Sha256};
+        let mut hasher = Sha256::new();
+        hasher.update(code);
+        hasher.update(salt);
+        base64::encode(hasher.finalize(), base64::Variant::Original)
+    }
+
+    /// The sha256 of the code and the salt, as the controlling side hashes the password before
+    /// the challenge.
+    pub fn salted_hash(&self) -> Vec<u8> {
+        base64::decode(&self.hash, base64::Variant::Original).unwrap_or_default()
+    }
+}
+
+/// The config a deployment is locked down with, the base64 of a json signed by its owner.
+///
+/// Its settings override the options as those of the custom client do, so that neither the ui nor
+/// the cli nor the peers can change them, and the features it names are disabled as the hard
+/// settings of the custom client disable them. A config which does not verify is refused as a
+/// whole, and all the features are disabled instead, as if a config disabling them had loaded.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct LockedConfig {
+    // the options, e.g. custom-rendezvous-server, key, verification-method
+    #[serde(default)]
+    pub settings: HashMap<String, String>,
+    // of settings, installation, account, ab and tcp-listen
+    #[serde(default)]
+    pub disabled: Vec<String>,
+    // of the permanent password, 0 for any
+    #[serde(default)]
+    pub password_min_length: usize,
+}
+
+impl LockedConfig {
+    pub const FEATURES: [&'static str; 5] =
+        ["settings", "installation", "account", "ab", "tcp-listen"];
+
+    pub fn verify(signed: &str, pk: &sign::PublicKey) -> crate::ResultType<Self> {
+        let Ok(data) = base64::decode(signed.trim(), base64::Variant::Original) else {
+            anyhow::bail!("Invalid base64 of the locked config");
+        };
+        let Ok(data) = sign::verify(&data, pk) else {
+            anyhow::bail!("Signature mismatch of the locked config");
+        };
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    /// Verifies the config `signed` by the key `pk` and locks the options down to it.
+    pub fn load(signed: &str, pk: &sign::PublicKey) -> crate::ResultType<()> {
+        Self::lock(Self::verify(signed, pk)?);
+        Ok(())
+    }
+
+    /// Locks the options down with all the features disabled, for a config which can not be
+    /// verified.
+    pub fn fail_closed() {
+        log::error!("The locked config can not be verified, all the features are disabled");
+        Self::lock(Self {
+            disabled: Self::FEATURES.iter().map(|x| x.to_string()).collect(),
+            ..Default::default()
+        });
+    }
+
+    fn lock(config: Self) {
+        OVERWRITE_SETTINGS
+            .write()
+            .unwrap()
+            .extend(config.settings.clone());
+        let mut hard = HARD_SETTINGS.write().unwrap();
+        for feature in config.disabled.iter() {
+            hard.insert(format!("disable-{}", feature), "Y".to_owned());
+        }
+        log::info!(
+            "Locked config loaded, {} settings, disabled: {:?}",
+            config.settings.len(),
+            config.disabled
+        );
+        *LOCKED_CONFIG.write().unwrap() = Some(config);
+    }
+
+    #[inline]
+    pub fn is_locked() -> bool {
+        LOCKED_CONFIG.read().unwrap().is_some()
+    }
+
+    #[inline]
+    pub fn password_min_length() -> usize {
+        LOCKED_CONFIG
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|c| c.password_min_length)
+            .unwrap_or(0)
+    }
+}
+
+/// The key of a device of the peers trusted, which it proves it owns at each login by signing the
+/// challenge of the hash.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct TrustedKey {
+    // the public key in hex, in the groups of 4 digits the fingerprints of the keys are shown in
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub fingerprint: String,
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub id: String,
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub name: String,
+    #[serde(default, deserialize_with = "deserialize_string")]
+    pub platform: String,
+    // in milliseconds, when trusted
+    #[serde(default, deserialize_with = "deserialize_i64")]
+    pub time: i64,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct TrustedDevice {
     pub hwid: Bytes,
@@ -2038,16 +2622,28 @@ pub struct TrustedDevice {
     pub platform: String,
 }
 
+const TRUSTED_DEVICE_DAYS: i64 = 30;
+
 impl TrustedDevice {
+    // a device is trusted for the days of OPTION_TRUSTED_DEVICE_DAYS after its 2fa code is verified
     pub fn outdate(&self) -> bool {
-        const DAYS_90: i64 = 90 * 24 * 60 * 60 * 1000;
-        self.time + DAYS_90 < crate::get_time()
+        self.outdate_after(&Config::get_option(keys::OPTION_TRUSTED_DEVICE_DAYS))
+    }
+
+    fn outdate_after(&self, days: &str) -> bool {
+        let days = days
+            .parse::<i64>()
+            .ok()
+            .filter(|d| *d > 0)
+            .unwrap_or(TRUSTED_DEVICE_DAYS);
+        self.time + days * 24 * 60 * 60 * 1000 < crate::get_time()
     }
 }
 
 deserialize_default!(deserialize_string, String);
 deserialize_default!(deserialize_bool, bool);
 deserialize_default!(deserialize_i32, i32);
+deserialize_default!(deserialize_i64, i64);
 deserialize_default!(deserialize_vec_u8, Vec<u8>);
 deserialize_default!(deserialize_vec_string, Vec<String>);
 deserialize_default!(deserialize_vec_i32_string_i32, Vec<(i32, String, i32)>);
@@ -2056,6 +2652,10 @@ deserialize_default!(deserialize_vec_abpeer, Vec<AbPeer>);
 deserialize_default!(deserialize_vec_abentry, Vec<AbEntry>);
 deserialize_default!(deserialize_vec_groupuser, Vec<GroupUser>);
 deserialize_default!(deserialize_vec_grouppeer, Vec<GroupPeer>);
+deserialize_default!(deserialize_vec_permissionprofile, Vec<PermissionProfile>);
+deserialize_default!(deserialize_vec_onetimepassword, Vec<OneTimePassword>);
+deserialize_default!(deserialize_vec_trustedkey, Vec<TrustedKey>);
+deserialize_default!(deserialize_vec_loginlockout, Vec<LoginLockout>);
 deserialize_default!(deserialize_keypair, KeyPair);
 deserialize_default!(deserialize_size, Size);
 deserialize_default!(deserialize_hashmap_string_string, HashMap<String, String>);
@@ -2179,6 +2779,15 @@ pub mod keys {
     pub const OPTION_I444: &str = "i444";
     pub const OPTION_REVERSE_MOUSE_WHEEL: &str = "reverse_mouse_wheel";
     pub const OPTION_SWAP_LEFT_RIGHT_MOUSE: &str = "swap-left-right-mouse";
+    pub const OPTION_FORWARD_GAMEPAD: &str = "forward-gamepad";
+    // one of the presets of the audio encoder, empty for the default
+    pub const OPTION_AUDIO_QUALITY: &str = "audio-quality";
+    // the audio input of the peer to capture, empty for the one of its settings
+    pub const OPTION_REMOTE_AUDIO_INPUT: &str = "remote-audio-input";
+    // suppress the noise of the audio of the peer, and of the microphone in a voice call
+    pub const OPTION_DENOISE_AUDIO: &str = "denoise-audio";
+    // a halo around the cursor of the peer and a ripple at each click, drawn on its screen
+    pub const OPTION_PRESENTER_MODE: &str = "presenter-mode";
     pub const OPTION_DISPLAYS_AS_INDIVIDUAL_WINDOWS: &str = "displays_as_individual_windows";
     pub const OPTION_USE_ALL_MY_DISPLAYS_FOR_THE_REMOTE_SESSION: &str =
         "use_all_my_displays_for_the_remote_session";
@@ -2188,6 +2797,8 @@ pub mod keys {
     pub const OPTION_CUSTOM_IMAGE_QUALITY: &str = "custom_image_quality";
     pub const OPTION_CUSTOM_FPS: &str = "custom-fps";
     pub const OPTION_CODEC_PREFERENCE: &str = "codec-preference";
+    // comma separated, sharpen, deband, grayscale, smooth
+    pub const OPTION_VIDEO_FILTERS: &str = "video-filters";
     pub const OPTION_SYNC_INIT_CLIPBOARD: &str = "sync-init-clipboard";
     pub const OPTION_THEME: &str = "theme";
     pub const OPTION_LANGUAGE: &str = "lang";
@@ -2208,30 +2819,180 @@ pub mod keys {
     pub const OPTION_ENABLE_FILE_TRANSFER: &str = "enable-file-transfer";
     pub const OPTION_ENABLE_AUDIO: &str = "enable-audio";
     pub const OPTION_ENABLE_TUNNEL: &str = "enable-tunnel";
+    // the peers may open the terminals of this side, off unless allowed
+    pub const OPTION_ALLOW_TERMINAL: &str = "allow-terminal";
+    // the command line of the shell of the terminals, the one of the platform if empty
+    pub const OPTION_TERMINAL_SHELL: &str = "terminal-shell";
+    // the peers logged in may wake the devices of the lans of this side through it, off unless
+    // allowed
+    pub const OPTION_ALLOW_WOL_RELAY: &str = "allow-wol-relay";
+    // only one peer controls at once when several are connected, off unless allowed
+    pub const OPTION_ALLOW_INPUT_ARBITRATION: &str = "allow-input-arbitration";
+    // the peers controlling may draw over the screen, off unless allowed
+    pub const OPTION_ALLOW_ANNOTATION: &str = "allow-annotation";
     pub const OPTION_ENABLE_REMOTE_RESTART: &str = "enable-remote-restart";
     pub const OPTION_ENABLE_RECORD_SESSION: &str = "enable-record-session";
     pub const OPTION_ENABLE_BLOCK_INPUT: &str = "enable-block-input";
+    // the parts of enable-keyboard
+    pub const OPTION_ENABLE_KEY_INPUT: &str = "enable-key-input";
+    pub const OPTION_ENABLE_MOUSE_INPUT: &str = "enable-mouse-input";
+    pub const OPTION_ENABLE_CLIPBOARD_TYPING: &str = "enable-clipboard-typing";
     pub const OPTION_ALLOW_REMOTE_CONFIG_MODIFICATION: &str = "allow-remote-config-modification";
     pub const OPTION_ENABLE_LAN_DISCOVERY: &str = "enable-lan-discovery";
     pub const OPTION_DIRECT_SERVER: &str = "direct-server";
     pub const OPTION_DIRECT_ACCESS_PORT: &str = "direct-access-port";
+    // the pre-shared secret of the knocks which open the direct access port, empty for none
+    pub const OPTION_DIRECT_ACCESS_KNOCK_KEY: &str = "direct-access-knock-key";
+    // the direct access port and the punch holes are mapped on the router with PCP, NAT-PMP or UPnP
+    pub const OPTION_ALLOW_PORT_MAPPING: &str = "allow-port-mapping";
+    // comma separated, the aliases of this device registered with the rendezvous servers
+    pub const OPTION_ALIASES: &str = "aliases";
+    // "ipv4" or "ipv6" to try first when both are reachable, empty for the order of the system
+    pub const OPTION_PREFERRED_IP_FAMILY: &str = "preferred-ip-family";
+    pub const OPTION_ALLOW_QUIC: &str = "allow-quic";
+    // the servers are reached through their tls tunnel on port 443, "Y" always, "N" never, and if
+    // their ports are unreachable otherwise
+    pub const OPTION_TLS_TUNNEL: &str = "tls-tunnel";
+    // comma separated, the security key credentials of the controlling sides, one of which is to be
+    // touched to be let in
+    pub const OPTION_FIDO2_CREDENTIALS: &str = "fido2-credentials";
+    // the same of the keys of the host, one of which is to be touched to change the security settings
+    pub const OPTION_FIDO2_HOST_CREDENTIALS: &str = "fido2-host-credentials";
+    // the TURN server to relay the sessions through instead of the relay server, e.g.
+    // "turn:turn.example.com:3478", and its long-term credentials
+    pub const OPTION_TURN_SERVER: &str = "turn-server";
+    pub const OPTION_TURN_USERNAME: &str = "turn-username";
+    pub const OPTION_TURN_PASSWORD: &str = "turn-password";
+    // in seconds, between the heartbeats of a session
+    pub const OPTION_KEEP_ALIVE_INTERVAL: &str = "keep-alive-interval";
+    // the heartbeats missed before a session is shown as degraded
+    pub const OPTION_KEEP_ALIVE_RETRIES: &str = "keep-alive-retries";
+    // in seconds, a session is closed after nothing is received for this long
+    pub const OPTION_KEEP_ALIVE_TIMEOUT: &str = "keep-alive-timeout";
+    // the dscp marked on the packets of the sessions, by name like "EF" or "AF41" or by value, empty
+    // for no marking
+    pub const OPTION_DSCP_INTERACTIVE: &str = "dscp-interactive";
+    pub const OPTION_DSCP_FILE_TRANSFER: &str = "dscp-file-transfer";
     pub const OPTION_WHITELIST: &str = "whitelist";
+    // the allow and deny rules of the addresses of the connections, one a line, e.g.
+    // "allow 192.168.1.0/24 mon-fri 09:00-18:00"
+    pub const OPTION_IP_RULES: &str = "ip-rules";
+    // the windows of the time the unattended connections are accepted in, one a line, e.g.
+    // "mon-fri 08:00-18:00", or "123456789: sat 10:00-12:00" for a peer
+    pub const OPTION_UNATTENDED_SCHEDULE: &str = "unattended-schedule";
+    // refuse the unattended connections outside the schedule instead of asking to accept them
+    pub const OPTION_REFUSE_OUTSIDE_SCHEDULE: &str = "refuse-outside-schedule";
     pub const OPTION_ALLOW_AUTO_DISCONNECT: &str = "allow-auto-disconnect";
     pub const OPTION_AUTO_DISCONNECT_TIMEOUT: &str = "auto-disconnect-timeout";
+    // "view-only" takes the control away instead of disconnecting the inactive sessions
+    pub const OPTION_AUTO_DISCONNECT_ACTION: &str = "auto-disconnect-action";
+    pub const OPTION_ALLOW_AUTO_BLOCK_INPUT: &str = "allow-auto-block-input";
+    // in seconds
+    pub const OPTION_AUTO_BLOCK_INPUT_TIMEOUT: &str = "auto-block-input-timeout";
+    pub const OPTION_ALLOW_VOICE_CALL_VIRTUAL_MIC: &str = "allow-voice-call-virtual-mic";
+    // turn the microphone down while only the peer of a voice call talks, see echo
+    pub const OPTION_ENABLE_VOICE_CALL_ECHO_SUPPRESSION: &str =
+        "enable-voice-call-echo-suppression";
+    // the microphone sent in a voice call only while a button is held
+    pub const OPTION_VOICE_CALL_PUSH_TO_TALK: &str = "voice-call-push-to-talk";
+    // the view settings of the peers kept in the account signed in, see view_settings
+    pub const OPTION_SYNC_VIEW_SETTINGS: &str = "sync-view-settings";
     pub const OPTION_ALLOW_ONLY_CONN_WINDOW_OPEN: &str = "allow-only-conn-window-open";
     pub const OPTION_ALLOW_AUTO_RECORD_INCOMING: &str = "allow-auto-record-incoming";
+    // the ids of the peers and the time drawn over the screen shared, see watermark of the server
+    pub const OPTION_ALLOW_WATERMARK: &str = "allow-watermark";
+    // the load of this side sent to the peers watching it, see system_stats of the server, off
+    // unless allowed
+    pub const OPTION_ALLOW_SYSTEM_STATS: &str = "allow-system-stats";
+    // the processes of this side listed and killed by the peers, see task_manager of the server
+    pub const OPTION_ALLOW_TASK_MANAGER: &str = "allow-task-manager";
+    // the services of this side listed and controlled by the peers, see service_manager of the
+    // server
+    pub const OPTION_ALLOW_SERVICE_MANAGER: &str = "allow-service-manager";
+    // the camera of this side viewed by the peers, see camera_service of the server
+    pub const OPTION_ALLOW_REMOTE_CAMERA: &str = "allow-remote-camera";
+    // the sessions of the view-only peers share the stream of the others, see broadcast of the server
+    pub const OPTION_ALLOW_BROADCAST: &str = "allow-broadcast";
+    // the most viewers of the broadcast at a time
+    pub const OPTION_BROADCAST_MAX_VIEWERS: &str = "broadcast-max-viewers";
     pub const OPTION_ALLOW_AUTO_RECORD_OUTGOING: &str = "allow-auto-record-outgoing";
     pub const OPTION_VIDEO_SAVE_DIRECTORY: &str = "video-save-directory";
+    // in MB
+    pub const OPTION_RECORD_SPLIT_SIZE: &str = "record-split-size";
+    // in minutes
+    pub const OPTION_RECORD_SPLIT_DURATION: &str = "record-split-duration";
     pub const OPTION_ENABLE_ABR: &str = "enable-abr";
+    // Edge of the square around the cursor encoded with higher quality, in pixels, 0 to disable
+    pub const OPTION_ROI_SIZE: &str = "roi-size";
+    pub const OPTION_ALLOW_LOSSLESS_TEXT_PATCHES: &str = "allow-lossless-text-patches";
+    pub const OPTION_ENCODER_FALLBACK_CHAIN: &str = "encoder-fallback-chain";
+    // kbps, shared by video, audio and file transfer of a session, empty for no limit
+    pub const OPTION_BANDWIDTH_LIMIT: &str = "bandwidth-limit";
+    // kbps, shared by all the file transfers sent, empty for no limit
+    pub const OPTION_FILE_TRANSFER_RATE_LIMIT: &str = "file-transfer-rate-limit";
+    // the file transfer blocks are compressed with zstd, unless either side disables it
+    pub const OPTION_ENABLE_FILE_TRANSFER_COMPRESSION: &str = "enable-file-transfer-compression";
+    // the file transfers of the peers are recorded in file_transfer_audit.log
+    pub const OPTION_ENABLE_FILE_TRANSFER_AUDIT_LOG: &str = "enable-file-transfer-audit-log";
+    // the url the events of the file transfers are posted to, empty for none
+    pub const OPTION_FILE_TRANSFER_AUDIT_WEBHOOK: &str = "file-transfer-audit-webhook";
+    // json result of --bench-codecs
+    pub const OPTION_CODEC_BENCHMARK: &str = "codec-benchmark";
     pub const OPTION_ALLOW_REMOVE_WALLPAPER: &str = "allow-remove-wallpaper";
     pub const OPTION_ALLOW_ALWAYS_SOFTWARE_RENDER: &str = "allow-always-software-render";
     pub const OPTION_ALLOW_LINUX_HEADLESS: &str = "allow-linux-headless";
     pub const OPTION_ENABLE_HWCODEC: &str = "enable-hwcodec";
     pub const OPTION_APPROVE_MODE: &str = "approve-mode";
+    // the peers accepted by click must tell why they connect before the accept dialog shows
+    pub const OPTION_REQUIRE_ACCESS_REASON: &str = "require-access-reason";
+    // the connections not accepted in time are denied
+    pub const OPTION_ALLOW_APPROVE_TIMEOUT: &str = "allow-approve-timeout";
+    // in seconds
+    pub const OPTION_APPROVE_TIMEOUT: &str = "approve-timeout";
+    // the devices of the peers not trusted by their keys must be accepted in the cm, and are
+    // trusted then
+    pub const OPTION_TRUST_KEYS_ON_FIRST_USE: &str = "trust-keys-on-first-use";
+    // the devices of the peers not trusted by their keys are refused, even with the password
+    pub const OPTION_REJECT_UNTRUSTED_KEYS: &str = "reject-untrusted-keys";
+    // the connections notified to the admins of this side, see notify of the server
+    pub const OPTION_NOTIFY_WEBHOOK: &str = "notify-webhook";
+    // host:port, implicit tls on 465 and starttls on the others
+    pub const OPTION_NOTIFY_SMTP_SERVER: &str = "notify-smtp-server";
+    pub const OPTION_NOTIFY_SMTP_USERNAME: &str = "notify-smtp-username";
+    pub const OPTION_NOTIFY_SMTP_PASSWORD: &str = "notify-smtp-password";
+    // the smtp username if empty
+    pub const OPTION_NOTIFY_EMAIL_FROM: &str = "notify-email-from";
+    // comma separated
+    pub const OPTION_NOTIFY_EMAIL_TO: &str = "notify-email-to";
+    // comma separated of session_start, session_end and auth_failure, all of them if empty
+    pub const OPTION_NOTIFY_EVENTS: &str = "notify-events";
+    // the identity provider the controlling side signs in with, see idp, which checks the ldap
+    // credentials too
+    pub const OPTION_OIDC_ISSUER: &str = "oidc-issuer";
+    pub const OPTION_OIDC_CLIENT_ID: &str = "oidc-client-id";
+    // "oidc" or "ldap", required of the incoming connections, empty for none
+    pub const OPTION_IDENTITY_PROVIDER: &str = "identity-provider";
+    // the group the identities required must be in, empty for any
+    pub const OPTION_IDENTITY_GROUP: &str = "identity-group";
+    // the security events of the connections are recorded in audit.log, see audit_log
+    pub const OPTION_ENABLE_AUDIT_LOG: &str = "enable-audit-log";
+    // the days the audit logs moved aside are kept, 90 if empty
+    pub const OPTION_AUDIT_LOG_RETENTION_DAYS: &str = "audit-log-retention-days";
+    // the days a device which passed the 2fa is trusted, 30 if empty
+    pub const OPTION_TRUSTED_DEVICE_DAYS: &str = "trusted-device-days";
+    // a json array of the rules of the data loss prevention, see dlp
+    pub const OPTION_DLP_RULES: &str = "dlp-rules";
     pub const OPTION_VERIFICATION_METHOD: &str = "verification-method";
     pub const OPTION_CUSTOM_RENDEZVOUS_SERVER: &str = "custom-rendezvous-server";
+    // comma separated, tried in order once the custom rendezvous server is down
+    pub const OPTION_BACKUP_RENDEZVOUS_SERVERS: &str = "backup-rendezvous-servers";
+    // comma separated, the relay server of a session is picked among them and the relay server by
+    // latency
+    pub const OPTION_EXTRA_RELAY_SERVERS: &str = "extra-relay-servers";
     pub const OPTION_API_SERVER: &str = "api-server";
     pub const OPTION_KEY: &str = "key";
+    // the sha256 of the certificates or the public keys the tls servers are pinned to, see tls_pin
+    pub const OPTION_TLS_PINS: &str = "tls-pins";
     pub const OPTION_PRESET_ADDRESS_BOOK_NAME: &str = "preset-address-book-name";
     pub const OPTION_PRESET_ADDRESS_BOOK_TAG: &str = "preset-address-book-tag";
     pub const OPTION_ENABLE_DIRECTX_CAPTURE: &str = "enable-directx-capture";
@@ -2266,6 +3027,20 @@ pub mod keys {
     pub const OPTION_FLUTTER_PEER_CARD_UI_TYLE: &str = "peer-card-ui-type";
     pub const OPTION_FLUTTER_CURRENT_AB_NAME: &str = "current-ab-name";
     pub const OPTION_ALLOW_REMOTE_CM_MODIFICATION: &str = "allow-remote-cm-modification";
+    // comma separated, alt-tab, cmd-tab, win, media
+    pub const OPTION_LOCAL_SHORTCUTS: &str = "local-shortcuts";
+    pub const OPTION_CAPTURE_ALL_KEYS_IN_FULLSCREEN: &str = "capture-all-keys-in-fullscreen";
+    // in milliseconds, between the keys of the clipboard typed as keystrokes
+    pub const OPTION_CLIPBOARD_TYPING_DELAY: &str = "clipboard-typing-delay";
+    pub const OPTION_ENABLE_PRESERVE_FILE_PERMISSIONS: &str = "enable-preserve-file-permissions";
+    pub const OPTION_ENABLE_PRESERVE_FILE_TIMESTAMPS: &str = "enable-preserve-file-timestamps";
+    pub const OPTION_ENABLE_PRESERVE_FILE_XATTRS: &str = "enable-preserve-file-xattrs";
+    // skip, copy or follow, the symbolic links in the folders sent
+    pub const OPTION_FILE_TRANSFER_LINK_POLICY: &str = "file-transfer-link-policy";
+    // ask, overwrite, newer, skip or rename, the file existing where a file is written
+    pub const OPTION_FILE_TRANSFER_CONFLICT_POLICY: &str = "file-transfer-conflict-policy";
+    // the files removed are deleted instead of moved to the trash
+    pub const OPTION_ENABLE_DELETE_PERMANENTLY: &str = "enable-delete-permanently";
 
     // android floating window options
     pub const OPTION_DISABLE_FLOATING_WINDOW: &str = "disable-floating-window";
@@ -2306,6 +3081,7 @@ pub mod keys {
         OPTION_I444,
         OPTION_REVERSE_MOUSE_WHEEL,
         OPTION_SWAP_LEFT_RIGHT_MOUSE,
+        OPTION_FORWARD_GAMEPAD,
         OPTION_DISPLAYS_AS_INDIVIDUAL_WINDOWS,
         OPTION_USE_ALL_MY_DISPLAYS_FOR_THE_REMOTE_SESSION,
         OPTION_VIEW_STYLE,
@@ -2314,6 +3090,8 @@ pub mod keys {
         OPTION_CUSTOM_IMAGE_QUALITY,
         OPTION_CUSTOM_FPS,
         OPTION_CODEC_PREFERENCE,
+        OPTION_VIDEO_FILTERS,
+        OPTION_BANDWIDTH_LIMIT,
         OPTION_SYNC_INIT_CLIPBOARD,
     ];
     // DEFAULT_LOCAL_SETTINGS, OVERWRITE_LOCAL_SETTINGS
@@ -2347,6 +3125,19 @@ pub mod keys {
         OPTION_ALLOW_REMOTE_CM_MODIFICATION,
         OPTION_ALLOW_AUTO_RECORD_OUTGOING,
         OPTION_VIDEO_SAVE_DIRECTORY,
+        OPTION_VOICE_CALL_PUSH_TO_TALK,
+        OPTION_SYNC_VIEW_SETTINGS,
+        OPTION_RECORD_SPLIT_SIZE,
+        OPTION_RECORD_SPLIT_DURATION,
+        OPTION_LOCAL_SHORTCUTS,
+        OPTION_CAPTURE_ALL_KEYS_IN_FULLSCREEN,
+        OPTION_CLIPBOARD_TYPING_DELAY,
+        OPTION_ENABLE_PRESERVE_FILE_PERMISSIONS,
+        OPTION_ENABLE_PRESERVE_FILE_TIMESTAMPS,
+        OPTION_ENABLE_PRESERVE_FILE_XATTRS,
+        OPTION_FILE_TRANSFER_LINK_POLICY,
+        OPTION_FILE_TRANSFER_CONFLICT_POLICY,
+        OPTION_ENABLE_DELETE_PERMANENTLY,
     ];
     // DEFAULT_SETTINGS, OVERWRITE_SETTINGS
     pub const KEYS_SETTINGS: &[&str] = &[
@@ -2356,31 +3147,100 @@ pub mod keys {
         OPTION_ENABLE_FILE_TRANSFER,
         OPTION_ENABLE_AUDIO,
         OPTION_ENABLE_TUNNEL,
+        OPTION_ALLOW_TERMINAL,
+        OPTION_TERMINAL_SHELL,
+        OPTION_ALLOW_WOL_RELAY,
+        OPTION_ALLOW_INPUT_ARBITRATION,
+        OPTION_ALLOW_ANNOTATION,
         OPTION_ENABLE_REMOTE_RESTART,
         OPTION_ENABLE_RECORD_SESSION,
         OPTION_ENABLE_BLOCK_INPUT,
+        OPTION_ENABLE_KEY_INPUT,
+        OPTION_ENABLE_MOUSE_INPUT,
+        OPTION_ENABLE_CLIPBOARD_TYPING,
         OPTION_ALLOW_REMOTE_CONFIG_MODIFICATION,
         OPTION_ENABLE_LAN_DISCOVERY,
         OPTION_DIRECT_SERVER,
         OPTION_DIRECT_ACCESS_PORT,
+        OPTION_DIRECT_ACCESS_KNOCK_KEY,
+        OPTION_ALLOW_PORT_MAPPING,
+        OPTION_PREFERRED_IP_FAMILY,
+        OPTION_ALLOW_QUIC,
+        OPTION_TLS_TUNNEL,
+        OPTION_TURN_SERVER,
+        OPTION_TURN_USERNAME,
+        OPTION_TURN_PASSWORD,
+        OPTION_KEEP_ALIVE_INTERVAL,
+        OPTION_KEEP_ALIVE_RETRIES,
+        OPTION_KEEP_ALIVE_TIMEOUT,
+        OPTION_DSCP_INTERACTIVE,
+        OPTION_DSCP_FILE_TRANSFER,
         OPTION_WHITELIST,
+        OPTION_IP_RULES,
+        OPTION_UNATTENDED_SCHEDULE,
+        OPTION_REFUSE_OUTSIDE_SCHEDULE,
         OPTION_ALLOW_AUTO_DISCONNECT,
         OPTION_AUTO_DISCONNECT_TIMEOUT,
+        OPTION_AUTO_DISCONNECT_ACTION,
+        OPTION_ALLOW_AUTO_BLOCK_INPUT,
+        OPTION_AUTO_BLOCK_INPUT_TIMEOUT,
+        OPTION_ALLOW_VOICE_CALL_VIRTUAL_MIC,
+        OPTION_ENABLE_VOICE_CALL_ECHO_SUPPRESSION,
         OPTION_ALLOW_ONLY_CONN_WINDOW_OPEN,
         OPTION_ALLOW_AUTO_RECORD_INCOMING,
+        OPTION_ALLOW_WATERMARK,
+        OPTION_ALLOW_SYSTEM_STATS,
+        OPTION_ALLOW_TASK_MANAGER,
+        OPTION_ALLOW_SERVICE_MANAGER,
+        OPTION_ALLOW_REMOTE_CAMERA,
+        OPTION_ALLOW_BROADCAST,
+        OPTION_BROADCAST_MAX_VIEWERS,
+        OPTION_RECORD_SPLIT_SIZE,
+        OPTION_RECORD_SPLIT_DURATION,
         OPTION_ENABLE_ABR,
+        OPTION_ROI_SIZE,
+        OPTION_ALLOW_LOSSLESS_TEXT_PATCHES,
+        OPTION_ENCODER_FALLBACK_CHAIN,
+        OPTION_BANDWIDTH_LIMIT,
+        OPTION_FILE_TRANSFER_RATE_LIMIT,
+        OPTION_ENABLE_FILE_TRANSFER_COMPRESSION,
+        OPTION_ENABLE_FILE_TRANSFER_AUDIT_LOG,
+        OPTION_FILE_TRANSFER_AUDIT_WEBHOOK,
         OPTION_ALLOW_REMOVE_WALLPAPER,
         OPTION_ALLOW_ALWAYS_SOFTWARE_RENDER,
         OPTION_ALLOW_LINUX_HEADLESS,
         OPTION_ENABLE_HWCODEC,
         OPTION_APPROVE_MODE,
+        OPTION_REQUIRE_ACCESS_REASON,
+        OPTION_ALLOW_APPROVE_TIMEOUT,
+        OPTION_APPROVE_TIMEOUT,
+        OPTION_TRUST_KEYS_ON_FIRST_USE,
+        OPTION_REJECT_UNTRUSTED_KEYS,
+        OPTION_NOTIFY_WEBHOOK,
+        OPTION_NOTIFY_SMTP_SERVER,
+        OPTION_NOTIFY_SMTP_USERNAME,
+        OPTION_NOTIFY_SMTP_PASSWORD,
+        OPTION_NOTIFY_EMAIL_FROM,
+        OPTION_NOTIFY_EMAIL_TO,
+        OPTION_NOTIFY_EVENTS,
+        OPTION_OIDC_ISSUER,
+        OPTION_OIDC_CLIENT_ID,
+        OPTION_IDENTITY_PROVIDER,
+        OPTION_IDENTITY_GROUP,
+        OPTION_ENABLE_AUDIT_LOG,
+        OPTION_AUDIT_LOG_RETENTION_DAYS,
+        OPTION_TRUSTED_DEVICE_DAYS,
+        OPTION_DLP_RULES,
         OPTION_VERIFICATION_METHOD,
         OPTION_PROXY_URL,
         OPTION_PROXY_USERNAME,
         OPTION_PROXY_PASSWORD,
         OPTION_CUSTOM_RENDEZVOUS_SERVER,
+        OPTION_BACKUP_RENDEZVOUS_SERVERS,
+        OPTION_EXTRA_RELAY_SERVERS,
         OPTION_API_SERVER,
         OPTION_KEY,
+        OPTION_TLS_PINS,
         OPTION_PRESET_ADDRESS_BOOK_NAME,
         OPTION_PRESET_ADDRESS_BOOK_TAG,
         OPTION_ENABLE_DIRECTX_CAPTURE,
@@ -2623,6 +3483,94 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_permission_profiles_serialize() {
+        let mut cfg = Config2::default();
+        cfg.permission_profiles.push(PermissionProfile {
+            id: "123456789".to_owned(),
+            permissions: HashMap::from([
+                ("keyboard".to_owned(), false),
+                ("file".to_owned(), false),
+            ]),
+        });
+        cfg.options.insert("a".to_owned(), "b".to_owned());
+        let res = toml::to_string_pretty(&cfg).unwrap();
+        assert_eq!(toml::from_str::<Config2>(&res), Ok(cfg));
+        let wrong_type_str = r#"
+        permission_profiles = 1
+        "#;
+        assert_eq!(
+            toml::from_str::<Config2>(wrong_type_str),
+            Ok(Default::default())
+        );
+    }
+
+    #[test]
+    fn test_one_time_passwords_serialize() {
+        let mut cfg = Config2::default();
+        cfg.one_time_passwords.push(OneTimePassword {
+            id: "a2b3c4d5".to_owned(),
+            label: "technician".to_owned(),
+            hash: OneTimePassword::hash("abcdefghjk", "salt"),
+            created_at: 1,
+            expires_at: 2,
+            scope: OneTimePassword::SCOPE_VIEW.to_owned(),
+        });
+        cfg.options.insert("a".to_owned(), "b".to_owned());
+        let res = toml::to_string_pretty(&cfg).unwrap();
+        assert_eq!(toml::from_str::<Config2>(&res), Ok(cfg.clone()));
+        assert!(cfg.one_time_passwords[0].expired());
+        use sha2::{Digest, Sha256};
+        assert_eq!(
+            cfg.one_time_passwords[0].salted_hash(),
+            Sha256::digest("abcdefghjksalt").to_vec()
+        );
+        let wrong_type_str = r#"
+        one_time_passwords = 1
+        "#;
+        assert_eq!(
+            toml::from_str::<Config2>(wrong_type_str),
+            Ok(Default::default())
+        );
+    }
+
+    #[test]
+    fn test_locked_config_verify() {
+        let (pk, sk) = sign::gen_keypair();
+        let json = r#"{
+            "settings": {"custom-rendezvous-server": "id.example.com", "verification-method": "use-temporary-password"},
+            "disabled": ["settings", "installation"],
+            "password_min_length": 12
+        }"#;
+        let signed = base64::encode(sign::sign(json.as_bytes(), &sk), base64::Variant::Original);
+        let config = LockedConfig::verify(&signed, &pk).unwrap();
+        assert_eq!(
+            config.settings["custom-rendezvous-server"],
+            "id.example.com"
+        );
+        assert_eq!(config.disabled, vec!["settings", "installation"]);
+        assert_eq!(config.password_min_length, 12);
+        let (other_pk, _) = sign::gen_keypair();
+        assert!(LockedConfig::verify(&signed, &other_pk).is_err());
+        let unsigned = base64::encode(json, base64::Variant::Original);
+        assert!(LockedConfig::verify(&unsigned, &pk).is_err());
+    }
+
+    #[test]
+    fn test_trusted_device_outdate() {
+        const DAY: i64 = 24 * 60 * 60 * 1000;
+        let device = |days: i64| TrustedDevice {
+            time: crate::get_time() - days * DAY,
+            ..Default::default()
+        };
+        assert!(!device(29).outdate_after(""));
+        assert!(device(31).outdate_after(""));
+        assert!(!device(89).outdate_after("90"));
+        assert!(device(91).outdate_after("90"));
+        assert!(!device(29).outdate_after("0"));
+        assert!(device(31).outdate_after("invalid"));
+    }
+
     #[test]
     fn test_peer_config_deserialize() {
         let default_peer_config = toml::from_str::<PeerConfig>("").unwrap();
@@ -0,0 +1,393 @@
+// The rules of the data loss prevention, which block or log the clipboard and the files passing
+// between this side and the peers.
+//
+// A rule names what it matches: the extensions of the files, a size threshold, a regex on the
+// clipboard text, and the direction, both if none. A rule with extensions is only of the files and
+// one with a pattern only of the clipboard text, while one with neither is of both. When a rule
+// blocking and a rule logging both match, it is blocked.
+//
+// The rules come along the policy of the management server, which the server sets with
+// `set_rules`, and from the option `dlp-rules` on this side, a json array of them as well, checked
+// with `check_rules` before it is saved. A blocking rule whose pattern is invalid anyway blocks all
+// in its direction, one logging is dropped, and an option which does not parse blocks all.
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    bail,
+    config::{keys, Config},
+    log,
+    message_proto::FileEntry,
+    ResultType,
+};
+
+lazy_static::lazy_static! {
+    // the rules of the management server, with a generation bumped whenever they are set
+    static ref SERVER_RULES: RwLock<(u64, Vec<Rule>)> = Default::default();
+    // the option and the generation of the server rules the rules compiled are of
+    static ref COMPILED: Mutex<Option<(String, u64, Arc<Vec<Compiled>>)>> = Default::default();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Block,
+    Log,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    // from the peer to this side
+    Incoming,
+    // from this side to the peer
+    Outgoing,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    #[serde(default)]
+    pub name: String,
+    pub action: Action,
+    // both if None
+    #[serde(default)]
+    pub direction: Option<Direction>,
+    // without the dot, case insensitive
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    // in bytes, of the file or the clipboard text
+    #[serde(default)]
+    pub min_size: u64,
+    #[serde(default)]
+    pub pattern: String,
+}
+
+pub enum Subject<'a> {
+    Text(&'a str),
+    File { name: &'a str, size: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verdict {
+    pub rule: String,
+    pub action: Action,
+}
+
+impl Verdict {
+    #[inline]
+    pub fn blocked(&self) -> bool {
+        self.action == Action::Block
+    }
+}
+
+struct Compiled {
+    rule: Rule,
+    regex: Option<Regex>,
+    extensions: Vec<String>,
+    // the pattern is invalid, the rule blocks all rather than nothing
+    all: bool,
+}
+
+impl Compiled {
+    // None if the pattern of a rule logging is invalid, which is logged
+    fn new(rule: Rule) -> Option<Self> {
+        let mut all = false;
+        let regex = if rule.pattern.is_empty() {
+            None
+        } else {
+            match Regex::new(&rule.pattern) {
+                Ok(regex) => Some(regex),
+                Err(err) if rule.action == Action::Block => {
+                    log::error!(
+                        "Invalid pattern of the dlp rule {}, blocking all: {}",
+                        rule.name,
+                        err
+                    );
+                    all = true;
+                    None
+                }
+                Err(err) => {
+                    log::error!("Invalid pattern of the dlp rule {}: {}", rule.name, err);
+                    return None;
+                }
+            }
+        };
+        let extensions = rule
+            .extensions
+            .iter()
+            .map(|e| e.trim_start_matches('.').to_lowercase())
+            .collect();
+        Some(Self {
+            rule,
+            regex,
+            extensions,
+            all,
+        })
+    }
+
+    fn matches(&self, subject: &Subject, direction: Direction) -> bool {
+        if self.rule.direction.map(|d| d != direction).unwrap_or(false) {
+            return false;
+        }
+        if self.all {
+            return true;
+        }
+        match subject {
+            Subject::Text(text) => {
+                self.extensions.is_empty()
+                    && text.len() as u64 >= self.rule.min_size
+                    && self
+                        .regex
+                        .as_ref()
+                        .map(|r| r.is_match(text))
+                        .unwrap_or(true)
+            }
+            Subject::File { name, size } => {
+                // the names of the peers on windows
+                let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+                self.regex.is_none()
+                    && *size >= self.rule.min_size
+                    && (self.extensions.is_empty()
+                        || Path::new(name)
+                            .extension()
+                            .and_then(|e| e.to_str())
+                            .map(|e| self.extensions.contains(&e.to_lowercase()))
+                            .unwrap_or(false))
+            }
+        }
+    }
+}
+
+/// Sets the rules of the management server.
+pub fn set_rules(rules: Vec<Rule>) {
+    let mut server = SERVER_RULES.write().unwrap();
+    if server.1 != rules {
+        *server = (server.0 + 1, rules);
+    }
+}
+
+/// Checks the rules of the option `dlp-rules` before they are saved.
+pub fn check_rules(option: &str) -> ResultType<()> {
+    let rules: Vec<Rule> = serde_json::from_str(option)?;
+    for rule in rules.iter().filter(|r| !r.pattern.is_empty()) {
+        if let Err(err) = Regex::new(&rule.pattern) {
+            bail!("Invalid pattern of the dlp rule {}: {}", rule.name, err);
+        }
+    }
+    Ok(())
+}
+
+fn rules() -> Arc<Vec<Compiled>> {
+    let option = Config::get_option(keys::OPTION_DLP_RULES);
+    let server = SERVER_RULES.read().unwrap();
+    let mut compiled = COMPILED.lock().unwrap();
+    if let Some((o, generation, rules)) = compiled.as_ref() {
+        if *o == option && *generation == server.0 {
+            return rules.clone();
+        }
+    }
+    let rules = Arc::new(compile(&server.1, &option));
+    *compiled = Some((option, server.0, rules.clone()));
+    rules
+}
+
+fn compile(server: &[Rule], option: &str) -> Vec<Compiled> {
+    let mut list = server.to_vec();
+    let mut invalid = false;
+    if !option.is_empty() {
+        match serde_json::from_str::<Vec<Rule>>(option) {
+            Ok(rules) => list.extend(rules),
+            Err(err) => {
+                log::error!("Invalid dlp rules, blocking all: {}", err);
+                invalid = true;
+            }
+        }
+    }
+    let mut rules: Vec<Compiled> = list.into_iter().filter_map(Compiled::new).collect();
+    if invalid {
+        rules.push(Compiled {
+            rule: Rule {
+                name: keys::OPTION_DLP_RULES.to_owned(),
+                action: Action::Block,
+                direction: None,
+                extensions: vec![],
+                min_size: 0,
+                pattern: String::new(),
+            },
+            regex: None,
+            extensions: vec![],
+            all: true,
+        });
+    }
+    rules
+}
+
+fn check_in<'a>(
+    rules: &[Compiled],
+    subjects: impl Iterator<Item = Subject<'a>>,
+    direction: Direction,
+) -> Option<(Verdict, String)> {
+    let mut logged = None;
+    for subject in subjects {
+        for compiled in rules.iter().filter(|c| c.matches(&subject, direction)) {
+            let name = match &subject {
+                Subject::Text(_) => String::new(),
+                Subject::File { name, .. } => name.to_string(),
+            };
+            let verdict = Verdict {
+                rule: compiled.rule.name.clone(),
+                action: compiled.rule.action,
+            };
+            if verdict.blocked() {
+                return Some((verdict, name));
+            }
+            logged.get_or_insert((verdict, name));
+        }
+    }
+    logged
+}
+
+/// The verdict of the rules on `subject`, None if no rule matches.
+pub fn check(subject: Subject, direction: Direction) -> Option<Verdict> {
+    check_in(&rules(), std::iter::once(subject), direction).map(|(v, _)| v)
+}
+
+/// The verdict of the rules on the files and the name of the file it is of, the first one blocked
+/// or else the first one logged.
+pub fn check_files<'a>(
+    files: impl IntoIterator<Item = (&'a str, u64)>,
+    direction: Direction,
+) -> Option<(Verdict, String)> {
+    let rules = rules();
+    if rules.is_empty() {
+        return None;
+    }
+    check_in(
+        &rules,
+        files
+            .into_iter()
+            .map(|(name, size)| Subject::File { name, size }),
+        direction,
+    )
+}
+
+#[inline]
+pub fn check_file_entries(files: &[FileEntry], direction: Direction) -> Option<(Verdict, String)> {
+    check_files(files.iter().map(|f| (f.name.as_str(), f.size)), direction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let rules: Vec<Rule> = serde_json::from_str(
+            r#"[
+                {"name": "executables", "action": "block", "direction": "incoming", "extensions": [".EXE", "msi"]},
+                {"name": "large", "action": "log", "min_size": 1000},
+                {"name": "card", "action": "block", "direction": "outgoing", "pattern": "\\b\\d{4}-\\d{4}-\\d{4}-\\d{4}\\b"},
+                {"name": "invalid", "action": "log", "pattern": "("}
+            ]"#,
+        )
+        .unwrap();
+        let rules: Vec<Compiled> = rules.into_iter().filter_map(Compiled::new).collect();
+        assert_eq!(rules.len(), 3);
+        let verdict = |subject, direction| check_in(&rules, std::iter::once(subject), direction);
+
+        let blocked = verdict(
+            Subject::File {
+                name: "dir\\setup.exe",
+                size: 10,
+            },
+            Direction::Incoming,
+        )
+        .unwrap();
+        assert_eq!(blocked.0.rule, "executables");
+        assert!(blocked.0.blocked());
+        let file = Subject::File {
+            name: "setup.exe",
+            size: 10,
+        };
+        assert!(verdict(file, Direction::Outgoing).is_none());
+        // blocked before logged
+        let large = Subject::File {
+            name: "a/setup.Exe",
+            size: 2000,
+        };
+        assert_eq!(
+            verdict(large, Direction::Incoming).unwrap().0.rule,
+            "executables"
+        );
+        let large = Subject::File {
+            name: "a/b.txt",
+            size: 2000,
+        };
+        assert_eq!(
+            verdict(large, Direction::Outgoing).unwrap().0.action,
+            Action::Log
+        );
+
+        let card = "paid by 1234-5678-9012-3456";
+        let res = verdict(Subject::Text(card), Direction::Outgoing).unwrap();
+        assert_eq!(res.0.rule, "card");
+        assert!(verdict(Subject::Text(card), Direction::Incoming).is_none());
+        assert!(verdict(Subject::Text("1234"), Direction::Outgoing).is_none());
+
+        let files = [("a.txt", 1), ("b.msi", 1), ("c.txt", 5000)];
+        let res = check_in(
+            &rules,
+            files
+                .iter()
+                .map(|(name, size)| Subject::File { name, size: *size }),
+            Direction::Incoming,
+        )
+        .unwrap();
+        assert_eq!(res.1, "b.msi");
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let option =
+            r#"[{"name": "invalid", "action": "block", "direction": "outgoing", "pattern": "("}]"#;
+        assert!(check_rules(option).is_err());
+        assert!(check_rules("[").is_err());
+        assert!(check_rules(r#"[{"name": "valid", "action": "block", "pattern": "a+"}]"#).is_ok());
+        let rules: Vec<Rule> = serde_json::from_str(option).unwrap();
+        let rules: Vec<Compiled> = rules.into_iter().filter_map(Compiled::new).collect();
+        let verdict = |subject, direction| check_in(&rules, std::iter::once(subject), direction);
+        // blocks all in its direction
+        assert!(verdict(Subject::Text("a"), Direction::Outgoing)
+            .unwrap()
+            .0
+            .blocked());
+        let file = Subject::File {
+            name: "a.txt",
+            size: 1,
+        };
+        assert!(verdict(file, Direction::Outgoing).unwrap().0.blocked());
+        assert!(verdict(Subject::Text("a"), Direction::Incoming).is_none());
+    }
+
+    #[test]
+    fn test_invalid_option() {
+        let rules = compile(&[], "[");
+        let verdict = |subject, direction| check_in(&rules, std::iter::once(subject), direction);
+        for direction in [Direction::Incoming, Direction::Outgoing] {
+            assert!(verdict(Subject::Text("a"), direction).unwrap().0.blocked());
+            let file = Subject::File {
+                name: "a.txt",
+                size: 1,
+            };
+            assert!(verdict(file, direction).unwrap().0.blocked());
+        }
+        assert!(compile(&[], "").is_empty());
+        assert_eq!(compile(&[], r#"[{"action": "log"}]"#).len(), 1);
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 #[cfg(windows)]
 use std::os::windows::prelude::*;
 use std::path::{Path, PathBuf};
@@ -5,15 +6,28 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use serde_derive::{Deserialize, Serialize};
 use serde_json::json;
-use tokio::{fs::File, io::*};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::*,
+};
 
 use crate::{anyhow::anyhow, bail, get_version_number, message_proto::*, ResultType, Stream};
 // https://doc.rust-lang.org/std/os/windows/fs/trait.MetadataExt.html
 use crate::{
     compress::{compress, decompress},
-    config::Config,
+    config::{keys, Config},
 };
 
+pub mod attributes;
+pub mod audit;
+pub mod conflict;
+pub mod delta;
+pub mod links;
+pub mod search;
+pub mod throttle;
+pub mod trash;
+pub mod verify;
+
 pub fn read_dir(path: &Path, include_hidden: bool) -> ResultType<FileDirectory> {
     let mut dir = FileDirectory {
         path: get_string(path),
@@ -122,37 +136,69 @@ pub fn get_home_as_string() -> String {
     get_string(&Config::get_home())
 }
 
+/// The error of listing a path which does not exist.
+pub const NOT_EXISTS: &str = "Not exists";
+
 fn read_dir_recursive(
     path: &PathBuf,
     prefix: &Path,
     include_hidden: bool,
+    walker: &mut links::Walker,
 ) -> ResultType<Vec<FileEntry>> {
     let mut files = Vec::new();
     if path.is_dir() {
-        // to-do: symbol link handling, cp the link rather than the content
-        // to-do: file mode, for unix
-        let fd = read_dir(path, include_hidden)?;
-        for entry in fd.entries.iter() {
-            match entry.entry_type.enum_value() {
-                Ok(FileType::File) => {
-                    let mut entry = entry.clone();
-                    entry.name = get_string(&prefix.join(entry.name));
+        if !walker.enter(path) {
+            return Ok(files);
+        }
+        let fd = match read_dir(path, include_hidden) {
+            Ok(fd) => fd,
+            Err(err) => {
+                walker.leave();
+                return Err(err);
+            }
+        };
+        for mut entry in fd.entries {
+            let full = path.join(&entry.name);
+            let name = prefix.join(&entry.name);
+            match (entry.entry_type.enum_value(), walker.policy) {
+                (Ok(FileType::File), _) => {
+                    entry.name = get_string(&name);
+                    walker.check_hard_link(&full, &mut entry);
                     files.push(entry);
                 }
-                Ok(FileType::Dir) => {
-                    if let Ok(mut tmp) = read_dir_recursive(
-                        &path.join(&entry.name),
-                        &prefix.join(&entry.name),
-                        include_hidden,
-                    ) {
-                        for entry in tmp.drain(0..) {
+                (Ok(FileType::Dir), _) | (Ok(FileType::DirLink), FileLinkPolicy::FollowLinks) => {
+                    if let Ok(mut tmp) = read_dir_recursive(&full, &name, include_hidden, walker) {
+                        files.append(&mut tmp);
+                    }
+                }
+                (Ok(FileType::FileLink), FileLinkPolicy::FollowLinks) => {
+                    // the broken links are skipped
+                    if let Ok(meta) = std::fs::metadata(&full) {
+                        if meta.is_file() {
+                            entry.name = get_string(&name);
+                            entry.entry_type = FileType::File.into();
+                            entry.size = meta.len();
+                            entry.modified_time = meta
+                                .modified()
+                                .ok()
+                                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                                .map(|d| d.as_secs())
+                                .unwrap_or(0);
                             files.push(entry);
                         }
                     }
                 }
+                (Ok(FileType::FileLink), FileLinkPolicy::CopyLinks)
+                | (Ok(FileType::DirLink), FileLinkPolicy::CopyLinks) => {
+                    if walker.read_link(&full, &mut entry) {
+                        entry.name = get_string(&name);
+                        files.push(entry);
+                    }
+                }
                 _ => {}
             }
         }
+        walker.leave();
         Ok(files)
     } else if path.is_file() {
         let (size, modified_time) = if let Ok(meta) = std::fs::metadata(path) {
@@ -177,12 +223,25 @@ fn read_dir_recursive(
         });
         Ok(files)
     } else {
-        bail!("Not exists");
+        bail!(NOT_EXISTS);
     }
 }
 
 pub fn get_recursive_files(path: &str, include_hidden: bool) -> ResultType<Vec<FileEntry>> {
-    read_dir_recursive(&get_path(path), &get_path(""), include_hidden)
+    get_recursive_files_with_links(path, include_hidden, FileLinkPolicy::SkipLinks)
+}
+
+pub fn get_recursive_files_with_links(
+    path: &str,
+    include_hidden: bool,
+    policy: FileLinkPolicy,
+) -> ResultType<Vec<FileEntry>> {
+    read_dir_recursive(
+        &get_path(path),
+        &get_path(""),
+        include_hidden,
+        &mut links::Walker::new(policy),
+    )
 }
 
 #[inline]
@@ -219,7 +278,58 @@ pub struct TransferJob {
     // indicating the last file is skipped
     file_skipped: bool,
     file_is_waiting: bool,
-    default_overwrite_strategy: Option<bool>,
+    #[serde(skip_serializing)]
+    conflict_policy: FileConflictPolicy,
+    // the file of the number asked about, with the modified times of it and the file existing
+    #[serde(skip_serializing)]
+    conflict: Option<(i32, u64, u64)>,
+    // reader: the writer resolves the conflicts
+    peer_resolves_conflicts: bool,
+    // the checksums of the copy of the writer for the file of the number
+    #[serde(skip_serializing)]
+    delta: Option<(i32, FileBlockChecksums)>,
+    // the reader sends only the blocks changed, the matcher is of the file of the number
+    #[serde(skip_serializing)]
+    matcher: Option<(i32, delta::Matcher)>,
+    // the writer copies the blocks not sent from it
+    #[serde(skip_serializing)]
+    basis: Option<Basis>,
+    // whether the peer reading supports the delta
+    delta_supported: bool,
+    #[serde(skip_serializing)]
+    bucket: throttle::TokenBucket,
+    // the peer takes part in the verification of the files
+    verify: bool,
+    // the sha256 of the file of the number read or written so far
+    #[serde(skip_serializing)]
+    hasher: Option<(i32, verify::Hasher)>,
+    // reader: the file sent, waiting for the writer to check it
+    #[serde(skip_serializing)]
+    verifying: Option<i32>,
+    // writer: the reply to the reader for the file written
+    #[serde(skip_serializing)]
+    verification: Option<verify::Verification>,
+    // writer: the files corrupted and sent again
+    #[serde(skip_serializing)]
+    refetched: Vec<i32>,
+    // the names of the files still corrupted
+    corrupted: Vec<String>,
+    // the sha256 of the files sent or checked in hex, by the name
+    digests: BTreeMap<String, String>,
+    // reader: the blocks are sent uncompressed, as either side asks
+    uncompressed: bool,
+    // the attributes of the files kept besides the content
+    attributes: attributes::Options,
+}
+
+#[derive(Debug)]
+enum Basis {
+    // the partial `.download` file, which is continued in place after the bytes kept
+    Partial { kept: u64, truncated: bool },
+    // the file to overwrite
+    Old(File),
+    // the corrupted `.download` file, whose blocks changed are written in place
+    Repair { pos: u64 },
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -236,6 +346,9 @@ pub struct TransferJobMeta {
     pub file_num: i32,
     #[serde(default)]
     pub is_remote: bool,
+    // paused by the user, not resumed when the peer reconnects
+    #[serde(default)]
+    pub paused: bool,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -258,16 +371,51 @@ fn get_ext(name: &str) -> &str {
 
 #[inline]
 fn is_compressed_file(name: &str) -> bool {
-    let ext = get_ext(name);
-    ext == "xz"
-        || ext == "gz"
-        || ext == "zip"
-        || ext == "7z"
-        || ext == "rar"
-        || ext == "bz2"
-        || ext == "tgz"
-        || ext == "png"
-        || ext == "jpg"
+    const EXTS: &[&str] = &[
+        "xz", "gz", "zip", "7z", "rar", "bz2", "tgz", "txz", "tbz2", "zst", "lz4", "lzma", "cab",
+        "jar", "apk", "docx", "xlsx", "pptx", "odt", "ods", "png", "jpg", "jpeg", "gif", "webp",
+        "heic", "avif", "mp3", "aac", "m4a", "ogg", "opus", "flac", "mp4", "m4v", "mkv", "mov",
+        "avi", "webm",
+    ];
+    let ext = get_ext(name).to_lowercase();
+    EXTS.contains(&ext.as_str())
+}
+
+// bits per byte, above which the data is random-like, e.g. compressed or encrypted
+const MAX_ENTROPY: f64 = 7.5;
+
+// Whether the data is not worth compressing, by the entropy of its bytes.
+fn is_incompressible(data: &[u8]) -> bool {
+    if data.is_empty() {
+        return true;
+    }
+    let mut counts = [0usize; 256];
+    for x in data {
+        counts[*x as usize] += 1;
+    }
+    let len = data.len() as f64;
+    let entropy: f64 = counts
+        .iter()
+        .filter(|n| **n > 0)
+        .map(|n| {
+            let p = *n as f64 / len;
+            -p * p.log2()
+        })
+        .sum();
+    entropy > MAX_ENTROPY
+}
+
+const BUF_SIZE: usize = 128 * 1024;
+
+/// The data compressed if it is worth it, and whether it is.
+pub fn compress_data(name: &str, data: Vec<u8>) -> (Vec<u8>, bool) {
+    if !is_compressed_file(name) && !is_incompressible(&data) {
+        let tmp = compress(&data);
+        if !tmp.is_empty() && tmp.len() < data.len() {
+            return (tmp, true);
+        }
+    }
+    (data, false)
 }
 
 impl TransferJob {
@@ -298,6 +446,7 @@ impl TransferJob {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new_read(
         id: i32,
         remote: String,
@@ -306,9 +455,10 @@ impl TransferJob {
         show_hidden: bool,
         is_remote: bool,
         enable_overwrite_detection: bool,
+        links: FileLinkPolicy,
     ) -> ResultType<Self> {
         log::info!("new read {}", path);
-        let files = get_recursive_files(&path, show_hidden)?;
+        let files = get_recursive_files_with_links(&path, show_hidden, links)?;
         let total_size = files.iter().map(|x| x.size).sum();
         Ok(Self {
             id,
@@ -320,6 +470,7 @@ impl TransferJob {
             files,
             total_size,
             enable_overwrite_detection,
+            uncompressed: !Config::get_bool_option(keys::OPTION_ENABLE_FILE_TRANSFER_COMPRESSION),
             ..Default::default()
         })
     }
@@ -363,14 +514,27 @@ impl TransferJob {
         let file_num = self.file_num as usize;
         if file_num < self.files.len() {
             let entry = &self.files[file_num];
+            if self.corrupted.contains(&entry.name) || self.is_refetching() {
+                return;
+            }
             let path = self.join(&entry.name);
             let download_path = format!("{}.download", get_string(&path));
             std::fs::rename(download_path, &path).ok();
-            filetime::set_file_mtime(
-                &path,
-                filetime::FileTime::from_unix_time(entry.modified_time as _, 0),
-            )
-            .ok();
+            attributes::restore(&path, entry, &self.attributes);
+        }
+    }
+
+    /// Creates the links of the job, once the files they are to are written.
+    pub fn create_links(&self) {
+        for entry in self.files.iter().filter(|e| links::is_link(e)) {
+            if let Err(err) = links::create(&self.path, &self.join(&entry.name), entry) {
+                log::error!(
+                    "id: {}, failed to create the link {}: {}",
+                    self.id,
+                    entry.name,
+                    err
+                );
+            }
         }
     }
 
@@ -393,9 +557,10 @@ impl TransferJob {
             bail!("Wrong file number");
         }
         if file_num != self.file_num as usize || self.file.is_none() {
-            self.modify_time();
+            // the file of the job resumed or sent again is not written yet
             if let Some(file) = self.file.as_mut() {
                 file.sync_all().await?;
+                self.modify_time();
             }
             self.file_num = block.file_num;
             let entry = &self.files[file_num];
@@ -403,26 +568,176 @@ impl TransferJob {
             if let Some(p) = path.parent() {
                 std::fs::create_dir_all(p).ok();
             }
-            let path = format!("{}.download", get_string(&path));
-            self.file = Some(File::create(&path).await?);
+            let download_path = format!("{}.download", get_string(&path));
+            let copy = self
+                .delta
+                .as_ref()
+                .filter(|d| d.0 == block.file_num)
+                .map(|d| (d.1.partial, d.1.repair));
+            self.basis = None;
+            self.hasher = None;
+            match copy {
+                Some((_, true)) => {
+                    let file = OpenOptions::new()
+                        .read(true)
+                        .write(true)
+                        .open(&download_path)
+                        .await?;
+                    self.file = Some(file);
+                    self.basis = Some(Basis::Repair { pos: 0 });
+                }
+                Some((true, _)) => {
+                    let file = OpenOptions::new()
+                        .read(true)
+                        .write(true)
+                        .open(&download_path)
+                        .await?;
+                    self.file = Some(file);
+                    self.basis = Some(Basis::Partial {
+                        kept: 0,
+                        truncated: false,
+                    });
+                }
+                Some((false, _)) => {
+                    self.basis = Some(Basis::Old(File::open(&path).await?));
+                    self.file = Some(File::create(&download_path).await?);
+                }
+                None => self.file = Some(File::create(&download_path).await?),
+            }
         }
-        if block.compressed {
-            let tmp = decompress(&block.data);
-            self.file
-                .as_mut()
-                .ok_or(anyhow!("file is None"))?
-                .write_all(&tmp)
-                .await?;
-            self.finished_size += tmp.len() as u64;
-        } else {
-            self.file
-                .as_mut()
-                .ok_or(anyhow!("file is None"))?
-                .write_all(&block.data)
-                .await?;
-            self.finished_size += block.data.len() as u64;
+        if !block.copy_blks.is_empty() {
+            self.copy_blocks(&block.copy_blks).await?;
+        }
+        // the block without the data is the end of the file
+        if !block.data.is_empty() || block.copy_blks.is_empty() {
+            if let Some(Basis::Partial { kept, truncated }) = self.basis.as_mut() {
+                if !*truncated {
+                    let file = self.file.as_mut().ok_or(anyhow!("file is None"))?;
+                    file.set_len(*kept).await?;
+                    file.seek(SeekFrom::Start(*kept)).await?;
+                    *truncated = true;
+                }
+            }
         }
+        let data = if block.compressed {
+            decompress(&block.data)
+        } else {
+            block.data.to_vec()
+        };
+        self.file
+            .as_mut()
+            .ok_or(anyhow!("file is None"))?
+            .write_all(&data)
+            .await?;
+        self.finished_size += data.len() as u64;
         self.transferred += block.data.len() as u64;
+        self.hash(block.file_num, &data);
+        if let Some(Basis::Repair { pos }) = self.basis.as_mut() {
+            *pos += data.len() as u64;
+        }
+        if block.data.is_empty() && block.copy_blks.is_empty() {
+            self.end_file(block.file_num, &block.checksum).await?;
+        }
+        Ok(())
+    }
+
+    async fn end_file(&mut self, file_num: i32, checksum: &[u8]) -> ResultType<()> {
+        if let Some(Basis::Repair { pos }) = self.basis {
+            let file = self.file.as_mut().ok_or(anyhow!("file is None"))?;
+            file.set_len(pos).await?;
+        }
+        let digest = self.finish_hash(file_num);
+        // sent by the reader without the verification
+        if checksum.is_empty() {
+            return Ok(());
+        }
+        let name = self.files[file_num as usize].name.clone();
+        if digest == checksum {
+            self.digests.insert(name, verify::hex(&digest));
+        } else if self.verify && !self.refetched.contains(&file_num) {
+            log::warn!("id: {}, file {} is corrupted, sent again", self.id, name);
+            self.refetched.push(file_num);
+            if let Some(file) = self.file.take() {
+                file.sync_all().await?;
+            }
+            self.finished_size = self
+                .finished_size
+                .saturating_sub(self.files[file_num as usize].size);
+            self.verification = Some(verify::Verification::Refetch(file_num));
+            return Ok(());
+        } else {
+            log::error!("id: {}, file {} is corrupted", self.id, name);
+            self.corrupted.push(name);
+        }
+        if self.verify {
+            self.verification = Some(verify::Verification::Checked(file_num));
+        }
+        Ok(())
+    }
+
+    // the hasher of the file, new if it is not hashed yet
+    fn take_hasher(&mut self, file_num: i32) -> verify::Hasher {
+        match self.hasher.take() {
+            Some((n, hasher)) if n == file_num => hasher,
+            _ => Default::default(),
+        }
+    }
+
+    fn hash(&mut self, file_num: i32, data: &[u8]) {
+        let mut hasher = self.take_hasher(file_num);
+        hasher.update(data);
+        self.hasher = Some((file_num, hasher));
+    }
+
+    #[inline]
+    fn finish_hash(&mut self, file_num: i32) -> Vec<u8> {
+        self.take_hasher(file_num).finalize()
+    }
+
+    async fn copy_blocks(&mut self, blks: &[u32]) -> ResultType<()> {
+        let block_size = match &self.delta {
+            Some((file_num, checksums)) if *file_num == self.file_num => {
+                checksums.block_size as u64
+            }
+            _ => bail!("No blocks to copy"),
+        };
+        let mut hasher = self.take_hasher(self.file_num);
+        let file = self.file.as_mut().ok_or(anyhow!("file is None"))?;
+        let mut buf = vec![0; block_size as usize];
+        match self.basis.as_mut() {
+            // the blocks kept in place are read for the checksum
+            Some(Basis::Partial { kept, truncated }) => {
+                for blk in blks {
+                    if *truncated || *blk as u64 * block_size != *kept {
+                        bail!("Block {} is not the next one of the partial file", blk);
+                    }
+                    file.read_exact(&mut buf).await?;
+                    hasher.update(&buf);
+                    *kept += block_size;
+                }
+            }
+            Some(Basis::Repair { pos }) => {
+                for blk in blks {
+                    if *blk as u64 * block_size != *pos {
+                        bail!("Block {} is not the next one of the file repaired", blk);
+                    }
+                    file.read_exact(&mut buf).await?;
+                    hasher.update(&buf);
+                    *pos += block_size;
+                }
+            }
+            Some(Basis::Old(old)) => {
+                for blk in blks {
+                    old.seek(SeekFrom::Start(*blk as u64 * block_size)).await?;
+                    old.read_exact(&mut buf).await?;
+                    file.write_all(&buf).await?;
+                    hasher.update(&buf);
+                }
+            }
+            None => bail!("No blocks to copy"),
+        }
+        self.finished_size += blks.len() as u64 * block_size;
+        self.hasher = Some((self.file_num, hasher));
         Ok(())
     }
 
@@ -436,6 +751,15 @@ impl TransferJob {
     }
 
     pub async fn read(&mut self, stream: &mut Stream) -> ResultType<Option<FileTransferBlock>> {
+        // the links are created by the writer
+        while self.file.is_none()
+            && self
+                .files
+                .get(self.file_num as usize)
+                .map_or(false, links::is_link)
+        {
+            self.file_num += 1;
+        }
         let file_num = self.file_num as usize;
         if file_num >= self.files.len() {
             self.file.take();
@@ -464,7 +788,16 @@ impl TransferJob {
             }
             return Ok(None);
         }
-        const BUF_SIZE: usize = 128 * 1024;
+        if self.matcher.as_ref().map(|m| m.0) != Some(self.file_num) {
+            self.matcher = self
+                .delta
+                .as_ref()
+                .filter(|d| d.0 == self.file_num)
+                .map(|d| (d.0, delta::Matcher::new(&d.1)));
+        }
+        if self.matcher.is_some() {
+            return self.read_delta(file_num).await;
+        }
         let mut buf: Vec<u8> = vec![0; BUF_SIZE];
         let mut compressed = false;
         let mut offset: usize = 0;
@@ -492,20 +825,17 @@ impl TransferJob {
             }
         }
         unsafe { buf.set_len(offset) };
+        let mut checksum = vec![];
         if offset == 0 {
+            checksum = self.end_read(file_num as _);
             self.file_num += 1;
             self.file = None;
             self.file_confirmed = false;
             self.file_is_waiting = false;
         } else {
+            self.hash(file_num as _, &buf);
             self.finished_size += offset as u64;
-            if !is_compressed_file(name) {
-                let tmp = compress(&buf);
-                if tmp.len() < buf.len() {
-                    buf = tmp;
-                    compressed = true;
-                }
-            }
+            (buf, compressed) = self.compress_data(file_num, buf);
             self.transferred += buf.len() as u64;
         }
         Ok(Some(FileTransferBlock {
@@ -513,10 +843,82 @@ impl TransferJob {
             file_num: file_num as _,
             data: buf.into(),
             compressed,
+            checksum: checksum.into(),
             ..Default::default()
         }))
     }
 
+    // Returns the checksum of the file read, sent at its end.
+    fn end_read(&mut self, file_num: i32) -> Vec<u8> {
+        let digest = self.finish_hash(file_num);
+        if let Some(file) = self.files.get(file_num as usize) {
+            self.digests.insert(file.name.clone(), verify::hex(&digest));
+        }
+        if self.verify {
+            self.verifying = Some(file_num);
+        }
+        digest
+    }
+
+    // Reads until the matcher has the blocks found or the data to send.
+    async fn read_delta(&mut self, file_num: usize) -> ResultType<Option<FileTransferBlock>> {
+        let mut buf: Vec<u8> = vec![0; BUF_SIZE];
+        let mut eof = false;
+        loop {
+            let matcher = &mut self.matcher.as_mut().ok_or(anyhow!("matcher is None"))?.1;
+            if let Some((copy_blks, data)) = matcher.next_block() {
+                self.finished_size += (copy_blks.len() * matcher.block_size() + data.len()) as u64;
+                let (data, compressed) = self.compress_data(file_num, data);
+                self.transferred += data.len() as u64;
+                return Ok(Some(FileTransferBlock {
+                    id: self.id,
+                    file_num: file_num as _,
+                    data: data.into(),
+                    compressed,
+                    copy_blks,
+                    ..Default::default()
+                }));
+            }
+            if eof {
+                let checksum = self.end_read(file_num as _);
+                self.file_num += 1;
+                self.file = None;
+                self.matcher = None;
+                self.file_confirmed = false;
+                self.file_is_waiting = false;
+                return Ok(Some(FileTransferBlock {
+                    id: self.id,
+                    file_num: file_num as _,
+                    checksum: checksum.into(),
+                    ..Default::default()
+                }));
+            }
+            let res = self
+                .file
+                .as_mut()
+                .ok_or(anyhow!("file is None"))?
+                .read(&mut buf)
+                .await;
+            match res {
+                Ok(n) => {
+                    eof = n == 0;
+                    self.hash(file_num as _, &buf[..n]);
+                    if let Some((_, matcher)) = self.matcher.as_mut() {
+                        matcher.feed(&buf[..n], eof);
+                    }
+                }
+                Err(err) => {
+                    self.file_num += 1;
+                    self.file = None;
+                    self.matcher = None;
+                    self.file_confirmed = false;
+                    self.file_is_waiting = false;
+                    return Err(err.into());
+                }
+            }
+        }
+    }
+
     async fn send_current_digest(&mut self, stream: &mut Stream) -> ResultType<()> {
         let mut msg = Message::new();
         let mut resp = FileResponse::new();
@@ -535,6 +937,8 @@ impl TransferJob {
             file_num: self.file_num,
             last_modified,
             file_size: meta.len(),
+            delta: true,
+            verify: self.verify,
             ..Default::default()
         });
         msg.set_file_response(resp);
@@ -548,12 +952,76 @@ impl TransferJob {
         Ok(())
     }
 
-    pub fn set_overwrite_strategy(&mut self, overwrite_strategy: Option<bool>) {
-        self.default_overwrite_strategy = overwrite_strategy;
+    pub fn set_conflict_policy(&mut self, policy: FileConflictPolicy) {
+        self.conflict_policy = policy;
+    }
+
+    pub fn conflict_policy(&self) -> FileConflictPolicy {
+        self.conflict_policy
     }
 
-    pub fn default_overwrite_strategy(&self) -> Option<bool> {
-        self.default_overwrite_strategy
+    /// Resolves the conflict of the file of the number, modified at `incoming_modified`, with the
+    /// file existing, modified at `existing_modified`, by the policy of the job. The conflict is
+    /// kept for the answer of the user if it is to be asked.
+    pub fn resolve_conflict(
+        &mut self,
+        file_num: i32,
+        incoming_modified: u64,
+        existing_modified: u64,
+    ) -> conflict::Resolution {
+        let resolution =
+            conflict::resolve(self.conflict_policy, incoming_modified, existing_modified);
+        if resolution == conflict::Resolution::Ask {
+            self.conflict = Some((file_num, incoming_modified, existing_modified));
+        }
+        resolution
+    }
+
+    /// Resolves the conflict asked by the policy the user answered, which is applied to the next
+    /// conflicts of the job too if `remember`.
+    pub fn answer_conflict(
+        &mut self,
+        file_num: i32,
+        policy: FileConflictPolicy,
+        remember: bool,
+    ) -> conflict::Resolution {
+        if remember {
+            self.conflict_policy = policy;
+        }
+        let (incoming_modified, existing_modified) = self
+            .conflict
+            .take()
+            .filter(|c| c.0 == file_num)
+            .map(|c| (c.1, c.2))
+            .unwrap_or_default();
+        conflict::resolve(policy, incoming_modified, existing_modified)
+    }
+
+    /// The writer of the upload resolves the conflicts, the answers of the user are sent to it.
+    pub fn set_peer_resolves_conflicts(&mut self, peer_resolves_conflicts: bool) {
+        self.peer_resolves_conflicts = peer_resolves_conflicts;
+    }
+
+    pub fn peer_resolves_conflicts(&self) -> bool {
+        self.peer_resolves_conflicts
+    }
+
+    /// Writes the file of the number beside the file existing, under a name not taken. The
+    /// checksums of the file existing are dropped, the ones returned match nothing for the reader
+    /// which has them.
+    pub fn rename_conflicting(&mut self, file_num: i32) -> Option<FileBlockChecksums> {
+        if let Some(entry) = self.files.get_mut(file_num as usize) {
+            let name = conflict::free_name(&self.path, &entry.name);
+            log::info!("id: {}, write {} as {}", self.id, entry.name, name);
+            entry.name = name;
+        }
+        if self.delta.as_ref().map(|d| d.0) != Some(file_num) {
+            return None;
+        }
+        self.delta.take().map(|(_, checksums)| FileBlockChecksums {
+            block_size: checksums.block_size,
+            ..Default::default()
+        })
     }
 
     pub fn set_file_confirmed(&mut self, file_confirmed: bool) {
@@ -595,20 +1063,41 @@ impl TransferJob {
     /// [`Note`]
     /// Conditions:
     /// 1. Files are not waiting for confirmation by peers.
+    /// 2. The last file sent is checked by the peer.
     #[inline]
     pub fn job_completed(&self) -> bool {
         // has no error, Condition 2
-        !self.enable_overwrite_detection || (!self.file_confirmed && !self.file_is_waiting)
+        (!self.enable_overwrite_detection || (!self.file_confirmed && !self.file_is_waiting))
+            && self.verifying.is_none()
     }
 
     /// Get job error message, useful for getting status when job had finished
     pub fn job_error(&self) -> Option<String> {
+        if let Some(err) = self.corrupted_error() {
+            return Some(err);
+        }
         if self.job_skipped() {
             return Some("skipped".to_string());
         }
         None
     }
 
+    /// The error of the files still corrupted after written, or not sent again before the job is
+    /// done.
+    pub fn corrupted_error(&self) -> Option<String> {
+        let mut corrupted = self.corrupted.clone();
+        if self.is_refetching() {
+            if let Some(entry) = self.files.get(self.file_num as usize) {
+                corrupted.push(entry.name.clone());
+            }
+        }
+        if corrupted.is_empty() {
+            None
+        } else {
+            Some(format!("Corrupted: {}", corrupted.join(", ")))
+        }
+    }
+
     pub fn set_file_skipped(&mut self) -> bool {
         log::debug!("skip file {} in job {}", self.file_num, self.id);
         self.file.take();
@@ -620,6 +1109,28 @@ impl TransferJob {
     }
 
     pub fn confirm(&mut self, r: &FileTransferSendConfirmRequest) -> bool {
+        // the replies to the files sent before
+        match r.union {
+            Some(file_transfer_send_confirm_request::Union::Checked(_)) => {
+                if self.verifying == Some(r.file_num) {
+                    self.verifying = None;
+                }
+                return true;
+            }
+            Some(file_transfer_send_confirm_request::Union::Refetch(ref checksums)) => {
+                if let Err(err) = self.refetch(r.file_num, checksums.clone()) {
+                    log::error!(
+                        "id: {}, failed to send file {} again: {}",
+                        self.id,
+                        r.file_num,
+                        err
+                    );
+                    self.verifying = None;
+                }
+                return true;
+            }
+            _ => {}
+        }
         if self.file_num() != r.file_num {
             log::info!("file num truncated, ignoring");
         } else {
@@ -634,12 +1145,178 @@ impl TransferJob {
                 Some(file_transfer_send_confirm_request::Union::OffsetBlk(_offset)) => {
                     self.set_file_confirmed(true);
                 }
+                Some(file_transfer_send_confirm_request::Union::Checksums(ref checksums)) => {
+                    self.set_delta(r.file_num, checksums.clone());
+                    self.set_file_confirmed(true);
+                }
                 _ => {}
             }
         }
         true
     }
 
+    // Goes back to the file corrupted, only the blocks changed are sent again.
+    fn refetch(&mut self, file_num: i32, checksums: FileBlockChecksums) -> ResultType<()> {
+        let entry = self
+            .files
+            .get(file_num as usize)
+            .ok_or(anyhow!("Wrong file number"))?;
+        let file = std::fs::File::open(self.join(&entry.name))?;
+        log::info!("id: {}, send file {} again", self.id, entry.name);
+        self.finished_size = self.finished_size.saturating_sub(entry.size);
+        self.file = Some(File::from_std(file));
+        self.file_num = file_num;
+        self.file_confirmed = true;
+        self.file_is_waiting = false;
+        self.matcher = None;
+        self.hasher = None;
+        self.verifying = None;
+        self.set_delta(file_num, checksums);
+        Ok(())
+    }
+
+    /// Only the blocks changed against the checksums of the copy of the writer are sent for the
+    /// file, set on both sides.
+    pub fn set_delta(&mut self, file_num: i32, checksums: FileBlockChecksums) {
+        log::info!(
+            "id: {}, file_num: {}, delta of {} blocks, partial: {}",
+            self.id,
+            file_num,
+            checksums.weak.len(),
+            checksums.partial
+        );
+        self.delta = Some((file_num, checksums));
+    }
+
+    #[inline]
+    pub fn set_delta_supported(&mut self, delta_supported: bool) {
+        self.delta_supported = delta_supported;
+    }
+
+    #[inline]
+    pub fn delta_supported(&self) -> bool {
+        self.delta_supported
+    }
+
+    /// Whether the peer takes part in the verification of the files.
+    #[inline]
+    pub fn set_verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+
+    #[inline]
+    pub fn verify(&self) -> bool {
+        self.verify
+    }
+
+    /// Whether the writer waits for a corrupted file to be sent again, the digest of the file after
+    /// it is sent again too.
+    #[inline]
+    pub fn is_refetching(&self) -> bool {
+        self.file.is_none() && self.refetched.last() == Some(&self.file_num)
+    }
+
+    #[inline]
+    pub fn digests(&self) -> &BTreeMap<String, String> {
+        &self.digests
+    }
+
+    /// The reply of the writer to the reader for the file written, none if it is not checked yet or
+    /// the reader does not take part.
+    pub async fn verification_reply(&mut self) -> Option<Message> {
+        let union = match self.verification.take()? {
+            verify::Verification::Checked(file_num) => (
+                file_num,
+                file_transfer_send_confirm_request::Union::Checked(true),
+            ),
+            verify::Verification::Refetch(file_num) => {
+                let path = self.join(&self.files.get(file_num as usize)?.name);
+                let download_path = PathBuf::from(format!("{}.download", get_string(&path)));
+                let res = tokio::task::spawn_blocking(move || {
+                    let file_size = std::fs::metadata(&download_path)?.len();
+                    delta::checksums(&download_path, file_size, false)
+                })
+                .await;
+                match res {
+                    Ok(Ok(mut checksums)) => {
+                        checksums.repair = true;
+                        self.set_delta(file_num, checksums.clone());
+                        (
+                            file_num,
+                            file_transfer_send_confirm_request::Union::Refetch(checksums),
+                        )
+                    }
+                    res => {
+                        log::error!(
+                            "failed to read the checksums of the corrupted file: {:?}",
+                            res.map(|r| r.err())
+                        );
+                        if let Some(entry) = self.files.get(file_num as usize) {
+                            self.corrupted.push(entry.name.clone());
+                        }
+                        self.refetched.pop();
+                        (
+                            file_num,
+                            file_transfer_send_confirm_request::Union::Checked(true),
+                        )
+                    }
+                }
+            }
+        };
+        Some(new_send_confirm(FileTransferSendConfirmRequest {
+            id: self.id,
+            file_num: union.0,
+            union: Some(union.1),
+            verify: true,
+            ..Default::default()
+        }))
+    }
+
+    fn compress_data(&self, file_num: usize, data: Vec<u8>) -> (Vec<u8>, bool) {
+        if self.uncompressed {
+            return (data, false);
+        }
+        compress_data(&self.files[file_num].name, data)
+    }
+
+    /// Sends the blocks uncompressed, the compression costs more than it saves on a fast network.
+    #[inline]
+    pub fn set_uncompressed(&mut self, uncompressed: bool) {
+        self.uncompressed = uncompressed;
+    }
+
+    #[inline]
+    pub fn uncompressed(&self) -> bool {
+        self.uncompressed
+    }
+
+    /// Keeps the attributes of the options, and reads them into the files if the job reads.
+    pub fn set_attributes(&mut self, options: attributes::Options, is_read: bool) {
+        self.attributes = options;
+        if is_read {
+            for i in 0..self.files.len() {
+                let path = self.join(&self.files[i].name);
+                attributes::capture(&path, &mut self.files[i], &options);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn attributes(&self) -> attributes::Options {
+        self.attributes
+    }
+
+    /// Limits the rate the job is sent at, in kbps, 0 for no limit.
+    #[inline]
+    pub fn set_rate_limit(&mut self, kbps: u32) {
+        self.bucket.set_kbps(kbps);
+    }
+
+    #[inline]
+    pub fn rate_limit(&self) -> u32 {
+        self.bucket.kbps()
+    }
+
     #[inline]
     pub fn gen_meta(&self) -> TransferJobMeta {
         TransferJobMeta {
@@ -649,8 +1326,21 @@ impl TransferJob {
             file_num: self.file_num,
             show_hidden: self.show_hidden,
             is_remote: self.is_remote,
+            paused: self.is_last_job,
         }
     }
+
+    /// Closes the file written, the peer stops reading and the transfer is continued from the
+    /// partial file once resumed.
+    pub async fn pause_write(&mut self) {
+        if let Some(file) = self.file.take() {
+            file.sync_all().await.ok();
+        }
+        self.basis = None;
+        self.hasher = None;
+        self.delta = None;
+        self.is_last_job = true;
+    }
 }
 
 #[inline]
@@ -681,6 +1371,36 @@ pub fn new_dir(id: i32, path: String, files: Vec<FileEntry>) -> Message {
     msg_out
 }
 
+#[inline]
+pub fn new_search_result(id: i32, entries: Vec<FileEntry>, done: bool, truncated: bool) -> Message {
+    let mut resp = FileResponse::new();
+    resp.set_search_result(FileSearchResult {
+        id,
+        entries,
+        done,
+        truncated,
+        ..Default::default()
+    });
+    let mut msg_out = Message::new();
+    msg_out.set_file_response(resp);
+    msg_out
+}
+
+#[inline]
+pub fn new_range(id: i32, offset: u64, data: Vec<u8>, size: u64) -> Message {
+    let mut resp = FileResponse::new();
+    resp.set_range(FileRange {
+        id,
+        offset,
+        data: data.into(),
+        size,
+        ..Default::default()
+    });
+    let mut msg_out = Message::new();
+    msg_out.set_file_response(resp);
+    msg_out
+}
+
 #[inline]
 pub fn new_block(block: FileTransferBlock) -> Message {
     let mut resp = FileResponse::new();
@@ -706,6 +1426,8 @@ pub fn new_receive(
     file_num: i32,
     files: Vec<FileEntry>,
     total_size: u64,
+    attributes: attributes::Options,
+    conflict_policy: FileConflictPolicy,
 ) -> Message {
     let mut action = FileAction::new();
     action.set_receive(FileTransferReceiveRequest {
@@ -714,6 +1436,9 @@ pub fn new_receive(
         files,
         file_num,
         total_size,
+        verify: true,
+        metadata: Some(attributes.to_proto()).into(),
+        conflict_policy: conflict_policy.into(),
         ..Default::default()
     });
     let mut msg_out = Message::new();
@@ -722,7 +1447,14 @@ pub fn new_receive(
 }
 
 #[inline]
-pub fn new_send(id: i32, path: String, file_num: i32, include_hidden: bool) -> Message {
+pub fn new_send(
+    id: i32,
+    path: String,
+    file_num: i32,
+    include_hidden: bool,
+    attributes: attributes::Options,
+    links: FileLinkPolicy,
+) -> Message {
     log::info!("new send: {}, id: {}", path, id);
     let mut action = FileAction::new();
     action.set_send(FileTransferSendRequest {
@@ -730,6 +1462,11 @@ pub fn new_send(id: i32, path: String, file_num: i32, include_hidden: bool) -> M
         path,
         include_hidden,
         file_num,
+        verify: true,
+        metadata: Some(attributes.to_proto()).into(),
+        link_policy: links.into(),
+        // the option of this side, as the peer reads
+        uncompressed: !Config::get_bool_option(keys::OPTION_ENABLE_FILE_TRANSFER_COMPRESSION),
         ..Default::default()
     });
     let mut msg_out = Message::new();
@@ -750,6 +1487,20 @@ pub fn new_done(id: i32, file_num: i32) -> Message {
     msg_out
 }
 
+/// The done of the transfer job, with the digests of its files.
+pub fn new_transfer_done(job: &TransferJob) -> Message {
+    let mut resp = FileResponse::new();
+    resp.set_done(FileTransferDone {
+        id: job.id(),
+        file_num: job.file_num(),
+        digests: job.digests.clone().into_iter().collect(),
+        ..Default::default()
+    });
+    let mut msg_out = Message::new();
+    msg_out.set_file_response(resp);
+    msg_out
+}
+
 #[inline]
 pub fn remove_job(id: i32, jobs: &mut Vec<TransferJob>) {
     *jobs = jobs.drain(0..).filter(|x| x.id() != id).collect();
@@ -765,14 +1516,18 @@ pub fn get_job_immutable(id: i32, jobs: &[TransferJob]) -> Option<&TransferJob>
     jobs.iter().find(|x| x.id() == id)
 }
 
+/// Send the next block of each job.
+///
+/// Returns the log of the finished job, the bytes of the sent blocks and the finished jobs.
 pub async fn handle_read_jobs(
     jobs: &mut Vec<TransferJob>,
     stream: &mut crate::Stream,
-) -> ResultType<String> {
+) -> ResultType<(String, usize, Vec<TransferJob>)> {
     let mut job_log = Default::default();
+    let mut sent = 0;
     let mut finished = Vec::new();
     for job in jobs.iter_mut() {
-        if job.is_last_job {
+        if job.is_last_job || !throttle::ready(&mut job.bucket) {
             continue;
         }
         match job.read(stream).await {
@@ -782,6 +1537,8 @@ pub async fn handle_read_jobs(
                     .await?;
             }
             Ok(Some(block)) => {
+                sent += block.data.len();
+                throttle::consume(&mut job.bucket, block.data.len());
                 stream.send(&new_block(block)).await?;
             }
             Ok(None) => {
@@ -795,7 +1552,7 @@ pub async fn handle_read_jobs(
                                 .send(&new_error(job.id(), err, job.file_num()))
                                 .await?
                         }
-                        None => stream.send(&new_done(job.id(), job.file_num())).await?,
+                        None => stream.send(&new_transfer_done(job)).await?,
                     }
                 } else {
                     // waiting confirmation.
@@ -803,10 +1560,10 @@ pub async fn handle_read_jobs(
             }
         }
     }
-    for id in finished {
-        remove_job(id, jobs);
-    }
-    Ok(job_log)
+    let (finished, left): (Vec<_>, Vec<_>) =
+        jobs.drain(..).partition(|job| finished.contains(&job.id()));
+    *jobs = left;
+    Ok((job_log, sent, finished))
 }
 
 pub fn remove_all_empty_dir(path: &PathBuf) -> ResultType<()> {
@@ -838,6 +1595,27 @@ pub fn create_dir(dir: &str) -> ResultType<()> {
     Ok(())
 }
 
+/// The most bytes read of a file for a preview at a time.
+pub const MAX_RANGE_SIZE: u32 = 1024 * 1024;
+
+/// Reads at most `length` bytes of the file from `offset` for a preview, capped by
+/// [`MAX_RANGE_SIZE`]. Returns them with the size of the file.
+pub fn read_range(path: &str, offset: u64, length: u32) -> ResultType<(Vec<u8>, u64)> {
+    use std::io::{Read, Seek, SeekFrom};
+    let mut file = std::fs::File::open(get_path(path))?;
+    let meta = file.metadata()?;
+    if meta.is_dir() {
+        bail!("{} is a folder", path);
+    }
+    let mut data = Vec::new();
+    if offset < meta.len() {
+        file.seek(SeekFrom::Start(offset))?;
+        file.take(length.min(MAX_RANGE_SIZE) as u64)
+            .read_to_end(&mut data)?;
+    }
+    Ok((data, meta.len()))
+}
+
 #[inline]
 pub fn rename_file(path: &str, new_name: &str) -> ResultType<()> {
     let path = std::path::Path::new(&path);
@@ -857,6 +1635,19 @@ pub fn rename_file(path: &str, new_name: &str) -> ResultType<()> {
 pub fn transform_windows_path(entries: &mut Vec<FileEntry>) {
     for entry in entries {
         entry.name = entry.name.replace('\\', "/");
+        if links::is_link(entry) {
+            entry.link_target = entry.link_target.replace('\\', "/");
+        }
+    }
+}
+
+/// The partial file left by an interrupted transfer to `file_path`.
+pub fn get_partial_download(file_path: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(format!("{}.download", file_path));
+    if path.metadata().ok()?.len() > 0 {
+        Some(path)
+    } else {
+        None
     }
 }
 
@@ -913,3 +1704,44 @@ pub fn serialize_transfer_job(job: &TransferJob, done: bool, cancel: bool, error
     value["error"] = json!(error);
     serde_json::to_string(&value).unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_range() {
+        let path = std::env::temp_dir().join(format!("hbb_range_{}", std::process::id()));
+        std::fs::write(&path, b"0123456789").unwrap();
+        let path = get_string(&path);
+        assert_eq!(read_range(&path, 2, 3).unwrap(), (b"234".to_vec(), 10));
+        assert_eq!(read_range(&path, 8, 100).unwrap(), (b"89".to_vec(), 10));
+        assert_eq!(read_range(&path, 20, 1).unwrap(), (vec![], 10));
+        std::fs::remove_file(&path).unwrap();
+        assert!(read_range(&get_string(&std::env::temp_dir()), 0, 1).is_err());
+    }
+
+    #[test]
+    fn test_compress_data() {
+        assert!(is_compressed_file("a/b.tar.GZ"));
+        assert!(!is_compressed_file("a.log"));
+        let text = "2024-01-01 00:00:00 INFO connected\n"
+            .repeat(1000)
+            .into_bytes();
+        assert!(!is_incompressible(&text));
+        let (data, compressed) = compress_data("a.log", text.clone());
+        assert!(compressed);
+        assert!(data.len() < text.len() / 10, "{}", data.len());
+        assert_eq!(decompress(&data), text);
+        // random-like data is sent as it is
+        let mut x = 1u32;
+        let random: Vec<u8> = (0..BUF_SIZE)
+            .map(|_| {
+                x = x.wrapping_mul(1103515245).wrapping_add(12345);
+                (x >> 16) as u8
+            })
+            .collect();
+        assert!(is_incompressible(&random));
+        assert_eq!(compress_data("a.bin", random.clone()), (random, false));
+    }
+}
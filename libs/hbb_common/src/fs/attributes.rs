@@ -0,0 +1,436 @@
+// Keep the permissions, timestamps and extended attributes of the files transferred.
+//
+// The reader fills them in the entries of the files, and the writer sets them on the copies once
+// written, both as the options of the transfer ask. Everything is best-effort, what this side can
+// not set is left as it is.
+//
+// Between Windows and Unix the permissions are mapped: the read-only attribute is a mode without
+// the write bits, the executables are guessed by their extensions, and the hidden entries of Unix
+// get the hidden attribute on Windows. The extended attributes are in the `user.` namespace on
+// Linux and without one on macOS, the names are mapped between the two.
+
+use crate::{
+    config::{keys, LocalConfig},
+    message_proto::{FileEntry, FileTransferMetadata, FileType, FileXattr},
+};
+use bytes::Bytes;
+use serde_derive::{Deserialize, Serialize};
+use std::{path::Path, time::SystemTime};
+
+// the values larger are not sent
+const MAX_XATTR_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Options {
+    pub permissions: bool,
+    pub timestamps: bool,
+    pub xattrs: bool,
+}
+
+impl Default for Options {
+    // what is kept with the peers not sending the options
+    fn default() -> Self {
+        Self {
+            permissions: false,
+            timestamps: true,
+            xattrs: false,
+        }
+    }
+}
+
+impl Options {
+    pub fn from_local_config() -> Self {
+        Self {
+            permissions: LocalConfig::get_bool_option(
+                keys::OPTION_ENABLE_PRESERVE_FILE_PERMISSIONS,
+            ),
+            timestamps: LocalConfig::get_bool_option(keys::OPTION_ENABLE_PRESERVE_FILE_TIMESTAMPS),
+            xattrs: LocalConfig::get_bool_option(keys::OPTION_ENABLE_PRESERVE_FILE_XATTRS),
+        }
+    }
+
+    pub fn from_proto(metadata: Option<&FileTransferMetadata>) -> Self {
+        match metadata {
+            Some(m) => Self {
+                permissions: m.permissions,
+                timestamps: m.timestamps,
+                xattrs: m.xattrs,
+            },
+            None => Self::default(),
+        }
+    }
+
+    pub fn to_proto(&self) -> FileTransferMetadata {
+        FileTransferMetadata {
+            permissions: self.permissions,
+            timestamps: self.timestamps,
+            xattrs: self.xattrs,
+            ..Default::default()
+        }
+    }
+}
+
+/// The attributes of an entry besides its name, size and modified time, passed over the ipc.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntryAttributes {
+    pub is_hidden: bool,
+    pub mode: u32,
+    pub accessed_time: u64,
+    pub xattrs: Vec<(String, Bytes)>,
+    pub link_target: String,
+    pub hard_link: bool,
+    pub dir_link: bool,
+}
+
+impl EntryAttributes {
+    pub fn of(entry: &FileEntry) -> Self {
+        Self {
+            is_hidden: entry.is_hidden,
+            mode: entry.mode,
+            accessed_time: entry.accessed_time,
+            xattrs: entry
+                .xattrs
+                .iter()
+                .map(|x| (x.name.clone(), x.value.clone()))
+                .collect(),
+            link_target: entry.link_target.clone(),
+            hard_link: entry.hard_link,
+            dir_link: entry.entry_type.enum_value() == Ok(FileType::DirLink),
+        }
+    }
+
+    pub fn set_to(self, entry: &mut FileEntry) {
+        entry.is_hidden = self.is_hidden;
+        entry.mode = self.mode;
+        entry.accessed_time = self.accessed_time;
+        entry.xattrs = self
+            .xattrs
+            .into_iter()
+            .map(|(name, value)| FileXattr {
+                name,
+                value,
+                ..Default::default()
+            })
+            .collect();
+        entry.link_target = self.link_target;
+        entry.hard_link = self.hard_link;
+        if self.dir_link {
+            entry.entry_type = FileType::DirLink.into();
+        } else if !entry.link_target.is_empty() && !entry.hard_link {
+            entry.entry_type = FileType::FileLink.into();
+        }
+    }
+}
+
+fn unix_time(t: std::io::Result<SystemTime>) -> u64 {
+    t.ok()
+        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Fills the attributes of the file at `path` in its entry.
+pub fn capture(path: &Path, entry: &mut FileEntry, options: &Options) {
+    let meta = match std::fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(_) => return,
+    };
+    if options.permissions {
+        entry.mode = mode_of(&meta, path);
+    }
+    if options.timestamps {
+        entry.accessed_time = unix_time(meta.accessed());
+    }
+    if options.xattrs {
+        entry.xattrs = xattr::list(path)
+            .into_iter()
+            .filter(|(_, value)| value.len() <= MAX_XATTR_SIZE)
+            .map(|(name, value)| FileXattr {
+                name,
+                value: value.into(),
+                ..Default::default()
+            })
+            .collect();
+    }
+}
+
+/// Sets the attributes in the entry on the file written at `path`.
+pub fn restore(path: &Path, entry: &FileEntry, options: &Options) {
+    if options.xattrs {
+        for x in entry.xattrs.iter() {
+            xattr::set(path, &x.name, &x.value);
+        }
+    }
+    if options.permissions {
+        set_permissions(path, entry);
+    }
+    if options.timestamps {
+        let mtime = filetime::FileTime::from_unix_time(entry.modified_time as _, 0);
+        if entry.accessed_time > 0 {
+            let atime = filetime::FileTime::from_unix_time(entry.accessed_time as _, 0);
+            filetime::set_file_times(path, atime, mtime).ok();
+        } else {
+            filetime::set_file_mtime(path, mtime).ok();
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn mode_of(meta: &std::fs::Metadata, _path: &Path) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o7777
+}
+
+#[cfg(windows)]
+fn mode_of(meta: &std::fs::Metadata, path: &Path) -> u32 {
+    const EXECUTABLES: &[&str] = &["exe", "com", "bat", "cmd", "ps1", "sh"];
+    let mut mode = if meta.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    };
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if EXECUTABLES.contains(&ext.as_str()) {
+        mode |= 0o111;
+    }
+    mode
+}
+
+#[cfg(not(windows))]
+fn set_permissions(path: &Path, entry: &FileEntry) {
+    use std::os::unix::fs::PermissionsExt;
+    if entry.mode != 0 {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(entry.mode)).ok();
+    }
+}
+
+#[cfg(windows)]
+fn set_permissions(path: &Path, entry: &FileEntry) {
+    use std::os::windows::{ffi::OsStrExt, fs::MetadataExt};
+    use winapi::um::{fileapi::SetFileAttributesW, winnt};
+    let attributes = match std::fs::metadata(path) {
+        Ok(meta) => meta.file_attributes(),
+        Err(_) => return,
+    };
+    let mut new_attributes = attributes;
+    if entry.mode != 0 {
+        if entry.mode & 0o222 == 0 {
+            new_attributes |= winnt::FILE_ATTRIBUTE_READONLY;
+        } else {
+            new_attributes &= !winnt::FILE_ATTRIBUTE_READONLY;
+        }
+    }
+    if entry.is_hidden {
+        new_attributes |= winnt::FILE_ATTRIBUTE_HIDDEN;
+    }
+    if new_attributes != attributes {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        unsafe {
+            SetFileAttributesW(wide.as_ptr(), new_attributes);
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod xattr {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt, path::Path};
+
+    fn c_path(path: &Path) -> Option<CString> {
+        CString::new(path.as_os_str().as_bytes()).ok()
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn list_names(path: &CString, buf: &mut [u8]) -> isize {
+        libc::listxattr(path.as_ptr(), buf.as_mut_ptr() as _, buf.len())
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn list_names(path: &CString, buf: &mut [u8]) -> isize {
+        libc::listxattr(path.as_ptr(), buf.as_mut_ptr() as _, buf.len(), 0)
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn get_value(path: &CString, name: &CString, buf: &mut [u8]) -> isize {
+        libc::getxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            buf.as_mut_ptr() as _,
+            buf.len(),
+        )
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn get_value(path: &CString, name: &CString, buf: &mut [u8]) -> isize {
+        libc::getxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            buf.as_mut_ptr() as _,
+            buf.len(),
+            0,
+            0,
+        )
+    }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn set_value(path: &CString, name: &CString, value: &[u8]) -> i32 {
+        libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr() as _,
+            value.len(),
+            0,
+        )
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe fn set_value(path: &CString, name: &CString, value: &[u8]) -> i32 {
+        libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr() as _,
+            value.len(),
+            0,
+            0,
+        )
+    }
+
+    // the size asked first may be outgrown before the read
+    fn read(f: impl Fn(&mut [u8]) -> isize) -> Option<Vec<u8>> {
+        for _ in 0..3 {
+            let size = f(&mut []);
+            if size < 0 {
+                return None;
+            }
+            let mut buf = vec![0u8; size as usize];
+            let n = f(&mut buf);
+            if n >= 0 {
+                buf.truncate(n as usize);
+                return Some(buf);
+            }
+        }
+        None
+    }
+
+    pub fn list(path: &Path) -> Vec<(String, Vec<u8>)> {
+        let path = match c_path(path) {
+            Some(path) => path,
+            None => return vec![],
+        };
+        let names = match read(|buf| unsafe { list_names(&path, buf) }) {
+            Some(names) => names,
+            None => return vec![],
+        };
+        names
+            .split(|c| *c == 0)
+            .filter(|name| !name.is_empty())
+            .filter_map(|name| {
+                let c_name = CString::new(name).ok()?;
+                let value = read(|buf| unsafe { get_value(&path, &c_name, buf) })?;
+                Some((String::from_utf8(name.to_vec()).ok()?, value))
+            })
+            .collect()
+    }
+
+    pub fn set(path: &Path, name: &str, value: &[u8]) {
+        let name = match local_name(name) {
+            Some(name) => name,
+            None => return,
+        };
+        if let (Some(path), Ok(name)) = (c_path(path), CString::new(name)) {
+            unsafe {
+                set_value(&path, &name, value);
+            }
+        }
+    }
+
+    // the names of the attributes of the other system, which have no meaning here, are skipped
+    #[cfg(target_os = "linux")]
+    fn local_name(name: &str) -> Option<String> {
+        const NAMESPACES: &[&str] = &["user.", "trusted.", "security.", "system."];
+        if NAMESPACES.iter().any(|ns| name.starts_with(ns)) {
+            Some(name.to_owned())
+        } else if name.starts_with("com.apple.") {
+            None
+        } else {
+            Some(format!("user.{}", name))
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn local_name(name: &str) -> Option<String> {
+        const NAMESPACES: &[&str] = &["trusted.", "security.", "system."];
+        if let Some(name) = name.strip_prefix("user.") {
+            Some(name.to_owned())
+        } else if NAMESPACES.iter().any(|ns| name.starts_with(ns)) {
+            None
+        } else {
+            Some(name.to_owned())
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod xattr {
+    use std::path::Path;
+
+    pub fn list(_path: &Path) -> Vec<(String, Vec<u8>)> {
+        vec![]
+    }
+
+    pub fn set(_path: &Path, _name: &str, _value: &[u8]) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_restore() {
+        let dir = std::env::temp_dir().join(format!("hbb_attributes_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (src, dst) = (dir.join("src"), dir.join("dst"));
+        std::fs::write(&src, b"src").unwrap();
+        std::fs::write(&dst, b"src").unwrap();
+        filetime::set_file_times(
+            &src,
+            filetime::FileTime::from_unix_time(1_600_000_000, 0),
+            filetime::FileTime::from_unix_time(1_500_000_000, 0),
+        )
+        .unwrap();
+        #[cfg(not(windows))]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&src, std::fs::Permissions::from_mode(0o751)).unwrap();
+        }
+        let options = Options {
+            permissions: true,
+            timestamps: true,
+            xattrs: true,
+        };
+        let mut entry = FileEntry {
+            modified_time: 1_500_000_000,
+            ..Default::default()
+        };
+        capture(&src, &mut entry, &options);
+        assert_eq!(entry.accessed_time, 1_600_000_000);
+        let attributes = EntryAttributes::of(&entry);
+        let mut copied = FileEntry {
+            modified_time: entry.modified_time,
+            ..Default::default()
+        };
+        attributes.set_to(&mut copied);
+        restore(&dst, &copied, &options);
+        let meta = std::fs::metadata(&dst).unwrap();
+        assert_eq!(unix_time(meta.modified()), 1_500_000_000);
+        assert_eq!(unix_time(meta.accessed()), 1_600_000_000);
+        #[cfg(not(windows))]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(meta.permissions().mode() & 0o7777, 0o751);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+// Record the file transfers of the peers on this side, for the audits of the support sessions.
+//
+// An event is made of a transfer job when it is done, failed or cancelled: the peer, whether the
+// files were sent to it or received from it, the path on this side, the files with their sizes and
+// sha256, and the bytes transferred. A part of a file read for a preview and a file attached in
+// the chat are ones too. The events are appended as JSON lines to a log beside the other logs,
+// which is moved aside to `.1` once it grows too large. The server passes them to the webhook and
+// the plugins as well.
+
+use super::{get_string, TransferJob};
+use crate::{config::Config, ResultType};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const LOG_NAME: &str = "file_transfer_audit.log";
+const MAX_LOG_SIZE: u64 = 10 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    // to the peer
+    Send,
+    // from the peer
+    Receive,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileRecord {
+    pub name: String,
+    pub size: u64,
+    // sha256 in hex, empty if the file was not hashed, e.g. skipped or sent by an older peer
+    #[serde(default)]
+    pub checksum: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferEvent {
+    // in seconds
+    pub time: u64,
+    // filled by the server, which knows the peer
+    #[serde(default)]
+    pub peer_id: String,
+    #[serde(default)]
+    pub peer_name: String,
+    pub direction: Direction,
+    pub path: String,
+    pub files: Vec<FileRecord>,
+    // of the files written or read, before the compression
+    pub bytes: u64,
+    pub result: Outcome,
+}
+
+impl TransferEvent {
+    pub fn of(job: &TransferJob, direction: Direction, result: Outcome) -> Self {
+        let digests = job.digests();
+        Self {
+            time: now(),
+            peer_id: String::new(),
+            peer_name: String::new(),
+            direction,
+            path: get_string(&job.path),
+            files: job
+                .files()
+                .iter()
+                .map(|f| FileRecord {
+                    name: f.name.clone(),
+                    size: f.size,
+                    checksum: digests.get(&f.name).cloned().unwrap_or_default(),
+                })
+                .collect(),
+            bytes: job.finished_size(),
+            result,
+        }
+    }
+
+    /// The `bytes` of the single file `path` of `size` passed without a job, e.g. read for a
+    /// preview or attached in the chat.
+    pub fn of_file(direction: Direction, path: &str, size: u64, bytes: u64) -> Self {
+        Self {
+            time: now(),
+            peer_id: String::new(),
+            peer_name: String::new(),
+            direction,
+            path: path.to_owned(),
+            // the name of a single file is empty, as in a job
+            files: vec![FileRecord {
+                size,
+                ..Default::default()
+            }],
+            bytes,
+            result: Outcome::Done,
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Appends the event to the audit log of this side.
+pub fn append(event: &TransferEvent) -> ResultType<()> {
+    append_to(&Config::log_path().join(LOG_NAME), event)
+}
+
+fn append_to(path: &Path, event: &TransferEvent) -> ResultType<()> {
+    if path.metadata().map(|m| m.len()).unwrap_or(0) >= MAX_LOG_SIZE {
+        let mut old = path.as_os_str().to_owned();
+        old.push(".1");
+        std::fs::rename(path, old)?;
+    }
+    let mut line = serde_json::to_string(event)?;
+    line.push('\n');
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append() {
+        let dir = std::env::temp_dir().join(format!("hbb_audit_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(LOG_NAME);
+        let event = TransferEvent {
+            time: 1,
+            peer_id: "123".to_owned(),
+            peer_name: "peer".to_owned(),
+            direction: Direction::Receive,
+            path: "/tmp/a".to_owned(),
+            files: vec![FileRecord {
+                name: "b".to_owned(),
+                size: 2,
+                checksum: "ab".to_owned(),
+            }],
+            bytes: 2,
+            result: Outcome::Failed("skipped".to_owned()),
+        };
+        append_to(&path, &event).unwrap();
+        append_to(&path, &event).unwrap();
+        let log = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("\"direction\":\"receive\""));
+        assert!(lines[0].contains("\"result\":{\"failed\":\"skipped\"}"));
+        let parsed: TransferEvent = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(parsed.result, event.result);
+        assert_eq!(parsed.files[0].checksum, "ab");
+
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .set_len(MAX_LOG_SIZE)
+            .unwrap();
+        append_to(&path, &event).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+        assert!(dir.join(format!("{}.1", LOG_NAME)).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+// The file existing where a file sent is written, resolved by the policy of the transfer.
+//
+// `AskOnConflict` leaves it to the user, the other policies are applied without asking: the file
+// existing is overwritten, overwritten only if the file sent was modified later, kept by skipping
+// the file sent, or kept with the file sent written beside it as `name (1).ext`. The writer
+// resolves the conflicts. The reader resolves them as well for the peers writing which do not know
+// the policies, but can not rename on the peer and asks instead.
+
+use super::get_string;
+use crate::{
+    config::{keys, LocalConfig},
+    message_proto::FileConflictPolicy,
+};
+use std::path::Path;
+
+pub fn policy_from_local_config() -> FileConflictPolicy {
+    parse(&LocalConfig::get_option(
+        keys::OPTION_FILE_TRANSFER_CONFLICT_POLICY,
+    ))
+    .unwrap_or(FileConflictPolicy::AskOnConflict)
+}
+
+/// The policy of the name in the options and on the command line.
+pub fn parse(name: &str) -> Option<FileConflictPolicy> {
+    match name {
+        "ask" => Some(FileConflictPolicy::AskOnConflict),
+        "overwrite" => Some(FileConflictPolicy::OverwriteExisting),
+        "newer" => Some(FileConflictPolicy::OverwriteIfNewer),
+        "skip" => Some(FileConflictPolicy::SkipExisting),
+        "rename" => Some(FileConflictPolicy::RenameWithSuffix),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Ask,
+    Overwrite,
+    Skip,
+    Rename,
+}
+
+/// Resolves the conflict of the file sent, modified at `incoming_modified`, with the file existing,
+/// modified at `existing_modified`, in seconds.
+pub fn resolve(
+    policy: FileConflictPolicy,
+    incoming_modified: u64,
+    existing_modified: u64,
+) -> Resolution {
+    match policy {
+        FileConflictPolicy::AskOnConflict => Resolution::Ask,
+        FileConflictPolicy::OverwriteExisting => Resolution::Overwrite,
+        FileConflictPolicy::OverwriteIfNewer => {
+            if incoming_modified > existing_modified {
+                Resolution::Overwrite
+            } else {
+                Resolution::Skip
+            }
+        }
+        FileConflictPolicy::SkipExisting => Resolution::Skip,
+        FileConflictPolicy::RenameWithSuffix => Resolution::Rename,
+    }
+}
+
+/// The first name of `name (n).ext` relative to `root` which neither a file nor a partial download
+/// has.
+pub fn free_name(root: &Path, name: &str) -> String {
+    let path = Path::new(name);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut n = 1;
+    loop {
+        let candidate = get_string(&path.with_file_name(format!("{} ({}){}", stem, n, ext)));
+        let full = root.join(&candidate);
+        if !full.exists() && !Path::new(&format!("{}.download", get_string(&full))).exists() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conflict() {
+        assert_eq!(
+            resolve(FileConflictPolicy::OverwriteIfNewer, 2, 1),
+            Resolution::Overwrite
+        );
+        assert_eq!(
+            resolve(FileConflictPolicy::OverwriteIfNewer, 1, 1),
+            Resolution::Skip
+        );
+        assert_eq!(parse("rename"), Some(FileConflictPolicy::RenameWithSuffix));
+        assert_eq!(parse("always"), None);
+
+        let root = std::env::temp_dir().join(format!("hbb_conflict_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("a")).unwrap();
+        let name = get_string(&Path::new("a").join("f.txt"));
+        std::fs::write(root.join(&name), b"").unwrap();
+        assert_eq!(
+            free_name(&root, &name),
+            get_string(&Path::new("a").join("f (1).txt"))
+        );
+        std::fs::write(root.join("a").join("f (1).txt.download"), b"").unwrap();
+        assert_eq!(
+            free_name(&root, &name),
+            get_string(&Path::new("a").join("f (2).txt"))
+        );
+        assert_eq!(free_name(&root, ".profile"), ".profile (1)");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,365 @@
+// Send only the blocks changed of a file the writer has a copy of, like rsync.
+//
+// The writer sends the checksums of the whole blocks of its copy. The reader looks for them in its
+// file with the rolling checksum, and sends the indexes of the blocks found instead of their data.
+// The copy is either the old version of a file to overwrite, or the partial `.download` file of an
+// interrupted transfer. The partial file is compared block by block from the beginning only, and
+// the transfer is continued at the first block changed. The copy corrupted in a transfer is also
+// compared block by block, and only the blocks changed are sent to be written in place.
+
+use crate::{message_proto::FileBlockChecksums, ResultType};
+use sodiumoxide::crypto::hash::sha256;
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{ErrorKind, Read},
+    path::Path,
+};
+
+const MIN_BLOCK_SIZE: u64 = 4 * 1024;
+const MAX_BLOCK_SIZE: u64 = 128 * 1024;
+const STRONG_LEN: usize = 16;
+
+/// About the square root of the file size like rsync, so a large file does not have too many
+/// checksums to send.
+pub fn block_size(file_size: u64) -> u32 {
+    let size = ((file_size as f64).sqrt() as u64).clamp(MIN_BLOCK_SIZE, MAX_BLOCK_SIZE);
+    (size / 1024 * 1024) as _
+}
+
+/// The checksums of the copy at `path`, the blocks are sized for the file of `file_size` to send.
+pub fn checksums(path: &Path, file_size: u64, partial: bool) -> ResultType<FileBlockChecksums> {
+    let file = std::fs::File::open(path)?;
+    read_checksums(file, block_size(file_size), partial)
+}
+
+fn read_checksums(
+    mut reader: impl Read,
+    block_size: u32,
+    partial: bool,
+) -> ResultType<FileBlockChecksums> {
+    let mut checksums = FileBlockChecksums {
+        block_size,
+        partial,
+        ..Default::default()
+    };
+    let mut buf = vec![0; block_size as usize];
+    loop {
+        match reader.read_exact(&mut buf) {
+            Ok(()) => {
+                checksums.weak.push(Rolling::new(&buf).digest());
+                checksums.strong.push(strong(&buf).into());
+            }
+            // the last block which is not whole is sent as it is
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(checksums)
+}
+
+fn strong(data: &[u8]) -> Vec<u8> {
+    sha256::hash(data).0[..STRONG_LEN].to_vec()
+}
+
+// The checksum of rsync, which rolls over the file byte by byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rolling {
+    a: u32,
+    b: u32,
+    len: u32,
+}
+
+impl Rolling {
+    fn new(data: &[u8]) -> Self {
+        let len = data.len() as u32;
+        let mut a = 0u32;
+        let mut b = 0u32;
+        for (i, x) in data.iter().enumerate() {
+            a = a.wrapping_add(*x as u32);
+            b = b.wrapping_add((len - i as u32).wrapping_mul(*x as u32));
+        }
+        Self { a, b, len }
+    }
+
+    fn roll(&mut self, out: u8, input: u8) {
+        self.a = self.a.wrapping_sub(out as u32).wrapping_add(input as u32);
+        self.b = self
+            .b
+            .wrapping_sub(self.len.wrapping_mul(out as u32))
+            .wrapping_add(self.a);
+    }
+
+    fn digest(&self) -> u32 {
+        (self.a & 0xffff) | (self.b << 16)
+    }
+}
+
+#[derive(Debug)]
+enum Op {
+    Copy(u32),
+    Data(Vec<u8>),
+}
+
+/// Finds the blocks of the writer in the file read.
+#[derive(Debug)]
+pub struct Matcher {
+    block_size: usize,
+    weak: Vec<u32>,
+    strong: Vec<Vec<u8>>,
+    index: HashMap<u32, Vec<u32>>,
+    partial: bool,
+    repair: bool,
+    // the next block of the partial file to compare, none after the first one changed
+    next: Option<usize>,
+    // the bytes read but not matched yet
+    buf: Vec<u8>,
+    // of the block at the beginning of `buf`
+    rolling: Option<Rolling>,
+    ops: VecDeque<Op>,
+}
+
+impl Matcher {
+    pub fn new(checksums: &FileBlockChecksums) -> Self {
+        let mut index: HashMap<u32, Vec<u32>> = HashMap::new();
+        let partial = checksums.partial || checksums.repair;
+        if !partial {
+            for (i, weak) in checksums.weak.iter().enumerate() {
+                index.entry(*weak).or_default().push(i as _);
+            }
+        }
+        Self {
+            block_size: checksums.block_size.max(1) as _,
+            weak: checksums.weak.clone(),
+            strong: checksums.strong.iter().map(|s| s.to_vec()).collect(),
+            index,
+            partial,
+            repair: checksums.repair,
+            next: Some(0),
+            buf: vec![],
+            rolling: None,
+            ops: Default::default(),
+        }
+    }
+
+    #[inline]
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Matches the bytes read next, `eof` if the file ends.
+    pub fn feed(&mut self, input: &[u8], eof: bool) {
+        self.buf.extend_from_slice(input);
+        let pos = if self.partial {
+            self.match_partial(eof)
+        } else {
+            self.match_rolling(eof)
+        };
+        self.buf.drain(..pos);
+    }
+
+    // returns the bytes matched or sent as data
+    fn match_partial(&mut self, eof: bool) -> usize {
+        let bs = self.block_size;
+        let mut pos = 0;
+        while let Some(i) = self.next {
+            if i >= self.strong.len() || i >= self.weak.len() {
+                self.next = None;
+                break;
+            }
+            if self.buf.len() - pos < bs {
+                break;
+            }
+            let block = &self.buf[pos..pos + bs];
+            if Rolling::new(block).digest() == self.weak[i] && strong(block) == self.strong[i] {
+                self.ops.push_back(Op::Copy(i as _));
+                pos += bs;
+                self.next = Some(i + 1);
+            } else if self.repair {
+                self.push_data(pos, pos + bs);
+                pos += bs;
+                self.next = Some(i + 1);
+            } else {
+                self.next = None;
+            }
+        }
+        if self.next.is_none() || eof {
+            self.push_data(pos, self.buf.len());
+            pos = self.buf.len();
+        }
+        pos
+    }
+
+    fn match_rolling(&mut self, eof: bool) -> usize {
+        let bs = self.block_size;
+        let mut pos = 0;
+        let mut data_start = 0;
+        while !self.index.is_empty() && self.buf.len() - pos >= bs {
+            let window = &self.buf[pos..pos + bs];
+            let rolling = *self.rolling.get_or_insert_with(|| Rolling::new(window));
+            if let Some(i) = self.find(rolling.digest(), window) {
+                self.push_data(data_start, pos);
+                self.ops.push_back(Op::Copy(i));
+                pos += bs;
+                data_start = pos;
+                self.rolling = None;
+                continue;
+            }
+            match (self.rolling.as_mut(), self.buf.get(pos + bs)) {
+                (Some(rolling), Some(input)) => rolling.roll(self.buf[pos], *input),
+                _ => self.rolling = None,
+            }
+            pos += 1;
+        }
+        if eof || self.index.is_empty() {
+            pos = self.buf.len();
+        }
+        self.push_data(data_start, pos);
+        pos
+    }
+
+    fn find(&self, weak: u32, block: &[u8]) -> Option<u32> {
+        let candidates = self.index.get(&weak)?;
+        let strong = strong(block);
+        candidates
+            .iter()
+            .find(|i| self.strong.get(**i as usize) == Some(&strong))
+            .copied()
+    }
+
+    fn push_data(&mut self, start: usize, end: usize) {
+        if start < end {
+            self.ops.push_back(Op::Data(self.buf[start..end].to_vec()));
+        }
+    }
+
+    /// The blocks to copy and the data after them, none if nothing is matched yet.
+    pub fn next_block(&mut self) -> Option<(Vec<u32>, Vec<u8>)> {
+        let mut copies = vec![];
+        while let Some(Op::Copy(i)) = self.ops.front() {
+            copies.push(*i);
+            self.ops.pop_front();
+        }
+        let data = match self.ops.front() {
+            Some(Op::Data(_)) => match self.ops.pop_front() {
+                Some(Op::Data(data)) => data,
+                _ => vec![],
+            },
+            _ => vec![],
+        };
+        if copies.is_empty() && data.is_empty() {
+            None
+        } else {
+            Some((copies, data))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_SIZE: u32 = 1024;
+
+    fn random(len: usize, seed: u32) -> Vec<u8> {
+        let mut x = seed;
+        (0..len)
+            .map(|_| {
+                x = x.wrapping_mul(1103515245).wrapping_add(12345);
+                (x >> 16) as u8
+            })
+            .collect()
+    }
+
+    // returns the file rebuilt and the bytes of the data sent
+    fn transfer(copy: &[u8], file: &[u8], partial: bool) -> (Vec<u8>, usize) {
+        let checksums = read_checksums(copy, BLOCK_SIZE, partial).unwrap();
+        transfer_with(&checksums, copy, file)
+    }
+
+    fn transfer_with(checksums: &FileBlockChecksums, copy: &[u8], file: &[u8]) -> (Vec<u8>, usize) {
+        let partial = checksums.partial || checksums.repair;
+        let mut matcher = Matcher::new(checksums);
+        let mut rebuilt = vec![];
+        let mut sent = 0;
+        let chunks: Vec<&[u8]> = file.chunks(3000).collect();
+        for (i, chunk) in chunks.iter().enumerate() {
+            matcher.feed(chunk, i == chunks.len() - 1);
+            while let Some((copies, data)) = matcher.next_block() {
+                for blk in copies {
+                    let start = blk as usize * BLOCK_SIZE as usize;
+                    if partial {
+                        assert_eq!(start, rebuilt.len());
+                    }
+                    rebuilt.extend_from_slice(&copy[start..start + BLOCK_SIZE as usize]);
+                }
+                sent += data.len();
+                rebuilt.extend(data);
+            }
+        }
+        (rebuilt, sent)
+    }
+
+    #[test]
+    fn test_rolling() {
+        let data = random(3000, 1);
+        let mut rolling = Rolling::new(&data[..1000]);
+        for i in 0..2000 {
+            rolling.roll(data[i], data[i + 1000]);
+            assert_eq!(rolling, Rolling::new(&data[i + 1..i + 1001]));
+        }
+    }
+
+    #[test]
+    fn test_delta() {
+        let copy = random(100_000, 2);
+        let mut file = copy.clone();
+        file.splice(30_000..30_000, random(500, 3));
+        file[70_000] ^= 0xff;
+        file.extend(random(2000, 4));
+        let (rebuilt, sent) = transfer(&copy, &file, false);
+        assert!(rebuilt == file);
+        assert!(sent < 10_000, "{}", sent);
+        let (rebuilt, sent) = transfer(&[], &file, false);
+        assert!(rebuilt == file);
+        assert_eq!(sent, file.len());
+    }
+
+    #[test]
+    fn test_partial() {
+        let file = random(100_000, 5);
+        let mut copy = file[..50_500].to_vec();
+        let (rebuilt, sent) = transfer(&copy, &file, true);
+        assert!(rebuilt == file);
+        assert_eq!(sent, file.len() - 49 * BLOCK_SIZE as usize);
+        // changed since
+        copy[20_000] ^= 0xff;
+        let (rebuilt, sent) = transfer(&copy, &file, true);
+        assert!(rebuilt == file);
+        assert_eq!(sent, file.len() - 19 * BLOCK_SIZE as usize);
+    }
+
+    #[test]
+    fn test_repair() {
+        let file = random(100_000, 6);
+        let mut copy = file.clone();
+        copy[20_000] ^= 0xff;
+        copy[70_000] ^= 0xff;
+        copy.truncate(90_500);
+        let mut checksums = read_checksums(&copy[..], BLOCK_SIZE, false).unwrap();
+        checksums.repair = true;
+        let (rebuilt, sent) = transfer_with(&checksums, &copy, &file);
+        assert!(rebuilt == file);
+        // the 2 blocks changed and the data after the whole blocks of the copy
+        assert_eq!(
+            sent,
+            2 * BLOCK_SIZE as usize + file.len() - 88 * BLOCK_SIZE as usize
+        );
+    }
+
+    #[test]
+    fn test_block_size() {
+        assert_eq!(block_size(0), MIN_BLOCK_SIZE as u32);
+        assert_eq!(block_size(100 << 20), 10 * 1024);
+        assert_eq!(block_size(1 << 40), MAX_BLOCK_SIZE as u32);
+    }
+}
@@ -0,0 +1,208 @@
+// The symbolic and hard links in the folders sent, handled by the policy of the transfer.
+//
+// `SkipLinks` leaves the symbolic links out. `FollowLinks` sends what they point to, a folder linked
+// back to one of the folders it is in is a cycle and skipped. `CopyLinks` sends the symbolic links
+// as their targets, and the files hard linked to a file sent before as its name, the writer creates
+// the links once the files are written. The reader skips the links, they have no data.
+
+use super::get_string;
+use crate::{
+    config::{keys, LocalConfig},
+    message_proto::{FileEntry, FileLinkPolicy},
+};
+#[cfg(unix)]
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub fn policy_from_local_config() -> FileLinkPolicy {
+    match LocalConfig::get_option(keys::OPTION_FILE_TRANSFER_LINK_POLICY).as_str() {
+        "copy" => FileLinkPolicy::CopyLinks,
+        "follow" => FileLinkPolicy::FollowLinks,
+        _ => FileLinkPolicy::SkipLinks,
+    }
+}
+
+#[inline]
+pub fn is_link(entry: &FileEntry) -> bool {
+    !entry.link_target.is_empty()
+}
+
+pub(super) struct Walker {
+    pub policy: FileLinkPolicy,
+    // the folders being walked, canonicalized
+    ancestors: Vec<PathBuf>,
+    // the name of the first file sent of the inode with more than one link
+    #[cfg(unix)]
+    inodes: HashMap<(u64, u64), String>,
+}
+
+impl Walker {
+    pub fn new(policy: FileLinkPolicy) -> Self {
+        Self {
+            policy,
+            ancestors: Vec::new(),
+            #[cfg(unix)]
+            inodes: HashMap::new(),
+        }
+    }
+
+    /// Returns false if the folder is one of the folders it is in.
+    pub fn enter(&mut self, dir: &Path) -> bool {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        if self.ancestors.contains(&dir) {
+            log::info!("skip the cycle of the links to {:?}", dir);
+            return false;
+        }
+        self.ancestors.push(dir);
+        true
+    }
+
+    pub fn leave(&mut self) {
+        self.ancestors.pop();
+    }
+
+    /// Turns the entry of the symbolic link at `path` into the link, false if it can not be read.
+    pub fn read_link(&self, path: &Path, entry: &mut FileEntry) -> bool {
+        match std::fs::read_link(path) {
+            Ok(target) if !get_string(&target).is_empty() => {
+                entry.link_target = get_string(&target);
+                entry.size = 0;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Turns the entry of the file at `path` into a hard link if its inode has been sent.
+    #[cfg(unix)]
+    pub fn check_hard_link(&mut self, path: &Path, entry: &mut FileEntry) {
+        use std::os::unix::fs::MetadataExt;
+        if self.policy != FileLinkPolicy::CopyLinks {
+            return;
+        }
+        let meta = match std::fs::metadata(path) {
+            Ok(meta) if meta.nlink() > 1 => meta,
+            _ => return,
+        };
+        match self.inodes.get(&(meta.dev(), meta.ino())) {
+            Some(first) => {
+                entry.link_target = first.clone();
+                entry.hard_link = true;
+                entry.size = 0;
+            }
+            None => {
+                self.inodes
+                    .insert((meta.dev(), meta.ino()), entry.name.clone());
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn check_hard_link(&mut self, _path: &Path, _entry: &mut FileEntry) {}
+}
+
+/// Creates the link of the entry at `path`, replacing a file or link there. A hard link is created
+/// to `root` joined with its target.
+pub fn create(root: &Path, path: &Path, entry: &FileEntry) -> std::io::Result<()> {
+    if let Some(p) = path.parent() {
+        std::fs::create_dir_all(p).ok();
+    }
+    if let Ok(meta) = path.symlink_metadata() {
+        if meta.is_dir() {
+            return Ok(());
+        }
+        std::fs::remove_file(path)?;
+    }
+    if entry.hard_link {
+        return std::fs::hard_link(root.join(&entry.link_target), path);
+    }
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(&entry.link_target, path)
+    }
+    #[cfg(windows)]
+    {
+        if entry.entry_type.enum_value() == Ok(crate::message_proto::FileType::DirLink) {
+            std::os::windows::fs::symlink_dir(&entry.link_target, path)
+        } else {
+            std::os::windows::fs::symlink_file(&entry.link_target, path)
+        }
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::fs::get_recursive_files_with_links;
+
+    #[test]
+    fn test_links() {
+        let root = std::env::temp_dir().join(format!("hbb_links_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let src = root.join("src");
+        std::fs::create_dir_all(src.join("a")).unwrap();
+        std::fs::write(src.join("a").join("f"), b"f").unwrap();
+        std::fs::hard_link(src.join("a").join("f"), src.join("h")).unwrap();
+        std::os::unix::fs::symlink("a/f", src.join("l")).unwrap();
+        // a cycle
+        std::os::unix::fs::symlink("..", src.join("a").join("up")).unwrap();
+        let names = |policy| {
+            let mut files = get_recursive_files_with_links(&get_string(&src), false, policy)
+                .unwrap()
+                .into_iter()
+                .map(|f| (f.name, f.link_target, f.hard_link))
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+        let f = get_string(&Path::new("a").join("f"));
+
+        assert_eq!(
+            names(FileLinkPolicy::SkipLinks),
+            vec![
+                (f.clone(), "".to_owned(), false),
+                ("h".to_owned(), "".to_owned(), false),
+            ]
+        );
+        let copied = names(FileLinkPolicy::CopyLinks);
+        assert_eq!(copied.len(), 4);
+        assert!(copied.contains(&("l".to_owned(), "a/f".to_owned(), false)));
+        assert!(copied.contains(&(
+            get_string(&Path::new("a").join("up")),
+            "..".to_owned(),
+            false
+        )));
+        // either is sent first
+        assert!(
+            copied.contains(&("h".to_owned(), f.clone(), true))
+                || copied.contains(&(f.clone(), "h".to_owned(), true))
+        );
+        // the cycle is walked once
+        let followed = names(FileLinkPolicy::FollowLinks);
+        assert_eq!(followed.len(), 3);
+        assert!(followed.iter().all(|f| f.1.is_empty()));
+
+        let dst = root.join("dst");
+        for (name, target, hard_link) in copied {
+            let entry = FileEntry {
+                name: name.clone(),
+                link_target: target,
+                hard_link,
+                ..Default::default()
+            };
+            if is_link(&entry) {
+                if hard_link {
+                    std::fs::create_dir_all(dst.join("a")).unwrap();
+                    std::fs::write(dst.join(&entry.link_target), b"f").unwrap();
+                }
+                create(&dst, &dst.join(&name), &entry).unwrap();
+            }
+        }
+        assert_eq!(std::fs::read(dst.join("l")).unwrap(), b"f");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+// Search a folder by the names of its entries on the side owning it, so the peer does not walk the
+// tree over the network.
+//
+// The pattern is a glob of `*` and `?`, or a part of the name if it has neither, matched
+// case-insensitively. The tree is walked breadth first, the nearest entries are found first, and
+// the walk stops at the depth, time or result limit. Links are listed but never followed, they may
+// loop. The entries found are handed over in batches while the walk goes on.
+
+use super::{get_string, read_dir};
+use crate::message_proto::{FileEntry, FileType};
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+pub const DEFAULT_MAX_DEPTH: u32 = 32;
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_TIMEOUT: Duration = Duration::from_secs(600);
+const MAX_RESULTS: usize = 10_000;
+const BATCH_SIZE: usize = 100;
+const BATCH_INTERVAL: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    chars: Vec<char>,
+    wildcard: bool,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Self {
+        let chars: Vec<char> = pattern.trim().to_lowercase().chars().collect();
+        let wildcard = chars.iter().any(|c| *c == '*' || *c == '?');
+        Self { chars, wildcard }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        let name: Vec<char> = name.to_lowercase().chars().collect();
+        if self.wildcard {
+            glob_match(&self.chars, &name)
+        } else {
+            name.windows(self.chars.len().max(1))
+                .any(|w| w == self.chars.as_slice())
+        }
+    }
+}
+
+// backtracks to the last `*` only, linear enough for the names
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((sp, sn)) = star {
+            p = sp + 1;
+            n = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_depth: u32,
+    pub timeout: Duration,
+}
+
+impl Limits {
+    // 0 for the defaults
+    pub fn new(max_depth: u32, timeout_secs: u32) -> Self {
+        Self {
+            max_depth: if max_depth == 0 {
+                DEFAULT_MAX_DEPTH
+            } else {
+                max_depth
+            },
+            timeout: if timeout_secs == 0 {
+                DEFAULT_TIMEOUT
+            } else {
+                Duration::from_secs(timeout_secs as _).min(MAX_TIMEOUT)
+            },
+        }
+    }
+}
+
+/// Searches `root` and calls `on_found` with the batches of the entries found, named relative to
+/// `root`. Returns whether the tree was searched to the end, false if a limit was hit or it was
+/// cancelled. The folders unreadable are skipped.
+pub fn search(
+    root: &Path,
+    pattern: &Pattern,
+    include_hidden: bool,
+    limits: Limits,
+    cancelled: &AtomicBool,
+    mut on_found: impl FnMut(Vec<FileEntry>),
+) -> bool {
+    let deadline = Instant::now() + limits.timeout;
+    let mut queue: VecDeque<(PathBuf, u32)> = VecDeque::new();
+    queue.push_back((PathBuf::new(), 1));
+    let mut batch = Vec::new();
+    let mut last_sent = Instant::now();
+    let mut found = 0;
+    let mut complete = true;
+    'walk: while let Some((relative, depth)) = queue.pop_front() {
+        if cancelled.load(Ordering::Relaxed) || Instant::now() >= deadline {
+            complete = false;
+            break;
+        }
+        let dir = match read_dir(&root.join(&relative), include_hidden) {
+            Ok(dir) => dir,
+            Err(_) => continue,
+        };
+        for mut entry in dir.entries {
+            let name = relative.join(&entry.name);
+            if entry.entry_type.enum_value() == Ok(FileType::Dir) {
+                if depth < limits.max_depth {
+                    queue.push_back((name.clone(), depth + 1));
+                } else {
+                    complete = false;
+                }
+            }
+            if !pattern.matches(&entry.name) {
+                continue;
+            }
+            entry.name = get_string(&name);
+            batch.push(entry);
+            found += 1;
+            if found >= MAX_RESULTS {
+                complete = false;
+                break 'walk;
+            }
+        }
+        if batch.len() >= BATCH_SIZE || (!batch.is_empty() && last_sent.elapsed() >= BATCH_INTERVAL)
+        {
+            on_found(std::mem::take(&mut batch));
+            last_sent = Instant::now();
+        }
+    }
+    if !batch.is_empty() {
+        on_found(batch);
+    }
+    complete && queue.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern() {
+        assert!(Pattern::new("*.txt").matches("Notes.TXT"));
+        assert!(!Pattern::new("*.txt").matches("notes.txt.bak"));
+        assert!(Pattern::new("a?c*").matches("abcdef"));
+        assert!(Pattern::new("*a*b*").matches("xxaxxbxx"));
+        assert!(!Pattern::new("a?c").matches("ac"));
+        assert!(Pattern::new("port").matches("Report.doc"));
+        assert!(!Pattern::new("port").matches("pot"));
+        assert!(Pattern::new("*").matches(""));
+    }
+
+    #[test]
+    fn test_search() {
+        let root = std::env::temp_dir().join(format!("hbb_search_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("a").join("b")).unwrap();
+        std::fs::write(root.join("one.txt"), b"1").unwrap();
+        std::fs::write(root.join("a").join("two.txt"), b"2").unwrap();
+        std::fs::write(root.join("a").join("b").join("three.txt"), b"3").unwrap();
+        std::fs::write(root.join("a").join("skip.md"), b"").unwrap();
+        let cancelled = AtomicBool::new(false);
+        let search_names = |limits| {
+            let mut names = Vec::new();
+            let complete = search(
+                &root,
+                &Pattern::new("*.TXT"),
+                false,
+                limits,
+                &cancelled,
+                |batch| names.extend(batch.into_iter().map(|e| e.name)),
+            );
+            (names, complete)
+        };
+
+        let (names, complete) = search_names(Limits::new(0, 0));
+        assert!(complete);
+        assert_eq!(
+            names,
+            vec![
+                "one.txt".to_owned(),
+                get_string(&Path::new("a").join("two.txt")),
+                get_string(&Path::new("a").join("b").join("three.txt")),
+            ]
+        );
+        let (names, complete) = search_names(Limits::new(2, 0));
+        assert!(!complete);
+        assert_eq!(names.len(), 2);
+        cancelled.store(true, Ordering::Relaxed);
+        let (names, complete) = search_names(Limits::new(0, 0));
+        assert!(!complete);
+        assert!(names.is_empty());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+// Limit the rate of the file transfer sent, with token buckets.
+//
+// Each transfer has its own limit, and all the transfers sent by the process share the global one
+// of the file-transfer-rate-limit option. A block is read whole, so the bucket goes into debt by
+// the block sent and the next block waits until the debt is paid.
+//
+// While the video of a session is congested, the global rate is lowered to `CONGESTED_KBPS` for the
+// video to get the bandwidth back. The video is sent by the controlled side, so the sessions it
+// serves report the congestion.
+
+use crate::config::{keys, Config};
+use std::{
+    collections::HashSet,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+// the rate yielding to the congested video
+const CONGESTED_KBPS: u32 = 256;
+// the tokens saved while idle
+const MAX_BURST: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+pub struct TokenBucket {
+    // 0 for no limit
+    kbps: u32,
+    // bytes, negative in debt
+    tokens: f64,
+    last: Instant,
+}
+
+impl Default for TokenBucket {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl TokenBucket {
+    pub fn new(kbps: u32) -> Self {
+        Self {
+            kbps,
+            tokens: 0.,
+            last: Instant::now(),
+        }
+    }
+
+    #[inline]
+    pub fn kbps(&self) -> u32 {
+        self.kbps
+    }
+
+    pub fn set_kbps(&mut self, kbps: u32) {
+        self.kbps = kbps;
+    }
+
+    fn refill(&mut self, kbps: u32, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last);
+        self.last = now;
+        if kbps == 0 {
+            self.tokens = 0.;
+            return;
+        }
+        // bytes per second
+        let rate = kbps as f64 * 125.;
+        self.tokens =
+            (self.tokens + rate * elapsed.as_secs_f64()).min(rate * MAX_BURST.as_secs_f64());
+    }
+
+    fn ready(&mut self, kbps: u32, now: Instant) -> bool {
+        self.refill(kbps, now);
+        kbps == 0 || self.tokens >= 0.
+    }
+
+    fn consume(&mut self, bytes: usize) {
+        self.tokens -= bytes as f64;
+    }
+}
+
+#[derive(Default)]
+struct Global {
+    bucket: TokenBucket,
+    kbps: u32,
+    loaded: Option<Instant>,
+    congested: HashSet<i32>,
+}
+
+impl Global {
+    fn kbps(&mut self, now: Instant) -> u32 {
+        if self
+            .loaded
+            .map(|t| now.saturating_duration_since(t) >= RELOAD_INTERVAL)
+            .unwrap_or(true)
+        {
+            self.kbps = Config::get_option(keys::OPTION_FILE_TRANSFER_RATE_LIMIT)
+                .parse()
+                .unwrap_or(0);
+            self.loaded = Some(now);
+        }
+        if self.congested.is_empty() {
+            self.kbps
+        } else if self.kbps == 0 {
+            CONGESTED_KBPS
+        } else {
+            self.kbps.min(CONGESTED_KBPS)
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref GLOBAL: Mutex<Global> = Default::default();
+}
+
+/// Whether the next block of the transfer can be sent now.
+pub fn ready(bucket: &mut TokenBucket) -> bool {
+    let now = Instant::now();
+    if !bucket.ready(bucket.kbps, now) {
+        return false;
+    }
+    let mut global = GLOBAL.lock().unwrap();
+    let kbps = global.kbps(now);
+    global.bucket.ready(kbps, now)
+}
+
+/// Counts the bytes of the block sent by the transfer.
+pub fn consume(bucket: &mut TokenBucket, bytes: usize) {
+    bucket.consume(bytes);
+    GLOBAL.lock().unwrap().bucket.consume(bytes);
+}
+
+/// Notes whether the video of the session is congested, until the session closes.
+pub fn set_congested(session: i32, congested: bool) {
+    let mut global = GLOBAL.lock().unwrap();
+    if congested {
+        if global.congested.insert(session) {
+            log::info!("file transfer yields to the congested video of session {session}");
+        }
+    } else {
+        global.congested.remove(&session);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(800);
+        // 100 KB/s in blocks of 10 KB for 2 seconds
+        let mut sent = 0;
+        for ms in 0..2000 {
+            let now = start + Duration::from_millis(ms);
+            if bucket.ready(bucket.kbps, now) {
+                bucket.consume(10_000);
+                sent += 10_000;
+            }
+        }
+        assert!((190_000..=220_000).contains(&sent), "{}", sent);
+        // no debt is paid without a limit
+        let mut bucket = TokenBucket::new(0);
+        bucket.consume(1 << 20);
+        assert!(bucket.ready(0, start));
+    }
+
+    #[test]
+    fn test_burst() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(800);
+        assert!(bucket.ready(bucket.kbps, start));
+        // idle for a long time
+        let now = start + Duration::from_secs(60);
+        let mut sent = 0;
+        while bucket.ready(bucket.kbps, now) {
+            bucket.consume(1000);
+            sent += 1000;
+        }
+        assert_eq!(sent, 21_000);
+    }
+}
@@ -0,0 +1,355 @@
+// Move the files removed into the trash of the platform instead of deleting them, so a removal can
+// be undone.
+//
+// The unixes follow the freedesktop.org trash: the file is moved into `files` of the home trash, or
+// of `$topdir/.Trash-$uid` if it is on another file system, and its original path is recorded in
+// `info`. macOS moves it into `~/.Trash`. Windows has the shell move it into the Recycle Bin and
+// finds it there again by the `$I` file recording its original path, the file is not found if the
+// bin was too small for it and the shell deleted it.
+
+use super::get_recursive_files;
+use crate::{bail, ResultType};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct Trashed {
+    pub original: PathBuf,
+    // where it is in the trash
+    path: PathBuf,
+    // the record of the original path, removed when it is put back
+    info: Option<PathBuf>,
+}
+
+/// Moves the file or folder into the trash. Returns `None` if it was moved but can not be put back.
+pub fn trash(path: &Path) -> ResultType<Option<Trashed>> {
+    if path.symlink_metadata().is_err() {
+        bail!("{} does not exist", path.display());
+    }
+    platform::trash(path)
+}
+
+/// Moves the folder into the trash if no file is left in it, the files of a folder removed are
+/// trashed one by one before it. Otherwise removes the empty folders of it only.
+pub fn trash_dir_if_empty(path: &Path) -> ResultType<Option<Trashed>> {
+    if get_recursive_files(&super::get_string(path), true)?.is_empty() {
+        trash(path)
+    } else {
+        super::remove_all_empty_dir(&path.to_path_buf())?;
+        Ok(None)
+    }
+}
+
+/// Puts the file back where it was, unless something has been created there since.
+pub fn restore(trashed: &Trashed) -> ResultType<()> {
+    if trashed.original.symlink_metadata().is_ok() {
+        bail!("{} exists", trashed.original.display());
+    }
+    if let Some(parent) = trashed.original.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(&trashed.path, &trashed.original)?;
+    if let Some(info) = &trashed.info {
+        std::fs::remove_file(info).ok();
+    }
+    Ok(())
+}
+
+/// Puts back what the removal jobs of `ids` moved to the trash, the last trashed first, and forgets
+/// it. The first failure is returned once the rest is put back.
+pub fn restore_jobs(trashed: &mut Vec<(i32, Trashed)>, ids: &[i32]) -> ResultType<()> {
+    let mut res = Ok(());
+    let mut restored = false;
+    for i in (0..trashed.len()).rev() {
+        if !ids.contains(&trashed[i].0) {
+            continue;
+        }
+        restored = true;
+        if let Err(err) = restore(&trashed.remove(i).1) {
+            if res.is_ok() {
+                res = Err(err);
+            }
+        }
+    }
+    if !restored {
+        bail!("Nothing to undo");
+    }
+    res
+}
+
+// `name`, `name.2`, `name.3`... for the names taken in the trash
+#[cfg(all(unix, not(any(target_os = "android", target_os = "ios"))))]
+fn trash_name(name: &str, n: usize) -> String {
+    if n == 1 {
+        name.to_owned()
+    } else {
+        format!("{}.{}", name, n)
+    }
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "ios"))
+))]
+mod platform {
+    use super::*;
+    use std::{
+        io::{ErrorKind, Write},
+        os::unix::{ffi::OsStrExt, fs::DirBuilderExt, fs::MetadataExt},
+    };
+
+    pub fn trash(path: &Path) -> ResultType<Option<Trashed>> {
+        let home = match dirs_next::data_dir() {
+            Some(dir) => dir.join("Trash"),
+            None => bail!("No trash"),
+        };
+        std::fs::create_dir_all(&home)?;
+        let parent = match path.parent() {
+            Some(parent) => parent,
+            None => bail!("Can not trash {}", path.display()),
+        };
+        let dev = parent.metadata()?.dev();
+        if home.metadata()?.dev() == dev {
+            trash_into(path, &home)
+        } else {
+            let uid = unsafe { libc::getuid() };
+            let trash = topdir(parent, dev).join(format!(".Trash-{}", uid));
+            std::fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(&trash)?;
+            trash_into(path, &trash)
+        }
+    }
+
+    pub(super) fn trash_into(path: &Path, trash: &Path) -> ResultType<Option<Trashed>> {
+        let (files, infos) = (trash.join("files"), trash.join("info"));
+        std::fs::create_dir_all(&files)?;
+        std::fs::create_dir_all(&infos)?;
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => bail!("Can not trash {}", path.display()),
+        };
+        // the info file created first reserves the name
+        let mut n = 1;
+        let (name, info, mut file) = loop {
+            let candidate = trash_name(&name, n);
+            n += 1;
+            if files.join(&candidate).symlink_metadata().is_ok() {
+                continue;
+            }
+            let info = infos.join(format!("{}.trashinfo", candidate));
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&info)
+            {
+                Ok(file) => break (candidate, info, file),
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err.into()),
+            }
+        };
+        let trashed = files.join(name);
+        let res = write!(
+            file,
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            escape(path),
+            chrono::Local::now().format("%Y-%m-%dT%H:%M:%S")
+        )
+        .and_then(|_| std::fs::rename(path, &trashed));
+        if let Err(err) = res {
+            std::fs::remove_file(&info).ok();
+            return Err(err.into());
+        }
+        Ok(Some(Trashed {
+            original: path.to_path_buf(),
+            path: trashed,
+            info: Some(info),
+        }))
+    }
+
+    // the top of the mount point `dir` is on
+    fn topdir(dir: &Path, dev: u64) -> PathBuf {
+        let mut top = dir;
+        for ancestor in dir.ancestors().skip(1) {
+            match ancestor.metadata() {
+                Ok(meta) if meta.dev() == dev => top = ancestor,
+                _ => break,
+            }
+        }
+        top.to_path_buf()
+    }
+
+    fn escape(path: &Path) -> String {
+        let mut escaped = String::new();
+        for b in path.as_os_str().as_bytes() {
+            if b.is_ascii_alphanumeric() || b"/-_.~".contains(b) {
+                escaped.push(*b as char);
+            } else {
+                escaped.push_str(&format!("%{:02X}", b));
+            }
+        }
+        escaped
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+
+    pub fn trash(path: &Path) -> ResultType<Option<Trashed>> {
+        let trash = match dirs_next::home_dir() {
+            Some(dir) => dir.join(".Trash"),
+            None => bail!("No trash"),
+        };
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => bail!("Can not trash {}", path.display()),
+        };
+        let mut n = 1;
+        let trashed = loop {
+            let candidate = trash.join(trash_name(&name, n));
+            if candidate.symlink_metadata().is_err() {
+                break candidate;
+            }
+            n += 1;
+        };
+        std::fs::rename(path, &trashed)?;
+        Ok(Some(Trashed {
+            original: path.to_path_buf(),
+            path: trashed,
+            info: None,
+        }))
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use std::{
+        convert::TryInto,
+        os::windows::ffi::{OsStrExt, OsStringExt},
+    };
+    use winapi::um::shellapi::{
+        SHFileOperationW, FOF_ALLOWUNDO, FOF_NOCONFIRMATION, FOF_NOERRORUI, FOF_SILENT, FO_DELETE,
+        SHFILEOPSTRUCTW,
+    };
+
+    pub fn trash(path: &Path) -> ResultType<Option<Trashed>> {
+        let from: Vec<u16> = path.as_os_str().encode_wide().chain([0, 0]).collect();
+        let mut op: SHFILEOPSTRUCTW = unsafe { std::mem::zeroed() };
+        op.wFunc = FO_DELETE as _;
+        op.pFrom = from.as_ptr();
+        op.fFlags = (FOF_ALLOWUNDO | FOF_NOCONFIRMATION | FOF_NOERRORUI | FOF_SILENT) as _;
+        let res = unsafe { SHFileOperationW(&mut op) };
+        if res != 0 || op.fAnyOperationsAborted != 0 {
+            bail!(
+                "Failed to move {} to the Recycle Bin: {}",
+                path.display(),
+                res
+            );
+        }
+        Ok(find(path))
+    }
+
+    // the newest `$I` file of the path in the bins of the users on the drive, `$R` has the data
+    fn find(path: &Path) -> Option<Trashed> {
+        let bin = path.ancestors().last()?.join("$Recycle.Bin");
+        let original = path.to_string_lossy().to_lowercase();
+        let mut found: Option<(u64, PathBuf)> = None;
+        for user in std::fs::read_dir(bin).ok()?.flatten() {
+            let entries = match std::fs::read_dir(user.path()) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                if !entry.file_name().to_string_lossy().starts_with("$I") {
+                    continue;
+                }
+                let data = match std::fs::read(entry.path()) {
+                    Ok(data) => data,
+                    Err(_) => continue,
+                };
+                if let Some((deleted, name)) = parse_info(&data) {
+                    if name.to_lowercase() == original
+                        && found.as_ref().map(|f| deleted > f.0).unwrap_or(true)
+                    {
+                        found = Some((deleted, entry.path()));
+                    }
+                }
+            }
+        }
+        let info = found?.1;
+        let name = info.file_name()?.to_string_lossy().replacen("$I", "$R", 1);
+        Some(Trashed {
+            original: path.to_path_buf(),
+            path: info.with_file_name(name),
+            info: Some(info),
+        })
+    }
+
+    // the deletion time and the original path, a fixed buffer in version 1, counted in version 2
+    fn parse_info(data: &[u8]) -> Option<(u64, String)> {
+        let u64_at = |i: usize| Some(u64::from_le_bytes(data.get(i..i + 8)?.try_into().ok()?));
+        let deleted = u64_at(16)?;
+        let wide = match u64_at(0)? {
+            1 => data.get(24..)?,
+            2 => {
+                let len = u32::from_le_bytes(data.get(24..28)?.try_into().ok()?) as usize;
+                data.get(28..28 + len * 2)?
+            }
+            _ => return None,
+        };
+        let wide: Vec<u16> = wide
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|c| *c != 0)
+            .collect();
+        let name = std::ffi::OsString::from_wide(&wide);
+        Some((deleted, name.to_string_lossy().to_string()))
+    }
+}
+
+#[cfg(any(target_os = "android", target_os = "ios"))]
+mod platform {
+    use super::*;
+
+    pub fn trash(_path: &Path) -> ResultType<Option<Trashed>> {
+        bail!("No trash")
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::fs::get_string;
+
+    #[test]
+    fn test_trash() {
+        let root = std::env::temp_dir().join(format!("hbb_trash_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let (dir, trash) = (root.join("a b"), root.join("Trash"));
+        std::fs::create_dir_all(dir.join("empty")).unwrap();
+        std::fs::write(dir.join("f"), b"1").unwrap();
+        let first = platform::trash_into(&dir.join("f"), &trash)
+            .unwrap()
+            .unwrap();
+        std::fs::write(dir.join("f"), b"2").unwrap();
+        let second = platform::trash_into(&dir.join("f"), &trash)
+            .unwrap()
+            .unwrap();
+        assert!(!dir.join("f").exists());
+        assert!(trash.join("files").join("f.2").exists());
+        let info = std::fs::read_to_string(trash.join("info").join("f.trashinfo")).unwrap();
+        assert!(info.contains(&format!("Path={}/a%20b/f\n", get_string(&root))));
+
+        let mut trashed = vec![(1, first.clone()), (2, second)];
+        assert!(restore_jobs(&mut trashed, &[2]).is_ok());
+        assert!(restore_jobs(&mut trashed, &[2]).is_err());
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(std::fs::read(dir.join("f")).unwrap(), b"2");
+        // something is there again
+        assert!(restore(&first).is_err());
+        assert!(!trash.join("info").join("f.2.trashinfo").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,62 @@
+// Verify the files transferred with their sha256.
+//
+// The reader hashes the file as it reads it and sends the checksum in the block at the end of the
+// file, the writer hashes what it writes and compares. When both sides take part, which they tell
+// in the request of the job, the digest or the confirm, the writer replies to each file sent: it is
+// checked, or it is corrupted and sent again once with only the blocks changed, compared in place
+// with the corrupted copy. The reader waits for the reply to the last file before it finishes the
+// job. The files still corrupted are reported as the error of the job and kept as `.download`.
+
+use sodiumoxide::crypto::hash::sha256;
+
+#[derive(Default)]
+pub struct Hasher(sha256::State);
+
+impl std::fmt::Debug for Hasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Hasher")
+    }
+}
+
+impl Hasher {
+    #[inline]
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    #[inline]
+    pub fn finalize(self) -> Vec<u8> {
+        self.0.finalize().0.to_vec()
+    }
+}
+
+pub fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+/// The reply of the writer to the file sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    Checked(i32),
+    Refetch(i32),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hasher() {
+        let mut hasher = Hasher::default();
+        hasher.update(b"ab");
+        hasher.update(b"c");
+        assert_eq!(
+            hex(&hasher.finalize()),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex(&Hasher::default().finalize()),
+            hex(&sha256::hash(b"").0)
+        );
+    }
+}
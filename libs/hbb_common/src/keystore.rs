@@ -0,0 +1,130 @@
+// The secrets kept in the keystore of the platform instead of the config: the credential manager on
+// windows, the keychain on macos and the secret service on linux.
+//
+// The config keeps `MARKER` in place of a secret moved into the keystore, and the secret is
+// encrypted in the config as before wherever there is no keystore, like a service on linux without
+// a session bus, or on the mobiles.
+
+use std::{collections::HashMap, sync::Mutex};
+
+pub const MARKER: &str = "keystore";
+pub const PERMANENT_PASSWORD: &str = "permanent-password";
+
+lazy_static::lazy_static! {
+    // whether the keystore works, probed on first use
+    static ref AVAILABLE: Mutex<Option<bool>> = Default::default();
+    // the secrets known to be in the keystore, so that storing the config does not rewrite them
+    static ref STORED: Mutex<HashMap<String, String>> = Default::default();
+}
+
+pub fn available() -> bool {
+    let mut available = AVAILABLE.lock().unwrap();
+    if available.is_none() {
+        *available = Some(imp::probe());
+    }
+    available.unwrap_or(false)
+}
+
+/// The secret `name` in the keystore, None if there is none.
+pub fn get(name: &str) -> Option<String> {
+    if !available() {
+        return None;
+    }
+    let secret = imp::get(name)?;
+    STORED
+        .lock()
+        .unwrap()
+        .insert(name.to_owned(), secret.clone());
+    Some(secret)
+}
+
+/// Keeps `secret` as `name` in the keystore, or removes it if empty, false if there is no keystore
+/// or it fails.
+pub fn set(name: &str, secret: &str) -> bool {
+    if !available() {
+        return false;
+    }
+    let mut stored = STORED.lock().unwrap();
+    if stored.get(name).map(|s| s == secret).unwrap_or(false) {
+        return true;
+    }
+    if !imp::set(name, secret) {
+        return false;
+    }
+    stored.insert(name.to_owned(), secret.to_owned());
+    true
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+mod imp {
+    use crate::{config::APP_NAME, log};
+    use keyring::{Entry, Error};
+
+    fn entry(name: &str) -> Option<Entry> {
+        let service = APP_NAME.read().unwrap().clone();
+        match Entry::new(&service, name) {
+            Ok(entry) => Some(entry),
+            Err(err) => {
+                log::error!("Failed to open the keystore entry {}: {}", name, err);
+                None
+            }
+        }
+    }
+
+    pub fn probe() -> bool {
+        match entry("probe").map(|e| e.get_password()) {
+            Some(Ok(_)) | Some(Err(Error::NoEntry)) => true,
+            Some(Err(err)) => {
+                log::info!("No keystore: {}", err);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn get(name: &str) -> Option<String> {
+        match entry(name)?.get_password() {
+            Ok(secret) => Some(secret),
+            Err(Error::NoEntry) => None,
+            Err(err) => {
+                log::error!("Failed to read {} from the keystore: {}", name, err);
+                None
+            }
+        }
+    }
+
+    pub fn set(name: &str, secret: &str) -> bool {
+        let entry = match entry(name) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let res = if secret.is_empty() {
+            match entry.delete_credential() {
+                Err(Error::NoEntry) => Ok(()),
+                res => res,
+            }
+        } else {
+            entry.set_password(secret)
+        };
+        if let Err(err) = res {
+            log::error!("Failed to write {} to the keystore: {}", name, err);
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+mod imp {
+    pub fn probe() -> bool {
+        false
+    }
+
+    pub fn get(_name: &str) -> Option<String> {
+        None
+    }
+
+    pub fn set(_name: &str, _secret: &str) -> bool {
+        false
+    }
+}
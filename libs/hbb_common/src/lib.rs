@@ -17,16 +17,21 @@ use std::{
 pub use tokio;
 pub use tokio_util;
 pub mod proxy;
+pub mod quic;
 pub mod socket_client;
 pub mod tcp;
+pub mod tls_pin;
 pub mod udp;
+pub mod websocket;
 pub use env_logger;
 pub use log;
 pub mod bytes_codec;
 pub use anyhow::{self, bail};
 pub use futures_util;
 pub mod config;
+pub mod dlp;
 pub mod fs;
+pub mod keystore;
 pub use lazy_static;
 #[cfg(not(any(target_os = "android", target_os = "ios")))]
 pub use mac_address;
@@ -240,6 +245,13 @@ pub fn is_valid_custom_id(id: &str) -> bool {
         .is_match(id)
 }
 
+/// An alias of a device, like "buildserver-3", which starts with a letter not to be taken for an id.
+pub fn is_valid_alias(alias: &str) -> bool {
+    regex::Regex::new(r"^[a-z][a-z0-9_-]{2,31}$")
+        .unwrap()
+        .is_match(alias)
+}
+
 // Support 1.1.10-1, the number after - is a patch version.
 pub fn get_version_number(v: &str) -> i64 {
     let mut versions = v.split('-');
@@ -324,10 +336,12 @@ pub fn is_ipv6_str(id: &str) -> bool {
     if let Ok(reg) = regex::Regex::new(
         r"^((([a-fA-F0-9]{1,4}:{1,2})+[a-fA-F0-9]{1,4})|(\[([a-fA-F0-9]{1,4}:{1,2})+[a-fA-F0-9]{1,4}\]:\d+))$",
     ) {
-        reg.is_match(id)
-    } else {
-        false
+        if reg.is_match(id) {
+            return true;
+        }
     }
+    // the compressed forms the regex misses, e.g. "::1" or "[2001:db8::]:21118"
+    id.parse::<std::net::Ipv6Addr>().is_ok() || id.parse::<std::net::SocketAddrV6>().is_ok()
 }
 
 #[inline]
@@ -434,6 +448,10 @@ mod test {
         assert!(is_ipv6_str("[1:2::0]:1"));
         assert!(!is_ipv6_str("[1:2::0]:"));
         assert!(!is_ipv6_str("1:2::0]:1"));
+        assert!(is_ipv6_str("[2001:db8::1]:21118"));
+        assert!(is_ipv6_str("::1"));
+        assert!(is_ipv6_str("[2001:db8::]:21118"));
+        assert!(!is_ipv6_str("[2001:db8::]"));
     }
 
     #[test]
@@ -489,6 +507,17 @@ mod test {
         assert_eq!(AddrMangle::decode(&AddrMangle::encode(addr_v6)), addr_v6);
     }
 
+    #[test]
+    fn test_is_valid_alias() {
+        assert!(is_valid_alias("buildserver-3"));
+        assert!(is_valid_alias("nas_01"));
+        assert!(!is_valid_alias("ab"));
+        assert!(!is_valid_alias("3rd-floor"));
+        assert!(!is_valid_alias("Build"));
+        assert!(!is_valid_alias("build server"));
+        assert!(!is_valid_alias(&"a".repeat(33)));
+    }
+
     #[test]
     fn test_get_version_number() {
         assert_eq!(get_version_number("1.1.10"), 1001100);
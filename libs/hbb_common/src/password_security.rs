@@ -187,6 +187,54 @@ pub fn symmetric_crypt(data: &[u8], encrypt: bool) -> Result<Vec<u8>, ()> {
     }
 }
 
+// The secrets only ever checked on this side, like the unlock pin, are kept as their Argon2id hashes
+// in the PHC string format, which carries the parameters and the salt of each one along.
+const SECRET_HASH_PREFIX: &str = "$argon2id$";
+
+/// The Argon2id hash of `secret` with a random salt of its own, empty if it fails.
+pub fn hash_secret(secret: &str) -> String {
+    use argon2::{
+        password_hash::{PasswordHasher, SaltString},
+        Argon2,
+    };
+
+    let salt = match SaltString::encode_b64(&rand::random::<[u8; 16]>()) {
+        Ok(salt) => salt,
+        Err(err) => {
+            log::error!("Failed to generate the salt: {}", err);
+            return String::default();
+        }
+    };
+    match Argon2::default().hash_password(secret.as_bytes(), &salt) {
+        Ok(hash) => hash.to_string(),
+        Err(err) => {
+            log::error!("Failed to hash the secret: {}", err);
+            String::default()
+        }
+    }
+}
+
+/// Whether `secret` is the one `hash` of `hash_secret` is of.
+pub fn verify_secret(secret: &str, hash: &str) -> bool {
+    use argon2::{
+        password_hash::{PasswordHash, PasswordVerifier},
+        Argon2,
+    };
+
+    PasswordHash::new(hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(secret.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+#[inline]
+pub fn is_secret_hash(s: &str) -> bool {
+    s.starts_with(SECRET_HASH_PREFIX)
+}
+
 mod test {
 
     #[test]
@@ -292,4 +340,17 @@ mod test {
         test_speed(10 * 1024 * 1024, "10M");
         test_speed(100 * 1024 * 1024, "100M");
     }
+    #[test]
+    fn test_secret_hash() {
+        use super::*;
+
+        let hash = hash_secret("1234");
+        assert!(is_secret_hash(&hash));
+        assert!(verify_secret("1234", &hash));
+        assert!(!verify_secret("12345", &hash));
+        // salted
+        assert_ne!(hash, hash_secret("1234"));
+        assert!(!is_secret_hash("1234"));
+        assert!(!verify_secret("1234", "1234"));
+    }
 }
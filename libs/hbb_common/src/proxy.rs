@@ -23,6 +23,8 @@ use crate::{
     ResultType,
 };
 
+mod ntlm;
+
 #[derive(Debug, ThisError)]
 pub enum ProxyError {
     #[error("IO Error: {0}")]
@@ -45,6 +47,8 @@ pub enum ProxyError {
     HttpCode200(u16),
     #[error("The proxy address resolution failed: {0}")]
     AddressResolutionFailed(String),
+    #[error("NTLM authentication failed: {0}")]
+    NtlmError(String),
     #[cfg(any(target_os = "windows", target_os = "macos"))]
     #[error("The native tls error: {0}")]
     NativeTlsError(#[from] tokio_native_tls::native_tls::Error),
@@ -117,13 +121,6 @@ pub struct Auth {
 }
 
 impl Auth {
-    fn get_proxy_authorization(&self) -> String {
-        format!(
-            "Proxy-Authorization: Basic {}\r\n",
-            self.get_basic_authorization()
-        )
-    }
-
     pub fn get_basic_authorization(&self) -> String {
         let authorization = format!("{}:{}", &self.user_name, &self.password);
         general_purpose::STANDARD.encode(authorization.as_bytes())
@@ -374,6 +371,7 @@ impl Proxy {
         stream.set_nodelay(true).ok();
 
         let addr = stream.local_addr()?;
+        let socket = crate::tcp::raw_socket(&stream);
 
         return match self.intercept {
             ProxyScheme::Http { .. } => {
@@ -385,6 +383,9 @@ impl Proxy {
                     addr,
                     None,
                     0,
+                    None,
+                    socket,
+                    None,
                 ))
             }
             ProxyScheme::Https { .. } => {
@@ -396,6 +397,9 @@ impl Proxy {
                     addr,
                     None,
                     0,
+                    None,
+                    socket,
+                    None,
                 ))
             }
             ProxyScheme::Socks5 { .. } => {
@@ -423,6 +427,9 @@ impl Proxy {
                     addr,
                     None,
                     0,
+                    None,
+                    socket,
+                    None,
                 ))
             }
         };
@@ -479,28 +486,68 @@ impl Proxy {
     {
         let mut stream = BufStream::new(io);
         let (domain, port) = get_domain_and_port(target)?;
+        let auth = self.intercept.maybe_auth();
 
-        let request = self.make_request(&domain, port);
-        stream.write_all(request.as_bytes()).await?;
-        stream.flush().await?;
-        recv_and_check_response(&mut stream).await?;
+        // the users of a domain are only taken by ntlm, the others may be by the basic authentication
+        let ntlm_only = auth.map_or(false, |a| ntlm::is_domain_user(&a.user_name));
+        let authorization = if ntlm_only {
+            Some(ntlm_authorization(&ntlm::negotiate()))
+        } else {
+            auth.map(|a| format!("Basic {}", a.get_basic_authorization()))
+        };
+        let mut response =
+            send_request(&mut stream, &domain, port, authorization.as_deref()).await?;
+        if let Some(auth) = auth {
+            if response.code == 407 && !ntlm_only && response.offers_ntlm() && !response.closes()
+            {
+                info!("Basic authentication refused by the proxy, try NTLM");
+                let negotiate = ntlm_authorization(&ntlm::negotiate());
+                response = send_request(&mut stream, &domain, port, Some(&negotiate)).await?;
+            }
+            if response.code == 407 && !response.closes() {
+                if let Some(challenge) = response.ntlm_challenge() {
+                    let msg = ntlm::authenticate(&challenge, &auth.user_name, &auth.password)
+                        .map_err(ProxyError::NtlmError)?;
+                    let authenticate = ntlm_authorization(&msg);
+                    response =
+                        send_request(&mut stream, &domain, port, Some(&authenticate)).await?;
+                }
+            }
+        }
+        if response.code != 200 {
+            return Err(ProxyError::HttpCode200(response.code));
+        }
         Ok(stream)
     }
+}
 
-    fn make_request(&self, host: &str, port: u16) -> String {
-        let mut request = format!(
-            "CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n",
-            host = host,
-            port = port
-        );
-
-        if let Some(auth) = self.intercept.maybe_auth() {
-            request = format!("{}{}", request, auth.get_proxy_authorization());
-        }
+fn ntlm_authorization(msg: &[u8]) -> String {
+    format!("NTLM {}", general_purpose::STANDARD.encode(msg))
+}
 
-        request.push_str("\r\n");
-        request
-    }
+async fn send_request<IO>(
+    stream: &mut BufStream<IO>,
+    host: &str,
+    port: u16,
+    authorization: Option<&str>,
+) -> Result<ProxyResponse, ProxyError>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut request = format!(
+        "CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n",
+        host = host,
+        port = port
+    );
+    if let Some(authorization) = authorization {
+        request.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+        // the ntlm handshake is made on the same connection
+        request.push_str("Proxy-Connection: Keep-Alive\r\n");
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+    stream.flush().await?;
+    recv_response(stream).await
 }
 
 fn get_domain_and_port<'a, T: IntoTargetAddr<'a>>(target: T) -> Result<(String, u16), ProxyError> {
@@ -537,7 +584,39 @@ where
     }
 }
 
-async fn recv_and_check_response<IO>(stream: &mut BufStream<IO>) -> Result<(), ProxyError>
+struct ProxyResponse {
+    code: u16,
+    headers: Vec<(String, String)>,
+}
+
+impl ProxyResponse {
+    fn header_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.trim())
+    }
+
+    fn offers_ntlm(&self) -> bool {
+        self.header_values("Proxy-Authenticate")
+            .any(|v| v.eq_ignore_ascii_case("NTLM"))
+    }
+
+    fn ntlm_challenge(&self) -> Option<Vec<u8>> {
+        self.header_values("Proxy-Authenticate")
+            .filter_map(|v| v.strip_prefix("NTLM "))
+            .find_map(|v| general_purpose::STANDARD.decode(v.trim()).ok())
+    }
+
+    // the proxy closes the connection after the response
+    fn closes(&self) -> bool {
+        self.header_values("Proxy-Connection")
+            .chain(self.header_values("Connection"))
+            .any(|v| v.eq_ignore_ascii_case("close"))
+    }
+}
+
+async fn recv_response<IO>(stream: &mut BufStream<IO>) -> Result<ProxyResponse, ProxyError>
 where
     IO: AsyncRead + AsyncWrite + Unpin,
 {
@@ -548,14 +627,23 @@ where
     let response_bytes = response_string.into_bytes();
     response.parse(&response_bytes)?;
 
-    return match response.code {
-        Some(code) => {
-            if code == 200 {
-                Ok(())
-            } else {
-                Err(ProxyError::HttpCode200(code))
-            }
+    let code = response.code.ok_or(ProxyError::NoHttpCode)?;
+    let headers: Vec<(String, String)> = response
+        .headers
+        .iter()
+        .map(|h| (h.name.to_owned(), String::from_utf8_lossy(h.value).into_owned()))
+        .collect();
+    // the body of a refusal is skipped, the next request is sent on the same connection
+    if code != 200 {
+        let len = headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case("Content-Length"))
+            .and_then(|(_, v)| v.trim().parse::<u64>().ok())
+            .unwrap_or(0);
+        if len > 0 {
+            use tokio::io::AsyncReadExt;
+            tokio::io::copy(&mut (&mut *stream).take(len), &mut tokio::io::sink()).await?;
         }
-        None => Err(ProxyError::NoHttpCode),
-    };
+    }
+    Ok(ProxyResponse { code, headers })
 }
@@ -0,0 +1,377 @@
+// NTLMv2 authentication with the http proxies of the corporate networks, which do not take the
+// basic authentication.
+//
+// The handshake is made of three messages on the same connection: this side negotiates, the proxy
+// challenges, this side authenticates with the proof of the password computed on the challenge,
+// see https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-nlmp. The md4 and md5 it
+// needs are small enough to be implemented here.
+
+use std::{
+    convert::TryInto,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const SIGNATURE: &[u8] = b"NTLMSSP\0";
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+const REQUEST_TARGET: u32 = 0x0000_0004;
+const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+const NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+const NEGOTIATE_128: u32 = 0x2000_0000;
+const NEGOTIATE_56: u32 = 0x8000_0000;
+const FLAGS: u32 = NEGOTIATE_UNICODE
+    | REQUEST_TARGET
+    | NEGOTIATE_NTLM
+    | NEGOTIATE_ALWAYS_SIGN
+    | NEGOTIATE_EXTENDED_SESSIONSECURITY
+    | NEGOTIATE_128
+    | NEGOTIATE_56;
+// of the target info
+const AV_EOL: u16 = 0;
+const AV_TIMESTAMP: u16 = 7;
+// from 1601 to 1970, in seconds
+const FILETIME_UNIX_OFFSET: u64 = 11_644_473_600;
+
+/// Whether the user is given in the form of the domain logons, "DOMAIN\user".
+pub fn is_domain_user(user: &str) -> bool {
+    user.contains('\\')
+}
+
+/// The first message, sent before the proxy challenges.
+pub fn negotiate() -> Vec<u8> {
+    let mut msg = SIGNATURE.to_vec();
+    msg.extend_from_slice(&1u32.to_le_bytes());
+    msg.extend_from_slice(&FLAGS.to_le_bytes());
+    // no domain and workstation, the payload starts after them
+    for _ in 0..2 {
+        push_field(&mut msg, 0, 32);
+    }
+    msg
+}
+
+/// The last message, answering the `challenge` of the proxy. `user` may be "DOMAIN\user",
+/// "user@domain" or the user alone.
+pub fn authenticate(challenge: &[u8], user: &str, password: &str) -> Result<Vec<u8>, String> {
+    let (server_challenge, target_info) = parse_challenge(challenge)?;
+    let mut client_challenge = [0u8; 8];
+    rand::Rng::fill(&mut rand::thread_rng(), &mut client_challenge);
+    let time = timestamp(&target_info).unwrap_or_else(|| {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        (now.as_secs() + FILETIME_UNIX_OFFSET) * 10_000_000 + now.subsec_nanos() as u64 / 100
+    });
+    let (domain, user) = match user.split_once('\\') {
+        Some((domain, user)) => (domain, user),
+        None => ("", user),
+    };
+    let (lm, nt) = responses(
+        user,
+        domain,
+        password,
+        &server_challenge,
+        &client_challenge,
+        time,
+        &target_info,
+    );
+    let fields = [
+        lm,
+        nt,
+        utf16(domain),
+        utf16(user),
+        Vec::new(), // workstation
+        Vec::new(), // session key
+    ];
+    let mut msg = SIGNATURE.to_vec();
+    msg.extend_from_slice(&3u32.to_le_bytes());
+    let mut offset = 64;
+    for field in fields.iter() {
+        push_field(&mut msg, field.len(), offset);
+        offset += field.len();
+    }
+    msg.extend_from_slice(&FLAGS.to_le_bytes());
+    for field in fields.iter() {
+        msg.extend_from_slice(field);
+    }
+    Ok(msg)
+}
+
+fn push_field(msg: &mut Vec<u8>, len: usize, offset: usize) {
+    msg.extend_from_slice(&(len as u16).to_le_bytes());
+    msg.extend_from_slice(&(len as u16).to_le_bytes());
+    msg.extend_from_slice(&(offset as u32).to_le_bytes());
+}
+
+// the server challenge and the target info
+fn parse_challenge(msg: &[u8]) -> Result<([u8; 8], Vec<u8>), String> {
+    if msg.len() < 32 || &msg[..8] != SIGNATURE || msg[8..12] != 2u32.to_le_bytes() {
+        return Err("invalid challenge".to_owned());
+    }
+    let server_challenge = msg[24..32].try_into().unwrap_or_default();
+    let mut target_info = Vec::new();
+    if msg.len() >= 48 {
+        let len = u16::from_le_bytes([msg[40], msg[41]]) as usize;
+        let offset = u32::from_le_bytes([msg[44], msg[45], msg[46], msg[47]]) as usize;
+        target_info = msg
+            .get(offset..offset + len)
+            .ok_or_else(|| "invalid target info".to_owned())?
+            .to_vec();
+    }
+    Ok((server_challenge, target_info))
+}
+
+// the time of the server, which is to be used if given
+fn timestamp(target_info: &[u8]) -> Option<u64> {
+    let mut pos = 0;
+    while pos + 4 <= target_info.len() {
+        let id = u16::from_le_bytes([target_info[pos], target_info[pos + 1]]);
+        let len = u16::from_le_bytes([target_info[pos + 2], target_info[pos + 3]]) as usize;
+        let value = target_info.get(pos + 4..pos + 4 + len)?;
+        match id {
+            AV_EOL => break,
+            AV_TIMESTAMP if len == 8 => return Some(u64::from_le_bytes(value.try_into().ok()?)),
+            _ => {}
+        }
+        pos += 4 + len;
+    }
+    None
+}
+
+// the lm and nt responses of ntlmv2
+fn responses(
+    user: &str,
+    domain: &str,
+    password: &str,
+    server_challenge: &[u8; 8],
+    client_challenge: &[u8; 8],
+    time: u64,
+    target_info: &[u8],
+) -> (Vec<u8>, Vec<u8>) {
+    let nt_hash = md4(&utf16(password));
+    let mut identity = utf16(&user.to_uppercase());
+    identity.extend(utf16(domain));
+    let key = hmac_md5(&nt_hash, &identity);
+
+    let mut lm = hmac_md5(&key, &[&server_challenge[..], client_challenge].concat()).to_vec();
+    lm.extend_from_slice(client_challenge);
+
+    let mut blob = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    blob.extend_from_slice(&time.to_le_bytes());
+    blob.extend_from_slice(client_challenge);
+    blob.extend_from_slice(&[0; 4]);
+    blob.extend_from_slice(target_info);
+    blob.extend_from_slice(&[0; 4]);
+    let mut nt = hmac_md5(&key, &[&server_challenge[..], &blob].concat()).to_vec();
+    nt.extend(blob);
+    (lm, nt)
+}
+
+fn utf16(s: &str) -> Vec<u8> {
+    s.encode_utf16().flat_map(|c| c.to_le_bytes()).collect()
+}
+
+// the padding of md4 and md5, the length in bits at the end
+fn pad(data: &[u8]) -> Vec<u8> {
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+    msg
+}
+
+fn words(block: &[u8]) -> [u32; 16] {
+    let mut x = [0u32; 16];
+    for (i, w) in x.iter_mut().enumerate() {
+        *w = u32::from_le_bytes(block[i * 4..i * 4 + 4].try_into().unwrap_or_default());
+    }
+    x
+}
+
+fn digest(state: [u32; 4]) -> [u8; 16] {
+    let mut out = [0u8; 16];
+    for (i, s) in state.iter().enumerate() {
+        out[i * 4..i * 4 + 4].copy_from_slice(&s.to_le_bytes());
+    }
+    out
+}
+
+const INIT: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+
+// https://www.rfc-editor.org/rfc/rfc1320
+fn md4(data: &[u8]) -> [u8; 16] {
+    let f = |x: u32, y: u32, z: u32| (x & y) | (!x & z);
+    let g = |x: u32, y: u32, z: u32| (x & y) | (x & z) | (y & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+    let mut state = INIT;
+    for block in pad(data).chunks(64) {
+        let x = words(block);
+        let [mut a, mut b, mut c, mut d] = state;
+        let step = |a: u32, f: u32, x: u32, k: u32, s: u32| {
+            a.wrapping_add(f)
+                .wrapping_add(x)
+                .wrapping_add(k)
+                .rotate_left(s)
+        };
+        for &i in &[0, 4, 8, 12] {
+            a = step(a, f(b, c, d), x[i], 0, 3);
+            d = step(d, f(a, b, c), x[i + 1], 0, 7);
+            c = step(c, f(d, a, b), x[i + 2], 0, 11);
+            b = step(b, f(c, d, a), x[i + 3], 0, 19);
+        }
+        for &i in &[0, 1, 2, 3] {
+            a = step(a, g(b, c, d), x[i], 0x5a827999, 3);
+            d = step(d, g(a, b, c), x[i + 4], 0x5a827999, 5);
+            c = step(c, g(d, a, b), x[i + 8], 0x5a827999, 9);
+            b = step(b, g(c, d, a), x[i + 12], 0x5a827999, 13);
+        }
+        for &i in &[0, 2, 1, 3] {
+            a = step(a, h(b, c, d), x[i], 0x6ed9eba1, 3);
+            d = step(d, h(a, b, c), x[i + 8], 0x6ed9eba1, 9);
+            c = step(c, h(d, a, b), x[i + 4], 0x6ed9eba1, 11);
+            b = step(b, h(c, d, a), x[i + 12], 0x6ed9eba1, 15);
+        }
+        state = [
+            state[0].wrapping_add(a),
+            state[1].wrapping_add(b),
+            state[2].wrapping_add(c),
+            state[3].wrapping_add(d),
+        ];
+    }
+    digest(state)
+}
+
+// https://www.rfc-editor.org/rfc/rfc1321
+const MD5_SHIFTS: [[u32; 4]; 4] = [
+    [7, 12, 17, 22],
+    [5, 9, 14, 20],
+    [4, 11, 16, 23],
+    [6, 10, 15, 21],
+];
+const MD5_K: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+fn md5(data: &[u8]) -> [u8; 16] {
+    let mut state = INIT;
+    for block in pad(data).chunks(64) {
+        let x = words(block);
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let f = f.wrapping_add(a).wrapping_add(MD5_K[i]).wrapping_add(x[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(f.rotate_left(MD5_SHIFTS[i / 16][i % 4]));
+        }
+        state = [
+            state[0].wrapping_add(a),
+            state[1].wrapping_add(b),
+            state[2].wrapping_add(c),
+            state[3].wrapping_add(d),
+        ];
+    }
+    digest(state)
+}
+
+fn hmac_md5(key: &[u8], data: &[u8]) -> [u8; 16] {
+    let mut block = [0u8; 64];
+    if key.len() > 64 {
+        block[..16].copy_from_slice(&md5(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner: Vec<u8> = block
+        .iter()
+        .map(|b| b ^ 0x36)
+        .chain(data.iter().cloned())
+        .collect();
+    let outer: Vec<u8> = block
+        .iter()
+        .map(|b| b ^ 0x5c)
+        .chain(md5(&inner).iter().cloned())
+        .collect();
+    md5(&outer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_hashes() {
+        assert_eq!(hex(&md4(b"")), "31d6cfe0d16ae931b73c59d7e0c089c0");
+        assert_eq!(hex(&md4(b"abc")), "a448017aaf21d8525fc10ae87aa6729d");
+        assert_eq!(hex(&md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(hex(&md5(b"abc")), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(hex(&md5(&[b'a'; 100])), "36a92cc94a9e0fa21f625f8bfb007adf");
+        assert_eq!(
+            hex(&hmac_md5(
+                b"key",
+                b"The quick brown fox jumps over the lazy dog"
+            )),
+            "80070713463e7749b90c2dc24911e275"
+        );
+    }
+
+    // the example of ms-nlmp 4.2.4
+    #[test]
+    fn test_ntlmv2() {
+        assert_eq!(
+            hex(&md4(&utf16("Password"))),
+            "a4f49c406510bdcab6824ee7c30fd852"
+        );
+        let mut challenge = SIGNATURE.to_vec();
+        challenge.extend_from_slice(&2u32.to_le_bytes());
+        challenge.extend_from_slice(&[0; 12]);
+        challenge.extend_from_slice(&[0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]);
+        challenge.extend_from_slice(&[0; 8]);
+        let mut target_info = vec![0x02, 0, 12, 0];
+        target_info.extend(utf16("Domain"));
+        target_info.extend_from_slice(&[0x01, 0, 12, 0]);
+        target_info.extend(utf16("Server"));
+        target_info.extend_from_slice(&[0; 4]);
+        push_field(&mut challenge, target_info.len(), 48);
+        challenge.extend_from_slice(&target_info);
+
+        let (server_challenge, info) = parse_challenge(&challenge).unwrap();
+        assert_eq!(info, target_info);
+        assert_eq!(timestamp(&info), None);
+        let (lm, nt) = responses(
+            "User",
+            "Domain",
+            "Password",
+            &server_challenge,
+            &[0xaa; 8],
+            0,
+            &info,
+        );
+        assert_eq!(hex(&lm), "86c35097ac9cec102554764a57cccc19aaaaaaaaaaaaaaaa");
+        assert_eq!(hex(&nt[..16]), "68cd0ab851e51c96aabc927bebef6a1c");
+
+        let msg = authenticate(&challenge, "Domain\\User", "Password").unwrap();
+        assert_eq!(&msg[..8], SIGNATURE);
+        // the user follows the responses and the domain
+        let user_offset = u32::from_le_bytes(msg[40..44].try_into().unwrap()) as usize;
+        assert_eq!(&msg[user_offset..user_offset + 8], &utf16("User")[..]);
+        assert!(parse_challenge(&negotiate()).is_err());
+    }
+}
@@ -0,0 +1,486 @@
+//! QUIC transport of the direct connections.
+//!
+//! The peer listens on the udp port of the same number as the direct access one. The first
+//! bidirectional stream of a connection carries the login and control messages like a tcp
+//! stream does, the video, audio, file and clipboard messages go on their own unidirectional
+//! streams, so that a lost packet of one does not hold the others back. The streams are
+//! encrypted with the session key as the tcp ones are, each channel with nonces of its own.
+//!
+//! The tls certificates are self-signed ones made on each start of the listener, the peer is
+//! authenticated by the signed key exchange on the first stream, as on tcp.
+
+use crate::{
+    bail,
+    bytes_codec::BytesCodec,
+    config::{self, keys, Config},
+    message_proto::{message, Message},
+    tcp::{DynTcpStream, Encrypt, FramedStream},
+    ResultType,
+};
+use bytes::BytesMut;
+use futures::{SinkExt, StreamExt};
+use quinn::{
+    congestion::BbrConfig,
+    crypto::rustls::{QuicClientConfig, QuicServerConfig},
+    ClientConfig, Connection, Endpoint, EndpointConfig, Incoming, RecvStream, SendStream,
+    ServerConfig, TokioRuntime, TransportConfig, VarInt,
+};
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{ring, CryptoProvider},
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime},
+    DigitallySignedStruct, SignatureScheme,
+};
+use socket2::{Domain, Socket, Type};
+use sodiumoxide::crypto::secretbox::Key;
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    io,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::mpsc,
+};
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+const ALPN: &[u8] = b"rustdesk";
+// sent first on the first stream, which the peer does not see until something is written on it
+const VERSION: u8 = 1;
+const HANDSHAKE_TIMEOUT: u64 = 5_000;
+const IDLE_TIMEOUT: u32 = 30_000;
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(5);
+// the messages received on the other streams and not read yet, before the peer is held back
+const CHANNEL_QUEUE: usize = 64;
+
+/// The stream a message is sent on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    Control = 0,
+    Video = 1,
+    Audio = 2,
+    File = 3,
+    Clipboard = 4,
+}
+
+impl Channel {
+    pub fn of(msg: &Message) -> Self {
+        match &msg.union {
+            Some(message::Union::VideoFrame(_)) => Self::Video,
+            Some(message::Union::AudioFrame(_)) => Self::Audio,
+            Some(message::Union::FileAction(_)) | Some(message::Union::FileResponse(_)) => {
+                Self::File
+            }
+            Some(message::Union::Clipboard(_))
+            | Some(message::Union::MultiClipboards(_))
+            | Some(message::Union::Cliprdr(_)) => Self::Clipboard,
+            _ => Self::Control,
+        }
+    }
+
+    fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(Self::Control),
+            1 => Some(Self::Video),
+            2 => Some(Self::Audio),
+            3 => Some(Self::File),
+            4 => Some(Self::Clipboard),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the direct connections are made and accepted over QUIC as well.
+pub fn enabled() -> bool {
+    config::option2bool(
+        keys::OPTION_ALLOW_QUIC,
+        &Config::get_option(keys::OPTION_ALLOW_QUIC),
+    )
+}
+
+/// The first stream of a connection, read and written as a tcp stream is.
+struct QuicStream {
+    send: SendStream,
+    recv: RecvStream,
+    // the client endpoint is of this connection only, and must live as long as it
+    _endpoint: Option<Endpoint>,
+}
+
+impl AsyncRead for QuicStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        AsyncRead::poll_read(Pin::new(&mut self.recv), cx, buf)
+    }
+}
+
+impl AsyncWrite for QuicStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        AsyncWrite::poll_write(Pin::new(&mut self.send), cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        AsyncWrite::poll_flush(Pin::new(&mut self.send), cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        AsyncWrite::poll_shutdown(Pin::new(&mut self.send), cx)
+    }
+}
+
+/// The streams of a connection besides the first one, each opened on the first message sent
+/// on its channel, and those the peer opened.
+pub struct Channels {
+    conn: Connection,
+    send: HashMap<Channel, FramedWrite<SendStream, BytesCodec>>,
+    key: Option<Key>,
+    enc: HashMap<Channel, Encrypt>,
+    dec: HashMap<Channel, Encrypt>,
+    rx: mpsc::Receiver<(Channel, BytesMut)>,
+}
+
+impl Channels {
+    fn new(conn: Connection) -> Self {
+        let (tx, rx) = mpsc::channel(CHANNEL_QUEUE);
+        let accepting = conn.clone();
+        tokio::spawn(async move {
+            while let Ok(recv) = accepting.accept_uni().await {
+                tokio::spawn(read_channel(recv, tx.clone()));
+            }
+        });
+        Self {
+            conn,
+            send: Default::default(),
+            key: None,
+            enc: Default::default(),
+            dec: Default::default(),
+            rx,
+        }
+    }
+
+    pub(crate) fn set_key(&mut self, key: Key) {
+        self.key = Some(key);
+        self.enc.clear();
+        self.dec.clear();
+    }
+
+    pub(crate) async fn send(&mut self, channel: Channel, msg: Vec<u8>) -> ResultType<()> {
+        let Some(key) = self.key.as_ref() else {
+            bail!("QUIC channel used before the session key is set");
+        };
+        let msg = self
+            .enc
+            .entry(channel)
+            .or_insert_with(|| Encrypt::for_channel(key.clone(), channel as _))
+            .enc(&msg);
+        if !self.send.contains_key(&channel) {
+            let mut stream = self.conn.open_uni().await?;
+            stream.write_all(&[channel as u8]).await?;
+            self.send
+                .insert(channel, FramedWrite::new(stream, BytesCodec::new()));
+        }
+        if let Some(stream) = self.send.get_mut(&channel) {
+            stream.send(bytes::Bytes::from(msg)).await?;
+        }
+        Ok(())
+    }
+
+    /// The next message received on the streams the peer opened, decrypted.
+    pub(crate) async fn recv(&mut self) -> Option<Result<BytesMut, io::Error>> {
+        let (channel, mut bytes) = self.rx.recv().await?;
+        if let Some(key) = self.key.as_ref() {
+            let dec = self
+                .dec
+                .entry(channel)
+                .or_insert_with(|| Encrypt::for_channel(key.clone(), channel as _));
+            if let Err(err) = dec.dec(&mut bytes) {
+                return Some(Err(err));
+            }
+        }
+        Some(Ok(bytes))
+    }
+}
+
+impl Drop for Channels {
+    fn drop(&mut self) {
+        // the task accepting the streams holds the connection too
+        self.conn.close(VarInt::from_u32(0), b"");
+    }
+}
+
+async fn read_channel(mut recv: RecvStream, tx: mpsc::Sender<(Channel, BytesMut)>) {
+    let mut channel = [0u8; 1];
+    if recv.read_exact(&mut channel).await.is_err() {
+        return;
+    }
+    let Some(channel) = Channel::from_u8(channel[0]) else {
+        log::warn!("Unknown QUIC channel {}", channel[0]);
+        return;
+    };
+    let mut framed = FramedRead::new(recv, BytesCodec::new());
+    while let Some(Ok(bytes)) = framed.next().await {
+        if tx.send((channel, bytes)).await.is_err() {
+            break;
+        }
+    }
+}
+
+fn stream(
+    conn: Connection,
+    send: SendStream,
+    recv: RecvStream,
+    endpoint: Option<Endpoint>,
+    local_addr: SocketAddr,
+) -> FramedStream {
+    let quic = QuicStream {
+        send,
+        recv,
+        _endpoint: endpoint,
+    };
+    FramedStream(
+        tokio_util::codec::Framed::new(DynTcpStream(Box::new(quic)), BytesCodec::new()),
+        local_addr,
+        None,
+        0,
+        None,
+        None,
+        Some(Box::new(Channels::new(conn))),
+    )
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn transport() -> Arc<TransportConfig> {
+    let mut transport = TransportConfig::default();
+    transport
+        .max_idle_timeout(Some(VarInt::from_u32(IDLE_TIMEOUT).into()))
+        .keep_alive_interval(Some(KEEP_ALIVE_INTERVAL))
+        // bbr does not take the random losses of wi-fi for congestion
+        .congestion_controller_factory(Arc::new(BbrConfig::default()));
+    Arc::new(transport)
+}
+
+/// Accepts any certificate, the handshake signatures are still checked.
+#[derive(Debug)]
+struct AnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+fn client_config() -> ResultType<ClientConfig> {
+    let provider = provider();
+    let mut crypto = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AnyCertificate(provider)))
+        .with_no_client_auth();
+    crypto.alpn_protocols = vec![ALPN.to_vec()];
+    crypto.enable_early_data = true;
+    let mut config = ClientConfig::new(Arc::new(QuicClientConfig::try_from(crypto)?));
+    config.transport_config(transport());
+    Ok(config)
+}
+
+fn server_config() -> ResultType<ServerConfig> {
+    let cert = rcgen::generate_simple_self_signed(vec!["rustdesk".to_owned()])?;
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der()));
+    let mut crypto = rustls::ServerConfig::builder_with_provider(provider())
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .with_no_client_auth()
+        .with_single_cert(vec![cert.cert.der().clone()], key)?;
+    crypto.alpn_protocols = vec![ALPN.to_vec()];
+    // quinn takes early data only with this
+    crypto.max_early_data_size = u32::MAX;
+    let mut config = ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(crypto)?));
+    config.transport_config(transport());
+    Ok(config)
+}
+
+lazy_static::lazy_static! {
+    // shared by the connections for the sessions they resume, the endpoints are not: each
+    // runs in the runtime it was made in, which may end with the session
+    static ref CLIENT_CONFIG: Option<ClientConfig> = match client_config() {
+        Ok(config) => Some(config),
+        Err(err) => {
+            log::error!("Failed to make the QUIC client config: {}", err);
+            None
+        }
+    };
+}
+
+/// Connects to the QUIC listener of a peer. A session resumed with the peer sends the first
+/// stream with the handshake, without waiting for it to complete.
+pub async fn connect(addr: SocketAddr, ms_timeout: u64) -> ResultType<FramedStream> {
+    crate::timeout(ms_timeout, connect_(addr)).await?
+}
+
+async fn connect_(addr: SocketAddr) -> ResultType<FramedStream> {
+    let Some(config) = CLIENT_CONFIG.clone() else {
+        bail!("QUIC is not available");
+    };
+    let endpoint = Endpoint::client(Config::get_any_listen_addr(addr.is_ipv4()))?;
+    let local_addr = endpoint.local_addr()?;
+    // the sessions are resumed by the address of the peer, not the name in its certificate
+    let connecting = endpoint.connect_with(config, addr, &addr.ip().to_string())?;
+    let (conn, (send, recv)) = match connecting.into_0rtt() {
+        Ok((conn, accepted)) => {
+            let first = open(&conn).await;
+            if accepted.await {
+                (conn.clone(), first?)
+            } else {
+                // the streams sent in 0-rtt are rejected with it
+                let first = open(&conn).await?;
+                (conn, first)
+            }
+        }
+        Err(connecting) => {
+            let conn = connecting.await?;
+            let first = open(&conn).await?;
+            (conn, first)
+        }
+    };
+    log::info!("QUIC connected to {}", addr);
+    Ok(stream(conn, send, recv, Some(endpoint), local_addr))
+}
+
+async fn open(conn: &Connection) -> ResultType<(SendStream, RecvStream)> {
+    let (mut send, recv) = conn.open_bi().await?;
+    send.write_all(&[VERSION]).await?;
+    Ok((send, recv))
+}
+
+/// Listens on the udp `port` of all the addresses, v4 and v6.
+pub fn listen_any(port: u16) -> ResultType<Endpoint> {
+    let socket = Socket::new(Domain::ipv6(), Type::dgram(), None)
+        .and_then(|socket| {
+            socket.set_only_v6(false).ok();
+            socket.bind(&SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port).into())?;
+            Ok(socket)
+        })
+        .or_else(|_| {
+            let socket = Socket::new(Domain::ipv4(), Type::dgram(), None)?;
+            socket.bind(&Config::get_any_listen_addr(true).into())?;
+            Ok::<_, io::Error>(socket)
+        })?;
+    Ok(Endpoint::new(
+        EndpointConfig::default(),
+        Some(server_config()?),
+        socket.into_udp_socket(),
+        Arc::new(TokioRuntime),
+    )?)
+}
+
+/// Completes an incoming connection, with the first stream the peer opened on it.
+pub async fn accept(incoming: Incoming) -> ResultType<(FramedStream, SocketAddr)> {
+    let addr = incoming.remote_address();
+    let local_addr = incoming
+        .local_ip()
+        .map(|ip| SocketAddr::new(ip, 0))
+        .unwrap_or_else(|| Config::get_any_listen_addr(addr.is_ipv4()));
+    // the early data of a resumed session is read as soon as it comes
+    let conn = match incoming.accept()?.into_0rtt() {
+        Ok((conn, _)) => conn,
+        Err(connecting) => connecting.await?,
+    };
+    let (send, mut recv) = crate::timeout(HANDSHAKE_TIMEOUT, conn.accept_bi()).await??;
+    let mut version = [0u8; 1];
+    crate::timeout(HANDSHAKE_TIMEOUT, recv.read_exact(&mut version)).await??;
+    if version[0] != VERSION {
+        bail!("Unsupported QUIC version {} from {}", version[0], addr);
+    }
+    Ok((stream(conn, send, recv, None, local_addr), addr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_proto::VideoFrame;
+    use protobuf::Message as _;
+    use sodiumoxide::crypto::secretbox;
+
+    #[tokio::test]
+    async fn test_channels() {
+        let endpoint = listen_any(0).unwrap();
+        let port = endpoint.local_addr().unwrap().port();
+        let key = secretbox::gen_key();
+        let server_key = key.clone();
+        let server = tokio::spawn(async move {
+            let incoming = endpoint.accept().await.unwrap();
+            let (mut stream, _) = accept(incoming).await.unwrap();
+            stream.send_raw(b"plain".to_vec()).await.unwrap();
+            stream.set_key(server_key);
+            let mut msg = Message::new();
+            msg.set_video_frame(VideoFrame {
+                display: 1,
+                ..Default::default()
+            });
+            stream.send_on(Channel::of(&msg), &msg).await.unwrap();
+            stream.next().await.unwrap().unwrap()
+        });
+        let addr = SocketAddr::new(IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), port);
+        let mut stream = connect(addr, 3_000).await.unwrap();
+        assert!(stream.is_quic());
+        assert_eq!(&stream.next().await.unwrap().unwrap()[..], b"plain");
+        stream.set_key(key);
+        let bytes = stream.next().await.unwrap().unwrap();
+        let msg = Message::parse_from_bytes(&bytes).unwrap();
+        assert_eq!(msg.video_frame().display, 1);
+        stream.send_raw(b"done".to_vec()).await.unwrap();
+        assert_eq!(&server.await.unwrap()[..], b"done");
+    }
+}
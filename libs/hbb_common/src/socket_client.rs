@@ -1,11 +1,20 @@
 use crate::{
-    config::{Config, NetworkType},
-    tcp::FramedStream,
+    bail,
+    config::{keys, Config, NetworkType},
+    quic,
+    tcp::{self, FramedStream},
     udp::FramedSocket,
-    ResultType,
+    websocket, ResultType,
 };
 use anyhow::Context;
-use std::net::SocketAddr;
+use futures::FutureExt;
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 use tokio::net::ToSocketAddrs;
 use tokio_socks::{IntoTargetAddr, TargetAddr};
 
@@ -128,6 +137,316 @@ pub async fn connect_tcp_local<
     FramedStream::new(target, local, ms_timeout).await
 }
 
+/// Connects to the direct access port of a peer, over QUIC ahead of tcp when it is allowed.
+/// Tcp is tried a little later in case the peer does not listen for QUIC, or udp is blocked.
+pub async fn connect_direct(target: String, ms_timeout: u64) -> ResultType<FramedStream> {
+    if quic::enabled() && Config::get_socks().is_none() {
+        if let Some(addr) = tokio::net::lookup_host(&target)
+            .await
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+        {
+            let attempts = vec![
+                quic::connect(addr, ms_timeout).boxed(),
+                connect_tcp(target, ms_timeout).boxed(),
+            ];
+            return race(attempts).await;
+        }
+    }
+    connect_tcp(target, ms_timeout).await
+}
+
+// the servers reached through their tunnel lately, by host, are tried with it first for a while
+const TUNNEL_TTL: Duration = Duration::from_secs(600);
+
+lazy_static::lazy_static! {
+    static ref TUNNELED: Mutex<HashMap<String, Instant>> = Default::default();
+}
+
+/// Connects to the rendezvous or relay server `addr`, through the tls tunnel of the server at
+/// `path` if its port is unreachable, or as configured by `OPTION_TLS_TUNNEL`.
+pub async fn connect_server(addr: &str, path: &str, ms_timeout: u64) -> ResultType<FramedStream> {
+    let host = host_of(addr).to_owned();
+    let option = Config::get_option(keys::OPTION_TLS_TUNNEL);
+    // a proxy is reachable by definition
+    if option == "N" || Config::get_socks().is_some() {
+        return connect_tcp(addr, ms_timeout).await;
+    }
+    if option == "Y" {
+        return websocket::connect(&host, path, ms_timeout).await;
+    }
+    if is_tunneled(&host) {
+        match websocket::connect(&host, path, ms_timeout).await {
+            Ok(stream) => return Ok(stream),
+            Err(err) => log::debug!("Failed to connect to the tunnel of {}: {}", host, err),
+        }
+    }
+    match connect_tcp(addr, ms_timeout).await {
+        Ok(stream) => {
+            TUNNELED.lock().unwrap().remove(&host);
+            Ok(stream)
+        }
+        Err(err) => match websocket::connect(&host, path, ms_timeout).await {
+            Ok(stream) => {
+                log::info!("{} is unreachable, connected through its tls tunnel", addr);
+                set_tunneled(&host);
+                Ok(stream)
+            }
+            Err(tunnel_err) => {
+                log::debug!(
+                    "Failed to connect to the tunnel of {}: {}",
+                    host,
+                    tunnel_err
+                );
+                Err(err)
+            }
+        },
+    }
+}
+
+/// Whether the server `addr` was last reached through its tunnel, so that nothing but the server
+/// is reachable.
+pub fn is_tunneled(addr: &str) -> bool {
+    let host = host_of(addr);
+    if Config::get_option(keys::OPTION_TLS_TUNNEL) == "Y" && Config::get_socks().is_none() {
+        return true;
+    }
+    let mut tunneled = TUNNELED.lock().unwrap();
+    match tunneled.get(host) {
+        Some(time) if time.elapsed() < TUNNEL_TTL => true,
+        Some(_) => {
+            tunneled.remove(host);
+            false
+        }
+        None => false,
+    }
+}
+
+/// Tries the tunnel of the server `addr` whose port does not answer, remembered if it connects.
+pub async fn probe_tunnel(addr: &str, path: &str, ms_timeout: u64) -> bool {
+    let host = host_of(addr);
+    if Config::get_option(keys::OPTION_TLS_TUNNEL) == "N" || Config::get_socks().is_some() {
+        return false;
+    }
+    if websocket::connect(host, path, ms_timeout).await.is_err() {
+        return false;
+    }
+    set_tunneled(host);
+    true
+}
+
+/// Keeps the server `addr` reached through its tunnel, while a connection through it lasts.
+pub fn set_tunneled(addr: &str) {
+    TUNNELED
+        .lock()
+        .unwrap()
+        .insert(host_of(addr).to_owned(), Instant::now());
+}
+
+// "host", "host:port", "[ipv6]:port" or "ipv6"
+fn host_of(addr: &str) -> &str {
+    if let Some(rest) = addr.strip_prefix('[') {
+        return rest.split(']').next().unwrap_or(rest);
+    }
+    if addr.parse::<Ipv6Addr>().is_ok() {
+        return addr;
+    }
+    addr.rsplit_once(':').map_or(addr, |(host, _)| host)
+}
+
+/// The address family tried first, as configured by `OPTION_PREFERRED_IP_FAMILY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpFamily {
+    Any,
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    pub fn preferred() -> Self {
+        match Config::get_option(keys::OPTION_PREFERRED_IP_FAMILY).as_str() {
+            "ipv4" => Self::V4,
+            "ipv6" => Self::V6,
+            _ => Self::Any,
+        }
+    }
+}
+
+// of happy eyeballs, https://datatracker.ietf.org/doc/html/rfc8305#section-5
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+// any global addresses do, only the routes to them are looked up
+const ROUTE_TARGET_V4: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53);
+const ROUTE_TARGET_V6: SocketAddr = SocketAddr::new(
+    IpAddr::V6(Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888)),
+    53,
+);
+
+/// Orders the addresses to be tried, alternating between the families and starting with the one
+/// preferred, or with the family of the first address if none is.
+pub fn order_by_family(addrs: Vec<SocketAddr>, family: IpFamily) -> Vec<SocketAddr> {
+    let v6_first = match family {
+        IpFamily::V4 => false,
+        IpFamily::V6 => true,
+        IpFamily::Any => match addrs.first() {
+            Some(addr) => addr.is_ipv6(),
+            None => return addrs,
+        },
+    };
+    let (first, second): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == v6_first);
+    let mut ordered = Vec::with_capacity(first.len() + second.len());
+    let (mut first, mut second) = (first.into_iter(), second.into_iter());
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => break,
+            (a, b) => {
+                ordered.extend(a);
+                ordered.extend(b);
+            }
+        }
+    }
+    ordered
+}
+
+/// Runs the connection attempts in their order, starting one more every
+/// `CONNECTION_ATTEMPT_DELAY` until one succeeds, so that a family which does not work only
+/// delays the other a little. The first connected is returned, the others are dropped.
+pub async fn race<T, F: Future<Output = ResultType<T>>>(attempts: Vec<F>) -> ResultType<T> {
+    if attempts.is_empty() {
+        bail!("No address to connect to");
+    }
+    let attempts = attempts.into_iter().enumerate().map(|(i, attempt)| {
+        Box::pin(async move {
+            if i > 0 {
+                tokio::time::sleep(CONNECTION_ATTEMPT_DELAY * i as u32).await;
+            }
+            attempt.await
+        })
+    });
+    Ok(futures::future::select_ok(attempts).await?.0)
+}
+
+/// The global ipv6 address of this side on the route to `target`, with a port taken for it to
+/// punch holes and listen on. None if there is no such route, or the address is local only.
+pub fn local_ipv6_candidate(target: Option<SocketAddr>) -> Option<SocketAddr> {
+    let target = target
+        .filter(|addr| addr.is_ipv6())
+        .unwrap_or(ROUTE_TARGET_V6);
+    let ip = match route_source(target)? {
+        IpAddr::V6(ip) if is_global_ipv6(&ip) => ip,
+        _ => return None,
+    };
+    tcp::new_socket(SocketAddr::new(IpAddr::V6(ip), 0), true)
+        .ok()?
+        .local_addr()
+        .ok()
+}
+
+/// The addresses of this side on the routes to the internet and to `targets`.
+pub fn local_addrs(targets: &[SocketAddr]) -> Vec<IpAddr> {
+    let mut addrs = Vec::new();
+    for target in [ROUTE_TARGET_V4, ROUTE_TARGET_V6].iter().chain(targets) {
+        if let Some(ip) = route_source(*target) {
+            if !addrs.contains(&ip) {
+                addrs.push(ip);
+            }
+        }
+    }
+    addrs
+}
+
+// the source address of the packets to `target`
+fn route_source(target: SocketAddr) -> Option<IpAddr> {
+    let local: IpAddr = if target.is_ipv4() {
+        Ipv4Addr::UNSPECIFIED.into()
+    } else {
+        Ipv6Addr::UNSPECIFIED.into()
+    };
+    let socket = std::net::UdpSocket::bind((local, 0)).ok()?;
+    // nothing is sent by connecting an udp socket
+    socket.connect(target).ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+/// Whether `ip` is in `network`, given as a CIDR or a single address.
+pub fn in_network(ip: &IpAddr, network: &str) -> bool {
+    let (addr, prefix) = match network.split_once('/') {
+        Some((addr, prefix)) => (addr, prefix.parse::<u32>().ok()),
+        None => (network, None),
+    };
+    match (ip, addr.parse::<IpAddr>()) {
+        (IpAddr::V4(ip), Ok(IpAddr::V4(net))) => {
+            let prefix = prefix.unwrap_or(32).min(32);
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(*ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), Ok(IpAddr::V6(net))) => {
+            let prefix = prefix.unwrap_or(128).min(128);
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(*ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+/// A blocking udp socket on `port` of ipv6 only, which has joined the multicast `group` on the
+/// `interfaces` given by index, e.g. for the discovery of the peers on the local network.
+pub fn new_multicast_v6(
+    port: u16,
+    group: &Ipv6Addr,
+    interfaces: &[u32],
+) -> ResultType<std::net::UdpSocket> {
+    use socket2::{Domain, Protocol, Socket, Type};
+    let socket = Socket::new(Domain::ipv6(), Type::dgram(), Some(Protocol::udp()))?;
+    // not to take the port from the ipv4 socket listening on it
+    socket.set_only_v6(true)?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port).into())?;
+    let mut joined = false;
+    for index in interfaces {
+        joined |= socket.join_multicast_v6(group, *index).is_ok();
+    }
+    if !joined {
+        socket.join_multicast_v6(group, 0)?;
+    }
+    Ok(socket.into_udp_socket())
+}
+
+/// A non-blocking udp socket on `port` of both ipv4 and ipv6 if possible, ipv4 only otherwise.
+pub fn new_udp_any(port: u16) -> ResultType<std::net::UdpSocket> {
+    use socket2::{Domain, Protocol, Socket, Type};
+    let socket = match Socket::new(Domain::ipv6(), Type::dgram(), Some(Protocol::udp())) {
+        Ok(socket)
+            if socket.set_only_v6(false).is_ok()
+                && socket
+                    .bind(&SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port).into())
+                    .is_ok() =>
+        {
+            socket
+        }
+        _ => {
+            let socket = Socket::new(Domain::ipv4(), Type::dgram(), Some(Protocol::udp()))?;
+            socket.bind(&SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port).into())?;
+            socket
+        }
+    };
+    socket.set_nonblocking(true)?;
+    Ok(socket.into_udp_socket())
+}
+
+fn is_global_ipv6(ip: &Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !ip.is_loopback()
+        && !ip.is_unspecified()
+        && ip.to_ipv4().is_none()
+        // link local
+        && first & 0xffc0 != 0xfe80
+        // unique local
+        && first & 0xfe00 != 0xfc00
+}
+
 #[inline]
 pub fn is_ipv4(target: &TargetAddr<'_>) -> bool {
     match target {
@@ -155,14 +474,23 @@ pub fn ipv4_to_ipv6(addr: String, ipv4: bool) -> String {
 }
 
 async fn test_target(target: &str) -> ResultType<SocketAddr> {
-    if let Ok(Ok(s)) = super::timeout(1000, tokio::net::TcpStream::connect(target)).await {
-        if let Ok(addr) = s.peer_addr() {
-            return Ok(addr);
-        }
+    let addrs = order_by_family(
+        tokio::net::lookup_host(target).await?.collect(),
+        IpFamily::preferred(),
+    );
+    let attempts = addrs
+        .iter()
+        .map(|addr| async move {
+            super::timeout(1000, tokio::net::TcpStream::connect(addr)).await??;
+            Ok::<_, anyhow::Error>(*addr)
+        })
+        .collect();
+    if let Ok(addr) = race(attempts).await {
+        return Ok(addr);
     }
-    tokio::net::lookup_host(target)
-        .await?
-        .next()
+    addrs
+        .first()
+        .cloned()
         .context(format!("Failed to look up host for {target}"))
 }
 
@@ -271,6 +599,48 @@ mod tests {
         assert!(test_if_valid_server_for_proxy_("abcd.com:1").is_empty());
     }
 
+    #[test]
+    fn test_order_by_family() {
+        let a4: SocketAddr = "1.1.1.1:1".parse().unwrap();
+        let b4: SocketAddr = "2.2.2.2:1".parse().unwrap();
+        let a6: SocketAddr = "[1::1]:1".parse().unwrap();
+        let b6: SocketAddr = "[2::2]:1".parse().unwrap();
+        let addrs = vec![a4, b4, a6, b6];
+        assert_eq!(
+            order_by_family(addrs.clone(), IpFamily::Any),
+            vec![a4, a6, b4, b6]
+        );
+        assert_eq!(
+            order_by_family(addrs.clone(), IpFamily::V6),
+            vec![a6, a4, b6, b4]
+        );
+        assert_eq!(
+            order_by_family(vec![a6, b6, a4], IpFamily::V4),
+            vec![a4, a6, b6]
+        );
+        assert_eq!(order_by_family(vec![a4, b4], IpFamily::V6), vec![a4, b4]);
+        assert!(is_global_ipv6(&"2001:db8::1".parse().unwrap()));
+        assert!(!is_global_ipv6(&"fe80::1".parse().unwrap()));
+        assert!(!is_global_ipv6(&"fd00::1".parse().unwrap()));
+        assert!(!is_global_ipv6(&"::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_in_network() {
+        let ip: IpAddr = "10.1.2.3".parse().unwrap();
+        assert!(in_network(&ip, "10.0.0.0/8"));
+        assert!(in_network(&ip, "10.1.2.0/24"));
+        assert!(!in_network(&ip, "10.1.3.0/24"));
+        assert!(in_network(&ip, "10.1.2.3"));
+        assert!(in_network(&ip, "0.0.0.0/0"));
+        assert!(!in_network(&ip, "2001:db8::/32"));
+        assert!(!in_network(&ip, "bad"));
+        let ip: IpAddr = "2001:db8::1".parse().unwrap();
+        assert!(in_network(&ip, "2001:db8::/32"));
+        assert!(!in_network(&ip, "2001:db9::/32"));
+        assert!(!in_network(&ip, "10.0.0.0/8"));
+    }
+
     #[test]
     fn test_check_port() {
         assert_eq!(check_port("[1:2]:12", 32), "[1:2]:12");
@@ -279,6 +649,9 @@ mod tests {
         assert_eq!(check_port("1.1.1.1", 32), "1.1.1.1:32");
         assert_eq!(check_port("1.1.1.1:32", 32), "1.1.1.1:32");
         assert_eq!(check_port("test.com:32", 0), "test.com:32");
+        assert_eq!(check_port("2001:db8::1", 32), "[2001:db8::1]:32");
+        assert_eq!(check_port("[2001:db8::1]:21118", 32), "[2001:db8::1]:21118");
+        assert_eq!(check_port("::1", 32), "[::1]:32");
         assert_eq!(increase_port("[1:2]:12", 1), "[1:2]:13");
         assert_eq!(increase_port("1.2.2.4:12", 1), "1.2.2.4:13");
         assert_eq!(increase_port("1.2.2.4", 1), "1.2.2.4");
@@ -288,4 +661,13 @@ mod tests {
         assert_eq!(increase_port("22:1:13", 4), "22:1:13");
         assert_eq!(increase_port("z1:2", 1), "z1:3");
     }
+
+    #[test]
+    fn test_host_of() {
+        assert_eq!(host_of("rs.example.com:21117"), "rs.example.com");
+        assert_eq!(host_of("rs.example.com"), "rs.example.com");
+        assert_eq!(host_of("1.2.3.4:21116"), "1.2.3.4");
+        assert_eq!(host_of("[2001:db8::1]:21116"), "2001:db8::1");
+        assert_eq!(host_of("2001:db8::1"), "2001:db8::1");
+    }
 }
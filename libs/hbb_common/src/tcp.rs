@@ -1,4 +1,11 @@
-use crate::{bail, bytes_codec::BytesCodec, ResultType, config::Socks5Server, proxy::Proxy};
+use crate::{
+    bail,
+    bytes_codec::BytesCodec,
+    config::Socks5Server,
+    proxy::Proxy,
+    quic::{Channel, Channels},
+    ResultType,
+};
 use anyhow::Context as AnyhowCtx;
 use bytes::{BufMut, Bytes, BytesMut};
 use futures::{SinkExt, StreamExt};
@@ -8,6 +15,7 @@ use sodiumoxide::crypto::{
     secretbox::{self, Key, Nonce},
 };
 use std::{
+    collections::VecDeque,
     io::{self, Error, ErrorKind},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     ops::{Deref, DerefMut},
@@ -16,7 +24,7 @@ use std::{
 };
 use tokio::{
     io::{AsyncRead, AsyncWrite, ReadBuf},
-    net::{lookup_host, TcpListener, TcpSocket, ToSocketAddrs},
+    net::{lookup_host, TcpListener, TcpSocket, TcpStream, ToSocketAddrs},
 };
 use tokio_socks::IntoTargetAddr;
 use tokio_util::codec::Framed;
@@ -25,15 +33,77 @@ pub trait TcpStreamTrait: AsyncRead + AsyncWrite + Unpin {}
 pub struct DynTcpStream(pub(crate) Box<dyn TcpStreamTrait + Send + Sync>);
 
 #[derive(Clone)]
-pub struct Encrypt(Key, u64, u64);
+pub struct Encrypt(Key, u64, u64, u8);
 
 pub struct FramedStream(
     pub(crate) Framed<DynTcpStream, BytesCodec>,
     pub(crate) SocketAddr,
     pub(crate) Option<Encrypt>,
     pub(crate) u64,
+    pub(crate) Option<Box<Resend>>,
+    // the socket under the stream, for the socket options, owned by the stream
+    pub(crate) Option<RawSocket>,
+    // the other streams of a quic connection
+    pub(crate) Option<Box<Channels>>,
 );
 
+#[cfg(unix)]
+pub(crate) type RawSocket = std::os::unix::io::RawFd;
+#[cfg(windows)]
+pub(crate) type RawSocket = std::os::windows::io::RawSocket;
+
+pub(crate) fn raw_socket(stream: &TcpStream) -> Option<RawSocket> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+        Some(stream.as_raw_fd())
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::AsRawSocket;
+        Some(stream.as_raw_socket())
+    }
+}
+
+// the plain messages kept to be sent again
+const MAX_RESEND_BYTES: usize = 8 * 1024 * 1024;
+
+/// The state of a session which can move to another stream: the messages sent are numbered and
+/// kept until the peer reports them received, so that the lost ones are sent again on the new
+/// stream. The received messages are counted by the caller, which knows the ones of the session.
+#[derive(Default)]
+pub struct Resend {
+    sent: u64,
+    received: u64,
+    // the last messages sent, up to `sent`
+    queue: VecDeque<Vec<u8>>,
+    bytes: usize,
+    // moved to a new stream, the messages are held until the peer reports what it has received
+    paused: bool,
+}
+
+impl Resend {
+    fn push(&mut self, msg: Vec<u8>) {
+        self.sent += 1;
+        self.bytes += msg.len();
+        self.queue.push_back(msg);
+        while self.bytes > MAX_RESEND_BYTES && self.queue.len() > 1 {
+            if let Some(msg) = self.queue.pop_front() {
+                self.bytes -= msg.len();
+            }
+        }
+    }
+
+    fn ack(&mut self, received: u64) {
+        let first = self.sent + 1 - self.queue.len() as u64;
+        for _ in first..=received.min(self.sent) {
+            if let Some(msg) = self.queue.pop_front() {
+                self.bytes -= msg.len();
+            }
+        }
+    }
+}
+
 impl Deref for FramedStream {
     type Target = Framed<DynTcpStream, BytesCodec>;
 
@@ -79,34 +149,89 @@ pub(crate) fn new_socket(addr: std::net::SocketAddr, reuse: bool) -> Result<TcpS
     Ok(socket)
 }
 
+#[cfg(unix)]
+use libc::{IPPROTO_IP, IPPROTO_IPV6, IPV6_TCLASS, IP_TOS};
+#[cfg(windows)]
+use winapi::shared::{
+    ws2def::{IPPROTO_IP, IPPROTO_IPV6},
+    ws2ipdef::{IPV6_TCLASS, IP_TOS},
+};
+
+fn set_socket_option(socket: RawSocket, level: i32, name: i32, value: i32) -> ResultType<()> {
+    let len = std::mem::size_of_val(&value);
+    #[cfg(unix)]
+    let res = unsafe {
+        libc::setsockopt(
+            socket,
+            level,
+            name,
+            &value as *const i32 as *const libc::c_void,
+            len as libc::socklen_t,
+        )
+    };
+    #[cfg(windows)]
+    let res = unsafe {
+        winapi::um::winsock2::setsockopt(
+            socket as winapi::um::winsock2::SOCKET,
+            level,
+            name,
+            &value as *const i32 as *const i8,
+            len as i32,
+        )
+    };
+    if res != 0 {
+        bail!("setsockopt failed: {}", io::Error::last_os_error());
+    }
+    Ok(())
+}
+
 impl FramedStream {
     pub async fn new<T: ToSocketAddrs + std::fmt::Display>(
         remote_addr: T,
         local_addr: Option<SocketAddr>,
         ms_timeout: u64,
     ) -> ResultType<Self> {
-        for remote_addr in lookup_host(&remote_addr).await? {
-            let local = if let Some(addr) = local_addr {
-                addr
-            } else {
-                crate::config::Config::get_any_listen_addr(remote_addr.is_ipv4())
-            };
-            if let Ok(socket) = new_socket(local, true) {
-                if let Ok(Ok(stream)) =
-                    super::timeout(ms_timeout, socket.connect(remote_addr)).await
-                {
-                    stream.set_nodelay(true).ok();
-                    let addr = stream.local_addr()?;
-                    return Ok(Self(
-                        Framed::new(DynTcpStream(Box::new(stream)), BytesCodec::new()),
-                        addr,
-                        None,
-                        0,
-                    ));
-                }
-            }
+        use crate::socket_client::{order_by_family, race, IpFamily};
+        let remote_addrs = order_by_family(
+            lookup_host(&remote_addr)
+                .await?
+                // a socket bound to one family can not connect to the other
+                .filter(|addr| local_addr.map_or(true, |l| l.is_ipv4() == addr.is_ipv4()))
+                .collect(),
+            IpFamily::preferred(),
+        );
+        let attempts = remote_addrs
+            .into_iter()
+            .map(|addr| Self::connect_addr(addr, local_addr, ms_timeout))
+            .collect();
+        match race(attempts).await {
+            Ok(stream) => Ok(stream),
+            Err(_) => bail!(format!("Failed to connect to {remote_addr}")),
         }
-        bail!(format!("Failed to connect to {remote_addr}"));
+    }
+
+    async fn connect_addr(
+        remote_addr: SocketAddr,
+        local_addr: Option<SocketAddr>,
+        ms_timeout: u64,
+    ) -> ResultType<Self> {
+        let local = local_addr.unwrap_or_else(|| {
+            crate::config::Config::get_any_listen_addr(remote_addr.is_ipv4())
+        });
+        let socket = new_socket(local, true)?;
+        let stream = super::timeout(ms_timeout, socket.connect(remote_addr)).await??;
+        stream.set_nodelay(true).ok();
+        let addr = stream.local_addr()?;
+        let socket = raw_socket(&stream);
+        Ok(Self(
+            Framed::new(DynTcpStream(Box::new(stream)), BytesCodec::new()),
+            addr,
+            None,
+            0,
+            None,
+            socket,
+            None,
+        ))
     }
 
     pub async fn connect<'t, T>(
@@ -131,14 +256,36 @@ impl FramedStream {
     }
 
     pub fn from(stream: impl TcpStreamTrait + Send + Sync + 'static, addr: SocketAddr) -> Self {
+        let socket = (&stream as &dyn std::any::Any)
+            .downcast_ref::<TcpStream>()
+            .and_then(raw_socket);
         Self(
             Framed::new(DynTcpStream(Box::new(stream)), BytesCodec::new()),
             addr,
             None,
             0,
+            None,
+            socket,
+            None,
         )
     }
 
+    /// Marks the packets sent with the differentiated services code point `dscp`.
+    pub fn set_dscp(&self, dscp: u8) -> ResultType<()> {
+        let socket = match self.5 {
+            Some(socket) => socket,
+            None => bail!("no socket to mark"),
+        };
+        let tos = (dscp as i32) << 2;
+        if self.1.is_ipv4() {
+            set_socket_option(socket, IPPROTO_IP, IP_TOS, tos)
+        } else {
+            // the ipv4 peers of a dual stack socket are sent to with the ipv4 option
+            set_socket_option(socket, IPPROTO_IP, IP_TOS, tos).ok();
+            set_socket_option(socket, IPPROTO_IPV6, IPV6_TCLASS, tos)
+        }
+    }
+
     pub fn set_raw(&mut self) {
         self.0.codec_mut().set_raw();
         self.2 = None;
@@ -153,16 +300,114 @@ impl FramedStream {
         self.send_raw(msg.write_to_bytes()?).await
     }
 
+    /// Sends on the stream of `channel` if the connection has one, as `send` does otherwise.
+    pub async fn send_on(&mut self, channel: Channel, msg: &impl Message) -> ResultType<()> {
+        match self.6.as_mut() {
+            // the resent messages must keep their order, so they all go on the first stream
+            Some(channels)
+                if channel != Channel::Control && self.2.is_some() && self.4.is_none() =>
+            {
+                let sent = channels.send(channel, msg.write_to_bytes()?);
+                if self.3 > 0 {
+                    super::timeout(self.3, sent).await??;
+                } else {
+                    sent.await?;
+                }
+                Ok(())
+            }
+            _ => self.send(msg).await,
+        }
+    }
+
     #[inline]
     pub async fn send_raw(&mut self, msg: Vec<u8>) -> ResultType<()> {
         let mut msg = msg;
-        if let Some(key) = self.2.as_mut() {
+        if let Some(resend) = self.4.as_mut() {
+            if resend.paused {
+                resend.push(msg);
+                return Ok(());
+            }
+            let out = match self.2.as_mut() {
+                Some(key) => key.enc(&msg),
+                None => msg.clone(),
+            };
+            resend.push(msg);
+            msg = out;
+        } else if let Some(key) = self.2.as_mut() {
             msg = key.enc(&msg);
         }
         self.send_bytes(bytes::Bytes::from(msg)).await?;
         Ok(())
     }
 
+    /// Keeps the messages sent from now on to be sent again if the session moves to another
+    /// stream.
+    pub fn enable_resend(&mut self) {
+        self.4.get_or_insert_with(Default::default);
+    }
+
+    pub fn is_resend_enabled(&self) -> bool {
+        self.4.is_some()
+    }
+
+    /// Counts a message of the session received.
+    pub fn on_received(&mut self) {
+        if let Some(resend) = self.4.as_mut() {
+            resend.received += 1;
+        }
+    }
+
+    /// The messages of the session received so far.
+    pub fn received(&self) -> u64 {
+        self.4.as_ref().map(|x| x.received).unwrap_or_default()
+    }
+
+    /// Drops the messages the peer has reported received.
+    pub fn ack(&mut self, received: u64) {
+        if let Some(resend) = self.4.as_mut() {
+            resend.ack(received);
+        }
+    }
+
+    /// Moves the session from `old` to this stream. The messages the peer has not received are
+    /// sent again once `resume` is called with what it has received, the new ones are held until
+    /// then.
+    pub fn take_over(&mut self, old: &mut FramedStream) {
+        let mut resend = old.4.take().unwrap_or_default();
+        resend.paused = true;
+        self.4 = Some(resend);
+    }
+
+    /// Sends again the messages after the `received` first ones and the ones held since
+    /// `take_over`.
+    pub async fn resume(&mut self, received: u64) -> ResultType<()> {
+        let msgs = match self.4.as_mut() {
+            Some(resend) if resend.paused => {
+                let first = resend.sent + 1 - resend.queue.len() as u64;
+                if received > resend.sent || received + 1 < first {
+                    bail!(
+                        "messages lost, {} received of {} sent, kept from {}",
+                        received,
+                        resend.sent,
+                        first
+                    );
+                }
+                resend.paused = false;
+                let skip = (received + 1 - first) as usize;
+                resend.queue.iter().skip(skip).cloned().collect::<Vec<_>>()
+            }
+            _ => return Ok(()),
+        };
+        for msg in msgs {
+            let msg = match self.2.as_mut() {
+                Some(key) => key.enc(&msg),
+                None => msg,
+            };
+            self.send_bytes(bytes::Bytes::from(msg)).await?;
+        }
+        Ok(())
+    }
+
     #[inline]
     pub async fn send_bytes(&mut self, bytes: Bytes) -> ResultType<()> {
         if self.3 > 0 {
@@ -175,9 +420,25 @@ impl FramedStream {
 
     #[inline]
     pub async fn next(&mut self) -> Option<Result<BytesMut, Error>> {
-        let mut res = self.0.next().await;
+        match self.6.as_mut() {
+            // the other streams may overtake the first one, their messages wait for the key
+            Some(channels) if self.2.is_some() => tokio::select! {
+                res = self.0.next() => Self::decrypt(self.2.as_mut(), res),
+                Some(res) = channels.recv() => Some(res),
+            },
+            _ => {
+                let res = self.0.next().await;
+                Self::decrypt(self.2.as_mut(), res)
+            }
+        }
+    }
+
+    fn decrypt(
+        key: Option<&mut Encrypt>,
+        mut res: Option<Result<BytesMut, Error>>,
+    ) -> Option<Result<BytesMut, Error>> {
         if let Some(Ok(bytes)) = res.as_mut() {
-            if let Some(key) = self.2.as_mut() {
+            if let Some(key) = key {
                 if let Err(err) = key.dec(bytes) {
                     return Some(Err(err));
                 }
@@ -196,12 +457,22 @@ impl FramedStream {
     }
 
     pub fn set_key(&mut self, key: Key) {
+        if let Some(channels) = self.6.as_mut() {
+            channels.set_key(key.clone());
+        }
         self.2 = Some(Encrypt::new(key));
     }
 
-    fn get_nonce(seqnum: u64) -> Nonce {
+    pub fn is_quic(&self) -> bool {
+        self.6.is_some()
+    }
+
+    fn get_nonce(seqnum: u64, channel: u8) -> Nonce {
         let mut nonce = Nonce([0u8; secretbox::NONCEBYTES]);
-        nonce.0[..std::mem::size_of_val(&seqnum)].copy_from_slice(&seqnum.to_le_bytes());
+        let n = std::mem::size_of_val(&seqnum);
+        nonce.0[..n].copy_from_slice(&seqnum.to_le_bytes());
+        // the streams of a quic connection share the key, not the nonces
+        nonce.0[n] = channel;
         nonce
     }
 }
@@ -292,7 +563,11 @@ impl<R: AsyncRead + AsyncWrite + Unpin> TcpStreamTrait for R {}
 
 impl Encrypt {
     pub fn new(key: Key) -> Self {
-        Self(key, 0, 0)
+        Self(key, 0, 0, 0)
+    }
+
+    pub(crate) fn for_channel(key: Key, channel: u8) -> Self {
+        Self(key, 0, 0, channel)
     }
 
     pub fn dec(&mut self, bytes: &mut BytesMut) -> Result<(), Error> {
@@ -300,7 +575,7 @@ impl Encrypt {
             return Ok(());
         }
         self.2 += 1;
-        let nonce = FramedStream::get_nonce(self.2);
+        let nonce = FramedStream::get_nonce(self.2, self.3);
         match secretbox::open(bytes, &nonce, &self.0) {
             Ok(res) => {
                 bytes.clear();
@@ -313,7 +588,7 @@ impl Encrypt {
 
     pub fn enc(&mut self, data: &[u8]) -> Vec<u8> {
         self.1 += 1;
-        let nonce = FramedStream::get_nonce(self.1);
+        let nonce = FramedStream::get_nonce(self.1, self.3);
         secretbox::seal(&data, &nonce, &self.0)
     }
 
@@ -339,3 +614,81 @@ impl Encrypt {
         Ok(Key(key))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resend_ack() {
+        let mut resend = Resend::default();
+        for i in 0..5u8 {
+            resend.push(vec![i]);
+        }
+        resend.ack(2);
+        assert_eq!(resend.queue, [vec![2], vec![3], vec![4]]);
+        resend.ack(1);
+        assert_eq!(resend.queue.len(), 3);
+        resend.ack(9);
+        assert!(resend.queue.is_empty());
+        assert_eq!(resend.bytes, 0);
+        resend.push(vec![0; MAX_RESEND_BYTES]);
+        resend.push(vec![1]);
+        assert_eq!(resend.queue, [vec![1]]);
+        assert_eq!(resend.sent, 7);
+    }
+
+    #[test]
+    fn test_dscp() {
+        test_dscp_async();
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    async fn test_dscp_async() {
+        let listener = new_listener("127.0.0.1:0", false).await.unwrap();
+        let (a, b) = pair(&listener).await;
+        assert!(a.set_dscp(46).is_ok());
+        assert!(b.set_dscp(8).is_ok());
+        let (c, _) = tokio::io::duplex(64);
+        assert!(FramedStream::from(c, a.local_addr()).set_dscp(46).is_err());
+    }
+
+    #[test]
+    fn test_take_over() {
+        test_take_over_async();
+    }
+
+    async fn pair(listener: &TcpListener) -> (FramedStream, FramedStream) {
+        let addr = listener.local_addr().unwrap();
+        let a = FramedStream::new(addr, None, 1_000).await.unwrap();
+        let (b, addr) = listener.accept().await.unwrap();
+        (a, FramedStream::from(b, addr))
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    async fn test_take_over_async() {
+        let listener = new_listener("127.0.0.1:0", false).await.unwrap();
+        let (mut a1, mut b1) = pair(&listener).await;
+        a1.enable_resend();
+        b1.enable_resend();
+        for i in 1..=3u8 {
+            a1.send_raw(vec![i]).await.unwrap();
+        }
+        assert_eq!(&b1.next().await.unwrap().unwrap()[..], &[1]);
+        b1.on_received();
+        // the first stream is given up with two messages in flight
+        let (mut a2, mut b2) = pair(&listener).await;
+        a2.take_over(&mut a1);
+        a2.send_raw(vec![4]).await.unwrap();
+        b2.take_over(&mut b1);
+        assert_eq!(b2.received(), 1);
+        a2.resume(b2.received()).await.unwrap();
+        for i in 2..=4u8 {
+            assert_eq!(&b2.next().await.unwrap().unwrap()[..], &[i]);
+        }
+        a2.ack(4);
+        let (mut a3, _b3) = pair(&listener).await;
+        a3.take_over(&mut a2);
+        assert!(a3.resume(1).await.is_err());
+    }
+}
@@ -0,0 +1,233 @@
+// The pins of the tls servers, the api server and the websocket tunnel of the rendezvous and the
+// relay servers, so that a hijacked dns can not lead to an imposter with a certificate of a
+// trusted CA.
+//
+// A pin is "sha256/" and the base64 of the sha256 of the DER of the certificate of the server, or
+// of its public key, as `openssl x509 -pubkey -noout | openssl pkey -pubin -outform der |
+// openssl dgst -sha256 -binary | base64` prints. Once any pin is set, a certificate is trusted
+// if and only if it matches one of them, so that the servers may be self-signed, and the chain
+// and the CAs of the platform are not checked.
+
+use std::{convert::TryFrom, fmt, sync::Arc};
+
+use base64::{engine::general_purpose, Engine};
+use sha2::{Digest, Sha256};
+use tokio_rustls::rustls::{
+    self,
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, SignatureScheme,
+};
+
+use crate::{
+    bail,
+    config::{keys, Config},
+    log, ResultType,
+};
+
+const PREFIX: &str = "sha256/";
+
+pub type Pin = [u8; 32];
+
+pub fn parse(value: &str) -> ResultType<Vec<Pin>> {
+    value
+        .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let Some(hash) = p.strip_prefix(PREFIX) else {
+                bail!("Invalid pin, \"{}\" expected: {}", PREFIX, p);
+            };
+            match general_purpose::STANDARD.decode(hash).map(Pin::try_from) {
+                Ok(Ok(pin)) => Ok(pin),
+                _ => bail!("Invalid sha256 of the pin: {}", p),
+            }
+        })
+        .collect()
+}
+
+/// The pins of the tls servers, None if they are not pinned.
+///
+/// The broken pins are skipped, so that the servers are rejected if no pin is left.
+pub fn pins() -> Option<Vec<Pin>> {
+    let value = Config::get_option(keys::OPTION_TLS_PINS);
+    if value.trim().is_empty() {
+        return None;
+    }
+    match parse(&value) {
+        Ok(pins) => Some(pins),
+        Err(err) => {
+            log::error!("{}", err);
+            Some(parse_valid(&value))
+        }
+    }
+}
+
+fn parse_valid(value: &str) -> Vec<Pin> {
+    value
+        .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .filter_map(|p| parse(p).ok())
+        .flatten()
+        .collect()
+}
+
+/// Whether the DER of the certificate `cert`, or of its public key, matches one of `pins`.
+pub fn matches(pins: &[Pin], cert: &[u8]) -> bool {
+    let hash = |data: &[u8]| -> Pin { Sha256::digest(data).into() };
+    if pins.contains(&hash(cert)) {
+        return true;
+    }
+    match spki(cert) {
+        Some(spki) => pins.contains(&hash(spki)),
+        None => false,
+    }
+}
+
+/// The rustls config checking the certificates with `pins` only, and the signatures of the
+/// handshakes with the platform verifier.
+pub fn client_config(pins: Vec<Pin>) -> ClientConfig {
+    ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinnedVerifier {
+            inner: rustls_platform_verifier::Verifier::new(),
+            pins,
+        }))
+        .with_no_client_auth()
+}
+
+struct PinnedVerifier {
+    inner: rustls_platform_verifier::Verifier,
+    pins: Vec<Pin>,
+}
+
+impl fmt::Debug for PinnedVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PinnedVerifier")
+            .field("pins", &self.pins.len())
+            .finish()
+    }
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if !matches(&self.pins, end_entity) {
+            log::error!("The certificate of {:?} matches no pin", server_name);
+            return Err(rustls::Error::General(
+                "The certificate of the server matches no pin".to_owned(),
+            ));
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+// The tag, the content and the rest of the DER element at the start of `data`.
+fn der(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, data) = data.split_first()?;
+    let (&len, mut data) = data.split_first()?;
+    let len = if len < 0x80 {
+        len as usize
+    } else {
+        let n = (len & 0x7F) as usize;
+        if n == 0 || n > 4 || data.len() < n {
+            return None;
+        }
+        let len = data[..n].iter().fold(0, |l, b| l << 8 | *b as usize);
+        data = &data[n..];
+        len
+    };
+    if data.len() < len {
+        return None;
+    }
+    Some((tag, &data[..len], &data[len..]))
+}
+
+// The whole DER of the SubjectPublicKeyInfo of a certificate.
+fn spki(cert: &[u8]) -> Option<&[u8]> {
+    let (_, cert, _) = der(cert)?;
+    let (_, tbs, _) = der(cert)?;
+    let mut rest = tbs;
+    // the explicit version
+    let (tag, _, next) = der(rest)?;
+    if tag == 0xA0 {
+        rest = next;
+    }
+    // the serial number, the signature algorithm, the issuer, the validity and the subject
+    for _ in 0..5 {
+        rest = der(rest)?.2;
+    }
+    let (_, _, next) = der(rest)?;
+    Some(&rest[..rest.len() - next.len()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a self-signed P-256 certificate
+    const CERT: &str = "MIIBcDCCARWgAwIBAgIUNqAy/8a01062o4vk3SJPDQh05rQwCgYIKoZIzj0EAwIwDDEKMAgGA1UEAwwBYTAgFw0yNjEwMTYxOTA0NDhaGA8yMTI2MDkyMjE5MDQ0OFowDDEKMAgGA1UEAwwBYTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABK63iMJUKRctLvFOOdmNO0gzQU6PjHms3GWst58HJ3h+V9ndP67RIWJ1S4vT4C86FH0JfIJ2as+Tv3E1lMkBf4SjUzBRMB0GA1UdDgQWBBRKbmzBn4sIAYt3g2jrkqnhb+RSnDAfBgNVHSMEGDAWgBRKbmzBn4sIAYt3g2jrkqnhb+RSnDAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0kAMEYCIQD6mBkvq++fxVirGXrDCHmtqlD7AdiolxNKS5CrDgRDogIhAOGgGKsPR4+S+C8VXZC+TWOgzsskmk9JpilNwXgyvqkD";
+    const CERT_PIN: &str = "sha256/JRbEVhFSc+Kaz+7RP8KhCB1+Bvw1mlG5CJDFnWTpMCQ=";
+    const SPKI_PIN: &str = "sha256/Z0vaSTv/WXQgyK1N5Mruf31STFQHW/Pb9yEoRQ7GHDs=";
+
+    #[test]
+    fn test_parse() {
+        let pins = parse(&format!("{}, {}\n", CERT_PIN, SPKI_PIN)).unwrap();
+        assert_eq!(pins.len(), 2);
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("sha1/JRbEVhFSc+Kaz+7RP8KhCB1+Bvw1mlG5CJDFnWTpMCQ=").is_err());
+        assert!(parse("sha256/JRbEVhFSc+Kaz+7RP8KhCB1+Bvw1mlG5CJDF").is_err());
+        assert_eq!(parse_valid(&format!("{} sha256/x", SPKI_PIN)).len(), 1);
+    }
+
+    #[test]
+    fn test_matches() {
+        let cert = general_purpose::STANDARD.decode(CERT).unwrap();
+        assert!(matches(&parse(CERT_PIN).unwrap(), &cert));
+        assert!(matches(&parse(SPKI_PIN).unwrap(), &cert));
+        assert!(!matches(&[[0; 32]], &cert));
+        assert!(!matches(&parse(SPKI_PIN).unwrap(), &cert[..100]));
+    }
+
+    #[test]
+    fn test_verify_self_signed() {
+        let cert = CertificateDer::from(general_purpose::STANDARD.decode(CERT).unwrap());
+        let name = ServerName::try_from("rs.example.com").unwrap();
+        let verify = |pins: Vec<Pin>| {
+            let verifier = PinnedVerifier {
+                inner: rustls_platform_verifier::Verifier::new(),
+                pins,
+            };
+            verifier.verify_server_cert(&cert, &[], &name, &[], UnixTime::now())
+        };
+        assert!(verify(parse(SPKI_PIN).unwrap()).is_ok());
+        assert!(verify(parse(CERT_PIN).unwrap()).is_ok());
+        assert!(verify(vec![[0; 32]]).is_err());
+    }
+}
@@ -0,0 +1,410 @@
+// A tunnel of the tcp stream to a server port through a tls websocket on port 443, for the
+// networks which let nothing but https out.
+//
+// The stream is carried by the binary messages of the websocket, at the path standing for the
+// port, and the endpoint unwraps them to a tcp connection to the port, as websockify does behind
+// a tls reverse proxy. The messages do not follow the frames of the stream, so that the raw
+// streams of the port forwards are carried all the same.
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use base64::{engine::general_purpose, Engine};
+use bytes::{Buf, BufMut, BytesMut};
+use httparse::{Response, EMPTY_HEADER};
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio_util::codec::Framed;
+
+use crate::{
+    bail,
+    bytes_codec::BytesCodec,
+    socket_client::check_port,
+    tcp::{DynTcpStream, FramedStream},
+    ResultType,
+};
+
+pub const TUNNEL_PORT: i32 = 443;
+// the paths of the rendezvous and the relay ports
+pub const RENDEZVOUS_PATH: &str = "/ws/id";
+pub const RELAY_PATH: &str = "/ws/relay";
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MAX_HEADER_LEN: usize = 8 * 1024;
+const MAX_PAYLOAD_LEN: usize = 64 * 1024 * 1024;
+// of the messages sent
+const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// Connects to `path` of the tunnel of the server `host`.
+pub async fn connect(host: &str, path: &str, ms_timeout: u64) -> ResultType<FramedStream> {
+    let FramedStream(framed, addr, _, _, _, socket, _) =
+        FramedStream::new(check_port(host, TUNNEL_PORT), None, ms_timeout).await?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let stream = framed.into_inner();
+    let stream = match crate::tls_pin::pins() {
+        Some(pins) => DynTcpStream(Box::new(
+            super::timeout(ms_timeout, tls_connect_pinned(host, stream, pins)).await??,
+        )),
+        None => DynTcpStream(Box::new(
+            super::timeout(ms_timeout, tls_connect(host, stream)).await??,
+        )),
+    };
+    let stream = super::timeout(ms_timeout, handshake(stream, host, path)).await??;
+    Ok(FramedStream(
+        Framed::new(DynTcpStream(Box::new(stream)), BytesCodec::new()),
+        addr,
+        None,
+        0,
+        None,
+        socket,
+        None,
+    ))
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+async fn tls_connect(
+    host: &str,
+    stream: DynTcpStream,
+) -> ResultType<tokio_native_tls::TlsStream<DynTcpStream>> {
+    use tokio_native_tls::{native_tls, TlsConnector};
+    let connector = TlsConnector::from(native_tls::TlsConnector::new()?);
+    Ok(connector.connect(host, stream).await?)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+async fn tls_connect(
+    host: &str,
+    stream: DynTcpStream,
+) -> ResultType<tokio_rustls::client::TlsStream<DynTcpStream>> {
+    use std::convert::TryFrom;
+    let config = rustls_platform_verifier::tls_config();
+    let domain = rustls_pki_types::ServerName::try_from(host)?.to_owned();
+    let connector = tokio_rustls::TlsConnector::from(std::sync::Arc::new(config));
+    Ok(connector.connect(domain, stream).await?)
+}
+
+// rustls on all the platforms, for its verifier to check the pins
+async fn tls_connect_pinned(
+    host: &str,
+    stream: DynTcpStream,
+    pins: Vec<crate::tls_pin::Pin>,
+) -> ResultType<tokio_rustls::client::TlsStream<DynTcpStream>> {
+    use std::convert::TryFrom;
+    let config = crate::tls_pin::client_config(pins);
+    let domain = rustls_pki_types::ServerName::try_from(host)?.to_owned();
+    let connector = tokio_rustls::TlsConnector::from(std::sync::Arc::new(config));
+    Ok(connector.connect(domain, stream).await?)
+}
+
+async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    host: &str,
+    path: &str,
+) -> ResultType<WsStream<S>> {
+    let key = general_purpose::STANDARD.encode(rand::random::<[u8; 16]>());
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        path, host, key
+    );
+    stream.write_all(request.as_bytes()).await?;
+    stream.flush().await?;
+    let mut buf = BytesMut::new();
+    loop {
+        if buf.len() > MAX_HEADER_LEN {
+            bail!("The websocket handshake response is too long");
+        }
+        if stream.read_buf(&mut buf).await? == 0 {
+            bail!("The websocket handshake is closed by the server");
+        }
+        let mut headers = [EMPTY_HEADER; 32];
+        let mut response = Response::new(&mut headers);
+        let len = match response.parse(&buf)? {
+            httparse::Status::Complete(len) => len,
+            httparse::Status::Partial => continue,
+        };
+        if response.code != Some(101) {
+            bail!(
+                "The websocket handshake is refused by the server: {:?}",
+                response.code
+            );
+        }
+        let accept = response
+            .headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case("sec-websocket-accept"))
+            .map(|h| h.value);
+        if accept != Some(accept_key(&key).as_bytes()) {
+            bail!("The websocket handshake is not accepted by the server");
+        }
+        // the messages sent right after the response
+        buf.advance(len);
+        return Ok(WsStream::new(stream, buf));
+    }
+}
+
+fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(GUID.as_bytes());
+    general_purpose::STANDARD.encode(sha1.finalize())
+}
+
+/// The stream carried by the binary messages of a websocket, of the client side.
+pub struct WsStream<S> {
+    inner: S,
+    // received, not decoded yet
+    rbuf: BytesMut,
+    // decoded, not read yet
+    payload: BytesMut,
+    // encoded, not written yet
+    wbuf: BytesMut,
+    closed: bool,
+    close_sent: bool,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> WsStream<S> {
+    fn new(inner: S, rbuf: BytesMut) -> Self {
+        Self {
+            inner,
+            rbuf,
+            payload: BytesMut::new(),
+            wbuf: BytesMut::new(),
+            closed: false,
+            close_sent: false,
+        }
+    }
+
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.wbuf.is_empty() {
+            let n = futures::ready!(Pin::new(&mut self.inner).poll_write(cx, &self.wbuf))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.wbuf.advance(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for WsStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        loop {
+            if !this.payload.is_empty() {
+                let n = this.payload.len().min(buf.remaining());
+                buf.put_slice(&this.payload.split_to(n));
+                return Poll::Ready(Ok(()));
+            }
+            if this.closed {
+                return Poll::Ready(Ok(()));
+            }
+            match decode(&mut this.rbuf)? {
+                Some((OP_BINARY | OP_TEXT | OP_CONTINUATION, payload)) => {
+                    this.payload = payload;
+                }
+                Some((OP_PING, payload)) => {
+                    encode(&mut this.wbuf, OP_PONG, &payload);
+                    // answered now if it can be, else with the next write
+                    if let Poll::Ready(Err(err)) = this.poll_write_buf(cx) {
+                        return Poll::Ready(Err(err));
+                    }
+                }
+                Some((OP_CLOSE, _)) => this.closed = true,
+                Some(_) => {}
+                None => {
+                    let mut tmp = [0u8; 8192];
+                    let mut read = ReadBuf::new(&mut tmp);
+                    futures::ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read))?;
+                    if read.filled().is_empty() {
+                        this.closed = true;
+                    }
+                    this.rbuf.extend_from_slice(read.filled());
+                }
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for WsStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        // one message is kept at most
+        futures::ready!(this.poll_write_buf(cx))?;
+        let n = buf.len().min(MAX_MESSAGE_LEN);
+        encode(&mut this.wbuf, OP_BINARY, &buf[..n]);
+        if let Poll::Ready(Err(err)) = this.poll_write_buf(cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        futures::ready!(self.poll_write_buf(cx))?;
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.close_sent {
+            futures::ready!(self.poll_write_buf(cx))?;
+            encode(&mut self.wbuf, OP_CLOSE, &[]);
+            self.close_sent = true;
+        }
+        futures::ready!(self.poll_write_buf(cx))?;
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+// a masked message of the client side
+fn encode(dst: &mut BytesMut, opcode: u8, payload: &[u8]) {
+    dst.put_u8(0x80 | opcode);
+    let len = payload.len();
+    if len < 126 {
+        dst.put_u8(0x80 | len as u8);
+    } else if len <= u16::MAX as usize {
+        dst.put_u8(0x80 | 126);
+        dst.put_u16(len as u16);
+    } else {
+        dst.put_u8(0x80 | 127);
+        dst.put_u64(len as u64);
+    }
+    let mask = rand::random::<[u8; 4]>();
+    dst.put_slice(&mask);
+    let start = dst.len();
+    dst.put_slice(payload);
+    for (i, b) in dst[start..].iter_mut().enumerate() {
+        *b ^= mask[i % 4];
+    }
+}
+
+// the opcode and the payload of the frame at the head of `src`, none if it is not complete
+fn decode(src: &mut BytesMut) -> io::Result<Option<(u8, BytesMut)>> {
+    if src.len() < 2 {
+        return Ok(None);
+    }
+    let opcode = src[0] & 0x0F;
+    let (len, mut head) = match src[1] & 0x7F {
+        126 => {
+            if src.len() < 4 {
+                return Ok(None);
+            }
+            (u16::from_be_bytes([src[2], src[3]]) as usize, 4)
+        }
+        127 => {
+            if src.len() < 10 {
+                return Ok(None);
+            }
+            let mut len = [0u8; 8];
+            len.copy_from_slice(&src[2..10]);
+            (u64::from_be_bytes(len) as usize, 10)
+        }
+        len => (len as usize, 2),
+    };
+    if len > MAX_PAYLOAD_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "websocket message too long",
+        ));
+    }
+    let mask = if src[1] & 0x80 != 0 {
+        if src.len() < head + 4 {
+            return Ok(None);
+        }
+        let mut mask = [0u8; 4];
+        mask.copy_from_slice(&src[head..head + 4]);
+        head += 4;
+        Some(mask)
+    } else {
+        None
+    };
+    if src.len() < head + len {
+        src.reserve(head + len - src.len());
+        return Ok(None);
+    }
+    src.advance(head);
+    let mut payload = src.split_to(len);
+    if let Some(mask) = mask {
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= mask[i % 4];
+        }
+    }
+    Ok(Some((opcode, payload)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key() {
+        // of rfc 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut buf = BytesMut::new();
+        let long = vec![7u8; 70_000];
+        encode(&mut buf, OP_BINARY, b"hello");
+        encode(&mut buf, OP_PING, &long[..300]);
+        encode(&mut buf, OP_BINARY, &long);
+        let mut partial = buf.split_to(3);
+        assert!(decode(&mut partial).unwrap().is_none());
+        partial.unsplit(buf);
+        let (opcode, payload) = decode(&mut partial).unwrap().unwrap();
+        assert_eq!((opcode, &payload[..]), (OP_BINARY, &b"hello"[..]));
+        let (opcode, payload) = decode(&mut partial).unwrap().unwrap();
+        assert_eq!((opcode, &payload[..]), (OP_PING, &long[..300]));
+        let (opcode, payload) = decode(&mut partial).unwrap().unwrap();
+        assert_eq!((opcode, &payload[..]), (OP_BINARY, &long[..]));
+        assert!(partial.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let (client, mut server) = tokio::io::duplex(1 << 20);
+        // a ping and a message of the server, unmasked
+        let mut sent = BytesMut::new();
+        sent.put_slice(&[0x80 | OP_PING, 1, 9]);
+        sent.put_slice(&[0x80 | OP_BINARY, 3]);
+        sent.put_slice(b"abc");
+        server.write_all(&sent).await.unwrap();
+        let mut stream = WsStream::new(client, BytesMut::new());
+        let mut buf = [0u8; 3];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"abc");
+        stream.write_all(b"xyz").await.unwrap();
+        stream.flush().await.unwrap();
+        let mut received = BytesMut::new();
+        while received.len() < 16 {
+            server.read_buf(&mut received).await.unwrap();
+        }
+        let (opcode, payload) = decode(&mut received).unwrap().unwrap();
+        assert_eq!((opcode, &payload[..]), (OP_PONG, &[9u8][..]));
+        let (opcode, payload) = decode(&mut received).unwrap().unwrap();
+        assert_eq!((opcode, &payload[..]), (OP_BINARY, &b"xyz"[..]));
+        server.write_all(&[0x80 | OP_CLOSE, 0]).await.unwrap();
+        assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,143 @@
+use docopt::Docopt;
+use scrap::{
+    cpu_features, scale_argb, set_simd_enabled, swap_red_blue, ARGBToI420, ARGBToI444, ScaleFilter,
+};
+use std::time::{Duration, Instant};
+
+// cargo run --package scrap --example convert_benchmark --release
+
+const USAGE: &'static str = "
+Color conversion and scaling benchmark, plain C kernels vs SIMD kernels.
+
+Usage:
+  convert_benchmark [--width=WIDTH] [--height=HEIGHT] [--count=COUNT]
+  convert_benchmark (-h | --help)
+
+Options:
+  -h --help             Show this screen.
+  --width=WIDTH         Frame width [default: 3840].
+  --height=HEIGHT       Frame height [default: 2160].
+  --count=COUNT         Run count of each operation [default: 50].
+";
+
+#[derive(Debug, serde::Deserialize, Clone, Copy)]
+struct Args {
+    flag_width: usize,
+    flag_height: usize,
+    flag_count: usize,
+}
+
+fn main() {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+    let (w, h, count) = (args.flag_width, args.flag_height, args.flag_count);
+    println!(
+        "benchmark {}x{}, count: {}, cpu features: {:?}",
+        w,
+        h,
+        count,
+        cpu_features()
+    );
+    let mut bgra = Vec::with_capacity(w * h * 4);
+    for y in 0..h {
+        for x in 0..w {
+            bgra.extend_from_slice(&[x as u8, y as u8, (x ^ y) as u8, 255]);
+        }
+    }
+
+    let ops: Vec<(&str, Box<dyn Fn(&[u8], &mut Vec<u8>)>)> = vec![
+        (
+            "BGRA -> I420",
+            Box::new(move |src, dst| {
+                dst.resize(w * h * 2, 0);
+                let (y, uv) = dst.split_at_mut(w * h);
+                let (u, v) = uv.split_at_mut(w * h / 4);
+                unsafe {
+                    ARGBToI420(
+                        src.as_ptr(),
+                        (w * 4) as _,
+                        y.as_mut_ptr(),
+                        w as _,
+                        u.as_mut_ptr(),
+                        (w / 2) as _,
+                        v.as_mut_ptr(),
+                        (w / 2) as _,
+                        w as _,
+                        h as _,
+                    );
+                }
+            }),
+        ),
+        (
+            "BGRA -> I444",
+            Box::new(move |src, dst| {
+                dst.resize(w * h * 3, 0);
+                let (y, uv) = dst.split_at_mut(w * h);
+                let (u, v) = uv.split_at_mut(w * h);
+                unsafe {
+                    ARGBToI444(
+                        src.as_ptr(),
+                        (w * 4) as _,
+                        y.as_mut_ptr(),
+                        w as _,
+                        u.as_mut_ptr(),
+                        w as _,
+                        v.as_mut_ptr(),
+                        w as _,
+                        w as _,
+                        h as _,
+                    );
+                }
+            }),
+        ),
+        (
+            "BGRA -> RGBA",
+            Box::new(move |src, dst| {
+                swap_red_blue(src, w * 4, w, h, dst).unwrap();
+            }),
+        ),
+        (
+            "scale 1/2 box",
+            Box::new(move |src, dst| {
+                scale_argb(src, w * 4, (w, h), dst, (w / 2, h / 2), ScaleFilter::Box).unwrap();
+            }),
+        ),
+        (
+            "scale 2/3 bilinear",
+            Box::new(move |src, dst| {
+                scale_argb(
+                    src,
+                    w * 4,
+                    (w, h),
+                    dst,
+                    (w * 2 / 3, h * 2 / 3),
+                    ScaleFilter::Bilinear,
+                )
+                .unwrap();
+            }),
+        ),
+    ];
+
+    let mut dst = Vec::new();
+    for (name, op) in ops.iter() {
+        let mut times = [Duration::ZERO; 2];
+        for (i, simd) in [false, true].iter().enumerate() {
+            set_simd_enabled(*simd);
+            op(&bgra, &mut dst); // warm up
+            let start = Instant::now();
+            for _ in 0..count {
+                op(&bgra, &mut dst);
+            }
+            times[i] = start.elapsed() / count as _;
+        }
+        println!(
+            "{:<20} C: {:?}, SIMD: {:?}, speedup: {:.2}x",
+            name,
+            times[0],
+            times[1],
+            times[0].as_secs_f64() / times[1].as_secs_f64().max(f64::EPSILON)
+        );
+    }
+    set_simd_enabled(true);
+}
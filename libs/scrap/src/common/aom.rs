@@ -6,7 +6,7 @@
 
 include!(concat!(env!("OUT_DIR"), "/aom_ffi.rs"));
 
-use crate::codec::{base_bitrate, codec_thread_num, Quality};
+use crate::codec::{base_bitrate, codec_thread_num, Quality, Roi};
 use crate::{codec::EncoderApi, EncodeFrame, STRIDE_ALIGN};
 use crate::{common::GoogleImage, generate_call_macro, generate_call_ptr_macro, Error, Result};
 use crate::{EncodeInput, EncodeYuvFormat, Pixfmt};
@@ -309,7 +309,9 @@ impl EncoderApi for AomEncoder {
         false
     }
 
-    fn disable(&self) {}
+    fn set_roi(&mut self, _roi: Option<Roi>) -> ResultType<()> {
+        Ok(())
+    }
 }
 
 impl AomEncoder {
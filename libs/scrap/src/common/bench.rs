@@ -0,0 +1,483 @@
+// Codec benchmark on synthetic screen content, run by --bench-codecs.
+//
+// Every encoder available on the machine encodes the same sequence at the balanced quality, and
+// the output is decoded again to measure the quality. The encoders fast enough for real time are
+// ranked by quality and bitrate. The result is stored in the codec-benchmark option, the auto
+// codec selection and the encoder fallback use its order instead of the fixed one.
+//
+// The vram encoders take captured textures and are not benchmarked.
+#![cfg_attr(target_os = "ios", allow(dead_code))]
+
+#[cfg(feature = "hwcodec")]
+use crate::hwcodec::{HwRamEncoder, HwRamEncoderConfig};
+use crate::{
+    codec::{EncoderCfg, Quality},
+    fallback, CodecFormat, Pixfmt, TraitPixelBuffer, VpxEncoderConfig, VpxVideoCodecId,
+};
+use hbb_common::{
+    config::{keys::OPTION_CODEC_BENCHMARK, Config},
+    message_proto::video_frame,
+    serde_derive::{Deserialize, Serialize},
+    serde_json,
+};
+
+pub const WIDTH: usize = 1920;
+pub const HEIGHT: usize = 1080;
+pub const COUNT: usize = 90;
+const FPS: usize = 30;
+// Slower encoders would drop frames in a session.
+const MIN_FPS: f64 = FPS as f64;
+// The psnr in dB a doubled bitrate is worth, a rough figure for screen content.
+const DB_PER_DOUBLE_BITRATE: f64 = 3.0;
+// Scrolled pixels per frame.
+const SCROLL: usize = 4;
+const LINE_HEIGHT: usize = 16;
+const GLYPH_WIDTH: usize = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchResult {
+    // The encoder name, same as the patterns of the fallback chain, e.g. vp9, h264_nvenc
+    pub name: String,
+    // CodecFormat::to_string, Unknown if there is no output
+    pub format: String,
+    pub fps: f64,
+    pub kbps: u32,
+    // None if the output can't be decoded on this machine
+    pub psnr: Option<f64>,
+    pub error: Option<String>,
+}
+
+impl BenchResult {
+    fn realtime(&self) -> bool {
+        self.error.is_none() && self.fps >= MIN_FPS
+    }
+
+    fn score(&self) -> f64 {
+        self.psnr.unwrap_or_default() - DB_PER_DOUBLE_BITRATE * (self.kbps.max(1) as f64).log2()
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Benchmark {
+    pub width: usize,
+    pub height: usize,
+    pub results: Vec<BenchResult>,
+}
+
+impl Benchmark {
+    /// The real time encoders, the best first. The ones whose quality is unknown go last.
+    pub fn ranked(&self) -> Vec<&BenchResult> {
+        let mut v: Vec<_> = self.results.iter().filter(|r| r.realtime()).collect();
+        v.sort_by(|a, b| {
+            a.psnr
+                .is_none()
+                .cmp(&b.psnr.is_none())
+                .then(b.score().total_cmp(&a.score()))
+        });
+        v
+    }
+
+    /// The formats of the ranked encoders, the best first.
+    pub fn formats(&self) -> Vec<CodecFormat> {
+        let mut v = vec![];
+        for r in self.ranked() {
+            let format = parse_format(&r.format);
+            if format != CodecFormat::Unknown && !v.contains(&format) {
+                v.push(format);
+            }
+        }
+        v
+    }
+
+    /// The names of the ranked encoders, used as the fallback chain if it is not set.
+    pub fn encoders(&self) -> Vec<String> {
+        self.ranked().iter().map(|r| r.name.clone()).collect()
+    }
+}
+
+fn parse_format(s: &str) -> CodecFormat {
+    [
+        CodecFormat::VP8,
+        CodecFormat::VP9,
+        CodecFormat::AV1,
+        CodecFormat::H264,
+        CodecFormat::H265,
+    ]
+    .iter()
+    .find(|f| f.to_string() == s)
+    .copied()
+    .unwrap_or(CodecFormat::Unknown)
+}
+
+/// The stored result of the last benchmark.
+pub fn load() -> Option<Benchmark> {
+    let s = Config::get_option(OPTION_CODEC_BENCHMARK);
+    if s.is_empty() {
+        return None;
+    }
+    serde_json::from_str(&s).ok()
+}
+
+struct Synthetic {
+    data: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+impl TraitPixelBuffer for Synthetic {
+    fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn stride(&self) -> Vec<usize> {
+        vec![self.width * 4]
+    }
+
+    fn pixfmt(&self) -> Pixfmt {
+        Pixfmt::BGRA
+    }
+}
+
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846ca68b);
+    x ^ (x >> 16)
+}
+
+fn fill(frame: &mut Synthetic, x: usize, y: usize, w: usize, h: usize, bgra: [u8; 4]) {
+    let (fw, fh) = (frame.width, frame.height);
+    for y in y.min(fh)..(y + h).min(fh) {
+        for x in x.min(fw)..(x + w).min(fw) {
+            let i = (y * fw + x) * 4;
+            frame.data[i..i + 4].copy_from_slice(&bgra);
+        }
+    }
+}
+
+// A desktop with a window of scrolling text, a picture moving over it and a cursor. The same
+// index always draws the same frame, so the decoded frames can be compared without keeping them.
+fn draw(frame: &mut Synthetic, index: usize) {
+    let (w, h) = (frame.width, frame.height);
+    fill(frame, 0, 0, w, h, [0xa0, 0x70, 0x30, 0xff]);
+    let (wx, wy, ww, wh) = (w / 8, h / 8, w * 3 / 4, h * 3 / 4);
+    fill(frame, wx, wy, ww, wh, [0xff, 0xff, 0xff, 0xff]);
+    fill(frame, wx, wy, ww, 24, [0xd0, 0x90, 0x40, 0xff]);
+    // text
+    let scroll = index * SCROLL;
+    for y in wy + 32..wy + wh - 8 {
+        let line = (y - wy + scroll) / LINE_HEIGHT;
+        let gy = (y - wy + scroll) % LINE_HEIGHT;
+        if gy >= 12 {
+            continue;
+        }
+        let line_len = 20 + hash(line as u32) as usize % 80;
+        for x in wx + 8..wx + ww - 8 {
+            let col = (x - wx - 8) / GLYPH_WIDTH;
+            if col >= line_len {
+                break;
+            }
+            // a small set of glyphs, repeated like letters
+            let glyph = hash((line * 131 + col) as u32) % 48;
+            if glyph < 6 {
+                continue; // space
+            }
+            let gx = (x - wx - 8) % GLYPH_WIDTH;
+            if gx >= 6 || hash(glyph * 97 + (gy * 6 + gx) as u32) % 3 != 0 {
+                continue;
+            }
+            let i = (y * w + x) * 4;
+            frame.data[i..i + 4].copy_from_slice(&[0x20, 0x20, 0x20, 0xff]);
+        }
+    }
+    // picture
+    let (pw, ph) = (w / 6, h / 6);
+    let px = (index * 8) % (w - pw).max(1);
+    let py = h / 2;
+    for y in py..(py + ph).min(h) {
+        for x in px..px + pw {
+            let (dx, dy) = (x - px, y - py);
+            let i = (y * w + x) * 4;
+            frame.data[i..i + 4].copy_from_slice(&[
+                (dx * 255 / pw) as u8,
+                (dy * 255 / ph) as u8,
+                ((dx + dy + index) % 256) as u8,
+                0xff,
+            ]);
+        }
+    }
+    // cursor
+    let cx = (index * 13) % w;
+    let cy = (index * 7) % h;
+    fill(frame, cx, cy, 12, 18, [0, 0, 0, 0xff]);
+}
+
+// psnr of the color channels, the decoded image is BGRA with the alignment of `rgb`
+fn psnr(src: &Synthetic, rgb: &crate::ImageRgb) -> Option<f64> {
+    let (w, h) = (src.width.min(rgb.w), src.height.min(rgb.h));
+    let align = rgb.align().max(1);
+    let stride = (rgb.w * 4 + align - 1) & !(align - 1);
+    if w == 0 || h == 0 || rgb.raw.len() < stride * rgb.h {
+        return None;
+    }
+    let mut sum = 0u64;
+    for y in 0..h {
+        let a = &src.data[y * src.width * 4..(y * src.width + w) * 4];
+        let b = &rgb.raw[y * stride..y * stride + w * 4];
+        for (a, b) in a.chunks_exact(4).zip(b.chunks_exact(4)) {
+            for c in 0..3 {
+                let d = a[c] as i64 - b[c] as i64;
+                sum += (d * d) as u64;
+            }
+        }
+    }
+    let mse = sum as f64 / (w * h * 3) as f64;
+    if mse == 0.0 {
+        return Some(99.0);
+    }
+    Some(10.0 * (255.0 * 255.0 / mse).log10())
+}
+
+fn configs(width: usize, height: usize) -> Vec<EncoderCfg> {
+    let quality = Quality::Balanced;
+    let mut v = vec![];
+    for codec in [VpxVideoCodecId::VP8, VpxVideoCodecId::VP9] {
+        v.push(EncoderCfg::VPX(VpxEncoderConfig {
+            width: width as _,
+            height: height as _,
+            quality,
+            codec,
+            keyframe_interval: None,
+        }));
+    }
+    if !crate::codec::disable_av1() {
+        v.push(EncoderCfg::AOM(crate::aom::AomEncoderConfig {
+            width: width as _,
+            height: height as _,
+            quality,
+            keyframe_interval: None,
+        }));
+    }
+    #[cfg(feature = "hwcodec")]
+    if crate::codec::enable_hwcodec_option() {
+        #[cfg(not(target_os = "android"))]
+        if !crate::hwcodec::HwCodecConfig::already_set() {
+            crate::hwcodec::HwCodecConfig::set(crate::hwcodec::check_available_hwcodec());
+        }
+        for format in [CodecFormat::H264, CodecFormat::H265] {
+            for info in HwRamEncoder::all(format) {
+                v.push(EncoderCfg::HWRAM(HwRamEncoderConfig {
+                    name: info.name,
+                    mc_name: info.mc_name,
+                    width,
+                    height,
+                    quality,
+                    keyframe_interval: None,
+                }));
+            }
+        }
+    }
+    v
+}
+
+fn encoded(frame: &video_frame::Union) -> Option<(i64, usize)> {
+    use video_frame::Union::*;
+    match frame {
+        Vp8s(f) | Vp9s(f) | Av1s(f) | H264s(f) | H265s(f) => Some((
+            f.frames.last()?.pts,
+            f.frames.iter().map(|f| f.data.len()).sum(),
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_os = "ios"))]
+fn bench(cfg: EncoderCfg, width: usize, height: usize, count: usize) -> BenchResult {
+    use crate::codec::{Decoder, Encoder};
+    use std::time::{Duration, Instant};
+
+    let mut result = BenchResult {
+        name: fallback::name(&cfg),
+        format: CodecFormat::Unknown.to_string(),
+        fps: 0.0,
+        kbps: 0,
+        psnr: None,
+        error: None,
+    };
+    let mut encoder = match Encoder::new(cfg, false) {
+        Ok(encoder) => encoder,
+        Err(e) => {
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+    let mut frame = Synthetic {
+        data: vec![0; width * height * 4],
+        width,
+        height,
+    };
+    let (mut yuv, mut mid_data) = (vec![], vec![]);
+    let mut decoder: Option<Decoder> = None;
+    let mut rgb = crate::ImageRgb::new(crate::ImageFormat::ARGB, 1);
+    let (mut bytes, mut encode_time, mut psnrs) = (0, Duration::ZERO, vec![]);
+    let mut last_error = None;
+    for index in 0..count {
+        draw(&mut frame, index);
+        if let Err(e) = crate::convert_to_yuv(&frame, encoder.yuvfmt(), &mut yuv, &mut mid_data) {
+            result.error = Some(e.to_string());
+            return result;
+        }
+        let ms = (index * 1000 / FPS) as i64;
+        let start = Instant::now();
+        let vf = encoder.encode_to_message(crate::EncodeInput::YUV(&yuv), ms);
+        encode_time += start.elapsed();
+        // no output for some frames is normal for the encoders with latency
+        let union = match vf.map(|vf| vf.union) {
+            Ok(Some(union)) => union,
+            Ok(None) => continue,
+            Err(e) => {
+                last_error = Some(e.to_string());
+                continue;
+            }
+        };
+        let Some((pts, len)) = encoded(&union) else {
+            continue;
+        };
+        bytes += len;
+        let format = CodecFormat::from(&union);
+        result.format = format.to_string();
+        let decoder = decoder.get_or_insert_with(|| Decoder::new(format, None));
+        let decoded = decoder.handle_video_frame(
+            &union,
+            &mut rgb,
+            &mut Default::default(),
+            &mut true,
+            &mut None,
+        );
+        if let Ok(true) = decoded {
+            // compare to the frame of the decoded pts, not the last input
+            let decoded_index = ((pts.max(0) as usize * FPS + 500) / 1000).min(index);
+            if decoded_index != index {
+                draw(&mut frame, decoded_index);
+            }
+            psnrs.extend(psnr(&frame, &rgb));
+        }
+    }
+    if bytes == 0 {
+        result.error = Some(last_error.unwrap_or("no output".to_owned()));
+        return result;
+    }
+    result.fps = count as f64 / encode_time.as_secs_f64().max(f64::EPSILON);
+    result.kbps = (bytes * 8 * FPS / count / 1000) as u32;
+    if !psnrs.is_empty() {
+        result.psnr = Some(psnrs.iter().sum::<f64>() / psnrs.len() as f64);
+    }
+    result
+}
+
+/// Encode `count` frames of `width` x `height` with every available encoder.
+///
+/// `progress` is called with the result of each encoder as soon as it is measured.
+#[cfg(not(target_os = "ios"))]
+pub fn run(
+    width: usize,
+    height: usize,
+    count: usize,
+    mut progress: impl FnMut(&BenchResult),
+) -> Benchmark {
+    let mut benchmark = Benchmark {
+        width,
+        height,
+        results: vec![],
+    };
+    for cfg in configs(width, height) {
+        let result = bench(cfg, width, height, count);
+        progress(&result);
+        benchmark.results.push(result);
+    }
+    benchmark
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(
+        name: &str,
+        format: CodecFormat,
+        fps: f64,
+        kbps: u32,
+        psnr: Option<f64>,
+    ) -> BenchResult {
+        BenchResult {
+            name: name.to_owned(),
+            format: format.to_string(),
+            fps,
+            kbps,
+            psnr,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_rank() {
+        let benchmark = Benchmark {
+            width: WIDTH,
+            height: HEIGHT,
+            results: vec![
+                result("vp9", CodecFormat::VP9, 60.0, 2000, Some(40.0)),
+                // too slow
+                result("av1", CodecFormat::AV1, 10.0, 1000, Some(42.0)),
+                // the same quality at half the bitrate
+                result("h264_nvenc", CodecFormat::H264, 200.0, 1000, Some(40.0)),
+                // quality unknown
+                result("hevc_qsv", CodecFormat::H265, 200.0, 500, None),
+                result("vp8", CodecFormat::VP8, 100.0, 3000, Some(38.0)),
+            ],
+        };
+        assert_eq!(
+            benchmark.encoders(),
+            vec!["h264_nvenc", "vp9", "vp8", "hevc_qsv"]
+        );
+        assert_eq!(
+            benchmark.formats(),
+            vec![
+                CodecFormat::H264,
+                CodecFormat::VP9,
+                CodecFormat::VP8,
+                CodecFormat::H265
+            ]
+        );
+        let s = serde_json::to_string(&benchmark).unwrap();
+        let benchmark: Benchmark = serde_json::from_str(&s).unwrap();
+        assert_eq!(benchmark.results.len(), 5);
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut a = Synthetic {
+            data: vec![0; 320 * 240 * 4],
+            width: 320,
+            height: 240,
+        };
+        let mut b = Synthetic {
+            data: vec![0; 320 * 240 * 4],
+            width: 320,
+            height: 240,
+        };
+        draw(&mut a, 5);
+        draw(&mut b, 5);
+        assert_eq!(a.data, b.data);
+        draw(&mut b, 6);
+        assert_ne!(a.data, b.data);
+    }
+}
@@ -13,7 +13,9 @@ use crate::mediacodec::{MediaCodecDecoder, H264_DECODER_SUPPORT, H265_DECODER_SU
 use crate::vram::*;
 use crate::{
     aom::{self, AomDecoder, AomEncoder, AomEncoderConfig},
+    bench,
     common::GoogleImage,
+    fallback,
     vpxcodec::{self, VpxDecoder, VpxDecoderConfig, VpxEncoder, VpxEncoderConfig, VpxVideoCodecId},
     CodecFormat, EncodeInput, EncodeYuvFormat, ImageRgb, ImageTexture,
 };
@@ -75,11 +77,21 @@ pub trait EncoderApi {
 
     fn is_hardware(&self) -> bool;
 
-    fn disable(&self);
+    fn set_roi(&mut self, roi: Option<Roi>) -> ResultType<()>;
+}
+
+/// Region of the frame in pixels which is encoded with higher quality than the rest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Roi {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
 }
 
 pub struct Encoder {
     pub codec: Box<dyn EncoderApi>,
+    name: String,
 }
 
 impl Deref for Encoder {
@@ -129,23 +141,26 @@ pub enum EncodingUpdate {
 impl Encoder {
     pub fn new(config: EncoderCfg, i444: bool) -> ResultType<Encoder> {
         log::info!("new encoder: {config:?}, i444: {i444}");
+        let name = fallback::name(&config);
         match config {
             EncoderCfg::VPX(_) => Ok(Encoder {
                 codec: Box::new(VpxEncoder::new(config, i444)?),
+                name,
             }),
             EncoderCfg::AOM(_) => Ok(Encoder {
                 codec: Box::new(AomEncoder::new(config, i444)?),
+                name,
             }),
 
             #[cfg(feature = "hwcodec")]
             EncoderCfg::HWRAM(_) => match HwRamEncoder::new(config, i444) {
                 Ok(hw) => Ok(Encoder {
                     codec: Box::new(hw),
+                    name,
                 }),
                 Err(e) => {
-                    log::error!("new hw encoder failed: {e:?}, clear config");
-                    HwCodecConfig::clear(false, true);
-                    *ENCODE_CODEC_FORMAT.lock().unwrap() = CodecFormat::VP9;
+                    log::error!("new hw encoder failed: {e:?}");
+                    Self::report_failure_by_name(&name, &e.to_string());
                     Err(e)
                 }
             },
@@ -153,17 +168,32 @@ impl Encoder {
             EncoderCfg::VRAM(_) => match VRamEncoder::new(config, i444) {
                 Ok(tex) => Ok(Encoder {
                     codec: Box::new(tex),
+                    name,
                 }),
                 Err(e) => {
-                    log::error!("new vram encoder failed: {e:?}, clear config");
-                    HwCodecConfig::clear(true, true);
-                    *ENCODE_CODEC_FORMAT.lock().unwrap() = CodecFormat::VP9;
+                    log::error!("new vram encoder failed: {e:?}");
+                    Self::report_failure_by_name(&name, &e.to_string());
                     Err(e)
                 }
             },
         }
     }
 
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Stop using the encoder until its cooldown expires, and negotiate the codec again.
+    pub fn report_failure(&self, reason: &str) {
+        Self::report_failure_by_name(&self.name, reason);
+    }
+
+    fn report_failure_by_name(name: &str, reason: &str) {
+        fallback::report_failure(name, reason);
+        Self::update(EncodingUpdate::Check);
+    }
+
     pub fn update(update: EncodingUpdate) {
         log::info!("update:{:?}", update);
         let mut decodings = PEER_DECODINGS.lock().unwrap();
@@ -200,15 +230,16 @@ impl Encoder {
         let mut h265vram_encoding = false;
         #[cfg(feature = "vram")]
         if enable_vram_option(true) {
+            let healthy = |format| {
+                VRamEncoder::available(format)
+                    .iter()
+                    .any(|f| fallback::is_healthy(&fallback::vram_name(f)))
+            };
             if _all_support_h264_decoding {
-                if VRamEncoder::available(CodecFormat::H264).len() > 0 {
-                    h264vram_encoding = true;
-                }
+                h264vram_encoding = healthy(CodecFormat::H264);
             }
             if _all_support_h265_decoding {
-                if VRamEncoder::available(CodecFormat::H265).len() > 0 {
-                    h265vram_encoding = true;
-                }
+                h265vram_encoding = healthy(CodecFormat::H265);
             }
         }
         #[allow(unused_mut)]
@@ -217,13 +248,17 @@ impl Encoder {
         let mut h265hw_encoding: Option<String> = None;
         #[cfg(feature = "hwcodec")]
         if enable_hwcodec_option() {
+            let healthy = |format| {
+                HwRamEncoder::all(format)
+                    .drain(..)
+                    .find(|c| fallback::is_healthy(&c.name.to_lowercase()))
+                    .map(|c| c.name)
+            };
             if _all_support_h264_decoding {
-                h264hw_encoding =
-                    HwRamEncoder::try_get(CodecFormat::H264).map_or(None, |c| Some(c.name));
+                h264hw_encoding = healthy(CodecFormat::H264);
             }
             if _all_support_h265_decoding {
-                h265hw_encoding =
-                    HwRamEncoder::try_get(CodecFormat::H265).map_or(None, |c| Some(c.name));
+                h265hw_encoding = healthy(CodecFormat::H265);
             }
         }
         let h264_useable =
@@ -280,6 +315,22 @@ impl Encoder {
                 auto_codec = CodecFormat::VP8
             }
         }
+        // the order measured by --bench-codecs, the vram encoders are not measured
+        let auto_is_vram = auto_codec == CodecFormat::H264 && h264hw_encoding.is_none()
+            || auto_codec == CodecFormat::H265 && h265hw_encoding.is_none();
+        if let Some(benchmark) = bench::load().filter(|_| !auto_is_vram) {
+            let useable = |format: &CodecFormat| match format {
+                CodecFormat::VP8 => vp8_useable,
+                CodecFormat::VP9 => true,
+                CodecFormat::AV1 => av1_useable,
+                CodecFormat::H264 => _all_support_h264_decoding && h264hw_encoding.is_some(),
+                CodecFormat::H265 => _all_support_h265_decoding && h265hw_encoding.is_some(),
+                CodecFormat::Unknown => false,
+            };
+            if let Some(format) = benchmark.formats().into_iter().find(useable) {
+                auto_codec = format;
+            }
+        }
 
         *format = match preference {
             PreferCodec::VP8 => CodecFormat::VP8,
@@ -977,7 +1028,7 @@ pub fn codec_thread_num(limit: usize) -> usize {
     res
 }
 
-fn disable_av1() -> bool {
+pub(crate) fn disable_av1() -> bool {
     // aom is very slow for x86 sciter version on windows x64
     // disable it for all 32 bit platforms
     std::mem::size_of::<usize>() == 4
@@ -6,8 +6,6 @@
 
 include!(concat!(env!("OUT_DIR"), "/yuv_ffi.rs"));
 
-#[cfg(not(target_os = "ios"))]
-use crate::PixelBuffer;
 use crate::{generate_call_macro, EncodeYuvFormat, TraitPixelBuffer};
 use hbb_common::{bail, log, ResultType};
 
@@ -15,11 +13,12 @@ generate_call_macro!(call_yuv, false);
 
 #[cfg(not(target_os = "ios"))]
 pub fn convert_to_yuv(
-    captured: &PixelBuffer,
+    captured: &impl TraitPixelBuffer,
     dst_fmt: EncodeYuvFormat,
     dst: &mut Vec<u8>,
     mid_data: &mut Vec<u8>,
 ) -> ResultType<()> {
+    log_cpu_features();
     let src = captured.data();
     let src_stride = captured.stride();
     let src_pixfmt = captured.pixfmt();
@@ -197,3 +196,133 @@ pub fn convert_to_yuv(
     }
     Ok(())
 }
+
+/// Scaling quality, from fastest to smoothest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleFilter {
+    Nearest,
+    Bilinear,
+    Box,
+}
+
+impl ScaleFilter {
+    fn mode(self) -> FilterMode {
+        match self {
+            ScaleFilter::Nearest => FilterMode::kFilterNone,
+            ScaleFilter::Bilinear => FilterMode::kFilterBilinear,
+            ScaleFilter::Box => FilterMode::kFilterBox,
+        }
+    }
+}
+
+fn check_argb(src: &[u8], src_stride: usize, width: usize, height: usize) -> ResultType<()> {
+    if width == 0 || height == 0 {
+        bail!("empty image: {}x{}", width, height);
+    }
+    if src_stride < width * 4 {
+        bail!("src_stride too small: {} < {}", src_stride, width * 4);
+    }
+    if src.len() < src_stride * (height - 1) + width * 4 {
+        bail!("wrong src len, {} < {} * {}", src.len(), src_stride, height);
+    }
+    Ok(())
+}
+
+/// Scale a 4 bytes per pixel image, the channel order is kept.
+///
+/// `dst` is packed, its stride is `dst_size.0 * 4`.
+pub fn scale_argb(
+    src: &[u8],
+    src_stride: usize,
+    src_size: (usize, usize),
+    dst: &mut Vec<u8>,
+    dst_size: (usize, usize),
+    filter: ScaleFilter,
+) -> ResultType<()> {
+    let (src_width, src_height) = src_size;
+    let (dst_width, dst_height) = dst_size;
+    check_argb(src, src_stride, src_width, src_height)?;
+    if dst_width == 0 || dst_height == 0 {
+        bail!("empty dst: {}x{}", dst_width, dst_height);
+    }
+    dst.resize(dst_width * dst_height * 4, 0);
+    call_yuv!(ARGBScale(
+        src.as_ptr(),
+        src_stride as _,
+        src_width as _,
+        src_height as _,
+        dst.as_mut_ptr(),
+        (dst_width * 4) as _,
+        dst_width as _,
+        dst_height as _,
+        filter.mode(),
+    ));
+    Ok(())
+}
+
+/// Swap the red and blue channels, BGRA <-> RGBA.
+///
+/// `dst` is packed, its stride is `width * 4`.
+pub fn swap_red_blue(
+    src: &[u8],
+    src_stride: usize,
+    width: usize,
+    height: usize,
+    dst: &mut Vec<u8>,
+) -> ResultType<()> {
+    check_argb(src, src_stride, width, height)?;
+    dst.resize(width * height * 4, 0);
+    // ABGRToARGB is the same shuffle as ARGBToABGR
+    call_yuv!(ABGRToARGB(
+        src.as_ptr(),
+        src_stride as _,
+        dst.as_mut_ptr(),
+        (width * 4) as _,
+        width as _,
+        height as _,
+    ));
+    Ok(())
+}
+
+/// The SIMD instruction sets libyuv detected on this cpu, they are selected at runtime.
+pub fn cpu_features() -> Vec<&'static str> {
+    let flags = unsafe { InitCpuFlags() };
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    let features = [
+        (kCpuHasSSE2, "SSE2"),
+        (kCpuHasSSSE3, "SSSE3"),
+        (kCpuHasSSE41, "SSE4.1"),
+        (kCpuHasAVX, "AVX"),
+        (kCpuHasAVX2, "AVX2"),
+        (kCpuHasAVX512BW, "AVX512BW"),
+    ];
+    #[cfg(any(target_arch = "arm", target_arch = "aarch64"))]
+    let features = [(kCpuHasNEON, "NEON")];
+    #[cfg(not(any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_arch = "arm",
+        target_arch = "aarch64"
+    )))]
+    let features: [(i32, &str); 0] = [];
+    features
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+/// Disable the SIMD kernels to compare with the plain C ones, enabled by default.
+pub fn set_simd_enabled(enabled: bool) {
+    // 0 resets to auto detection, 1 keeps only the initialized bit.
+    unsafe {
+        MaskCpuFlags(if enabled { 0 } else { 1 });
+    }
+}
+
+pub fn log_cpu_features() {
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| {
+        log::info!("libyuv cpu features: {:?}", cpu_features());
+    });
+}
@@ -66,6 +66,11 @@ impl TraitCapturer for Capturer {
     fn set_output_texture(&mut self, texture: bool) {
         self.inner.set_output_texture(texture);
     }
+
+    #[cfg(feature = "vram")]
+    fn texture_supported(&self) -> bool {
+        self.inner.texture_supported()
+    }
 }
 
 pub struct PixelBuffer<'a> {
@@ -254,4 +259,9 @@ impl TraitCapturer for CapturerMag {
 
     #[cfg(feature = "vram")]
     fn set_output_texture(&mut self, _texture: bool) {}
+
+    #[cfg(feature = "vram")]
+    fn texture_supported(&self) -> bool {
+        false
+    }
 }
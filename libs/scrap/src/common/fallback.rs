@@ -0,0 +1,214 @@
+// Encoder fallback policy.
+//
+// An encoder that fails is not used again until its cooldown expires, and the cooldown doubles
+// with each failure in a row. The encoder is selected from the candidates of the negotiated codec
+// by the fallback chain option, a comma separated list of encoder name patterns like
+// "nvenc,qsv,vp9". The candidates not matched by the chain keep the default order after the
+// matched ones. Without the option, the encoders ranked by --bench-codecs are the chain.
+
+use crate::{codec::EncoderCfg, vpxcodec::VpxVideoCodecId};
+use hbb_common::{
+    config::{keys::OPTION_ENCODER_FALLBACK_CHAIN, Config},
+    lazy_static, log,
+};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const COOLDOWN: Duration = Duration::from_secs(30);
+pub const MAX_COOLDOWN: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Copy)]
+struct Health {
+    failures: u32,
+    failed_at: Instant,
+    expired: bool,
+}
+
+impl Health {
+    fn cooldown(&self) -> Duration {
+        let times = 1u32 << self.failures.saturating_sub(1).min(5);
+        (COOLDOWN * times).min(MAX_COOLDOWN)
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref HEALTH: Mutex<HashMap<String, Health>> = Default::default();
+    // Changes when an encoder fails or its cooldown expires, the video services compare it to
+    // know when to select the encoder again.
+    static ref GENERATION: Mutex<u64> = Default::default();
+}
+
+/// The name of the encoder, matched by the patterns of the fallback chain.
+///
+/// e.g. vp9, av1, h264_nvenc, hevc_qsv, h264_amf_vram
+pub fn name(cfg: &EncoderCfg) -> String {
+    match cfg {
+        EncoderCfg::VPX(vpx) => match vpx.codec {
+            VpxVideoCodecId::VP8 => "vp8".to_owned(),
+            VpxVideoCodecId::VP9 => "vp9".to_owned(),
+        },
+        EncoderCfg::AOM(_) => "av1".to_owned(),
+        #[cfg(feature = "hwcodec")]
+        EncoderCfg::HWRAM(hw) => hw.name.to_lowercase(),
+        #[cfg(feature = "vram")]
+        EncoderCfg::VRAM(vram) => vram_name(&vram.feature),
+    }
+}
+
+#[cfg(feature = "vram")]
+pub fn vram_name(feature: &hwcodec::vram::FeatureContext) -> String {
+    use hwcodec::common::{AdapterVendor::*, DataFormat, Driver};
+
+    let codec = match feature.data_format {
+        DataFormat::H264 => "h264",
+        DataFormat::H265 => "hevc",
+        _ => "unknown",
+    };
+    let vendor = match feature.driver {
+        Driver::NV => "nvenc",
+        Driver::AMF => "amf",
+        Driver::MFX => "qsv",
+        Driver::FFMPEG => match feature.vendor {
+            ADAPTER_VENDOR_NVIDIA => "nvenc",
+            ADAPTER_VENDOR_AMD => "amf",
+            ADAPTER_VENDOR_INTEL => "qsv",
+            _ => "ffmpeg",
+        },
+    };
+    format!("{codec}_{vendor}_vram")
+}
+
+fn chain() -> Vec<String> {
+    let chain: Vec<String> = Config::get_option(OPTION_ENCODER_FALLBACK_CHAIN)
+        .split(',')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if chain.is_empty() {
+        return crate::bench::load()
+            .map(|b| b.encoders())
+            .unwrap_or_default();
+    }
+    chain
+}
+
+fn rank(name: &str, chain: &[String]) -> usize {
+    chain
+        .iter()
+        .position(|pattern| name.contains(pattern.as_str()))
+        .unwrap_or(chain.len())
+}
+
+/// Select the first healthy candidate in the order of the fallback chain.
+///
+/// The candidates are in the default order, `None` if all of them are cooling down.
+pub fn select(candidates: Vec<EncoderCfg>) -> Option<EncoderCfg> {
+    select_with_chain(candidates, &chain())
+}
+
+fn select_with_chain(mut candidates: Vec<EncoderCfg>, chain: &[String]) -> Option<EncoderCfg> {
+    candidates.retain(|c| is_healthy(&name(c)));
+    // stable, keep the default order of the same rank
+    candidates.sort_by_key(|c| rank(&name(c), chain));
+    candidates.into_iter().next()
+}
+
+pub fn is_healthy(name: &str) -> bool {
+    match HEALTH.lock().unwrap().get(name) {
+        Some(health) => health.failed_at.elapsed() >= health.cooldown(),
+        None => true,
+    }
+}
+
+/// Mark the encoder as failed, it is not selected until the cooldown expires.
+pub fn report_failure(name: &str, reason: &str) {
+    // lock in the same order as `generation`
+    let mut generation = GENERATION.lock().unwrap();
+    let mut health = HEALTH.lock().unwrap();
+    let h = health.entry(name.to_owned()).or_insert(Health {
+        failures: 0,
+        failed_at: Instant::now(),
+        expired: false,
+    });
+    h.failures += 1;
+    h.failed_at = Instant::now();
+    h.expired = false;
+    log::warn!(
+        "encoder {name} failed {} times in a row, retry after {:?}, reason: {reason}",
+        h.failures,
+        h.cooldown()
+    );
+    *generation += 1;
+}
+
+/// The encoder has been working longer than `MAX_COOLDOWN`, forget its failures.
+pub fn report_success(name: &str) {
+    let mut health = HEALTH.lock().unwrap();
+    if health.get(name).map(|h| h.expired).unwrap_or(false) {
+        log::info!("encoder {name} is healthy again");
+        health.remove(name);
+    }
+}
+
+/// The generation of the encoder health, changed by failures and expired cooldowns.
+pub fn generation() -> u64 {
+    let mut generation = GENERATION.lock().unwrap();
+    for (name, h) in HEALTH.lock().unwrap().iter_mut() {
+        if !h.expired && h.failed_at.elapsed() >= h.cooldown() {
+            h.expired = true;
+            log::info!("encoder {name} cooldown expired, retry it");
+            *generation += 1;
+        }
+    }
+    *generation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{codec::Quality, VpxEncoderConfig};
+
+    fn vpx(codec: VpxVideoCodecId) -> EncoderCfg {
+        EncoderCfg::VPX(VpxEncoderConfig {
+            width: 1920,
+            height: 1080,
+            quality: Quality::Balanced,
+            codec,
+            keyframe_interval: None,
+        })
+    }
+
+    #[test]
+    fn test_select() {
+        let candidates = || vec![vpx(VpxVideoCodecId::VP9), vpx(VpxVideoCodecId::VP8)];
+        let selected = |chain: &[&str]| {
+            let chain: Vec<String> = chain.iter().map(|s| s.to_string()).collect();
+            select_with_chain(candidates(), &chain).map(|c| name(&c))
+        };
+        assert_eq!(selected(&[]), Some("vp9".to_owned()));
+        assert_eq!(selected(&["vp8"]), Some("vp8".to_owned()));
+        assert_eq!(selected(&["nvenc", "vp8", "vp9"]), Some("vp8".to_owned()));
+
+        report_failure("vp8", "test");
+        assert!(!is_healthy("vp8"));
+        assert_eq!(selected(&["vp8"]), Some("vp9".to_owned()));
+        HEALTH.lock().unwrap().remove("vp8");
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let mut h = Health {
+            failures: 1,
+            failed_at: Instant::now(),
+            expired: false,
+        };
+        assert_eq!(h.cooldown(), COOLDOWN);
+        h.failures = 2;
+        assert_eq!(h.cooldown(), COOLDOWN * 2);
+        h.failures = 100;
+        assert_eq!(h.cooldown(), MAX_COOLDOWN);
+    }
+}
@@ -0,0 +1,242 @@
+// Post-processing filters of the decoded frames on the client.
+//
+// They run on the rgb image before it is handed to the ui, so every frontend gets the same result.
+// Frames decoded to textures are not filtered.
+
+use crate::{scale_argb, ImageFormat, ImageRgb, ScaleFilter};
+
+// Neighboring pixels closer than this are treated as a banded gradient.
+const DEBAND_THRESHOLD: i32 = 4;
+// The view scale below which the frame is smoothed before the ui shrinks it.
+const SMOOTH_SCALE: f64 = 0.95;
+
+/// The filters selected for a session, parsed from a comma separated list like "sharpen,grayscale".
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VideoFilters {
+    pub sharpen: bool,
+    pub deband: bool,
+    pub grayscale: bool,
+    pub smooth: bool,
+}
+
+impl VideoFilters {
+    pub fn parse(s: &str) -> Self {
+        let mut filters = Self::default();
+        for name in s.split(',').map(|s| s.trim()) {
+            match name {
+                "sharpen" => filters.sharpen = true,
+                "deband" => filters.deband = true,
+                "grayscale" => filters.grayscale = true,
+                "smooth" => filters.smooth = true,
+                _ => {}
+            }
+        }
+        filters
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Default)]
+pub struct FilterContext {
+    pub filters: VideoFilters,
+    // The scale of the view to the remote display in physical pixels, < 1 if the view is smaller.
+    pub view_scale: f64,
+    src: Vec<u8>,
+    small: Vec<u8>,
+}
+
+impl FilterContext {
+    pub fn set(&mut self, filters: VideoFilters, view_scale: f64) {
+        self.filters = filters;
+        self.view_scale = view_scale;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    pub fn apply(&mut self, rgb: &mut ImageRgb) {
+        if self.filters.is_empty() {
+            return;
+        }
+        // 3 bytes per pixel is only used by sciter on windows
+        if !matches!(rgb.fmt(), ImageFormat::ARGB | ImageFormat::ABGR) || rgb.w < 3 || rgb.h < 3 {
+            return;
+        }
+        let align = rgb.align().max(1);
+        let stride = (rgb.w * 4 + align - 1) & !(align - 1);
+        if rgb.raw.len() < stride * rgb.h {
+            return;
+        }
+        if self.filters.deband {
+            self.src.clone_from(&rgb.raw);
+            deband(&self.src, &mut rgb.raw, stride, rgb.w, rgb.h);
+        }
+        if self.filters.smooth && self.view_scale > 0.0 && self.view_scale < SMOOTH_SCALE {
+            self.smooth(rgb, stride);
+        }
+        if self.filters.sharpen {
+            self.src.clone_from(&rgb.raw);
+            sharpen(&self.src, &mut rgb.raw, stride, rgb.w, rgb.h);
+        }
+        if self.filters.grayscale {
+            let fmt = rgb.fmt();
+            grayscale(&mut rgb.raw, stride, rgb.w, rgb.h, fmt);
+        }
+    }
+
+    // Shrink with a box filter to the view size and stretch back, so the ui scaler which only
+    // samples a few pixels does not alias thin lines and text.
+    fn smooth(&mut self, rgb: &mut ImageRgb, stride: usize) {
+        let (w, h) = (rgb.w, rgb.h);
+        let small_w = ((w as f64 * self.view_scale) as usize).max(1);
+        let small_h = ((h as f64 * self.view_scale) as usize).max(1);
+        if scale_argb(
+            &rgb.raw,
+            stride,
+            (w, h),
+            &mut self.small,
+            (small_w, small_h),
+            ScaleFilter::Box,
+        )
+        .is_err()
+        {
+            return;
+        }
+        if scale_argb(
+            &self.small,
+            small_w * 4,
+            (small_w, small_h),
+            &mut self.src,
+            (w, h),
+            ScaleFilter::Bilinear,
+        )
+        .is_err()
+        {
+            return;
+        }
+        for y in 0..h {
+            rgb.raw[y * stride..y * stride + w * 4]
+                .copy_from_slice(&self.src[y * w * 4..(y + 1) * w * 4]);
+        }
+    }
+}
+
+// The 4 neighbors of each pixel, the border pixels are kept.
+fn for_each_inner(
+    src: &[u8],
+    dst: &mut [u8],
+    stride: usize,
+    w: usize,
+    h: usize,
+    f: impl Fn(i32, [i32; 4]) -> i32,
+) {
+    for y in 1..h - 1 {
+        for x in 1..w - 1 {
+            let i = y * stride + x * 4;
+            // color channels only, keep alpha
+            for c in 0..3 {
+                let p = i + c;
+                let neighbors = [
+                    src[p - stride] as i32,
+                    src[p + stride] as i32,
+                    src[p - 4] as i32,
+                    src[p + 4] as i32,
+                ];
+                dst[p] = f(src[p] as i32, neighbors).clamp(0, 255) as u8;
+            }
+        }
+    }
+}
+
+fn sharpen(src: &[u8], dst: &mut [u8], stride: usize, w: usize, h: usize) {
+    // center + (4 * center - neighbors) / 2
+    for_each_inner(src, dst, stride, w, h, |c, n| {
+        c + (4 * c - n.iter().sum::<i32>()) / 2
+    });
+}
+
+fn deband(src: &[u8], dst: &mut [u8], stride: usize, w: usize, h: usize) {
+    // average the low contrast areas only, edges are kept
+    for_each_inner(src, dst, stride, w, h, |c, n| {
+        if n.iter().all(|v| (v - c).abs() <= DEBAND_THRESHOLD) {
+            (c * 4 + n.iter().sum::<i32>() + 4) / 8
+        } else {
+            c
+        }
+    });
+}
+
+fn grayscale(raw: &mut [u8], stride: usize, w: usize, h: usize, fmt: ImageFormat) {
+    // libyuv ARGB is BGRA in memory
+    let (wb, wr) = match fmt {
+        ImageFormat::ABGR => (77, 29),
+        _ => (29, 77),
+    };
+    for y in 0..h {
+        for px in raw[y * stride..y * stride + w * 4].chunks_exact_mut(4) {
+            let l = (px[0] as u32 * wb + px[1] as u32 * 150 + px[2] as u32 * wr + 128) >> 8;
+            px[0] = l as u8;
+            px[1] = l as u8;
+            px[2] = l as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(w: usize, h: usize, f: impl Fn(usize, usize) -> [u8; 4]) -> ImageRgb {
+        let mut rgb = ImageRgb::new(ImageFormat::ARGB, 1);
+        rgb.w = w;
+        rgb.h = h;
+        for y in 0..h {
+            for x in 0..w {
+                rgb.raw.extend_from_slice(&f(x, y));
+            }
+        }
+        rgb
+    }
+
+    #[test]
+    fn test_parse() {
+        let filters = VideoFilters::parse("sharpen, grayscale,unknown");
+        assert!(filters.sharpen && filters.grayscale);
+        assert!(!filters.deband && !filters.smooth);
+        assert!(VideoFilters::parse("").is_empty());
+    }
+
+    #[test]
+    fn test_filters() {
+        let mut ctx = FilterContext::default();
+
+        ctx.set(VideoFilters::parse("grayscale"), 1.0);
+        let mut rgb = image(4, 4, |_, _| [10, 200, 50, 255]);
+        ctx.apply(&mut rgb);
+        assert!(rgb
+            .raw
+            .chunks_exact(4)
+            .all(|p| p[0] == p[1] && p[1] == p[2]));
+
+        // a small step is smoothed, a sharp edge is kept
+        ctx.set(VideoFilters::parse("deband"), 1.0);
+        let mut rgb = image(5, 5, |x, y| [100 + (x + y) as u8 % 2 * 3, 0, 0, 255]);
+        ctx.apply(&mut rgb);
+        assert_eq!(rgb.raw[(2 * 5 + 2) * 4], 102);
+        let mut rgb = image(5, 5, |x, _| [if x < 2 { 0 } else { 255 }, 0, 0, 255]);
+        let before = rgb.raw.clone();
+        ctx.apply(&mut rgb);
+        assert_eq!(rgb.raw, before);
+
+        // sharpen increases the contrast of an edge
+        ctx.set(VideoFilters::parse("sharpen"), 1.0);
+        let mut rgb = image(5, 5, |x, _| [if x < 2 { 100 } else { 150 }, 0, 0, 255]);
+        ctx.apply(&mut rgb);
+        assert!(rgb.raw[(2 * 5 + 1) * 4] < 100);
+        assert!(rgb.raw[(2 * 5 + 2) * 4] > 150);
+    }
+}
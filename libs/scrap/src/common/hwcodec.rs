@@ -1,6 +1,7 @@
 use crate::{
     codec::{
-        base_bitrate, codec_thread_num, enable_hwcodec_option, EncoderApi, EncoderCfg, Quality as Q,
+        base_bitrate, codec_thread_num, enable_hwcodec_option, EncoderApi, EncoderCfg,
+        Quality as Q, Roi,
     },
     convert::*,
     CodecFormat, EncodeInput, ImageFormat, ImageRgb, Pixfmt, HW_STRIDE_ALIGN,
@@ -213,12 +214,28 @@ impl EncoderApi for HwRamEncoder {
         true
     }
 
-    fn disable(&self) {
-        HwCodecConfig::clear(false, true);
+    fn set_roi(&mut self, _roi: Option<Roi>) -> ResultType<()> {
+        Ok(())
     }
 }
 
 impl HwRamEncoder {
+    /// All the encoders of the format, the best first.
+    pub fn all(format: CodecFormat) -> Vec<CodecInfo> {
+        let data_format = match format {
+            CodecFormat::H264 => DataFormat::H264,
+            CodecFormat::H265 => DataFormat::H265,
+            _ => return vec![],
+        };
+        let mut v: Vec<_> = HwCodecConfig::get()
+            .ram_encode
+            .drain(..)
+            .filter(|c| c.format == data_format)
+            .collect();
+        v.sort_by_key(|c| c.priority);
+        v
+    }
+
     pub fn try_get(format: CodecFormat) -> Option<CodecInfo> {
         let mut info = None;
         let best = CodecInfo::prioritized(HwCodecConfig::get().ram_encode);
@@ -0,0 +1,387 @@
+// A minimal Matroska muxer of H264 or H265 and Opus, for the recordings of the hardware codecs with
+// audio, as the hardware muxer of mp4 has a video stream only.
+//
+// The header is written at the first key frame, whose parameter sets make the codec private. The
+// frames come in the Annex B format and are stored with the lengths of their NAL units instead.
+// The blocks are buffered by cluster, a cluster starting at each key frame, and the sizes of the
+// segment and the duration are filled in at the end.
+
+use hbb_common::{bail, ResultType};
+use std::io::{Seek, SeekFrom, Write};
+
+const EBML: u32 = 0x1A45DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const DOC_TYPE: u32 = 0x4282;
+const DOC_TYPE_VERSION: u32 = 0x4287;
+const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+const SEGMENT: u32 = 0x18538067;
+const INFO: u32 = 0x1549A966;
+const TIMESTAMP_SCALE: u32 = 0x2AD7B1;
+const DURATION: u32 = 0x4489;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+const TRACKS: u32 = 0x1654AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const CODEC_PRIVATE: u32 = 0x63A2;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const AUDIO: u32 = 0xE1;
+const SAMPLING_FREQUENCY: u32 = 0xB5;
+const CHANNELS: u32 = 0x9F;
+const CLUSTER: u32 = 0x1F43B675;
+const TIMESTAMP: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+
+const VIDEO_TRACK: u8 = 1;
+const AUDIO_TRACK: u8 = 2;
+// the size of the segment, filled in at the end
+const SIZE_LEN: usize = 8;
+
+pub struct MkvAudio {
+    pub sample_rate: u32,
+    pub channels: u16,
+    // the OpusHead
+    pub head: Vec<u8>,
+}
+
+pub struct Mkv<W: Write + Seek> {
+    out: W,
+    width: usize,
+    height: usize,
+    hevc: bool,
+    audio: Option<MkvAudio>,
+    // of the segment data and of the duration, once the header is written
+    positions: Option<(u64, u64)>,
+    // the ms of the first frame, the timestamps of the file start from it
+    base: i64,
+    cluster: Vec<u8>,
+    cluster_ms: i64,
+    last_ms: i64,
+}
+
+impl<W: Write + Seek> Mkv<W> {
+    pub fn new(out: W, width: usize, height: usize, hevc: bool, audio: Option<MkvAudio>) -> Self {
+        Self {
+            out,
+            width,
+            height,
+            hevc,
+            audio,
+            positions: None,
+            base: 0,
+            cluster: vec![],
+            cluster_ms: 0,
+            last_ms: 0,
+        }
+    }
+
+    /// Writes a frame in the Annex B format, `ms` is its pts. The frames before the first key frame
+    /// are dropped.
+    pub fn write_video(&mut self, data: &[u8], ms: i64, key: bool) -> ResultType<bool> {
+        if self.positions.is_none() {
+            if !key {
+                return Ok(false);
+            }
+            self.write_header(data)?;
+            self.base = ms;
+        }
+        let ms = (ms - self.base).max(self.last_ms);
+        if key || ms - self.cluster_ms > i16::MAX as i64 {
+            self.flush()?;
+            self.cluster_ms = ms;
+        }
+        let mut frame = vec![];
+        for nal in nal_units(data) {
+            frame.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+            frame.extend_from_slice(nal);
+        }
+        self.add_block(VIDEO_TRACK, ms, key, &frame);
+        Ok(true)
+    }
+
+    /// Writes an Opus packet, `ms` is on the timeline of the video pts. The packets before the
+    /// first key frame are dropped.
+    pub fn write_audio(&mut self, data: &[u8], ms: i64) -> ResultType<bool> {
+        if self.positions.is_none() || self.audio.is_none() {
+            return Ok(false);
+        }
+        let ms = (ms - self.base).max(self.last_ms);
+        if ms - self.cluster_ms > i16::MAX as i64 {
+            self.flush()?;
+            self.cluster_ms = ms;
+        }
+        self.add_block(AUDIO_TRACK, ms, true, data);
+        Ok(true)
+    }
+
+    /// Writes the last cluster, the size of the segment and the duration.
+    pub fn finalize(&mut self) -> ResultType<()> {
+        let Some((segment, duration)) = self.positions else {
+            return Ok(());
+        };
+        self.flush()?;
+        let end = self.out.stream_position()?;
+        self.out.seek(SeekFrom::Start(segment - SIZE_LEN as u64))?;
+        self.out.write_all(&size_of_len(end - segment, SIZE_LEN))?;
+        self.out.seek(SeekFrom::Start(duration))?;
+        self.out.write_all(&(self.last_ms as f64).to_be_bytes())?;
+        self.out.seek(SeekFrom::Start(end))?;
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn write_header(&mut self, key_frame: &[u8]) -> ResultType<()> {
+        let codec_private = if self.hevc {
+            hvcc(key_frame)?
+        } else {
+            avcc(key_frame)?
+        };
+        let mut header = vec![];
+        element(
+            &mut header,
+            EBML,
+            &[
+                uint(EBML_VERSION, 1),
+                uint(EBML_READ_VERSION, 1),
+                uint(EBML_MAX_ID_LENGTH, 4),
+                uint(EBML_MAX_SIZE_LENGTH, 8),
+                bytes(DOC_TYPE, b"matroska"),
+                uint(DOC_TYPE_VERSION, 4),
+                uint(DOC_TYPE_READ_VERSION, 2),
+            ]
+            .concat(),
+        );
+        header.extend_from_slice(&id(SEGMENT));
+        // unknown until the end
+        header.extend_from_slice(&[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        let segment = header.len();
+        let info = [
+            uint(TIMESTAMP_SCALE, 1_000_000),
+            bytes(MUXING_APP, b"rustdesk"),
+            bytes(WRITING_APP, b"rustdesk"),
+            bytes(DURATION, &0f64.to_be_bytes()),
+        ]
+        .concat();
+        element(&mut header, INFO, &info);
+        // the float is the last 8 bytes of the info
+        let duration = header.len() - 8;
+        let mut video = [
+            uint(TRACK_NUMBER, VIDEO_TRACK as _),
+            uint(TRACK_UID, VIDEO_TRACK as _),
+            uint(TRACK_TYPE, 1),
+            bytes(
+                CODEC_ID,
+                if self.hevc {
+                    b"V_MPEGH/ISO/HEVC"
+                } else {
+                    b"V_MPEG4/ISO/AVC"
+                },
+            ),
+            bytes(CODEC_PRIVATE, &codec_private),
+        ]
+        .concat();
+        element(
+            &mut video,
+            VIDEO,
+            &[
+                uint(PIXEL_WIDTH, self.width as _),
+                uint(PIXEL_HEIGHT, self.height as _),
+            ]
+            .concat(),
+        );
+        let mut tracks = vec![];
+        element(&mut tracks, TRACK_ENTRY, &video);
+        if let Some(audio) = &self.audio {
+            let mut entry = [
+                uint(TRACK_NUMBER, AUDIO_TRACK as _),
+                uint(TRACK_UID, AUDIO_TRACK as _),
+                uint(TRACK_TYPE, 2),
+                bytes(CODEC_ID, b"A_OPUS"),
+                bytes(CODEC_PRIVATE, &audio.head),
+            ]
+            .concat();
+            element(
+                &mut entry,
+                AUDIO,
+                &[
+                    bytes(
+                        SAMPLING_FREQUENCY,
+                        &(audio.sample_rate as f64).to_be_bytes(),
+                    ),
+                    uint(CHANNELS, audio.channels as _),
+                ]
+                .concat(),
+            );
+            element(&mut tracks, TRACK_ENTRY, &entry);
+        }
+        element(&mut header, TRACKS, &tracks);
+        let start = self.out.stream_position()?;
+        self.out.write_all(&header)?;
+        self.positions = Some((start + segment as u64, start + duration as u64));
+        Ok(())
+    }
+
+    fn add_block(&mut self, track: u8, ms: i64, key: bool, data: &[u8]) {
+        let mut block = vec![0x80 | track];
+        block.extend_from_slice(&((ms - self.cluster_ms) as i16).to_be_bytes());
+        block.push(if key { 0x80 } else { 0 });
+        block.extend_from_slice(data);
+        element(&mut self.cluster, SIMPLE_BLOCK, &block);
+        self.last_ms = ms;
+    }
+
+    fn flush(&mut self) -> ResultType<()> {
+        if self.cluster.is_empty() {
+            return Ok(());
+        }
+        let mut data = uint(TIMESTAMP, self.cluster_ms as _);
+        data.append(&mut self.cluster);
+        let mut cluster = vec![];
+        element(&mut cluster, CLUSTER, &data);
+        self.out.write_all(&cluster)?;
+        Ok(())
+    }
+}
+
+fn id(id: u32) -> Vec<u8> {
+    let bytes = id.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count();
+    bytes[skip..].to_vec()
+}
+
+// The size in `len` bytes, the marker bit included.
+fn size_of_len(size: u64, len: usize) -> Vec<u8> {
+    let v = size | (1 << (7 * len));
+    v.to_be_bytes()[8 - len..].to_vec()
+}
+
+fn size(size: u64) -> Vec<u8> {
+    // all ones of a length is reserved for unknown
+    let len = (1..8).find(|len| size < (1 << (7 * len)) - 1).unwrap_or(8);
+    size_of_len(size, len)
+}
+
+fn element(out: &mut Vec<u8>, id_: u32, data: &[u8]) {
+    out.extend_from_slice(&id(id_));
+    out.extend_from_slice(&size(data.len() as _));
+    out.extend_from_slice(data);
+}
+
+fn bytes(id: u32, data: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    element(&mut out, id, data);
+    out
+}
+
+fn uint(id: u32, v: u64) -> Vec<u8> {
+    let bytes = v.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count().min(7);
+    self::bytes(id, &bytes[skip..])
+}
+
+// The NAL units of a frame in the Annex B format.
+fn nal_units(data: &[u8]) -> Vec<&[u8]> {
+    let mut starts = vec![];
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i..i + 3] == [0, 0, 1] {
+            starts.push((i, i + 3));
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    let mut units = vec![];
+    for (n, &(_, begin)) in starts.iter().enumerate() {
+        let mut end = starts.get(n + 1).map(|s| s.0).unwrap_or(data.len());
+        // of a 4 bytes start code
+        while end > begin && data[end - 1] == 0 {
+            end -= 1;
+        }
+        if end > begin {
+            units.push(&data[begin..end]);
+        }
+    }
+    units
+}
+
+// The AVCDecoderConfigurationRecord of ISO/IEC 14496-15.
+fn avcc(key_frame: &[u8]) -> ResultType<Vec<u8>> {
+    let units = nal_units(key_frame);
+    let of_type =
+        |t: u8| -> Vec<&[u8]> { units.iter().filter(|u| u[0] & 0x1F == t).copied().collect() };
+    let (sps, pps) = (of_type(7), of_type(8));
+    let Some(first) = sps.first().filter(|s| s.len() >= 4) else {
+        bail!("No sps in the key frame");
+    };
+    let mut avcc = vec![
+        1,
+        first[1],
+        first[2],
+        first[3],
+        0xFF,
+        0xE0 | sps.len() as u8,
+    ];
+    for s in sps.iter() {
+        avcc.extend_from_slice(&(s.len() as u16).to_be_bytes());
+        avcc.extend_from_slice(s);
+    }
+    avcc.push(pps.len() as u8);
+    for p in pps.iter() {
+        avcc.extend_from_slice(&(p.len() as u16).to_be_bytes());
+        avcc.extend_from_slice(p);
+    }
+    Ok(avcc)
+}
+
+// The HEVCDecoderConfigurationRecord of ISO/IEC 14496-15, 4:2:0 of 8 bits as encoded.
+fn hvcc(key_frame: &[u8]) -> ResultType<Vec<u8>> {
+    let units = nal_units(key_frame);
+    let nal_type = |u: &[u8]| (u[0] >> 1) & 0x3F;
+    let Some(sps) = units.iter().find(|u| nal_type(u) == 33) else {
+        bail!("No sps in the key frame");
+    };
+    // without the emulation prevention bytes
+    let mut rbsp = vec![];
+    for &b in sps.iter().skip(2) {
+        if b == 3 && rbsp.ends_with(&[0, 0]) {
+            continue;
+        }
+        rbsp.push(b);
+    }
+    if rbsp.len() < 13 {
+        bail!("Invalid sps");
+    }
+    let sub_layers = ((rbsp[0] >> 1) & 0x07) + 1;
+    let nested = rbsp[0] & 0x01;
+    // the general profile, tier and level
+    let mut hvcc = vec![1];
+    hvcc.extend_from_slice(&rbsp[1..13]);
+    hvcc.extend_from_slice(&[0xF0, 0x00, 0xFC, 0xFD, 0xF8, 0xF8, 0x00, 0x00]);
+    hvcc.push(sub_layers << 3 | nested << 2 | 0x03);
+    // the arrays of the vps, sps and pps
+    let types: Vec<u8> = [32, 33, 34]
+        .iter()
+        .copied()
+        .filter(|t| units.iter().any(|u| nal_type(u) == *t))
+        .collect();
+    hvcc.push(types.len() as u8);
+    for t in types {
+        let of_type: Vec<&[u8]> = units.iter().filter(|u| nal_type(u) == t).copied().collect();
+        hvcc.push(0x80 | t);
+        hvcc.extend_from_slice(&(of_type.len() as u16).to_be_bytes());
+        for u in of_type {
+            hvcc.extend_from_slice(&(u.len() as u16).to_be_bytes());
+            hvcc.extend_from_slice(u);
+        }
+    }
+    Ok(hvcc)
+}
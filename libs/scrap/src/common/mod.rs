@@ -49,7 +49,13 @@ pub const STRIDE_ALIGN: usize = 64; // commonly used in libvpx vpx_img_alloc cal
 pub const HW_STRIDE_ALIGN: usize = 0; // recommended by av_frame_get_buffer
 
 pub mod aom;
+pub mod bench;
+pub mod fallback;
+pub mod filter;
+#[cfg(feature = "hwcodec")]
+mod mkv;
 pub mod record;
+pub mod text_patch;
 mod vpx;
 
 #[repr(usize)]
@@ -138,6 +144,10 @@ pub trait TraitCapturer {
 
     #[cfg(feature = "vram")]
     fn set_output_texture(&mut self, texture: bool);
+
+    // Whether the frames can stay on the gpu and be encoded without copying to system memory.
+    #[cfg(feature = "vram")]
+    fn texture_supported(&self) -> bool;
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -172,6 +182,7 @@ pub trait TraitPixelBuffer {
 #[cfg(not(any(target_os = "ios")))]
 pub enum Frame<'a> {
     PixelBuffer(PixelBuffer<'a>),
+    // (ID3D11Texture2D, rotation), the captured texture is passed to the vram encoder directly.
     Texture((*mut c_void, usize)),
 }
 
@@ -1,3 +1,5 @@
+#[cfg(feature = "hwcodec")]
+use super::mkv::{Mkv, MkvAudio};
 use crate::CodecFormat;
 #[cfg(feature = "hwcodec")]
 use hbb_common::anyhow::anyhow;
@@ -16,10 +18,33 @@ use std::{
     sync::mpsc::Sender,
     time::Instant,
 };
-use webm::mux::{self, Segment, Track, VideoTrack, Writer};
+use webm::mux::{self, AudioTrack, Segment, Track, VideoTrack, Writer};
 
 const MIN_SECS: u64 = 1;
 
+/// Limits after which a recording continues in a new file, 0 means no limit.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecordSplit {
+    pub max_bytes: u64,
+    pub max_secs: u64,
+}
+
+impl RecordSplit {
+    /// `size` is in MB and `duration` is in minutes, empty or invalid values disable the limit.
+    pub fn new(size: &str, duration: &str) -> Self {
+        Self {
+            max_bytes: size.trim().parse::<u64>().unwrap_or(0) * 1024 * 1024,
+            max_secs: duration.trim().parse::<u64>().unwrap_or(0) * 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordAudioFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
 #[derive(Debug, Clone)]
 pub struct RecorderContext {
     pub server: bool,
@@ -27,6 +52,7 @@ pub struct RecorderContext {
     pub dir: String,
     pub display: usize,
     pub tx: Option<Sender<RecordState>>,
+    pub split: RecordSplit,
 }
 
 #[derive(Debug, Clone)]
@@ -35,6 +61,7 @@ pub struct RecorderContext2 {
     pub width: usize,
     pub height: usize,
     pub format: CodecFormat,
+    pub audio: Option<RecordAudioFormat>,
 }
 
 impl RecorderContext2 {
@@ -53,6 +80,8 @@ impl RecorderContext2 {
                 || self.format == CodecFormat::AV1
             {
                 ".webm"
+            } else if self.audio.is_some() {
+                ".mkv"
             } else {
                 ".mp4"
             };
@@ -72,6 +101,10 @@ pub trait RecorderApi {
     where
         Self: Sized;
     fn write_video(&mut self, frame: &EncodedVideoFrame) -> bool;
+    // Opus packet, `ms` is on the same timeline as the video pts.
+    fn write_audio(&mut self, _data: &[u8], _ms: i64) -> bool {
+        false
+    }
 }
 
 #[derive(Debug)]
@@ -88,6 +121,10 @@ pub struct Recorder {
     ctx2: Option<RecorderContext2>,
     pts: Option<i64>,
     check_failed: bool,
+    audio: Option<RecordAudioFormat>,
+    // pts of the last video frame and when it was written, used to stamp audio packets
+    last_video: Option<(i64, Instant)>,
+    file_start: Instant,
 }
 
 impl Deref for Recorder {
@@ -112,18 +149,26 @@ impl Recorder {
             ctx2: None,
             pts: None,
             check_failed: false,
+            audio: None,
+            last_video: None,
+            file_start: Instant::now(),
         })
     }
 
     fn check(&mut self, w: usize, h: usize, format: CodecFormat) -> ResultType<()> {
         match self.ctx2 {
             Some(ref ctx2) => {
-                if ctx2.width != w || ctx2.height != h || ctx2.format != format {
+                if ctx2.width != w
+                    || ctx2.height != h
+                    || ctx2.format != format
+                    || ctx2.audio != self.audio
+                {
                     let mut ctx2 = RecorderContext2 {
                         width: w,
                         height: h,
                         format,
                         filename: Default::default(),
+                        audio: self.audio,
                     };
                     ctx2.set_filename(&self.ctx)?;
                     self.ctx2 = Some(ctx2);
@@ -136,6 +181,7 @@ impl Recorder {
                     height: h,
                     format,
                     filename: Default::default(),
+                    audio: self.audio,
                 };
                 ctx2.set_filename(&self.ctx)?;
                 self.ctx2 = Some(ctx2);
@@ -151,6 +197,11 @@ impl Recorder {
                     WebmRecorder::new(self.ctx.clone(), (*ctx2).clone())?,
                 )),
                 #[cfg(feature = "hwcodec")]
+                _ if ctx2.audio.is_some() => Some(Box::new(MkvRecorder::new(
+                    self.ctx.clone(),
+                    (*ctx2).clone(),
+                )?)),
+                #[cfg(feature = "hwcodec")]
                 _ => Some(Box::new(HwRecorder::new(
                     self.ctx.clone(),
                     (*ctx2).clone(),
@@ -159,6 +210,7 @@ impl Recorder {
                 _ => bail!("unsupported codec type"),
             };
             self.pts = None;
+            self.file_start = Instant::now();
             self.send_state(RecordState::NewFile(ctx2.filename.clone()));
         }
         Ok(())
@@ -195,18 +247,21 @@ impl Recorder {
             video_frame::Union::Vp8s(vp8s) => {
                 for f in vp8s.frames.iter() {
                     self.check_pts(f.pts, w, h, format)?;
+                    self.check_split(f.key, w, h, format)?;
                     self.as_mut().map(|x| x.write_video(f));
                 }
             }
             video_frame::Union::Vp9s(vp9s) => {
                 for f in vp9s.frames.iter() {
                     self.check_pts(f.pts, w, h, format)?;
+                    self.check_split(f.key, w, h, format)?;
                     self.as_mut().map(|x| x.write_video(f));
                 }
             }
             video_frame::Union::Av1s(av1s) => {
                 for f in av1s.frames.iter() {
                     self.check_pts(f.pts, w, h, format)?;
+                    self.check_split(f.key, w, h, format)?;
                     self.as_mut().map(|x| x.write_video(f));
                 }
             }
@@ -214,6 +269,7 @@ impl Recorder {
             video_frame::Union::H264s(h264s) => {
                 for f in h264s.frames.iter() {
                     self.check_pts(f.pts, w, h, format)?;
+                    self.check_split(f.key, w, h, format)?;
                     self.as_mut().map(|x| x.write_video(f));
                 }
             }
@@ -221,6 +277,7 @@ impl Recorder {
             video_frame::Union::H265s(h265s) => {
                 for f in h265s.frames.iter() {
                     self.check_pts(f.pts, w, h, format)?;
+                    self.check_split(f.key, w, h, format)?;
                     self.as_mut().map(|x| x.write_video(f));
                 }
             }
@@ -234,21 +291,88 @@ impl Recorder {
         // https://stackoverflow.com/questions/76379101/how-to-create-one-playable-webm-file-from-two-different-video-tracks-with-same-c
         let old_pts = self.pts;
         self.pts = Some(pts);
+        self.last_video = Some((pts, Instant::now()));
         if old_pts.clone().unwrap_or_default() > pts {
             log::info!("pts {:?} -> {}, change record filename", old_pts, pts);
-            self.inner = None;
-            self.ctx2 = None;
-            let res = self.check(w, h, format);
-            if res.is_err() {
-                self.check_failed = true;
-                log::error!("check failed: {:?}", res);
-                res?;
-            }
+            self.new_file(w, h, format)?;
             self.pts = Some(pts);
         }
         Ok(())
     }
 
+    // Only split on key frames so that every file starts decodable.
+    fn check_split(
+        &mut self,
+        key: bool,
+        w: usize,
+        h: usize,
+        format: CodecFormat,
+    ) -> ResultType<()> {
+        if !key || self.inner.is_none() {
+            return Ok(());
+        }
+        let Some(ctx2) = &self.ctx2 else {
+            return Ok(());
+        };
+        let split = self.ctx.split;
+        let by_time = split.max_secs > 0 && self.file_start.elapsed().as_secs() >= split.max_secs;
+        let by_size = split.max_bytes > 0
+            && std::fs::metadata(&ctx2.filename)
+                .map(|m| m.len() >= split.max_bytes)
+                .unwrap_or(false);
+        if by_time || by_size {
+            log::info!("split record file, by time: {by_time}, by size: {by_size}");
+            let pts = self.pts;
+            self.new_file(w, h, format)?;
+            self.pts = pts;
+        }
+        Ok(())
+    }
+
+    fn new_file(&mut self, w: usize, h: usize, format: CodecFormat) -> ResultType<()> {
+        self.inner = None;
+        self.ctx2 = None;
+        let res = self.check(w, h, format);
+        if res.is_err() {
+            self.check_failed = true;
+            log::error!("check failed: {:?}", res);
+            res?;
+        }
+        Ok(())
+    }
+
+    /// The audio track is added when the next file is created, because webm
+    /// needs all tracks declared before the first frame.
+    ///
+    /// The surround of more than 2 channels is not recorded, its head needs the
+    /// mapping of the opus multistream.
+    pub fn set_audio_format(&mut self, sample_rate: u32, channels: u16) {
+        if channels > 2 {
+            log::info!("the audio of {channels} channels is not recorded");
+            self.audio = None;
+            return;
+        }
+        self.audio = Some(RecordAudioFormat {
+            sample_rate,
+            channels,
+        });
+    }
+
+    pub fn write_audio(&mut self, data: &[u8]) {
+        let Some((pts, time)) = self.last_video else {
+            return;
+        };
+        let ms = pts + time.elapsed().as_millis() as i64;
+        self.as_mut().map(|x| x.write_audio(data, ms));
+    }
+
+    pub fn filename(&self) -> Option<String> {
+        if self.inner.is_none() {
+            return None;
+        }
+        self.ctx2.as_ref().map(|ctx2| ctx2.filename.clone())
+    }
+
     fn send_state(&self, state: RecordState) {
         self.ctx.tx.as_ref().map(|tx| tx.send(state));
     }
@@ -256,6 +380,8 @@ impl Recorder {
 
 struct WebmRecorder {
     vt: VideoTrack,
+    at: Option<AudioTrack>,
+    audio_ns: u64,
     webm: Option<Segment<Writer<File>>>,
     ctx: RecorderContext,
     ctx2: RecorderContext2,
@@ -299,8 +425,25 @@ impl RecorderApi for WebmRecorder {
                 bail!("Failed to set codec private");
             }
         }
+        let at = match ctx2.audio {
+            Some(audio) => {
+                let at = webm.add_audio_track(
+                    audio.sample_rate as _,
+                    audio.channels as _,
+                    None,
+                    mux::AudioCodecId::Opus,
+                );
+                if !webm.set_codec_private(at.track_number(), &opus_head(audio)) {
+                    bail!("Failed to set opus codec private");
+                }
+                Some(at)
+            }
+            None => None,
+        };
         Ok(WebmRecorder {
             vt,
+            at,
+            audio_ns: 0,
             webm: Some(webm),
             ctx,
             ctx2,
@@ -326,6 +469,32 @@ impl RecorderApi for WebmRecorder {
             false
         }
     }
+
+    fn write_audio(&mut self, data: &[u8], ms: i64) -> bool {
+        // keep the file starting with a video key frame
+        if !self.key {
+            return false;
+        }
+        let Some(at) = self.at.as_mut() else {
+            return false;
+        };
+        // audio is stamped from the wall clock, never let it go backwards
+        let ns = (ms.max(0) as u64 * 1_000_000).max(self.audio_ns + 1);
+        self.audio_ns = ns;
+        at.add_frame(data, ns, true)
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7845#section-5.1
+fn opus_head(audio: RecordAudioFormat) -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1); // version
+    head.push(audio.channels as u8);
+    head.extend_from_slice(&0u16.to_le_bytes()); // pre-skip
+    head.extend_from_slice(&audio.sample_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // channel mapping family
+    head
 }
 
 impl Drop for WebmRecorder {
@@ -340,6 +509,7 @@ impl Drop for WebmRecorder {
     }
 }
 
+// The hardware muxer only has a video stream, the recordings with audio go to MkvRecorder.
 #[cfg(feature = "hwcodec")]
 struct HwRecorder {
     muxer: Muxer,
@@ -399,3 +569,217 @@ impl Drop for HwRecorder {
         self.ctx.tx.as_ref().map(|tx| tx.send(state));
     }
 }
+
+#[cfg(feature = "hwcodec")]
+struct MkvRecorder {
+    mkv: Mkv<File>,
+    ctx: RecorderContext,
+    ctx2: RecorderContext2,
+    written: bool,
+    start: Instant,
+}
+
+#[cfg(feature = "hwcodec")]
+impl RecorderApi for MkvRecorder {
+    fn new(ctx: RecorderContext, ctx2: RecorderContext2) -> ResultType<Self> {
+        let audio = ctx2.audio.map(|audio| MkvAudio {
+            sample_rate: audio.sample_rate,
+            channels: audio.channels,
+            head: opus_head(audio),
+        });
+        let mkv = Mkv::new(
+            File::create(&ctx2.filename)?,
+            ctx2.width,
+            ctx2.height,
+            ctx2.format == CodecFormat::H265,
+            audio,
+        );
+        Ok(MkvRecorder {
+            mkv,
+            ctx,
+            ctx2,
+            written: false,
+            start: Instant::now(),
+        })
+    }
+
+    fn write_video(&mut self, frame: &EncodedVideoFrame) -> bool {
+        let ok = self
+            .mkv
+            .write_video(&frame.data, frame.pts, frame.key)
+            .unwrap_or(false);
+        if ok {
+            self.written = true;
+        }
+        ok
+    }
+
+    fn write_audio(&mut self, data: &[u8], ms: i64) -> bool {
+        self.mkv.write_audio(data, ms).unwrap_or(false)
+    }
+}
+
+#[cfg(feature = "hwcodec")]
+impl Drop for MkvRecorder {
+    fn drop(&mut self) {
+        self.mkv.finalize().ok();
+        let mut state = RecordState::WriteTail;
+        if !self.written || self.start.elapsed().as_secs() < MIN_SECS {
+            std::fs::remove_file(&self.ctx2.filename).ok();
+            state = RecordState::RemoveFile;
+        }
+        self.ctx.tx.as_ref().map(|tx| tx.send(state));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const AUDIO: RecordAudioFormat = RecordAudioFormat {
+        sample_rate: 48000,
+        channels: 2,
+    };
+
+    fn context(format: CodecFormat) -> (RecorderContext, RecorderContext2) {
+        let ctx = RecorderContext {
+            server: false,
+            id: "test".to_owned(),
+            dir: std::env::temp_dir()
+                .join(format!("record-{}-{}", format, hbb_common::get_time()))
+                .to_string_lossy()
+                .to_string(),
+            display: 0,
+            tx: None,
+            split: Default::default(),
+        };
+        let mut ctx2 = RecorderContext2 {
+            filename: Default::default(),
+            width: 640,
+            height: 480,
+            format,
+            audio: Some(AUDIO),
+        };
+        ctx2.set_filename(&ctx).unwrap();
+        (ctx, ctx2)
+    }
+
+    fn frame(data: &[u8], pts: i64, key: bool) -> EncodedVideoFrame {
+        EncodedVideoFrame {
+            data: data.to_vec().into(),
+            pts,
+            key,
+            ..Default::default()
+        }
+    }
+
+    // The elements of `data` by their ids, an unknown size is to the end.
+    fn children(mut data: &[u8]) -> Vec<(u32, &[u8])> {
+        let vint = |data: &[u8]| -> Option<(u64, usize)> {
+            let len = data.first()?.leading_zeros() as usize + 1;
+            if len > 8 || data.len() < len {
+                return None;
+            }
+            Some((data[..len].iter().fold(0, |v, b| v << 8 | *b as u64), len))
+        };
+        let mut children = vec![];
+        while let Some((id, id_len)) = vint(data) {
+            let Some((size, size_len)) = vint(&data[id_len..]) else {
+                break;
+            };
+            let size = size & !(1 << (7 * size_len));
+            let start = id_len + size_len;
+            let end = if size == (1 << (7 * size_len)) - 1 {
+                data.len()
+            } else {
+                (start + size as usize).min(data.len())
+            };
+            children.push((id as u32, &data[start..end]));
+            data = &data[end..];
+        }
+        children
+    }
+
+    // The codec ids of the audio tracks of a Matroska or WebM file.
+    fn audio_codecs(filename: &str) -> Vec<String> {
+        const SEGMENT: u32 = 0x18538067;
+        const TRACKS: u32 = 0x1654AE6B;
+        const TRACK_ENTRY: u32 = 0xAE;
+        const TRACK_TYPE: u32 = 0x83;
+        const CODEC_ID: u32 = 0x86;
+        let data = std::fs::read(filename).unwrap();
+        let mut codecs = vec![];
+        for (_, segment) in children(&data).into_iter().filter(|e| e.0 == SEGMENT) {
+            for (_, tracks) in children(segment).into_iter().filter(|e| e.0 == TRACKS) {
+                for (_, entry) in children(tracks).into_iter().filter(|e| e.0 == TRACK_ENTRY) {
+                    let entry = children(entry);
+                    if entry.iter().any(|e| e.0 == TRACK_TYPE && e.1 == [2]) {
+                        codecs.extend(
+                            entry
+                                .iter()
+                                .filter(|e| e.0 == CODEC_ID)
+                                .map(|e| String::from_utf8_lossy(e.1).to_string()),
+                        );
+                    }
+                }
+            }
+        }
+        codecs
+    }
+
+    fn record(mut recorder: impl RecorderApi, key: &[u8], delta: &[u8]) {
+        assert!(recorder.write_video(&frame(key, 0, true)));
+        assert!(recorder.write_audio(&[0xFC, 0xFF, 0xFE], 10));
+        assert!(recorder.write_video(&frame(delta, 33, false)));
+        assert!(recorder.write_audio(&[0xFC, 0xFF, 0xFE], 30));
+        std::thread::sleep(Duration::from_secs(MIN_SECS));
+    }
+
+    #[test]
+    fn test_webm_audio() {
+        let (ctx, ctx2) = context(CodecFormat::VP9);
+        assert!(ctx2.filename.ends_with(".webm"));
+        let filename = ctx2.filename.clone();
+        record(
+            WebmRecorder::new(ctx.clone(), ctx2).unwrap(),
+            &[0x82, 0x49, 0x83, 0x42, 0x00],
+            &[0x86, 0x00],
+        );
+        assert_eq!(audio_codecs(&filename), ["A_OPUS"]);
+        std::fs::remove_dir_all(&ctx.dir).ok();
+    }
+
+    #[cfg(feature = "hwcodec")]
+    #[test]
+    fn test_mkv_audio() {
+        let h264 = [
+            &[0, 0, 0, 1, 0x67, 0x42, 0xC0, 0x1F, 0xDA][..],
+            &[0, 0, 0, 1, 0x68, 0xCE, 0x3C, 0x80],
+            &[0, 0, 1, 0x65, 0x88, 0x84, 0x00],
+        ]
+        .concat();
+        let h265 = [
+            &[0, 0, 0, 1, 0x40, 0x01, 0x0C, 0x01][..],
+            &[
+                0, 0, 0, 1, 0x42, 0x01, 0x01, 0x01, 0x60, 0, 0, 0x03, 0, 0x90,
+            ],
+            &[0, 0, 0, 0, 0, 0x5D, 0xA0],
+            &[0, 0, 0, 1, 0x44, 0x01, 0xC1],
+            &[0, 0, 1, 0x26, 0x01, 0xAF, 0x00],
+        ]
+        .concat();
+        for (format, key) in [(CodecFormat::H264, h264), (CodecFormat::H265, h265)] {
+            let (ctx, ctx2) = context(format);
+            assert!(ctx2.filename.ends_with(".mkv"));
+            let filename = ctx2.filename.clone();
+            record(
+                MkvRecorder::new(ctx.clone(), ctx2).unwrap(),
+                &key,
+                &[0, 0, 0, 1, 0x41, 0x9A],
+            );
+            assert_eq!(audio_codecs(&filename), ["A_OPUS"]);
+            std::fs::remove_dir_all(&ctx.dir).ok();
+        }
+    }
+}
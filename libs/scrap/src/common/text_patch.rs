@@ -0,0 +1,363 @@
+// Lossless patches for text regions, similar to the tight encoding of VNC.
+//
+// The server looks for tiles with text-like content, which are many sharp edges on a dominant
+// background color. Once such a tile has not changed for a while, it is sent zstd compressed
+// and the client draws it over every decoded frame, until the tile changes again.
+
+use crate::{swap_red_blue, ImageFormat, ImageRgb, Pixfmt};
+use hbb_common::{
+    compress::{compress, decompress},
+    message_proto::{LosslessPatch, LosslessPatches},
+};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+pub const TILE_SIZE: usize = 64;
+// Wait until typing or scrolling settles down.
+const STABLE_DURATION: Duration = Duration::from_millis(300);
+const MAX_PATCHES_PER_FRAME: usize = 16;
+// Luma difference of two neighboring pixels to be a sharp edge.
+const EDGE_CONTRAST: i32 = 96;
+const MIN_EDGE_PERMILLE: usize = 30;
+const MIN_BACKGROUND_PERCENT: usize = 40;
+
+#[derive(Debug, Clone, Copy)]
+struct Tile {
+    changed_at: Instant,
+    checked: bool,
+    patched: bool,
+}
+
+pub struct TextPatchDetector {
+    width: usize,
+    height: usize,
+    stride: usize,
+    pixfmt: Pixfmt,
+    last: Vec<u8>,
+    tiles: Vec<Tile>,
+}
+
+impl TextPatchDetector {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            stride: 0,
+            pixfmt: Pixfmt::BGRA,
+            last: Vec::new(),
+            tiles: Vec::new(),
+        }
+    }
+
+    fn cols(&self) -> usize {
+        self.width.div_ceil(TILE_SIZE)
+    }
+
+    fn rows(&self) -> usize {
+        self.height.div_ceil(TILE_SIZE)
+    }
+
+    fn tile_rect(&self, index: usize) -> (usize, usize, usize, usize) {
+        let x = index % self.cols() * TILE_SIZE;
+        let y = index / self.cols() * TILE_SIZE;
+        (
+            x,
+            y,
+            TILE_SIZE.min(self.width - x),
+            TILE_SIZE.min(self.height - y),
+        )
+    }
+
+    /// Compare the captured frame with the last one.
+    ///
+    /// Returns the removals of the changed tiles and the patches of the settled text tiles.
+    pub fn update(
+        &mut self,
+        data: &[u8],
+        stride: usize,
+        pixfmt: Pixfmt,
+    ) -> Option<LosslessPatches> {
+        if pixfmt != Pixfmt::BGRA && pixfmt != Pixfmt::RGBA {
+            return None;
+        }
+        if stride < self.width * 4 || data.len() < stride * self.height {
+            return None;
+        }
+        let now = Instant::now();
+        if stride != self.stride || pixfmt != self.pixfmt || self.last.len() != data.len() {
+            self.stride = stride;
+            self.pixfmt = pixfmt;
+            self.last = data.to_vec();
+            self.tiles = vec![
+                Tile {
+                    changed_at: now,
+                    checked: false,
+                    patched: false,
+                };
+                self.cols() * self.rows()
+            ];
+            return Some(LosslessPatches {
+                reset: true,
+                ..Default::default()
+            });
+        }
+        let mut patches = Vec::new();
+        for i in 0..self.tiles.len() {
+            let (x, y, w, h) = self.tile_rect(i);
+            let mut changed = false;
+            for row in y..y + h {
+                let range = row * stride + x * 4..row * stride + (x + w) * 4;
+                if data[range.clone()] != self.last[range.clone()] {
+                    self.last[range.clone()].copy_from_slice(&data[range]);
+                    changed = true;
+                }
+            }
+            if changed {
+                let tile = &mut self.tiles[i];
+                if tile.patched {
+                    patches.push(LosslessPatch {
+                        x: x as _,
+                        y: y as _,
+                        width: w as _,
+                        height: h as _,
+                        ..Default::default()
+                    });
+                }
+                *tile = Tile {
+                    changed_at: now,
+                    checked: false,
+                    patched: false,
+                };
+            }
+        }
+        self.take_settled(&mut patches);
+        if patches.is_empty() {
+            None
+        } else {
+            Some(LosslessPatches {
+                patches,
+                ..Default::default()
+            })
+        }
+    }
+
+    /// The patches of the text tiles settled since the last frame, while no new frame is captured.
+    pub fn idle(&mut self) -> Option<LosslessPatches> {
+        let mut patches = Vec::new();
+        self.take_settled(&mut patches);
+        if patches.is_empty() {
+            None
+        } else {
+            Some(LosslessPatches {
+                patches,
+                ..Default::default()
+            })
+        }
+    }
+
+    fn take_settled(&mut self, patches: &mut Vec<LosslessPatch>) {
+        let mut count = 0;
+        for i in 0..self.tiles.len() {
+            if count >= MAX_PATCHES_PER_FRAME {
+                break;
+            }
+            let tile = self.tiles[i];
+            if tile.checked || tile.changed_at.elapsed() < STABLE_DURATION {
+                continue;
+            }
+            self.tiles[i].checked = true;
+            let (x, y, w, h) = self.tile_rect(i);
+            let pixels = self.tile_bgra(x, y, w, h);
+            if is_text(&pixels, w, h) {
+                patches.push(LosslessPatch {
+                    x: x as _,
+                    y: y as _,
+                    width: w as _,
+                    height: h as _,
+                    data: compress(&pixels).into(),
+                    ..Default::default()
+                });
+                self.tiles[i].patched = true;
+                count += 1;
+            }
+        }
+    }
+
+    fn tile_bgra(&self, x: usize, y: usize, w: usize, h: usize) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(w * h * 4);
+        if self.pixfmt == Pixfmt::RGBA {
+            let start = y * self.stride + x * 4;
+            // an empty tile is never text
+            swap_red_blue(&self.last[start..], self.stride, w, h, &mut pixels).ok();
+            return pixels;
+        }
+        for row in y..y + h {
+            let start = row * self.stride + x * 4;
+            pixels.extend_from_slice(&self.last[start..start + w * 4]);
+        }
+        pixels
+    }
+}
+
+// Text has many sharp edges on a background of a single color, photos and gradients have not.
+fn is_text(bgra: &[u8], w: usize, h: usize) -> bool {
+    if w < 2 || h == 0 {
+        return false;
+    }
+    let luma = |px: &[u8]| (px[2] as i32 * 299 + px[1] as i32 * 587 + px[0] as i32 * 114) / 1000;
+    let mut edges = 0;
+    let mut colors: HashMap<[u8; 3], usize> = HashMap::new();
+    for row in bgra.chunks_exact(w * 4) {
+        let mut last: Option<i32> = None;
+        for px in row.chunks_exact(4) {
+            let l = luma(px);
+            if let Some(last) = last {
+                if (l - last).abs() >= EDGE_CONTRAST {
+                    edges += 1;
+                }
+            }
+            last = Some(l);
+            *colors.entry([px[0], px[1], px[2]]).or_default() += 1;
+        }
+    }
+    let background = colors.values().max().cloned().unwrap_or_default();
+    edges * 1000 >= w * h * MIN_EDGE_PERMILLE && background * 100 >= w * h * MIN_BACKGROUND_PERCENT
+}
+
+/// The patches received by the client, drawn over each decoded frame.
+#[derive(Default)]
+pub struct TextPatchCache {
+    patches: HashMap<(usize, usize), (usize, usize, Vec<u8>)>,
+}
+
+impl TextPatchCache {
+    pub fn update(&mut self, patches: &LosslessPatches) {
+        if patches.reset {
+            self.patches.clear();
+        }
+        for p in patches.patches.iter() {
+            if p.x < 0 || p.y < 0 || p.width < 0 || p.height < 0 {
+                continue;
+            }
+            let (x, y, w, h) = (
+                p.x as usize,
+                p.y as usize,
+                p.width as usize,
+                p.height as usize,
+            );
+            if w > TILE_SIZE || h > TILE_SIZE {
+                continue;
+            }
+            if p.data.is_empty() {
+                self.patches.remove(&(x, y));
+                continue;
+            }
+            let pixels = decompress(&p.data);
+            if pixels.len() == w * h * 4 {
+                self.patches.insert((x, y), (w, h, pixels));
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+    }
+
+    pub fn apply(&self, rgb: &mut ImageRgb) {
+        let bpp = match rgb.fmt() {
+            ImageFormat::Raw => 3,
+            ImageFormat::ARGB | ImageFormat::ABGR => 4,
+        };
+        let align = rgb.align().max(1);
+        let bytes_per_row = (rgb.w * bpp + align - 1) & !(align - 1);
+        if rgb.raw.len() < bytes_per_row * rgb.h {
+            return;
+        }
+        for ((x, y), (w, h, pixels)) in self.patches.iter() {
+            if x.saturating_add(*w) > rgb.w || y.saturating_add(*h) > rgb.h {
+                continue;
+            }
+            for row in 0..*h {
+                let src = &pixels[row * w * 4..(row + 1) * w * 4];
+                let start = (y + row) * bytes_per_row + x * bpp;
+                let dst = &mut rgb.raw[start..start + w * bpp];
+                for (d, s) in dst.chunks_exact_mut(bpp).zip(src.chunks_exact(4)) {
+                    match rgb.fmt {
+                        // libyuv ARGB is BGRA in memory
+                        ImageFormat::ARGB => d.copy_from_slice(s),
+                        ImageFormat::ABGR => d.copy_from_slice(&[s[2], s[1], s[0], s[3]]),
+                        ImageFormat::Raw => d.copy_from_slice(&[s[2], s[1], s[0]]),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_tile() -> Vec<u8> {
+        let mut bgra = vec![255u8; TILE_SIZE * TILE_SIZE * 4];
+        for y in 0..TILE_SIZE {
+            for x in 0..TILE_SIZE {
+                if y % 8 < 5 && x % 3 == 0 {
+                    let i = (y * TILE_SIZE + x) * 4;
+                    bgra[i..i + 3].copy_from_slice(&[0, 0, 0]);
+                }
+            }
+        }
+        bgra
+    }
+
+    #[test]
+    fn test_is_text() {
+        assert!(is_text(&text_tile(), TILE_SIZE, TILE_SIZE));
+        let mut gradient = Vec::new();
+        for y in 0..TILE_SIZE {
+            for x in 0..TILE_SIZE {
+                gradient.extend_from_slice(&[x as u8 * 4, y as u8 * 4, (x + y) as u8, 255]);
+            }
+        }
+        assert!(!is_text(&gradient, TILE_SIZE, TILE_SIZE));
+        assert!(!is_text(
+            &vec![255u8; TILE_SIZE * TILE_SIZE * 4],
+            TILE_SIZE,
+            TILE_SIZE
+        ));
+    }
+
+    #[test]
+    fn test_detect_and_apply() {
+        let mut detector = TextPatchDetector::new(TILE_SIZE, TILE_SIZE);
+        let frame = text_tile();
+        let stride = TILE_SIZE * 4;
+        assert!(detector.update(&frame, stride, Pixfmt::BGRA).unwrap().reset);
+        assert!(detector.idle().is_none());
+        std::thread::sleep(STABLE_DURATION);
+        let patches = detector.idle().unwrap();
+        assert_eq!(patches.patches.len(), 1);
+        // checked only once
+        assert!(detector.idle().is_none());
+
+        let mut cache = TextPatchCache::default();
+        cache.update(&patches);
+        let mut rgb = ImageRgb::new(ImageFormat::ARGB, 1);
+        rgb.w = TILE_SIZE;
+        rgb.h = TILE_SIZE;
+        rgb.raw = vec![128; stride * TILE_SIZE];
+        cache.apply(&mut rgb);
+        assert_eq!(rgb.raw, frame);
+
+        // a change removes the patch
+        let mut changed = frame.clone();
+        changed[0] = 1;
+        let patches = detector.update(&changed, stride, Pixfmt::BGRA).unwrap();
+        assert!(patches.patches[0].data.is_empty());
+        cache.update(&patches);
+        assert!(cache.is_empty());
+    }
+}
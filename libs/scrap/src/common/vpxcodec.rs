@@ -7,7 +7,7 @@ use hbb_common::log;
 use hbb_common::message_proto::{Chroma, EncodedVideoFrame, EncodedVideoFrames, VideoFrame};
 use hbb_common::ResultType;
 
-use crate::codec::{base_bitrate, codec_thread_num, EncoderApi, Quality};
+use crate::codec::{base_bitrate, codec_thread_num, EncoderApi, Quality, Roi};
 use crate::{EncodeInput, EncodeYuvFormat, GoogleImage, Pixfmt, STRIDE_ALIGN};
 
 use super::vpx::{vp8e_enc_control_id::*, vpx_codec_err_t::*, *};
@@ -22,6 +22,10 @@ generate_call_ptr_macro!(call_vpx_ptr);
 const DEFAULT_QP_MAX: u32 = 56; // no more than 63
 const DEFAULT_QP_MIN: u32 = 12; // no more than 63
 
+// Quantizer deltas of the roi segment and the background segment
+const ROI_DELTA_Q: c_int = -20;
+const ROI_BACKGROUND_DELTA_Q: c_int = 10;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum VpxVideoCodecId {
     VP8,
@@ -41,6 +45,7 @@ pub struct VpxEncoder {
     id: VpxVideoCodecId,
     i444: bool,
     yuvfmt: EncodeYuvFormat,
+    roi_blocks: Option<(usize, usize, usize, usize)>, // (row start, row end, col start, col end)
 }
 
 pub struct VpxDecoder {
@@ -177,6 +182,7 @@ impl EncoderApi for VpxEncoder {
                     id: config.codec,
                     i444,
                     yuvfmt: Self::get_yuvfmt(config.width, config.height, i444),
+                    roi_blocks: None,
                 })
             }
             _ => Err(anyhow!("encoder type mismatch")),
@@ -247,7 +253,52 @@ impl EncoderApi for VpxEncoder {
         false
     }
 
-    fn disable(&self) {}
+    fn set_roi(&mut self, roi: Option<Roi>) -> ResultType<()> {
+        // The segment map is per 16x16 macroblock for VP8 and per 8x8 block for VP9.
+        let block = if self.id == VpxVideoCodecId::VP9 {
+            8
+        } else {
+            16
+        };
+        let rows = (self.height + block - 1) / block;
+        let cols = (self.width + block - 1) / block;
+        let roi_blocks = roi.map(|r| {
+            (
+                (r.y / block).min(rows),
+                ((r.y + r.height + block - 1) / block).min(rows),
+                (r.x / block).min(cols),
+                ((r.x + r.width + block - 1) / block).min(cols),
+            )
+        });
+        if roi_blocks == self.roi_blocks {
+            return Ok(());
+        }
+        let mut map = vec![0u8; rows * cols];
+        let mut roi_map: vpx_roi_map_t = unsafe { std::mem::zeroed() };
+        if let Some((r0, r1, c0, c1)) = roi_blocks {
+            for r in r0..r1 {
+                map[r * cols + c0..r * cols + c1].fill(1);
+            }
+            roi_map.delta_q[0] = ROI_BACKGROUND_DELTA_Q;
+            roi_map.delta_q[1] = ROI_DELTA_Q;
+        }
+        // All zero deltas disable the roi, the map is copied by the encoder.
+        roi_map.roi_map = map.as_mut_ptr();
+        roi_map.rows = rows as _;
+        roi_map.cols = cols as _;
+        let control = if self.id == VpxVideoCodecId::VP9 {
+            VP9E_SET_ROI_MAP
+        } else {
+            VP8E_SET_ROI_MAP
+        };
+        call_vpx!(vpx_codec_control_(
+            &mut self.ctx,
+            control as _,
+            &mut roi_map as *mut vpx_roi_map_t
+        ));
+        self.roi_blocks = roi_blocks;
+        Ok(())
+    }
 }
 
 impl VpxEncoder {
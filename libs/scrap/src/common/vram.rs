@@ -5,7 +5,7 @@ use std::{
 };
 
 use crate::{
-    codec::{base_bitrate, enable_vram_option, EncoderApi, EncoderCfg, Quality},
+    codec::{base_bitrate, enable_vram_option, EncoderApi, EncoderCfg, Quality, Roi},
     hwcodec::HwCodecConfig,
     AdapterDevice, CodecFormat, EncodeInput, EncodeYuvFormat, Pixfmt,
 };
@@ -203,26 +203,25 @@ impl EncoderApi for VRamEncoder {
         true
     }
 
-    fn disable(&self) {
-        HwCodecConfig::clear(true, true);
+    fn set_roi(&mut self, _roi: Option<Roi>) -> ResultType<()> {
+        Ok(())
     }
 }
 
 impl VRamEncoder {
     pub fn try_get(device: &AdapterDevice, format: CodecFormat) -> Option<FeatureContext> {
-        let v: Vec<_> = Self::available(format)
+        Self::all(device, format).drain(..).next()
+    }
+
+    /// All the encoders of the format on the adapter, the preferred first.
+    pub fn all(device: &AdapterDevice, format: CodecFormat) -> Vec<FeatureContext> {
+        let mut v: Vec<_> = Self::available(format)
             .drain(..)
             .filter(|e| e.luid == device.luid)
             .collect();
-        if v.len() > 0 {
-            // prefer ffmpeg
-            if let Some(ctx) = v.iter().find(|c| c.driver == Driver::FFMPEG) {
-                return Some(ctx.clone());
-            }
-            Some(v[0].clone())
-        } else {
-            None
-        }
+        // prefer ffmpeg
+        v.sort_by_key(|c| c.driver != Driver::FFMPEG);
+        v
     }
 
     pub fn available(format: CodecFormat) -> Vec<FeatureContext> {
@@ -327,6 +327,23 @@ impl Capturer {
         self.output_texture = texture;
     }
 
+    // The texture of a rotated display is rotated by the video processor, without it the frame
+    // has to go through system memory.
+    #[cfg(feature = "vram")]
+    pub fn texture_supported(&self) -> bool {
+        match self.display.rotation() {
+            DXGI_MODE_ROTATION_IDENTITY | DXGI_MODE_ROTATION_UNSPECIFIED => true,
+            _ => self.can_rotate_texture(),
+        }
+    }
+
+    fn can_rotate_texture(&self) -> bool {
+        !self.rotate.video_context.is_null()
+            && !self.rotate.video_device.is_null()
+            && !self.rotate.video_processor_enum.is_null()
+            && !self.rotate.video_processor.is_null()
+    }
+
     unsafe fn load_frame(&mut self, timeout: UINT) -> io::Result<(*const u8, i32)> {
         let mut frame = ptr::null_mut();
         #[allow(invalid_value)]
@@ -497,13 +514,7 @@ impl Capturer {
                 DXGI_MODE_ROTATION_ROTATE270 => 270,
                 _ => 0,
             };
-            if rotation != 0
-                && !self.texture.is_null()
-                && !self.rotate.video_context.is_null()
-                && !self.rotate.video_device.is_null()
-                && !self.rotate.video_processor_enum.is_null()
-                && !self.rotate.video_processor.is_null()
-            {
+            if rotation != 0 && !self.texture.is_null() && self.can_rotate_texture() {
                 let mut desc: D3D11_TEXTURE2D_DESC = mem::zeroed();
                 (*self.texture.0).GetDesc(&mut desc);
                 if rotation == 90 || rotation == 270 {
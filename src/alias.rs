@@ -0,0 +1,143 @@
+// Aliases of this device, names like "buildserver-3" which a peer can connect by instead of the id.
+//
+// They are kept in the options and registered with each rendezvous server by the rendezvous
+// mediator whenever they or the id change, replacing the ones registered before, and the server
+// resolves them to the id at connect time. An alias registered by another device is refused and
+// reported in the status, the others are registered all the same.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use hbb_common::{
+    anyhow::bail,
+    config::{keys::OPTION_ALIASES, Config},
+    get_uuid, is_valid_alias, log,
+    rendezvous_proto::{register_alias_response, RegisterAlias, RegisterAliasResponse},
+    ResultType,
+};
+use serde_derive::{Deserialize, Serialize};
+
+pub const MAX_ALIASES: usize = 8;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AliasStatus {
+    pub aliases: Vec<String>,
+    // a rendezvous server has answered the registration of the aliases
+    pub registered: bool,
+    // the aliases registered by other devices
+    pub taken: Vec<String>,
+    // of the registration, empty if none
+    pub error: String,
+}
+
+lazy_static::lazy_static! {
+    // the id and the aliases last sent to each rendezvous server
+    static ref SENT: Mutex<HashMap<String, (String, Vec<String>)>> = Default::default();
+    static ref STATUS: Mutex<AliasStatus> = Default::default();
+}
+
+pub fn get() -> Vec<String> {
+    parse(&Config::get_option(OPTION_ALIASES))
+}
+
+/// Replaces the aliases of this device, the error if they are not valid.
+pub fn set(aliases: Vec<String>) -> ResultType<()> {
+    let aliases = parse(&aliases.join(","));
+    if aliases.len() > MAX_ALIASES {
+        bail!("Too many aliases, at most {}", MAX_ALIASES);
+    }
+    if let Some(alias) = aliases.iter().find(|a| !is_valid_alias(a)) {
+        bail!("Invalid alias: {}", alias);
+    }
+    Config::set_option(OPTION_ALIASES.to_owned(), aliases.join(","));
+    Ok(())
+}
+
+pub fn status() -> AliasStatus {
+    let aliases = get();
+    let status = STATUS.lock().unwrap().clone();
+    if status.aliases == aliases {
+        status
+    } else {
+        // not sent yet
+        AliasStatus {
+            aliases,
+            ..Default::default()
+        }
+    }
+}
+
+/// The registration to send to the rendezvous server `host`, if the aliases or the id changed
+/// since the last one sent to it.
+pub fn registration(host: &str) -> Option<RegisterAlias> {
+    let id = Config::get_id();
+    let aliases = get();
+    let mut sent = SENT.lock().unwrap();
+    match sent.get(host) {
+        Some((i, a)) if *i == id && *a == aliases => return None,
+        // nothing to replace
+        None if aliases.is_empty() => return None,
+        _ => {}
+    }
+    // marked as sent, the servers which do not know the aliases do not answer
+    sent.insert(host.to_owned(), (id.clone(), aliases.clone()));
+    let mut status = STATUS.lock().unwrap();
+    if status.aliases != aliases {
+        *status = AliasStatus {
+            aliases: aliases.clone(),
+            ..Default::default()
+        };
+    }
+    Some(RegisterAlias {
+        id,
+        uuid: get_uuid().into(),
+        aliases,
+        ..Default::default()
+    })
+}
+
+pub fn on_response(host: &str, rar: RegisterAliasResponse) {
+    use register_alias_response::Result;
+    let error = match rar.result.enum_value() {
+        Ok(Result::OK) | Ok(Result::ALIAS_EXISTS) => "",
+        Ok(Result::INVALID_ALIAS_FORMAT) => "Invalid format",
+        Ok(Result::TOO_MANY_ALIASES) => "Too many aliases",
+        Ok(Result::UUID_MISMATCH) => "UUID mismatch",
+        Ok(Result::NOT_SUPPORT) => "server_not_support",
+        _ => "Server error",
+    };
+    if error.is_empty() {
+        log::info!("Aliases registered to {}, taken: {:?}", host, rar.taken);
+    } else {
+        log::error!("Failed to register aliases to {}: {}", host, error);
+    }
+    let mut status = STATUS.lock().unwrap();
+    status.registered = error.is_empty();
+    status.taken = rar.taken;
+    status.error = error.to_owned();
+}
+
+// lower case, the aliases differing in case only are the same
+fn parse(value: &str) -> Vec<String> {
+    let mut aliases: Vec<String> = Vec::new();
+    for alias in value.split(',') {
+        let alias = alias.trim().to_lowercase();
+        if !alias.is_empty() && !aliases.contains(&alias) {
+            aliases.push(alias);
+        }
+    }
+    aliases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse(" BuildServer-3, nas,buildserver-3,, "),
+            vec!["buildserver-3".to_owned(), "nas".to_owned()]
+        );
+        assert!(parse("").is_empty());
+    }
+}
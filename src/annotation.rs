@@ -0,0 +1,176 @@
+// The drawings of the peers controlling over the screen of this side, for the walkthroughs of a
+// training or a support: the cm keeps them, shows them over the screen, and sends them all to each
+// peer whenever they change, so a peer sees the ones of the others too.
+//
+// A stroke belongs to the connection which drew it, which takes its own back with undo or clear,
+// and they go with it once it leaves. The user of this side clears the ones of a peer from the cm.
+// The overlay on the screen of this side is on Windows only, and hidden from the capture, so the
+// peers do not see a stroke twice.
+
+use std::sync::Mutex;
+
+use hbb_common::{
+    message_proto::{annotation, annotation_stroke::Shape, Annotation, AnnotationStroke},
+    protobuf::Message as _,
+};
+
+const MAX_STROKES: usize = 256;
+const MAX_POINTS: usize = 4096;
+const MAX_WIDTH: u32 = 64;
+
+lazy_static::lazy_static! {
+    static ref BOARD: Mutex<Board> = Default::default();
+}
+
+#[derive(Default)]
+struct Board {
+    // with the connection which drew it, in the order drawn
+    strokes: Vec<(i32, AnnotationStroke)>,
+}
+
+impl Board {
+    fn apply(&mut self, conn: i32, annotation: Annotation) -> bool {
+        match annotation.union {
+            Some(annotation::Union::Stroke(stroke)) => {
+                let Some(stroke) = sanitize(stroke) else {
+                    return false;
+                };
+                if self.strokes.len() >= MAX_STROKES {
+                    self.strokes.remove(0);
+                }
+                self.strokes.push((conn, stroke));
+                true
+            }
+            Some(annotation::Union::Undo(_)) => {
+                match self.strokes.iter().rposition(|(c, _)| *c == conn) {
+                    Some(pos) => {
+                        self.strokes.remove(pos);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Some(annotation::Union::Clear(_)) => self.clear(conn),
+            _ => false,
+        }
+    }
+
+    fn clear(&mut self, conn: i32) -> bool {
+        let len = self.strokes.len();
+        self.strokes.retain(|(c, _)| *c != conn);
+        self.strokes.len() != len
+    }
+
+    fn count(&self, conn: i32) -> usize {
+        self.strokes.iter().filter(|(c, _)| *c == conn).count()
+    }
+
+    fn strokes(&self) -> Vec<AnnotationStroke> {
+        self.strokes.iter().map(|(_, s)| s.clone()).collect()
+    }
+}
+
+// None if nothing can be drawn of it
+fn sanitize(mut stroke: AnnotationStroke) -> Option<AnnotationStroke> {
+    let (min_points, max_points) = match stroke.shape.enum_value() {
+        Ok(Shape::Freehand) => (1, MAX_POINTS),
+        Ok(Shape::Arrow) | Ok(Shape::Box) => (2, 2),
+        Err(_) => return None,
+    };
+    let len = stroke.points.len() / 2 * 2;
+    stroke.points.truncate(len.min(max_points * 2));
+    if stroke.points.len() < min_points * 2 {
+        return None;
+    }
+    stroke.color &= 0xFFFFFF;
+    stroke.width = stroke.width.clamp(1, MAX_WIDTH);
+    Some(stroke)
+}
+
+/// Applies the annotation of the connection, true if the drawings changed.
+pub fn apply(conn: i32, annotation: Annotation) -> bool {
+    let changed = BOARD.lock().unwrap().apply(conn, annotation);
+    if changed {
+        on_changed();
+    }
+    changed
+}
+
+/// Takes the strokes of the connection back, true if it drew any.
+pub fn clear(conn: i32) -> bool {
+    let changed = BOARD.lock().unwrap().clear(conn);
+    if changed {
+        on_changed();
+    }
+    changed
+}
+
+/// The strokes the connection drew.
+pub fn count(conn: i32) -> usize {
+    BOARD.lock().unwrap().count(conn)
+}
+
+pub fn is_empty() -> bool {
+    BOARD.lock().unwrap().strokes.is_empty()
+}
+
+/// All the drawings as sent to the peers, the bytes of an annotation.
+pub fn board() -> Vec<u8> {
+    let mut annotation = Annotation::new();
+    annotation.mut_board().strokes = BOARD.lock().unwrap().strokes();
+    annotation.write_to_bytes().unwrap_or_default()
+}
+
+fn on_changed() {
+    #[cfg(windows)]
+    crate::platform::win_annotation::show(BOARD.lock().unwrap().strokes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stroke(shape: Shape, points: Vec<i32>) -> Annotation {
+        let mut annotation = Annotation::new();
+        annotation.set_stroke(AnnotationStroke {
+            shape: shape.into(),
+            points,
+            color: 0xFF123456,
+            width: 1000,
+            ..Default::default()
+        });
+        annotation
+    }
+
+    fn undo() -> Annotation {
+        let mut annotation = Annotation::new();
+        annotation.set_undo(true);
+        annotation
+    }
+
+    #[test]
+    fn test_board() {
+        let mut board = Board::default();
+        assert!(board.apply(1, stroke(Shape::Freehand, vec![0, 0, 10, 10, 20])));
+        assert!(!board.apply(1, stroke(Shape::Arrow, vec![0, 0, 10])));
+        assert!(board.apply(2, stroke(Shape::Box, vec![0, 0, 10, 10, 20, 20])));
+        assert!(board.apply(1, stroke(Shape::Arrow, vec![0, 0, 10, 10])));
+        let strokes = board.strokes();
+        assert_eq!(strokes[0].points, vec![0, 0, 10, 10]);
+        assert_eq!(strokes[0].color, 0x123456);
+        assert_eq!(strokes[0].width, MAX_WIDTH);
+        assert_eq!(strokes[1].points, vec![0, 0, 10, 10]);
+        // a peer takes its own strokes back only
+        assert!(board.apply(1, undo()));
+        assert_eq!(board.count(1), 1);
+        assert_eq!(board.count(2), 1);
+        assert!(board.clear(1));
+        assert!(!board.apply(1, undo()));
+        assert_eq!(board.strokes().len(), 1);
+        for _ in 0..MAX_STROKES {
+            board.apply(3, stroke(Shape::Freehand, vec![0, 0]));
+        }
+        assert_eq!(board.count(2), 0);
+        assert_eq!(board.strokes().len(), MAX_STROKES);
+    }
+}
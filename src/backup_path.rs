@@ -0,0 +1,220 @@
+// Relayed backup path of a direct session.
+//
+// Once a direct session is logged in, the controlling side asks for a token and requests a relay
+// with it as the uuid. The relay request reaches the rendezvous mediator of the controlled side
+// like any other, which hands the secured stream to the session instead of starting a new one.
+// Both sides send keep alives on the backup path with the count of the session messages received,
+// which lets the peer drop the ones kept to be sent again.
+//
+// The session moves to the backup path when the direct one fails, or when it is degraded on the
+// controlling side. A relayed session is moved the same way to a backup path through a better
+// relay server, which the controlled side asks for. The side which moves first sends its count on
+// the backup path and holds its messages, the other one answers with its own count and both send
+// again what the peer has not received, so that the session goes on without renegotiation.
+
+use std::{
+    collections::HashMap,
+    io::Error,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use hbb_common::{
+    bail,
+    bytes::BytesMut,
+    log,
+    message_proto::{message, multipath, Message, Multipath},
+    protobuf::Message as _,
+    rendezvous_proto::ConnType,
+    tokio::sync::mpsc,
+    ResultType, Stream,
+};
+
+const BACKUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The relay of a connection, for the controlling side to connect the backup path.
+#[derive(Debug, Clone, Default)]
+pub struct BackupRelay {
+    // the backup path of a relayed connection is only connected to move it to another relay
+    pub direct: bool,
+    pub relay_server: String,
+    pub rendezvous_server: String,
+    pub signed_id_pk: Vec<u8>,
+    pub key: String,
+    pub token: String,
+    pub conn_type: ConnType,
+}
+
+lazy_static::lazy_static! {
+    static ref TOKENS: Mutex<HashMap<String, mpsc::UnboundedSender<Stream>>> = Default::default();
+}
+
+/// The session waiting for the backup path of the relay `uuid`, if any.
+pub fn take_waiting_session(uuid: &str) -> Option<mpsc::UnboundedSender<Stream>> {
+    let mut tokens = TOKENS.lock().unwrap();
+    tokens.retain(|_, tx| !tx.is_closed());
+    tokens.remove(uuid)
+}
+
+fn new_msg(union: multipath::Union) -> Message {
+    let mut msg = Message::new();
+    msg.set_multipath(Multipath {
+        union: Some(union),
+        ..Default::default()
+    });
+    msg
+}
+
+pub fn request_msg() -> Message {
+    new_msg(multipath::Union::Request(true))
+}
+
+pub fn token_msg(token: String) -> Message {
+    new_msg(multipath::Union::Token(token))
+}
+
+pub fn migrate_msg(relay_server: String) -> Message {
+    new_msg(multipath::Union::Migrate(relay_server))
+}
+
+/// Whether the message counts in the session, the ones of the backup path do not.
+pub fn is_counted(msg: &Message) -> bool {
+    !matches!(
+        &msg.union,
+        Some(message::Union::Multipath(Multipath {
+            union: Some(multipath::Union::KeepAlive(_) | multipath::Union::SwitchPath(_)),
+            ..
+        }))
+    )
+}
+
+pub struct Backup {
+    path: Option<Stream>,
+    last_recv: Instant,
+    tx: mpsc::UnboundedSender<Stream>,
+    rx: mpsc::UnboundedReceiver<Stream>,
+}
+
+impl Default for Backup {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            path: None,
+            last_recv: Instant::now(),
+            tx,
+            rx,
+        }
+    }
+}
+
+impl Backup {
+    /// The sender the backup path is to be handed to.
+    pub fn sender(&self) -> mpsc::UnboundedSender<Stream> {
+        self.tx.clone()
+    }
+
+    /// A token for the peer to connect the backup path with.
+    pub fn new_token(&self) -> String {
+        let token = uuid::Uuid::new_v4().to_string();
+        let mut tokens = TOKENS.lock().unwrap();
+        tokens.retain(|_, tx| !tx.is_closed());
+        tokens.insert(token.clone(), self.tx.clone());
+        token
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// The next message of the backup path, pending while there is none.
+    pub async fn next(&mut self) -> Option<Result<BytesMut, Error>> {
+        loop {
+            match self.path.as_mut() {
+                Some(path) => return path.next().await,
+                None => {
+                    if let Some(path) = self.rx.recv().await {
+                        log::info!("backup path connected");
+                        self.path = Some(path);
+                        self.last_recv = Instant::now();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handles what came from the backup path, returns the count of the peer if it has moved
+    /// to it.
+    pub fn on_next(
+        &mut self,
+        res: Option<Result<BytesMut, Error>>,
+        active: &mut Stream,
+    ) -> Option<u64> {
+        let bytes = match res {
+            Some(Ok(bytes)) => bytes,
+            Some(Err(err)) => {
+                log::info!("backup path closed: {}", err);
+                self.path = None;
+                return None;
+            }
+            None => {
+                log::info!("backup path reset by the peer");
+                self.path = None;
+                return None;
+            }
+        };
+        self.last_recv = Instant::now();
+        match Message::parse_from_bytes(&bytes).map(|msg| msg.union) {
+            Ok(Some(message::Union::Multipath(Multipath {
+                union: Some(union), ..
+            }))) => match union {
+                multipath::Union::KeepAlive(received) => {
+                    active.ack(received);
+                    None
+                }
+                multipath::Union::SwitchPath(received) => Some(received),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Sends the keep alive every second, and gives up the backup path if the peer is silent.
+    pub async fn keep_alive(&mut self, active: &Stream) {
+        let Some(path) = self.path.as_mut() else {
+            return;
+        };
+        if self.last_recv.elapsed() > BACKUP_TIMEOUT {
+            log::info!("backup path timeout");
+            self.path = None;
+            return;
+        }
+        let msg = new_msg(multipath::Union::KeepAlive(active.received()));
+        if let Err(err) = path.send(&msg).await {
+            log::info!("backup path closed: {}", err);
+            self.path = None;
+        }
+    }
+
+    /// Moves the session to the backup path, with the count of the peer if it has moved first.
+    pub async fn switch(
+        &mut self,
+        active: &mut Stream,
+        peer_received: Option<u64>,
+    ) -> ResultType<()> {
+        let Some(mut path) = self.path.take() else {
+            bail!("no backup path");
+        };
+        log::info!(
+            "moving the session to the backup path, {} messages received",
+            active.received()
+        );
+        path.send(&new_msg(multipath::Union::SwitchPath(active.received())))
+            .await?;
+        path.take_over(active);
+        *active = path;
+        if let Some(received) = peer_received {
+            active.resume(received).await?;
+        }
+        Ok(())
+    }
+}
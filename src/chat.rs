@@ -0,0 +1,175 @@
+// The files and the images sent in the chat, a log dropped in by a technician without opening the
+// file manager: a file goes whole in a chat message, compressed as the blocks of the file
+// transfer, so it is limited to MAX_ATTACHMENT_SIZE, and is saved under a name not taken in the
+// downloads of the receiver.
+//
+// A message with an id not 0 is answered with a receipt, the error of the receiver in it if the
+// message could not be delivered, so the sender tells the ones delivered. The connection checks the
+// attachments of both ways with the DLP rules, and records them as file transfers.
+
+use std::path::{Path, PathBuf};
+
+use hbb_common::{
+    bail,
+    compress::decompress_bounded,
+    directories_next::UserDirs,
+    fs::{self, conflict},
+    message_proto::{ChatAttachment, ChatMessage, ChatReceipt, Message, Misc},
+    ResultType,
+};
+
+pub const MAX_ATTACHMENT_SIZE: u64 = 8 * 1024 * 1024;
+const IMAGE_EXTS: &[&str] = &["png", "jpg", "jpeg", "gif", "bmp", "webp"];
+
+/// The message carrying the file at `path`.
+pub fn attach(id: u32, path: &str) -> ResultType<ChatMessage> {
+    let path = Path::new(path);
+    let size = std::fs::metadata(path)?.len();
+    if size > MAX_ATTACHMENT_SIZE {
+        bail!(
+            "The file is larger than {} MB",
+            MAX_ATTACHMENT_SIZE / 1024 / 1024
+        );
+    }
+    let name = fs::get_file_name(path);
+    let (data, compressed) = fs::compress_data(&name, std::fs::read(path)?);
+    Ok(new_message(
+        id,
+        ChatAttachment {
+            image: is_image(&name),
+            name,
+            data: data.into(),
+            compressed,
+            ..Default::default()
+        },
+    ))
+}
+
+pub fn new_message(id: u32, attachment: ChatAttachment) -> ChatMessage {
+    ChatMessage {
+        text: attachment.name.clone(),
+        id,
+        attachment: Some(attachment).into(),
+        ..Default::default()
+    }
+}
+
+/// The file of the attachment, never decompressed past MAX_ATTACHMENT_SIZE.
+pub fn data(attachment: &ChatAttachment) -> ResultType<Vec<u8>> {
+    if !attachment.compressed {
+        if attachment.data.len() as u64 > MAX_ATTACHMENT_SIZE {
+            bail!("The file is too large");
+        }
+        return Ok(attachment.data.to_vec());
+    }
+    match decompress_bounded(&attachment.data, MAX_ATTACHMENT_SIZE as _) {
+        Ok(data) => Ok(data),
+        Err(err) => bail!("The file is too large or invalid: {}", err),
+    }
+}
+
+/// Saves the attachment in the downloads, the path it is saved at.
+pub fn save(attachment: &ChatAttachment) -> ResultType<String> {
+    let dir = UserDirs::new()
+        .and_then(|d| d.download_dir().map(|d| d.to_path_buf()))
+        .unwrap_or_else(|| fs::get_path(&fs::get_home_as_string()));
+    save_to(&dir, attachment)
+}
+
+fn save_to(dir: &Path, attachment: &ChatAttachment) -> ResultType<String> {
+    let data = data(attachment)?;
+    std::fs::create_dir_all(dir)?;
+    let mut name = safe_name(&attachment.name);
+    if dir.join(&name).exists() {
+        name = conflict::free_name(dir, &name);
+    }
+    let path: PathBuf = dir.join(name);
+    std::fs::write(&path, data)?;
+    Ok(fs::get_string(&path))
+}
+
+// the name of the file only, the peer does not choose where it goes
+fn safe_name(name: &str) -> String {
+    let name = name
+        .rsplit(|c| c == '/' || c == '\\')
+        .next()
+        .unwrap_or_default()
+        .trim();
+    if name.is_empty() || name == "." || name == ".." {
+        "attachment".to_owned()
+    } else {
+        name.chars()
+            .map(|c| if ":*?\"<>|".contains(c) { '_' } else { c })
+            .collect()
+    }
+}
+
+fn is_image(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .map(|e| IMAGE_EXTS.contains(&e.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+pub fn new_receipt(id: u32, error: String) -> Message {
+    let mut misc = Misc::new();
+    misc.set_chat_receipt(ChatReceipt {
+        id,
+        error,
+        ..Default::default()
+    });
+    let mut msg_out = Message::new();
+    msg_out.set_misc(misc);
+    msg_out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_name() {
+        assert_eq!(safe_name("../../etc/passwd"), "passwd");
+        assert_eq!(safe_name("C:\\Users\\a\\log.txt"), "log.txt");
+        assert_eq!(safe_name("a:b?.txt"), "a_b_.txt");
+        assert_eq!(safe_name(".."), "attachment");
+        assert_eq!(safe_name("dir/"), "attachment");
+    }
+
+    #[test]
+    fn test_attach_and_save() {
+        let dir = std::env::temp_dir().join(format!("chat_attachment_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("a.log");
+        let text = "a line of the log\n".repeat(1000);
+        std::fs::write(&src, &text).unwrap();
+        let msg = attach(1, &fs::get_string(&src)).unwrap();
+        let attachment = msg.attachment.as_ref().unwrap();
+        assert_eq!(msg.text, "a.log");
+        assert!(attachment.compressed && !attachment.image);
+        // beside the one existing
+        let saved = save_to(&dir, attachment).unwrap();
+        assert_eq!(Path::new(&saved), dir.join("a (1).log"));
+        assert_eq!(std::fs::read_to_string(&saved).unwrap(), text);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_data_bounded() {
+        let attachment = |data: Vec<u8>, compressed| ChatAttachment {
+            name: "a.bin".to_owned(),
+            data: data.into(),
+            compressed,
+            ..Default::default()
+        };
+        let small = vec![0u8; 1000];
+        let compressed = hbb_common::compress::compress(&small);
+        assert_eq!(data(&attachment(compressed, true)).unwrap(), small);
+        // a few bytes on the wire, more than allowed once decompressed
+        let large = vec![0u8; MAX_ATTACHMENT_SIZE as usize + 1];
+        let compressed = hbb_common::compress::compress(&large);
+        assert!(compressed.len() < 1024 * 1024);
+        assert!(data(&attachment(compressed, true)).is_err());
+        assert!(data(&attachment(large, false)).is_err());
+    }
+}
@@ -191,3 +191,79 @@ pub async fn start_one_port_forward(
     }
     log::info!("port forward (:{}) exit", port);
 }
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn take_screenshot(id: &str, display: i32, key: String, token: String) {
+    match crate::client::screenshot::take_screenshot(id, display, "", &key, &token).await {
+        Ok(png) => {
+            let filename = format!(
+                "{}_display{}_{}.png",
+                id,
+                display,
+                chrono::Local::now().format("%Y%m%d%H%M%S")
+            );
+            match std::fs::write(&filename, png) {
+                Ok(_) => log::info!("screenshot saved to {}", filename),
+                Err(err) => log::error!("Failed to save {}: {}", filename, err),
+            }
+        }
+        Err(err) => {
+            log::error!("Failed to take screenshot of {}: {}", id, err);
+        }
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn play_macro(id: &str, name: &str, key: String, token: String) {
+    match crate::client::input_macro::play(id, name, "", &key, &token).await {
+        Ok(_) => log::info!("macro {} played on {}", name, id),
+        Err(err) => log::error!("Failed to play macro {} on {}: {}", name, id, err),
+    }
+}
+
+/// Opens a terminal of the peer in this one, returns the exit code of its shell.
+#[tokio::main(flavor = "current_thread")]
+pub async fn open_terminal(id: &str, key: String, token: String) -> i32 {
+    match crate::client::terminal::run(id, "", &key, &token).await {
+        Ok(exit_code) => exit_code,
+        Err(err) => {
+            log::error!("Failed to open the terminal of {}: {}", id, err);
+            1
+        }
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn serve_webdav(id: &str, port: u16, key: String, token: String) {
+    if let Err(err) = crate::client::webdav::serve(id, port, "", &key, &token).await {
+        log::error!("Failed to serve the files of {} over WebDAV: {}", id, err);
+    }
+}
+
+/// Copies `from` into the folder `to`, from the peer if not `upload`, returns false if failed.
+#[tokio::main(flavor = "current_thread")]
+pub async fn copy_files(
+    id: &str,
+    upload: bool,
+    from: &str,
+    to: &str,
+    policy: FileConflictPolicy,
+    key: String,
+    token: String,
+) -> bool {
+    let res = if upload {
+        crate::client::file_copy::upload(id, from, to, policy, "", &key, &token).await
+    } else {
+        crate::client::file_copy::download(id, from, to, policy, "", &key, &token).await
+    };
+    match res {
+        Ok(_) => {
+            log::info!("copied {} to {}", from, to);
+            true
+        }
+        Err(err) => {
+            log::error!("Failed to copy {} to {}: {}", from, to, err);
+            false
+        }
+    }
+}
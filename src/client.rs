@@ -14,7 +14,7 @@ use ringbuf::{ring_buffer::RbBase, Rb};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     ffi::c_void,
     io,
     net::SocketAddr,
@@ -27,6 +27,7 @@ use std::{
 };
 use uuid::Uuid;
 
+use av_sync::AvSync;
 pub use file_trait::FileManager;
 #[cfg(not(feature = "flutter"))]
 #[cfg(not(any(target_os = "android", target_os = "ios")))]
@@ -44,7 +45,10 @@ use hbb_common::{
     protobuf::{Message as _, MessageField},
     rand,
     rendezvous_proto::*,
-    socket_client::{connect_tcp, connect_tcp_local, ipv4_to_ipv6},
+    socket_client::{
+        self, connect_server, connect_tcp, connect_tcp_local, ipv4_to_ipv6, local_ipv6_candidate,
+        race, IpFamily,
+    },
     sodiumoxide::{base64, crypto::sign},
     tcp::FramedStream,
     timeout,
@@ -52,23 +56,29 @@ use hbb_common::{
         self,
         time::{interval, Duration, Instant},
     },
-    AddrMangle, ResultType, Stream,
+    websocket, AddrMangle, ResultType, Stream,
 };
 use hbb_common::{
-    config::keys::OPTION_ALLOW_AUTO_RECORD_OUTGOING,
+    config::keys::{
+        OPTION_ALLOW_AUTO_RECORD_OUTGOING, OPTION_RECORD_SPLIT_DURATION, OPTION_RECORD_SPLIT_SIZE,
+    },
     tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver},
 };
 pub use helper::*;
 use scrap::{
     codec::Decoder,
-    record::{Recorder, RecorderContext},
+    filter::{FilterContext, VideoFilters},
+    record::{RecordSplit, Recorder, RecorderContext},
+    text_patch::TextPatchCache,
     CodecFormat, ImageFormat, ImageRgb, ImageTexture,
 };
 
 use crate::{
     check_port,
     common::input::{MOUSE_BUTTON_LEFT, MOUSE_BUTTON_RIGHT, MOUSE_TYPE_DOWN, MOUSE_TYPE_UP},
-    create_symmetric_key_msg, decode_id_pk, get_rs_pk, is_keyboard_mode_supported, secure_tcp,
+    create_symmetric_key_msg, decode_id_pk, get_rs_pk, is_keyboard_mode_supported,
+    opus_multistream::MultistreamDecoder,
+    secure_tcp,
     ui_interface::{get_builtin_option, use_texture_render},
     ui_session_interface::{InvokeUiSession, Session},
 };
@@ -81,9 +91,23 @@ use crate::ui_session_interface::SessionPermissionConfig;
 
 pub use super::lang::*;
 
+pub mod av_sync;
+pub mod camera;
+pub mod file_copy;
 pub mod file_trait;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub mod gamepad;
+pub mod headless;
 pub mod helper;
+pub mod input_macro;
 pub mod io_loop;
+pub mod metrics;
+pub mod screenshot;
+pub mod sync_job;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub mod terminal;
+pub mod wake_on_lan;
+pub mod webdav;
 
 pub const MILLI1: Duration = Duration::from_millis(1);
 pub const SEC30: Duration = Duration::from_secs(30);
@@ -106,7 +130,13 @@ pub const LOGIN_MSG_PASSWORD_EMPTY: &str = "Empty Password";
 pub const LOGIN_MSG_PASSWORD_WRONG: &str = "Wrong Password";
 pub const LOGIN_MSG_2FA_WRONG: &str = "Wrong 2FA Code";
 pub const REQUIRE_2FA: &'static str = "2FA Required";
+pub const LOGIN_MSG_FIDO2_FAILED: &str = "Security key not verified";
 pub const LOGIN_MSG_NO_PASSWORD_ACCESS: &str = "No Password Access";
+pub const LOGIN_MSG_ACCESS_REASON_REQUIRED: &str = "Access reason required";
+pub const LOGIN_MSG_IDENTITY_NOT_VERIFIED: &str = "Identity not verified";
+pub const LOGIN_MSG_UNTRUSTED_DEVICE: &str = "Untrusted device";
+pub const LOGIN_MSG_NEW_DEVICE: &str = "New device";
+pub const LOGIN_MSG_OUTSIDE_SCHEDULE: &str = "Outside schedule";
 pub const LOGIN_MSG_OFFLINE: &str = "Offline";
 pub const LOGIN_SCREEN_WAYLAND: &str = "Wayland login screen is not supported";
 #[cfg(target_os = "linux")]
@@ -235,6 +265,7 @@ impl Client {
         debug_assert!(peer == interface.get_id());
         interface.update_direct(None);
         interface.update_received(false);
+        interface.get_lch().write().unwrap().backup_relay = None;
         match Self::_start(peer, key, token, conn_type, interface).await {
             Err(err) => {
                 let err_str = err.to_string();
@@ -263,7 +294,11 @@ impl Client {
         if hbb_common::is_ip_str(peer) {
             return Ok((
                 (
-                    connect_tcp(check_port(peer, RELAY_PORT + 1), CONNECT_TIMEOUT).await?,
+                    crate::port_knock::connect(
+                        &check_port(peer, RELAY_PORT + 1),
+                        CONNECT_TIMEOUT,
+                    )
+                    .await?,
                     true,
                     None,
                 ),
@@ -273,7 +308,7 @@ impl Client {
         // Allow connect to {domain}:{port}
         if hbb_common::is_domain_port_str(peer) {
             return Ok((
-                (connect_tcp(peer, CONNECT_TIMEOUT).await?, true, None),
+                (crate::port_knock::connect(peer, CONNECT_TIMEOUT).await?, true, None),
                 (0, "".to_owned()),
             ));
         }
@@ -301,13 +336,18 @@ impl Client {
             }
         };
 
-        let mut socket = connect_tcp(&*rendezvous_server, CONNECT_TIMEOUT).await;
+        let mut socket = connect_server(
+            &rendezvous_server,
+            websocket::RENDEZVOUS_PATH,
+            CONNECT_TIMEOUT,
+        )
+        .await;
         debug_assert!(!servers.contains(&rendezvous_server));
         if socket.is_err() && !servers.is_empty() {
             log::info!("try the other servers: {:?}", servers);
             for server in servers {
                 let server = check_port(server, RENDEZVOUS_PORT);
-                socket = connect_tcp(&*server, CONNECT_TIMEOUT).await;
+                socket = connect_server(&server, websocket::RENDEZVOUS_PATH, CONNECT_TIMEOUT).await;
                 if socket.is_ok() {
                     rendezvous_server = server;
                     break;
@@ -320,6 +360,17 @@ impl Client {
         log::info!("rendezvous server: {}", rendezvous_server);
         let mut socket = socket?;
         let my_addr = socket.local_addr();
+        // nothing but the servers is reachable if the rendezvous server is through its tunnel
+        let force_relay =
+            interface.is_force_relay() || socket_client::is_tunneled(&rendezvous_server);
+        // the rendezvous server only sees the address of one family, the global ipv6 address is
+        // passed to the peer to be tried as well
+        let my_addr_v6 = if my_addr.is_ipv4() && !force_relay && !Config::is_proxy() {
+            local_ipv6_candidate(None)
+        } else {
+            None
+        };
+        let mut peer_addr_v6 = None;
         let mut signed_id_pk = Vec::new();
         let mut relay_server = "".to_owned();
 
@@ -334,11 +385,13 @@ impl Client {
         let my_nat_type = crate::get_nat_type(100).await;
         let mut is_local = false;
         let mut feedback = 0;
+        // the id the rendezvous server resolves `peer` to if it is an alias, which the peer signs
+        let mut peer_id = peer.to_owned();
         for i in 1..=3 {
             log::info!("#{} punch attempt with {}, id: {}", i, my_addr, peer);
             let mut msg_out = RendezvousMessage::new();
             use hbb_common::protobuf::Enum;
-            let nat_type = if interface.is_force_relay() {
+            let nat_type = if force_relay {
                 NatType::SYMMETRIC
             } else {
                 NatType::from_i32(my_nat_type).unwrap_or(NatType::UNKNOWN_NAT)
@@ -350,6 +403,9 @@ impl Client {
                 licence_key: key.to_owned(),
                 conn_type: conn_type.into(),
                 version: crate::VERSION.to_owned(),
+                socket_addr_v6: my_addr_v6
+                    .map(|addr| AddrMangle::encode(addr).into())
+                    .unwrap_or_default(),
                 ..Default::default()
             });
             socket.send(&msg_out).await?;
@@ -384,8 +440,20 @@ impl Client {
                             signed_id_pk = ph.pk.into();
                             relay_server = ph.relay_server;
                             peer_addr = AddrMangle::decode(&ph.socket_addr);
+                            if ph.socket_addr_v6.len() == 18 {
+                                peer_addr_v6 = Some(AddrMangle::decode(&ph.socket_addr_v6));
+                            }
                             feedback = ph.feedback;
-                            log::info!("Hole Punched {} = {}", peer, peer_addr);
+                            if !ph.peer_id.is_empty() {
+                                log::info!("{} is an alias of {}", peer, ph.peer_id);
+                                peer_id = ph.peer_id;
+                            }
+                            log::info!(
+                                "Hole Punched {} = {}, ipv6: {:?}",
+                                peer,
+                                peer_addr,
+                                peer_addr_v6
+                            );
                             break;
                         }
                     }
@@ -396,8 +464,22 @@ impl Client {
                             rr.relay_server
                         );
                         signed_id_pk = rr.pk().into();
+                        if !rr.peer_id.is_empty() {
+                            log::info!("{} is an alias of {}", peer, rr.peer_id);
+                            peer_id = rr.peer_id.clone();
+                        }
+                        interface.get_lch().write().unwrap().backup_relay =
+                            Some(crate::backup_path::BackupRelay {
+                                direct: false,
+                                relay_server: rr.relay_server.clone(),
+                                rendezvous_server: rendezvous_server.clone(),
+                                signed_id_pk: signed_id_pk.clone(),
+                                key: key.to_owned(),
+                                token: token.to_owned(),
+                                conn_type,
+                            });
                         let mut conn = Self::create_relay(
-                            peer,
+                            &peer_id,
                             rr.uuid,
                             rr.relay_server,
                             key,
@@ -407,7 +489,7 @@ impl Client {
                         .await?;
                         feedback = rr.feedback;
                         let pk =
-                            Self::secure_connection(peer, signed_id_pk, key, &mut conn).await?;
+                            Self::secure_connection(&peer_id, signed_id_pk, key, &mut conn).await?;
                         return Ok(((conn, false, pk), (feedback, rendezvous_server)));
                     }
                     _ => {
@@ -435,7 +517,8 @@ impl Client {
             Self::connect(
                 my_addr,
                 peer_addr,
-                peer,
+                my_addr_v6.zip(peer_addr_v6),
+                &peer_id,
                 signed_id_pk,
                 &relay_server,
                 &rendezvous_server,
@@ -457,6 +540,7 @@ impl Client {
     async fn connect(
         local_addr: SocketAddr,
         peer: SocketAddr,
+        ipv6: Option<(SocketAddr, SocketAddr)>,
         peer_id: &str,
         signed_id_pk: Vec<u8>,
         relay_server: &str,
@@ -502,10 +586,32 @@ impl Client {
                 connect_timeout = MIN;
             }
         }
-        log::info!("peer address: {}, timeout: {}", peer, connect_timeout);
+        log::info!(
+            "peer address: {}, ipv6: {:?}, timeout: {}",
+            peer,
+            ipv6.map(|(_, peer)| peer),
+            connect_timeout
+        );
         let start = std::time::Instant::now();
-        // NOTICE: Socks5 is be used event in intranet. Which may be not a good way.
-        let mut conn = connect_tcp_local(peer, Some(local_addr), connect_timeout).await;
+        let mut conn = if socket_client::is_tunneled(rendezvous_server) {
+            Err(anyhow!(
+                "Only the servers are reachable, through their tunnel"
+            ))
+        } else {
+            // NOTICE: Socks5 is be used event in intranet. Which may be not a good way.
+            match ipv6 {
+                Some((local_addr_v6, peer_v6)) => {
+                    let v4 = connect_tcp_local(peer, Some(local_addr), connect_timeout);
+                    let v6 = connect_tcp_local(peer_v6, Some(local_addr_v6), connect_timeout);
+                    if IpFamily::preferred() == IpFamily::V4 {
+                        race(vec![v4, v6]).await
+                    } else {
+                        race(vec![v6, v4]).await
+                    }
+                }
+                None => connect_tcp_local(peer, Some(local_addr), connect_timeout).await,
+            }
+        };
         let mut direct = !conn.is_err();
         interface.update_direct(Some(direct));
         if interface.is_force_relay() || conn.is_err() {
@@ -518,6 +624,7 @@ impl Client {
                     key,
                     token,
                     conn_type,
+                    None,
                 )
                 .await;
                 interface.update_direct(Some(false));
@@ -536,6 +643,18 @@ impl Client {
         }
         let mut conn = conn?;
         log::info!("{:?} used to establish connection", start.elapsed());
+        if !relay_server.is_empty() {
+            interface.get_lch().write().unwrap().backup_relay =
+                Some(crate::backup_path::BackupRelay {
+                    direct,
+                    relay_server: relay_server.to_owned(),
+                    rendezvous_server: rendezvous_server.to_owned(),
+                    signed_id_pk: signed_id_pk.clone(),
+                    key: key.to_owned(),
+                    token: token.to_owned(),
+                    conn_type,
+                });
+        }
         let pk = Self::secure_connection(peer_id, signed_id_pk, key, &mut conn).await?;
         Ok((conn, direct, pk))
     }
@@ -580,7 +699,16 @@ impl Client {
                 let bytes = res?;
                 if let Ok(msg_in) = Message::parse_from_bytes(&bytes) {
                     if let Some(message::Union::SignedId(si)) = msg_in.union {
-                        if let Ok((id, their_pk_b)) = decode_id_pk(&si.id, &sign_pk) {
+                        let signed = decode_id_pk(&si.id, &sign_pk).or_else(|err| {
+                            // the peer is rotating its keypair, and the rendezvous server has
+                            // not been given the new public key yet
+                            let signed = decode_id_pk(&si.old_id, &sign_pk).map_err(|_| err);
+                            if signed.is_ok() {
+                                log::info!("Handshake with the old keypair of {}", peer_id);
+                            }
+                            signed
+                        });
+                        if let Ok((id, their_pk_b)) = signed {
                             if id == peer_id {
                                 let (asymmetric_value, symmetric_value, key) =
                                     create_symmetric_key_msg(their_pk_b);
@@ -619,6 +747,28 @@ impl Client {
         Ok(option_pk)
     }
 
+    /// Connect the relayed backup path of a direct connection, with the token of the peer as the
+    /// uuid of the relay.
+    pub async fn connect_backup_relay(
+        peer: &str,
+        relay: crate::backup_path::BackupRelay,
+        uuid: &str,
+    ) -> ResultType<Stream> {
+        let mut conn = Self::request_relay(
+            peer,
+            relay.relay_server,
+            &relay.rendezvous_server,
+            !relay.signed_id_pk.is_empty(),
+            &relay.key,
+            &relay.token,
+            relay.conn_type,
+            Some(uuid),
+        )
+        .await?;
+        Self::secure_connection(peer, relay.signed_id_pk, &relay.key, &mut conn).await?;
+        Ok(conn)
+    }
+
     /// Request a relay connection to the server.
     async fn request_relay(
         peer: &str,
@@ -628,16 +778,25 @@ impl Client {
         key: &str,
         token: &str,
         conn_type: ConnType,
+        uuid: Option<&str>,
     ) -> ResultType<Stream> {
+        let fixed_uuid = uuid;
         let mut succeed = false;
         let mut uuid = "".to_owned();
         let mut ipv4 = true;
+        // the peer may answer with a TURN relayed address, asked once, the backup paths go through
+        // the relay server asked for
+        let mut turn = fixed_uuid.is_none();
 
         for i in 1..=3 {
             // use different socket due to current hbbs implementation requiring different nat address for each attempt
-            let mut socket = connect_tcp(rendezvous_server, CONNECT_TIMEOUT)
-                .await
-                .with_context(|| "Failed to connect to rendezvous server")?;
+            let mut socket = connect_server(
+                rendezvous_server,
+                websocket::RENDEZVOUS_PATH,
+                CONNECT_TIMEOUT,
+            )
+            .await
+            .with_context(|| "Failed to connect to rendezvous server")?;
 
             if !key.is_empty() && !token.is_empty() {
                 // mainly for the security of token
@@ -646,7 +805,9 @@ impl Client {
 
             ipv4 = socket.local_addr().is_ipv4();
             let mut msg_out = RendezvousMessage::new();
-            uuid = Uuid::new_v4().to_string();
+            uuid = fixed_uuid
+                .map(|x| x.to_owned())
+                .unwrap_or_else(|| Uuid::new_v4().to_string());
             log::info!(
                 "#{} request relay attempt, id: {}, uuid: {}, relay_server: {}, secure: {}",
                 i,
@@ -661,6 +822,7 @@ impl Client {
                 uuid: uuid.clone(),
                 relay_server: relay_server.clone(),
                 secure,
+                turn,
                 ..Default::default()
             });
             socket.send(&msg_out).await?;
@@ -672,6 +834,21 @@ impl Client {
                     if !rs.refuse_reason.is_empty() {
                         bail!(rs.refuse_reason);
                     }
+                    if !rs.turn_addr.is_empty() {
+                        turn = false;
+                        log::info!("connecting to the TURN relayed address {}", rs.turn_addr);
+                        match connect_tcp(&*rs.turn_addr, CONNECT_TIMEOUT).await {
+                            Ok(conn) => return Ok(conn),
+                            Err(err) => {
+                                log::warn!(
+                                    "Failed to connect to the TURN relayed address {}: {}",
+                                    rs.turn_addr,
+                                    err
+                                );
+                                continue;
+                            }
+                        }
+                    }
                     succeed = true;
                     break;
                 }
@@ -692,8 +869,9 @@ impl Client {
         conn_type: ConnType,
         ipv4: bool,
     ) -> ResultType<Stream> {
-        let mut conn = connect_tcp(
-            ipv4_to_ipv6(check_port(relay_server, RELAY_PORT), ipv4),
+        let mut conn = connect_server(
+            &ipv4_to_ipv6(check_port(relay_server, RELAY_PORT), ipv4),
+            websocket::RELAY_PATH,
             CONNECT_TIMEOUT,
         )
         .await
@@ -883,10 +1061,40 @@ impl ClipboardHandler for ClientClipboardHandler {
     }
 }
 
+// The plain opus of mono and stereo, or the multistream of more channels.
+enum OpusDecoder {
+    Plain(AudioDecoder),
+    Multistream(MultistreamDecoder),
+}
+
+impl OpusDecoder {
+    fn new(f: &AudioFormat) -> ResultType<Self> {
+        if f.mapping.is_empty() {
+            let channels = if f.channels > 1 { Stereo } else { Mono };
+            Ok(Self::Plain(AudioDecoder::new(f.sample_rate, channels)?))
+        } else {
+            Ok(Self::Multistream(MultistreamDecoder::new(
+                f.sample_rate,
+                f.channels as _,
+                f.streams,
+                f.coupled_streams,
+                &f.mapping,
+            )?))
+        }
+    }
+
+    fn decode_float(&mut self, input: &[u8], output: &mut [f32]) -> ResultType<usize> {
+        Ok(match self {
+            Self::Plain(d) => d.decode_float(input, output, false)?,
+            Self::Multistream(d) => d.decode_float(input, output)?,
+        })
+    }
+}
+
 /// Audio handler for the [`Client`].
 #[derive(Default)]
 pub struct AudioHandler {
-    audio_decoder: Option<(AudioDecoder, Vec<f32>)>,
+    audio_decoder: Option<(OpusDecoder, Vec<f32>)>,
     #[cfg(target_os = "android")]
     oboe: Option<OboePlayer>,
     #[cfg(target_os = "linux")]
@@ -901,6 +1109,8 @@ pub struct AudioHandler {
     device_channel: u16,
     #[cfg(not(any(target_os = "android", target_os = "linux")))]
     ready: Arc<std::sync::Mutex<bool>>,
+    // the name of the output device, the default one if none
+    output: Option<String>,
 }
 
 #[cfg(not(any(target_os = "android", target_os = "linux")))]
@@ -938,6 +1148,13 @@ impl AudioBuffer {
 }
 
 impl AudioHandler {
+    pub fn with_output(output: String) -> Self {
+        Self {
+            output: Some(output),
+            ..Default::default()
+        }
+    }
+
     /// Start the audio playback.
     #[cfg(target_os = "linux")]
     fn start_audio(&mut self, format0: AudioFormat) -> ResultType<()> {
@@ -953,15 +1170,23 @@ impl AudioHandler {
         if !spec.is_valid() {
             bail!("Invalid audio format");
         }
+        // the surround is decoded in the order of WAVE, not the default one of PulseAudio
+        let mut map = pulse::channelmap::Map::default();
+        let map = if spec.channels > 2 {
+            map.init_extend(spec.channels, pulse::channelmap::MapDef::WAVEEx)
+                .map(|m| &*m)
+        } else {
+            None
+        };
 
         self.simple = Some(Simple::new(
             None,                   // Use the default server
             &crate::get_app_name(), // Our application’s name
             Direction::Playback,    // We want a playback stream
-            None,                   // Use the default device
+            self.output.as_deref(), // None for the default device
             "playback",             // Description of our stream
             &spec,                  // Our sample format
-            None,                   // Use default channel map
+            map,                    // None for the default channel map
             None,                   // Use default buffering attributes
         )?);
         self.sample_rate = (format0.sample_rate, format0.sample_rate);
@@ -971,6 +1196,9 @@ impl AudioHandler {
     /// Start the audio playback.
     #[cfg(target_os = "android")]
     fn start_audio(&mut self, format0: AudioFormat) -> ResultType<()> {
+        if let Some(output) = &self.output {
+            bail!("Choosing the output device \"{output}\" is not supported");
+        }
         self.oboe = Some(OboePlayer::new(
             format0.channels as _,
             format0.sample_rate as _,
@@ -982,11 +1210,18 @@ impl AudioHandler {
     /// Start the audio playback.
     #[cfg(not(any(target_os = "android", target_os = "linux")))]
     fn start_audio(&mut self, format0: AudioFormat) -> ResultType<()> {
-        let device = AUDIO_HOST
-            .default_output_device()
-            .with_context(|| "Failed to get default output device")?;
+        let device = match &self.output {
+            Some(output) => AUDIO_HOST
+                .output_devices()
+                .map_err(|e| anyhow!(e))?
+                .find(|d| d.name().ok().as_ref() == Some(output))
+                .with_context(|| format!("Failed to get output device \"{output}\""))?,
+            None => AUDIO_HOST
+                .default_output_device()
+                .with_context(|| "Failed to get default output device")?,
+        };
         log::info!(
-            "Using default output device: \"{}\"",
+            "Using output device: \"{}\"",
             device.name().unwrap_or("".to_owned())
         );
         let config = device.default_output_config().map_err(|e| anyhow!(e))?;
@@ -1025,7 +1260,7 @@ impl AudioHandler {
 
     /// Handle audio format and create an audio decoder.
     pub fn handle_format(&mut self, f: AudioFormat) {
-        match AudioDecoder::new(f.sample_rate, if f.channels > 1 { Stereo } else { Mono }) {
+        match OpusDecoder::new(&f) {
             Ok(d) => {
                 let buffer = vec![0.; f.sample_rate as usize * f.channels as usize];
                 self.audio_decoder = Some((d, buffer));
@@ -1055,9 +1290,10 @@ impl AudioHandler {
             return;
         }
         self.audio_decoder.as_mut().map(|(d, buffer)| {
-            if let Ok(n) = d.decode_float(&frame.data, buffer, false) {
+            if let Ok(n) = d.decode_float(&frame.data, buffer) {
                 let channels = self.channels;
                 let n = n * (channels as usize);
+                crate::echo::on_far_end(&buffer[0..n]);
                 #[cfg(not(any(target_os = "android", target_os = "linux")))]
                 {
                     let sample_rate0 = self.sample_rate.0;
@@ -1153,9 +1389,13 @@ pub struct VideoHandler {
     pub texture: ImageTexture,
     recorder: Arc<Mutex<Option<Recorder>>>,
     record: bool,
+    record_audio_format: Option<AudioFormat>,
     _display: usize, // useful for debug
     fail_counter: usize,
     first_frame: bool,
+    text_patches: TextPatchCache,
+    last_pixelbuffer: bool,
+    filter: FilterContext,
 }
 
 impl VideoHandler {
@@ -1179,9 +1419,13 @@ impl VideoHandler {
             texture: Default::default(),
             recorder: Default::default(),
             record: false,
+            record_audio_format: None,
             _display,
             fail_counter: 0,
             first_frame: true,
+            text_patches: Default::default(),
+            last_pixelbuffer: false,
+            filter: Default::default(),
         }
     }
 
@@ -1208,6 +1452,13 @@ impl VideoHandler {
                 );
                 if res.as_ref().is_ok_and(|x| *x) {
                     self.fail_counter = 0;
+                    self.last_pixelbuffer = *pixelbuffer;
+                    if *pixelbuffer && !self.text_patches.is_empty() {
+                        self.text_patches.apply(&mut self.rgb);
+                    }
+                    if *pixelbuffer {
+                        self.filter.apply(&mut self.rgb);
+                    }
                 } else {
                     if self.fail_counter < usize::MAX {
                         if self.first_frame && self.fail_counter < MAX_DECODE_FAIL_COUNTER {
@@ -1239,6 +1490,24 @@ impl VideoHandler {
         }
     }
 
+    /// Set the post-processing filters applied to the decoded rgb frames.
+    pub fn set_filters(&mut self, filters: VideoFilters, view_scale: f64) {
+        self.filter.set(filters, view_scale);
+    }
+
+    /// Update the lossless text patches, returns true if the last frame should be drawn again.
+    ///
+    /// The filtered frame can't be filtered again, the patches are drawn with the next frame.
+    pub fn handle_lossless_patches(&mut self, patches: &LosslessPatches) -> bool {
+        self.text_patches.update(patches);
+        if self.last_pixelbuffer && !self.rgb.raw.is_empty() && self.filter.is_empty() {
+            self.text_patches.apply(&mut self.rgb);
+            true
+        } else {
+            false
+        }
+    }
+
     /// Reset the decoder, change format if it is Some
     pub fn reset(&mut self, format: Option<CodecFormat>) {
         log::info!(
@@ -1264,14 +1533,44 @@ impl VideoHandler {
                 dir: crate::ui_interface::video_save_directory(false),
                 display,
                 tx: None,
+                split: RecordSplit::new(
+                    &LocalConfig::get_option(OPTION_RECORD_SPLIT_SIZE),
+                    &LocalConfig::get_option(OPTION_RECORD_SPLIT_DURATION),
+                ),
             })
-            .map_or(Default::default(), |r| Arc::new(Mutex::new(Some(r))));
+            .map_or(Default::default(), |mut r| {
+                if let Some(f) = &self.record_audio_format {
+                    r.set_audio_format(f.sample_rate, f.channels as _);
+                }
+                Arc::new(Mutex::new(Some(r)))
+            });
         } else {
             self.recorder = Default::default();
         }
 
         self.record = start;
     }
+
+    /// Set the format of the audio muxed into the recording.
+    pub fn set_record_audio_format(&mut self, format: AudioFormat) {
+        self.recorder
+            .lock()
+            .unwrap()
+            .as_mut()
+            .map(|r| r.set_audio_format(format.sample_rate, format.channels as _));
+        self.record_audio_format = Some(format);
+    }
+
+    /// Write an audio frame into the recording.
+    pub fn record_audio(&mut self, frame: &AudioFrame) {
+        if self.record {
+            self.recorder
+                .lock()
+                .unwrap()
+                .as_mut()
+                .map(|r| r.write_audio(&frame.data));
+        }
+    }
 }
 
 // The source of sent password
@@ -1343,6 +1642,7 @@ pub struct LoginConfigHandler {
     pub session_id: u64, // used for local <-> server communication
     pub supported_encoding: SupportedEncoding,
     pub restarting_remote_device: bool,
+    pub reconnect_after_restart: bool,
     pub force_relay: bool,
     pub direct: Option<bool>,
     pub received: bool,
@@ -1359,6 +1659,10 @@ pub struct LoginConfigHandler {
     shared_password: Option<String>, // Store the shared password
     pub enable_trusted_devices: bool,
     pub record: bool,
+    pub screenshot_only: bool, // login only to fetch a screenshot, no video session
+    pub view_scale: f64,       // the scale of the remote display in the view, 0 if unknown
+    pub backup_relay: Option<crate::backup_path::BackupRelay>, // set if a relay server is known
+    pub remote_printer: bool, // the documents of the virtual printer of the peer printed here
 }
 
 impl Deref for LoginConfigHandler {
@@ -1448,6 +1752,7 @@ impl LoginConfigHandler {
         self.session_id = sid;
         self.supported_encoding = Default::default();
         self.restarting_remote_device = false;
+        self.reconnect_after_restart = false;
         self.force_relay =
             config::option2bool("force-always-relay", &self.get_option("force-always-relay"))
                 || force_relay;
@@ -1655,6 +1960,22 @@ impl LoginConfigHandler {
                 BoolOption::No
             })
             .into();
+        } else if name == config::keys::OPTION_DENOISE_AUDIO {
+            let on = config.options.get(&name).map(|o| !o.is_empty()) != Some(true);
+            if on {
+                config.options.insert(name.clone(), "Y".to_owned());
+            } else {
+                config.options.remove(&name);
+            }
+            option.denoise_audio = (if on { BoolOption::Yes } else { BoolOption::No }).into();
+        } else if name == config::keys::OPTION_PRESENTER_MODE {
+            let on = config.options.get(&name).map(|o| !o.is_empty()) != Some(true);
+            if on {
+                config.options.insert(name.clone(), "Y".to_owned());
+            } else {
+                config.options.remove(&name);
+            }
+            option.presenter_mode = (if on { BoolOption::Yes } else { BoolOption::No }).into();
         } else if name == "disable-clipboard" {
             config.disable_clipboard.v = !config.disable_clipboard.v;
             option.disable_clipboard = (if config.disable_clipboard.v {
@@ -1757,11 +2078,24 @@ impl LoginConfigHandler {
     fn get_option_message(&self, ignore_default: bool) -> Option<OptionMessage> {
         if self.conn_type.eq(&ConnType::PORT_FORWARD)
             || self.conn_type.eq(&ConnType::RDP)
-            || self.conn_type.eq(&ConnType::FILE_TRANSFER)
+            || self.conn_type.eq(&ConnType::TERMINAL)
         {
             return None;
         }
+        let bandwidth_limit = self.get_bandwidth_limit();
+        if self.conn_type.eq(&ConnType::FILE_TRANSFER) {
+            if bandwidth_limit > 0 {
+                return Some(OptionMessage {
+                    bandwidth_limit,
+                    ..Default::default()
+                });
+            }
+            return None;
+        }
         let mut msg = OptionMessage::new();
+        if bandwidth_limit > 0 {
+            msg.bandwidth_limit = bandwidth_limit;
+        }
         let q = self.image_quality.clone();
         if let Some(q) = self.get_image_quality_enum(&q, ignore_default) {
             msg.image_quality = q.into();
@@ -1807,6 +2141,16 @@ impl LoginConfigHandler {
         if self.get_toggle_option("disable-audio") {
             msg.disable_audio = BoolOption::Yes.into();
         }
+        if self.get_toggle_option(config::keys::OPTION_DENOISE_AUDIO) {
+            msg.denoise_audio = BoolOption::Yes.into();
+        }
+        if self.get_toggle_option(config::keys::OPTION_PRESENTER_MODE) {
+            msg.presenter_mode = BoolOption::Yes.into();
+        }
+        let audio_quality = self.get_option(config::keys::OPTION_AUDIO_QUALITY);
+        if let Some(q) = Self::get_audio_quality(&audio_quality) {
+            msg.audio_quality = MessageField::some(q);
+        }
         if !view_only && self.get_toggle_option(config::keys::OPTION_ENABLE_FILE_COPY_PASTE) {
             msg.enable_file_transfer = BoolOption::Yes.into();
         }
@@ -1894,6 +2238,25 @@ impl LoginConfigHandler {
         }
     }
 
+    pub fn is_gamepad_supported(&self) -> bool {
+        self.features.as_ref().map(|f| f.gamepad).unwrap_or(false)
+    }
+
+    /// The keyboard layout of the controlled side, see `keyboard::layout`.
+    pub fn peer_keyboard_layout(&self) -> String {
+        let Some(pi) = self.peer_info.as_ref() else {
+            return "".to_owned();
+        };
+        serde_json::from_str::<HashMap<String, serde_json::Value>>(&pi.platform_additions)
+            .ok()
+            .and_then(|m| m.get("keyboard_layout")?.as_str().map(|s| s.to_owned()))
+            .unwrap_or_default()
+    }
+
+    pub fn is_ime_input_supported(&self) -> bool {
+        self.features.as_ref().map(|f| f.ime_input).unwrap_or(false)
+    }
+
     /// Create a [`Message`] for refreshing video.
     pub fn refresh() -> Message {
         let mut misc = Misc::new();
@@ -1981,6 +2344,66 @@ impl LoginConfigHandler {
         msg_out
     }
 
+    /// Set the bandwidth limit of the session in kbps, 0 for no limit.
+    pub fn set_bandwidth_limit(&mut self, kbps: i32) -> Message {
+        let mut misc = Misc::new();
+        misc.set_option(OptionMessage {
+            bandwidth_limit: if kbps > 0 { kbps } else { -1 },
+            ..Default::default()
+        });
+        let mut msg_out = Message::new();
+        msg_out.set_misc(misc);
+        let value = if kbps > 0 {
+            kbps.to_string()
+        } else {
+            "".to_owned()
+        };
+        self.set_option(config::keys::OPTION_BANDWIDTH_LIMIT.to_owned(), value);
+        msg_out
+    }
+
+    /// Set the preset of the audio encoder of the peer, empty for the default.
+    pub fn set_audio_quality(&mut self, value: String) -> Message {
+        let mut misc = Misc::new();
+        misc.set_option(OptionMessage {
+            audio_quality: MessageField::some(Self::get_audio_quality(&value).unwrap_or_default()),
+            ..Default::default()
+        });
+        let mut msg_out = Message::new();
+        msg_out.set_misc(misc);
+        self.set_option(config::keys::OPTION_AUDIO_QUALITY.to_owned(), value);
+        msg_out
+    }
+
+    /// The encoder of the preset, none for the default, which is the lowest delay.
+    fn get_audio_quality(preset: &str) -> Option<AudioQuality> {
+        use audio_quality::Application;
+        // application, kbps, milliseconds of a frame, fec, dtx, max channels
+        let (application, bitrate, frame_duration, fec, dtx, max_channels) = match preset {
+            "music" => (Application::Music, 128, 20, false, false, 0),
+            // the speech over a poor network
+            "voice" => (Application::Voice, 24, 20, true, true, 0),
+            // the 5.1 or 7.1 of the peer kept, about 64 kbps a channel
+            "surround" => (Application::Music, 512, 20, false, false, 8),
+            _ => return None,
+        };
+        Some(AudioQuality {
+            application: application.into(),
+            bitrate,
+            frame_duration,
+            fec,
+            dtx,
+            max_channels,
+            ..Default::default()
+        })
+    }
+
+    fn get_bandwidth_limit(&self) -> i32 {
+        self.get_option(config::keys::OPTION_BANDWIDTH_LIMIT)
+            .parse()
+            .unwrap_or(0)
+    }
+
     pub fn get_option(&self, k: &str) -> String {
         if let Some(v) = self.config.options.get(k) {
             v.clone()
@@ -2039,12 +2462,27 @@ impl LoginConfigHandler {
             self.version = hbb_common::get_version_number(&pi.version);
         }
         self.features = pi.features.clone().into_option();
-        let serde = PeerInfoSerde {
+        let mut config = self.load_config();
+        let mut serde = PeerInfoSerde {
             username: pi.username.clone(),
             hostname: pi.hostname.clone(),
             platform: pi.platform.clone(),
+            ..Default::default()
         };
-        let mut config = self.load_config();
+        if pi.lan_addresses.is_empty() {
+            // the older versions do not tell them, keep the ones learned before
+            serde.macs = std::mem::take(&mut config.info.macs);
+            serde.lan_networks = std::mem::take(&mut config.info.lan_networks);
+        } else {
+            for addr in pi.lan_addresses.iter() {
+                if !serde.macs.contains(&addr.mac) {
+                    serde.macs.push(addr.mac.clone());
+                }
+                if !addr.network.is_empty() && !serde.lan_networks.contains(&addr.network) {
+                    serde.lan_networks.push(addr.network.clone());
+                }
+            }
+        }
         config.info = serde;
         let password = self.password.clone();
         let password0 = config.password.clone();
@@ -2183,6 +2621,7 @@ impl LoginConfigHandler {
         } else {
             Bytes::new()
         };
+        let device_key = crate::common::sign_device_key(&pure_id, &self.hash.challenge);
         let mut lr = LoginRequest {
             username: pure_id,
             password: password.into(),
@@ -2199,6 +2638,11 @@ impl LoginConfigHandler {
             })
             .into(),
             hwid,
+            screenshot_only: self.screenshot_only,
+            identity: crate::idp::identity(&self.hash.identity_provider).into(),
+            device_key: device_key.into(),
+            // a view-only session joins the broadcast if there is one
+            broadcast_viewer: self.get_toggle_option("view-only"),
             ..Default::default()
         };
         match self.conn_type {
@@ -2212,6 +2656,7 @@ impl LoginConfigHandler {
                 port: self.port_forward.1,
                 ..Default::default()
             }),
+            ConnType::TERMINAL => lr.set_terminal(Terminal::new()),
             _ => {}
         }
 
@@ -2237,9 +2682,13 @@ impl LoginConfigHandler {
         msg_out
     }
 
-    pub fn restart_remote_device(&self) -> Message {
+    pub fn restart_remote_device(&self, safe_mode: bool) -> Message {
         let mut misc = Misc::new();
-        misc.set_restart_remote_device(true);
+        if safe_mode {
+            misc.set_restart_in_safe_mode(true);
+        } else {
+            misc.set_restart_remote_device(true);
+        }
         let mut msg_out = Message::new();
         msg_out.set_misc(misc);
         msg_out
@@ -2266,6 +2715,7 @@ pub enum MediaData {
     AudioFormat(AudioFormat),
     Reset(Option<usize>),
     RecordScreen(bool),
+    LosslessPatches(Box<LosslessPatches>),
 }
 
 pub type MediaSender = mpsc::Sender<MediaData>;
@@ -2290,6 +2740,7 @@ pub fn start_video_audio_threads<F, T>(
     Arc<RwLock<HashMap<usize, ArrayQueue<VideoFrame>>>>,
     Arc<RwLock<Option<usize>>>,
     Arc<RwLock<Option<Chroma>>>,
+    Arc<Mutex<AvSync>>,
 )
 where
     F: 'static + FnMut(usize, &mut scrap::ImageRgb, *mut c_void, bool) + Send,
@@ -2305,12 +2756,15 @@ where
     let chroma = Arc::new(RwLock::new(None));
     let chroma_cloned = chroma.clone();
     let mut last_chroma = None;
+    let av_sync: Arc<Mutex<AvSync>> = Default::default();
+    let av_sync_cloned = av_sync.clone();
 
     std::thread::spawn(move || {
         #[cfg(windows)]
         sync_cpu_usage();
         get_hwcodec_config();
         let mut handler_controller_map = HashMap::new();
+        let mut record_audio_format: Option<AudioFormat> = None;
         let mut count = 0;
         let mut duration = std::time::Duration::ZERO;
         loop {
@@ -2338,10 +2792,14 @@ where
                             }
                         };
                         let display = vf.display as usize;
+                        let timestamp = vf.timestamp;
                         let start = std::time::Instant::now();
                         let format = CodecFormat::from(&vf);
                         if !handler_controller_map.contains_key(&display) {
                             let mut handler = VideoHandler::new(format, display);
+                            if let Some(f) = &record_audio_format {
+                                handler.set_record_audio_format(f.clone());
+                            }
                             let record = session.lc.read().unwrap().record;
                             let id = session.lc.read().unwrap().id.clone();
                             if record {
@@ -2360,6 +2818,16 @@ where
                             let mut tmp_chroma = None;
                             let format_changed =
                                 handler_controller.handler.decoder.format() != format;
+                            let (filters, view_scale) = {
+                                let lc = session.lc.read().unwrap();
+                                (
+                                    VideoFilters::parse(
+                                        &lc.get_option(config::keys::OPTION_VIDEO_FILTERS),
+                                    ),
+                                    lc.view_scale,
+                                )
+                            };
+                            handler_controller.handler.set_filters(filters, view_scale);
                             match handler_controller.handler.handle_frame(
                                 vf,
                                 &mut pixelbuffer,
@@ -2372,6 +2840,7 @@ where
                                         handler_controller.handler.texture.texture,
                                         pixelbuffer,
                                     );
+                                    av_sync.lock().unwrap().on_video(timestamp);
 
                                     // chroma
                                     if tmp_chroma.is_some() && last_chroma != tmp_chroma {
@@ -2443,6 +2912,35 @@ where
                             }
                         }
                     }
+                    MediaData::AudioFormat(f) => {
+                        for (_, handler_controler) in handler_controller_map.iter_mut() {
+                            handler_controler.handler.set_record_audio_format(f.clone());
+                        }
+                        record_audio_format = Some(f);
+                    }
+                    MediaData::AudioFrame(af) => {
+                        // Audio is muxed into the recording of the first display only.
+                        if let Some(display) = handler_controller_map.keys().min().cloned() {
+                            if let Some(handler_controler) =
+                                handler_controller_map.get_mut(&display)
+                            {
+                                handler_controler.handler.record_audio(&af);
+                            }
+                        }
+                    }
+                    MediaData::LosslessPatches(patches) => {
+                        let display = patches.display as usize;
+                        if let Some(handler_controler) = handler_controller_map.get_mut(&display) {
+                            if handler_controler.handler.handle_lossless_patches(&patches) {
+                                video_callback(
+                                    display,
+                                    &mut handler_controler.handler.rgb,
+                                    handler_controler.handler.texture.texture,
+                                    true,
+                                );
+                            }
+                        }
+                    }
                     MediaData::RecordScreen(start) => {
                         log::info!("record screen command: start: {start}");
                         let record = session.lc.read().unwrap().record;
@@ -2467,36 +2965,97 @@ where
         }
         log::info!("Video decoder loop exits");
     });
-    let audio_sender = start_audio_thread();
+    let audio_sender = spawn_audio_thread(vec![], Some(av_sync_cloned.clone()));
     return (
         video_sender,
         audio_sender,
         video_queue_map_cloned,
         decode_fps_map,
         chroma_cloned,
+        av_sync_cloned,
     );
 }
 
 /// Start an audio thread
 /// Return a audio [`MediaSender`]
 pub fn start_audio_thread() -> MediaSender {
+    start_audio_thread_with_outputs(vec![])
+}
+
+/// Start an audio thread which plays to the output devices besides the default one.
+pub fn start_audio_thread_with_outputs(outputs: Vec<String>) -> MediaSender {
+    spawn_audio_thread(outputs, None)
+}
+
+// The frames are held for the video if `av_sync` is set.
+fn spawn_audio_thread(outputs: Vec<String>, av_sync: Option<Arc<Mutex<AvSync>>>) -> MediaSender {
     let (audio_sender, audio_receiver) = mpsc::channel::<MediaData>();
     std::thread::spawn(move || {
-        let mut audio_handler = AudioHandler::default();
+        let new_handlers = || -> Vec<AudioHandler> {
+            std::iter::once(AudioHandler::default())
+                .chain(outputs.iter().cloned().map(AudioHandler::with_output))
+                .collect()
+        };
+        let mut audio_handlers = new_handlers();
+        fn play(audio_handlers: &mut [AudioHandler], af: AudioFrame) {
+            for audio_handler in audio_handlers.iter_mut() {
+                audio_handler.handle_frame(af.clone());
+            }
+        }
+        // the frames and when they are played
+        let mut held: VecDeque<(Instant, AudioFrame)> = Default::default();
         loop {
-            if let Ok(data) = audio_receiver.recv() {
-                match data {
-                    MediaData::AudioFrame(af) => {
-                        audio_handler.handle_frame(*af);
+            let data = match held.front() {
+                Some((due, _)) => {
+                    match audio_receiver.recv_timeout(due.saturating_duration_since(Instant::now()))
+                    {
+                        Ok(data) => Some(data),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => break,
                     }
-                    MediaData::AudioFormat(f) => {
-                        log::debug!("recved audio format, sample rate={}", f.sample_rate);
-                        audio_handler.handle_format(f);
+                }
+                None => match audio_receiver.recv() {
+                    Ok(data) => Some(data),
+                    Err(_) => break,
+                },
+            };
+            while held.front().map(|f| f.0 <= Instant::now()) == Some(true) {
+                if let Some((_, af)) = held.pop_front() {
+                    play(&mut audio_handlers, af);
+                }
+            }
+            match data {
+                Some(MediaData::AudioFrame(af)) => {
+                    let delay = match &av_sync {
+                        Some(av_sync) => av_sync.lock().unwrap().audio_delay(af.timestamp),
+                        None => Some(Duration::ZERO),
+                    };
+                    let Some(delay) = delay else {
+                        continue;
+                    };
+                    if delay.is_zero() && held.is_empty() {
+                        play(&mut audio_handlers, *af);
+                    } else {
+                        // in the order they came
+                        let now = Instant::now();
+                        let due = (now + delay).max(held.back().map(|f| f.0).unwrap_or(now));
+                        held.push_back((due, *af));
                     }
-                    _ => {}
                 }
-            } else {
-                break;
+                // the devices are opened again by the next format
+                Some(MediaData::Reset(_)) => {
+                    log::info!("audio playback stopped");
+                    held.clear();
+                    audio_handlers = new_handlers();
+                }
+                Some(MediaData::AudioFormat(f)) => {
+                    log::debug!("recved audio format, sample rate={}", f.sample_rate);
+                    held.clear();
+                    for audio_handler in audio_handlers.iter_mut() {
+                        audio_handler.handle_format(f.clone());
+                    }
+                }
+                _ => {}
             }
         }
         log::info!("Audio decoder loop exits");
@@ -2853,6 +3412,18 @@ lazy_static::lazy_static! {
             text: "Please wait for the remote side to accept your session request...",
             link: "",
             try_again: true,
+        }), (LOGIN_MSG_NEW_DEVICE, LoginErrorMsgBox{
+            msgtype: "wait-remote-accept-nook",
+            title: "Prompt",
+            text: "new_device_wait_tip",
+            link: "",
+            try_again: true,
+        }), (LOGIN_MSG_OUTSIDE_SCHEDULE, LoginErrorMsgBox{
+            msgtype: "wait-remote-accept-nook",
+            title: "Prompt",
+            text: "outside_schedule_wait_tip",
+            link: "",
+            try_again: true,
         })]);
         Arc::new(map)
     };
@@ -2882,6 +3453,9 @@ pub fn handle_login_error(
         }
         interface.msgbox("input-2fa", err, "", "");
         true
+    } else if err == LOGIN_MSG_ACCESS_REASON_REQUIRED {
+        interface.msgbox("input-access-reason", err, "", "");
+        true
     } else if LOGIN_ERROR_MAP.contains_key(err) {
         if let Some(msgbox_info) = LOGIN_ERROR_MAP.get(err) {
             interface.msgbox(
@@ -2922,6 +3496,9 @@ pub async fn handle_hash(
     peer: &mut Stream,
 ) {
     lc.write().unwrap().hash = hash.clone();
+    if !hash.identity_provider.is_empty() {
+        tokio::task::spawn_blocking(crate::idp::refresh).await.ok();
+    }
     // Take care of password application order
 
     // switch_uuid
@@ -3198,29 +3775,51 @@ pub enum Data {
     Close,
     Login((String, String, String, bool)),
     Message(Message),
-    SendFiles((i32, String, String, i32, bool, bool)),
+    // the name of the conflict policy, empty for the option
+    SendFiles((i32, String, String, i32, bool, bool, String)),
     RemoveDirAll((i32, String, bool, bool)),
     ConfirmDeleteFiles((i32, i32)),
     SetNoConfirm(i32),
-    RemoveDir((i32, String)),
-    RemoveFile((i32, String, i32, bool)),
+    // moved to the trash if the last is true
+    RemoveDir((i32, String, bool, bool)),
+    RemoveFile((i32, String, i32, bool, bool)),
+    // the ids of the removal jobs to undo
+    UndoRemove((i32, Vec<i32>, bool)),
     CreateDir((i32, String, bool)),
+    // id, path, offset, length, is_remote
+    ReadRange((i32, String, u64, u32, bool)),
     CancelJob(i32),
     RemovePortForward(i32),
     AddPortForward((i32, String, i32)),
     #[cfg(not(feature = "flutter"))]
     ToggleClipboardFile,
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    ToggleGamepad,
+    #[cfg(not(target_os = "ios"))]
+    ToggleDenoise,
+    ToggleAudio,
     NewRDP,
-    SetConfirmOverrideFile((i32, i32, bool, bool, bool)),
+    ResolveFileConflict((i32, i32, FileConflictPolicy, bool, bool)),
+    // the confirmation of a file to write, sent once its checksums are read
+    SendConfirm(FileTransferSendConfirmRequest),
     AddJob((i32, String, String, i32, bool, bool)),
     ResumeJob((i32, bool)),
+    PauseJob((i32, bool)),
+    // the ids of the transfer jobs in the order of the queue
+    ReorderJobs(Vec<i32>),
     RecordScreen(bool),
     ElevateDirect,
     ElevateWithLogon(String, String),
     NewVoiceCall,
+    // the button of push-to-talk held or released
+    VoiceCallTalking(bool),
     CloseVoiceCall,
     ResetDecoder(Option<usize>),
     RenameFile((i32, String, String, bool)),
+    // the id, the name of the sync job and whether it is a dry run
+    SyncJob((i32, String, bool)),
+    // the id of the job and the rate it is sent at in kbps, 0 for no limit
+    SetJobRateLimit((i32, u32)),
 }
 
 /// Keycode for key events.
@@ -3402,7 +4001,7 @@ async fn hc_connection_(
     let mut keep_alive = crate::DEFAULT_KEEP_ALIVE;
 
     let host = check_port(&rendezvous_server, RENDEZVOUS_PORT);
-    let mut conn = connect_tcp(host.clone(), CONNECT_TIMEOUT).await?;
+    let mut conn = connect_server(&host, websocket::RENDEZVOUS_PATH, CONNECT_TIMEOUT).await?;
     let key = crate::get_key(true).await;
     crate::secure_tcp(&mut conn, &key).await?;
     let mut msg_out = RendezvousMessage::new();
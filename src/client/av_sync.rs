@@ -0,0 +1,92 @@
+// Keep the audio of a session in sync with its video.
+//
+// The controlled side stamps the audio and video frames with the milliseconds of one clock. The
+// video thread notes the stamp of the frame it shows, the audio thread holds the frames which are
+// ahead of the video and drops the ones far behind it. The peers without the stamps send 0.
+
+use std::time::{Duration, Instant};
+
+// the drift not worth holding the audio for
+const TOLERANCE_MS: i64 = 30;
+// the video is probably stalled, the audio is not held for it
+const MAX_HOLD_MS: i64 = 500;
+const MAX_LATE_MS: i64 = 200;
+
+#[derive(Debug, Default)]
+pub struct AvSync {
+    // the stamp of the video shown last, and when
+    video: Option<(i64, Instant)>,
+    // the audio ahead of the video in milliseconds, negative if behind
+    drift: Option<i64>,
+}
+
+impl AvSync {
+    pub fn on_video(&mut self, timestamp: i64) {
+        if timestamp > 0 {
+            self.video = Some((timestamp, Instant::now()));
+        }
+    }
+
+    /// How long the audio frame is held before it is played, none to drop it.
+    pub fn audio_delay(&mut self, timestamp: i64) -> Option<Duration> {
+        let Some(drift) = self.measure(timestamp, Instant::now()) else {
+            return Some(Duration::ZERO);
+        };
+        self.drift = Some(drift);
+        delay(drift)
+    }
+
+    /// The last drift measured, positive if the audio is ahead.
+    pub fn drift(&self) -> Option<i64> {
+        self.drift
+    }
+
+    fn measure(&self, timestamp: i64, now: Instant) -> Option<i64> {
+        let (video, shown) = self.video?;
+        if timestamp <= 0 {
+            return None;
+        }
+        // the video stays on the last frame if the screen does not change
+        let video_now = video + now.saturating_duration_since(shown).as_millis() as i64;
+        Some(timestamp - video_now)
+    }
+}
+
+fn delay(drift: i64) -> Option<Duration> {
+    if drift < -MAX_LATE_MS {
+        None
+    } else if drift > TOLERANCE_MS && drift <= MAX_HOLD_MS {
+        Some(Duration::from_millis(drift as _))
+    } else {
+        Some(Duration::ZERO)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        assert_eq!(delay(0), Some(Duration::ZERO));
+        assert_eq!(delay(TOLERANCE_MS), Some(Duration::ZERO));
+        assert_eq!(delay(100), Some(Duration::from_millis(100)));
+        assert_eq!(delay(MAX_HOLD_MS + 1), Some(Duration::ZERO));
+        assert_eq!(delay(-100), Some(Duration::ZERO));
+        assert_eq!(delay(-MAX_LATE_MS - 1), None);
+    }
+
+    #[test]
+    fn test_measure() {
+        let mut sync = AvSync::default();
+        let now = Instant::now();
+        assert_eq!(sync.measure(1000, now), None);
+        sync.video = Some((1000, now));
+        assert_eq!(sync.measure(0, now), None);
+        assert_eq!(sync.measure(1100, now), Some(100));
+        assert_eq!(
+            sync.measure(1100, now + Duration::from_millis(40)),
+            Some(60)
+        );
+    }
+}
@@ -0,0 +1,59 @@
+// The camera of the peer viewed apart from its displays, see camera_service of the server.
+//
+// The frames are decoded in a thread of the session, so the displays are not held up by them, and
+// none of them is dropped, the ones after a key frame depend on the ones before.
+
+use crate::ui_session_interface::{InvokeUiSession, Session};
+use hbb_common::{log, message_proto::*};
+use scrap::{codec::Decoder, CodecFormat, ImageFormat, ImageRgb, ImageTexture};
+use std::sync::mpsc;
+
+/// Decodes the frames of the camera until it is dropped.
+pub struct Viewer {
+    sender: mpsc::Sender<VideoFrame>,
+}
+
+impl Viewer {
+    pub fn start<T: InvokeUiSession>(handler: Session<T>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || run(handler, receiver));
+        Self { sender }
+    }
+
+    pub fn handle_frame(&self, frame: VideoFrame) {
+        self.sender.send(frame).ok();
+    }
+}
+
+fn run<T: InvokeUiSession>(handler: Session<T>, receiver: mpsc::Receiver<VideoFrame>) {
+    let mut format = CodecFormat::Unknown;
+    let mut decoder: Option<Decoder> = None;
+    // in the order of the bytes of the flutter ui
+    let mut rgb = ImageRgb::new(ImageFormat::ABGR, 1);
+    let mut texture = ImageTexture::default();
+    while let Ok(frame) = receiver.recv() {
+        let Some(union) = frame.union else {
+            continue;
+        };
+        let frame_format = CodecFormat::from(&union);
+        if decoder.is_none() || frame_format != format {
+            format = frame_format;
+            decoder = Some(Decoder::new(format, None));
+        }
+        let Some(decoder) = decoder.as_mut() else {
+            continue;
+        };
+        let mut pixelbuffer = true;
+        match decoder.handle_video_frame(
+            &union,
+            &mut rgb,
+            &mut texture,
+            &mut pixelbuffer,
+            &mut None,
+        ) {
+            Ok(true) => handler.on_camera_frame(rgb.w, rgb.h, rgb.raw.clone()),
+            Ok(false) => {}
+            Err(err) => log::error!("Failed to decode the frame of the camera: {}", err),
+        }
+    }
+}
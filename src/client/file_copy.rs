@@ -0,0 +1,342 @@
+// Copy a file or folder to or from a peer without any UI, for the unattended batch copies of the
+// command line.
+//
+// The files existing are resolved by the conflict policy given, nobody is asked. A peer which
+// knows the policies resolves the conflicts of an upload itself, an older one sends the digests to
+// this side, which can not rename on it and skips the file instead.
+
+use super::{headless, Interface};
+use hbb_common::{
+    bail,
+    config::READ_TIMEOUT,
+    fs::{self, conflict::Resolution, DigestCheckResult},
+    futures::StreamExt,
+    get_version_number, log,
+    message_proto::*,
+    protobuf::Message as _,
+    rendezvous_proto::ConnType,
+    timeout,
+    tokio::{self, time},
+    ResultType, Stream,
+};
+use std::time::{Duration, Instant};
+
+const JOB_ID: i32 = 1;
+
+/// Copies `from` on the peer into `to` on this side, the folder copied is created in `to`.
+pub async fn download(
+    id: &str,
+    from: &str,
+    to: &str,
+    policy: FileConflictPolicy,
+    password: &str,
+    key: &str,
+    token: &str,
+) -> ResultType<()> {
+    let mut conn =
+        headless::login(id, ConnType::FILE_TRANSFER, password, key, token, |_| {}).await?;
+    download_on(&mut conn, JOB_ID, from, to, policy).await
+}
+
+/// Downloads as [`download`] over the connection logged in, the job is `job_id`.
+pub(super) async fn download_on(
+    conn: &mut headless::Connection,
+    job_id: i32,
+    from: &str,
+    to: &str,
+    policy: FileConflictPolicy,
+) -> ResultType<()> {
+    let headless::Connection {
+        session,
+        stream,
+        peer_info,
+        ..
+    } = conn;
+    let od = fs::can_enable_overwrite_detection(get_version_number(&peer_info.version));
+    let mut job = fs::TransferJob::new_write(
+        job_id,
+        from.to_owned(),
+        to.to_owned(),
+        0,
+        true,
+        true,
+        Vec::new(),
+        od,
+    );
+    job.set_verify(true);
+    let attributes = fs::attributes::Options::from_local_config();
+    job.set_attributes(attributes, false);
+    job.set_conflict_policy(policy);
+    stream
+        .send(&fs::new_send(
+            job_id,
+            from.to_owned(),
+            0,
+            true,
+            attributes,
+            fs::links::policy_from_local_config(),
+        ))
+        .await?;
+    loop {
+        let msg_in = match timeout(READ_TIMEOUT, stream.next()).await {
+            Err(_) => bail!("Timeout"),
+            Ok(Some(Ok(bytes))) => Message::parse_from_bytes(&bytes)?,
+            Ok(Some(Err(err))) => bail!("Connection closed: {}", err),
+            Ok(None) => bail!("Reset by the peer"),
+        };
+        let fr = match msg_in.union {
+            Some(message::Union::FileResponse(fr)) => fr,
+            Some(message::Union::TestDelay(t)) => {
+                session.handle_test_delay(t, stream).await;
+                continue;
+            }
+            _ => continue,
+        };
+        match fr.union {
+            Some(file_response::Union::Dir(fd)) if fd.id == job_id => {
+                #[cfg(windows)]
+                let entries = fd.entries.to_vec();
+                #[cfg(not(windows))]
+                let mut entries = fd.entries.to_vec();
+                #[cfg(not(windows))]
+                if peer_info.platform == "Windows" {
+                    fs::transform_windows_path(&mut entries);
+                }
+                log::info!("download {} files from {}", entries.len(), from);
+                job.set_files(entries);
+            }
+            Some(file_response::Union::Digest(digest)) if digest.id == job_id => {
+                // the corrupted file sent again is already confirmed
+                if job.is_refetching() {
+                    continue;
+                }
+                let Some(file) = job.files().get(digest.file_num as usize) else {
+                    continue;
+                };
+                let path = fs::get_string(&job.join(&file.name));
+                let union = match fs::is_write_need_confirmation(&path, &digest)? {
+                    DigestCheckResult::IsSame => {
+                        file_transfer_send_confirm_request::Union::Skip(true)
+                    }
+                    DigestCheckResult::NeedConfirm(existing) => {
+                        match job.resolve_conflict(
+                            digest.file_num,
+                            digest.last_modified,
+                            existing.last_modified,
+                        ) {
+                            Resolution::Overwrite => {
+                                file_transfer_send_confirm_request::Union::OffsetBlk(0)
+                            }
+                            Resolution::Rename => {
+                                job.rename_conflicting(digest.file_num);
+                                file_transfer_send_confirm_request::Union::OffsetBlk(0)
+                            }
+                            Resolution::Skip | Resolution::Ask => {
+                                log::info!("skip {}, it exists", path);
+                                file_transfer_send_confirm_request::Union::Skip(true)
+                            }
+                        }
+                    }
+                    DigestCheckResult::NoSuchFile => {
+                        file_transfer_send_confirm_request::Union::OffsetBlk(0)
+                    }
+                };
+                let req = FileTransferSendConfirmRequest {
+                    id: job_id,
+                    file_num: digest.file_num,
+                    union: Some(union),
+                    ..Default::default()
+                };
+                job.confirm(&req);
+                stream.send(&fs::new_send_confirm(req)).await?;
+            }
+            Some(file_response::Union::Block(block)) if block.id == job_id => {
+                job.write(block).await?;
+                if let Some(msg) = job.verification_reply().await {
+                    stream.send(&msg).await?;
+                }
+            }
+            Some(file_response::Union::Done(d)) if d.id == job_id => {
+                job.modify_time();
+                job.create_links();
+                if let Some(err) = job.corrupted_error() {
+                    bail!("{}", err);
+                }
+                log::info!("downloaded {} bytes from {}", job.transferred(), from);
+                return Ok(());
+            }
+            Some(file_response::Union::Error(e)) if e.id == job_id => {
+                job.remove_download_file();
+                bail!("{}", e.error);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Copies `from` on this side into `to` on the peer, the folder copied is created in `to`.
+pub async fn upload(
+    id: &str,
+    from: &str,
+    to: &str,
+    policy: FileConflictPolicy,
+    password: &str,
+    key: &str,
+    token: &str,
+) -> ResultType<()> {
+    let mut conn =
+        headless::login(id, ConnType::FILE_TRANSFER, password, key, token, |_| {}).await?;
+    upload_on(&mut conn, JOB_ID, from, to, policy).await
+}
+
+/// Uploads as [`upload`] over the connection logged in, the job is `job_id`.
+pub(super) async fn upload_on(
+    conn: &mut headless::Connection,
+    job_id: i32,
+    from: &str,
+    to: &str,
+    policy: FileConflictPolicy,
+) -> ResultType<()> {
+    let headless::Connection {
+        session,
+        stream,
+        peer_info,
+        ..
+    } = conn;
+    let od = fs::can_enable_overwrite_detection(get_version_number(&peer_info.version));
+    let mut job = fs::TransferJob::new_read(
+        job_id,
+        to.to_owned(),
+        from.to_owned(),
+        0,
+        true,
+        false,
+        od,
+        fs::links::policy_from_local_config(),
+    )?;
+    let attributes = fs::attributes::Options::from_local_config();
+    job.set_attributes(attributes, true);
+    job.set_conflict_policy(policy);
+    #[cfg(not(windows))]
+    let files = job.files().clone();
+    #[cfg(windows)]
+    let mut files = job.files().clone();
+    #[cfg(windows)]
+    if peer_info.platform != "Windows" {
+        fs::transform_windows_path(&mut files);
+    }
+    log::info!("upload {} files to {}", files.len(), to);
+    stream
+        .send(&fs::new_receive(
+            job_id,
+            to.to_owned(),
+            0,
+            files,
+            job.total_size(),
+            attributes,
+            policy,
+        ))
+        .await?;
+    let mut jobs = vec![job];
+    let mut timer = time::interval(Duration::from_millis(1));
+    let mut last_recv_time = Instant::now();
+    loop {
+        tokio::select! {
+            _ = timer.tick() => {
+                if last_recv_time.elapsed() >= READ_TIMEOUT {
+                    bail!("Timeout");
+                }
+                fs::handle_read_jobs(&mut jobs, stream).await?;
+                if jobs.is_empty() {
+                    log::info!("uploaded {} to {}", from, to);
+                    return Ok(());
+                }
+            }
+            res = stream.next() => {
+                let msg_in = match res {
+                    Some(Ok(bytes)) => Message::parse_from_bytes(&bytes)?,
+                    Some(Err(err)) => bail!("Connection closed: {}", err),
+                    None => bail!("Reset by the peer"),
+                };
+                last_recv_time = Instant::now();
+                handle_upload_message(msg_in, &mut jobs, stream, session).await?;
+            }
+        }
+    }
+}
+
+async fn handle_upload_message(
+    msg_in: Message,
+    jobs: &mut Vec<fs::TransferJob>,
+    stream: &mut Stream,
+    session: &headless::Session,
+) -> ResultType<()> {
+    match msg_in.union {
+        Some(message::Union::FileResponse(fr)) => match fr.union {
+            Some(file_response::Union::Digest(digest)) => {
+                let Some(job) = fs::get_job(digest.id, jobs) else {
+                    return Ok(());
+                };
+                if digest.verify {
+                    job.set_verify(true);
+                }
+                let Some(file) = job.files().get(digest.file_num as usize) else {
+                    return Ok(());
+                };
+                let (name, modified_time) = (file.name.clone(), file.modified_time);
+                if digest.resolves_conflict {
+                    // the policy is to ask, nobody answers
+                    let mut msg = Message::new();
+                    let mut action = FileAction::new();
+                    action.set_conflict_resolution(FileConflictResolution {
+                        id: digest.id,
+                        file_num: digest.file_num,
+                        policy: FileConflictPolicy::SkipExisting.into(),
+                        ..Default::default()
+                    });
+                    msg.set_file_action(action);
+                    stream.send(&msg).await?;
+                    return Ok(());
+                }
+                let resolution =
+                    job.resolve_conflict(digest.file_num, modified_time, digest.last_modified);
+                let union = if resolution == Resolution::Overwrite {
+                    if let Some(checksums) = digest.checksums.clone().into_option() {
+                        job.set_delta(digest.file_num, checksums);
+                    }
+                    file_transfer_send_confirm_request::Union::OffsetBlk(0)
+                } else {
+                    log::info!("skip {}, it exists", name);
+                    file_transfer_send_confirm_request::Union::Skip(true)
+                };
+                let req = FileTransferSendConfirmRequest {
+                    id: digest.id,
+                    file_num: digest.file_num,
+                    union: Some(union),
+                    ..Default::default()
+                };
+                job.confirm(&req);
+                stream.send(&fs::new_send_confirm(req)).await?;
+            }
+            Some(file_response::Union::Error(e)) if fs::get_job(e.id, jobs).is_some() => {
+                bail!("{}", e.error);
+            }
+            _ => {}
+        },
+        Some(message::Union::FileAction(action)) => {
+            if let Some(file_action::Union::SendConfirm(c)) = action.union {
+                if let Some(job) = fs::get_job(c.id, jobs) {
+                    if c.verify {
+                        job.set_verify(true);
+                    }
+                    job.confirm(&c);
+                }
+            }
+        }
+        Some(message::Union::TestDelay(t)) => {
+            session.handle_test_delay(t, stream).await;
+        }
+        _ => {}
+    }
+    Ok(())
+}
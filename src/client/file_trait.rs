@@ -55,8 +55,33 @@ pub trait FileManager: Interface {
         self.send(Data::Message(msg_out));
     }
 
-    fn remove_file(&self, id: i32, path: String, file_num: i32, is_remote: bool) {
-        self.send(Data::RemoveFile((id, path, file_num, is_remote)));
+    // cancelled with `cancel_job`
+    fn search_remote_files(
+        &self,
+        id: i32,
+        path: String,
+        pattern: String,
+        include_hidden: bool,
+        max_depth: u32,
+        timeout_secs: u32,
+    ) {
+        let mut msg_out = Message::new();
+        let mut file_action = FileAction::new();
+        file_action.set_search(FileSearch {
+            id,
+            path,
+            pattern,
+            include_hidden,
+            max_depth,
+            timeout_secs,
+            ..Default::default()
+        });
+        msg_out.set_file_action(file_action);
+        self.send(Data::Message(msg_out));
+    }
+
+    fn remove_file(&self, id: i32, path: String, file_num: i32, is_remote: bool, trash: bool) {
+        self.send(Data::RemoveFile((id, path, file_num, is_remote, trash)));
     }
 
     fn remove_dir_all(&self, id: i32, path: String, is_remote: bool, include_hidden: bool) {
@@ -71,12 +96,17 @@ pub trait FileManager: Interface {
         self.send(Data::SetNoConfirm(id));
     }
 
-    fn remove_dir(&self, id: i32, path: String, is_remote: bool) {
-        if is_remote {
-            self.send(Data::RemoveDir((id, path)));
-        } else {
-            fs::remove_all_empty_dir(&fs::get_path(&path)).ok();
-        }
+    fn remove_dir(&self, id: i32, path: String, is_remote: bool, trash: bool) {
+        self.send(Data::RemoveDir((id, path, is_remote, trash)));
+    }
+
+    fn undo_remove(&self, id: i32, remove_ids: Vec<i32>, is_remote: bool) {
+        self.send(Data::UndoRemove((id, remove_ids, is_remote)));
+    }
+
+    // for the previews, answered with `file_range` or the error of the job
+    fn read_file_range(&self, id: i32, path: String, offset: u64, length: u32, is_remote: bool) {
+        self.send(Data::ReadRange((id, path, offset, length, is_remote)));
     }
 
     fn create_dir(&self, id: i32, path: String, is_remote: bool) {
@@ -91,6 +121,7 @@ pub trait FileManager: Interface {
         file_num: i32,
         include_hidden: bool,
         is_remote: bool,
+        conflict_policy: String,
     ) {
         self.send(Data::SendFiles((
             id,
@@ -99,6 +130,7 @@ pub trait FileManager: Interface {
             file_num,
             include_hidden,
             is_remote,
+            conflict_policy,
         )));
     }
 
@@ -125,6 +157,14 @@ pub trait FileManager: Interface {
         self.send(Data::ResumeJob((id, is_remote)));
     }
 
+    fn pause_job(&self, id: i32, is_remote: bool) {
+        self.send(Data::PauseJob((id, is_remote)));
+    }
+
+    fn reorder_jobs(&self, ids: Vec<i32>) {
+        self.send(Data::ReorderJobs(ids));
+    }
+
     fn set_confirm_override_file(
         &self,
         id: i32,
@@ -138,16 +178,45 @@ pub trait FileManager: Interface {
             id,
             need_override
         );
-        self.send(Data::SetConfirmOverrideFile((
-            id,
-            file_num,
-            need_override,
-            remember,
-            is_upload,
+        let policy = if need_override {
+            FileConflictPolicy::OverwriteExisting
+        } else {
+            FileConflictPolicy::SkipExisting
+        };
+        self.send(Data::ResolveFileConflict((
+            id, file_num, policy, remember, is_upload,
         )));
     }
 
+    fn resolve_file_conflict(
+        &self,
+        id: i32,
+        file_num: i32,
+        policy: String,
+        remember: bool,
+        is_upload: bool,
+    ) {
+        log::info!(
+            "resolve the conflict of file transfer, job: {}, policy: {}",
+            id,
+            policy
+        );
+        if let Some(policy) = fs::conflict::parse(&policy) {
+            self.send(Data::ResolveFileConflict((
+                id, file_num, policy, remember, is_upload,
+            )));
+        }
+    }
+
     fn rename_file(&self, act_id: i32, path: String, new_name: String, is_remote: bool) {
         self.send(Data::RenameFile((act_id, path, new_name, is_remote)));
     }
+
+    fn run_sync_job(&self, act_id: i32, name: String, dry_run: bool) {
+        self.send(Data::SyncJob((act_id, name, dry_run)));
+    }
+
+    fn set_job_rate_limit(&self, act_id: i32, kbps: u32) {
+        self.send(Data::SetJobRateLimit((act_id, kbps)));
+    }
 }
@@ -0,0 +1,135 @@
+// Forward the controllers of the controlling side to the peer.
+//
+// The controllers are polled in a thread of the session, and the full state of a controller is
+// sent on every change in the XInput layout. gilrs maps DualShock and the other controllers to it.
+
+use crate::client::Data;
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+use hbb_common::{log, message_proto::*, tokio::sync::mpsc};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(4);
+
+const BUTTONS: [(Button, u32); 15] = [
+    (Button::DPadUp, 0x0001),
+    (Button::DPadDown, 0x0002),
+    (Button::DPadLeft, 0x0004),
+    (Button::DPadRight, 0x0008),
+    (Button::Start, 0x0010),
+    (Button::Select, 0x0020),
+    (Button::LeftThumb, 0x0040),
+    (Button::RightThumb, 0x0080),
+    // the bumpers, the triggers are axes
+    (Button::LeftTrigger, 0x0100),
+    (Button::RightTrigger, 0x0200),
+    (Button::Mode, 0x0400),
+    (Button::South, 0x1000),
+    (Button::East, 0x2000),
+    (Button::West, 0x4000),
+    (Button::North, 0x8000),
+];
+
+/// Forwards the controllers until it is dropped.
+pub struct Forwarder {
+    stop: Arc<AtomicBool>,
+}
+
+impl Forwarder {
+    pub fn start(sender: mpsc::UnboundedSender<Data>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_cloned = stop.clone();
+        std::thread::spawn(move || run(sender, stop_cloned));
+        Self { stop }
+    }
+}
+
+impl Drop for Forwarder {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+fn run(sender: mpsc::UnboundedSender<Data>, stop: Arc<AtomicBool>) {
+    let mut gilrs = match Gilrs::new() {
+        Ok(gilrs) => gilrs,
+        Err(e) => {
+            log::error!("failed to read gamepads: {e}");
+            return;
+        }
+    };
+    log::info!("start forwarding gamepads");
+    let send = |evt: GamepadEvent| {
+        let mut msg = Message::new();
+        msg.set_gamepad_event(evt);
+        sender.send(Data::Message(msg)).is_ok()
+    };
+    let mut changed: Vec<GamepadId> = vec![];
+    'outer: while !stop.load(Ordering::SeqCst) {
+        while let Some(event) = gilrs.next_event() {
+            if let EventType::Disconnected = event.event {
+                changed.retain(|id| *id != event.id);
+                if !send(disconnected(event.id)) {
+                    break 'outer;
+                }
+            } else if !changed.contains(&event.id) {
+                changed.push(event.id);
+            }
+        }
+        for id in changed.drain(..) {
+            if !send(state(&gilrs, id)) {
+                break 'outer;
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    // unplug the virtual controllers of the peer
+    for (id, _) in gilrs.gamepads() {
+        send(disconnected(id));
+    }
+    log::info!("stop forwarding gamepads");
+}
+
+fn disconnected(id: GamepadId) -> GamepadEvent {
+    GamepadEvent {
+        index: usize::from(id) as _,
+        connected: false,
+        ..Default::default()
+    }
+}
+
+fn state(gilrs: &Gilrs, id: GamepadId) -> GamepadEvent {
+    let gamepad = gilrs.gamepad(id);
+    let mut buttons = 0;
+    for (button, bit) in BUTTONS.iter() {
+        if gamepad.is_pressed(*button) {
+            buttons |= bit;
+        }
+    }
+    // up is positive in both gilrs and XInput
+    let axis = |axis: Axis| (gamepad.value(axis) * i16::MAX as f32) as i32;
+    let trigger = |button: Button| {
+        let value = match gamepad.button_data(button) {
+            Some(data) => data.value(),
+            None => gamepad.is_pressed(button) as u8 as f32,
+        };
+        (value * u8::MAX as f32) as u32
+    };
+    GamepadEvent {
+        index: usize::from(id) as _,
+        buttons,
+        left_trigger: trigger(Button::LeftTrigger2),
+        right_trigger: trigger(Button::RightTrigger2),
+        left_x: axis(Axis::LeftStickX),
+        left_y: axis(Axis::LeftStickY),
+        right_x: axis(Axis::RightStickX),
+        right_y: axis(Axis::RightStickY),
+        connected: gamepad.is_connected(),
+        ..Default::default()
+    }
+}
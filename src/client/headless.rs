@@ -0,0 +1,185 @@
+// Sessions without any UI, for the command line and the requests from the peer list.
+
+use crate::client::*;
+use async_trait::async_trait;
+use hbb_common::{
+    allow_err, bail,
+    config::READ_TIMEOUT,
+    futures::StreamExt,
+    log,
+    message_proto::*,
+    protobuf::Message as _,
+    rendezvous_proto::ConnType,
+    timeout,
+    tokio::{self, sync::mpsc},
+    ResultType, Stream,
+};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// A non-interactive session, any prompt is treated as a failure.
+#[derive(Clone)]
+pub struct Session {
+    lc: Arc<RwLock<LoginConfigHandler>>,
+    pub sender: mpsc::UnboundedSender<Data>,
+    pub error: Arc<Mutex<String>>,
+}
+
+#[async_trait]
+impl Interface for Session {
+    fn get_lch(&self) -> Arc<RwLock<LoginConfigHandler>> {
+        self.lc.clone()
+    }
+
+    fn msgbox(&self, msgtype: &str, title: &str, text: &str, _link: &str) {
+        log::info!("{}: {}: {}", msgtype, title, text);
+        if msgtype.contains("error")
+            || msgtype.starts_with("input")
+            || msgtype.starts_with("re-input")
+        {
+            let mut error = self.error.lock().unwrap();
+            if error.is_empty() {
+                *error = if text.is_empty() {
+                    title.to_owned()
+                } else {
+                    format!("{}: {}", title, text)
+                };
+            }
+            self.sender.send(Data::Close).ok();
+        }
+    }
+
+    fn handle_login_error(&self, err: &str) -> bool {
+        handle_login_error(self.lc.clone(), err, self)
+    }
+
+    fn handle_peer_info(&self, pi: PeerInfo) {
+        self.lc.write().unwrap().handle_peer_info(&pi);
+    }
+
+    fn set_multiple_windows_session(&self, _sessions: Vec<WindowsSession>) {}
+
+    async fn handle_hash(&self, pass: &str, hash: Hash, peer: &mut Stream) {
+        handle_hash(self.lc.clone(), pass, hash, self, peer).await;
+    }
+
+    async fn handle_login_from_ui(
+        &self,
+        os_username: String,
+        os_password: String,
+        password: String,
+        remember: bool,
+        peer: &mut Stream,
+    ) {
+        handle_login_from_ui(
+            self.lc.clone(),
+            os_username,
+            os_password,
+            password,
+            remember,
+            peer,
+        )
+        .await;
+    }
+
+    async fn handle_test_delay(&self, t: TestDelay, peer: &mut Stream) {
+        handle_test_delay(t, peer).await;
+    }
+
+    fn send(&self, data: Data) {
+        self.sender.send(data).ok();
+    }
+}
+
+/// A logged in connection.
+pub struct Connection {
+    pub session: Session,
+    pub stream: Stream,
+    pub receiver: mpsc::UnboundedReceiver<Data>,
+    pub peer_info: PeerInfo,
+    _hc: Option<mpsc::UnboundedSender<()>>,
+}
+
+/// Connect to the peer and log in, `init` sets up the login config before connecting.
+///
+/// The password falls back to the saved one of the peer if `password` is empty.
+pub async fn login(
+    id: &str,
+    conn_type: ConnType,
+    password: &str,
+    key: &str,
+    token: &str,
+    init: impl FnOnce(&mut LoginConfigHandler),
+) -> ResultType<Connection> {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<Data>();
+    let session = Session {
+        lc: Default::default(),
+        sender,
+        error: Default::default(),
+    };
+    session
+        .lc
+        .write()
+        .unwrap()
+        .initialize(id.to_owned(), conn_type, None, false, None, None, None);
+    init(&mut session.lc.write().unwrap());
+    let ((mut stream, direct, _pk), (feedback, rendezvous_server)) =
+        Client::start(id, key, token, conn_type, session.clone()).await?;
+    session.update_direct(Some(direct));
+    let hc = hc_connection(feedback, rendezvous_server, token).await;
+    loop {
+        tokio::select! {
+            res = timeout(READ_TIMEOUT, stream.next()) => match res {
+                Err(_) => {
+                    bail!("Timeout");
+                }
+                Ok(Some(Ok(bytes))) => {
+                    let msg_in = Message::parse_from_bytes(&bytes)?;
+                    match msg_in.union {
+                        Some(message::Union::Hash(hash)) => {
+                            session.handle_hash(password, hash, &mut stream).await;
+                        }
+                        Some(message::Union::LoginResponse(lr)) => match lr.union {
+                            Some(login_response::Union::Error(err)) => {
+                                if !session.handle_login_error(&err) {
+                                    bail!("{}", err);
+                                }
+                            }
+                            Some(login_response::Union::PeerInfo(pi)) => {
+                                session.handle_peer_info(pi.clone());
+                                return Ok(Connection {
+                                    session,
+                                    stream,
+                                    receiver,
+                                    peer_info: pi,
+                                    _hc: hc,
+                                });
+                            }
+                            _ => {}
+                        }
+                        Some(message::Union::TestDelay(t)) => {
+                            session.handle_test_delay(t, &mut stream).await;
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(Some(Err(err))) => {
+                    bail!("Connection closed: {}", err);
+                }
+                _ => {
+                    bail!("Reset by the peer");
+                }
+            },
+            d = receiver.recv() => {
+                match d {
+                    Some(Data::Close) => {
+                        bail!("{}", session.error.lock().unwrap());
+                    }
+                    Some(Data::Message(msg)) => {
+                        allow_err!(stream.send(&msg).await);
+                    }
+                    _ => {}
+                }
+            },
+        }
+    }
+}
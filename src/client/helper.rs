@@ -13,6 +13,12 @@ pub struct QualityStatus {
     pub target_bitrate: Option<i32>,
     pub codec_format: Option<CodecFormat>,
     pub chroma: Option<String>,
+    pub bandwidth: Option<String>, // kbps
+    pub av_sync: Option<i32>,      // ms the audio is ahead of the video
+    pub jitter: Option<i32>,       // ms
+    pub loss: Option<String>,      // percent of the heartbeats
+    pub encode_fps: Option<i32>,
+    pub direct: Option<bool>,
 }
 
 #[inline]
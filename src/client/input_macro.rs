@@ -0,0 +1,323 @@
+// Record the keyboard and mouse input sent to a peer with the timing, and play it again.
+//
+// The macros of a peer are saved in `macros/<id>/<name>.json` in the config directory. A step is
+// a message in protobuf and base64, so the mouse positions are the ones on the displays of the
+// peer it was recorded with.
+
+use super::{headless, Data, Interface};
+use hbb_common::{
+    allow_err, bail,
+    base64::{engine::general_purpose::STANDARD, Engine as _},
+    config::{Config, READ_TIMEOUT},
+    futures::StreamExt,
+    log,
+    message_proto::*,
+    protobuf::Message as _,
+    rendezvous_proto::ConnType,
+    timeout,
+    tokio::{self, sync::mpsc},
+    ResultType,
+};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+const EXTENSION: &str = "json";
+// the longest sleep before the stop flag is checked again
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Macro {
+    steps: Vec<Step>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Step {
+    // milliseconds after the last step
+    delay: u64,
+    message: String,
+}
+
+impl Macro {
+    pub fn load(id: &str, name: &str) -> ResultType<Self> {
+        let content = std::fs::read_to_string(path(id, name)?)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn save(&self, id: &str, name: &str) -> ResultType<()> {
+        let path = path(id, name)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn messages(&self) -> ResultType<Vec<(Duration, Message)>> {
+        let mut messages = vec![];
+        for step in self.steps.iter() {
+            let bytes = STANDARD.decode(&step.message)?;
+            messages.push((
+                Duration::from_millis(step.delay),
+                Message::parse_from_bytes(&bytes)?,
+            ));
+        }
+        Ok(messages)
+    }
+}
+
+fn path(id: &str, name: &str) -> ResultType<PathBuf> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || name.chars().any(|c| std::path::is_separator(c) || c == ':')
+    {
+        bail!("invalid macro name: {name}");
+    }
+    Ok(Config::path("macros")
+        .join(id)
+        .join(format!("{name}.{EXTENSION}")))
+}
+
+/// The names of the macros of the peer.
+pub fn list(id: &str) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(Config::path("macros").join(id)) else {
+        return vec![];
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().map(|e| e == EXTENSION) == Some(true))
+        .filter_map(|p| Some(p.file_stem()?.to_string_lossy().to_string()))
+        .collect();
+    names.sort();
+    names
+}
+
+pub fn remove(id: &str, name: &str) -> ResultType<()> {
+    std::fs::remove_file(path(id, name)?)?;
+    Ok(())
+}
+
+pub struct Recorder {
+    name: String,
+    last: Option<Instant>,
+    recorded: Macro,
+}
+
+impl Recorder {
+    pub fn new(name: &str) -> ResultType<Self> {
+        // check the name before the recording
+        path("", name)?;
+        Ok(Self {
+            name: name.to_owned(),
+            last: None,
+            recorded: Default::default(),
+        })
+    }
+
+    pub fn record(&mut self, msg: &Message) {
+        if !matches!(
+            msg.union,
+            Some(message::Union::KeyEvent(_))
+                | Some(message::Union::MouseEvent(_))
+                | Some(message::Union::PointerDeviceEvent(_))
+        ) {
+            return;
+        }
+        let Ok(bytes) = msg.write_to_bytes() else {
+            return;
+        };
+        let now = Instant::now();
+        let delay = self.last.map(|t| now - t).unwrap_or_default();
+        self.last = Some(now);
+        self.recorded.steps.push(Step {
+            delay: delay.as_millis() as _,
+            message: STANDARD.encode(bytes),
+        });
+    }
+
+    /// Saves the macro of the peer, returns the number of the steps.
+    pub fn save(self, id: &str) -> ResultType<usize> {
+        self.recorded.save(id, &self.name)?;
+        Ok(self.recorded.steps.len())
+    }
+}
+
+/// The text typed key by key, for the consoles which have neither the clipboard nor the input of
+/// unicode, e.g. BIOS and the virtual machines.
+pub fn keystrokes(text: &str, delay: Duration) -> Vec<(Duration, Message)> {
+    let mut messages = vec![];
+    for c in text.replace("\r\n", "\n").chars() {
+        let mut key_event = KeyEvent::new();
+        match c {
+            '\n' | '\r' => key_event.set_control_key(ControlKey::Return),
+            '\t' => key_event.set_control_key(ControlKey::Tab),
+            c => key_event.set_chr(c as _),
+        }
+        key_event.press = true;
+        key_event.mode = KeyboardMode::Legacy.into();
+        key_event.clipboard_typing = true;
+        let mut msg = Message::new();
+        msg.set_key_event(key_event);
+        let delay = if messages.is_empty() {
+            Duration::ZERO
+        } else {
+            delay
+        };
+        messages.push((delay, msg));
+    }
+    messages
+}
+
+/// Plays the macro until it is dropped.
+pub struct Player {
+    stop: Arc<AtomicBool>,
+}
+
+impl Player {
+    /// `close` sends `Data::Close` after the last step.
+    pub fn start(
+        messages: Vec<(Duration, Message)>,
+        sender: mpsc::UnboundedSender<Data>,
+        close: bool,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_cloned = stop.clone();
+        std::thread::spawn(move || {
+            for (delay, msg) in messages {
+                let wake = Instant::now() + delay;
+                while let Some(left) = wake.checked_duration_since(Instant::now()) {
+                    if stop_cloned.load(Ordering::SeqCst) || left.is_zero() {
+                        break;
+                    }
+                    std::thread::sleep(left.min(STOP_CHECK_INTERVAL));
+                }
+                if stop_cloned.load(Ordering::SeqCst) || sender.send(Data::Message(msg)).is_err() {
+                    log::info!("macro stopped");
+                    return;
+                }
+            }
+            if close {
+                sender.send(Data::Close).ok();
+            }
+        });
+        Self { stop }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.stop.load(Ordering::SeqCst) && Arc::strong_count(&self.stop) > 1
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Connect to the peer and play the macro, returns after the last step is sent.
+///
+/// The password falls back to the saved one of the peer if `password` is empty.
+pub async fn play(id: &str, name: &str, password: &str, key: &str, token: &str) -> ResultType<()> {
+    let messages = Macro::load(id, name)?.messages()?;
+    let headless::Connection {
+        session,
+        mut stream,
+        mut receiver,
+        ..
+    } = headless::login(id, ConnType::DEFAULT_CONN, password, key, token, |_| {}).await?;
+    let _player = Player::start(messages, session.sender.clone(), true);
+    loop {
+        tokio::select! {
+            res = timeout(READ_TIMEOUT, stream.next()) => match res {
+                Err(_) => {
+                    bail!("Timeout");
+                }
+                Ok(Some(Ok(bytes))) => {
+                    if let Ok(msg_in) = Message::parse_from_bytes(&bytes) {
+                        if let Some(message::Union::TestDelay(t)) = msg_in.union {
+                            session.handle_test_delay(t, &mut stream).await;
+                        }
+                    }
+                }
+                Ok(Some(Err(err))) => {
+                    bail!("Connection closed: {}", err);
+                }
+                _ => {
+                    bail!("Reset by the peer");
+                }
+            },
+            d = receiver.recv() => {
+                match d {
+                    Some(Data::Close) => {
+                        let error = session.error.lock().unwrap().clone();
+                        if !error.is_empty() {
+                            bail!("{}", error);
+                        }
+                        return Ok(());
+                    }
+                    Some(Data::Message(msg)) => {
+                        allow_err!(stream.send(&msg).await);
+                    }
+                    _ => {}
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut recorder = Recorder::new("test").unwrap();
+        let mut msg = Message::new();
+        msg.set_mouse_event(MouseEvent {
+            x: 10,
+            y: 20,
+            ..Default::default()
+        });
+        recorder.record(&msg);
+        let mut other = Message::new();
+        other.set_test_delay(Default::default());
+        recorder.record(&other);
+        let messages = recorder.recorded.messages().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0, Duration::ZERO);
+        assert_eq!(messages[0].1, msg);
+    }
+
+    #[test]
+    fn test_keystrokes() {
+        let delay = Duration::from_millis(20);
+        let messages = keystrokes("aB\r\n\t", delay);
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].0, Duration::ZERO);
+        assert_eq!(messages[1].0, delay);
+        let keys: Vec<KeyEvent> = messages
+            .into_iter()
+            .map(|(_, msg)| msg.key_event().clone())
+            .collect();
+        assert_eq!(keys[0].chr(), 'a' as u32);
+        assert_eq!(keys[1].chr(), 'B' as u32);
+        assert_eq!(keys[2].control_key(), ControlKey::Return);
+        assert_eq!(keys[3].control_key(), ControlKey::Tab);
+        assert!(keys.iter().all(|k| k.press && k.clipboard_typing));
+    }
+
+    #[test]
+    fn test_name() {
+        assert!(Recorder::new("").is_err());
+        assert!(Recorder::new("../a").is_err());
+        assert!(Recorder::new(".hidden").is_err());
+        assert!(Recorder::new("update windows").is_ok());
+    }
+}
@@ -1,9 +1,10 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     num::NonZeroI64,
+    path::PathBuf,
     sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc, RwLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
     },
 };
 
@@ -13,8 +14,8 @@ use crate::clipboard::{update_clipboard, ClipboardSide, CLIPBOARD_INTERVAL};
 use crate::{audio_service, ConnInner, CLIENT_SERVER};
 use crate::{
     client::{
-        self, new_voice_call_request, Client, Data, Interface, MediaData, MediaSender,
-        QualityStatus, MILLI1, SEC30,
+        self, av_sync::AvSync, new_voice_call_request, sync_job, Client, Data, Interface,
+        MediaData, MediaSender, QualityStatus, MILLI1, SEC30,
     },
     common::get_default_sound_input,
     ui_session_interface::{InvokeUiSession, Session},
@@ -57,9 +58,21 @@ pub struct Remote<T: InvokeUiSession> {
     // Stop sending local audio to remote client.
     stop_voice_call_sender: Option<std::sync::mpsc::Sender<()>>,
     voice_call_request_timestamp: Option<NonZeroI64>,
+    // the connection of the voice call to the audio service of this side
+    voice_call_conn_id: Option<i32>,
+    // the microphone is sent, off while the button of push-to-talk is not held
+    voice_call_talking: Arc<AtomicBool>,
     read_jobs: Vec<fs::TransferJob>,
     write_jobs: Vec<fs::TransferJob>,
     remove_jobs: HashMap<i32, RemoveJob>,
+    // the files of this side the removal jobs moved to the trash, the last removed last
+    trashed: Vec<(i32, fs::trash::Trashed)>,
+    // the sync jobs waiting for the files of the peer
+    sync_runs: HashMap<i32, SyncRun>,
+    // the uploads paused, whose writers of the peer are kept
+    paused_jobs: HashSet<i32>,
+    // the transfer queue saved last
+    saved_jobs: Option<TransferSerde>,
     timer: crate::RustDeskInterval,
     last_update_jobs_status: (Instant, HashMap<i32, u64>),
     is_connected: bool,
@@ -67,13 +80,27 @@ pub struct Remote<T: InvokeUiSession> {
     #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
     client_conn_id: i32, // used for file clipboard
     data_count: Arc<AtomicUsize>,
+    // of the session, reported with the delay tests for the congestion control of the peer
+    received_bytes: u64,
+    // the relayed path the session moves to if the direct one fails
+    backup: crate::backup_path::Backup,
+    // the messages of the peer are counted from its token for the backup path on
+    counting_received: bool,
+    // the better relay server the peer has asked to move the relayed session to
+    migrate_to: Option<String>,
+    metrics: super::metrics::Metrics,
     frame_count_map: Arc<RwLock<HashMap<usize, usize>>>,
     video_format: CodecFormat,
     elevation_requested: bool,
     fps_control: FpsControl,
     decode_fps: Arc<RwLock<Option<usize>>>,
     chroma: Arc<RwLock<Option<Chroma>>>,
+    av_sync: Arc<Mutex<AvSync>>,
     peer_info: ParsedPeerInfo,
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    gamepad: Option<super::gamepad::Forwarder>,
+    // the decoder of the camera of the peer, from its first frame
+    camera: Option<super::camera::Viewer>,
 }
 
 #[derive(Default)]
@@ -102,6 +129,7 @@ impl<T: InvokeUiSession> Remote<T> {
         frame_count_map: Arc<RwLock<HashMap<usize, usize>>>,
         decode_fps: Arc<RwLock<Option<usize>>>,
         chroma: Arc<RwLock<Option<Chroma>>>,
+        av_sync: Arc<Mutex<AvSync>>,
     ) -> Self {
         Self {
             handler,
@@ -113,6 +141,10 @@ impl<T: InvokeUiSession> Remote<T> {
             read_jobs: Vec::new(),
             write_jobs: Vec::new(),
             remove_jobs: Default::default(),
+            trashed: Vec::new(),
+            sync_runs: Default::default(),
+            paused_jobs: Default::default(),
+            saved_jobs: None,
             timer: crate::rustdesk_interval(time::interval(SEC30)),
             last_update_jobs_status: (Instant::now(), Default::default()),
             is_connected: false,
@@ -120,15 +152,26 @@ impl<T: InvokeUiSession> Remote<T> {
             #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
             client_conn_id: 0,
             data_count: Arc::new(AtomicUsize::new(0)),
+            received_bytes: 0,
+            backup: Default::default(),
+            counting_received: false,
+            migrate_to: None,
+            metrics: Default::default(),
             frame_count_map,
             video_format: CodecFormat::Unknown,
             stop_voice_call_sender: None,
             voice_call_request_timestamp: None,
+            voice_call_conn_id: None,
+            voice_call_talking: Default::default(),
             elevation_requested: false,
             fps_control: Default::default(),
             decode_fps,
             chroma,
+            av_sync,
             peer_info: Default::default(),
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            gamepad: None,
+            camera: None,
         }
     }
 
@@ -150,7 +193,7 @@ impl<T: InvokeUiSession> Remote<T> {
         )
         .await
         {
-            Ok(((mut peer, direct, pk), (feedback, rendezvous_server))) => {
+            Ok(((mut peer, mut direct, pk), (feedback, rendezvous_server))) => {
                 self.handler
                     .connection_round_state
                     .lock()
@@ -158,6 +201,7 @@ impl<T: InvokeUiSession> Remote<T> {
                     .set_connected();
                 self.handler.set_connection_type(peer.is_secured(), direct); // flutter -> connection_ready
                 self.handler.update_direct(Some(direct));
+                crate::dscp::mark(&peer, conn_type == ConnType::FILE_TRANSFER);
                 if conn_type == ConnType::DEFAULT_CONN {
                     self.handler
                         .set_fingerprint(crate::common::pk_to_fingerprint(pk.unwrap_or_default()));
@@ -190,14 +234,28 @@ impl<T: InvokeUiSession> Remote<T> {
                 let mut fps_instant = Instant::now();
 
                 let _keep_it = client::hc_connection(feedback, rendezvous_server, token).await;
+                let mut heartbeat = crate::heartbeat::Heartbeat::new();
+                #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                super::metrics::start_ipc();
 
                 loop {
                     tokio::select! {
                         res = peer.next() => {
+                            if self.backup.is_ready() && !matches!(res, Some(Ok(_))) {
+                                if !self.switch_to_backup(&mut peer, None).await {
+                                    break;
+                                }
+                                direct = false;
+                                continue;
+                            }
                             if let Some(res) = res {
                                 match res {
                                     Err(err) => {
-                                        self.handler.on_establish_connection_error(err.to_string());
+                                        if self.handler.is_restarting_remote_device() {
+                                            self.on_peer_restarting();
+                                        } else {
+                                            self.handler.on_establish_connection_error(err.to_string());
+                                        }
                                         break;
                                     }
                                     Ok(ref bytes) => {
@@ -207,6 +265,7 @@ impl<T: InvokeUiSession> Remote<T> {
                                             self.handler.update_received(true);
                                         }
                                         self.data_count.fetch_add(bytes.len(), Ordering::Relaxed);
+                                        self.received_bytes += bytes.len() as u64;
                                         if !self.handle_msg_from_peer(bytes, &mut peer).await {
                                             break
                                         }
@@ -214,8 +273,7 @@ impl<T: InvokeUiSession> Remote<T> {
                                 }
                             } else {
                                 if self.handler.is_restarting_remote_device() {
-                                    log::info!("Restart remote device");
-                                    self.handler.msgbox("restarting", "Restarting remote device", "remote_restarting_tip", "");
+                                    self.on_peer_restarting();
                                 } else {
                                     log::info!("Reset by the peer");
                                     self.handler.msgbox("error", "Connection Error", "Reset by the peer", "");
@@ -223,6 +281,15 @@ impl<T: InvokeUiSession> Remote<T> {
                                 break;
                             }
                         }
+                        res = self.backup.next() => {
+                            if let Some(received) = self.backup.on_next(res, &mut peer) {
+                                last_recv_time = Instant::now();
+                                if !self.switch_to_backup(&mut peer, Some(received)).await {
+                                    break;
+                                }
+                                direct = false;
+                            }
+                        }
                         d = self.receiver.recv() => {
                             if let Some(d) = d {
                                 if !self.handle_msg_from_ui(d, &mut peer).await {
@@ -235,10 +302,6 @@ impl<T: InvokeUiSession> Remote<T> {
                            self.handle_local_clipboard_msg(&mut peer, _msg).await;
                         }
                         _ = self.timer.tick() => {
-                            if last_recv_time.elapsed() >= SEC30 {
-                                self.handler.msgbox("error", "Connection Error", "Timeout", "");
-                                break;
-                            }
                             if !self.read_jobs.is_empty() {
                                 if let Err(err) = fs::handle_read_jobs(&mut self.read_jobs, &mut peer).await {
                                     self.handler.msgbox("error", "Connection Error", &err.to_string(), "");
@@ -250,6 +313,35 @@ impl<T: InvokeUiSession> Remote<T> {
                             }
                         }
                         _ = status_timer.tick() => {
+                            self.backup.keep_alive(&peer).await;
+                            if self.backup.is_ready() && self.migrate_to.take().is_some() {
+                                if !self.switch_to_backup(&mut peer, None).await {
+                                    break;
+                                }
+                                direct = false;
+                            }
+                            let silence = last_recv_time.elapsed();
+                            if heartbeat.is_dead(silence) {
+                                self.handler.msgbox("error", "Connection Error", "Timeout", "");
+                                break;
+                            }
+                            if let Some(degraded) = heartbeat.update(silence) {
+                                if degraded {
+                                    log::info!("connection degraded, nothing received for {:?}", silence);
+                                }
+                                self.handler.set_connection_degraded(degraded);
+                            }
+                            if let Some(msg) = heartbeat.next() {
+                                self.metrics.on_heartbeat(msg.test_delay().time);
+                                allow_err!(peer.send(&msg).await);
+                            }
+                            if self.backup.is_ready() && heartbeat.is_degraded() {
+                                log::info!("nothing received on the direct path for {:?}", silence);
+                                if !self.switch_to_backup(&mut peer, None).await {
+                                    break;
+                                }
+                                direct = false;
+                            }
                             self.fps_control(direct);
                             let elapsed = fps_instant.elapsed().as_millis();
                             if elapsed < 1000 {
@@ -258,6 +350,7 @@ impl<T: InvokeUiSession> Remote<T> {
                             fps_instant = Instant::now();
                             let mut speed = self.data_count.swap(0, Ordering::Relaxed);
                             speed = speed * 1000 / elapsed as usize;
+                            let bitrate = (speed * 8 / 1000) as u32;
                             let speed = format!("{:.2}kB/s", speed as f32 / 1024 as f32);
 
                             let mut frame_count_map_write = self.frame_count_map.write().unwrap();
@@ -275,10 +368,23 @@ impl<T: InvokeUiSession> Remote<T> {
                                 None => "-",
                             };
                             let chroma = Some(chroma.to_string());
+                            let av_sync = self.av_sync.lock().unwrap().drift().map(|d| d as i32);
+                            let kind = if conn_type == ConnType::FILE_TRANSFER {
+                                "file-transfer"
+                            } else {
+                                "remote"
+                            };
+                            self.metrics
+                                .report(self.handler.get_id(), kind, direct, bitrate, fps.clone());
                             self.handler.update_quality_status(QualityStatus {
                                 speed: Some(speed),
                                 fps,
                                 chroma,
+                                av_sync,
+                                jitter: self.metrics.jitter().map(|j| j as _),
+                                loss: Some(format!("{:.1}", self.metrics.loss())),
+                                encode_fps: self.metrics.encode_fps().map(|f| f as _),
+                                direct: Some(direct),
                                 ..Default::default()
                             });
                         }
@@ -368,13 +474,26 @@ impl<T: InvokeUiSession> Remote<T> {
     }
 
     fn handle_job_status(&mut self, id: i32, file_num: i32, err: Option<String>) {
+        self.handle_job_result(id, file_num, err, "");
+    }
+
+    // `digests` is the json of the sha256 of the files checked, by their names.
+    fn handle_job_result(&mut self, id: i32, file_num: i32, err: Option<String>, digests: &str) {
+        self.paused_jobs.remove(&id);
+        self.save_jobs();
         if let Some(job) = self.remove_jobs.get_mut(&id) {
             if job.no_confirm {
                 let file_num = (file_num + 1) as usize;
                 if file_num < job.files.len() {
                     let path = format!("{}{}{}", job.path, job.sep, job.files[file_num].name);
                     self.sender
-                        .send(Data::RemoveFile((id, path, file_num as i32, job.is_remote)))
+                        .send(Data::RemoveFile((
+                            id,
+                            path,
+                            file_num as i32,
+                            job.is_remote,
+                            job.trash,
+                        )))
                         .ok();
                     let elapsed = job.last_update_job_status.elapsed().as_millis() as i32;
                     if elapsed >= 1000 {
@@ -390,7 +509,7 @@ impl<T: InvokeUiSession> Remote<T> {
         if let Some(err) = err {
             self.handler.job_error(id, err, file_num);
         } else {
-            self.handler.job_done(id, file_num);
+            self.handler.job_done(id, file_num, digests);
         }
     }
 
@@ -401,6 +520,81 @@ impl<T: InvokeUiSession> Remote<T> {
         }
     }
 
+    async fn start_sync_job(&mut self, id: i32, name: String, dry_run: bool, peer: &mut Stream) {
+        let peer_id = self.handler.get_id();
+        let Some(job) = sync_job::get(&peer_id, &name) else {
+            self.sync_job_failed(id, &name, dry_run, "no such sync job".to_owned());
+            return;
+        };
+        if !dry_run {
+            allow_err!(sync_job::mark_run(&peer_id, &name));
+        }
+        let local = if std::path::Path::new(&job.local).exists() {
+            match fs::get_recursive_files(&job.local, job.include_hidden) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    self.sync_job_failed(id, &name, dry_run, err.to_string());
+                    return;
+                }
+            }
+        } else {
+            vec![]
+        };
+        let mut msg_out = Message::new();
+        let mut file_action = FileAction::new();
+        file_action.set_all_files(ReadAllFiles {
+            id,
+            path: job.remote.clone(),
+            include_hidden: job.include_hidden,
+            ..Default::default()
+        });
+        msg_out.set_file_action(file_action);
+        allow_err!(peer.send(&msg_out).await);
+        self.sync_runs.insert(
+            id,
+            SyncRun {
+                job,
+                local,
+                dry_run,
+            },
+        );
+    }
+
+    fn plan_sync_job(&self, id: i32, run: SyncRun, remote: &[FileEntry]) {
+        let plan = sync_job::plan(
+            &run.job,
+            &run.local,
+            remote,
+            self.handler.get_path_sep(false),
+            self.handler.get_path_sep(true),
+        );
+        if !run.dry_run {
+            let uploads = plan.copies.iter().filter(|c| c.upload).count();
+            let text = format!(
+                "{} to upload, {} to download, {} conflicts skipped",
+                uploads,
+                plan.copies.len() - uploads,
+                plan.conflicts.len()
+            );
+            allow_err!(sync_job::log(&self.handler.get_id(), &run.job.name, text));
+        }
+        let plan = serde_json::to_string(&plan).unwrap_or_default();
+        self.handler
+            .sync_job_plan(id, &run.job.name, run.dry_run, &plan, "");
+    }
+
+    fn sync_job_failed(&self, id: i32, name: &str, dry_run: bool, err: String) {
+        log::error!("sync job {name} failed: {err}");
+        if !dry_run {
+            allow_err!(sync_job::log(
+                &self.handler.get_id(),
+                name,
+                format!("failed: {err}")
+            ));
+        }
+        self.handler.sync_job_plan(id, name, dry_run, "", &err);
+    }
+
     // Start a voice call recorder, records audio and send to remote
     fn start_voice_call(&mut self) -> Option<std::sync::mpsc::Sender<()>> {
         if self.handler.is_file_transfer() || self.handler.is_port_forward() {
@@ -421,6 +615,7 @@ impl<T: InvokeUiSession> Remote<T> {
                 hbb_common::tokio::sync::mpsc::unbounded_channel();
             // Create a stand-alone inner, add subscribe to audio service
             let conn_id = CLIENT_SERVER.write().unwrap().get_new_id();
+            self.voice_call_conn_id = Some(conn_id);
             let client_conn_inner = ConnInner::new(conn_id.clone(), Some(tx_audio_data), None);
             // now we subscribe
             CLIENT_SERVER.write().unwrap().subscribe(
@@ -429,6 +624,12 @@ impl<T: InvokeUiSession> Remote<T> {
                 true,
             );
             let tx_audio = self.sender.clone();
+            let push_to_talk =
+                config::LocalConfig::get_option(config::keys::OPTION_VOICE_CALL_PUSH_TO_TALK)
+                    == "Y";
+            self.voice_call_talking
+                .store(!push_to_talk, Ordering::SeqCst);
+            let talking = self.voice_call_talking.clone();
             std::thread::spawn(move || {
                 loop {
                     // check if client is closed
@@ -442,6 +643,7 @@ impl<T: InvokeUiSession> Remote<T> {
                                 false,
                             );
                             crate::audio_service::set_voice_call_input_device(None, true);
+                            crate::audio_service::on_connection_close(conn_id);
                             break;
                         }
                         _ => {}
@@ -449,6 +651,9 @@ impl<T: InvokeUiSession> Remote<T> {
                     match rx_audio_data.try_recv() {
                         Ok((_instant, msg)) => match &msg.union {
                             Some(message::Union::AudioFrame(frame)) => {
+                                if !talking.load(Ordering::SeqCst) {
+                                    continue;
+                                }
                                 let mut msg = Message::new();
                                 msg.set_audio_frame(frame.clone());
                                 tx_audio.send(Data::Message(msg)).ok();
@@ -478,6 +683,27 @@ impl<T: InvokeUiSession> Remote<T> {
         }
     }
 
+    fn on_peer_restarting(&self) {
+        log::info!("Restart remote device");
+        let reconnect = self.handler.lc.read().unwrap().reconnect_after_restart;
+        if reconnect {
+            self.handler.msgbox(
+                "restarting",
+                "Restarting remote device",
+                "remote_restarting_reconnect_tip",
+                "",
+            );
+            self.handler.reconnect_after_restart();
+        } else {
+            self.handler.msgbox(
+                "restarting",
+                "Restarting remote device",
+                "remote_restarting_tip",
+                "",
+            );
+        }
+    }
+
     async fn handle_msg_from_ui(&mut self, data: Data, peer: &mut Stream) -> bool {
         match data {
             Data::Close => {
@@ -497,15 +723,41 @@ impl<T: InvokeUiSession> Remote<T> {
             Data::ToggleClipboardFile => {
                 self.check_clipboard_file_context();
             }
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            Data::ToggleGamepad => {
+                self.check_gamepad();
+            }
+            #[cfg(not(target_os = "ios"))]
+            Data::ToggleDenoise => {
+                self.check_denoise();
+            }
+            Data::ToggleAudio => {
+                // the peer is still heard in a voice call
+                if self.handler.lc.read().unwrap().disable_audio.v
+                    && self.stop_voice_call_sender.is_none()
+                {
+                    self.stop_audio();
+                }
+            }
             Data::Message(msg) => {
                 allow_err!(peer.send(&msg).await);
             }
-            Data::SendFiles((id, path, to, file_num, include_hidden, is_remote)) => {
+            Data::SendFiles((
+                id,
+                path,
+                to,
+                file_num,
+                include_hidden,
+                is_remote,
+                conflict_policy,
+            )) => {
                 log::info!("send files, is remote {}", is_remote);
                 let od = can_enable_overwrite_detection(self.handler.lc.read().unwrap().version);
+                let conflict_policy = fs::conflict::parse(&conflict_policy)
+                    .unwrap_or_else(fs::conflict::policy_from_local_config);
                 if is_remote {
                     log::debug!("New job {}, write to {} from remote {}", id, to, path);
-                    self.write_jobs.push(fs::TransferJob::new_write(
+                    let mut job = fs::TransferJob::new_write(
                         id,
                         path.clone(),
                         to,
@@ -514,10 +766,23 @@ impl<T: InvokeUiSession> Remote<T> {
                         is_remote,
                         Vec::new(),
                         od,
-                    ));
+                    );
+                    // the files are verified if the peer reads them with the checksums
+                    job.set_verify(true);
+                    let attributes = fs::attributes::Options::from_local_config();
+                    job.set_attributes(attributes, false);
+                    job.set_conflict_policy(conflict_policy);
+                    self.write_jobs.push(job);
                     allow_err!(
-                        peer.send(&fs::new_send(id, path, file_num, include_hidden))
-                            .await
+                        peer.send(&fs::new_send(
+                            id,
+                            path,
+                            file_num,
+                            include_hidden,
+                            attributes,
+                            fs::links::policy_from_local_config(),
+                        ))
+                        .await
                     );
                 } else {
                     match fs::TransferJob::new_read(
@@ -528,11 +793,14 @@ impl<T: InvokeUiSession> Remote<T> {
                         include_hidden,
                         is_remote,
                         od,
+                        fs::links::policy_from_local_config(),
                     ) {
                         Err(err) => {
                             self.handle_job_status(id, -1, Some(err.to_string()));
                         }
-                        Ok(job) => {
+                        Ok(mut job) => {
+                            job.set_attributes(fs::attributes::Options::from_local_config(), true);
+                            job.set_conflict_policy(conflict_policy);
                             log::debug!(
                                 "New job {}, read {} to remote {}, {} files",
                                 id,
@@ -557,11 +825,20 @@ impl<T: InvokeUiSession> Remote<T> {
                                 fs::transform_windows_path(&mut files);
                             }
                             let total_size = job.total_size();
+                            let attributes = job.attributes();
                             self.read_jobs.push(job);
                             self.timer = crate::rustdesk_interval(time::interval(MILLI1));
                             allow_err!(
-                                peer.send(&fs::new_receive(id, to, file_num, files, total_size))
-                                    .await
+                                peer.send(&fs::new_receive(
+                                    id,
+                                    to,
+                                    file_num,
+                                    files,
+                                    total_size,
+                                    attributes,
+                                    conflict_policy,
+                                ))
+                                .await
                             );
                         }
                     }
@@ -587,6 +864,9 @@ impl<T: InvokeUiSession> Remote<T> {
                         od,
                     );
                     job.is_last_job = true;
+                    job.set_verify(true);
+                    job.set_attributes(fs::attributes::Options::from_local_config(), false);
+                    job.set_conflict_policy(fs::conflict::policy_from_local_config());
                     self.write_jobs.push(job);
                 } else {
                     match fs::TransferJob::new_read(
@@ -597,11 +877,14 @@ impl<T: InvokeUiSession> Remote<T> {
                         include_hidden,
                         is_remote,
                         od,
+                        fs::links::policy_from_local_config(),
                     ) {
                         Err(err) => {
                             self.handle_job_status(id, -1, Some(err.to_string()));
                         }
                         Ok(mut job) => {
+                            job.set_attributes(fs::attributes::Options::from_local_config(), true);
+                            job.set_conflict_policy(fs::conflict::policy_from_local_config());
                             log::debug!(
                                 "new read waiting job {}, read {} to remote {}, {} files",
                                 id,
@@ -632,7 +915,9 @@ impl<T: InvokeUiSession> Remote<T> {
                                 id,
                                 job.remote.clone(),
                                 job.file_num,
-                                job.show_hidden
+                                job.show_hidden,
+                                job.attributes(),
+                                fs::links::policy_from_local_config(),
                             ))
                             .await
                         );
@@ -640,6 +925,10 @@ impl<T: InvokeUiSession> Remote<T> {
                 } else {
                     if let Some(job) = get_job(id, &mut self.read_jobs) {
                         job.is_last_job = false;
+                        if self.paused_jobs.remove(&id) {
+                            self.timer = crate::rustdesk_interval(time::interval(MILLI1));
+                            return true;
+                        }
                         allow_err!(
                             peer.send(&fs::new_receive(
                                 id,
@@ -647,12 +936,44 @@ impl<T: InvokeUiSession> Remote<T> {
                                 job.file_num,
                                 job.files.clone(),
                                 job.total_size(),
+                                job.attributes(),
+                                job.conflict_policy(),
                             ))
                             .await
                         );
                     }
                 }
             }
+            Data::PauseJob((id, is_remote)) => {
+                if is_remote {
+                    if let Some(job) = get_job(id, &mut self.write_jobs) {
+                        // the reader of the peer is removed, and created again once resumed
+                        let mut msg_out = Message::new();
+                        let mut file_action = FileAction::new();
+                        file_action.set_cancel(FileTransferCancel {
+                            id,
+                            ..Default::default()
+                        });
+                        msg_out.set_file_action(file_action);
+                        allow_err!(peer.send(&msg_out).await);
+                        job.pause_write().await;
+                    }
+                } else if let Some(job) = get_job(id, &mut self.read_jobs) {
+                    job.is_last_job = true;
+                    self.paused_jobs.insert(id);
+                }
+                self.save_jobs();
+            }
+            Data::ReorderJobs(ids) => {
+                let pos = |job: &fs::TransferJob| {
+                    ids.iter()
+                        .position(|id| *id == job.id())
+                        .unwrap_or(usize::MAX)
+                };
+                self.read_jobs.sort_by_key(pos);
+                self.write_jobs.sort_by_key(pos);
+                self.save_jobs();
+            }
             Data::SetNoConfirm(id) => {
                 if let Some(job) = self.remove_jobs.get_mut(&id) {
                     job.no_confirm = true;
@@ -670,16 +991,30 @@ impl<T: InvokeUiSession> Remote<T> {
                     }
                 }
             }
-            Data::SetConfirmOverrideFile((id, file_num, need_override, remember, is_upload)) => {
+            Data::ResolveFileConflict((id, file_num, policy, remember, is_upload)) => {
                 if is_upload {
                     if let Some(job) = fs::get_job(id, &mut self.read_jobs) {
-                        if remember {
-                            job.set_overwrite_strategy(Some(need_override));
+                        if job.peer_resolves_conflicts() {
+                            let mut msg = Message::new();
+                            let mut file_action = FileAction::new();
+                            file_action.set_conflict_resolution(FileConflictResolution {
+                                id,
+                                file_num,
+                                policy: policy.into(),
+                                remember,
+                                ..Default::default()
+                            });
+                            msg.set_file_action(file_action);
+                            allow_err!(peer.send(&msg).await);
+                            return true;
                         }
+                        // the peer can not write the file under another name
+                        let overwrite = job.answer_conflict(file_num, policy, remember)
+                            == fs::conflict::Resolution::Overwrite;
                         job.confirm(&FileTransferSendConfirmRequest {
                             id,
                             file_num,
-                            union: if need_override {
+                            union: if overwrite {
                                 Some(file_transfer_send_confirm_request::Union::OffsetBlk(0))
                             } else {
                                 Some(file_transfer_send_confirm_request::Union::Skip(true))
@@ -689,28 +1024,17 @@ impl<T: InvokeUiSession> Remote<T> {
                     }
                 } else {
                     if let Some(job) = fs::get_job(id, &mut self.write_jobs) {
-                        if remember {
-                            job.set_overwrite_strategy(Some(need_override));
-                        }
-                        let mut msg = Message::new();
-                        let mut file_action = FileAction::new();
-                        let req = FileTransferSendConfirmRequest {
-                            id,
-                            file_num,
-                            union: if need_override {
-                                Some(file_transfer_send_confirm_request::Union::OffsetBlk(0))
-                            } else {
-                                Some(file_transfer_send_confirm_request::Union::Skip(true))
-                            },
-                            ..Default::default()
-                        };
-                        job.confirm(&req);
-                        file_action.set_send_confirm(req);
-                        msg.set_file_action(file_action);
-                        allow_err!(peer.send(&msg).await);
+                        let resolution = job.answer_conflict(file_num, policy, remember);
+                        self.confirm_conflict(id, file_num, resolution, peer).await;
                     }
                 }
             }
+            Data::SendConfirm(req) => {
+                if let Some(job) = fs::get_job(req.id, &mut self.write_jobs) {
+                    job.confirm(&req);
+                    allow_err!(peer.send(&new_send_confirm(req)).await);
+                }
+            }
             Data::RemoveDirAll((id, path, is_remote, include_hidden)) => {
                 let sep = self.handler.get_path_sep(is_remote);
                 if is_remote {
@@ -760,20 +1084,36 @@ impl<T: InvokeUiSession> Remote<T> {
                 }
                 fs::remove_job(id, &mut self.read_jobs);
                 self.remove_jobs.remove(&id);
+                self.sync_runs.remove(&id);
+                self.paused_jobs.remove(&id);
+                self.save_jobs();
             }
-            Data::RemoveDir((id, path)) => {
-                let mut msg_out = Message::new();
-                let mut file_action = FileAction::new();
-                file_action.set_remove_dir(FileRemoveDir {
-                    id,
-                    path,
-                    recursive: true,
-                    ..Default::default()
-                });
-                msg_out.set_file_action(file_action);
-                allow_err!(peer.send(&msg_out).await);
+            Data::RemoveDir((id, path, is_remote, trash)) => {
+                if is_remote {
+                    let mut msg_out = Message::new();
+                    let mut file_action = FileAction::new();
+                    file_action.set_remove_dir(FileRemoveDir {
+                        id,
+                        path,
+                        recursive: true,
+                        trash,
+                        ..Default::default()
+                    });
+                    msg_out.set_file_action(file_action);
+                    allow_err!(peer.send(&msg_out).await);
+                } else if trash {
+                    match fs::trash::trash_dir_if_empty(&fs::get_path(&path)) {
+                        Ok(trashed) => self.trashed.extend(trashed.map(|t| (id, t))),
+                        Err(err) => log::error!("Failed to trash {}: {}", path, err),
+                    }
+                } else {
+                    fs::remove_all_empty_dir(&fs::get_path(&path)).ok();
+                }
             }
-            Data::RemoveFile((id, path, file_num, is_remote)) => {
+            Data::RemoveFile((id, path, file_num, is_remote, trash)) => {
+                if let Some(job) = self.remove_jobs.get_mut(&id) {
+                    job.trash = trash;
+                }
                 if is_remote {
                     let mut msg_out = Message::new();
                     let mut file_action = FileAction::new();
@@ -781,12 +1121,19 @@ impl<T: InvokeUiSession> Remote<T> {
                         id,
                         path,
                         file_num,
+                        trash,
                         ..Default::default()
                     });
                     msg_out.set_file_action(file_action);
                     allow_err!(peer.send(&msg_out).await);
                 } else {
-                    match fs::remove_file(&path) {
+                    let res = if trash {
+                        fs::trash::trash(&fs::get_path(&path))
+                            .map(|trashed| self.trashed.extend(trashed.map(|t| (id, t))))
+                    } else {
+                        fs::remove_file(&path)
+                    };
+                    match res {
                         Err(err) => {
                             self.handle_job_status(id, file_num, Some(err.to_string()));
                         }
@@ -796,6 +1143,22 @@ impl<T: InvokeUiSession> Remote<T> {
                     }
                 }
             }
+            Data::UndoRemove((id, remove_ids, is_remote)) => {
+                if is_remote {
+                    let mut msg_out = Message::new();
+                    let mut file_action = FileAction::new();
+                    file_action.set_undo_remove(FileUndoRemove {
+                        id,
+                        remove_ids,
+                        ..Default::default()
+                    });
+                    msg_out.set_file_action(file_action);
+                    allow_err!(peer.send(&msg_out).await);
+                } else {
+                    let err = fs::trash::restore_jobs(&mut self.trashed, &remove_ids).err();
+                    self.handle_job_status(id, 0, err.map(|e| e.to_string()));
+                }
+            }
             Data::CreateDir((id, path, is_remote)) => {
                 if is_remote {
                     let mut msg_out = Message::new();
@@ -818,6 +1181,30 @@ impl<T: InvokeUiSession> Remote<T> {
                     }
                 }
             }
+            Data::ReadRange((id, path, offset, length, is_remote)) => {
+                if is_remote {
+                    let mut msg_out = Message::new();
+                    let mut file_action = FileAction::new();
+                    file_action.set_read_range(FileReadRange {
+                        id,
+                        path,
+                        offset,
+                        length,
+                        ..Default::default()
+                    });
+                    msg_out.set_file_action(file_action);
+                    allow_err!(peer.send(&msg_out).await);
+                } else {
+                    match fs::read_range(&path, offset, length) {
+                        Err(err) => {
+                            self.handle_job_status(id, -1, Some(err.to_string()));
+                        }
+                        Ok((data, size)) => {
+                            self.handler.file_range(id, offset, &data, size);
+                        }
+                    }
+                }
+            }
             Data::RenameFile((id, path, new_name, is_remote)) => {
                 if is_remote {
                     let mut msg_out = Message::new();
@@ -837,6 +1224,25 @@ impl<T: InvokeUiSession> Remote<T> {
                     self.handle_job_status(id, -1, err);
                 }
             }
+            Data::SyncJob((id, name, dry_run)) => {
+                self.start_sync_job(id, name, dry_run, peer).await;
+            }
+            Data::SetJobRateLimit((id, kbps)) => {
+                // uploads are sent here, downloads by the peer
+                if let Some(job) = fs::get_job(id, &mut self.read_jobs) {
+                    job.set_rate_limit(kbps);
+                } else {
+                    let mut msg_out = Message::new();
+                    let mut file_action = FileAction::new();
+                    file_action.set_rate_limit(FileTransferRateLimit {
+                        id,
+                        kbps,
+                        ..Default::default()
+                    });
+                    msg_out.set_file_action(file_action);
+                    allow_err!(peer.send(&msg_out).await);
+                }
+            }
             Data::RecordScreen(start) => {
                 let _ = self.video_sender.send(MediaData::RecordScreen(start));
             }
@@ -874,6 +1280,9 @@ impl<T: InvokeUiSession> Remote<T> {
                 allow_err!(peer.send(&msg).await);
                 self.handler.on_voice_call_waiting();
             }
+            Data::VoiceCallTalking(talking) => {
+                self.voice_call_talking.store(talking, Ordering::SeqCst);
+            }
             Data::CloseVoiceCall => {
                 self.stop_voice_call();
                 let msg = new_voice_call_request(false);
@@ -933,12 +1342,23 @@ impl<T: InvokeUiSession> Remote<T> {
                 );
             }
             self.last_update_jobs_status.0 = Instant::now();
+            self.save_jobs();
         }
     }
 
     pub async fn sync_jobs_status_to_local(&mut self) -> bool {
         log::info!("sync transfer job status");
-        let mut config: PeerConfig = self.handler.load_config();
+        self.save_jobs();
+        true
+    }
+
+    // Saves the transfer queue of the peer once it changes, so it is resumed when the peer
+    // reconnects, even if the session is dropped.
+    fn save_jobs(&mut self) {
+        // the other sessions of the peer have no transfer queue
+        if !self.handler.is_file_transfer() {
+            return;
+        }
         let mut transfer_metas = TransferSerde::default();
         for job in self.read_jobs.iter() {
             let json_str = serde_json::to_string(&job.gen_meta()).unwrap_or_default();
@@ -948,12 +1368,16 @@ impl<T: InvokeUiSession> Remote<T> {
             let json_str = serde_json::to_string(&job.gen_meta()).unwrap_or_default();
             transfer_metas.write_jobs.push(json_str);
         }
+        if self.saved_jobs.as_ref() == Some(&transfer_metas) {
+            return;
+        }
         log::info!("meta: {:?}", transfer_metas);
+        let mut config: PeerConfig = self.handler.load_config();
         if config.transfer != transfer_metas {
-            config.transfer = transfer_metas;
+            config.transfer = transfer_metas.clone();
             self.handler.save_config(config);
         }
-        true
+        self.saved_jobs = Some(transfer_metas);
     }
 
     async fn send_toggle_virtual_display_msg(&self, peer: &mut Stream) {
@@ -1109,8 +1533,46 @@ impl<T: InvokeUiSession> Remote<T> {
         }
     }
 
+    // moves the session to the relayed backup path, the path in use being lost or relayed by a
+    // worse relay server
+    async fn switch_to_backup(&mut self, peer: &mut Stream, peer_received: Option<u64>) -> bool {
+        if let Err(err) = self.backup.switch(peer, peer_received).await {
+            self.handler
+                .msgbox("error", "Connection Error", &err.to_string(), "");
+            return false;
+        }
+        self.handler.set_connection_type(peer.is_secured(), false);
+        self.handler.update_direct(Some(false));
+        crate::dscp::mark(peer, self.handler.is_file_transfer());
+        true
+    }
+
+    fn connect_backup(&self, uuid: String) {
+        let Some(mut relay) = self.handler.lc.read().unwrap().backup_relay.clone() else {
+            return;
+        };
+        match self.migrate_to.as_ref() {
+            Some(relay_server) => relay.relay_server = relay_server.clone(),
+            None if !relay.direct => return,
+            None => {}
+        }
+        let id = self.handler.get_id();
+        let tx = self.backup.sender();
+        tokio::spawn(async move {
+            match Client::connect_backup_relay(&id, relay, &uuid).await {
+                Ok(path) => {
+                    tx.send(path).ok();
+                }
+                Err(err) => log::info!("Failed to connect the backup path: {}", err),
+            }
+        });
+    }
+
     async fn handle_msg_from_peer(&mut self, data: &[u8], peer: &mut Stream) -> bool {
         if let Ok(msg_in) = Message::parse_from_bytes(&data) {
+            if self.counting_received && crate::backup_path::is_counted(&msg_in) {
+                peer.on_received();
+            }
             match msg_in.union {
                 Some(message::Union::VideoFrame(vf)) => {
                     if !self.first_frame {
@@ -1154,6 +1616,11 @@ impl<T: InvokeUiSession> Remote<T> {
                         .last_active_time
                         .insert(display, Instant::now());
                 }
+                Some(message::Union::LosslessPatches(patches)) => {
+                    self.video_sender
+                        .send(MediaData::LosslessPatches(Box::new(patches)))
+                        .ok();
+                }
                 Some(message::Union::Hash(hash)) => {
                     self.handler
                         .handle_hash(&self.handler.password.clone(), hash, peer)
@@ -1172,10 +1639,14 @@ impl<T: InvokeUiSession> Remote<T> {
                     Some(login_response::Union::PeerInfo(pi)) => {
                         let peer_version = pi.version.clone();
                         let peer_platform = pi.platform.clone();
+                        let platform_additions = pi.platform_additions.clone();
                         self.set_peer_info(&pi);
                         self.handler.handle_peer_info(pi);
                         self.check_clipboard_file_context();
+                        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                        self.check_gamepad();
                         if !(self.handler.is_file_transfer() || self.handler.is_port_forward()) {
+                            self.handler.restore_remote_audio_input(&platform_additions);
                             #[cfg(feature = "flutter")]
                             #[cfg(not(any(target_os = "android", target_os = "ios")))]
                             let rx = Client::try_start_clipboard(None);
@@ -1224,9 +1695,51 @@ impl<T: InvokeUiSession> Remote<T> {
                         }
 
                         self.is_connected = true;
+                        if !self.handler.is_port_forward()
+                            && !peer.is_resend_enabled()
+                            && self
+                                .handler
+                                .lc
+                                .read()
+                                .unwrap()
+                                .backup_relay
+                                .as_ref()
+                                .map_or(false, |relay| relay.direct)
+                        {
+                            // numbered from the request on, the first message the peer counts
+                            peer.enable_resend();
+                            allow_err!(peer.send(&crate::backup_path::request_msg()).await);
+                        }
                     }
                     _ => {}
                 },
+                Some(message::Union::Fido2Challenge(_challenge)) => {
+                    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                    {
+                        self.handler.msgbox(
+                            "wait-fido2-nook",
+                            "Security key",
+                            "fido2-touch-tip",
+                            "",
+                        );
+                        let sender = self.sender.clone();
+                        // blocking until the key is touched
+                        std::thread::spawn(move || {
+                            let mut msg_out = Message::new();
+                            msg_out.set_fido2_assertion(crate::fido2::assert(&_challenge));
+                            sender.send(Data::Message(msg_out)).ok();
+                        });
+                    }
+                    #[cfg(any(target_os = "android", target_os = "ios"))]
+                    {
+                        let mut msg_out = Message::new();
+                        msg_out.set_fido2_assertion(Fido2Assertion {
+                            error: "Security keys are not supported on this device".to_owned(),
+                            ..Default::default()
+                        });
+                        allow_err!(peer.send(&msg_out).await);
+                    }
+                }
                 Some(message::Union::CursorData(cd)) => {
                     self.handler.set_cursor_data(cd);
                 }
@@ -1276,6 +1789,10 @@ impl<T: InvokeUiSession> Remote<T> {
                                     fs::transform_windows_path(&mut entries);
                                 }
                             }
+                            if let Some(run) = self.sync_runs.remove(&fd.id) {
+                                self.plan_sync_job(fd.id, run, &entries);
+                                return true;
+                            }
                             self.handler
                                 .update_folder_files(fd.id, &entries, fd.path, false, false);
                             if let Some(job) = fs::get_job(fd.id, &mut self.write_jobs) {
@@ -1285,44 +1802,92 @@ impl<T: InvokeUiSession> Remote<T> {
                                 job.files = entries;
                             }
                         }
+                        Some(file_response::Union::SearchResult(r)) => {
+                            #[cfg(windows)]
+                            let entries = r.entries.to_vec();
+                            #[cfg(not(windows))]
+                            let mut entries = r.entries.to_vec();
+                            #[cfg(not(windows))]
+                            {
+                                if self.handler.peer_platform() == "Windows" {
+                                    fs::transform_windows_path(&mut entries);
+                                }
+                            }
+                            self.handler
+                                .file_search_result(r.id, &entries, r.done, r.truncated);
+                        }
+                        Some(file_response::Union::Range(r)) => {
+                            self.handler.file_range(r.id, r.offset, &r.data, r.size);
+                        }
                         Some(file_response::Union::Digest(digest)) => {
                             if digest.is_upload {
                                 if let Some(job) = fs::get_job(digest.id, &mut self.read_jobs) {
+                                    if digest.verify {
+                                        job.set_verify(true);
+                                    }
+                                    if let Some(checksums) = digest.checksums.clone().into_option()
+                                    {
+                                        job.set_delta(digest.file_num, checksums);
+                                    }
                                     if let Some(file) = job.files().get(digest.file_num as usize) {
                                         let read_path = get_string(&job.join(&file.name));
-                                        let overwrite_strategy = job.default_overwrite_strategy();
-                                        if let Some(overwrite) = overwrite_strategy {
-                                            let req = FileTransferSendConfirmRequest {
-                                                id: digest.id,
-                                                file_num: digest.file_num,
-                                                union: Some(if overwrite {
+                                        let modified_time = file.modified_time;
+                                        job.set_peer_resolves_conflicts(digest.resolves_conflict);
+                                        let resolution = if digest.resolves_conflict {
+                                            fs::conflict::Resolution::Ask
+                                        } else {
+                                            job.resolve_conflict(
+                                                digest.file_num,
+                                                modified_time,
+                                                digest.last_modified,
+                                            )
+                                        };
+                                        match resolution {
+                                            fs::conflict::Resolution::Overwrite
+                                            | fs::conflict::Resolution::Skip => {
+                                                let union = if resolution
+                                                    == fs::conflict::Resolution::Overwrite
+                                                {
                                                     file_transfer_send_confirm_request::Union::OffsetBlk(0)
                                                 } else {
                                                     file_transfer_send_confirm_request::Union::Skip(
                                                         true,
                                                     )
-                                                }),
-                                                ..Default::default()
-                                            };
-                                            job.confirm(&req);
-                                            let msg = new_send_confirm(req);
-                                            allow_err!(peer.send(&msg).await);
-                                        } else {
-                                            self.handler.override_file_confirm(
-                                                digest.id,
-                                                digest.file_num,
-                                                read_path,
-                                                true,
-                                                digest.is_identical,
-                                            );
+                                                };
+                                                let req = FileTransferSendConfirmRequest {
+                                                    id: digest.id,
+                                                    file_num: digest.file_num,
+                                                    union: Some(union),
+                                                    ..Default::default()
+                                                };
+                                                job.confirm(&req);
+                                                let msg = new_send_confirm(req);
+                                                allow_err!(peer.send(&msg).await);
+                                            }
+                                            // the peer can not write the file under another name
+                                            fs::conflict::Resolution::Ask
+                                            | fs::conflict::Resolution::Rename => {
+                                                self.handler.override_file_confirm(
+                                                    digest.id,
+                                                    digest.file_num,
+                                                    read_path,
+                                                    true,
+                                                    digest.is_identical,
+                                                );
+                                            }
                                         }
                                     }
                                 }
                             } else {
                                 if let Some(job) = fs::get_job(digest.id, &mut self.write_jobs) {
+                                    job.set_delta_supported(digest.delta);
+                                    // the corrupted file sent again is already confirmed
+                                    if job.is_refetching() {
+                                        return true;
+                                    }
                                     if let Some(file) = job.files().get(digest.file_num as usize) {
                                         let write_path = get_string(&job.join(&file.name));
-                                        let overwrite_strategy = job.default_overwrite_strategy();
+                                        let file_size = file.size;
                                         match fs::is_write_need_confirmation(&write_path, &digest) {
                                             Ok(res) => match res {
                                                 DigestCheckResult::IsSame => {
@@ -1336,42 +1901,57 @@ impl<T: InvokeUiSession> Remote<T> {
                                                     let msg = new_send_confirm(req);
                                                     allow_err!(peer.send(&msg).await);
                                                 }
-                                                DigestCheckResult::NeedConfirm(digest) => {
-                                                    if let Some(overwrite) = overwrite_strategy {
+                                                DigestCheckResult::NeedConfirm(existing) => {
+                                                    match job.resolve_conflict(
+                                                        digest.file_num,
+                                                        digest.last_modified,
+                                                        existing.last_modified,
+                                                    ) {
+                                                        fs::conflict::Resolution::Ask => {
+                                                            self.handler.override_file_confirm(
+                                                                digest.id,
+                                                                digest.file_num,
+                                                                write_path,
+                                                                false,
+                                                                existing.is_identical,
+                                                            );
+                                                        }
+                                                        resolution => {
+                                                            self.confirm_conflict(
+                                                                digest.id,
+                                                                digest.file_num,
+                                                                resolution,
+                                                                peer,
+                                                            )
+                                                            .await;
+                                                        }
+                                                    }
+                                                }
+                                                DigestCheckResult::NoSuchFile => {
+                                                    // continue the interrupted transfer
+                                                    let partial =
+                                                        fs::get_partial_download(&write_path)
+                                                            .filter(|_| job.delta_supported());
+                                                    if let Some(partial) = partial {
+                                                        self.confirm_with_checksums(
+                                                            digest.id,
+                                                            digest.file_num,
+                                                            partial,
+                                                            file_size,
+                                                            true,
+                                                        );
+                                                    } else {
                                                         let req = FileTransferSendConfirmRequest {
                                                             id: digest.id,
                                                             file_num: digest.file_num,
-                                                            union: Some(if overwrite {
-                                                                file_transfer_send_confirm_request::Union::OffsetBlk(0)
-                                                            } else {
-                                                                file_transfer_send_confirm_request::Union::Skip(true)
-                                                            }),
+                                                            union: Some(file_transfer_send_confirm_request::Union::OffsetBlk(0)),
                                                             ..Default::default()
                                                         };
                                                         job.confirm(&req);
                                                         let msg = new_send_confirm(req);
                                                         allow_err!(peer.send(&msg).await);
-                                                    } else {
-                                                        self.handler.override_file_confirm(
-                                                            digest.id,
-                                                            digest.file_num,
-                                                            write_path,
-                                                            false,
-                                                            digest.is_identical,
-                                                        );
                                                     }
                                                 }
-                                                DigestCheckResult::NoSuchFile => {
-                                                    let req = FileTransferSendConfirmRequest {
-                                                        id: digest.id,
-                                                        file_num: digest.file_num,
-                                                        union: Some(file_transfer_send_confirm_request::Union::OffsetBlk(0)),
-                                                        ..Default::default()
-                                                    };
-                                                    job.confirm(&req);
-                                                    let msg = new_send_confirm(req);
-                                                    allow_err!(peer.send(&msg).await);
-                                                }
                                             },
                                             Err(err) => {
                                                 println!("error receiving digest: {}", err);
@@ -1385,6 +1965,8 @@ impl<T: InvokeUiSession> Remote<T> {
                             if let Some(job) = fs::get_job(block.id, &mut self.write_jobs) {
                                 if let Err(_err) = job.write(block).await {
                                     // to-do: add "skip" for writing job
+                                } else if let Some(msg) = job.verification_reply().await {
+                                    allow_err!(peer.send(&msg).await);
                                 }
                                 self.update_jobs_status();
                             }
@@ -1393,12 +1975,25 @@ impl<T: InvokeUiSession> Remote<T> {
                             let mut err: Option<String> = None;
                             if let Some(job) = fs::get_job(d.id, &mut self.write_jobs) {
                                 job.modify_time();
+                                job.create_links();
                                 err = job.job_error();
                                 fs::remove_job(d.id, &mut self.write_jobs);
                             }
-                            self.handle_job_status(d.id, d.file_num, err);
+                            let digests = serde_json::to_string(&d.digests).unwrap_or_default();
+                            self.handle_job_result(d.id, d.file_num, err, &digests);
                         }
                         Some(file_response::Union::Error(e)) => {
+                            if let Some(run) = self.sync_runs.remove(&e.id) {
+                                // the folder is created by the first file uploaded
+                                if e.error == fs::NOT_EXISTS
+                                    && run.job.direction != sync_job::Direction::Download
+                                {
+                                    self.plan_sync_job(e.id, run, &[]);
+                                } else {
+                                    self.sync_job_failed(e.id, &run.job.name, run.dry_run, e.error);
+                                }
+                                return true;
+                            }
                             if let Some(_job) = fs::get_job(e.id, &mut self.write_jobs) {
                                 fs::remove_job(e.id, &mut self.write_jobs);
                             }
@@ -1409,10 +2004,69 @@ impl<T: InvokeUiSession> Remote<T> {
                 }
                 Some(message::Union::Misc(misc)) => match misc.union {
                     Some(misc::Union::AudioFormat(f)) => {
+                        // The video thread muxes audio into the screen recording.
+                        self.video_sender
+                            .send(MediaData::AudioFormat(f.clone()))
+                            .ok();
                         self.audio_sender.send(MediaData::AudioFormat(f)).ok();
                     }
-                    Some(misc::Union::ChatMessage(c)) => {
-                        self.handler.new_message(c.text);
+                    Some(misc::Union::ChatMessage(mut c)) => {
+                        let mut error = "".to_owned();
+                        match c.attachment.take() {
+                            Some(attachment) => match crate::chat::save(&attachment) {
+                                Ok(path) => self.handler.new_attachment(path, attachment.image),
+                                Err(err) => {
+                                    log::error!("Failed to save the chat attachment: {}", err);
+                                    error = err.to_string();
+                                }
+                            },
+                            None => self.handler.new_message(c.text),
+                        }
+                        if c.id != 0 {
+                            let msg = crate::chat::new_receipt(c.id, error);
+                            allow_err!(peer.send(&msg).await);
+                        }
+                    }
+                    Some(misc::Union::ChatReceipt(r)) => {
+                        self.handler.chat_receipt(r.id, r.error);
+                    }
+                    Some(misc::Union::SessionHandoff(h)) => {
+                        // opened on the other device, it logs in with the ticket as the uuid of
+                        // the switch of the sides
+                        let link = uuid::Uuid::from_slice(&h.ticket)
+                            .map(|ticket| {
+                                format!(
+                                    "{}connection/new/{}?handoff={}",
+                                    crate::get_uri_prefix(),
+                                    self.handler.get_id(),
+                                    ticket
+                                )
+                            })
+                            .unwrap_or_default();
+                        self.handler.session_handoff(link, h.timeout, h.error);
+                    }
+                    Some(misc::Union::SystemStats(stats)) => {
+                        let stats = super::metrics::HostStats::from(&stats);
+                        self.metrics.on_system_stats(stats.clone());
+                        self.handler.update_system_stats(stats);
+                    }
+                    Some(misc::Union::ProcessList(list)) => {
+                        self.handler.update_process_list(&list);
+                    }
+                    Some(misc::Union::ProcessKilled(killed)) => {
+                        self.handler.process_killed(&killed);
+                    }
+                    Some(misc::Union::ServiceList(list)) => {
+                        self.handler.update_service_list(&list);
+                    }
+                    Some(misc::Union::ServiceControlResult(res)) => {
+                        self.handler.service_control_result(&res);
+                    }
+                    Some(misc::Union::CameraList(list)) => {
+                        self.handler.update_camera_list(&list);
+                    }
+                    Some(misc::Union::BroadcastViewers(viewers)) => {
+                        self.handler.update_broadcast_viewers(&viewers);
                     }
                     Some(misc::Union::PermissionInfo(p)) => {
                         log::info!("Change permission {:?} -> {}", p.permission, p.enabled);
@@ -1433,6 +2087,9 @@ impl<T: InvokeUiSession> Remote<T> {
                                 self.handler.set_permission("clipboard", p.enabled);
                             }
                             Ok(Permission::Audio) => {
+                                if !p.enabled {
+                                    self.stop_audio();
+                                }
                                 self.handler.set_permission("audio", p.enabled);
                             }
                             Ok(Permission::File) => {
@@ -1452,6 +2109,15 @@ impl<T: InvokeUiSession> Remote<T> {
                             Ok(Permission::BlockInput) => {
                                 self.handler.set_permission("block_input", p.enabled);
                             }
+                            Ok(Permission::KeyInput) => {
+                                self.handler.set_permission("key_input", p.enabled);
+                            }
+                            Ok(Permission::MouseInput) => {
+                                self.handler.set_permission("mouse_input", p.enabled);
+                            }
+                            Ok(Permission::ClipboardTyping) => {
+                                self.handler.set_permission("clipboard_typing", p.enabled);
+                            }
                             _ => {}
                         }
                     }
@@ -1545,6 +2211,30 @@ impl<T: InvokeUiSession> Remote<T> {
                             }
                         }
                     }
+                    Some(misc::Union::Volume(volume)) => {
+                        self.handler.update_remote_volume(volume.level, volume.mute);
+                    }
+                    Some(misc::Union::ServerRecordStatus(on)) => {
+                        self.handler.update_server_record_status(on);
+                    }
+                    Some(misc::Union::InactivityWarning(w)) => {
+                        self.handler.inactivity_warning(w.seconds, w.view_only);
+                    }
+                    Some(misc::Union::InputControl(control)) => {
+                        self.handler.update_input_control(control);
+                    }
+                    Some(misc::Union::SecureInput(on)) => {
+                        let keyboard = self.handler.server_keyboard_enabled.read().unwrap().clone();
+                        let msgtype = "custom-nook-nocancel-hasclose-info";
+                        let title = "Prompt";
+                        let text = "secure_input_tip";
+                        if on && keyboard {
+                            self.handler.msgbox(msgtype, title, text, "");
+                        } else {
+                            self.handler
+                                .cancel_msgbox(&format!("{}-{}-{}-{}", msgtype, title, text, ""));
+                        }
+                    }
                     Some(misc::Union::ElevationResponse(err)) => {
                         if err.is_empty() {
                             self.handler.msgbox("wait-uac", "", "", "");
@@ -1596,13 +2286,72 @@ impl<T: InvokeUiSession> Remote<T> {
                     Some(misc::Union::FollowCurrentDisplay(d_idx)) => {
                         self.handler.set_current_display(d_idx);
                     }
+                    Some(misc::Union::EncoderFallback(f)) => {
+                        log::info!("encoder fallback: {:?}", f);
+                        self.handler.msgbox(
+                            "custom-nook-nocancel-hasclose-info",
+                            "Encoder fallback",
+                            &format!(
+                                "Display #{}: {} -> {}, {}",
+                                f.display + 1,
+                                f.from,
+                                f.to,
+                                f.reason
+                            ),
+                            "",
+                        );
+                    }
                     _ => {}
                 },
-                Some(message::Union::TestDelay(t)) => {
+                Some(message::Union::TestDelay(mut t)) => {
+                    if t.from_client {
+                        self.metrics.on_echo(t.time);
+                    } else {
+                        self.metrics.on_test_delay(&t);
+                    }
+                    t.received_bytes = self.received_bytes;
                     self.handler.handle_test_delay(t, peer).await;
                 }
+                Some(message::Union::Multipath(mp)) => match mp.union {
+                    Some(multipath::Union::Token(uuid)) => {
+                        if !self.counting_received {
+                            // the first message the peer has numbered
+                            self.counting_received = true;
+                            peer.on_received();
+                        }
+                        self.connect_backup(uuid);
+                    }
+                    Some(multipath::Union::Migrate(relay_server)) => {
+                        // a backup path through it is connected with a new token, and the
+                        // session moves to it at once
+                        if !self.handler.is_port_forward()
+                            && self.handler.lc.read().unwrap().backup_relay.is_some()
+                        {
+                            log::info!("moving the relayed session to {}", relay_server);
+                            self.migrate_to = Some(relay_server);
+                            peer.enable_resend();
+                            allow_err!(peer.send(&crate::backup_path::request_msg()).await);
+                        }
+                    }
+                    Some(multipath::Union::SwitchPath(received)) => {
+                        // the answer of the peer after this side has moved
+                        if let Err(err) = peer.resume(received).await {
+                            self.handler
+                                .msgbox("error", "Connection Error", &err.to_string(), "");
+                            return false;
+                        }
+                    }
+                    _ => {}
+                },
                 Some(message::Union::AudioFrame(frame)) => {
-                    if !self.handler.lc.read().unwrap().disable_audio.v {
+                    if self.handler.lc.read().unwrap().record {
+                        self.video_sender
+                            .send(MediaData::AudioFrame(Box::new(frame.clone())))
+                            .ok();
+                    }
+                    if !self.handler.lc.read().unwrap().disable_audio.v
+                        || self.stop_voice_call_sender.is_some()
+                    {
                         self.audio_sender
                             .send(MediaData::AudioFrame(Box::new(frame)))
                             .ok();
@@ -1611,6 +2360,9 @@ impl<T: InvokeUiSession> Remote<T> {
                 Some(message::Union::FileAction(action)) => match action.union {
                     Some(file_action::Union::SendConfirm(c)) => {
                         if let Some(job) = fs::get_job(c.id, &mut self.read_jobs) {
+                            if c.verify {
+                                job.set_verify(true);
+                            }
                             job.confirm(&c);
                         }
                     }
@@ -1648,6 +2400,7 @@ impl<T: InvokeUiSession> Remote<T> {
                                 // The peer accepted the voice call.
                                 self.handler.on_voice_call_started();
                                 self.stop_voice_call_sender = self.start_voice_call();
+                                self.check_denoise();
                             } else {
                                 // The peer refused the voice call.
                                 self.handler.on_voice_call_closed("");
@@ -1655,6 +2408,37 @@ impl<T: InvokeUiSession> Remote<T> {
                         }
                     }
                 }
+                Some(message::Union::Annotation(annotation)) => {
+                    if let Some(annotation::Union::Board(board)) = annotation.union {
+                        self.handler.update_annotation(board.strokes);
+                    }
+                }
+                Some(message::Union::CameraFrame(frame)) => {
+                    if !frame.error.is_empty() {
+                        self.handler.camera_error(frame.error);
+                    } else if let Some(vf) = frame.frame.into_option() {
+                        self.camera
+                            .get_or_insert_with(|| {
+                                super::camera::Viewer::start(self.handler.clone())
+                            })
+                            .handle_frame(vf);
+                    }
+                }
+                #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                Some(message::Union::PrintJob(job)) => {
+                    // only while this side prints the documents of the peer
+                    if !self.handler.lc.read().unwrap().remote_printer {
+                        log::warn!("Dropped the print job of the peer, the printer is off");
+                    } else {
+                        match crate::printer::save(&job) {
+                            Ok(path) => {
+                                let copies = crate::printer::copies(&job);
+                                self.handler.print_job(job.title, path, copies);
+                            }
+                            Err(err) => log::error!("Failed to save the print job: {}", err),
+                        }
+                    }
+                }
                 Some(message::Union::PeerInfo(pi)) => {
                     self.handler.set_displays(&pi.displays);
                     self.handler.set_platform_additions(&pi.platform_additions);
@@ -1873,6 +2657,124 @@ impl<T: InvokeUiSession> Remote<T> {
         }
     }
 
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    fn check_gamepad(&mut self) {
+        let enabled = {
+            let lc = self.handler.lc.read().unwrap();
+            lc.get_toggle_option(config::keys::OPTION_FORWARD_GAMEPAD) && lc.is_gamepad_supported()
+        } && !(self.handler.is_file_transfer() || self.handler.is_port_forward());
+        if enabled != self.gamepad.is_some() {
+            self.gamepad = if enabled {
+                Some(super::gamepad::Forwarder::start(self.sender.clone()))
+            } else {
+                None
+            };
+        }
+    }
+
+    // Confirms the file written on this side by the resolution of its conflict with the file there.
+    async fn confirm_conflict(
+        &mut self,
+        id: i32,
+        file_num: i32,
+        resolution: fs::conflict::Resolution,
+        peer: &mut Stream,
+    ) {
+        let Some(job) = fs::get_job(id, &mut self.write_jobs) else {
+            return;
+        };
+        let union = match resolution {
+            fs::conflict::Resolution::Overwrite => {
+                let copy = job
+                    .files()
+                    .get(file_num as usize)
+                    .filter(|_| job.delta_supported())
+                    .map(|f| (job.join(&f.name), f.size));
+                if let Some((path, file_size)) = copy {
+                    self.confirm_with_checksums(id, file_num, path, file_size, false);
+                    return;
+                }
+                file_transfer_send_confirm_request::Union::OffsetBlk(0)
+            }
+            fs::conflict::Resolution::Rename => {
+                job.rename_conflicting(file_num);
+                file_transfer_send_confirm_request::Union::OffsetBlk(0)
+            }
+            // nothing is answered
+            fs::conflict::Resolution::Skip | fs::conflict::Resolution::Ask => {
+                file_transfer_send_confirm_request::Union::Skip(true)
+            }
+        };
+        let req = FileTransferSendConfirmRequest {
+            id,
+            file_num,
+            union: Some(union),
+            ..Default::default()
+        };
+        job.confirm(&req);
+        allow_err!(peer.send(&new_send_confirm(req)).await);
+    }
+
+    // The checksums of the copy of the file on this side are read in the background, as the file
+    // may be large, then the transfer is confirmed with them.
+    fn confirm_with_checksums(
+        &self,
+        id: i32,
+        file_num: i32,
+        path: PathBuf,
+        file_size: u64,
+        partial: bool,
+    ) {
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            let res = tokio::task::spawn_blocking(move || {
+                fs::delta::checksums(&path, file_size, partial)
+            })
+            .await;
+            let union = match res {
+                Ok(Ok(checksums)) => {
+                    file_transfer_send_confirm_request::Union::Checksums(checksums)
+                }
+                res => {
+                    log::error!("failed to read the checksums: {:?}", res.map(|r| r.err()));
+                    file_transfer_send_confirm_request::Union::OffsetBlk(0)
+                }
+            };
+            sender
+                .send(Data::SendConfirm(FileTransferSendConfirmRequest {
+                    id,
+                    file_num,
+                    union: Some(union),
+                    ..Default::default()
+                }))
+                .ok();
+        });
+    }
+
+    // Closes the output devices once the audio is off. The peer stops the capture itself, and sends
+    // the format again to its new subscription once the audio is on.
+    fn stop_audio(&self) {
+        self.audio_sender.send(MediaData::Reset(None)).ok();
+    }
+
+    // The microphone of this side in a voice call.
+    #[cfg(not(target_os = "ios"))]
+    fn check_denoise(&self) {
+        let Some(conn_id) = self.voice_call_conn_id else {
+            return;
+        };
+        if self.stop_voice_call_sender.is_none() {
+            return;
+        }
+        let on = self
+            .handler
+            .lc
+            .read()
+            .unwrap()
+            .get_toggle_option(config::keys::OPTION_DENOISE_AUDIO);
+        crate::audio_service::set_denoise(conn_id, on);
+    }
+
     #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
     fn handle_cliprdr_msg(&self, clip: hbb_common::message_proto::Cliprdr) {
         log::debug!("handling cliprdr msg from server peer");
@@ -1915,6 +2817,8 @@ struct RemoveJob {
     sep: &'static str,
     is_remote: bool,
     no_confirm: bool,
+    // the files confirmed are moved to the trash
+    trash: bool,
     last_update_job_status: Instant,
 }
 
@@ -1926,6 +2830,7 @@ impl RemoveJob {
             sep,
             is_remote,
             no_confirm: false,
+            trash: false,
             last_update_job_status: Instant::now(),
         }
     }
@@ -1939,6 +2844,12 @@ impl RemoveJob {
     }
 }
 
+struct SyncRun {
+    job: sync_job::SyncJob,
+    local: Vec<FileEntry>,
+    dry_run: bool,
+}
+
 struct FpsControl {
     last_queue_size: usize,
     refresh_times: usize,
@@ -0,0 +1,293 @@
+// Connection quality metrics of the sessions of this process.
+//
+// The io loop of each session measures the round trip time, its jitter and the loss with its own
+// heartbeats, takes the target bitrate and the frames sent from the test delays of the controlled
+// side, and reports them every second with the bitrate received, the frames decoded and whether
+// the connection is relayed. They are shown in the quality monitor, and streamed over the
+// "_metrics" ipc to the monitoring agents, which `--metrics` prints as json lines, with the load
+// of the controlled side while the session watches it.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+use hbb_common::{futures::StreamExt, log, sleep, tokio};
+use hbb_common::{
+    get_time,
+    message_proto::{SystemStats, TestDelay},
+};
+use serde_derive::{Deserialize, Serialize};
+
+// the heartbeats not answered within this many milliseconds are lost
+const LOSS_DEADLINE: i64 = 5_000;
+// the heartbeats the loss is computed over
+const LOSS_WINDOW: usize = 60;
+// in milliseconds, the controlled side sends its load every 2 seconds
+const HOST_STATS_TIMEOUT: i64 = 6_000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SessionMetrics {
+    pub id: String,
+    // "remote" or "file-transfer"
+    pub conn_type: String,
+    pub direct: bool,
+    // in milliseconds, none before the first heartbeat is answered
+    pub rtt: Option<u32>,
+    pub jitter: Option<u32>,
+    // in percent, of the heartbeats
+    pub loss: f32,
+    // in kbps
+    pub target_bitrate: Option<u32>,
+    pub bitrate: u32,
+    // sent by the controlled side, none if it does not tell
+    pub encode_fps: Option<u32>,
+    // by display
+    pub decode_fps: HashMap<usize, i32>,
+    // in milliseconds since the epoch
+    pub time: i64,
+    // the load of the controlled side, while watched
+    #[serde(default)]
+    pub host: Option<HostStats>,
+}
+
+/// The load of the controlled side, as it is sent in the stats of the system.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct HostStats {
+    // in percent
+    pub cpu: f32,
+    pub cores: Vec<f32>,
+    // in bytes
+    pub memory_used: u64,
+    pub memory_total: u64,
+    // the mount points, the bytes in total and the ones available
+    pub disks: Vec<(String, u64, u64)>,
+    // the labels of the sensors, in celsius
+    pub temperatures: Vec<(String, f32)>,
+}
+
+impl From<&SystemStats> for HostStats {
+    fn from(stats: &SystemStats) -> Self {
+        Self {
+            cpu: stats.cpu,
+            cores: stats.cores.clone(),
+            memory_used: stats.memory_used,
+            memory_total: stats.memory_total,
+            disks: stats
+                .disks
+                .iter()
+                .map(|d| (d.mount_point.clone(), d.total, d.available))
+                .collect(),
+            temperatures: stats
+                .temperatures
+                .iter()
+                .map(|t| (t.label.clone(), t.celsius))
+                .collect(),
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref SESSIONS: Mutex<HashMap<(String, String), SessionMetrics>> = Default::default();
+}
+
+/// The last metrics of the sessions of this process.
+pub fn get() -> Vec<SessionMetrics> {
+    SESSIONS.lock().unwrap().values().cloned().collect()
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    key: Option<(String, String)>,
+    // the time of the heartbeats sent, and whether they are answered
+    heartbeats: VecDeque<(i64, bool)>,
+    rtt: Option<u32>,
+    jitter: Option<f64>,
+    target_bitrate: Option<u32>,
+    encode_fps: Option<u32>,
+    // with the time it came, it is not sent once the session stops watching
+    host: Option<(HostStats, i64)>,
+}
+
+impl Metrics {
+    pub fn on_heartbeat(&mut self, time: i64) {
+        self.heartbeats.push_back((time, false));
+        while self.heartbeats.len() > LOSS_WINDOW {
+            self.heartbeats.pop_front();
+        }
+    }
+
+    /// The heartbeat sent at `time` is echoed back.
+    pub fn on_echo(&mut self, time: i64) {
+        let Some(heartbeat) = self.heartbeats.iter_mut().find(|(t, _)| *t == time) else {
+            return;
+        };
+        heartbeat.1 = true;
+        let rtt = (get_time() - time).max(0) as u32;
+        if let Some(last) = self.rtt {
+            // as the interarrival jitter of rfc 3550
+            let diff = (rtt as f64 - last as f64).abs();
+            let jitter = self.jitter.unwrap_or_default();
+            self.jitter = Some(jitter + (diff - jitter) / 16.);
+        }
+        self.rtt = Some(rtt);
+    }
+
+    pub fn on_test_delay(&mut self, t: &TestDelay) {
+        self.target_bitrate = Some(t.target_bitrate);
+        // zero from the versions which do not tell
+        if t.encode_fps > 0 || self.encode_fps.is_some() {
+            self.encode_fps = Some(t.encode_fps);
+        }
+    }
+
+    pub fn on_system_stats(&mut self, stats: HostStats) {
+        self.host = Some((stats, get_time()));
+    }
+
+    pub fn jitter(&self) -> Option<u32> {
+        self.jitter.map(|j| j.round() as _)
+    }
+
+    pub fn encode_fps(&self) -> Option<u32> {
+        self.encode_fps
+    }
+
+    pub fn loss(&self) -> f32 {
+        let now = get_time();
+        let (sent, lost) = self
+            .heartbeats
+            .iter()
+            .filter(|(t, _)| now - t >= LOSS_DEADLINE)
+            .fold((0, 0), |(sent, lost), (_, answered)| {
+                (sent + 1, lost + !answered as usize)
+            });
+        if sent == 0 {
+            0.
+        } else {
+            lost as f32 * 100. / sent as f32
+        }
+    }
+
+    /// Publishes the metrics of the last second.
+    pub fn report(
+        &mut self,
+        id: String,
+        conn_type: &str,
+        direct: bool,
+        bitrate: u32,
+        decode_fps: HashMap<usize, i32>,
+    ) {
+        let metrics = SessionMetrics {
+            id: id.clone(),
+            conn_type: conn_type.to_owned(),
+            direct,
+            rtt: self.rtt,
+            jitter: self.jitter(),
+            loss: self.loss(),
+            target_bitrate: self.target_bitrate,
+            bitrate,
+            encode_fps: self.encode_fps,
+            decode_fps,
+            time: get_time(),
+            host: self
+                .host
+                .as_ref()
+                .filter(|(_, t)| get_time() - t < HOST_STATS_TIMEOUT)
+                .map(|(s, _)| s.clone()),
+        };
+        let key = (id, conn_type.to_owned());
+        self.key = Some(key.clone());
+        SESSIONS.lock().unwrap().insert(key, metrics);
+    }
+}
+
+impl Drop for Metrics {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            SESSIONS.lock().unwrap().remove(&key);
+        }
+    }
+}
+
+/// Streams the metrics of the sessions to the ipc clients, once a second.
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub fn start_ipc() {
+    static START: std::sync::Once = std::sync::Once::new();
+    START.call_once(|| {
+        std::thread::spawn(serve);
+    });
+}
+
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+#[tokio::main(flavor = "current_thread")]
+async fn serve() {
+    use crate::ipc::{self, Data};
+
+    match ipc::new_listener("_metrics").await {
+        Ok(mut incoming) => {
+            while let Some(Ok(stream)) = incoming.next().await {
+                tokio::spawn(async move {
+                    let mut conn = ipc::Connection::new(stream);
+                    loop {
+                        sleep(1.).await;
+                        if conn.send(&Data::SessionMetrics(get())).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        }
+        Err(err) => log::info!("Failed to start the metrics ipc server: {}", err),
+    }
+}
+
+/// Prints the metrics streamed by the client as json lines, one a session, until it exits.
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+#[tokio::main(flavor = "current_thread")]
+pub async fn print() -> hbb_common::ResultType<()> {
+    let mut conn = crate::ipc::connect(1_000, "_metrics").await?;
+    while let Some(data) = conn.next().await? {
+        if let crate::ipc::Data::SessionMetrics(sessions) = data {
+            for metrics in sessions {
+                println!("{}", serde_json::to_string(&metrics)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        let now = get_time();
+        let mut metrics = Metrics::default();
+        for i in 0..4 {
+            metrics.on_heartbeat(now - LOSS_DEADLINE - i);
+        }
+        metrics.on_heartbeat(now);
+        metrics.on_echo(now - LOSS_DEADLINE);
+        metrics.on_echo(now - LOSS_DEADLINE - 1);
+        // not sent
+        metrics.on_echo(now - 1);
+        assert_eq!(metrics.loss(), 50.);
+        assert!(metrics.rtt.unwrap() >= LOSS_DEADLINE as u32);
+        assert_eq!(metrics.jitter(), Some(0));
+        metrics.report("1".to_owned(), "remote", true, 100, Default::default());
+        assert_eq!(get().len(), 1);
+        assert_eq!(get()[0].host, None);
+        let stats = HostStats {
+            cpu: 12.5,
+            ..Default::default()
+        };
+        metrics.on_system_stats(stats.clone());
+        metrics.report("1".to_owned(), "remote", true, 100, Default::default());
+        assert_eq!(get()[0].host, Some(stats));
+        drop(metrics);
+        assert!(get().is_empty());
+    }
+}
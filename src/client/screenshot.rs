@@ -0,0 +1,83 @@
+use crate::client::*;
+use hbb_common::{
+    allow_err, bail, config::READ_TIMEOUT, futures::StreamExt, message_proto::*,
+    protobuf::Message as _, rendezvous_proto::ConnType, timeout, tokio, ResultType,
+};
+
+/// Connect to the peer and fetch a PNG of the display, no video session is started.
+///
+/// The password falls back to the saved one of the peer if `password` is empty.
+pub async fn take_screenshot(
+    id: &str,
+    display: i32,
+    password: &str,
+    key: &str,
+    token: &str,
+) -> ResultType<Vec<u8>> {
+    let super::headless::Connection {
+        session,
+        mut stream,
+        mut receiver,
+        peer_info,
+        ..
+    } = super::headless::login(id, ConnType::DEFAULT_CONN, password, key, token, |lc| {
+        lc.screenshot_only = true;
+    })
+    .await?;
+    if display < 0 || display as usize >= peer_info.displays.len() {
+        bail!(
+            "Invalid display {}, displays len: {}",
+            display,
+            peer_info.displays.len()
+        );
+    }
+    let sid = hbb_common::rand::random::<u64>().to_string();
+    let mut msg_out = Message::new();
+    msg_out.set_screenshot_request(ScreenshotRequest {
+        display,
+        sid: sid.clone(),
+        ..Default::default()
+    });
+    allow_err!(stream.send(&msg_out).await);
+    loop {
+        tokio::select! {
+            res = timeout(READ_TIMEOUT, stream.next()) => match res {
+                Err(_) => {
+                    bail!("Timeout");
+                }
+                Ok(Some(Ok(bytes))) => {
+                    let msg_in = Message::parse_from_bytes(&bytes)?;
+                    match msg_in.union {
+                        Some(message::Union::ScreenshotResponse(res)) if res.sid == sid => {
+                            if !res.msg.is_empty() {
+                                bail!("{}", res.msg);
+                            }
+                            return Ok(res.data.to_vec());
+                        }
+                        Some(message::Union::TestDelay(t)) => {
+                            session.handle_test_delay(t, &mut stream).await;
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(Some(Err(err))) => {
+                    bail!("Connection closed: {}", err);
+                }
+                _ => {
+                    bail!("Reset by the peer");
+                }
+            },
+            d = receiver.recv() => {
+                match d {
+                    Some(Data::Close) => {
+                        bail!("{}", session.error.lock().unwrap());
+                    }
+                    Some(Data::Message(msg)) => {
+                        allow_err!(stream.send(&msg).await);
+                    }
+                    _ => {}
+                }
+            },
+        }
+    }
+}
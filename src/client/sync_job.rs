@@ -0,0 +1,318 @@
+// Keep a local folder and a folder of the peer in sync, on top of the file transfer.
+//
+// The jobs of a peer are saved with the log of their runs in `sync/<id>.json` in the config
+// directory. A run lists both folders recursively and compares the files by the size and the
+// modified time, which the transfer keeps. One-way sync copies the files which differ from the
+// source, two-way sync copies the newer one. No file is deleted, a file removed on one side is
+// copied back from the other side in two-way sync.
+
+use hbb_common::{bail, config::Config, get_time, message_proto::FileEntry, ResultType};
+use serde_derive::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf};
+
+const MAX_LOGS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Upload,
+    Download,
+    TwoWay,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncJob {
+    pub name: String,
+    pub local: String,
+    pub remote: String,
+    pub direction: Direction,
+    // minutes between the runs while the file transfer is open, 0 to run it manually only
+    #[serde(default)]
+    pub interval: u32,
+    #[serde(default)]
+    pub include_hidden: bool,
+    // milliseconds
+    #[serde(default)]
+    pub last_run: i64,
+    #[serde(default)]
+    pub logs: Vec<Log>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Log {
+    // milliseconds
+    pub time: i64,
+    pub text: String,
+}
+
+/// The files to copy in a run.
+#[derive(Debug, Default, Serialize)]
+pub struct Plan {
+    pub copies: Vec<Copy>,
+    // changed on both sides at the same time, left as they are
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Copy {
+    // relative to the folders, separated by `/`
+    pub name: String,
+    pub upload: bool,
+    pub size: u64,
+    pub from: String,
+    pub to: String,
+}
+
+fn path(id: &str) -> PathBuf {
+    Config::path("sync").join(format!("{id}.json"))
+}
+
+/// The jobs of the peer.
+pub fn list(id: &str) -> Vec<SyncJob> {
+    std::fs::read_to_string(path(id))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn get(id: &str, name: &str) -> Option<SyncJob> {
+    list(id).into_iter().find(|job| job.name == name)
+}
+
+fn save_all(id: &str, jobs: &[SyncJob]) -> ResultType<()> {
+    let path = path(id);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_json::to_string(jobs)?)?;
+    Ok(())
+}
+
+/// Adds the job, or replaces the one of the same name and keeps its log.
+pub fn save(id: &str, mut job: SyncJob) -> ResultType<()> {
+    job.name = job.name.trim().to_owned();
+    if job.name.is_empty() || job.local.is_empty() || job.remote.is_empty() {
+        bail!("the name and the folders of the sync job are required");
+    }
+    let mut jobs = list(id);
+    match jobs.iter_mut().find(|j| j.name == job.name) {
+        Some(old) => {
+            job.last_run = old.last_run;
+            job.logs = std::mem::take(&mut old.logs);
+            *old = job;
+        }
+        None => jobs.push(job),
+    }
+    save_all(id, &jobs)
+}
+
+pub fn remove(id: &str, name: &str) -> ResultType<()> {
+    let mut jobs = list(id);
+    jobs.retain(|job| job.name != name);
+    save_all(id, &jobs)
+}
+
+fn update(id: &str, name: &str, f: impl FnOnce(&mut SyncJob)) -> ResultType<()> {
+    let mut jobs = list(id);
+    let Some(job) = jobs.iter_mut().find(|job| job.name == name) else {
+        bail!("no such sync job: {name}");
+    };
+    f(job);
+    save_all(id, &jobs)
+}
+
+/// Appends to the log of the job, the oldest lines are dropped.
+pub fn log(id: &str, name: &str, text: String) -> ResultType<()> {
+    update(id, name, |job| {
+        job.logs.push(Log {
+            time: get_time(),
+            text,
+        });
+        let n = job.logs.len().saturating_sub(MAX_LOGS);
+        job.logs.drain(..n);
+    })
+}
+
+/// Notes the job started, for its schedule.
+pub fn mark_run(id: &str, name: &str) -> ResultType<()> {
+    update(id, name, |job| job.last_run = get_time())
+}
+
+/// The names of the scheduled jobs due to run.
+pub fn due(id: &str) -> Vec<String> {
+    let now = get_time();
+    list(id)
+        .into_iter()
+        .filter(|job| job.interval > 0 && now - job.last_run >= job.interval as i64 * 60_000)
+        .map(|job| job.name)
+        .collect()
+}
+
+fn join(dir: &str, name: &str, sep: &str) -> String {
+    format!(
+        "{}{}{}",
+        dir.trim_end_matches(sep),
+        sep,
+        name.replace('/', sep)
+    )
+}
+
+/// Compares the files listed recursively in the folders of the job.
+pub fn plan(
+    job: &SyncJob,
+    local: &[FileEntry],
+    remote: &[FileEntry],
+    local_sep: &str,
+    remote_sep: &str,
+) -> Plan {
+    let mut files: BTreeMap<String, (Option<&FileEntry>, Option<&FileEntry>)> = BTreeMap::new();
+    for entry in local {
+        files.entry(entry.name.replace('\\', "/")).or_default().0 = Some(entry);
+    }
+    for entry in remote {
+        files.entry(entry.name.replace('\\', "/")).or_default().1 = Some(entry);
+    }
+    let mut plan = Plan::default();
+    for (name, (local, remote)) in files {
+        let upload = match (local, remote) {
+            (Some(_), None) => {
+                if job.direction == Direction::Download {
+                    continue;
+                }
+                true
+            }
+            (None, Some(_)) => {
+                if job.direction == Direction::Upload {
+                    continue;
+                }
+                false
+            }
+            (Some(l), Some(r)) => {
+                if l.size == r.size && l.modified_time == r.modified_time {
+                    continue;
+                }
+                match job.direction {
+                    Direction::Upload => true,
+                    Direction::Download => false,
+                    Direction::TwoWay if l.modified_time == r.modified_time => {
+                        plan.conflicts.push(name);
+                        continue;
+                    }
+                    Direction::TwoWay => l.modified_time > r.modified_time,
+                }
+            }
+            (None, None) => continue,
+        };
+        let local_path = join(&job.local, &name, local_sep);
+        let remote_path = join(&job.remote, &name, remote_sep);
+        let (size, from, to) = if upload {
+            (local.map(|e| e.size), local_path, remote_path)
+        } else {
+            (remote.map(|e| e.size), remote_path, local_path)
+        };
+        plan.copies.push(Copy {
+            name,
+            upload,
+            size: size.unwrap_or_default(),
+            from,
+            to,
+        });
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, size: u64, modified_time: u64) -> FileEntry {
+        FileEntry {
+            name: name.to_owned(),
+            size,
+            modified_time,
+            ..Default::default()
+        }
+    }
+
+    fn job(direction: Direction) -> SyncJob {
+        SyncJob {
+            name: "test".to_owned(),
+            local: "/home/a/".to_owned(),
+            remote: "C:\\b".to_owned(),
+            direction,
+            interval: 0,
+            include_hidden: false,
+            last_run: 0,
+            logs: vec![],
+        }
+    }
+
+    fn copies(plan: &Plan) -> Vec<(&str, bool)> {
+        plan.copies
+            .iter()
+            .map(|c| (c.name.as_str(), c.upload))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan() {
+        let local = [
+            entry("same", 1, 10),
+            entry("local_only", 1, 10),
+            entry("local_newer", 2, 20),
+            entry("remote_newer", 1, 10),
+            entry("conflict", 1, 10),
+            entry("dir/sub", 1, 10),
+        ];
+        let remote = [
+            entry("same", 1, 10),
+            entry("remote_only", 1, 10),
+            entry("local_newer", 1, 10),
+            entry("remote_newer", 2, 20),
+            entry("conflict", 2, 10),
+            entry("dir\\sub", 1, 10),
+        ];
+        let two_way = plan(&job(Direction::TwoWay), &local, &remote, "/", "\\");
+        assert_eq!(
+            copies(&two_way),
+            [
+                ("local_newer", true),
+                ("local_only", true),
+                ("remote_newer", false),
+                ("remote_only", false),
+            ]
+        );
+        assert_eq!(two_way.conflicts, ["conflict"]);
+        assert_eq!(two_way.copies[0].from, "/home/a/local_newer");
+        assert_eq!(two_way.copies[0].to, "C:\\b\\local_newer");
+
+        let upload = plan(&job(Direction::Upload), &local, &remote, "/", "\\");
+        assert_eq!(
+            copies(&upload),
+            [
+                ("conflict", true),
+                ("local_newer", true),
+                ("local_only", true),
+                ("remote_newer", true),
+            ]
+        );
+        assert!(upload.conflicts.is_empty());
+
+        let download = plan(&job(Direction::Download), &local, &remote, "/", "\\");
+        assert_eq!(
+            copies(&download),
+            [
+                ("conflict", false),
+                ("local_newer", false),
+                ("remote_newer", false),
+                ("remote_only", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_join() {
+        assert_eq!(join("C:\\b\\", "dir/sub", "\\"), "C:\\b\\dir\\sub");
+        assert_eq!(join("/", "dir/sub", "/"), "/dir/sub");
+    }
+}
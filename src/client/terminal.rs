@@ -0,0 +1,263 @@
+// Open a terminal of the peer in the one of the command line, without any video: the keys typed are
+// sent to the shell of the peer and its output is written as it comes, so the programs run there
+// draw on this terminal.
+//
+// This terminal is in the raw mode while the session lasts. Its size is sent when the terminal is
+// opened and whenever it changes. The session ends once the shell of the peer exits, which gives
+// the exit code, or the connection is closed.
+
+use super::{headless, Data, Interface};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    terminal,
+};
+use hbb_common::{
+    allow_err, bail,
+    config::READ_TIMEOUT,
+    futures::StreamExt,
+    message_proto::*,
+    protobuf::Message as _,
+    rendezvous_proto::ConnType,
+    timeout,
+    tokio::{self, sync::mpsc},
+    ResultType,
+};
+use std::io::Write;
+
+// the only terminal of the session
+const TERMINAL_ID: i32 = 1;
+
+enum Input {
+    Data(Vec<u8>),
+    // rows and cols
+    Resize(u16, u16),
+}
+
+// leaves the raw mode however the session ends
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> ResultType<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        terminal::disable_raw_mode().ok();
+    }
+}
+
+/// Runs the shell of the peer in this terminal until it exits, returns its exit code.
+///
+/// The password falls back to the saved one of the peer if `password` is empty.
+pub async fn run(id: &str, password: &str, key: &str, token: &str) -> ResultType<i32> {
+    let headless::Connection {
+        session,
+        mut stream,
+        mut receiver,
+        ..
+    } = headless::login(id, ConnType::TERMINAL, password, key, token, |_| {}).await?;
+    let (cols, rows) = terminal::size().unwrap_or((80, 24));
+    let mut action = TerminalAction::new();
+    action.set_open(OpenTerminal {
+        id: TERMINAL_ID,
+        rows: rows as _,
+        cols: cols as _,
+        ..Default::default()
+    });
+    stream.send(&action_msg(action)).await?;
+    let _raw_mode = RawMode::enable()?;
+    let (tx, mut rx) = mpsc::unbounded_channel::<Input>();
+    std::thread::spawn(move || read_input(tx));
+    let mut stdout = std::io::stdout();
+    loop {
+        tokio::select! {
+            res = timeout(READ_TIMEOUT, stream.next()) => match res {
+                Err(_) => {
+                    bail!("Timeout");
+                }
+                Ok(Some(Ok(bytes))) => {
+                    let msg_in = Message::parse_from_bytes(&bytes)?;
+                    match msg_in.union {
+                        Some(message::Union::TerminalResponse(r)) => match r.union {
+                            Some(terminal_response::Union::Opened(opened)) => {
+                                if !opened.error.is_empty() {
+                                    bail!("{}", opened.error);
+                                }
+                            }
+                            Some(terminal_response::Union::Data(data)) if data.id == TERMINAL_ID => {
+                                stdout.write_all(&data.data)?;
+                                stdout.flush()?;
+                            }
+                            Some(terminal_response::Union::Closed(closed)) if closed.id == TERMINAL_ID => {
+                                return Ok(closed.exit_code);
+                            }
+                            _ => {}
+                        },
+                        Some(message::Union::Misc(misc)) => {
+                            if let Some(misc::Union::CloseReason(reason)) = misc.union {
+                                bail!("{}", reason);
+                            }
+                        }
+                        Some(message::Union::TestDelay(t)) => {
+                            session.handle_test_delay(t, &mut stream).await;
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(Some(Err(err))) => {
+                    bail!("Connection closed: {}", err);
+                }
+                _ => {
+                    bail!("Reset by the peer");
+                }
+            },
+            Some(input) = rx.recv() => {
+                let mut action = TerminalAction::new();
+                match input {
+                    Input::Data(data) => action.set_data(TerminalData {
+                        id: TERMINAL_ID,
+                        data: data.into(),
+                        ..Default::default()
+                    }),
+                    Input::Resize(rows, cols) => action.set_resize(ResizeTerminal {
+                        id: TERMINAL_ID,
+                        rows: rows as _,
+                        cols: cols as _,
+                        ..Default::default()
+                    }),
+                }
+                stream.send(&action_msg(action)).await?;
+            }
+            d = receiver.recv() => {
+                match d {
+                    Some(Data::Close) => {
+                        bail!("{}", session.error.lock().unwrap());
+                    }
+                    Some(Data::Message(msg)) => {
+                        allow_err!(stream.send(&msg).await);
+                    }
+                    _ => {}
+                }
+            },
+        }
+    }
+}
+
+fn action_msg(action: TerminalAction) -> Message {
+    let mut msg = Message::new();
+    msg.set_terminal_action(action);
+    msg
+}
+
+// blocks on the events of this terminal until the session ends
+fn read_input(tx: mpsc::UnboundedSender<Input>) {
+    loop {
+        let input = match event::read() {
+            Ok(Event::Key(key)) => match key_bytes(&key) {
+                Some(bytes) => Input::Data(bytes),
+                None => continue,
+            },
+            Ok(Event::Paste(text)) => Input::Data(text.into_bytes()),
+            Ok(Event::Resize(cols, rows)) => Input::Resize(rows, cols),
+            Ok(_) => continue,
+            Err(_) => break,
+        };
+        if tx.send(input).is_err() {
+            break;
+        }
+    }
+}
+
+// the bytes an xterm sends for the key
+fn key_bytes(key: &KeyEvent) -> Option<Vec<u8>> {
+    if key.kind == KeyEventKind::Release {
+        return None;
+    }
+    let seq: &[u8] = match key.code {
+        KeyCode::Char(c) => {
+            let mut bytes = if key.modifiers.contains(KeyModifiers::CONTROL) && c.is_ascii() {
+                match c.to_ascii_lowercase() {
+                    c @ 'a'..='z' => vec![c as u8 - b'a' + 1],
+                    ' ' | '@' | '2' => vec![0],
+                    '[' | '3' => vec![0x1b],
+                    '\\' | '4' => vec![0x1c],
+                    ']' | '5' => vec![0x1d],
+                    '^' | '6' => vec![0x1e],
+                    '_' | '/' | '7' => vec![0x1f],
+                    _ => c.to_string().into_bytes(),
+                }
+            } else {
+                c.to_string().into_bytes()
+            };
+            if key.modifiers.contains(KeyModifiers::ALT) {
+                bytes.insert(0, 0x1b);
+            }
+            return Some(bytes);
+        }
+        KeyCode::Enter => b"\r",
+        KeyCode::Backspace => b"\x7f",
+        KeyCode::Tab => b"\t",
+        KeyCode::BackTab => b"\x1b[Z",
+        KeyCode::Esc => b"\x1b",
+        KeyCode::Up => b"\x1b[A",
+        KeyCode::Down => b"\x1b[B",
+        KeyCode::Right => b"\x1b[C",
+        KeyCode::Left => b"\x1b[D",
+        KeyCode::Home => b"\x1b[H",
+        KeyCode::End => b"\x1b[F",
+        KeyCode::PageUp => b"\x1b[5~",
+        KeyCode::PageDown => b"\x1b[6~",
+        KeyCode::Insert => b"\x1b[2~",
+        KeyCode::Delete => b"\x1b[3~",
+        KeyCode::F(n) => match n {
+            1 => b"\x1bOP",
+            2 => b"\x1bOQ",
+            3 => b"\x1bOR",
+            4 => b"\x1bOS",
+            5 => b"\x1b[15~",
+            6 => b"\x1b[17~",
+            7 => b"\x1b[18~",
+            8 => b"\x1b[19~",
+            9 => b"\x1b[20~",
+            10 => b"\x1b[21~",
+            11 => b"\x1b[23~",
+            12 => b"\x1b[24~",
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(seq.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_bytes() {
+        let key = |code, modifiers| key_bytes(&KeyEvent::new(code, modifiers));
+        assert_eq!(
+            key(KeyCode::Char('é'), KeyModifiers::NONE),
+            Some("é".into())
+        );
+        assert_eq!(
+            key(KeyCode::Char('c'), KeyModifiers::CONTROL),
+            Some(vec![3])
+        );
+        assert_eq!(
+            key(KeyCode::Char('b'), KeyModifiers::ALT),
+            Some(b"\x1bb".to_vec())
+        );
+        assert_eq!(
+            key(KeyCode::Up, KeyModifiers::NONE),
+            Some(b"\x1b[A".to_vec())
+        );
+        assert_eq!(key(KeyCode::F(13), KeyModifiers::NONE), None);
+        let mut release = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        release.kind = KeyEventKind::Release;
+        assert_eq!(key_bytes(&release), None);
+    }
+}
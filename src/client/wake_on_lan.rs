@@ -0,0 +1,116 @@
+// Wake a peer offline through another one online on its lan, which sends the magic packets to the
+// macs of the peer learned from the sessions with it: the broadcasts do not cross the routers, so
+// the peers out of the lan can not wake it by themselves.
+//
+// The peers told as on the same lan are the ones seen on a network of the peer, the private
+// networks of the other sites may be the same, so they are only suggested.
+
+use crate::client::*;
+use hbb_common::{
+    allow_err, bail,
+    config::{PeerConfig, READ_TIMEOUT},
+    futures::StreamExt,
+    message_proto::*,
+    protobuf::Message as _,
+    rendezvous_proto::ConnType,
+    timeout, tokio, ResultType,
+};
+
+/// Connect to `relay_id`, no video session is started, and have it wake `id`.
+///
+/// The password falls back to the saved one of the relay if `password` is empty.
+pub async fn wake(
+    id: &str,
+    relay_id: &str,
+    password: &str,
+    key: &str,
+    token: &str,
+) -> ResultType<()> {
+    let macs = PeerConfig::load(id).info.macs;
+    if macs.is_empty() {
+        bail!("No MAC address of {} known, connect to it once first", id);
+    }
+    let super::headless::Connection {
+        session,
+        mut stream,
+        mut receiver,
+        ..
+    } = super::headless::login(
+        relay_id,
+        ConnType::DEFAULT_CONN,
+        password,
+        key,
+        token,
+        |lc| {
+            lc.screenshot_only = true;
+        },
+    )
+    .await?;
+    let mut msg_out = Message::new();
+    msg_out.set_wake_on_lan(WakeOnLan {
+        macs,
+        ..Default::default()
+    });
+    allow_err!(stream.send(&msg_out).await);
+    loop {
+        tokio::select! {
+            res = timeout(READ_TIMEOUT, stream.next()) => match res {
+                Err(_) => {
+                    bail!("Timeout");
+                }
+                Ok(Some(Ok(bytes))) => {
+                    let msg_in = Message::parse_from_bytes(&bytes)?;
+                    match msg_in.union {
+                        Some(message::Union::WakeOnLanResponse(res)) => {
+                            if !res.error.is_empty() {
+                                bail!("{}", res.error);
+                            }
+                            return Ok(());
+                        }
+                        Some(message::Union::TestDelay(t)) => {
+                            session.handle_test_delay(t, &mut stream).await;
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(Some(Err(err))) => {
+                    bail!("Connection closed: {}", err);
+                }
+                _ => {
+                    bail!("Reset by the peer");
+                }
+            },
+            d = receiver.recv() => {
+                match d {
+                    Some(Data::Close) => {
+                        bail!("{}", session.error.lock().unwrap());
+                    }
+                    Some(Data::Message(msg)) => {
+                        allow_err!(stream.send(&msg).await);
+                    }
+                    _ => {}
+                }
+            },
+        }
+    }
+}
+
+/// The peers seen on a network of `id`, which may wake it.
+pub fn relays(id: &str) -> Vec<String> {
+    let networks = PeerConfig::load(id).info.lan_networks;
+    if networks.is_empty() {
+        return vec![];
+    }
+    PeerConfig::peers(None)
+        .into_iter()
+        .filter(|(other, _, config)| {
+            other != id
+                && config
+                    .info
+                    .lan_networks
+                    .iter()
+                    .any(|network| networks.contains(network))
+        })
+        .map(|(other, ..)| other)
+        .collect()
+}
@@ -0,0 +1,871 @@
+// Serve the files of the peer over WebDAV on a local port, so the tools speaking it (file managers,
+// IDEs, rclone, davfs2) work on them through a file transfer connection.
+//
+// The listener is bound to the loopback only, and takes the requests to `127.0.0.1:<port>` or
+// `localhost:<port>` with the password of the session only, a random one shown once it is served,
+// so that neither the other users of this side nor the web pages rebinding a name of theirs to the
+// loopback get at the files. The requests of all the clients are handled one at a time by the task
+// owning the connection: a file read is downloaded into a temporary file before it is sent, a file
+// written is received into one before it is uploaded. The paths of the URLs are the paths of the
+// peer, `/C:/Users/` for `C:\Users` of Windows, whose `/` lists the drives. Locks, copies and
+// setting the properties are not supported.
+
+use super::{file_copy, headless};
+use hbb_common::{
+    bail, chrono,
+    config::{keys, Config, LocalConfig, READ_TIMEOUT},
+    fs,
+    futures::StreamExt,
+    log,
+    message_proto::*,
+    protobuf::Message as _,
+    rendezvous_proto::ConnType,
+    tcp, timeout,
+    tokio::{
+        self,
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+        net::TcpStream,
+        sync::{mpsc, oneshot},
+    },
+    ResultType,
+};
+use std::{
+    fmt::Write as _,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+// in milliseconds, the peer does not answer for a folder it can not read
+const LIST_TIMEOUT: u64 = 5_000;
+const ALLOW: &str = "OPTIONS, PROPFIND, GET, HEAD, PUT, DELETE, MKCOL, MOVE";
+const USER: &str = "webdav";
+
+enum Op {
+    List(String),
+    Download(String, PathBuf),
+    Upload(PathBuf, String),
+    CreateDir(String),
+    // true for a folder, removed with everything in it
+    Remove(String, bool),
+    Rename(String, String),
+}
+
+enum Reply {
+    Dir(Vec<FileEntry>),
+    Done,
+}
+
+type Request = (Op, oneshot::Sender<ResultType<Reply>>);
+
+/// Serves the files of the peer on `127.0.0.1:port` until the connection is closed.
+pub async fn serve(id: &str, port: u16, password: &str, key: &str, token: &str) -> ResultType<()> {
+    let conn = headless::login(id, ConnType::FILE_TRANSFER, password, key, token, |_| {}).await?;
+    let windows = conn.peer_info.platform == "Windows";
+    let listener = tcp::new_listener(format!("127.0.0.1:{}", port), false).await?;
+    let auth = Auth::new(port, &Config::get_auto_password(24));
+    log::info!("serving the files of {} on http://127.0.0.1:{}", id, port);
+    println!(
+        "http://127.0.0.1:{}/, user: {}, password: {}",
+        port, USER, auth.password
+    );
+    let (tx, mut rx) = mpsc::unbounded_channel::<Request>();
+    let mut peer = Peer {
+        conn,
+        windows,
+        last_id: 0,
+    };
+    loop {
+        tokio::select! {
+            res = listener.accept() => {
+                let (socket, addr) = res?;
+                let tx = tx.clone();
+                let auth = auth.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = handle_client(socket, tx, &auth, windows).await {
+                        log::debug!("WebDAV client {}: {}", addr, err);
+                    }
+                });
+            }
+            Some((op, reply)) = rx.recv() => {
+                reply.send(peer.run(op).await).ok();
+            }
+            res = peer.conn.stream.next() => {
+                let msg_in = match res {
+                    Some(Ok(bytes)) => Message::parse_from_bytes(&bytes)?,
+                    Some(Err(err)) => bail!("Connection closed: {}", err),
+                    None => bail!("Reset by the peer"),
+                };
+                if let Some(message::Union::TestDelay(t)) = msg_in.union {
+                    peer.conn
+                        .session
+                        .handle_test_delay(t, &mut peer.conn.stream)
+                        .await;
+                }
+            }
+        }
+    }
+}
+
+struct Peer {
+    conn: headless::Connection,
+    windows: bool,
+    last_id: i32,
+}
+
+impl Peer {
+    async fn run(&mut self, op: Op) -> ResultType<Reply> {
+        self.last_id += 1;
+        let id = self.last_id;
+        let mut action = FileAction::new();
+        match op {
+            Op::List(path) => {
+                action.set_read_dir(ReadDir {
+                    path: path.clone(),
+                    include_hidden: true,
+                    ..Default::default()
+                });
+                self.send(action).await?;
+                match timeout(LIST_TIMEOUT, self.wait_dir(&path)).await {
+                    Ok(res) => res.map(Reply::Dir),
+                    Err(_) => bail!("Failed to read {}", path),
+                }
+            }
+            Op::Download(from, to) => {
+                let to = fs::get_string(&to);
+                let policy = FileConflictPolicy::OverwriteExisting;
+                file_copy::download_on(&mut self.conn, id, &from, &to, policy).await?;
+                Ok(Reply::Done)
+            }
+            Op::Upload(from, to) => {
+                let from = fs::get_string(&from);
+                let policy = FileConflictPolicy::OverwriteExisting;
+                file_copy::upload_on(&mut self.conn, id, &from, &to, policy).await?;
+                Ok(Reply::Done)
+            }
+            Op::CreateDir(path) => {
+                action.set_create(FileDirCreate {
+                    id,
+                    path,
+                    ..Default::default()
+                });
+                self.send(action).await?;
+                self.wait_done(id).await
+            }
+            Op::Remove(path, is_dir) => self.remove(id, path, is_dir).await,
+            Op::Rename(path, new_path) => {
+                // the peer joins the new name to the folder of the path, a full path replaces it
+                action.set_rename(FileRename {
+                    id,
+                    path,
+                    new_name: new_path,
+                    ..Default::default()
+                });
+                self.send(action).await?;
+                self.wait_done(id).await
+            }
+        }
+    }
+
+    // the files first, then the folders left empty
+    async fn remove(&mut self, id: i32, path: String, is_dir: bool) -> ResultType<Reply> {
+        let trash = !LocalConfig::get_bool_option(keys::OPTION_ENABLE_DELETE_PERMANENTLY);
+        let mut action = FileAction::new();
+        if !is_dir {
+            action.set_remove_file(FileRemoveFile {
+                id,
+                path,
+                trash,
+                ..Default::default()
+            });
+            self.send(action).await?;
+            return self.wait_done(id).await;
+        }
+        action.set_all_files(ReadAllFiles {
+            id,
+            path: path.clone(),
+            include_hidden: true,
+            ..Default::default()
+        });
+        self.send(action).await?;
+        let files = loop {
+            match self.next_response().await?.union {
+                Some(file_response::Union::Dir(fd)) if fd.id == id => break fd.entries,
+                Some(file_response::Union::Error(e)) if e.id == id => bail!("{}", e.error),
+                _ => {}
+            }
+        };
+        for (i, file) in files.into_iter().enumerate() {
+            let mut action = FileAction::new();
+            action.set_remove_file(FileRemoveFile {
+                id,
+                path: join(&path, &file.name, self.windows),
+                file_num: i as _,
+                trash,
+                ..Default::default()
+            });
+            self.send(action).await?;
+            self.wait_done(id).await?;
+        }
+        let mut action = FileAction::new();
+        action.set_remove_dir(FileRemoveDir {
+            id,
+            path,
+            recursive: true,
+            trash,
+            ..Default::default()
+        });
+        self.send(action).await?;
+        self.wait_done(id).await
+    }
+
+    async fn send(&mut self, action: FileAction) -> ResultType<()> {
+        let mut msg = Message::new();
+        msg.set_file_action(action);
+        self.conn.stream.send(&msg).await
+    }
+
+    // the next file response, the test delays are answered meanwhile
+    async fn next_response(&mut self) -> ResultType<FileResponse> {
+        loop {
+            let msg_in = match timeout(READ_TIMEOUT, self.conn.stream.next()).await {
+                Err(_) => bail!("Timeout"),
+                Ok(Some(Ok(bytes))) => Message::parse_from_bytes(&bytes)?,
+                Ok(Some(Err(err))) => bail!("Connection closed: {}", err),
+                Ok(None) => bail!("Reset by the peer"),
+            };
+            match msg_in.union {
+                Some(message::Union::FileResponse(fr)) => return Ok(fr),
+                Some(message::Union::TestDelay(t)) => {
+                    self.conn
+                        .session
+                        .handle_test_delay(t, &mut self.conn.stream)
+                        .await;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    async fn wait_done(&mut self, id: i32) -> ResultType<Reply> {
+        loop {
+            match self.next_response().await?.union {
+                Some(file_response::Union::Done(d)) if d.id == id => return Ok(Reply::Done),
+                Some(file_response::Union::Error(e)) if e.id == id => bail!("{}", e.error),
+                _ => {}
+            }
+        }
+    }
+
+    // a folder read has no id, it is told by its path
+    async fn wait_dir(&mut self, path: &str) -> ResultType<Vec<FileEntry>> {
+        let trim = |p: &str| p.trim_end_matches(|c| c == '/' || c == '\\').to_owned();
+        loop {
+            if let Some(file_response::Union::Dir(fd)) = self.next_response().await?.union {
+                if (self.windows && trim(&fd.path).eq_ignore_ascii_case(&trim(path)))
+                    || trim(&fd.path) == trim(path)
+                {
+                    return Ok(fd.entries);
+                }
+            }
+        }
+    }
+}
+
+struct HttpRequest {
+    method: String,
+    // decoded
+    path: String,
+    // the names in lower case
+    headers: Vec<(String, String)>,
+}
+
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Clone)]
+struct Auth {
+    hosts: [String; 2],
+    password: String,
+    // of the header of the basic authentication
+    credentials: String,
+}
+
+impl Auth {
+    fn new(port: u16, password: &str) -> Self {
+        Self {
+            hosts: [format!("127.0.0.1:{}", port), format!("localhost:{}", port)],
+            password: password.to_owned(),
+            credentials: format!(
+                "Basic {}",
+                crate::encode64(format!("{}:{}", USER, password))
+            ),
+        }
+    }
+
+    // the status the request is refused with
+    fn check(&self, req: &HttpRequest) -> Option<u16> {
+        let host = req.header("host").unwrap_or_default();
+        if !self.hosts.iter().any(|h| h.eq_ignore_ascii_case(host)) {
+            return Some(403);
+        }
+        if req.header("authorization") != Some(self.credentials.as_str()) {
+            return Some(401);
+        }
+        None
+    }
+}
+
+async fn handle_client(
+    socket: TcpStream,
+    tx: mpsc::UnboundedSender<Request>,
+    auth: &Auth,
+    windows: bool,
+) -> ResultType<()> {
+    let mut reader = BufReader::new(socket);
+    while let Some(req) = read_head(&mut reader).await? {
+        if let Some(status) = auth.check(&req) {
+            // the body is not read, so the connection is closed
+            let mut headers = vec![("Connection", "close".to_owned())];
+            if status == 401 {
+                headers.push(("WWW-Authenticate", "Basic realm=\"WebDAV\"".to_owned()));
+            }
+            return respond(reader.get_mut(), status, &headers, b"").await;
+        }
+        let close = req
+            .header("connection")
+            .map(|c| c.eq_ignore_ascii_case("close"))
+            .unwrap_or(false);
+        handle_request(&mut reader, &req, &tx, windows).await?;
+        if close {
+            break;
+        }
+    }
+    Ok(())
+}
+
+async fn handle_request(
+    reader: &mut BufReader<TcpStream>,
+    req: &HttpRequest,
+    tx: &mpsc::UnboundedSender<Request>,
+    windows: bool,
+) -> ResultType<()> {
+    let path = peer_path(&req.path, windows);
+    if req.header("expect") == Some("100-continue") {
+        reader
+            .get_mut()
+            .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+            .await?;
+    }
+    if req.method == "PUT" {
+        return put(reader, req, tx, &path, windows).await;
+    }
+    // the bodies of the rest are not used
+    read_body(reader, req, &mut tokio::io::sink()).await?;
+    let socket = reader.get_mut();
+    match req.method.as_str() {
+        "OPTIONS" => {
+            let headers = [("DAV", "1".to_owned()), ("Allow", ALLOW.to_owned())];
+            respond(socket, 200, &headers, b"").await
+        }
+        "PROPFIND" => {
+            let Some(entry) = stat(tx, &path, windows).await else {
+                return respond(socket, 404, &[], b"").await;
+            };
+            let mut body = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n".to_owned();
+            body.push_str("<D:multistatus xmlns:D=\"DAV:\">\n");
+            push_response(&mut body, &path, &entry, windows);
+            if is_dir(&entry) && req.header("depth") != Some("0") {
+                if let Ok(Reply::Dir(entries)) = call(tx, Op::List(path.clone())).await {
+                    for entry in entries {
+                        push_response(
+                            &mut body,
+                            &join(&path, &entry.name, windows),
+                            &entry,
+                            windows,
+                        );
+                    }
+                }
+            }
+            body.push_str("</D:multistatus>\n");
+            let headers = [("Content-Type", "application/xml; charset=utf-8".to_owned())];
+            respond(socket, 207, &headers, body.as_bytes()).await
+        }
+        "GET" | "HEAD" => {
+            let entry = match stat(tx, &path, windows).await {
+                Some(entry) if !is_dir(&entry) => entry,
+                Some(_) => return respond(socket, 405, &[], b"").await,
+                None => return respond(socket, 404, &[], b"").await,
+            };
+            let mut headers = vec![("Content-Type", "application/octet-stream".to_owned())];
+            if entry.modified_time > 0 {
+                headers.push(("Last-Modified", http_date(entry.modified_time)));
+            }
+            if req.method == "HEAD" {
+                return write_head(socket, 200, &headers, entry.size).await;
+            }
+            let temp = temp_path();
+            let res = match call(tx, Op::Download(path, temp.clone())).await {
+                Ok(_) => send_file(socket, &headers, &temp).await,
+                Err(err) => respond_error(socket, err).await,
+            };
+            std::fs::remove_file(&temp).ok();
+            res
+        }
+        "DELETE" => {
+            if path == "/" {
+                return respond(socket, 403, &[], b"").await;
+            }
+            let Some(entry) = stat(tx, &path, windows).await else {
+                return respond(socket, 404, &[], b"").await;
+            };
+            match call(tx, Op::Remove(path, is_dir(&entry))).await {
+                Ok(_) => respond(socket, 204, &[], b"").await,
+                Err(err) => respond_error(socket, err).await,
+            }
+        }
+        "MKCOL" => {
+            if stat(tx, &path, windows).await.is_some() {
+                return respond(socket, 405, &[], b"").await;
+            }
+            match call(tx, Op::CreateDir(path)).await {
+                Ok(_) => respond(socket, 201, &[], b"").await,
+                Err(err) => respond_error(socket, err).await,
+            }
+        }
+        "MOVE" => {
+            let Some(destination) = req.header("destination") else {
+                return respond(socket, 400, &[], b"").await;
+            };
+            let destination = peer_path(&url_path(destination), windows);
+            if stat(tx, &path, windows).await.is_none() {
+                return respond(socket, 404, &[], b"").await;
+            }
+            let existing = stat(tx, &destination, windows).await;
+            if let Some(entry) = &existing {
+                if req.header("overwrite") == Some("F") {
+                    return respond(socket, 412, &[], b"").await;
+                }
+                let op = Op::Remove(destination.clone(), is_dir(entry));
+                if let Err(err) = call(tx, op).await {
+                    return respond_error(socket, err).await;
+                }
+            }
+            match call(tx, Op::Rename(path, destination)).await {
+                Ok(_) if existing.is_some() => respond(socket, 204, &[], b"").await,
+                Ok(_) => respond(socket, 201, &[], b"").await,
+                Err(err) => respond_error(socket, err).await,
+            }
+        }
+        _ => respond(socket, 501, &[], b"").await,
+    }
+}
+
+async fn put(
+    reader: &mut BufReader<TcpStream>,
+    req: &HttpRequest,
+    tx: &mpsc::UnboundedSender<Request>,
+    path: &str,
+    windows: bool,
+) -> ResultType<()> {
+    let existing = stat(tx, path, windows).await;
+    if existing.as_ref().map(is_dir).unwrap_or(false) {
+        read_body(reader, req, &mut tokio::io::sink()).await?;
+        return respond(reader.get_mut(), 405, &[], b"").await;
+    }
+    let temp = temp_path();
+    let mut file = tokio::fs::File::create(&temp).await?;
+    let res = match read_body(reader, req, &mut file).await {
+        Ok(_) => file.flush().await,
+        Err(err) => {
+            std::fs::remove_file(&temp).ok();
+            return Err(err);
+        }
+    };
+    drop(file);
+    let res = match res {
+        Ok(_) => call(tx, Op::Upload(temp.clone(), path.to_owned())).await,
+        Err(err) => Err(err.into()),
+    };
+    std::fs::remove_file(&temp).ok();
+    let socket = reader.get_mut();
+    match res {
+        Ok(_) if existing.is_some() => respond(socket, 204, &[], b"").await,
+        Ok(_) => respond(socket, 201, &[], b"").await,
+        Err(err) => respond_error(socket, err).await,
+    }
+}
+
+async fn call(tx: &mpsc::UnboundedSender<Request>, op: Op) -> ResultType<Reply> {
+    let (reply, rx) = oneshot::channel();
+    if tx.send((op, reply)).is_err() {
+        bail!("Connection closed");
+    }
+    match rx.await {
+        Ok(res) => res,
+        Err(_) => bail!("Connection closed"),
+    }
+}
+
+// the entry of the path in the folder it is in
+async fn stat(tx: &mpsc::UnboundedSender<Request>, path: &str, windows: bool) -> Option<FileEntry> {
+    if path == "/" {
+        return Some(FileEntry {
+            entry_type: FileType::Dir.into(),
+            ..Default::default()
+        });
+    }
+    let sep = if windows { '\\' } else { '/' };
+    let trimmed = path.trim_end_matches(sep);
+    let (parent, name) = match trimmed.rfind(sep) {
+        Some(i) => (&trimmed[..=i], &trimmed[i + 1..]),
+        // a drive
+        None => ("/", trimmed),
+    };
+    match call(tx, Op::List(parent.to_owned())).await {
+        Ok(Reply::Dir(entries)) => entries.into_iter().find(|e| {
+            if windows {
+                e.name.eq_ignore_ascii_case(name)
+            } else {
+                e.name == name
+            }
+        }),
+        _ => None,
+    }
+}
+
+fn is_dir(entry: &FileEntry) -> bool {
+    matches!(
+        entry.entry_type.enum_value(),
+        Ok(FileType::Dir) | Ok(FileType::DirLink) | Ok(FileType::DirDrive)
+    )
+}
+
+// the path of the peer of the path of a URL
+fn peer_path(url_path: &str, windows: bool) -> String {
+    let path = url_path.trim_end_matches('/');
+    if path.is_empty() {
+        return "/".to_owned();
+    }
+    if !windows {
+        return path.to_owned();
+    }
+    let path = path.trim_start_matches('/').replace('/', "\\");
+    if path.ends_with(':') {
+        path + "\\"
+    } else {
+        path
+    }
+}
+
+fn join(path: &str, name: &str, windows: bool) -> String {
+    let sep = if windows { '\\' } else { '/' };
+    if windows && path == "/" {
+        format!("{}{}", name, sep)
+    } else if path.ends_with(sep) {
+        format!("{}{}", path, name)
+    } else {
+        format!("{}{}{}", path, sep, name)
+    }
+}
+
+fn push_response(body: &mut String, path: &str, entry: &FileEntry, windows: bool) {
+    let mut href = if windows && path != "/" {
+        format!("/{}", path.replace('\\', "/"))
+    } else {
+        path.to_owned()
+    };
+    if is_dir(entry) && !href.ends_with('/') {
+        href.push('/');
+    }
+    write!(
+        body,
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop>",
+        encode(&href)
+    )
+    .ok();
+    if !entry.name.is_empty() {
+        write!(
+            body,
+            "<D:displayname>{}</D:displayname>",
+            xml_escape(&entry.name)
+        )
+        .ok();
+    }
+    if is_dir(entry) {
+        body.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+    } else {
+        write!(
+            body,
+            "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength>",
+            entry.size
+        )
+        .ok();
+    }
+    if entry.modified_time > 0 {
+        write!(
+            body,
+            "<D:getlastmodified>{}</D:getlastmodified>",
+            http_date(entry.modified_time)
+        )
+        .ok();
+    }
+    body.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+}
+
+async fn read_head(reader: &mut BufReader<TcpStream>) -> ResultType<Option<HttpRequest>> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut size = 0;
+    loop {
+        let mut line = String::new();
+        let n = reader.read_line(&mut line).await?;
+        if n == 0 {
+            if lines.is_empty() {
+                return Ok(None);
+            }
+            bail!("Unexpected end of the request");
+        }
+        size += n;
+        if size > MAX_HEAD_SIZE {
+            bail!("The request is too large");
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            // the empty lines before the request
+            if lines.is_empty() {
+                continue;
+            }
+            break;
+        }
+        lines.push(line.to_owned());
+    }
+    let mut parts = lines[0].split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        bail!("Bad request: {}", lines[0]);
+    };
+    let headers = lines[1..]
+        .iter()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_owned()))
+        .collect();
+    Ok(Some(HttpRequest {
+        method: method.to_owned(),
+        path: url_path(target),
+        headers,
+    }))
+}
+
+async fn read_body<W: AsyncWrite + Unpin>(
+    reader: &mut BufReader<TcpStream>,
+    req: &HttpRequest,
+    sink: &mut W,
+) -> ResultType<()> {
+    let chunked = req
+        .header("transfer-encoding")
+        .map(|t| t.to_lowercase().contains("chunked"))
+        .unwrap_or(false);
+    if !chunked {
+        let len = req
+            .header("content-length")
+            .and_then(|l| l.parse::<u64>().ok())
+            .unwrap_or(0);
+        if tokio::io::copy(&mut (&mut *reader).take(len), sink).await? < len {
+            bail!("Unexpected end of the body");
+        }
+        return Ok(());
+    }
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            bail!("Unexpected end of the body");
+        }
+        let size = line.trim().split(';').next().unwrap_or_default().trim();
+        let size = u64::from_str_radix(size, 16)?;
+        if size == 0 {
+            // the trailers
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+                    return Ok(());
+                }
+            }
+        }
+        if tokio::io::copy(&mut (&mut *reader).take(size), sink).await? < size {
+            bail!("Unexpected end of the body");
+        }
+        // the line end of the chunk
+        line.clear();
+        reader.read_line(&mut line).await?;
+    }
+}
+
+async fn write_head(
+    socket: &mut TcpStream,
+    status: u16,
+    headers: &[(&str, String)],
+    len: u64,
+) -> ResultType<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\n",
+        status,
+        reason(status),
+        len
+    );
+    for (k, v) in headers {
+        write!(head, "{}: {}\r\n", k, v).ok();
+    }
+    head.push_str("\r\n");
+    socket.write_all(head.as_bytes()).await?;
+    Ok(())
+}
+
+async fn respond(
+    socket: &mut TcpStream,
+    status: u16,
+    headers: &[(&str, String)],
+    body: &[u8],
+) -> ResultType<()> {
+    write_head(socket, status, headers, body.len() as _).await?;
+    socket.write_all(body).await?;
+    Ok(())
+}
+
+async fn respond_error(socket: &mut TcpStream, err: hbb_common::anyhow::Error) -> ResultType<()> {
+    log::debug!("WebDAV request failed: {}", err);
+    let headers = [("Content-Type", "text/plain; charset=utf-8".to_owned())];
+    respond(socket, 500, &headers, err.to_string().as_bytes()).await
+}
+
+async fn send_file(
+    socket: &mut TcpStream,
+    headers: &[(&str, String)],
+    path: &PathBuf,
+) -> ResultType<()> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let len = file.metadata().await?.len();
+    write_head(socket, 200, headers, len).await?;
+    tokio::io::copy(&mut file, socket).await?;
+    Ok(())
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        207 => "Multi-Status",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        412 => "Precondition Failed",
+        501 => "Not Implemented",
+        _ => "Internal Server Error",
+    }
+}
+
+fn temp_path() -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "rustdesk_webdav_{}_{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+// the decoded path of the URL, which may be absolute
+fn url_path(target: &str) -> String {
+    let target = match target.find("://") {
+        Some(i) => {
+            let rest = &target[i + 3..];
+            rest.find('/').map(|j| &rest[j..]).unwrap_or("/")
+        }
+        None => target,
+    };
+    let target = target.split(|c| c == '?' || c == '#').next().unwrap_or("/");
+    let bytes = target.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn encode(path: &str) -> String {
+    let mut encoded = String::new();
+    for b in path.bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            write!(encoded, "%{:02X}", b).ok();
+        }
+    }
+    encoded
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn http_date(secs: u64) -> String {
+    use chrono::TimeZone;
+    chrono::Utc
+        .timestamp_opt(secs as _, 0)
+        .single()
+        .map(|t| t.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth() {
+        let auth = Auth::new(8080, "secret");
+        let req = |host: &str, authorization: &str| HttpRequest {
+            method: "GET".to_owned(),
+            path: "/".to_owned(),
+            headers: vec![
+                ("host".to_owned(), host.to_owned()),
+                ("authorization".to_owned(), authorization.to_owned()),
+            ],
+        };
+        let credentials = format!("Basic {}", crate::encode64("webdav:secret"));
+        assert_eq!(auth.check(&req("127.0.0.1:8080", &credentials)), None);
+        assert_eq!(auth.check(&req("LOCALHOST:8080", &credentials)), None);
+        assert_eq!(
+            auth.check(&req("evil.example.com:8080", &credentials)),
+            Some(403)
+        );
+        assert_eq!(auth.check(&req("localhost:8081", &credentials)), Some(403));
+        assert_eq!(auth.check(&req("localhost:8080", "")), Some(401));
+        let wrong = format!("Basic {}", crate::encode64("webdav:guess"));
+        assert_eq!(auth.check(&req("localhost:8080", &wrong)), Some(401));
+    }
+}
@@ -435,7 +435,7 @@ pub fn start_clipbard_master_thread(
     h
 }
 
-pub use proto::get_msg_if_not_support_multi_clip;
+pub use proto::{check_dlp, get_msg_if_not_support_multi_clip};
 mod proto {
     use arboard::ClipboardData;
     use hbb_common::{
@@ -576,6 +576,36 @@ mod proto {
             .collect()
     }
 
+    /// The verdict of the rules of the data loss prevention on the text of the clipboards.
+    pub fn check_dlp(
+        clipboards: &[Clipboard],
+        direction: hbb_common::dlp::Direction,
+    ) -> Option<hbb_common::dlp::Verdict> {
+        let mut logged = None;
+        for clipboard in clipboards {
+            if !matches!(
+                clipboard.format.enum_value(),
+                Ok(ClipboardFormat::Text | ClipboardFormat::Rtf | ClipboardFormat::Html)
+            ) {
+                continue;
+            }
+            let data = if clipboard.compress {
+                decompress(&clipboard.content)
+            } else {
+                clipboard.content.to_vec()
+            };
+            let text = String::from_utf8_lossy(&data);
+            match hbb_common::dlp::check(hbb_common::dlp::Subject::Text(&text), direction) {
+                Some(verdict) if verdict.blocked() => return Some(verdict),
+                Some(verdict) => {
+                    logged.get_or_insert(verdict);
+                }
+                None => {}
+            }
+        }
+        logged
+    }
+
     pub fn get_msg_if_not_support_multi_clip(
         version: &str,
         platform: &str,
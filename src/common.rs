@@ -497,9 +497,8 @@ pub fn test_nat_type() {
 async fn test_nat_type_() -> ResultType<bool> {
     log::info!("Testing nat ...");
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
-    let is_direct = crate::ipc::get_socks_async(1_000).await.is_none(); // sync socks BTW
-    #[cfg(any(target_os = "android", target_os = "ios"))]
-    let is_direct = Config::get_socks().is_none(); // sync socks BTW
+    crate::ipc::get_socks_async(1_000).await; // sync socks BTW
+    let is_direct = Config::get_socks().is_none();
     if !is_direct {
         Config::set_nat_type(NatType::SYMMETRIC as _);
         return Ok(true);
@@ -1131,6 +1130,33 @@ pub fn pk_to_fingerprint(pk: Vec<u8>) -> String {
         .collect()
 }
 
+/// The short authentication string of the public key of a fingerprint, twelve digits easier to
+/// compare over the phone than the fingerprint, "" if the fingerprint is not of a public key.
+pub fn fingerprint_to_sas(fingerprint: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    let hex: Vec<u8> = fingerprint.bytes().filter(|c| *c != b' ').collect();
+    if hex.len() != sign::PUBLICKEYBYTES * 2 {
+        return "".to_owned();
+    }
+    let pk = hex
+        .chunks(2)
+        .map(|c| {
+            std::str::from_utf8(c)
+                .ok()
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>();
+    let Some(pk) = pk else {
+        return "".to_owned();
+    };
+    let hash = Sha256::digest(&pk);
+    let mut n = [0u8; 8];
+    n.copy_from_slice(&hash[..8]);
+    let s = format!("{:012}", u64::from_be_bytes(n) % 1_000_000_000_000);
+    format!("{} {} {}", &s[..4], &s[4..8], &s[8..])
+}
+
 #[inline]
 pub async fn get_next_nonkeyexchange_msg(
     conn: &mut FramedStream,
@@ -1338,13 +1364,9 @@ pub fn load_custom_client() {
         read_custom_client(data.trim());
         return;
     }
-    let Some(path) = std::env::current_exe().map_or(None, |x| x.parent().map(|x| x.to_path_buf()))
-    else {
+    let Some(path) = resource_path("custom.txt") else {
         return;
     };
-    #[cfg(target_os = "macos")]
-    let path = path.join("../Resources");
-    let path = path.join("custom.txt");
     if path.is_file() {
         let Ok(data) = std::fs::read_to_string(&path) else {
             log::error!("Failed to read custom client config");
@@ -1354,6 +1376,42 @@ pub fn load_custom_client() {
     }
 }
 
+// beside the executable, where only the installer can write
+fn resource_path(name: &str) -> Option<std::path::PathBuf> {
+    let path = std::env::current_exe().map_or(None, |x| x.parent().map(|x| x.to_path_buf()))?;
+    #[cfg(target_os = "macos")]
+    let path = path.join("../Resources");
+    Some(path.join(name))
+}
+
+/// Locks the options down to the config signed in locked.txt, which is verified with the key of
+/// the deployment built in as LOCKED_CONFIG_KEY. If locked.txt is there but not verified, e.g.
+/// no key is built in, the options are locked down with all the features disabled.
+pub fn load_locked_config() {
+    let Some(path) = resource_path("locked.txt") else {
+        return;
+    };
+    if !path.is_file() {
+        return;
+    }
+    if let Err(err) = try_load_locked_config(&path) {
+        log::error!("Refused the locked config: {}", err);
+        config::LockedConfig::fail_closed();
+    }
+}
+
+fn try_load_locked_config(path: &std::path::Path) -> ResultType<()> {
+    let data = std::fs::read_to_string(path)?;
+    let key = match option_env!("LOCKED_CONFIG_KEY") {
+        Some(key) if !key.is_empty() => key,
+        _ => bail!("No key of the deployment is built in"),
+    };
+    let Some(pk) = get_rs_pk(key) else {
+        bail!("Failed to parse the public key of the locked config");
+    };
+    config::LockedConfig::load(&data, &pk)
+}
+
 fn read_custom_client_advanced_settings(
     settings: serde_json::Value,
     map_display_settings: &HashMap<String, &&str>,
@@ -1512,6 +1570,37 @@ pub fn is_empty_uni_link(arg: &str) -> bool {
     arg[prefix.len()..].chars().all(|c| c == '/')
 }
 
+// The peer the login is to along the challenge of its hash, so that the signature is of no use to
+// any other peer relaying the challenge of its own.
+#[inline]
+fn device_key_message(username: &str, challenge: &str) -> Vec<u8> {
+    format!("{}\n{}", username, challenge).into_bytes()
+}
+
+/// The key of this device signing the login to `username` with `challenge` of its hash.
+pub fn sign_device_key(username: &str, challenge: &str) -> DeviceKey {
+    let (sk, pk) = Config::get_device_key_pair();
+    let Some(sk) = sign::SecretKey::from_slice(&sk) else {
+        return Default::default();
+    };
+    DeviceKey {
+        pk: pk.into(),
+        signed: sign::sign(&device_key_message(username, challenge), &sk).into(),
+        ..Default::default()
+    }
+}
+
+/// The fingerprint of the key of the device of the peer, None if it did not sign the login to
+/// `username` with `challenge`.
+pub fn verify_device_key(key: &DeviceKey, username: &str, challenge: &str) -> Option<String> {
+    let pk = sign::PublicKey::from_slice(&key.pk)?;
+    let data = sign::verify(&key.signed, &pk).ok()?;
+    if data != device_key_message(username, challenge) {
+        return None;
+    }
+    Some(pk_to_fingerprint(key.pk.to_vec()))
+}
+
 pub fn get_hwid() -> Bytes {
     use sha2::{Digest, Sha256};
 
@@ -1661,6 +1750,45 @@ mod tests {
             Duration::from_nanos(0)
         );
     }
+
+    #[test]
+    fn test_fingerprint_to_sas() {
+        let fingerprint = pk_to_fingerprint(vec![7; sign::PUBLICKEYBYTES]);
+        let sas = fingerprint_to_sas(&fingerprint);
+        assert_eq!(sas.len(), 14);
+        assert!(sas.split(' ').all(|s| s.len() == 4));
+        assert_eq!(sas, fingerprint_to_sas(&fingerprint.replace(' ', "")));
+        assert_ne!(
+            sas,
+            fingerprint_to_sas(&pk_to_fingerprint(vec![8; sign::PUBLICKEYBYTES]))
+        );
+        assert_eq!(fingerprint_to_sas(""), "");
+        assert_eq!(fingerprint_to_sas(&fingerprint[1..]), "");
+        assert_eq!(fingerprint_to_sas("zz"), "");
+    }
+
+    #[test]
+    fn test_verify_device_key() {
+        let (pk, sk) = sign::gen_keypair();
+        let key = DeviceKey {
+            pk: pk.0.to_vec().into(),
+            signed: sign::sign(&device_key_message("123456789", "abcdef"), &sk).into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            verify_device_key(&key, "123456789", "abcdef"),
+            Some(pk_to_fingerprint(pk.0.to_vec()))
+        );
+        // relayed by another peer
+        assert!(verify_device_key(&key, "987654321", "abcdef").is_none());
+        assert!(verify_device_key(&key, "123456789", "abcdeg").is_none());
+        let (other_pk, _) = sign::gen_keypair();
+        let key = DeviceKey {
+            pk: other_pk.0.to_vec().into(),
+            ..key
+        };
+        assert!(verify_device_key(&key, "123456789", "abcdef").is_none());
+    }
 }
 
 #[inline]
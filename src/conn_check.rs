@@ -0,0 +1,168 @@
+// Connectivity diagnostics, printed as json by `--check-conn`.
+//
+// A session goes through the relay when the hole punching fails, which mostly comes from a
+// symmetric nat, the udp to the rendezvous server being blocked or a proxy. The nat is classified
+// as in `test_nat_type`, by the ports seen by the two ports of the rendezvous server from the same
+// local port, and the rendezvous server and the relay server are reached over tcp and udp with the
+// round trip times measured. Nothing of the configuration is changed.
+
+use std::time::Instant;
+
+use hbb_common::{
+    anyhow::{anyhow, bail},
+    config::{Config, CONNECT_TIMEOUT, RELAY_PORT},
+    protobuf::Message as _,
+    rendezvous_proto::*,
+    socket_client, ResultType,
+};
+use serde_derive::Serialize;
+
+const UDP_TRIES: usize = 3;
+// in milliseconds
+const UDP_TIMEOUT: u64 = 1_000;
+
+#[derive(Debug, Default, Serialize)]
+pub struct Check {
+    pub ok: bool,
+    // in milliseconds, -1 if failed
+    pub rtt: i64,
+    pub error: String,
+}
+
+impl Check {
+    fn new(result: ResultType<i64>) -> Self {
+        match result {
+            Ok(rtt) => Self {
+                ok: true,
+                rtt,
+                error: String::new(),
+            },
+            Err(err) => Self {
+                ok: false,
+                rtt: -1,
+                error: err.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub rendezvous_server: String,
+    pub relay_server: String,
+    pub proxy: bool,
+    // cone, symmetric or unknown
+    pub nat_type: String,
+    pub local_addr: String,
+    // the public ports seen by the two ports of the rendezvous server
+    pub public_ports: Vec<i32>,
+    pub tcp_rendezvous: Check,
+    pub udp_rendezvous: Check,
+    pub tcp_relay: Check,
+    // whether the hole punching can be expected to work from this side
+    pub punch_hole: bool,
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn check() -> Report {
+    crate::ipc::get_socks_async(1_000).await;
+    let (rendezvous_server, _, _) = crate::common::get_rendezvous_server(1_000).await;
+    let mut relay_server = crate::relay_select::pick().await;
+    if relay_server.is_empty() {
+        relay_server = crate::increase_port(&rendezvous_server, 1);
+    }
+    let relay_server = crate::check_port(relay_server, RELAY_PORT);
+    let mut report = Report {
+        proxy: Config::get_socks().is_some(),
+        nat_type: "unknown".to_owned(),
+        ..Default::default()
+    };
+    match test_nat(&rendezvous_server).await {
+        Ok((local_addr, ports, rtt)) => {
+            report.local_addr = local_addr;
+            if ports.len() == 2 {
+                report.nat_type = if ports[0] == ports[1] {
+                    "cone".to_owned()
+                } else {
+                    "symmetric".to_owned()
+                };
+            }
+            report.public_ports = ports;
+            report.tcp_rendezvous = Check::new(Ok(rtt));
+        }
+        Err(err) => report.tcp_rendezvous = Check::new(Err(err)),
+    }
+    report.udp_rendezvous = Check::new(test_udp(&rendezvous_server).await);
+    report.tcp_relay = Check::new(test_tcp(&relay_server).await);
+    report.punch_hole = !report.proxy && report.nat_type == "cone" && report.udp_rendezvous.ok;
+    report.rendezvous_server = rendezvous_server;
+    report.relay_server = relay_server;
+    report
+}
+
+// the local address, the public ports and the round trip time of the first request
+async fn test_nat(server: &str) -> ResultType<(String, Vec<i32>, i64)> {
+    let servers = [server.to_owned(), crate::increase_port(server, -1)];
+    let mut msg_out = RendezvousMessage::new();
+    msg_out.set_test_nat_request(TestNatRequest {
+        serial: Config::get_serial(),
+        ..Default::default()
+    });
+    let mut local_addr = None;
+    let mut ports = Vec::new();
+    let mut rtt = 0;
+    for server in servers.iter() {
+        let start = Instant::now();
+        // the same local port for both, the nat maps it to the same public port if it is a cone
+        let mut socket =
+            socket_client::connect_tcp_local(&**server, local_addr, CONNECT_TIMEOUT).await?;
+        if local_addr.is_none() {
+            local_addr = Some(socket.local_addr());
+        }
+        socket.send(&msg_out).await?;
+        match crate::get_next_nonkeyexchange_msg(&mut socket, Some(CONNECT_TIMEOUT)).await {
+            Some(RendezvousMessage {
+                union: Some(rendezvous_message::Union::TestNatResponse(tnr)),
+                ..
+            }) => {
+                if ports.is_empty() {
+                    rtt = start.elapsed().as_millis() as _;
+                }
+                ports.push(tnr.port);
+            }
+            // the second port is not required for the rest
+            _ if !ports.is_empty() => break,
+            _ => bail!("no nat test response from {}", server),
+        }
+    }
+    let local_addr = local_addr.map(|x| x.to_string()).unwrap_or_default();
+    Ok((local_addr, ports, rtt))
+}
+
+async fn test_udp(server: &str) -> ResultType<i64> {
+    let (mut socket, addr) = socket_client::new_udp_for(server, CONNECT_TIMEOUT).await?;
+    let mut msg_out = RendezvousMessage::new();
+    // answered with a failure for the empty id, unlike the registration it does not move the
+    // address of this device on the server
+    msg_out.set_punch_hole_request(PunchHoleRequest::default());
+    for _ in 0..UDP_TRIES {
+        let start = Instant::now();
+        socket.send(&msg_out, addr.clone()).await?;
+        while let Some(Ok((bytes, _))) = socket.next_timeout(UDP_TIMEOUT).await {
+            if let Ok(RendezvousMessage {
+                union: Some(rendezvous_message::Union::PunchHoleResponse(_)),
+                ..
+            }) = RendezvousMessage::parse_from_bytes(&bytes)
+            {
+                return Ok(start.elapsed().as_millis() as _);
+            }
+        }
+    }
+    Err(anyhow!("no udp response after {} tries", UDP_TRIES))
+}
+
+async fn test_tcp(server: &str) -> ResultType<i64> {
+    let start = Instant::now();
+    socket_client::connect_tcp(server, CONNECT_TIMEOUT).await?;
+    Ok(start.elapsed().as_millis() as _)
+}
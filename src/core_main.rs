@@ -30,6 +30,7 @@ macro_rules! my_println{
 #[cfg(not(any(target_os = "android", target_os = "ios")))]
 pub fn core_main() -> Option<Vec<String>> {
     crate::load_custom_client();
+    crate::load_locked_config();
     #[cfg(windows)]
     crate::platform::windows::bootstrap();
     let mut args = Vec::new();
@@ -309,6 +310,9 @@ pub fn core_main() -> Option<Vec<String>> {
         } else if args[0] == "--password" {
             if args.len() == 2 {
                 if crate::platform::is_installed() && is_root() {
+                    if !approve_host_change() {
+                        return None;
+                    }
                     if let Err(err) = crate::ipc::set_permanent_password(args[1].to_owned()) {
                         println!("{err}");
                     } else {
@@ -323,6 +327,9 @@ pub fn core_main() -> Option<Vec<String>> {
             #[cfg(feature = "flutter")]
             if args.len() == 2 {
                 if crate::platform::is_installed() && is_root() {
+                    if !approve_host_change() {
+                        return None;
+                    }
                     if let Err(err) = crate::ipc::set_unlock_pin(args[1].to_owned(), false) {
                         println!("{err}");
                     } else {
@@ -336,6 +343,29 @@ pub fn core_main() -> Option<Vec<String>> {
         } else if args[0] == "--get-id" {
             println!("{}", crate::ipc::get_id());
             return None;
+        } else if args[0] == "--rendezvous-status" {
+            println!("{}", crate::ipc::get_rendezvous_status());
+            return None;
+        } else if args[0] == "--check-conn" {
+            let report = crate::conn_check::check();
+            println!("{}", serde_json::to_string(&report).unwrap_or_default());
+            return None;
+        } else if args[0] == "--metrics" {
+            if let Err(err) = crate::client::metrics::print() {
+                println!("{}", err);
+            }
+            return None;
+        } else if args[0] == "--lan-peers" {
+            let refresh = args.iter().any(|x| x == "--refresh");
+            let peers = crate::ipc::get_lan_peers(refresh).unwrap_or_else(|_| {
+                // no service to ask, discover from here
+                if refresh {
+                    hbb_common::allow_err!(crate::lan::discover());
+                }
+                hbb_common::config::LanPeers::load().peers
+            });
+            println!("{}", serde_json::to_string(&peers).unwrap_or_default());
+            return None;
         } else if args[0] == "--set-id" {
             if args.len() == 2 {
                 if crate::platform::is_installed() && is_root() {
@@ -350,6 +380,201 @@ pub fn core_main() -> Option<Vec<String>> {
                 }
             }
             return None;
+        } else if args[0] == "--aliases" {
+            match crate::ipc::aliases(None) {
+                Ok(status) => println!("{}", serde_json::to_string(&status).unwrap_or_default()),
+                Err(err) => println!("{}", err),
+            }
+            return None;
+        } else if args[0] == "--set-aliases" {
+            // comma separated, empty to remove them all
+            if args.len() == 2 {
+                if crate::platform::is_installed() && is_root() {
+                    let aliases = args[1].split(',').map(|a| a.to_owned()).collect();
+                    match crate::ipc::aliases(Some(aliases)) {
+                        Ok(status) => {
+                            println!("{}", serde_json::to_string(&status).unwrap_or_default())
+                        }
+                        Err(err) => println!("{}", err),
+                    }
+                } else {
+                    println!("Installation and administrative privileges required!");
+                }
+            }
+            return None;
+        } else if args[0] == "--rotate-key" {
+            // the days the old keypair is still signed with, for the rendezvous servers to be
+            // given the new public key, 0 if it is compromised
+            if crate::platform::is_installed() && is_root() {
+                let grace_days = args.get(1).and_then(|d| d.parse().ok()).unwrap_or(7);
+                match crate::ipc::rotate_key_pair(grace_days) {
+                    Ok(()) => println!("Done!"),
+                    Err(err) => println!("{}", err),
+                }
+            } else {
+                println!("Installation and administrative privileges required!");
+            }
+            return None;
+        } else if args[0] == "--install-remote-printer" || args[0] == "--uninstall-remote-printer" {
+            // the virtual printer whose documents are printed on the controlling side
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            if is_root() {
+                let res = if args[0] == "--install-remote-printer" {
+                    crate::printer::install()
+                } else {
+                    crate::printer::uninstall()
+                };
+                match res {
+                    Ok(()) => println!("Done!"),
+                    Err(err) => println!("{}", err),
+                }
+            } else {
+                println!("Administrative privileges required!");
+            }
+            #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+            println!("The remote printer is supported on Linux and macOS only");
+            return None;
+        } else if args[0] == "--print-backend" {
+            // run by CUPS for the jobs of the virtual printer
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            std::process::exit(crate::printer::backend(&args[1..]));
+            #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+            return None;
+        } else if args[0] == "--lockouts" {
+            match crate::ipc::login_lockouts(None) {
+                Ok(lockouts) => {
+                    println!("{}", serde_json::to_string(&lockouts).unwrap_or_default())
+                }
+                Err(err) => println!("{}", err),
+            }
+            return None;
+        } else if args[0] == "--clear-lockouts" {
+            // of a peer id or an ip, all without it
+            if crate::platform::is_installed() && is_root() {
+                match crate::ipc::login_lockouts(Some(args.get(1).cloned())) {
+                    Ok(lockouts) => {
+                        println!("{}", serde_json::to_string(&lockouts).unwrap_or_default())
+                    }
+                    Err(err) => println!("{}", err),
+                }
+            } else {
+                println!("Installation and administrative privileges required!");
+            }
+            return None;
+        } else if args[0] == "--one-time-passwords" {
+            // [count minutes [view|file-transfer] [label]], the codes generated are printed once
+            if !(crate::platform::is_installed() && is_root()) {
+                println!("Installation and administrative privileges required!");
+                return None;
+            }
+            let request = if args.len() >= 3 {
+                let (Ok(count), Ok(minutes)) = (args[1].parse(), args[2].parse()) else {
+                    println!("Invalid count or minutes");
+                    return None;
+                };
+                crate::ipc::DataOneTimePasswords::Generate {
+                    count,
+                    minutes,
+                    scope: args.get(3).cloned().unwrap_or_default(),
+                    label: args.get(4).cloned().unwrap_or_default(),
+                }
+            } else {
+                crate::ipc::DataOneTimePasswords::Query
+            };
+            match crate::ipc::one_time_passwords(request) {
+                Ok(crate::ipc::DataOneTimePasswords::Generated(codes)) => {
+                    for code in codes {
+                        println!("{}", code);
+                    }
+                }
+                Ok(crate::ipc::DataOneTimePasswords::List(list)) => {
+                    println!("{}", serde_json::to_string(&list).unwrap_or_default())
+                }
+                Ok(_) => {}
+                Err(err) => println!("{}", err),
+            }
+            return None;
+        } else if args[0] == "--audit-log" {
+            // a json query, e.g. {"kind": "auth", "peer_id": "123456789", "since": 0, "limit": 100}
+            let query = match args.get(1).map(|q| serde_json::from_str(q)) {
+                Some(Ok(query)) => query,
+                Some(Err(err)) => {
+                    println!("Invalid query: {}", err);
+                    return None;
+                }
+                None => Default::default(),
+            };
+            match crate::ipc::audit_log(Some(query)) {
+                Ok(crate::ipc::DataAuditLog::Entries(entries)) => {
+                    for entry in entries {
+                        println!("{}", serde_json::to_string(&entry).unwrap_or_default());
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => println!("{}", err),
+            }
+            return None;
+        } else if args[0] == "--verify-audit-log" {
+            match crate::ipc::audit_log(None) {
+                Ok(crate::ipc::DataAuditLog::Verified(res)) => {
+                    println!("{}", serde_json::to_string(&res).unwrap_or_default())
+                }
+                Ok(_) => {}
+                Err(err) => println!("{}", err),
+            }
+            return None;
+        } else if args[0] == "--fido2-register" {
+            // on the controlling side, the token printed is added on the controlled side
+            if args.len() == 2 {
+                match crate::fido2::register(&args[1]) {
+                    Ok(credential) => println!("{}", credential.to_token()),
+                    Err(err) => println!("{}", err),
+                }
+            }
+            return None;
+        } else if args[0] == "--fido2-add"
+            || args[0] == "--fido2-remove"
+            || args[0] == "--fido2-register-host"
+        {
+            use hbb_common::config::keys::{
+                OPTION_FIDO2_CREDENTIALS, OPTION_FIDO2_HOST_CREDENTIALS,
+            };
+            if args.len() == 2 {
+                if crate::platform::is_installed() && is_root() {
+                    if !approve_host_change() {
+                        return None;
+                    }
+                    let options = crate::ipc::get_options();
+                    let get = |key: &str| options.get(key).cloned().unwrap_or_default();
+                    let res = if args[0] == "--fido2-add" {
+                        crate::fido2::Credential::from_token(&args[1]).map(|c| {
+                            let value = get(OPTION_FIDO2_CREDENTIALS);
+                            let value = crate::fido2::add_credential(&value, &c);
+                            crate::ipc::set_option(OPTION_FIDO2_CREDENTIALS, &value);
+                        })
+                    } else if args[0] == "--fido2-remove" {
+                        for key in [OPTION_FIDO2_CREDENTIALS, OPTION_FIDO2_HOST_CREDENTIALS] {
+                            let value = crate::fido2::remove_credential(&get(key), &args[1]);
+                            crate::ipc::set_option(key, &value);
+                        }
+                        Ok(())
+                    } else {
+                        println!("Touch the security key to register");
+                        crate::fido2::register(&args[1]).map(|c| {
+                            let value = get(OPTION_FIDO2_HOST_CREDENTIALS);
+                            let value = crate::fido2::add_credential(&value, &c);
+                            crate::ipc::set_option(OPTION_FIDO2_HOST_CREDENTIALS, &value);
+                        })
+                    };
+                    match res {
+                        Ok(()) => println!("Done!"),
+                        Err(err) => println!("{}", err),
+                    }
+                } else {
+                    println!("Installation and administrative privileges required!");
+                }
+            }
+            return None;
         } else if args[0] == "--config" {
             if args.len() == 2 && !args[0].contains("host=") {
                 if crate::platform::is_installed() && is_root() {
@@ -380,7 +605,14 @@ pub fn core_main() -> Option<Vec<String>> {
                 if args.len() == 2 {
                     let options = crate::ipc::get_options();
                     println!("{}", options.get(&args[1]).unwrap_or(&"".to_owned()));
-                } else if args.len() == 3 {
+                } else if args.len() == 3 && approve_host_change() {
+                    if args[1] == hbb_common::config::keys::OPTION_DLP_RULES && !args[2].is_empty()
+                    {
+                        if let Err(err) = hbb_common::dlp::check_rules(&args[2]) {
+                            println!("{}", err);
+                            return None;
+                        }
+                    }
                     crate::ipc::set_option(&args[1], &args[2]);
                 }
             } else {
@@ -470,6 +702,9 @@ pub fn core_main() -> Option<Vec<String>> {
             #[cfg(feature = "hwcodec")]
             crate::ipc::hwcodec_process();
             return None;
+        } else if args[0] == "--bench-codecs" {
+            bench_codecs();
+            return None;
         } else if args[0] == "--cm" {
             // call connection manager to establish connections
             // meanwhile, return true to call flutter window to show control panel
@@ -557,6 +792,40 @@ fn import_config(path: &str) {
     }
 }
 
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn bench_codecs() {
+    use scrap::bench;
+
+    println!(
+        "encoding {} frames of {}x{} synthetic screen content",
+        bench::COUNT,
+        bench::WIDTH,
+        bench::HEIGHT
+    );
+    println!(
+        "{:<20} {:<8} {:>8} {:>8} {:>8}",
+        "encoder", "format", "fps", "kbps", "psnr"
+    );
+    let benchmark = bench::run(bench::WIDTH, bench::HEIGHT, bench::COUNT, |r| {
+        if let Some(e) = &r.error {
+            println!("{:<20} failed: {}", r.name, e);
+            return;
+        }
+        let psnr = r.psnr.map(|p| format!("{p:.2}")).unwrap_or("-".to_owned());
+        println!(
+            "{:<20} {:<8} {:>8.1} {:>8} {:>8}",
+            r.name, r.format, r.fps, r.kbps, psnr
+        );
+    });
+    let formats: Vec<_> = benchmark.formats().iter().map(|f| f.to_string()).collect();
+    println!("codec order: {}", formats.join(" > "));
+    println!("encoder order: {}", benchmark.encoders().join(", "));
+    match hbb_common::serde_json::to_string(&benchmark) {
+        Ok(s) => crate::ipc::set_option(config::keys::OPTION_CODEC_BENCHMARK, &s),
+        Err(e) => log::error!("failed to store the codec benchmark: {e}"),
+    }
+}
+
 /// invoke a new connection
 ///
 /// [Note]
@@ -666,3 +935,15 @@ fn is_root() -> bool {
     #[allow(unreachable_code)]
     crate::platform::is_root()
 }
+
+// a security key of the host is touched first if any is registered
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn approve_host_change() -> bool {
+    match crate::fido2::approve_host_change(&crate::ipc::get_options()) {
+        Ok(()) => true,
+        Err(err) => {
+            println!("{}", err);
+            false
+        }
+    }
+}
@@ -0,0 +1,84 @@
+// Marking of the session traffic with the differentiated services code points, so that the
+// networks can prioritize it.
+//
+// A session is a single stream, so the marking is by the kind of the session: the remote control
+// and view sessions, which carry the video, audio and input, with the interactive class, and the
+// file transfer and port forward ones with the other. Windows only applies the marking with a QoS
+// policy for the application.
+
+use hbb_common::{
+    allow_err,
+    config::{
+        keys::{OPTION_DSCP_FILE_TRANSFER, OPTION_DSCP_INTERACTIVE},
+        Config,
+    },
+    log, Stream,
+};
+
+const MAX_DSCP: u8 = 63;
+
+/// Marks the packets of `stream` as configured for the kind of the session.
+pub fn mark(stream: &Stream, file_transfer: bool) {
+    let name = if file_transfer {
+        OPTION_DSCP_FILE_TRANSFER
+    } else {
+        OPTION_DSCP_INTERACTIVE
+    };
+    let value = Config::get_option(name);
+    if value.trim().is_empty() {
+        return;
+    }
+    match parse(&value) {
+        Some(dscp) => allow_err!(stream.set_dscp(dscp)),
+        None => log::warn!("Invalid {}: {}", name, value),
+    }
+}
+
+// "EF", "AF41", "CS1", "LE" or a value
+fn parse(value: &str) -> Option<u8> {
+    let value = value.trim().to_uppercase();
+    let dscp = match value.as_str() {
+        "EF" => 46,
+        "VA" => 44,
+        "LE" => 1,
+        "BE" | "DF" => 0,
+        _ => {
+            if let Some(class) = value.strip_prefix("CS") {
+                let class = class.parse::<u8>().ok().filter(|c| *c <= 7)?;
+                class * 8
+            } else if let Some(af) = value.strip_prefix("AF") {
+                let mut digits = af.chars().map(|c| c.to_digit(10));
+                let class = digits.next()??;
+                let drop = digits.next()??;
+                if digits.next().is_some() || !(1..=4).contains(&class) || !(1..=3).contains(&drop)
+                {
+                    return None;
+                }
+                (class * 8 + drop * 2) as u8
+            } else {
+                value.parse::<u8>().ok()?
+            }
+        }
+    };
+    (dscp <= MAX_DSCP).then_some(dscp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("EF"), Some(46));
+        assert_eq!(parse(" af41 "), Some(34));
+        assert_eq!(parse("AF13"), Some(14));
+        assert_eq!(parse("CS1"), Some(8));
+        assert_eq!(parse("be"), Some(0));
+        assert_eq!(parse("26"), Some(26));
+        assert_eq!(parse("64"), None);
+        assert_eq!(parse("AF51"), None);
+        assert_eq!(parse("AF4"), None);
+        assert_eq!(parse("CS8"), None);
+        assert_eq!(parse("fast"), None);
+    }
+}
@@ -0,0 +1,118 @@
+// The echo suppression of a voice call: the voice of the peer played on the speakers is taken
+// back by the microphone and sent to the peer again. The level of the audio played is kept, and
+// the microphone is turned down while the peer talks and this side does not, louder than the
+// echo, as the speakerphones do. It is no adaptive canceller, so both sides talking at once are
+// let through, with the echo.
+//
+// The audio played and the microphone are in the same process on both sides of a call, the
+// handlers of the audio played on one side and the audio service on the other.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// how long the echo of the audio played is expected after it
+const HANGOVER: Duration = Duration::from_millis(250);
+// the audio played below it makes no echo to speak of
+const MIN_FAR_END: f32 = 0.003;
+// the microphone louder than the audio played by it is this side talking
+const DOUBLE_TALK_RATIO: f32 = 2.0;
+const SUPPRESSED_GAIN: f32 = 0.05;
+// the change of the gain for a sample, so the microphone is not cut in a click
+const GAIN_STEP: f32 = 1.0 / 480.0;
+
+lazy_static::lazy_static! {
+    // the level of the audio played last, and when
+    static ref FAR_END: Mutex<Option<(f32, Instant)>> = Default::default();
+}
+
+/// The audio decoded to be played, the samples of all the channels.
+pub fn on_far_end(samples: &[f32]) {
+    let level = rms(samples);
+    let now = Instant::now();
+    let mut far_end = FAR_END.lock().unwrap();
+    // the louder one within the hangover, the echo lasts
+    let keep = far_end
+        .map(|(l, t)| l > level && now.saturating_duration_since(t) < HANGOVER)
+        .unwrap_or(false);
+    if !keep {
+        *far_end = Some((level, now));
+    }
+}
+
+fn far_end_level(now: Instant) -> f32 {
+    FAR_END
+        .lock()
+        .unwrap()
+        .filter(|(_, t)| now.saturating_duration_since(*t) < HANGOVER)
+        .map(|(l, _)| l)
+        .unwrap_or(0.)
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.;
+    }
+    (samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+/// Turns the microphone down while only the peer talks.
+pub struct Suppressor {
+    gain: f32,
+}
+
+impl Default for Suppressor {
+    fn default() -> Self {
+        Self { gain: 1. }
+    }
+}
+
+impl Suppressor {
+    pub fn process(&mut self, near: &[f32]) -> Vec<f32> {
+        self.process_with(near, far_end_level(Instant::now()))
+    }
+
+    fn process_with(&mut self, near: &[f32], far: f32) -> Vec<f32> {
+        let echo_only = far > MIN_FAR_END && rms(near) < far * DOUBLE_TALK_RATIO;
+        let target = if echo_only { SUPPRESSED_GAIN } else { 1. };
+        near.iter()
+            .map(|x| {
+                if self.gain < target {
+                    self.gain = (self.gain + GAIN_STEP).min(target);
+                } else if self.gain > target {
+                    self.gain = (self.gain - GAIN_STEP).max(target);
+                }
+                x * self.gain
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(amplitude: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| amplitude * (i as f32 * 0.1).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_suppressor() {
+        let mut s = Suppressor::default();
+        // nothing played, the microphone as it is
+        let near = tone(0.1, 960);
+        assert_eq!(s.process_with(&near, 0.), near);
+        // the echo of the peer turned down, gradually
+        let echo = tone(0.02, 960);
+        let out = s.process_with(&echo, 0.05);
+        assert!(out[0].abs() <= echo[0].abs());
+        assert!(rms(&out[480..]) < rms(&echo[480..]) * 0.1);
+        // this side talking over it is let through
+        let talk = tone(0.3, 960);
+        let out = s.process_with(&talk, 0.05);
+        assert!((rms(&out[480..]) - rms(&talk[480..])).abs() < 1e-6);
+    }
+}
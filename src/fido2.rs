@@ -0,0 +1,905 @@
+// Security keys as the second factor of the incoming connections and of the changes to the
+// security settings made from the command line.
+//
+// The credentials are made on the security key of the controlling side with `--fido2-register`,
+// which prints them as tokens to be added on the controlled side with `--fido2-add`. Once any is
+// added, the controlled side sends a random challenge after the password and the 2fa code, and the
+// controlling side answers with the assertion of the key touched, which is verified against the
+// public keys added. The keys registered with `--fido2-register-host` on the controlled side are
+// touched there to approve the changes of the password, the unlock pin and the options.
+//
+// The keys are reached over usb hid with CTAP2, or CTAP1/U2F for the keys which only speak it.
+// The signatures of both are made over the same data, so that a credential made with one is
+// verified as an assertion of the other. NFC readers are not supported.
+
+use std::time::Duration;
+
+use hbb_common::{
+    anyhow::{anyhow, Context},
+    bail,
+    base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine},
+    config::{
+        keys::{OPTION_FIDO2_CREDENTIALS, OPTION_FIDO2_HOST_CREDENTIALS},
+        Config,
+    },
+    log,
+    message_proto::{Fido2Assertion, Fido2Challenge},
+    rand, ResultType,
+};
+use sha2::{Digest, Sha256};
+
+// the relying party of the credentials, the same for all the devices
+const RP_ID: &str = "rustdesk";
+// of the key to be touched
+const TOUCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+// the flags of the authenticator data
+const FLAG_UP: u8 = 0x01;
+const FLAG_AT: u8 = 0x40;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credential {
+    pub name: String,
+    pub id: Vec<u8>,
+    // uncompressed P-256 point
+    pub public_key: Vec<u8>,
+}
+
+impl Credential {
+    /// "name:id:public key", the last two in base64url.
+    pub fn to_token(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.name,
+            URL_SAFE_NO_PAD.encode(&self.id),
+            URL_SAFE_NO_PAD.encode(&self.public_key)
+        )
+    }
+
+    pub fn from_token(token: &str) -> ResultType<Self> {
+        let mut parts = token.trim().split(':');
+        let (Some(name), Some(id), Some(public_key), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            bail!("Invalid security key credential: {}", token);
+        };
+        let credential = Self {
+            name: name.to_owned(),
+            id: URL_SAFE_NO_PAD.decode(id)?,
+            public_key: URL_SAFE_NO_PAD.decode(public_key)?,
+        };
+        if credential.name.is_empty() || credential.id.is_empty() {
+            bail!("Invalid security key credential: {}", token);
+        }
+        if credential.public_key.len() != 65 || credential.public_key[0] != 0x04 {
+            bail!("Invalid public key of the security key credential");
+        }
+        Ok(credential)
+    }
+}
+
+/// The credentials of the option `name`, comma separated tokens.
+pub fn credentials(value: &str) -> Vec<Credential> {
+    value
+        .split(',')
+        .filter(|t| !t.trim().is_empty())
+        .filter_map(|t| match Credential::from_token(t) {
+            Ok(credential) => Some(credential),
+            Err(err) => {
+                log::warn!("{}", err);
+                None
+            }
+        })
+        .collect()
+}
+
+/// `value` with `credential` added, replacing the one of the same name.
+pub fn add_credential(value: &str, credential: &Credential) -> String {
+    let mut tokens: Vec<String> = credentials(value)
+        .iter()
+        .filter(|c| c.name != credential.name)
+        .map(|c| c.to_token())
+        .collect();
+    tokens.push(credential.to_token());
+    tokens.join(",")
+}
+
+pub fn remove_credential(value: &str, name: &str) -> String {
+    credentials(value)
+        .iter()
+        .filter(|c| c.name != name)
+        .map(|c| c.to_token())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The challenge of an incoming connection to be answered by a security key.
+pub struct Challenge {
+    client_data_hash: [u8; 32],
+    credentials: Vec<Credential>,
+    sent: bool,
+}
+
+impl Challenge {
+    /// None if no security key is required.
+    pub fn new() -> Option<Self> {
+        let credentials = credentials(&Config::get_option(OPTION_FIDO2_CREDENTIALS));
+        if credentials.is_empty() {
+            return None;
+        }
+        Some(Self {
+            client_data_hash: rand::random(),
+            credentials,
+            sent: false,
+        })
+    }
+
+    pub fn message(&mut self) -> Fido2Challenge {
+        self.sent = true;
+        Fido2Challenge {
+            rp_id: RP_ID.to_owned(),
+            client_data_hash: self.client_data_hash.to_vec().into(),
+            credential_ids: self
+                .credentials
+                .iter()
+                .map(|c| c.id.clone().into())
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn verify(&self, assertion: &Fido2Assertion) -> ResultType<&str> {
+        if !self.sent {
+            bail!("No challenge sent");
+        }
+        if !assertion.error.is_empty() {
+            bail!("{}", assertion.error);
+        }
+        let credential = self
+            .credentials
+            .iter()
+            .find(|c| c.id == assertion.credential_id.as_ref())
+            .context("Unknown security key credential")?;
+        verify(
+            &credential.public_key,
+            &self.client_data_hash,
+            &assertion.auth_data,
+            &assertion.signature,
+        )?;
+        Ok(&credential.name)
+    }
+}
+
+fn verify(
+    public_key: &[u8],
+    client_data_hash: &[u8],
+    auth_data: &[u8],
+    signature: &[u8],
+) -> ResultType<()> {
+    if auth_data.len() < 37 {
+        bail!("Invalid authenticator data");
+    }
+    if auth_data[..32] != Sha256::digest(RP_ID.as_bytes())[..] {
+        bail!("The assertion is not for this relying party");
+    }
+    if auth_data[32] & FLAG_UP == 0 {
+        bail!("The security key is not touched");
+    }
+    let mut signed = auth_data.to_vec();
+    signed.extend_from_slice(client_data_hash);
+    ring::signature::UnparsedPublicKey::new(&ring::signature::ECDSA_P256_SHA256_ASN1, public_key)
+        .verify(&signed, signature)
+        .map_err(|_| anyhow!("Invalid signature of the security key"))
+}
+
+/// The assertion of the security key touched for `challenge`, or why there is none.
+pub fn assert(challenge: &Fido2Challenge) -> Fido2Assertion {
+    let ids: Vec<Vec<u8>> = challenge
+        .credential_ids
+        .iter()
+        .map(|id| id.to_vec())
+        .collect();
+    match get_assertion(&challenge.rp_id, &challenge.client_data_hash, &ids) {
+        Ok(assertion) => assertion,
+        Err(err) => {
+            log::error!("Failed to get the assertion of the security key: {}", err);
+            Fido2Assertion {
+                error: err.to_string(),
+                ..Default::default()
+            }
+        }
+    }
+}
+
+/// Makes a credential named `name` on the security key attached, touched to be made.
+pub fn register(name: &str) -> ResultType<Credential> {
+    if name.is_empty() || name.contains(':') || name.contains(',') {
+        bail!("Invalid name of the credential: {}", name);
+    }
+    let device = hid::devices()?
+        .into_iter()
+        .next()
+        .context("No security key found")?;
+    let client_data_hash: [u8; 32] = rand::random();
+    let (id, public_key) = device.make_credential(RP_ID, &client_data_hash)?;
+    Ok(Credential {
+        name: name.to_owned(),
+        id,
+        public_key,
+    })
+}
+
+/// Asks for the touch of a security key registered for the host, if any is, before a change of
+/// the security settings.
+pub fn approve_host_change(options: &std::collections::HashMap<String, String>) -> ResultType<()> {
+    let credentials = credentials(
+        options
+            .get(OPTION_FIDO2_HOST_CREDENTIALS)
+            .map(|v| v.as_str())
+            .unwrap_or_default(),
+    );
+    if credentials.is_empty() {
+        return Ok(());
+    }
+    println!("Touch the security key of the host to approve the change");
+    let mut challenge = Challenge {
+        client_data_hash: rand::random(),
+        credentials,
+        sent: false,
+    };
+    let assertion = assert(&challenge.message());
+    let name = challenge.verify(&assertion)?;
+    log::info!("Change of the security settings approved by {}", name);
+    Ok(())
+}
+
+fn get_assertion(
+    rp_id: &str,
+    client_data_hash: &[u8],
+    ids: &[Vec<u8>],
+) -> ResultType<Fido2Assertion> {
+    let devices = hid::devices()?;
+    if devices.is_empty() {
+        bail!("No security key found");
+    }
+    // the key holding a credential is the one to touch, the keys which can not tell without a
+    // touch are asked if none of the others holds one
+    let app = Sha256::digest(rp_id.as_bytes());
+    for device in devices.iter().filter(|d| d.has_u2f()) {
+        for id in ids {
+            if device.u2f_holds(&app, id)? {
+                return if device.has_ctap2() {
+                    device.get_assertion(rp_id, client_data_hash, std::slice::from_ref(id))
+                } else {
+                    device.u2f_authenticate(&app, client_data_hash, id)
+                };
+            }
+        }
+    }
+    if let Some(device) = devices.iter().find(|d| !d.has_u2f()) {
+        return device.get_assertion(rp_id, client_data_hash, ids);
+    }
+    bail!("The security key is not registered with the peer")
+}
+
+// the credential id and the public key of the attested credential data of `auth_data`
+fn parse_attested_credential(auth_data: &[u8]) -> ResultType<(Vec<u8>, Vec<u8>)> {
+    if auth_data.len() < 55 || auth_data[32] & FLAG_AT == 0 {
+        bail!("No credential in the authenticator data");
+    }
+    let len = u16::from_be_bytes([auth_data[53], auth_data[54]]) as usize;
+    let id = auth_data
+        .get(55..55 + len)
+        .context("Invalid authenticator data")?
+        .to_vec();
+    let (key, _) = cbor::Value::decode(&auth_data[55 + len..])?;
+    let coord = |label: i64| match key.get(&cbor::Value::Int(label)) {
+        Some(cbor::Value::Bytes(b)) if b.len() == 32 => Ok(b.clone()),
+        _ => Err(anyhow!("Unsupported public key of the security key")),
+    };
+    // ES256 only, of the parameters asked for
+    if key.get(&cbor::Value::Int(3)) != Some(&cbor::Value::Int(-7)) {
+        bail!("Unsupported algorithm of the security key");
+    }
+    let mut public_key = vec![0x04];
+    public_key.extend(coord(-2)?);
+    public_key.extend(coord(-3)?);
+    Ok((id, public_key))
+}
+
+// The subset of CBOR the authenticators speak, the maps are encoded in the order given, which is
+// to be the canonical one of CTAP2.
+mod cbor {
+    use hbb_common::{bail, ResultType};
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Value {
+        Int(i64),
+        Bytes(Vec<u8>),
+        Text(String),
+        Array(Vec<Value>),
+        Map(Vec<(Value, Value)>),
+        Bool(bool),
+        Null,
+    }
+
+    impl Value {
+        pub fn text(s: &str) -> Self {
+            Self::Text(s.to_owned())
+        }
+
+        pub fn get(&self, key: &Value) -> Option<&Value> {
+            match self {
+                Self::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+                _ => None,
+            }
+        }
+
+        pub fn encode(&self, out: &mut Vec<u8>) {
+            match self {
+                Self::Int(v) if *v >= 0 => head(out, 0, *v as u64),
+                Self::Int(v) => head(out, 1, (-1 - *v) as u64),
+                Self::Bytes(b) => {
+                    head(out, 2, b.len() as u64);
+                    out.extend_from_slice(b);
+                }
+                Self::Text(t) => {
+                    head(out, 3, t.len() as u64);
+                    out.extend_from_slice(t.as_bytes());
+                }
+                Self::Array(values) => {
+                    head(out, 4, values.len() as u64);
+                    for v in values {
+                        v.encode(out);
+                    }
+                }
+                Self::Map(entries) => {
+                    head(out, 5, entries.len() as u64);
+                    for (k, v) in entries {
+                        k.encode(out);
+                        v.encode(out);
+                    }
+                }
+                Self::Bool(b) => out.push(if *b { 0xF5 } else { 0xF4 }),
+                Self::Null => out.push(0xF6),
+            }
+        }
+
+        /// The value at the head of `data` and its length.
+        pub fn decode(data: &[u8]) -> ResultType<(Self, usize)> {
+            let Some(&first) = data.first() else {
+                bail!("Truncated CBOR");
+            };
+            let (major, info) = (first >> 5, first & 0x1F);
+            let (arg, mut pos) = match info {
+                0..=23 => (info as u64, 1),
+                24..=27 => {
+                    let n = 1 << (info - 24);
+                    let Some(bytes) = data.get(1..1 + n) else {
+                        bail!("Truncated CBOR");
+                    };
+                    (bytes.iter().fold(0u64, |a, b| (a << 8) | *b as u64), 1 + n)
+                }
+                _ => bail!("Unsupported CBOR"),
+            };
+            let mut take = |len: u64| -> ResultType<&[u8]> {
+                let end = pos.saturating_add(len as usize);
+                let Some(bytes) = data.get(pos..end) else {
+                    bail!("Truncated CBOR");
+                };
+                pos = end;
+                Ok(bytes)
+            };
+            let value = match major {
+                0 => Self::Int(arg as i64),
+                1 => Self::Int(-1 - arg as i64),
+                2 => Self::Bytes(take(arg)?.to_vec()),
+                3 => Self::Text(String::from_utf8(take(arg)?.to_vec())?),
+                4 => {
+                    let mut values = Vec::new();
+                    for _ in 0..arg {
+                        let (v, len) = Self::decode(&data[pos..])?;
+                        values.push(v);
+                        pos += len;
+                    }
+                    Self::Array(values)
+                }
+                5 => {
+                    let mut entries = Vec::new();
+                    for _ in 0..arg {
+                        let (k, len) = Self::decode(&data[pos..])?;
+                        pos += len;
+                        let (v, len) = Self::decode(&data[pos..])?;
+                        pos += len;
+                        entries.push((k, v));
+                    }
+                    Self::Map(entries)
+                }
+                // a tag, of the value which follows
+                6 => {
+                    let (v, len) = Self::decode(&data[pos..])?;
+                    pos += len;
+                    v
+                }
+                _ => match info {
+                    20 => Self::Bool(false),
+                    21 => Self::Bool(true),
+                    22 | 23 => Self::Null,
+                    _ => bail!("Unsupported CBOR"),
+                },
+            };
+            Ok((value, pos))
+        }
+    }
+
+    fn head(out: &mut Vec<u8>, major: u8, arg: u64) {
+        let major = major << 5;
+        if arg < 24 {
+            out.push(major | arg as u8);
+        } else if arg <= u8::MAX as u64 {
+            out.push(major | 24);
+            out.push(arg as u8);
+        } else if arg <= u16::MAX as u64 {
+            out.push(major | 25);
+            out.extend_from_slice(&(arg as u16).to_be_bytes());
+        } else if arg <= u32::MAX as u64 {
+            out.push(major | 26);
+            out.extend_from_slice(&(arg as u32).to_be_bytes());
+        } else {
+            out.push(major | 27);
+            out.extend_from_slice(&arg.to_be_bytes());
+        }
+    }
+}
+
+// CTAPHID, https://fidoalliance.org/specs/fido-v2.1-ps-20210615/fido-client-to-authenticator-protocol-v2.1-ps-20210615.html#usb
+mod hid {
+    use super::{cbor::Value, parse_attested_credential, TOUCH_TIMEOUT};
+    use hbb_common::{
+        anyhow::{anyhow, Context},
+        bail, log,
+        message_proto::Fido2Assertion,
+        rand, ResultType,
+    };
+    use sha2::{Digest, Sha256};
+    use std::time::{Duration, Instant};
+
+    const FIDO_USAGE_PAGE: u16 = 0xF1D0;
+    const REPORT_LEN: usize = 64;
+    const BROADCAST_CID: u32 = 0xFFFF_FFFF;
+
+    const CMD_MSG: u8 = 0x03;
+    const CMD_INIT: u8 = 0x06;
+    const CMD_CBOR: u8 = 0x10;
+    const CMD_KEEPALIVE: u8 = 0x3B;
+    const CMD_ERROR: u8 = 0x3F;
+
+    const CAP_CBOR: u8 = 0x04;
+    const CAP_NMSG: u8 = 0x08;
+
+    const CTAP2_MAKE_CREDENTIAL: u8 = 0x01;
+    const CTAP2_GET_ASSERTION: u8 = 0x02;
+    const CTAP2_ERR_PIN_REQUIRED: u8 = 0x36;
+
+    const U2F_REGISTER: u8 = 0x01;
+    const U2F_AUTHENTICATE: u8 = 0x02;
+    const U2F_ENFORCE_PRESENCE: u8 = 0x03;
+    const U2F_CHECK_ONLY: u8 = 0x07;
+    const SW_NO_ERROR: u16 = 0x9000;
+    const SW_CONDITIONS_NOT_SATISFIED: u16 = 0x6985;
+
+    pub struct Device {
+        device: hidapi::HidDevice,
+        cid: u32,
+        capabilities: u8,
+    }
+
+    /// The security keys attached.
+    pub fn devices() -> ResultType<Vec<Device>> {
+        let api = hidapi::HidApi::new()?;
+        let mut devices = Vec::new();
+        for info in api
+            .device_list()
+            .filter(|d| d.usage_page() == FIDO_USAGE_PAGE)
+        {
+            match info
+                .open_device(&api)
+                .map_err(|e| anyhow!(e))
+                .and_then(Device::init)
+            {
+                Ok(device) => devices.push(device),
+                Err(err) => log::debug!("Failed to open {:?}: {}", info.product_string(), err),
+            }
+        }
+        Ok(devices)
+    }
+
+    impl Device {
+        fn init(device: hidapi::HidDevice) -> ResultType<Self> {
+            let mut device = Self {
+                device,
+                cid: BROADCAST_CID,
+                capabilities: 0,
+            };
+            let nonce: [u8; 8] = rand::random();
+            device.send(CMD_INIT, &nonce)?;
+            let deadline = Instant::now() + Duration::from_secs(1);
+            loop {
+                let (cmd, data) = device.recv(deadline)?;
+                // the answers to the other clients on the broadcast channel are skipped
+                if cmd == CMD_INIT && data.len() >= 17 && data[..8] == nonce {
+                    device.cid = u32::from_be_bytes([data[8], data[9], data[10], data[11]]);
+                    device.capabilities = data[16];
+                    return Ok(device);
+                }
+            }
+        }
+
+        pub fn has_ctap2(&self) -> bool {
+            self.capabilities & CAP_CBOR != 0
+        }
+
+        pub fn has_u2f(&self) -> bool {
+            self.capabilities & CAP_NMSG == 0
+        }
+
+        fn send(&self, cmd: u8, data: &[u8]) -> ResultType<()> {
+            // the report id first
+            let mut report = [0u8; REPORT_LEN + 1];
+            report[1..5].copy_from_slice(&self.cid.to_be_bytes());
+            report[5] = 0x80 | cmd;
+            report[6..8].copy_from_slice(&(data.len() as u16).to_be_bytes());
+            let first = &data[..data.len().min(REPORT_LEN - 7)];
+            report[8..8 + first.len()].copy_from_slice(first);
+            self.device.write(&report)?;
+            for (seq, chunk) in data[first.len()..].chunks(REPORT_LEN - 5).enumerate() {
+                let mut report = [0u8; REPORT_LEN + 1];
+                report[1..5].copy_from_slice(&self.cid.to_be_bytes());
+                report[5] = seq as u8;
+                report[6..6 + chunk.len()].copy_from_slice(chunk);
+                self.device.write(&report)?;
+            }
+            Ok(())
+        }
+
+        // the next message of the channel but the keepalives
+        fn recv(&self, deadline: Instant) -> ResultType<(u8, Vec<u8>)> {
+            loop {
+                let report = self.read(deadline)?;
+                if report[4] & 0x80 == 0 {
+                    continue;
+                }
+                let cmd = report[4] & 0x7F;
+                let len = u16::from_be_bytes([report[5], report[6]]) as usize;
+                let mut data = report[7..].to_vec();
+                let mut seq = 0;
+                while data.len() < len {
+                    let report = self.read(deadline)?;
+                    if report[4] != seq {
+                        bail!("Unexpected packet of the security key");
+                    }
+                    data.extend_from_slice(&report[5..]);
+                    seq += 1;
+                }
+                data.truncate(len);
+                match cmd {
+                    CMD_KEEPALIVE => continue,
+                    CMD_ERROR => bail!("Security key error {:?}", data.first()),
+                    _ => return Ok((cmd, data)),
+                }
+            }
+        }
+
+        // the next report of the channel
+        fn read(&self, deadline: Instant) -> ResultType<[u8; REPORT_LEN]> {
+            loop {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                if timeout.is_zero() {
+                    bail!("Timeout waiting for the security key");
+                }
+                let mut report = [0u8; REPORT_LEN];
+                let n = self
+                    .device
+                    .read_timeout(&mut report, timeout.as_millis() as i32)?;
+                if n >= 7 && report[..4] == self.cid.to_be_bytes() {
+                    return Ok(report);
+                }
+            }
+        }
+
+        // the response of a CTAP2 command, or its status if it fails
+        fn ctap2(&self, command: u8, params: Value) -> ResultType<Result<Value, u8>> {
+            let mut data = vec![command];
+            params.encode(&mut data);
+            self.send(CMD_CBOR, &data)?;
+            let deadline = Instant::now() + TOUCH_TIMEOUT;
+            loop {
+                let (cmd, data) = self.recv(deadline)?;
+                if cmd != CMD_CBOR {
+                    continue;
+                }
+                let status = *data.first().context("Empty response of the security key")?;
+                if status != 0 {
+                    return Ok(Err(status));
+                }
+                if data.len() == 1 {
+                    return Ok(Ok(Value::Null));
+                }
+                return Ok(Ok(Value::decode(&data[1..])?.0));
+            }
+        }
+
+        // the response of a U2F command and its status word
+        fn apdu(&self, ins: u8, p1: u8, data: &[u8]) -> ResultType<(Vec<u8>, u16)> {
+            let mut apdu = vec![0, ins, p1, 0, 0];
+            apdu.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            apdu.extend_from_slice(data);
+            apdu.extend_from_slice(&[0, 0]);
+            self.send(CMD_MSG, &apdu)?;
+            let deadline = Instant::now() + Duration::from_secs(3);
+            loop {
+                let (cmd, mut data) = self.recv(deadline)?;
+                if cmd != CMD_MSG {
+                    continue;
+                }
+                if data.len() < 2 {
+                    bail!("Invalid response of the security key");
+                }
+                let sw = data.split_off(data.len() - 2);
+                return Ok((data, u16::from_be_bytes([sw[0], sw[1]])));
+            }
+        }
+
+        // polled until the key is touched
+        fn apdu_touched(&self, ins: u8, p1: u8, data: &[u8]) -> ResultType<Vec<u8>> {
+            let deadline = Instant::now() + TOUCH_TIMEOUT;
+            loop {
+                match self.apdu(ins, p1, data)? {
+                    (response, SW_NO_ERROR) => return Ok(response),
+                    (_, SW_CONDITIONS_NOT_SATISFIED) if Instant::now() < deadline => {
+                        std::thread::sleep(Duration::from_millis(200));
+                    }
+                    (_, SW_CONDITIONS_NOT_SATISFIED) => {
+                        bail!("Timeout waiting for the touch of the security key")
+                    }
+                    (_, sw) => bail!("Security key error {:04X}", sw),
+                }
+            }
+        }
+
+        /// Whether the key holds the credential `id` of the application `app`, without a touch.
+        pub fn u2f_holds(&self, app: &[u8], id: &[u8]) -> ResultType<bool> {
+            if id.len() > u8::MAX as usize {
+                return Ok(false);
+            }
+            let mut data = vec![0u8; 32];
+            data.extend_from_slice(app);
+            data.push(id.len() as u8);
+            data.extend_from_slice(id);
+            let (_, sw) = self.apdu(U2F_AUTHENTICATE, U2F_CHECK_ONLY, &data)?;
+            Ok(sw == SW_CONDITIONS_NOT_SATISFIED)
+        }
+
+        pub fn u2f_authenticate(
+            &self,
+            app: &[u8],
+            client_data_hash: &[u8],
+            id: &[u8],
+        ) -> ResultType<Fido2Assertion> {
+            let mut data = client_data_hash.to_vec();
+            data.extend_from_slice(app);
+            data.push(id.len() as u8);
+            data.extend_from_slice(id);
+            let response = self.apdu_touched(U2F_AUTHENTICATE, U2F_ENFORCE_PRESENCE, &data)?;
+            if response.len() < 6 {
+                bail!("Invalid response of the security key");
+            }
+            // the presence and the counter make the authenticator data of CTAP2 after the app
+            let mut auth_data = app.to_vec();
+            auth_data.extend_from_slice(&response[..5]);
+            Ok(Fido2Assertion {
+                credential_id: id.to_vec().into(),
+                auth_data: auth_data.into(),
+                signature: response[5..].to_vec().into(),
+                ..Default::default()
+            })
+        }
+
+        pub fn get_assertion(
+            &self,
+            rp_id: &str,
+            client_data_hash: &[u8],
+            ids: &[Vec<u8>],
+        ) -> ResultType<Fido2Assertion> {
+            let allow_list = ids
+                .iter()
+                .map(|id| {
+                    Value::Map(vec![
+                        (Value::text("id"), Value::Bytes(id.clone())),
+                        (Value::text("type"), Value::text("public-key")),
+                    ])
+                })
+                .collect();
+            let params = Value::Map(vec![
+                (Value::Int(1), Value::text(rp_id)),
+                (Value::Int(2), Value::Bytes(client_data_hash.to_vec())),
+                (Value::Int(3), Value::Array(allow_list)),
+                (
+                    Value::Int(5),
+                    Value::Map(vec![(Value::text("up"), Value::Bool(true))]),
+                ),
+            ]);
+            let response = match self.ctap2(CTAP2_GET_ASSERTION, params)? {
+                Ok(response) => response,
+                Err(status) => bail!("Security key error {:02X}", status),
+            };
+            let bytes = |key: i64| match response.get(&Value::Int(key)) {
+                Some(Value::Bytes(b)) => Ok(b.clone()),
+                _ => Err(anyhow!("Invalid assertion of the security key")),
+            };
+            // omitted if only one is allowed
+            let credential_id = match response
+                .get(&Value::Int(1))
+                .and_then(|c| c.get(&Value::text("id")))
+            {
+                Some(Value::Bytes(id)) => id.clone(),
+                _ if ids.len() == 1 => ids[0].clone(),
+                _ => bail!("Invalid assertion of the security key"),
+            };
+            Ok(Fido2Assertion {
+                credential_id: credential_id.into(),
+                auth_data: bytes(2)?.into(),
+                signature: bytes(3)?.into(),
+                ..Default::default()
+            })
+        }
+
+        /// The id and the public key of a new credential, with CTAP2, or U2F if the key asks for
+        /// its pin.
+        pub fn make_credential(
+            &self,
+            rp_id: &str,
+            client_data_hash: &[u8],
+        ) -> ResultType<(Vec<u8>, Vec<u8>)> {
+            if self.has_ctap2() {
+                let user_id: [u8; 16] = rand::random();
+                let params = Value::Map(vec![
+                    (Value::Int(1), Value::Bytes(client_data_hash.to_vec())),
+                    (
+                        Value::Int(2),
+                        Value::Map(vec![
+                            (Value::text("id"), Value::text(rp_id)),
+                            (Value::text("name"), Value::text(rp_id)),
+                        ]),
+                    ),
+                    (
+                        Value::Int(3),
+                        Value::Map(vec![
+                            (Value::text("id"), Value::Bytes(user_id.to_vec())),
+                            (Value::text("name"), Value::text(rp_id)),
+                        ]),
+                    ),
+                    (
+                        Value::Int(4),
+                        Value::Array(vec![Value::Map(vec![
+                            (Value::text("alg"), Value::Int(-7)),
+                            (Value::text("type"), Value::text("public-key")),
+                        ])]),
+                    ),
+                ]);
+                match self.ctap2(CTAP2_MAKE_CREDENTIAL, params)? {
+                    Ok(response) => match response.get(&Value::Int(2)) {
+                        Some(Value::Bytes(auth_data)) => {
+                            return parse_attested_credential(auth_data)
+                        }
+                        _ => bail!("Invalid credential of the security key"),
+                    },
+                    Err(CTAP2_ERR_PIN_REQUIRED) if self.has_u2f() => {}
+                    Err(status) => bail!("Security key error {:02X}", status),
+                }
+            }
+            if !self.has_u2f() {
+                bail!("The security key is not supported");
+            }
+            let mut data = client_data_hash.to_vec();
+            data.extend_from_slice(&Sha256::digest(rp_id.as_bytes()));
+            let response = self.apdu_touched(U2F_REGISTER, 0, &data)?;
+            // 0x05, the public key, the length of the key handle and the key handle
+            if response.len() < 67 || response[0] != 0x05 {
+                bail!("Invalid registration of the security key");
+            }
+            let public_key = response[1..66].to_vec();
+            let len = response[66] as usize;
+            let id = response
+                .get(67..67 + len)
+                .context("Invalid registration of the security key")?
+                .to_vec();
+            Ok((id, public_key))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cbor::Value, *};
+
+    #[test]
+    fn test_cbor() {
+        let value = Value::Map(vec![
+            (Value::Int(1), Value::text("rustdesk")),
+            (Value::Int(-3), Value::Bytes(vec![7; 300])),
+            (
+                Value::text("list"),
+                Value::Array(vec![Value::Bool(true), Value::Null, Value::Int(-70_000)]),
+            ),
+        ]);
+        let mut encoded = Vec::new();
+        value.encode(&mut encoded);
+        assert_eq!(&encoded[..3], &[0xA3, 0x01, 0x68]);
+        assert_eq!(Value::decode(&encoded).unwrap(), (value, encoded.len()));
+        assert!(Value::decode(&encoded[..encoded.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        use ring::{
+            rand::SystemRandom,
+            signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING},
+        };
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng)
+            .unwrap();
+        let credential = Credential {
+            name: "yubikey".to_owned(),
+            id: vec![1, 2, 3],
+            public_key: key.public_key().as_ref().to_vec(),
+        };
+        assert_eq!(
+            Credential::from_token(&credential.to_token()).unwrap(),
+            credential
+        );
+        let mut challenge = Challenge {
+            client_data_hash: rand::random(),
+            credentials: vec![credential],
+            sent: false,
+        };
+        let message = challenge.message();
+        let mut auth_data = Sha256::digest(RP_ID.as_bytes()).to_vec();
+        auth_data.extend_from_slice(&[FLAG_UP, 0, 0, 0, 9]);
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&message.client_data_hash);
+        let mut assertion = Fido2Assertion {
+            credential_id: vec![1, 2, 3].into(),
+            auth_data: auth_data.into(),
+            signature: key.sign(&rng, &signed).unwrap().as_ref().to_vec().into(),
+            ..Default::default()
+        };
+        assert_eq!(challenge.verify(&assertion).unwrap(), "yubikey");
+        assertion.credential_id = vec![1, 2].into();
+        assert!(challenge.verify(&assertion).is_err());
+        assertion.credential_id = vec![1, 2, 3].into();
+        challenge.client_data_hash = rand::random();
+        assert!(challenge.verify(&assertion).is_err());
+    }
+
+    #[test]
+    fn test_parse_attested_credential() {
+        let mut auth_data = Sha256::digest(RP_ID.as_bytes()).to_vec();
+        auth_data.extend_from_slice(&[FLAG_UP | FLAG_AT, 0, 0, 0, 1]);
+        auth_data.extend_from_slice(&[0; 16]);
+        auth_data.extend_from_slice(&[0, 2, 0xAB, 0xCD]);
+        Value::Map(vec![
+            (Value::Int(1), Value::Int(2)),
+            (Value::Int(3), Value::Int(-7)),
+            (Value::Int(-1), Value::Int(1)),
+            (Value::Int(-2), Value::Bytes(vec![1; 32])),
+            (Value::Int(-3), Value::Bytes(vec![2; 32])),
+        ])
+        .encode(&mut auth_data);
+        let (id, public_key) = parse_attested_credential(&auth_data).unwrap();
+        assert_eq!(id, vec![0xAB, 0xCD]);
+        assert_eq!(public_key.len(), 65);
+        assert_eq!((public_key[0], public_key[1], public_key[64]), (4, 1, 2));
+    }
+}
@@ -190,6 +190,8 @@ pub struct FlutterHandler {
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     hooks: Arc<RwLock<HashMap<String, SessionHook>>>,
     use_texture_render: Arc<AtomicBool>,
+    // the last frame of the camera of the peer
+    camera_rgba: Arc<RwLock<Vec<u8>>>,
 }
 
 impl Default for FlutterHandler {
@@ -203,6 +205,7 @@ impl Default for FlutterHandler {
             use_texture_render: Arc::new(
                 AtomicBool::new(crate::ui_interface::use_texture_render()),
             ),
+            camera_rgba: Default::default(),
         }
     }
 }
@@ -652,6 +655,15 @@ impl InvokeUiSession for FlutterHandler {
                     &status.codec_format.map_or(NULL, |it| it.to_string()),
                 ),
                 ("chroma", &status.chroma.map_or(NULL, |it| it.to_string())),
+                ("bandwidth", &status.bandwidth.map_or(NULL, |it| it)),
+                ("av_sync", &status.av_sync.map_or(NULL, |it| it.to_string())),
+                ("jitter", &status.jitter.map_or(NULL, |it| it.to_string())),
+                ("loss", &status.loss.map_or(NULL, |it| it)),
+                (
+                    "encode_fps",
+                    &status.encode_fps.map_or(NULL, |it| it.to_string()),
+                ),
+                ("direct", &status.direct.map_or(NULL, |it| it.to_string())),
             ],
             &[],
         );
@@ -668,6 +680,14 @@ impl InvokeUiSession for FlutterHandler {
         );
     }
 
+    fn set_connection_degraded(&self, degraded: bool) {
+        self.push_event(
+            "connection_degraded",
+            &[("degraded", &degraded.to_string())],
+            &[],
+        );
+    }
+
     fn set_fingerprint(&self, fingerprint: String) {
         self.push_event("fingerprint", &[("fingerprint", &fingerprint)], &[]);
     }
@@ -684,10 +704,14 @@ impl InvokeUiSession for FlutterHandler {
         );
     }
 
-    fn job_done(&self, id: i32, file_num: i32) {
+    fn job_done(&self, id: i32, file_num: i32, digests: &str) {
         self.push_event(
             "job_done",
-            &[("id", &id.to_string()), ("file_num", &file_num.to_string())],
+            &[
+                ("id", &id.to_string()),
+                ("file_num", &file_num.to_string()),
+                ("digests", digests),
+            ],
             &[],
         );
     }
@@ -726,6 +750,34 @@ impl InvokeUiSession for FlutterHandler {
         }
     }
 
+    fn file_search_result(&self, id: i32, entries: &Vec<FileEntry>, done: bool, truncated: bool) {
+        self.push_event(
+            "file_search_result",
+            &[
+                (
+                    "value",
+                    &crate::common::make_fd_to_json(id, "".to_owned(), entries),
+                ),
+                ("done", &done.to_string()),
+                ("truncated", &truncated.to_string()),
+            ],
+            &[],
+        );
+    }
+
+    fn file_range(&self, id: i32, offset: u64, data: &[u8], size: u64) {
+        self.push_event(
+            "file_range",
+            &[
+                ("id", &id.to_string()),
+                ("offset", &offset.to_string()),
+                ("data", &crate::encode64(data)),
+                ("size", &size.to_string()),
+            ],
+            &[],
+        );
+    }
+
     // unused in flutter
     fn update_transfer_list(&self) {}
 
@@ -805,6 +857,10 @@ impl InvokeUiSession for FlutterHandler {
         let mut features: HashMap<&str, bool> = Default::default();
         for ref f in pi.features.iter() {
             features.insert("privacy_mode", f.privacy_mode);
+            features.insert("gamepad", f.gamepad);
+            features.insert("pen", f.pen);
+            features.insert("touch", f.touch);
+            features.insert("ime_input", f.ime_input);
         }
         // compatible with 1.1.9
         if get_version_number(&pi.version) < get_version_number("1.2.0") {
@@ -918,6 +974,184 @@ impl InvokeUiSession for FlutterHandler {
         self.push_event("chat_client_mode", &[("text", &msg)], &[]);
     }
 
+    fn new_attachment(&self, path: String, image: bool) {
+        let name = hbb_common::fs::get_file_name(std::path::Path::new(&path));
+        self.push_event(
+            "chat_client_mode",
+            &[
+                ("text", &name),
+                ("path", &path),
+                ("image", &image.to_string()),
+            ],
+            &[],
+        );
+    }
+
+    fn chat_receipt(&self, id: u32, error: String) {
+        self.push_event(
+            "chat_receipt",
+            &[("msg_id", &id.to_string()), ("error", &error)],
+            &[],
+        );
+    }
+
+    fn session_handoff(&self, link: String, timeout: u32, error: String) {
+        self.push_event(
+            "session_handoff",
+            &[
+                ("link", &link),
+                ("timeout", &timeout.to_string()),
+                ("error", &error),
+            ],
+            &[],
+        );
+    }
+
+    fn update_system_stats(&self, stats: crate::client::metrics::HostStats) {
+        self.push_event(
+            "system_stats",
+            &[("stats", &serde_json::to_string(&stats).unwrap_or_default())],
+            &[],
+        );
+    }
+
+    fn update_process_list(&self, list: &ProcessList) {
+        let processes: Vec<_> = list
+            .processes
+            .iter()
+            .map(|p| {
+                serde_json::json!({
+                    "pid": p.pid,
+                    "parent": p.parent,
+                    "name": p.name,
+                    "user": p.user,
+                    "cpu": p.cpu,
+                    "memory": p.memory,
+                })
+            })
+            .collect();
+        self.push_event(
+            "process_list",
+            &[
+                (
+                    "processes",
+                    &serde_json::to_string(&processes).unwrap_or_default(),
+                ),
+                ("total", &list.total.to_string()),
+                ("error", &list.error),
+            ],
+            &[],
+        );
+    }
+
+    fn process_killed(&self, killed: &ProcessKilled) {
+        self.push_event(
+            "process_killed",
+            &[
+                ("pid", &killed.pid.to_string()),
+                ("error", &killed.error),
+                ("elevation_required", &killed.elevation_required.to_string()),
+            ],
+            &[],
+        );
+    }
+
+    fn update_service_list(&self, list: &ServiceList) {
+        let services: Vec<_> = list
+            .services
+            .iter()
+            .map(|s| {
+                serde_json::json!({
+                    "name": s.name,
+                    "description": s.description,
+                    "running": s.running,
+                    "state": s.state,
+                })
+            })
+            .collect();
+        self.push_event(
+            "service_list",
+            &[
+                (
+                    "services",
+                    &serde_json::to_string(&services).unwrap_or_default(),
+                ),
+                ("error", &list.error),
+            ],
+            &[],
+        );
+    }
+
+    fn service_control_result(&self, res: &ServiceControlResult) {
+        self.push_event(
+            "service_control_result",
+            &[
+                ("name", &res.name),
+                ("action", &res.action.value().to_string()),
+                ("error", &res.error),
+            ],
+            &[],
+        );
+    }
+
+    fn update_camera_list(&self, list: &CameraList) {
+        let cameras: Vec<_> = list
+            .cameras
+            .iter()
+            .map(|c| serde_json::json!({"index": c.index, "name": c.name}))
+            .collect();
+        self.push_event(
+            "camera_list",
+            &[
+                (
+                    "cameras",
+                    &serde_json::to_string(&cameras).unwrap_or_default(),
+                ),
+                ("error", &list.error),
+            ],
+            &[],
+        );
+    }
+
+    fn on_camera_frame(&self, width: usize, height: usize, rgba: Vec<u8>) {
+        *self.camera_rgba.write().unwrap() = rgba;
+        self.push_event(
+            "camera_frame",
+            &[
+                ("width", &width.to_string()),
+                ("height", &height.to_string()),
+            ],
+            &[],
+        );
+    }
+
+    fn camera_error(&self, error: String) {
+        self.push_event("camera_frame", &[("error", &error)], &[]);
+    }
+
+    fn print_job(&self, title: String, path: String, copies: u32) {
+        self.push_event(
+            "print_job",
+            &[
+                ("title", &title),
+                ("path", &path),
+                ("copies", &copies.to_string()),
+            ],
+            &[],
+        );
+    }
+
+    fn update_broadcast_viewers(&self, viewers: &BroadcastViewers) {
+        self.push_event(
+            "broadcast_viewers",
+            &[
+                ("count", &viewers.count.to_string()),
+                ("max", &viewers.max.to_string()),
+            ],
+            &[],
+        );
+    }
+
     fn switch_display(&self, display: &SwitchDisplay) {
         let resolutions = serialize_resolutions(&display.resolutions.resolutions);
         self.push_event(
@@ -1014,6 +1248,77 @@ impl InvokeUiSession for FlutterHandler {
     fn update_record_status(&self, start: bool) {
         self.push_event("record_status", &[("start", &start.to_string())], &[]);
     }
+
+    fn update_server_record_status(&self, on: bool) {
+        self.push_event("server_record_status", &[("on", &on.to_string())], &[]);
+    }
+
+    fn inactivity_warning(&self, seconds: u32, view_only: bool) {
+        self.push_event(
+            "inactivity_warning",
+            &[
+                ("seconds", &seconds.to_string()),
+                ("view_only", &view_only.to_string()),
+            ],
+            &[],
+        );
+    }
+
+    fn update_remote_volume(&self, level: u32, mute: bool) {
+        self.push_event(
+            "remote_volume",
+            &[("level", &level.to_string()), ("mute", &mute.to_string())],
+            &[],
+        );
+    }
+
+    fn update_input_control(&self, control: InputControl) {
+        self.push_event(
+            "input_control",
+            &[
+                ("enabled", &control.enabled.to_string()),
+                ("holding", &control.holding.to_string()),
+                ("holder", &control.holder),
+                ("requested", &control.requested.to_string()),
+            ],
+            &[],
+        );
+    }
+
+    fn update_annotation(&self, strokes: Vec<AnnotationStroke>) {
+        let strokes: Vec<_> = strokes
+            .iter()
+            .map(|s| {
+                json!({
+                    "shape": s.shape.value(),
+                    "points": s.points,
+                    "color": s.color,
+                    "width": s.width,
+                })
+            })
+            .collect();
+        self.push_event(
+            "annotation",
+            &[("strokes", &json!(strokes).to_string())],
+            &[],
+        );
+    }
+
+    fn sync_job_plan(&self, id: i32, name: &str, dry_run: bool, plan: &str, err: &str) {
+        let id = id.to_string();
+        let dry_run = dry_run.to_string();
+        self.push_event(
+            "sync_job_plan",
+            &[
+                ("id", id.as_str()),
+                ("name", name),
+                ("dry_run", dry_run.as_str()),
+                ("plan", plan),
+                ("error", err),
+            ],
+            &[],
+        );
+    }
 }
 
 impl FlutterHandler {
@@ -1327,6 +1632,30 @@ pub mod connection_manager {
             );
         }
 
+        fn new_attachment(&self, id: i32, path: String, image: bool) {
+            let name = hbb_common::fs::get_file_name(std::path::Path::new(&path));
+            self.push_event(
+                "chat_server_mode",
+                &[
+                    ("id", &id.to_string()),
+                    ("text", &name),
+                    ("path", &path),
+                    ("image", &image.to_string()),
+                ],
+            );
+        }
+
+        fn chat_receipt(&self, id: i32, msg_id: u32, error: String) {
+            self.push_event(
+                "chat_receipt",
+                &[
+                    ("id", &id.to_string()),
+                    ("msg_id", &msg_id.to_string()),
+                    ("error", &error),
+                ],
+            );
+        }
+
         fn change_theme(&self, dark: String) {
             self.push_event("theme", &[("dark", &dark)]);
         }
@@ -1351,6 +1680,16 @@ pub mod connection_manager {
             self.push_event("update_voice_call_state", &[("client", &client_json)]);
         }
 
+        fn update_input_control_state(&self, client: &crate::ui_cm_interface::Client) {
+            let client_json = serde_json::to_string(&client).unwrap_or("".into());
+            self.push_event("update_input_control_state", &[("client", &client_json)]);
+        }
+
+        fn update_annotation_state(&self, client: &crate::ui_cm_interface::Client) {
+            let client_json = serde_json::to_string(&client).unwrap_or("".into());
+            self.push_event("update_annotation_state", &[("client", &client_json)]);
+        }
+
         fn file_transfer_log(&self, action: &str, log: &str) {
             self.push_event("cm_file_transfer_log", &[(action, log)]);
         }
@@ -1513,6 +1852,14 @@ pub fn session_get_rgba_size(session_id: SessionID, display: usize) -> usize {
     0
 }
 
+/// The last frame of the camera of the peer, in rgba.
+pub fn session_get_camera_frame(session_id: SessionID) -> Vec<u8> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        return session.camera_rgba.read().unwrap().clone();
+    }
+    vec![]
+}
+
 #[no_mangle]
 pub extern "C" fn session_get_rgba(session_uuid_str: *const char, display: usize) -> *const u8 {
     if let Ok(session_id) = char_to_session_id(session_uuid_str) {
@@ -1710,6 +2057,34 @@ fn session_send_touch_pan(
     }
 }
 
+fn session_send_touch_points(
+    session_id: SessionID,
+    v: &serde_json::Value,
+    alt: bool,
+    ctrl: bool,
+    shift: bool,
+    command: bool,
+) {
+    let Some(v) = v.get("v").and_then(|v| v.as_array()) else {
+        return;
+    };
+    let get_i64 = |p: &serde_json::Value, k: &str| p.get(k).and_then(|x| x.as_i64());
+    let points = v
+        .iter()
+        .filter_map(|p| {
+            Some(TouchPoint {
+                id: get_i64(p, "id")? as _,
+                x: get_i64(p, "x")? as _,
+                y: get_i64(p, "y")? as _,
+                ..Default::default()
+            })
+        })
+        .collect();
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.send_touch_points(points, alt, ctrl, shift, command);
+    }
+}
+
 fn session_send_touch_event(
     session_id: SessionID,
     v: &serde_json::Value,
@@ -1720,6 +2095,7 @@ fn session_send_touch_event(
 ) {
     match v.get("t").and_then(|t| t.as_str()) {
         Some("scale") => session_send_touch_scale(session_id, v, alt, ctrl, shift, command),
+        Some("points") => session_send_touch_points(session_id, v, alt, ctrl, shift, command),
         Some(pan_event) => {
             session_send_touch_pan(session_id, v, pan_event, alt, ctrl, shift, command)
         }
@@ -1727,6 +2103,35 @@ fn session_send_touch_event(
     }
 }
 
+fn session_send_pen_event(
+    session_id: SessionID,
+    v: &serde_json::Value,
+    alt: bool,
+    ctrl: bool,
+    shift: bool,
+    command: bool,
+) {
+    let Some(v) = v.get("v") else {
+        return;
+    };
+    let get_i64 = |k: &str| v.get(k).and_then(|x| x.as_i64()).unwrap_or_default();
+    let get_bool = |k: &str| v.get(k).and_then(|x| x.as_bool()).unwrap_or_default();
+    let evt = PenEvent {
+        x: get_i64("x") as _,
+        y: get_i64("y") as _,
+        pressure: get_i64("pressure") as _,
+        tilt_x: get_i64("tilt_x") as _,
+        tilt_y: get_i64("tilt_y") as _,
+        buttons: get_i64("buttons") as _,
+        contact: get_bool("contact"),
+        in_range: get_bool("in_range"),
+        ..Default::default()
+    };
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.send_pen_event(evt, alt, ctrl, shift, command);
+    }
+}
+
 pub fn session_send_pointer(session_id: SessionID, msg: String) {
     if let Ok(m) = serde_json::from_str::<HashMap<String, serde_json::Value>>(&msg) {
         let alt = m.get("alt").is_some();
@@ -1736,6 +2141,7 @@ pub fn session_send_pointer(session_id: SessionID, msg: String) {
         match (m.get("k"), m.get("v")) {
             (Some(k), Some(v)) => match k.as_str() {
                 Some("touch") => session_send_touch_event(session_id, v, alt, ctrl, shift, command),
+                Some("pen") => session_send_pen_event(session_id, v, alt, ctrl, shift, command),
                 _ => {}
             },
             _ => {}
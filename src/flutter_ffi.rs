@@ -19,6 +19,7 @@ use hbb_common::allow_err;
 use hbb_common::{
     config::{self, LocalConfig, PeerConfig, PeerInfoSerde},
     fs, lazy_static, log,
+    message_proto::volume_control,
     rendezvous_proto::ConnType,
     ResultType,
 };
@@ -46,6 +47,8 @@ fn initialize(app_dir: &str, custom_client_config: &str) {
     } else {
         crate::read_custom_client(custom_client_config);
     }
+    // after the custom client, so that its overrides do not loosen the locked config
+    crate::load_locked_config();
     #[cfg(target_os = "android")]
     {
         // flexi_logger can't work when android_logger initialized.
@@ -213,6 +216,12 @@ pub fn session_send2fa(session_id: SessionID, code: String, trust_this_device: b
     }
 }
 
+pub fn session_send_access_reason(session_id: SessionID, reason: String) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.send_access_reason(reason);
+    }
+}
+
 pub fn session_get_enable_trusted_devices(session_id: SessionID) -> SyncReturn<bool> {
     let v = if let Some(session) = sessions::get_session_by_session_id(&session_id) {
         session.get_enable_trusted_devices()
@@ -261,6 +270,71 @@ pub fn session_get_is_recording(session_id: SessionID) -> SyncReturn<bool> {
     }
 }
 
+// Returns the error, empty if ok.
+pub fn session_start_macro_recording(session_id: SessionID, name: String) -> SyncReturn<String> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.start_macro_recording(name))
+    } else {
+        SyncReturn("".to_owned())
+    }
+}
+
+// Returns the error, empty if ok.
+pub fn session_stop_macro_recording(session_id: SessionID) -> SyncReturn<String> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.stop_macro_recording())
+    } else {
+        SyncReturn("".to_owned())
+    }
+}
+
+pub fn session_is_recording_macro(session_id: SessionID) -> SyncReturn<bool> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.is_recording_macro())
+    } else {
+        SyncReturn(false)
+    }
+}
+
+// Returns the error, empty if ok.
+pub fn session_play_macro(session_id: SessionID, name: String) -> SyncReturn<String> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.play_macro(name))
+    } else {
+        SyncReturn("".to_owned())
+    }
+}
+
+pub fn session_stop_macro(session_id: SessionID) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.stop_macro();
+    }
+}
+
+pub fn session_is_playing_macro(session_id: SessionID) -> SyncReturn<bool> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.is_playing_macro())
+    } else {
+        SyncReturn(false)
+    }
+}
+
+// json array of the names
+pub fn session_get_macros(session_id: SessionID) -> SyncReturn<String> {
+    let macros = if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.get_macros()
+    } else {
+        vec![]
+    };
+    SyncReturn(serde_json::to_string(&macros).unwrap_or_default())
+}
+
+pub fn session_remove_macro(session_id: SessionID, name: String) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.remove_macro(name);
+    }
+}
+
 pub fn session_reconnect(session_id: SessionID, force_relay: bool) {
     if let Some(session) = sessions::get_session_by_session_id(&session_id) {
         session.reconnect(force_relay);
@@ -464,6 +538,36 @@ pub fn session_set_custom_fps(session_id: SessionID, fps: i32) {
     }
 }
 
+pub fn session_set_bandwidth_limit(session_id: SessionID, kbps: i32) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.set_bandwidth_limit(kbps);
+    }
+}
+
+pub fn session_set_audio_quality(session_id: SessionID, value: String) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.set_audio_quality(value);
+    }
+}
+
+pub fn session_query_remote_volume(session_id: SessionID) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.control_remote_volume(volume_control::Union::Query(true));
+    }
+}
+
+pub fn session_set_remote_volume(session_id: SessionID, level: i32) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.control_remote_volume(volume_control::Union::Level(level.clamp(0, 100) as _));
+    }
+}
+
+pub fn session_set_remote_mute(session_id: SessionID, mute: bool) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.control_remote_volume(volume_control::Union::Mute(mute));
+    }
+}
+
 pub fn session_lock_screen(session_id: SessionID) {
     if let Some(session) = sessions::get_session_by_session_id(&session_id) {
         session.lock_screen();
@@ -540,6 +644,13 @@ pub fn session_enter_or_leave(_session_id: SessionID, _enter: bool) -> SyncRetur
     SyncReturn(())
 }
 
+// The remote window is fullscreen, all the keys may be sent to the peer then.
+pub fn main_set_fullscreen(_fullscreen: bool) -> SyncReturn<()> {
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    crate::keyboard::shortcuts::set_fullscreen(_fullscreen);
+    SyncReturn(())
+}
+
 pub fn session_input_key(
     session_id: SessionID,
     name: String,
@@ -563,11 +674,39 @@ pub fn session_input_string(session_id: SessionID, value: String) {
     }
 }
 
+pub fn session_type_clipboard(session_id: SessionID, value: String) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.type_clipboard(&value);
+    }
+}
+
+pub fn session_set_remote_audio_input(session_id: SessionID, device: String) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.set_remote_audio_input(device);
+    }
+}
+
+pub fn session_input_ime(session_id: SessionID, text: String, commit: bool) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.input_ime(text, commit);
+    }
+}
+
 // chat_client_mode
-pub fn session_send_chat(session_id: SessionID, text: String) {
+pub fn session_send_chat(session_id: SessionID, id: u32, text: String) {
     if let Some(session) = sessions::get_session_by_session_id(&session_id) {
-        session.send_chat(text);
+        session.send_chat_with_receipt(id, text);
+    }
+}
+
+// the error if the file can not be sent
+pub fn session_send_chat_attachment(session_id: SessionID, id: u32, path: String) -> String {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        if let Err(err) = session.send_chat_attachment(id, path) {
+            return err.to_string();
+        }
     }
+    "".to_owned()
 }
 
 pub fn session_peer_option(session_id: SessionID, name: String, value: String) {
@@ -596,6 +735,40 @@ pub fn session_read_remote_dir(session_id: SessionID, path: String, include_hidd
     }
 }
 
+pub fn session_search_remote_files(
+    session_id: SessionID,
+    act_id: i32,
+    path: String,
+    pattern: String,
+    include_hidden: bool,
+    max_depth: u32,
+    timeout_secs: u32,
+) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.search_remote_files(
+            act_id,
+            path,
+            pattern,
+            include_hidden,
+            max_depth,
+            timeout_secs,
+        );
+    }
+}
+
+pub fn session_read_file_range(
+    session_id: SessionID,
+    act_id: i32,
+    path: String,
+    offset: u64,
+    length: u32,
+    is_remote: bool,
+) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.read_file_range(act_id, path, offset, length, is_remote);
+    }
+}
+
 pub fn session_send_files(
     session_id: SessionID,
     act_id: i32,
@@ -605,9 +778,18 @@ pub fn session_send_files(
     include_hidden: bool,
     is_remote: bool,
     _is_dir: bool,
+    conflict_policy: String,
 ) {
     if let Some(session) = sessions::get_session_by_session_id(&session_id) {
-        session.send_files(act_id, path, to, file_num, include_hidden, is_remote);
+        session.send_files(
+            act_id,
+            path,
+            to,
+            file_num,
+            include_hidden,
+            is_remote,
+            conflict_policy,
+        );
     }
 }
 
@@ -624,15 +806,29 @@ pub fn session_set_confirm_override_file(
     }
 }
 
+pub fn session_resolve_file_conflict(
+    session_id: SessionID,
+    act_id: i32,
+    file_num: i32,
+    policy: String,
+    remember: bool,
+    is_upload: bool,
+) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.resolve_file_conflict(act_id, file_num, policy, remember, is_upload);
+    }
+}
+
 pub fn session_remove_file(
     session_id: SessionID,
     act_id: i32,
     path: String,
     file_num: i32,
     is_remote: bool,
+    trash: bool,
 ) {
     if let Some(session) = sessions::get_session_by_session_id(&session_id) {
-        session.remove_file(act_id, path, file_num, is_remote);
+        session.remove_file(act_id, path, file_num, is_remote, trash);
     }
 }
 
@@ -653,9 +849,21 @@ pub fn session_remove_all_empty_dirs(
     act_id: i32,
     path: String,
     is_remote: bool,
+    trash: bool,
+) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.remove_dir(act_id, path, is_remote, trash);
+    }
+}
+
+pub fn session_undo_remove(
+    session_id: SessionID,
+    act_id: i32,
+    remove_ids: Vec<i32>,
+    is_remote: bool,
 ) {
     if let Some(session) = sessions::get_session_by_session_id(&session_id) {
-        session.remove_dir(act_id, path, is_remote);
+        session.undo_remove(act_id, remove_ids, is_remote);
     }
 }
 
@@ -721,6 +929,19 @@ pub fn session_resume_job(session_id: SessionID, act_id: i32, is_remote: bool) {
     }
 }
 
+pub fn session_pause_job(session_id: SessionID, act_id: i32, is_remote: bool) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.pause_job(act_id, is_remote);
+    }
+}
+
+// the ids of the transfer jobs in the order of the queue
+pub fn session_reorder_jobs(session_id: SessionID, ids: Vec<i32>) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.reorder_jobs(ids);
+    }
+}
+
 pub fn session_rename_file(
     session_id: SessionID,
     act_id: i32,
@@ -733,6 +954,60 @@ pub fn session_rename_file(
     }
 }
 
+pub fn session_run_sync_job(session_id: SessionID, act_id: i32, name: String, dry_run: bool) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.run_sync_job(act_id, name, dry_run);
+    }
+}
+
+// kbps, 0 for no limit
+pub fn session_set_job_rate_limit(session_id: SessionID, act_id: i32, kbps: u32) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.set_job_rate_limit(act_id, kbps);
+    }
+}
+
+// json array of the sync jobs of the peer
+pub fn session_get_sync_jobs(session_id: SessionID) -> SyncReturn<String> {
+    let jobs = if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.get_sync_jobs()
+    } else {
+        vec![]
+    };
+    SyncReturn(serde_json::to_string(&jobs).unwrap_or_default())
+}
+
+// Returns the error, empty if ok.
+pub fn session_save_sync_job(session_id: SessionID, job: String) -> SyncReturn<String> {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        SyncReturn(session.save_sync_job(job))
+    } else {
+        SyncReturn("".to_owned())
+    }
+}
+
+pub fn session_remove_sync_job(session_id: SessionID, name: String) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.remove_sync_job(name);
+    }
+}
+
+pub fn session_log_sync_job(session_id: SessionID, name: String, text: String) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.log_sync_job(name, text);
+    }
+}
+
+// json array of the names of the scheduled sync jobs due to run
+pub fn session_get_due_sync_jobs(session_id: SessionID) -> SyncReturn<String> {
+    let names = if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.get_due_sync_jobs()
+    } else {
+        vec![]
+    };
+    SyncReturn(serde_json::to_string(&names).unwrap_or_default())
+}
+
 pub fn session_elevate_direct(session_id: SessionID) {
     if let Some(session) = sessions::get_session_by_session_id(&session_id) {
         session.elevate_direct();
@@ -761,6 +1036,12 @@ pub fn session_set_size(session_id: SessionID, display: usize, width: usize, hei
     super::flutter::session_set_size(session_id, display, width, height)
 }
 
+pub fn session_set_view_scale(session_id: SessionID, scale: f64) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.set_view_scale(scale);
+    }
+}
+
 pub fn session_send_selected_session_id(session_id: SessionID, sid: String) {
     if let Some(session) = sessions::get_session_by_session_id(&session_id) {
         session.send_selected_session_id(sid);
@@ -867,6 +1148,14 @@ pub fn main_get_socks() -> Vec<String> {
     get_socks()
 }
 
+pub fn main_get_network_profiles() -> String {
+    get_network_profiles()
+}
+
+pub fn main_set_network_profiles(profiles: String) {
+    set_network_profiles(profiles)
+}
+
 pub fn main_get_app_name() -> String {
     get_app_name()
 }
@@ -1161,6 +1450,7 @@ pub fn main_load_fav_peers() {
                             username: d.username.clone(),
                             hostname: d.hostname.clone(),
                             platform: d.platform.clone(),
+                            ..Default::default()
                         },
                         ..Default::default()
                     },
@@ -1343,6 +1633,116 @@ pub fn session_close_voice_call(session_id: SessionID) {
     }
 }
 
+pub fn session_set_voice_call_talking(session_id: SessionID, talking: bool) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.set_voice_call_talking(talking);
+    }
+}
+
+pub fn session_request_input_control(session_id: SessionID, request: bool) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.request_input_control(request);
+    }
+}
+
+pub fn session_request_handoff(session_id: SessionID) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.request_session_handoff();
+    }
+}
+
+pub fn session_watch_system_stats(session_id: SessionID, on: bool) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.watch_system_stats(on);
+    }
+}
+
+pub fn session_list_processes(session_id: SessionID, sort: i32, ascending: bool, limit: u32) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.list_processes(sort, ascending, limit);
+    }
+}
+
+pub fn session_kill_process(session_id: SessionID, pid: u32) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.kill_process(pid);
+    }
+}
+
+pub fn session_list_services(session_id: SessionID) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.list_services();
+    }
+}
+
+pub fn session_control_service(session_id: SessionID, name: String, action: i32) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.control_service(name, action);
+    }
+}
+
+pub fn session_list_cameras(session_id: SessionID) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.list_cameras();
+    }
+}
+
+pub fn session_request_camera(
+    session_id: SessionID,
+    on: bool,
+    index: i32,
+    fps: u32,
+    quality: u32,
+    vp9: bool,
+) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.request_camera(on, index, fps, quality, vp9);
+    }
+}
+
+pub fn session_get_camera_frame(session_id: SessionID) -> SyncReturn<Vec<u8>> {
+    SyncReturn(super::flutter::session_get_camera_frame(session_id))
+}
+
+pub fn session_remote_printer(session_id: SessionID, on: bool) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.remote_printer(on);
+    }
+}
+
+/// Prints the document of the virtual printer of a peer, the error if any.
+pub fn main_print_pdf(path: String, copies: u32) -> SyncReturn<String> {
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    let res = match crate::printer::print(&path, copies) {
+        Ok(()) => "".to_owned(),
+        Err(err) => err.to_string(),
+    };
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    let res = {
+        let _ = (path, copies);
+        "Printing is not supported on this side".to_owned()
+    };
+    SyncReturn(res)
+}
+
+pub fn session_annotate(
+    session_id: SessionID,
+    shape: i32,
+    points: Vec<i32>,
+    color: u32,
+    width: u32,
+) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.annotate(shape, points, color, width);
+    }
+}
+
+pub fn session_undo_annotation(session_id: SessionID, all: bool) {
+    if let Some(session) = sessions::get_session_by_session_id(&session_id) {
+        session.undo_annotation(all);
+    }
+}
+
 pub fn session_get_conn_token(session_id: SessionID) -> SyncReturn<Option<String>> {
     if let Some(session) = sessions::get_session_by_session_id(&session_id) {
         SyncReturn(session.get_conn_token())
@@ -1359,6 +1759,14 @@ pub fn cm_close_voice_call(id: i32) {
     crate::ui_cm_interface::close_voice_call(id);
 }
 
+pub fn cm_handle_input_control_request(id: i32, grant: bool) {
+    crate::ui_cm_interface::handle_input_control_request(id, grant);
+}
+
+pub fn cm_clear_annotations(id: i32) {
+    crate::ui_cm_interface::clear_annotations(id);
+}
+
 pub fn set_voice_call_input_device(_is_cm: bool, _device: String) {
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     if _is_cm {
@@ -1412,6 +1820,18 @@ pub fn main_get_fingerprint() -> String {
     get_fingerprint()
 }
 
+pub fn main_get_old_fingerprint() -> String {
+    get_old_fingerprint()
+}
+
+pub fn main_fingerprint_to_sas(fingerprint: String) -> SyncReturn<String> {
+    SyncReturn(crate::common::fingerprint_to_sas(&fingerprint))
+}
+
+pub fn main_rotate_key_pair(grace_days: u32) {
+    rotate_key_pair(grace_days)
+}
+
 pub fn cm_get_clients_state() -> String {
     crate::ui_cm_interface::get_clients_state()
 }
@@ -1563,9 +1983,9 @@ pub fn session_send_mouse(session_id: SessionID, msg: String) {
     }
 }
 
-pub fn session_restart_remote_device(session_id: SessionID) {
+pub fn session_restart_remote_device(session_id: SessionID, safe_mode: bool, reconnect: bool) {
     if let Some(session) = sessions::get_session_by_session_id(&session_id) {
-        session.restart_remote_device();
+        session.restart_remote_device_with(safe_mode, reconnect);
     }
 }
 
@@ -1665,6 +2085,10 @@ pub fn main_set_unlock_pin(pin: String) -> SyncReturn<String> {
     SyncReturn(set_unlock_pin(pin))
 }
 
+pub fn main_verify_unlock_pin(pin: String) -> SyncReturn<bool> {
+    SyncReturn(verify_unlock_pin(pin))
+}
+
 pub fn main_check_mouse_time() {
     check_mouse_time();
 }
@@ -1686,14 +2110,42 @@ pub fn main_wol(id: String) {
     crate::lan::send_wol(id)
 }
 
+/// The peers which may wake `id`, in json.
+pub fn main_get_wol_relays(id: String) -> SyncReturn<String> {
+    SyncReturn(serde_json::to_string(&crate::client::wake_on_lan::relays(&id)).unwrap_or_default())
+}
+
+pub fn main_wake_through_peer(_id: String, _relay_id: String) -> String {
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    return wake_through_peer(_id, _relay_id);
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    return "".to_owned();
+}
+
 pub fn main_create_shortcut(_id: String) {
     #[cfg(windows)]
     create_shortcut(_id);
 }
 
-pub fn cm_send_chat(conn_id: i32, msg: String) {
+pub fn main_take_screenshot(_id: String, _display: i32) -> String {
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    return take_screenshot(_id, _display);
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    return "".to_owned();
+}
+
+pub fn cm_send_chat(conn_id: i32, id: u32, msg: String) {
     #[cfg(not(any(target_os = "ios")))]
-    crate::ui_cm_interface::send_chat(conn_id, msg);
+    crate::ui_cm_interface::send_chat(conn_id, id, msg);
+}
+
+// the error if the file can not be sent
+pub fn cm_send_chat_attachment(conn_id: i32, id: u32, path: String) -> String {
+    #[cfg(not(any(target_os = "ios")))]
+    if let Err(err) = crate::ui_cm_interface::send_chat_attachment(conn_id, id, path) {
+        return err.to_string();
+    }
+    "".to_owned()
 }
 
 pub fn cm_login_res(conn_id: i32, res: bool) {
@@ -1732,6 +2184,11 @@ pub fn cm_switch_permission(conn_id: i32, name: String, enabled: bool) {
     crate::ui_cm_interface::switch_permission(conn_id, name, enabled)
 }
 
+pub fn cm_save_permission_profile(conn_id: i32, save: bool) {
+    #[cfg(not(any(target_os = "ios")))]
+    crate::ui_cm_interface::save_permission_profile(conn_id, save)
+}
+
 pub fn cm_can_elevate() -> SyncReturn<bool> {
     SyncReturn(crate::ui_cm_interface::can_elevate())
 }
@@ -1907,6 +2364,33 @@ pub fn main_account_auth_result() -> String {
     account_auth_result()
 }
 
+pub fn main_idp_oidc_auth() {
+    crate::idp::oidc_auth();
+}
+
+pub fn main_idp_oidc_auth_cancel() {
+    crate::idp::oidc_auth_cancel();
+}
+
+pub fn main_idp_oidc_auth_result() -> String {
+    crate::idp::oidc_auth_result()
+}
+
+pub fn main_idp_ldap_sign_in(username: String, password: String) -> String {
+    match crate::idp::ldap_sign_in(&username, &password) {
+        Ok(()) => "".to_owned(),
+        Err(err) => err.to_string(),
+    }
+}
+
+pub fn main_idp_sign_out() {
+    crate::idp::sign_out();
+}
+
+pub fn main_idp_get_session() -> SyncReturn<String> {
+    SyncReturn(crate::idp::get_session())
+}
+
 pub fn main_on_main_window_close() {
     // may called more than one times
     #[cfg(windows)]
@@ -2291,10 +2775,74 @@ pub fn main_clear_trusted_devices() {
     clear_trusted_devices()
 }
 
+pub fn main_get_permission_profiles() -> String {
+    get_permission_profiles()
+}
+
+pub fn main_remove_permission_profiles(json: String) {
+    remove_permission_profiles(&json)
+}
+
+pub fn main_get_one_time_passwords() -> String {
+    get_one_time_passwords()
+}
+
+pub fn main_generate_one_time_passwords(
+    count: i32,
+    minutes: i32,
+    scope: String,
+    label: String,
+) -> String {
+    generate_one_time_passwords(count.max(0) as _, minutes.max(0) as _, scope, label)
+}
+
+pub fn main_remove_one_time_passwords(json: String) {
+    remove_one_time_passwords(&json)
+}
+
+pub fn main_get_trusted_keys() -> String {
+    get_trusted_keys()
+}
+
+pub fn main_remove_trusted_keys(json: String) {
+    remove_trusted_keys(&json)
+}
+
+// the error of the tls pins, empty if they are valid
+pub fn main_check_tls_pins(pins: String) -> SyncReturn<String> {
+    match hbb_common::tls_pin::parse(&pins) {
+        Ok(_) => SyncReturn("".to_owned()),
+        Err(err) => SyncReturn(err.to_string()),
+    }
+}
+
+// the error of the ip rules, empty if they are valid
+pub fn main_check_ip_rules(rules: String) -> SyncReturn<String> {
+    #[cfg(not(any(target_os = "ios")))]
+    if let Err(err) = crate::server::ip_rules::parse(&rules) {
+        return SyncReturn(err.to_string());
+    }
+    SyncReturn("".to_owned())
+}
+
+// the error of the schedule of the unattended access, empty if it is valid
+pub fn main_check_unattended_schedule(schedule: String) -> SyncReturn<String> {
+    #[cfg(not(any(target_os = "ios")))]
+    if let Err(err) = crate::server::unattended_schedule::parse(&schedule) {
+        return SyncReturn(err.to_string());
+    }
+    SyncReturn("".to_owned())
+}
+
 pub fn main_max_encrypt_len() -> SyncReturn<usize> {
     SyncReturn(max_encrypt_len())
 }
 
+// of the locked config, 0 for any
+pub fn main_password_min_length() -> SyncReturn<usize> {
+    SyncReturn(config::LockedConfig::password_min_length())
+}
+
 pub fn session_request_new_display_init_msgs(session_id: SessionID, display: usize) {
     if let Some(session) = sessions::get_session_by_session_id(&session_id) {
         session.request_init_msgs(display);
@@ -5,8 +5,10 @@ use serde_json::{Map, Value};
 #[cfg(feature = "flutter")]
 pub mod account;
 mod http_client;
+pub mod policy;
 pub mod record_upload;
 pub mod sync;
+pub mod view_settings;
 pub use http_client::create_http_client;
 pub use http_client::create_http_client_async;
 
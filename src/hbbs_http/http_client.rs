@@ -7,6 +7,23 @@ use reqwest::Client as AsyncClient;
 macro_rules! configure_http_client {
     ($builder:expr, $Client: ty) => {{
         let mut builder = $builder;
+        let pins = hbb_common::tls_pin::pins();
+        let pinned = pins.is_some();
+        if let Some(pins) = pins {
+            // the rustls of reqwest, 0.23 as the one of hbb_common
+            builder = builder.use_preconfigured_tls(hbb_common::tls_pin::client_config(pins));
+        }
+        // trusts no server rather than the ones not pinned
+        let fallback = || {
+            if pinned {
+                <$Client>::builder()
+                    .tls_built_in_root_certs(false)
+                    .build()
+                    .unwrap_or_else(|_| <$Client>::new())
+            } else {
+                <$Client>::new()
+            }
+        };
         let client = if let Some(conf) = Config::get_socks() {
             let proxy_result = Proxy::from_conf(&conf, None);
 
@@ -37,24 +54,24 @@ macro_rules! configure_http_client {
                             }
                             builder.build().unwrap_or_else(|e| {
                                 info!("Failed to create a proxied client: {}", e);
-                                <$Client>::new()
+                                fallback()
                             })
                         }
                         Err(e) => {
                             info!("Failed to set up proxy: {}", e);
-                            <$Client>::new()
+                            fallback()
                         }
                     }
                 }
                 Err(e) => {
                     info!("Failed to configure proxy: {}", e);
-                    <$Client>::new()
+                    fallback()
                 }
             }
         } else {
             builder.build().unwrap_or_else(|e| {
                 info!("Failed to create a client: {}", e);
-                <$Client>::new()
+                fallback()
             })
         };
 
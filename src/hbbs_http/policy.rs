@@ -0,0 +1,341 @@
+// The policy of the management server, the features allowed to the roles of the peers.
+//
+// The policy comes along the heartbeat as the base64 of a json signed with the key of the
+// rendezvous server, so that neither the local user nor the network can loosen it by editing the
+// config. The newest one verified is kept and an older one is refused, which stops a looser policy
+// from being replayed. A policy kept which no longer verifies is not dropped but taken as the
+// lockdown, every feature locked off and all the data blocked, until a fresh one verifies. The
+// features of a role are taken away from its peers at login, and if the policy is mandatory, the
+// connection manager can not switch them on again. The rules of the data loss prevention of the
+// policy apply to all the peers, and so does its recording, which records every incoming session
+// whatever the options of this side.
+
+use std::sync::{Mutex, RwLock};
+
+use hbb_common::{
+    bail,
+    config::{self, Config, LocalConfig},
+    dlp, log,
+    sodiumoxide::crypto::sign,
+    ResultType,
+};
+use serde::Deserialize;
+
+const KEY: &str = "policy";
+// the permissions of the cm a mandatory policy locks
+const LOCKABLE: [&str; 3] = ["file", "clipboard", "terminal"];
+
+lazy_static::lazy_static! {
+    // the policy signed and the one verified from it, the lockdown if it does not verify
+    static ref CACHE: Mutex<Option<(String, Policy)>> = Default::default();
+    // the recording of the policy kept
+    static ref RECORDING: RwLock<Recording> = Default::default();
+}
+
+/// The features of a role, not restricted if None.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Features {
+    pub file_transfer: Option<bool>,
+    pub clipboard: Option<bool>,
+    pub privacy_mode: Option<bool>,
+    pub terminal: Option<bool>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+struct Role {
+    #[serde(default)]
+    name: String,
+    // the ids of the peers
+    #[serde(default)]
+    peers: Vec<String>,
+    // the names verified by the identity provider
+    #[serde(default)]
+    users: Vec<String>,
+    #[serde(default)]
+    features: Features,
+}
+
+/// The recording of the incoming sessions.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct Recording {
+    #[serde(default)]
+    pub enforced: bool,
+    // the directory of the recordings, the one of the options if empty
+    #[serde(default)]
+    pub dir: String,
+    // to the api server as well
+    #[serde(default)]
+    pub upload: bool,
+    // the recordings older are removed, none if 0
+    #[serde(default)]
+    pub retention_days: u64,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Policy {
+    #[serde(default)]
+    pub version: i64,
+    #[serde(default)]
+    pub mandatory: bool,
+    // the first role matching a peer is its role
+    #[serde(default)]
+    roles: Vec<Role>,
+    // the role of the peers matching none
+    #[serde(default)]
+    default_role: String,
+    #[serde(default)]
+    pub dlp: Vec<dlp::Rule>,
+    #[serde(default)]
+    pub recording: Recording,
+}
+
+/// The role of a peer in the policy.
+#[derive(Debug, Default, Clone)]
+pub struct RolePolicy {
+    pub role: String,
+    pub features: Features,
+    pub mandatory: bool,
+}
+
+impl Policy {
+    // The most restrictive, of a policy kept which does not verify.
+    fn lockdown() -> Self {
+        const ROLE: &str = "lockdown";
+        Policy {
+            version: 0,
+            mandatory: true,
+            roles: vec![Role {
+                name: ROLE.to_owned(),
+                features: Features {
+                    file_transfer: Some(false),
+                    clipboard: Some(false),
+                    privacy_mode: Some(false),
+                    terminal: Some(false),
+                },
+                ..Default::default()
+            }],
+            default_role: ROLE.to_owned(),
+            // of all the files and the clipboard text in both directions
+            dlp: vec![dlp::Rule {
+                name: ROLE.to_owned(),
+                action: dlp::Action::Block,
+                direction: None,
+                extensions: vec![],
+                min_size: 0,
+                pattern: String::new(),
+            }],
+            recording: Recording {
+                enforced: true,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn role(&self, peer_id: &str, identity: Option<&str>) -> Option<RolePolicy> {
+        let role = self
+            .roles
+            .iter()
+            .find(|r| {
+                r.peers.iter().any(|p| p == peer_id)
+                    || identity
+                        .map(|i| r.users.iter().any(|u| u.eq_ignore_ascii_case(i)))
+                        .unwrap_or(false)
+            })
+            .or_else(|| {
+                self.roles
+                    .iter()
+                    .find(|r| !self.default_role.is_empty() && r.name == self.default_role)
+            })?;
+        Some(RolePolicy {
+            role: role.name.clone(),
+            features: role.features.clone(),
+            mandatory: self.mandatory,
+        })
+    }
+}
+
+impl RolePolicy {
+    /// Whether the feature of the permission `name` of the cm, or "privacy_mode" or "terminal",
+    /// is allowed.
+    pub fn allows(&self, name: &str) -> bool {
+        let allowed = match name {
+            "file" => self.features.file_transfer,
+            "clipboard" => self.features.clipboard,
+            "privacy_mode" => self.features.privacy_mode,
+            "terminal" => self.features.terminal,
+            _ => None,
+        };
+        allowed.unwrap_or(true)
+    }
+
+    /// The permissions the cm can not switch on.
+    pub fn locked(&self) -> Vec<String> {
+        if !self.mandatory {
+            return vec![];
+        }
+        LOCKABLE
+            .iter()
+            .filter(|name| !self.allows(name))
+            .map(|name| name.to_string())
+            .collect()
+    }
+}
+
+fn public_key() -> Option<sign::PublicKey> {
+    let key = Config::get_option("key");
+    crate::common::get_rs_pk(if key.is_empty() {
+        config::RS_PUB_KEY
+    } else {
+        &key
+    })
+}
+
+fn decode(signed: &str, pk: &sign::PublicKey) -> ResultType<Policy> {
+    let data = crate::decode64(signed)?;
+    let Ok(data) = sign::verify(&data, pk) else {
+        bail!("Signature mismatch of the policy");
+    };
+    Ok(serde_json::from_slice(&data)?)
+}
+
+fn verify(signed: &str) -> ResultType<Policy> {
+    let Some(pk) = public_key() else {
+        bail!("Invalid public key of the server");
+    };
+    decode(signed, &pk)
+}
+
+/// The policy kept, None if none, the lockdown if it no longer verifies.
+pub fn current() -> Option<Policy> {
+    let signed = LocalConfig::get_option(KEY);
+    if signed.is_empty() {
+        return None;
+    }
+    let mut cache = CACHE.lock().unwrap();
+    if let Some((s, policy)) = cache.as_ref() {
+        if *s == signed {
+            return Some(policy.clone());
+        }
+    }
+    let policy = verify(&signed).unwrap_or_else(|err| {
+        log::error!("Failed to verify the policy kept, locked down: {}", err);
+        Policy::lockdown()
+    });
+    *cache = Some((signed, policy.clone()));
+    Some(policy)
+}
+
+#[inline]
+pub fn version() -> i64 {
+    current().map(|p| p.version).unwrap_or(0)
+}
+
+/// Keeps the policy `signed` by the server, unless it is older than the one kept.
+pub fn update(signed: &str) -> ResultType<()> {
+    if signed == LocalConfig::get_option(KEY) {
+        return Ok(());
+    }
+    let policy = verify(signed)?;
+    let version = version();
+    if policy.version < version {
+        bail!(
+            "The policy {} is older than the one kept, {}",
+            policy.version,
+            version
+        );
+    }
+    log::info!("Policy {} kept", policy.version);
+    LocalConfig::set_option(KEY.to_owned(), signed.to_owned());
+    apply_policy(policy);
+    Ok(())
+}
+
+fn apply_policy(policy: Policy) {
+    dlp::set_rules(policy.dlp);
+    super::record_upload::set_enable(policy.recording.enforced && policy.recording.upload);
+    let mut recording = RECORDING.write().unwrap();
+    if *recording != policy.recording {
+        log::info!("Recording of the policy: {:?}", policy.recording);
+        *recording = policy.recording;
+    }
+}
+
+/// Applies the rules of the data loss prevention and the recording of the policy kept.
+pub fn apply() {
+    apply_policy(current().unwrap_or_default());
+}
+
+/// The recording of the policy, not enforced if none.
+pub fn recording() -> Recording {
+    RECORDING.read().unwrap().clone()
+}
+
+#[inline]
+pub fn recording_enforced() -> bool {
+    RECORDING.read().unwrap().enforced
+}
+
+/// The role of the peer `peer_id`, signed in as `identity` if any, None if not restricted.
+pub fn role_of(peer_id: &str, identity: Option<&str>) -> Option<RolePolicy> {
+    current()?.role(peer_id, identity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_and_role() {
+        let (pk, sk) = sign::gen_keypair();
+        let json = r#"{
+            "version": 3,
+            "mandatory": true,
+            "default_role": "guest",
+            "roles": [
+                {"name": "admin", "users": ["Alice"], "features": {}},
+                {"name": "support", "peers": ["123456789"], "features": {"file_transfer": false}},
+                {"name": "guest", "features": {"file_transfer": false, "clipboard": false, "privacy_mode": false}}
+            ],
+            "dlp": [{"name": "executables", "action": "block", "extensions": ["exe"]}],
+            "recording": {"enforced": true, "retention_days": 30}
+        }"#;
+        let signed = crate::encode64(sign::sign(json.as_bytes(), &sk));
+        let policy = decode(&signed, &pk).unwrap();
+        assert_eq!(policy.version, 3);
+        assert_eq!(policy.dlp[0].action, dlp::Action::Block);
+        assert!(policy.recording.enforced && !policy.recording.upload);
+        assert_eq!(policy.recording.retention_days, 30);
+        let (other_pk, _) = sign::gen_keypair();
+        assert!(decode(&signed, &other_pk).is_err());
+
+        let admin = policy.role("123456789", Some("alice")).unwrap();
+        assert_eq!(admin.role, "admin");
+        assert!(admin.allows("file") && admin.locked().is_empty());
+        let support = policy.role("123456789", None).unwrap();
+        assert!(!support.allows("file") && support.allows("clipboard"));
+        assert_eq!(support.locked(), vec!["file"]);
+        let guest = policy.role("987654321", Some("bob")).unwrap();
+        assert!(!guest.allows("privacy_mode") && guest.allows("terminal"));
+        assert_eq!(guest.locked(), vec!["file", "clipboard"]);
+
+        let policy: Policy = serde_json::from_str(r#"{"roles": [{"name": "a"}]}"#).unwrap();
+        assert!(policy.role("987654321", None).is_none());
+    }
+
+    #[test]
+    fn test_lockdown() {
+        let policy = Policy::lockdown();
+        let role = policy.role("123456789", Some("alice")).unwrap();
+        for name in ["file", "clipboard", "privacy_mode", "terminal"] {
+            assert!(!role.allows(name));
+        }
+        assert_eq!(role.locked(), LOCKABLE);
+        assert!(policy.recording.enforced);
+        // any fresh policy verified replaces it
+        assert_eq!(policy.version, 0);
+        // a rule of neither extensions nor a pattern matches all
+        let rule = &policy.dlp[0];
+        assert_eq!(rule.action, dlp::Action::Block);
+        assert!(rule.direction.is_none() && rule.extensions.is_empty() && rule.pattern.is_empty());
+    }
+}
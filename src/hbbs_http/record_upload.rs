@@ -24,6 +24,10 @@ pub fn is_enable() -> bool {
     ENABLE.lock().unwrap().clone()
 }
 
+pub fn set_enable(v: bool) {
+    *ENABLE.lock().unwrap() = v;
+}
+
 pub fn run(rx: Receiver<RecordState>) {
     let mut uploader = RecordUploader {
         client: create_http_client(),
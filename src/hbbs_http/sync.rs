@@ -51,6 +51,7 @@ pub struct StrategyOptions {
 #[cfg(not(any(target_os = "ios")))]
 #[tokio::main(flavor = "current_thread")]
 async fn start_hbbs_sync_async() {
+    super::policy::apply();
     let mut interval = crate::rustdesk_interval(tokio::time::interval_at(
         Instant::now() + TIME_CONN,
         TIME_CONN,
@@ -130,6 +131,7 @@ async fn start_hbbs_sync_async() {
                 }
                 let modified_at = LocalConfig::get_option("strategy_timestamp").parse::<i64>().unwrap_or(0);
                 v["modified_at"] = json!(modified_at);
+                v["policy_version"] = json!(super::policy::version());
                 if let Ok(s) = crate::post_request(url.clone(), v.to_string(), "").await {
                     if let Ok(mut rsp) = serde_json::from_str::<HashMap::<&str, Value>>(&s) {
                         if let Some(conns)  = rsp.remove("disconnect") {
@@ -144,6 +146,11 @@ async fn start_hbbs_sync_async() {
                                 }
                             }
                         }
+                        if let Some(Value::String(policy)) = rsp.remove("policy") {
+                            if let Err(err) = super::policy::update(&policy) {
+                                hbb_common::log::error!("Failed to update the policy: {}", err);
+                            }
+                        }
                         if let Some(strategy) = rsp.remove("strategy") {
                             if let Ok(strategy) = serde_json::from_value::<StrategyOptions>(strategy) {
                                 handle_config_options(strategy.config_options);
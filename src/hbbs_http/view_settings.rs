@@ -0,0 +1,231 @@
+// The view settings of the peers kept in the account, so the ones of a peer follow the user to the
+// other devices signed in: the view style and the scroll style, the image quality, the keyboard
+// mode, and the display and the canvas the flutter ui keeps, with the zoom and the scroll in it.
+//
+// The settings of a peer are pulled when a session to it starts and pushed when it ends. The newer
+// ones win, by the time they were pushed, the one of the last sync is kept in the config of the
+// peer.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use hbb_common::{
+    bail,
+    config::{keys, Config, LocalConfig, PeerConfig},
+    log, ResultType,
+};
+use serde_derive::{Deserialize, Serialize};
+
+use super::create_http_client_async;
+use crate::client::LoginConfigHandler;
+
+// the keys of the flutter ui synced
+const UI_KEYS: [&str; 2] = ["canvas", "last-display"];
+// the time of the settings synced last, in the options of the peer
+const SYNCED_AT: &str = "view-settings-synced-at";
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(6);
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ViewSettings {
+    #[serde(default)]
+    pub view_style: String,
+    #[serde(default)]
+    pub scroll_style: String,
+    #[serde(default)]
+    pub image_quality: String,
+    #[serde(default)]
+    pub custom_image_quality: Vec<i32>,
+    #[serde(default)]
+    pub keyboard_mode: String,
+    #[serde(default)]
+    pub ui: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Synced {
+    #[serde(default)]
+    updated_at: i64,
+    #[serde(default)]
+    settings: ViewSettings,
+}
+
+impl ViewSettings {
+    pub fn from_config(config: &PeerConfig) -> Self {
+        Self {
+            view_style: config.view_style.clone(),
+            scroll_style: config.scroll_style.clone(),
+            image_quality: config.image_quality.clone(),
+            custom_image_quality: config.custom_image_quality.clone(),
+            keyboard_mode: config.keyboard_mode.clone(),
+            ui: UI_KEYS
+                .iter()
+                .filter_map(|k| Some((k.to_string(), config.ui_flutter.get(*k)?.clone())))
+                .collect(),
+        }
+    }
+
+    /// Applies the settings to the config of the peer, true if it changed. The ones empty are
+    /// left as they are.
+    pub fn apply(&self, config: &mut PeerConfig) -> bool {
+        let mut changed = false;
+        let mut set = |field: &mut String, value: &String| {
+            if !value.is_empty() && field != value {
+                *field = value.clone();
+                changed = true;
+            }
+        };
+        set(&mut config.view_style, &self.view_style);
+        set(&mut config.scroll_style, &self.scroll_style);
+        set(&mut config.image_quality, &self.image_quality);
+        set(&mut config.keyboard_mode, &self.keyboard_mode);
+        for k in UI_KEYS {
+            if let Some(v) = self.ui.get(k).filter(|v| !v.is_empty()) {
+                set(config.ui_flutter.entry(k.to_owned()).or_default(), v);
+            }
+        }
+        if !self.custom_image_quality.is_empty()
+            && config.custom_image_quality != self.custom_image_quality
+        {
+            config.custom_image_quality = self.custom_image_quality.clone();
+            changed = true;
+        }
+        changed
+    }
+}
+
+/// If the view settings are synced, with the account signed in.
+pub fn enabled() -> bool {
+    LocalConfig::get_bool_option(keys::OPTION_SYNC_VIEW_SETTINGS)
+        && !LocalConfig::get_option("access_token").is_empty()
+}
+
+fn url(id: &str) -> String {
+    format!(
+        "{}/api/peer-settings/{}",
+        crate::get_api_server(
+            Config::get_option("api-server"),
+            Config::get_option("custom-rendezvous-server"),
+        ),
+        id
+    )
+}
+
+fn synced_at(config: &PeerConfig) -> i64 {
+    config
+        .options
+        .get(SYNCED_AT)
+        .and_then(|t| t.parse().ok())
+        .unwrap_or(0)
+}
+
+async fn pull(id: &str) -> ResultType<Option<Synced>> {
+    let resp = create_http_client_async()
+        .get(url(id))
+        .bearer_auth(LocalConfig::get_option("access_token"))
+        .timeout(TIMEOUT)
+        .send()
+        .await?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !resp.status().is_success() {
+        bail!("Failed to pull the view settings: {}", resp.status());
+    }
+    Ok(Some(resp.json().await?))
+}
+
+async fn push(id: &str, synced: &Synced) -> ResultType<()> {
+    let resp = create_http_client_async()
+        .put(url(id))
+        .bearer_auth(LocalConfig::get_option("access_token"))
+        .json(synced)
+        .timeout(TIMEOUT)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        bail!("Failed to push the view settings: {}", resp.status());
+    }
+    Ok(())
+}
+
+/// Applies the settings of the peer in the account to the session, if they are newer than the
+/// ones synced last.
+pub async fn pull_session(id: String, lc: Arc<RwLock<LoginConfigHandler>>) {
+    let synced = match pull(&id).await {
+        Ok(Some(synced)) => synced,
+        Ok(None) => return,
+        Err(err) => {
+            log::warn!("{}", err);
+            return;
+        }
+    };
+    let mut lc = lc.write().unwrap();
+    let mut config = lc.load_config();
+    if synced.updated_at <= synced_at(&config) {
+        return;
+    }
+    log::info!("The view settings of {} pulled", id);
+    synced.settings.apply(&mut config);
+    config
+        .options
+        .insert(SYNCED_AT.to_owned(), synced.updated_at.to_string());
+    lc.save_config(config);
+}
+
+/// Pushes the settings of the peer of the session ended to the account.
+pub async fn push_session(id: String, lc: Arc<RwLock<LoginConfigHandler>>) {
+    let synced = Synced {
+        updated_at: hbb_common::get_time(),
+        settings: ViewSettings::from_config(&lc.read().unwrap().load_config()),
+    };
+    match push(&id, &synced).await {
+        Ok(()) => lc
+            .write()
+            .unwrap()
+            .set_option(SYNCED_AT.to_owned(), synced.updated_at.to_string()),
+        Err(err) => log::warn!("{}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let mut config = PeerConfig::default();
+        config.keyboard_mode = "map".to_owned();
+        config
+            .ui_flutter
+            .insert("canvas".to_owned(), "{\"scale\":1.5}".to_owned());
+        config
+            .ui_flutter
+            .insert("wm_RemoteDesktop".to_owned(), "{}".to_owned());
+        let settings = ViewSettings::from_config(&config);
+        assert_eq!(settings.ui.len(), 1);
+        let mut other = PeerConfig::default();
+        other.keyboard_mode = "translate".to_owned();
+        assert!(settings.apply(&mut other));
+        assert_eq!(other.keyboard_mode, "map");
+        assert_eq!(
+            other.ui_flutter.get("canvas"),
+            config.ui_flutter.get("canvas")
+        );
+        // the frame of the window is not synced
+        assert!(other.ui_flutter.get("wm_RemoteDesktop").is_none());
+        assert!(!settings.apply(&mut other));
+        // nor are the ones not set
+        let settings = ViewSettings {
+            view_style: "original".to_owned(),
+            ..Default::default()
+        };
+        other.view_style = "adaptive".to_owned();
+        other.image_quality = "best".to_owned();
+        assert!(settings.apply(&mut other));
+        assert_eq!(other.view_style, "original");
+        assert_eq!(other.image_quality, "best");
+        assert_eq!(other.keyboard_mode, "map");
+    }
+}
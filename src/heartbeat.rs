@@ -0,0 +1,118 @@
+// Heartbeats of the sessions and the detection of the dead ones.
+//
+// The controlled side sends a test delay every second once the last one is answered, which also
+// drives the video quality, and the controlling side sends its own heartbeats, echoed by the
+// controlled side, so that a silent link is noticed on both sides whichever direction fails. A
+// session is degraded once the heartbeats are missed a few times, which is shown on the
+// controlling side and moves a direct session to its backup path, and it is closed once nothing is
+// received for the timeout.
+
+use std::time::{Duration, Instant};
+
+use hbb_common::{
+    config::{
+        keys::{OPTION_KEEP_ALIVE_INTERVAL, OPTION_KEEP_ALIVE_RETRIES, OPTION_KEEP_ALIVE_TIMEOUT},
+        Config,
+    },
+    get_time,
+    message_proto::{Message, TestDelay},
+};
+
+// in seconds
+const DEFAULT_INTERVAL: u64 = 1;
+const MAX_INTERVAL: u64 = 60;
+const DEFAULT_RETRIES: u64 = 3;
+const MAX_RETRIES: u64 = 100;
+// in seconds
+const DEFAULT_TIMEOUT: u64 = 30;
+
+pub struct Heartbeat {
+    interval: Duration,
+    retries: u32,
+    timeout: Duration,
+    degraded: bool,
+    last_sent: Instant,
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        let interval = option(OPTION_KEEP_ALIVE_INTERVAL, DEFAULT_INTERVAL).min(MAX_INTERVAL);
+        let retries = option(OPTION_KEEP_ALIVE_RETRIES, DEFAULT_RETRIES).min(MAX_RETRIES);
+        // a session is degraded for a while before it is closed
+        let timeout =
+            option(OPTION_KEEP_ALIVE_TIMEOUT, DEFAULT_TIMEOUT).max(interval * (retries + 1));
+        Self {
+            interval: Duration::from_secs(interval),
+            retries: retries as _,
+            timeout: Duration::from_secs(timeout),
+            degraded: false,
+            last_sent: Instant::now(),
+        }
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.degraded
+    }
+
+    /// The new degraded state, if nothing received for `silence` changes it.
+    pub fn update(&mut self, silence: Duration) -> Option<bool> {
+        let degraded = silence >= self.interval * self.retries;
+        if degraded == self.degraded {
+            return None;
+        }
+        self.degraded = degraded;
+        Some(degraded)
+    }
+
+    pub fn is_dead(&self, silence: Duration) -> bool {
+        silence >= self.timeout
+    }
+
+    /// The heartbeat of the controlling side, if it is time to send one.
+    pub fn next(&mut self) -> Option<Message> {
+        if self.last_sent.elapsed() < self.interval {
+            return None;
+        }
+        self.last_sent = Instant::now();
+        let mut msg_out = Message::new();
+        msg_out.set_test_delay(TestDelay {
+            time: get_time(),
+            from_client: true,
+            ..Default::default()
+        });
+        Some(msg_out)
+    }
+}
+
+fn option(name: &str, default: u64) -> u64 {
+    Config::get_option(name)
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|v| *v > 0)
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update() {
+        let mut heartbeat = Heartbeat {
+            interval: Duration::from_secs(2),
+            retries: 3,
+            timeout: Duration::from_secs(30),
+            degraded: false,
+            last_sent: Instant::now(),
+        };
+        assert_eq!(heartbeat.update(Duration::from_secs(5)), None);
+        assert_eq!(heartbeat.update(Duration::from_secs(6)), Some(true));
+        assert_eq!(heartbeat.update(Duration::from_secs(7)), None);
+        assert!(heartbeat.is_degraded());
+        assert!(!heartbeat.is_dead(Duration::from_secs(29)));
+        assert!(heartbeat.is_dead(Duration::from_secs(30)));
+        assert_eq!(heartbeat.update(Duration::ZERO), Some(false));
+        assert!(heartbeat.next().is_none());
+    }
+}
@@ -0,0 +1,607 @@
+// Logins of the controlling side with an OIDC provider or LDAP, besides the account of the api
+// server, and the checks of these identities on the controlled side.
+//
+// With OIDC, the controlling side signs in with the device authorization grant. With LDAP, it sends
+// the username and the password with the password grant to the token endpoint of the same issuer,
+// which checks them against its directory, e.g. Keycloak or Dex federating LDAP. Either way the
+// controlling side gets an id token signed by the issuer, keeps it refreshed with the refresh
+// token, and never keeps or sends the password elsewhere. A controlled side requiring an identity
+// names the provider in its hash, and only then is the id token attached to the login request. It
+// verifies the signature with the keys of the issuer, and then accepts the members of the group
+// required only.
+//
+// An id token is not bound to the session, the controlled side may replay it elsewhere within its
+// lifetime. So the identity is checked on top of the password or the click, never instead.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use hbb_common::{
+    bail,
+    base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine},
+    config::{keys, Config, LocalConfig},
+    log,
+    message_proto::Identity,
+    ResultType,
+};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::hbbs_http::create_http_client;
+
+pub const OIDC: &str = "oidc";
+pub const LDAP: &str = "ldap";
+
+// the local option of the session signed in
+const SESSION: &str = "idp-session";
+const SCOPE: &str = "openid profile email offline_access";
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+// in seconds, the id token is refreshed this long before it expires, and accepted this long after
+const LEEWAY: i64 = 60;
+const JWKS_TTL: Duration = Duration::from_secs(3600);
+
+lazy_static::lazy_static! {
+    static ref AUTH: RwLock<AuthResult> = Default::default();
+    // the issuer, when the keys are fetched, and the keys
+    static ref JWKS: Mutex<Option<(String, Instant, Vec<Jwk>)>> = Default::default();
+}
+
+// of the sign-in running, the others stop polling
+static AUTH_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Session {
+    provider: String,
+    // who is signed in, to be shown
+    name: String,
+    #[serde(default)]
+    id_token: String,
+    #[serde(default)]
+    refresh_token: String,
+    // of the id token, in seconds
+    #[serde(default)]
+    expires_at: i64,
+}
+
+impl Session {
+    fn load() -> Option<Self> {
+        serde_json::from_str(&LocalConfig::get_option(SESSION)).ok()
+    }
+
+    fn store(&self) {
+        LocalConfig::set_option(
+            SESSION.to_owned(),
+            serde_json::to_string(self).unwrap_or_default(),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AuthResult {
+    running: bool,
+    // to be entered at `url`
+    user_code: String,
+    url: String,
+    // who is signed in, once done
+    name: String,
+    failed_msg: String,
+}
+
+#[derive(Deserialize)]
+struct Discovery {
+    issuer: String,
+    #[serde(default)]
+    device_authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct DeviceAuthorization {
+    device_code: String,
+    user_code: String,
+    #[serde(alias = "verification_url")]
+    verification_uri: String,
+    #[serde(default)]
+    verification_uri_complete: String,
+    expires_in: u64,
+    #[serde(default)]
+    interval: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    #[serde(default)]
+    id_token: String,
+    #[serde(default)]
+    refresh_token: String,
+    #[serde(default)]
+    error: String,
+    #[serde(default)]
+    error_description: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct Jwk {
+    kty: String,
+    #[serde(default)]
+    kid: String,
+    #[serde(default)]
+    n: String,
+    #[serde(default)]
+    e: String,
+    #[serde(default)]
+    crv: String,
+    #[serde(default)]
+    x: String,
+    #[serde(default)]
+    y: String,
+}
+
+#[derive(Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+/// The provider and the name signed in with, as json, empty if not signed in.
+pub fn get_session() -> String {
+    match Session::load() {
+        Some(s) => serde_json::json!({ "provider": s.provider, "name": s.name }).to_string(),
+        None => "".to_owned(),
+    }
+}
+
+pub fn sign_out() {
+    oidc_auth_cancel();
+    LocalConfig::set_option(SESSION.to_owned(), "".to_owned());
+}
+
+/// Starts signing in with the device authorization grant of the OIDC provider, whose progress is
+/// polled with `oidc_auth_result`.
+pub fn oidc_auth() {
+    let id = AUTH_ID.fetch_add(1, Ordering::SeqCst) + 1;
+    *AUTH.write().unwrap() = AuthResult {
+        running: true,
+        ..Default::default()
+    };
+    std::thread::spawn(move || {
+        let res = oidc_auth_task(id);
+        update_auth(id, |r| {
+            if let Err(err) = res {
+                log::error!("Failed to sign in with OIDC: {}", err);
+                r.failed_msg = err.to_string();
+            }
+            r.running = false;
+        });
+    });
+}
+
+pub fn oidc_auth_cancel() {
+    AUTH_ID.fetch_add(1, Ordering::SeqCst);
+    AUTH.write().unwrap().running = false;
+}
+
+pub fn oidc_auth_result() -> String {
+    serde_json::to_string(&*AUTH.read().unwrap()).unwrap_or_default()
+}
+
+fn update_auth(id: u64, f: impl FnOnce(&mut AuthResult)) {
+    let mut auth = AUTH.write().unwrap();
+    if AUTH_ID.load(Ordering::SeqCst) == id {
+        f(&mut auth);
+    }
+}
+
+fn oidc_auth_task(id: u64) -> ResultType<()> {
+    let (issuer, client_id) = oidc_client()?;
+    let discovery = discover(&issuer)?;
+    if discovery.device_authorization_endpoint.is_empty() {
+        bail!("The OIDC provider does not support the device authorization grant");
+    }
+    let device: DeviceAuthorization = create_http_client()
+        .post(&discovery.device_authorization_endpoint)
+        .form(&[("client_id", client_id.as_str()), ("scope", SCOPE)])
+        .send()?
+        .error_for_status()?
+        .json()?;
+    update_auth(id, |r| {
+        r.user_code = device.user_code.clone();
+        r.url = if device.verification_uri_complete.is_empty() {
+            device.verification_uri.clone()
+        } else {
+            device.verification_uri_complete.clone()
+        };
+    });
+    let deadline = Instant::now() + Duration::from_secs(device.expires_in);
+    let mut interval = device.interval.max(5);
+    while Instant::now() < deadline {
+        std::thread::sleep(Duration::from_secs(interval));
+        if AUTH_ID.load(Ordering::SeqCst) != id {
+            return Ok(());
+        }
+        let res = request_token(
+            &discovery.token_endpoint,
+            &[
+                ("grant_type", DEVICE_CODE_GRANT),
+                ("device_code", &device.device_code),
+                ("client_id", &client_id),
+            ],
+        )?;
+        match res.error.as_str() {
+            "" => {
+                let session = token_session(OIDC, res, "")?;
+                log::info!("Signed in with OIDC as {}", session.name);
+                session.store();
+                update_auth(id, |r| r.name = session.name);
+                return Ok(());
+            }
+            "authorization_pending" => {}
+            "slow_down" => interval += 5,
+            _ => bail!("{} {}", res.error, res.error_description),
+        }
+    }
+    bail!("timeout")
+}
+
+/// Refreshes the id token about to expire, blocking.
+pub fn refresh() {
+    let Some(session) = Session::load() else {
+        return;
+    };
+    if session.refresh_token.is_empty()
+        || session.expires_at - LEEWAY > hbb_common::get_time() / 1000
+    {
+        return;
+    }
+    let res = (|| -> ResultType<Session> {
+        let (issuer, client_id) = oidc_client()?;
+        let discovery = discover(&issuer)?;
+        let res = request_token(
+            &discovery.token_endpoint,
+            &[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", &session.refresh_token),
+                ("client_id", &client_id),
+            ],
+        )?;
+        if !res.error.is_empty() {
+            bail!("{} {}", res.error, res.error_description);
+        }
+        token_session(&session.provider, res, &session.refresh_token)
+    })();
+    match res {
+        Ok(session) => session.store(),
+        Err(err) => log::error!("Failed to refresh the id token: {}", err),
+    }
+}
+
+/// Signs in with the LDAP credentials checked by the OIDC issuer, blocking.
+pub fn ldap_sign_in(username: &str, password: &str) -> ResultType<()> {
+    if username.is_empty() || password.is_empty() {
+        bail!("Empty LDAP username or password");
+    }
+    let (issuer, client_id) = oidc_client()?;
+    if !issuer.starts_with("https://") {
+        bail!("The password is sent to an OIDC issuer of https only");
+    }
+    let discovery = discover(&issuer)?;
+    let res = request_token(
+        &discovery.token_endpoint,
+        &[
+            ("grant_type", "password"),
+            ("username", username),
+            ("password", password),
+            ("client_id", &client_id),
+            ("scope", SCOPE),
+        ],
+    )?;
+    if !res.error.is_empty() {
+        bail!("{} {}", res.error, res.error_description);
+    }
+    let session = token_session(LDAP, res, "")?;
+    log::info!("Signed in with LDAP as {}", session.name);
+    session.store();
+    Ok(())
+}
+
+/// The identity at `provider` to be attached to the login request, None if not signed in there.
+pub fn identity(provider: &str) -> Option<Identity> {
+    let session = Session::load()?;
+    if session.provider != provider {
+        return None;
+    }
+    Some(Identity {
+        provider: session.provider,
+        token: session.id_token,
+        ..Default::default()
+    })
+}
+
+/// The identity provider required of the incoming connections, empty for none.
+pub fn required_provider() -> String {
+    Config::get_option(keys::OPTION_IDENTITY_PROVIDER)
+        .trim()
+        .to_lowercase()
+}
+
+/// Who `identity` is, once verified at the provider required and found in the group required,
+/// blocking.
+pub fn verify(identity: &Identity) -> ResultType<String> {
+    let provider = required_provider();
+    if identity.provider != provider {
+        bail!("Sign-in with {} required", provider.to_uppercase());
+    }
+    if provider != OIDC && provider != LDAP {
+        bail!("Unsupported identity provider: {}", provider);
+    }
+    let (issuer, client_id) = oidc_client()?;
+    let claims = verify_id_token(&identity.token, &issuer, &client_id)?;
+    let (name, groups) = (claims_name(&claims), claim_strings(&claims["groups"]));
+    let group = Config::get_option(keys::OPTION_IDENTITY_GROUP);
+    if !group.is_empty() && !in_group(&groups, &group) {
+        bail!("{} is not in the group {}", name, group);
+    }
+    Ok(name)
+}
+
+fn oidc_client() -> ResultType<(String, String)> {
+    let issuer = Config::get_option(keys::OPTION_OIDC_ISSUER);
+    let client_id = Config::get_option(keys::OPTION_OIDC_CLIENT_ID);
+    if issuer.is_empty() || client_id.is_empty() {
+        bail!("The OIDC issuer or client id is not set");
+    }
+    Ok((issuer.trim_end_matches('/').to_owned(), client_id))
+}
+
+fn discover(issuer: &str) -> ResultType<Discovery> {
+    let discovery: Discovery = create_http_client()
+        .get(format!("{}/.well-known/openid-configuration", issuer))
+        .send()?
+        .error_for_status()?
+        .json()?;
+    if discovery.issuer.trim_end_matches('/') != issuer {
+        bail!("The issuer {} does not match {}", discovery.issuer, issuer);
+    }
+    Ok(discovery)
+}
+
+fn request_token(token_endpoint: &str, form: &[(&str, &str)]) -> ResultType<TokenResponse> {
+    // the errors come with the status 400
+    Ok(create_http_client()
+        .post(token_endpoint)
+        .form(form)
+        .send()?
+        .json()?)
+}
+
+fn token_session(provider: &str, res: TokenResponse, refresh_token: &str) -> ResultType<Session> {
+    if res.id_token.is_empty() {
+        bail!("No id token is returned");
+    }
+    let claims = jwt_part(&res.id_token, 1)?;
+    Ok(Session {
+        provider: provider.to_owned(),
+        name: claims_name(&claims),
+        expires_at: claims["exp"].as_i64().unwrap_or_default(),
+        refresh_token: if res.refresh_token.is_empty() {
+            refresh_token.to_owned()
+        } else {
+            res.refresh_token
+        },
+        id_token: res.id_token,
+        ..Default::default()
+    })
+}
+
+fn verify_id_token(token: &str, issuer: &str, client_id: &str) -> ResultType<Value> {
+    let kid = jwt_part(token, 0)?["kid"]
+        .as_str()
+        .unwrap_or_default()
+        .to_owned();
+    let mut keys = jwks(issuer, false)?;
+    if !kid.is_empty() && !keys.iter().any(|k| k.kid == kid) {
+        // rotated by the issuer
+        keys = jwks(issuer, true)?;
+    }
+    verify_jwt(
+        token,
+        &keys,
+        issuer,
+        client_id,
+        hbb_common::get_time() / 1000,
+    )
+}
+
+fn jwks(issuer: &str, refetch: bool) -> ResultType<Vec<Jwk>> {
+    let mut cache = JWKS.lock().unwrap();
+    if !refetch {
+        if let Some((cached, time, keys)) = cache.as_ref() {
+            if cached == issuer && time.elapsed() < JWKS_TTL {
+                return Ok(keys.clone());
+            }
+        }
+    }
+    let discovery = discover(issuer)?;
+    let jwks: Jwks = create_http_client()
+        .get(&discovery.jwks_uri)
+        .send()?
+        .error_for_status()?
+        .json()?;
+    *cache = Some((issuer.to_owned(), Instant::now(), jwks.keys.clone()));
+    Ok(jwks.keys)
+}
+
+// The header or the claims of a jwt, not verified.
+fn jwt_part(token: &str, i: usize) -> ResultType<Value> {
+    let Some(part) = token.split('.').nth(i) else {
+        bail!("Invalid jwt");
+    };
+    Ok(serde_json::from_slice(&URL_SAFE_NO_PAD.decode(part)?)?)
+}
+
+fn verify_jwt(
+    token: &str,
+    keys: &[Jwk],
+    issuer: &str,
+    audience: &str,
+    now: i64,
+) -> ResultType<Value> {
+    let (Some((signed, signature)), 3) = (token.rsplit_once('.'), token.split('.').count()) else {
+        bail!("Invalid jwt");
+    };
+    let header = jwt_part(token, 0)?;
+    let signature = URL_SAFE_NO_PAD.decode(signature)?;
+    let kid = header["kid"].as_str().unwrap_or_default();
+    let alg = header["alg"].as_str().unwrap_or_default();
+    if !keys
+        .iter()
+        .filter(|k| kid.is_empty() || k.kid == kid)
+        .any(|k| verify_signature(k, alg, signed.as_bytes(), &signature))
+    {
+        bail!("Invalid signature of the id token");
+    }
+    let claims = jwt_part(token, 1)?;
+    if claims["iss"].as_str().map(|s| s.trim_end_matches('/')) != Some(issuer) {
+        bail!("The id token is not issued by {}", issuer);
+    }
+    if !claim_strings(&claims["aud"]).iter().any(|a| a == audience) {
+        bail!("The id token is not for {}", audience);
+    }
+    if claims["exp"].as_i64().unwrap_or_default() + LEEWAY < now {
+        bail!("The id token is expired");
+    }
+    Ok(claims)
+}
+
+fn verify_signature(key: &Jwk, alg: &str, signed: &[u8], signature: &[u8]) -> bool {
+    use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+    let decode = |s: &str| URL_SAFE_NO_PAD.decode(s).unwrap_or_default();
+    match (alg, key.kty.as_str()) {
+        ("RS256", "RSA") => RsaPublicKeyComponents {
+            n: decode(&key.n),
+            e: decode(&key.e),
+        }
+        .verify(&signature::RSA_PKCS1_2048_8192_SHA256, signed, signature)
+        .is_ok(),
+        ("ES256", "EC") if key.crv == "P-256" => {
+            let mut point = vec![4];
+            point.extend(decode(&key.x));
+            point.extend(decode(&key.y));
+            UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point)
+                .verify(signed, signature)
+                .is_ok()
+        }
+        _ => false,
+    }
+}
+
+fn claims_name(claims: &Value) -> String {
+    ["preferred_username", "email", "name", "sub"]
+        .iter()
+        .find_map(|k| claims[k].as_str().filter(|s| !s.is_empty()))
+        .unwrap_or_default()
+        .to_owned()
+}
+
+// A claim of a string or of an array of strings.
+fn claim_strings(claim: &Value) -> Vec<String> {
+    match claim {
+        Value::String(s) => vec![s.clone()],
+        Value::Array(v) => v
+            .iter()
+            .filter_map(|s| s.as_str().map(|s| s.to_owned()))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+// By the name, or by the DN of LDAP or its first value, e.g. "admins" of "cn=admins,dc=example".
+fn in_group(groups: &[String], group: &str) -> bool {
+    groups.iter().any(|g| {
+        g.eq_ignore_ascii_case(group)
+            || matches!(
+                g.split(',').next().and_then(|rdn| rdn.split_once('=')),
+                Some((_, name)) if name.trim().eq_ignore_ascii_case(group)
+            )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_jwt() {
+        use ring::{
+            rand::SystemRandom,
+            signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+        };
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+            .unwrap();
+        let point = key.public_key().as_ref();
+        let jwk = Jwk {
+            kty: "EC".to_owned(),
+            kid: "k1".to_owned(),
+            crv: "P-256".to_owned(),
+            x: URL_SAFE_NO_PAD.encode(&point[1..33]),
+            y: URL_SAFE_NO_PAD.encode(&point[33..]),
+            ..Default::default()
+        };
+        let sign = |header: Value, claims: Value| {
+            let signed = format!(
+                "{}.{}",
+                URL_SAFE_NO_PAD.encode(header.to_string()),
+                URL_SAFE_NO_PAD.encode(claims.to_string())
+            );
+            let signature = key.sign(&rng, signed.as_bytes()).unwrap();
+            format!("{}.{}", signed, URL_SAFE_NO_PAD.encode(signature.as_ref()))
+        };
+        let header = serde_json::json!({ "alg": "ES256", "kid": "k1" });
+        let claims = serde_json::json!({
+            "iss": "https://idp.example.com/",
+            "aud": ["rustdesk", "other"],
+            "exp": 1000,
+            "email": "alice@example.com",
+            "groups": ["admins", "staff"],
+        });
+        let token = sign(header.clone(), claims.clone());
+        let keys = [jwk.clone()];
+        let issuer = "https://idp.example.com";
+        let claims = verify_jwt(&token, &keys, issuer, "rustdesk", 1000).unwrap();
+        assert_eq!(claims_name(&claims), "alice@example.com");
+        assert!(in_group(&claim_strings(&claims["groups"]), "Staff"));
+        assert!(verify_jwt(&token, &keys, issuer, "another", 1000).is_err());
+        assert!(verify_jwt(&token, &keys, "https://evil.example.com", "rustdesk", 1000).is_err());
+        assert!(verify_jwt(&token, &keys, issuer, "rustdesk", 1000 + LEEWAY + 1).is_err());
+        let mut tampered = token.clone();
+        tampered.insert_str(token.find('.').unwrap() + 1, "e30");
+        assert!(verify_jwt(&tampered, &keys, issuer, "rustdesk", 1000).is_err());
+        let none = sign(serde_json::json!({ "alg": "none" }), claims);
+        assert!(verify_jwt(&none, &keys, issuer, "rustdesk", 1000).is_err());
+        let other = Jwk {
+            kid: "k2".to_owned(),
+            ..jwk
+        };
+        assert!(verify_jwt(&token, &[other], issuer, "rustdesk", 1000).is_err());
+    }
+
+    #[test]
+    fn test_in_group() {
+        let groups = vec![
+            "cn=Admins,ou=groups,dc=example,dc=com".to_owned(),
+            "staff".to_owned(),
+        ];
+        assert!(in_group(&groups, "admins"));
+        assert!(in_group(&groups, "cn=admins,ou=groups,dc=example,dc=com"));
+        assert!(in_group(&groups, "staff"));
+        assert!(!in_group(&groups, "groups"));
+        assert!(!in_group(&[], "staff"));
+    }
+}
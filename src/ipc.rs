@@ -53,11 +53,17 @@ pub enum FS {
         path: String,
         id: i32,
         recursive: bool,
+        trash: bool,
     },
     RemoveFile {
         path: String,
         id: i32,
         file_num: i32,
+        trash: bool,
+    },
+    UndoRemove {
+        id: i32,
+        remove_ids: Vec<i32>,
     },
     CreateDir {
         path: String,
@@ -71,6 +77,12 @@ pub enum FS {
         overwrite_detection: bool,
         total_size: u64,
         conn_id: i32,
+        verify: bool,
+        attributes: hbb_common::fs::attributes::Options,
+        // of the files in the same order
+        file_attributes: Vec<hbb_common::fs::attributes::EntryAttributes>,
+        // the value of FileConflictPolicy
+        conflict_policy: i32,
     },
     CancelWrite {
         id: i32,
@@ -80,6 +92,8 @@ pub enum FS {
         file_num: i32,
         data: Bytes,
         compressed: bool,
+        copy_blks: Vec<u32>,
+        checksum: Bytes,
     },
     WriteDone {
         id: i32,
@@ -101,12 +115,27 @@ pub enum FS {
         file_size: u64,
         last_modified: u64,
         is_upload: bool,
+        delta: bool,
     },
     Rename {
         id: i32,
         path: String,
         new_name: String,
     },
+    Search {
+        id: i32,
+        path: String,
+        pattern: String,
+        include_hidden: bool,
+        max_depth: u32,
+        timeout_secs: u32,
+    },
+    ResolveConflict {
+        id: i32,
+        file_num: i32,
+        policy: i32,
+        remember: bool,
+    },
 }
 
 #[cfg(target_os = "windows")]
@@ -178,6 +207,75 @@ pub enum DataPortableService {
     RequestStart,
     WillClose,
     CmShowElevation(bool),
+    KillProcess(u32),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RecordStatus {
+    pub recording: bool,
+    pub dir: String,
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "t", content = "c")]
+pub enum DataRecord {
+    Start,
+    Stop,
+    Query,
+    Status(RecordStatus),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "t", content = "c")]
+pub enum DataAliases {
+    Set(Vec<String>),
+    Query,
+    Status(crate::alias::AliasStatus),
+    Error(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "t", content = "c")]
+pub enum DataLoginLockouts {
+    Query,
+    // a peer id or an ip, all if None
+    Clear(Option<String>),
+    List(Vec<config::LoginLockout>),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "t", content = "c")]
+pub enum DataOneTimePasswords {
+    Query,
+    Generate {
+        count: usize,
+        minutes: u32,
+        scope: String,
+        label: String,
+    },
+    Remove(Vec<String>),
+    List(Vec<config::OneTimePassword>),
+    // the codes generated, shown once
+    Generated(Vec<String>),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "t", content = "c")]
+pub enum DataTrustedKeys {
+    Query,
+    // the fingerprints
+    Remove(Vec<String>),
+    List(Vec<config::TrustedKey>),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "t", content = "c")]
+pub enum DataAuditLog {
+    Query(Box<crate::server::audit_log::Query>),
+    Entries(Vec<crate::server::audit_log::Entry>),
+    Verify,
+    Verified(crate::server::audit_log::Verification),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -186,6 +284,7 @@ pub enum Data {
     Login {
         id: i32,
         is_file_transfer: bool,
+        is_terminal: bool,
         peer_id: String,
         name: String,
         authorized: bool,
@@ -198,15 +297,36 @@ pub enum Data {
         restart: bool,
         recording: bool,
         block_input: bool,
+        key_input: bool,
+        mouse_input: bool,
+        clipboard_typing: bool,
+        terminal: bool,
         from_switch: bool,
     },
     ChatMessage {
         text: String,
+        // of the receipt the peer answers with, to the peer only
+        id: u32,
+    },
+    // of the peer to the cm, the data follows raw, and of the cm to be sent to the peer
+    ChatAttachment {
+        id: u32,
+        name: String,
+        data: Bytes,
+        compressed: bool,
+        image: bool,
+    },
+    // of the peer to the cm, the receipt of a message of the cm
+    ChatReceipt {
+        id: u32,
+        error: String,
     },
     SwitchPermission {
         name: String,
         enabled: bool,
     },
+    // from the cm, the permissions of the connection are saved for its peer, or forgotten
+    SavePermissionProfile(bool),
     SystemInfo(Option<String>),
     ClickTime(i64),
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
@@ -225,6 +345,9 @@ pub enum Data {
     ConfirmedKey(Option<(Vec<u8>, Vec<u8>)>),
     RawMessage(Vec<u8>),
     Socks(Option<config::Socks5Server>),
+    NetworkProfiles(Option<Vec<config::NetworkProfile>>),
+    LanPeers(Option<Vec<config::DiscoveryPeer>>),
+    DiscoverLanPeers,
     FS(FS),
     Test,
     SyncConfig(Option<Box<(Config, Config2)>>),
@@ -254,12 +377,19 @@ pub enum Data {
     StartVoiceCall,
     VoiceCallResponse(bool),
     CloseVoiceCall(String),
+    // the peer asks for the control of the input, or no longer
+    InputControlRequest(bool),
+    InputControlResponse(bool),
+    // the bytes of an annotation, of the peer to the cm, and of all the drawings the other way
+    Annotation(Vec<u8>),
     #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     Plugin(Plugin),
     #[cfg(windows)]
     SyncWinCpuUsage(Option<f64>),
     FileTransferLog((String, String)),
+    // a transfer written by the cm, the connection records it
+    FileTransferEvent(hbb_common::fs::audit::TransferEvent),
     #[cfg(windows)]
     ControlledSessionCount(usize),
     CmErr(String),
@@ -270,6 +400,31 @@ pub enum Data {
     HwCodecConfig(Option<String>),
     RemoveTrustedDevices(Vec<Bytes>),
     ClearTrustedDevices,
+    RemovePermissionProfiles(Vec<String>),
+    Record(DataRecord),
+    SessionMetrics(Vec<crate::client::metrics::SessionMetrics>),
+    Aliases(DataAliases),
+    LoginLockouts(DataLoginLockouts),
+    // the days the old keypair is kept signing along
+    RotateKeyPair(u32),
+    // why the peer connects, sent to the cm right before `Login`
+    AccessReason(String),
+    // the permissions the policy locks, sent to the cm right before `Login`
+    PolicyLocked(Vec<String>),
+    // the key of the device of the peer, trusted once accepted, sent to the cm right before `Login`
+    NewDeviceKey(String),
+    AuditLog(DataAuditLog),
+    OneTimePasswords(DataOneTimePasswords),
+    TrustedKeys(DataTrustedKeys),
+    // of the backend of the virtual printer, the pdf follows raw, and of the server to the
+    // connection sending it
+    PrintJob {
+        title: String,
+        copies: u32,
+        data: Bytes,
+    },
+    // the error of the job printed, empty if sent to the peer
+    PrintJobResult(String),
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -440,7 +595,7 @@ async fn handle(data: Data, stream: &mut Connection) {
         }
         Data::Socks(s) => match s {
             None => {
-                allow_err!(stream.send(&Data::Socks(Config::get_socks())).await);
+                allow_err!(stream.send(&Data::Socks(Config::get_default_socks())).await);
             }
             Some(data) => {
                 if data.proxy.is_empty() {
@@ -453,6 +608,36 @@ async fn handle(data: Data, stream: &mut Connection) {
                 log::info!("socks updated");
             }
         },
+        Data::NetworkProfiles(profiles) => match profiles {
+            None => {
+                allow_err!(
+                    stream
+                        .send(&Data::NetworkProfiles(Some(Config::get_network_profiles())))
+                        .await
+                );
+            }
+            Some(profiles) => {
+                Config::set_network_profiles(profiles);
+                crate::common::test_nat_type();
+                RendezvousMediator::restart();
+                log::info!("network profiles updated");
+            }
+        },
+        Data::LanPeers(None) => {
+            let peers = config::LanPeers::load().peers;
+            allow_err!(stream.send(&Data::LanPeers(Some(peers))).await);
+        }
+        Data::DiscoverLanPeers => {
+            // the discovery blocks on its own runtime for the answers
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            std::thread::spawn(move || {
+                allow_err!(crate::lan::discover());
+                tx.send(()).ok();
+            });
+            rx.await.ok();
+            let peers = config::LanPeers::load().peers;
+            allow_err!(stream.send(&Data::LanPeers(Some(peers))).await);
+        }
         Data::VideoConnCount(None) => {
             let n = crate::server::AUTHED_CONNS
                 .lock()
@@ -481,12 +666,17 @@ async fn handle(data: Data, stream: &mut Connection) {
                     ));
                 } else if name == "rendezvous_servers" {
                     value = Some(Config::get_rendezvous_servers().join(","));
+                } else if name == "rendezvous_status" {
+                    value = Some(crate::rendezvous_failover::status());
                 } else if name == "fingerprint" {
                     value = if Config::get_key_confirmed() {
                         Some(crate::common::pk_to_fingerprint(Config::get_key_pair().1))
                     } else {
                         None
                     };
+                } else if name == "old-fingerprint" {
+                    value = Config::get_old_key_pair()
+                        .map(|(_, pk)| crate::common::pk_to_fingerprint(pk));
                 } else if name == "hide_cm" {
                     value = if crate::hbbs_http::sync::is_pro() {
                         Some(hbb_common::password_security::hide_cm().to_string())
@@ -499,6 +689,11 @@ async fn handle(data: Data, stream: &mut Connection) {
                     value = Some(Config::get_unlock_pin());
                 } else if name == "trusted-devices" {
                     value = Some(Config::get_trusted_devices_json());
+                } else if name == "permission-profiles" {
+                    value = Some(
+                        serde_json::to_string(&Config::get_permission_profiles())
+                            .unwrap_or_default(),
+                    );
                 } else {
                     value = None;
                 }
@@ -657,6 +852,109 @@ async fn handle(data: Data, stream: &mut Connection) {
         Data::ClearTrustedDevices => {
             Config::clear_trusted_devices();
         }
+        Data::RemovePermissionProfiles(ids) => {
+            Config::remove_permission_profiles(&ids);
+        }
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        Data::RotateKeyPair(grace_days) => {
+            if let Err(err) = Config::rotate_key_pair(grace_days) {
+                log::error!("Failed to rotate the keypair: {}", err);
+            }
+        }
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        Data::PrintJob { title, copies, .. } => {
+            let error = match stream.next_raw().await {
+                Ok(data) => match crate::server::print_jobs::send(title, copies, data.to_vec()) {
+                    Ok(()) => "".to_owned(),
+                    Err(err) => err.to_string(),
+                },
+                Err(err) => err.to_string(),
+            };
+            allow_err!(stream.send(&Data::PrintJobResult(error)).await);
+        }
+        Data::Record(record) => match record {
+            DataRecord::Start => crate::video_service::set_record_incoming(true),
+            DataRecord::Stop => crate::video_service::set_record_incoming(false),
+            DataRecord::Query => {
+                let files = crate::video_service::recording_files();
+                let status = RecordStatus {
+                    recording: !files.is_empty(),
+                    dir: crate::video_service::record_save_directory(),
+                    files,
+                };
+                allow_err!(stream.send(&Data::Record(DataRecord::Status(status))).await);
+            }
+            _ => {}
+        },
+        Data::Aliases(aliases) => {
+            let reply = match aliases {
+                DataAliases::Set(aliases) => match crate::alias::set(aliases) {
+                    Ok(()) => DataAliases::Status(crate::alias::status()),
+                    Err(err) => DataAliases::Error(err.to_string()),
+                },
+                DataAliases::Query => DataAliases::Status(crate::alias::status()),
+                _ => return,
+            };
+            allow_err!(stream.send(&Data::Aliases(reply)).await);
+        }
+        Data::LoginLockouts(request) => {
+            match request {
+                DataLoginLockouts::Query => {}
+                DataLoginLockouts::Clear(source) => {
+                    let n = crate::server::login_lockout::clear(source.as_deref());
+                    log::info!("Cleared the login lockouts of {} sources", n);
+                }
+                _ => return,
+            }
+            let lockouts = crate::server::login_lockout::list();
+            allow_err!(
+                stream
+                    .send(&Data::LoginLockouts(DataLoginLockouts::List(lockouts)))
+                    .await
+            );
+        }
+        Data::AuditLog(request) => {
+            let reply = match request {
+                DataAuditLog::Query(query) => {
+                    DataAuditLog::Entries(crate::server::audit_log::query(&query))
+                }
+                DataAuditLog::Verify => DataAuditLog::Verified(crate::server::audit_log::verify()),
+                _ => return,
+            };
+            allow_err!(stream.send(&Data::AuditLog(reply)).await);
+        }
+        Data::OneTimePasswords(request) => {
+            let reply = match request {
+                DataOneTimePasswords::Query => {
+                    DataOneTimePasswords::List(Config::get_one_time_passwords())
+                }
+                DataOneTimePasswords::Generate {
+                    count,
+                    minutes,
+                    scope,
+                    label,
+                } => DataOneTimePasswords::Generated(Config::generate_one_time_passwords(
+                    count, minutes, &scope, &label,
+                )),
+                DataOneTimePasswords::Remove(ids) => {
+                    Config::remove_one_time_passwords(&ids);
+                    DataOneTimePasswords::List(Config::get_one_time_passwords())
+                }
+                _ => return,
+            };
+            allow_err!(stream.send(&Data::OneTimePasswords(reply)).await);
+        }
+        Data::TrustedKeys(request) => {
+            let reply = match request {
+                DataTrustedKeys::Query => DataTrustedKeys::List(Config::get_trusted_keys()),
+                DataTrustedKeys::Remove(fingerprints) => {
+                    Config::remove_trusted_keys(&fingerprints);
+                    DataTrustedKeys::List(Config::get_trusted_keys())
+                }
+                _ => return,
+            };
+            allow_err!(stream.send(&Data::TrustedKeys(reply)).await);
+        }
         _ => {}
     }
 }
@@ -920,7 +1218,22 @@ pub fn get_fingerprint() -> String {
         .unwrap_or_default()
 }
 
+pub fn get_old_fingerprint() -> String {
+    get_config("old-fingerprint")
+        .unwrap_or_default()
+        .unwrap_or_default()
+}
+
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub fn rotate_key_pair(grace_days: u32) -> ResultType<()> {
+    set_data(&Data::RotateKeyPair(grace_days))
+}
+
 pub fn set_permanent_password(v: String) -> ResultType<()> {
+    let min_len = config::LockedConfig::password_min_length();
+    if !v.is_empty() && v.chars().count() < min_len {
+        bail!("The password must be at least {} characters", min_len);
+    }
     Config::set_permanent_password(&v);
     set_config("permanent-password", v)
 }
@@ -987,6 +1300,23 @@ pub fn clear_trusted_devices() {
     allow_err!(set_data(&Data::ClearTrustedDevices));
 }
 
+#[cfg(feature = "flutter")]
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub fn get_permission_profiles() -> String {
+    if let Ok(Some(v)) = get_config("permission-profiles") {
+        v
+    } else {
+        serde_json::to_string(&Config::get_permission_profiles()).unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "flutter")]
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub fn remove_permission_profiles(ids: Vec<String>) {
+    Config::remove_permission_profiles(&ids);
+    allow_err!(set_data(&Data::RemovePermissionProfiles(ids)));
+}
+
 pub fn get_id() -> String {
     if let Ok(Some(v)) = get_config("id") {
         // update salt also, so that next time reinstallation not causing first-time auto-login failure
@@ -1017,6 +1347,14 @@ pub async fn get_rendezvous_server(ms_timeout: u64) -> (String, Vec<String>) {
     }
 }
 
+/// The rendezvous server in use and the probes of the failover servers, as json.
+pub fn get_rendezvous_status() -> String {
+    get_config("rendezvous_status")
+        .ok()
+        .flatten()
+        .unwrap_or_else(crate::rendezvous_failover::status)
+}
+
 async fn get_options_(ms_timeout: u64) -> ResultType<HashMap<String, String>> {
     let mut c = connect(ms_timeout, "").await?;
     c.send(&Data::Options(None)).await?;
@@ -1099,12 +1437,14 @@ async fn get_socks_(ms_timeout: u64) -> ResultType<Option<config::Socks5Server>>
         Config::set_socks(value.clone());
         Ok(value)
     } else {
-        Ok(Config::get_socks())
+        Ok(Config::get_default_socks())
     }
 }
 
 pub async fn get_socks_async(ms_timeout: u64) -> Option<config::Socks5Server> {
-    get_socks_(ms_timeout).await.unwrap_or(Config::get_socks())
+    get_socks_(ms_timeout)
+        .await
+        .unwrap_or(Config::get_default_socks())
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -1126,6 +1466,45 @@ pub async fn set_socks(value: config::Socks5Server) -> ResultType<()> {
     Ok(())
 }
 
+#[tokio::main(flavor = "current_thread")]
+pub async fn get_network_profiles() -> Vec<config::NetworkProfile> {
+    if let Ok(mut c) = connect(1_000, "").await {
+        if c.send(&Data::NetworkProfiles(None)).await.is_ok() {
+            if let Ok(Some(Data::NetworkProfiles(Some(value)))) = c.next_timeout(1_000).await {
+                Config::set_network_profiles(value.clone());
+                return value;
+            }
+        }
+    }
+    Config::get_network_profiles()
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn set_network_profiles(value: Vec<config::NetworkProfile>) -> ResultType<()> {
+    Config::set_network_profiles(value.clone());
+    connect(1_000, "")
+        .await?
+        .send(&Data::NetworkProfiles(Some(value)))
+        .await?;
+    Ok(())
+}
+
+/// The peers discovered on the local network by the service, discovered again first if `refresh`.
+#[tokio::main(flavor = "current_thread")]
+pub async fn get_lan_peers(refresh: bool) -> ResultType<Vec<config::DiscoveryPeer>> {
+    let mut c = connect(1_000, "").await?;
+    if refresh {
+        c.send(&Data::DiscoverLanPeers).await?;
+    } else {
+        c.send(&Data::LanPeers(None)).await?;
+    }
+    // the answers of the peers are waited for some seconds
+    if let Some(Data::LanPeers(Some(peers))) = c.next_timeout(10_000).await? {
+        return Ok(peers);
+    }
+    bail!("no lan peers received");
+}
+
 pub fn get_proxy_status() -> bool {
     Config::get_socks().is_some()
 }
@@ -1272,6 +1651,131 @@ async fn handle_wayland_screencast_restore_token(
     return Ok(None);
 }
 
+/// Start or stop recording incoming sessions in the server process.
+#[tokio::main(flavor = "current_thread")]
+pub async fn set_record_incoming(start: bool) -> ResultType<()> {
+    let record = if start {
+        DataRecord::Start
+    } else {
+        DataRecord::Stop
+    };
+    set_data_async(&Data::Record(record)).await
+}
+
+#[tokio::main(flavor = "current_thread")]
+pub async fn get_record_status() -> ResultType<RecordStatus> {
+    let ms_timeout = 1_000;
+    let mut c = connect(ms_timeout, "").await?;
+    c.send(&Data::Record(DataRecord::Query)).await?;
+    if let Some(Data::Record(DataRecord::Status(status))) = c.next_timeout(ms_timeout).await? {
+        return Ok(status);
+    }
+    bail!("no record status received");
+}
+
+/// Sets the aliases of this device in the server process, or queries them if none, with the status of
+/// their registration.
+#[tokio::main(flavor = "current_thread")]
+pub async fn aliases(set: Option<Vec<String>>) -> ResultType<crate::alias::AliasStatus> {
+    let ms_timeout = 1_000;
+    let mut c = connect(ms_timeout, "").await?;
+    let request = match set {
+        Some(aliases) => DataAliases::Set(aliases),
+        None => DataAliases::Query,
+    };
+    c.send(&Data::Aliases(request)).await?;
+    match c.next_timeout(ms_timeout).await? {
+        Some(Data::Aliases(DataAliases::Status(status))) => Ok(status),
+        Some(Data::Aliases(DataAliases::Error(err))) => bail!(err),
+        _ => bail!("no aliases received"),
+    }
+}
+
+/// The sources of the failed logins which are backed off or locked out, after clearing those of
+/// a peer id or an ip first, or all of them if `Some(None)`.
+#[tokio::main(flavor = "current_thread")]
+pub async fn login_lockouts(
+    clear: Option<Option<String>>,
+) -> ResultType<Vec<config::LoginLockout>> {
+    let ms_timeout = 1_000;
+    let mut c = connect(ms_timeout, "").await?;
+    let request = match clear {
+        Some(source) => DataLoginLockouts::Clear(source),
+        None => DataLoginLockouts::Query,
+    };
+    c.send(&Data::LoginLockouts(request)).await?;
+    if let Some(Data::LoginLockouts(DataLoginLockouts::List(lockouts))) =
+        c.next_timeout(ms_timeout).await?
+    {
+        return Ok(lockouts);
+    }
+    bail!("no login lockouts received");
+}
+
+/// The one-time passwords of the service, or the codes generated, as `request` asks.
+#[tokio::main(flavor = "current_thread")]
+pub async fn one_time_passwords(request: DataOneTimePasswords) -> ResultType<DataOneTimePasswords> {
+    let ms_timeout = 1_000;
+    let mut c = connect(ms_timeout, "").await?;
+    c.send(&Data::OneTimePasswords(request)).await?;
+    if let Some(Data::OneTimePasswords(reply)) = c.next_timeout(ms_timeout).await? {
+        return Ok(reply);
+    }
+    bail!("no one-time passwords received");
+}
+
+/// The keys of the devices trusted by the service, after removing the ones `request` asks to.
+#[tokio::main(flavor = "current_thread")]
+pub async fn trusted_keys(request: DataTrustedKeys) -> ResultType<DataTrustedKeys> {
+    let ms_timeout = 1_000;
+    let mut c = connect(ms_timeout, "").await?;
+    c.send(&Data::TrustedKeys(request)).await?;
+    if let Some(Data::TrustedKeys(reply)) = c.next_timeout(ms_timeout).await? {
+        return Ok(reply);
+    }
+    bail!("no trusted keys received");
+}
+
+/// The entries of the audit log of the service matching `query`, or the result of walking its
+/// chain if None.
+#[tokio::main(flavor = "current_thread")]
+pub async fn audit_log(query: Option<crate::server::audit_log::Query>) -> ResultType<DataAuditLog> {
+    // the whole log is read for a query
+    let ms_timeout = 10_000;
+    let mut c = connect(ms_timeout, "").await?;
+    let request = match query {
+        Some(query) => DataAuditLog::Query(Box::new(query)),
+        None => DataAuditLog::Verify,
+    };
+    c.send(&Data::AuditLog(request)).await?;
+    match c.next_timeout(ms_timeout).await? {
+        Some(Data::AuditLog(reply @ (DataAuditLog::Entries(_) | DataAuditLog::Verified(_)))) => {
+            Ok(reply)
+        }
+        _ => bail!("no audit log received"),
+    }
+}
+
+/// Hands the pdf of a job of the virtual printer to the server, for the peer to print.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[tokio::main(flavor = "current_thread")]
+pub async fn print_job(title: String, copies: u32, data: Vec<u8>) -> ResultType<()> {
+    let ms_timeout = 10_000;
+    let mut c = connect(1_000, "").await?;
+    c.send(&Data::PrintJob {
+        title,
+        copies,
+        data: Bytes::new(),
+    })
+    .await?;
+    c.send_raw(data.into()).await?;
+    match c.next_timeout(ms_timeout).await? {
+        Some(Data::PrintJobResult(error)) if error.is_empty() => Ok(()),
+        Some(Data::PrintJobResult(error)) => bail!(error),
+        _ => bail!("no result of the job received"),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -20,6 +20,12 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+mod keymap;
+pub mod layout;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub mod shortcuts;
+
 #[cfg(windows)]
 static mut IS_ALT_GR: bool = false;
 
@@ -105,6 +111,20 @@ pub mod client {
     }
 
     pub fn process_event(keyboard_mode: &str, event: &Event, lock_modes: Option<i32>) {
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        for event in keymap::remap(&get_peer_id(), event) {
+            process_mapped_event(keyboard_mode, &event, lock_modes);
+        }
+        #[cfg(any(target_os = "android", target_os = "ios"))]
+        process_mapped_event(keyboard_mode, event, lock_modes);
+    }
+
+    // The event is not remapped again, it may be a key sent to the peer already.
+    pub(super) fn process_mapped_event(
+        keyboard_mode: &str,
+        event: &Event,
+        lock_modes: Option<i32>,
+    ) {
         let keyboard_mode = get_keyboard_mode_enum(keyboard_mode);
         if is_long_press(&event) {
             return;
@@ -121,13 +141,23 @@ pub mod client {
         lock_modes: Option<i32>,
         session: &Session<T>,
     ) {
-        let keyboard_mode = get_keyboard_mode_enum(keyboard_mode);
-        if is_long_press(&event) {
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        if shortcuts::handle(event, false) != shortcuts::Handling::Remote {
             return;
         }
+        let keyboard_mode = get_keyboard_mode_enum(keyboard_mode);
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        let events = keymap::remap(&session.lc.read().unwrap().id, event);
+        #[cfg(any(target_os = "android", target_os = "ios"))]
+        let events = vec![event.clone()];
         let peer = session.peer_platform().to_lowercase();
-        for key_event in event_to_key_events(peer, &event, keyboard_mode, lock_modes) {
-            session.send_key_event(&key_event);
+        for event in events.iter() {
+            if is_long_press(event) {
+                continue;
+            }
+            for key_event in event_to_key_events(peer.clone(), event, keyboard_mode, lock_modes) {
+                session.send_key_event(&key_event);
+            }
         }
     }
 
@@ -279,11 +309,17 @@ fn start_grab_loop() {
             let _scan_code = event.position_code;
             let _code = event.platform_code as KeyCode;
             let res = if KEYBOARD_HOOKED.load(Ordering::SeqCst) {
-                client::process_event(&get_keyboard_mode(), &event, None);
-                if is_press {
-                    None
-                } else {
-                    Some(event)
+                match shortcuts::handle(&event, cfg!(target_os = "windows")) {
+                    shortcuts::Handling::Local => Some(event),
+                    shortcuts::Handling::Injected => None,
+                    shortcuts::Handling::Remote => {
+                        client::process_event(&get_keyboard_mode(), &event, None);
+                        if is_press {
+                            None
+                        } else {
+                            Some(event)
+                        }
+                    }
                 }
             } else {
                 Some(event)
@@ -339,6 +375,9 @@ fn start_grab_loop() {
         EventType::KeyPress(key) | EventType::KeyRelease(key) => {
             if let Key::Unknown(keycode) = key {
                 log::error!("rdev get unknown key, keycode is {:?}", keycode);
+            } else if shortcuts::handle(&event, false) != shortcuts::Handling::Remote {
+                // The keyboard is grabbed, the shortcut is only kept from the peer.
+                return Some(event);
             } else {
                 client::process_event(&get_keyboard_mode(), &event, None);
             }
@@ -379,16 +418,21 @@ pub fn release_remote_keys(keyboard_mode: &str) {
     // todo!: client quit suddenly, how to release keys?
     let to_release = TO_RELEASE.lock().unwrap().clone();
     TO_RELEASE.lock().unwrap().clear();
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    {
+        keymap::reset();
+        shortcuts::reset();
+    }
     for (key, mut event) in to_release.into_iter() {
         event.event_type = EventType::KeyRelease(key);
-        client::process_event(keyboard_mode, &event, None);
+        client::process_mapped_event(keyboard_mode, &event, None);
         // If Alt or AltGr is pressed, we need to send another key stoke to release it.
         // Because the controlled side may hold the alt state, if local window is switched by [Alt + Tab].
         if key == Key::Alt || key == Key::AltGr {
             event.event_type = EventType::KeyPress(key);
-            client::process_event(keyboard_mode, &event, None);
+            client::process_mapped_event(keyboard_mode, &event, None);
             event.event_type = EventType::KeyRelease(key);
-            client::process_event(keyboard_mode, &event, None);
+            client::process_mapped_event(keyboard_mode, &event, None);
         }
     }
 }
@@ -584,6 +628,14 @@ pub fn event_to_key_events(
         _ => {}
     }
 
+    // The keys are typed the same on the peer.
+    let keyboard_mode = if keyboard_mode == KeyboardMode::Translate
+        && layout::is_same_as_peer(&peer, &get_peer_keyboard_layout())
+    {
+        KeyboardMode::Map
+    } else {
+        keyboard_mode
+    };
     key_event.mode = keyboard_mode.into();
 
     let mut key_events = match keyboard_mode {
@@ -628,6 +680,31 @@ pub fn send_key_event(key_event: &KeyEvent) {
     }
 }
 
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn get_peer_id() -> String {
+    #[cfg(not(any(feature = "flutter", feature = "cli")))]
+    if let Some(session) = CUR_SESSION.lock().unwrap().as_ref() {
+        return session.lc.read().unwrap().id.clone();
+    }
+    #[cfg(feature = "flutter")]
+    if let Some(session) = flutter::get_cur_session() {
+        return session.lc.read().unwrap().id.clone();
+    }
+    "".to_owned()
+}
+
+fn get_peer_keyboard_layout() -> String {
+    #[cfg(not(any(feature = "flutter", feature = "cli")))]
+    if let Some(session) = CUR_SESSION.lock().unwrap().as_ref() {
+        return session.lc.read().unwrap().peer_keyboard_layout();
+    }
+    #[cfg(feature = "flutter")]
+    if let Some(session) = flutter::get_cur_session() {
+        return session.lc.read().unwrap().peer_keyboard_layout();
+    }
+    "".to_owned()
+}
+
 pub fn get_peer_platform() -> String {
     #[cfg(not(any(feature = "flutter", feature = "cli")))]
     if let Some(session) = CUR_SESSION.lock().unwrap().as_ref() {
@@ -973,7 +1050,7 @@ fn try_fill_unicode(_peer: &str, event: &Event, key_event: &KeyEvent, events: &m
             if let Some(name) = &unicode_info.name {
                 if name.len() > 0 {
                     let mut evt = key_event.clone();
-                    evt.set_seq(name.to_string());
+                    evt.set_seq(layout::compose_with_dead_key(name));
                     evt.down = true;
                     events.push(evt);
                 }
@@ -1075,10 +1152,14 @@ pub fn translate_keyboard_mode(peer: &str, event: &Event, key_event: KeyEvent) -
                 // try clear dead key state
                 // rdev::clear_dead_key_state();
             } else {
+                layout::set_dead_key(unicode_info.name.as_deref());
                 return events;
             }
             #[cfg(not(target_os = "macos"))]
-            return events;
+            {
+                layout::set_dead_key(unicode_info.name.as_deref());
+                return events;
+            }
         }
     }
 
@@ -0,0 +1,389 @@
+// Remap the local keys before they are translated for the peer.
+//
+// The keymap of a peer is read from `keymaps/<id>.toml` (or `.json`) in the config directory,
+// `keymaps/default.toml` is used if the peer has none. The keys are named after `rdev::Key`:
+//
+// [keys]
+// ControlLeft = "MetaLeft"
+// MetaLeft = "ControlLeft"
+//
+// [combos]
+// "AltGr+KeyE" = "ControlLeft+Alt+KeyE"
+//
+// A combo matches when its last key is pressed and exactly its modifiers are held on this side,
+// it is checked before the keys. The file is read again when it changes, the keys already pressed
+// are released as they were pressed.
+
+use super::is_modifier;
+use hbb_common::{config::Config, log, toml, ResultType};
+use rdev::{Event, EventType, Key};
+use serde_derive::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct KeymapFile {
+    keys: HashMap<String, String>,
+    combos: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Combo {
+    modifiers: Vec<Key>,
+    key: Key,
+}
+
+#[derive(Default)]
+struct Keymap {
+    keys: HashMap<Key, Key>,
+    combos: Vec<(Combo, Combo)>,
+}
+
+#[derive(Default)]
+struct State {
+    id: String,
+    checked: Option<Instant>,
+    // the file and the modified time of the loaded keymap
+    loaded: Option<(PathBuf, Option<SystemTime>)>,
+    keymap: Keymap,
+    // the modifiers held on this side
+    held: HashSet<Key>,
+    // the key sent for a pressed key
+    mapped: HashMap<Key, Key>,
+    // the combo sent for a pressed key, and the modifiers it released
+    active: HashMap<Key, (Combo, Vec<Key>)>,
+}
+
+lazy_static::lazy_static! {
+    static ref STATE: Mutex<State> = Default::default();
+}
+
+/// Returns the events to send for a local key event of the session with the peer `id`.
+pub fn remap(id: &str, event: &Event) -> Vec<Event> {
+    let mut state = STATE.lock().unwrap();
+    if matches!(event.event_type, EventType::KeyPress(_)) {
+        state.check_file(id);
+    }
+    state.remap(event)
+}
+
+/// Forgets the pressed keys, they are released on the peer.
+pub fn reset() {
+    let mut state = STATE.lock().unwrap();
+    state.held.clear();
+    state.mapped.clear();
+    state.active.clear();
+}
+
+fn keymap_paths(id: &str) -> Vec<PathBuf> {
+    let dir = Config::path("keymaps");
+    let mut paths = vec![];
+    for name in [id, "default"] {
+        if name.is_empty() {
+            continue;
+        }
+        paths.push(dir.join(format!("{name}.toml")));
+        paths.push(dir.join(format!("{name}.json")));
+    }
+    paths
+}
+
+impl State {
+    fn check_file(&mut self, id: &str) {
+        if self.id == id && self.checked.map(|t| t.elapsed() < CHECK_INTERVAL) == Some(true) {
+            return;
+        }
+        self.checked = Some(Instant::now());
+        if self.id != id {
+            self.id = id.to_owned();
+            self.loaded = None;
+            self.keymap = Default::default();
+        }
+        let found = keymap_paths(id).into_iter().find(|p| p.is_file()).map(|p| {
+            let modified = std::fs::metadata(&p).and_then(|m| m.modified()).ok();
+            (p, modified)
+        });
+        if found == self.loaded {
+            return;
+        }
+        match &found {
+            Some((path, _)) => match load(path) {
+                Ok(keymap) => {
+                    log::info!("keymap loaded from {}", path.display());
+                    self.keymap = keymap;
+                }
+                // keep the last one while the file is being edited
+                Err(e) => log::error!("failed to load the keymap {}: {e}", path.display()),
+            },
+            None => self.keymap = Default::default(),
+        }
+        self.loaded = found;
+    }
+
+    fn remap(&mut self, event: &Event) -> Vec<Event> {
+        let (key, down) = match event.event_type {
+            EventType::KeyPress(key) => (key, true),
+            EventType::KeyRelease(key) => (key, false),
+            _ => return vec![event.clone()],
+        };
+        if is_modifier(&key) {
+            if down {
+                self.held.insert(key);
+            } else {
+                self.held.remove(&key);
+            }
+        }
+
+        if !down {
+            if let Some((combo, released)) = self.active.remove(&key) {
+                let mut events = vec![with_key(event, combo.key, false)];
+                for m in combo.modifiers.iter().rev() {
+                    events.push(with_key(event, *m, false));
+                }
+                for m in released.iter() {
+                    if self.held.contains(m) {
+                        events.push(with_key(event, self.sent_key(*m), true));
+                    }
+                }
+                return events;
+            }
+            return match self.mapped.remove(&key) {
+                Some(to) => vec![with_key(event, to, false)],
+                None => vec![event.clone()],
+            };
+        }
+
+        // repeated by holding the key
+        if let Some((combo, _)) = self.active.get(&key) {
+            return vec![with_key(event, combo.key, true)];
+        }
+        if let Some(to) = self.mapped.get(&key) {
+            return vec![with_key(event, *to, true)];
+        }
+
+        let matched = self.keymap.combos.iter().find(|(from, _)| {
+            from.key == key
+                && from.modifiers.len() == self.held.len()
+                && from.modifiers.iter().all(|m| self.held.contains(m))
+        });
+        if let Some((from, to)) = matched.cloned() {
+            let mut events = vec![];
+            for m in from.modifiers.iter() {
+                events.push(with_key(event, self.sent_key(*m), false));
+            }
+            for m in to.modifiers.iter() {
+                events.push(with_key(event, *m, true));
+            }
+            events.push(with_key(event, to.key, true));
+            self.active.insert(key, (to, from.modifiers));
+            return events;
+        }
+
+        match self.keymap.keys.get(&key) {
+            Some(to) => {
+                self.mapped.insert(key, *to);
+                vec![with_key(event, *to, true)]
+            }
+            None => vec![event.clone()],
+        }
+    }
+
+    // the key sent for the pressed key
+    fn sent_key(&self, key: Key) -> Key {
+        self.mapped.get(&key).copied().unwrap_or(key)
+    }
+}
+
+fn load(path: &Path) -> ResultType<Keymap> {
+    let content = std::fs::read_to_string(path)?;
+    let file: KeymapFile = if path.extension().map(|e| e == "json") == Some(true) {
+        serde_json::from_str(&content)?
+    } else {
+        toml::from_str(&content)?
+    };
+    parse(file)
+}
+
+fn parse(file: KeymapFile) -> ResultType<Keymap> {
+    let mut keymap = Keymap::default();
+    for (from, to) in file.keys.iter() {
+        keymap.keys.insert(parse_key(from)?, parse_key(to)?);
+    }
+    for (from, to) in file.combos.iter() {
+        keymap.combos.push((parse_combo(from)?, parse_combo(to)?));
+    }
+    Ok(keymap)
+}
+
+fn parse_combo(s: &str) -> ResultType<Combo> {
+    let mut keys = s
+        .split('+')
+        .map(|k| parse_key(k.trim()))
+        .collect::<ResultType<Vec<_>>>()?;
+    let Some(key) = keys.pop() else {
+        hbb_common::bail!("empty combo");
+    };
+    if let Some(k) = keys.iter().find(|k| !is_modifier(k)) {
+        hbb_common::bail!("{k:?} is not a modifier in {s}");
+    }
+    Ok(Combo {
+        modifiers: keys,
+        key,
+    })
+}
+
+fn with_key(event: &Event, key: Key, down: bool) -> Event {
+    let mut event = event.clone();
+    event.event_type = if down {
+        EventType::KeyPress(key)
+    } else {
+        EventType::KeyRelease(key)
+    };
+    // the character of the original key is wrong for the translate mode
+    event.unicode = None;
+    #[cfg(target_os = "windows")]
+    {
+        event.platform_code = rdev::win_code_from_key(key).unwrap_or(0);
+        event.position_code = rdev::win_scancode_from_key(key).unwrap_or(0) as _;
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let code = rdev::code_from_key(key).unwrap_or(0);
+        event.platform_code = code as _;
+        event.position_code = code as _;
+    }
+    event
+}
+
+macro_rules! key_names {
+    ($($name:ident),* $(,)?) => {
+        fn parse_key(s: &str) -> ResultType<Key> {
+            match s {
+                $(stringify!($name) => Ok(Key::$name),)*
+                _ => hbb_common::bail!("unknown key: {s}"),
+            }
+        }
+    };
+}
+
+key_names! {
+    Alt, AltGr, Backspace, CapsLock, ControlLeft, ControlRight, Delete, DownArrow, End, Escape,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, Home, LeftArrow, MetaLeft, MetaRight,
+    PageDown, PageUp, Return, RightArrow, ShiftLeft, ShiftRight, Space, Tab, UpArrow, PrintScreen,
+    ScrollLock, Pause, NumLock, BackQuote, Num0, Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8,
+    Num9, Minus, Equal, KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL,
+    KeyM, KeyN, KeyO, KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ,
+    LeftBracket, RightBracket, SemiColon, Quote, BackSlash, Comma, Dot, Slash, Insert, KpReturn,
+    KpMinus, KpPlus, KpMultiply, KpDivide, KpDecimal, Kp0, Kp1, Kp2, Kp3, Kp4, Kp5, Kp6, Kp7,
+    Kp8, Kp9, Apps,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(key: Key, down: bool) -> Event {
+        Event {
+            time: SystemTime::now(),
+            unicode: None,
+            platform_code: 0,
+            position_code: 0,
+            event_type: if down {
+                EventType::KeyPress(key)
+            } else {
+                EventType::KeyRelease(key)
+            },
+            usb_hid: 0,
+            #[cfg(any(target_os = "windows", target_os = "macos"))]
+            extra_data: 0,
+        }
+    }
+
+    fn types(events: Vec<Event>) -> Vec<EventType> {
+        events.into_iter().map(|e| e.event_type).collect()
+    }
+
+    fn state(content: &str) -> State {
+        State {
+            keymap: parse(toml::from_str(content).unwrap()).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let combo = parse_combo("ControlLeft + Alt+KeyE").unwrap();
+        assert_eq!(combo.modifiers, vec![Key::ControlLeft, Key::Alt]);
+        assert_eq!(combo.key, Key::KeyE);
+        assert!(parse_combo("KeyA+KeyB").is_err());
+        assert!(parse_key("Ctrl").is_err());
+        let file: KeymapFile = serde_json::from_str(r#"{"keys": {"KeyA": "KeyB"}}"#).unwrap();
+        assert_eq!(parse(file).unwrap().keys.get(&Key::KeyA), Some(&Key::KeyB));
+    }
+
+    #[test]
+    fn test_swap_keys() {
+        let mut state = state("[keys]\nControlLeft = 'MetaLeft'\nMetaLeft = 'ControlLeft'");
+        use EventType::*;
+        let mut sent = vec![];
+        for (key, down) in [
+            (Key::ControlLeft, true),
+            (Key::KeyC, true),
+            (Key::KeyC, false),
+            (Key::ControlLeft, false),
+        ] {
+            sent.extend(types(state.remap(&event(key, down))));
+        }
+        assert_eq!(
+            sent,
+            vec![
+                KeyPress(Key::MetaLeft),
+                KeyPress(Key::KeyC),
+                KeyRelease(Key::KeyC),
+                KeyRelease(Key::MetaLeft)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_combo() {
+        let mut state = state("[combos]\n'AltGr+KeyE' = 'ControlLeft+Alt+KeyE'");
+        use EventType::*;
+        assert_eq!(
+            types(state.remap(&event(Key::AltGr, true))),
+            vec![KeyPress(Key::AltGr)]
+        );
+        assert_eq!(
+            types(state.remap(&event(Key::KeyE, true))),
+            vec![
+                KeyRelease(Key::AltGr),
+                KeyPress(Key::ControlLeft),
+                KeyPress(Key::Alt),
+                KeyPress(Key::KeyE)
+            ]
+        );
+        // the keymap changed while the key is pressed
+        state.keymap = Default::default();
+        assert_eq!(
+            types(state.remap(&event(Key::KeyE, false))),
+            vec![
+                KeyRelease(Key::KeyE),
+                KeyRelease(Key::Alt),
+                KeyRelease(Key::ControlLeft),
+                KeyPress(Key::AltGr)
+            ]
+        );
+        // no modifier
+        assert_eq!(
+            types(state.remap(&event(Key::KeyE, true))),
+            vec![KeyPress(Key::KeyE)]
+        );
+    }
+}
@@ -0,0 +1,218 @@
+// The keyboard layouts of the two sides in the translate mode.
+//
+// The layouts are named the same on all the platforms, e.g. `de` for the German layout of Windows,
+// X11 and macOS. The controlled side reports its layout in `keyboard_layout` of the platform
+// additions. If the layouts are the same, the keys are sent by their positions, so the dead keys,
+// AltGr and the numpad work on the peer as they do on this side. Otherwise the characters are sent,
+// the dead keys are composed on this side, and the peer types the characters it has no key for.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// name, layout id of Windows, input source of macOS, layout(variant) of xkb
+const LAYOUTS: &[(&str, u16, &str, &str)] = &[
+    ("us", 0x0409, "com.apple.keylayout.US", "us"),
+    ("gb", 0x0809, "com.apple.keylayout.British", "gb"),
+    ("de", 0x0407, "com.apple.keylayout.German", "de"),
+    ("fr", 0x040C, "com.apple.keylayout.French", "fr"),
+    ("be", 0x080C, "com.apple.keylayout.Belgian", "be"),
+    ("ch", 0x0807, "com.apple.keylayout.SwissGerman", "ch"),
+    ("ch-fr", 0x100C, "com.apple.keylayout.SwissFrench", "ch(fr)"),
+    ("es", 0x040A, "com.apple.keylayout.Spanish-ISO", "es"),
+    ("it", 0x0410, "com.apple.keylayout.Italian-Pro", "it"),
+    ("pt", 0x0816, "com.apple.keylayout.Portuguese", "pt"),
+    ("br", 0x0416, "com.apple.keylayout.Brazilian-ABNT2", "br"),
+    ("se", 0x041D, "com.apple.keylayout.Swedish-Pro", "se"),
+    ("no", 0x0414, "com.apple.keylayout.Norwegian", "no"),
+    ("dk", 0x0406, "com.apple.keylayout.Danish", "dk"),
+    ("fi", 0x040B, "com.apple.keylayout.Finnish", "fi"),
+    ("ru", 0x0419, "com.apple.keylayout.RussianWin", "ru"),
+];
+
+// dead key, the letters and the composed ones
+const DEAD_KEYS: &[(char, &str, &str)] = &[
+    ('`', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    ('´', "aeiouyAEIOUY", "áéíóúýÁÉÍÓÚÝ"),
+    ('^', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    ('~', "anoANO", "ãñõÃÑÕ"),
+    ('¨', "aeiouyAEIOU", "äëïöüÿÄËÏÖÜ"),
+];
+
+lazy_static::lazy_static! {
+    static ref CURRENT: Mutex<Option<(Instant, Option<&'static str>)>> = Default::default();
+    static ref DEAD_KEY: Mutex<Option<char>> = Default::default();
+}
+
+pub fn from_windows(hkl: u32) -> Option<&'static str> {
+    let layout = (hkl >> 16) as u16;
+    // not the default layout of the language, e.g. Dvorak
+    if layout & 0xF000 == 0xF000 {
+        return None;
+    }
+    LAYOUTS.iter().find(|l| l.1 == layout).map(|l| l.0)
+}
+
+pub fn from_macos(input_source: &str) -> Option<&'static str> {
+    LAYOUTS.iter().find(|l| l.2 == input_source).map(|l| l.0)
+}
+
+pub fn from_xkb(layout: &str, variant: &str) -> Option<&'static str> {
+    let name = if variant.is_empty() {
+        layout.to_owned()
+    } else {
+        format!("{layout}({variant})")
+    };
+    LAYOUTS.iter().find(|l| l.3 == name).map(|l| l.0)
+}
+
+/// The layout of this side, none if it is not known.
+pub fn current() -> Option<&'static str> {
+    let mut current = CURRENT.lock().unwrap();
+    if let Some((checked, layout)) = *current {
+        if checked.elapsed() < CHECK_INTERVAL {
+            return layout;
+        }
+    }
+    let layout = detect();
+    *current = Some((Instant::now(), layout));
+    layout
+}
+
+fn detect() -> Option<&'static str> {
+    #[cfg(target_os = "windows")]
+    return from_windows(crate::platform::windows::get_keyboard_layout());
+    #[cfg(target_os = "macos")]
+    return from_macos(&crate::platform::macos::get_keyboard_layout()?);
+    #[cfg(target_os = "linux")]
+    return detect_xkb();
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    return None;
+}
+
+// the layout of Wayland is not known
+#[cfg(target_os = "linux")]
+fn detect_xkb() -> Option<&'static str> {
+    if !crate::platform::linux::is_x11() {
+        return None;
+    }
+    let (layout, variant) = crate::platform::linux::get_keyboard_layout()?;
+    from_xkb(&layout, &variant)
+}
+
+/// Whether the keys can be sent by their positions, `peer` is the lowercase platform of the peer.
+pub fn is_same_as_peer(peer: &str, peer_layout: &str) -> bool {
+    let Some(layout) = current() else {
+        return false;
+    };
+    is_same(
+        (layout, cfg!(target_os = "macos")),
+        (peer_layout, peer == super::OS_LOWER_MACOS),
+    )
+}
+
+// The layout names and whether they are of macOS.
+fn is_same(layout: (&str, bool), peer_layout: (&str, bool)) -> bool {
+    // Option of macOS types other characters than AltGr of the same layout on PC.
+    layout == peer_layout
+}
+
+/// Keeps the dead key until the next character.
+pub fn set_dead_key(name: Option<&str>) {
+    let mut chars = name.unwrap_or_default().chars();
+    *DEAD_KEY.lock().unwrap() = match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    };
+}
+
+/// The text of the key pressed after a dead key, the text itself if there is no dead key.
+pub fn compose_with_dead_key(text: &str) -> String {
+    let Some(dead) = DEAD_KEY.lock().unwrap().take() else {
+        return text.to_owned();
+    };
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(' '), None) => dead.to_string(),
+        (Some(base), None) => compose(dead, base)
+            .map(|c| c.to_string())
+            .unwrap_or(text.to_owned()),
+        // composed already
+        _ => text.to_owned(),
+    }
+}
+
+fn compose(dead: char, base: char) -> Option<char> {
+    // the dead keys of US international
+    let dead = match dead {
+        '\'' => '´',
+        '"' => '¨',
+        c => c,
+    };
+    let (_, letters, composed) = DEAD_KEYS.iter().find(|d| d.0 == dead)?;
+    let i = letters.chars().position(|c| c == base)?;
+    composed.chars().nth(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platforms() {
+        for (name, id, input_source, xkb) in LAYOUTS.iter() {
+            let hkl = ((*id as u32) << 16) | *id as u32;
+            assert_eq!(from_windows(hkl), Some(*name));
+            assert_eq!(from_macos(input_source), Some(*name));
+            let (layout, variant) = match xkb.split_once('(') {
+                Some((layout, variant)) => (layout, variant.trim_end_matches(')')),
+                None => (*xkb, ""),
+            };
+            assert_eq!(from_xkb(layout, variant), Some(*name));
+        }
+        // Spanish with the modern sort
+        assert_eq!(from_windows(0x040A0C0A), Some("es"));
+        // US Dvorak
+        assert_eq!(from_windows(0xF0020409), None);
+        assert_eq!(from_xkb("de", "nodeadkeys"), None);
+        assert_eq!(from_macos("com.apple.keylayout.Dvorak"), None);
+    }
+
+    #[test]
+    fn test_same() {
+        assert!(is_same(("de", false), ("de", false)));
+        assert!(is_same(("fr", true), ("fr", true)));
+        assert!(!is_same(("de", true), ("de", false)));
+        assert!(!is_same(("de", false), ("us", false)));
+        assert!(!is_same(("us", false), ("", false)));
+    }
+
+    #[test]
+    fn test_dead_keys() {
+        let cases = [
+            // fr
+            ("^", "e", "ê"),
+            ("¨", "u", "ü"),
+            // de
+            ("´", "E", "É"),
+            ("`", "a", "à"),
+            // es
+            ("~", "n", "ñ"),
+            // us international
+            ("'", "e", "é"),
+            ("\"", "o", "ö"),
+            // not composed
+            ("^", " ", "^"),
+            ("^", "x", "x"),
+            ("^", "ê", "ê"),
+        ];
+        for (dead, text, composed) in cases {
+            set_dead_key(Some(dead));
+            assert_eq!(compose_with_dead_key(text), composed);
+            assert_eq!(compose_with_dead_key(text), text);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+// Keep the system shortcuts chosen by the user on this side, instead of sending them to the peer.
+//
+// The shortcuts are in the local option `local-shortcuts`, empty to send all the keys. All the keys
+// are sent if the remote window is fullscreen and `capture-all-keys-in-fullscreen` is set.
+//
+// The modifiers of a shortcut may be sent to the peer already, e.g. Alt of Alt+Tab. They are
+// released on the peer, and pressed again on this side if the hook swallowed them.
+
+use super::*;
+use hbb_common::config::{keys, LocalConfig};
+use std::collections::HashSet;
+
+pub const ALT_TAB: &str = "alt-tab";
+pub const CMD_TAB: &str = "cmd-tab";
+// the Windows or Super key, and the keys pressed with it
+pub const WIN: &str = "win";
+pub const MEDIA: &str = "media";
+
+static IS_FULLSCREEN: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    // pressed and kept on this side, until they are released
+    static ref LOCAL_KEYS: Mutex<HashSet<Key>> = Default::default();
+}
+
+#[derive(PartialEq)]
+pub enum Handling {
+    Remote,
+    Local,
+    // pressed again on this side with its modifiers, the event is not needed
+    Injected,
+}
+
+pub fn set_fullscreen(fullscreen: bool) {
+    IS_FULLSCREEN.store(fullscreen, Ordering::SeqCst);
+}
+
+pub fn is_enabled(shortcut: &str) -> bool {
+    enabled().iter().any(|s| s == shortcut)
+}
+
+pub fn reset() {
+    LOCAL_KEYS.lock().unwrap().clear();
+}
+
+fn enabled() -> Vec<String> {
+    if IS_FULLSCREEN.load(Ordering::SeqCst)
+        && LocalConfig::get_option(keys::OPTION_CAPTURE_ALL_KEYS_IN_FULLSCREEN) == "Y"
+    {
+        return vec![];
+    }
+    LocalConfig::get_option(keys::OPTION_LOCAL_SHORTCUTS)
+        .split(',')
+        .map(|s| s.trim().to_owned())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Checks whether the key event is kept on this side.
+///
+/// `inject` is true for the keyboard hook, which swallows the modifiers sent to the peer.
+pub fn handle(event: &Event, inject: bool) -> Handling {
+    let (key, down) = match event.event_type {
+        EventType::KeyPress(key) => (key, true),
+        EventType::KeyRelease(key) => (key, false),
+        _ => return Handling::Remote,
+    };
+    let mut local_keys = LOCAL_KEYS.lock().unwrap();
+    if !down {
+        return if local_keys.remove(&key) {
+            Handling::Local
+        } else {
+            Handling::Remote
+        };
+    }
+    // repeated, or pressed by `press_locally`
+    if local_keys.contains(&key) {
+        return Handling::Local;
+    }
+    let shortcuts = enabled();
+    if shortcuts.is_empty() {
+        return Handling::Remote;
+    }
+    let with_local_modifier = local_keys.iter().any(is_modifier);
+    if !with_local_modifier && !shortcuts.iter().any(|s| is_shortcut(s, key, event)) {
+        return Handling::Remote;
+    }
+    local_keys.insert(key);
+    let modifiers: Vec<Key> = MODIFIERS_STATE
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|(k, down)| down.then_some(*k))
+        .collect();
+    if modifiers.is_empty() || is_modifier(&key) {
+        return Handling::Local;
+    }
+    local_keys.extend(modifiers.iter().copied());
+    drop(local_keys);
+
+    let keyboard_mode = get_keyboard_mode();
+    for m in modifiers.iter() {
+        let press = TO_RELEASE.lock().unwrap().get(m).cloned();
+        if let Some(mut release) = press {
+            release.event_type = EventType::KeyRelease(*m);
+            client::process_mapped_event(&keyboard_mode, &release, None);
+        }
+    }
+    if inject {
+        press_locally(&modifiers, key);
+        Handling::Injected
+    } else {
+        Handling::Local
+    }
+}
+
+fn is_shortcut(shortcut: &str, key: Key, event: &Event) -> bool {
+    let state = MODIFIERS_STATE.lock().unwrap();
+    let held = |k: Key| state.get(&k).copied().unwrap_or(false);
+    match shortcut {
+        ALT_TAB => key == Key::Tab && held(Key::Alt),
+        CMD_TAB => key == Key::Tab && (held(Key::MetaLeft) || held(Key::MetaRight)),
+        WIN => matches!(key, Key::MetaLeft | Key::MetaRight),
+        MEDIA => is_media_key(event),
+        _ => false,
+    }
+}
+
+fn is_media_key(_event: &Event) -> bool {
+    // VK_VOLUME_MUTE to VK_MEDIA_PLAY_PAUSE
+    #[cfg(target_os = "windows")]
+    return (0xAD..=0xB3).contains(&_event.platform_code);
+    // XF86XK_AudioLowerVolume to XF86XK_AudioNext
+    #[cfg(target_os = "linux")]
+    return (0x1008FF11..=0x1008FF17).contains(&_event.platform_code);
+    // the media keys are not keyboard events on macOS
+    #[cfg(target_os = "macos")]
+    return false;
+}
+
+// The hook swallowed the modifiers, the shortcut only works if they are pressed before the key.
+fn press_locally(modifiers: &[Key], key: Key) {
+    for k in modifiers.iter().chain(std::iter::once(&key)) {
+        if let Err(e) = rdev::simulate(&EventType::KeyPress(*k)) {
+            log::error!("failed to press {:?} locally: {:?}", k, e);
+        }
+    }
+}
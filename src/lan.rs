@@ -15,18 +15,39 @@ use hbb_common::{
 
 use std::{
     collections::{HashMap, HashSet},
-    net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket},
-    time::Instant,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, UdpSocket},
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
 type Message = RendezvousMessage;
 
+// The networks without ipv4 are queried by this link-local multicast group, which is the one of
+// the private experiments, on the port of the broadcast.
+const MULTICAST_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x114);
+
 #[cfg(not(target_os = "ios"))]
 pub(super) fn start_listening() -> ResultType<()> {
     let addr = SocketAddr::from(([0, 0, 0, 0], get_broadcast_port()));
     let socket = std::net::UdpSocket::bind(addr)?;
-    socket.set_read_timeout(Some(std::time::Duration::from_millis(1000)))?;
+    match hbb_common::socket_client::new_multicast_v6(
+        get_broadcast_port(),
+        &MULTICAST_V6,
+        &ipv6_interfaces(),
+    ) {
+        Ok(socket_v6) => {
+            std::thread::spawn(move || {
+                allow_err!(listen(socket_v6));
+            });
+        }
+        Err(err) => log::warn!("lan discovery over ipv6 not started: {}", err),
+    }
     log::info!("lan discovery listener started");
+    listen(socket)
+}
+
+#[cfg(not(target_os = "ios"))]
+fn listen(socket: UdpSocket) -> ResultType<()> {
+    socket.set_read_timeout(Some(std::time::Duration::from_millis(1000)))?;
     loop {
         let mut buf = [0; 2048];
         if let Ok((len, addr)) = socket.recv_from(&mut buf) {
@@ -57,6 +78,9 @@ pub(super) fn start_listening() -> ResultType<()> {
                                     hostname,
                                     username: crate::platform::get_active_username(),
                                     platform: whoami::platform().to_string(),
+                                    os: os_version(),
+                                    version: crate::VERSION.to_owned(),
+                                    rendezvous_online: config::get_online_state() > 0,
                                     ..Default::default()
                                 };
                                 msg_out.set_peer_discovery(peer);
@@ -82,24 +106,73 @@ pub async fn discover() -> ResultType<()> {
 }
 
 pub fn send_wol(id: String) {
-    let interfaces = default_net::get_interfaces();
+    let mut macs = vec![];
     for peer in &config::LanPeers::load().peers {
         if peer.id == id {
-            for (_, mac) in peer.ip_mac.iter() {
-                if let Ok(mac_addr) = mac.parse() {
-                    for interface in &interfaces {
-                        for ipv4 in &interface.ipv4 {
-                            // remove below mask check to avoid unexpected bug
-                            // if (u32::from(ipv4.addr) & u32::from(ipv4.netmask)) == (u32::from(peer_ip) & u32::from(ipv4.netmask))
-                            log::info!("Send wol to {mac_addr} of {}", ipv4.addr);
-                            allow_err!(wol::send_wol(mac_addr, None, Some(IpAddr::V4(ipv4.addr))));
-                        }
-                    }
+            macs.extend(peer.ip_mac.values().cloned());
+            break;
+        }
+    }
+    // the ones learned from the sessions, the peer may not be discovered while asleep
+    for mac in config::PeerConfig::load(&id).info.macs {
+        if !macs.contains(&mac) {
+            macs.push(mac);
+        }
+    }
+    wake(&macs);
+}
+
+/// Sends the magic packets to the macs from all the interfaces, returns the number of the macs
+/// valid.
+pub fn wake(macs: &[String]) -> usize {
+    let interfaces = default_net::get_interfaces();
+    let mut n = 0;
+    for mac in macs {
+        if let Ok(mac_addr) = mac.parse() {
+            n += 1;
+            for interface in &interfaces {
+                for ipv4 in &interface.ipv4 {
+                    // remove below mask check to avoid unexpected bug
+                    // if (u32::from(ipv4.addr) & u32::from(ipv4.netmask)) == (u32::from(peer_ip) & u32::from(ipv4.netmask))
+                    log::info!("Send wol to {mac_addr} of {}", ipv4.addr);
+                    allow_err!(wol::send_wol(mac_addr, None, Some(IpAddr::V4(ipv4.addr))));
                 }
             }
-            break;
         }
     }
+    n
+}
+
+/// The macs of this side with the ipv4 networks of them, told to the peers logged in.
+pub fn lan_addresses() -> Vec<hbb_common::message_proto::LanAddress> {
+    let mut addresses = vec![];
+    for interface in default_net::get_interfaces() {
+        let Some(mac_addr) = interface.mac_addr else {
+            continue;
+        };
+        let mac = mac_addr.address();
+        if mac == "00:00:00:00:00:00" {
+            continue;
+        }
+        for ipv4 in &interface.ipv4 {
+            if ipv4.addr.is_loopback() || ipv4.addr.is_link_local() {
+                continue;
+            }
+            addresses.push(hbb_common::message_proto::LanAddress {
+                mac: mac.clone(),
+                network: network(ipv4.addr, ipv4.prefix_len),
+                ..Default::default()
+            });
+        }
+    }
+    addresses
+}
+
+fn network(addr: Ipv4Addr, prefix_len: u8) -> String {
+    let mask = u32::MAX
+        .checked_shl(32 - prefix_len.min(32) as u32)
+        .unwrap_or(0);
+    format!("{}/{}", Ipv4Addr::from(u32::from(addr) & mask), prefix_len)
 }
 
 #[inline]
@@ -107,6 +180,24 @@ fn get_broadcast_port() -> u16 {
     (RENDEZVOUS_PORT + 3) as _
 }
 
+#[cfg(not(target_os = "ios"))]
+fn os_version() -> String {
+    use hbb_common::sysinfo::System;
+    System::new().long_os_version().unwrap_or_default()
+}
+
+// the indexes of the interfaces with ipv6
+fn ipv6_interfaces() -> Vec<u32> {
+    #[cfg(not(target_os = "ios"))]
+    return default_net::get_interfaces()
+        .into_iter()
+        .filter(|interface| !interface.ipv6.is_empty() && interface.index > 0)
+        .map(|interface| interface.index)
+        .collect();
+    #[cfg(target_os = "ios")]
+    Vec::new()
+}
+
 fn get_mac(_ip: &IpAddr) -> String {
     #[cfg(not(target_os = "ios"))]
     if let Ok(mac) = get_mac_by_ip(_ip) {
@@ -142,8 +233,13 @@ fn get_mac_by_ip(ip: &IpAddr) -> ResultType<String> {
 }
 
 // Mainly from https://github.com/shellrow/default-net/blob/cf7ca24e7e6e8e566ed32346c9cfddab3f47e2d6/src/interface/shared.rs#L4
-fn get_ipaddr_by_peer<A: ToSocketAddrs>(peer: A) -> Option<IpAddr> {
-    let socket = match UdpSocket::bind("0.0.0.0:0") {
+fn get_ipaddr_by_peer(peer: &SocketAddr) -> Option<IpAddr> {
+    let local = if peer.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = match UdpSocket::bind(local) {
         Ok(s) => s,
         Err(_) => return None,
     };
@@ -183,10 +279,23 @@ fn create_broadcast_sockets() -> Vec<UdpSocket> {
     sockets
 }
 
+// one per interface, the link-local multicast is sent on the interface given by the scope
+fn create_multicast_sockets_v6() -> Vec<(UdpSocket, SocketAddr)> {
+    ipv6_interfaces()
+        .into_iter()
+        .filter_map(|index| {
+            let socket = UdpSocket::bind("[::]:0").ok()?;
+            let group = SocketAddrV6::new(MULTICAST_V6, get_broadcast_port(), 0, index);
+            Some((socket, group.into()))
+        })
+        .collect()
+}
+
 fn send_query() -> ResultType<Vec<UdpSocket>> {
-    let sockets = create_broadcast_sockets();
-    if sockets.is_empty() {
-        bail!("Found no bindable ipv4 addresses");
+    let broadcast_sockets = create_broadcast_sockets();
+    let multicast_sockets = create_multicast_sockets_v6();
+    if broadcast_sockets.is_empty() && multicast_sockets.is_empty() {
+        bail!("Found no bindable addresses");
     }
 
     let mut msg_out = Message::new();
@@ -209,9 +318,15 @@ fn send_query() -> ResultType<Vec<UdpSocket>> {
     msg_out.set_peer_discovery(peer);
     let out = msg_out.write_to_bytes()?;
     let maddr = SocketAddr::from(([255, 255, 255, 255], get_broadcast_port()));
-    for socket in &sockets {
+    for socket in &broadcast_sockets {
         allow_err!(socket.send_to(&out, maddr));
     }
+    let mut sockets = broadcast_sockets;
+    for (socket, group) in multicast_sockets {
+        if socket.send_to(&out, group).is_ok() {
+            sockets.push(socket);
+        }
+    }
     log::info!("discover ping sent");
     Ok(sockets)
 }
@@ -270,6 +385,13 @@ fn wait_response(
                                     hostname: p.hostname.clone(),
                                     platform: p.platform.clone(),
                                     online: true,
+                                    os: p.os.clone(),
+                                    version: p.version.clone(),
+                                    rendezvous_online: p.rendezvous_online,
+                                    last_seen: SystemTime::now()
+                                        .duration_since(UNIX_EPOCH)
+                                        .map(|d| d.as_secs() as i64)
+                                        .unwrap_or_default(),
                                 }));
                             }
                         }
@@ -340,3 +462,17 @@ async fn handle_received_peers(mut rx: UnboundedReceiver<config::DiscoveryPeer>)
     crate::flutter_ffi::main_load_lan_peers();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network() {
+        let addr = Ipv4Addr::new(192, 168, 1, 23);
+        assert_eq!(network(addr, 24), "192.168.1.0/24");
+        assert_eq!(network(addr, 20), "192.168.0.0/20");
+        assert_eq!(network(addr, 32), "192.168.1.23/32");
+        assert_eq!(network(addr, 0), "0.0.0.0/0");
+    }
+}
@@ -236,5 +236,49 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("enable-trusted-devices-tip", "Skip 2FA verification on trusted devices"),
         ("one-way-file-transfer-tip", "One-way file transfer is enabled on the controlled side."),
         ("web_id_input_tip", "You can input an ID in the same server, direct IP access is not supported in web client.\nIf you want to access a device on another server, please append the server address (<id>@<server_address>?key=<key_value>), for example,\n9123456234@192.168.16.1:21117?key=5Qbwsde3unUcJBtrx9ZkvUmwFNoExHzpryHuPUdqlWM=.\nIf you want to access a device on a public server, please input \"<id>@public\", the key is not needed for public server."),
+        ("auto_block_input_tip", "Block the local keyboard and mouse while the remote side is controlling"),
+        ("secure_input_tip", "The remote system is in secure input, e.g. a password field or the secure desktop, and drops the keys typed from here until it ends."),
+        ("voice_call_virtual_mic_tip", "Use the voice of the peer in a voice call as a microphone of this computer (VB-CABLE or BlackHole is needed on Windows and macOS)"),
+        ("sync_interval_tip", "Minutes between the runs while the file transfer is open, 0 to run manually"),
+        ("search_files_tip", "A part of the name, or a pattern like *.txt, searched under the current folder"),
+        ("search_truncated_tip", "The search stopped at the depth, time or result limit, not every match is listed."),
+        ("network_profiles_tip", "The proxy of the first profile with a network this device is on is used instead of the default one. Use DOMAIN\\user as the username for NTLM."),
+        ("network_profile_cidr_tip", "e.g. 10.1.0.0/16, 2001:db8::/32"),
+        ("network_profile_direct_tip", "Empty to connect directly"),
+        ("direct_access_knock_tip", "If set, the port is only opened for the devices which knock with the same key, set it on the controlling device as well."),
+        ("turn_server_tip", "The incoming sessions are relayed through the TURN server over tcp if it is reachable, otherwise through the relay server."),
+        ("keep_alive_interval_tip", "Seconds between the heartbeats"),
+        ("keep_alive_retries_tip", "Heartbeats missed before the connection is shown as degraded"),
+        ("keep_alive_timeout_tip", "Seconds without any data before the connection is closed"),
+        ("connection_degraded_tip", "Nothing received from the remote device for a while, the connection may be lost."),
+        ("dscp_interactive_tip", "Remote control sessions, like EF or AF41, empty for no marking"),
+        ("dscp_file_transfer_tip", "File transfer and port forward sessions, like CS1, empty for no marking"),
+        ("port_mapping_tip", "Map the direct access port and the hole punching ports on the router with UPnP, NAT-PMP or PCP"),
+        ("fido2-touch-tip", "Touch your security key to let the remote device verify you"),
+        ("permission_profile_tip", "The permissions turned off here are turned off for the next connections from this peer too"),
+        ("ip_rules_tip", "One rule a line, \"allow\" or \"deny\", an IP or CIDR, and optionally the days and the time it applies in. The first rule matching decides, the IPs matching none are denied if there is any allow rule"),
+        ("rotate_key_tip", "A new key is generated, and the connecting peers see a new fingerprint. The old key is still accepted for a week while the ID server learns the new one, unless it is compromised"),
+        ("tls_pins_tip", "The API server and the WebSocket tunnel are only trusted if their certificate, or its public key, matches one of these pins, \"sha256/\" and the base64 of the SHA-256 of its DER, one a line"),
+        ("access_reason_tip", "The remote side asks why you connect before accepting the session"),
+        ("approve_timeout_tip", "Deny the incoming sessions not accepted in time"),
+        ("oidc_device_code_tip", "Open the link below and enter this code to sign in"),
+        ("identity_provider_tip", "The incoming sessions must be signed in with this identity provider, and in the required group if set"),
+        ("audit_log_tip", "Record the logins, permissions, file transfers and privacy mode of the incoming sessions in a tamper-evident audit log"),
+        ("one_time_passwords_tip", "Single-use passwords which expire, each invalidated by its first login, and whose codes are shown only once when generated"),
+        ("server_record_tip", "The session is recorded by the remote side"),
+        ("trusted_device_keys_tip", "The devices of the peers trusted by their keys, which they prove they own at each login. A device removed has to be accepted again, or is rejected if untrusted device keys are"),
+        ("new_device_key_tip", "The key of the device of the peer, which is trusted once you accept the connection"),
+        ("new_device_wait_tip", "This device is not trusted by the remote side yet, please wait for the remote side to accept your session request"),
+        ("notifications_tip", "Notify the admins of this device of the sessions and the failed authentications, by a JSON POST to the webhook and by email through the SMTP server, whichever is set"),
+        ("inactivity_disconnect_tip", "The remote side will close the session for inactivity, move the mouse or press a key to stay connected"),
+        ("inactivity_view_only_tip", "The remote side will take the control away for inactivity, move the mouse or press a key to keep it"),
+        ("wake-through-peer-tip", "The peer is woken by the ID below, online on its LAN. The peers seen on the same network are listed."),
+        ("remote_restarting_reconnect_tip", "Remote device is restarting, the session will reconnect once it is back online. The one-time password changes on restart, enter the permanent password if asked."),
+        ("remote_restart_timeout_tip", "Remote device is not back online in time, please reconnect later"),
+        ("session_handoff_tip", "Open this link or scan the code on the other device, signed in to the same account, within {} seconds. This session is closed once it is taken over."),
+        ("unattended_schedule_tip", "One window a line, the days and the time of the day the connections with a password are accepted in, and the ID of a peer before it for the windows of that peer, e.g. \"123456789: sat 10:00-12:00\". Outside of them the connections are to be accepted here"),
+        ("outside_schedule_wait_tip", "It is outside the schedule of the unattended access of the remote device, please wait for the remote side to accept your session request..."),
+        ("kill_process_elevation_tip", "The process is of another user or elevated. Request the elevation to end it."),
+        ("print_job_tip", "The remote side sent a document of its virtual printer, print it on the default printer of this side?"),
     ].iter().cloned().collect();
 }
@@ -653,5 +653,258 @@ pub static ref T: std::collections::HashMap<&'static str, &'static str> =
         ("Upload folder", "Subir carpeta"),
         ("Upload files", "Subir archivos"),
         ("Clipboard is synchronized", "Portapapeles sincronizado"),
+        ("Screenshot", ""),
+        ("Encoder fallback", ""),
+        ("Sharpen", ""),
+        ("Deband", ""),
+        ("Grayscale", ""),
+        ("Smooth scaling", ""),
+        ("Bandwidth limit", ""),
+        ("Unlimited", ""),
+        ("Bandwidth", ""),
+        ("Forward gamepad", ""),
+        ("Native touch", ""),
+        ("Keep shortcuts on this computer", ""),
+        ("Win key", ""),
+        ("Media keys", ""),
+        ("Capture all keys in fullscreen", ""),
+        ("Macros", ""),
+        ("Macro name", ""),
+        ("Record macro", ""),
+        ("No macros", ""),
+        ("Play", ""),
+        ("Stop macro", ""),
+        ("Stop recording macro", ""),
+        ("auto_block_input_tip", ""),
+        ("Timeout in seconds", ""),
+        ("Enable key input", ""),
+        ("Enable mouse input", ""),
+        ("Enable clipboard typing", ""),
+        ("Stop typing", ""),
+        ("Clipboard typing delay", ""),
+        ("secure_input_tip", ""),
+        ("voice_call_virtual_mic_tip", ""),
+        ("Audio quality", ""),
+        ("Low latency", ""),
+        ("Voice", ""),
+        ("Music", ""),
+        ("Noise suppression", ""),
+        ("Remote volume", ""),
+        ("Surround", ""),
+        ("Sync jobs", ""),
+        ("No sync jobs", ""),
+        ("Local folder", ""),
+        ("Remote folder", ""),
+        ("Upload only", ""),
+        ("Download only", ""),
+        ("Two-way", ""),
+        ("sync_interval_tip", ""),
+        ("Preview", ""),
+        ("Run", ""),
+        ("Sync log", ""),
+        ("Edit", ""),
+        ("Conflicts", ""),
+        ("Up to date", ""),
+        ("Speed limit", ""),
+        ("Compress file transfer", ""),
+        ("Pause", ""),
+        ("Move up", ""),
+        ("Search files", ""),
+        ("search_files_tip", ""),
+        ("search_truncated_tip", ""),
+        ("No files found", ""),
+        ("Preserve permissions", ""),
+        ("Preserve timestamps", ""),
+        ("Preserve extended attributes", ""),
+        ("Links", ""),
+        ("Skip links", ""),
+        ("Copy links", ""),
+        ("Follow links", ""),
+        ("On conflict", ""),
+        ("Ask every time", ""),
+        ("Overwrite if newer", ""),
+        ("Keep both", ""),
+        ("Delete permanently", ""),
+        ("Undo delete", ""),
+        ("Nothing to undo", ""),
+        ("Log file transfers", ""),
+        ("Select a file to preview", ""),
+        ("File too large to preview", ""),
+        ("No preview available", ""),
+        ("Failed to preview", ""),
+        ("Preferred IP version", ""),
+        ("Network profiles", ""),
+        ("Networks", ""),
+        ("network_profiles_tip", ""),
+        ("network_profile_cidr_tip", ""),
+        ("network_profile_direct_tip", ""),
+        ("Use QUIC for direct IP access", ""),
+        ("Knock key", ""),
+        ("direct_access_knock_tip", ""),
+        ("TURN server", ""),
+        ("turn_server_tip", ""),
+        ("Keep alive", ""),
+        ("keep_alive_interval_tip", ""),
+        ("keep_alive_retries_tip", ""),
+        ("keep_alive_timeout_tip", ""),
+        ("connection_degraded_tip", ""),
+        ("DSCP marking", ""),
+        ("dscp_interactive_tip", ""),
+        ("dscp_file_transfer_tip", ""),
+        ("Map ports on the router", ""),
+        ("port_mapping_tip", ""),
+        ("Security key", ""),
+        ("fido2-touch-tip", ""),
+        ("Security key not verified", ""),
+        ("Remember for this peer", ""),
+        ("Forget for this peer", ""),
+        ("permission_profile_tip", ""),
+        ("IP rules", ""),
+        ("Use IP rules", ""),
+        ("ip_rules_tip", ""),
+        ("Short authentication string", ""),
+        ("Old fingerprint", ""),
+        ("Rotate key", ""),
+        ("rotate_key_tip", ""),
+        ("The key is compromised", ""),
+        ("TLS pins", ""),
+        ("tls_pins_tip", ""),
+        ("Access reason", ""),
+        ("access_reason_tip", ""),
+        ("Require access reason", ""),
+        ("approve_timeout_tip", ""),
+        ("The remote side did not accept in time", ""),
+        ("Identity provider", ""),
+        ("Sign in with OIDC", ""),
+        ("Sign in with LDAP", ""),
+        ("oidc_device_code_tip", ""),
+        ("OIDC issuer", ""),
+        ("OIDC client ID", ""),
+        ("Required group", ""),
+        ("identity_provider_tip", ""),
+        ("None", ""),
+        ("Identity not verified", ""),
+        ("Locked by the policy", ""),
+        ("Not allowed by the policy", ""),
+        ("audit_log_tip", ""),
+        ("Retention in days", ""),
+        ("One-time passwords", ""),
+        ("one_time_passwords_tip", ""),
+        ("Count", ""),
+        ("Valid for minutes", ""),
+        ("Any", ""),
+        ("View only", ""),
+        ("File transfer only", ""),
+        ("Generate", ""),
+        ("server_record_tip", ""),
+        ("Trust device keys on first use", ""),
+        ("Reject untrusted device keys", ""),
+        ("Trusted device keys", ""),
+        ("trusted_device_keys_tip", ""),
+        ("Revoke", ""),
+        ("New device", ""),
+        ("new_device_key_tip", ""),
+        ("new_device_wait_tip", ""),
+        ("Untrusted device", ""),
+        ("Notifications", ""),
+        ("notifications_tip", ""),
+        ("Webhook", ""),
+        ("SMTP server", ""),
+        ("From", ""),
+        ("To", ""),
+        ("Session started", ""),
+        ("Session ended", ""),
+        ("Authentication failed", ""),
+        ("Watermark the shared screen", ""),
+        ("Take the control away instead of disconnecting", ""),
+        ("Inactive session", ""),
+        ("inactivity_disconnect_tip", ""),
+        ("inactivity_view_only_tip", ""),
+        ("Enable terminal", ""),
+        ("No permission of terminal", ""),
+        ("Wake through a peer", ""),
+        ("wake-through-peer-tip", ""),
+        ("Enable Wake-on-LAN relay", ""),
+        ("Wake-on-LAN through this device is disabled", ""),
+        ("No valid MAC address", ""),
+        ("Reconnect when it is back online", ""),
+        ("Restart into safe mode with networking", ""),
+        ("Failed to restart into the safe mode", ""),
+        ("remote_restarting_reconnect_tip", ""),
+        ("remote_restart_timeout_tip", ""),
+        ("Request control", ""),
+        ("Cancel control request", ""),
+        ("Release control", ""),
+        ("The control is taken by", ""),
+        ("The control is given to you", ""),
+        ("Give control", ""),
+        ("Let one peer control at a time", ""),
+        ("Draw on screen", ""),
+        ("Stop drawing", ""),
+        ("Freehand", ""),
+        ("Arrow", ""),
+        ("Box", ""),
+        ("Color", ""),
+        ("Undo", ""),
+        ("Clear drawings", ""),
+        ("Enable drawing on screen", ""),
+        ("Presenter mode", ""),
+        ("Send a file", ""),
+        ("Sending", ""),
+        ("Delivered", ""),
+        ("Push to talk", ""),
+        ("Hold to talk", ""),
+        ("Suppress the echo in a voice call", ""),
+        ("Hand off the session", ""),
+        ("session_handoff_tip", ""),
+        ("The session was handed off to another device", ""),
+        ("The ticket is invalid or expired", ""),
+        ("The account differs from the one of the session handed off", ""),
+        ("Only a remote control session can be handed off", ""),
+        ("Schedule the unattended access", ""),
+        ("unattended_schedule_tip", ""),
+        ("Refuse outside the schedule", ""),
+        ("outside_schedule_wait_tip", ""),
+        ("Unattended access is not allowed at this time", ""),
+        ("Sync the view settings of the peers", ""),
+        ("Show system stats", ""),
+        ("System stats", ""),
+        ("Memory", ""),
+        ("Cores", ""),
+        ("Disks", ""),
+        ("Temperatures", ""),
+        ("Enable system stats", ""),
+        ("CPU", ""),
+        ("Task manager", ""),
+        ("End process", ""),
+        ("User", ""),
+        ("PID", ""),
+        ("Enable task manager", ""),
+        ("kill_process_elevation_tip", ""),
+        ("The task manager is disabled on the remote side", ""),
+        ("The process of this side is not to be killed", ""),
+        ("Service manager", ""),
+        ("Start the service", ""),
+        ("Stop the service", ""),
+        ("Restart the service", ""),
+        ("Allow the peers to control the services", ""),
+        ("The service manager is disabled on the remote side", ""),
+        ("Invalid service name", ""),
+        ("Camera", ""),
+        ("Show camera", ""),
+        ("Allow the peers to view the camera", ""),
+        ("Only a remote control session can view the camera", ""),
+        ("The camera is not allowed to be viewed on the remote side", ""),
+        ("The camera is not supported on the remote side", ""),
+        ("Print", ""),
+        ("print_job_tip", ""),
+        ("Copies", ""),
+        ("Print the documents of the remote side", ""),
+        ("No peer prints the documents of this side", ""),
+        ("No application to print the document", ""),
+        ("The document is too large", ""),
+        ("Allow the view-only peers to join the broadcast", ""),
+        ("Viewers of the broadcast", ""),
+        ("The broadcast is full", ""),
     ].iter().cloned().collect();
 }
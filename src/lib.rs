@@ -12,6 +12,9 @@ mod server;
 pub use self::server::*;
 mod client;
 mod lan;
+mod opus_multistream;
+#[cfg(not(any(target_os = "ios")))]
+mod rendezvous_failover;
 #[cfg(not(any(target_os = "ios")))]
 mod rendezvous_mediator;
 #[cfg(not(any(target_os = "ios")))]
@@ -36,13 +39,34 @@ pub mod flutter;
 #[cfg(any(target_os = "android", target_os = "ios", feature = "flutter"))]
 pub mod flutter_ffi;
 use common::*;
+#[cfg(not(any(target_os = "ios")))]
+mod alias;
+#[cfg(not(any(target_os = "ios")))]
+mod annotation;
 mod auth_2fa;
+mod backup_path;
+mod chat;
 #[cfg(feature = "cli")]
 pub mod cli;
 #[cfg(not(any(target_os = "android", target_os = "ios", feature = "cli")))]
+mod conn_check;
+#[cfg(not(any(target_os = "android", target_os = "ios", feature = "cli")))]
 pub mod core_main;
 mod custom_server;
+mod echo;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+mod fido2;
+mod idp;
 mod lang;
+mod port_knock;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+mod printer;
+#[cfg(not(any(target_os = "ios")))]
+mod port_mapping;
+mod relay_select;
+mod turn;
+mod heartbeat;
+mod dscp;
 #[cfg(not(any(target_os = "android", target_os = "ios")))]
 mod port_forward;
 #[cfg(not(any(target_os = "android", target_os = "ios")))]
@@ -43,8 +43,17 @@ fn main() {
     use clap::App;
     use hbb_common::log;
     let args = format!(
-        "-p, --port-forward=[PORT-FORWARD-OPTIONS] 'Format: remote-id:local-port:remote-port[:remote-host]'
+        "-p, --port-forward=[PORT-FORWARD-OPTIONS] 'Format: remote-id:local-port:remote-port[:remote-host], remote-port 0 for a SOCKS5 tunnel'
         -c, --connect=[REMOTE_ID] 'test only'
+        --screenshot=[SCREENSHOT-OPTIONS] 'Format: remote-id[:display], save a PNG of the remote display'
+        --play-macro=[MACRO-OPTIONS] 'Format: remote-id:name, play a macro recorded with the remote'
+        --download=[REMOTE_ID] 'copy the file or folder --from of the remote into the local folder --to'
+        --upload=[REMOTE_ID] 'copy the local file or folder --from into the folder --to of the remote'
+        --from=[PATH] ''
+        --to=[PATH] ''
+        --on-conflict=[POLICY] 'overwrite, newer, skip or rename, the files existing where copied'
+        --webdav=[WEBDAV-OPTIONS] 'Format: remote-id:local-port, serve the files of the remote over WebDAV on localhost'
+        --terminal=[REMOTE_ID] 'open a shell of the remote in this terminal'
         -k, --key=[KEY] ''
        -s, --server=[] 'Start server'",
     );
@@ -98,6 +107,83 @@ fn main() {
         let key = matches.value_of("key").unwrap_or("").to_owned();
         let token = LocalConfig::get_option("access_token");
         cli::connect_test(p, key, token);
+    } else if let Some(p) = matches.value_of("screenshot") {
+        let options: Vec<&str> = p.split(":").collect();
+        let mut display = 0;
+        if options.len() > 1 {
+            if let Ok(v) = options[1].parse::<i32>() {
+                display = v;
+            } else {
+                log::error!("Wrong display");
+                return;
+            }
+        }
+        common::test_rendezvous_server();
+        common::test_nat_type();
+        let key = matches.value_of("key").unwrap_or("").to_owned();
+        let token = LocalConfig::get_option("access_token");
+        cli::take_screenshot(options[0], display, key, token);
+    } else if let Some(p) = matches.value_of("play-macro") {
+        let Some((id, name)) = p.split_once(':') else {
+            log::error!("Wrong play-macro options");
+            return;
+        };
+        common::test_rendezvous_server();
+        common::test_nat_type();
+        let key = matches.value_of("key").unwrap_or("").to_owned();
+        let token = LocalConfig::get_option("access_token");
+        cli::play_macro(id, name, key, token);
+    } else if let Some(id) = matches
+        .value_of("download")
+        .or_else(|| matches.value_of("upload"))
+    {
+        let (Some(from), Some(to)) = (matches.value_of("from"), matches.value_of("to")) else {
+            log::error!("Missing --from or --to");
+            return;
+        };
+        let policy = match matches.value_of("on-conflict") {
+            Some(name) => match hbb_common::fs::conflict::parse(name) {
+                Some(policy) => policy,
+                None => {
+                    log::error!("Wrong on-conflict policy");
+                    return;
+                }
+            },
+            None => hbb_common::fs::conflict::policy_from_local_config(),
+        };
+        common::test_rendezvous_server();
+        common::test_nat_type();
+        let key = matches.value_of("key").unwrap_or("").to_owned();
+        let token = LocalConfig::get_option("access_token");
+        let upload = matches.value_of("upload").is_some();
+        if !cli::copy_files(id, upload, from, to, policy, key, token) {
+            common::global_clean();
+            std::process::exit(1);
+        }
+    } else if let Some(p) = matches.value_of("webdav") {
+        let Some((id, port)) = p.split_once(':') else {
+            log::error!("Wrong webdav options");
+            return;
+        };
+        let Ok(port) = port.parse::<u16>() else {
+            log::error!("Wrong local-port");
+            return;
+        };
+        common::test_rendezvous_server();
+        common::test_nat_type();
+        let key = matches.value_of("key").unwrap_or("").to_owned();
+        let token = LocalConfig::get_option("access_token");
+        cli::serve_webdav(id, port, key, token);
+    } else if let Some(id) = matches.value_of("terminal") {
+        common::test_rendezvous_server();
+        common::test_nat_type();
+        let key = matches.value_of("key").unwrap_or("").to_owned();
+        let token = LocalConfig::get_option("access_token");
+        let exit_code = cli::open_terminal(id, key, token);
+        if exit_code != 0 {
+            common::global_clean();
+            std::process::exit(exit_code);
+        }
     } else if let Some(p) = matches.value_of("server") {
         log::info!("id={}", hbb_common::config::Config::get_id());
         crate::start_server(true, false);
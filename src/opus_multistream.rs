@@ -0,0 +1,300 @@
+// Opus multistream, for the audio of more than 2 channels, e.g. 5.1 and 7.1.
+//
+// magnum-opus binds the encoder and the decoder of mono and stereo only, the multistream API of
+// the libopus it links is declared here. The surround mapping of Vorbis codes the channels in
+// their order, while the capture and the playback are in the order of WAVE, so the channels are
+// reordered on both sides.
+
+use hbb_common::{bail, ResultType};
+use magnum_opus::{Application, Bitrate};
+use std::os::raw::{c_int, c_uchar};
+
+const OPUS_OK: c_int = 0;
+const OPUS_AUTO: c_int = -1000;
+const OPUS_BITRATE_MAX: c_int = -1;
+const OPUS_SET_BITRATE_REQUEST: c_int = 4002;
+const OPUS_SET_INBAND_FEC_REQUEST: c_int = 4012;
+const OPUS_SET_PACKET_LOSS_PERC_REQUEST: c_int = 4014;
+// the surround mapping of Vorbis, 1 to 8 channels
+const MAPPING_FAMILY_VORBIS: c_int = 1;
+pub const MAX_CHANNELS: u16 = 8;
+
+// the channel of WAVE of each channel of Vorbis
+const ORDER_6: [usize; 6] = [0, 2, 1, 4, 5, 3];
+const ORDER_8: [usize; 8] = [0, 2, 1, 6, 7, 4, 5, 3];
+
+#[repr(C)]
+struct OpusMSEncoder {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct OpusMSDecoder {
+    _private: [u8; 0],
+}
+
+extern "C" {
+    fn opus_multistream_surround_encoder_create(
+        fs: i32,
+        channels: c_int,
+        mapping_family: c_int,
+        streams: *mut c_int,
+        coupled_streams: *mut c_int,
+        mapping: *mut c_uchar,
+        application: c_int,
+        error: *mut c_int,
+    ) -> *mut OpusMSEncoder;
+    fn opus_multistream_encode_float(
+        st: *mut OpusMSEncoder,
+        pcm: *const f32,
+        frame_size: c_int,
+        data: *mut c_uchar,
+        max_data_bytes: i32,
+    ) -> i32;
+    fn opus_multistream_encoder_ctl(st: *mut OpusMSEncoder, request: c_int, ...) -> c_int;
+    fn opus_multistream_encoder_destroy(st: *mut OpusMSEncoder);
+    fn opus_multistream_decoder_create(
+        fs: i32,
+        channels: c_int,
+        streams: c_int,
+        coupled_streams: c_int,
+        mapping: *const c_uchar,
+        error: *mut c_int,
+    ) -> *mut OpusMSDecoder;
+    fn opus_multistream_decode_float(
+        st: *mut OpusMSDecoder,
+        data: *const c_uchar,
+        len: i32,
+        pcm: *mut f32,
+        frame_size: c_int,
+        decode_fec: c_int,
+    ) -> c_int;
+    fn opus_multistream_decoder_destroy(st: *mut OpusMSDecoder);
+}
+
+/// Whether the channels are sent in multistream, mono and stereo are sent in the plain opus.
+#[inline]
+pub fn is_supported(channels: u16) -> bool {
+    order(channels).is_some()
+}
+
+fn order(channels: u16) -> Option<&'static [usize]> {
+    match channels {
+        6 => Some(&ORDER_6),
+        8 => Some(&ORDER_8),
+        _ => None,
+    }
+}
+
+fn check(ret: c_int, what: &str) -> ResultType<c_int> {
+    if ret < OPUS_OK {
+        bail!("opus multistream {what} failed: {ret}");
+    }
+    Ok(ret)
+}
+
+fn wave_to_vorbis(data: &[f32], order: &[usize]) -> Vec<f32> {
+    data.chunks_exact(order.len())
+        .flat_map(|frame| order.iter().map(move |i| frame[*i]))
+        .collect()
+}
+
+fn vorbis_to_wave(data: &mut [f32], order: &[usize]) {
+    let mut vorbis = vec![0.; order.len()];
+    for frame in data.chunks_exact_mut(order.len()) {
+        vorbis.copy_from_slice(frame);
+        for (sample, i) in vorbis.iter().zip(order) {
+            frame[*i] = *sample;
+        }
+    }
+}
+
+pub struct MultistreamEncoder {
+    st: *mut OpusMSEncoder,
+    order: &'static [usize],
+    streams: u32,
+    coupled_streams: u32,
+    mapping: Vec<u8>,
+}
+
+unsafe impl Send for MultistreamEncoder {}
+
+impl MultistreamEncoder {
+    pub fn new(sample_rate: u32, channels: u16, application: Application) -> ResultType<Self> {
+        let Some(order) = order(channels) else {
+            bail!("{channels} channels are not supported by opus multistream");
+        };
+        let mut streams = 0;
+        let mut coupled_streams = 0;
+        let mut mapping = vec![0u8; channels as usize];
+        let mut error = OPUS_OK;
+        let st = unsafe {
+            opus_multistream_surround_encoder_create(
+                sample_rate as _,
+                channels as _,
+                MAPPING_FAMILY_VORBIS,
+                &mut streams,
+                &mut coupled_streams,
+                mapping.as_mut_ptr(),
+                application as c_int,
+                &mut error,
+            )
+        };
+        check(error, "encoder create")?;
+        if st.is_null() {
+            bail!("opus multistream encoder create failed");
+        }
+        Ok(Self {
+            st,
+            order,
+            streams: streams as _,
+            coupled_streams: coupled_streams as _,
+            mapping,
+        })
+    }
+
+    /// The streams, the coupled ones and the mapping the decoder needs.
+    pub fn layout(&self) -> (u32, u32, &[u8]) {
+        (self.streams, self.coupled_streams, &self.mapping)
+    }
+
+    /// The bitrate of all the streams.
+    pub fn set_bitrate(&mut self, bitrate: Bitrate) -> ResultType<()> {
+        let value = match bitrate {
+            Bitrate::Bits(bits) => bits,
+            Bitrate::Max => OPUS_BITRATE_MAX,
+            Bitrate::Auto => OPUS_AUTO,
+        };
+        self.ctl(OPUS_SET_BITRATE_REQUEST, value)
+    }
+
+    pub fn set_inband_fec(&mut self, fec: bool) -> ResultType<()> {
+        self.ctl(OPUS_SET_INBAND_FEC_REQUEST, fec as _)
+    }
+
+    pub fn set_packet_loss_perc(&mut self, perc: i32) -> ResultType<()> {
+        self.ctl(OPUS_SET_PACKET_LOSS_PERC_REQUEST, perc)
+    }
+
+    fn ctl(&mut self, request: c_int, value: c_int) -> ResultType<()> {
+        check(
+            unsafe { opus_multistream_encoder_ctl(self.st, request, value) },
+            "encoder ctl",
+        )?;
+        Ok(())
+    }
+
+    /// Encodes the interleaved samples in the order of WAVE.
+    pub fn encode_vec_float(&mut self, input: &[f32], max_size: usize) -> ResultType<Vec<u8>> {
+        let input = wave_to_vorbis(input, self.order);
+        let mut output = vec![0u8; max_size];
+        let len = check(
+            unsafe {
+                opus_multistream_encode_float(
+                    self.st,
+                    input.as_ptr(),
+                    (input.len() / self.order.len()) as _,
+                    output.as_mut_ptr(),
+                    output.len() as _,
+                )
+            },
+            "encode",
+        )?;
+        output.truncate(len as _);
+        Ok(output)
+    }
+}
+
+impl Drop for MultistreamEncoder {
+    fn drop(&mut self) {
+        unsafe { opus_multistream_encoder_destroy(self.st) };
+    }
+}
+
+pub struct MultistreamDecoder {
+    st: *mut OpusMSDecoder,
+    order: &'static [usize],
+}
+
+unsafe impl Send for MultistreamDecoder {}
+
+impl MultistreamDecoder {
+    pub fn new(
+        sample_rate: u32,
+        channels: u16,
+        streams: u32,
+        coupled_streams: u32,
+        mapping: &[u8],
+    ) -> ResultType<Self> {
+        let Some(order) = order(channels) else {
+            bail!("{channels} channels are not supported by opus multistream");
+        };
+        if mapping.len() != channels as usize {
+            bail!("invalid opus multistream mapping of {channels} channels: {mapping:?}");
+        }
+        let mut error = OPUS_OK;
+        let st = unsafe {
+            opus_multistream_decoder_create(
+                sample_rate as _,
+                channels as _,
+                streams as _,
+                coupled_streams as _,
+                mapping.as_ptr(),
+                &mut error,
+            )
+        };
+        check(error, "decoder create")?;
+        if st.is_null() {
+            bail!("opus multistream decoder create failed");
+        }
+        Ok(Self { st, order })
+    }
+
+    /// Decodes into the interleaved samples in the order of WAVE, returns the samples of each
+    /// channel.
+    pub fn decode_float(&mut self, input: &[u8], output: &mut [f32]) -> ResultType<usize> {
+        let n = check(
+            unsafe {
+                opus_multistream_decode_float(
+                    self.st,
+                    input.as_ptr(),
+                    input.len() as _,
+                    output.as_mut_ptr(),
+                    (output.len() / self.order.len()) as _,
+                    0,
+                )
+            },
+            "decode",
+        )? as usize;
+        vorbis_to_wave(&mut output[..n * self.order.len()], self.order);
+        Ok(n)
+    }
+}
+
+impl Drop for MultistreamDecoder {
+    fn drop(&mut self) {
+        unsafe { opus_multistream_decoder_destroy(self.st) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reorder() {
+        // FL FR FC LFE BL BR of WAVE
+        let wave = [1., 2., 3., 4., 5., 6.];
+        let vorbis = wave_to_vorbis(&wave, &ORDER_6);
+        // FL FC FR RL RR LFE of Vorbis
+        assert_eq!(vorbis, [1., 3., 2., 5., 6., 4.]);
+        for order in [&ORDER_6[..], &ORDER_8[..]] {
+            let wave: Vec<f32> = (0..order.len() * 3).map(|x| x as f32).collect();
+            let mut data = wave_to_vorbis(&wave, order);
+            vorbis_to_wave(&mut data, order);
+            assert_eq!(data, wave);
+        }
+        assert!(!is_supported(2));
+        assert!(is_supported(8));
+    }
+}
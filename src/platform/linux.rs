@@ -967,6 +967,60 @@ pub fn change_resolution_directly(name: &str, width: usize, height: usize) -> Re
     Ok(())
 }
 
+/// The layout and the variant of X11, none if there are more than one to switch between.
+pub fn get_keyboard_layout() -> Option<(String, String)> {
+    let output = run_cmds("setxkbmap -query").ok()?;
+    let get = |name: &str| {
+        output
+            .lines()
+            .find_map(|l| l.strip_prefix(name))
+            .map(|v| v.trim().to_owned())
+            .unwrap_or_default()
+    };
+    let (layout, variant) = (get("layout:"), get("variant:"));
+    if layout.is_empty() || layout.contains(',') {
+        return None;
+    }
+    Some((layout, variant))
+}
+
+fn default_sink() -> ResultType<(
+    pulsectl::controllers::SinkController,
+    pulsectl::controllers::types::DeviceInfo,
+)> {
+    use pulsectl::controllers::*;
+    let mut handler = SinkController::create().map_err(|e| anyhow!("{:?}", e))?;
+    let dev = handler
+        .get_default_device()
+        .map_err(|e| anyhow!("{:?}", e))?;
+    Ok((handler, dev))
+}
+
+/// The volume of the default sink from 0 to 100, and whether it is muted.
+pub fn get_master_volume() -> ResultType<(u32, bool)> {
+    let (_, dev) = default_sink()?;
+    let normal = pulse::volume::Volume::NORMAL.0 as u64;
+    let level = (dev.volume.avg().0 as u64 * 100 + normal / 2) / normal;
+    Ok((level.min(100) as u32, dev.mute))
+}
+
+pub fn set_master_volume(level: u32) -> ResultType<()> {
+    use pulsectl::controllers::DeviceControl;
+    let (mut handler, dev) = default_sink()?;
+    let volume = level as u64 * pulse::volume::Volume::NORMAL.0 as u64 / 100;
+    let mut volumes = dev.volume;
+    volumes.set(volumes.len(), pulse::volume::Volume(volume as u32));
+    handler.set_device_volume_by_index(dev.index, &volumes);
+    Ok(())
+}
+
+pub fn set_master_mute(mute: bool) -> ResultType<()> {
+    use pulsectl::controllers::DeviceControl;
+    let (mut handler, dev) = default_sink()?;
+    handler.set_device_mute_by_index(dev.index, mute);
+    Ok(())
+}
+
 #[inline]
 pub fn is_xwayland_running() -> bool {
     if let Ok(output) = run_cmds("pgrep -a Xwayland") {
@@ -56,6 +56,74 @@ extern "C" {
     fn MacSetMode(display: u32, width: u32, height: u32) -> BOOL;
 }
 
+#[link(name = "Carbon", kind = "framework")]
+extern "C" {
+    static kTISPropertyInputSourceID: CFStringRef;
+    fn TISCopyCurrentKeyboardLayoutInputSource() -> *mut c_void;
+    fn TISGetInputSourceProperty(source: *mut c_void, key: CFStringRef) -> *mut c_void;
+    fn IsSecureEventInputEnabled() -> u8;
+}
+
+/// The input source id of the keyboard layout, e.g. `com.apple.keylayout.German`.
+pub fn get_keyboard_layout() -> Option<String> {
+    use core_foundation::{
+        base::{CFRelease, TCFType},
+        string::CFString,
+    };
+    unsafe {
+        let source = TISCopyCurrentKeyboardLayoutInputSource();
+        if source.is_null() {
+            return None;
+        }
+        let id = TISGetInputSourceProperty(source, kTISPropertyInputSourceID) as CFStringRef;
+        let layout = (!id.is_null()).then(|| CFString::wrap_under_get_rule(id).to_string());
+        CFRelease(source as _);
+        layout
+    }
+}
+
+/// The secure keyboard entry of the terminals or the password fields, the injected keys are dropped.
+pub fn is_secure_input() -> bool {
+    unsafe { IsSecureEventInputEnabled() != 0 }
+}
+
+fn run_applescript(script: &str) -> ResultType<String> {
+    let output = std::process::Command::new("osascript")
+        .args(["-e", script])
+        .output()?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// The output volume from 0 to 100, and whether it is muted.
+pub fn get_master_volume() -> ResultType<(u32, bool)> {
+    // e.g. "output volume:50, input volume:75, alert volume:100, output muted:false"
+    let settings = run_applescript("get volume settings")?;
+    let get = |name: &str| {
+        settings
+            .split(',')
+            .find_map(|s| s.trim().strip_prefix(name))
+            .map(|v| v.trim().to_owned())
+            .unwrap_or_default()
+    };
+    let Ok(level) = get("output volume:").parse::<u32>() else {
+        bail!("no output volume: {settings}");
+    };
+    Ok((level.min(100), get("output muted:") == "true"))
+}
+
+pub fn set_master_volume(level: u32) -> ResultType<()> {
+    run_applescript(&format!("set volume output volume {}", level.min(100)))?;
+    Ok(())
+}
+
+pub fn set_master_mute(mute: bool) -> ResultType<()> {
+    run_applescript(&format!("set volume output muted {mute}"))?;
+    Ok(())
+}
+
 pub fn major_version() -> u32 {
     unsafe { majorVersion() }
 }
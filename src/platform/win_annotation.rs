@@ -0,0 +1,227 @@
+// The overlay showing the drawings of the peers over the screens of this side: a window above all
+// the others covering the virtual screen, transparent but for the strokes, which lets the mouse
+// through and is hidden from the capture.
+//
+// It has a thread of its own, started with the first drawing, and is hidden while there is none.
+
+use std::{
+    mem::{size_of, zeroed},
+    ptr::{null, null_mut},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use hbb_common::{
+    log,
+    message_proto::{annotation_stroke::Shape, AnnotationStroke},
+};
+use winapi::{
+    shared::{
+        minwindef::{DWORD, FALSE, LPARAM, LRESULT, TRUE, UINT, WPARAM},
+        windef::{COLORREF, HDC, HWND, POINT},
+    },
+    um::{libloaderapi::GetModuleHandleW, wingdi::*, winuser::*},
+};
+
+const CLASS_NAME: &str = "RustDeskAnnotationOverlay";
+// the color painted transparent, RGB(1, 0, 1)
+const KEY_COLOR: COLORREF = 0x00010001;
+// https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-setwindowdisplayaffinity
+const WDA_EXCLUDEFROMCAPTURE: DWORD = 0x11;
+const WM_ANNOTATION_CHANGED: UINT = WM_USER + 1;
+const ARROW_HEAD_LEN: f64 = 16.0;
+// of a wing of the head to the shaft, in radians
+const ARROW_HEAD_ANGLE: f64 = 0.45;
+
+lazy_static::lazy_static! {
+    static ref STROKES: Mutex<Vec<AnnotationStroke>> = Default::default();
+}
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+static OVERLAY: AtomicUsize = AtomicUsize::new(0);
+
+/// Shows the drawings over the screens, hides the overlay if there is none.
+pub fn show(strokes: Vec<AnnotationStroke>) {
+    *STROKES.lock().unwrap() = strokes;
+    if !STARTED.swap(true, Ordering::SeqCst) {
+        std::thread::spawn(run);
+        return;
+    }
+    let hwnd = OVERLAY.load(Ordering::SeqCst) as HWND;
+    if !hwnd.is_null() {
+        unsafe {
+            PostMessageW(hwnd, WM_ANNOTATION_CHANGED, 0, 0);
+        }
+    }
+}
+
+fn run() {
+    unsafe {
+        let hinstance = GetModuleHandleW(null());
+        let class_name = wide(CLASS_NAME);
+        let mut wc: WNDCLASSEXW = zeroed();
+        wc.cbSize = size_of::<WNDCLASSEXW>() as _;
+        wc.lpfnWndProc = Some(wnd_proc);
+        wc.hInstance = hinstance;
+        wc.lpszClassName = class_name.as_ptr();
+        RegisterClassExW(&wc);
+        let hwnd = CreateWindowExW(
+            WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_TOPMOST | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+            class_name.as_ptr(),
+            class_name.as_ptr(),
+            WS_POPUP,
+            0,
+            0,
+            0,
+            0,
+            null_mut(),
+            null_mut(),
+            hinstance,
+            null_mut(),
+        );
+        if hwnd.is_null() {
+            log::error!(
+                "Failed to create the annotation overlay: {}",
+                std::io::Error::last_os_error()
+            );
+            STARTED.store(false, Ordering::SeqCst);
+            return;
+        }
+        SetLayeredWindowAttributes(hwnd, KEY_COLOR, 0, LWA_COLORKEY);
+        // before Windows 10 2004, the peers see the strokes of the overlay too
+        if SetWindowDisplayAffinity(hwnd, WDA_EXCLUDEFROMCAPTURE) == FALSE {
+            log::warn!(
+                "Failed to hide the annotation overlay from the capture: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        OVERLAY.store(hwnd as _, Ordering::SeqCst);
+        update(hwnd);
+        let mut msg: MSG = zeroed();
+        while GetMessageW(&mut msg, null_mut(), 0, 0) > 0 {
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+}
+
+unsafe fn update(hwnd: HWND) {
+    if STROKES.lock().unwrap().is_empty() {
+        ShowWindow(hwnd, SW_HIDE);
+        return;
+    }
+    // the screens may have changed since the last time
+    SetWindowPos(
+        hwnd,
+        HWND_TOPMOST,
+        GetSystemMetrics(SM_XVIRTUALSCREEN),
+        GetSystemMetrics(SM_YVIRTUALSCREEN),
+        GetSystemMetrics(SM_CXVIRTUALSCREEN),
+        GetSystemMetrics(SM_CYVIRTUALSCREEN),
+        SWP_NOACTIVATE | SWP_SHOWWINDOW,
+    );
+    InvalidateRect(hwnd, null(), TRUE);
+}
+
+unsafe extern "system" fn wnd_proc(
+    hwnd: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match msg {
+        WM_ANNOTATION_CHANGED => {
+            update(hwnd);
+            0
+        }
+        WM_ERASEBKGND => 1,
+        WM_PAINT => {
+            let mut ps: PAINTSTRUCT = zeroed();
+            let hdc = BeginPaint(hwnd, &mut ps);
+            paint(hwnd, hdc);
+            EndPaint(hwnd, &ps);
+            0
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+unsafe fn paint(hwnd: HWND, hdc: HDC) {
+    let mut rect = zeroed();
+    GetClientRect(hwnd, &mut rect);
+    let background = CreateSolidBrush(KEY_COLOR);
+    FillRect(hdc, &rect, background);
+    DeleteObject(background as _);
+    // the points are in the coordinates of the screens, the window starts at the virtual screen
+    let (x0, y0) = (
+        GetSystemMetrics(SM_XVIRTUALSCREEN),
+        GetSystemMetrics(SM_YVIRTUALSCREEN),
+    );
+    let old_brush = SelectObject(hdc, GetStockObject(NULL_BRUSH as _));
+    for stroke in STROKES.lock().unwrap().iter() {
+        let mut points: Vec<POINT> = stroke
+            .points
+            .chunks_exact(2)
+            .map(|p| POINT {
+                x: p[0] - x0,
+                y: p[1] - y0,
+            })
+            .collect();
+        if points.is_empty() {
+            continue;
+        }
+        let pen = CreatePen(PS_SOLID as _, stroke.width as _, color(stroke.color));
+        let old_pen = SelectObject(hdc, pen as _);
+        match stroke.shape.enum_value() {
+            Ok(Shape::Box) if points.len() == 2 => {
+                Rectangle(hdc, points[0].x, points[0].y, points[1].x, points[1].y);
+            }
+            Ok(Shape::Arrow) if points.len() == 2 => {
+                Polyline(hdc, points.as_ptr(), 2);
+                let head = arrow_head(points[0], points[1], stroke.width);
+                Polyline(hdc, head.as_ptr(), head.len() as _);
+            }
+            _ => {
+                // a dot
+                if points.len() == 1 {
+                    points.push(POINT {
+                        x: points[0].x + 1,
+                        y: points[0].y,
+                    });
+                }
+                Polyline(hdc, points.as_ptr(), points.len() as _);
+            }
+        }
+        SelectObject(hdc, old_pen);
+        DeleteObject(pen as _);
+    }
+    SelectObject(hdc, old_brush);
+}
+
+// the two wings of the head around its tip
+fn arrow_head(tail: POINT, head: POINT, width: u32) -> [POINT; 3] {
+    let (dx, dy) = ((head.x - tail.x) as f64, (head.y - tail.y) as f64);
+    let angle = dy.atan2(dx);
+    let len = ARROW_HEAD_LEN + width as f64 * 2.0;
+    let wing = |a: f64| POINT {
+        x: head.x - (len * (angle + a).cos()).round() as i32,
+        y: head.y - (len * (angle + a).sin()).round() as i32,
+    };
+    [wing(ARROW_HEAD_ANGLE), head, wing(-ARROW_HEAD_ANGLE)]
+}
+
+// 0xRRGGBB to the COLORREF, never the transparent one
+fn color(rgb: u32) -> COLORREF {
+    let bgr = RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8);
+    if bgr == KEY_COLOR {
+        RGB(0, 0, 1)
+    } else {
+        bgr
+    }
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
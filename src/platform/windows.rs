@@ -538,6 +538,7 @@ async fn run_service(_arguments: Vec<OsString>) -> ResultType<()> {
 
     // Tell the system that the service is running now
     status_handle.set_service_status(next_status)?;
+    clear_safe_mode_boot();
 
     let mut session_id = unsafe { get_current_session(share_rdp()) };
     log::info!("session id {}", session_id);
@@ -1680,6 +1681,23 @@ pub fn run_uac(exe: &str, arg: &str) -> ResultType<bool> {
     }
 }
 
+/// Prints the file with the application of its type, false if there is none.
+pub fn print_file(path: &str) -> ResultType<bool> {
+    let wop = wide_string("print");
+    let wpath = wide_string(path);
+    unsafe {
+        let ret = ShellExecuteW(
+            NULL as _,
+            wop.as_ptr() as _,
+            wpath.as_ptr() as _,
+            NULL as _,
+            NULL as _,
+            SW_HIDE,
+        );
+        Ok(ret as i32 > 32)
+    }
+}
+
 pub fn check_super_user_permission() -> ResultType<bool> {
     run_uac(
         std::env::current_exe()?
@@ -2106,6 +2124,157 @@ pub fn get_unicode_from_vk(vk: u32) -> Option<u16> {
     }
 }
 
+/// The HKL of the foreground window, the low word is the language and the high word the layout.
+pub fn get_keyboard_layout() -> u32 {
+    unsafe {
+        let current_window_thread_id = GetWindowThreadProcessId(GetForegroundWindow(), null_mut());
+        GetKeyboardLayout(current_window_thread_id) as usize as u32
+    }
+}
+
+/// The input desktop can not be opened, e.g. the secure desktop of UAC or the lock screen, which
+/// only the service can inject the input to.
+pub fn is_secure_input() -> bool {
+    unsafe {
+        let desktop = OpenInputDesktop(0, FALSE, DESKTOP_SWITCHDESKTOP);
+        if desktop.is_null() {
+            return true;
+        }
+        CloseDesktop(desktop);
+        false
+    }
+}
+
+// Runs `f` with the endpoint volume of the default output.
+fn with_endpoint_volume<T>(
+    f: impl FnOnce(&winapi::um::endpointvolume::IAudioEndpointVolume) -> ResultType<T>,
+) -> ResultType<T> {
+    use winapi::{
+        um::{
+            combaseapi::{CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_ALL},
+            endpointvolume::IAudioEndpointVolume,
+            mmdeviceapi::{
+                eConsole, eRender, CLSID_MMDeviceEnumerator, IMMDevice, IMMDeviceEnumerator,
+            },
+            objbase::COINIT_MULTITHREADED,
+        },
+        Interface,
+    };
+    unsafe {
+        // not uninitialized if the thread is in another apartment already
+        let initialized = SUCCEEDED(CoInitializeEx(null_mut(), COINIT_MULTITHREADED));
+        let res = (|| {
+            let mut enumerator: *mut IMMDeviceEnumerator = null_mut();
+            let hr = CoCreateInstance(
+                &CLSID_MMDeviceEnumerator,
+                null_mut(),
+                CLSCTX_ALL,
+                &IMMDeviceEnumerator::uuidof(),
+                &mut enumerator as *mut _ as _,
+            );
+            if FAILED(hr) {
+                bail!("failed to create the device enumerator: {:#x}", hr);
+            }
+            let mut device: *mut IMMDevice = null_mut();
+            let hr = (*enumerator).GetDefaultAudioEndpoint(eRender, eConsole, &mut device);
+            (*enumerator).Release();
+            if FAILED(hr) {
+                bail!("failed to get the default output: {:#x}", hr);
+            }
+            let mut volume: *mut IAudioEndpointVolume = null_mut();
+            let hr = (*device).Activate(
+                &IAudioEndpointVolume::uuidof(),
+                CLSCTX_ALL,
+                null_mut(),
+                &mut volume as *mut _ as _,
+            );
+            (*device).Release();
+            if FAILED(hr) {
+                bail!("failed to activate the endpoint volume: {:#x}", hr);
+            }
+            let res = f(&*volume);
+            (*volume).Release();
+            res
+        })();
+        if initialized {
+            CoUninitialize();
+        }
+        res
+    }
+}
+
+/// The volume of the default output from 0 to 100, and whether it is muted.
+pub fn get_master_volume() -> ResultType<(u32, bool)> {
+    with_endpoint_volume(|volume| unsafe {
+        let mut level = 0f32;
+        let mut mute: BOOL = FALSE;
+        if FAILED(volume.GetMasterVolumeLevelScalar(&mut level))
+            || FAILED(volume.GetMute(&mut mute))
+        {
+            bail!("failed to get the volume");
+        }
+        Ok(((level * 100.).round() as u32, mute != FALSE))
+    })
+}
+
+pub fn set_master_volume(level: u32) -> ResultType<()> {
+    with_endpoint_volume(|volume| unsafe {
+        let hr = volume.SetMasterVolumeLevelScalar(level.min(100) as f32 / 100., std::ptr::null());
+        if FAILED(hr) {
+            bail!("failed to set the volume: {:#x}", hr);
+        }
+        Ok(())
+    })
+}
+
+pub fn set_master_mute(mute: bool) -> ResultType<()> {
+    with_endpoint_volume(|volume| unsafe {
+        let hr = volume.SetMute(if mute { TRUE } else { FALSE }, std::ptr::null());
+        if FAILED(hr) {
+            bail!("failed to set the mute: {:#x}", hr);
+        }
+        Ok(())
+    })
+}
+
+const SAFE_BOOT_NETWORK_PATH: &str = "SYSTEM\\CurrentControlSet\\Control\\SafeBoot\\Network";
+
+/// Boot into the safe mode with networking next time, with the service registered to run there so
+/// that the peer can connect again.
+pub fn set_safe_mode_boot() -> ResultType<()> {
+    let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+    let (key, _) = hklm.create_subkey(format!(
+        "{}\\{}",
+        SAFE_BOOT_NETWORK_PATH,
+        crate::get_app_name()
+    ))?;
+    key.set_value("", &"Service")?;
+    bcdedit(&["/set", "{current}", "safeboot", "network"])
+}
+
+/// The safe mode of set_safe_mode_boot is for one boot only, undone as the service starts.
+pub fn clear_safe_mode_boot() {
+    let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+    let path = format!("{}\\{}", SAFE_BOOT_NETWORK_PATH, crate::get_app_name());
+    if hklm.open_subkey(&path).is_err() {
+        return;
+    }
+    log::info!("Boot normally next time");
+    allow_err!(bcdedit(&["/deletevalue", "{current}", "safeboot"]));
+    allow_err!(hklm.delete_subkey(&path));
+}
+
+fn bcdedit(args: &[&str]) -> ResultType<()> {
+    let status = std::process::Command::new("bcdedit")
+        .args(args)
+        .creation_flags(CREATE_NO_WINDOW)
+        .status()?;
+    if !status.success() {
+        bail!("bcdedit {:?} failed: {}", args, status);
+    }
+    Ok(())
+}
+
 pub fn is_process_consent_running() -> ResultType<bool> {
     let output = std::process::Command::new("cmd")
         .args(&["/C", "tasklist | findstr consent.exe"])
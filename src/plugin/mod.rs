@@ -24,8 +24,8 @@ pub use manager::{
     install_plugin, load_plugin_list, remove_uninstalled, uninstall_plugin,
 };
 pub use plugins::{
-    handle_client_event, handle_listen_event, handle_server_event, handle_ui_event, load_plugin,
-    reload_plugin, sync_ui, unload_plugin,
+    handle_client_event, handle_listen_event, handle_listen_event_data, handle_server_event,
+    handle_ui_event, load_plugin, reload_plugin, sync_ui, unload_plugin,
 };
 
 const MSG_TO_UI_TYPE_PLUGIN_EVENT: &str = "plugin_event";
@@ -37,6 +37,8 @@ pub const EVENT_ON_CONN_CLIENT: &str = "on_conn_client";
 pub const EVENT_ON_CONN_SERVER: &str = "on_conn_server";
 pub const EVENT_ON_CONN_CLOSE_CLIENT: &str = "on_conn_close_client";
 pub const EVENT_ON_CONN_CLOSE_SERVER: &str = "on_conn_close_server";
+// with the event of the transfer as the data
+pub const EVENT_ON_FILE_TRANSFER_SERVER: &str = "on_file_transfer_server";
 
 static PLUGIN_SOURCE_LOCAL_DIR: &str = "plugins";
 
@@ -259,6 +259,8 @@ make_plugin!(
 #[derive(Serialize)]
 pub struct MsgListenEvent {
     pub event: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
 }
 
 #[cfg(target_os = "windows")]
@@ -467,7 +469,7 @@ pub fn handle_server_event(id: &str, peer: &str, event: &[u8]) -> ResultType<()>
     handle_event(METHOD_HANDLE_PEER, id, peer, event)
 }
 
-fn _handle_listen_event(event: String, peer: String) {
+fn _handle_listen_event(event: String, peer: String, data: Option<serde_json::Value>) {
     let mut plugins = Vec::new();
     for info in PLUGIN_INFO.read().unwrap().values() {
         if info.desc.listen_events().contains(&event.to_string()) {
@@ -481,6 +483,7 @@ fn _handle_listen_event(event: String, peer: String) {
 
     if let Ok(evt) = serde_json::to_string(&MsgListenEvent {
         event: event.clone(),
+        data,
     }) {
         let mut evt_bytes = evt.as_bytes().to_vec();
         evt_bytes.push(0);
@@ -516,7 +519,13 @@ fn _handle_listen_event(event: String, peer: String) {
 
 #[inline]
 pub fn handle_listen_event(event: String, peer: String) {
-    std::thread::spawn(|| _handle_listen_event(event, peer));
+    std::thread::spawn(|| _handle_listen_event(event, peer, None));
+}
+
+/// Passes the event to the plugins listening with the data of it.
+#[inline]
+pub fn handle_listen_event_data(event: String, peer: String, data: serde_json::Value) {
+    std::thread::spawn(|| _handle_listen_event(event, peer, Some(data)));
 }
 
 #[inline]
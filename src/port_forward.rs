@@ -1,8 +1,12 @@
-use std::sync::{Arc, RwLock};
+use std::{
+    net::{Ipv4Addr, Ipv6Addr},
+    sync::{Arc, RwLock},
+};
 
 use crate::client::*;
 use hbb_common::{
     allow_err, bail,
+    bytes::Bytes,
     config::READ_TIMEOUT,
     futures::{SinkExt, StreamExt},
     log,
@@ -10,11 +14,26 @@ use hbb_common::{
     protobuf::Message as _,
     rendezvous_proto::ConnType,
     tcp, timeout,
-    tokio::{self, net::TcpStream, sync::mpsc},
+    tokio::{
+        self,
+        io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+        net::TcpStream,
+        sync::mpsc,
+    },
     tokio_util::codec::{BytesCodec, Framed},
     ResultType, Stream,
 };
 
+// in milliseconds
+const SOCKS_TIMEOUT: u64 = 3_000;
+const SOCKS_VERSION: u8 = 5;
+const SOCKS_NO_AUTH: u8 = 0;
+const SOCKS_NO_METHOD: u8 = 0xff;
+const SOCKS_CONNECT: u8 = 1;
+const SOCKS_SUCCEEDED: u8 = 0;
+const SOCKS_REFUSED: u8 = 5;
+const SOCKS_NOT_SUPPORTED: u8 = 7;
+
 fn run_rdp(port: u16) {
     std::process::Command::new("cmdkey")
         .arg("/delete:localhost")
@@ -54,23 +73,48 @@ pub async fn listen(
     remote_host: String,
     remote_port: i32,
 ) -> ResultType<()> {
-    let listener = tcp::new_listener(format!("0.0.0.0:{}", port), true).await?;
+    let is_rdp = port == 0;
+    // a remote port of 0 tunnels to the targets requested through a socks5 proxy, which is not
+    // opened to the network
+    let is_socks = !is_rdp && remote_port == 0;
+    let listen_addr = if is_socks { "127.0.0.1" } else { "0.0.0.0" };
+    let listener = tcp::new_listener(format!("{}:{}", listen_addr, port), true).await?;
     let addr = listener.local_addr()?;
     log::info!("listening on port {:?}", addr);
-    let is_rdp = port == 0;
     if is_rdp {
         run_rdp(addr.port());
     }
     let mut ui_receiver = ui_receiver;
     loop {
         tokio::select! {
-            Ok((forward, addr)) = listener.accept() => {
+            Ok((mut forward, addr)) = listener.accept() => {
                 log::info!("new connection from {:?}", addr);
-                lc.write().unwrap().port_forward = (remote_host.clone(), remote_port);
+                let target = if is_socks {
+                    match timeout(SOCKS_TIMEOUT, accept_socks(&mut forward)).await {
+                        Ok(Ok(target)) => target,
+                        Ok(Err(err)) => {
+                            log::error!("socks request from {:?} failed: {}", addr, err);
+                            continue;
+                        }
+                        Err(_) => {
+                            log::error!("socks request from {:?} timed out", addr);
+                            continue;
+                        }
+                    }
+                } else {
+                    (remote_host.clone(), remote_port)
+                };
+                log::info!("forwarding {:?} to {:?}", addr, target);
+                lc.write().unwrap().port_forward = target;
                 let id = id.clone();
                 let password = password.clone();
                 let mut forward = Framed::new(forward, BytesCodec::new());
-                match connect_and_login(&id, &password, &mut ui_receiver, interface.clone(), &mut forward, key, token, is_rdp).await {
+                let res = connect_and_login(&id, &password, &mut ui_receiver, interface.clone(), &mut forward, key, token, is_rdp).await;
+                if is_socks {
+                    let reply = if matches!(res, Ok(Some(_))) { SOCKS_SUCCEEDED } else { SOCKS_REFUSED };
+                    allow_err!(forward.send(socks_reply(reply)).await);
+                }
+                match res {
                     Ok(Some(stream)) => {
                         let interface = interface.clone();
                         tokio::spawn(async move {
@@ -121,6 +165,7 @@ async fn connect_and_login(
     let ((mut stream, direct, _pk), (feedback, rendezvous_server)) =
         Client::start(id, key, token, conn_type, interface.clone()).await?;
     interface.update_direct(Some(direct));
+    crate::dscp::mark(&stream, true);
     let mut buffer = Vec::new();
     let mut received = false;
 
@@ -194,6 +239,58 @@ async fn connect_and_login(
     Ok(Some(stream))
 }
 
+// the target of a socks5 connect request, only tcp is tunneled
+async fn accept_socks<S: AsyncRead + AsyncWrite + Unpin>(
+    socket: &mut S,
+) -> ResultType<(String, i32)> {
+    let mut head = [0u8; 2];
+    socket.read_exact(&mut head).await?;
+    if head[0] != SOCKS_VERSION {
+        bail!("unsupported socks version {}", head[0]);
+    }
+    let mut methods = vec![0u8; head[1] as usize];
+    socket.read_exact(&mut methods).await?;
+    if !methods.contains(&SOCKS_NO_AUTH) {
+        socket.write_all(&[SOCKS_VERSION, SOCKS_NO_METHOD]).await?;
+        bail!("no acceptable socks authentication method");
+    }
+    socket.write_all(&[SOCKS_VERSION, SOCKS_NO_AUTH]).await?;
+    let mut request = [0u8; 4];
+    socket.read_exact(&mut request).await?;
+    if request[1] != SOCKS_CONNECT {
+        socket.write_all(&socks_reply(SOCKS_NOT_SUPPORTED)).await?;
+        bail!("unsupported socks command {}", request[1]);
+    }
+    let host = match request[3] {
+        1 => {
+            let mut ip = [0u8; 4];
+            socket.read_exact(&mut ip).await?;
+            Ipv4Addr::from(ip).to_string()
+        }
+        3 => {
+            let len = socket.read_u8().await?;
+            let mut name = vec![0u8; len as usize];
+            socket.read_exact(&mut name).await?;
+            String::from_utf8(name)?
+        }
+        4 => {
+            let mut ip = [0u8; 16];
+            socket.read_exact(&mut ip).await?;
+            format!("[{}]", Ipv6Addr::from(ip))
+        }
+        atyp => {
+            socket.write_all(&socks_reply(SOCKS_NOT_SUPPORTED)).await?;
+            bail!("unsupported socks address type {}", atyp);
+        }
+    };
+    let port = socket.read_u16().await?;
+    Ok((host, port as _))
+}
+
+fn socks_reply(reply: u8) -> Bytes {
+    Bytes::from(vec![SOCKS_VERSION, reply, 0, 1, 0, 0, 0, 0, 0, 0])
+}
+
 async fn run_forward(forward: Framed<TcpStream, BytesCodec>, stream: Stream) -> ResultType<()> {
     log::info!("new port forwarding connection started");
     let mut forward = forward;
@@ -218,3 +315,44 @@ async fn run_forward(forward: Framed<TcpStream, BytesCodec>, stream: Stream) ->
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_accept_socks() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client
+            .write_all(&[5, 1, 0, 5, 1, 0, 3, 4, b'h', b'o', b's', b't', 0, 22])
+            .await
+            .unwrap();
+        assert_eq!(
+            accept_socks(&mut server).await.unwrap(),
+            ("host".to_owned(), 22)
+        );
+        let mut buf = [0u8; 2];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [5, 0]);
+
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let mut request = vec![5, 1, 0, 5, 1, 0, 4];
+        request.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        request.extend_from_slice(&[1, 0]);
+        client.write_all(&request).await.unwrap();
+        assert_eq!(
+            accept_socks(&mut server).await.unwrap(),
+            ("[::1]".to_owned(), 256)
+        );
+
+        let (mut client, mut server) = tokio::io::duplex(64);
+        client
+            .write_all(&[5, 1, 0, 5, 3, 0, 1, 127, 0, 0, 1, 0, 53])
+            .await
+            .unwrap();
+        assert!(accept_socks(&mut server).await.is_err());
+        let mut buf = [0u8; 12];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf[2..4], [5, SOCKS_NOT_SUPPORTED]);
+    }
+}
@@ -0,0 +1,217 @@
+// Single packet authorization of the direct access.
+//
+// With a knock key set, the direct access port is only listened on for a while after a valid
+// knock, an udp packet on the same port with a timestamp and a nonce authenticated by the key,
+// and only the sources of the valid knocks are accepted then. The rest of the time the port is
+// closed to the scans. The controlling side knocks before connecting to an ip if it has the key
+// set as well. The knocks are sent directly, they do not open the port for a connection through
+// a proxy.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use hbb_common::{
+    allow_err,
+    anyhow::anyhow,
+    config::{keys::OPTION_DIRECT_ACCESS_KNOCK_KEY, Config},
+    log, socket_client,
+    sodiumoxide::{
+        crypto::{auth::hmacsha256, hash::sha256},
+        randombytes::randombytes,
+    },
+    timeout,
+    tokio::{
+        net::{lookup_host, UdpSocket},
+        time::sleep,
+    },
+    ResultType, Stream,
+};
+
+const VERSION: u8 = 1;
+const NONCE_LEN: usize = 16;
+const KNOCK_LEN: usize = 1 + 8 + NONCE_LEN + hmacsha256::TAGBYTES;
+// in seconds, the clocks of the two sides may differ by this much
+const MAX_CLOCK_SKEW: u64 = 30;
+// the port stays open for the source of a valid knock for this long
+const WINDOW: Duration = Duration::from_secs(30);
+const KNOCKS: usize = 3;
+// the listener is opened within a second of the knock
+const CONNECT_TRIES: usize = 5;
+const CONNECT_INTERVAL: Duration = Duration::from_millis(300);
+
+pub fn get_key() -> Option<hmacsha256::Key> {
+    let secret = Config::get_option(OPTION_DIRECT_ACCESS_KNOCK_KEY);
+    if secret.is_empty() {
+        return None;
+    }
+    Some(hmacsha256::Key(sha256::hash(secret.as_bytes()).0))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn new_knock(key: &hmacsha256::Key, time: u64) -> Vec<u8> {
+    let mut knock = Vec::with_capacity(KNOCK_LEN);
+    knock.push(VERSION);
+    knock.extend_from_slice(&time.to_be_bytes());
+    knock.extend_from_slice(&randombytes(NONCE_LEN));
+    let tag = hmacsha256::authenticate(&knock, key);
+    knock.extend_from_slice(&tag.0);
+    knock
+}
+
+// the nonce of a valid knock
+fn check_knock(key: &hmacsha256::Key, knock: &[u8], now: u64) -> Option<[u8; NONCE_LEN]> {
+    if knock.len() != KNOCK_LEN || knock[0] != VERSION {
+        return None;
+    }
+    let (data, tag) = knock.split_at(KNOCK_LEN - hmacsha256::TAGBYTES);
+    if !hmacsha256::verify(&hmacsha256::Tag::from_slice(tag)?, data, key) {
+        return None;
+    }
+    let mut time = [0u8; 8];
+    time.copy_from_slice(&data[1..9]);
+    if now.abs_diff(u64::from_be_bytes(time)) > MAX_CLOCK_SKEW {
+        return None;
+    }
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce.copy_from_slice(&data[9..]);
+    Some(nonce)
+}
+
+/// Connects to the direct access port of `target`, knocking first if the knock key is set.
+/// QUIC is only tried without the key, the knocks open the tcp port alone.
+pub async fn connect(target: &str, ms_timeout: u64) -> ResultType<Stream> {
+    let Some(key) = get_key() else {
+        return socket_client::connect_direct(target.to_owned(), ms_timeout).await;
+    };
+    let addrs: Vec<SocketAddr> = lookup_host(target).await?.collect();
+    for addr in addrs.iter() {
+        let local: SocketAddr = if addr.is_ipv4() {
+            "0.0.0.0:0".parse()?
+        } else {
+            "[::]:0".parse()?
+        };
+        let socket = UdpSocket::bind(local).await?;
+        for _ in 0..KNOCKS {
+            allow_err!(socket.send_to(&new_knock(&key, now()), addr).await);
+        }
+    }
+    let mut res = Err(anyhow!("no address of {}", target));
+    for _ in 0..CONNECT_TRIES {
+        res = socket_client::connect_tcp(target, ms_timeout).await;
+        if res.is_ok() {
+            break;
+        }
+        sleep(CONNECT_INTERVAL).await;
+    }
+    res
+}
+
+/// The knocks received on the direct access port, which open it for their sources.
+pub struct Gate {
+    port: u16,
+    key: hmacsha256::Key,
+    socket: UdpSocket,
+    allowed: HashMap<IpAddr, Instant>,
+    seen: HashMap<[u8; NONCE_LEN], Instant>,
+}
+
+impl Gate {
+    pub fn new(port: u16, key: hmacsha256::Key) -> ResultType<Self> {
+        let socket = UdpSocket::from_std(socket_client::new_udp_any(port)?)?;
+        log::info!("Direct server waiting for knocks on udp port {}", port);
+        Ok(Self {
+            port,
+            key,
+            socket,
+            allowed: Default::default(),
+            seen: Default::default(),
+        })
+    }
+
+    pub fn is_for(&self, port: u16, key: &hmacsha256::Key) -> bool {
+        self.port == port && self.key == *key
+    }
+
+    /// Handles the knocks received, waiting up to `ms` for one.
+    pub async fn recv(&mut self, ms: u64) {
+        let mut buf = [0u8; KNOCK_LEN + 1];
+        if ms > 0 {
+            if let Ok(Ok((n, addr))) = timeout(ms, self.socket.recv_from(&mut buf)).await {
+                self.on_knock(&buf[..n], addr);
+            }
+        }
+        while let Ok((n, addr)) = self.socket.try_recv_from(&mut buf) {
+            self.on_knock(&buf[..n], addr);
+        }
+    }
+
+    fn on_knock(&mut self, knock: &[u8], addr: SocketAddr) {
+        let Some(nonce) = check_knock(&self.key, knock, now()) else {
+            log::debug!("invalid knock from {}", addr);
+            return;
+        };
+        // a knock is valid once, replayed within the clock skew
+        self.seen
+            .retain(|_, t| t.elapsed() < Duration::from_secs(MAX_CLOCK_SKEW * 2));
+        if self.seen.insert(nonce, Instant::now()).is_some() {
+            return;
+        }
+        let ip = canonical(addr.ip());
+        if !self.allowed.contains_key(&ip) {
+            log::info!("Direct server opened for {}", ip);
+        }
+        self.allowed.insert(ip, Instant::now());
+    }
+
+    /// Whether a knock is in its window.
+    pub fn is_open(&mut self) -> bool {
+        self.allowed.retain(|_, t| t.elapsed() < WINDOW);
+        !self.allowed.is_empty()
+    }
+
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        self.allowed
+            .get(&canonical(ip))
+            .map_or(false, |t| t.elapsed() < WINDOW)
+    }
+}
+
+// the ipv4 addresses accepted by an ipv6 socket are mapped
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        _ => ip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_knock() {
+        let key = hmacsha256::Key([1; 32]);
+        let knock = new_knock(&key, 1_000);
+        assert!(check_knock(&key, &knock, 1_000).is_some());
+        assert!(check_knock(&key, &knock, 1_000 + MAX_CLOCK_SKEW).is_some());
+        assert!(check_knock(&key, &knock, 1_001 + MAX_CLOCK_SKEW).is_none());
+        assert!(check_knock(&hmacsha256::Key([2; 32]), &knock, 1_000).is_none());
+        let mut tampered = knock.clone();
+        tampered[1] ^= 1;
+        assert!(check_knock(&key, &tampered, 1_000).is_none());
+        assert!(check_knock(&key, &knock[1..], 1_000).is_none());
+        assert_eq!(
+            canonical("::ffff:1.2.3.4".parse().unwrap()),
+            "1.2.3.4".parse::<IpAddr>().unwrap()
+        );
+    }
+}
@@ -0,0 +1,601 @@
+// Mapping of the tcp ports on the router with PCP, NAT-PMP or UPnP IGD, if allowed.
+//
+// The direct access port is mapped while it is listened on, so that it is reachable from the
+// internet behind a nat. A port is also mapped for each punch hole, and the punch hole is made from
+// it, so that the nat keeps the port and lets the peer in whatever its filtering.
+//
+// The gateway is looked for once and remembered with the protocol it speaks: PCP at the default
+// gateway, NAT-PMP if it is an older one, then UPnP IGD found with SSDP. The mappings are leased
+// and renewed at the half of the lease, and deleted once not needed; the lease cleans up after a
+// crash.
+
+use std::{
+    io::{Error, ErrorKind},
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use hbb_common::{
+    anyhow::{anyhow, bail},
+    config::{keys::OPTION_ALLOW_PORT_MAPPING, option2bool, Config},
+    log, rand, timeout,
+    tokio::{
+        self,
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpStream, UdpSocket},
+        sync::oneshot,
+    },
+    ResultType,
+};
+
+const PCP_PORT: u16 = 5351;
+const PCP_VERSION: u8 = 2;
+const PCP_OPCODE_ANNOUNCE: u8 = 0;
+const PCP_OPCODE_MAP: u8 = 1;
+const NAT_PMP_OPCODE_TCP: u8 = 2;
+// the bit set on the opcode of the responses
+const RESPONSE: u8 = 0x80;
+const PROTOCOL_TCP: u8 = 6;
+const SSDP_ADDR: &str = "239.255.255.250:1900";
+const IGD: &str = "urn:schemas-upnp-org:device:InternetGatewayDevice:1";
+// the upnp error of the routers which only keep the mappings without a lease
+const ONLY_PERMANENT_LEASES: &str = "725";
+// in milliseconds, of the first try, doubled on each retry as in rfc 6886
+const RETRY_TIMEOUT: u64 = 250;
+const RETRIES: u32 = 3;
+// in milliseconds
+const SSDP_TIMEOUT: u64 = 2_000;
+const HTTP_TIMEOUT: u64 = 2_000;
+// in seconds
+const LEASE: u32 = 3_600;
+const PUNCH_LEASE: u32 = 120;
+// the gateway is looked for again after this long if none is found
+const DISCOVER_INTERVAL: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone)]
+enum Gateway {
+    // PCP, or NAT-PMP if `legacy`
+    Pcp {
+        ip: Ipv4Addr,
+        legacy: bool,
+    },
+    Upnp {
+        control_url: String,
+        service: String,
+    },
+}
+
+// a mapping made on the gateway
+#[derive(Debug, Clone)]
+struct Entry {
+    local: SocketAddrV4,
+    external: u16,
+    // in seconds, as granted, 0 for none
+    lease: u32,
+    // of PCP, the renewals and the deletion must repeat it
+    nonce: [u8; 12],
+}
+
+lazy_static::lazy_static! {
+    // the gateway found, none if none is, and when it was looked for
+    static ref GATEWAY: Mutex<Option<(Option<Gateway>, Instant)>> = Default::default();
+}
+
+pub fn is_allowed() -> bool {
+    option2bool(
+        OPTION_ALLOW_PORT_MAPPING,
+        &Config::get_option(OPTION_ALLOW_PORT_MAPPING),
+    )
+}
+
+/// A tcp port mapped on the router, the mapping is deleted once it is dropped.
+pub struct Mapping {
+    local: SocketAddr,
+    _stop: oneshot::Sender<()>,
+}
+
+impl Mapping {
+    /// Maps `port` for as long as it is kept, retrying until the gateway maps it.
+    pub fn start(port: u16) -> Self {
+        let (tx, mut rx) = oneshot::channel();
+        tokio::spawn(async move {
+            loop {
+                if let Some(gateway) = gateway().await {
+                    match add(&gateway, port, port, LEASE, rand::random()).await {
+                        Ok(entry) => {
+                            log::info!(
+                                "Port {} mapped to the external port {}",
+                                port,
+                                entry.external
+                            );
+                            hold(gateway, entry, LEASE, rx).await;
+                            return;
+                        }
+                        Err(err) => {
+                            log::error!("Failed to map port {}: {}", port, err);
+                            forget(&gateway);
+                        }
+                    }
+                }
+                tokio::select! {
+                    _ = &mut rx => return,
+                    _ = tokio::time::sleep(DISCOVER_INTERVAL) => {}
+                }
+            }
+        });
+        Self {
+            local: SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port),
+            _stop: tx,
+        }
+    }
+
+    /// Maps a new port to punch a hole from, none if no gateway is known to map it.
+    pub async fn for_punch_hole() -> Option<Self> {
+        if !is_allowed() || Config::is_proxy() {
+            return None;
+        }
+        let gateway = known_gateway()?;
+        match add_free_port(&gateway).await {
+            Ok(entry) => {
+                let (tx, rx) = oneshot::channel();
+                let local = SocketAddr::V4(entry.local);
+                log::debug!("Punch hole from {} mapped to {}", local, entry.external);
+                tokio::spawn(hold(gateway, entry, PUNCH_LEASE, rx));
+                Some(Self { local, _stop: tx })
+            }
+            Err(err) => {
+                log::debug!("Failed to map a port to punch hole from: {}", err);
+                forget(&gateway);
+                None
+            }
+        }
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local
+    }
+}
+
+async fn add_free_port(gateway: &Gateway) -> ResultType<Entry> {
+    let ip = local_ip(gateway_ip(gateway)?)?;
+    let port = std::net::TcpListener::bind((ip, 0))?.local_addr()?.port();
+    add(gateway, port, port, PUNCH_LEASE, rand::random()).await
+}
+
+// renews the mapping until stopped, then deletes it
+async fn hold(gateway: Gateway, mut entry: Entry, lease: u32, mut stop: oneshot::Receiver<()>) {
+    loop {
+        if entry.lease == 0 {
+            // kept without a lease
+            stop.await.ok();
+            break;
+        }
+        tokio::select! {
+            _ = &mut stop => break,
+            _ = tokio::time::sleep(Duration::from_secs(entry.lease as u64 / 2)) => {
+                let port = entry.local.port();
+                match add(&gateway, port, entry.external, lease, entry.nonce).await {
+                    Ok(renewed) => entry = renewed,
+                    Err(err) => log::warn!("Failed to renew the mapping of port {}: {}", port, err),
+                }
+            }
+        }
+    }
+    if let Err(err) = delete(&gateway, &entry).await {
+        log::debug!(
+            "Failed to delete the mapping of port {}: {}",
+            entry.local.port(),
+            err
+        );
+    }
+}
+
+async fn gateway() -> Option<Gateway> {
+    if let Some((gateway, time)) = GATEWAY.lock().unwrap().clone() {
+        if gateway.is_some() || time.elapsed() < DISCOVER_INTERVAL {
+            return gateway;
+        }
+    }
+    let gateway = match discover().await {
+        Ok(gateway) => {
+            log::info!("Port mapping gateway: {:?}", gateway);
+            Some(gateway)
+        }
+        Err(err) => {
+            log::info!("No port mapping gateway: {}", err);
+            None
+        }
+    };
+    *GATEWAY.lock().unwrap() = Some((gateway.clone(), Instant::now()));
+    gateway
+}
+
+// the gateway without waiting for it to be looked for, which a punch hole can not
+fn known_gateway() -> Option<Gateway> {
+    let cached = GATEWAY.lock().unwrap().clone();
+    match cached {
+        Some((Some(gateway), _)) => Some(gateway),
+        Some((None, time)) if time.elapsed() < DISCOVER_INTERVAL => None,
+        _ => {
+            tokio::spawn(gateway());
+            None
+        }
+    }
+}
+
+// the gateway is looked for again next time, after it fails to map
+fn forget(gateway: &Gateway) {
+    let mut cached = GATEWAY.lock().unwrap();
+    if let Some((Some(g), _)) = cached.as_ref() {
+        if gateway_ip(g).ok() == gateway_ip(gateway).ok() {
+            *cached = None;
+        }
+    }
+}
+
+async fn discover() -> ResultType<Gateway> {
+    if let Some(gateway) = default_net::get_default_gateway()
+        .ok()
+        .and_then(|gateway| match gateway.ip_addr {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+    {
+        // any answer tells it is spoken, an older gateway answers with its version, 0 of NAT-PMP
+        if let Ok(local) = local_ip(gateway) {
+            if let Ok(response) = request_udp(gateway, &pcp_announce(local)).await {
+                return Ok(Gateway::Pcp {
+                    ip: gateway,
+                    legacy: response.first() == Some(&0),
+                });
+            }
+        }
+    }
+    let (control_url, service) = discover_upnp().await?;
+    Ok(Gateway::Upnp {
+        control_url,
+        service,
+    })
+}
+
+async fn add(
+    gateway: &Gateway,
+    port: u16,
+    external: u16,
+    lease: u32,
+    nonce: [u8; 12],
+) -> ResultType<Entry> {
+    let ip = local_ip(gateway_ip(gateway)?)?;
+    let (external, lease) = match gateway {
+        Gateway::Pcp {
+            ip: gateway,
+            legacy,
+        } => {
+            if *legacy {
+                let request = nat_pmp_request(port, external, lease);
+                nat_pmp_response(&request_udp(*gateway, &request).await?)?
+            } else {
+                let request = pcp_request(ip, &nonce, port, external, lease);
+                pcp_response(&request_udp(*gateway, &request).await?, &nonce)?
+            }
+        }
+        Gateway::Upnp {
+            control_url,
+            service,
+        } => {
+            let mut lease = lease;
+            loop {
+                let args = [
+                    ("NewRemoteHost", String::new()),
+                    ("NewExternalPort", external.to_string()),
+                    ("NewProtocol", "TCP".to_owned()),
+                    ("NewInternalPort", port.to_string()),
+                    ("NewInternalClient", ip.to_string()),
+                    ("NewEnabled", "1".to_owned()),
+                    ("NewPortMappingDescription", crate::get_app_name()),
+                    ("NewLeaseDuration", lease.to_string()),
+                ];
+                match soap(control_url, service, "AddPortMapping", &args).await? {
+                    Ok(_) => break (external, lease),
+                    Err(code) if code == ONLY_PERMANENT_LEASES && lease > 0 => lease = 0,
+                    Err(code) => bail!("upnp error {}", code),
+                }
+            }
+        }
+    };
+    Ok(Entry {
+        local: SocketAddrV4::new(ip, port),
+        external,
+        lease,
+        nonce,
+    })
+}
+
+async fn delete(gateway: &Gateway, entry: &Entry) -> ResultType<()> {
+    let port = entry.local.port();
+    match gateway {
+        Gateway::Pcp {
+            ip: gateway,
+            legacy,
+        } => {
+            if *legacy {
+                let request = nat_pmp_request(port, 0, 0);
+                nat_pmp_response(&request_udp(*gateway, &request).await?)?;
+            } else {
+                let request = pcp_request(*entry.local.ip(), &entry.nonce, port, 0, 0);
+                pcp_response(&request_udp(*gateway, &request).await?, &entry.nonce)?;
+            }
+        }
+        Gateway::Upnp {
+            control_url,
+            service,
+        } => {
+            let args = [
+                ("NewRemoteHost", String::new()),
+                ("NewExternalPort", entry.external.to_string()),
+                ("NewProtocol", "TCP".to_owned()),
+            ];
+            if let Err(code) = soap(control_url, service, "DeletePortMapping", &args).await? {
+                bail!("upnp error {}", code);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn gateway_ip(gateway: &Gateway) -> ResultType<Ipv4Addr> {
+    match gateway {
+        Gateway::Pcp { ip, .. } => Ok(*ip),
+        Gateway::Upnp { control_url, .. } => match url::Url::parse(control_url)?.host() {
+            Some(url::Host::Ipv4(ip)) => Ok(ip),
+            _ => bail!("the gateway is not ipv4: {}", control_url),
+        },
+    }
+}
+
+// the address of this side on the route to the gateway, which the mappings are made for
+fn local_ip(gateway: Ipv4Addr) -> ResultType<Ipv4Addr> {
+    let socket = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.connect((gateway, PCP_PORT))?;
+    match socket.local_addr()?.ip() {
+        IpAddr::V4(ip) => Ok(ip),
+        IpAddr::V6(ip) => bail!("unexpected local address: {}", ip),
+    }
+}
+
+async fn request_udp(gateway: Ipv4Addr, request: &[u8]) -> ResultType<Vec<u8>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket.connect((gateway, PCP_PORT)).await?;
+    let mut buf = [0u8; 1100];
+    for i in 0..RETRIES {
+        socket.send(request).await?;
+        if let Ok(n) = timeout(RETRY_TIMEOUT << i, socket.recv(&mut buf)).await {
+            return Ok(buf[..n?].to_vec());
+        }
+    }
+    Err(Error::new(ErrorKind::TimedOut, "no response").into())
+}
+
+// an ANNOUNCE request of rfc 6887
+fn pcp_announce(ip: Ipv4Addr) -> Vec<u8> {
+    let mut request = vec![PCP_VERSION, PCP_OPCODE_ANNOUNCE, 0, 0, 0, 0, 0, 0];
+    request.extend_from_slice(&ip.to_ipv6_mapped().octets());
+    request
+}
+
+// a MAP request of rfc 6887
+fn pcp_request(ip: Ipv4Addr, nonce: &[u8; 12], port: u16, external: u16, lease: u32) -> Vec<u8> {
+    let mut request = Vec::with_capacity(60);
+    request.extend_from_slice(&[PCP_VERSION, PCP_OPCODE_MAP, 0, 0]);
+    request.extend_from_slice(&lease.to_be_bytes());
+    request.extend_from_slice(&ip.to_ipv6_mapped().octets());
+    request.extend_from_slice(nonce);
+    request.extend_from_slice(&[PROTOCOL_TCP, 0, 0, 0]);
+    request.extend_from_slice(&port.to_be_bytes());
+    request.extend_from_slice(&external.to_be_bytes());
+    request.extend_from_slice(&Ipv4Addr::UNSPECIFIED.to_ipv6_mapped().octets());
+    request
+}
+
+// the external port and the lease granted
+fn pcp_response(response: &[u8], nonce: &[u8; 12]) -> ResultType<(u16, u32)> {
+    if response.len() < 60 || response[0] != PCP_VERSION || response[1] != PCP_OPCODE_MAP | RESPONSE
+    {
+        bail!("invalid pcp response");
+    }
+    if response[3] != 0 {
+        bail!("pcp error {}", response[3]);
+    }
+    if &response[24..36] != nonce {
+        bail!("pcp response to another request");
+    }
+    Ok((
+        u16::from_be_bytes([response[42], response[43]]),
+        u32::from_be_bytes([response[4], response[5], response[6], response[7]]),
+    ))
+}
+
+// a mapping request of rfc 6886
+fn nat_pmp_request(port: u16, external: u16, lease: u32) -> Vec<u8> {
+    let mut request = vec![0, NAT_PMP_OPCODE_TCP, 0, 0];
+    request.extend_from_slice(&port.to_be_bytes());
+    request.extend_from_slice(&external.to_be_bytes());
+    request.extend_from_slice(&lease.to_be_bytes());
+    request
+}
+
+// the external port and the lease granted
+fn nat_pmp_response(response: &[u8]) -> ResultType<(u16, u32)> {
+    if response.len() < 16 || response[0] != 0 || response[1] != NAT_PMP_OPCODE_TCP | RESPONSE {
+        bail!("invalid nat-pmp response");
+    }
+    let result = u16::from_be_bytes([response[2], response[3]]);
+    if result != 0 {
+        bail!("nat-pmp error {}", result);
+    }
+    Ok((
+        u16::from_be_bytes([response[10], response[11]]),
+        u32::from_be_bytes([response[12], response[13], response[14], response[15]]),
+    ))
+}
+
+// the control url and the type of the wan connection service of the internet gateway device
+async fn discover_upnp() -> ResultType<(String, String)> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nST: {}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\n\r\n",
+        SSDP_ADDR, IGD
+    );
+    socket.send_to(search.as_bytes(), SSDP_ADDR).await?;
+    let mut buf = [0u8; 2048];
+    let n = timeout(SSDP_TIMEOUT, socket.recv(&mut buf))
+        .await
+        .map_err(|_| anyhow!("no internet gateway device"))??;
+    let response = String::from_utf8_lossy(&buf[..n]);
+    let location = response
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("location"))
+        .map(|(_, value)| value.trim().to_owned())
+        .ok_or_else(|| anyhow!("no location in the ssdp response"))?;
+    let (status, description) = http(&location, None).await?;
+    if status != 200 {
+        bail!("http status {} of {}", status, location);
+    }
+    let (service, control_url) = wan_service(&description)
+        .ok_or_else(|| anyhow!("no wan connection service in {}", location))?;
+    let control_url = url::Url::parse(&location)?.join(&control_url)?.to_string();
+    Ok((control_url, service))
+}
+
+// the type and the control url of the first wan connection service described
+fn wan_service(description: &str) -> Option<(String, String)> {
+    description.split("<service>").skip(1).find_map(|service| {
+        let service_type = xml_tag(service, "serviceType")?;
+        if service_type.contains(":WANIPConnection:") || service_type.contains(":WANPPPConnection:")
+        {
+            Some((
+                service_type.to_owned(),
+                xml_tag(service, "controlURL")?.to_owned(),
+            ))
+        } else {
+            None
+        }
+    })
+}
+
+fn xml_tag<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", name))?;
+    Some(xml[start..end].trim())
+}
+
+// the body of the response, or the upnp error code of the fault
+async fn soap(
+    control_url: &str,
+    service: &str,
+    action: &str,
+    args: &[(&str, String)],
+) -> ResultType<Result<String, String>> {
+    let args: String = args
+        .iter()
+        .map(|(name, value)| format!("<{name}>{value}</{name}>"))
+        .collect();
+    let body = format!(
+        "<?xml version=\"1.0\"?>\
+        <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+        s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+        <s:Body><u:{action} xmlns:u=\"{service}\">{args}</u:{action}></s:Body></s:Envelope>"
+    );
+    let soap_action = format!("{}#{}", service, action);
+    let (status, body) = http(control_url, Some((&soap_action, body))).await?;
+    if status == 200 {
+        Ok(Ok(body))
+    } else {
+        Ok(Err(xml_tag(&body, "errorCode")
+            .map(|code| code.to_owned())
+            .unwrap_or_else(|| format!("http status {}", status))))
+    }
+}
+
+// http 1.0 for the responses not to be chunked, posts the soap action and body if any
+async fn http(url: &str, soap: Option<(&str, String)>) -> ResultType<(u16, String)> {
+    let url = url::Url::parse(url)?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("no host in {}", url))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let path = &url[url::Position::BeforePath..];
+    let request = match soap {
+        Some((action, body)) => format!(
+            "POST {path} HTTP/1.0\r\nHost: {host}:{port}\r\n\
+            Content-Type: text/xml; charset=\"utf-8\"\r\nSOAPAction: \"{action}\"\r\n\
+            Content-Length: {}\r\n\r\n{body}",
+            body.len()
+        ),
+        None => format!("GET {path} HTTP/1.0\r\nHost: {host}:{port}\r\n\r\n"),
+    };
+    let response = timeout(HTTP_TIMEOUT, async {
+        let mut stream = TcpStream::connect((host, port)).await?;
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        Ok::<_, Error>(response)
+    })
+    .await??;
+    let response = String::from_utf8_lossy(&response);
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| anyhow!("invalid http response"))?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| anyhow!("invalid http response"))?;
+    Ok((status, body.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let nonce = [7u8; 12];
+        let mut response = pcp_request(Ipv4Addr::new(192, 168, 1, 2), &nonce, 21118, 0, LEASE);
+        assert_eq!(response.len(), 60);
+        response[1] |= RESPONSE;
+        response[42..44].copy_from_slice(&31118u16.to_be_bytes());
+        assert_eq!(pcp_response(&response, &nonce).unwrap(), (31118, LEASE));
+        assert!(pcp_response(&response, &[0; 12]).is_err());
+        response[3] = 2;
+        assert!(pcp_response(&response, &nonce).is_err());
+
+        let request = nat_pmp_request(21118, 21118, LEASE);
+        assert_eq!(request.len(), 12);
+        let mut response = vec![0, NAT_PMP_OPCODE_TCP | RESPONSE, 0, 0, 0, 0, 0, 1];
+        response.extend_from_slice(&request[4..]);
+        assert_eq!(nat_pmp_response(&response).unwrap(), (21118, LEASE));
+        response[3] = 3;
+        assert!(nat_pmp_response(&response).is_err());
+    }
+
+    #[test]
+    fn test_wan_service() {
+        let description = "<root><device><serviceList>\
+            <service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType>\
+            <controlURL>/l3f</controlURL></service>\
+            <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>\
+            <controlURL> /ctl/IPConn </controlURL></service>\
+            </serviceList></device></root>";
+        assert_eq!(
+            wan_service(description),
+            Some((
+                "urn:schemas-upnp-org:service:WANIPConnection:1".to_owned(),
+                "/ctl/IPConn".to_owned()
+            ))
+        );
+        assert_eq!(wan_service("<root></root>"), None);
+    }
+}
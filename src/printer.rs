@@ -0,0 +1,293 @@
+// The virtual printer of this side whose documents are printed on the controlling side, a queue of
+// CUPS on Linux and macOS with a backend of its own, and a PPD making CUPS hand the jobs to it in
+// pdf whatever the application printed.
+//
+// The backend is this program run by CUPS, as the user of CUPS, which hands the pdf of a job to the
+// server through the ipc. The server sends it to the peer which turned the printer on last, the job
+// is cancelled if there is none. The controlling side takes the jobs only while it turned the
+// printer on, saves the pdf in a directory of its user and prints it on the default printer of its
+// system once the user agrees, a few copies at most.
+
+use std::{fs::OpenOptions, io::Write, path::Path};
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::{
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    process::Command,
+};
+
+use hbb_common::{bail, config::Config, message_proto::PrintJob, ResultType};
+
+/// The largest document sent to the peer.
+pub const MAX_JOB_SIZE: usize = 64 * 1024 * 1024;
+/// The most copies of a document of the peer printed.
+pub const MAX_COPIES: u32 = 10;
+
+#[cfg(target_os = "linux")]
+const BACKEND_DIR: &str = "/usr/lib/cups/backend";
+#[cfg(target_os = "macos")]
+const BACKEND_DIR: &str = "/usr/libexec/cups/backend";
+
+// the exit codes of a backend of CUPS
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const BACKEND_OK: i32 = 0;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const BACKEND_FAILED: i32 = 1;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const BACKEND_CANCEL: i32 = 5;
+
+// CUPS converts the documents to pdf with its filters, none is run after
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const PPD: &str = r#"*PPD-Adobe: "4.3"
+*FormatVersion: "4.3"
+*FileVersion: "1.0"
+*LanguageVersion: English
+*LanguageEncoding: ISOLatin1
+*PCFileName: "REMOTE.PPD"
+*Manufacturer: "{app}"
+*Product: "({app} Printer)"
+*ModelName: "{app} Printer"
+*ShortNickName: "{app} Printer"
+*NickName: "{app} Printer"
+*PSVersion: "(3010.000) 0"
+*LanguageLevel: "3"
+*ColorDevice: True
+*DefaultColorSpace: RGB
+*FileSystem: False
+*Throughput: "1"
+*cupsVersion: 1.4
+*cupsFilter2: "application/vnd.cups-pdf application/pdf 0 -"
+*OpenUI *PageSize/Media Size: PickOne
+*OrderDependency: 10 AnySetup *PageSize
+*DefaultPageSize: A4
+*PageSize A4/A4: "<</PageSize[595 842]/ImagingBBox null>>setpagedevice"
+*PageSize Letter/US Letter: "<</PageSize[612 792]/ImagingBBox null>>setpagedevice"
+*CloseUI: *PageSize
+*OpenUI *PageRegion: PickOne
+*OrderDependency: 10 AnySetup *PageRegion
+*DefaultPageRegion: A4
+*PageRegion A4/A4: "<</PageSize[595 842]/ImagingBBox null>>setpagedevice"
+*PageRegion Letter/US Letter: "<</PageSize[612 792]/ImagingBBox null>>setpagedevice"
+*CloseUI: *PageRegion
+*DefaultImageableArea: A4
+*ImageableArea A4/A4: "0 0 595 842"
+*ImageableArea Letter/US Letter: "0 0 612 792"
+*DefaultPaperDimension: A4
+*PaperDimension A4/A4: "595 842"
+*PaperDimension Letter/US Letter: "612 792"
+"#;
+
+// the name of the queue, and the scheme of the uri of the device in lower case
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn queue() -> String {
+    crate::get_app_name().replace(' ', "")
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn scheme() -> String {
+    queue().to_lowercase()
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn run(cmd: &mut Command) -> ResultType<()> {
+    let output = cmd.output()?;
+    if !output.status.success() {
+        bail!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim().to_owned()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn installed() -> bool {
+    Path::new(BACKEND_DIR).join(scheme()).exists()
+}
+
+/// Adds the backend and the queue of the printer to CUPS, as root.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn install() -> ResultType<()> {
+    let exe = std::env::current_exe()?;
+    let backend = Path::new(BACKEND_DIR).join(scheme());
+    std::fs::write(
+        &backend,
+        format!(
+            "#!/bin/sh\nexec \"{}\" --print-backend \"$@\"\n",
+            exe.to_string_lossy()
+        ),
+    )?;
+    // not writable by the others, CUPS runs it as its user then
+    std::fs::set_permissions(&backend, std::fs::Permissions::from_mode(0o755))?;
+    // a new file of root only, not one the others could have put in the shared temp dir
+    let ppd = std::env::temp_dir().join(format!("{}-{}.ppd", scheme(), uuid::Uuid::new_v4()));
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&ppd)?
+        .write_all(PPD.replace("{app}", &crate::get_app_name()).as_bytes())?;
+    let res = run(Command::new("lpadmin")
+        .args(["-p", &queue(), "-E", "-v", &format!("{}:/", scheme()), "-P"])
+        .arg(&ppd)
+        .args([
+            "-D",
+            &format!("{} Printer", crate::get_app_name()),
+            "-o",
+            "printer-is-shared=false",
+        ]));
+    std::fs::remove_file(&ppd).ok();
+    res
+}
+
+/// Removes the queue and the backend of the printer from CUPS, as root.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn uninstall() -> ResultType<()> {
+    // the queue may be removed already
+    run(Command::new("lpadmin").args(["-x", &queue()])).ok();
+    let backend = Path::new(BACKEND_DIR).join(scheme());
+    if backend.exists() {
+        std::fs::remove_file(backend)?;
+    }
+    Ok(())
+}
+
+/// Run by CUPS with the arguments of a backend, the exit code for CUPS.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn backend(args: &[String]) -> i32 {
+    use std::io::Read;
+
+    if args.is_empty() {
+        // the devices of the backend listed
+        println!(
+            "direct {}:/ \"Unknown\" \"{} Printer\"",
+            scheme(),
+            crate::get_app_name()
+        );
+        return BACKEND_OK;
+    }
+    // job-id user title copies options [file]
+    if args.len() < 5 {
+        eprintln!("ERROR: Invalid arguments of the backend");
+        return BACKEND_FAILED;
+    }
+    let title = args[2].clone();
+    let copies = args[3].parse().unwrap_or(1);
+    let data = match args.get(5) {
+        Some(file) => std::fs::read(file),
+        None => {
+            let mut data = vec![];
+            std::io::stdin().read_to_end(&mut data).map(|_| data)
+        }
+    };
+    let data = match data {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("ERROR: Failed to read the job: {}", err);
+            return BACKEND_FAILED;
+        }
+    };
+    if data.len() > MAX_JOB_SIZE {
+        eprintln!(
+            "ERROR: The document is larger than {} MB",
+            MAX_JOB_SIZE / 1024 / 1024
+        );
+        return BACKEND_CANCEL;
+    }
+    match crate::ipc::print_job(title, copies, data) {
+        Ok(()) => BACKEND_OK,
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            BACKEND_CANCEL
+        }
+    }
+}
+
+/// The copies of a job of the peer printed, 1 to MAX_COPIES.
+pub fn copies(job: &PrintJob) -> u32 {
+    job.copies.clamp(1, MAX_COPIES)
+}
+
+/// Saves the document of the peer in the directory of the print jobs of the user, the path of the
+/// pdf.
+pub fn save(job: &PrintJob) -> ResultType<String> {
+    save_to(&Config::path("print-jobs"), job)
+}
+
+fn save_to(dir: &Path, job: &PrintJob) -> ResultType<String> {
+    if job.data.len() > MAX_JOB_SIZE {
+        bail!("The document is too large");
+    }
+    std::fs::create_dir_all(dir)?;
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    let path = dir.join(format!(
+        "{}-{}.pdf",
+        hbb_common::get_time(),
+        uuid::Uuid::new_v4()
+    ));
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    options.mode(0o600);
+    options.open(&path)?.write_all(&job.data)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Prints the pdf on the default printer of this side, MAX_COPIES at most.
+pub fn print(path: &str, copies: u32) -> ResultType<()> {
+    let copies = copies.clamp(1, MAX_COPIES);
+    #[cfg(windows)]
+    {
+        // the application of the pdfs prints it, once a copy
+        for _ in 0..copies {
+            if !crate::platform::windows::print_file(path)? {
+                bail!("No application to print the document");
+            }
+        }
+        Ok(())
+    }
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        run(Command::new("lp")
+            .args(["-n", &copies.to_string()])
+            .arg(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save() {
+        let job = PrintJob {
+            title: "a".to_owned(),
+            copies: 1,
+            data: b"%PDF-1.4"[..].to_vec().into(),
+            ..Default::default()
+        };
+        let dir = std::env::temp_dir().join(format!("print-jobs-test-{}", std::process::id()));
+        let path = save_to(&dir, &job).unwrap();
+        assert!(path.ends_with(".pdf"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"%PDF-1.4");
+        // never the same file twice
+        assert_ne!(save_to(&dir, &job).unwrap(), path);
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        assert_eq!(
+            std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777,
+            0o700
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_copies() {
+        let job = |copies| PrintJob {
+            copies,
+            ..Default::default()
+        };
+        assert_eq!(copies(&job(0)), 1);
+        assert_eq!(copies(&job(3)), 3);
+        assert_eq!(copies(&job(u32::MAX)), MAX_COPIES);
+    }
+}
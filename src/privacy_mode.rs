@@ -61,6 +61,9 @@ pub trait PrivacyMode: Sync + Send {
 
     fn pre_conn_id(&self) -> i32;
 
+    // the privacy mode goes on for the connection taking over the session handed off
+    fn hand_over(&mut self, conn_id: i32);
+
     fn get_impl_key(&self) -> &str;
 
     #[inline]
@@ -399,6 +402,19 @@ pub fn get_privacy_mode_conn_id() -> Option<i32> {
         .map(|pm| pm.pre_conn_id())
 }
 
+/// Keeps the privacy mode of the connection on for another one, true if it was on.
+pub fn hand_over(from: i32, to: i32) -> bool {
+    let mut lock = PRIVACY_MODE.lock().unwrap();
+    let Some(privacy_mode) = lock.as_mut() else {
+        return false;
+    };
+    if from == INVALID_PRIVACY_MODE_CONN_ID || privacy_mode.pre_conn_id() != from {
+        return false;
+    }
+    privacy_mode.hand_over(to);
+    true
+}
+
 #[inline]
 pub fn is_in_privacy_mode() -> bool {
     PRIVACY_MODE
@@ -156,6 +156,11 @@ impl PrivacyMode for PrivacyModeImpl {
         self.conn_id
     }
 
+    #[inline]
+    fn hand_over(&mut self, conn_id: i32) {
+        self.conn_id = conn_id;
+    }
+
     #[inline]
     fn get_impl_key(&self) -> &str {
         &self.impl_key
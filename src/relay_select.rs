@@ -0,0 +1,207 @@
+// Selection of the relay server by latency when extra relay servers are configured.
+//
+// The relay servers are probed in the background: a relay request without uuid is sent, which the
+// relay server drops at once, so the time from the connect to the close covers both the round trip
+// and how busy the server is. The samples are smoothed, and a session is relayed by the server with
+// the lowest score, probed again at connect time if the scores are stale. A relayed session moves
+// to another server once it is better by a margin, so that the jitter does not move it around.
+
+use std::{sync::Mutex, time::Instant};
+
+use hbb_common::{
+    anyhow::bail,
+    config::{keys::OPTION_EXTRA_RELAY_SERVERS, Config, RELAY_PORT},
+    futures::future::join_all,
+    log,
+    rendezvous_proto::*,
+    sleep,
+    socket_client::{self, check_port},
+    timeout, ResultType,
+};
+
+// in seconds
+const PROBE_INTERVAL: f32 = 60.;
+// in milliseconds
+const PROBE_TIMEOUT: u64 = 1_000;
+// the weight of a new sample in the score
+const SMOOTHING: f64 = 0.3;
+// the score of another server must be below this ratio of the one in use and lower by this many
+// milliseconds to move a session to it
+const MIGRATE_RATIO: f64 = 0.7;
+const MIGRATE_MARGIN: f64 = 30.;
+
+#[derive(Debug, Clone)]
+struct Probe {
+    host: String,
+    // in milliseconds, smoothed, none if the last probe failed
+    score: Option<f64>,
+    time: Instant,
+}
+
+lazy_static::lazy_static! {
+    static ref PROBES: Mutex<Vec<Probe>> = Default::default();
+}
+
+/// The relay servers configured, more than one if the selection is on.
+pub fn servers() -> Vec<String> {
+    let mut servers: Vec<String> = Vec::new();
+    let relay_server = Config::get_option("relay-server");
+    let extra = Config::get_option(OPTION_EXTRA_RELAY_SERVERS);
+    for host in std::iter::once(relay_server.as_str()).chain(extra.split(',')) {
+        let host = host.trim();
+        if !host.is_empty() {
+            let host = check_port(host, RELAY_PORT);
+            if !servers.contains(&host) {
+                servers.push(host);
+            }
+        }
+    }
+    servers
+}
+
+/// The relay server to use, empty if none is configured.
+pub async fn pick() -> String {
+    let servers = servers();
+    if servers.len() <= 1 {
+        return servers.into_iter().next().unwrap_or_default();
+    }
+    let stale = {
+        let probes = PROBES.lock().unwrap();
+        servers.iter().any(|host| {
+            !probes
+                .iter()
+                .any(|p| &p.host == host && p.time.elapsed().as_secs_f32() < PROBE_INTERVAL * 2.)
+        })
+    };
+    if stale {
+        probe_all(&servers).await;
+    }
+    let probes = PROBES.lock().unwrap();
+    best(&probes, &servers)
+        .map(|p| p.host.clone())
+        .unwrap_or_else(|| servers[0].clone())
+}
+
+/// A relay server which is better enough than `current` to move a session to.
+pub fn better_than(current: &str) -> Option<String> {
+    let servers = servers();
+    if servers.len() <= 1 || current.is_empty() {
+        return None;
+    }
+    let current = check_port(current, RELAY_PORT);
+    let probes = PROBES.lock().unwrap();
+    let current = probes.iter().find(|p| p.host == current)?.score?;
+    let best = best(&probes, &servers)?;
+    let score = best.score?;
+    if score < current * MIGRATE_RATIO && current - score > MIGRATE_MARGIN {
+        Some(best.host.clone())
+    } else {
+        None
+    }
+}
+
+fn best<'a>(probes: &'a [Probe], servers: &[String]) -> Option<&'a Probe> {
+    probes
+        .iter()
+        .filter(|p| p.score.is_some() && servers.contains(&p.host))
+        .min_by(|a, b| {
+            a.score
+                .partial_cmp(&b.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+}
+
+/// Probes the relay servers while more than one is configured.
+pub async fn run() {
+    loop {
+        let servers = servers();
+        if servers.len() > 1 {
+            probe_all(&servers).await;
+        } else {
+            PROBES.lock().unwrap().clear();
+        }
+        sleep(PROBE_INTERVAL).await;
+    }
+}
+
+async fn probe_all(servers: &[String]) {
+    let results = join_all(servers.iter().map(|host| probe(host.clone()))).await;
+    let mut probes = PROBES.lock().unwrap();
+    probes.retain(|p| servers.contains(&p.host));
+    for (host, result) in servers.iter().zip(results) {
+        let sample = match result {
+            Ok(ms) => Some(ms),
+            Err(err) => {
+                log::debug!("Failed to probe the relay server {}: {}", host, err);
+                None
+            }
+        };
+        match probes.iter_mut().find(|p| &p.host == host) {
+            Some(probe) => {
+                probe.score = smooth(probe.score, sample);
+                probe.time = Instant::now();
+            }
+            None => probes.push(Probe {
+                host: host.clone(),
+                score: sample,
+                time: Instant::now(),
+            }),
+        }
+    }
+}
+
+fn smooth(score: Option<f64>, sample: Option<f64>) -> Option<f64> {
+    match (score, sample) {
+        (Some(score), Some(sample)) => Some(score * (1. - SMOOTHING) + sample * SMOOTHING),
+        _ => sample,
+    }
+}
+
+// the time in milliseconds from the connect to the close of a relay request without uuid
+async fn probe(host: String) -> ResultType<f64> {
+    let start = Instant::now();
+    let mut socket = socket_client::connect_tcp(&*host, PROBE_TIMEOUT).await?;
+    let mut msg_out = RendezvousMessage::new();
+    msg_out.set_request_relay(RequestRelay {
+        licence_key: crate::get_key(true).await,
+        ..Default::default()
+    });
+    socket.send(&msg_out).await?;
+    match timeout(PROBE_TIMEOUT, socket.next()).await {
+        Ok(None) | Ok(Some(Err(_))) => Ok(start.elapsed().as_secs_f64() * 1000.),
+        Ok(Some(Ok(_))) => bail!("unexpected response"),
+        Err(_) => bail!("timeout"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(host: &str, score: Option<f64>) -> Probe {
+        Probe {
+            host: host.to_owned(),
+            score,
+            time: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn test_best() {
+        let servers = vec!["a".to_owned(), "b".to_owned(), "c".to_owned()];
+        let probes = [
+            probe("a", Some(80.)),
+            probe("b", None),
+            probe("c", Some(20.)),
+        ];
+        assert_eq!(best(&probes, &servers).map(|p| p.host.as_str()), Some("c"));
+        assert_eq!(
+            best(&probes[..2], &servers[..1]).map(|p| p.host.as_str()),
+            Some("a")
+        );
+        assert!(best(&probes[1..2], &servers).is_none());
+        assert!((smooth(Some(100.), Some(0.)).unwrap() - 70.).abs() < 1e-9);
+        assert_eq!(smooth(None, Some(10.)), Some(10.));
+        assert_eq!(smooth(Some(10.), None), None);
+    }
+}
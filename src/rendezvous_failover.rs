@@ -0,0 +1,213 @@
+// Failover of the rendezvous servers when backup servers are configured.
+//
+// The servers are probed in the background with a nat test request, which only a rendezvous server
+// answers. The host registers to the first healthy server of the list: once the server in use fails
+// several probes in a row, the next healthy one takes over, and once a server earlier in the list
+// has passed several probes in a row again, it is taken back. The backup servers are expected to
+// share the key of the first one. The probe results are served over ipc for the diagnostics.
+
+use std::{
+    sync::Mutex,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use hbb_common::{
+    anyhow::bail,
+    config::{Config, CONNECT_TIMEOUT, RENDEZVOUS_PORT},
+    futures::future::join_all,
+    log,
+    rendezvous_proto::*,
+    sleep,
+    socket_client::{self, check_port},
+    websocket, ResultType,
+};
+use serde_derive::Serialize;
+
+// in seconds
+const PROBE_INTERVAL: f32 = 15.;
+const MAX_FAILS: u32 = 2;
+const MIN_SUCCESSES_TO_FAIL_BACK: u32 = 3;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Probe {
+    pub host: String,
+    // in milliseconds, -1 if the last probe failed
+    pub latency: i64,
+    // consecutive
+    pub fails: u32,
+    pub successes: u32,
+    // in seconds, 0 if not probed yet
+    pub time: u64,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Status {
+    pub enabled: bool,
+    pub active: String,
+    pub probes: Vec<Probe>,
+}
+
+lazy_static::lazy_static! {
+    static ref PROBES: Mutex<Vec<Probe>> = Default::default();
+}
+
+/// The server in use and the last probes, as json.
+pub fn status() -> String {
+    let status = Status {
+        enabled: Config::is_rendezvous_failover(),
+        active: Config::get_rendezvous_server(),
+        probes: PROBES.lock().unwrap().clone(),
+    };
+    serde_json::to_string(&status).unwrap_or_default()
+}
+
+/// The servers to register to, all of them unless the failover is on.
+pub fn servers() -> Vec<String> {
+    let servers = Config::get_rendezvous_servers();
+    if !Config::is_rendezvous_failover() {
+        return servers;
+    }
+    let active = Config::get_active_rendezvous_server();
+    if active.is_empty() {
+        // not probed yet
+        let first = servers.into_iter().next().unwrap_or_default();
+        Config::set_active_rendezvous_server(&first);
+        vec![first]
+    } else {
+        vec![active]
+    }
+}
+
+/// Probes the servers while the failover is on and switches the server in use when needed.
+pub async fn run() {
+    loop {
+        if Config::is_rendezvous_failover() {
+            probe_all().await;
+        } else {
+            PROBES.lock().unwrap().clear();
+        }
+        sleep(PROBE_INTERVAL).await;
+    }
+}
+
+async fn probe_all() {
+    let servers = Config::get_rendezvous_servers();
+    let results = join_all(servers.iter().map(|host| probe(host.clone()))).await;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let probes = {
+        let mut probes = PROBES.lock().unwrap();
+        let old = std::mem::take(&mut *probes);
+        for (host, result) in servers.iter().zip(results) {
+            let mut probe = old
+                .iter()
+                .find(|p| &p.host == host)
+                .cloned()
+                .unwrap_or_else(|| Probe {
+                    host: host.clone(),
+                    ..Default::default()
+                });
+            probe.time = now;
+            match result {
+                Ok(latency) => {
+                    probe.latency = latency;
+                    probe.successes += 1;
+                    probe.fails = 0;
+                    probe.error.clear();
+                }
+                Err(err) => {
+                    probe.latency = -1;
+                    probe.successes = 0;
+                    probe.fails += 1;
+                    probe.error = err.to_string();
+                }
+            }
+            probes.push(probe);
+        }
+        probes.clone()
+    };
+    let active = Config::get_active_rendezvous_server();
+    let current = probes.iter().position(|p| p.host == active).unwrap_or(0);
+    let next = select(current, &probes);
+    if next != current {
+        log::info!(
+            "rendezvous server failover from {} to {}",
+            probes[current].host,
+            probes[next].host
+        );
+        Config::set_active_rendezvous_server(&probes[next].host);
+        crate::RendezvousMediator::restart();
+    }
+}
+
+// the index of the server to use, given the one in use
+fn select(current: usize, probes: &[Probe]) -> usize {
+    // fail back to the first server of the list which is steadily up again
+    if let Some(i) = probes[..current]
+        .iter()
+        .position(|p| p.successes >= MIN_SUCCESSES_TO_FAIL_BACK)
+    {
+        return i;
+    }
+    match probes.get(current) {
+        Some(p) if p.fails >= MAX_FAILS => probes
+            .iter()
+            .position(|p| p.fails == 0 && p.time > 0)
+            .unwrap_or(current),
+        _ => current,
+    }
+}
+
+// the latency in milliseconds
+async fn probe(host: String) -> ResultType<i64> {
+    let start = Instant::now();
+    let host = check_port(host, RENDEZVOUS_PORT);
+    let mut socket =
+        socket_client::connect_server(&host, websocket::RENDEZVOUS_PATH, CONNECT_TIMEOUT).await?;
+    let mut msg_out = RendezvousMessage::new();
+    msg_out.set_test_nat_request(TestNatRequest {
+        serial: Config::get_serial(),
+        ..Default::default()
+    });
+    socket.send(&msg_out).await?;
+    match crate::get_next_nonkeyexchange_msg(&mut socket, Some(CONNECT_TIMEOUT)).await {
+        Some(RendezvousMessage {
+            union: Some(rendezvous_message::Union::TestNatResponse(_)),
+            ..
+        }) => Ok(start.elapsed().as_millis() as _),
+        _ => bail!("no response"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(fails: u32, successes: u32) -> Probe {
+        Probe {
+            fails,
+            successes,
+            time: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        // stays while up
+        assert_eq!(select(0, &[probe(0, 1), probe(0, 5)]), 0);
+        // a single failure is tolerated
+        assert_eq!(select(0, &[probe(1, 0), probe(0, 5)]), 0);
+        // fails over in order to the first server up
+        assert_eq!(select(0, &[probe(2, 0), probe(1, 0), probe(0, 1)]), 2);
+        // stays if no server is up
+        assert_eq!(select(0, &[probe(2, 0), probe(3, 0)]), 0);
+        // fails back once the first server is steadily up
+        assert_eq!(select(2, &[probe(0, 2), probe(0, 1), probe(0, 9)]), 2);
+        assert_eq!(select(2, &[probe(0, 3), probe(0, 1), probe(0, 9)]), 0);
+        assert_eq!(select(2, &[probe(2, 0), probe(0, 3), probe(0, 9)]), 1);
+    }
+}
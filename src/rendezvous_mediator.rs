@@ -19,10 +19,11 @@ use hbb_common::{
     proxy::Proxy,
     rendezvous_proto::*,
     sleep,
-    socket_client::{self, connect_tcp, is_ipv4},
+    socket_client::{self, connect_server, is_ipv4},
     tcp::FramedStream,
     tokio::{self, select, sync::Mutex, time::interval},
     udp::FramedSocket,
+    websocket::RENDEZVOUS_PATH,
     AddrMangle, IntoTargetAddr, ResultType, TargetAddr,
 };
 
@@ -76,6 +77,12 @@ impl RendezvousMediator {
         tokio::spawn(async move {
             direct_server(server_cloned).await;
         });
+        let server_cloned = server.clone();
+        tokio::spawn(async move {
+            quic_server(server_cloned).await;
+        });
+        tokio::spawn(crate::rendezvous_failover::run());
+        tokio::spawn(crate::relay_select::run());
         #[cfg(target_os = "android")]
         let start_lan_listening = true;
         #[cfg(not(any(target_os = "android", target_os = "ios")))]
@@ -101,7 +108,7 @@ impl RendezvousMediator {
                     nat_tested = true;
                 }
                 let mut futs = Vec::new();
-                let servers = Config::get_rendezvous_servers();
+                let servers = crate::rendezvous_failover::servers();
                 SHOULD_EXIT.store(false, Ordering::SeqCst);
                 MANUAL_RESTARTED.store(false, Ordering::SeqCst);
                 for host in servers.clone() {
@@ -229,6 +236,10 @@ impl RendezvousMediator {
                                 Config::update_latency(&host, -1);
                                 old_latency = 0;
                                 if last_dns_check.elapsed().as_millis() as i64 > DNS_INTERVAL {
+                                    // reachable through its tunnel only, over tcp then
+                                    if socket_client::probe_tunnel(&host, RENDEZVOUS_PATH, CONNECT_TIMEOUT).await {
+                                        bail!("{} is unreachable but through its tunnel", host);
+                                    }
                                     // in some case of network reconnect (dial IP network),
                                     // old UDP socket not work any more after network recover
                                     if let Some((s, new_addr)) = socket_client::rebind_udp_for(&rz.host).await? {
@@ -266,8 +277,13 @@ impl RendezvousMediator {
                 if rpr.request_pk {
                     log::info!("request_pk received from {}", self.host);
                     self.register_pk(sink).await?;
+                } else {
+                    self.register_alias(sink).await?;
                 }
             }
+            Some(rendezvous_message::Union::RegisterAliasResponse(rar)) => {
+                crate::alias::on_response(&self.host, rar);
+            }
             Some(rendezvous_message::Union::RegisterPkResponse(rpr)) => {
                 update_latency();
                 match rpr.result.enum_value() {
@@ -327,7 +343,8 @@ impl RendezvousMediator {
 
     pub async fn start_tcp(server: ServerPtr, host: String) -> ResultType<()> {
         let host = check_port(&host, RENDEZVOUS_PORT);
-        let mut conn = connect_tcp(host.clone(), CONNECT_TIMEOUT).await?;
+        let mut conn = connect_server(&host, RENDEZVOUS_PATH, CONNECT_TIMEOUT).await?;
+        let tunneled = socket_client::is_tunneled(&host);
         let key = crate::get_key(true).await;
         crate::secure_tcp(&mut conn, &key).await?;
         let mut rz = Self {
@@ -368,11 +385,17 @@ impl RendezvousMediator {
                     if last_recv_msg.elapsed().as_millis() as u64 > rz.keep_alive as u64 * 3 / 2 {
                         bail!("Rendezvous connection is timeout");
                     }
+                    if tunneled {
+                        socket_client::set_tunneled(&host);
+                    }
                     if (!Config::get_key_confirmed() ||
                         !Config::get_host_key_confirmed(&host)) &&
                         last_register_sent.map(|x| x.elapsed().as_millis() as i64).unwrap_or(REG_INTERVAL) >= REG_INTERVAL {
                         rz.register_pk(Sink::Stream(&mut conn)).await?;
                         last_register_sent = Some(Instant::now());
+                    } else if Config::get_key_confirmed() &&
+                        Config::get_host_key_confirmed(&host) {
+                        rz.register_alias(Sink::Stream(&mut conn)).await?;
                     }
                 }
             }
@@ -392,6 +415,7 @@ impl RendezvousMediator {
         if (cfg!(debug_assertions) && option_env!("TEST_TCP").is_some())
             || is_http_proxy
             || get_builtin_option(config::keys::OPTION_DISABLE_UDP) == "Y"
+            || socket_client::is_tunneled(&host)
         {
             Self::start_tcp(server, host).await
         } else {
@@ -400,6 +424,11 @@ impl RendezvousMediator {
     }
 
     async fn handle_request_relay(&self, rr: RequestRelay, server: ServerPtr) -> ResultType<()> {
+        if rr.turn && !socket_client::is_tunneled(&self.host) {
+            if let Some(allocation) = self.allocate_turn(&rr.socket_addr).await {
+                return self.create_turn_relay(rr, allocation, server).await;
+            }
+        }
         self.create_relay(
             rr.socket_addr.into(),
             rr.relay_server,
@@ -429,7 +458,7 @@ impl RendezvousMediator {
             secure,
         );
 
-        let mut socket = connect_tcp(&*self.host, CONNECT_TIMEOUT).await?;
+        let mut socket = connect_server(&self.host, RENDEZVOUS_PATH, CONNECT_TIMEOUT).await?;
 
         let mut msg_out = Message::new();
         let mut rr = RelayResponse {
@@ -456,10 +485,70 @@ impl RendezvousMediator {
         Ok(())
     }
 
+    // the TURN allocation for the requester at `socket_addr`, none to use the relay server
+    async fn allocate_turn(&self, socket_addr: &[u8]) -> Option<crate::turn::Allocation> {
+        let (turn_server, username, password) = crate::turn::get_server()?;
+        let peer_addr = AddrMangle::decode(socket_addr);
+        match crate::turn::Allocation::new(&turn_server, username, password, peer_addr.ip()).await {
+            Ok(allocation) => Some(allocation),
+            Err(err) => {
+                log::warn!(
+                    "Failed to allocate on the TURN server {}, fall back to the relay server: {}",
+                    turn_server,
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    async fn create_turn_relay(
+        &self,
+        rr: RequestRelay,
+        mut allocation: crate::turn::Allocation,
+        server: ServerPtr,
+    ) -> ResultType<()> {
+        let peer_addr = AddrMangle::decode(&rr.socket_addr);
+        log::info!(
+            "create_turn_relay requested from {:?}, relayed address: {}, uuid: {}, secure: {}",
+            peer_addr,
+            allocation.relayed(),
+            rr.uuid,
+            rr.secure,
+        );
+        let mut socket = connect_server(&self.host, RENDEZVOUS_PATH, CONNECT_TIMEOUT).await?;
+        let mut msg_out = Message::new();
+        msg_out.set_relay_response(RelayResponse {
+            socket_addr: rr.socket_addr,
+            version: crate::VERSION.to_owned(),
+            turn_addr: allocation.relayed().to_string(),
+            ..Default::default()
+        });
+        socket.send(&msg_out).await?;
+        // the requester asks again for the relay server if it can not connect
+        let stream = allocation.accept(CONNECT_TIMEOUT).await?;
+        let keep_alive = tokio::spawn(allocation.keep_alive());
+        let res = crate::server::create_relayed_connection(
+            server,
+            stream,
+            &rr.uuid,
+            peer_addr,
+            rr.secure,
+            String::new(),
+        )
+        .await;
+        keep_alive.abort();
+        res
+    }
+
     async fn handle_intranet(&self, fla: FetchLocalAddr, server: ServerPtr) -> ResultType<()> {
-        let relay_server = self.get_relay_server(fla.relay_server.clone());
+        let relay_server = self.get_relay_server(fla.relay_server.clone()).await;
         // nat64, go relay directly, because current hbbs will crash if demangle ipv6 address
-        if is_ipv4(&self.addr) && !config::is_disable_tcp_listen() && !Config::is_proxy() {
+        if is_ipv4(&self.addr)
+            && !config::is_disable_tcp_listen()
+            && !Config::is_proxy()
+            && !socket_client::is_tunneled(&self.host)
+        {
             if let Err(err) = self
                 .handle_intranet_(fla.clone(), server.clone(), relay_server.clone())
                 .await
@@ -489,7 +578,7 @@ impl RendezvousMediator {
     ) -> ResultType<()> {
         let peer_addr = AddrMangle::decode(&fla.socket_addr);
         log::debug!("Handle intranet from {:?}", peer_addr);
-        let mut socket = connect_tcp(&*self.host, CONNECT_TIMEOUT).await?;
+        let mut socket = connect_server(&self.host, RENDEZVOUS_PATH, CONNECT_TIMEOUT).await?;
         let local_addr = socket.local_addr();
         // we saw invalid local_addr while using proxy, local_addr.ip() == "::1"
         let local_addr: SocketAddr =
@@ -505,15 +594,16 @@ impl RendezvousMediator {
         });
         let bytes = msg_out.write_to_bytes()?;
         socket.send_raw(bytes).await?;
-        crate::accept_connection(server.clone(), socket, peer_addr, true).await;
+        crate::accept_connection(server.clone(), socket, peer_addr, true, None).await;
         Ok(())
     }
 
     async fn handle_punch_hole(&self, ph: PunchHole, server: ServerPtr) -> ResultType<()> {
-        let relay_server = self.get_relay_server(ph.relay_server);
+        let relay_server = self.get_relay_server(ph.relay_server).await;
         if ph.nat_type.enum_value() == Ok(NatType::SYMMETRIC)
             || Config::get_nat_type() == NatType::SYMMETRIC as i32
             || config::is_disable_tcp_listen()
+            || socket_client::is_tunneled(&self.host)
         {
             let uuid = Uuid::new_v4().to_string();
             return self
@@ -529,14 +619,26 @@ impl RendezvousMediator {
         }
         let peer_addr = AddrMangle::decode(&ph.socket_addr);
         log::debug!("Punch hole to {:?}", peer_addr);
+        // a port mapped on the router is punched from if it can be, the nat lets the peer in then
+        let mapping = if is_ipv4(&self.addr) {
+            crate::port_mapping::Mapping::for_punch_hole().await
+        } else {
+            None
+        };
         let mut socket = {
-            let socket = connect_tcp(&*self.host, CONNECT_TIMEOUT).await?;
+            let socket = socket_client::connect_tcp_local(
+                &*self.host,
+                mapping.as_ref().map(|m| m.local_addr()),
+                CONNECT_TIMEOUT,
+            )
+            .await?;
             let local_addr = socket.local_addr();
             // key important here for punch hole to tell my gateway incoming peer is safe.
             // it can not be async here, because local_addr can not be reused, we must close the connection before use it again.
             allow_err!(socket_client::connect_tcp_local(peer_addr, Some(local_addr), 30).await);
             socket
         };
+        let local_addr_v6 = self.punch_hole_v6(&ph.socket_addr_v6).await;
         let mut msg_out = Message::new();
         use hbb_common::protobuf::Enum;
         let nat_type = NatType::from_i32(Config::get_nat_type()).unwrap_or(NatType::UNKNOWN_NAT);
@@ -546,14 +648,32 @@ impl RendezvousMediator {
             relay_server,
             nat_type: nat_type.into(),
             version: crate::VERSION.to_owned(),
+            socket_addr_v6: local_addr_v6
+                .map(|addr| AddrMangle::encode(addr).into())
+                .unwrap_or_default(),
             ..Default::default()
         });
         let bytes = msg_out.write_to_bytes()?;
         socket.send_raw(bytes).await?;
-        crate::accept_connection(server.clone(), socket, peer_addr, true).await;
+        crate::accept_connection(server.clone(), socket, peer_addr, true, local_addr_v6).await;
+        drop(mapping);
         Ok(())
     }
 
+    // The peer is reached over ipv6 too if both sides have a global address, besides the address
+    // of the family of the rendezvous connection. No nat is expected, the firewall is opened by
+    // connecting out, returns the address to be listened on.
+    async fn punch_hole_v6(&self, peer_addr_v6: &[u8]) -> Option<SocketAddr> {
+        if peer_addr_v6.len() != 18 || Config::is_proxy() {
+            return None;
+        }
+        let peer_addr_v6 = AddrMangle::decode(peer_addr_v6);
+        let local_addr_v6 = socket_client::local_ipv6_candidate(Some(peer_addr_v6))?;
+        log::debug!("Punch hole to {:?} from {:?}", peer_addr_v6, local_addr_v6);
+        allow_err!(socket_client::connect_tcp_local(peer_addr_v6, Some(local_addr_v6), 30).await);
+        Some(local_addr_v6)
+    }
+
     async fn register_pk(&mut self, socket: Sink<'_>) -> ResultType<()> {
         let mut msg_out = Message::new();
         let pk = Config::get_key_pair().1;
@@ -569,6 +689,16 @@ impl RendezvousMediator {
         Ok(())
     }
 
+    async fn register_alias(&mut self, socket: Sink<'_>) -> ResultType<()> {
+        if let Some(ra) = crate::alias::registration(&self.host) {
+            log::info!("Register aliases {:?} to {}", ra.aliases, self.host);
+            let mut msg_out = Message::new();
+            msg_out.set_register_alias(ra);
+            socket.send(&msg_out).await?;
+        }
+        Ok(())
+    }
+
     async fn handle_uuid_mismatch(&mut self, socket: Sink<'_>) -> ResultType<()> {
         {
             let mut solving = SOLVING_PK_MISMATCH.lock().await;
@@ -614,8 +744,8 @@ impl RendezvousMediator {
         Ok(())
     }
 
-    fn get_relay_server(&self, provided_by_rendezvous_server: String) -> String {
-        let mut relay_server = Config::get_option("relay-server");
+    async fn get_relay_server(&self, provided_by_rendezvous_server: String) -> String {
+        let mut relay_server = crate::relay_select::pick().await;
         if relay_server.is_empty() {
             relay_server = provided_by_rendezvous_server;
         }
@@ -639,12 +769,56 @@ fn get_direct_port() -> i32 {
 async fn direct_server(server: ServerPtr) {
     let mut listener = None;
     let mut port = 0;
+    // the knocks which open the listener, if the knock key is set
+    let mut gate: Option<crate::port_knock::Gate> = None;
+    let mut gate_failed_port = 0;
+    let mut mapping: Option<crate::port_mapping::Mapping> = None;
     loop {
         let disabled = !option2bool(
             OPTION_DIRECT_SERVER,
             &Config::get_option(OPTION_DIRECT_SERVER),
         ) || option2bool("stop-service", &Config::get_option("stop-service"));
-        if !disabled && listener.is_none() {
+        let key = if disabled {
+            None
+        } else {
+            crate::port_knock::get_key()
+        };
+        match key.as_ref() {
+            Some(key) => {
+                let knock_port = get_direct_port();
+                if !gate
+                    .as_ref()
+                    .map_or(false, |g| g.is_for(knock_port as _, key))
+                    && gate_failed_port != knock_port
+                {
+                    gate = None;
+                    match crate::port_knock::Gate::new(knock_port as _, key.clone()) {
+                        Ok(g) => {
+                            gate = Some(g);
+                            gate_failed_port = 0;
+                        }
+                        Err(err) => {
+                            log::error!(
+                                "Failed to wait for knocks on port: {}, error: {}",
+                                knock_port,
+                                err
+                            );
+                            gate_failed_port = knock_port;
+                        }
+                    }
+                }
+            }
+            None => {
+                gate = None;
+                gate_failed_port = 0;
+            }
+        }
+        if let Some(g) = gate.as_mut() {
+            g.recv(if listener.is_none() { 1000 } else { 0 }).await;
+        }
+        // closed to all without a knock, and to all if the knocks can not be received
+        let closed = key.is_some() && !gate.as_mut().map_or(false, |g| g.is_open());
+        if !disabled && !closed && listener.is_none() {
             port = get_direct_port();
             match hbb_common::tcp::listen_any(port as _).await {
                 Ok(l) => {
@@ -671,12 +845,26 @@ async fn direct_server(server: ServerPtr) {
             }
         }
         if let Some(l) = listener.as_mut() {
-            if disabled || port != get_direct_port() {
+            if disabled || closed || port != get_direct_port() {
                 log::info!("Exit direct access listen");
                 listener = None;
+                mapping = None;
                 continue;
             }
-            if let Ok(Ok((stream, addr))) = hbb_common::timeout(1000, l.accept()).await {
+            if mapping.is_some() != crate::port_mapping::is_allowed() {
+                mapping = if mapping.is_none() {
+                    Some(crate::port_mapping::Mapping::start(port as _))
+                } else {
+                    None
+                };
+            }
+            // the knocks are not received while waiting here
+            let ms = if gate.is_some() { 100 } else { 1000 };
+            if let Ok(Ok((stream, addr))) = hbb_common::timeout(ms, l.accept()).await {
+                if !gate.as_ref().map_or(true, |g| g.allows(addr.ip())) {
+                    log::info!("direct access from {} without a knock, dropped", addr);
+                    continue;
+                }
                 stream.set_nodelay(true).ok();
                 log::info!("direct access from {}", addr);
                 let local_addr = stream
@@ -690,19 +878,78 @@ async fn direct_server(server: ServerPtr) {
                             hbb_common::Stream::from(stream, local_addr),
                             addr,
                             false,
+                            false,
+                            String::new(),
                         )
                         .await
                     );
                 });
-            } else {
+            } else if gate.is_none() {
                 sleep(0.1).await;
             }
-        } else {
+        } else if gate.is_none() {
             sleep(1.).await;
         }
     }
 }
 
+// on the udp port of the direct access one
+async fn quic_server(server: ServerPtr) {
+    let mut endpoint = None;
+    let mut port = 0;
+    loop {
+        let disabled = !option2bool(
+            OPTION_DIRECT_SERVER,
+            &Config::get_option(OPTION_DIRECT_SERVER),
+        ) || option2bool("stop-service", &Config::get_option("stop-service"))
+            || !hbb_common::quic::enabled()
+            // the knocks are received on the same udp port, and only open the tcp one
+            || crate::port_knock::get_key().is_some();
+        if (disabled || port != get_direct_port()) && endpoint.take().is_some() {
+            log::info!("Exit QUIC direct access listen");
+        }
+        if !disabled && endpoint.is_none() {
+            port = get_direct_port();
+            match hbb_common::quic::listen_any(port as _) {
+                Ok(e) => {
+                    log::info!("QUIC direct server listening on: {:?}", e.local_addr());
+                    endpoint = Some(e);
+                }
+                Err(err) => {
+                    log::error!(
+                        "Failed to start QUIC direct server on port: {}, error: {}",
+                        port,
+                        err
+                    );
+                    while port == get_direct_port() && hbb_common::quic::enabled() {
+                        sleep(1.).await;
+                    }
+                    continue;
+                }
+            }
+        }
+        let Some(e) = endpoint.as_ref() else {
+            sleep(1.).await;
+            continue;
+        };
+        if let Ok(Some(incoming)) = hbb_common::timeout(1000, e.accept()).await {
+            let server = server.clone();
+            tokio::spawn(async move {
+                match hbb_common::quic::accept(incoming).await {
+                    Ok((stream, addr)) => {
+                        log::info!("direct access over QUIC from {}", addr);
+                        allow_err!(
+                            crate::server::create_tcp_connection(server, stream, addr, false)
+                                .await
+                        );
+                    }
+                    Err(err) => log::debug!("Failed to accept QUIC connection: {}", err),
+                }
+            });
+        }
+    }
+}
+
 enum Sink<'a> {
     Framed(&'a mut FramedSocket, &'a TargetAddr<'a>),
     Stream(&'a mut FramedStream),
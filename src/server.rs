@@ -31,6 +31,40 @@ use service::{EmptyExtraFieldService, GenericService, Service, Subscriber};
 use crate::ipc::Data;
 
 pub mod audio_service;
+pub mod audit_log;
+mod bandwidth;
+mod broadcast;
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+pub mod camera_service;
+mod congestion;
+#[cfg(feature = "denoise")]
+mod denoise;
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+mod gamepad;
+#[cfg(feature = "flutter")]
+mod handoff;
+mod input_control;
+pub mod ip_rules;
+pub mod login_lockout;
+mod notify;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+mod pen;
+mod presenter;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub mod print_jobs;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+mod service_manager;
+mod system_stats;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+mod task_manager;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+mod terminal;
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+mod touch;
+pub mod unattended_schedule;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+mod virtual_mic;
+mod watermark;
 cfg_if::cfg_if! {
 if #[cfg(not(any(target_os = "android", target_os = "ios")))] {
 mod clipboard_service;
@@ -98,6 +132,8 @@ pub fn new() -> ServerPtr {
         id_count: hbb_common::rand::random::<i32>() % 1000 + 1000, // ensure positive
     };
     server.add_service(Box::new(audio_service::new()));
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    server.add_service(Box::new(camera_service::new()));
     #[cfg(not(target_os = "ios"))]
     server.add_service(Box::new(display_service::new()));
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
@@ -118,7 +154,12 @@ pub fn new() -> ServerPtr {
     Arc::new(RwLock::new(server))
 }
 
-async fn accept_connection_(server: ServerPtr, socket: Stream, secure: bool) -> ResultType<()> {
+async fn accept_connection_(
+    server: ServerPtr,
+    socket: Stream,
+    secure: bool,
+    local_addr_v6: Option<SocketAddr>,
+) -> ResultType<()> {
     let local_addr = socket.local_addr();
     drop(socket);
     // even we drop socket, below still may fail if not use reuse_addr,
@@ -126,10 +167,45 @@ async fn accept_connection_(server: ServerPtr, socket: Stream, secure: bool) ->
     // see “Only one usage of each socket address is normally permitted” on windows sometimes,
     let listener = new_listener(local_addr, true).await?;
     log::info!("Server listening on: {}", &listener.local_addr()?);
-    if let Ok((stream, addr)) = timeout(CONNECT_TIMEOUT, listener.accept()).await? {
+    // the peer connects to whichever of the addresses punched it reaches first
+    let listener_v6 = match local_addr_v6 {
+        Some(addr) => match new_listener(addr, true).await {
+            Ok(l) => {
+                log::info!("Server listening on: {}", addr);
+                Some(l)
+            }
+            Err(err) => {
+                log::debug!("Failed to listen on {}: {}", addr, err);
+                None
+            }
+        },
+        None => None,
+    };
+    let accept_v6 = async {
+        match listener_v6.as_ref() {
+            Some(l) => l.accept().await,
+            None => std::future::pending().await,
+        }
+    };
+    let accepted = timeout(CONNECT_TIMEOUT, async {
+        tokio::select! {
+            res = listener.accept() => res,
+            res = accept_v6 => res,
+        }
+    })
+    .await?;
+    if let Ok((stream, addr)) = accepted {
         stream.set_nodelay(true).ok();
         let stream_addr = stream.local_addr()?;
-        create_tcp_connection(server, Stream::from(stream, stream_addr), addr, secure).await?;
+        create_tcp_connection(
+            server,
+            Stream::from(stream, stream_addr),
+            addr,
+            secure,
+            false,
+            String::new(),
+        )
+        .await?;
     }
     Ok(())
 }
@@ -139,9 +215,36 @@ pub async fn create_tcp_connection(
     stream: Stream,
     addr: SocketAddr,
     secure: bool,
+    relay: bool,
+    // the relay server the session goes through, empty if none or a TURN server
+    relay_server: String,
 ) -> ResultType<()> {
     let mut stream = stream;
     let id = server.write().unwrap().get_new_id();
+    secure_connection(&mut stream, secure).await?;
+    #[cfg(target_os = "macos")]
+    {
+        use std::process::Command;
+        Command::new("/usr/bin/caffeinate")
+            .arg("-u")
+            .arg("-t 5")
+            .spawn()
+            .ok();
+        log::info!("wake up macos");
+    }
+    Connection::start(
+        addr,
+        stream,
+        id,
+        Arc::downgrade(&server),
+        relay,
+        relay_server,
+    )
+    .await;
+    Ok(())
+}
+
+async fn secure_connection(stream: &mut Stream, secure: bool) -> ResultType<()> {
     let (sk, pk) = Config::get_key_pair();
     if secure && pk.len() == sign::PUBLICKEYBYTES && sk.len() == sign::SECRETKEYBYTES {
         let mut sk_ = [0u8; sign::SECRETKEYBYTES];
@@ -149,18 +252,21 @@ pub async fn create_tcp_connection(
         let sk = sign::SecretKey(sk_);
         let mut msg_out = Message::new();
         let (our_pk_b, our_sk_b) = box_::gen_keypair();
+        let id_pk = IdPk {
+            id: Config::get_id(),
+            pk: Bytes::from(our_pk_b.0.to_vec()),
+            ..Default::default()
+        }
+        .write_to_bytes()
+        .unwrap_or_default();
+        // peers may still be given the old public key by a rendezvous server
+        let old_id = Config::get_old_key_pair()
+            .and_then(|(old_sk, _)| sign::SecretKey::from_slice(&old_sk))
+            .map(|old_sk| sign::sign(&id_pk, &old_sk).into())
+            .unwrap_or_default();
         msg_out.set_signed_id(SignedId {
-            id: sign::sign(
-                &IdPk {
-                    id: Config::get_id(),
-                    pk: Bytes::from(our_pk_b.0.to_vec()),
-                    ..Default::default()
-                }
-                .write_to_bytes()
-                .unwrap_or_default(),
-                &sk,
-            )
-            .into(),
+            id: sign::sign(&id_pk, &sk).into(),
+            old_id,
             ..Default::default()
         });
         timeout(CONNECT_TIMEOUT, stream.send(&msg_out)).await??;
@@ -193,18 +299,6 @@ pub async fn create_tcp_connection(
             }
         }
     }
-
-    #[cfg(target_os = "macos")]
-    {
-        use std::process::Command;
-        Command::new("/usr/bin/caffeinate")
-            .arg("-u")
-            .arg("-t 5")
-            .spawn()
-            .ok();
-        log::info!("wake up macos");
-    }
-    Connection::start(addr, stream, id, Arc::downgrade(&server)).await;
     Ok(())
 }
 
@@ -213,8 +307,9 @@ pub async fn accept_connection(
     socket: Stream,
     peer_addr: SocketAddr,
     secure: bool,
+    local_addr_v6: Option<SocketAddr>,
 ) {
-    if let Err(err) = accept_connection_(server, socket, secure).await {
+    if let Err(err) = accept_connection_(server, socket, secure, local_addr_v6).await {
         log::error!("Failed to accept connection from {}: {}", peer_addr, err);
     }
 }
@@ -247,8 +342,9 @@ async fn create_relay_connection_(
     secure: bool,
     ipv4: bool,
 ) -> ResultType<()> {
-    let mut stream = socket_client::connect_tcp(
-        socket_client::ipv4_to_ipv6(crate::check_port(relay_server, RELAY_PORT), ipv4),
+    let mut stream = socket_client::connect_server(
+        &socket_client::ipv4_to_ipv6(crate::check_port(&relay_server, RELAY_PORT), ipv4),
+        hbb_common::websocket::RELAY_PATH,
         CONNECT_TIMEOUT,
     )
     .await?;
@@ -256,11 +352,30 @@ async fn create_relay_connection_(
     let licence_key = crate::get_key(true).await;
     msg_out.set_request_relay(RequestRelay {
         licence_key,
-        uuid,
+        uuid: uuid.clone(),
         ..Default::default()
     });
     stream.send(&msg_out).await?;
-    create_tcp_connection(server, stream, peer_addr, secure).await?;
+    create_relayed_connection(server, stream, &uuid, peer_addr, secure, relay_server).await
+}
+
+/// Serves the session of a relayed stream, or hands the stream to the session waiting for it as
+/// the backup path, returning once the session ends.
+pub async fn create_relayed_connection(
+    server: ServerPtr,
+    mut stream: Stream,
+    uuid: &str,
+    peer_addr: SocketAddr,
+    secure: bool,
+    relay_server: String,
+) -> ResultType<()> {
+    if let Some(session) = crate::backup_path::take_waiting_session(uuid) {
+        secure_connection(&mut stream, secure).await?;
+        session.send(stream).ok();
+        session.closed().await;
+        return Ok(());
+    }
+    create_tcp_connection(server, stream, peer_addr, secure, true, relay_server).await?;
     Ok(())
 }
 
@@ -13,17 +13,41 @@
 // https://github.com/krruzic/pulsectl
 
 use super::*;
+use crate::opus_multistream::MultistreamEncoder;
 #[cfg(not(any(target_os = "linux", target_os = "android")))]
 use hbb_common::anyhow::anyhow;
-use magnum_opus::{Application::*, Channels::*, Encoder};
-use std::sync::atomic::{AtomicBool, Ordering};
+use magnum_opus::{Application::*, Bitrate, Channels::*, Encoder};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 pub const NAME: &'static str = "audio";
 pub const AUDIO_DATA_SIZE_U8: usize = 960 * 4; // 10ms in 48000 stereo
 static RESTARTING: AtomicBool = AtomicBool::new(false);
+// The bitrate set to the encoder in kbps, 0 for the default of opus.
+static BITRATE_KBPS: AtomicU32 = AtomicU32::new(0);
+// Whether the inband FEC is set to the encoder.
+static FEC: AtomicBool = AtomicBool::new(false);
+// the packet loss the FEC is made for
+const FEC_PACKET_LOSS_PERC: i32 = 10;
+// the samples below it are silent for the DTX, about -80 dB
+const DTX_SILENCE: f32 = 1e-4;
 
 lazy_static::lazy_static! {
     static ref VOICE_CALL_INPUT_DEVICE: Arc::<Mutex::<Option<String>>> = Default::default();
+    // the connection which chose it, and the device
+    static ref PEER_INPUT_DEVICE: Arc::<Mutex::<Option<(i32, String)>>> = Default::default();
+    // the connection which set it last, and the quality
+    static ref QUALITY: Arc::<Mutex::<Option<(i32, AudioQuality)>>> = Default::default();
+    // the samples waiting for the frame duration of the quality
+    static ref PENDING: Mutex<Vec<f32>> = Default::default();
+    // the connection which turned the noise suppression on last
+    static ref DENOISE: Arc::<Mutex::<Option<i32>>> = Default::default();
+    static ref ECHO_SUPPRESSOR: Mutex<crate::echo::Suppressor> = Default::default();
+}
+
+#[cfg(feature = "denoise")]
+lazy_static::lazy_static! {
+    // made for the format sent last
+    static ref DENOISER: Mutex<Option<super::denoise::Denoiser>> = Default::default();
 }
 
 #[cfg(not(any(target_os = "linux", target_os = "android")))]
@@ -58,12 +82,162 @@ pub fn set_voice_call_input_device(device: Option<String>, set_if_present: bool)
     restart();
 }
 
+/// The input device chosen by the controlling side, none for the one of the settings.
+pub fn set_peer_input_device(conn_id: i32, device: Option<String>) {
+    let device = device.map(|d| (conn_id, d));
+    let mut lock = PEER_INPUT_DEVICE.lock().unwrap();
+    if lock.as_ref().map(|d| &d.1) == device.as_ref().map(|d| &d.1) {
+        return;
+    }
+    *lock = device;
+    drop(lock);
+    restart();
+}
+
+/// The encoder is shared by all the connections, the one which set the quality last wins.
+pub fn set_quality(conn_id: i32, quality: AudioQuality) {
+    log::info!("audio quality of connection {conn_id}: {quality:?}");
+    let mut lock = QUALITY.lock().unwrap();
+    let (application, max_channels) = lock
+        .as_ref()
+        .map(|q| (q.1.application.value(), q.1.max_channels))
+        .unwrap_or_default();
+    let restart_encoder =
+        application != quality.application.value() || max_channels != quality.max_channels;
+    *lock = Some((conn_id, quality));
+    drop(lock);
+    // neither the application nor the channels can be changed once the encoder is created
+    if restart_encoder {
+        restart();
+    }
+}
+
+/// The noise suppression is shared by all the connections too, it is only done with the feature
+/// `denoise`.
+pub fn set_denoise(conn_id: i32, on: bool) {
+    log::info!("audio noise suppression of connection {conn_id}: {on}");
+    *DENOISE.lock().unwrap() = on.then_some(conn_id);
+}
+
+fn quality() -> AudioQuality {
+    QUALITY
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|q| q.1.clone())
+        .unwrap_or_default()
+}
+
+fn application() -> magnum_opus::Application {
+    match quality().application.enum_value() {
+        Ok(audio_quality::Application::Voice) => Voip,
+        Ok(audio_quality::Application::Music) => Audio,
+        _ => LowDelay,
+    }
+}
+
+// The plain opus of mono and stereo, or the multistream of more channels.
+enum AudioEncoder {
+    Opus(Encoder),
+    Multistream(MultistreamEncoder),
+}
+
+impl AudioEncoder {
+    fn new(sample_rate: u32, channels: u16) -> ResultType<Self> {
+        Ok(match channels {
+            1 => Self::Opus(Encoder::new(sample_rate, Mono, application())?),
+            2 => Self::Opus(Encoder::new(sample_rate, Stereo, application())?),
+            _ => Self::Multistream(MultistreamEncoder::new(
+                sample_rate,
+                channels,
+                application(),
+            )?),
+        })
+    }
+
+    fn set_bitrate(&mut self, bitrate: Bitrate) -> ResultType<()> {
+        match self {
+            Self::Opus(e) => e.set_bitrate(bitrate)?,
+            Self::Multistream(e) => e.set_bitrate(bitrate)?,
+        }
+        Ok(())
+    }
+
+    fn set_inband_fec(&mut self, fec: bool) -> ResultType<()> {
+        match self {
+            Self::Opus(e) => e.set_inband_fec(fec)?,
+            Self::Multistream(e) => e.set_inband_fec(fec)?,
+        }
+        Ok(())
+    }
+
+    fn set_packet_loss_perc(&mut self, perc: i32) -> ResultType<()> {
+        match self {
+            Self::Opus(e) => e.set_packet_loss_perc(perc)?,
+            Self::Multistream(e) => e.set_packet_loss_perc(perc)?,
+        }
+        Ok(())
+    }
+
+    fn encode_vec_float(&mut self, input: &[f32], max_size: usize) -> ResultType<Vec<u8>> {
+        Ok(match self {
+            Self::Opus(e) => e.encode_vec_float(input, max_size)?,
+            Self::Multistream(e) => e.encode_vec_float(input, max_size)?,
+        })
+    }
+}
+
+/// Goes back to the input device of the settings and the default quality, if the connection
+/// chose the current ones.
+pub fn on_connection_close(conn_id: i32) {
+    let chosen = PEER_INPUT_DEVICE.lock().unwrap().as_ref().map(|d| d.0) == Some(conn_id);
+    if chosen {
+        set_peer_input_device(conn_id, None);
+    }
+    let chosen = QUALITY.lock().unwrap().as_ref().map(|q| q.0) == Some(conn_id);
+    if chosen {
+        set_quality(conn_id, Default::default());
+    }
+    let mut denoise = DENOISE.lock().unwrap();
+    if *denoise == Some(conn_id) {
+        *denoise = None;
+    }
+}
+
+/// The names of the input devices, the monitors of the outputs included on Linux.
+#[cfg(not(target_os = "android"))]
+pub fn get_input_devices() -> Vec<String> {
+    #[cfg(not(target_os = "linux"))]
+    {
+        use cpal::traits::{DeviceTrait, HostTrait};
+        let Ok(devices) = cpal::default_host().devices() else {
+            return vec![];
+        };
+        devices
+            .filter(|d| d.default_input_config().is_ok())
+            .filter_map(|d| d.name().ok())
+            .collect()
+    }
+    #[cfg(target_os = "linux")]
+    {
+        crate::platform::linux::get_pa_sources()
+            .drain(..)
+            .map(|x| x.1)
+            .collect()
+    }
+}
+
 #[inline]
 fn get_audio_input() -> String {
     VOICE_CALL_INPUT_DEVICE
         .lock()
         .unwrap()
         .clone()
+        .or(PEER_INPUT_DEVICE
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|d| d.1.clone()))
         .unwrap_or(Config::get_option("audio-input"))
 }
 
@@ -87,7 +261,10 @@ mod pa_impl {
         unsafe {
             AUDIO_ZERO_COUNT = 0;
         }
-        let mut encoder = Encoder::new(crate::platform::PA_SAMPLE_RATE, Stereo, LowDelay)?;
+        let mut encoder = AudioEncoder::new(crate::platform::PA_SAMPLE_RATE, 2)?;
+        BITRATE_KBPS.store(0, Ordering::SeqCst);
+        FEC.store(false, Ordering::SeqCst);
+        PENDING.lock().unwrap().clear();
         #[cfg(target_os = "linux")]
         allow_err!(
             stream
@@ -103,7 +280,11 @@ mod pa_impl {
         let mut android_data = vec![];
         while sp.ok() && !RESTARTING.load(Ordering::SeqCst) {
             sp.snapshot(|sps| {
-                sps.send(create_format_msg(crate::platform::PA_SAMPLE_RATE, 2));
+                sps.send(create_format_msg(
+                    crate::platform::PA_SAMPLE_RATE,
+                    2,
+                    &encoder,
+                ));
                 Ok(())
             })?;
             #[cfg(target_os = "linux")]
@@ -208,7 +389,7 @@ mod cpal_impl {
         sample_rate: u32,
         device_channel: u16,
         encode_channel: u16,
-        encoder: &mut Encoder,
+        encoder: &mut AudioEncoder,
         sp: &GenericService,
     ) {
         let mut data = data;
@@ -297,25 +478,41 @@ mod cpal_impl {
         } else {
             48000
         };
-        let ch = if config.channels() > 1 { Stereo } else { Mono };
+        let ch = encode_channels(config.channels());
+        let encoder = AudioEncoder::new(sample_rate, ch)?;
+        let format = create_format_msg(sample_rate, ch, &encoder);
         let stream = match config.sample_format() {
-            I8 => build_input_stream::<i8>(device, &config, sp, sample_rate, ch)?,
-            I16 => build_input_stream::<i16>(device, &config, sp, sample_rate, ch)?,
-            I32 => build_input_stream::<i32>(device, &config, sp, sample_rate, ch)?,
-            I64 => build_input_stream::<i64>(device, &config, sp, sample_rate, ch)?,
-            U8 => build_input_stream::<u8>(device, &config, sp, sample_rate, ch)?,
-            U16 => build_input_stream::<u16>(device, &config, sp, sample_rate, ch)?,
-            U32 => build_input_stream::<u32>(device, &config, sp, sample_rate, ch)?,
-            U64 => build_input_stream::<u64>(device, &config, sp, sample_rate, ch)?,
-            F32 => build_input_stream::<f32>(device, &config, sp, sample_rate, ch)?,
-            F64 => build_input_stream::<f64>(device, &config, sp, sample_rate, ch)?,
+            I8 => build_input_stream::<i8>(device, &config, sp, sample_rate, ch, encoder)?,
+            I16 => build_input_stream::<i16>(device, &config, sp, sample_rate, ch, encoder)?,
+            I32 => build_input_stream::<i32>(device, &config, sp, sample_rate, ch, encoder)?,
+            I64 => build_input_stream::<i64>(device, &config, sp, sample_rate, ch, encoder)?,
+            U8 => build_input_stream::<u8>(device, &config, sp, sample_rate, ch, encoder)?,
+            U16 => build_input_stream::<u16>(device, &config, sp, sample_rate, ch, encoder)?,
+            U32 => build_input_stream::<u32>(device, &config, sp, sample_rate, ch, encoder)?,
+            U64 => build_input_stream::<u64>(device, &config, sp, sample_rate, ch, encoder)?,
+            F32 => build_input_stream::<f32>(device, &config, sp, sample_rate, ch, encoder)?,
+            F64 => build_input_stream::<f64>(device, &config, sp, sample_rate, ch, encoder)?,
             f => bail!("unsupported audio format: {:?}", f),
         };
         stream.play()?;
-        Ok((
-            Box::new(stream),
-            Arc::new(create_format_msg(sample_rate, ch as _)),
-        ))
+        Ok((Box::new(stream), Arc::new(format)))
+    }
+
+    // The surround of 5.1 and 7.1 is kept if the quality allows so many channels, the others are
+    // mixed down to stereo.
+    fn encode_channels(device_channel: u16) -> u16 {
+        let max_channels = quality()
+            .max_channels
+            .min(crate::opus_multistream::MAX_CHANNELS as _);
+        if crate::opus_multistream::is_supported(device_channel)
+            && device_channel as u32 <= max_channels
+        {
+            device_channel
+        } else if device_channel > 1 {
+            2
+        } else {
+            1
+        }
     }
 
     fn build_input_stream<T>(
@@ -323,7 +520,8 @@ mod cpal_impl {
         config: &cpal::SupportedStreamConfig,
         sp: GenericService,
         sample_rate: u32,
-        encode_channel: magnum_opus::Channels,
+        encode_channel: u16,
+        mut encoder: AudioEncoder,
     ) -> ResultType<cpal::Stream>
     where
         T: cpal::SizedSample + dasp::sample::ToSample<f32>,
@@ -338,7 +536,9 @@ mod cpal_impl {
             AUDIO_ZERO_COUNT = 0;
         }
         let device_channel = config.channels();
-        let mut encoder = Encoder::new(sample_rate, encode_channel, LowDelay)?;
+        BITRATE_KBPS.store(0, Ordering::SeqCst);
+        FEC.store(false, Ordering::SeqCst);
+        PENDING.lock().unwrap().clear();
         // https://www.opus-codec.org/docs/html_api/group__opusencoder.html#gace941e4ef26ed844879fde342ffbe546
         // https://chromium.googlesource.com/chromium/deps/opus/+/1.1.1/include/opus.h
         // Do not set `frame_size = sample_rate as usize / 100;`
@@ -367,7 +567,7 @@ mod cpal_impl {
                         sample_rate_0,
                         sample_rate,
                         device_channel,
-                        encode_channel as _,
+                        encode_channel,
                         &mut encoder,
                         &sp,
                     );
@@ -380,12 +580,23 @@ mod cpal_impl {
     }
 }
 
-fn create_format_msg(sample_rate: u32, channels: u16) -> Message {
-    let format = AudioFormat {
+fn create_format_msg(sample_rate: u32, channels: u16, encoder: &AudioEncoder) -> Message {
+    video_service::set_record_audio_format(sample_rate, channels);
+    #[cfg(feature = "denoise")]
+    {
+        *DENOISER.lock().unwrap() = super::denoise::Denoiser::new(sample_rate, channels);
+    }
+    let mut format = AudioFormat {
         sample_rate,
         channels: channels as _,
         ..Default::default()
     };
+    if let AudioEncoder::Multistream(e) = encoder {
+        let (streams, coupled_streams, mapping) = e.layout();
+        format.streams = streams;
+        format.coupled_streams = coupled_streams;
+        format.mapping = mapping.to_vec().into();
+    }
     let mut misc = Misc::new();
     misc.set_audio_format(format);
     let mut msg = Message::new();
@@ -399,7 +610,56 @@ fn create_format_msg(sample_rate: u32, channels: u16) -> Message {
 const MAX_AUDIO_ZERO_COUNT: u16 = 800;
 static mut AUDIO_ZERO_COUNT: u16 = 0;
 
-fn send_f32(data: &[f32], encoder: &mut Encoder, sp: &GenericService) {
+// Follow the bandwidth limit of the sessions and the quality.
+fn update_encoder(encoder: &mut AudioEncoder, quality: &AudioQuality) {
+    let kbps = match (bandwidth::audio_kbps().unwrap_or_default(), quality.bitrate) {
+        (0, kbps) | (kbps, 0) => kbps,
+        (limit, kbps) => limit.min(kbps),
+    };
+    if BITRATE_KBPS.swap(kbps, Ordering::SeqCst) != kbps {
+        let bitrate = if kbps > 0 {
+            Bitrate::Bits(kbps as i32 * 1000)
+        } else {
+            Bitrate::Auto
+        };
+        log::info!("audio bitrate: {bitrate:?}");
+        allow_err!(encoder.set_bitrate(bitrate));
+    }
+    if FEC.swap(quality.fec, Ordering::SeqCst) != quality.fec {
+        allow_err!(encoder.set_inband_fec(quality.fec));
+        let perc = if quality.fec { FEC_PACKET_LOSS_PERC } else { 0 };
+        allow_err!(encoder.set_packet_loss_perc(perc));
+    }
+}
+
+// None if the noise suppression is off, or the format is not supported.
+#[cfg(feature = "denoise")]
+fn denoise(data: &[f32]) -> Option<Vec<f32>> {
+    DENOISE.lock().unwrap().as_ref()?;
+    Some(DENOISER.lock().unwrap().as_mut()?.process(data))
+}
+
+// None if not in a voice call, or the echo suppression is off.
+fn suppress_echo(data: &[f32]) -> Option<Vec<f32>> {
+    VOICE_CALL_INPUT_DEVICE.lock().unwrap().as_ref()?;
+    if !Config::get_bool_option(hbb_common::config::keys::OPTION_ENABLE_VOICE_CALL_ECHO_SUPPRESSION)
+    {
+        return None;
+    }
+    Some(ECHO_SUPPRESSOR.lock().unwrap().process(data))
+}
+
+fn send_f32(data: &[f32], encoder: &mut AudioEncoder, sp: &GenericService) {
+    #[cfg(feature = "denoise")]
+    let denoised = denoise(data);
+    #[cfg(feature = "denoise")]
+    let data = denoised.as_deref().unwrap_or(data);
+    let suppressed = suppress_echo(data);
+    let data = suppressed.as_deref().unwrap_or(data);
+    let quality = quality();
+    if quality.dtx && data.iter().all(|x| x.abs() < DTX_SILENCE) {
+        return;
+    }
     if data.iter().filter(|x| **x != 0.).next().is_some() {
         unsafe {
             AUDIO_ZERO_COUNT = 0;
@@ -416,6 +676,7 @@ fn send_f32(data: &[f32], encoder: &mut Encoder, sp: &GenericService) {
             AUDIO_ZERO_COUNT += 1;
         }
     }
+    update_encoder(encoder, &quality);
     #[cfg(target_os = "android")]
     {
         // the permitted opus data size are 120, 240, 480, 960, 1920, and 2880
@@ -430,9 +691,11 @@ fn send_f32(data: &[f32], encoder: &mut Encoder, sp: &GenericService) {
                     .encode_vec_float(&data[i * BATCH_SIZE..(i + 1) * BATCH_SIZE], BATCH_SIZE)
                 {
                     Ok(data) => {
+                        video_service::record_audio(&data);
                         let mut msg_out = Message::new();
                         msg_out.set_audio_frame(AudioFrame {
                             data: data.into(),
+                            timestamp: video_service::media_timestamp(),
                             ..Default::default()
                         });
                         sp.send(msg_out);
@@ -446,12 +709,30 @@ fn send_f32(data: &[f32], encoder: &mut Encoder, sp: &GenericService) {
         }
     }
 
+    // the frames of 10 ms are encoded together
+    #[cfg(not(target_os = "android"))]
+    let data = {
+        let chunks = match quality.frame_duration {
+            20 => 2,
+            40 => 4,
+            60 => 6,
+            _ => 1,
+        };
+        let mut pending = PENDING.lock().unwrap();
+        pending.extend_from_slice(data);
+        if pending.len() < data.len() * chunks {
+            return;
+        }
+        pending.drain(..data.len() * chunks).collect::<Vec<f32>>()
+    };
     #[cfg(not(target_os = "android"))]
-    match encoder.encode_vec_float(data, data.len() * 6) {
+    match encoder.encode_vec_float(&data, data.len() * 6) {
         Ok(data) => {
+            video_service::record_audio(&data);
             let mut msg_out = Message::new();
             msg_out.set_audio_frame(AudioFrame {
                 data: data.into(),
+                timestamp: video_service::media_timestamp(),
                 ..Default::default()
             });
             sp.send(msg_out);
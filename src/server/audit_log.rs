@@ -0,0 +1,387 @@
+// The audit log of the security events on this side: the connections, the results of the
+// authentications, the permission changes, the file transfers, the clipboard files, the privacy
+// mode, the matches of the rules of the data loss prevention, the processes killed and the services
+// controlled.
+//
+// The events are appended as JSON lines to audit.log beside the other logs, which is moved aside
+// to audit.log.<milliseconds> once it grows too large, and the logs moved aside are removed after
+// the days of the retention. Every entry carries the sha256 of the one before and its own, chained
+// across the logs moved aside, so that an entry edited, inserted or removed breaks the chain
+// `verify` walks. Since the whole chain could be rewritten by whoever can write the logs, the hash
+// of the last entry is returned as the head, for the auditors to keep elsewhere.
+
+use std::{
+    collections::VecDeque,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use hbb_common::{
+    config::{keys, Config},
+    get_time, log, ResultType,
+};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+const LOG_NAME: &str = "audit.log";
+const MAX_LOG_SIZE: u64 = 10 * 1024 * 1024;
+const RETENTION_DAYS: u64 = 90;
+const QUERY_LIMIT: usize = 1000;
+
+lazy_static::lazy_static! {
+    // the seq and the hash of the last entry, loaded on first use
+    static ref LAST: Mutex<Option<(u64, String)>> = Default::default();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Connection,
+    Auth,
+    Permission,
+    FileTransfer,
+    ClipboardFile,
+    PrivacyMode,
+    Dlp,
+    Terminal,
+    WakeOnLan,
+    KillProcess,
+    Service,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub seq: u64,
+    // in milliseconds
+    pub time: i64,
+    pub kind: Kind,
+    pub conn_id: i32,
+    #[serde(default)]
+    pub peer_id: String,
+    #[serde(default)]
+    pub ip: String,
+    #[serde(default)]
+    pub data: Value,
+    // the hash of the entry before, empty for the first one
+    pub prev: String,
+    // the sha256 in hex of the entry without it
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+}
+
+impl Entry {
+    fn digest(&self) -> String {
+        let mut entry = self.clone();
+        entry.hash.clear();
+        hex::encode(Sha256::digest(
+            serde_json::to_vec(&entry).unwrap_or_default(),
+        ))
+    }
+}
+
+/// The entries matching all the filters set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Query {
+    // in milliseconds, 0 for any
+    #[serde(default)]
+    pub since: i64,
+    #[serde(default)]
+    pub until: i64,
+    #[serde(default)]
+    pub kind: Option<Kind>,
+    #[serde(default)]
+    pub peer_id: String,
+    // of the last entries matching, QUERY_LIMIT if 0
+    #[serde(default)]
+    pub limit: usize,
+}
+
+impl Query {
+    fn matches(&self, entry: &Entry) -> bool {
+        (self.since == 0 || entry.time >= self.since)
+            && (self.until == 0 || entry.time < self.until)
+            && self.kind.map(|k| k == entry.kind).unwrap_or(true)
+            && (self.peer_id.is_empty() || self.peer_id == entry.peer_id)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Verification {
+    pub entries: u64,
+    // the hash of the last entry
+    pub head: String,
+    // where the chain breaks, empty if it does not
+    pub error: String,
+}
+
+#[inline]
+fn enabled() -> bool {
+    Config::get_bool_option(keys::OPTION_ENABLE_AUDIT_LOG)
+}
+
+fn retention() -> Duration {
+    let days = Config::get_option(keys::OPTION_AUDIT_LOG_RETENTION_DAYS)
+        .parse::<u64>()
+        .unwrap_or(RETENTION_DAYS);
+    Duration::from_secs(days * 24 * 3600)
+}
+
+/// Records the event of the connection `conn_id` from `peer_id` at `ip`, if the audit log is
+/// enabled.
+pub fn record(kind: Kind, conn_id: i32, peer_id: &str, ip: &str, data: Value) {
+    if !enabled() {
+        return;
+    }
+    let entry = Entry {
+        seq: 0,
+        time: get_time(),
+        kind,
+        conn_id,
+        peer_id: peer_id.to_owned(),
+        ip: ip.to_owned(),
+        data,
+        prev: String::new(),
+        hash: String::new(),
+    };
+    let mut last = LAST.lock().unwrap();
+    if let Err(err) = append(&Config::log_path(), &mut last, entry, retention()) {
+        log::error!("Failed to record the audit event: {}", err);
+    }
+}
+
+/// The last entries of the audit log matching `query`.
+pub fn query(query: &Query) -> Vec<Entry> {
+    query_in(&Config::log_path(), query)
+}
+
+/// Walks the chain of the audit log.
+pub fn verify() -> Verification {
+    // not while an entry is appended
+    let _lock = LAST.lock().unwrap();
+    verify_in(&Config::log_path())
+}
+
+fn append(
+    dir: &Path,
+    last: &mut Option<(u64, String)>,
+    mut entry: Entry,
+    retention: Duration,
+) -> ResultType<()> {
+    let path = dir.join(LOG_NAME);
+    if last.is_none() {
+        *last = Some(last_entry(dir));
+        prune(dir, retention);
+    }
+    if path.metadata().map(|m| m.len()).unwrap_or(0) >= MAX_LOG_SIZE {
+        fs::rename(&path, dir.join(format!("{}.{}", LOG_NAME, get_time())))?;
+        prune(dir, retention);
+    }
+    let (seq, prev) = last.clone().unwrap_or_default();
+    entry.seq = seq + 1;
+    entry.prev = prev;
+    entry.hash = entry.digest();
+    let mut line = serde_json::to_string(&entry)?;
+    line.push('\n');
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?
+        .write_all(line.as_bytes())?;
+    *last = Some((entry.seq, entry.hash));
+    Ok(())
+}
+
+// The logs moved aside from the oldest, and then the current one.
+fn logs(dir: &Path) -> Vec<PathBuf> {
+    let prefix = format!("{}.", LOG_NAME);
+    let mut logs: Vec<(i64, PathBuf)> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| {
+                    let path = e.ok()?.path();
+                    let time = path
+                        .file_name()?
+                        .to_str()?
+                        .strip_prefix(&prefix)?
+                        .parse()
+                        .ok()?;
+                    Some((time, path))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    logs.sort_by_key(|(time, _)| *time);
+    let mut logs: Vec<PathBuf> = logs.into_iter().map(|(_, path)| path).collect();
+    let path = dir.join(LOG_NAME);
+    if path.exists() {
+        logs.push(path);
+    }
+    logs
+}
+
+// the lines which do not parse as the errors
+fn entries(path: &Path) -> Vec<Result<Entry, String>> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str::<Entry>(line).map_err(|_| line.to_owned()))
+        .collect()
+}
+
+fn last_entry(dir: &Path) -> (u64, String) {
+    logs(dir)
+        .iter()
+        .rev()
+        .find_map(|path| entries(path).into_iter().filter_map(|e| e.ok()).last())
+        .map(|e| (e.seq, e.hash))
+        .unwrap_or_default()
+}
+
+fn prune(dir: &Path, retention: Duration) {
+    let now = SystemTime::now();
+    let current = dir.join(LOG_NAME);
+    for path in logs(dir) {
+        if path == current {
+            continue;
+        }
+        let old = path
+            .metadata()
+            .and_then(|m| m.modified())
+            .map(|t| now.duration_since(t).unwrap_or_default() > retention)
+            .unwrap_or(false);
+        if old {
+            log::info!("Remove the audit log {:?}", path);
+            if let Err(err) = fs::remove_file(&path) {
+                log::error!("Failed to remove the audit log {:?}: {}", path, err);
+            }
+        }
+    }
+}
+
+fn query_in(dir: &Path, query: &Query) -> Vec<Entry> {
+    let limit = if query.limit == 0 {
+        QUERY_LIMIT
+    } else {
+        query.limit
+    };
+    let mut res = VecDeque::new();
+    for path in logs(dir) {
+        for entry in entries(&path).into_iter().filter_map(|e| e.ok()) {
+            if query.matches(&entry) {
+                if res.len() == limit {
+                    res.pop_front();
+                }
+                res.push_back(entry);
+            }
+        }
+    }
+    res.into()
+}
+
+fn verify_in(dir: &Path) -> Verification {
+    let mut res = Verification::default();
+    // the first entry kept may follow the ones removed after the retention
+    let mut last: Option<(u64, String)> = None;
+    for path in logs(dir) {
+        for entry in entries(&path) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(line) => {
+                    res.error = format!("Invalid entry after {}: {}", res.entries, line);
+                    return res;
+                }
+            };
+            if entry.digest() != entry.hash {
+                res.error = format!("The hash of the entry {} mismatches", entry.seq);
+                return res;
+            }
+            if let Some((seq, hash)) = last.as_ref() {
+                if entry.seq != seq + 1 || entry.prev != *hash {
+                    res.error = format!("The chain breaks between {} and {}", seq, entry.seq);
+                    return res;
+                }
+            }
+            res.entries += 1;
+            res.head = entry.hash.clone();
+            last = Some((entry.seq, entry.hash));
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(kind: Kind, peer_id: &str, time: i64) -> Entry {
+        Entry {
+            seq: 0,
+            time,
+            kind,
+            conn_id: 1,
+            peer_id: peer_id.to_owned(),
+            ip: "10.0.0.1".to_owned(),
+            data: json!({"result": "success"}),
+            prev: String::new(),
+            hash: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_chain() {
+        let dir = std::env::temp_dir().join(format!("audit_log_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let retention = Duration::from_secs(3600);
+        let mut last = None;
+        append(&dir, &mut last, entry(Kind::Connection, "a", 1), retention).unwrap();
+        append(&dir, &mut last, entry(Kind::Auth, "a", 2), retention).unwrap();
+        // moved aside, and chained on from the log moved aside
+        fs::rename(dir.join(LOG_NAME), dir.join(format!("{}.1", LOG_NAME))).unwrap();
+        let mut last = None;
+        append(&dir, &mut last, entry(Kind::Auth, "b", 3), retention).unwrap();
+        append(&dir, &mut last, entry(Kind::Permission, "b", 4), retention).unwrap();
+        assert_eq!(last.as_ref().unwrap().0, 4);
+
+        let res = verify_in(&dir);
+        assert_eq!(res.entries, 4);
+        assert!(res.error.is_empty(), "{}", res.error);
+        assert_eq!(res.head, last.unwrap().1);
+
+        let auths = query_in(
+            &dir,
+            &Query {
+                kind: Some(Kind::Auth),
+                ..Default::default()
+            },
+        );
+        assert_eq!(auths.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![2, 3]);
+        let res = query_in(
+            &dir,
+            &Query {
+                since: 2,
+                peer_id: "b".to_owned(),
+                limit: 1,
+                ..Default::default()
+            },
+        );
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].seq, 4);
+
+        // edited
+        let path = dir.join(LOG_NAME);
+        let log = fs::read_to_string(&path).unwrap();
+        fs::write(&path, log.replace("\"peer_id\":\"b\"", "\"peer_id\":\"c\"")).unwrap();
+        assert!(verify_in(&dir).error.contains("hash of the entry 3"));
+        // removed
+        let second = log.lines().nth(1).unwrap();
+        fs::write(&path, format!("{}\n", second)).unwrap();
+        assert!(verify_in(&dir).error.contains("between 2 and 4"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+// Per-session bandwidth cap.
+//
+// The controlled side limits all its sessions with the bandwidth-limit option, the controlling
+// side limits its own session with the same option of the peer, the lower one is used.
+//
+// Video, audio and file transfer share the budget of a session. Audio goes first with a small
+// share, file transfer gets what the others leave but no less than `FILE_MIN_PERCENT` while it is
+// running, and the video bitrate is lowered to fit the rest. The video and audio encoders are
+// shared by all sessions, so they follow the smallest budget.
+//
+// The relayed sessions are limited by their congestion control as well while the path is queuing.
+
+use super::{congestion::Bbr, *};
+use hbb_common::config::keys;
+use scrap::codec::{base_bitrate, Quality};
+use std::time::Instant;
+
+const WINDOW: Duration = Duration::from_secs(1);
+const AUDIO_PERCENT: u32 = 10;
+const MIN_AUDIO_KBPS: u32 = 12;
+const MAX_AUDIO_KBPS: u32 = 64;
+const FILE_MIN_PERCENT: u32 = 20;
+const MIN_VIDEO_KBPS: u32 = 50;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Usage {
+    Video = 0,
+    Audio = 1,
+    File = 2,
+    Other = 3,
+}
+
+#[derive(Debug)]
+struct Session {
+    host_limit: Option<u32>,
+    client_limit: Option<u32>,
+    congestion: Option<Bbr>,
+    window_start: Instant,
+    bytes: [usize; 4],
+    kbps: [u32; 4],
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            host_limit: host_limit(),
+            client_limit: None,
+            congestion: None,
+            window_start: Instant::now(),
+            bytes: Default::default(),
+            kbps: Default::default(),
+        }
+    }
+
+    // Measure the usage of the last window, and reload the option of the controlled side.
+    fn roll(&mut self) {
+        let elapsed = self.window_start.elapsed();
+        if elapsed < WINDOW {
+            return;
+        }
+        let millis = elapsed.as_millis().max(1) as usize;
+        for i in 0..self.bytes.len() {
+            // bits per millisecond is kbps
+            self.kbps[i] = (self.bytes[i] * 8 / millis) as u32;
+            self.bytes[i] = 0;
+        }
+        self.window_start = Instant::now();
+        self.host_limit = host_limit();
+    }
+
+    fn limit(&self) -> Option<u32> {
+        let congestion = self.congestion.as_ref().and_then(|c| c.limit_kbps());
+        [self.host_limit, self.client_limit, congestion]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    fn kbps(&self, usage: Usage) -> u32 {
+        self.kbps[usage as usize]
+    }
+
+    fn file_active(&self) -> bool {
+        self.kbps(Usage::File) > 0 || self.bytes[Usage::File as usize] > 0
+    }
+
+    fn audio_budget(&self) -> Option<u32> {
+        self.limit()
+            .map(|limit| (limit * AUDIO_PERCENT / 100).clamp(MIN_AUDIO_KBPS, MAX_AUDIO_KBPS))
+    }
+
+    fn video_budget(&self) -> Option<u32> {
+        let limit = self.limit()?;
+        let mut used = self.kbps(Usage::Other);
+        if self.kbps(Usage::Audio) > 0 {
+            used += self.audio_budget().unwrap_or_default();
+        }
+        if self.file_active() {
+            used += limit * FILE_MIN_PERCENT / 100;
+        }
+        Some(limit.saturating_sub(used).max(MIN_VIDEO_KBPS))
+    }
+
+    fn file_budget(&self) -> Option<u32> {
+        let limit = self.limit()?;
+        let used = self.kbps(Usage::Video) + self.kbps(Usage::Audio) + self.kbps(Usage::Other);
+        Some(
+            limit
+                .saturating_sub(used)
+                .max(limit * FILE_MIN_PERCENT / 100),
+        )
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref SESSIONS: Mutex<HashMap<i32, Session>> = Default::default();
+}
+
+fn host_limit() -> Option<u32> {
+    Config::get_option(keys::OPTION_BANDWIDTH_LIMIT)
+        .parse::<u32>()
+        .ok()
+        .filter(|v| *v > 0)
+}
+
+/// Set the limit of the controlling side in kbps, `None` removes it.
+pub fn set_client_limit(conn_id: i32, kbps: Option<u32>) {
+    log::info!("bandwidth limit of connection {conn_id}: {kbps:?} kbps");
+    SESSIONS
+        .lock()
+        .unwrap()
+        .entry(conn_id)
+        .or_insert_with(Session::new)
+        .client_limit = kbps;
+}
+
+/// Control the congestion of the connection, which is relayed.
+pub fn enable_congestion_control(conn_id: i32) {
+    SESSIONS
+        .lock()
+        .unwrap()
+        .entry(conn_id)
+        .or_insert_with(Session::new)
+        .congestion = Some(Bbr::new());
+}
+
+/// The round trip time of a delay test in milliseconds, with the bytes the peer has received.
+pub fn on_feedback(conn_id: i32, rtt: u32, received: u64) {
+    if let Some(congestion) = SESSIONS
+        .lock()
+        .unwrap()
+        .get_mut(&conn_id)
+        .and_then(|s| s.congestion.as_mut())
+    {
+        congestion.on_feedback(rtt, received);
+    }
+}
+
+/// Count the bytes sent to the connection.
+pub fn record(conn_id: i32, usage: Usage, bytes: usize) {
+    let mut sessions = SESSIONS.lock().unwrap();
+    let session = sessions.entry(conn_id).or_insert_with(Session::new);
+    session.roll();
+    session.bytes[usage as usize] += bytes;
+    if let Some(congestion) = session.congestion.as_mut() {
+        congestion.on_sent(bytes);
+    }
+}
+
+/// Whether the next file block can be sent in the budget of this window and the pacing.
+pub fn file_allowed(conn_id: i32) -> bool {
+    let mut sessions = SESSIONS.lock().unwrap();
+    let Some(session) = sessions.get_mut(&conn_id) else {
+        return true;
+    };
+    session.roll();
+    let in_budget = match session.file_budget() {
+        Some(kbps) => session.bytes[Usage::File as usize] * 8 < kbps as usize * 1000,
+        None => true,
+    };
+    in_budget && session.congestion.as_mut().map_or(true, |c| c.can_send())
+}
+
+/// The usage of the last window in kbps, reported to the controlling side.
+pub fn usage(conn_id: i32) -> Option<BandwidthUsage> {
+    let mut sessions = SESSIONS.lock().unwrap();
+    let session = sessions.get_mut(&conn_id)?;
+    session.roll();
+    Some(BandwidthUsage {
+        limit: session.limit().unwrap_or_default(),
+        video: session.kbps(Usage::Video),
+        audio: session.kbps(Usage::Audio),
+        file: session.kbps(Usage::File),
+        other: session.kbps(Usage::Other),
+        ..Default::default()
+    })
+}
+
+pub fn on_connection_close(conn_id: i32) {
+    SESSIONS.lock().unwrap().remove(&conn_id);
+}
+
+/// The audio bitrate in kbps of the smallest budget, `None` if no session is limited.
+pub fn audio_kbps() -> Option<u32> {
+    SESSIONS
+        .lock()
+        .unwrap()
+        .values()
+        .filter_map(|s| s.audio_budget())
+        .min()
+}
+
+/// The video bitrate in kbps of the smallest budget, `None` if no session is limited.
+pub fn video_kbps() -> Option<u32> {
+    SESSIONS
+        .lock()
+        .unwrap()
+        .values()
+        .filter_map(|s| s.video_budget())
+        .min()
+}
+
+/// Lower the quality to fit the video budget.
+pub fn limit_quality(quality: Quality, width: u32, height: u32) -> Quality {
+    let Some(kbps) = video_kbps() else {
+        return quality;
+    };
+    let base = base_bitrate(width, height).max(1);
+    // in steps of 5 percent, not to reset the encoder on every small change of the usage
+    let max_ratio = kbps * 100 / base;
+    let max_ratio = (max_ratio - max_ratio % 5).max(1);
+    // the ratios of the presets differ a little between the encoders, close enough here
+    let ratio = match quality {
+        Quality::Best => 150,
+        Quality::Balanced => 100 * 2 / 3,
+        Quality::Low => 50,
+        Quality::Custom(b) => b,
+    };
+    if ratio > max_ratio {
+        Quality::Custom(max_ratio)
+    } else {
+        quality
+    }
+}
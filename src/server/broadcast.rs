@@ -0,0 +1,155 @@
+// The broadcast of this side, a presentation to many view-only peers at a time. The frames are
+// encoded once for all the sessions as always, the viewers only do not hold the encoder up: the
+// video service does not wait for them to fetch a frame, and their fps, quality and delay are left
+// out of the qos, so a slow viewer does not slow down the others. A viewer lagging behind skips the
+// frames up to the next key frame instead, see Connection.
+//
+// A peer joins as a viewer by connecting view-only, up to the most viewers of the options, and all
+// its permissions but the audio are taken away. The count of the viewers is sent to all the remote
+// sessions of this side whenever it changes.
+
+use super::*;
+use hbb_common::config::keys;
+
+pub const DEFAULT_MAX_VIEWERS: usize = 20;
+/// The longest a frame waits for a viewer before the viewer lags behind.
+pub const MAX_LAG: Duration = Duration::from_secs(2);
+
+lazy_static::lazy_static! {
+    // the remote sessions joined while it is on, and whether they are viewers
+    static ref SESSIONS: Mutex<Vec<(ConnInner, bool)>> = Default::default();
+}
+
+pub fn enabled() -> bool {
+    Connection::permission(keys::OPTION_ALLOW_BROADCAST)
+}
+
+pub fn max_viewers() -> usize {
+    Config::get_option(keys::OPTION_BROADCAST_MAX_VIEWERS)
+        .parse()
+        .ok()
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_MAX_VIEWERS)
+}
+
+/// Whether another viewer is refused.
+pub fn full() -> bool {
+    viewers(&SESSIONS.lock().unwrap()) >= max_viewers()
+}
+
+pub fn is_viewer(conn_id: i32) -> bool {
+    SESSIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|(s, viewer)| *viewer && s.id() == conn_id)
+}
+
+/// Joins unless it is a viewer and the viewers are full, checked and joined under one lock so that
+/// the viewers logging in at a time can not exceed the most.
+pub fn try_join(inner: ConnInner, viewer: bool) -> bool {
+    let mut sessions = SESSIONS.lock().unwrap();
+    let len = sessions.len();
+    // the slot held before is released on failure too
+    sessions.retain(|(s, _)| s.id() != inner.id());
+    let joined = !viewer || viewers(&sessions) < max_viewers();
+    if joined {
+        sessions.push((inner, viewer));
+    }
+    if joined || sessions.len() != len {
+        notify(&mut sessions);
+    }
+    joined
+}
+
+pub fn on_connection_close(conn_id: i32) {
+    let mut sessions = SESSIONS.lock().unwrap();
+    let len = sessions.len();
+    sessions.retain(|(s, _)| s.id() != conn_id);
+    if sessions.len() != len {
+        notify(&mut sessions);
+    }
+}
+
+fn viewers(sessions: &[(ConnInner, bool)]) -> usize {
+    sessions.iter().filter(|(_, viewer)| *viewer).count()
+}
+
+fn notify(sessions: &mut [(ConnInner, bool)]) {
+    let mut misc = Misc::new();
+    misc.set_broadcast_viewers(BroadcastViewers {
+        count: viewers(sessions) as _,
+        max: max_viewers() as _,
+        ..Default::default()
+    });
+    let mut msg_out = Message::new();
+    msg_out.set_misc(misc);
+    let msg_out = Arc::new(msg_out);
+    for (s, _) in sessions.iter_mut() {
+        s.send(msg_out.clone());
+    }
+}
+
+/// Whether the frames after it can be decoded without the ones before.
+pub fn is_key_frame(msg: &Message) -> bool {
+    let Some(message::Union::VideoFrame(vf)) = &msg.union else {
+        return false;
+    };
+    match &vf.union {
+        Some(video_frame::Union::Vp8s(f))
+        | Some(video_frame::Union::Vp9s(f))
+        | Some(video_frame::Union::Av1s(f))
+        | Some(video_frame::Union::H264s(f))
+        | Some(video_frame::Union::H265s(f)) => f.frames.first().map(|f| f.key).unwrap_or(false),
+        // the raw ones are whole
+        Some(_) => true,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(key: bool) -> Message {
+        let mut msg = Message::new();
+        msg.set_video_frame(VideoFrame {
+            union: Some(video_frame::Union::Vp9s(EncodedVideoFrames {
+                frames: vec![EncodedVideoFrame {
+                    key,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            })),
+            ..Default::default()
+        });
+        msg
+    }
+
+    #[test]
+    fn test_try_join() {
+        let max = max_viewers();
+        let handles: Vec<_> = (0..max as i32 * 2)
+            .map(|id| std::thread::spawn(move || try_join(ConnInner::new(id, None, None), true)))
+            .collect();
+        let joined = handles
+            .into_iter()
+            .filter_map(|h| h.join().ok())
+            .filter(|joined| *joined)
+            .count();
+        assert_eq!(joined, max);
+        assert!(full());
+        assert!(try_join(ConnInner::new(-1, None, None), false));
+        for id in -1..max as i32 * 2 {
+            on_connection_close(id);
+        }
+        assert!(!full());
+    }
+
+    #[test]
+    fn test_is_key_frame() {
+        assert!(is_key_frame(&frame(true)));
+        assert!(!is_key_frame(&frame(false)));
+        assert!(!is_key_frame(&Message::new()));
+    }
+}
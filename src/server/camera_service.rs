@@ -0,0 +1,222 @@
+// The camera of this side streamed to the peers watching it, apart from the displays, to check the
+// room of a kiosk or of a machine of a lab. It has a codec, a rate of frames and a quality of its
+// own, the ones of the connection which requested it last, as the camera is opened once for all.
+//
+// The frames are captured in rgb, with the highest rate of frames the camera offers, converted to
+// yuv and encoded with vpx, which is always there. A peer subscribing gets a key frame, the encoder
+// is made again for it.
+
+use super::*;
+use nokhwa::{
+    pixel_format::RgbFormat,
+    utils::{ApiBackend, CameraIndex, RequestedFormat, RequestedFormatType},
+    Camera,
+};
+use scrap::{
+    codec::{EncoderApi, EncoderCfg, Quality},
+    convert_to_yuv,
+    vpxcodec::{VpxEncoder, VpxEncoderConfig, VpxVideoCodecId},
+    EncodeInput, Pixfmt, TraitPixelBuffer,
+};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Instant,
+};
+
+pub const NAME: &'static str = "camera";
+static RESTARTING: AtomicBool = AtomicBool::new(false);
+const DEFAULT_FPS: u32 = 10;
+const MAX_FPS: u32 = 30;
+
+lazy_static::lazy_static! {
+    // the connection which requested it last, and the request
+    static ref REQUEST: Mutex<Option<(i32, CameraRequest)>> = Default::default();
+}
+
+pub fn new() -> GenericService {
+    let svc = EmptyExtraFieldService::new(NAME.to_owned(), true);
+    GenericService::run(&svc.clone(), run);
+    svc.sp
+}
+
+pub fn list() -> ResultType<Vec<CameraInfo>> {
+    Ok(nokhwa::query(ApiBackend::Auto)?
+        .iter()
+        .filter_map(|c| {
+            Some(CameraInfo {
+                index: c.index().as_index().ok()? as _,
+                name: c.human_name(),
+                ..Default::default()
+            })
+        })
+        .collect())
+}
+
+/// Sets the camera and the settings of the stream, the camera is opened again if they changed.
+pub fn set_request(conn_id: i32, req: CameraRequest) {
+    let mut lock = REQUEST.lock().unwrap();
+    let changed = lock.as_ref().map(|r| &r.1) != Some(&req);
+    *lock = Some((conn_id, req));
+    drop(lock);
+    if changed {
+        restart();
+    }
+}
+
+pub fn on_connection_close(conn_id: i32) {
+    let mut lock = REQUEST.lock().unwrap();
+    if lock.as_ref().map(|r| r.0) == Some(conn_id) {
+        *lock = None;
+    }
+}
+
+fn restart() {
+    log::info!("restart the camera service");
+    RESTARTING.store(true, Ordering::SeqCst);
+}
+
+struct Rgba {
+    data: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+impl TraitPixelBuffer for Rgba {
+    fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn stride(&self) -> Vec<usize> {
+        vec![self.width * 4]
+    }
+
+    fn pixfmt(&self) -> Pixfmt {
+        Pixfmt::RGBA
+    }
+}
+
+fn error_frame(index: i32, err: &str) -> Message {
+    let mut msg_out = Message::new();
+    msg_out.set_camera_frame(CameraFrame {
+        index,
+        error: err.to_owned(),
+        ..Default::default()
+    });
+    msg_out
+}
+
+fn new_encoder(req: &CameraRequest, width: usize, height: usize) -> ResultType<VpxEncoder> {
+    VpxEncoder::new(
+        EncoderCfg::VPX(VpxEncoderConfig {
+            width: width as _,
+            height: height as _,
+            quality: if req.quality > 0 {
+                Quality::Custom(req.quality)
+            } else {
+                Quality::Balanced
+            },
+            codec: if req.vp9 {
+                VpxVideoCodecId::VP9
+            } else {
+                VpxVideoCodecId::VP8
+            },
+            keyframe_interval: None,
+        }),
+        false,
+    )
+}
+
+fn run(sp: EmptyExtraFieldService) -> ResultType<()> {
+    RESTARTING.store(false, Ordering::SeqCst);
+    let req = REQUEST
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|r| r.1.clone())
+        .unwrap_or_default();
+    let index = req.index;
+    let mut camera = match Camera::new(
+        CameraIndex::Index(index as _),
+        RequestedFormat::new::<RgbFormat>(RequestedFormatType::AbsoluteHighestFrameRate),
+    )
+    .and_then(|mut c| c.open_stream().map(|_| c))
+    {
+        Ok(camera) => camera,
+        Err(err) => {
+            sp.send(error_frame(index, &err.to_string()));
+            bail!("Failed to open the camera {}: {}", index, err);
+        }
+    };
+    let resolution = camera.resolution();
+    // the chroma of yuv420 is half the size
+    let (width, height) = (
+        resolution.width() as usize & !1,
+        resolution.height() as usize & !1,
+    );
+    log::info!("camera {} opened, {}x{}", index, width, height);
+    let mut encoder = new_encoder(&req, width, height)?;
+    let fps = if req.fps > 0 {
+        req.fps.min(MAX_FPS)
+    } else {
+        DEFAULT_FPS
+    };
+    let spf = Duration::from_secs_f32(1. / fps as f32);
+    let start = Instant::now();
+    let (mut yuv, mut mid_data) = (vec![], vec![]);
+    while sp.ok() && !RESTARTING.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        sp.snapshot(|_| {
+            encoder = new_encoder(&req, width, height)?;
+            Ok(())
+        })?;
+        let rgb = match camera.frame().and_then(|f| f.decode_image::<RgbFormat>()) {
+            Ok(rgb) => rgb,
+            Err(err) => {
+                sp.send(error_frame(index, &err.to_string()));
+                bail!("Failed to capture the camera {}: {}", index, err);
+            }
+        };
+        let (w, h) = (rgb.width() as usize, rgb.height() as usize);
+        if w < width || h < height {
+            // the format changed, the camera is opened again
+            bail!("The size of the frames of the camera {} changed", index);
+        }
+        let mut data = Vec::with_capacity(width * height * 4);
+        for row in rgb.as_raw().chunks_exact(w * 3).take(height) {
+            for px in row.chunks_exact(3).take(width) {
+                data.extend_from_slice(&[px[0], px[1], px[2], 255]);
+            }
+        }
+        let frame = Rgba {
+            data,
+            width,
+            height,
+        };
+        convert_to_yuv(&frame, encoder.yuvfmt(), &mut yuv, &mut mid_data)?;
+        let ms = start.elapsed().as_millis() as i64;
+        // the encoder may keep the frame for a while
+        if let Ok(frame) = encoder.encode_to_message(EncodeInput::YUV(&yuv), ms) {
+            let mut msg_out = Message::new();
+            msg_out.set_camera_frame(CameraFrame {
+                index,
+                frame: Some(frame).into(),
+                ..Default::default()
+            });
+            sp.send(msg_out);
+        }
+        let elapsed = now.elapsed();
+        if elapsed < spf {
+            std::thread::sleep(spf - elapsed);
+        }
+    }
+    allow_err!(camera.stop_stream());
+    Ok(())
+}
@@ -0,0 +1,263 @@
+// BBR-like congestion control of the relayed sessions.
+//
+// The relay server buffers generously, so a file transfer through it fills a queue which delays
+// the video and the input by seconds. The controlling side reports the bytes it has received with
+// each answer to the delay test, from which the delivery rate of the path and the round trip time
+// without queue are estimated, like BBR does from the acks. The file blocks are paced at the
+// estimated bandwidth and held while more than `CWND_GAIN` bandwidth-delay products are in flight,
+// and the bandwidth becomes the limit of the session while a queue builds up, so that the video
+// bitrate is lowered to fit by the bandwidth budget.
+//
+// The feedback comes once per delay test, so a round here is about a second rather than a round
+// trip, and the gains are cycled per round.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+// rounds of the max filter of the bandwidth
+const BW_WINDOW: u64 = 10;
+const MIN_RTT_WINDOW: Duration = Duration::from_secs(10);
+// in percent
+const STARTUP_GAIN: u32 = 289;
+const DRAIN_GAIN: u32 = 35;
+const PROBE_RTT_GAIN: u32 = 50;
+const PROBE_BW_GAINS: [u32; 8] = [125, 75, 100, 100, 100, 100, 100, 100];
+const FULL_BW_GROWTH: u32 = 125;
+const FULL_BW_ROUNDS: u32 = 3;
+const CWND_GAIN: u64 = 2;
+const MIN_CWND: u64 = 64 * 1024;
+// the least extra round trip time taken as a queue, in milliseconds
+const MIN_QUEUE_DELAY: u32 = 20;
+const MAX_BURST: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Startup,
+    Drain,
+    ProbeBw(usize),
+    ProbeRtt,
+}
+
+#[derive(Debug)]
+pub struct Bbr {
+    state: State,
+    round: u64,
+    // bytes
+    sent: u64,
+    received: u64,
+    last_feedback: Option<Instant>,
+    // the delivery rates in kbps with their rounds
+    rates: VecDeque<(u64, u32)>,
+    // in milliseconds
+    min_rtt: Option<(u32, Instant)>,
+    rtt: u32,
+    full_bw: u32,
+    full_bw_rounds: u32,
+    // a file block was held back in this round, the rate was not limited by the sender
+    held: bool,
+    // bytes which may be sent at once, negative after a block larger than the burst
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Bbr {
+    pub fn new() -> Self {
+        Self {
+            state: State::Startup,
+            round: 0,
+            sent: 0,
+            received: 0,
+            last_feedback: None,
+            rates: VecDeque::new(),
+            min_rtt: None,
+            rtt: 0,
+            full_bw: 0,
+            full_bw_rounds: 0,
+            held: false,
+            tokens: 0.,
+            last_refill: Instant::now(),
+        }
+    }
+
+    pub fn on_sent(&mut self, bytes: usize) {
+        self.sent += bytes as u64;
+        self.tokens -= bytes as f64;
+    }
+
+    /// The answer to the delay test, `received` is 0 for the peers which do not report it.
+    pub fn on_feedback(&mut self, rtt: u32, received: u64) {
+        if received == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let app_limited = !self.held && self.inflight() < self.bdp();
+        if let Some(last) = self.last_feedback {
+            let millis = now.duration_since(last).as_millis().max(1) as u64;
+            // bits per millisecond is kbps
+            let rate = (received.saturating_sub(self.received) * 8 / millis) as u32;
+            self.rates
+                .retain(|(round, _)| round + BW_WINDOW > self.round);
+            if !app_limited || rate >= self.btl_bw() || self.rates.is_empty() {
+                self.rates.push_back((self.round, rate));
+            }
+        }
+        self.received = received;
+        self.last_feedback = Some(now);
+        self.rtt = rtt;
+        self.round += 1;
+        self.held = false;
+
+        match self.min_rtt {
+            _ if self.state == State::ProbeRtt => {
+                // the queue is drained, the new minimum is taken whatever it is
+                self.min_rtt = Some((rtt, now));
+                self.state = State::ProbeBw(0);
+                return;
+            }
+            Some((min, at)) if rtt > min && at.elapsed() < MIN_RTT_WINDOW => {}
+            Some(_) if rtt > self.min_rtt_or(rtt) && self.state != State::Startup => {
+                // not refreshed for long, drain the queue to measure again
+                self.state = State::ProbeRtt;
+                return;
+            }
+            _ => self.min_rtt = Some((rtt, now)),
+        }
+
+        self.state = match self.state {
+            State::Startup => {
+                let bw = self.btl_bw();
+                if bw >= self.full_bw.saturating_mul(FULL_BW_GROWTH) / 100 {
+                    self.full_bw = bw;
+                    self.full_bw_rounds = 0;
+                } else if !app_limited {
+                    self.full_bw_rounds += 1;
+                }
+                if self.full_bw_rounds >= FULL_BW_ROUNDS {
+                    State::Drain
+                } else {
+                    State::Startup
+                }
+            }
+            State::Drain if self.inflight() > self.bdp() => State::Drain,
+            State::Drain => State::ProbeBw(0),
+            State::ProbeBw(i) => State::ProbeBw((i + 1) % PROBE_BW_GAINS.len()),
+            State::ProbeRtt => State::ProbeBw(0),
+        };
+    }
+
+    fn min_rtt_or(&self, rtt: u32) -> u32 {
+        self.min_rtt.map(|(min, _)| min).unwrap_or(rtt)
+    }
+
+    // kbps
+    fn btl_bw(&self) -> u32 {
+        self.rates.iter().map(|(_, rate)| *rate).max().unwrap_or(0)
+    }
+
+    // bytes
+    fn bdp(&self) -> u64 {
+        // kbps by milliseconds is bits
+        self.btl_bw() as u64 * self.min_rtt_or(0) as u64 / 8
+    }
+
+    fn inflight(&self) -> u64 {
+        self.sent.saturating_sub(self.received)
+    }
+
+    fn gain(&self) -> u32 {
+        match self.state {
+            State::Startup => STARTUP_GAIN,
+            State::Drain => DRAIN_GAIN,
+            State::ProbeBw(i) => PROBE_BW_GAINS[i],
+            State::ProbeRtt => PROBE_RTT_GAIN,
+        }
+    }
+
+    fn pacing_kbps(&self) -> Option<u32> {
+        if self.rates.is_empty() {
+            return None;
+        }
+        Some((self.btl_bw() as u64 * self.gain() as u64 / 100) as u32)
+    }
+
+    fn cwnd(&self) -> u64 {
+        if self.state == State::ProbeRtt {
+            return MIN_CWND;
+        }
+        (CWND_GAIN * self.bdp()).max(MIN_CWND)
+    }
+
+    /// Whether the next file block can be sent now, within the window and the pacing.
+    pub fn can_send(&mut self) -> bool {
+        let Some(kbps) = self.pacing_kbps() else {
+            return true;
+        };
+        // kbps is 125 bytes per second
+        let bytes_per_sec = kbps as f64 * 125.;
+        let elapsed = self.last_refill.elapsed().as_secs_f64();
+        self.last_refill = Instant::now();
+        self.tokens =
+            (self.tokens + elapsed * bytes_per_sec).min(bytes_per_sec * MAX_BURST.as_secs_f64());
+        let ok = self.inflight() < self.cwnd() && self.tokens >= 0.;
+        if !ok {
+            self.held = true;
+        }
+        ok
+    }
+
+    /// The bandwidth of the session in kbps while a queue builds up on the path, `None` while the
+    /// path is not known or not queuing.
+    pub fn limit_kbps(&self) -> Option<u32> {
+        let (min_rtt, _) = self.min_rtt?;
+        let queuing = self.rtt > min_rtt + (min_rtt / 4).max(MIN_QUEUE_DELAY);
+        match self.state {
+            State::Startup => None,
+            State::ProbeRtt | State::Drain => self.pacing_kbps(),
+            State::ProbeBw(_) if queuing => self.pacing_kbps(),
+            State::ProbeBw(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a round on a path of `kbps`, the sender filling it
+    fn round(bbr: &mut Bbr, kbps: u64, rtt: u32, millis: u64) {
+        bbr.last_feedback = bbr.last_feedback.map(|t| t - Duration::from_millis(millis));
+        let bytes = kbps * millis / 8;
+        bbr.sent += bytes;
+        bbr.held = true;
+        bbr.on_feedback(rtt, bbr.received + bytes);
+    }
+
+    #[test]
+    fn test_bbr() {
+        let mut bbr = Bbr::new();
+        assert!(bbr.can_send());
+        bbr.on_feedback(50, 0);
+        assert_eq!(bbr.last_feedback, None);
+        bbr.on_feedback(50, 1);
+        for _ in 0..10 {
+            round(&mut bbr, 8_000, 50, 1_000);
+        }
+        assert_eq!(bbr.btl_bw(), 8_000);
+        assert_eq!(bbr.min_rtt_or(0), 50);
+        assert!(matches!(bbr.state, State::ProbeBw(_)));
+        // 2 bdp of 50 kB
+        assert_eq!(bbr.cwnd(), 100_000);
+
+        bbr.rtt = 400;
+        bbr.state = State::ProbeBw(2);
+        assert_eq!(bbr.limit_kbps(), Some(8_000));
+        bbr.rtt = 60;
+        assert_eq!(bbr.limit_kbps(), None);
+
+        bbr.sent = bbr.received + 200_000;
+        assert!(!bbr.can_send());
+        assert!(bbr.held);
+    }
+}
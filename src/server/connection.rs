@@ -1,4 +1,12 @@
-use super::{input_service::*, *};
+#[cfg(feature = "flutter")]
+use super::handoff;
+use super::{
+    audit_log::{self, Kind as AuditKind},
+    input_control,
+    input_service::*,
+    notify::{self, Event as NotifyEvent, Notification},
+    presenter, *,
+};
 #[cfg(not(any(target_os = "android", target_os = "ios")))]
 use crate::clipboard::{update_clipboard, ClipboardSide};
 #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
@@ -15,9 +23,12 @@ use crate::platform::WallPaperRemover;
 use crate::portable_service::client as portable_client;
 use crate::{
     client::{
-        new_voice_call_request, new_voice_call_response, start_audio_thread, MediaData, MediaSender,
+        new_voice_call_request, new_voice_call_response, start_audio_thread_with_outputs,
+        MediaData, MediaSender,
     },
-    display_service, ipc, privacy_mode, video_service, VERSION,
+    display_service,
+    hbbs_http::policy::{self, RolePolicy},
+    ipc, privacy_mode, video_service, VERSION,
 };
 #[cfg(any(target_os = "android", target_os = "ios"))]
 use crate::{common::DEVICE_NAME, flutter::connection_manager::start_channel};
@@ -27,12 +38,18 @@ use hbb_common::platform::linux::run_cmds;
 #[cfg(target_os = "android")]
 use hbb_common::protobuf::EnumOrUnknown;
 use hbb_common::{
-    config::{self, keys, Config, TrustedDevice},
-    fs::{self, can_enable_overwrite_detection},
+    config::{self, keys, Config, OneTimePassword, PermissionProfile, TrustedDevice, TrustedKey},
+    dlp,
+    fs::{
+        self,
+        audit::{Direction, Outcome, TransferEvent},
+        can_enable_overwrite_detection,
+    },
     futures::{SinkExt, StreamExt},
     get_time, get_version_number,
     message_proto::{option_message::BoolOption, permission_info::Permission},
     password_security::{self as password, ApproveMode},
+    quic::Channel,
     sleep, timeout,
     tokio::{
         net::TcpStream,
@@ -130,6 +147,9 @@ enum MessageInput {
     Key((KeyEvent, bool)),
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     Pointer((PointerDeviceEvent, i32)),
+    // the commit string of the input method
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    Text(String),
     BlockOn,
     BlockOff,
     #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
@@ -140,6 +160,61 @@ enum MessageInput {
     BlockOffPlugin(String),
 }
 
+// Blocks the local input while the peer is controlling, until the peer is idle for the timeout.
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+struct AutoBlockInput {
+    timeout: Option<Duration>,
+    // the last input of the peer while the local input is blocked
+    last_input: Option<Instant>,
+}
+
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+impl AutoBlockInput {
+    fn new() -> Self {
+        let timeout = (Config::get_option(keys::OPTION_ALLOW_AUTO_BLOCK_INPUT) == "Y").then(|| {
+            let seconds: u64 = Config::get_option(keys::OPTION_AUTO_BLOCK_INPUT_TIMEOUT)
+                .parse()
+                .unwrap_or(0);
+            Duration::from_secs(if seconds == 0 { 3 } else { seconds })
+        });
+        Self {
+            timeout,
+            last_input: None,
+        }
+    }
+
+    // `block_input_mode` is the block requested by the peer, which is kept.
+    fn on_input(&mut self, block_input_mode: bool) {
+        if self.timeout.is_none() {
+            return;
+        }
+        if self.last_input.is_none() && !block_input_mode {
+            let (ok, msg) = crate::platform::block_input(true);
+            if !ok {
+                log::error!("failed to block the input automatically: {msg}");
+                self.timeout = None;
+                return;
+            }
+        }
+        self.last_input = Some(Instant::now());
+    }
+
+    fn on_idle(&mut self, block_input_mode: bool) {
+        let (Some(last_input), Some(timeout)) = (self.last_input, self.timeout) else {
+            return;
+        };
+        if last_input.elapsed() < timeout {
+            // unblocked by Ctrl+Alt+Del
+            let _ = crate::platform::block_input(true);
+            return;
+        }
+        if !block_input_mode {
+            let _ = crate::platform::block_input(false);
+        }
+        self.last_input = None;
+    }
+}
+
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
 pub struct SessionKey {
     peer_id: String,
@@ -152,6 +227,7 @@ struct Session {
     last_recv_time: Arc<Mutex<Instant>>,
     random_password: String,
     tfa: bool,
+    fido2: bool,
 }
 
 #[cfg(not(any(target_os = "android", target_os = "ios")))]
@@ -167,12 +243,20 @@ pub enum AuthConnType {
     Remote,
     FileTransfer,
     PortForward,
+    Terminal,
 }
 
 pub struct Connection {
     inner: ConnInner,
     display_idx: usize,
     stream: super::Stream,
+    // the relayed path the session moves to if the direct one fails
+    backup: crate::backup_path::Backup,
+    // the relay server of the path in use, empty if none
+    relay_server: String,
+    // the better relay server the peer is asked to move the session to, and when
+    migrate_to: Option<(String, Instant)>,
+    relay_checked: Instant,
     server: super::ServerPtrWeak,
     hash: Hash,
     read_jobs: Vec<fs::TransferJob>,
@@ -181,9 +265,18 @@ pub struct Connection {
     file_transfer: Option<(String, bool)>,
     port_forward_socket: Option<Framed<TcpStream, BytesCodec>>,
     port_forward_address: String,
+    // the session is of the terminals only, without any video
+    terminal_session: bool,
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    terminals: super::terminal::Terminals,
     tx_to_cm: mpsc::UnboundedSender<ipc::Data>,
+    // the jobs of the virtual printer are handed in as the data of the cm are, see print_jobs
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    tx_print_jobs: mpsc::UnboundedSender<ipc::Data>,
     authorized: bool,
     require_2fa: Option<totp_rs::TOTP>,
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    require_fido2: Option<crate::fido2::Challenge>,
     keyboard: bool,
     clipboard: bool,
     audio: bool,
@@ -191,8 +284,15 @@ pub struct Connection {
     restart: bool,
     recording: bool,
     block_input: bool,
+    // the parts of keyboard
+    key_input: bool,
+    mouse_input: bool,
+    clipboard_typing: bool,
+    terminal: bool,
     last_test_delay: Option<Instant>,
     network_delay: u32,
+    // the video frames sent since the last test delay, and when it was sent
+    video_frames: (u32, Instant),
     lock_after_session_end: bool,
     show_remote_cursor: bool,
     // by peer
@@ -212,6 +312,10 @@ pub struct Connection {
     tx_input: std_mpsc::Sender<MessageInput>,
     // handle input messages
     video_ack_required: bool,
+    // a viewer of the broadcast of this side, see broadcast
+    broadcast_viewer: bool,
+    // behind the broadcast, and whether a key frame is asked for already
+    broadcast_lagging: Option<bool>,
     server_audit_conn: String,
     server_audit_file: String,
     lr: LoginRequest,
@@ -220,11 +324,27 @@ pub struct Connection {
     file_transferred: bool,
     #[cfg(windows)]
     portable: PortableState,
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    last_secure_input: bool,
+    last_record_incoming: bool,
+    last_input_control: Option<InputControl>,
     from_switch: bool,
+    // logged in with the ticket of a session handed off by another device of the peer
+    handed_off: bool,
+    // sampling the load of this side while the peer watches it
+    system_stats: Option<super::system_stats::Sampler>,
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    task_manager: Option<super::task_manager::Lister>,
     voice_call_request_timestamp: Option<NonZeroI64>,
+    voice_call: bool,
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    virtual_mic: Option<virtual_mic::VirtualMic>,
     options_in_login: Option<OptionMessage>,
     #[cfg(not(any(target_os = "ios")))]
     pressed_modifiers: HashSet<rdev::Key>,
+    // the peer is composing with its input method
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    ime_composing: bool,
     #[cfg(target_os = "linux")]
     linux_headless_handle: LinuxHeadlessHandle,
     closed: bool,
@@ -232,6 +352,21 @@ pub struct Connection {
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     start_cm_ipc_para: Option<StartCmIpcPara>,
     auto_disconnect_timer: Option<(Instant, u64)>,
+    inactivity_warned: bool,
+    // why the peer connects, asked before the accept dialog if required
+    access_reason: Option<String>,
+    access_reason_asked: bool,
+    // who the peer is at the identity provider required
+    identity: Option<String>,
+    // the role of the peer in the policy of the management server
+    policy: Option<RolePolicy>,
+    // the one-time password the connection is authorized by
+    one_time_password: Option<OneTimePassword>,
+    // the key of the device of the peer, trusted once accepted if not yet
+    device_key: Option<TrustedKey>,
+    device_trusted: bool,
+    // the connection is denied if not accepted before
+    approve_deadline: Option<Instant>,
     authed_conn_id: Option<self::raii::AuthedConnID>,
     file_remove_log_control: FileRemoveLogControl,
     last_supported_encoding: Option<SupportedEncoding>,
@@ -258,9 +393,9 @@ impl Subscriber for ConnInner {
 
     #[inline]
     fn send(&mut self, msg: Arc<Message>) {
-        // Send SwitchDisplay on the same channel as VideoFrame to avoid send order problems.
+        // Send SwitchDisplay and LosslessPatches on the same channel as VideoFrame to avoid send order problems.
         let tx_by_video = match &msg.union {
-            Some(message::Union::VideoFrame(_)) => true,
+            Some(message::Union::VideoFrame(_)) | Some(message::Union::LosslessPatches(_)) => true,
             Some(message::Union::Misc(misc)) => match &misc.union {
                 Some(misc::Union::SwitchDisplay(_)) => true,
                 _ => false,
@@ -285,6 +420,31 @@ const MILLI1: Duration = Duration::from_millis(1);
 const SEND_TIMEOUT_VIDEO: u64 = 12_000;
 const SEND_TIMEOUT_OTHER: u64 = SEND_TIMEOUT_VIDEO * 10;
 const SESSION_TIMEOUT: Duration = Duration::from_secs(30);
+// the delay of the video the file transfers sent yield to
+const CONGESTED_DELAY_MS: u32 = 500;
+const CONGESTED_RESPONSE: Duration = Duration::from_secs(2);
+// how often a relayed session looks for a better relay server
+const RELAY_CHECK_INTERVAL: Duration = Duration::from_secs(120);
+// in chars
+const MAX_ACCESS_REASON_LEN: usize = 256;
+// the peer is warned before an inactive session is closed or downgraded to view only
+const INACTIVITY_WARNING_SECS: u64 = 60;
+// the macs a peer wakes at once through this side
+const MAX_WOL_MACS: usize = 16;
+// the permissions the cm switches, saved in the permission profiles
+const PERMISSION_NAMES: [&str; 11] = [
+    "keyboard",
+    "clipboard",
+    "audio",
+    "file",
+    "restart",
+    "recording",
+    "block_input",
+    "key_input",
+    "mouse_input",
+    "clipboard_typing",
+    "terminal",
+];
 
 impl Connection {
     pub async fn start(
@@ -292,11 +452,17 @@ impl Connection {
         stream: super::Stream,
         id: i32,
         server: super::ServerPtrWeak,
+        relay: bool,
+        relay_server: String,
     ) {
         let _raii_id = raii::ConnectionID::new(id);
+        if relay {
+            bandwidth::enable_congestion_control(id);
+        }
         let hash = Hash {
             salt: Config::get_salt(),
             challenge: Config::get_auto_password(6),
+            identity_provider: crate::idp::required_provider(),
             ..Default::default()
         };
         let (tx_from_cm_holder, mut rx_from_cm) = mpsc::unbounded_channel::<ipc::Data>();
@@ -324,8 +490,14 @@ impl Connection {
                 tx_video: Some(tx_video),
             },
             require_2fa: crate::auth_2fa::get_2fa(None),
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            require_fido2: crate::fido2::Challenge::new(),
             display_idx: *display_service::PRIMARY_DISPLAY_IDX,
             stream,
+            backup: Default::default(),
+            relay_server,
+            migrate_to: None,
+            relay_checked: Instant::now(),
             server,
             hash,
             read_jobs: Vec::new(),
@@ -334,7 +506,12 @@ impl Connection {
             file_transfer: None,
             port_forward_socket: None,
             port_forward_address: "".to_owned(),
+            terminal_session: false,
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            terminals: Default::default(),
             tx_to_cm,
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            tx_print_jobs: tx_from_cm_holder.clone(),
             authorized: false,
             keyboard: Connection::permission("enable-keyboard"),
             clipboard: Connection::permission("enable-clipboard"),
@@ -344,8 +521,13 @@ impl Connection {
             restart: Connection::permission("enable-remote-restart"),
             recording: Connection::permission("enable-record-session"),
             block_input: Connection::permission("enable-block-input"),
+            key_input: Connection::permission(keys::OPTION_ENABLE_KEY_INPUT),
+            mouse_input: Connection::permission(keys::OPTION_ENABLE_MOUSE_INPUT),
+            clipboard_typing: Connection::permission(keys::OPTION_ENABLE_CLIPBOARD_TYPING),
+            terminal: Connection::permission(keys::OPTION_ALLOW_TERMINAL),
             last_test_delay: None,
             network_delay: 0,
+            video_frames: (0, Instant::now()),
             lock_after_session_end: false,
             show_remote_cursor: false,
             follow_remote_cursor: false,
@@ -359,6 +541,8 @@ impl Connection {
             disable_keyboard: false,
             tx_input,
             video_ack_required: false,
+            broadcast_viewer: false,
+            broadcast_lagging: None,
             server_audit_conn: "".to_owned(),
             server_audit_file: "".to_owned(),
             lr: Default::default(),
@@ -367,12 +551,25 @@ impl Connection {
             file_transferred: false,
             #[cfg(windows)]
             portable: Default::default(),
+            #[cfg(any(target_os = "windows", target_os = "macos"))]
+            last_secure_input: false,
+            last_record_incoming: false,
+            last_input_control: None,
             from_switch: false,
+            handed_off: false,
+            system_stats: None,
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            task_manager: None,
             audio_sender: None,
             voice_call_request_timestamp: None,
+            voice_call: false,
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            virtual_mic: None,
             options_in_login: None,
             #[cfg(not(any(target_os = "ios")))]
             pressed_modifiers: Default::default(),
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            ime_composing: false,
             #[cfg(target_os = "linux")]
             linux_headless_handle,
             closed: false,
@@ -385,6 +582,15 @@ impl Connection {
                 tx_cm_stream_ready,
             }),
             auto_disconnect_timer: None,
+            inactivity_warned: false,
+            access_reason: None,
+            access_reason_asked: false,
+            identity: None,
+            policy: None,
+            one_time_password: None,
+            device_key: None,
+            device_trusted: false,
+            approve_deadline: None,
             authed_conn_id: None,
             file_remove_log_control: FileRemoveLogControl::new(id),
             last_supported_encoding: None,
@@ -427,9 +633,23 @@ impl Connection {
         if !conn.block_input {
             conn.send_permission(Permission::BlockInput, false).await;
         }
+        if !conn.key_input {
+            conn.send_permission(Permission::KeyInput, false).await;
+        }
+        if !conn.mouse_input {
+            conn.send_permission(Permission::MouseInput, false).await;
+        }
+        if !conn.clipboard_typing {
+            conn.send_permission(Permission::ClipboardTyping, false)
+                .await;
+        }
+        if !conn.terminal {
+            conn.send_permission(Permission::Terminal, false).await;
+        }
         let mut test_delay_timer =
             crate::rustdesk_interval(time::interval_at(Instant::now(), TEST_DELAY_TIMEOUT));
         let mut last_recv_time = Instant::now();
+        let mut heartbeat = crate::heartbeat::Heartbeat::new();
 
         conn.stream.set_send_timeout(
             if conn.file_transfer.is_some() || conn.port_forward_socket.is_some() {
@@ -450,13 +670,22 @@ impl Connection {
                 Some(data) = rx_from_cm.recv() => {
                     match data {
                         ipc::Data::Authorize => {
+                            if !conn.authorized {
+                                conn.post_approval_audit("accepted");
+                            }
                             conn.require_2fa.take();
+                            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                            conn.require_fido2.take();
+                            conn.trust_device_key();
                             conn.send_logon_response().await;
                             if conn.port_forward_socket.is_some() {
                                 break;
                             }
                         }
                         ipc::Data::Close => {
+                            if !conn.authorized {
+                                conn.post_approval_audit("denied");
+                            }
                             conn.chat_unanswered = false; // seen
                             conn.file_transferred = false; //seen
                             conn.send_close_reason_no_retry("").await;
@@ -475,10 +704,11 @@ impl Connection {
                                 break;
                             }
                         }
-                        ipc::Data::ChatMessage{text} => {
+                        ipc::Data::ChatMessage{text, id} => {
                             let mut misc = Misc::new();
                             misc.set_chat_message(ChatMessage {
                                 text,
+                                id,
                                 ..Default::default()
                             });
                             let mut msg_out = Message::new();
@@ -487,9 +717,17 @@ impl Connection {
                             conn.chat_unanswered = false;
                         }
                         ipc::Data::SwitchPermission{name, enabled} => {
+                            if enabled && conn.policy.as_ref().map(|p| p.locked().contains(&name)).unwrap_or(false) {
+                                log::warn!("Permission {} is locked by the policy", name);
+                                continue;
+                            }
                             log::info!("Change permission {} -> {}", name, enabled);
+                            conn.record_permission_audit(&name, enabled, "cm");
                             if &name == "keyboard" {
                                 conn.keyboard = enabled;
+                                if enabled && conn.auto_disconnect_timer.is_none() {
+                                    conn.auto_disconnect_timer = Self::get_auto_disconenct_timer();
+                                }
                                 conn.send_permission(Permission::Keyboard, enabled).await;
                                 if let Some(s) = conn.server.upgrade() {
                                     s.write().unwrap().subscribe(
@@ -511,12 +749,20 @@ impl Connection {
                                     if let Some(s) = conn.server.upgrade() {
                                         s.write().unwrap().subscribe(
                                             super::audio_service::NAME,
-                                            conn.inner.clone(), conn.audio_enabled());
+                                            conn.inner.clone(), conn.audio_subscribed());
                                     }
                                 }
                             } else if &name == "file" {
                                 conn.file = enabled;
                                 conn.send_permission(Permission::File, enabled).await;
+                                #[cfg(any(target_os = "linux", target_os = "macos"))]
+                                if !enabled {
+                                    super::print_jobs::set_receiver(
+                                        conn.inner.id(),
+                                        conn.tx_print_jobs.clone(),
+                                        false,
+                                    );
+                                }
                             } else if &name == "restart" {
                                 conn.restart = enabled;
                                 conn.send_permission(Permission::Restart, enabled).await;
@@ -526,13 +772,65 @@ impl Connection {
                             } else if &name == "block_input" {
                                 conn.block_input = enabled;
                                 conn.send_permission(Permission::BlockInput, enabled).await;
+                            } else if &name == "key_input" {
+                                conn.key_input = enabled;
+                                conn.send_permission(Permission::KeyInput, enabled).await;
+                            } else if &name == "mouse_input" {
+                                conn.mouse_input = enabled;
+                                conn.send_permission(Permission::MouseInput, enabled).await;
+                            } else if &name == "clipboard_typing" {
+                                conn.clipboard_typing = enabled;
+                                conn.send_permission(Permission::ClipboardTyping, enabled).await;
+                            } else if &name == "terminal" {
+                                conn.terminal = enabled;
+                                conn.send_permission(Permission::Terminal, enabled).await;
+                                #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                                if !enabled {
+                                    conn.terminals.close_all();
+                                }
                             }
                         }
+                        ipc::Data::SavePermissionProfile(save) => {
+                            conn.save_permission_profile(save);
+                        }
                         ipc::Data::RawMessage(bytes) => {
                             allow_err!(conn.stream.send_raw(bytes).await);
                         }
+                        ipc::Data::ChatAttachment { id, name, data, compressed, image } => {
+                            let attachment = ChatAttachment {
+                                name,
+                                data,
+                                compressed,
+                                image,
+                                ..Default::default()
+                            };
+                            match conn.check_chat_attachment(&attachment, Direction::Send) {
+                                Ok(()) => {
+                                    let mut misc = Misc::new();
+                                    misc.set_chat_message(crate::chat::new_message(id, attachment));
+                                    let mut msg_out = Message::new();
+                                    msg_out.set_misc(misc);
+                                    conn.send(msg_out).await;
+                                }
+                                Err(err) => {
+                                    conn.send_to_cm(ipc::Data::ChatReceipt {
+                                        id,
+                                        error: err.to_string(),
+                                    });
+                                }
+                            }
+                        }
+                        ipc::Data::FileTransferEvent(event) => {
+                            conn.record_file_transfer(event);
+                        }
+                        #[cfg(any(target_os = "linux", target_os = "macos"))]
+                        ipc::Data::PrintJob { title, copies, data } => {
+                            conn.send_print_job(title, copies, data).await;
+                        }
                         #[cfg(any(target_os="windows", target_os="linux", target_os = "macos"))]
                         ipc::Data::ClipboardFile(clip) => {
+                            let clip = conn.apply_clipboard_file_dlp(clip, dlp::Direction::Outgoing);
+                            conn.record_clipboard_file_audit(&clip, true);
                             allow_err!(conn.stream.send(&clip_2_msg(clip)).await);
                         }
                         ipc::Data::PrivacyModeState((_, state, impl_key)) => {
@@ -571,6 +869,17 @@ impl Connection {
                             msg.set_misc(misc);
                             conn.send(msg).await;
                         }
+                        ipc::Data::InputControlResponse(grant) => {
+                            input_control::answer(conn.inner.id(), grant);
+                            conn.input_control_check();
+                        }
+                        ipc::Data::Annotation(bytes) => {
+                            if let Ok(annotation) = Annotation::parse_from_bytes(&bytes) {
+                                let mut msg = Message::new();
+                                msg.set_annotation(annotation);
+                                conn.send(msg).await;
+                            }
+                        }
                         ipc::Data::VoiceCallResponse(accepted) => {
                             conn.handle_voice_call(accepted).await;
                         }
@@ -585,6 +894,14 @@ impl Connection {
                     }
                 },
                 res = conn.stream.next() => {
+                    if conn.backup.is_ready() && !matches!(res, Some(Ok(_))) {
+                        if let Err(err) = conn.backup.switch(&mut conn.stream, None).await {
+                            conn.on_close(&err.to_string(), true).await;
+                            break;
+                        }
+                        conn.on_switched(id);
+                        continue;
+                    }
                     if let Some(res) = res {
                         match res {
                             Err(err) => {
@@ -595,6 +912,9 @@ impl Connection {
                                 last_recv_time = Instant::now();
                                 conn.session_last_recv_time.as_mut().map(|t| *t.lock().unwrap() = Instant::now());
                                 if let Ok(msg_in) = Message::parse_from_bytes(&bytes) {
+                                    if crate::backup_path::is_counted(&msg_in) {
+                                        conn.stream.on_received();
+                                    }
                                     if !conn.on_message(msg_in).await {
                                         break;
                                     }
@@ -613,14 +933,35 @@ impl Connection {
                         break;
                     }
                 },
+                res = conn.backup.next() => {
+                    if let Some(received) = conn.backup.on_next(res, &mut conn.stream) {
+                        last_recv_time = Instant::now();
+                        if let Err(err) = conn.backup.switch(&mut conn.stream, Some(received)).await {
+                            conn.on_close(&err.to_string(), true).await;
+                            break;
+                        }
+                        conn.on_switched(id);
+                    }
+                }
                 _ = conn.file_timer.tick() => {
                     if !conn.read_jobs.is_empty() {
+                        if !bandwidth::file_allowed(id) {
+                            continue;
+                        }
                         conn.send_to_cm(ipc::Data::FileTransferLog(("transfer".to_string(), fs::serialize_transfer_jobs(&conn.read_jobs))));
                         match fs::handle_read_jobs(&mut conn.read_jobs, &mut conn.stream).await {
-                            Ok(log) => {
+                            Ok((log, sent, finished)) => {
+                                bandwidth::record(id, bandwidth::Usage::File, sent);
                                 if !log.is_empty() {
                                     conn.send_to_cm(ipc::Data::FileTransferLog(("transfer".to_string(), log)));
                                 }
+                                for job in finished {
+                                    let result = match job.job_error() {
+                                        Some(err) => Outcome::Failed(err),
+                                        None => Outcome::Done,
+                                    };
+                                    conn.record_file_transfer(TransferEvent::of(&job, Direction::Send, result));
+                                }
                             }
                             Err(err) =>  {
                                 conn.on_close(&err.to_string(), false).await;
@@ -642,10 +983,15 @@ impl Connection {
                     if !conn.video_ack_required {
                         video_service::notify_video_frame_fetched(id, Some(instant.into()));
                     }
-                    if let Err(err) = conn.stream.send(&value as &Message).await {
+                    if conn.skip_broadcast_frame(instant.elapsed(), &value) {
+                        continue;
+                    }
+                    bandwidth::record(id, bandwidth::Usage::Video, value.compute_size() as _);
+                    if let Err(err) = conn.stream.send_on(Channel::Video, &value as &Message).await {
                         conn.on_close(&err.to_string(), false).await;
                         break;
                     }
+                    conn.video_frames.0 += 1;
                 },
                 Some((instant, value)) = rx.recv() => {
                     let latency = instant.elapsed().as_millis() as i64;
@@ -689,7 +1035,21 @@ impl Connection {
                                 msg = Arc::new(new_msg);
                             }
                         }
+                        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                        Some(message::Union::TerminalResponse(r)) => {
+                            if let Some(terminal_response::Union::Closed(closed)) = &r.union {
+                                conn.terminals.on_closed(closed.id);
+                                conn.record_audit(
+                                    AuditKind::Terminal,
+                                    json!({"action": "close", "id": closed.id, "exit_code": closed.exit_code}),
+                                );
+                            }
+                        }
                         Some(message::Union::MultiClipboards(_multi_clipboards)) => {
+                            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                            if !conn.dlp_allows_clipboard(&_multi_clipboards.clipboards, dlp::Direction::Outgoing) {
+                                continue;
+                            }
                             #[cfg(not(any(target_os = "android", target_os = "ios")))]
                             if let Some(msg_out) = crate::clipboard::get_msg_if_not_support_multi_clip(&conn.lr.version, &conn.lr.my_platform, _multi_clipboards) {
                                 if let Err(err) = conn.stream.send(&msg_out).await {
@@ -703,30 +1063,62 @@ impl Connection {
                     }
 
                     let msg: &Message = &msg;
-                    if let Err(err) = conn.stream.send(msg).await {
+                    let usage = match &msg.union {
+                        Some(message::Union::AudioFrame(_)) => bandwidth::Usage::Audio,
+                        _ => bandwidth::Usage::Other,
+                    };
+                    bandwidth::record(id, usage, msg.compute_size() as _);
+                    if let Err(err) = conn.stream.send_on(Channel::of(msg), msg).await {
                         conn.on_close(&err.to_string(), false).await;
                         break;
                     }
                 },
                 _ = second_timer.tick() => {
+                    conn.backup.keep_alive(&conn.stream).await;
+                    conn.check_relay().await;
                     #[cfg(windows)]
                     conn.portable_check();
-                    if let Some((instant, minute)) = conn.auto_disconnect_timer.as_ref() {
-                        if instant.elapsed().as_secs() > minute * 60 {
-                            conn.send_close_reason_no_retry("Connection failed due to inactivity").await;
-                            conn.on_close("auto disconnect", true).await;
-                            break;
-                        }
+                    #[cfg(any(target_os = "windows", target_os = "macos"))]
+                    conn.secure_input_check();
+                    conn.record_incoming_check();
+                    conn.input_control_check();
+                    if let Some(stats) = conn.system_stats.as_mut().and_then(|s| s.next()) {
+                        let mut misc = Misc::new();
+                        misc.set_system_stats(stats);
+                        let mut msg_out = Message::new();
+                        msg_out.set_misc(misc);
+                        conn.send(msg_out).await;
+                    }
+                    #[cfg(feature = "flutter")]
+                    if handoff::is_handed_off(id) {
+                        conn.send_close_reason_no_retry("The session was handed off to another device").await;
+                        conn.on_close("handed off", false).await;
+                        break;
+                    }
+                    if conn.check_auto_disconnect().await {
+                        break;
+                    }
+                    if !conn.authorized && matches!(conn.approve_deadline, Some(d) if Instant::now() > d) {
+                        conn.post_approval_audit("timeout");
+                        conn.send_close_reason_no_retry("The remote side did not accept in time").await;
+                        conn.on_close("approve timeout", true).await;
+                        break;
                     }
                     conn.file_remove_log_control.on_timer().drain(..).map(|x| conn.send_to_cm(x)).count();
                     #[cfg(feature = "hwcodec")]
                     conn.update_supported_encoding();
                 }
                 _ = test_delay_timer.tick() => {
-                    if last_recv_time.elapsed() >= SEC30 {
+                    let silence = last_recv_time.elapsed();
+                    if heartbeat.is_dead(silence) {
                         conn.on_close("Timeout", true).await;
                         break;
                     }
+                    match heartbeat.update(silence) {
+                        Some(true) => log::info!("Connection {} degraded, nothing received for {:?}", id, silence),
+                        Some(false) => log::info!("Connection {} recovered", id),
+                        None => {}
+                    }
                     // The control end will jump out of the loop after receiving LoginResponse and will not reply to the TestDelay
                     if conn.last_test_delay.is_none() && !(conn.port_forward_socket.is_some() && conn.authorized) {
                         conn.last_test_delay = Some(Instant::now());
@@ -734,11 +1126,14 @@ impl Connection {
                         msg_out.set_test_delay(TestDelay{
                             last_delay: conn.network_delay,
                             target_bitrate: video_service::VIDEO_QOS.lock().unwrap().bitrate(),
+                            bandwidth_usage: bandwidth::usage(id).into(),
+                            encode_fps: conn.take_video_fps(),
                             ..Default::default()
                         });
                         conn.send(msg_out.into()).await;
                     }
                     video_service::VIDEO_QOS.lock().unwrap().user_delay_response_elapsed(conn.inner.id(), conn.delay_response_instant.elapsed().as_millis());
+                    conn.update_video_congestion();
                 }
             }
         }
@@ -779,6 +1174,7 @@ impl Connection {
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     fn handle_input(receiver: std_mpsc::Receiver<MessageInput>, tx: Sender) {
         let mut block_input_mode = false;
+        let mut auto_block = AutoBlockInput::new();
         #[cfg(any(target_os = "windows", target_os = "macos"))]
         {
             rdev::set_mouse_extra_info(enigo::ENIGO_INPUT_EXTRA_VALUE);
@@ -790,9 +1186,11 @@ impl Connection {
             match receiver.recv_timeout(std::time::Duration::from_millis(500)) {
                 Ok(v) => match v {
                     MessageInput::Mouse((msg, id)) => {
+                        auto_block.on_input(block_input_mode);
                         handle_mouse(&msg, id);
                     }
                     MessageInput::Key((mut msg, press)) => {
+                        auto_block.on_input(block_input_mode);
                         // todo: press and down have similar meanings.
                         if press && msg.mode.enum_value() == Ok(KeyboardMode::Legacy) {
                             msg.down = true;
@@ -804,8 +1202,13 @@ impl Connection {
                         }
                     }
                     MessageInput::Pointer((msg, id)) => {
+                        auto_block.on_input(block_input_mode);
                         handle_pointer(&msg, id);
                     }
+                    MessageInput::Text(text) => {
+                        auto_block.on_input(block_input_mode);
+                        input_text(&text);
+                    }
                     MessageInput::BlockOn => {
                         let (ok, msg) = crate::platform::block_input(true);
                         if ok {
@@ -822,6 +1225,7 @@ impl Connection {
                         let (ok, msg) = crate::platform::block_input(false);
                         if ok {
                             block_input_mode = false;
+                            auto_block.last_input = None;
                         } else {
                             Self::send_block_input_error(
                                 &tx,
@@ -858,6 +1262,7 @@ impl Connection {
                     }
                 },
                 Err(err) => {
+                    auto_block.on_idle(block_input_mode);
                     #[cfg(not(any(target_os = "android", target_os = "ios")))]
                     if block_input_mode {
                         let _ = crate::platform::block_input(true);
@@ -980,11 +1385,43 @@ impl Connection {
         true
     }
 
+    // If the schedule of the unattended access lets the peer log in without being accepted now.
+    fn unattended_allowed(&self) -> bool {
+        super::unattended_schedule::allows(
+            &Config::get_option(keys::OPTION_UNATTENDED_SCHEDULE),
+            &self.lr.my_id,
+        )
+    }
+
+    async fn check_ip_rules(&mut self, addr: &SocketAddr) -> bool {
+        let Some(rule) =
+            super::ip_rules::check(&Config::get_option(keys::OPTION_IP_RULES), addr.ip())
+        else {
+            return true;
+        };
+        log::warn!(
+            "Connection from {} denied by the ip rule \"{}\"",
+            addr.ip(),
+            rule
+        );
+        self.send_login_error("Your ip is blocked by the peer")
+            .await;
+        // the rule is empty if no allow rule matches
+        Self::post_alarm_audit(
+            AlarmAuditType::IpRule,
+            json!({ "ip": addr.ip(), "rule": rule }),
+        );
+        false
+    }
+
     async fn on_open(&mut self, addr: SocketAddr) -> bool {
         log::debug!("#{} Connection opened from {}.", self.inner.id, addr);
         if !self.check_whitelist(&addr).await {
             return false;
         }
+        if !self.check_ip_rules(&addr).await {
+            return false;
+        }
         #[cfg(not(any(target_os = "android", target_os = "ios")))]
         if crate::is_server() && Config::get_option("allow-only-conn-window-open") == "Y" {
             if !crate::check_process("", !crate::platform::is_root()) {
@@ -1001,6 +1438,7 @@ impl Connection {
             "ip": addr.ip(),
             "action": "new",
         }));
+        self.record_audit(AuditKind::Connection, json!({"action": "open"}));
         true
     }
 
@@ -1032,6 +1470,222 @@ impl Connection {
         });
     }
 
+    #[inline]
+    fn record_audit(&self, kind: AuditKind, data: Value) {
+        if kind == AuditKind::Auth
+            && matches!(data["result"].as_str(), Some("failure") | Some("rejected"))
+        {
+            self.notify(NotifyEvent::AuthFailure, data.clone());
+        }
+        audit_log::record(kind, self.inner.id(), &self.lr.my_id, &self.ip, data);
+    }
+
+    fn notify(&self, event: NotifyEvent, data: Value) {
+        let mut notification = Notification::new(
+            event,
+            self.inner.id(),
+            &self.lr.my_id,
+            &self.lr.my_name,
+            &self.ip,
+        );
+        notification.data = data;
+        notify::notify(notification);
+    }
+
+    fn record_permission_audit(&self, name: &str, enabled: bool, by: &str) {
+        self.record_audit(
+            AuditKind::Permission,
+            json!({"name": name, "enabled": enabled, "by": by}),
+        );
+    }
+
+    // the files offered on the clipboard and requested, not the blocks of them
+    #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+    fn record_clipboard_file_audit(&self, clip: &ipc::ClipboardFile, to_peer: bool) {
+        // FILECONTENTS_SIZE, asked once for each file before its contents
+        const FILECONTENTS_SIZE: i32 = 0x1;
+        let mut data = match clip {
+            ipc::ClipboardFile::FormatList { format_list } => json!({
+                "event": "format_list",
+                "formats": format_list.iter().map(|(_, name)| name).collect::<Vec<_>>(),
+            }),
+            ipc::ClipboardFile::FileContentsRequest {
+                list_index,
+                dw_flags,
+                ..
+            } if *dw_flags == FILECONTENTS_SIZE => json!({
+                "event": "file_request",
+                "list_index": list_index,
+            }),
+            _ => return,
+        };
+        data["to_peer"] = json!(to_peer);
+        self.record_audit(AuditKind::ClipboardFile, data);
+    }
+
+    // what a rule of the data loss prevention matches, the clipboard, the clipboard file or the file
+    // `name`
+    fn record_dlp(
+        &self,
+        verdict: &dlp::Verdict,
+        what: &str,
+        name: &str,
+        direction: dlp::Direction,
+    ) {
+        log::warn!(
+            "DLP rule {:?} matches the {} {:?} {:?} of {}: {:?}",
+            verdict.rule,
+            what,
+            name,
+            direction,
+            self.lr.my_id,
+            verdict.action
+        );
+        self.record_audit(
+            AuditKind::Dlp,
+            json!({
+                "rule": verdict.rule,
+                "action": verdict.action,
+                "what": what,
+                "name": name,
+                "direction": direction,
+            }),
+        );
+    }
+
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    fn dlp_allows_clipboard(&self, clipboards: &[Clipboard], direction: dlp::Direction) -> bool {
+        let Some(verdict) = crate::clipboard::check_dlp(clipboards, direction) else {
+            return true;
+        };
+        self.record_dlp(&verdict, "clipboard", "", direction);
+        !verdict.blocked()
+    }
+
+    // a file list blocked is replaced with a failure, which fails the paste
+    #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+    fn apply_clipboard_file_dlp(
+        &self,
+        clip: ipc::ClipboardFile,
+        direction: dlp::Direction,
+    ) -> ipc::ClipboardFile {
+        let Some((verdict, name)) = ::clipboard::dlp::check(&clip, direction) else {
+            return clip;
+        };
+        self.record_dlp(&verdict, "clipboard_file", &name, direction);
+        if verdict.blocked() {
+            ::clipboard::dlp::blocked_response()
+        } else {
+            clip
+        }
+    }
+
+    fn check_dlp_files(&self, files: &[FileEntry], direction: dlp::Direction) -> ResultType<()> {
+        let Some((verdict, name)) = dlp::check_file_entries(files, direction) else {
+            return Ok(());
+        };
+        self.record_dlp(&verdict, "file", &name, direction);
+        if verdict.blocked() {
+            bail!("{} is blocked by the DLP rule {}", name, verdict.rule);
+        }
+        Ok(())
+    }
+
+    // a document of the virtual printer, which leaves this side as a file sent does
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    async fn send_print_job(&mut self, title: String, copies: u32, data: Bytes) {
+        let size = data.len() as u64;
+        let file = FileEntry {
+            name: title.clone(),
+            size,
+            ..Default::default()
+        };
+        if let Err(err) = self.check_dlp_files(&[file], dlp::Direction::Outgoing) {
+            log::error!("Dropped the print job: {}", err);
+            return;
+        }
+        self.post_file_audit(
+            FileAuditType::RemoteSend,
+            &title,
+            vec![(String::new(), size as _)],
+            json!({ "print": true, "copies": copies }),
+        );
+        self.record_file_transfer(TransferEvent::of_file(Direction::Send, &title, size, size));
+        let mut msg_out = Message::new();
+        msg_out.set_print_job(PrintJob {
+            title,
+            copies,
+            data,
+            ..Default::default()
+        });
+        self.send(msg_out).await;
+    }
+
+    // a file attached in the chat, which passes as a file transfer does
+    fn check_chat_attachment(
+        &self,
+        attachment: &ChatAttachment,
+        direction: Direction,
+    ) -> ResultType<()> {
+        let size = crate::chat::data(attachment)?.len() as u64;
+        let file = FileEntry {
+            name: attachment.name.clone(),
+            size,
+            ..Default::default()
+        };
+        let dlp_direction = match direction {
+            Direction::Send => dlp::Direction::Outgoing,
+            Direction::Receive => dlp::Direction::Incoming,
+        };
+        self.check_dlp_files(&[file], dlp_direction)?;
+        self.record_file_transfer(TransferEvent::of_file(
+            direction,
+            &attachment.name,
+            size,
+            size,
+        ));
+        Ok(())
+    }
+
+    fn record_privacy_mode_audit(&self, on: bool, msg_out: &Message) {
+        let state = msg_out.misc().back_notification().privacy_mode_state();
+        self.record_audit(
+            AuditKind::PrivacyMode,
+            json!({"on": on, "state": format!("{:?}", state)}),
+        );
+    }
+
+    // who accepted or denied the connection, and why the peer asked for it
+    fn post_approval_audit(&self, result: &str) {
+        let reason = self.access_reason.clone().unwrap_or_default();
+        let approver = crate::platform::get_active_username();
+        log::info!(
+            "Connection from {}({}) {} by {}, reason: {:?}",
+            self.lr.my_id,
+            self.ip,
+            result,
+            approver,
+            reason
+        );
+        self.post_conn_audit(json!({
+            "action": "approval",
+            "result": result,
+            "reason": reason,
+            "approver": approver,
+            "peer": (self.lr.my_id.clone(), self.lr.my_name.clone()),
+            "ip": self.ip.clone(),
+        }));
+        self.record_audit(
+            AuditKind::Auth,
+            json!({
+                "method": "approval",
+                "result": result,
+                "reason": reason,
+                "approver": approver,
+            }),
+        );
+    }
+
     fn post_file_audit(
         &self,
         r#type: FileAuditType,
@@ -1067,6 +1721,32 @@ impl Connection {
         });
     }
 
+    // to the audit log, the webhook and the plugins, whichever is enabled
+    fn record_file_transfer(&self, mut event: TransferEvent) {
+        event.peer_id = self.lr.my_id.clone();
+        event.peer_name = self.lr.my_name.clone();
+        self.record_audit(AuditKind::FileTransfer, json!(event));
+        if Config::get_bool_option(keys::OPTION_ENABLE_FILE_TRANSFER_AUDIT_LOG) {
+            if let Err(err) = fs::audit::append(&event) {
+                log::error!("Failed to record the file transfer: {}", err);
+            }
+        }
+        let url = Config::get_option(keys::OPTION_FILE_TRANSFER_AUDIT_WEBHOOK);
+        if !url.is_empty() {
+            let v = json!(event);
+            tokio::spawn(async move {
+                allow_err!(Self::post_audit_async(url, v).await);
+            });
+        }
+        #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        crate::plugin::handle_listen_event_data(
+            crate::plugin::EVENT_ON_FILE_TRANSFER_SERVER.to_owned(),
+            self.lr.my_id.clone(),
+            json!(event),
+        );
+    }
+
     pub fn post_alarm_audit(typ: AlarmAuditType, info: Value) {
         let url = crate::get_audit_server(
             Config::get_option("api-server"),
@@ -1095,7 +1775,11 @@ impl Connection {
         if self.authorized {
             return;
         }
-        if self.require_2fa.is_some() && !self.is_recent_session(true) && !self.from_switch {
+        if self.require_2fa.is_some()
+            && !self.is_recent_session(true)
+            && !self.from_switch
+            && !self.handed_off
+        {
             self.require_2fa.as_ref().map(|totp| {
                 let bot = crate::auth_2fa::TelegramBot::get();
                 let bot = match bot {
@@ -1126,11 +1810,38 @@ impl Connection {
             self.send_login_error(crate::client::REQUIRE_2FA).await;
             return;
         }
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        if self.require_fido2.is_some()
+            && !self.is_recent_session_fido2()
+            && !self.from_switch
+            && !self.handed_off
+        {
+            if let Some(challenge) = self.require_fido2.as_mut() {
+                let mut msg_out = Message::new();
+                msg_out.set_fido2_challenge(challenge.message());
+                self.send(msg_out).await;
+            }
+            return;
+        }
+        let remote = self.file_transfer.is_none()
+            && self.port_forward_socket.is_none()
+            && !self.terminal_session;
+        // counted and joined at once, other viewers may have joined since the login was checked
+        if remote
+            && broadcast::enabled()
+            && !broadcast::try_join(self.inner.clone(), self.broadcast_viewer)
+        {
+            self.send_login_error("The broadcast is full").await;
+            return;
+        }
         self.authorized = true;
+        super::login_lockout::on_success(&self.lr.my_id, &self.ip);
         let (conn_type, auth_conn_type) = if self.file_transfer.is_some() {
             (1, AuthConnType::FileTransfer)
         } else if self.port_forward_socket.is_some() {
             (2, AuthConnType::PortForward)
+        } else if self.terminal_session {
+            (3, AuthConnType::Terminal)
         } else {
             (0, AuthConnType::Remote)
         };
@@ -1147,6 +1858,17 @@ impl Connection {
         self.post_conn_audit(
             json!({"peer": ((&self.lr.my_id, &self.lr.my_name)), "type": conn_type}),
         );
+        self.record_audit(
+            AuditKind::Auth,
+            json!({"result": "success", "peer_name": self.lr.my_name, "type": conn_type}),
+        );
+        let session = match conn_type {
+            1 => "file_transfer",
+            2 => "port_forward",
+            3 => "terminal",
+            _ => "remote",
+        };
+        self.notify(NotifyEvent::SessionStart, json!({"session": session}));
         #[allow(unused_mut)]
         let mut username = crate::platform::get_active_username();
         let mut res = LoginResponse::new();
@@ -1166,14 +1888,33 @@ impl Connection {
             pi.hostname = DEVICE_NAME.lock().unwrap().clone();
             pi.platform = "Android".into();
         }
-        #[cfg(all(target_os = "macos", not(feature = "unix-file-copy-paste")))]
-        let platform_additions = serde_json::Map::new();
-        #[cfg(any(
-            target_os = "windows",
-            target_os = "linux",
-            all(target_os = "macos", feature = "unix-file-copy-paste")
-        ))]
+        pi.lan_addresses = crate::lan::lan_addresses();
+        #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
         let mut platform_additions = serde_json::Map::new();
+        #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+        if let Some(layout) = crate::keyboard::layout::current() {
+            platform_additions.insert("keyboard_layout".into(), json!(layout));
+        }
+        #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+        if self.audio {
+            platform_additions.insert(
+                "audio_inputs".into(),
+                json!(audio_service::get_input_devices()),
+            );
+            platform_additions.insert("volume".into(), json!(true));
+        }
+        // only the capture of cpal keeps the channels of 5.1 and 7.1
+        #[cfg(any(target_os = "windows", target_os = "macos"))]
+        if self.audio {
+            platform_additions.insert("surround".into(), json!(true));
+        }
+        #[cfg(all(
+            feature = "denoise",
+            any(target_os = "windows", target_os = "linux", target_os = "macos")
+        ))]
+        if self.audio {
+            platform_additions.insert("denoise".into(), json!(true));
+        }
         #[cfg(target_os = "linux")]
         {
             if crate::platform::current_is_wayland() {
@@ -1225,7 +1966,10 @@ impl Connection {
             return;
         }
         #[cfg(target_os = "linux")]
-        if !self.file_transfer.is_some() && !self.port_forward_socket.is_some() {
+        if !self.file_transfer.is_some()
+            && !self.port_forward_socket.is_some()
+            && !self.terminal_session
+        {
             let mut msg = "".to_string();
             if crate::platform::linux::is_login_screen_wayland() {
                 msg = crate::client::LOGIN_SCREEN_WAYLAND.to_owned()
@@ -1270,7 +2014,16 @@ impl Connection {
         pi.username = username;
         pi.sas_enabled = sas_enabled;
         pi.features = Some(Features {
-            privacy_mode: privacy_mode::is_privacy_mode_supported(),
+            privacy_mode: privacy_mode::is_privacy_mode_supported()
+                && self.policy_allows("privacy_mode"),
+            #[cfg(any(target_os = "windows", target_os = "linux"))]
+            gamepad: gamepad::is_supported(),
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            pen: pen::is_supported(),
+            #[cfg(any(target_os = "windows", target_os = "linux"))]
+            touch: touch::is_supported(),
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            ime_input: is_text_input_supported(),
             ..Default::default()
         })
         .into();
@@ -1280,7 +2033,7 @@ impl Connection {
         let mut wait_session_id_confirm = false;
         #[cfg(windows)]
         self.handle_windows_specific_session(&mut pi, &mut wait_session_id_confirm);
-        if self.file_transfer.is_some() {
+        if self.file_transfer.is_some() || self.terminal_session {
             res.set_peer_info(pi);
         } else {
             let supported_encoding = scrap::codec::Encoder::supported_encoding();
@@ -1356,9 +2109,14 @@ impl Connection {
         }
     }
 
+    // a session of the remote control or view, which is sent the video
+    fn is_remote(&self) -> bool {
+        self.file_transfer.is_none() && self.port_forward_socket.is_none() && !self.terminal_session
+    }
+
     fn try_sub_services(&mut self) {
-        let is_remote = self.file_transfer.is_none() && self.port_forward_socket.is_none();
-        if is_remote && !self.services_subed {
+        // Screenshot-only connections never start video or audio.
+        if self.is_remote() && !self.services_subed && !self.lr.screenshot_only {
             self.services_subed = true;
             if let Some(s) = self.server.upgrade() {
                 let mut noperms = Vec::new();
@@ -1377,7 +2135,7 @@ impl Connection {
                 {
                     noperms.push(super::clipboard_service::NAME);
                 }
-                if !self.audio_enabled() {
+                if !self.audio_subscribed() {
                     noperms.push(super::audio_service::NAME);
                 }
                 let mut s = s.write().unwrap();
@@ -1390,6 +2148,44 @@ impl Connection {
         }
     }
 
+    fn take_screenshot(&self, request: ScreenshotRequest) {
+        let mut inner = self.inner.clone();
+        std::thread::spawn(move || {
+            let mut response = ScreenshotResponse {
+                sid: request.sid,
+                ..Default::default()
+            };
+            match video_service::take_screenshot(request.display as usize) {
+                Ok(data) => response.data = data.into(),
+                Err(e) => {
+                    log::error!("Failed to take screenshot: {}", e);
+                    response.msg = e.to_string();
+                }
+            }
+            let mut msg_out = Message::new();
+            msg_out.set_screenshot_response(response);
+            inner.send(Arc::new(msg_out));
+        });
+    }
+
+    // the peer wakes a device of the lans of this side, offline so not reachable by it
+    async fn wake_on_lan(&mut self, wake: WakeOnLan) {
+        let mut response = WakeOnLanResponse::new();
+        if !Config::get_bool_option(keys::OPTION_ALLOW_WOL_RELAY) {
+            response.error = "Wake-on-LAN through this device is disabled".to_owned();
+        } else {
+            let macs: Vec<String> = wake.macs.into_iter().take(MAX_WOL_MACS).collect();
+            if crate::lan::wake(&macs) == 0 {
+                response.error = "No valid MAC address".to_owned();
+            } else {
+                self.record_audit(AuditKind::WakeOnLan, json!({ "macs": macs }));
+            }
+        }
+        let mut msg_out = Message::new();
+        msg_out.set_wake_on_lan_response(response);
+        self.send(msg_out).await;
+    }
+
     #[cfg(windows)]
     fn handle_windows_specific_session(
         &mut self,
@@ -1442,6 +2238,34 @@ impl Connection {
         self.keyboard && !self.disable_keyboard
     }
 
+    fn peer_key_input_enabled(&self) -> bool {
+        self.peer_keyboard_enabled() && self.key_input && input_control::allows(self.inner.id())
+    }
+
+    fn peer_mouse_input_enabled(&self) -> bool {
+        self.peer_keyboard_enabled() && self.mouse_input && input_control::allows(self.inner.id())
+    }
+
+    fn peer_clipboard_typing_enabled(&self) -> bool {
+        self.peer_keyboard_enabled()
+            && self.clipboard_typing
+            && input_control::allows(self.inner.id())
+    }
+
+    // the session may send input to this side
+    fn can_input(&self) -> bool {
+        self.authorized
+            && self.file_transfer.is_none()
+            && self.port_forward_socket.is_none()
+            && !self.terminal_session
+            && self.peer_keyboard_enabled()
+    }
+
+    // the peer may draw over the screen
+    fn annotation_enabled(&self) -> bool {
+        self.can_input() && Config::get_bool_option(keys::OPTION_ALLOW_ANNOTATION)
+    }
+
     fn clipboard_enabled(&self) -> bool {
         self.clipboard && !self.disable_clipboard
     }
@@ -1450,15 +2274,41 @@ impl Connection {
         self.audio && !self.disable_audio
     }
 
+    // the voice call goes on without the audio of this side forwarded
+    fn audio_subscribed(&self) -> bool {
+        self.audio_enabled() || self.voice_call
+    }
+
+    fn update_audio_subscription(&self) {
+        if let Some(s) = self.server.upgrade() {
+            s.write().unwrap().subscribe(
+                super::audio_service::NAME,
+                self.inner.clone(),
+                self.audio_subscribed(),
+            );
+        }
+    }
+
     #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
     fn file_transfer_enabled(&self) -> bool {
         self.file && self.enable_file_transfer
     }
 
     fn try_start_cm(&mut self, peer_id: String, name: String, authorized: bool) {
+        if let Some(reason) = self.access_reason.clone() {
+            self.send_to_cm(ipc::Data::AccessReason(reason));
+        }
+        let locked = self.policy.as_ref().map(|p| p.locked()).unwrap_or_default();
+        if !locked.is_empty() {
+            self.send_to_cm(ipc::Data::PolicyLocked(locked));
+        }
+        if let Some(key) = self.device_key.as_ref().filter(|_| !self.device_trusted) {
+            self.send_to_cm(ipc::Data::NewDeviceKey(key.fingerprint.clone()));
+        }
         self.send_to_cm(ipc::Data::Login {
             id: self.inner.id(),
             is_file_transfer: self.file_transfer.is_some(),
+            is_terminal: self.terminal_session,
             port_forward: self.port_forward_address.clone(),
             peer_id,
             name,
@@ -1471,15 +2321,450 @@ impl Connection {
             restart: self.restart,
             recording: self.recording,
             block_input: self.block_input,
+            key_input: self.key_input,
+            mouse_input: self.mouse_input,
+            clipboard_typing: self.clipboard_typing,
+            terminal: self.terminal,
             from_switch: self.from_switch,
         });
     }
 
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    fn task_manager_allowed(&self) -> Option<String> {
+        if !self.keyboard {
+            Some("No permission".to_owned())
+        } else if !Connection::permission(keys::OPTION_ALLOW_TASK_MANAGER) {
+            Some("The task manager is disabled on the remote side".to_owned())
+        } else {
+            None
+        }
+    }
+
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    async fn list_processes(&mut self, req: ListProcesses) {
+        let list = match self.task_manager_allowed() {
+            Some(error) => ProcessList {
+                error,
+                ..Default::default()
+            },
+            None => self
+                .task_manager
+                .get_or_insert_with(super::task_manager::Lister::new)
+                .list(&req),
+        };
+        let mut misc = Misc::new();
+        misc.set_process_list(list);
+        let mut msg_out = Message::new();
+        msg_out.set_misc(misc);
+        self.send(msg_out).await;
+    }
+
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    async fn kill_process(&mut self, pid: u32) {
+        let res = match self.task_manager_allowed() {
+            Some(error) => ProcessKilled {
+                pid,
+                error,
+                ..Default::default()
+            },
+            None => {
+                let res = self
+                    .task_manager
+                    .get_or_insert_with(super::task_manager::Lister::new)
+                    .kill(pid);
+                self.record_audit(
+                    AuditKind::KillProcess,
+                    json!({"pid": pid, "error": res.error}),
+                );
+                res
+            }
+        };
+        let mut misc = Misc::new();
+        misc.set_process_killed(res);
+        let mut msg_out = Message::new();
+        msg_out.set_misc(misc);
+        self.send(msg_out).await;
+    }
+
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    fn service_manager_allowed(&self) -> Option<String> {
+        if !self.keyboard {
+            Some("No permission".to_owned())
+        } else if !Connection::permission(keys::OPTION_ALLOW_SERVICE_MANAGER) {
+            Some("The service manager is disabled on the remote side".to_owned())
+        } else {
+            None
+        }
+    }
+
+    // Answered from a thread, listing the services may take a second or so.
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    async fn list_services(&mut self) {
+        let error = self.service_manager_allowed();
+        let mut inner = self.inner.clone();
+        std::thread::spawn(move || {
+            let mut list = ServiceList::new();
+            match error {
+                Some(error) => list.error = error,
+                None => match super::service_manager::list() {
+                    Ok(services) => list.services = services,
+                    Err(err) => {
+                        log::error!("Failed to list the services: {}", err);
+                        list.error = err.to_string();
+                    }
+                },
+            }
+            let mut misc = Misc::new();
+            misc.set_service_list(list);
+            let mut msg_out = Message::new();
+            msg_out.set_misc(misc);
+            inner.send(Arc::new(msg_out));
+        });
+    }
+
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    async fn control_service(&mut self, control: ServiceControl) {
+        let error = self.service_manager_allowed();
+        let mut inner = self.inner.clone();
+        let (peer_id, ip) = (self.lr.my_id.clone(), self.ip.clone());
+        std::thread::spawn(move || {
+            let action = control.action.enum_value_or_default();
+            let mut res = ServiceControlResult {
+                name: control.name.clone(),
+                action: control.action,
+                ..Default::default()
+            };
+            match error {
+                Some(error) => res.error = error,
+                None => {
+                    if let Err(err) = super::service_manager::control(&control.name, action) {
+                        log::error!("Failed to {:?} service {}: {}", action, control.name, err);
+                        res.error = err.to_string();
+                    }
+                    audit_log::record(
+                        AuditKind::Service,
+                        inner.id(),
+                        &peer_id,
+                        &ip,
+                        json!({
+                            "name": control.name,
+                            "action": format!("{:?}", action).to_lowercase(),
+                            "error": res.error,
+                        }),
+                    );
+                }
+            }
+            let mut misc = Misc::new();
+            misc.set_service_control_result(res);
+            let mut msg_out = Message::new();
+            msg_out.set_misc(misc);
+            inner.send(Arc::new(msg_out));
+        });
+    }
+
+    fn camera_allowed(&self) -> Option<String> {
+        if self.file_transfer.is_some()
+            || self.port_forward_socket.is_some()
+            || self.terminal_session
+        {
+            Some("Only a remote control session can view the camera".to_owned())
+        } else if !Connection::permission(keys::OPTION_ALLOW_REMOTE_CAMERA) {
+            Some("The camera is not allowed to be viewed on the remote side".to_owned())
+        } else if !cfg!(any(target_os = "windows", target_os = "linux")) {
+            Some("The camera is not supported on the remote side".to_owned())
+        } else {
+            None
+        }
+    }
+
+    // Answered from a thread, the cameras are probed by the system.
+    async fn list_cameras(&mut self) {
+        let error = self.camera_allowed();
+        let mut inner = self.inner.clone();
+        std::thread::spawn(move || {
+            let mut list = CameraList::new();
+            match error {
+                Some(error) => list.error = error,
+                #[cfg(any(target_os = "windows", target_os = "linux"))]
+                None => match super::camera_service::list() {
+                    Ok(cameras) => list.cameras = cameras,
+                    Err(err) => {
+                        log::error!("Failed to list the cameras: {}", err);
+                        list.error = err.to_string();
+                    }
+                },
+                #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+                None => {}
+            }
+            let mut misc = Misc::new();
+            misc.set_camera_list(list);
+            let mut msg_out = Message::new();
+            msg_out.set_misc(misc);
+            inner.send(Arc::new(msg_out));
+        });
+    }
+
+    async fn request_camera(&mut self, req: CameraRequest) {
+        if req.on {
+            if let Some(error) = self.camera_allowed() {
+                let mut msg_out = Message::new();
+                msg_out.set_camera_frame(CameraFrame {
+                    index: req.index,
+                    error,
+                    ..Default::default()
+                });
+                self.send(msg_out).await;
+                return;
+            }
+            log::info!("The camera {} viewed by the peer", req.index);
+            #[cfg(any(target_os = "windows", target_os = "linux"))]
+            super::camera_service::set_request(self.inner.id(), req.clone());
+        }
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        if let Some(s) = self.server.upgrade() {
+            s.write()
+                .unwrap()
+                .subscribe(super::camera_service::NAME, self.inner.clone(), req.on);
+        }
+    }
+
+    // A ticket for another device of the peer to take the session over with.
+    #[cfg(feature = "flutter")]
+    async fn send_handoff_ticket(&mut self) {
+        let mut handoff = SessionHandoff::new();
+        if self.file_transfer.is_some()
+            || self.port_forward_socket.is_some()
+            || self.terminal_session
+        {
+            handoff.error = "Only a remote control session can be handed off".to_owned();
+        } else {
+            let permissions = PERMISSION_NAMES
+                .iter()
+                .filter_map(|name| Some((name.to_string(), *self.permission_mut(name)?.1)))
+                .collect();
+            let ticket = handoff::new_ticket(handoff::Handoff {
+                conn_id: self.inner.id(),
+                identity: self.identity.clone(),
+                permissions,
+            });
+            log::info!("#{} Handoff ticket sent", self.inner.id());
+            handoff.ticket = ticket.as_bytes().to_vec().into();
+            handoff.timeout = handoff::TICKET_TIMEOUT.as_secs() as _;
+        }
+        let mut misc = Misc::new();
+        misc.set_session_handoff(handoff);
+        let mut msg_out = Message::new();
+        msg_out.set_misc(misc);
+        self.send(msg_out).await;
+    }
+
+    // Logs the peer in with the ticket of a session handed off, which goes on with the permissions
+    // and the privacy mode of it, and is closed.
+    #[cfg(feature = "flutter")]
+    async fn take_over(&mut self, lr: &LoginRequest, handoff: handoff::Handoff) {
+        if !self.check_identity(lr).await {
+            return;
+        }
+        if handoff.identity.is_some() && handoff.identity != self.identity {
+            self.send_login_error("The account differs from the one of the session handed off")
+                .await;
+            return;
+        }
+        log::info!(
+            "#{} Take over the session of #{}",
+            self.inner.id(),
+            handoff.conn_id
+        );
+        for (name, enabled) in handoff.permissions {
+            // never more than the policy allows the peer now
+            let enabled = enabled && self.policy_allows(&name);
+            if let Some((permission, value)) = self.permission_mut(&name) {
+                if *value != enabled {
+                    *value = enabled;
+                    self.send_permission(permission, enabled).await;
+                }
+            }
+        }
+        let privacy_mode = privacy_mode::hand_over(handoff.conn_id, self.inner.id());
+        handoff::hand_off(handoff.conn_id);
+        // anyone holding the ticket gets here, the second factor is asked again unless the ticket
+        // is bound to the account just checked
+        self.handed_off = handoff.is_bound_to(&self.identity);
+        self.send_logon_response().await;
+        self.try_start_cm(lr.my_id.clone(), lr.my_name.clone(), self.authorized);
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        self.try_start_cm_ipc();
+        if privacy_mode {
+            if let Some(impl_key) = privacy_mode::get_cur_impl_key() {
+                self.send(crate::common::make_privacy_mode_msg(
+                    back_notification::PrivacyModeState::PrvOnSucceeded,
+                    impl_key,
+                ))
+                .await;
+            }
+        }
+    }
+
+    // Verifies the identity of the peer if one is required, counted as a login failure if wrong.
+    async fn check_identity(&mut self, lr: &LoginRequest) -> bool {
+        if self.identity.is_some() || crate::idp::required_provider().is_empty() {
+            return true;
+        }
+        let (failure, res) = self.check_failure(0).await;
+        if !res {
+            return false;
+        }
+        let identity = lr.identity.clone().unwrap_or_default();
+        let attached = !identity.provider.is_empty();
+        let res = match tokio::task::spawn_blocking(move || crate::idp::verify(&identity)).await {
+            Ok(res) => res,
+            Err(err) => Err(err.into()),
+        };
+        match res {
+            Ok(name) => {
+                log::info!("Identity {} of {} verified", name, lr.my_id);
+                self.post_conn_audit(json!({
+                    "action": "identity",
+                    "name": name,
+                }));
+                self.record_audit(
+                    AuditKind::Auth,
+                    json!({"method": "identity", "result": "success", "name": name}),
+                );
+                self.identity = Some(name);
+                self.apply_policy().await;
+                true
+            }
+            Err(err) => {
+                log::warn!("Identity of {} not verified: {}", lr.my_id, err);
+                self.record_audit(
+                    AuditKind::Auth,
+                    json!({"method": "identity", "result": "failure", "error": err.to_string()}),
+                );
+                if attached {
+                    self.update_failure(failure, false, 0);
+                }
+                self.send_login_error(format!(
+                    "{}: {}",
+                    crate::client::LOGIN_MSG_IDENTITY_NOT_VERIFIED,
+                    err
+                ))
+                .await;
+                false
+            }
+        }
+    }
+
+    // Checks the key of the device of the peer against the keys trusted, if required. A device not
+    // trusted is refused, or else has to be accepted in the cm, and is trusted then.
+    async fn check_device_key(&mut self, lr: &LoginRequest) -> bool {
+        let reject = Config::get_bool_option(keys::OPTION_REJECT_UNTRUSTED_KEYS);
+        if self.device_trusted
+            || !reject && !Config::get_bool_option(keys::OPTION_TRUST_KEYS_ON_FIRST_USE)
+        {
+            self.device_trusted = true;
+            return true;
+        }
+        let fingerprint = lr.device_key.as_ref().and_then(|key| {
+            crate::common::verify_device_key(key, &lr.username, &self.hash.challenge)
+        });
+        if fingerprint
+            .as_ref()
+            .map(|f| Config::is_key_trusted(f))
+            .unwrap_or(false)
+        {
+            self.device_trusted = true;
+            return true;
+        }
+        self.record_audit(
+            AuditKind::Auth,
+            json!({
+                "method": "device_key",
+                "result": if reject { "rejected" } else { "new" },
+                "fingerprint": fingerprint.clone().unwrap_or_default(),
+            }),
+        );
+        if reject {
+            log::warn!("Reject the untrusted device of {}", lr.my_id);
+            self.send_login_error(crate::client::LOGIN_MSG_UNTRUSTED_DEVICE)
+                .await;
+            sleep(1.).await;
+            return false;
+        }
+        self.device_key = fingerprint.map(|fingerprint| TrustedKey {
+            fingerprint,
+            id: lr.my_id.clone(),
+            name: lr.my_name.clone(),
+            platform: lr.my_platform.clone(),
+            time: 0,
+        });
+        true
+    }
+
+    // Trusts the key of the device of the peer accepted in the cm.
+    fn trust_device_key(&mut self) {
+        if self.device_trusted {
+            return;
+        }
+        self.device_trusted = true;
+        if let Some(mut key) = self.device_key.take() {
+            log::info!("Trust the device key {} of {}", key.fingerprint, key.id);
+            key.time = hbb_common::get_time();
+            Config::trust_key(key);
+        }
+    }
+
+    #[inline]
+    fn require_access_reason() -> bool {
+        Config::get_option(keys::OPTION_REQUIRE_ACCESS_REASON) == "Y"
+    }
+
+    // Shows the accept dialog, unless the reason required is not told yet, which is asked for if
+    // `ask_reason`.
+    async fn request_approval(&mut self, ask_reason: bool) -> bool {
+        if Self::require_access_reason() && self.access_reason.is_none() {
+            if ask_reason {
+                self.access_reason_asked = true;
+                self.send_login_error(crate::client::LOGIN_MSG_ACCESS_REASON_REQUIRED)
+                    .await;
+            }
+            return false;
+        }
+        self.try_start_cm(self.lr.my_id.clone(), self.lr.my_name.clone(), false);
+        if self.approve_deadline.is_none()
+            && Config::get_option(keys::OPTION_ALLOW_APPROVE_TIMEOUT) == "Y"
+        {
+            let mut secs: u64 = Config::get_option(keys::OPTION_APPROVE_TIMEOUT)
+                .parse()
+                .unwrap_or(60);
+            if secs == 0 {
+                secs = 60;
+            }
+            self.approve_deadline = Some(Instant::now() + Duration::from_secs(secs));
+        }
+        true
+    }
+
     #[inline]
     fn send_to_cm(&mut self, data: ipc::Data) {
         self.tx_to_cm.send(data).ok();
     }
 
+    // Lets the file transfers sent yield to the video while its delay is high.
+    fn update_video_congestion(&self) {
+        if self.file_transfer.is_some()
+            || self.port_forward_socket.is_some()
+            || self.terminal_session
+        {
+            return;
+        }
+        fs::throttle::set_congested(
+            self.inner.id(),
+            self.network_delay > CONGESTED_DELAY_MS
+                || self.delay_response_instant.elapsed() > CONGESTED_RESPONSE,
+        );
+    }
+
     #[inline]
     fn send_fs(&mut self, data: ipc::FS) {
         self.send_to_cm(ipc::Data::FS(data));
@@ -1543,12 +2828,56 @@ impl Connection {
         let mut hasher = Sha256::new();
         hasher.update(password);
         hasher.update(&self.hash.salt);
+        self.validate_salted_password(&hasher.finalize()[..])
+    }
+
+    // `salted` is the sha256 of the password and the salt
+    fn validate_salted_password(&self, salted: &[u8]) -> bool {
         let mut hasher2 = Sha256::new();
-        hasher2.update(&hasher.finalize()[..]);
+        hasher2.update(salted);
         hasher2.update(&self.hash.challenge);
         hasher2.finalize()[..] == self.lr.password[..]
     }
 
+    // taken away once used, and only by the connections in its scope
+    fn validate_one_time_password(&mut self) -> bool {
+        if self.lr.password.is_empty() {
+            return false;
+        }
+        let file_transfer = self.file_transfer.is_some();
+        let port_forward = self.port_forward_socket.is_some();
+        let terminal = self.terminal_session;
+        let Some(password) = Config::take_one_time_password(|p| {
+            let in_scope = match p.scope.as_str() {
+                "" => true,
+                OneTimePassword::SCOPE_VIEW => !file_transfer && !port_forward && !terminal,
+                OneTimePassword::SCOPE_FILE_TRANSFER => file_transfer,
+                _ => false,
+            };
+            in_scope && self.validate_salted_password(&p.salted_hash())
+        }) else {
+            return false;
+        };
+        log::info!(
+            "One-time password {} ({}) used by {}",
+            password.id,
+            password.label,
+            self.lr.my_id
+        );
+        self.record_audit(
+            AuditKind::Auth,
+            json!({
+                "method": "one_time_password",
+                "result": "success",
+                "id": password.id,
+                "label": password.label,
+                "scope": password.scope,
+            }),
+        );
+        self.one_time_password = Some(password);
+        true
+    }
+
     fn validate_password(&mut self) -> bool {
         if password::temporary_enabled() {
             let password = password::temporary_password();
@@ -1562,10 +2891,18 @@ impl Connection {
             }
         }
         if password::permanent_enabled() {
-            if self.validate_one_password(Config::get_permanent_password()) {
+            if Config::is_permanent_password_unavailable() {
+                log::error!(
+                    "Refused the permanent password of {}, the keystore it is in can not be read",
+                    self.lr.my_id
+                );
+            } else if self.validate_one_password(Config::get_permanent_password()) {
                 return true;
             }
         }
+        if password::temporary_enabled() && self.validate_one_time_password() {
+            return true;
+        }
         false
     }
 
@@ -1592,6 +2929,18 @@ impl Connection {
         false
     }
 
+    // the security key was touched for the session, which the password has to be valid for too
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    fn is_recent_session_fido2(&mut self) -> bool {
+        let fido2 = SESSIONS
+            .lock()
+            .unwrap()
+            .get(&self.session_key())
+            .map(|s| s.fido2)
+            .unwrap_or_default();
+        fido2 && self.is_recent_session(self.require_2fa.is_some())
+    }
+
     pub fn permission(enable_prefix_option: &str) -> bool {
         #[cfg(feature = "flutter")]
         #[cfg(not(any(target_os = "android", target_os = "ios")))]
@@ -1649,6 +2998,150 @@ impl Connection {
             }
         }
         self.video_ack_required = lr.video_ack_required;
+        self.broadcast_viewer = lr.broadcast_viewer && lr.union.is_none() && broadcast::enabled();
+        self.apply_permission_profile().await;
+        self.apply_policy().await;
+        self.apply_broadcast_viewer().await;
+    }
+
+    // the permissions saved for the peer take away the ones of the options
+    async fn apply_permission_profile(&mut self) {
+        let Some(profile) = Config::get_permission_profile(&self.lr.my_id) else {
+            return;
+        };
+        log::info!("Apply the permission profile of {}", profile.id);
+        for (name, enabled) in profile.permissions {
+            if !enabled {
+                self.revoke_permission(&name, "profile").await;
+            }
+        }
+    }
+
+    // the features the role of the peer is denied are taken away, after the permission profile
+    async fn apply_policy(&mut self) {
+        self.policy = policy::role_of(&self.lr.my_id, self.identity.as_deref());
+        let Some(policy) = self.policy.clone() else {
+            return;
+        };
+        log::info!(
+            "Apply the role {} of the policy to {}",
+            policy.role,
+            self.lr.my_id
+        );
+        for name in ["file", "clipboard", "terminal"] {
+            if !policy.allows(name) {
+                self.revoke_permission(name, "policy").await;
+            }
+        }
+    }
+
+    // a connection by a view-only one-time password can not control this side
+    async fn apply_one_time_password_scope(&mut self) {
+        let view = self
+            .one_time_password
+            .as_ref()
+            .map(|p| p.scope == OneTimePassword::SCOPE_VIEW)
+            .unwrap_or(false);
+        if !view {
+            return;
+        }
+        for name in PERMISSION_NAMES {
+            if name != "audio" {
+                self.revoke_permission(name, "one_time_password").await;
+            }
+        }
+    }
+
+    // a viewer of the broadcast only watches and listens
+    async fn apply_broadcast_viewer(&mut self) {
+        if !self.broadcast_viewer {
+            return;
+        }
+        for name in PERMISSION_NAMES {
+            if name != "audio" {
+                self.revoke_permission(name, "broadcast").await;
+            }
+        }
+    }
+
+    // a viewer lagging behind the broadcast skips the frames up to a key frame, asked for once it
+    // catches up, as the encoder is not held up by it
+    fn skip_broadcast_frame(&mut self, delay: Duration, msg: &Message) -> bool {
+        if !self.broadcast_viewer {
+            return false;
+        }
+        if delay > broadcast::MAX_LAG {
+            if self.broadcast_lagging.is_none() {
+                log::info!("#{} lags behind the broadcast", self.inner.id());
+                self.broadcast_lagging = Some(false);
+            }
+            return true;
+        }
+        match self.broadcast_lagging {
+            None => false,
+            Some(_) if broadcast::is_key_frame(msg) => {
+                self.broadcast_lagging = None;
+                false
+            }
+            Some(requested) => {
+                if !requested {
+                    self.refresh_video_display(None);
+                    self.broadcast_lagging = Some(true);
+                }
+                true
+            }
+        }
+    }
+
+    // switches the permission off if on, `by` is what takes it away
+    async fn revoke_permission(&mut self, name: &str, by: &str) {
+        let Some((permission, value)) = self.permission_mut(name) else {
+            return;
+        };
+        if *value {
+            *value = false;
+            self.send_permission(permission, false).await;
+            self.record_permission_audit(name, false, by);
+        }
+    }
+
+    #[inline]
+    fn policy_allows(&self, name: &str) -> bool {
+        self.policy.as_ref().map(|p| p.allows(name)).unwrap_or(true)
+    }
+
+    fn save_permission_profile(&mut self, save: bool) {
+        let id = self.lr.my_id.clone();
+        if id.is_empty() {
+            return;
+        }
+        log::info!("Save the permission profile of {}: {}", id, save);
+        if save {
+            let permissions = PERMISSION_NAMES
+                .iter()
+                .filter_map(|name| Some((name.to_string(), *self.permission_mut(name)?.1)))
+                .collect();
+            Config::set_permission_profile(PermissionProfile { id, permissions });
+        } else {
+            Config::remove_permission_profiles(&vec![id]);
+        }
+    }
+
+    fn permission_mut(&mut self, name: &str) -> Option<(Permission, &mut bool)> {
+        Some(match name {
+            "keyboard" => (Permission::Keyboard, &mut self.keyboard),
+            "clipboard" => (Permission::Clipboard, &mut self.clipboard),
+            "audio" => (Permission::Audio, &mut self.audio),
+            "file" => (Permission::File, &mut self.file),
+            "restart" => (Permission::Restart, &mut self.restart),
+            "recording" => (Permission::Recording, &mut self.recording),
+            "block_input" => (Permission::BlockInput, &mut self.block_input),
+            "key_input" => (Permission::KeyInput, &mut self.key_input),
+            "mouse_input" => (Permission::MouseInput, &mut self.mouse_input),
+            "clipboard_typing" => (Permission::ClipboardTyping, &mut self.clipboard_typing),
+            "terminal" => (Permission::Terminal, &mut self.terminal),
+            _ => return None,
+        })
     }
 
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
@@ -1691,9 +3184,17 @@ impl Connection {
             if self.authorized {
                 return true;
             }
+            if !self.check_identity(&lr).await {
+                return true;
+            }
+            if !self.check_device_key(&lr).await {
+                return false;
+            }
             match lr.union {
                 Some(login_request::Union::FileTransfer(ft)) => {
-                    if !Connection::permission(keys::OPTION_ENABLE_FILE_TRANSFER) {
+                    if !Connection::permission(keys::OPTION_ENABLE_FILE_TRANSFER)
+                        || !self.policy_allows("file")
+                    {
                         self.send_login_error("No permission of file transfer")
                             .await;
                         sleep(1.).await;
@@ -1701,6 +3202,17 @@ impl Connection {
                     }
                     self.file_transfer = Some((ft.dir, ft.show_hidden));
                 }
+                Some(login_request::Union::Terminal(_)) => {
+                    if cfg!(any(target_os = "android", target_os = "ios"))
+                        || !Connection::permission(keys::OPTION_ALLOW_TERMINAL)
+                        || !self.policy_allows("terminal")
+                    {
+                        self.send_login_error("No permission of terminal").await;
+                        sleep(1.).await;
+                        return false;
+                    }
+                    self.terminal_session = true;
+                }
                 Some(login_request::Union::PortForward(mut pf)) => {
                     if !Connection::permission("enable-tunnel") {
                         self.send_login_error("No permission of IP tunneling").await;
@@ -1739,8 +3251,15 @@ impl Connection {
                     if !self.check_privacy_mode_on().await {
                         return false;
                     }
+                    // refused early, before the password, try_join decides when authorized
+                    if self.broadcast_viewer && broadcast::full() {
+                        self.send_login_error("The broadcast is full").await;
+                        sleep(1.).await;
+                        return false;
+                    }
                 }
             }
+            self.mark_dscp();
 
             #[cfg(not(any(target_os = "android", target_os = "ios")))]
             self.try_start_cm_ipc();
@@ -1771,14 +3290,31 @@ impl Connection {
                     && crate::get_builtin_option(keys::OPTION_ALLOW_LOGON_SCREEN_PASSWORD) == "Y"))
                 || password::approve_mode() == ApproveMode::Both && !password::has_valid_password()
             {
-                self.try_start_cm(lr.my_id, lr.my_name, false);
-                if hbb_common::get_version_number(&lr.version)
-                    >= hbb_common::get_version_number("1.2.0")
+                if self.request_approval(true).await
+                    && hbb_common::get_version_number(&lr.version)
+                        >= hbb_common::get_version_number("1.2.0")
                 {
                     self.send_login_error(crate::client::LOGIN_MSG_NO_PASSWORD_ACCESS)
                         .await;
                 }
                 return true;
+            } else if !self.unattended_allowed() {
+                log::info!(
+                    "#{} Unattended access of {} outside the schedule",
+                    self.inner.id(),
+                    lr.my_id
+                );
+                if Config::get_bool_option(keys::OPTION_REFUSE_OUTSIDE_SCHEDULE) {
+                    self.send_login_error("Unattended access is not allowed at this time")
+                        .await;
+                    sleep(1.).await;
+                    return false;
+                }
+                if self.request_approval(true).await {
+                    self.send_login_error(crate::client::LOGIN_MSG_OUTSIDE_SCHEDULE)
+                        .await;
+                }
+                return true;
             } else if self.is_recent_session(false) {
                 if err_msg.is_empty() {
                     #[cfg(target_os = "linux")]
@@ -1790,7 +3326,7 @@ impl Connection {
                 }
             } else if lr.password.is_empty() {
                 if err_msg.is_empty() {
-                    self.try_start_cm(lr.my_id, lr.my_name, false);
+                    self.request_approval(true).await;
                 } else {
                     self.send_login_error(
                         crate::client::LOGIN_MSG_DESKTOP_SESSION_NOT_READY_PASSWORD_EMPTY,
@@ -1803,11 +3339,15 @@ impl Connection {
                     return true;
                 }
                 if !self.validate_password() {
+                    self.record_audit(
+                        AuditKind::Auth,
+                        json!({"method": "password", "result": "failure"}),
+                    );
                     self.update_failure(failure, false, 0);
                     if err_msg.is_empty() {
                         self.send_login_error(crate::client::LOGIN_MSG_PASSWORD_WRONG)
                             .await;
-                        self.try_start_cm(lr.my_id, lr.my_name, false);
+                        self.request_approval(false).await;
                     } else {
                         self.send_login_error(
                             crate::client::LOGIN_MSG_DESKTOP_SESSION_NOT_READY_PASSWORD_WRONG,
@@ -1816,7 +3356,14 @@ impl Connection {
                     }
                 } else {
                     self.update_failure(failure, true, 0);
-                    if err_msg.is_empty() {
+                    self.apply_one_time_password_scope().await;
+                    if !self.device_trusted {
+                        // trusted on its first use only once accepted
+                        if self.request_approval(true).await {
+                            self.send_login_error(crate::client::LOGIN_MSG_NEW_DEVICE)
+                                .await;
+                        }
+                    } else if err_msg.is_empty() {
                         #[cfg(target_os = "linux")]
                         self.linux_headless_handle.wait_desktop_cm_ready().await;
                         self.send_logon_response().await;
@@ -1853,12 +3400,82 @@ impl Connection {
                             });
                         }
                     } else {
+                        self.record_audit(
+                            AuditKind::Auth,
+                            json!({"method": "2fa", "result": "failure"}),
+                        );
                         self.update_failure(failure, false, 1);
                         self.send_login_error(crate::client::LOGIN_MSG_2FA_WRONG)
                             .await;
                     }
                 }
             }
+        } else if let Some(message::Union::Fido2Assertion(_assertion)) = msg.union {
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            if self.require_fido2.is_some() {
+                let (failure, res) = self.check_failure(1).await;
+                if !res {
+                    return true;
+                }
+                let verified = self
+                    .require_fido2
+                    .as_ref()
+                    .map(|c| c.verify(&_assertion).map(|name| name.to_owned()));
+                match verified {
+                    Some(Ok(name)) => {
+                        log::info!("Security key {} verified", name);
+                        self.update_failure(failure, true, 1);
+                        self.require_fido2.take();
+                        raii::AuthedConnID::set_session_fido2(self.session_key());
+                        self.send_logon_response().await;
+                        self.try_start_cm(
+                            self.lr.my_id.to_owned(),
+                            self.lr.my_name.to_owned(),
+                            self.authorized,
+                        );
+                    }
+                    Some(Err(err)) => {
+                        log::warn!("Security key not verified: {}", err);
+                        self.record_audit(
+                            AuditKind::Auth,
+                            json!({"method": "fido2", "result": "failure"}),
+                        );
+                        self.update_failure(failure, false, 1);
+                        self.send_login_error(crate::client::LOGIN_MSG_FIDO2_FAILED)
+                            .await;
+                    }
+                    None => {}
+                }
+            }
+        } else if let Some(message::Union::AccessReason(r)) = msg.union {
+            if !self.authorized && self.access_reason_asked {
+                let reason: String = r
+                    .reason
+                    .trim()
+                    .chars()
+                    .take(MAX_ACCESS_REASON_LEN)
+                    .collect();
+                if reason.is_empty() {
+                    self.send_login_error(crate::client::LOGIN_MSG_ACCESS_REASON_REQUIRED)
+                        .await;
+                } else if self.access_reason.is_none() {
+                    self.access_reason = Some(reason);
+                    self.request_approval(false).await;
+                    if password::approve_mode() == ApproveMode::Click
+                        || password::approve_mode() == ApproveMode::Both
+                            && !password::has_valid_password()
+                    {
+                        self.send_login_error(crate::client::LOGIN_MSG_NO_PASSWORD_ACCESS)
+                            .await;
+                    } else if !self.unattended_allowed() {
+                        self.send_login_error(crate::client::LOGIN_MSG_OUTSIDE_SCHEDULE)
+                            .await;
+                    } else {
+                        self.send_login_error(crate::client::LOGIN_MSG_PASSWORD_EMPTY)
+                            .await;
+                    }
+                }
+            }
         } else if let Some(message::Union::TestDelay(t)) = msg.union {
             if t.from_client {
                 let mut msg_out = Message::new();
@@ -1872,6 +3489,7 @@ impl Connection {
                         .lock()
                         .unwrap()
                         .user_network_delay(self.inner.id(), new_delay);
+                    bandwidth::on_feedback(self.inner.id(), new_delay, t.received_bytes);
                     self.network_delay = new_delay;
                 }
                 self.delay_response_instant = Instant::now();
@@ -1885,20 +3503,18 @@ impl Connection {
                     .unwrap()
                     .retain(|_, v| v.0.elapsed() < Duration::from_secs(10));
                 let uuid_old = SWITCH_SIDES_UUID.lock().unwrap().remove(&lr.my_id);
-                if let Ok(uuid) = uuid::Uuid::from_slice(_s.uuid.to_vec().as_ref()) {
-                    if let Some((_instant, uuid_old)) = uuid_old {
-                        if uuid == uuid_old {
-                            self.from_switch = true;
-                            self.send_logon_response().await;
-                            self.try_start_cm(
-                                lr.my_id.clone(),
-                                lr.my_name.clone(),
-                                self.authorized,
-                            );
-                            #[cfg(not(any(target_os = "android", target_os = "ios")))]
-                            self.try_start_cm_ipc();
-                        }
-                    }
+                let uuid = uuid::Uuid::from_slice(_s.uuid.to_vec().as_ref()).ok();
+                if uuid.is_some() && uuid_old.map(|(_, uuid_old)| uuid_old) == uuid {
+                    self.from_switch = true;
+                    self.send_logon_response().await;
+                    self.try_start_cm(lr.my_id.clone(), lr.my_name.clone(), self.authorized);
+                    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                    self.try_start_cm_ipc();
+                } else if let Some(handoff) = uuid.as_ref().and_then(handoff::take) {
+                    self.take_over(&lr, handoff).await;
+                } else if uuid.is_some() {
+                    self.send_login_error("The ticket is invalid or expired")
+                        .await;
                 }
             }
         } else if self.authorized {
@@ -1906,6 +3522,26 @@ impl Connection {
                 return true;
             }
             match msg.union {
+                Some(message::Union::Multipath(mp)) => match mp.union {
+                    Some(multipath::Union::Request(_)) => {
+                        // counted from the request on, the token is the first message counted
+                        // by the peer, a later request asks for another token only
+                        if !self.stream.is_resend_enabled() {
+                            self.stream.enable_resend();
+                            self.stream.on_received();
+                        }
+                        let token = self.backup.new_token();
+                        self.send(crate::backup_path::token_msg(token)).await;
+                    }
+                    Some(multipath::Union::SwitchPath(received)) => {
+                        // the answer of the peer after this side has moved
+                        if let Err(err) = self.stream.resume(received).await {
+                            self.on_close(&err.to_string(), true).await;
+                            return false;
+                        }
+                    }
+                    _ => {}
+                },
                 #[allow(unused_mut)]
                 Some(message::Union::MouseEvent(mut me)) => {
                     #[cfg(any(target_os = "android", target_os = "ios"))]
@@ -1913,7 +3549,7 @@ impl Connection {
                         log::debug!("call_main_service_pointer_input fail:{}", e);
                     }
                     #[cfg(not(any(target_os = "android", target_os = "ios")))]
-                    if self.peer_keyboard_enabled() {
+                    if self.peer_mouse_input_enabled() {
                         if is_left_up(&me) {
                             CLICK_TIME.store(get_time(), Ordering::SeqCst);
                         } else {
@@ -1921,6 +3557,7 @@ impl Connection {
                         }
                         #[cfg(target_os = "macos")]
                         self.retina.on_mouse_event(&mut me, self.display_idx);
+                        presenter::on_mouse_event(&me);
                         self.input_mouse(me, self.inner.id());
                     }
                     self.update_auto_disconnect_timer();
@@ -1955,12 +3592,42 @@ impl Connection {
                         log::debug!("call_main_service_pointer_input fail:{}", e);
                     }
                     #[cfg(not(any(target_os = "android", target_os = "ios")))]
-                    if self.peer_keyboard_enabled() {
+                    if self.peer_mouse_input_enabled() {
                         MOUSE_MOVE_TIME.store(get_time(), Ordering::SeqCst);
+                        #[cfg(target_os = "macos")]
+                        let mut pde = pde;
+                        #[cfg(target_os = "macos")]
+                        if let Some(pointer_device_event::Union::PenEvent(pen)) = pde.union.as_mut()
+                        {
+                            self.retina.on_pen_event(pen, self.display_idx);
+                        }
                         self.input_pointer(pde, self.inner.id());
                     }
                     self.update_auto_disconnect_timer();
                 }
+                Some(message::Union::GamepadEvent(_evt)) => {
+                    #[cfg(any(target_os = "windows", target_os = "linux"))]
+                    if self.peer_key_input_enabled() {
+                        gamepad::handle(self.inner.id(), _evt);
+                    }
+                    self.update_auto_disconnect_timer();
+                }
+                #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                Some(message::Union::ImeInput(ime)) => {
+                    match ime.union {
+                        Some(ime_input::Union::Composition(text)) => {
+                            self.ime_composing = !text.is_empty();
+                        }
+                        Some(ime_input::Union::Commit(text)) => {
+                            self.ime_composing = false;
+                            if self.peer_key_input_enabled() && !text.is_empty() {
+                                self.tx_input.send(MessageInput::Text(text)).ok();
+                            }
+                        }
+                        _ => {}
+                    }
+                    self.update_auto_disconnect_timer();
+                }
                 #[cfg(any(target_os = "ios"))]
                 Some(message::Union::KeyEvent(..)) => {}
                 #[cfg(any(target_os = "android"))]
@@ -2016,7 +3683,17 @@ impl Connection {
                 }
                 #[cfg(not(any(target_os = "android", target_os = "ios")))]
                 Some(message::Union::KeyEvent(me)) => {
-                    if self.peer_keyboard_enabled() {
+                    let enabled = if me.clipboard_typing {
+                        self.peer_clipboard_typing_enabled()
+                    } else {
+                        self.peer_key_input_enabled()
+                    };
+                    if self.ime_composing
+                        && (me.down || me.press)
+                        && me.mode.enum_value_or(KeyboardMode::Legacy) == KeyboardMode::Legacy
+                    {
+                        // typed into the input method of the peer, the text is committed later
+                    } else if enabled {
                         if is_enter(&me) {
                             CLICK_TIME.store(get_time(), Ordering::SeqCst);
                         }
@@ -2073,7 +3750,12 @@ impl Connection {
                 Some(message::Union::Clipboard(cb)) => {
                     if self.clipboard {
                         #[cfg(not(any(target_os = "android", target_os = "ios")))]
-                        update_clipboard(vec![cb], ClipboardSide::Host);
+                        if self.dlp_allows_clipboard(
+                            std::slice::from_ref(&cb),
+                            dlp::Direction::Incoming,
+                        ) {
+                            update_clipboard(vec![cb], ClipboardSide::Host);
+                        }
                         #[cfg(all(feature = "flutter", target_os = "android"))]
                         {
                             let content = if cb.compress {
@@ -2094,10 +3776,11 @@ impl Connection {
                         }
                     }
                 }
-                Some(message::Union::MultiClipboards(_mcb)) =>
-                {
+                Some(message::Union::MultiClipboards(_mcb)) => {
                     #[cfg(not(any(target_os = "android", target_os = "ios")))]
-                    if self.clipboard {
+                    if self.clipboard
+                        && self.dlp_allows_clipboard(&_mcb.clipboards, dlp::Direction::Incoming)
+                    {
                         update_clipboard(_mcb.clipboards, ClipboardSide::Host);
                     }
                 }
@@ -2106,6 +3789,8 @@ impl Connection {
                     #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
                     if let Some(clip) = msg_2_clip(_clip) {
                         log::debug!("got clipfile from client peer");
+                        let clip = self.apply_clipboard_file_dlp(clip, dlp::Direction::Incoming);
+                        self.record_clipboard_file_audit(&clip, false);
                         self.send_to_cm(ipc::Data::ClipboardFile(clip))
                     }
                 }
@@ -2123,6 +3808,9 @@ impl Connection {
                                 Some(file_action::Union::Send(s)) => {
                                     job_id = Some(s.id);
                                 }
+                                Some(file_action::Union::ReadRange(r)) => {
+                                    job_id = Some(r.id);
+                                }
                                 Some(file_action::Union::RemoveFile(rf)) => {
                                     job_id = Some(rf.id);
                                 }
@@ -2135,6 +3823,9 @@ impl Connection {
                                 Some(file_action::Union::RemoveDir(rd)) => {
                                     job_id = Some(rd.id);
                                 }
+                                Some(file_action::Union::UndoRemove(u)) => {
+                                    job_id = Some(u.id);
+                                }
                                 _ => {}
                             }
                             if let Some(job_id) = job_id {
@@ -2150,10 +3841,50 @@ impl Connection {
                             Some(file_action::Union::AllFiles(f)) => {
                                 match fs::get_recursive_files(&f.path, f.include_hidden) {
                                     Err(err) => {
-                                        self.send(fs::new_error(f.id, err, -1)).await;
+                                        self.send(fs::new_error(f.id, err, -1)).await;
+                                    }
+                                    Ok(files) => {
+                                        self.send(fs::new_dir(f.id, f.path, files)).await;
+                                    }
+                                }
+                            }
+                            Some(file_action::Union::ReadRange(r)) => {
+                                let (path, offset, length) = (r.path.clone(), r.offset, r.length);
+                                let res = tokio::task::spawn_blocking(move || {
+                                    fs::read_range(&path, offset, length)
+                                })
+                                .await
+                                .map_err(|e| e.into())
+                                .and_then(|res| res)
+                                .and_then(|(data, size)| {
+                                    // checked as the whole file, the parts read add up to it
+                                    let file = FileEntry {
+                                        name: r.path.clone(),
+                                        size,
+                                        ..Default::default()
+                                    };
+                                    self.check_dlp_files(&[file], dlp::Direction::Outgoing)?;
+                                    Ok((data, size))
+                                });
+                                match res {
+                                    Err(err) => {
+                                        self.send(fs::new_error(r.id, err, -1)).await;
                                     }
-                                    Ok(files) => {
-                                        self.send(fs::new_dir(f.id, f.path, files)).await;
+                                    Ok((data, size)) => {
+                                        let bytes = data.len() as u64;
+                                        self.send(fs::new_range(r.id, r.offset, data, size)).await;
+                                        self.post_file_audit(
+                                            FileAuditType::RemoteSend,
+                                            &r.path,
+                                            vec![(String::new(), size as _)],
+                                            json!({"offset": r.offset, "length": bytes}),
+                                        );
+                                        self.record_file_transfer(TransferEvent::of_file(
+                                            Direction::Send,
+                                            &r.path,
+                                            size,
+                                            bytes,
+                                        ));
                                     }
                                 }
                             }
@@ -2172,16 +3903,31 @@ impl Connection {
                                     s.include_hidden,
                                     false,
                                     od,
-                                ) {
+                                    s.link_policy.enum_value_or_default(),
+                                )
+                                .and_then(|job| {
+                                    self.check_dlp_files(job.files(), dlp::Direction::Outgoing)?;
+                                    Ok(job)
+                                }) {
                                     Err(err) => {
                                         self.send(fs::new_error(id, err, 0)).await;
                                     }
                                     Ok(mut job) => {
+                                        job.set_attributes(
+                                            fs::attributes::Options::from_proto(
+                                                s.metadata.as_ref(),
+                                            ),
+                                            true,
+                                        );
                                         self.send(fs::new_dir(id, path, job.files().to_vec()))
                                             .await;
                                         let mut files = job.files().to_owned();
                                         job.is_remote = true;
                                         job.conn_id = self.inner.id();
+                                        job.set_verify(s.verify);
+                                        if s.uncompressed {
+                                            job.set_uncompressed(true);
+                                        }
                                         self.read_jobs.push(job);
                                         self.file_timer =
                                             crate::rustdesk_interval(time::interval(MILLI1));
@@ -2205,6 +3951,12 @@ impl Connection {
                                 let od = can_enable_overwrite_detection(get_version_number(
                                     &self.lr.version,
                                 ));
+                                if let Err(err) =
+                                    self.check_dlp_files(&r.files, dlp::Direction::Incoming)
+                                {
+                                    self.send(fs::new_error(r.id, err, 0)).await;
+                                    return true;
+                                }
                                 self.send_fs(ipc::FS::NewWrite {
                                     path: r.path.clone(),
                                     id: r.id,
@@ -2218,6 +3970,16 @@ impl Connection {
                                     overwrite_detection: od,
                                     total_size: r.total_size,
                                     conn_id: self.inner.id(),
+                                    verify: r.verify,
+                                    attributes: fs::attributes::Options::from_proto(
+                                        r.metadata.as_ref(),
+                                    ),
+                                    file_attributes: r
+                                        .files
+                                        .iter()
+                                        .map(fs::attributes::EntryAttributes::of)
+                                        .collect(),
+                                    conflict_policy: r.conflict_policy.value(),
                                 });
                                 self.post_file_audit(
                                     FileAuditType::RemoteReceive,
@@ -2236,6 +3998,7 @@ impl Connection {
                                     path: d.path.clone(),
                                     id: d.id,
                                     recursive: d.recursive,
+                                    trash: d.trash,
                                 });
                                 self.file_remove_log_control.on_remove_dir(d);
                             }
@@ -2244,9 +4007,16 @@ impl Connection {
                                     path: f.path.clone(),
                                     id: f.id,
                                     file_num: f.file_num,
+                                    trash: f.trash,
                                 });
                                 self.file_remove_log_control.on_remove_file(f);
                             }
+                            Some(file_action::Union::UndoRemove(u)) => {
+                                self.send_fs(ipc::FS::UndoRemove {
+                                    id: u.id,
+                                    remove_ids: u.remove_ids,
+                                });
+                            }
                             Some(file_action::Union::Create(c)) => {
                                 self.send_fs(ipc::FS::CreateDir {
                                     path: c.path.clone(),
@@ -2270,6 +4040,11 @@ impl Connection {
                                         "transfer".to_string(),
                                         fs::serialize_transfer_job(job, false, true, ""),
                                     )));
+                                    self.record_file_transfer(TransferEvent::of(
+                                        job,
+                                        Direction::Send,
+                                        Outcome::Cancelled,
+                                    ));
                                 }
                                 fs::remove_job(c.id, &mut self.read_jobs);
                             }
@@ -2278,6 +4053,29 @@ impl Connection {
                                     job.confirm(&r);
                                 }
                             }
+                            Some(file_action::Union::RateLimit(r)) => {
+                                if let Some(job) = fs::get_job(r.id, &mut self.read_jobs) {
+                                    job.set_rate_limit(r.kbps);
+                                }
+                            }
+                            Some(file_action::Union::Search(s)) => {
+                                self.send_fs(ipc::FS::Search {
+                                    id: s.id,
+                                    path: s.path,
+                                    pattern: s.pattern,
+                                    include_hidden: s.include_hidden,
+                                    max_depth: s.max_depth,
+                                    timeout_secs: s.timeout_secs,
+                                });
+                            }
+                            Some(file_action::Union::ConflictResolution(r)) => {
+                                self.send_fs(ipc::FS::ResolveConflict {
+                                    id: r.id,
+                                    file_num: r.file_num,
+                                    policy: r.policy.value(),
+                                    remember: r.remember,
+                                });
+                            }
                             Some(file_action::Union::Rename(r)) => {
                                 self.send_fs(ipc::FS::Rename {
                                     id: r.id,
@@ -2305,6 +4103,8 @@ impl Connection {
                             file_num: block.file_num,
                             data: block.data,
                             compressed: block.compressed,
+                            copy_blks: block.copy_blks,
+                            checksum: block.checksum,
                         });
                     }
                     Some(file_response::Union::Done(d)) => {
@@ -2319,6 +4119,7 @@ impl Connection {
                         file_size: d.file_size,
                         last_modified: d.last_modified,
                         is_upload: true,
+                        delta: d.delta,
                     }),
                     Some(file_response::Union::Error(e)) => {
                         self.send_fs(ipc::FS::WriteError {
@@ -2329,6 +4130,30 @@ impl Connection {
                     }
                     _ => {}
                 },
+                #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                Some(message::Union::TerminalAction(action)) => {
+                    if let Some(terminal_action::Union::Open(open)) = &action.union {
+                        if !self.terminal || self.file_transfer.is_some() {
+                            let mut response = TerminalResponse::new();
+                            response.set_opened(TerminalOpened {
+                                id: open.id,
+                                error: "No permission of terminal".to_owned(),
+                                ..Default::default()
+                            });
+                            let mut msg_out = Message::new();
+                            msg_out.set_terminal_response(response);
+                            self.send(msg_out).await;
+                            return true;
+                        }
+                        self.record_audit(
+                            AuditKind::Terminal,
+                            json!({"action": "open", "id": open.id}),
+                        );
+                    }
+                    if self.terminal {
+                        self.terminals.handle(action, &self.inner);
+                    }
+                }
                 Some(message::Union::Misc(misc)) => match misc.union {
                     Some(misc::Union::SwitchDisplay(s)) => {
                         self.handle_switch_display(s).await;
@@ -2346,11 +4171,49 @@ impl Connection {
                     Some(misc::Union::TogglePrivacyMode(t)) => {
                         self.toggle_privacy_mode(t).await;
                     }
-                    Some(misc::Union::ChatMessage(c)) => {
-                        self.send_to_cm(ipc::Data::ChatMessage { text: c.text });
+                    Some(misc::Union::ChatMessage(mut c)) => {
+                        if let Some(attachment) = c.attachment.take() {
+                            let error = if !self.file_transfer_enabled() {
+                                Some("No permission of file transfer".to_owned())
+                            } else {
+                                self.check_chat_attachment(&attachment, Direction::Receive)
+                                    .err()
+                                    .map(|err| err.to_string())
+                            };
+                            match error {
+                                // the cm answers once saved
+                                None => self.send_to_cm(ipc::Data::ChatAttachment {
+                                    id: c.id,
+                                    name: attachment.name,
+                                    data: attachment.data,
+                                    compressed: attachment.compressed,
+                                    image: attachment.image,
+                                }),
+                                Some(error) => {
+                                    if c.id != 0 {
+                                        self.send(crate::chat::new_receipt(c.id, error)).await;
+                                    }
+                                }
+                            }
+                        } else {
+                            self.send_to_cm(ipc::Data::ChatMessage {
+                                text: c.text,
+                                id: 0,
+                            });
+                            if c.id != 0 {
+                                self.send(crate::chat::new_receipt(c.id, "".to_owned()))
+                                    .await;
+                            }
+                        }
                         self.chat_unanswered = true;
                         self.update_auto_disconnect_timer();
                     }
+                    Some(misc::Union::ChatReceipt(r)) => {
+                        self.send_to_cm(ipc::Data::ChatReceipt {
+                            id: r.id,
+                            error: r.error,
+                        });
+                    }
                     Some(misc::Union::Option(o)) => {
                         self.update_options(&o).await;
                     }
@@ -2397,6 +4260,73 @@ impl Connection {
                             }
                         }
                     }
+                    Some(misc::Union::RequestInputControl(request)) => {
+                        if request {
+                            if self.can_input() {
+                                input_control::request(self.inner.id());
+                            }
+                        } else {
+                            input_control::release(self.inner.id());
+                        }
+                        self.input_control_check();
+                    }
+                    #[cfg(feature = "flutter")]
+                    Some(misc::Union::RequestSessionHandoff(_)) => {
+                        self.send_handoff_ticket().await;
+                    }
+                    Some(misc::Union::WatchSystemStats(on)) => {
+                        if on
+                            && self.file_transfer.is_none()
+                            && self.port_forward_socket.is_none()
+                            && !self.terminal_session
+                            && Connection::permission(keys::OPTION_ALLOW_SYSTEM_STATS)
+                        {
+                            if self.system_stats.is_none() {
+                                self.system_stats = Some(super::system_stats::Sampler::new());
+                            }
+                        } else {
+                            self.system_stats = None;
+                        }
+                    }
+                    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                    Some(misc::Union::ListProcesses(req)) => {
+                        self.list_processes(req).await;
+                    }
+                    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                    Some(misc::Union::KillProcess(pid)) => {
+                        self.kill_process(pid).await;
+                    }
+                    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                    Some(misc::Union::ListServices(_)) => {
+                        self.list_services().await;
+                    }
+                    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                    Some(misc::Union::ServiceControl(control)) => {
+                        self.control_service(control).await;
+                    }
+                    Some(misc::Union::ListCameras(_)) => {
+                        self.list_cameras().await;
+                    }
+                    Some(misc::Union::CameraRequest(req)) => {
+                        self.request_camera(req).await;
+                    }
+                    // the documents leave this side as the files do
+                    #[cfg(any(target_os = "linux", target_os = "macos"))]
+                    Some(misc::Union::RemotePrinter(on)) => {
+                        let on = on && self.file && crate::printer::installed();
+                        log::info!("The documents printed on the side of the peer: {}", on);
+                        super::print_jobs::set_receiver(
+                            self.inner.id(),
+                            self.tx_print_jobs.clone(),
+                            on,
+                        );
+                    }
+                    Some(misc::Union::RestartInSafeMode(_)) => {
+                        #[cfg(windows)]
+                        if self.restart && !self.restart_in_safe_mode().await {
+                            return false;
+                        }
+                    }
                     #[cfg(windows)]
                     Some(misc::Union::ElevationRequest(r)) => match r.union {
                         Some(elevation_request::Union::Direct(_)) => {
@@ -2412,15 +4342,46 @@ impl Connection {
                         _ => {}
                     },
                     Some(misc::Union::AudioFormat(format)) => {
-                        if !self.disable_audio {
+                        if self.voice_call || !self.disable_audio {
                             // Drop the audio sender previously.
                             drop(std::mem::replace(&mut self.audio_sender, None));
-                            self.audio_sender = Some(start_audio_thread());
+                            #[allow(unused_mut)]
+                            let mut outputs = vec![];
+                            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                            {
+                                if self.virtual_mic.is_none() {
+                                    self.virtual_mic = virtual_mic::open();
+                                }
+                                outputs.extend(
+                                    self.virtual_mic.as_ref().map(|m| m.output().to_owned()),
+                                );
+                            }
+                            self.audio_sender = Some(start_audio_thread_with_outputs(outputs));
                             self.audio_sender
                                 .as_ref()
                                 .map(|a| allow_err!(a.send(MediaData::AudioFormat(format))));
                         }
                     }
+                    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                    Some(misc::Union::AudioInput(device)) => {
+                        if !self.audio {
+                            log::warn!("audio is not allowed to choose the input");
+                        } else if device.is_empty() {
+                            audio_service::set_peer_input_device(self.inner.id(), None);
+                        } else if audio_service::get_input_devices().contains(&device) {
+                            audio_service::set_peer_input_device(self.inner.id(), Some(device));
+                        } else {
+                            log::warn!("no audio input {device}");
+                        }
+                    }
+                    #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+                    Some(misc::Union::VolumeControl(control)) => {
+                        if self.audio {
+                            self.control_volume(control);
+                        } else {
+                            log::warn!("audio is not allowed to control the volume");
+                        }
+                    }
                     #[cfg(feature = "flutter")]
                     Some(misc::Union::SwitchSidesRequest(s)) => {
                         if let Ok(uuid) = uuid::Uuid::from_slice(&s.uuid.to_vec()[..]) {
@@ -2493,7 +4454,7 @@ impl Connection {
                     _ => {}
                 },
                 Some(message::Union::AudioFrame(frame)) => {
-                    if !self.disable_audio {
+                    if self.voice_call || !self.disable_audio {
                         if let Some(sender) = &self.audio_sender {
                             allow_err!(sender.send(MediaData::AudioFrame(Box::new(frame))));
                         } else {
@@ -2518,6 +4479,21 @@ impl Connection {
                 Some(message::Union::VoiceCallResponse(_response)) => {
                     // TODO: Maybe we can do a voice call from cm directly.
                 }
+                Some(message::Union::ScreenshotRequest(request)) => {
+                    if self.is_remote() {
+                        self.take_screenshot(request);
+                    }
+                }
+                Some(message::Union::WakeOnLan(wake)) => {
+                    self.wake_on_lan(wake).await;
+                }
+                Some(message::Union::Annotation(annotation)) => {
+                    if self.annotation_enabled() && !annotation.has_board() {
+                        if let Ok(bytes) = annotation.write_to_bytes() {
+                            self.send_to_cm(ipc::Data::Annotation(bytes));
+                        }
+                    }
+                }
                 _ => {}
             }
         }
@@ -2543,6 +4519,23 @@ impl Connection {
             .lock()
             .unwrap()
             .insert(self.ip.clone(), failure);
+        if let Some(lockout) = super::login_lockout::on_failure(&self.lr.my_id, &self.ip) {
+            log::warn!(
+                "Locked out the logins of {} from {} for {} minutes",
+                self.lr.my_id,
+                self.ip,
+                lockout / 60_000
+            );
+            Self::post_alarm_audit(
+                AlarmAuditType::LoginLockout,
+                json!({
+                            "ip": self.ip,
+                            "id": self.lr.my_id.clone(),
+                            "name": self.lr.my_name.clone(),
+                            "minutes": lockout / 60_000,
+                }),
+            );
+        }
     }
 
     async fn check_failure(&mut self, i: usize) -> (((i32, i32, i32), i32), bool) {
@@ -2553,7 +4546,14 @@ impl Connection {
             .map(|x| x.clone())
             .unwrap_or((0, 0, 0));
         let time = (get_time() / 60_000) as i32;
-        let res = if failure.2 > 30 {
+        let res = if let Some(wait) = super::login_lockout::wait(&self.lr.my_id, &self.ip) {
+            self.send_login_error(format!(
+                "Too many wrong attempts, please try {} seconds later",
+                (wait + 999) / 1000
+            ))
+            .await;
+            false
+        } else if failure.2 > 30 {
             self.send_login_error("Too many wrong attempts").await;
             Self::post_alarm_audit(
                 AlarmAuditType::ExceedThirtyAttempts,
@@ -2798,6 +4798,8 @@ impl Connection {
                     crate::get_default_sound_input(),
                     false,
                 );
+                self.voice_call = true;
+                self.update_audio_subscription();
                 self.send_to_cm(Data::StartVoiceCall);
             } else {
                 self.send_to_cm(Data::CloseVoiceCall("".to_owned()));
@@ -2810,6 +4812,15 @@ impl Connection {
 
     pub async fn close_voice_call(&mut self) {
         crate::audio_service::set_voice_call_input_device(None, true);
+        if std::mem::take(&mut self.voice_call) {
+            self.update_audio_subscription();
+        }
+        // stop playing before the virtual microphone is removed
+        self.audio_sender = None;
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        {
+            self.virtual_mic = None;
+        }
         // Notify the connection manager that the voice call has been closed.
         self.send_to_cm(Data::CloseVoiceCall("".to_owned()));
     }
@@ -2840,6 +4851,23 @@ impl Connection {
                 .unwrap()
                 .user_custom_fps(self.inner.id(), o.custom_fps as _);
         }
+        if o.bandwidth_limit != 0 {
+            let kbps = (o.bandwidth_limit > 0).then_some(o.bandwidth_limit as u32);
+            bandwidth::set_client_limit(self.inner.id(), kbps);
+        }
+        if let Some(q) = o.audio_quality.as_ref() {
+            audio_service::set_quality(self.inner.id(), q.clone());
+        }
+        if let Ok(q) = o.denoise_audio.enum_value() {
+            if q != BoolOption::NotSet {
+                audio_service::set_denoise(self.inner.id(), q == BoolOption::Yes);
+            }
+        }
+        if let Ok(q) = o.presenter_mode.enum_value() {
+            if q != BoolOption::NotSet {
+                presenter::set(self.inner.id(), q == BoolOption::Yes);
+            }
+        }
         if let Some(q) = o.supported_decoding.clone().take() {
             scrap::codec::Encoder::update(scrap::codec::EncodingUpdate::Update(self.inner.id(), q));
         }
@@ -2887,13 +4915,7 @@ impl Connection {
         if let Ok(q) = o.disable_audio.enum_value() {
             if q != BoolOption::NotSet {
                 self.disable_audio = q == BoolOption::Yes;
-                if let Some(s) = self.server.upgrade() {
-                    s.write().unwrap().subscribe(
-                        super::audio_service::NAME,
-                        self.inner.clone(),
-                        self.audio_enabled(),
-                    );
-                }
+                self.update_audio_subscription();
             }
         }
         #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
@@ -2985,6 +5007,12 @@ impl Connection {
                 "Unsupported. 1 Multi-screen is not supported. 2 Please confirm the license is activated.".to_string(),
                 impl_key,
             )
+        } else if !self.policy_allows("privacy_mode") {
+            crate::common::make_privacy_mode_msg_with_details(
+                back_notification::PrivacyModeState::PrvOnFailedDenied,
+                "Not allowed by the policy".to_string(),
+                impl_key,
+            )
         } else {
             let is_pre_privacy_on = privacy_mode::is_in_privacy_mode();
             let pre_impl_key = privacy_mode::get_cur_impl_key();
@@ -3054,6 +5082,7 @@ impl Connection {
                 ),
             }
         };
+        self.record_privacy_mode_audit(true, &msg_out);
         self.send(msg_out).await;
     }
 
@@ -3068,6 +5097,7 @@ impl Connection {
         } else {
             Self::turn_off_privacy_to_msg(self.inner.id)
         };
+        self.record_privacy_mode_audit(false, &msg_out);
         self.send(msg_out).await;
     }
 
@@ -3110,6 +5140,13 @@ impl Connection {
         // But it's not necessary now and we have to consider two audio services(client, server).
         crate::audio_service::set_voice_call_input_device(None, true);
         log::info!("#{} Connection closed: {}", self.inner.id(), reason);
+        self.record_audit(
+            AuditKind::Connection,
+            json!({"action": "close", "reason": reason}),
+        );
+        if self.authorized {
+            self.notify(NotifyEvent::SessionEnd, json!({"reason": reason}));
+        }
         if lock && self.lock_after_session_end && self.keyboard {
             #[cfg(not(any(target_os = "android", target_os = "ios")))]
             lock_screen().await;
@@ -3124,6 +5161,29 @@ impl Connection {
         let data = ipc::Data::Close;
         self.tx_to_cm.send(data).ok();
         self.port_forward_socket.take();
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        self.terminals.close_all();
+    }
+
+    // false if the session is closed for the failure
+    #[cfg(windows)]
+    async fn restart_in_safe_mode(&mut self) -> bool {
+        if let Err(e) = crate::platform::windows::set_safe_mode_boot() {
+            log::error!("Failed to set the safe mode boot: {}", e);
+            crate::platform::windows::clear_safe_mode_boot();
+            self.send_close_reason_no_retry("Failed to restart into the safe mode")
+                .await;
+            self.on_close("safe mode boot failure", false).await;
+            return false;
+        }
+        match system_shutdown::force_reboot() {
+            Ok(_) => log::info!("Restart into the safe mode by the peer"),
+            Err(e) => {
+                log::error!("Failed to restart: {}", e);
+                crate::platform::windows::clear_safe_mode_boot();
+            }
+        }
+        true
     }
 
     // The `reason` should be consistent with `check_if_retry` if not empty
@@ -3148,9 +5208,64 @@ impl Connection {
         });
     }
 
+    // the session has moved to the backup path
+    fn on_switched(&mut self, id: i32) {
+        if let Some((relay_server, _)) = self.migrate_to.take() {
+            log::info!("relayed session moved to {}", relay_server);
+            self.relay_server = relay_server;
+        }
+        bandwidth::enable_congestion_control(id);
+        self.mark_dscp();
+    }
+
+    // the video frames sent a second since the last call
+    fn take_video_fps(&mut self) -> u32 {
+        let (frames, since) = std::mem::replace(&mut self.video_frames, (0, Instant::now()));
+        let elapsed = since.elapsed().as_secs_f32();
+        if elapsed > 0. {
+            (frames as f32 / elapsed).round() as _
+        } else {
+            0
+        }
+    }
+
+    fn mark_dscp(&self) {
+        crate::dscp::mark(
+            &self.stream,
+            self.file_transfer.is_some() || self.port_forward_socket.is_some(),
+        );
+    }
+
+    // asks the peer to move a long relayed session to a better relay server
+    async fn check_relay(&mut self) {
+        if !self.authorized
+            || self.port_forward_socket.is_some()
+            || self.relay_server.is_empty()
+            || self.relay_checked.elapsed() < RELAY_CHECK_INTERVAL
+        {
+            return;
+        }
+        self.relay_checked = Instant::now();
+        if let Some((_, asked)) = self.migrate_to.as_ref() {
+            if asked.elapsed() < RELAY_CHECK_INTERVAL {
+                return;
+            }
+        }
+        if let Some(relay_server) = crate::relay_select::better_than(&self.relay_server) {
+            log::info!(
+                "asking to move the relayed session from {} to {}",
+                self.relay_server,
+                relay_server
+            );
+            self.migrate_to = Some((relay_server.clone(), Instant::now()));
+            self.send(crate::backup_path::migrate_msg(relay_server))
+                .await;
+        }
+    }
+
     #[inline]
     async fn send(&mut self, msg: Message) {
-        allow_err!(self.stream.send(&msg).await);
+        allow_err!(self.stream.send_on(Channel::of(&msg), &msg).await);
     }
 
     pub fn alive_conns() -> Vec<i32> {
@@ -3162,6 +5277,7 @@ impl Connection {
         if self.portable.is_installed
             || self.file_transfer.is_some()
             || self.port_forward_socket.is_some()
+            || self.terminal_session
             || !self.keyboard
         {
             return;
@@ -3209,6 +5325,106 @@ impl Connection {
         }
     }
 
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    fn secure_input_check(&mut self) {
+        if !self.authorized
+            || self.file_transfer.is_some()
+            || self.port_forward_socket.is_some()
+            || self.terminal_session
+            || !self.peer_keyboard_enabled()
+        {
+            return;
+        }
+        let secure_input = crate::platform::is_secure_input();
+        // the portable service injects to the secure desktop, the prompt of UAC is told already
+        #[cfg(windows)]
+        let secure_input = secure_input
+            && !portable_client::running()
+            && !*crate::video_service::IS_UAC_RUNNING.lock().unwrap();
+        if secure_input != self.last_secure_input {
+            self.last_secure_input = secure_input;
+            let mut misc = Misc::new();
+            misc.set_secure_input(secure_input);
+            let mut msg = Message::new();
+            msg.set_misc(misc);
+            self.inner.send(msg.into());
+        }
+    }
+
+    // Tells the peer who holds the control of the input, and the cm whether it asks for it.
+    fn input_control_check(&mut self) {
+        let id = self.inner.id();
+        let state = if self.can_input() {
+            input_control::join(id, &self.lr.my_name);
+            input_control::state(id)
+        } else {
+            input_control::leave(id);
+            None
+        };
+        if state == self.last_input_control {
+            return;
+        }
+        let holding = |s: &Option<InputControl>| s.as_ref().map(|s| s.holding).unwrap_or(true);
+        // the keys held down are not released by the peer once it loses the control
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        if holding(&self.last_input_control) && !holding(&state) {
+            self.release_pressed_modifiers();
+        }
+        let requested = |s: &Option<InputControl>| s.as_ref().map(|s| s.requested).unwrap_or(false);
+        if requested(&self.last_input_control) != requested(&state) {
+            self.send_to_cm(ipc::Data::InputControlRequest(requested(&state)));
+        }
+        let mut misc = Misc::new();
+        misc.set_input_control(state.clone().unwrap_or_default());
+        let mut msg = Message::new();
+        msg.set_misc(misc);
+        self.inner.send(msg.into());
+        self.last_input_control = state;
+    }
+
+    // Tells the peer whether its session is recorded on this side.
+    fn record_incoming_check(&mut self) {
+        if !self.authorized || self.file_transfer.is_some() || self.port_forward_socket.is_some() {
+            return;
+        }
+        let recording = video_service::is_record_incoming();
+        if recording != self.last_record_incoming {
+            self.last_record_incoming = recording;
+            let mut misc = Misc::new();
+            misc.set_server_record_status(recording);
+            let mut msg = Message::new();
+            msg.set_misc(misc);
+            self.inner.send(msg.into());
+        }
+    }
+
+    // Sets the master volume, and replies with it.
+    #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
+    fn control_volume(&mut self, control: VolumeControl) {
+        let res = match control.union {
+            Some(volume_control::Union::Level(level)) => crate::platform::set_master_volume(level),
+            Some(volume_control::Union::Mute(mute)) => crate::platform::set_master_mute(mute),
+            _ => Ok(()),
+        };
+        if let Err(e) = res {
+            log::error!("failed to set the volume: {e}");
+        }
+        match crate::platform::get_master_volume() {
+            Ok((level, mute)) => {
+                let mut misc = Misc::new();
+                misc.set_volume(Volume {
+                    level,
+                    mute,
+                    ..Default::default()
+                });
+                let mut msg = Message::new();
+                msg.set_misc(misc);
+                self.inner.send(msg.into());
+            }
+            Err(e) => log::error!("failed to get the volume: {e}"),
+        }
+    }
+
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     fn release_pressed_modifiers(&mut self) {
         for modifier in self.pressed_modifiers.iter() {
@@ -3231,6 +5447,50 @@ impl Connection {
         }
     }
 
+    // Warns the peer before the session is closed or downgraded to view only for inactivity, and
+    // closes or downgrades it then, true if closed.
+    async fn check_auto_disconnect(&mut self) -> bool {
+        let Some((instant, minute)) = self.auto_disconnect_timer.as_ref() else {
+            return false;
+        };
+        let left = (minute * 60).saturating_sub(instant.elapsed().as_secs());
+        let view_only = Config::get_option(keys::OPTION_AUTO_DISCONNECT_ACTION) == "view-only";
+        if left == 0 {
+            self.inactivity_warned = false;
+            if view_only && self.keyboard {
+                log::info!("Downgrade to view only due to inactivity");
+                self.auto_disconnect_timer = None;
+                self.revoke_permission("keyboard", "inactivity").await;
+                if let Some(s) = self.server.upgrade() {
+                    s.write().unwrap().subscribe(
+                        NAME_CURSOR,
+                        self.inner.clone(),
+                        self.show_remote_cursor,
+                    );
+                }
+                return false;
+            }
+            self.send_close_reason_no_retry("Connection failed due to inactivity")
+                .await;
+            self.on_close("auto disconnect", true).await;
+            return true;
+        }
+        let warn = left <= INACTIVITY_WARNING_SECS;
+        if warn != self.inactivity_warned {
+            self.inactivity_warned = warn;
+            let mut misc = Misc::new();
+            misc.set_inactivity_warning(InactivityWarning {
+                seconds: if warn { left as _ } else { 0 },
+                view_only: view_only && self.keyboard,
+                ..Default::default()
+            });
+            let mut msg = Message::new();
+            msg.set_misc(misc);
+            self.send(msg).await;
+        }
+        false
+    }
+
     fn update_auto_disconnect_timer(&mut self) {
         self.auto_disconnect_timer
             .as_mut()
@@ -3302,6 +5562,17 @@ impl Connection {
     }
 }
 
+/// The ids of the peers controlling this side, once each.
+pub fn authed_remote_peer_ids() -> Vec<String> {
+    let mut ids: Vec<String> = vec![];
+    for c in AUTHED_CONNS.lock().unwrap().iter() {
+        if c.1 == AuthConnType::Remote && !ids.contains(&c.2.peer_id) {
+            ids.push(c.2.peer_id.clone());
+        }
+    }
+    ids
+}
+
 pub fn insert_switch_sides_uuid(id: String, uuid: uuid::Uuid) {
     SWITCH_SIDES_UUID
         .lock()
@@ -3437,8 +5708,13 @@ async fn start_ipc(
                         if let Data::FS(ipc::FS::WriteBlock{id,
                             file_num,
                             data,
-                            compressed}) = data {
-                                stream.send(&Data::FS(ipc::FS::WriteBlock{id, file_num, data: Bytes::new(), compressed})).await?;
+                            compressed,
+                            copy_blks,
+                            checksum}) = data {
+                                stream.send(&Data::FS(ipc::FS::WriteBlock{id, file_num, data: Bytes::new(), compressed, copy_blks, checksum})).await?;
+                                stream.send_raw(data).await?;
+                        } else if let Data::ChatAttachment{id, name, data, compressed, image} = data {
+                                stream.send(&Data::ChatAttachment{id, name, data: Bytes::new(), compressed, image}).await?;
                                 stream.send_raw(data).await?;
                         } else {
                             stream.send(&data).await?;
@@ -3467,6 +5743,8 @@ pub enum AlarmAuditType {
     IpWhitelist = 0,
     ExceedThirtyAttempts = 1,
     SixAttemptsWithinOneMinute = 2,
+    IpRule = 3,
+    LoginLockout = 4,
 }
 
 pub enum FileAuditType {
@@ -3657,6 +5935,7 @@ impl Default for PortableState {
 
 impl Drop for Connection {
     fn drop(&mut self) {
+        input_control::leave(self.inner.id());
         #[cfg(not(any(target_os = "android", target_os = "ios")))]
         self.release_pressed_modifiers();
     }
@@ -3735,13 +6014,23 @@ impl Retina {
             // x and y are always 0, +1 or -1
             return;
         }
+        self.to_points(&mut e.x, &mut e.y, current);
+    }
+
+    #[inline]
+    fn on_pen_event(&mut self, e: &mut PenEvent, current: usize) {
+        self.to_points(&mut e.x, &mut e.y, current);
+    }
+
+    #[inline]
+    fn to_points(&self, x: &mut i32, y: &mut i32, current: usize) {
         let Some(d) = self.displays.get(current) else {
             return;
         };
         let s = d.scale;
-        if s > 1.0 && e.x >= d.x && e.y >= d.y && e.x < d.x + d.width && e.y < d.y + d.height {
-            e.x = d.x + ((e.x - d.x) as f64 / s) as i32;
-            e.y = d.y + ((e.y - d.y) as f64 / s) as i32;
+        if s > 1.0 && *x >= d.x && *y >= d.y && *x < d.x + d.width && *y < d.y + d.height {
+            *x = d.x + ((*x - d.x) as f64 / s) as i32;
+            *y = d.y + ((*y - d.y) as f64 / s) as i32;
         }
     }
 
@@ -3791,6 +6080,19 @@ mod raii {
                 .lock()
                 .unwrap()
                 .on_connection_close(self.0);
+            bandwidth::on_connection_close(self.0);
+            fs::throttle::set_congested(self.0, false);
+            #[cfg(any(target_os = "windows", target_os = "linux"))]
+            gamepad::on_connection_close(self.0);
+            audio_service::on_connection_close(self.0);
+            #[cfg(any(target_os = "windows", target_os = "linux"))]
+            camera_service::on_connection_close(self.0);
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            print_jobs::on_connection_close(self.0);
+            presenter::on_connection_close(self.0);
+            broadcast::on_connection_close(self.0);
+            #[cfg(feature = "flutter")]
+            handoff::on_connection_close(self.0);
         }
     }
 
@@ -3883,6 +6185,7 @@ mod raii {
                     Session {
                         random_password: password.unwrap_or_default(),
                         tfa: tfa.unwrap_or_default(),
+                        fido2: false,
                         last_recv_time: Arc::new(Mutex::new(Instant::now())),
                     },
                 );
@@ -3901,6 +6204,26 @@ mod raii {
                         last_recv_time: Arc::new(Mutex::new(Instant::now())),
                         random_password: "".to_owned(),
                         tfa: true,
+                        fido2: false,
+                    },
+                );
+            }
+        }
+
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        pub fn set_session_fido2(key: SessionKey) {
+            let mut lock = SESSIONS.lock().unwrap();
+            let session = lock.get_mut(&key);
+            if let Some(session) = session {
+                session.fido2 = true;
+            } else {
+                lock.insert(
+                    key,
+                    Session {
+                        last_recv_time: Arc::new(Mutex::new(Instant::now())),
+                        random_password: "".to_owned(),
+                        tfa: false,
+                        fido2: true,
                     },
                 );
             }
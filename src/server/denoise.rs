@@ -0,0 +1,79 @@
+// Suppress the noise of the audio captured, with nnnoiseless, RNNoise ported to Rust.
+//
+// RNNoise takes the frames of 480 samples of 48 kHz mono, in the range of i16. The channels are
+// denoised one by one. The audio of the other sample rates is sent as it is.
+
+use nnnoiseless::{DenoiseState, FRAME_SIZE};
+use std::collections::VecDeque;
+
+const SAMPLE_RATE: u32 = 48000;
+const SCALE: f32 = i16::MAX as f32;
+
+pub struct Denoiser {
+    states: Vec<Box<DenoiseState<'static>>>,
+    // the samples of each channel waiting for a whole frame
+    input: Vec<Vec<f32>>,
+    // interleaved
+    output: VecDeque<f32>,
+}
+
+impl Denoiser {
+    pub fn new(sample_rate: u32, channels: u16) -> Option<Self> {
+        if sample_rate != SAMPLE_RATE || channels == 0 {
+            return None;
+        }
+        let channels = channels as usize;
+        Some(Self {
+            states: (0..channels).map(|_| DenoiseState::new()).collect(),
+            input: vec![Vec::with_capacity(FRAME_SIZE); channels],
+            output: Default::default(),
+        })
+    }
+
+    /// The denoised audio of the same length, delayed by the samples of a frame at most.
+    pub fn process(&mut self, data: &[f32]) -> Vec<f32> {
+        let channels = self.states.len();
+        for (i, sample) in data.iter().enumerate() {
+            self.input[i % channels].push(sample * SCALE);
+        }
+        let mut frame = [0f32; FRAME_SIZE];
+        let mut denoised = vec![[0f32; FRAME_SIZE]; channels];
+        while self.input[0].len() >= FRAME_SIZE {
+            for (c, state) in self.states.iter_mut().enumerate() {
+                frame.copy_from_slice(&self.input[c][..FRAME_SIZE]);
+                self.input[c].drain(..FRAME_SIZE);
+                state.process_frame(&mut denoised[c], &frame);
+            }
+            for i in 0..FRAME_SIZE {
+                self.output
+                    .extend(denoised.iter().map(|d| (d[i] / SCALE).clamp(-1., 1.)));
+            }
+        }
+        // silence until a whole frame is denoised
+        let missing = data.len().saturating_sub(self.output.len());
+        let mut result = vec![0.; missing];
+        result.extend(self.output.drain(..data.len() - missing));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_length() {
+        assert!(Denoiser::new(16000, 2).is_none());
+        let mut denoiser = Denoiser::new(SAMPLE_RATE, 2).unwrap();
+        let mut total = 0;
+        // not the multiples of a frame
+        for _ in 0..10 {
+            let data = vec![0.1; 700];
+            let result = denoiser.process(&data);
+            assert_eq!(result.len(), data.len());
+            total += result.len();
+        }
+        assert_eq!(total, 7000);
+        assert!(denoiser.output.len() < FRAME_SIZE * 2);
+    }
+}
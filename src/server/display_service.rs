@@ -416,6 +416,22 @@ pub fn try_get_displays() -> ResultType<Vec<Display>> {
     try_get_displays_(false)
 }
 
+// The bounding rect (left, top, right, bottom) of all the displays, for the absolute input devices.
+// Only for X11, Display::all() of wayland is not safe to call out of the video service.
+#[cfg(target_os = "linux")]
+pub fn try_get_displays_rect() -> ResultType<(i32, i32, i32, i32)> {
+    let mut rect: Option<(i32, i32, i32, i32)> = None;
+    for d in try_get_displays()?.iter() {
+        let (x, y) = d.origin();
+        let (r, b) = (x + d.width() as i32, y + d.height() as i32);
+        rect = Some(match rect {
+            Some((l, t, rr, bb)) => (l.min(x), t.min(y), rr.max(r), bb.max(b)),
+            None => (x, y, r, b),
+        });
+    }
+    rect.ok_or_else(|| hbb_common::anyhow::anyhow!("no displays"))
+}
+
 // We can't get full control of the virtual display if we use amyuni idd.
 // If we add a virtual display, we cannot remove it automatically.
 // So when using amyuni idd, we only add a virtual display for headless if it is required.
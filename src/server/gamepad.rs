@@ -0,0 +1,267 @@
+// Virtual controllers fed by the gamepad events of the controlling side.
+//
+// Each controller of a connection is a virtual Xbox 360 controller, plugged on its first event and
+// unplugged when it is disconnected on the controlling side or the connection closes. DualShock
+// controllers are forwarded in the same layout. Windows needs the ViGEmBus driver, linux needs
+// write access to /dev/uinput.
+//
+// The devices are owned by one thread, the events are queued to it.
+
+use super::*;
+use std::{collections::HashSet, sync::mpsc};
+
+// Games rarely handle more, XInput has 4 slots.
+const MAX_PADS: usize = 4;
+
+enum Event {
+    Update(i32, GamepadEvent),
+    Close(i32),
+}
+
+trait VirtualPad {
+    fn update(&mut self, evt: &GamepadEvent) -> ResultType<()>;
+}
+
+lazy_static::lazy_static! {
+    static ref TX: Mutex<Option<mpsc::Sender<Event>>> = Default::default();
+}
+
+pub fn is_supported() -> bool {
+    platform::is_supported()
+}
+
+pub fn handle(conn_id: i32, evt: GamepadEvent) {
+    let mut tx = TX.lock().unwrap();
+    let tx = tx.get_or_insert_with(|| {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || run(rx));
+        tx
+    });
+    tx.send(Event::Update(conn_id, evt)).ok();
+}
+
+pub fn on_connection_close(conn_id: i32) {
+    if let Some(tx) = TX.lock().unwrap().as_ref() {
+        tx.send(Event::Close(conn_id)).ok();
+    }
+}
+
+fn run(rx: mpsc::Receiver<Event>) {
+    let mut pads: HashMap<(i32, u32), Box<dyn VirtualPad>> = HashMap::new();
+    // not plugged again until the controller is reconnected, not to retry on every event
+    let mut failed: HashSet<(i32, u32)> = HashSet::new();
+    while let Ok(event) = rx.recv() {
+        match event {
+            Event::Update(conn_id, evt) => {
+                let key = (conn_id, evt.index);
+                if !evt.connected {
+                    if pads.remove(&key).is_some() {
+                        log::info!("gamepad {} of connection {} unplugged", evt.index, conn_id);
+                    }
+                    failed.remove(&key);
+                    continue;
+                }
+                if failed.contains(&key) {
+                    continue;
+                }
+                if !pads.contains_key(&key) {
+                    if pads.len() >= MAX_PADS {
+                        log::warn!("too many gamepads, ignore {key:?}");
+                        failed.insert(key);
+                        continue;
+                    }
+                    match platform::plug() {
+                        Ok(pad) => {
+                            log::info!("gamepad {} of connection {} plugged", evt.index, conn_id);
+                            pads.insert(key, pad);
+                        }
+                        Err(e) => {
+                            log::error!("failed to plug gamepad: {e}");
+                            failed.insert(key);
+                            continue;
+                        }
+                    }
+                }
+                if let Some(pad) = pads.get_mut(&key) {
+                    if let Err(e) = pad.update(&evt) {
+                        log::error!("failed to update gamepad: {e}");
+                        pads.remove(&key);
+                        failed.insert(key);
+                    }
+                }
+            }
+            Event::Close(conn_id) => {
+                pads.retain(|(id, _), _| *id != conn_id);
+                failed.retain(|(id, _)| *id != conn_id);
+            }
+        }
+    }
+}
+
+fn axis(v: i32) -> i16 {
+    v.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+fn trigger(v: u32) -> u8 {
+    v.min(u8::MAX as u32) as u8
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use hbb_common::anyhow::anyhow;
+    use vigem_client::{Client, TargetId, XButtons, XGamepad, Xbox360Wired};
+
+    pub fn is_supported() -> bool {
+        Client::connect().is_ok()
+    }
+
+    struct Pad(Xbox360Wired<Client>);
+
+    pub fn plug() -> ResultType<Box<dyn VirtualPad>> {
+        let client = Client::connect().map_err(|e| anyhow!("ViGEmBus: {e:?}"))?;
+        let mut target = Xbox360Wired::new(client, TargetId::XBOX360_WIRED);
+        target.plugin().map_err(|e| anyhow!("{e:?}"))?;
+        target.wait_ready().map_err(|e| anyhow!("{e:?}"))?;
+        Ok(Box::new(Pad(target)))
+    }
+
+    impl VirtualPad for Pad {
+        fn update(&mut self, evt: &GamepadEvent) -> ResultType<()> {
+            let gamepad = XGamepad {
+                buttons: XButtons {
+                    raw: evt.buttons as u16,
+                },
+                left_trigger: trigger(evt.left_trigger),
+                right_trigger: trigger(evt.right_trigger),
+                thumb_lx: axis(evt.left_x),
+                thumb_ly: axis(evt.left_y),
+                thumb_rx: axis(evt.right_x),
+                thumb_ry: axis(evt.right_y),
+            };
+            self.0.update(&gamepad).map_err(|e| anyhow!("{e:?}"))
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+    use evdev::{
+        uinput::{VirtualDevice, VirtualDeviceBuilder},
+        AbsInfo, AbsoluteAxisType, AttributeSet, BusType, EventType, InputEvent, InputId, Key,
+        UinputAbsSetup,
+    };
+
+    // XInput button bit and the key of the xpad driver
+    const BUTTONS: [(u32, Key); 11] = [
+        (0x0010, Key::BTN_START),
+        (0x0020, Key::BTN_SELECT),
+        (0x0040, Key::BTN_THUMBL),
+        (0x0080, Key::BTN_THUMBR),
+        (0x0100, Key::BTN_TL),
+        (0x0200, Key::BTN_TR),
+        (0x0400, Key::BTN_MODE),
+        (0x1000, Key::BTN_SOUTH),
+        (0x2000, Key::BTN_EAST),
+        (0x4000, Key::BTN_NORTH),
+        (0x8000, Key::BTN_WEST),
+    ];
+    const DPAD_UP: u32 = 0x0001;
+    const DPAD_DOWN: u32 = 0x0002;
+    const DPAD_LEFT: u32 = 0x0004;
+    const DPAD_RIGHT: u32 = 0x0008;
+
+    pub fn is_supported() -> bool {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open("/dev/uinput")
+            .is_ok()
+    }
+
+    struct Pad {
+        device: VirtualDevice,
+        // all the keys are sent on the first update
+        last_buttons: Option<u32>,
+    }
+
+    pub fn plug() -> ResultType<Box<dyn VirtualPad>> {
+        let mut keys = AttributeSet::<Key>::new();
+        for (_, key) in BUTTONS.iter() {
+            keys.insert(*key);
+        }
+        let stick = AbsInfo::new(0, i16::MIN as _, i16::MAX as _, 16, 128, 0);
+        let trigger_range = AbsInfo::new(0, 0, u8::MAX as _, 0, 0, 0);
+        let dpad = AbsInfo::new(0, -1, 1, 0, 0, 0);
+        // the ids of an Xbox 360 controller, so games and SDL pick the right mapping
+        let device = VirtualDeviceBuilder::new()?
+            .name("RustDesk Gamepad")
+            .input_id(InputId::new(BusType::BUS_USB, 0x045e, 0x028e, 0x0110))
+            .with_keys(&keys)?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_X, stick))?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_Y, stick))?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_RX, stick))?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_RY, stick))?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_Z, trigger_range))?
+            .with_absolute_axis(&UinputAbsSetup::new(
+                AbsoluteAxisType::ABS_RZ,
+                trigger_range,
+            ))?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_HAT0X, dpad))?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_HAT0Y, dpad))?
+            .build()?;
+        Ok(Box::new(Pad {
+            device,
+            last_buttons: None,
+        }))
+    }
+
+    fn hat(buttons: u32, negative: u32, positive: u32) -> i32 {
+        (buttons & positive != 0) as i32 - (buttons & negative != 0) as i32
+    }
+
+    impl VirtualPad for Pad {
+        fn update(&mut self, evt: &GamepadEvent) -> ResultType<()> {
+            let abs = |axis: AbsoluteAxisType, value: i32| {
+                InputEvent::new(EventType::ABSOLUTE, axis.0, value)
+            };
+            let mut events = vec![];
+            for (bit, key) in BUTTONS.iter() {
+                let down = evt.buttons & bit != 0;
+                if self
+                    .last_buttons
+                    .map(|b| down != (b & bit != 0))
+                    .unwrap_or(true)
+                {
+                    events.push(InputEvent::new(EventType::KEY, key.code(), down as i32));
+                }
+            }
+            // up is negative on linux
+            events.extend([
+                abs(AbsoluteAxisType::ABS_X, axis(evt.left_x) as i32),
+                abs(
+                    AbsoluteAxisType::ABS_Y,
+                    axis(evt.left_y.saturating_neg()) as i32,
+                ),
+                abs(AbsoluteAxisType::ABS_RX, axis(evt.right_x) as i32),
+                abs(
+                    AbsoluteAxisType::ABS_RY,
+                    axis(evt.right_y.saturating_neg()) as i32,
+                ),
+                abs(AbsoluteAxisType::ABS_Z, trigger(evt.left_trigger) as i32),
+                abs(AbsoluteAxisType::ABS_RZ, trigger(evt.right_trigger) as i32),
+                abs(
+                    AbsoluteAxisType::ABS_HAT0X,
+                    hat(evt.buttons, DPAD_LEFT, DPAD_RIGHT),
+                ),
+                abs(
+                    AbsoluteAxisType::ABS_HAT0Y,
+                    hat(evt.buttons, DPAD_UP, DPAD_DOWN),
+                ),
+            ]);
+            self.device.emit(&events)?;
+            self.last_buttons = Some(evt.buttons);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,116 @@
+// The handoff of a session to another device of the controlling side, from a desktop to a tablet:
+// the peer asks for a ticket and opens the link of it on the other device, which logs in with the
+// ticket instead of the password or the click, as the switch of the sides does. The new session
+// takes over the permissions and the privacy mode of the one handed off, which is closed then. The
+// elevation is of this side, it stays as it is.
+//
+// A ticket is used once and expires soon. The device taking over is still checked by the identity
+// provider required, and must be signed in as the same one as the session handed off. The ticket is
+// bound to that account, the 2FA and the security key are not asked again for it. A ticket of a
+// session without an account only stands for the password, the second factor is asked again.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+pub const TICKET_TIMEOUT: Duration = Duration::from_secs(120);
+
+lazy_static::lazy_static! {
+    static ref TICKETS: Mutex<HashMap<uuid::Uuid, (Instant, Handoff)>> = Default::default();
+    // the connections whose sessions are taken over, to be closed
+    static ref HANDED_OFF: Mutex<HashSet<i32>> = Default::default();
+}
+
+/// What the session handed off leaves to the one taking it over.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Handoff {
+    pub conn_id: i32,
+    // who the peer is at the identity provider required
+    pub identity: Option<String>,
+    pub permissions: Vec<(String, bool)>,
+}
+
+impl Handoff {
+    /// Whether the ticket is bound to the account `identity` checked on login, which is not asked
+    /// for the second factor again.
+    pub fn is_bound_to(&self, identity: &Option<String>) -> bool {
+        self.identity.is_some() && self.identity == *identity
+    }
+}
+
+/// A new ticket of the connection, the ones it got before are void.
+pub fn new_ticket(handoff: Handoff) -> uuid::Uuid {
+    let ticket = uuid::Uuid::new_v4();
+    let mut tickets = TICKETS.lock().unwrap();
+    tickets.retain(|_, (t, h)| h.conn_id != handoff.conn_id && t.elapsed() < TICKET_TIMEOUT);
+    tickets.insert(ticket, (Instant::now(), handoff));
+    ticket
+}
+
+/// The session of the ticket, once only.
+pub fn take(ticket: &uuid::Uuid) -> Option<Handoff> {
+    let (created, handoff) = TICKETS.lock().unwrap().remove(ticket)?;
+    (created.elapsed() < TICKET_TIMEOUT).then_some(handoff)
+}
+
+/// The session of the connection is taken over, it is closed.
+pub fn hand_off(conn_id: i32) {
+    HANDED_OFF.lock().unwrap().insert(conn_id);
+}
+
+/// True once, for the connection whose session is taken over.
+pub fn is_handed_off(conn_id: i32) -> bool {
+    HANDED_OFF.lock().unwrap().remove(&conn_id)
+}
+
+pub fn on_connection_close(conn_id: i32) {
+    TICKETS
+        .lock()
+        .unwrap()
+        .retain(|_, (_, h)| h.conn_id != conn_id);
+    HANDED_OFF.lock().unwrap().remove(&conn_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handoff(conn_id: i32) -> Handoff {
+        Handoff {
+            conn_id,
+            identity: Some("alice".to_owned()),
+            permissions: vec![("keyboard".to_owned(), false)],
+        }
+    }
+
+    #[test]
+    fn test_ticket() {
+        let first = new_ticket(handoff(-1));
+        let second = new_ticket(handoff(-1));
+        // the ticket asked before is void
+        assert_eq!(take(&first), None);
+        assert_eq!(take(&second), Some(handoff(-1)));
+        assert_eq!(take(&second), None);
+        // nor is the one of a connection closed
+        let ticket = new_ticket(handoff(-2));
+        on_connection_close(-2);
+        assert_eq!(take(&ticket), None);
+        hand_off(-3);
+        assert!(is_handed_off(-3));
+        assert!(!is_handed_off(-3));
+    }
+
+    #[test]
+    fn test_bound() {
+        assert!(handoff(-1).is_bound_to(&Some("alice".to_owned())));
+        assert!(!handoff(-1).is_bound_to(&Some("bob".to_owned())));
+        assert!(!handoff(-1).is_bound_to(&None));
+        let unbound = Handoff {
+            identity: None,
+            ..handoff(-1)
+        };
+        assert!(!unbound.is_bound_to(&None));
+    }
+}
@@ -0,0 +1,197 @@
+// The control of the input of this side when several peers are connected: one peer holds it and
+// only its input is injected, the others view until they are given it.
+//
+// Nobody holds it at first, the first peer to send input takes it. A peer asks for it then, and the
+// user of this side grants or denies; it is given to the first peer asking without the user once
+// the holder is idle for IDLE, or gives it up, or leaves. Off unless allowed, the peers all control
+// at once otherwise.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use hbb_common::{
+    config::{keys, Config},
+    message_proto::InputControl,
+};
+
+const IDLE: Duration = Duration::from_secs(30);
+
+lazy_static::lazy_static! {
+    static ref STATE: Mutex<State> = Default::default();
+}
+
+#[derive(Default)]
+struct State {
+    // the names of the peers of the connections which may send input
+    names: HashMap<i32, String>,
+    holder: Option<i32>,
+    // of the holder
+    last_input: Option<Instant>,
+    // the connections asking, first come first served
+    requests: Vec<i32>,
+}
+
+impl State {
+    fn allows(&mut self, conn: i32, now: Instant) -> bool {
+        if matches!(self.holder, Some(holder) if holder != conn) {
+            return false;
+        }
+        self.give(conn, now);
+        true
+    }
+
+    fn request(&mut self, conn: i32, now: Instant) -> bool {
+        if self.holder.is_none() || self.holder == Some(conn) || self.idle(now) {
+            self.give(conn, now);
+            return true;
+        }
+        if !self.requests.contains(&conn) {
+            self.requests.push(conn);
+        }
+        false
+    }
+
+    fn answer(&mut self, conn: i32, grant: bool, now: Instant) {
+        if !self.requests.contains(&conn) {
+            return;
+        }
+        if grant {
+            self.give(conn, now);
+        } else {
+            self.requests.retain(|c| *c != conn);
+        }
+    }
+
+    fn release(&mut self, conn: i32, now: Instant) {
+        self.requests.retain(|c| *c != conn);
+        if self.holder == Some(conn) {
+            self.holder = None;
+            if let Some(next) = self.requests.first().cloned() {
+                self.give(next, now);
+            }
+        }
+    }
+
+    fn check_idle(&mut self, now: Instant) {
+        if self.idle(now) {
+            if let Some(next) = self.requests.first().cloned() {
+                self.give(next, now);
+            }
+        }
+    }
+
+    fn idle(&self, now: Instant) -> bool {
+        self.holder.is_some()
+            && self
+                .last_input
+                .map(|t| now.saturating_duration_since(t) >= IDLE)
+                .unwrap_or(true)
+    }
+
+    fn give(&mut self, conn: i32, now: Instant) {
+        self.holder = Some(conn);
+        self.last_input = Some(now);
+        self.requests.retain(|c| *c != conn);
+    }
+
+    fn state(&self, conn: i32) -> InputControl {
+        let holder = match self.holder {
+            Some(holder) if holder != conn => self.names.get(&holder).cloned().unwrap_or_default(),
+            _ => "".to_owned(),
+        };
+        InputControl {
+            enabled: true,
+            holding: self.holder == Some(conn),
+            holder,
+            requested: self.requests.contains(&conn),
+            ..Default::default()
+        }
+    }
+}
+
+fn enabled() -> bool {
+    Config::get_bool_option(keys::OPTION_ALLOW_INPUT_ARBITRATION)
+}
+
+/// The connection may send input.
+pub fn join(conn: i32, name: &str) {
+    STATE.lock().unwrap().names.insert(conn, name.to_owned());
+}
+
+/// The connection may not send input any more, or is closed.
+pub fn leave(conn: i32) {
+    let mut state = STATE.lock().unwrap();
+    if state.names.remove(&conn).is_some() || state.holder == Some(conn) {
+        state.release(conn, Instant::now());
+    }
+}
+
+/// Whether the input of the connection is injected, it takes the control if nobody holds it.
+pub fn allows(conn: i32) -> bool {
+    !enabled() || STATE.lock().unwrap().allows(conn, Instant::now())
+}
+
+/// The connection asks for the control, true if it holds it then.
+pub fn request(conn: i32) -> bool {
+    STATE.lock().unwrap().request(conn, Instant::now())
+}
+
+/// The user of this side grants or denies the request of the connection.
+pub fn answer(conn: i32, grant: bool) {
+    STATE.lock().unwrap().answer(conn, grant, Instant::now());
+}
+
+/// The connection gives the control up, or withdraws its request.
+pub fn release(conn: i32) {
+    STATE.lock().unwrap().release(conn, Instant::now());
+}
+
+/// The state told to the peer of the connection, None if the input is not arbitrated.
+pub fn state(conn: i32) -> Option<InputControl> {
+    if !enabled() {
+        return None;
+    }
+    let mut state = STATE.lock().unwrap();
+    state.check_idle(Instant::now());
+    Some(state.state(conn))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arbitration() {
+        let mut state = State::default();
+        let now = Instant::now();
+        state.names.insert(1, "a".to_owned());
+        state.names.insert(2, "b".to_owned());
+        // the first input takes it
+        assert!(state.allows(1, now));
+        assert!(!state.allows(2, now));
+        assert_eq!(state.state(2).holder, "a");
+        // denied
+        assert!(!state.request(2, now));
+        assert!(state.state(2).requested);
+        state.answer(2, false, now);
+        assert!(!state.state(2).requested);
+        // handed on once the holder is idle
+        assert!(!state.request(2, now));
+        state.check_idle(now + IDLE / 2);
+        assert!(state.state(1).holding);
+        state.check_idle(now + IDLE);
+        assert!(state.state(2).holding);
+        assert_eq!(state.state(1).holder, "b");
+        // given up to the one asking
+        assert!(!state.request(1, now + IDLE));
+        state.release(2, now + IDLE);
+        assert!(state.state(1).holding);
+        // nobody holds it once the holder leaves
+        state.release(1, now + IDLE);
+        assert_eq!(state.holder, None);
+        assert!(state.allows(2, now + IDLE));
+    }
+}
@@ -9,7 +9,10 @@ use dispatch::Queue;
 use enigo::{Enigo, Key, KeyboardControllable, MouseButton, MouseControllable};
 use hbb_common::{
     get_time,
-    message_proto::{pointer_device_event::Union::TouchEvent, touch_event::Union::ScaleUpdate},
+    message_proto::{
+        pointer_device_event::Union::{PenEvent, TouchEvent},
+        touch_event::Union::{Points, ScaleUpdate},
+    },
     protobuf::EnumOrUnknown,
 };
 use rdev::{self, EventType, Key as RdevKey, KeyCode, RawKey};
@@ -345,6 +348,15 @@ fn update_last_cursor_pos(x: i32, y: i32) {
     }
 }
 
+pub fn latest_cursor_pos() -> Option<(i32, i32)> {
+    let (_, (x, y)) = *LATEST_SYS_CURSOR_POS.lock().unwrap();
+    if x == INVALID_CURSOR_POS || y == INVALID_CURSOR_POS {
+        None
+    } else {
+        Some((x, y))
+    }
+}
+
 fn run_pos(sp: EmptyExtraFieldService, state: &mut StatePos) -> ResultType<()> {
     let (_, (x, y)) = *LATEST_SYS_CURSOR_POS.lock().unwrap();
     if x == INVALID_CURSOR_POS || y == INVALID_CURSOR_POS {
@@ -958,8 +970,15 @@ pub fn handle_pointer_(evt: &PointerDeviceEvent, conn: i32) {
                 #[cfg(target_os = "windows")]
                 handle_scale(_scale_evt.scale);
             }
+            Some(Points(_points)) => {
+                #[cfg(any(target_os = "windows", target_os = "linux"))]
+                super::touch::handle(_points);
+            }
             _ => {}
         },
+        Some(PenEvent(evt)) => {
+            super::pen::handle(evt);
+        }
         _ => {}
     }
 }
@@ -1189,6 +1208,77 @@ pub fn handle_key(evt: &KeyEvent) {
     key_sleep();
 }
 
+/// Whether the text committed by the input method of the peer can be typed.
+pub fn is_text_input_supported() -> bool {
+    // xdo types any character on X11, the uinput keyboard does not
+    #[cfg(target_os = "linux")]
+    return crate::platform::linux::is_x11();
+    #[cfg(not(target_os = "linux"))]
+    return true;
+}
+
+/// Types the text as it is, regardless of the keyboard layout and the input method.
+#[cfg(target_os = "windows")]
+pub fn input_text(text: &str) {
+    use winapi::um::winuser::{
+        SendInput, INPUT, INPUT_KEYBOARD, KEYBDINPUT, KEYEVENTF_KEYUP, KEYEVENTF_UNICODE,
+    };
+    if EXITING.load(Ordering::SeqCst) {
+        return;
+    }
+    crate::platform::windows::try_change_desktop();
+    // One call for all, so the surrogate pairs are not split by the other input.
+    let mut inputs = vec![];
+    for unit in text.encode_utf16() {
+        for flags in [KEYEVENTF_UNICODE, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP] {
+            let mut input: INPUT = unsafe { std::mem::zeroed() };
+            input.type_ = INPUT_KEYBOARD;
+            unsafe {
+                *input.u.ki_mut() = KEYBDINPUT {
+                    wVk: 0,
+                    wScan: unit,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: enigo::ENIGO_INPUT_EXTRA_VALUE,
+                };
+            }
+            inputs.push(input);
+        }
+    }
+    let sent = unsafe {
+        SendInput(
+            inputs.len() as _,
+            inputs.as_mut_ptr(),
+            std::mem::size_of::<INPUT>() as _,
+        )
+    };
+    if sent as usize != inputs.len() {
+        log::error!("SendInput: {}", std::io::Error::last_os_error());
+    }
+}
+
+/// Types the text as it is, regardless of the keyboard layout and the input method.
+#[cfg(target_os = "macos")]
+pub fn input_text(text: &str) {
+    // posted as the unicode string of the key events, which the text input clients insert
+    let text = text.to_owned();
+    QUEUE.exec_async(move || {
+        if !EXITING.load(Ordering::SeqCst) {
+            ENIGO.lock().unwrap().key_sequence(&text);
+        }
+    });
+    key_sleep();
+}
+
+/// Types the text as it is, regardless of the keyboard layout and the input method.
+#[cfg(target_os = "linux")]
+pub fn input_text(text: &str) {
+    if EXITING.load(Ordering::SeqCst) || !is_text_input_supported() {
+        return;
+    }
+    ENIGO.lock().unwrap().key_sequence(text);
+}
+
 #[cfg(target_os = "macos")]
 #[inline]
 fn reset_input() {
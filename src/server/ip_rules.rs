@@ -0,0 +1,229 @@
+// The allow and deny rules of the addresses the connections are accepted from, checked after the
+// whitelist when a connection is opened, directly or through a relay server.
+//
+// One rule a line, or separated by semicolons: "allow" or "deny", an address or a CIDR, and
+// optionally the days and the time of the day it applies in, in local time, e.g.
+//
+//     deny 10.0.5.0/24
+//     allow 192.168.1.0/24 mon-fri 09:00-18:00
+//     allow 2001:db8::/32 sat,sun 22:00-06:00
+//
+// The first rule matching an address decides. If none does, the address is denied if there is
+// any allow rule, and accepted otherwise.
+
+use std::{net::IpAddr, str::FromStr};
+
+use chrono::{Datelike, Local, Timelike};
+use cidr_utils::cidr::IpCidr;
+use hbb_common::{
+    anyhow::{anyhow, Error},
+    bail, log, ResultType,
+};
+
+const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+pub(super) const ALL_DAYS: u8 = 0x7F;
+
+#[derive(Debug)]
+pub struct Rule {
+    text: String,
+    allow: bool,
+    cidr: IpCidr,
+    // bit 0 for monday
+    days: u8,
+    // in minutes of the day, the end excluded, over midnight if it is before the start
+    time: Option<(u32, u32)>,
+}
+
+impl FromStr for Rule {
+    type Err = Error;
+
+    fn from_str(text: &str) -> ResultType<Self> {
+        let mut parts = text.split_whitespace();
+        let allow = match parts.next().map(|p| p.to_lowercase()).as_deref() {
+            Some("allow") => true,
+            Some("deny") => false,
+            _ => bail!("Invalid rule, \"allow\" or \"deny\" expected: {}", text),
+        };
+        let Some(cidr) = parts.next().and_then(|p| IpCidr::from_str(p).ok()) else {
+            bail!("Invalid address of the rule: {}", text);
+        };
+        let mut rule = Self {
+            text: text.trim().to_owned(),
+            allow,
+            cidr,
+            days: ALL_DAYS,
+            time: None,
+        };
+        for part in parts {
+            if part.contains(':') && rule.time.is_none() {
+                rule.time = Some(
+                    parse_time(part)
+                        .ok_or_else(|| anyhow!("Invalid time of the rule: {}", text))?,
+                );
+            } else if rule.days == ALL_DAYS {
+                rule.days = parse_days(part)
+                    .ok_or_else(|| anyhow!("Invalid days of the rule: {}", text))?;
+            } else {
+                bail!("Invalid rule: {}", text);
+            }
+        }
+        Ok(rule)
+    }
+}
+
+impl Rule {
+    // `weekday` from monday, `minute` of the day
+    fn matches(&self, ip: IpAddr, weekday: u32, minute: u32) -> bool {
+        self.cidr.contains(ip) && in_window(self.days, self.time, weekday, minute)
+    }
+}
+
+/// If the time is in the days and the time of the day, `weekday` from monday, `minute` of the day.
+pub(super) fn in_window(days: u8, time: Option<(u32, u32)>, weekday: u32, minute: u32) -> bool {
+    // over midnight, the day the window starts on decides
+    let (day, in_time) = match time {
+        None => (weekday, true),
+        Some((start, end)) if start <= end => (weekday, start <= minute && minute < end),
+        Some((start, _)) if minute >= start => (weekday, true),
+        Some((_, end)) => ((weekday + 6) % 7, minute < end),
+    };
+    in_time && days & (1 << day) != 0
+}
+
+/// The day of the week from monday and the minute of the day, in local time.
+pub(super) fn now() -> (u32, u32) {
+    let now = Local::now();
+    (
+        now.weekday().num_days_from_monday(),
+        now.hour() * 60 + now.minute(),
+    )
+}
+
+pub fn parse(value: &str) -> ResultType<Vec<Rule>> {
+    value
+        .split(|c| c == ';' || c == '\n')
+        .filter(|r| !r.trim().is_empty())
+        .map(Rule::from_str)
+        .collect()
+}
+
+/// The rule denying `ip` now, "" for the lack of an allow rule, or None if it is accepted.
+pub fn check(value: &str, ip: IpAddr) -> Option<String> {
+    if value.trim().is_empty() {
+        return None;
+    }
+    let (weekday, minute) = now();
+    check_at(value, ip, weekday, minute)
+}
+
+fn check_at(value: &str, ip: IpAddr, weekday: u32, minute: u32) -> Option<String> {
+    let mut any_allow = false;
+    for rule in value.split(|c| c == ';' || c == '\n') {
+        if rule.trim().is_empty() {
+            continue;
+        }
+        // the rules are checked when set, the broken ones are skipped
+        let rule = match Rule::from_str(rule) {
+            Ok(rule) => rule,
+            Err(err) => {
+                log::error!("{}", err);
+                continue;
+            }
+        };
+        if rule.matches(ip, weekday, minute) {
+            return if rule.allow { None } else { Some(rule.text) };
+        }
+        any_allow |= rule.allow;
+    }
+    if any_allow {
+        Some("".to_owned())
+    } else {
+        None
+    }
+}
+
+// "HH:MM-HH:MM"
+pub(super) fn parse_time(s: &str) -> Option<(u32, u32)> {
+    let minute = |s: &str| {
+        let (h, m) = s.split_once(':')?;
+        let (h, m) = (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?);
+        if h > 24 || m > 59 || h == 24 && m > 0 {
+            return None;
+        }
+        Some(h * 60 + m)
+    };
+    let (start, end) = s.split_once('-')?;
+    Some((minute(start)?, minute(end)?))
+}
+
+// e.g. "mon-fri" or "sat,sun"
+pub(super) fn parse_days(s: &str) -> Option<u8> {
+    let day = |s: &str| DAYS.iter().position(|d| s.eq_ignore_ascii_case(d));
+    let mut days = 0;
+    for part in s.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (from, to) = (day(from)?, day(to)?);
+                let mut d = from;
+                loop {
+                    days |= 1 << d;
+                    if d == to {
+                        break;
+                    }
+                    d = (d + 1) % 7;
+                }
+            }
+            None => days |= 1 << day(part)?,
+        }
+    }
+    Some(days)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse_days("mon-fri"), Some(0x1F));
+        assert_eq!(parse_days("Sat,sun"), Some(0x60));
+        assert_eq!(parse_days("fri-mon"), Some(0x71));
+        assert_eq!(parse_days("weekend"), None);
+        assert_eq!(parse_time("09:00-18:30"), Some((540, 1110)));
+        assert_eq!(parse_time("25:00-18:30"), None);
+        assert!(parse("allow 10.0.0.0/8 mon-fri 09:00-18:00; deny ::/0\n\n").is_ok());
+        assert!(parse("permit 10.0.0.0/8").is_err());
+        assert!(parse("allow 10.0.0.0/33").is_err());
+        assert!(parse("allow 10.0.0.1 mon tue").is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        let rules = "deny 192.168.1.13\nallow 192.168.1.0/24 mon-fri 09:00-18:00";
+        // tuesday 10:00
+        assert_eq!(check_at(rules, ip("192.168.1.7"), 1, 600), None);
+        assert_eq!(
+            check_at(rules, ip("192.168.1.13"), 1, 600),
+            Some("deny 192.168.1.13".to_owned())
+        );
+        assert_eq!(check_at(rules, ip("10.0.0.1"), 1, 600), Some("".to_owned()));
+        // saturday, and tuesday 18:00
+        assert_eq!(
+            check_at(rules, ip("192.168.1.7"), 5, 600),
+            Some("".to_owned())
+        );
+        assert_eq!(
+            check_at(rules, ip("192.168.1.7"), 1, 1080),
+            Some("".to_owned())
+        );
+        // deny rules only
+        assert_eq!(check_at("deny 10.0.0.0/8", ip("192.168.1.7"), 1, 600), None);
+        // friday 22:00 to saturday 06:00
+        let rules = "allow 0.0.0.0/0 fri 22:00-06:00";
+        assert_eq!(check_at(rules, ip("1.1.1.1"), 4, 1380), None);
+        assert_eq!(check_at(rules, ip("1.1.1.1"), 5, 300), None);
+        assert!(check_at(rules, ip("1.1.1.1"), 5, 1380).is_some());
+        assert!(check_at(rules, ip("1.1.1.1"), 4, 300).is_some());
+    }
+}
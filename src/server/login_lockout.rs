@@ -0,0 +1,141 @@
+// The backoff of the logins of a source, a peer id from an ip, after its wrong passwords, 2fa codes
+// and security keys, on top of the limits of the connection per ip.
+//
+// From the FREE_FAILURES-th failure in a row on, the source waits a second before its next login,
+// twice as long after each further failure, and it is locked out for LOCKOUT once it fails
+// LOCKOUT_FAILURES times, again after each failure then. The failures are kept across the
+// restarts of the service, and forgotten on a successful login or FORGET after the last one.
+
+use std::sync::Mutex;
+
+use hbb_common::{
+    config::{LoginLockout, LoginLockouts},
+    get_time,
+};
+
+const FREE_FAILURES: i32 = 3;
+const LOCKOUT_FAILURES: i32 = 10;
+// in milliseconds
+const LOCKOUT: i64 = 60 * 60_000;
+const FORGET: i64 = 24 * 60 * 60_000;
+
+lazy_static::lazy_static! {
+    // loaded on first use
+    static ref LOCKOUTS: Mutex<Option<Vec<LoginLockout>>> = Default::default();
+}
+
+// `f` returns whether it changes the lockouts, which are stored then
+fn with<T>(f: impl FnOnce(&mut Vec<LoginLockout>, i64) -> (T, bool)) -> T {
+    let mut lock = LOCKOUTS.lock().unwrap();
+    let lockouts = lock.get_or_insert_with(|| LoginLockouts::load().lockouts);
+    let now = get_time();
+    let len = lockouts.len();
+    lockouts.retain(|l| l.locked_until > now || now - l.last_failure < FORGET);
+    let (res, changed) = f(lockouts, now);
+    if changed || lockouts.len() != len {
+        LoginLockouts::store(lockouts);
+    }
+    res
+}
+
+fn backoff(failures: i32) -> i64 {
+    if failures >= LOCKOUT_FAILURES {
+        LOCKOUT
+    } else if failures >= FREE_FAILURES {
+        1000 << (failures - FREE_FAILURES)
+    } else {
+        0
+    }
+}
+
+/// The milliseconds `id` from `ip` has to wait before its next login, None if it need not.
+pub fn wait(id: &str, ip: &str) -> Option<i64> {
+    with(|lockouts, now| {
+        let wait = lockouts
+            .iter()
+            .find(|l| l.id == id && l.ip == ip)
+            .map(|l| l.locked_until - now)
+            .filter(|wait| *wait > 0);
+        (wait, false)
+    })
+}
+
+/// Counts a failed login, the milliseconds of the lockout if it starts with it.
+pub fn on_failure(id: &str, ip: &str) -> Option<i64> {
+    with(|lockouts, now| (fail(lockouts, id, ip, now), true))
+}
+
+pub fn on_success(id: &str, ip: &str) {
+    with(|lockouts, _| {
+        let len = lockouts.len();
+        lockouts.retain(|l| l.id != id || l.ip != ip);
+        ((), lockouts.len() != len)
+    })
+}
+
+pub fn list() -> Vec<LoginLockout> {
+    with(|lockouts, _| (lockouts.clone(), false))
+}
+
+/// Forgets the failures of the peer id or the ip `source`, all if None, the number of the sources
+/// forgotten.
+pub fn clear(source: Option<&str>) -> usize {
+    with(|lockouts, _| {
+        let len = lockouts.len();
+        lockouts.retain(|l| matches!(source, Some(s) if l.id != s && l.ip != s));
+        let n = len - lockouts.len();
+        (n, n > 0)
+    })
+}
+
+fn fail(lockouts: &mut Vec<LoginLockout>, id: &str, ip: &str, now: i64) -> Option<i64> {
+    let i = match lockouts.iter().position(|l| l.id == id && l.ip == ip) {
+        Some(i) => i,
+        None => {
+            lockouts.push(LoginLockout {
+                id: id.to_owned(),
+                ip: ip.to_owned(),
+                ..Default::default()
+            });
+            lockouts.len() - 1
+        }
+    };
+    let lockout = &mut lockouts[i];
+    lockout.failures += 1;
+    lockout.last_failure = now;
+    let backoff = backoff(lockout.failures);
+    lockout.locked_until = now + backoff;
+    if lockout.failures == LOCKOUT_FAILURES {
+        Some(backoff)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fail() {
+        let mut lockouts = Vec::new();
+        for _ in 0..FREE_FAILURES - 1 {
+            assert_eq!(fail(&mut lockouts, "123", "1.1.1.1", 0), None);
+        }
+        assert_eq!(lockouts[0].locked_until, 0);
+        assert_eq!(fail(&mut lockouts, "123", "1.1.1.1", 0), None);
+        assert_eq!(lockouts[0].locked_until, 1000);
+        assert_eq!(fail(&mut lockouts, "123", "1.1.1.1", 0), None);
+        assert_eq!(lockouts[0].locked_until, 2000);
+        // another source
+        assert_eq!(fail(&mut lockouts, "123", "2.2.2.2", 0), None);
+        assert_eq!((lockouts.len(), lockouts[1].locked_until), (2, 0));
+        for _ in FREE_FAILURES + 2..LOCKOUT_FAILURES {
+            assert_eq!(fail(&mut lockouts, "123", "1.1.1.1", 0), None);
+        }
+        assert_eq!(lockouts[0].locked_until, 64_000);
+        assert_eq!(fail(&mut lockouts, "123", "1.1.1.1", 0), Some(LOCKOUT));
+        assert_eq!(fail(&mut lockouts, "123", "1.1.1.1", 10), None);
+        assert_eq!(lockouts[0].locked_until, 10 + LOCKOUT);
+    }
+}
@@ -0,0 +1,286 @@
+// The notifications of the connections to the admins of this side: a session starting or ending,
+// and an authentication failing. Each is posted as json to the webhook and mailed through the smtp
+// server, whichever is configured in the options of this side.
+//
+// Only the events in `notify-events` are notified, all of them if it is empty. The notifications
+// are sent in the background, and the ones failing are only logged, so that neither a slow server
+// nor a wrong option delays or breaks the connections.
+
+use std::time::Duration;
+
+use hbb_common::{
+    anyhow::anyhow,
+    bail,
+    config::{keys, Config, APP_NAME},
+    get_time, log, tokio, ResultType,
+};
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message as Email, Tokio1Executor,
+};
+use serde_derive::Serialize;
+use serde_json::{json, Value};
+
+const SMTP_PORT: u16 = 587;
+// the port of the implicit tls
+const SMTPS_PORT: u16 = 465;
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    SessionStart,
+    SessionEnd,
+    AuthFailure,
+}
+
+impl Event {
+    fn name(&self) -> &'static str {
+        match self {
+            Event::SessionStart => "session_start",
+            Event::SessionEnd => "session_end",
+            Event::AuthFailure => "auth_failure",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Event::SessionStart => "Session started",
+            Event::SessionEnd => "Session ended",
+            Event::AuthFailure => "Authentication failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub event: Event,
+    // in milliseconds
+    pub time: i64,
+    // of this side
+    pub id: String,
+    pub hostname: String,
+    pub conn_id: i32,
+    pub peer_id: String,
+    pub peer_name: String,
+    pub ip: String,
+    pub data: Value,
+}
+
+impl Notification {
+    pub fn new(event: Event, conn_id: i32, peer_id: &str, peer_name: &str, ip: &str) -> Self {
+        Self {
+            event,
+            time: get_time(),
+            id: Config::get_id(),
+            hostname: crate::common::hostname(),
+            conn_id,
+            peer_id: peer_id.to_owned(),
+            peer_name: peer_name.to_owned(),
+            ip: ip.to_owned(),
+            data: Value::Null,
+        }
+    }
+
+    fn subject(&self) -> String {
+        format!(
+            "{} {}: {} by {}",
+            APP_NAME.read().unwrap(),
+            self.id,
+            self.event.title(),
+            self.peer_id
+        )
+    }
+
+    fn text(&self) -> String {
+        let time = chrono::DateTime::from_timestamp_millis(self.time)
+            .map(|t| {
+                t.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M:%S %z")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        let mut text = format!(
+            "{}\n\nTime: {}\nDevice: {} ({})\nPeer: {} ({})\nIP: {}\n",
+            self.event.title(),
+            time,
+            self.id,
+            self.hostname,
+            self.peer_id,
+            self.peer_name,
+            self.ip
+        );
+        if let Value::Object(data) = &self.data {
+            for (k, v) in data {
+                let v = match v {
+                    Value::String(s) => s.clone(),
+                    v => v.to_string(),
+                };
+                text.push_str(&format!("{}: {}\n", k, v));
+            }
+        }
+        text
+    }
+}
+
+struct Smtp {
+    host: String,
+    port: u16,
+    username: String,
+    password: String,
+    from: String,
+    to: Vec<String>,
+}
+
+impl Smtp {
+    // None if not configured
+    fn from_options() -> Option<Self> {
+        let server = Config::get_option(keys::OPTION_NOTIFY_SMTP_SERVER);
+        let to: Vec<String> = Config::get_option(keys::OPTION_NOTIFY_EMAIL_TO)
+            .split(',')
+            .map(|s| s.trim().to_owned())
+            .filter(|s| !s.is_empty())
+            .collect();
+        if server.trim().is_empty() || to.is_empty() {
+            return None;
+        }
+        let (host, port) = match parse_server(&server) {
+            Ok(v) => v,
+            Err(err) => {
+                log::error!("Invalid smtp server {}: {}", server, err);
+                return None;
+            }
+        };
+        let username = Config::get_option(keys::OPTION_NOTIFY_SMTP_USERNAME);
+        let mut from = Config::get_option(keys::OPTION_NOTIFY_EMAIL_FROM);
+        if from.trim().is_empty() {
+            from = username.clone();
+        }
+        Some(Self {
+            host,
+            port,
+            username,
+            password: Config::get_option(keys::OPTION_NOTIFY_SMTP_PASSWORD),
+            from,
+            to,
+        })
+    }
+
+    async fn send(&self, subject: String, text: String) -> ResultType<()> {
+        let from: Mailbox = self
+            .from
+            .trim()
+            .parse()
+            .map_err(|err| anyhow!("Invalid sender {}: {}", self.from, err))?;
+        let mut builder = Email::builder()
+            .from(from)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in self.to.iter() {
+            let to: Mailbox = to
+                .parse()
+                .map_err(|err| anyhow!("Invalid recipient {}: {}", to, err))?;
+            builder = builder.to(to);
+        }
+        let email = builder.body(text)?;
+        let mut transport = if self.port == SMTPS_PORT {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(&self.host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)?
+        }
+        .port(self.port)
+        .timeout(Some(SMTP_TIMEOUT));
+        if !self.username.is_empty() {
+            transport = transport.credentials(Credentials::new(
+                self.username.clone(),
+                self.password.clone(),
+            ));
+        }
+        transport.build().send(email).await?;
+        Ok(())
+    }
+}
+
+// host:port, or the host alone on SMTP_PORT
+fn parse_server(server: &str) -> ResultType<(String, u16)> {
+    let server = server.trim();
+    let (host, port) = match server.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse::<u16>()?),
+        None => (server, SMTP_PORT),
+    };
+    if host.is_empty() {
+        bail!("No host");
+    }
+    Ok((host.to_owned(), port))
+}
+
+fn enabled(event: Event, events: &str) -> bool {
+    events.trim().is_empty() || events.split(',').any(|e| e.trim() == event.name())
+}
+
+/// Sends the notification to the webhook and by email, if configured and its event is notified.
+pub fn notify(notification: Notification) {
+    if !enabled(
+        notification.event,
+        &Config::get_option(keys::OPTION_NOTIFY_EVENTS),
+    ) {
+        return;
+    }
+    let webhook = Config::get_option(keys::OPTION_NOTIFY_WEBHOOK);
+    let smtp = Smtp::from_options();
+    if webhook.is_empty() && smtp.is_none() {
+        return;
+    }
+    tokio::spawn(async move {
+        if !webhook.is_empty() {
+            let body = json!(notification).to_string();
+            if let Err(err) = crate::post_request(webhook, body, "").await {
+                log::error!("Failed to post the notification: {}", err);
+            }
+        }
+        if let Some(smtp) = smtp {
+            if let Err(err) = smtp.send(notification.subject(), notification.text()).await {
+                log::error!("Failed to mail the notification: {}", err);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options() {
+        assert!(enabled(Event::AuthFailure, ""));
+        assert!(enabled(Event::AuthFailure, "session_start, auth_failure"));
+        assert!(!enabled(Event::SessionEnd, "session_start,auth_failure"));
+
+        assert_eq!(
+            parse_server("smtp.example.com").unwrap(),
+            ("smtp.example.com".to_owned(), SMTP_PORT)
+        );
+        assert_eq!(parse_server(" smtp.example.com:465 ").unwrap().1, 465);
+        assert!(parse_server("smtp.example.com:x").is_err());
+        assert!(parse_server(":25").is_err());
+
+        let mut n = Notification {
+            event: Event::SessionEnd,
+            time: 0,
+            id: "123456789".to_owned(),
+            hostname: "host".to_owned(),
+            conn_id: 1,
+            peer_id: "987654321".to_owned(),
+            peer_name: "alice".to_owned(),
+            ip: "10.0.0.1".to_owned(),
+            data: json!({"reason": "Closed manually"}),
+        };
+        assert!(n
+            .text()
+            .ends_with("IP: 10.0.0.1\nreason: Closed manually\n"));
+        n.data = Value::Null;
+        assert!(n.text().contains("Peer: 987654321 (alice)\n"));
+        assert_eq!(json!(n)["event"], "session_end");
+    }
+}
@@ -0,0 +1,441 @@
+// Inject the stylus of the controlling side with its pressure, tilt and barrel buttons.
+//
+// Windows uses a synthetic pen pointer of Windows Ink, macOS posts tablet point events, linux
+// plugs a uinput tablet that libinput picks up as a pen, which needs X11 and write access to
+// /dev/uinput.
+//
+// The pen events are run in the input thread, the same as the mouse events.
+
+use super::*;
+
+const BUTTON_BARREL: u32 = 1;
+const BUTTON_BARREL2: u32 = 2;
+const BUTTON_ERASER: u32 = 4;
+
+const MAX_PRESSURE: u32 = 1024;
+const MAX_TILT: i32 = 90;
+
+trait VirtualPen: Send {
+    fn update(&mut self, evt: &PenEvent, last: Option<&PenEvent>) -> ResultType<()>;
+
+    // checked when the pen enters the range, the pen is created again if true
+    fn is_stale(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Default)]
+struct State {
+    pen: Option<Box<dyn VirtualPen>>,
+    // not created again in this process
+    failed: bool,
+    last: Option<PenEvent>,
+}
+
+lazy_static::lazy_static! {
+    static ref STATE: Mutex<State> = Default::default();
+}
+
+pub fn is_supported() -> bool {
+    platform::is_supported()
+}
+
+pub fn handle(evt: &PenEvent) {
+    let mut evt = evt.clone();
+    evt.pressure = evt.pressure.min(MAX_PRESSURE);
+    evt.tilt_x = evt.tilt_x.clamp(-MAX_TILT, MAX_TILT);
+    evt.tilt_y = evt.tilt_y.clamp(-MAX_TILT, MAX_TILT);
+    if !evt.in_range {
+        evt.contact = false;
+    }
+    let mut state = STATE.lock().unwrap();
+    let last = state.last.take().filter(|l| l.in_range);
+    if last.is_none() {
+        if !evt.in_range {
+            return;
+        }
+        if state.pen.as_ref().map(|p| p.is_stale()).unwrap_or(false) {
+            state.pen = None;
+        }
+    }
+    if state.pen.is_none() {
+        if state.failed {
+            return;
+        }
+        match platform::create() {
+            Ok(pen) => state.pen = Some(pen),
+            Err(e) => {
+                log::error!("failed to create the virtual pen: {e}");
+                state.failed = true;
+                return;
+            }
+        }
+    }
+    if let Some(pen) = state.pen.as_mut() {
+        if let Err(e) = pen.update(&evt, last.as_ref()) {
+            log::error!("failed to inject the pen: {e}");
+            // created again on the next event
+            state.pen = None;
+            return;
+        }
+    }
+    state.last = Some(evt);
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use std::{ffi::CString, mem::zeroed};
+    use winapi::{
+        shared::{
+            minwindef::{BOOL, DWORD, UINT},
+            ntdef::HANDLE,
+            windef::POINT,
+        },
+        um::{
+            libloaderapi::{GetProcAddress, LoadLibraryA},
+            winuser::*,
+        },
+    };
+
+    // Not in winapi.
+    #[allow(dead_code)]
+    #[repr(C)]
+    union PointerInfoUnion {
+        touch_info: POINTER_TOUCH_INFO,
+        pen_info: POINTER_PEN_INFO,
+    }
+
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    struct POINTER_TYPE_INFO {
+        r#type: DWORD,
+        info: PointerInfoUnion,
+    }
+
+    const POINTER_FEEDBACK_DEFAULT: DWORD = 1;
+
+    type CreateSyntheticPointerDevice = unsafe extern "system" fn(DWORD, u32, DWORD) -> HANDLE;
+    type InjectSyntheticPointerInput =
+        unsafe extern "system" fn(HANDLE, *const POINTER_TYPE_INFO, UINT) -> BOOL;
+    type DestroySyntheticPointerDevice = unsafe extern "system" fn(HANDLE);
+
+    // Available since Windows 10 1809, loaded at runtime not to break the older systems.
+    struct Api {
+        create: CreateSyntheticPointerDevice,
+        inject: InjectSyntheticPointerInput,
+        destroy: DestroySyntheticPointerDevice,
+    }
+
+    lazy_static::lazy_static! {
+        static ref API: Option<Api> = load_api();
+    }
+
+    fn load_api() -> Option<Api> {
+        unsafe {
+            let module = LoadLibraryA(CString::new("user32.dll").ok()?.as_ptr());
+            if module.is_null() {
+                return None;
+            }
+            let get = |name: &str| -> Option<usize> {
+                let f = GetProcAddress(module, CString::new(name).ok()?.as_ptr());
+                (!f.is_null()).then(|| f as usize)
+            };
+            Some(Api {
+                create: std::mem::transmute(get("CreateSyntheticPointerDevice")?),
+                inject: std::mem::transmute(get("InjectSyntheticPointerInput")?),
+                destroy: std::mem::transmute(get("DestroySyntheticPointerDevice")?),
+            })
+        }
+    }
+
+    pub fn is_supported() -> bool {
+        API.is_some()
+    }
+
+    struct Pen(HANDLE);
+
+    // The handle is only used with the lock of STATE.
+    unsafe impl Send for Pen {}
+
+    impl Drop for Pen {
+        fn drop(&mut self) {
+            if let Some(api) = API.as_ref() {
+                unsafe { (api.destroy)(self.0) };
+            }
+        }
+    }
+
+    pub fn create() -> ResultType<Box<dyn VirtualPen>> {
+        let Some(api) = API.as_ref() else {
+            bail!("synthetic pointer is not supported");
+        };
+        let device = unsafe { (api.create)(PT_PEN, 1, POINTER_FEEDBACK_DEFAULT) };
+        if device.is_null() {
+            bail!(
+                "CreateSyntheticPointerDevice: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Ok(Box::new(Pen(device)))
+    }
+
+    impl VirtualPen for Pen {
+        fn update(&mut self, evt: &PenEvent, last: Option<&PenEvent>) -> ResultType<()> {
+            let Some(api) = API.as_ref() else {
+                bail!("synthetic pointer is not supported");
+            };
+            crate::platform::windows::try_change_desktop();
+            let was_contact = last.map(|l| l.contact).unwrap_or(false);
+            let mut flags = if evt.in_range {
+                POINTER_FLAG_INRANGE
+            } else {
+                0
+            };
+            if evt.contact {
+                flags |= POINTER_FLAG_INCONTACT | POINTER_FLAG_FIRSTBUTTON;
+            }
+            flags |= match (was_contact, evt.contact) {
+                (false, true) => POINTER_FLAG_DOWN,
+                (true, false) => POINTER_FLAG_UP,
+                _ => POINTER_FLAG_UPDATE,
+            };
+            let mut pen_flags = 0;
+            if evt.buttons & BUTTON_BARREL != 0 {
+                flags |= POINTER_FLAG_SECONDBUTTON;
+                pen_flags |= PEN_FLAG_BARREL;
+            }
+            if evt.buttons & BUTTON_ERASER != 0 {
+                pen_flags |= PEN_FLAG_INVERTED;
+                if evt.contact {
+                    pen_flags |= PEN_FLAG_ERASER;
+                }
+            }
+            let mut pen_info: POINTER_PEN_INFO = unsafe { zeroed() };
+            pen_info.pointerInfo.pointerType = PT_PEN;
+            pen_info.pointerInfo.pointerFlags = flags;
+            pen_info.pointerInfo.ptPixelLocation = POINT { x: evt.x, y: evt.y };
+            pen_info.penFlags = pen_flags;
+            pen_info.penMask = PEN_MASK_PRESSURE | PEN_MASK_TILT_X | PEN_MASK_TILT_Y;
+            pen_info.pressure = if evt.contact { evt.pressure } else { 0 };
+            pen_info.tiltX = evt.tilt_x;
+            pen_info.tiltY = evt.tilt_y;
+            let info = POINTER_TYPE_INFO {
+                r#type: PT_PEN,
+                info: PointerInfoUnion { pen_info },
+            };
+            if unsafe { (api.inject)(self.0, &info, 1) } == 0 {
+                bail!(
+                    "InjectSyntheticPointerInput: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::*;
+    use core_graphics::{
+        event::{CGEvent, CGEventTapLocation, CGEventType, CGMouseButton, EventField},
+        event_source::{CGEventSource, CGEventSourceStateID},
+        geometry::CGPoint,
+    };
+    use hbb_common::anyhow::anyhow;
+
+    // kCGEventMouseSubtypeTabletPoint
+    const SUBTYPE_TABLET_POINT: i64 = 1;
+    // NSPointingDeviceType
+    const POINTER_TYPE_PEN: i64 = 1;
+    const POINTER_TYPE_ERASER: i64 = 3;
+
+    pub fn is_supported() -> bool {
+        true
+    }
+
+    struct Pen;
+
+    pub fn create() -> ResultType<Box<dyn VirtualPen>> {
+        Ok(Box::new(Pen))
+    }
+
+    fn source() -> ResultType<CGEventSource> {
+        CGEventSource::new(CGEventSourceStateID::HIDSystemState)
+            .map_err(|_| anyhow!("failed to create the event source"))
+    }
+
+    fn proximity(evt: &PenEvent, enter: bool) -> ResultType<()> {
+        let event = CGEvent::new(source()?).map_err(|_| anyhow!("failed to create the event"))?;
+        event.set_type(CGEventType::TabletProximity);
+        let pointer_type = if evt.buttons & BUTTON_ERASER != 0 {
+            POINTER_TYPE_ERASER
+        } else {
+            POINTER_TYPE_PEN
+        };
+        event.set_integer_value_field(
+            EventField::TABLET_PROXIMITY_EVENT_POINTER_TYPE,
+            pointer_type,
+        );
+        event.set_integer_value_field(
+            EventField::TABLET_PROXIMITY_EVENT_ENTER_PROXIMITY,
+            enter as _,
+        );
+        event.post(CGEventTapLocation::HID);
+        Ok(())
+    }
+
+    impl VirtualPen for Pen {
+        fn update(&mut self, evt: &PenEvent, last: Option<&PenEvent>) -> ResultType<()> {
+            if last.is_none() {
+                proximity(evt, true)?;
+            }
+            let was_contact = last.map(|l| l.contact).unwrap_or(false);
+            let event_type = match (was_contact, evt.contact) {
+                (false, true) => CGEventType::LeftMouseDown,
+                (true, false) => CGEventType::LeftMouseUp,
+                (true, true) => CGEventType::LeftMouseDragged,
+                (false, false) => CGEventType::MouseMoved,
+            };
+            let point = CGPoint::new(evt.x as _, evt.y as _);
+            let event = CGEvent::new_mouse_event(source()?, event_type, point, CGMouseButton::Left)
+                .map_err(|_| anyhow!("failed to create the event"))?;
+            let pressure = evt.pressure as f64 / MAX_PRESSURE as f64;
+            event.set_integer_value_field(EventField::MOUSE_EVENT_SUB_TYPE, SUBTYPE_TABLET_POINT);
+            event.set_double_value_field(EventField::MOUSE_EVENT_PRESSURE, pressure);
+            event.set_integer_value_field(
+                EventField::TABLET_EVENT_POINT_PRESSURE,
+                (pressure * u16::MAX as f64) as _,
+            );
+            event.set_double_value_field(
+                EventField::TABLET_EVENT_TILT_X,
+                evt.tilt_x as f64 / MAX_TILT as f64,
+            );
+            // up is positive on macOS
+            event.set_double_value_field(
+                EventField::TABLET_EVENT_TILT_Y,
+                -evt.tilt_y as f64 / MAX_TILT as f64,
+            );
+            // bit 0 is the tip, the barrel buttons follow
+            event.set_integer_value_field(
+                EventField::TABLET_EVENT_POINT_BUTTONS,
+                (evt.contact as u32 | (evt.buttons & (BUTTON_BARREL | BUTTON_BARREL2)) << 1) as _,
+            );
+            event.post(CGEventTapLocation::HID);
+            if !evt.in_range {
+                proximity(evt, false)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+    use evdev::{
+        uinput::{VirtualDevice, VirtualDeviceBuilder},
+        AbsInfo, AbsoluteAxisType, AttributeSet, BusType, EventType, InputEvent, InputId, Key,
+        PropType, UinputAbsSetup,
+    };
+
+    // units per mm and per radian, libinput needs them to treat the device as a tablet
+    const RESOLUTION: i32 = 4;
+    const TILT_RESOLUTION: i32 = 57;
+
+    // the area of the tablet needs the displays of X11
+    pub fn is_supported() -> bool {
+        crate::platform::linux::is_x11()
+            && std::fs::OpenOptions::new()
+                .write(true)
+                .open("/dev/uinput")
+                .is_ok()
+    }
+
+    struct Pen {
+        device: VirtualDevice,
+        // the area of all the displays, mapped to the whole tablet
+        rect: (i32, i32, i32, i32),
+    }
+
+    pub fn create() -> ResultType<Box<dyn VirtualPen>> {
+        let rect = display_service::try_get_displays_rect()?;
+        let mut keys = AttributeSet::<Key>::new();
+        for key in [
+            Key::BTN_TOOL_PEN,
+            Key::BTN_TOOL_RUBBER,
+            Key::BTN_TOUCH,
+            Key::BTN_STYLUS,
+            Key::BTN_STYLUS2,
+        ] {
+            keys.insert(key);
+        }
+        let mut props = AttributeSet::<PropType>::new();
+        props.insert(PropType::DIRECT);
+        let x = AbsInfo::new(rect.0, rect.0, rect.2 - 1, 0, 0, RESOLUTION);
+        let y = AbsInfo::new(rect.1, rect.1, rect.3 - 1, 0, 0, RESOLUTION);
+        let pressure = AbsInfo::new(0, 0, MAX_PRESSURE as _, 0, 0, 0);
+        let tilt = AbsInfo::new(0, -MAX_TILT, MAX_TILT, 0, 0, TILT_RESOLUTION);
+        let device = VirtualDeviceBuilder::new()?
+            .name("RustDesk Pen")
+            .input_id(InputId::new(BusType::BUS_VIRTUAL, 0, 0, 0))
+            .with_properties(&props)?
+            .with_keys(&keys)?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_X, x))?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_Y, y))?
+            .with_absolute_axis(&UinputAbsSetup::new(
+                AbsoluteAxisType::ABS_PRESSURE,
+                pressure,
+            ))?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_TILT_X, tilt))?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_TILT_Y, tilt))?
+            .build()?;
+        Ok(Box::new(Pen { device, rect }))
+    }
+
+    fn tool(evt: &PenEvent) -> Key {
+        if evt.buttons & BUTTON_ERASER != 0 {
+            Key::BTN_TOOL_RUBBER
+        } else {
+            Key::BTN_TOOL_PEN
+        }
+    }
+
+    impl VirtualPen for Pen {
+        // the area of the tablet is fixed once it is created
+        fn is_stale(&self) -> bool {
+            display_service::try_get_displays_rect()
+                .map(|r| r != self.rect)
+                .unwrap_or(false)
+        }
+
+        fn update(&mut self, evt: &PenEvent, last: Option<&PenEvent>) -> ResultType<()> {
+            let key = |key: Key, down: bool| InputEvent::new(EventType::KEY, key.code(), down as _);
+            let abs = |axis: AbsoluteAxisType, value: i32| {
+                InputEvent::new(EventType::ABSOLUTE, axis.0, value)
+            };
+            let mut events = vec![];
+            // the eraser end is another tool
+            if let Some(last_tool) = last.map(tool).filter(|t| *t != tool(evt)) {
+                events.push(key(last_tool, false));
+            }
+            let x = evt.x.clamp(self.rect.0, self.rect.2 - 1);
+            let y = evt.y.clamp(self.rect.1, self.rect.3 - 1);
+            let pressure = if evt.contact { evt.pressure } else { 0 };
+            events.extend([
+                abs(AbsoluteAxisType::ABS_X, x),
+                abs(AbsoluteAxisType::ABS_Y, y),
+                abs(AbsoluteAxisType::ABS_PRESSURE, pressure as _),
+                abs(AbsoluteAxisType::ABS_TILT_X, evt.tilt_x),
+                abs(AbsoluteAxisType::ABS_TILT_Y, evt.tilt_y),
+                key(tool(evt), evt.in_range),
+                key(Key::BTN_TOUCH, evt.contact),
+                key(Key::BTN_STYLUS, evt.buttons & BUTTON_BARREL != 0),
+                key(Key::BTN_STYLUS2, evt.buttons & BUTTON_BARREL2 != 0),
+            ]);
+            self.device.emit(&events)?;
+            Ok(())
+        }
+    }
+}
@@ -491,6 +491,11 @@ pub mod server {
                                             crate::input_service::handle_key_(&evt);
                                         }
                                     }
+                                    KillProcess(pid) => {
+                                        if let Err(err) = crate::server::task_manager::kill_process(pid) {
+                                            log::error!("Failed to kill process {}: {}", pid, err);
+                                        }
+                                    }
                                     _ => {}
                                 },
                                 _ => {}
@@ -756,6 +761,11 @@ pub mod client {
 
         #[cfg(feature = "vram")]
         fn set_output_texture(&mut self, _texture: bool) {}
+
+        #[cfg(feature = "vram")]
+        fn texture_supported(&self) -> bool {
+            false
+        }
     }
 
     pub(super) fn start_ipc_server() -> mpsc::UnboundedSender<Data> {
@@ -892,6 +902,13 @@ pub mod client {
         ipc_send(Data::DataPortableService(DataPortableService::Key(v)))
     }
 
+    /// Kills the process in the elevated portable service.
+    pub fn kill_process(pid: u32) {
+        allow_err!(ipc_send(Data::DataPortableService(
+            DataPortableService::KillProcess(pid)
+        )));
+    }
+
     pub fn create_capturer(
         current_display: usize,
         display: scrap::Display,
@@ -0,0 +1,246 @@
+// The presenter mode a peer switches on for a presentation: a halo around the cursor and a ripple
+// at each click of the peers, drawn on the frames before they are encoded as the watermark is, so
+// every peer sees them and the recordings keep them.
+//
+// They are drawn on the luma only, as the watermark, and on the last frame again while the screen
+// does not change, so the halo follows the cursor and the ripples spread on a still screen too.
+
+use std::{
+    collections::HashSet,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use hbb_common::message_proto::MouseEvent;
+use scrap::{EncodeYuvFormat, Pixfmt};
+
+const RIPPLE_DURATION: Duration = Duration::from_millis(600);
+// of 256
+const HALO_ALPHA: u32 = 80;
+const RING_ALPHA: u32 = 192;
+// the lines of the frame a pixel of the radius of the halo is for
+const LINES_PER_RADIUS: usize = 30;
+const MIN_RADIUS: usize = 12;
+
+lazy_static::lazy_static! {
+    // the connections which switched it on
+    static ref CONNS: Mutex<HashSet<i32>> = Default::default();
+    // in the coordinates of the screens
+    static ref CLICKS: Mutex<Vec<((i32, i32), Instant)>> = Default::default();
+}
+
+#[inline]
+pub fn enabled() -> bool {
+    !CONNS.lock().unwrap().is_empty()
+}
+
+pub fn set(conn_id: i32, on: bool) {
+    let mut conns = CONNS.lock().unwrap();
+    if on {
+        conns.insert(conn_id);
+    } else {
+        conns.remove(&conn_id);
+    }
+}
+
+pub fn on_connection_close(conn_id: i32) {
+    set(conn_id, false);
+}
+
+/// A ripple at the cursor if the event presses a button.
+pub fn on_mouse_event(evt: &MouseEvent) {
+    const MOUSE_TYPE_DOWN: i32 = 1;
+    if evt.mask & 0x7 != MOUSE_TYPE_DOWN || !enabled() {
+        return;
+    }
+    if let Some(pos) = cursor_pos() {
+        let now = Instant::now();
+        let mut clicks = CLICKS.lock().unwrap();
+        clicks.retain(|(_, t)| now.saturating_duration_since(*t) < RIPPLE_DURATION);
+        clicks.push((pos, now));
+    }
+}
+
+fn cursor_pos() -> Option<(i32, i32)> {
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    return super::input_service::latest_cursor_pos();
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    None
+}
+
+// what is drawn, in the coordinates of the frame
+#[derive(Clone, PartialEq, Default)]
+struct Marks {
+    cursor: Option<(i32, i32)>,
+    // the centers and the progress of the ripples, of 256
+    ripples: Vec<((i32, i32), u32)>,
+}
+
+impl Marks {
+    fn now(origin: (i32, i32)) -> Self {
+        let now = Instant::now();
+        let ripples = CLICKS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|((x, y), t)| {
+                let elapsed = now.saturating_duration_since(*t);
+                (elapsed < RIPPLE_DURATION).then(|| {
+                    (
+                        (x - origin.0, y - origin.1),
+                        (elapsed.as_millis() * 256 / RIPPLE_DURATION.as_millis()) as u32,
+                    )
+                })
+            })
+            .collect();
+        Self {
+            cursor: cursor_pos().map(|(x, y)| (x - origin.0, y - origin.1)),
+            ripples,
+        }
+    }
+}
+
+/// The marks of a display, with the frame they are drawn on.
+#[derive(Default)]
+pub struct Presenter {
+    // the frame as it was before the marks
+    base: Vec<u8>,
+    last: Option<Marks>,
+}
+
+impl Presenter {
+    /// Draws on the frame converted for the encoder, `origin` the top left of the display.
+    pub fn draw(&mut self, yuv: &mut [u8], fmt: &EncodeYuvFormat, origin: (i32, i32)) {
+        self.base.clear();
+        self.base.extend_from_slice(yuv);
+        self.last = None;
+        self.draw_marks(yuv, fmt, Marks::now(origin));
+    }
+
+    /// Draws on the last frame again if the marks changed since, true if they did.
+    pub fn redraw(&mut self, yuv: &mut [u8], fmt: &EncodeYuvFormat, origin: (i32, i32)) -> bool {
+        if self.base.len() != yuv.len() || !supported(fmt) {
+            return false;
+        }
+        let marks = Marks::now(origin);
+        if self.last.as_ref() == Some(&marks) {
+            return false;
+        }
+        yuv.copy_from_slice(&self.base);
+        self.draw_marks(yuv, fmt, marks);
+        true
+    }
+
+    fn draw_marks(&mut self, yuv: &mut [u8], fmt: &EncodeYuvFormat, marks: Marks) {
+        if !supported(fmt) {
+            return;
+        }
+        let stride = fmt.stride.first().cloned().unwrap_or(fmt.w);
+        draw_luma(yuv, fmt.w, fmt.h, stride, &marks);
+        self.last = Some(marks);
+    }
+}
+
+fn supported(fmt: &EncodeYuvFormat) -> bool {
+    matches!(fmt.pixfmt, Pixfmt::I420 | Pixfmt::NV12 | Pixfmt::I444)
+}
+
+fn draw_luma(luma: &mut [u8], w: usize, h: usize, stride: usize, marks: &Marks) {
+    if luma.len() < stride * h {
+        return;
+    }
+    let radius = (h / LINES_PER_RADIUS).max(MIN_RADIUS) as i32;
+    let thickness = (radius / 8).max(2);
+    let mut plane = Plane {
+        luma,
+        w: w as i32,
+        h: h as i32,
+        stride,
+    };
+    if let Some(center) = marks.cursor {
+        plane.ring(center, 0, radius, |v| lighten(v, HALO_ALPHA));
+        plane.ring(center, radius - thickness, radius, |v| {
+            contrast(v, RING_ALPHA)
+        });
+    }
+    for (center, progress) in marks.ripples.iter() {
+        // spreads from the halo and fades out
+        let r = radius / 2 + radius * 2 * *progress as i32 / 256;
+        let alpha = RING_ALPHA * (256 - progress) / 256;
+        plane.ring(*center, r - thickness, r, |v| contrast(v, alpha));
+    }
+}
+
+struct Plane<'a> {
+    luma: &'a mut [u8],
+    w: i32,
+    h: i32,
+    stride: usize,
+}
+
+impl Plane<'_> {
+    // the pixels between the two radii, a disc if `inner` is 0
+    fn ring(&mut self, (cx, cy): (i32, i32), inner: i32, outer: i32, f: impl Fn(u8) -> u8) {
+        let inner2 = if inner > 0 { inner * inner } else { -1 };
+        let outer2 = outer * outer;
+        for y in (cy - outer).max(0)..(cy + outer + 1).min(self.h) {
+            for x in (cx - outer).max(0)..(cx + outer + 1).min(self.w) {
+                let d2 = (x - cx) * (x - cx) + (y - cy) * (y - cy);
+                if d2 > inner2 && d2 <= outer2 {
+                    let p = &mut self.luma[y as usize * self.stride + x as usize];
+                    *p = f(*p);
+                }
+            }
+        }
+    }
+}
+
+fn lighten(v: u8, alpha: u32) -> u8 {
+    let v = v as u32;
+    (v + (255 - v) * alpha / 256) as u8
+}
+
+// lighter on the dark pixels and darker on the light ones, to show on any background
+fn contrast(v: u8, alpha: u32) -> u8 {
+    let v = v as u32;
+    if v < 128 {
+        (v + (255 - v) * alpha / 256) as u8
+    } else {
+        (v - v * alpha / 256) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_luma() {
+        let (w, h, stride) = (640, 360, 648);
+        let radius = MIN_RADIUS as i32;
+        let marks = Marks {
+            cursor: Some((0, 100)),
+            ripples: vec![((300, 200), 128)],
+        };
+        let mut luma = vec![16u8; stride * h];
+        draw_luma(&mut luma, w, h, stride, &marks);
+        // the halo, clipped on the left
+        assert_eq!(luma[100 * stride], lighten(16, HALO_ALPHA));
+        assert_eq!(
+            luma[100 * stride + radius as usize - 1],
+            contrast(lighten(16, HALO_ALPHA), RING_ALPHA)
+        );
+        assert_eq!(luma[100 * stride + radius as usize + 1], 16);
+        // the ripple half way, a ring
+        let r = (radius / 2 + radius) as usize;
+        assert_eq!(luma[200 * stride + 300], 16);
+        assert_eq!(
+            luma[200 * stride + 300 + r],
+            contrast(16, RING_ALPHA * 128 / 256)
+        );
+        // not on the padding of the rows
+        assert!((0..h).all(|y| luma[y * stride + w..(y + 1) * stride]
+            .iter()
+            .all(|v| *v == 16)));
+    }
+}
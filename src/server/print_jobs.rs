@@ -0,0 +1,49 @@
+// The documents of the virtual printer of this side sent to the peers printing them, see printer of
+// the crate. A job goes to the connection which turned the printer on last, so it is printed once
+// with several peers connected. The connection checks it with the DLP rules and records it as a
+// file sent before it sends it, and drops it if blocked.
+
+use super::*;
+use tokio::sync::mpsc;
+
+lazy_static::lazy_static! {
+    // the connections which turned it on, the last one last, and the channels the jobs are handed
+    // to them through
+    static ref RECEIVERS: Mutex<Vec<(i32, mpsc::UnboundedSender<Data>)>> = Default::default();
+}
+
+pub fn set_receiver(conn_id: i32, tx: mpsc::UnboundedSender<Data>, on: bool) {
+    let mut receivers = RECEIVERS.lock().unwrap();
+    receivers.retain(|r| r.0 != conn_id);
+    if on {
+        receivers.push((conn_id, tx));
+    }
+}
+
+pub fn on_connection_close(conn_id: i32) {
+    RECEIVERS.lock().unwrap().retain(|r| r.0 != conn_id);
+}
+
+pub fn send(title: String, copies: u32, data: Vec<u8>) -> ResultType<()> {
+    let receivers = RECEIVERS.lock().unwrap();
+    let Some((conn_id, tx)) = receivers.last() else {
+        bail!("No peer prints the documents of this side");
+    };
+    log::info!(
+        "print job '{}' of {} bytes handed to the connection {}",
+        title,
+        data.len(),
+        conn_id
+    );
+    if tx
+        .send(Data::PrintJob {
+            title,
+            copies,
+            data: data.into(),
+        })
+        .is_err()
+    {
+        bail!("The connection printing the documents is closed");
+    }
+    Ok(())
+}
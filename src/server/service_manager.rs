@@ -0,0 +1,259 @@
+// The services of this side listed to the peer and started, stopped or restarted at its request,
+// for the maintenance of a server without a desktop to speak of: the ones of the SCM on Windows,
+// the units of systemd on Linux and the jobs of launchd on macOS.
+//
+// The tools of the system are run for them, `systemctl`, `launchctl`, and PowerShell and `net` on
+// Windows, with the name of the service as an argument of its own, never through a shell. They run
+// as this side runs, so a service is controlled only if the service of this side is installed. Only
+// the services listed may be controlled, so that a path, or a unit or a domain of another kind, is
+// never taken for one. A request is answered from a thread of its own, as a service may take a
+// while to stop.
+
+use std::process::Command;
+
+use hbb_common::{
+    bail,
+    message_proto::{service_control::Action, ServiceInfo},
+    ResultType,
+};
+
+/// The services sorted by name.
+pub fn list() -> ResultType<Vec<ServiceInfo>> {
+    let mut services = list_()?;
+    services.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    Ok(services)
+}
+
+pub fn control(name: &str, action: Action) -> ResultType<()> {
+    // it must not be taken as an option or a path by the tool
+    if name.is_empty()
+        || name.starts_with('-')
+        || name.contains(|c: char| c == '/' || c == '\\' || c.is_control())
+    {
+        bail!("Invalid service name");
+    }
+    if !list_()?.iter().any(|s| s.name == name) {
+        bail!("No such service: {}", name);
+    }
+    control_(name, action)
+}
+
+fn run(cmd: &mut Command) -> ResultType<String> {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(winapi::um::winbase::CREATE_NO_WINDOW);
+    }
+    let output = cmd.output()?;
+    if !output.status.success() {
+        let mut err = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        if err.is_empty() {
+            err = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+        }
+        if err.is_empty() {
+            err = output.status.to_string();
+        }
+        bail!(err);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn list_() -> ResultType<Vec<ServiceInfo>> {
+    Ok(parse_units(&run(Command::new("systemctl").args([
+        "list-units",
+        "--type=service",
+        "--all",
+        "--no-legend",
+        "--no-pager",
+        "--plain",
+    ]))?))
+}
+
+#[cfg(target_os = "linux")]
+fn control_(name: &str, action: Action) -> ResultType<()> {
+    let verb = match action {
+        Action::Start => "start",
+        Action::Stop => "stop",
+        Action::Restart => "restart",
+    };
+    run(Command::new("systemctl").args([verb, "--", name]))?;
+    Ok(())
+}
+
+// unit, load, active, sub and the description, e.g.
+// "cron.service loaded active running Regular background program processing daemon"
+#[cfg(target_os = "linux")]
+fn parse_units(out: &str) -> Vec<ServiceInfo> {
+    out.lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let unit = parts.next()?;
+            let _load = parts.next()?;
+            let active = parts.next()?;
+            let sub = parts.next()?;
+            Some(ServiceInfo {
+                name: unit.trim_end_matches(".service").to_owned(),
+                description: parts.collect::<Vec<_>>().join(" "),
+                running: active == "active",
+                state: sub.to_owned(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+#[cfg(target_os = "macos")]
+fn list_() -> ResultType<Vec<ServiceInfo>> {
+    Ok(parse_jobs(&run(Command::new("launchctl").arg("list"))?))
+}
+
+#[cfg(target_os = "macos")]
+fn control_(name: &str, action: Action) -> ResultType<()> {
+    let target = format!("system/{}", name);
+    match action {
+        Action::Start => run(Command::new("launchctl").args(["kickstart", &target]))?,
+        Action::Stop => run(Command::new("launchctl").args(["kill", "SIGTERM", &target]))?,
+        Action::Restart => run(Command::new("launchctl").args(["kickstart", "-k", &target]))?,
+    };
+    Ok(())
+}
+
+// the pid, "-" if not running, the last exit status and the label, after a header
+#[cfg(target_os = "macos")]
+fn parse_jobs(out: &str) -> Vec<ServiceInfo> {
+    out.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut parts = line.split('\t');
+            let pid = parts.next()?.trim();
+            let status = parts.next()?.trim();
+            let label = parts.next()?.trim();
+            let running = pid != "-";
+            let state = if running {
+                "running".to_owned()
+            } else if status == "0" {
+                "stopped".to_owned()
+            } else {
+                format!("exited {}", status)
+            };
+            Some(ServiceInfo {
+                name: label.to_owned(),
+                running,
+                state,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn list_() -> ResultType<Vec<ServiceInfo>> {
+    let out = run(Command::new("powershell").args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-Command",
+        "Get-CimInstance Win32_Service | Select-Object Name,DisplayName,State | ConvertTo-Json -Compress",
+    ]))?;
+    parse_services(&out)
+}
+
+#[cfg(windows)]
+fn control_(name: &str, action: Action) -> ResultType<()> {
+    // net waits for the service to start or stop, as sc does not
+    let net = |verb: &str| run(Command::new("net").args([verb, name]));
+    match action {
+        Action::Start => net("start")?,
+        Action::Stop => net("stop")?,
+        // the one stopped already is started
+        Action::Restart => match net("stop") {
+            Ok(_) => net("start")?,
+            Err(err) => net("start").map_err(|_| err)?,
+        },
+    };
+    Ok(())
+}
+
+// an array of Name, DisplayName and State, or the object alone if one
+#[cfg(windows)]
+fn parse_services(out: &str) -> ResultType<Vec<ServiceInfo>> {
+    let value: serde_json::Value = serde_json::from_str(out.trim())?;
+    let items = match value {
+        serde_json::Value::Array(items) => items,
+        serde_json::Value::Null => vec![],
+        item => vec![item],
+    };
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let state = item["State"].as_str().unwrap_or_default();
+            Some(ServiceInfo {
+                name: item["Name"].as_str()?.to_owned(),
+                description: item["DisplayName"].as_str().unwrap_or_default().to_owned(),
+                running: state == "Running",
+                state: state.to_lowercase().replace(' ', "_"),
+                ..Default::default()
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_name() {
+        assert!(control("--now", Action::Stop).is_err());
+        assert!(control("", Action::Start).is_err());
+        assert!(control("a\nb", Action::Start).is_err());
+        assert!(control("/etc/systemd/system/a.service", Action::Start).is_err());
+        assert!(control("system/a", Action::Start).is_err());
+        assert!(control("no-such-service-of-the-test", Action::Start).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_units() {
+        let services = parse_units(
+            "cron.service loaded active running Regular background program processing daemon\n\
+             nginx.service not-found inactive dead nginx.service\n",
+        );
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].name, "cron");
+        assert!(services[0].running);
+        assert_eq!(
+            services[0].description,
+            "Regular background program processing daemon"
+        );
+        assert!(!services[1].running);
+        assert_eq!(services[1].state, "dead");
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_parse_jobs() {
+        let services = parse_jobs("PID\tStatus\tLabel\n123\t0\tcom.a\n-\t78\tcom.b\n");
+        assert_eq!(services.len(), 2);
+        assert!(services[0].running);
+        assert_eq!(services[1].state, "exited 78");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_parse_services() {
+        let services = parse_services(
+            r#"[{"Name":"Spooler","DisplayName":"Print Spooler","State":"Running"},{"Name":"a","DisplayName":"A","State":"Stop Pending"}]"#,
+        )
+        .unwrap();
+        assert_eq!(services.len(), 2);
+        assert!(services[0].running);
+        assert_eq!(services[1].state, "stop_pending");
+        assert_eq!(
+            parse_services(r#"{"Name":"a","DisplayName":"A","State":"Stopped"}"#)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
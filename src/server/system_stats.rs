@@ -0,0 +1,78 @@
+// The load of this side sent to the peers watching it, in the panel of the stats of their
+// sessions: the cpu as a whole and by core, the memory, the disks and the temperatures of the
+// sensors readable.
+//
+// A connection samples them on its own while its peer watches, every few seconds, as the usage
+// of the cpu is the one between two refreshes.
+
+use std::time::{Duration, Instant};
+
+use hbb_common::{
+    message_proto::{system_stats, SystemStats},
+    sysinfo::System,
+};
+
+const INTERVAL: Duration = Duration::from_secs(2);
+
+pub struct Sampler {
+    sys: System,
+    last: Instant,
+}
+
+impl Sampler {
+    pub fn new() -> Self {
+        let mut sys = System::new();
+        sys.refresh_cpu();
+        sys.refresh_disks_list();
+        sys.refresh_components_list();
+        Self {
+            sys,
+            last: Instant::now(),
+        }
+    }
+
+    /// The stats to send, once an interval.
+    pub fn next(&mut self) -> Option<SystemStats> {
+        if self.last.elapsed() < INTERVAL {
+            return None;
+        }
+        self.last = Instant::now();
+        Some(self.sample())
+    }
+
+    fn sample(&mut self) -> SystemStats {
+        let sys = &mut self.sys;
+        sys.refresh_cpu();
+        sys.refresh_memory();
+        sys.refresh_disks();
+        sys.refresh_components();
+        SystemStats {
+            cpu: sys.global_cpu_info().cpu_usage(),
+            cores: sys.cpus().iter().map(|c| c.cpu_usage()).collect(),
+            memory_used: sys.used_memory(),
+            memory_total: sys.total_memory(),
+            disks: sys
+                .disks()
+                .iter()
+                .filter(|d| d.total_space() > 0)
+                .map(|d| system_stats::Disk {
+                    mount_point: d.mount_point().to_string_lossy().to_string(),
+                    total: d.total_space(),
+                    available: d.available_space(),
+                    ..Default::default()
+                })
+                .collect(),
+            temperatures: sys
+                .components()
+                .iter()
+                .filter(|c| c.temperature().is_finite() && c.temperature() > 0.)
+                .map(|c| system_stats::Temperature {
+                    label: c.label().to_owned(),
+                    celsius: c.temperature(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,237 @@
+// The processes of this side listed to the peer and killed at its request, so a runaway one is
+// handled without the task manager of the desktop over a slow video stream.
+//
+// The list is sorted and cut here, as the peer asks, to keep it small on a slow link. The usage of
+// the cpu is the one between two lists, so it is 0 in the first one. A process is killed as this
+// side runs, and one of another user or elevated is handed to the portable service on Windows if it
+// runs, the peer is told to request the elevation otherwise. Only the processes of the last list
+// sent are killed, and only while their pids are not reused.
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use hbb_common::{
+    message_proto::{list_processes::Sort, ListProcesses, ProcessInfo, ProcessKilled, ProcessList},
+    sysinfo::System,
+};
+
+pub struct Lister {
+    sys: System,
+    // the start times of the processes in the last list sent, by pid
+    listed: HashMap<u32, u64>,
+}
+
+impl Lister {
+    pub fn new() -> Self {
+        let mut sys = System::new();
+        sys.refresh_users_list();
+        Self {
+            sys,
+            listed: Default::default(),
+        }
+    }
+
+    pub fn list(&mut self, req: &ListProcesses) -> ProcessList {
+        let sys = &mut self.sys;
+        sys.refresh_processes();
+        let mut processes: Vec<ProcessInfo> = sys
+            .processes()
+            .values()
+            .map(|p| ProcessInfo {
+                pid: p.pid().as_u32(),
+                parent: p.parent().map(|p| p.as_u32()).unwrap_or_default(),
+                name: p.name().to_owned(),
+                user: p
+                    .user_id()
+                    .and_then(|uid| sys.get_user_by_id(uid))
+                    .map(|u| u.name().to_owned())
+                    .unwrap_or_default(),
+                cpu: p.cpu_usage(),
+                memory: p.memory(),
+                ..Default::default()
+            })
+            .collect();
+        let total = processes.len() as u32;
+        sort(
+            &mut processes,
+            req.sort.enum_value_or_default(),
+            req.ascending,
+        );
+        if req.limit > 0 {
+            processes.truncate(req.limit as _);
+        }
+        self.listed = processes
+            .iter()
+            .filter_map(|p| {
+                sys.process((p.pid as usize).into())
+                    .map(|x| (p.pid, x.start_time()))
+            })
+            .collect();
+        ProcessList {
+            processes,
+            total,
+            ..Default::default()
+        }
+    }
+
+    /// Kills the process if it is the one of the last list sent.
+    pub fn kill(&mut self, pid: u32) -> ProcessKilled {
+        let listed = self.listed.get(&pid).copied();
+        let same = listed.is_some()
+            && self.sys.refresh_process((pid as usize).into())
+            && self
+                .sys
+                .process((pid as usize).into())
+                .map(|p| p.start_time())
+                == listed;
+        if !same {
+            return ProcessKilled {
+                pid,
+                error: "The process is not in the list".to_owned(),
+                ..Default::default()
+            };
+        }
+        kill(pid)
+    }
+}
+
+fn sort(processes: &mut [ProcessInfo], by: Sort, ascending: bool) {
+    processes.sort_by(|a, b| {
+        let ord = match by {
+            Sort::Cpu => a.cpu.partial_cmp(&b.cpu).unwrap_or(Ordering::Equal),
+            Sort::Memory => a.memory.cmp(&b.memory),
+            Sort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            Sort::Pid => a.pid.cmp(&b.pid),
+        }
+        .then(a.pid.cmp(&b.pid));
+        if ascending {
+            ord
+        } else {
+            ord.reverse()
+        }
+    });
+}
+
+/// Kills the process, or hands it to the elevated portable service.
+pub fn kill(pid: u32) -> ProcessKilled {
+    let mut res = ProcessKilled {
+        pid,
+        ..Default::default()
+    };
+    if pid == std::process::id() {
+        res.error = "The process of this side is not to be killed".to_owned();
+        return res;
+    }
+    if let Err(err) = kill_process(pid) {
+        if err.kind() == std::io::ErrorKind::PermissionDenied {
+            #[cfg(windows)]
+            if crate::portable_service::client::running() {
+                crate::portable_service::client::kill_process(pid);
+                return res;
+            }
+            res.elevation_required = true;
+        }
+        res.error = err.to_string();
+    }
+    res
+}
+
+// 0 and the negative ones stand for groups of processes to kill(2), 1 is init
+fn check_pid(pid: u32) -> std::io::Result<()> {
+    if pid <= 1 || pid > i32::MAX as u32 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid process id: {}", pid),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn kill_process(pid: u32) -> std::io::Result<()> {
+    use winapi::um::{
+        handleapi::CloseHandle,
+        processthreadsapi::{OpenProcess, TerminateProcess},
+        winnt::PROCESS_TERMINATE,
+    };
+
+    check_pid(pid)?;
+
+    unsafe {
+        let handle = OpenProcess(PROCESS_TERMINATE, 0, pid);
+        if handle.is_null() {
+            return Err(std::io::Error::last_os_error());
+        }
+        let ok = TerminateProcess(handle, 1);
+        let err = std::io::Error::last_os_error();
+        CloseHandle(handle);
+        if ok == 0 {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+pub fn kill_process(pid: u32) -> std::io::Result<()> {
+    check_pid(pid)?;
+    if unsafe { hbb_common::libc::kill(pid as _, hbb_common::libc::SIGKILL) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, name: &str, cpu: f32, memory: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: name.to_owned(),
+            cpu,
+            memory,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut processes = vec![
+            process(3, "b", 5., 100),
+            process(1, "C", 50., 10),
+            process(2, "a", 5., 1000),
+        ];
+        let pids = |p: &[ProcessInfo]| p.iter().map(|p| p.pid).collect::<Vec<_>>();
+        sort(&mut processes, Sort::Cpu, false);
+        assert_eq!(pids(&processes), vec![1, 3, 2]);
+        sort(&mut processes, Sort::Memory, false);
+        assert_eq!(pids(&processes), vec![2, 3, 1]);
+        sort(&mut processes, Sort::Name, true);
+        assert_eq!(pids(&processes), vec![2, 3, 1]);
+        sort(&mut processes, Sort::Pid, true);
+        assert_eq!(pids(&processes), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_kill_self() {
+        let res = kill(std::process::id());
+        assert!(!res.error.is_empty());
+        assert!(!res.elevation_required);
+    }
+
+    #[test]
+    fn test_kill_invalid() {
+        for pid in [0, 1, i32::MAX as u32 + 1, u32::MAX] {
+            let err = kill_process(pid).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_kill_unlisted() {
+        let mut lister = Lister::new();
+        let res = lister.kill(std::process::id() + 1);
+        assert!(!res.error.is_empty());
+        assert!(!res.elevation_required);
+    }
+}
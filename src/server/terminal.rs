@@ -0,0 +1,267 @@
+// The terminals of this side the peer opens: shells in ptys streamed over the connection, so a quick
+// fix needs neither the video nor the control of the desktop.
+//
+// A terminal is told apart by the id the peer gives it, a connection opens a few at most. The
+// output is read by a thread a terminal and sent as it comes, so the bytes of a utf-8 char may be
+// split over two messages, which the peer decodes as a stream. The shell is the one of the option,
+// or PowerShell (cmd if missing) on Windows and the login shell elsewhere, run as the user this side
+// runs as. The terminals are killed with the connection, or once the permission is switched off.
+
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    sync::Arc,
+};
+
+use hbb_common::{
+    allow_err,
+    anyhow::anyhow,
+    bail,
+    config::{keys, Config},
+    log,
+    message_proto::*,
+    ResultType,
+};
+use portable_pty::{native_pty_system, ChildKiller, CommandBuilder, MasterPty, PtySize};
+
+use super::{service::Subscriber, ConnInner};
+
+const MAX_TERMINALS: usize = 8;
+const READ_BUF_SIZE: usize = 16 * 1024;
+const DEFAULT_ROWS: u16 = 24;
+const DEFAULT_COLS: u16 = 80;
+
+struct Terminal {
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    killer: Box<dyn ChildKiller + Send + Sync>,
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        self.killer.kill().ok();
+    }
+}
+
+/// The terminals a connection opened.
+#[derive(Default)]
+pub struct Terminals {
+    terminals: HashMap<i32, Terminal>,
+}
+
+impl Terminals {
+    pub fn handle(&mut self, action: TerminalAction, inner: &ConnInner) {
+        match action.union {
+            Some(terminal_action::Union::Open(open)) => {
+                let res = if self.terminals.contains_key(&open.id) {
+                    Err(anyhow!("Terminal {} is open", open.id))
+                } else if self.terminals.len() >= MAX_TERMINALS {
+                    Err(anyhow!("Too many terminals"))
+                } else {
+                    Self::open(&open, inner)
+                };
+                let mut opened = TerminalOpened {
+                    id: open.id,
+                    ..Default::default()
+                };
+                match res {
+                    Ok((terminal, shell)) => {
+                        log::info!("terminal {} opened: {}", open.id, shell);
+                        self.terminals.insert(open.id, terminal);
+                        opened.shell = shell;
+                    }
+                    Err(err) => {
+                        log::error!("Failed to open terminal {}: {}", open.id, err);
+                        opened.error = err.to_string();
+                    }
+                }
+                let mut response = TerminalResponse::new();
+                response.set_opened(opened);
+                inner.clone().send(Arc::new(response_msg(response)));
+            }
+            Some(terminal_action::Union::Resize(resize)) => {
+                if let Some(terminal) = self.terminals.get(&resize.id) {
+                    allow_err!(terminal.master.resize(size(resize.rows, resize.cols)));
+                }
+            }
+            Some(terminal_action::Union::Data(data)) => {
+                if let Some(terminal) = self.terminals.get_mut(&data.id) {
+                    if let Err(err) = terminal
+                        .writer
+                        .write_all(&data.data)
+                        .and_then(|_| terminal.writer.flush())
+                    {
+                        log::error!("Failed to write to terminal {}: {}", data.id, err);
+                    }
+                }
+            }
+            Some(terminal_action::Union::Close(close)) => {
+                // taken away once its closed is sent, see on_closed
+                if let Some(terminal) = self.terminals.get_mut(&close.id) {
+                    terminal.killer.kill().ok();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The shell of the terminal exited, the closed is on its way to the peer.
+    pub fn on_closed(&mut self, id: i32) {
+        if self.terminals.remove(&id).is_some() {
+            log::info!("terminal {} closed", id);
+        }
+    }
+
+    pub fn close_all(&mut self) {
+        self.terminals.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terminals.is_empty()
+    }
+
+    fn open(open: &OpenTerminal, inner: &ConnInner) -> ResultType<(Terminal, String)> {
+        let (cmd, shell) = shell()?;
+        let pair = native_pty_system().openpty(size(open.rows, open.cols))?;
+        let mut child = pair.slave.spawn_command(cmd)?;
+        // the reader ends once the shell and the ones it started have it no more
+        drop(pair.slave);
+        let reader = pair.master.try_clone_reader()?;
+        let writer = pair.master.take_writer()?;
+        let killer = child.clone_killer();
+        let id = open.id;
+        let tx = inner.clone();
+        let read = std::thread::spawn(move || read_loop(id, reader, tx));
+        let mut tx = inner.clone();
+        std::thread::spawn(move || {
+            let exit_code = match child.wait() {
+                Ok(status) => status.exit_code() as i32,
+                Err(_) => -1,
+            };
+            // the pty of conpty is not closed with the shell, the output left is dropped then
+            #[cfg(not(windows))]
+            read.join().ok();
+            #[cfg(windows)]
+            drop(read);
+            let mut response = TerminalResponse::new();
+            response.set_closed(TerminalClosed {
+                id,
+                exit_code,
+                ..Default::default()
+            });
+            tx.send(Arc::new(response_msg(response)));
+        });
+        Ok((
+            Terminal {
+                master: pair.master,
+                writer,
+                killer,
+            },
+            shell,
+        ))
+    }
+}
+
+fn read_loop(id: i32, mut reader: Box<dyn Read + Send>, mut tx: ConnInner) {
+    let mut buf = vec![0u8; READ_BUF_SIZE];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                let mut response = TerminalResponse::new();
+                response.set_data(TerminalData {
+                    id,
+                    data: buf[..n].to_vec().into(),
+                    ..Default::default()
+                });
+                tx.send(Arc::new(response_msg(response)));
+            }
+        }
+    }
+}
+
+fn response_msg(response: TerminalResponse) -> Message {
+    let mut msg = Message::new();
+    msg.set_terminal_response(response);
+    msg
+}
+
+fn size(rows: u32, cols: u32) -> PtySize {
+    let fit = |v: u32, default: u16| {
+        if v == 0 {
+            default
+        } else {
+            v.min(u16::MAX as u32) as u16
+        }
+    };
+    PtySize {
+        rows: fit(rows, DEFAULT_ROWS),
+        cols: fit(cols, DEFAULT_COLS),
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+// the command of the shell, and how it is shown to the peer
+fn shell() -> ResultType<(CommandBuilder, String)> {
+    let option = Config::get_option(keys::OPTION_TERMINAL_SHELL);
+    let mut args: Vec<String> = option.split_whitespace().map(|x| x.to_owned()).collect();
+    if args.is_empty() {
+        args = default_shell();
+    }
+    if args.is_empty() {
+        bail!("No shell found");
+    }
+    let shell = args.join(" ");
+    let mut cmd = CommandBuilder::from_argv(args.into_iter().map(Into::into).collect());
+    cmd.env("TERM", "xterm-256color");
+    // the service has no locale of a user, the shell would not take utf-8 otherwise
+    #[cfg(not(windows))]
+    if std::env::var("LANG").unwrap_or_default().is_empty() {
+        cmd.env("LANG", "C.UTF-8");
+    }
+    Ok((cmd, shell))
+}
+
+#[cfg(windows)]
+fn default_shell() -> Vec<String> {
+    let system32 =
+        std::path::Path::new(&std::env::var("SystemRoot").unwrap_or("C:\\Windows".into()))
+            .join("System32");
+    let powershell = system32.join("WindowsPowerShell\\v1.0\\powershell.exe");
+    if powershell.exists() {
+        return vec![
+            powershell.to_string_lossy().to_string(),
+            "-NoLogo".to_owned(),
+        ];
+    }
+    let cmd =
+        std::env::var("ComSpec").unwrap_or(system32.join("cmd.exe").to_string_lossy().to_string());
+    vec![cmd]
+}
+
+#[cfg(not(windows))]
+fn default_shell() -> Vec<String> {
+    let shell = std::env::var("SHELL").unwrap_or_default();
+    let shell = if !shell.is_empty() {
+        shell
+    } else if std::path::Path::new("/bin/bash").exists() {
+        "/bin/bash".to_owned()
+    } else {
+        "/bin/sh".to_owned()
+    };
+    vec![shell, "-l".to_owned()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size() {
+        let s = size(0, 0);
+        assert_eq!((s.rows, s.cols), (DEFAULT_ROWS, DEFAULT_COLS));
+        let s = size(50, 1_000_000);
+        assert_eq!((s.rows, s.cols), (50, u16::MAX));
+    }
+}
@@ -0,0 +1,354 @@
+// Inject the touch points of the controlling side as native touch, so the remote apps get the
+// multi-finger gestures like pinch-zoom themselves.
+//
+// Windows uses the touch injection, linux plugs a uinput multi-touch screen, which needs X11 and
+// write access to /dev/uinput.
+
+use super::*;
+
+// Each finger takes a slot until it is lifted.
+const MAX_CONTACTS: usize = 10;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Phase {
+    Down,
+    Move,
+    Up,
+}
+
+#[derive(Clone, Copy)]
+struct Contact {
+    slot: usize,
+    x: i32,
+    y: i32,
+    phase: Phase,
+}
+
+trait VirtualTouch: Send {
+    // All the contacts in the slots, including the lifted ones.
+    fn update(&mut self, contacts: &[Contact]) -> ResultType<()>;
+
+    // checked when the first finger touches, the device is created again if true
+    fn is_stale(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Default)]
+struct State {
+    device: Option<Box<dyn VirtualTouch>>,
+    // not created again in this process
+    failed: bool,
+    // the id of the controlling side and the position
+    slots: [Option<(u32, i32, i32)>; MAX_CONTACTS],
+}
+
+lazy_static::lazy_static! {
+    static ref STATE: Mutex<State> = Default::default();
+}
+
+pub fn is_supported() -> bool {
+    platform::is_supported()
+}
+
+pub fn handle(points: &TouchPoints) {
+    let mut state = STATE.lock().unwrap();
+    let idle = state.slots.iter().all(|s| s.is_none());
+    let contacts = update_slots(&mut state.slots, &points.points);
+    if contacts.is_empty() {
+        return;
+    }
+    if idle && state.device.as_ref().map(|d| d.is_stale()).unwrap_or(false) {
+        state.device = None;
+    }
+    if state.device.is_none() {
+        if state.failed {
+            state.slots = Default::default();
+            return;
+        }
+        match platform::create() {
+            Ok(device) => state.device = Some(device),
+            Err(e) => {
+                log::error!("failed to create the virtual touch screen: {e}");
+                state.failed = true;
+                state.slots = Default::default();
+                return;
+            }
+        }
+    }
+    if let Some(device) = state.device.as_mut() {
+        if let Err(e) = device.update(&contacts) {
+            log::error!("failed to inject the touch: {e}");
+            // created again on the next touch
+            state.device = None;
+            state.slots = Default::default();
+        }
+    }
+}
+
+fn update_slots(
+    slots: &mut [Option<(u32, i32, i32)>; MAX_CONTACTS],
+    points: &[TouchPoint],
+) -> Vec<Contact> {
+    let mut contacts = vec![];
+    for (slot, s) in slots.iter_mut().enumerate() {
+        if let Some((id, x, y)) = *s {
+            if let Some(p) = points.iter().find(|p| p.id == id) {
+                *s = Some((id, p.x, p.y));
+                contacts.push(Contact {
+                    slot,
+                    x: p.x,
+                    y: p.y,
+                    phase: Phase::Move,
+                });
+            } else {
+                *s = None;
+                contacts.push(Contact {
+                    slot,
+                    x,
+                    y,
+                    phase: Phase::Up,
+                });
+            }
+        }
+    }
+    for p in points.iter() {
+        if slots.iter().any(|s| s.map(|s| s.0) == Some(p.id)) {
+            continue;
+        }
+        // more fingers than the slots are ignored
+        let Some(slot) = slots.iter().position(|s| s.is_none()) else {
+            break;
+        };
+        slots[slot] = Some((p.id, p.x, p.y));
+        contacts.push(Contact {
+            slot,
+            x: p.x,
+            y: p.y,
+            phase: Phase::Down,
+        });
+    }
+    contacts
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::*;
+    use std::{ffi::CString, mem::zeroed};
+    use winapi::{
+        shared::{
+            minwindef::{BOOL, DWORD},
+            windef::{POINT, RECT},
+        },
+        um::{
+            libloaderapi::{GetProcAddress, LoadLibraryA},
+            winuser::*,
+        },
+    };
+
+    // the radius of a finger in pixels
+    const CONTACT_RADIUS: i32 = 2;
+
+    type InitializeTouchInjection = unsafe extern "system" fn(u32, DWORD) -> BOOL;
+    type InjectTouchInput = unsafe extern "system" fn(u32, *const POINTER_TOUCH_INFO) -> BOOL;
+
+    // Windows 8 or later.
+    struct Api {
+        initialize: InitializeTouchInjection,
+        inject: InjectTouchInput,
+    }
+
+    lazy_static::lazy_static! {
+        static ref API: Option<Api> = load_api();
+    }
+
+    fn load_api() -> Option<Api> {
+        unsafe {
+            let module = LoadLibraryA(CString::new("user32.dll").ok()?.as_ptr());
+            if module.is_null() {
+                return None;
+            }
+            let get = |name: &str| -> Option<usize> {
+                let f = GetProcAddress(module, CString::new(name).ok()?.as_ptr());
+                (!f.is_null()).then(|| f as usize)
+            };
+            Some(Api {
+                initialize: std::mem::transmute(get("InitializeTouchInjection")?),
+                inject: std::mem::transmute(get("InjectTouchInput")?),
+            })
+        }
+    }
+
+    pub fn is_supported() -> bool {
+        API.is_some()
+    }
+
+    struct Touch;
+
+    pub fn create() -> ResultType<Box<dyn VirtualTouch>> {
+        let Some(api) = API.as_ref() else {
+            bail!("touch injection is not supported");
+        };
+        if unsafe { (api.initialize)(MAX_CONTACTS as _, TOUCH_FEEDBACK_DEFAULT) } == 0 {
+            bail!(
+                "InitializeTouchInjection: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Ok(Box::new(Touch))
+    }
+
+    impl VirtualTouch for Touch {
+        fn update(&mut self, contacts: &[Contact]) -> ResultType<()> {
+            let Some(api) = API.as_ref() else {
+                bail!("touch injection is not supported");
+            };
+            crate::platform::windows::try_change_desktop();
+            let infos: Vec<POINTER_TOUCH_INFO> = contacts
+                .iter()
+                .map(|c| {
+                    let mut info: POINTER_TOUCH_INFO = unsafe { zeroed() };
+                    info.pointerInfo.pointerType = PT_TOUCH;
+                    info.pointerInfo.pointerId = c.slot as _;
+                    info.pointerInfo.ptPixelLocation = POINT { x: c.x, y: c.y };
+                    info.pointerInfo.pointerFlags = match c.phase {
+                        Phase::Down => {
+                            POINTER_FLAG_DOWN | POINTER_FLAG_INRANGE | POINTER_FLAG_INCONTACT
+                        }
+                        Phase::Move => {
+                            POINTER_FLAG_UPDATE | POINTER_FLAG_INRANGE | POINTER_FLAG_INCONTACT
+                        }
+                        Phase::Up => POINTER_FLAG_UP,
+                    };
+                    info.touchMask = TOUCH_MASK_CONTACTAREA;
+                    info.rcContact = RECT {
+                        left: c.x - CONTACT_RADIUS,
+                        top: c.y - CONTACT_RADIUS,
+                        right: c.x + CONTACT_RADIUS,
+                        bottom: c.y + CONTACT_RADIUS,
+                    };
+                    info
+                })
+                .collect();
+            if unsafe { (api.inject)(infos.len() as _, infos.as_ptr()) } == 0 {
+                bail!("InjectTouchInput: {}", std::io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::*;
+    use evdev::{
+        uinput::{VirtualDevice, VirtualDeviceBuilder},
+        AbsInfo, AbsoluteAxisType, AttributeSet, BusType, EventType, InputEvent, InputId, Key,
+        PropType, UinputAbsSetup,
+    };
+
+    // units per mm, libinput ignores the touch screens without it
+    const RESOLUTION: i32 = 4;
+
+    // the area of the screen needs the displays of X11
+    pub fn is_supported() -> bool {
+        crate::platform::linux::is_x11()
+            && std::fs::OpenOptions::new()
+                .write(true)
+                .open("/dev/uinput")
+                .is_ok()
+    }
+
+    struct Touch {
+        device: VirtualDevice,
+        // the area of all the displays
+        rect: (i32, i32, i32, i32),
+        // increased for each finger, the slot is reused
+        tracking_id: i32,
+    }
+
+    pub fn create() -> ResultType<Box<dyn VirtualTouch>> {
+        let rect = display_service::try_get_displays_rect()?;
+        let mut keys = AttributeSet::<Key>::new();
+        keys.insert(Key::BTN_TOUCH);
+        let mut props = AttributeSet::<PropType>::new();
+        props.insert(PropType::DIRECT);
+        let x = AbsInfo::new(rect.0, rect.0, rect.2 - 1, 0, 0, RESOLUTION);
+        let y = AbsInfo::new(rect.1, rect.1, rect.3 - 1, 0, 0, RESOLUTION);
+        let slot = AbsInfo::new(0, 0, MAX_CONTACTS as i32 - 1, 0, 0, 0);
+        let tracking_id = AbsInfo::new(0, 0, u16::MAX as _, 0, 0, 0);
+        let device = VirtualDeviceBuilder::new()?
+            .name("RustDesk Touch")
+            .input_id(InputId::new(BusType::BUS_VIRTUAL, 0, 0, 0))
+            .with_properties(&props)?
+            .with_keys(&keys)?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_X, x))?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_Y, y))?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_MT_SLOT, slot))?
+            .with_absolute_axis(&UinputAbsSetup::new(
+                AbsoluteAxisType::ABS_MT_TRACKING_ID,
+                tracking_id,
+            ))?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_MT_POSITION_X, x))?
+            .with_absolute_axis(&UinputAbsSetup::new(AbsoluteAxisType::ABS_MT_POSITION_Y, y))?
+            .build()?;
+        Ok(Box::new(Touch {
+            device,
+            rect,
+            tracking_id: 0,
+        }))
+    }
+
+    impl VirtualTouch for Touch {
+        // the area of the screen is fixed once it is created
+        fn is_stale(&self) -> bool {
+            display_service::try_get_displays_rect()
+                .map(|r| r != self.rect)
+                .unwrap_or(false)
+        }
+
+        fn update(&mut self, contacts: &[Contact]) -> ResultType<()> {
+            let abs = |axis: AbsoluteAxisType, value: i32| {
+                InputEvent::new(EventType::ABSOLUTE, axis.0, value)
+            };
+            let mut events = vec![];
+            for c in contacts.iter() {
+                events.push(abs(AbsoluteAxisType::ABS_MT_SLOT, c.slot as _));
+                match c.phase {
+                    Phase::Up => {
+                        events.push(abs(AbsoluteAxisType::ABS_MT_TRACKING_ID, -1));
+                        continue;
+                    }
+                    Phase::Down => {
+                        self.tracking_id = (self.tracking_id + 1) % (u16::MAX as i32 + 1);
+                        events.push(abs(AbsoluteAxisType::ABS_MT_TRACKING_ID, self.tracking_id));
+                    }
+                    Phase::Move => {}
+                }
+                let x = c.x.clamp(self.rect.0, self.rect.2 - 1);
+                let y = c.y.clamp(self.rect.1, self.rect.3 - 1);
+                events.push(abs(AbsoluteAxisType::ABS_MT_POSITION_X, x));
+                events.push(abs(AbsoluteAxisType::ABS_MT_POSITION_Y, y));
+            }
+            // the single touch events of the first finger
+            let first = contacts.iter().find(|c| c.phase != Phase::Up);
+            if let Some(c) = first {
+                events.push(abs(
+                    AbsoluteAxisType::ABS_X,
+                    c.x.clamp(self.rect.0, self.rect.2 - 1),
+                ));
+                events.push(abs(
+                    AbsoluteAxisType::ABS_Y,
+                    c.y.clamp(self.rect.1, self.rect.3 - 1),
+                ));
+            }
+            events.push(InputEvent::new(
+                EventType::KEY,
+                Key::BTN_TOUCH.code(),
+                first.is_some() as _,
+            ));
+            self.device.emit(&events)?;
+            Ok(())
+        }
+    }
+}
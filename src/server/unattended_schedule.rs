@@ -0,0 +1,145 @@
+// The windows of the time the unattended connections, the ones logging in with a password or as a
+// recent session, are accepted in, e.g. the working hours of an office. Outside of them the
+// connection is to be accepted by the user of this side as in the click mode, or refused if set so.
+//
+// One window a line, or separated by semicolons: the days and the time of the day, either of them
+// optional, in local time, and the id of a peer before it for the windows of that peer, e.g.
+//
+//     mon-fri 08:00-18:00
+//     123456789: sat,sun 10:00-12:00
+//
+// The windows of a peer replace the ones of all for it. There is no schedule without any window.
+
+use std::str::FromStr;
+
+use hbb_common::{
+    anyhow::{anyhow, Error},
+    bail, log, ResultType,
+};
+
+use super::ip_rules::{in_window, now, parse_days, parse_time, ALL_DAYS};
+
+#[derive(Debug)]
+pub struct Window {
+    peer: Option<String>,
+    // bit 0 for monday
+    days: u8,
+    // in minutes of the day, as the ones of the ip rules
+    time: Option<(u32, u32)>,
+}
+
+impl FromStr for Window {
+    type Err = Error;
+
+    fn from_str(text: &str) -> ResultType<Self> {
+        let mut parts = text.split_whitespace().peekable();
+        let peer = match parts.peek() {
+            Some(p) if p.ends_with(':') && !p.contains('-') => {
+                let peer = p.trim_end_matches(':').to_owned();
+                parts.next();
+                Some(peer)
+            }
+            _ => None,
+        };
+        let mut window = Self {
+            peer,
+            days: ALL_DAYS,
+            time: None,
+        };
+        let mut days = false;
+        for part in parts {
+            if part.contains(':') && window.time.is_none() {
+                window.time = Some(
+                    parse_time(part)
+                        .ok_or_else(|| anyhow!("Invalid time of the window: {}", text))?,
+                );
+            } else if !days {
+                window.days = parse_days(part)
+                    .ok_or_else(|| anyhow!("Invalid days of the window: {}", text))?;
+                days = true;
+            } else {
+                bail!("Invalid window: {}", text);
+            }
+        }
+        if !days && window.time.is_none() {
+            bail!("Invalid window, the days or the time expected: {}", text);
+        }
+        Ok(window)
+    }
+}
+
+pub fn parse(value: &str) -> ResultType<Vec<Window>> {
+    value
+        .split(|c| c == ';' || c == '\n')
+        .filter(|w| !w.trim().is_empty())
+        .map(Window::from_str)
+        .collect()
+}
+
+/// If an unattended connection of the peer is accepted now.
+pub fn allows(value: &str, peer: &str) -> bool {
+    if value.trim().is_empty() {
+        return true;
+    }
+    let (weekday, minute) = now();
+    allows_at(value, peer, weekday, minute)
+}
+
+fn allows_at(value: &str, peer: &str, weekday: u32, minute: u32) -> bool {
+    // the windows are checked when set, the broken ones are skipped
+    let windows: Vec<Window> = value
+        .split(|c| c == ';' || c == '\n')
+        .filter(|w| !w.trim().is_empty())
+        .filter_map(|w| match Window::from_str(w) {
+            Ok(w) => Some(w),
+            Err(err) => {
+                log::error!("{}", err);
+                None
+            }
+        })
+        .collect();
+    let of_peer = windows.iter().any(|w| w.peer.as_deref() == Some(peer));
+    let mut windows = windows
+        .iter()
+        .filter(|w| {
+            if of_peer {
+                w.peer.as_deref() == Some(peer)
+            } else {
+                w.peer.is_none()
+            }
+        })
+        .peekable();
+    if windows.peek().is_none() {
+        return true;
+    }
+    windows.any(|w| in_window(w.days, w.time, weekday, minute))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert!(parse("mon-fri 08:00-18:00; 123456789: sat,sun\n22:00-06:00\n").is_ok());
+        assert!(parse("123456789:").is_err());
+        assert!(parse("weekdays").is_err());
+        assert!(parse("mon tue").is_err());
+        assert!(parse("mon 08:00-25:00").is_err());
+    }
+
+    #[test]
+    fn test_allows() {
+        let schedule = "mon-fri 08:00-18:00\n123456789: sat 10:00-12:00";
+        // tuesday 10:00
+        assert!(allows_at(schedule, "987654321", 1, 600));
+        assert!(!allows_at(schedule, "123456789", 1, 600));
+        // saturday 11:00, and tuesday 18:00
+        assert!(!allows_at(schedule, "987654321", 5, 660));
+        assert!(allows_at(schedule, "123456789", 5, 660));
+        assert!(!allows_at(schedule, "987654321", 1, 1080));
+        // the windows of the peers only, none for the others
+        assert!(allows_at("123456789: sat", "987654321", 1, 600));
+        assert!(allows_at("", "987654321", 1, 600));
+    }
+}
@@ -129,6 +129,12 @@ impl VideoQoS {
     }
 
     pub fn refresh(&mut self, typ: Option<RefreshType>) {
+        // the viewers of the broadcast get what the others get
+        let users = || {
+            self.users
+                .iter()
+                .filter(|(id, _)| !super::broadcast::is_viewer(**id))
+        };
         // fps
         let user_fps = |u: &UserData| {
             // custom_fps
@@ -156,9 +162,7 @@ impl VideoQoS {
             }
             return fps;
         };
-        let mut fps = self
-            .users
-            .iter()
+        let mut fps = users()
             .map(|(_, u)| user_fps(u))
             .filter(|u| *u >= MIN_FPS)
             .min()
@@ -170,9 +174,7 @@ impl VideoQoS {
 
         // quality
         // latest image quality
-        let latest_quality = self
-            .users
-            .iter()
+        let latest_quality = users()
             .map(|(_, u)| u.quality)
             .filter(|q| *q != None)
             .max_by(|a, b| a.unwrap_or_default().0.cmp(&b.unwrap_or_default().0))
@@ -185,9 +187,7 @@ impl VideoQoS {
         let abr_enabled = self.in_vbr_state();
         if abr_enabled && typ != Some(RefreshType::SetImageQuality) {
             // max delay
-            let delay = self
-                .users
-                .iter()
+            let delay = users()
                 .map(|u| u.1.delay)
                 .filter(|d| d.is_some())
                 .max_by(|a, b| {
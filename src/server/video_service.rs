@@ -20,8 +20,10 @@
 
 use super::{
     display_service::{check_display_changed, get_display_info},
+    presenter::{self, Presenter},
     service::ServiceTmpl,
     video_qos::VideoQoS,
+    watermark::{self, Watermark},
     *,
 };
 #[cfg(target_os = "linux")]
@@ -37,7 +39,7 @@ use crate::{
 };
 use hbb_common::{
     anyhow::anyhow,
-    config,
+    config::{self, keys},
     tokio::sync::{
         mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
         Mutex as TokioMutex,
@@ -51,10 +53,12 @@ use scrap::vram::{VRamEncoder, VRamEncoderConfig};
 use scrap::Capturer;
 use scrap::{
     aom::AomEncoderConfig,
-    codec::{Encoder, EncoderCfg, Quality},
-    record::{Recorder, RecorderContext},
+    codec::{Encoder, EncoderCfg, EncodingUpdate, Quality},
+    fallback,
+    record::{RecordSplit, Recorder, RecorderContext},
+    text_patch::TextPatchDetector,
     vpxcodec::{VpxEncoderConfig, VpxVideoCodecId},
-    CodecFormat, Display, EncodeInput, TraitCapturer,
+    CodecFormat, Display, EncodeInput, TraitCapturer, TraitPixelBuffer,
 };
 #[cfg(windows)]
 use std::sync::Once;
@@ -62,11 +66,14 @@ use std::{
     collections::HashSet,
     io::ErrorKind::WouldBlock,
     ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
     time::{self, Duration, Instant},
 };
 
 pub const NAME: &'static str = "video";
 pub const OPTION_REFRESH: &'static str = "refresh";
+// Record incoming sessions even if "allow-auto-record-incoming" is off, set through ipc.
+static RECORD_INCOMING: AtomicBool = AtomicBool::new(false);
 
 lazy_static::lazy_static! {
     static ref FRAME_FETCHED_NOTIFIER: (UnboundedSender<(i32, Option<Instant>)>, Arc<TokioMutex<UnboundedReceiver<(i32, Option<Instant>)>>>) = {
@@ -76,6 +83,17 @@ lazy_static::lazy_static! {
     pub static ref VIDEO_QOS: Arc<Mutex<VideoQoS>> = Default::default();
     pub static ref IS_UAC_RUNNING: Arc<Mutex<bool>> = Default::default();
     pub static ref IS_FOREGROUND_WINDOW_ELEVATED: Arc<Mutex<bool>> = Default::default();
+    static ref RECORDERS: Mutex<HashMap<usize, Weak<Mutex<Option<Recorder>>>>> = Default::default();
+    static ref RECORD_AUDIO_FORMAT: Mutex<Option<(u32, u16)>> = Default::default();
+    // display -> (failed encoder, reason), reported to the clients once the next encoder runs
+    static ref ENCODER_FALLBACKS: Mutex<HashMap<usize, (String, String)>> = Default::default();
+    static ref MEDIA_CLOCK: Instant = Instant::now();
+}
+
+/// The milliseconds of the clock the video and audio frames are stamped with, never 0.
+#[inline]
+pub fn media_timestamp() -> i64 {
+    MEDIA_CLOCK.elapsed().as_millis() as i64 + 1
 }
 
 #[inline]
@@ -415,19 +433,23 @@ fn run(vs: VideoService) -> ResultType<()> {
     let mut video_qos = VIDEO_QOS.lock().unwrap();
     video_qos.refresh(None);
     let mut spf;
-    let mut quality = video_qos.quality();
-    let record_incoming = config::option2bool(
-        "allow-auto-record-incoming",
-        &Config::get_option("allow-auto-record-incoming"),
-    );
+    let mut quality = bandwidth::limit_quality(video_qos.quality(), c.width as _, c.height as _);
+    let record_incoming = is_record_incoming();
     let client_record = video_qos.record();
     drop(video_qos);
+    let mut watermark = if watermark::enabled() {
+        Some(Watermark::default())
+    } else {
+        None
+    };
+    let mut presenter = presenter::enabled().then(Presenter::default);
     let (mut encoder, encoder_cfg, codec_format, use_i444, recorder) = match setup_encoder(
         &c,
         display_idx,
         quality,
         client_record,
         record_incoming,
+        watermark.is_some() || presenter.is_some(),
         last_portable_service_running,
     ) {
         Ok(result) => result,
@@ -446,6 +468,7 @@ fn run(vs: VideoService) -> ResultType<()> {
                 quality,
                 client_record,
                 record_incoming,
+                watermark.is_some() || presenter.is_some(),
                 last_portable_service_running,
             )?
         }
@@ -463,6 +486,9 @@ fn run(vs: VideoService) -> ResultType<()> {
         .unwrap()
         .set_support_abr(display_idx, encoder.support_abr());
     log::info!("initial quality: {quality:?}");
+    send_encoder_fallback(&sp, display_idx, &encoder);
+    let fallback_generation = fallback::generation();
+    let mut fallback_success_reported = false;
 
     if sp.is_option_true(OPTION_REFRESH) {
         sp.set_option_bool(OPTION_REFRESH, false);
@@ -489,6 +515,20 @@ fn run(vs: VideoService) -> ResultType<()> {
     let mut first_frame = true;
     let capture_width = c.width;
     let capture_height = c.height;
+    let roi_size = Config::get_option(keys::OPTION_ROI_SIZE)
+        .parse::<usize>()
+        .unwrap_or(0);
+    // the patches would be sent without the marks
+    let mut text_patch = if watermark.is_none()
+        && presenter.is_none()
+        && config::option2bool(
+            keys::OPTION_ALLOW_LOSSLESS_TEXT_PATCHES,
+            &Config::get_option(keys::OPTION_ALLOW_LOSSLESS_TEXT_PATCHES),
+        ) {
+        Some(TextPatchDetector::new(capture_width, capture_height))
+    } else {
+        None
+    };
 
     while sp.ok() {
         #[cfg(windows)]
@@ -496,9 +536,11 @@ fn run(vs: VideoService) -> ResultType<()> {
 
         let mut video_qos = VIDEO_QOS.lock().unwrap();
         spf = video_qos.spf();
-        if quality != video_qos.quality() {
-            log::debug!("quality: {:?} -> {:?}", quality, video_qos.quality());
-            quality = video_qos.quality();
+        let target_quality =
+            bandwidth::limit_quality(video_qos.quality(), c.width as _, c.height as _);
+        if quality != target_quality {
+            log::debug!("quality: {:?} -> {:?}", quality, target_quality);
+            quality = target_quality;
             if encoder.support_changing_quality() {
                 allow_err!(encoder.set_quality(quality));
                 video_qos.store_bitrate(encoder.bitrate());
@@ -514,6 +556,18 @@ fn run(vs: VideoService) -> ResultType<()> {
             bail!("SWITCH");
         }
         drop(video_qos);
+        if record_incoming != is_record_incoming() {
+            log::info!("switch due to record incoming changed");
+            bail!("SWITCH");
+        }
+        if watermark.is_some() != watermark::enabled() {
+            log::info!("switch due to watermark changed");
+            bail!("SWITCH");
+        }
+        if presenter.is_some() != presenter::enabled() {
+            log::info!("switch due to presenter mode changed");
+            bail!("SWITCH");
+        }
 
         if sp.is_option_true(OPTION_REFRESH) {
             let _ = try_broadcast_display_changed(&sp, display_idx, &c, true);
@@ -528,6 +582,16 @@ fn run(vs: VideoService) -> ResultType<()> {
             );
             bail!("SWITCH");
         }
+        if fallback_generation != fallback::generation() {
+            // a failed encoder may be usable again, or the others fell back
+            Encoder::update(EncodingUpdate::Check);
+            log::info!("switch due to encoder health changed");
+            bail!("SWITCH");
+        }
+        if !fallback_success_reported && start.elapsed() > fallback::MAX_COOLDOWN {
+            fallback_success_reported = true;
+            fallback::report_success(encoder.name());
+        }
         #[cfg(windows)]
         if last_portable_service_running != crate::portable_service::client::running() {
             log::info!("switch due to portable service running changed");
@@ -568,7 +632,31 @@ fn run(vs: VideoService) -> ResultType<()> {
             Ok(frame) => {
                 repeat_encode_counter = 0;
                 if frame.valid() {
-                    let frame = frame.to(encoder.yuvfmt(), &mut yuv, &mut mid_data)?;
+                    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+                    if roi_size > 0 {
+                        allow_err!(encoder.set_roi(cursor_roi(&c, roi_size)));
+                    }
+                    if let (Some(d), scrap::Frame::PixelBuffer(pb)) = (text_patch.as_mut(), &frame)
+                    {
+                        let stride = pb.stride().first().cloned().unwrap_or_default();
+                        if let Some(patches) = d.update(pb.data(), stride, pb.pixfmt()) {
+                            send_lossless_patches(&sp, display_idx, patches);
+                        }
+                    }
+                    let marked = watermark.is_some() || presenter.is_some();
+                    let frame = match frame {
+                        scrap::Frame::PixelBuffer(_) if marked => {
+                            frame.to(encoder.yuvfmt(), &mut yuv, &mut mid_data)?;
+                            if let Some(w) = watermark.as_mut() {
+                                w.draw(&mut yuv, &encoder.yuvfmt());
+                            }
+                            if let Some(p) = presenter.as_mut() {
+                                p.draw(&mut yuv, &encoder.yuvfmt(), c.origin);
+                            }
+                            EncodeInput::YUV(&yuv)
+                        }
+                        _ => frame.to(encoder.yuvfmt(), &mut yuv, &mut mid_data)?,
+                    };
                     let send_conn_ids = handle_one_frame(
                         display_idx,
                         &sp,
@@ -623,7 +711,29 @@ fn run(vs: VideoService) -> ResultType<()> {
                         }
                     }
                 }
-                if !encoder.latency_free() && yuv.len() > 0 {
+                if let Some(patches) = text_patch.as_mut().and_then(|d| d.idle()) {
+                    send_lossless_patches(&sp, display_idx, patches);
+                }
+                // the cursor moved or a ripple spreads on a still screen
+                let redrawn = presenter
+                    .as_mut()
+                    .map(|p| p.redraw(&mut yuv, &encoder.yuvfmt(), c.origin))
+                    .unwrap_or_default();
+                if redrawn {
+                    let send_conn_ids = handle_one_frame(
+                        display_idx,
+                        &sp,
+                        EncodeInput::YUV(&yuv),
+                        ms,
+                        &mut encoder,
+                        recorder.clone(),
+                        &mut encode_fail_counter,
+                        &mut first_frame,
+                        capture_width,
+                        capture_height,
+                    )?;
+                    frame_controller.set_send(now, send_conn_ids);
+                } else if !encoder.latency_free() && yuv.len() > 0 {
                     // yun.len() > 0 means the frame is not texture.
                     if repeat_encode_counter < repeat_encode_max {
                         repeat_encode_counter += 1;
@@ -702,6 +812,7 @@ impl Drop for Raii {
         #[cfg(feature = "vram")]
         Encoder::update(scrap::codec::EncodingUpdate::Check);
         VIDEO_QOS.lock().unwrap().set_support_abr(self.0, true);
+        RECORDERS.lock().unwrap().remove(&self.0);
     }
 }
 
@@ -711,6 +822,7 @@ fn setup_encoder(
     quality: Quality,
     client_record: bool,
     record_incoming: bool,
+    watermark: bool,
     last_portable_service_running: bool,
 ) -> ResultType<(
     Encoder,
@@ -724,6 +836,7 @@ fn setup_encoder(
         display_idx,
         quality,
         client_record || record_incoming,
+        watermark,
         last_portable_service_running,
     );
     Encoder::set_fallback(&encoder_cfg);
@@ -736,7 +849,13 @@ fn setup_encoder(
         display_idx,
     );
     let use_i444 = Encoder::use_i444(&encoder_cfg);
-    let encoder = Encoder::new(encoder_cfg.clone(), use_i444)?;
+    let encoder = Encoder::new(encoder_cfg.clone(), use_i444).map_err(|e| {
+        ENCODER_FALLBACKS
+            .lock()
+            .unwrap()
+            .insert(display_idx, (fallback::name(&encoder_cfg), e.to_string()));
+        e
+    })?;
     Ok((encoder, encoder_cfg, codec_format, use_i444, recorder))
 }
 
@@ -745,11 +864,22 @@ fn get_encoder_config(
     _display_idx: usize,
     quality: Quality,
     record: bool,
+    _marked: bool,
     _portable_service: bool,
 ) -> EncoderCfg {
     #[cfg(all(windows, feature = "vram"))]
-    if _portable_service || c.is_gdi() {
-        log::info!("gdi:{}, portable:{}", c.is_gdi(), _portable_service);
+    if _portable_service || c.is_gdi() || !c.texture_supported() {
+        log::info!(
+            "gdi:{}, portable:{}, texture supported:{}",
+            c.is_gdi(),
+            _portable_service,
+            c.texture_supported()
+        );
+        VRamEncoder::set_not_use(_display_idx, true);
+    }
+    // the watermark and the presenter marks are drawn on the frames in the memory only
+    #[cfg(feature = "vram")]
+    if _marked {
         VRamEncoder::set_not_use(_display_idx, true);
     }
     #[cfg(feature = "vram")]
@@ -759,35 +889,40 @@ fn get_encoder_config(
     let negotiated_codec = Encoder::negotiated_codec();
     match negotiated_codec {
         CodecFormat::H264 | CodecFormat::H265 => {
+            // in the default order: vram > hwram > vp9, reordered by the fallback chain
+            #[allow(unused_mut)]
+            let mut candidates = vec![];
             #[cfg(feature = "vram")]
-            if let Some(feature) = VRamEncoder::try_get(&c.device(), negotiated_codec) {
-                return EncoderCfg::VRAM(VRamEncoderConfig {
+            for feature in VRamEncoder::all(&c.device(), negotiated_codec) {
+                candidates.push(EncoderCfg::VRAM(VRamEncoderConfig {
                     device: c.device(),
                     width: c.width,
                     height: c.height,
                     quality,
                     feature,
                     keyframe_interval,
-                });
+                }));
             }
             #[cfg(feature = "hwcodec")]
-            if let Some(hw) = HwRamEncoder::try_get(negotiated_codec) {
-                return EncoderCfg::HWRAM(HwRamEncoderConfig {
+            for hw in HwRamEncoder::all(negotiated_codec) {
+                candidates.push(EncoderCfg::HWRAM(HwRamEncoderConfig {
                     name: hw.name,
                     mc_name: hw.mc_name,
                     width: c.width,
                     height: c.height,
                     quality,
                     keyframe_interval,
-                });
+                }));
             }
-            EncoderCfg::VPX(VpxEncoderConfig {
+            let vp9 = EncoderCfg::VPX(VpxEncoderConfig {
                 width: c.width as _,
                 height: c.height as _,
                 quality,
                 codec: VpxVideoCodecId::VP9,
                 keyframe_interval,
-            })
+            });
+            candidates.push(vp9.clone());
+            fallback::select(candidates).unwrap_or(vp9)
         }
         format @ (CodecFormat::VP8 | CodecFormat::VP9) => EncoderCfg::VPX(VpxEncoderConfig {
             width: c.width as _,
@@ -823,12 +958,8 @@ fn get_recorder(
     record_incoming: bool,
     display: usize,
 ) -> Arc<Mutex<Option<Recorder>>> {
-    #[cfg(windows)]
-    let root = crate::platform::is_root();
-    #[cfg(not(windows))]
-    let root = false;
     let recorder = if record_incoming {
-        use crate::hbbs_http::record_upload;
+        use crate::hbbs_http::{policy, record_upload};
 
         let tx = if record_upload::is_enable() {
             let (tx, rx) = std::sync::mpsc::channel();
@@ -837,14 +968,37 @@ fn get_recorder(
         } else {
             None
         };
+        let policy = policy::recording();
+        let dir = if policy.enforced && !policy.dir.is_empty() {
+            policy.dir
+        } else {
+            record_save_directory()
+        };
+        if policy.enforced && policy.retention_days > 0 {
+            prune_recordings(&dir, policy.retention_days);
+        }
         Recorder::new(RecorderContext {
             server: true,
             id: Config::get_id(),
-            dir: crate::ui_interface::video_save_directory(root),
+            dir,
             display,
             tx,
+            split: RecordSplit::new(
+                &Config::get_option(keys::OPTION_RECORD_SPLIT_SIZE),
+                &Config::get_option(keys::OPTION_RECORD_SPLIT_DURATION),
+            ),
+        })
+        .map_or(Default::default(), |mut r| {
+            if let Some((sample_rate, channels)) = *RECORD_AUDIO_FORMAT.lock().unwrap() {
+                r.set_audio_format(sample_rate, channels);
+            }
+            let r = Arc::new(Mutex::new(Some(r)));
+            RECORDERS
+                .lock()
+                .unwrap()
+                .insert(display, Arc::downgrade(&r));
+            r
         })
-        .map_or(Default::default(), |r| Arc::new(Mutex::new(Some(r))))
     } else {
         Default::default()
     };
@@ -852,6 +1006,169 @@ fn get_recorder(
     recorder
 }
 
+// Removes the recordings of the incoming sessions older than the days of the retention.
+fn prune_recordings(dir: &str, retention_days: u64) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let retention = Duration::from_secs(retention_days * 24 * 3600);
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        let incoming = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| n.starts_with("incoming_"))
+            .unwrap_or(false);
+        let old = path
+            .metadata()
+            .and_then(|m| m.modified())
+            .map(|t| t.elapsed().unwrap_or_default() > retention)
+            .unwrap_or(false);
+        if incoming && old {
+            log::info!("Remove the recording {:?}", path);
+            if let Err(err) = std::fs::remove_file(&path) {
+                log::error!("Failed to remove the recording {:?}: {}", path, err);
+            }
+        }
+    }
+}
+
+/// Whether the incoming sessions are recorded, as asked, by the option or by the policy.
+#[inline]
+pub fn is_record_incoming() -> bool {
+    RECORD_INCOMING.load(Ordering::SeqCst)
+        || crate::hbbs_http::policy::recording_enforced()
+        || config::option2bool(
+            keys::OPTION_ALLOW_AUTO_RECORD_INCOMING,
+            &Config::get_option(keys::OPTION_ALLOW_AUTO_RECORD_INCOMING),
+        )
+}
+
+pub fn set_record_incoming(v: bool) {
+    log::info!("set record incoming: {v}");
+    RECORD_INCOMING.store(v, Ordering::SeqCst);
+}
+
+pub fn record_save_directory() -> String {
+    #[cfg(windows)]
+    let root = crate::platform::is_root();
+    #[cfg(not(windows))]
+    let root = false;
+    crate::ui_interface::video_save_directory(root)
+}
+
+/// The files being written by the incoming session recorders.
+pub fn recording_files() -> Vec<String> {
+    RECORDERS
+        .lock()
+        .unwrap()
+        .values()
+        .filter_map(|r| r.upgrade())
+        .filter_map(|r| r.lock().unwrap().as_ref().and_then(|r| r.filename()))
+        .collect()
+}
+
+pub fn set_record_audio_format(sample_rate: u32, channels: u16) {
+    *RECORD_AUDIO_FORMAT.lock().unwrap() = Some((sample_rate, channels));
+    for r in RECORDERS
+        .lock()
+        .unwrap()
+        .values()
+        .filter_map(|r| r.upgrade())
+    {
+        r.lock()
+            .unwrap()
+            .as_mut()
+            .map(|r| r.set_audio_format(sample_rate, channels));
+    }
+}
+
+pub fn record_audio(data: &[u8]) {
+    // Audio is muxed into the recording of the first display only.
+    let r = RECORDERS
+        .lock()
+        .unwrap()
+        .iter()
+        .min_by_key(|(display, _)| **display)
+        .and_then(|(_, r)| r.upgrade());
+    if let Some(r) = r {
+        r.lock().unwrap().as_mut().map(|r| r.write_audio(data));
+    }
+}
+
+/// Capture a single frame of the display and encode it as PNG, without starting the video service.
+pub fn take_screenshot(display: usize) -> ResultType<Vec<u8>> {
+    #[cfg(target_os = "linux")]
+    if !is_x11() {
+        bail!("Screenshot is not supported on Wayland");
+    }
+    #[cfg(windows)]
+    let portable_service_running = crate::portable_service::client::running();
+    #[cfg(not(windows))]
+    let portable_service_running = false;
+    let mut c = get_capturer(display, portable_service_running)?;
+    if c.privacy_mode_id != INVALID_PRIVACY_MODE_CONN_ID {
+        bail!("Screenshot is not allowed in privacy mode");
+    }
+    #[cfg(feature = "vram")]
+    c.set_output_texture(false);
+    let start = Instant::now();
+    loop {
+        match c.frame(Duration::from_millis(100)) {
+            Ok(scrap::Frame::PixelBuffer(pb)) if !pb.data().is_empty() => {
+                let (w, h) = (pb.width(), pb.height());
+                let stride = pb.stride().first().cloned().unwrap_or(w * 4);
+                let data = pb.data();
+                let mut rgba = Vec::new();
+                match pb.pixfmt() {
+                    scrap::Pixfmt::BGRA => {
+                        scrap::swap_red_blue(data, stride, w, h, &mut rgba)?;
+                        // the alpha channel of the captured frame is not always set
+                        rgba.iter_mut().skip(3).step_by(4).for_each(|a| *a = 255);
+                    }
+                    scrap::Pixfmt::RGBA => {
+                        for y in 0..h {
+                            rgba.extend_from_slice(&data[y * stride..y * stride + w * 4]);
+                        }
+                    }
+                    fmt => bail!("Unsupported pixel format {:?}", fmt),
+                }
+                let mut png = Vec::new();
+                repng::encode(&mut png, w as _, h as _, &rgba)?;
+                return Ok(png);
+            }
+            Ok(_) => {}
+            Err(ref e) if e.kind() == WouldBlock => {}
+            Err(e) => bail!("Failed to capture display {}: {}", display, e),
+        }
+        if start.elapsed() > Duration::from_secs(3) {
+            bail!("Timeout to capture display {}", display);
+        }
+    }
+}
+
+fn send_lossless_patches(sp: &GenericService, display: usize, mut patches: LosslessPatches) {
+    patches.display = display as _;
+    let mut msg = Message::new();
+    msg.set_lossless_patches(patches);
+    sp.send(msg);
+}
+
+// The square of `size` pixels centered on the cursor, none if the cursor is not on this display.
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn cursor_roi(c: &CapturerInfo, size: usize) -> Option<scrap::codec::Roi> {
+    let (x, y) = super::input_service::latest_cursor_pos()?;
+    let (x, y) = (x - c.origin.0, y - c.origin.1);
+    if x < 0 || y < 0 || x as usize >= c.width || y as usize >= c.height {
+        return None;
+    }
+    Some(scrap::codec::Roi {
+        x: (x as usize).saturating_sub(size / 2),
+        y: (y as usize).saturating_sub(size / 2),
+        width: size,
+        height: size,
+    })
+}
+
 #[cfg(target_os = "android")]
 fn check_change_scale(hardware: bool) -> ResultType<()> {
     use hbb_common::config::keys::OPTION_ENABLE_ANDROID_SOFTWARE_ENCODING_HALF_SCALE as SCALE_SOFT;
@@ -939,6 +1256,7 @@ fn handle_one_frame(
         Ok(mut vf) => {
             *encode_fail_counter = 0;
             vf.display = display as _;
+            vf.timestamp = media_timestamp();
             let mut msg = Message::new();
             msg.set_video_frame(vf);
             recorder
@@ -947,6 +1265,8 @@ fn handle_one_frame(
                 .as_mut()
                 .map(|r| r.write_message(&msg, width, height));
             send_conn_ids = sp.send_video_frame(msg);
+            // the viewers of the broadcast do not hold the others up
+            send_conn_ids.retain(|id| !super::broadcast::is_viewer(*id));
         }
         Err(e) => {
             *encode_fail_counter += 1;
@@ -964,14 +1284,14 @@ fn handle_one_frame(
             if (first && !repeat) || *encode_fail_counter >= max_fail_times {
                 *encode_fail_counter = 0;
                 if encoder.is_hardware() {
-                    encoder.disable();
+                    fall_back(display, encoder, &e.to_string());
                     log::error!("switch due to encoding fails, first frame: {first}, error: {e:?}");
                     bail!("SWITCH");
                 }
             }
             match e.to_string().as_str() {
                 scrap::codec::ENCODE_NEED_SWITCH => {
-                    encoder.disable();
+                    fall_back(display, encoder, &e.to_string());
                     log::error!("switch due to encoder need switch");
                     bail!("SWITCH");
                 }
@@ -982,6 +1302,36 @@ fn handle_one_frame(
     Ok(send_conn_ids)
 }
 
+fn fall_back(display: usize, encoder: &Encoder, reason: &str) {
+    encoder.report_failure(reason);
+    ENCODER_FALLBACKS
+        .lock()
+        .unwrap()
+        .insert(display, (encoder.name().to_owned(), reason.to_owned()));
+}
+
+// Tell the clients which encoder replaced the failed one.
+fn send_encoder_fallback(sp: &GenericService, display: usize, encoder: &Encoder) {
+    let Some((from, reason)) = ENCODER_FALLBACKS.lock().unwrap().remove(&display) else {
+        return;
+    };
+    if from == encoder.name() {
+        return;
+    }
+    log::info!("encoder fallback: {from} -> {}", encoder.name());
+    let mut misc = Misc::new();
+    misc.set_encoder_fallback(EncoderFallback {
+        display: display as _,
+        from,
+        to: encoder.name().to_owned(),
+        reason,
+        ..Default::default()
+    });
+    let mut msg = Message::new();
+    msg.set_misc(misc);
+    sp.send(msg);
+}
+
 #[inline]
 pub fn refresh() {
     #[cfg(target_os = "android")]
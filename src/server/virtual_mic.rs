@@ -0,0 +1,133 @@
+// Play the voice of the peer in a voice call to a virtual microphone too, so the apps on this side,
+// e.g. a meeting, can take it as their input.
+//
+// Linux creates a null sink in PulseAudio and remaps its monitor to a source, which is the
+// microphone. Windows and macOS have no such device by themselves, the output of a virtual cable
+// installed, e.g. VB-CABLE or BlackHole, is played to.
+
+use super::*;
+use hbb_common::config::keys;
+
+#[cfg(target_os = "linux")]
+const SINK: &str = "rustdesk_virtual_mic_sink";
+#[cfg(target_os = "linux")]
+const SOURCE: &str = "rustdesk_virtual_mic";
+// the beginning of the names of the outputs
+#[cfg(not(target_os = "linux"))]
+const CABLES: &[&str] = &["CABLE Input", "BlackHole"];
+
+#[cfg(target_os = "linux")]
+lazy_static::lazy_static! {
+    // the number of the voice calls, and the modules loaded for them
+    static ref MODULES: Mutex<(usize, Vec<String>)> = Default::default();
+}
+
+/// Kept while the voice of the peer is played to the microphone.
+pub struct VirtualMic {
+    output: String,
+}
+
+impl VirtualMic {
+    /// The name of the output device to play to.
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+}
+
+/// None if it is not allowed or the device is not available.
+pub fn open() -> Option<VirtualMic> {
+    if !Config::get_bool_option(keys::OPTION_ALLOW_VOICE_CALL_VIRTUAL_MIC) {
+        return None;
+    }
+    match platform_open() {
+        Ok(output) => {
+            log::info!("voice call is played to the virtual microphone: {output}");
+            Some(VirtualMic { output })
+        }
+        Err(e) => {
+            log::error!("failed to open the virtual microphone: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn platform_open() -> ResultType<String> {
+    let mut modules = MODULES.lock().unwrap();
+    if modules.0 == 0 {
+        let sink = load_module(&[
+            "module-null-sink",
+            &format!("sink_name={SINK}"),
+            "sink_properties=device.description=RustDesk-Voice-Call",
+        ])?;
+        let source = load_module(&[
+            "module-remap-source",
+            &format!("master={SINK}.monitor"),
+            &format!("source_name={SOURCE}"),
+            "source_properties=device.description=RustDesk-Microphone",
+        ]);
+        match source {
+            Ok(source) => modules.1 = vec![source, sink],
+            Err(e) => {
+                unload_module(&sink);
+                return Err(e);
+            }
+        }
+    }
+    modules.0 += 1;
+    Ok(SINK.to_owned())
+}
+
+// returns the index of the module
+#[cfg(target_os = "linux")]
+fn load_module(args: &[&str]) -> ResultType<String> {
+    let output = std::process::Command::new("pactl")
+        .arg("load-module")
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "pactl load-module {}: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+#[cfg(target_os = "linux")]
+fn unload_module(index: &str) {
+    let res = std::process::Command::new("pactl")
+        .args(["unload-module", index])
+        .status();
+    if !matches!(res, Ok(status) if status.success()) {
+        log::error!("failed to unload the module {index}: {res:?}");
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn platform_open() -> ResultType<String> {
+    use cpal::traits::{DeviceTrait, HostTrait};
+    let devices = cpal::default_host().output_devices()?;
+    for device in devices {
+        if let Ok(name) = device.name() {
+            if CABLES.iter().any(|c| name.starts_with(c)) {
+                return Ok(name);
+            }
+        }
+    }
+    bail!("no virtual cable is installed");
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for VirtualMic {
+    fn drop(&mut self) {
+        let mut modules = MODULES.lock().unwrap();
+        modules.0 = modules.0.saturating_sub(1);
+        if modules.0 == 0 {
+            for index in modules.1.drain(..) {
+                unload_module(&index);
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+// The watermark drawn on the screen shared: the ids of the peers controlling it and the time,
+// repeated over the whole frame, so that a screenshot or a recording of a session on the peer side
+// tells who took it and when.
+//
+// It is drawn on the frames before they are encoded, so no peer can hide it, and only on the luma,
+// lightening the dark pixels and darkening the light ones so that it shows on any background
+// whatever the yuv format. The frames on the gpu can not be drawn on, so the vram encoders are not
+// used while it is enabled.
+
+use std::time::{Duration, Instant};
+
+use hbb_common::config::{keys, Config};
+use scrap::{EncodeYuvFormat, Pixfmt};
+
+// of 256
+const ALPHA: u32 = 72;
+// the lines of the frame a pixel of the font spans one more for
+const LINES_PER_SCALE: usize = 360;
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+#[inline]
+pub fn enabled() -> bool {
+    Config::get_bool_option(keys::OPTION_ALLOW_WATERMARK)
+}
+
+// the rows of the 5x7 glyph, the most significant of the 5 bits on the left
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        ' ' => [0; GLYPH_HEIGHT],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '@' => [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
+
+// The pixels of `text` at `scale` and the size of the text.
+fn render(text: &str, scale: usize) -> (Vec<(usize, usize)>, usize, usize) {
+    let mut pixels = Vec::new();
+    let advance = (GLYPH_WIDTH + 1) * scale;
+    for (i, c) in text.chars().enumerate() {
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (0x10 >> col) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        pixels.push((i * advance + col * scale + dx, row * scale + dy));
+                    }
+                }
+            }
+        }
+    }
+    let width = (text.chars().count() * advance).saturating_sub(scale);
+    (pixels, width, GLYPH_HEIGHT * scale)
+}
+
+/// The watermark of a display, laid out again whenever its text changes.
+#[derive(Default)]
+pub struct Watermark {
+    text: String,
+    size: (usize, usize),
+    pixels: Vec<(usize, usize)>,
+    // the distance between the copies of the text
+    step: (usize, usize),
+    updated: Option<Instant>,
+}
+
+impl Watermark {
+    fn text() -> String {
+        let mut ids = super::authed_remote_peer_ids();
+        if ids.is_empty() {
+            ids.push(Config::get_id());
+        }
+        format!(
+            "{}  {}",
+            ids.join(", "),
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+        )
+    }
+
+    fn layout(&mut self, text: String, w: usize, h: usize) {
+        let scale = (h / LINES_PER_SCALE).max(1);
+        let (pixels, width, height) = render(&text, scale);
+        self.step = (width + 12 * GLYPH_WIDTH * scale, height * 6);
+        self.pixels = pixels;
+        self.text = text;
+        self.size = (w, h);
+    }
+
+    /// Draws on the frame converted for the encoder.
+    pub fn draw(&mut self, yuv: &mut [u8], fmt: &EncodeYuvFormat) {
+        match fmt.pixfmt {
+            Pixfmt::I420 | Pixfmt::NV12 | Pixfmt::I444 => {}
+            _ => return,
+        }
+        let stride = fmt.stride.first().cloned().unwrap_or(fmt.w);
+        let now = Instant::now();
+        if self
+            .updated
+            .map(|t| now.duration_since(t) >= UPDATE_INTERVAL)
+            .unwrap_or(true)
+        {
+            self.updated = Some(now);
+            let text = Self::text();
+            if text != self.text || self.size != (fmt.w, fmt.h) {
+                self.layout(text, fmt.w, fmt.h);
+            }
+        }
+        self.draw_luma(yuv, fmt.w, fmt.h, stride);
+    }
+
+    fn draw_luma(&self, luma: &mut [u8], w: usize, h: usize, stride: usize) {
+        let (step_x, step_y) = self.step;
+        if step_x == 0 || step_y == 0 || luma.len() < stride * h {
+            return;
+        }
+        let mut row = 0;
+        let mut oy = step_y / 4;
+        while oy < h {
+            // every other row shifted by half a copy
+            let mut ox = if row % 2 == 0 {
+                0
+            } else {
+                -(step_x as isize / 2)
+            };
+            while ox < w as isize {
+                for (x, y) in self.pixels.iter() {
+                    let (x, y) = (ox + *x as isize, oy + y);
+                    if x < 0 || x as usize >= w || y >= h {
+                        continue;
+                    }
+                    let x = x as usize;
+                    let p = &mut luma[y * stride + x];
+                    let v = *p as u32;
+                    *p = if v < 128 {
+                        v + (255 - v) * ALPHA / 256
+                    } else {
+                        v - v * ALPHA / 256
+                    } as u8;
+                }
+                ox += step_x as isize;
+            }
+            oy += step_y;
+            row += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw() {
+        let (pixels, width, height) = render("1-", 2);
+        assert_eq!((width, height), (22, 14));
+        // the 10 pixels of the glyph of 1 and the 5 of the dash, each 2x2
+        assert_eq!(pixels.len(), (10 + 5) * 4);
+        assert!(pixels.contains(&(12, 6)) && !pixels.contains(&(12, 4)));
+        assert_eq!(glyph('a'), glyph('A'));
+
+        let (w, h, stride) = (640, 360, 648);
+        let mut watermark = Watermark::default();
+        watermark.layout("123456789  2026-10-16 10:20:30".to_owned(), w, h);
+        let mut dark = vec![16u8; stride * h];
+        watermark.draw_luma(&mut dark, w, h, stride);
+        let lit = dark.iter().filter(|v| **v != 16).count();
+        assert!(lit > 0);
+        let drawn = 16 + (239 * ALPHA / 256) as u8;
+        assert!(dark.iter().all(|v| *v == 16 || *v == drawn));
+        // not on the padding of the rows
+        assert!((0..h).all(|y| dark[y * stride + w..(y + 1) * stride]
+            .iter()
+            .all(|v| *v == 16)));
+        let mut light = vec![235u8; stride * h];
+        watermark.draw_luma(&mut light, w, h, stride);
+        assert_eq!(light.iter().filter(|v| **v < 235).count(), lit);
+    }
+}
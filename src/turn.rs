@@ -0,0 +1,448 @@
+// TURN relay of the sessions, for the organizations with a TURN server instead of the relay
+// server.
+//
+// The controlled side allocates a relayed tcp address on the TURN server (RFC 6062) with the
+// permission for the address the controlling side is seen from by the rendezvous server, and
+// answers the relay request with it. The controlling side connects to it with plain tcp, and the
+// controlled side binds the connection offered by the TURN server as the session stream. The
+// allocation lives on the control connection, which is refreshed as long as the session lasts.
+// Either side falls back to the relay server if it fails.
+
+use std::net::{IpAddr, SocketAddr};
+
+use hbb_common::{
+    anyhow::anyhow,
+    bail,
+    config::{keys::*, Config, CONNECT_TIMEOUT},
+    log,
+    rand::random,
+    timeout,
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{lookup_host, TcpStream},
+        time::sleep,
+    },
+    ResultType, Stream,
+};
+use hmac::{Hmac, Mac};
+
+const DEFAULT_PORT: u16 = 3478;
+const MAGIC_COOKIE: u32 = 0x2112_A442;
+const HEADER_LEN: usize = 20;
+const MAX_MSG_LEN: usize = 4096;
+
+// methods
+const ALLOCATE: u16 = 0x003;
+const REFRESH: u16 = 0x004;
+const CREATE_PERMISSION: u16 = 0x008;
+const CONNECTION_BIND: u16 = 0x00B;
+const CONNECTION_ATTEMPT: u16 = 0x00C;
+
+// classes
+const REQUEST: u16 = 0x000;
+const INDICATION: u16 = 0x010;
+const SUCCESS: u16 = 0x100;
+const ERROR: u16 = 0x110;
+
+// attributes
+const USERNAME: u16 = 0x0006;
+const MESSAGE_INTEGRITY: u16 = 0x0008;
+const ERROR_CODE: u16 = 0x0009;
+const LIFETIME: u16 = 0x000D;
+const XOR_PEER_ADDRESS: u16 = 0x0012;
+const REALM: u16 = 0x0014;
+const NONCE: u16 = 0x0015;
+const XOR_RELAYED_ADDRESS: u16 = 0x0016;
+const REQUESTED_TRANSPORT: u16 = 0x0019;
+const CONNECTION_ID: u16 = 0x002A;
+
+const UNAUTHORIZED: u16 = 401;
+const STALE_NONCE: u16 = 438;
+const TRANSPORT_TCP: u8 = 6;
+// in seconds, refreshed at the half
+const ALLOCATION_LIFETIME: u32 = 600;
+
+/// The TURN server and its credentials, if set.
+pub fn get_server() -> Option<(String, String, String)> {
+    let server = Config::get_option(OPTION_TURN_SERVER);
+    if server.trim().is_empty() {
+        return None;
+    }
+    Some((
+        server.trim().to_owned(),
+        Config::get_option(OPTION_TURN_USERNAME),
+        Config::get_option(OPTION_TURN_PASSWORD),
+    ))
+}
+
+// "turn:host:port?transport=tcp" as in WebRTC, or "host:port"
+fn parse_server(server: &str) -> ResultType<String> {
+    if server.starts_with("turns:") {
+        bail!("TURN over TLS is not supported");
+    }
+    let server = server.trim_start_matches("turn:");
+    let server = server.split('?').next().unwrap_or_default();
+    if server.is_empty() {
+        bail!("invalid TURN server");
+    }
+    Ok(crate::check_port(server, DEFAULT_PORT as _))
+}
+
+#[derive(Debug, Default)]
+struct Msg {
+    typ: u16,
+    tid: [u8; 12],
+    attrs: Vec<(u16, Vec<u8>)>,
+}
+
+impl Msg {
+    fn new(typ: u16) -> Self {
+        Self {
+            typ,
+            tid: random(),
+            attrs: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, typ: u16, value: Vec<u8>) {
+        self.attrs.push((typ, value));
+    }
+
+    fn get(&self, typ: u16) -> Option<&[u8]> {
+        self.attrs
+            .iter()
+            .find(|(t, _)| *t == typ)
+            .map(|(_, v)| v.as_slice())
+    }
+
+    fn encode(&self, integrity_key: Option<&[u8]>) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(MAX_MSG_LEN);
+        buf.extend_from_slice(&self.typ.to_be_bytes());
+        buf.extend_from_slice(&[0, 0]);
+        buf.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        buf.extend_from_slice(&self.tid);
+        for (typ, value) in self.attrs.iter() {
+            buf.extend_from_slice(&typ.to_be_bytes());
+            buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            buf.extend_from_slice(value);
+            buf.resize((buf.len() + 3) & !3, 0);
+        }
+        if let Some(key) = integrity_key {
+            // the length counts the integrity attribute while it is computed
+            let len = (buf.len() - HEADER_LEN + 4 + 20) as u16;
+            buf[2..4].copy_from_slice(&len.to_be_bytes());
+            let mut mac = Hmac::<sha1::Sha1>::new_from_slice(key).expect("any key length");
+            mac.update(&buf);
+            buf.extend_from_slice(&MESSAGE_INTEGRITY.to_be_bytes());
+            buf.extend_from_slice(&20u16.to_be_bytes());
+            buf.extend_from_slice(&mac.finalize().into_bytes());
+        }
+        let len = (buf.len() - HEADER_LEN) as u16;
+        buf[2..4].copy_from_slice(&len.to_be_bytes());
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> ResultType<Self> {
+        if buf.len() < HEADER_LEN
+            || buf[4..8] != MAGIC_COOKIE.to_be_bytes()
+            || u16::from_be_bytes([buf[2], buf[3]]) as usize != buf.len() - HEADER_LEN
+        {
+            bail!("invalid STUN message");
+        }
+        let mut msg = Self {
+            typ: u16::from_be_bytes([buf[0], buf[1]]),
+            ..Default::default()
+        };
+        msg.tid.copy_from_slice(&buf[8..HEADER_LEN]);
+        let mut i = HEADER_LEN;
+        while i + 4 <= buf.len() {
+            let typ = u16::from_be_bytes([buf[i], buf[i + 1]]);
+            let len = u16::from_be_bytes([buf[i + 2], buf[i + 3]]) as usize;
+            i += 4;
+            if i + len > buf.len() {
+                bail!("invalid STUN attribute");
+            }
+            msg.add(typ, buf[i..i + len].to_vec());
+            i += (len + 3) & !3;
+        }
+        Ok(msg)
+    }
+
+    fn error_code(&self) -> u16 {
+        match self.get(ERROR_CODE) {
+            Some(v) if v.len() >= 4 => (v[2] & 0x7) as u16 * 100 + v[3] as u16,
+            _ => 0,
+        }
+    }
+
+    fn error(&self) -> String {
+        let reason = self
+            .get(ERROR_CODE)
+            .map(|v| String::from_utf8_lossy(v.get(4..).unwrap_or_default()).to_string())
+            .unwrap_or_default();
+        format!("{} {}", self.error_code(), reason)
+    }
+
+    fn xor_addr(&self, typ: u16) -> Option<SocketAddr> {
+        let v = self.get(typ)?;
+        if v.len() < 8 {
+            return None;
+        }
+        let port = u16::from_be_bytes([v[2], v[3]]) ^ (MAGIC_COOKIE >> 16) as u16;
+        let mut mask = MAGIC_COOKIE.to_be_bytes().to_vec();
+        mask.extend_from_slice(&self.tid);
+        let ip = match v[1] {
+            1 => {
+                let mut ip = [0u8; 4];
+                ip.copy_from_slice(&v[4..8]);
+                ip.iter_mut().zip(mask.iter()).for_each(|(b, m)| *b ^= m);
+                IpAddr::from(ip)
+            }
+            2 if v.len() >= 20 => {
+                let mut ip = [0u8; 16];
+                ip.copy_from_slice(&v[4..20]);
+                ip.iter_mut().zip(mask.iter()).for_each(|(b, m)| *b ^= m);
+                IpAddr::from(ip)
+            }
+            _ => return None,
+        };
+        Some(SocketAddr::new(ip, port))
+    }
+
+    fn add_xor_addr(&mut self, typ: u16, addr: SocketAddr) {
+        let mut mask = MAGIC_COOKIE.to_be_bytes().to_vec();
+        mask.extend_from_slice(&self.tid);
+        let (family, mut ip) = match addr.ip() {
+            IpAddr::V4(ip) => (1, ip.octets().to_vec()),
+            IpAddr::V6(ip) => (2, ip.octets().to_vec()),
+        };
+        ip.iter_mut().zip(mask.iter()).for_each(|(b, m)| *b ^= m);
+        let mut v = vec![0, family];
+        v.extend_from_slice(&(addr.port() ^ (MAGIC_COOKIE >> 16) as u16).to_be_bytes());
+        v.extend_from_slice(&ip);
+        self.add(typ, v);
+    }
+}
+
+async fn read_msg(stream: &mut TcpStream) -> ResultType<Msg> {
+    let mut buf = vec![0u8; HEADER_LEN];
+    stream.read_exact(&mut buf).await?;
+    let len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+    if len > MAX_MSG_LEN {
+        bail!("STUN message too long");
+    }
+    buf.resize(HEADER_LEN + len, 0);
+    stream.read_exact(&mut buf[HEADER_LEN..]).await?;
+    Msg::decode(&buf)
+}
+
+#[derive(Debug, Default)]
+struct Auth {
+    username: String,
+    password: String,
+    realm: String,
+    nonce: String,
+}
+
+impl Auth {
+    fn key(&self) -> Vec<u8> {
+        md5::compute(format!(
+            "{}:{}:{}",
+            self.username, self.realm, self.password
+        ))
+        .0
+        .to_vec()
+    }
+
+    // the realm and the nonce of the challenge, false if it is not one
+    fn on_challenge(&mut self, res: &Msg) -> bool {
+        if !matches!(res.error_code(), UNAUTHORIZED | STALE_NONCE) {
+            return false;
+        }
+        let (Some(realm), Some(nonce)) = (res.get(REALM), res.get(NONCE)) else {
+            return false;
+        };
+        self.realm = String::from_utf8_lossy(realm).to_string();
+        self.nonce = String::from_utf8_lossy(nonce).to_string();
+        true
+    }
+
+    // signs the request once the realm is known
+    fn encode(&self, mut msg: Msg) -> (Vec<u8>, [u8; 12]) {
+        let tid = msg.tid;
+        if self.realm.is_empty() {
+            return (msg.encode(None), tid);
+        }
+        msg.add(USERNAME, self.username.as_bytes().to_vec());
+        msg.add(REALM, self.realm.as_bytes().to_vec());
+        msg.add(NONCE, self.nonce.as_bytes().to_vec());
+        (msg.encode(Some(&self.key())), tid)
+    }
+}
+
+// sends the request on the stream, with the credentials if challenged, the attributes are added
+// by `build` to each transaction as the xor addresses depend on its id
+async fn request(
+    stream: &mut TcpStream,
+    auth: &mut Auth,
+    method: u16,
+    build: impl Fn(&mut Msg),
+) -> ResultType<Msg> {
+    for _ in 0..3 {
+        let mut msg = Msg::new(method | REQUEST);
+        build(&mut msg);
+        let (buf, tid) = auth.encode(msg);
+        stream.write_all(&buf).await?;
+        let res = loop {
+            let res = timeout(CONNECT_TIMEOUT, read_msg(stream)).await??;
+            // the indications meanwhile are not expected
+            if res.tid == tid {
+                break res;
+            }
+        };
+        if res.typ == method | SUCCESS {
+            return Ok(res);
+        }
+        if res.typ != method | ERROR || !auth.on_challenge(&res) {
+            bail!("TURN request {:#x} failed: {}", method, res.error());
+        }
+    }
+    bail!("TURN authentication failed")
+}
+
+/// A relayed tcp address on the TURN server.
+pub struct Allocation {
+    control: TcpStream,
+    server: SocketAddr,
+    auth: Auth,
+    relayed: SocketAddr,
+}
+
+impl Allocation {
+    /// Allocates on `server` for the peer connecting from `peer`.
+    pub async fn new(
+        server: &str,
+        username: String,
+        password: String,
+        peer: IpAddr,
+    ) -> ResultType<Self> {
+        let server = lookup_host(parse_server(server)?)
+            .await?
+            .next()
+            .ok_or_else(|| anyhow!("failed to resolve the TURN server"))?;
+        let mut control = timeout(CONNECT_TIMEOUT, TcpStream::connect(server)).await??;
+        control.set_nodelay(true).ok();
+        let mut auth = Auth {
+            username,
+            password,
+            ..Default::default()
+        };
+        let res = request(&mut control, &mut auth, ALLOCATE, |msg| {
+            msg.add(REQUESTED_TRANSPORT, vec![TRANSPORT_TCP, 0, 0, 0]);
+            msg.add(LIFETIME, ALLOCATION_LIFETIME.to_be_bytes().to_vec());
+        })
+        .await?;
+        let relayed = res
+            .xor_addr(XOR_RELAYED_ADDRESS)
+            .ok_or_else(|| anyhow!("no relayed address from the TURN server"))?;
+        log::info!("TURN relayed address {} allocated on {}", relayed, server);
+        let mut allocation = Self {
+            control,
+            server,
+            auth,
+            relayed,
+        };
+        allocation.permit(peer).await?;
+        Ok(allocation)
+    }
+
+    pub fn relayed(&self) -> SocketAddr {
+        self.relayed
+    }
+
+    // lets the peer connect from `ip` to the relayed address
+    async fn permit(&mut self, ip: IpAddr) -> ResultType<()> {
+        request(
+            &mut self.control,
+            &mut self.auth,
+            CREATE_PERMISSION,
+            |msg| msg.add_xor_addr(XOR_PEER_ADDRESS, SocketAddr::new(ip, 0)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    // the connection id and the address of the next peer connecting to the relayed address
+    async fn next_attempt(&mut self) -> ResultType<(Vec<u8>, Option<SocketAddr>)> {
+        loop {
+            let msg = read_msg(&mut self.control).await?;
+            if msg.typ != CONNECTION_ATTEMPT | INDICATION {
+                continue;
+            }
+            if let Some(id) = msg.get(CONNECTION_ID) {
+                return Ok((id.to_vec(), msg.xor_addr(XOR_PEER_ADDRESS)));
+            }
+        }
+    }
+
+    /// Waits for the peer to connect to the relayed address, and binds its connection.
+    pub async fn accept(&mut self, ms_timeout: u64) -> ResultType<Stream> {
+        let (id, peer) = timeout(ms_timeout, self.next_attempt()).await??;
+        log::info!("TURN connection attempt from {:?}", peer);
+        let mut data = timeout(CONNECT_TIMEOUT, TcpStream::connect(self.server)).await??;
+        data.set_nodelay(true).ok();
+        request(&mut data, &mut self.auth, CONNECTION_BIND, |msg| {
+            msg.add(CONNECTION_ID, id.clone())
+        })
+        .await?;
+        // the connection carries the data of the peer from now on
+        let local_addr = data.local_addr()?;
+        Ok(Stream::from(data, local_addr))
+    }
+
+    /// Refreshes the allocation until it fails, to be dropped with the session.
+    pub async fn keep_alive(mut self) {
+        loop {
+            sleep(std::time::Duration::from_secs(
+                ALLOCATION_LIFETIME as u64 / 2,
+            ))
+            .await;
+            let res = request(&mut self.control, &mut self.auth, REFRESH, |msg| {
+                msg.add(LIFETIME, ALLOCATION_LIFETIME.to_be_bytes().to_vec())
+            })
+            .await;
+            if let Err(err) = res {
+                log::error!("Failed to refresh the TURN allocation: {}", err);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_msg() {
+        let mut msg = Msg::new(ALLOCATE | REQUEST);
+        let addr: SocketAddr = "1.2.3.4:5678".parse().unwrap();
+        let addr_v6: SocketAddr = "[2001:db8::1]:5678".parse().unwrap();
+        msg.add_xor_addr(XOR_PEER_ADDRESS, addr);
+        msg.add_xor_addr(XOR_RELAYED_ADDRESS, addr_v6);
+        msg.add(REALM, b"realm".to_vec());
+        let buf = msg.encode(Some(b"key"));
+        assert_eq!(buf.len() % 4, 0);
+        let decoded = Msg::decode(&buf).unwrap();
+        assert_eq!(decoded.typ, ALLOCATE | REQUEST);
+        assert_eq!(decoded.xor_addr(XOR_PEER_ADDRESS), Some(addr));
+        assert_eq!(decoded.xor_addr(XOR_RELAYED_ADDRESS), Some(addr_v6));
+        assert_eq!(decoded.get(REALM), Some(&b"realm"[..]));
+        assert_eq!(decoded.get(MESSAGE_INTEGRITY).map(|v| v.len()), Some(20));
+        assert_eq!(
+            parse_server("turn:turn.example.com?transport=tcp").unwrap(),
+            "turn.example.com:3478"
+        );
+        assert!(parse_server("turns:turn.example.com").is_err());
+    }
+}
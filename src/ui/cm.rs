@@ -45,6 +45,10 @@ impl InvokeUiCM for SciterHandler {
         self.call("newMessage", &make_args!(id, text));
     }
 
+    fn new_attachment(&self, _id: i32, _path: String, _image: bool) {}
+
+    fn chat_receipt(&self, _id: i32, _msg_id: u32, _error: String) {}
+
     fn change_theme(&self, _dark: String) {
         // TODO
     }
@@ -64,6 +68,10 @@ impl InvokeUiCM for SciterHandler {
         );
     }
 
+    fn update_input_control_state(&self, _client: &crate::ui_cm_interface::Client) {}
+
+    fn update_annotation_state(&self, _client: &crate::ui_cm_interface::Client) {}
+
     fn file_transfer_log(&self, _action: &str, _log: &str) {}
 }
 
@@ -131,7 +139,7 @@ impl SciterConnectionManager {
     }
 
     fn send_msg(&self, id: i32, text: String) {
-        crate::ui_cm_interface::send_chat(id, text);
+        crate::ui_cm_interface::send_chat(id, 0, text);
     }
 
     fn t(&self, name: String) -> String {
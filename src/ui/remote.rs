@@ -149,13 +149,15 @@ impl InvokeUiSession for SciterHandler {
         self.call("setConnectionType", &make_args!(is_secured, direct));
     }
 
+    fn set_connection_degraded(&self, _degraded: bool) {}
+
     fn set_fingerprint(&self, _fingerprint: String) {}
 
     fn job_error(&self, id: i32, err: String, file_num: i32) {
         self.call("jobError", &make_args!(id, err, file_num));
     }
 
-    fn job_done(&self, id: i32, file_num: i32) {
+    fn job_done(&self, id: i32, file_num: i32, _digests: &str) {
         self.call("jobDone", &make_args!(id, file_num));
     }
 
@@ -199,6 +201,18 @@ impl InvokeUiSession for SciterHandler {
         self.call("updateTransferList", &make_args!());
     }
 
+    // unused in sciter
+    fn file_search_result(
+        &self,
+        _id: i32,
+        _entries: &Vec<FileEntry>,
+        _done: bool,
+        _truncated: bool,
+    ) {
+    }
+
+    fn file_range(&self, _id: i32, _offset: u64, _data: &[u8], _size: u64) {}
+
     fn confirm_delete_files(&self, id: i32, i: i32, name: String) {
         self.call("confirmDeleteFiles", &make_args!(id, i, name));
     }
@@ -280,6 +294,7 @@ impl InvokeUiSession for SciterHandler {
             ConnType::RDP => {}
             ConnType::PORT_FORWARD => {}
             ConnType::FILE_TRANSFER => {}
+            ConnType::TERMINAL => {}
             ConnType::DEFAULT_CONN => {
                 crate::keyboard::client::start_grab_loop();
             }
@@ -301,6 +316,32 @@ impl InvokeUiSession for SciterHandler {
         self.call("newMessage", &make_args!(msg));
     }
 
+    fn new_attachment(&self, _path: String, _image: bool) {}
+
+    fn chat_receipt(&self, _id: u32, _error: String) {}
+
+    fn session_handoff(&self, _link: String, _timeout: u32, _error: String) {}
+
+    fn update_system_stats(&self, _stats: crate::client::metrics::HostStats) {}
+
+    fn update_process_list(&self, _list: &ProcessList) {}
+
+    fn process_killed(&self, _killed: &ProcessKilled) {}
+
+    fn update_service_list(&self, _list: &ServiceList) {}
+
+    fn service_control_result(&self, _res: &ServiceControlResult) {}
+
+    fn update_camera_list(&self, _list: &CameraList) {}
+
+    fn on_camera_frame(&self, _width: usize, _height: usize, _rgba: Vec<u8>) {}
+
+    fn camera_error(&self, _error: String) {}
+
+    fn print_job(&self, _title: String, _path: String, _copies: u32) {}
+
+    fn update_broadcast_viewers(&self, _viewers: &BroadcastViewers) {}
+
     fn switch_display(&self, display: &SwitchDisplay) {
         self.call("switchDisplay", &make_args!(display.display));
     }
@@ -339,6 +380,18 @@ impl InvokeUiSession for SciterHandler {
     fn update_record_status(&self, start: bool) {
         self.call("updateRecordStatus", &make_args!(start));
     }
+
+    fn update_server_record_status(&self, _on: bool) {}
+
+    fn inactivity_warning(&self, _seconds: u32, _view_only: bool) {}
+
+    fn update_remote_volume(&self, _level: u32, _mute: bool) {}
+
+    fn update_input_control(&self, _control: InputControl) {}
+
+    fn update_annotation(&self, _strokes: Vec<AnnotationStroke>) {}
+
+    fn sync_job_plan(&self, _id: i32, _name: &str, _dry_run: bool, _plan: &str, _err: &str) {}
 }
 
 pub struct SciterSession(Session<SciterHandler>);
@@ -452,9 +505,9 @@ impl sciter::EventHandler for SciterSession {
         fn get_icon();
         fn get_home_dir();
         fn read_dir(String, bool);
-        fn remove_dir(i32, String, bool);
+        fn remove_dir(i32, String, bool, bool);
         fn create_dir(i32, String, bool);
-        fn remove_file(i32, String, i32, bool);
+        fn remove_file(i32, String, i32, bool, bool);
         fn read_remote_dir(String, bool);
         fn send_chat(String);
         fn switch_display(i32);
@@ -462,7 +515,7 @@ impl sciter::EventHandler for SciterSession {
         fn confirm_delete_files(i32, i32);
         fn set_no_confirm(i32);
         fn cancel_job(i32);
-        fn send_files(i32, String, String, i32, bool, bool);
+        fn send_files(i32, String, String, i32, bool, bool, String);
         fn add_job(i32, String, String, i32, bool, bool);
         fn resume_job(i32, bool);
         fn get_platform(bool);
@@ -12,7 +12,12 @@ use hbb_common::{
     allow_err,
     config::{keys::*, option2bool, Config},
     fs::is_write_need_confirmation,
-    fs::{self, get_string, new_send_confirm, DigestCheckResult},
+    fs::{
+        self,
+        audit::{self, Outcome},
+        conflict::Resolution,
+        get_string, new_send_confirm, DigestCheckResult,
+    },
     log,
     message_proto::*,
     protobuf::Message as _,
@@ -27,8 +32,8 @@ use hbb_common::{tokio::sync::Mutex as TokioMutex, ResultType};
 use serde_derive::Serialize;
 #[cfg(any(target_os = "android", target_os = "ios", feature = "flutter"))]
 use std::iter::FromIterator;
-#[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
-use std::sync::Arc;
+#[cfg(not(any(target_os = "ios")))]
+use std::sync::{atomic::AtomicBool, Arc};
 use std::{
     collections::HashMap,
     ops::{Deref, DerefMut},
@@ -44,6 +49,7 @@ pub struct Client {
     pub authorized: bool,
     pub disconnected: bool,
     pub is_file_transfer: bool,
+    pub is_terminal: bool,
     pub port_forward: String,
     pub name: String,
     pub peer_id: String,
@@ -54,9 +60,23 @@ pub struct Client {
     pub restart: bool,
     pub recording: bool,
     pub block_input: bool,
+    pub key_input: bool,
+    pub mouse_input: bool,
+    pub clipboard_typing: bool,
+    pub terminal: bool,
     pub from_switch: bool,
     pub in_voice_call: bool,
     pub incoming_voice_call: bool,
+    // asks for the control of the input held by another peer
+    pub input_control_requested: bool,
+    // the strokes the peer drew over the screen
+    pub annotations: usize,
+    // why the peer connects, empty if not asked
+    pub reason: String,
+    // the permissions the policy of the management server does not let switch on
+    pub locked: Vec<String>,
+    // the key of the device of the peer not trusted yet, empty if none
+    pub device_key: String,
     #[serde(skip)]
     #[cfg(not(any(target_os = "ios")))]
     tx: UnboundedSender<Data>,
@@ -71,6 +91,9 @@ struct IpcTaskRunner<T: InvokeUiCM> {
     close: bool,
     running: bool,
     conn_id: i32,
+    access_reason: String,
+    policy_locked: Vec<String>,
+    device_key: String,
     #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
     file_transfer_enabled: bool,
     #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
@@ -95,6 +118,12 @@ pub trait InvokeUiCM: Send + Clone + 'static + Sized {
 
     fn new_message(&self, id: i32, text: String);
 
+    // a file of the peer in the chat, saved at `path`
+    fn new_attachment(&self, id: i32, path: String, image: bool);
+
+    // of the message `msg_id` sent to the peer, delivered if `error` is empty
+    fn chat_receipt(&self, id: i32, msg_id: u32, error: String);
+
     fn change_theme(&self, dark: String);
 
     fn change_language(&self);
@@ -103,6 +132,10 @@ pub trait InvokeUiCM: Send + Clone + 'static + Sized {
 
     fn update_voice_call_state(&self, client: &Client);
 
+    fn update_input_control_state(&self, client: &Client);
+
+    fn update_annotation_state(&self, client: &Client);
+
     fn file_transfer_log(&self, action: &str, log: &str);
 }
 
@@ -125,6 +158,7 @@ impl<T: InvokeUiCM> ConnectionManager<T> {
         &self,
         id: i32,
         is_file_transfer: bool,
+        is_terminal: bool,
         port_forward: String,
         peer_id: String,
         name: String,
@@ -136,7 +170,14 @@ impl<T: InvokeUiCM> ConnectionManager<T> {
         restart: bool,
         recording: bool,
         block_input: bool,
+        key_input: bool,
+        mouse_input: bool,
+        clipboard_typing: bool,
+        terminal: bool,
         from_switch: bool,
+        reason: String,
+        locked: Vec<String>,
+        device_key: String,
         #[cfg(not(any(target_os = "ios")))] tx: mpsc::UnboundedSender<Data>,
     ) {
         let client = Client {
@@ -144,6 +185,7 @@ impl<T: InvokeUiCM> ConnectionManager<T> {
             authorized,
             disconnected: false,
             is_file_transfer,
+            is_terminal,
             port_forward,
             name: name.clone(),
             peer_id: peer_id.clone(),
@@ -154,17 +196,30 @@ impl<T: InvokeUiCM> ConnectionManager<T> {
             restart,
             recording,
             block_input,
+            key_input,
+            mouse_input,
+            clipboard_typing,
+            terminal,
             from_switch,
             #[cfg(not(any(target_os = "ios")))]
             tx,
             in_voice_call: false,
             incoming_voice_call: false,
+            input_control_requested: false,
+            annotations: 0,
+            reason,
+            locked,
+            device_key,
         };
         CLIENTS
             .write()
             .unwrap()
             .retain(|_, c| !(c.disconnected && c.peer_id == client.peer_id));
         CLIENTS.write().unwrap().insert(id, client.clone());
+        #[cfg(not(any(target_os = "ios")))]
+        if views_screen(&client) && !crate::annotation::is_empty() {
+            allow_err!(client.tx.send(Data::Annotation(crate::annotation::board())));
+        }
         self.ui_handler.add_connection(&client);
     }
 
@@ -189,6 +244,11 @@ impl<T: InvokeUiCM> ConnectionManager<T> {
                 .get_mut(&id)
                 .map(|c| c.disconnected = true);
         }
+        #[cfg(not(any(target_os = "ios")))]
+        if crate::annotation::clear(id) {
+            send_annotations();
+            self.update_annotation_state();
+        }
 
         #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
         {
@@ -248,6 +308,58 @@ impl<T: InvokeUiCM> ConnectionManager<T> {
             self.ui_handler.update_voice_call_state(client);
         }
     }
+
+    #[cfg(not(target_os = "ios"))]
+    fn input_control_requested(&self, id: i32, requested: bool) {
+        if let Some(client) = CLIENTS.write().unwrap().get_mut(&id) {
+            client.input_control_requested = requested;
+            self.ui_handler.update_input_control_state(client);
+        }
+    }
+
+    #[cfg(not(target_os = "ios"))]
+    fn chat_attachment(
+        &self,
+        id: i32,
+        msg_id: u32,
+        attachment: ChatAttachment,
+        tx: &UnboundedSender<Data>,
+    ) {
+        let error = match crate::chat::save(&attachment) {
+            Ok(path) => {
+                self.ui_handler.new_attachment(id, path, attachment.image);
+                "".to_owned()
+            }
+            Err(err) => {
+                log::error!("Failed to save the chat attachment: {}", err);
+                err.to_string()
+            }
+        };
+        if msg_id != 0 {
+            send_raw(crate::chat::new_receipt(msg_id, error), tx);
+        }
+    }
+
+    #[cfg(not(target_os = "ios"))]
+    fn annotation(&self, id: i32, bytes: Vec<u8>) {
+        if let Ok(annotation) = Annotation::parse_from_bytes(&bytes) {
+            if crate::annotation::apply(id, annotation) {
+                send_annotations();
+                self.update_annotation_state();
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "ios"))]
+    fn update_annotation_state(&self) {
+        for client in CLIENTS.write().unwrap().values_mut() {
+            let annotations = crate::annotation::count(client.id);
+            if client.annotations != annotations {
+                client.annotations = annotations;
+                self.ui_handler.update_annotation_state(client);
+            }
+        }
+    }
 }
 
 #[inline]
@@ -289,6 +401,23 @@ pub fn notify_input_control(v: bool) {
     }
 }
 
+// the connection shows the screen of this side
+#[cfg(not(any(target_os = "ios")))]
+fn views_screen(client: &Client) -> bool {
+    !client.is_file_transfer && !client.is_terminal && client.port_forward.is_empty()
+}
+
+// sends all the drawings to the peers seeing the screen
+#[cfg(not(any(target_os = "ios")))]
+fn send_annotations() {
+    let bytes = crate::annotation::board();
+    for client in CLIENTS.read().unwrap().values() {
+        if views_screen(client) && !client.disconnected {
+            allow_err!(client.tx.send(Data::Annotation(bytes.clone())));
+        }
+    }
+}
+
 #[inline]
 pub fn remove(id: i32) {
     CLIENTS.write().unwrap().remove(&id);
@@ -297,13 +426,34 @@ pub fn remove(id: i32) {
 // server mode send chat to peer
 #[inline]
 #[cfg(not(any(target_os = "ios")))]
-pub fn send_chat(id: i32, text: String) {
+pub fn send_chat(id: i32, msg_id: u32, text: String) {
     let clients = CLIENTS.read().unwrap();
     if let Some(client) = clients.get(&id) {
-        allow_err!(client.tx.send(Data::ChatMessage { text }));
+        allow_err!(client.tx.send(Data::ChatMessage { text, id: msg_id }));
     }
 }
 
+// server mode send a file in the chat to peer
+#[inline]
+#[cfg(not(any(target_os = "ios")))]
+pub fn send_chat_attachment(id: i32, msg_id: u32, path: String) -> ResultType<()> {
+    let mut chat = crate::chat::attach(msg_id, &path)?;
+    let Some(attachment) = chat.attachment.take() else {
+        return Ok(());
+    };
+    // checked by the connection before it is sent
+    if let Some(client) = CLIENTS.read().unwrap().get(&id) {
+        allow_err!(client.tx.send(Data::ChatAttachment {
+            id: msg_id,
+            name: attachment.name,
+            data: attachment.data,
+            compressed: attachment.compressed,
+            image: attachment.image,
+        }));
+    }
+    Ok(())
+}
+
 #[inline]
 #[cfg(not(any(target_os = "ios")))]
 pub fn switch_permission(id: i32, name: String, enabled: bool) {
@@ -312,6 +462,15 @@ pub fn switch_permission(id: i32, name: String, enabled: bool) {
     };
 }
 
+// the permissions of the connection are applied to the next ones from its peer, or no longer
+#[inline]
+#[cfg(not(any(target_os = "ios")))]
+pub fn save_permission_profile(id: i32, save: bool) {
+    if let Some(client) = CLIENTS.read().unwrap().get(&id) {
+        allow_err!(client.tx.send(Data::SavePermissionProfile(save)));
+    };
+}
+
 #[cfg(any(target_os = "android", target_os = "ios", feature = "flutter"))]
 #[inline]
 pub fn get_clients_state() -> String {
@@ -342,6 +501,8 @@ impl<T: InvokeUiCM> IpcTaskRunner<T> {
 
         // for tmp use, without real conn id
         let mut write_jobs: Vec<fs::TransferJob> = Vec::new();
+        let mut searches = HashMap::new();
+        let mut trashed = Vec::new();
 
         #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
         let is_authorized = self.cm.is_authorized(self.conn_id);
@@ -391,9 +552,9 @@ impl<T: InvokeUiCM> IpcTaskRunner<T> {
                         }
                         Ok(Some(data)) => {
                             match data {
-                                Data::Login{id, is_file_transfer, port_forward, peer_id, name, authorized, keyboard, clipboard, audio, file, file_transfer_enabled: _file_transfer_enabled, restart, recording, block_input, from_switch} => {
+                                Data::Login{id, is_file_transfer, is_terminal, port_forward, peer_id, name, authorized, keyboard, clipboard, audio, file, file_transfer_enabled: _file_transfer_enabled, restart, recording, block_input, key_input, mouse_input, clipboard_typing, terminal, from_switch} => {
                                     log::debug!("conn_id: {}", id);
-                                    self.cm.add_connection(id, is_file_transfer, port_forward, peer_id, name, authorized, keyboard, clipboard, audio, file, restart, recording, block_input, from_switch, self.tx.clone());
+                                    self.cm.add_connection(id, is_file_transfer, is_terminal, port_forward, peer_id, name, authorized, keyboard, clipboard, audio, file, restart, recording, block_input, key_input, mouse_input, clipboard_typing, terminal, from_switch, std::mem::take(&mut self.access_reason), std::mem::take(&mut self.policy_locked), std::mem::take(&mut self.device_key), self.tx.clone());
                                     self.conn_id = id;
                                     #[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
                                     {
@@ -402,6 +563,15 @@ impl<T: InvokeUiCM> IpcTaskRunner<T> {
                                     self.running = true;
                                     break;
                                 }
+                                Data::AccessReason(reason) => {
+                                    self.access_reason = reason;
+                                }
+                                Data::PolicyLocked(locked) => {
+                                    self.policy_locked = locked;
+                                }
+                                Data::NewDeviceKey(key) => {
+                                    self.device_key = key;
+                                }
                                 Data::Close => {
                                     log::info!("cm ipc connection closed from connection request");
                                     break;
@@ -418,17 +588,32 @@ impl<T: InvokeUiCM> IpcTaskRunner<T> {
                                 Data::ClickTime(ms) => {
                                     CLICK_TIME.store(ms, Ordering::SeqCst);
                                 }
-                                Data::ChatMessage { text } => {
+                                Data::ChatMessage { text, .. } => {
                                     self.cm.new_message(self.conn_id, text);
                                 }
+                                Data::ChatAttachment { id, name, data: _, compressed, image } => {
+                                    if let Ok(bytes) = self.stream.next_raw().await {
+                                        let attachment = ChatAttachment {
+                                            name,
+                                            data: bytes.into(),
+                                            compressed,
+                                            image,
+                                            ..Default::default()
+                                        };
+                                        self.cm.chat_attachment(self.conn_id, id, attachment, &self.tx);
+                                    }
+                                }
+                                Data::ChatReceipt { id, error } => {
+                                    self.cm.chat_receipt(self.conn_id, id, error);
+                                }
                                 Data::FS(mut fs) => {
-                                    if let ipc::FS::WriteBlock { id, file_num, data: _, compressed } = fs {
+                                    if let ipc::FS::WriteBlock { id, file_num, data: _, compressed, copy_blks, checksum } = fs {
                                         if let Ok(bytes) = self.stream.next_raw().await {
-                                            fs = ipc::FS::WriteBlock{id, file_num, data:bytes.into(), compressed};
-                                            handle_fs(fs, &mut write_jobs, &self.tx, Some(&tx_log)).await;
+                                            fs = ipc::FS::WriteBlock{id, file_num, data:bytes.into(), compressed, copy_blks, checksum};
+                                            handle_fs(fs, &mut write_jobs, &mut searches, &mut trashed, &self.tx, Some(&tx_log)).await;
                                         }
                                     } else {
-                                        handle_fs(fs, &mut write_jobs, &self.tx, Some(&tx_log)).await;
+                                        handle_fs(fs, &mut write_jobs, &mut searches, &mut trashed, &self.tx, Some(&tx_log)).await;
                                     }
                                     let log = fs::serialize_transfer_jobs(&write_jobs);
                                     self.cm.ui_handler.file_transfer_log("transfer", &log);
@@ -487,6 +672,12 @@ impl<T: InvokeUiCM> IpcTaskRunner<T> {
                                 Data::CloseVoiceCall(reason) => {
                                     self.cm.voice_call_closed(self.conn_id, reason.as_str());
                                 }
+                                Data::InputControlRequest(requested) => {
+                                    self.cm.input_control_requested(self.conn_id, requested);
+                                }
+                                Data::Annotation(bytes) => {
+                                    self.cm.annotation(self.conn_id, bytes);
+                                }
                                 #[cfg(target_os = "windows")]
                                 Data::ClipboardNonFile(_) => {
                                     match crate::clipboard::check_clipboard_cm() {
@@ -600,6 +791,9 @@ impl<T: InvokeUiCM> IpcTaskRunner<T> {
             close: true,
             running: true,
             conn_id: 0,
+            access_reason: Default::default(),
+            policy_locked: Default::default(),
+            device_key: Default::default(),
             #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
             file_transfer_enabled: false,
             #[cfg(any(target_os = "windows", target_os = "linux", target_os = "macos"))]
@@ -664,12 +858,18 @@ pub async fn start_listen<T: InvokeUiCM>(
     tx: mpsc::UnboundedSender<Data>,
 ) {
     let mut current_id = 0;
+    let mut access_reason = String::new();
+    let mut policy_locked = Vec::new();
+    let mut device_key = String::new();
     let mut write_jobs: Vec<fs::TransferJob> = Vec::new();
+    let mut searches = HashMap::new();
+    let mut trashed = Vec::new();
     loop {
         match rx.recv().await {
             Some(Data::Login {
                 id,
                 is_file_transfer,
+                is_terminal,
                 port_forward,
                 peer_id,
                 name,
@@ -681,6 +881,10 @@ pub async fn start_listen<T: InvokeUiCM>(
                 restart,
                 recording,
                 block_input,
+                key_input,
+                mouse_input,
+                clipboard_typing,
+                terminal,
                 from_switch,
                 ..
             }) => {
@@ -688,6 +892,7 @@ pub async fn start_listen<T: InvokeUiCM>(
                 cm.add_connection(
                     id,
                     is_file_transfer,
+                    is_terminal,
                     port_forward,
                     peer_id,
                     name,
@@ -699,15 +904,50 @@ pub async fn start_listen<T: InvokeUiCM>(
                     restart,
                     recording,
                     block_input,
+                    key_input,
+                    mouse_input,
+                    clipboard_typing,
+                    terminal,
                     from_switch,
+                    std::mem::take(&mut access_reason),
+                    std::mem::take(&mut policy_locked),
+                    std::mem::take(&mut device_key),
                     tx.clone(),
                 );
             }
-            Some(Data::ChatMessage { text }) => {
+            Some(Data::AccessReason(reason)) => {
+                access_reason = reason;
+            }
+            Some(Data::PolicyLocked(locked)) => {
+                policy_locked = locked;
+            }
+            Some(Data::NewDeviceKey(key)) => {
+                device_key = key;
+            }
+            Some(Data::ChatMessage { text, .. }) => {
                 cm.new_message(current_id, text);
             }
+            Some(Data::ChatAttachment {
+                id,
+                name,
+                data,
+                compressed,
+                image,
+            }) => {
+                let attachment = ChatAttachment {
+                    name,
+                    data,
+                    compressed,
+                    image,
+                    ..Default::default()
+                };
+                cm.chat_attachment(current_id, id, attachment, &tx);
+            }
+            Some(Data::ChatReceipt { id, error }) => {
+                cm.chat_receipt(current_id, id, error);
+            }
             Some(Data::FS(fs)) => {
-                handle_fs(fs, &mut write_jobs, &tx, None).await;
+                handle_fs(fs, &mut write_jobs, &mut searches, &mut trashed, &tx, None).await;
             }
             Some(Data::Close) => {
                 break;
@@ -721,6 +961,12 @@ pub async fn start_listen<T: InvokeUiCM>(
             Some(Data::CloseVoiceCall(reason)) => {
                 cm.voice_call_closed(current_id, reason.as_str());
             }
+            Some(Data::InputControlRequest(requested)) => {
+                cm.input_control_requested(current_id, requested);
+            }
+            Some(Data::Annotation(bytes)) => {
+                cm.annotation(current_id, bytes);
+            }
             None => {
                 break;
             }
@@ -734,6 +980,9 @@ pub async fn start_listen<T: InvokeUiCM>(
 async fn handle_fs(
     fs: ipc::FS,
     write_jobs: &mut Vec<fs::TransferJob>,
+    searches: &mut HashMap<i32, Arc<AtomicBool>>,
+    // what the removal jobs of the connection moved to the trash
+    trashed: &mut Vec<(i32, fs::trash::Trashed)>,
     tx: &UnboundedSender<Data>,
     tx_log: Option<&UnboundedSender<String>>,
 ) {
@@ -750,11 +999,38 @@ async fn handle_fs(
             path,
             id,
             recursive,
+            trash,
         } => {
-            remove_dir(path, id, recursive, tx).await;
+            if trash {
+                trash_dir(path, id, trashed, tx).await;
+            } else {
+                remove_dir(path, id, recursive, tx).await;
+            }
         }
-        ipc::FS::RemoveFile { path, id, file_num } => {
-            remove_file(path, id, file_num, tx).await;
+        ipc::FS::RemoveFile {
+            path,
+            id,
+            file_num,
+            trash,
+        } => {
+            if trash {
+                trash_file(path, id, file_num, trashed, tx).await;
+            } else {
+                remove_file(path, id, file_num, tx).await;
+            }
+        }
+        ipc::FS::UndoRemove { id, remove_ids } => {
+            let (mut restoring, kept): (Vec<_>, Vec<_>) = std::mem::take(trashed)
+                .into_iter()
+                .partition(|(job, _)| remove_ids.contains(job));
+            *trashed = kept;
+            handle_result(
+                spawn_blocking(move || fs::trash::restore_jobs(&mut restoring, &remove_ids)).await,
+                id,
+                0,
+                tx,
+            )
+            .await;
         }
         ipc::FS::CreateDir { path, id } => {
             create_dir(path, id, tx).await;
@@ -767,7 +1043,12 @@ async fn handle_fs(
             overwrite_detection,
             total_size,
             conn_id,
+            verify,
+            attributes,
+            mut file_attributes,
+            conflict_policy,
         } => {
+            use hbb_common::protobuf::Enum;
             // cm has no show_hidden context
             // dummy remote, show_hidden, is_remote
             let mut job = fs::TransferJob::new_write(
@@ -779,38 +1060,68 @@ async fn handle_fs(
                 false,
                 files
                     .drain(..)
-                    .map(|f| FileEntry {
-                        name: f.0,
-                        modified_time: f.1,
-                        ..Default::default()
+                    .zip(
+                        file_attributes
+                            .drain(..)
+                            .chain(std::iter::repeat_with(Default::default)),
+                    )
+                    .map(|(f, a)| {
+                        let mut entry = FileEntry {
+                            name: f.0,
+                            modified_time: f.1,
+                            ..Default::default()
+                        };
+                        a.set_to(&mut entry);
+                        entry
                     })
                     .collect(),
                 overwrite_detection,
             );
             job.total_size = total_size;
             job.conn_id = conn_id;
+            job.set_verify(verify);
+            job.set_attributes(attributes, false);
+            job.set_conflict_policy(
+                FileConflictPolicy::from_i32(conflict_policy).unwrap_or_default(),
+            );
             write_jobs.push(job);
         }
         ipc::FS::CancelWrite { id } => {
+            if let Some(cancelled) = searches.remove(&id) {
+                cancelled.store(true, Ordering::Relaxed);
+            }
             if let Some(job) = fs::get_job(id, write_jobs) {
                 job.remove_download_file();
                 tx_log.map(|tx: &UnboundedSender<String>| {
                     tx.send(serialize_transfer_job(job, false, true, ""))
                 });
+                send_transfer_event(job, Outcome::Cancelled, tx);
                 fs::remove_job(id, write_jobs);
             }
         }
         ipc::FS::WriteDone { id, file_num } => {
             if let Some(job) = fs::get_job(id, write_jobs) {
                 job.modify_time();
-                send_raw(fs::new_done(id, file_num), tx);
-                tx_log.map(|tx| tx.send(serialize_transfer_job(job, true, false, "")));
+                job.create_links();
+                match job.corrupted_error() {
+                    Some(err) => {
+                        tx_log.map(|tx| tx.send(serialize_transfer_job(job, false, false, &err)));
+                        send_transfer_event(job, Outcome::Failed(err.clone()), tx);
+                        send_raw(fs::new_error(id, err, file_num), tx);
+                    }
+                    None => {
+                        send_raw(fs::new_transfer_done(job), tx);
+                        tx_log.map(|tx| tx.send(serialize_transfer_job(job, true, false, "")));
+                        send_transfer_event(job, Outcome::Done, tx);
+                    }
+                }
                 fs::remove_job(id, write_jobs);
             }
         }
         ipc::FS::WriteError { id, file_num, err } => {
             if let Some(job) = fs::get_job(id, write_jobs) {
                 tx_log.map(|tx| tx.send(serialize_transfer_job(job, false, false, &err)));
+                send_transfer_event(job, Outcome::Failed(err.clone()), tx);
                 send_raw(fs::new_error(job.id(), err, file_num), tx);
                 fs::remove_job(job.id(), write_jobs);
             }
@@ -820,6 +1131,8 @@ async fn handle_fs(
             file_num,
             data,
             compressed,
+            copy_blks,
+            checksum,
         } => {
             if let Some(job) = fs::get_job(id, write_jobs) {
                 if let Err(err) = job
@@ -828,11 +1141,15 @@ async fn handle_fs(
                         file_num,
                         data,
                         compressed,
+                        copy_blks,
+                        checksum,
                         ..Default::default()
                     })
                     .await
                 {
                     send_raw(fs::new_error(id, err, file_num), &tx);
+                } else if let Some(msg_out) = job.verification_reply().await {
+                    send_raw(msg_out, &tx);
                 }
             }
         }
@@ -842,12 +1159,18 @@ async fn handle_fs(
             file_size,
             last_modified,
             is_upload,
+            delta,
         } => {
             if let Some(job) = fs::get_job(id, write_jobs) {
+                // the digest of the corrupted file sent again, which is already confirmed
+                if job.is_refetching() {
+                    return;
+                }
                 let mut req = FileTransferSendConfirmRequest {
                     id,
                     file_num,
                     union: Some(file_transfer_send_confirm_request::Union::OffsetBlk(0)),
+                    verify: job.verify(),
                     ..Default::default()
                 };
                 let digest = FileTransferDigest {
@@ -868,15 +1191,51 @@ async fn handle_fs(
                                     send_raw(msg_out, &tx);
                                 }
                                 DigestCheckResult::NeedConfirm(mut digest) => {
-                                    // upload to server, but server has the same file, request
-                                    digest.is_upload = is_upload;
-                                    let mut msg_out = Message::new();
-                                    let mut fr = FileResponse::new();
-                                    fr.set_digest(digest);
-                                    msg_out.set_file_response(fr);
-                                    send_raw(msg_out, &tx);
+                                    let resolution = job.resolve_conflict(
+                                        file_num,
+                                        last_modified,
+                                        digest.last_modified,
+                                    );
+                                    // the client sends the blocks changed if it is overwritten
+                                    let mut checksums = None;
+                                    if delta
+                                        && matches!(
+                                            resolution,
+                                            Resolution::Ask | Resolution::Overwrite
+                                        )
+                                    {
+                                        checksums = read_checksums(&path, file_size, false).await;
+                                        if let Some(checksums) = checksums.as_ref() {
+                                            job.set_delta(file_num, checksums.clone());
+                                        }
+                                    }
+                                    if resolution == Resolution::Ask {
+                                        // upload to server, but server has the same file, request
+                                        digest.is_upload = is_upload;
+                                        digest.verify = job.verify();
+                                        digest.resolves_conflict = true;
+                                        digest.checksums = checksums.into();
+                                        let mut msg_out = Message::new();
+                                        let mut fr = FileResponse::new();
+                                        fr.set_digest(digest);
+                                        msg_out.set_file_response(fr);
+                                        send_raw(msg_out, &tx);
+                                    } else {
+                                        confirm_conflict(job, file_num, resolution, checksums, &tx);
+                                    }
                                 }
                                 DigestCheckResult::NoSuchFile => {
+                                    // continue the interrupted transfer
+                                    let partial = fs::get_partial_download(&path);
+                                    if let Some(partial) = partial.filter(|_| delta) {
+                                        let partial = get_string(&partial);
+                                        if let Some(checksums) =
+                                            read_checksums(&partial, file_size, true).await
+                                        {
+                                            job.set_delta(file_num, checksums.clone());
+                                            req.set_checksums(checksums);
+                                        }
+                                    }
                                     let msg_out = new_send_confirm(req);
                                     send_raw(msg_out, &tx);
                                 }
@@ -889,13 +1248,94 @@ async fn handle_fs(
                 }
             }
         }
+        ipc::FS::ResolveConflict {
+            id,
+            file_num,
+            policy,
+            remember,
+        } => {
+            use hbb_common::protobuf::Enum;
+            if let Some(job) = fs::get_job(id, write_jobs) {
+                let policy = FileConflictPolicy::from_i32(policy).unwrap_or_default();
+                let resolution = job.answer_conflict(file_num, policy, remember);
+                // the checksums are sent with the digest
+                confirm_conflict(job, file_num, resolution, None, tx);
+            }
+        }
         ipc::FS::Rename { id, path, new_name } => {
             rename_file(path, new_name, id, tx).await;
         }
+        ipc::FS::Search {
+            id,
+            path,
+            pattern,
+            include_hidden,
+            max_depth,
+            timeout_secs,
+        } => {
+            // the finished searches have dropped their flags
+            searches.retain(|_, cancelled| Arc::strong_count(cancelled) > 1);
+            let cancelled = Arc::new(AtomicBool::new(false));
+            searches.insert(id, cancelled.clone());
+            let limits = fs::search::Limits::new(max_depth, timeout_secs);
+            search_files(
+                id,
+                path,
+                pattern,
+                include_hidden,
+                limits,
+                cancelled,
+                tx.clone(),
+            );
+        }
         _ => {}
     }
 }
 
+// The checksums of the copy of a file on this side, none if it can not be read.
+#[cfg(not(any(target_os = "ios")))]
+async fn read_checksums(path: &str, file_size: u64, partial: bool) -> Option<FileBlockChecksums> {
+    let path = fs::get_path(path);
+    match spawn_blocking(move || fs::delta::checksums(&path, file_size, partial)).await {
+        Ok(Ok(checksums)) => Some(checksums),
+        Ok(Err(err)) => {
+            log::error!("failed to read the checksums: {}", err);
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+// Confirms the file written by the resolution of its conflict with the file there, the checksums
+// are of the file overwritten.
+#[cfg(not(any(target_os = "ios")))]
+fn confirm_conflict(
+    job: &mut fs::TransferJob,
+    file_num: i32,
+    resolution: Resolution,
+    checksums: Option<FileBlockChecksums>,
+    tx: &UnboundedSender<Data>,
+) {
+    let mut req = FileTransferSendConfirmRequest {
+        id: job.id(),
+        file_num,
+        verify: job.verify(),
+        ..Default::default()
+    };
+    match resolution {
+        Resolution::Overwrite => match checksums {
+            Some(checksums) => req.set_checksums(checksums),
+            None => req.set_offset_blk(0),
+        },
+        Resolution::Rename => match job.rename_conflicting(file_num) {
+            Some(checksums) => req.set_checksums(checksums),
+            None => req.set_offset_blk(0),
+        },
+        Resolution::Skip | Resolution::Ask => req.set_skip(true),
+    }
+    send_raw(new_send_confirm(req), tx);
+}
+
 #[cfg(not(any(target_os = "ios")))]
 async fn read_dir(dir: &str, include_hidden: bool, tx: &UnboundedSender<Data>) {
     let path = {
@@ -914,6 +1354,49 @@ async fn read_dir(dir: &str, include_hidden: bool, tx: &UnboundedSender<Data>) {
     }
 }
 
+// Streams the entries found to the peer until the search ends, it goes on in the background.
+#[cfg(not(any(target_os = "ios")))]
+fn search_files(
+    id: i32,
+    dir: String,
+    pattern: String,
+    include_hidden: bool,
+    limits: fs::search::Limits,
+    cancelled: Arc<AtomicBool>,
+    tx: UnboundedSender<Data>,
+) {
+    let path = if dir.is_empty() {
+        Config::get_home()
+    } else {
+        fs::get_path(&dir)
+    };
+    let pattern = fs::search::Pattern::new(&pattern);
+    spawn_blocking(move || {
+        let complete = pattern.is_empty()
+            || fs::search::search(
+                &path,
+                &pattern,
+                include_hidden,
+                limits,
+                &cancelled,
+                |entries| {
+                    match fs::new_search_result(id, entries, false, false).write_to_bytes() {
+                        Ok(bytes) => {
+                            // the connection is gone
+                            if tx.send(Data::RawMessage(bytes)).is_err() {
+                                cancelled.store(true, Ordering::Relaxed);
+                            }
+                        }
+                        err => allow_err!(err),
+                    }
+                },
+            );
+        if !cancelled.load(Ordering::Relaxed) {
+            send_raw(fs::new_search_result(id, vec![], true, !complete), &tx);
+        }
+    });
+}
+
 #[cfg(not(any(target_os = "ios")))]
 async fn handle_result<F: std::fmt::Display, S: std::fmt::Display>(
     res: std::result::Result<std::result::Result<(), F>, S>,
@@ -945,6 +1428,20 @@ async fn remove_file(path: String, id: i32, file_num: i32, tx: &UnboundedSender<
     .await;
 }
 
+#[cfg(not(any(target_os = "ios")))]
+async fn trash_file(
+    path: String,
+    id: i32,
+    file_num: i32,
+    trashed: &mut Vec<(i32, fs::trash::Trashed)>,
+    tx: &UnboundedSender<Data>,
+) {
+    let res = spawn_blocking(move || fs::trash::trash(&fs::get_path(&path)))
+        .await
+        .map(|res| res.map(|t| trashed.extend(t.map(|t| (id, t)))));
+    handle_result(res, id, file_num, tx).await;
+}
+
 #[cfg(not(any(target_os = "ios")))]
 async fn create_dir(path: String, id: i32, tx: &UnboundedSender<Data>) {
     handle_result(
@@ -987,6 +1484,25 @@ async fn remove_dir(path: String, id: i32, recursive: bool, tx: &UnboundedSender
 }
 
 #[cfg(not(any(target_os = "ios")))]
+async fn trash_dir(
+    path: String,
+    id: i32,
+    trashed: &mut Vec<(i32, fs::trash::Trashed)>,
+    tx: &UnboundedSender<Data>,
+) {
+    let res = spawn_blocking(move || fs::trash::trash_dir_if_empty(&fs::get_path(&path)))
+        .await
+        .map(|res| res.map(|t| trashed.extend(t.map(|t| (id, t)))));
+    handle_result(res, id, 0, tx).await;
+}
+
+#[cfg(not(any(target_os = "ios")))]
+// the connection records it with the peer
+fn send_transfer_event(job: &fs::TransferJob, result: Outcome, tx: &UnboundedSender<Data>) {
+    let event = audit::TransferEvent::of(job, audit::Direction::Receive, result);
+    allow_err!(tx.send(Data::FileTransferEvent(event)));
+}
+
 fn send_raw(msg: Message, tx: &UnboundedSender<Data>) {
     match msg.write_to_bytes() {
         Ok(bytes) => {
@@ -1039,6 +1555,27 @@ pub fn handle_incoming_voice_call(id: i32, accept: bool) {
     };
 }
 
+#[cfg(any(target_os = "android", target_os = "ios", feature = "flutter"))]
+#[inline]
+pub fn handle_input_control_request(id: i32, grant: bool) {
+    if let Some(client) = CLIENTS.read().unwrap().get(&id) {
+        #[cfg(not(any(target_os = "ios")))]
+        allow_err!(client.tx.send(Data::InputControlResponse(grant)));
+    };
+}
+
+// the user of this side takes the strokes of the peer back
+#[cfg(any(target_os = "android", target_os = "ios", feature = "flutter"))]
+pub fn clear_annotations(id: i32) {
+    #[cfg(not(any(target_os = "ios")))]
+    if crate::annotation::clear(id) {
+        send_annotations();
+        if let Some(client) = CLIENTS.write().unwrap().get_mut(&id) {
+            client.annotations = 0;
+        }
+    }
+}
+
 #[cfg(any(target_os = "android", target_os = "ios", feature = "flutter"))]
 #[inline]
 pub fn close_voice_call(id: i32) {
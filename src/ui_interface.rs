@@ -315,52 +315,27 @@ pub fn test_if_valid_server(host: String, test_with_proxy: bool) -> String {
 #[cfg(feature = "flutter")]
 #[cfg(not(any(target_os = "android", target_os = "ios")))]
 pub fn get_sound_inputs() -> Vec<String> {
-    let mut a = Vec::new();
+    // can not call below in UI thread, because conflict with sciter sound com initialization
     #[cfg(not(target_os = "linux"))]
-    {
-        fn get_sound_inputs_() -> Vec<String> {
-            let mut out = Vec::new();
-            use cpal::traits::{DeviceTrait, HostTrait};
-            let host = cpal::default_host();
-            if let Ok(devices) = host.devices() {
-                for device in devices {
-                    if device.default_input_config().is_err() {
-                        continue;
-                    }
-                    if let Ok(name) = device.name() {
-                        out.push(name);
-                    }
-                }
-            }
-            out
-        }
-
-        let inputs = Arc::new(Mutex::new(Vec::new()));
-        let cloned = inputs.clone();
-        // can not call below in UI thread, because conflict with sciter sound com initialization
-        std::thread::spawn(move || *cloned.lock().unwrap() = get_sound_inputs_())
-            .join()
-            .ok();
-        for name in inputs.lock().unwrap().drain(..) {
-            a.push(name);
-        }
-    }
+    return std::thread::spawn(crate::audio_service::get_input_devices)
+        .join()
+        .unwrap_or_default();
     #[cfg(target_os = "linux")]
-    {
-        let inputs: Vec<String> = crate::platform::linux::get_pa_sources()
-            .drain(..)
-            .map(|x| x.1)
-            .collect();
-
-        for name in inputs {
-            a.push(name);
-        }
-    }
-    a
+    return crate::audio_service::get_input_devices();
 }
 
 #[inline]
-pub fn set_options(m: HashMap<String, String>) {
+pub fn set_options(mut m: HashMap<String, String>) {
+    if let Some(value) = m.get(OPTION_DLP_RULES).filter(|v| !v.is_empty()) {
+        if let Err(err) = hbb_common::dlp::check_rules(value) {
+            log::error!("The dlp rules are not saved: {}", err);
+            // the others are saved, with the rules as they are
+            match get_option(OPTION_DLP_RULES) {
+                old if old.is_empty() => m.remove(OPTION_DLP_RULES),
+                old => m.insert(OPTION_DLP_RULES.to_owned(), old),
+            };
+        }
+    }
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     {
         *OPTIONS.lock().unwrap() = m.clone();
@@ -372,6 +347,12 @@ pub fn set_options(m: HashMap<String, String>) {
 
 #[inline]
 pub fn set_option(key: String, value: String) {
+    if key == OPTION_DLP_RULES && !value.is_empty() {
+        if let Err(err) = hbb_common::dlp::check_rules(&value) {
+            log::error!("The dlp rules are not saved: {}", err);
+            return;
+        }
+    }
     if &key == "stop-service" {
         #[cfg(target_os = "macos")]
         {
@@ -434,7 +415,7 @@ pub fn get_socks() -> Vec<String> {
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     let s = ipc::get_socks();
     #[cfg(target_os = "android")]
-    let s = Config::get_socks();
+    let s = Config::get_default_socks();
     #[cfg(target_os = "ios")]
     let s: Option<config::Socks5Server> = None;
     match s {
@@ -471,6 +452,35 @@ pub fn set_socks(proxy: String, username: String, password: String) {
     }
 }
 
+#[inline]
+pub fn get_network_profiles() -> String {
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    let profiles = ipc::get_network_profiles();
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    let profiles = Config::get_network_profiles();
+    serde_json::to_string(&profiles).unwrap_or_default()
+}
+
+#[inline]
+pub fn set_network_profiles(profiles: String) {
+    let profiles: Vec<config::NetworkProfile> = match serde_json::from_str(&profiles) {
+        Ok(profiles) => profiles,
+        Err(err) => {
+            log::error!("Invalid network profiles: {}", err);
+            return;
+        }
+    };
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    ipc::set_network_profiles(profiles).ok();
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    {
+        Config::set_network_profiles(profiles);
+        crate::common::test_nat_type();
+        crate::RendezvousMediator::restart();
+        log::info!("network profiles updated");
+    }
+}
+
 #[inline]
 pub fn get_proxy_status() -> bool {
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
@@ -684,6 +694,41 @@ pub fn create_shortcut(_id: String) {
     crate::platform::windows::create_shortcut(&_id).ok();
 }
 
+/// Save a screenshot of the peer display to the video save directory, returns the error if any.
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+#[tokio::main(flavor = "current_thread")]
+pub async fn take_screenshot(id: String, display: i32) -> String {
+    let token = LocalConfig::get_option("access_token");
+    let key = crate::get_key(false).await;
+    match crate::client::screenshot::take_screenshot(&id, display, "", &key, &token).await {
+        Ok(png) => {
+            let path = std::path::Path::new(&video_save_directory(false)).join(format!(
+                "{}_display{}_{}.png",
+                id,
+                display,
+                chrono::Local::now().format("%Y%m%d%H%M%S")
+            ));
+            match std::fs::write(&path, png) {
+                Ok(_) => "".to_owned(),
+                Err(err) => err.to_string(),
+            }
+        }
+        Err(err) => err.to_string(),
+    }
+}
+
+/// Wake the peer through another one on its lan, returns the error if any.
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+#[tokio::main(flavor = "current_thread")]
+pub async fn wake_through_peer(id: String, relay_id: String) -> String {
+    let token = LocalConfig::get_option("access_token");
+    let key = crate::get_key(false).await;
+    match crate::client::wake_on_lan::wake(&id, &relay_id, "", &key, &token).await {
+        Ok(_) => "".to_owned(),
+        Err(err) => err.to_string(),
+    }
+}
+
 #[cfg(any(target_os = "android", target_os = "ios", feature = "flutter"))]
 #[inline]
 pub fn discover() {
@@ -727,6 +772,8 @@ pub fn get_lan_peers() -> Vec<HashMap<&'static str, String>> {
                 ("username", peer.username.clone()),
                 ("hostname", peer.hostname.clone()),
                 ("platform", peer.platform.clone()),
+                ("os", peer.os.clone()),
+                ("version", peer.version.clone()),
             ])
         })
         .collect()
@@ -1103,6 +1150,22 @@ pub fn get_fingerprint() -> String {
     return ipc::get_fingerprint();
 }
 
+/// The fingerprint of the keypair before the last rotation, while it is still signed with.
+pub fn get_old_fingerprint() -> String {
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    return "".to_owned();
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    return ipc::get_old_fingerprint();
+}
+
+pub fn rotate_key_pair(grace_days: u32) {
+    // the public key is the uuid of the device on mobile
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    let _ = grace_days;
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    allow_err!(ipc::rotate_key_pair(grace_days));
+}
+
 #[inline]
 pub fn get_login_device_info() -> LoginDeviceInfo {
     LoginDeviceInfo {
@@ -1474,6 +1537,14 @@ pub fn set_unlock_pin(pin: String) -> String {
     }
 }
 
+#[cfg(feature = "flutter")]
+pub fn verify_unlock_pin(pin: String) -> bool {
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    return false;
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    return password_security::verify_secret(pin.trim(), &ipc::get_unlock_pin());
+}
+
 #[cfg(feature = "flutter")]
 pub fn get_trusted_devices() -> String {
     #[cfg(any(target_os = "android", target_os = "ios"))]
@@ -1499,6 +1570,96 @@ pub fn clear_trusted_devices() {
     ipc::clear_trusted_devices();
 }
 
+#[cfg(feature = "flutter")]
+pub fn get_permission_profiles() -> String {
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    return serde_json::to_string(&Config::get_permission_profiles()).unwrap_or_default();
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    return ipc::get_permission_profiles();
+}
+
+#[cfg(feature = "flutter")]
+pub fn remove_permission_profiles(json: &str) {
+    let ids = serde_json::from_str::<Vec<String>>(json).unwrap_or_default();
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    Config::remove_permission_profiles(&ids);
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    ipc::remove_permission_profiles(ids);
+}
+
+#[cfg(feature = "flutter")]
+pub fn get_one_time_passwords() -> String {
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    let list = Config::get_one_time_passwords();
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    let list = match ipc::one_time_passwords(ipc::DataOneTimePasswords::Query) {
+        Ok(ipc::DataOneTimePasswords::List(list)) => list,
+        _ => vec![],
+    };
+    serde_json::to_string(&list).unwrap_or_default()
+}
+
+// the json of the codes generated, which are not kept
+#[cfg(feature = "flutter")]
+pub fn generate_one_time_passwords(
+    count: usize,
+    minutes: u32,
+    scope: String,
+    label: String,
+) -> String {
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    let codes = Config::generate_one_time_passwords(count, minutes, &scope, &label);
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    let codes = match ipc::one_time_passwords(ipc::DataOneTimePasswords::Generate {
+        count,
+        minutes,
+        scope,
+        label,
+    }) {
+        Ok(ipc::DataOneTimePasswords::Generated(codes)) => codes,
+        Ok(_) => vec![],
+        Err(err) => {
+            log::error!("Failed to generate the one-time passwords: {}", err);
+            vec![]
+        }
+    };
+    serde_json::to_string(&codes).unwrap_or_default()
+}
+
+#[cfg(feature = "flutter")]
+pub fn remove_one_time_passwords(json: &str) {
+    let ids = serde_json::from_str::<Vec<String>>(json).unwrap_or_default();
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    Config::remove_one_time_passwords(&ids);
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    allow_err!(ipc::one_time_passwords(ipc::DataOneTimePasswords::Remove(
+        ids
+    )));
+}
+
+#[cfg(feature = "flutter")]
+pub fn get_trusted_keys() -> String {
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    let list = Config::get_trusted_keys();
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    let list = match ipc::trusted_keys(ipc::DataTrustedKeys::Query) {
+        Ok(ipc::DataTrustedKeys::List(list)) => list,
+        _ => vec![],
+    };
+    serde_json::to_string(&list).unwrap_or_default()
+}
+
+#[cfg(feature = "flutter")]
+pub fn remove_trusted_keys(json: &str) {
+    let fingerprints = serde_json::from_str::<Vec<String>>(json).unwrap_or_default();
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    Config::remove_trusted_keys(&fingerprints);
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    allow_err!(ipc::trusted_keys(ipc::DataTrustedKeys::Remove(
+        fingerprints
+    )));
+}
+
 #[cfg(feature = "flutter")]
 pub fn max_encrypt_len() -> usize {
     hbb_common::config::ENCRYPT_MAX_LEN
@@ -12,7 +12,7 @@ use std::{
     ops::{Deref, DerefMut},
     str::FromStr,
     sync::{Arc, Mutex, RwLock},
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 use uuid::Uuid;
 
@@ -20,7 +20,7 @@ use uuid::Uuid;
 use hbb_common::fs;
 use hbb_common::{
     allow_err,
-    config::{Config, LocalConfig, PeerConfig},
+    config::{keys, Config, LocalConfig, PeerConfig},
     get_version_number, log,
     message_proto::*,
     rendezvous_proto::ConnType,
@@ -29,7 +29,7 @@ use hbb_common::{
         sync::mpsc,
         time::{Duration as TokioDuration, Instant},
     },
-    Stream,
+    ResultType, Stream,
 };
 
 use crate::client::io_loop::Remote;
@@ -38,12 +38,20 @@ use crate::client::{
     input_os_password, send_mouse, send_pointer_device_event, start_video_audio_threads,
     FileManager, Key, LoginConfigHandler, QualityStatus, KEY_MAP,
 };
+use crate::client::{input_macro, sync_job};
 #[cfg(not(any(target_os = "android", target_os = "ios")))]
 use crate::common::GrabState;
 use crate::keyboard;
 use crate::{client::Data, client::Interface};
 
 const CHANGE_RESOLUTION_VALID_TIMEOUT_SECS: u64 = 15;
+// in milliseconds, some consoles drop the keys typed too fast
+const DEFAULT_CLIPBOARD_TYPING_DELAY: u64 = 20;
+// how often the online state of a peer restarting is queried
+const RESTART_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+// the peer is reconnected to once online after it, even if never seen offline
+const RESTART_MIN_WAIT: Duration = Duration::from_secs(60);
+const RESTART_WAIT_TIMEOUT: Duration = Duration::from_secs(600);
 
 #[derive(Clone, Default)]
 pub struct Session<T: InvokeUiSession> {
@@ -58,6 +66,8 @@ pub struct Session<T: InvokeUiSession> {
     pub server_clipboard_enabled: Arc<RwLock<bool>>,
     pub last_change_display: Arc<Mutex<ChangeDisplayRecord>>,
     pub connection_round_state: Arc<Mutex<ConnectionRoundState>>,
+    pub macro_recorder: Arc<Mutex<Option<input_macro::Recorder>>>,
+    pub macro_player: Arc<Mutex<Option<input_macro::Player>>>,
 }
 
 #[derive(Clone)]
@@ -328,6 +338,17 @@ impl<T: InvokeUiSession> Session<T> {
         if name == hbb_common::config::keys::OPTION_ENABLE_FILE_COPY_PASTE {
             self.send(Data::ToggleClipboardFile);
         }
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        if name == hbb_common::config::keys::OPTION_FORWARD_GAMEPAD {
+            self.send(Data::ToggleGamepad);
+        }
+        #[cfg(not(target_os = "ios"))]
+        if name == hbb_common::config::keys::OPTION_DENOISE_AUDIO {
+            self.send(Data::ToggleDenoise);
+        }
+        if name == "disable-audio" {
+            self.send(Data::ToggleAudio);
+        }
         if let Some(msg) = msg {
             self.send(Data::Message(msg));
         }
@@ -402,6 +423,46 @@ impl<T: InvokeUiSession> Session<T> {
         self.lc.read().unwrap().record
     }
 
+    /// Captures the audio input of the peer, empty for the one of its settings.
+    pub fn set_remote_audio_input(&self, device: String) {
+        self.set_option(keys::OPTION_REMOTE_AUDIO_INPUT.to_owned(), device.clone());
+        let mut misc = Misc::new();
+        misc.set_audio_input(device);
+        let mut msg = Message::new();
+        msg.set_misc(misc);
+        self.send(Data::Message(msg));
+    }
+
+    /// Chooses the audio input saved for the peer again, if the peer still has it.
+    pub fn restore_remote_audio_input(&self, platform_additions: &str) {
+        let device = self.get_option(keys::OPTION_REMOTE_AUDIO_INPUT.to_owned());
+        if device.is_empty() {
+            return;
+        }
+        let found = serde_json::from_str::<serde_json::Value>(platform_additions)
+            .ok()
+            .and_then(|a| {
+                let inputs = a["audio_inputs"].as_array()?;
+                Some(inputs.iter().any(|d| *d == device.as_str()))
+            })
+            .unwrap_or(false);
+        if found {
+            self.set_remote_audio_input(device);
+        }
+    }
+
+    /// The peer replies with its master volume to the controls.
+    pub fn control_remote_volume(&self, control: volume_control::Union) {
+        let mut misc = Misc::new();
+        misc.set_volume_control(VolumeControl {
+            union: Some(control),
+            ..Default::default()
+        });
+        let mut msg = Message::new();
+        msg.set_misc(misc);
+        self.send(Data::Message(msg));
+    }
+
     pub fn save_custom_image_quality(&self, custom_image_quality: i32) {
         let msg = self
             .lc
@@ -431,6 +492,105 @@ impl<T: InvokeUiSession> Session<T> {
         self.send(Data::Message(msg));
     }
 
+    pub fn start_macro_recording(&self, name: String) -> String {
+        match input_macro::Recorder::new(&name) {
+            Ok(recorder) => {
+                *self.macro_recorder.lock().unwrap() = Some(recorder);
+                "".to_owned()
+            }
+            Err(e) => e.to_string(),
+        }
+    }
+
+    pub fn stop_macro_recording(&self) -> String {
+        let Some(recorder) = self.macro_recorder.lock().unwrap().take() else {
+            return "".to_owned();
+        };
+        match recorder.save(&self.get_id()) {
+            Ok(steps) => {
+                log::info!("macro recorded, {} steps", steps);
+                "".to_owned()
+            }
+            Err(e) => e.to_string(),
+        }
+    }
+
+    pub fn is_recording_macro(&self) -> bool {
+        self.macro_recorder.lock().unwrap().is_some()
+    }
+
+    pub fn play_macro(&self, name: String) -> String {
+        let Some(sender) = self.sender.read().unwrap().clone() else {
+            return "".to_owned();
+        };
+        match input_macro::Macro::load(&self.get_id(), &name).and_then(|m| m.messages()) {
+            Ok(messages) => {
+                *self.macro_player.lock().unwrap() =
+                    Some(input_macro::Player::start(messages, sender, false));
+                "".to_owned()
+            }
+            Err(e) => e.to_string(),
+        }
+    }
+
+    pub fn stop_macro(&self) {
+        self.macro_player.lock().unwrap().take();
+    }
+
+    pub fn is_playing_macro(&self) -> bool {
+        self.macro_player
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|p| p.is_playing())
+            .unwrap_or(false)
+    }
+
+    pub fn get_macros(&self) -> Vec<String> {
+        input_macro::list(&self.get_id())
+    }
+
+    pub fn remove_macro(&self, name: String) {
+        allow_err!(input_macro::remove(&self.get_id(), &name));
+    }
+
+    pub fn get_sync_jobs(&self) -> Vec<sync_job::SyncJob> {
+        sync_job::list(&self.get_id())
+    }
+
+    pub fn save_sync_job(&self, job: String) -> String {
+        let job = match serde_json::from_str(&job) {
+            Ok(job) => job,
+            Err(e) => return e.to_string(),
+        };
+        match sync_job::save(&self.get_id(), job) {
+            Ok(()) => "".to_owned(),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    pub fn remove_sync_job(&self, name: String) {
+        allow_err!(sync_job::remove(&self.get_id(), &name));
+    }
+
+    pub fn log_sync_job(&self, name: String, text: String) {
+        allow_err!(sync_job::log(&self.get_id(), &name, text));
+    }
+
+    pub fn get_due_sync_jobs(&self) -> Vec<String> {
+        sync_job::due(&self.get_id())
+    }
+
+    pub fn set_bandwidth_limit(&self, kbps: i32) {
+        let msg = self.lc.write().unwrap().set_bandwidth_limit(kbps);
+        self.send(Data::Message(msg));
+    }
+
+    pub fn set_audio_quality(&self, value: String) {
+        let msg = self.lc.write().unwrap().set_audio_quality(value);
+        self.send(Data::Message(msg));
+    }
+
     pub fn get_remember(&self) -> bool {
         self.lc.read().unwrap().remember
     }
@@ -444,13 +604,7 @@ impl<T: InvokeUiSession> Session<T> {
         remember: bool,
         is_upload: bool,
     ) -> bool {
-        self.send(Data::SetConfirmOverrideFile((
-            job_id,
-            file_num,
-            is_override,
-            remember,
-            is_upload,
-        )));
+        self.set_confirm_override_file(job_id, file_num, is_override, remember, is_upload);
         true
     }
 
@@ -487,12 +641,77 @@ impl<T: InvokeUiSession> Session<T> {
     }
 
     pub fn restart_remote_device(&self) {
+        self.restart_remote_device_with(false, false);
+    }
+
+    /// Restart the peer, into the safe mode with networking if `safe_mode` on Windows, and
+    /// reconnect once it is back online if `reconnect`.
+    pub fn restart_remote_device_with(&self, safe_mode: bool, reconnect: bool) {
         let mut lc = self.lc.write().unwrap();
         lc.restarting_remote_device = true;
-        let msg = lc.restart_remote_device();
+        lc.reconnect_after_restart = reconnect;
+        let msg = lc.restart_remote_device(safe_mode);
         self.send(Data::Message(msg));
     }
 
+    /// The peer is restarting: wait for it to be back online and reconnect, unless the session
+    /// is closed or reconnected meanwhile.
+    pub fn reconnect_after_restart(&self) {
+        // the close of the session comes here once the connection is gone
+        let (sender, receiver) = mpsc::unbounded_channel::<Data>();
+        *self.sender.write().unwrap() = Some(sender);
+        let round = self.connection_round_state.lock().unwrap().round;
+        let session = self.clone();
+        std::thread::spawn(move || session.wait_restart(round, receiver));
+    }
+
+    #[tokio::main(flavor = "current_thread")]
+    async fn wait_restart(&self, round: u32, mut receiver: mpsc::UnboundedReceiver<Data>) {
+        let id = self.get_id();
+        let start = Instant::now();
+        let mut offline = false;
+        let mut timer = crate::rustdesk_interval(tokio::time::interval(RESTART_CHECK_INTERVAL));
+        loop {
+            tokio::select! {
+                d = receiver.recv() => {
+                    if matches!(d, Some(Data::Close) | None) {
+                        log::info!("Stop waiting for {} to restart", id);
+                        return;
+                    }
+                }
+                _ = timer.tick() => {
+                    if self.connection_round_state.lock().unwrap().is_round_gt(round) {
+                        return;
+                    }
+                    if start.elapsed() > RESTART_WAIT_TIMEOUT {
+                        self.msgbox(
+                            "error",
+                            "Restarting remote device",
+                            "remote_restart_timeout_tip",
+                            "",
+                        );
+                        return;
+                    }
+                    let mut online = None;
+                    crate::client::peer_online::query_online_states(vec![id.clone()], |onlines, _| {
+                        online = Some(!onlines.is_empty())
+                    })
+                    .await;
+                    match online {
+                        Some(false) => offline = true,
+                        // the online state lags behind, the peer going down is seen a while later
+                        Some(true) if offline || start.elapsed() > RESTART_MIN_WAIT => {
+                            log::info!("{} is back online, reconnect", id);
+                            self.reconnect(false);
+                            return;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
     #[cfg(all(feature = "flutter", feature = "plugin_framework"))]
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     pub fn send_plugin_request(&self, request: PluginRequest) {
@@ -709,11 +928,26 @@ impl<T: InvokeUiSession> Session<T> {
     }
 
     pub fn send_chat(&self, text: String) {
-        let mut misc = Misc::new();
-        misc.set_chat_message(ChatMessage {
+        self.send_chat_with_receipt(0, text);
+    }
+
+    /// The peer answers with the receipt of `id` if not 0.
+    pub fn send_chat_with_receipt(&self, id: u32, text: String) {
+        self.send_chat_message(ChatMessage {
             text,
+            id,
             ..Default::default()
         });
+    }
+
+    pub fn send_chat_attachment(&self, id: u32, path: String) -> ResultType<()> {
+        self.send_chat_message(crate::chat::attach(id, &path)?);
+        Ok(())
+    }
+
+    fn send_chat_message(&self, chat: ChatMessage) {
+        let mut misc = Misc::new();
+        misc.set_chat_message(chat);
         let mut msg_out = Message::new();
         msg_out.set_misc(misc);
         self.send(Data::Message(msg_out));
@@ -794,6 +1028,41 @@ impl<T: InvokeUiSession> Session<T> {
         self.send(Data::Message(msg_out));
     }
 
+    /// Types the text of the clipboard key by key, which the peer may allow without the other keys.
+    ///
+    /// It is played as a macro, so stopped the same way.
+    pub fn type_clipboard(&self, value: &str) {
+        let Some(sender) = self.sender.read().unwrap().clone() else {
+            return;
+        };
+        let delay = LocalConfig::get_option(keys::OPTION_CLIPBOARD_TYPING_DELAY)
+            .parse()
+            .unwrap_or(DEFAULT_CLIPBOARD_TYPING_DELAY);
+        let messages = input_macro::keystrokes(value, Duration::from_millis(delay));
+        *self.macro_player.lock().unwrap() =
+            Some(input_macro::Player::start(messages, sender, false));
+    }
+
+    /// The composition or the commit string of the input method.
+    pub fn input_ime(&self, text: String, commit: bool) {
+        if !self.lc.read().unwrap().is_ime_input_supported() {
+            // the peers without it only get the typed text
+            if commit && !text.is_empty() {
+                self.input_string(&text);
+            }
+            return;
+        }
+        let mut ime = ImeInput::new();
+        if commit {
+            ime.set_commit(text);
+        } else {
+            ime.set_composition(text);
+        }
+        let mut msg_out = Message::new();
+        msg_out.set_ime_input(ime);
+        self.send(Data::Message(msg_out));
+    }
+
     #[cfg(any(target_os = "ios"))]
     pub fn handle_flutter_raw_key_event(
         &self,
@@ -904,6 +1173,12 @@ impl<T: InvokeUiSession> Session<T> {
             _ => None,
         };
         let Some(ctrl_key) = ctrl_key else { return };
+        #[cfg(not(any(target_os = "android", target_os = "ios")))]
+        if ctrl_key != ControlKey::Power
+            && keyboard::shortcuts::is_enabled(keyboard::shortcuts::MEDIA)
+        {
+            return;
+        }
         let mut key_event = KeyEvent {
             mode: KeyboardMode::Translate.into(),
             down: down_or_up,
@@ -1077,6 +1352,30 @@ impl<T: InvokeUiSession> Session<T> {
         send_pointer_device_event(evt, alt, ctrl, shift, command, self);
     }
 
+    pub fn send_touch_points(
+        &self,
+        points: Vec<TouchPoint>,
+        alt: bool,
+        ctrl: bool,
+        shift: bool,
+        command: bool,
+    ) {
+        let mut touch_evt = TouchEvent::new();
+        touch_evt.set_points(TouchPoints {
+            points,
+            ..Default::default()
+        });
+        let mut evt = PointerDeviceEvent::new();
+        evt.set_touch_event(touch_evt);
+        send_pointer_device_event(evt, alt, ctrl, shift, command, self);
+    }
+
+    pub fn send_pen_event(&self, evt: PenEvent, alt: bool, ctrl: bool, shift: bool, command: bool) {
+        let mut pde = PointerDeviceEvent::new();
+        pde.set_pen_event(evt);
+        send_pointer_device_event(pde, alt, ctrl, shift, command, self);
+    }
+
     #[inline]
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     fn is_scroll_reverse_mode(&self) -> bool {
@@ -1269,6 +1568,15 @@ impl<T: InvokeUiSession> Session<T> {
         self.send(Data::Message(msg_out));
     }
 
+    pub fn send_access_reason(&self, reason: String) {
+        let mut msg_out = Message::new();
+        msg_out.set_access_reason(AccessReason {
+            reason,
+            ..Default::default()
+        });
+        self.send(Data::Message(msg_out));
+    }
+
     pub fn get_enable_trusted_devices(&self) -> bool {
         self.lc.read().unwrap().enable_trusted_devices
     }
@@ -1435,6 +1743,160 @@ impl<T: InvokeUiSession> Session<T> {
         self.send(Data::CloseVoiceCall);
     }
 
+    /// The microphone sent while the button of push-to-talk is held.
+    #[inline]
+    pub fn set_voice_call_talking(&self, talking: bool) {
+        self.send(Data::VoiceCallTalking(talking));
+    }
+
+    /// Ask for the control of the input held by another peer, or give it up.
+    pub fn request_input_control(&self, request: bool) {
+        let mut misc = Misc::new();
+        misc.set_request_input_control(request);
+        let mut msg = Message::new();
+        msg.set_misc(misc);
+        self.send(Data::Message(msg));
+    }
+
+    /// Ask for a ticket to hand the session off to another device.
+    pub fn request_session_handoff(&self) {
+        let mut misc = Misc::new();
+        misc.set_request_session_handoff(true);
+        let mut msg = Message::new();
+        msg.set_misc(misc);
+        self.send(Data::Message(msg));
+    }
+
+    /// Starts or stops the stats of the system of the peer being sent.
+    pub fn watch_system_stats(&self, on: bool) {
+        let mut misc = Misc::new();
+        misc.set_watch_system_stats(on);
+        let mut msg = Message::new();
+        msg.set_misc(misc);
+        self.send(Data::Message(msg));
+    }
+
+    /// Lists the processes of the peer, sorted by `sort` of `ListProcesses`, `limit` of them.
+    pub fn list_processes(&self, sort: i32, ascending: bool, limit: u32) {
+        use hbb_common::protobuf::Enum;
+        let mut misc = Misc::new();
+        misc.set_list_processes(ListProcesses {
+            sort: list_processes::Sort::from_i32(sort)
+                .unwrap_or_default()
+                .into(),
+            ascending,
+            limit,
+            ..Default::default()
+        });
+        let mut msg = Message::new();
+        msg.set_misc(misc);
+        self.send(Data::Message(msg));
+    }
+
+    pub fn kill_process(&self, pid: u32) {
+        let mut misc = Misc::new();
+        misc.set_kill_process(pid);
+        let mut msg = Message::new();
+        msg.set_misc(misc);
+        self.send(Data::Message(msg));
+    }
+
+    pub fn list_services(&self) {
+        let mut misc = Misc::new();
+        misc.set_list_services(true);
+        let mut msg = Message::new();
+        msg.set_misc(misc);
+        self.send(Data::Message(msg));
+    }
+
+    /// Starts, stops or restarts the service of the peer, `action` of `ServiceControl`.
+    pub fn control_service(&self, name: String, action: i32) {
+        use hbb_common::protobuf::Enum;
+        let mut misc = Misc::new();
+        misc.set_service_control(ServiceControl {
+            name,
+            action: service_control::Action::from_i32(action)
+                .unwrap_or_default()
+                .into(),
+            ..Default::default()
+        });
+        let mut msg = Message::new();
+        msg.set_misc(misc);
+        self.send(Data::Message(msg));
+    }
+
+    pub fn list_cameras(&self) {
+        let mut misc = Misc::new();
+        misc.set_list_cameras(true);
+        let mut msg = Message::new();
+        msg.set_misc(misc);
+        self.send(Data::Message(msg));
+    }
+
+    /// Views the camera of the peer, or stops it if `on` is false.
+    pub fn request_camera(&self, on: bool, index: i32, fps: u32, quality: u32, vp9: bool) {
+        let mut misc = Misc::new();
+        misc.set_camera_request(CameraRequest {
+            on,
+            index,
+            fps,
+            quality,
+            vp9,
+            ..Default::default()
+        });
+        let mut msg = Message::new();
+        msg.set_misc(misc);
+        self.send(Data::Message(msg));
+    }
+
+    /// Prints the documents of the virtual printer of the peer on this side while `on`.
+    pub fn remote_printer(&self, on: bool) {
+        self.lc.write().unwrap().remote_printer = on;
+        let mut misc = Misc::new();
+        misc.set_remote_printer(on);
+        let mut msg = Message::new();
+        msg.set_misc(misc);
+        self.send(Data::Message(msg));
+    }
+
+    /// Draws over the screen of the peer, `points` in the coordinates of its screens.
+    pub fn annotate(&self, shape: i32, points: Vec<i32>, color: u32, width: u32) {
+        use hbb_common::protobuf::Enum;
+        let mut annotation = Annotation::new();
+        annotation.set_stroke(AnnotationStroke {
+            shape: annotation_stroke::Shape::from_i32(shape)
+                .unwrap_or_default()
+                .into(),
+            points,
+            color,
+            width,
+            ..Default::default()
+        });
+        self.send_annotation(annotation);
+    }
+
+    /// Takes the last stroke drawn back, or all of them.
+    pub fn undo_annotation(&self, all: bool) {
+        let mut annotation = Annotation::new();
+        if all {
+            annotation.set_clear(true);
+        } else {
+            annotation.set_undo(true);
+        }
+        self.send_annotation(annotation);
+    }
+
+    fn send_annotation(&self, annotation: Annotation) {
+        let mut msg = Message::new();
+        msg.set_annotation(annotation);
+        self.send(Data::Message(msg));
+    }
+
+    /// The scale of the remote display in the view, in physical pixels.
+    pub fn set_view_scale(&self, scale: f64) {
+        self.lc.write().unwrap().view_scale = scale;
+    }
+
     pub fn send_selected_session_id(&self, sid: String) {
         if let Ok(sid) = sid.parse::<u32>() {
             self.lc.write().unwrap().selected_windows_session_id = Some(sid);
@@ -1511,11 +1973,32 @@ pub trait InvokeUiSession: Send + Sync + Clone + 'static + Sized + Default {
     fn close_success(&self);
     fn update_quality_status(&self, qs: QualityStatus);
     fn set_connection_type(&self, is_secured: bool, direct: bool);
+    /// Whether the heartbeats of the peer are missed, before the connection is dropped.
+    fn set_connection_degraded(&self, degraded: bool);
     fn set_fingerprint(&self, fingerprint: String);
     fn job_error(&self, id: i32, err: String, file_num: i32);
-    fn job_done(&self, id: i32, file_num: i32);
+    fn job_done(&self, id: i32, file_num: i32, digests: &str);
     fn clear_all_jobs(&self);
     fn new_message(&self, msg: String);
+    // a file of the peer in the chat, saved at `path`
+    fn new_attachment(&self, path: String, image: bool);
+    // of the message `id` sent, delivered if `error` is empty
+    fn chat_receipt(&self, id: u32, error: String);
+    // the link another device takes the session over with, valid for `timeout` seconds
+    fn session_handoff(&self, link: String, timeout: u32, error: String);
+    // the load of the controlled side, while it is watched
+    fn update_system_stats(&self, stats: crate::client::metrics::HostStats);
+    fn update_process_list(&self, list: &ProcessList);
+    fn process_killed(&self, killed: &ProcessKilled);
+    fn update_service_list(&self, list: &ServiceList);
+    fn service_control_result(&self, res: &ServiceControlResult);
+    fn update_camera_list(&self, list: &CameraList);
+    // a frame of the camera of the peer decoded, in rgba
+    fn on_camera_frame(&self, width: usize, height: usize, rgba: Vec<u8>);
+    fn camera_error(&self, error: String);
+    // a document of the virtual printer of the peer, saved at `path` in pdf
+    fn print_job(&self, title: String, path: String, copies: u32);
+    fn update_broadcast_viewers(&self, viewers: &BroadcastViewers);
     fn update_transfer_list(&self);
     fn load_last_job(&self, cnt: i32, job_json: &str);
     fn update_folder_files(
@@ -1526,6 +2009,8 @@ pub trait InvokeUiSession: Send + Sync + Clone + 'static + Sized + Default {
         is_local: bool,
         only_count: bool,
     );
+    fn file_search_result(&self, id: i32, entries: &Vec<FileEntry>, done: bool, truncated: bool);
+    fn file_range(&self, id: i32, offset: u64, data: &[u8], size: u64);
     fn confirm_delete_files(&self, id: i32, i: i32, name: String);
     fn override_file_confirm(
         &self,
@@ -1558,6 +2043,18 @@ pub trait InvokeUiSession: Send + Sync + Clone + 'static + Sized + Default {
     #[cfg(feature = "flutter")]
     fn is_multi_ui_session(&self) -> bool;
     fn update_record_status(&self, start: bool);
+    /// Whether the peer records the session.
+    fn update_server_record_status(&self, on: bool);
+    /// The seconds left before the peer closes the inactive session, or takes the control away if
+    /// `view_only`, 0 if no longer inactive.
+    fn inactivity_warning(&self, seconds: u32, view_only: bool);
+    fn update_remote_volume(&self, level: u32, mute: bool);
+    /// Who holds the control of the input of the peer, when it arbitrates it.
+    fn update_input_control(&self, control: InputControl);
+    /// All the drawings over the screen of the peer.
+    fn update_annotation(&self, strokes: Vec<AnnotationStroke>);
+    /// The plan of the sync job in json, or the error.
+    fn sync_job_plan(&self, id: i32, name: &str, dry_run: bool, plan: &str, err: &str);
 }
 
 impl<T: InvokeUiSession> Deref for Session<T> {
@@ -1583,6 +2080,11 @@ impl<T: InvokeUiSession> Interface for Session<T> {
     }
 
     fn send(&self, data: Data) {
+        if let Data::Message(msg) = &data {
+            if let Some(recorder) = self.macro_recorder.lock().unwrap().as_mut() {
+                recorder.record(msg);
+            }
+        }
         if let Some(sender) = self.sender.read().unwrap().as_ref() {
             sender.send(data).ok();
         }
@@ -1704,9 +2206,19 @@ impl<T: InvokeUiSession> Interface for Session<T> {
 
     async fn handle_test_delay(&self, t: TestDelay, peer: &mut Stream) {
         if !t.from_client {
+            let bandwidth = t.bandwidth_usage.as_ref().map(|u| {
+                let limit = if u.limit > 0 {
+                    u.limit.to_string()
+                } else {
+                    "-".to_owned()
+                };
+                // video+audio+file+other/limit
+                format!("{}+{}+{}+{}/{}", u.video, u.audio, u.file, u.other, limit)
+            });
             self.update_quality_status(QualityStatus {
                 delay: Some(t.last_delay as _),
                 target_bitrate: Some(t.target_bitrate as _),
+                bandwidth,
                 ..Default::default()
             });
             handle_test_delay(t, peer).await;
@@ -1791,7 +2303,8 @@ pub async fn io_loop<T: InvokeUiSession>(handler: Session<T>, round: u32) {
             loop {
                 match receiver.recv().await {
                     Some(Data::AddPortForward((port, remote_host, remote_port))) => {
-                        if port <= 0 || remote_port <= 0 {
+                        // a remote port of 0 is a socks tunnel
+                        if port <= 0 || remote_port < 0 {
                             continue;
                         }
                         let (sender, receiver) = mpsc::unbounded_channel::<Data>();
@@ -1831,7 +2344,7 @@ pub async fn io_loop<T: InvokeUiSession>(handler: Session<T>, round: u32) {
         } else {
             let port = handler.args[0].parse::<i32>().unwrap_or(0);
             if handler.args.len() != 3
-                || handler.args[2].parse::<i32>().unwrap_or(0) <= 0
+                || handler.args[2].parse::<i32>().unwrap_or(-1) < 0
                 || port <= 0
             {
                 handler.on_error("Invalid arguments, usage:<br><br> rustdesk --port-forward remote-id listen-port remote-host remote-port");
@@ -1854,7 +2367,7 @@ pub async fn io_loop<T: InvokeUiSession>(handler: Session<T>, round: u32) {
     let frame_count_map: Arc<RwLock<HashMap<usize, usize>>> = Default::default();
     let frame_count_map_cl = frame_count_map.clone();
     let ui_handler = handler.ui_handler.clone();
-    let (video_sender, audio_sender, video_queue_map, decode_fps, chroma) =
+    let (video_sender, audio_sender, video_queue_map, decode_fps, chroma, av_sync) =
         start_video_audio_threads(
             handler.clone(),
             move |display: usize,
@@ -1874,6 +2387,15 @@ pub async fn io_loop<T: InvokeUiSession>(handler: Session<T>, round: u32) {
             },
         );
 
+    let sync_view_settings =
+        !handler.is_file_transfer() && crate::hbbs_http::view_settings::enabled();
+    let (id, lc) = (handler.get_id(), handler.lc.clone());
+    if sync_view_settings {
+        tokio::spawn(crate::hbbs_http::view_settings::pull_session(
+            id.clone(),
+            lc.clone(),
+        ));
+    }
     let mut remote = Remote::new(
         handler,
         video_queue_map,
@@ -1884,9 +2406,13 @@ pub async fn io_loop<T: InvokeUiSession>(handler: Session<T>, round: u32) {
         frame_count_map,
         decode_fps,
         chroma,
+        av_sync,
     );
     remote.io_loop(&key, &token, round).await;
     remote.sync_jobs_status_to_local().await;
+    if sync_view_settings {
+        crate::hbbs_http::view_settings::push_session(id, lc).await;
+    }
 }
 
 #[cfg(not(any(target_os = "android", target_os = "ios")))]